@@ -0,0 +1,75 @@
+//! ARM Data Watchpoint and Trace unit, for its cycle counter.
+//!
+//! The DWT is optional, and so is its cycle counter: the Cortex-M0 has none,
+//! and other cores may be built without. The counter is 32 bits wide, and
+//! wraps around after about 90 seconds at 48 MHz. It stops while the core
+//! sleeps.
+
+use kernel::common::registers::{register_bitfields, ReadWrite};
+use kernel::common::StaticRef;
+use kernel::hil;
+use kernel::ReturnCode;
+
+#[repr(C)]
+struct DwtRegisters {
+    ctrl: ReadWrite<u32, Control::Register>,
+    cyccnt: ReadWrite<u32>,
+}
+
+#[repr(C)]
+struct DebugRegisters {
+    demcr: ReadWrite<u32, ExceptionMonitorControl::Register>,
+}
+
+register_bitfields![u32,
+    Control [
+        /// 1 if the unit has no cycle counter.
+        NOCYCCNT        OFFSET(25) NUMBITS(1),
+
+        /// Enable the cycle counter.
+        CYCCNTENA       OFFSET(0)  NUMBITS(1)
+    ],
+
+    ExceptionMonitorControl [
+        /// Enable the DWT and ITM units.
+        TRCENA          OFFSET(24) NUMBITS(1)
+    ]
+];
+
+const DWT_BASE: StaticRef<DwtRegisters> =
+    unsafe { StaticRef::new(0xE0001000 as *const DwtRegisters) };
+const DEBUG_BASE: StaticRef<DebugRegisters> =
+    unsafe { StaticRef::new(0xE000EDFC as *const DebugRegisters) };
+
+/// The cycle counter of the DWT
+pub struct CycleCounter {
+    registers: StaticRef<DwtRegisters>,
+}
+
+pub static mut CYCLE_COUNTER: CycleCounter = CycleCounter::new();
+
+impl CycleCounter {
+    const fn new() -> CycleCounter {
+        CycleCounter {
+            registers: DWT_BASE,
+        }
+    }
+}
+
+impl hil::cycle_counter::CycleCounter for CycleCounter {
+    fn enable(&self) -> ReturnCode {
+        // The unit reads as zero until it is enabled
+        DEBUG_BASE
+            .demcr
+            .modify(ExceptionMonitorControl::TRCENA::SET);
+        if self.registers.ctrl.is_set(Control::NOCYCCNT) {
+            return ReturnCode::ENOSUPPORT;
+        }
+        self.registers.ctrl.modify(Control::CYCCNTENA::SET);
+        ReturnCode::SUCCESS
+    }
+
+    fn count(&self) -> u32 {
+        self.registers.cyccnt.get()
+    }
+}
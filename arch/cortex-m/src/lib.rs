@@ -2,11 +2,14 @@
 
 #![crate_name = "cortexm"]
 #![crate_type = "rlib"]
-#![feature(asm, const_fn, lang_items)]
+#![feature(asm, const_fn, lang_items, naked_functions)]
 #![no_std]
 
+pub mod dwt;
 pub mod nvic;
+pub mod sampler;
 pub mod scb;
+pub mod semihosting;
 pub mod support;
 pub mod syscall;
 pub mod systick;
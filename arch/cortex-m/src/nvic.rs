@@ -71,6 +71,12 @@ pub unsafe fn has_pending() -> bool {
     nvic.ispr.iter().fold(0, |i, ispr| ispr.get() | i) != 0
 }
 
+/// Called by `generic_isr` with the number of the interrupt it disabled.
+#[no_mangle]
+pub unsafe extern "C" fn cortexm_interrupt_entered(irq: u32) {
+    kernel::interrupt_latency::entered(irq);
+}
+
 /// An opaque wrapper for a single NVIC interrupt.
 ///
 /// Hand these out to low-level driver to let them control their own interrupts
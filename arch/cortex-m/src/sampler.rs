@@ -0,0 +1,72 @@
+//! Sampling of the program counter for `kernel::profiler`.
+//!
+//! The kernel handles interrupts from its main loop, once the code they
+//! interrupted is gone, so the interrupt of the sampling timer has a handler
+//! of its own, `sample_handler()`. It takes the program counter from the
+//! frame that the exception pushed, acknowledges the timer with the function
+//! the chip gave `install()`, records the sample, and returns right to the
+//! code it interrupted, be it the kernel or a process.
+//!
+//! The vector table in flash sends every interrupt to the kernel, so
+//! `install()` copies it to RAM, with the entry of the timer replaced.
+
+use core::ptr;
+
+use crate::scb;
+
+/// The number of vectors the table in RAM has room for: the 16 exceptions
+/// and 112 interrupts
+pub const MAX_VECTORS: usize = 128;
+
+#[repr(C, align(512))]
+struct VectorTable([usize; MAX_VECTORS]);
+
+static mut VECTORS: VectorTable = VectorTable([0; MAX_VECTORS]);
+
+/// Acknowledges the interrupt of the sampling timer.
+static mut ACKNOWLEDGE: Option<unsafe fn()> = None;
+
+/// Send interrupt `irq` to `sample_handler()`, which calls `acknowledge` to
+/// clear it. `num_vectors` is the length of the current vector table,
+/// including the 16 exceptions.
+pub unsafe fn install(irq: usize, num_vectors: usize, acknowledge: unsafe fn()) {
+    assert!(num_vectors <= MAX_VECTORS && 16 + irq < num_vectors);
+    let table = scb::vector_table();
+    for i in 0..num_vectors {
+        VECTORS.0[i] = ptr::read_volatile(table.add(i));
+    }
+    VECTORS.0[16 + irq] = sample_handler as usize;
+    ACKNOWLEDGE = Some(acknowledge);
+    scb::set_vector_table(VECTORS.0.as_ptr());
+}
+
+#[cfg(not(target_os = "none"))]
+pub unsafe extern "C" fn sample_handler() {}
+
+#[cfg(target_os = "none")]
+#[naked]
+/// Passes `EXC_RETURN` and the stack that the exception frame was pushed to
+/// on to `cortexm_sample()`, which returns from the exception.
+pub unsafe extern "C" fn sample_handler() {
+    asm!(
+        "
+    /* Bit 2 of EXC_RETURN is set if the frame is on the process stack */
+    mov r0, lr
+    mrs r1, msp
+    movs r2, #4
+    tst r0, r2
+    beq 1f
+    mrs r1, psp
+  1:
+    ldr r2, =cortexm_sample
+    bx r2"
+    : : : : "volatile" );
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn cortexm_sample(exc_return: usize, frame: *const usize) {
+    // The frame holds r0-r3, r12, lr, pc and xpsr
+    let pc = ptr::read_volatile(frame.add(6));
+    ACKNOWLEDGE.map(|acknowledge| acknowledge());
+    kernel::profiler::record(pc, exc_return & 0x4 != 0);
+}
@@ -5,6 +5,8 @@
 use kernel::common::cells::VolatileCell;
 use kernel::common::StaticRef;
 
+use crate::support;
+
 #[repr(C)]
 struct ScbRegisters {
     cpuid: VolatileCell<u32>,
@@ -54,3 +56,15 @@ pub unsafe fn reset() {
     let reset = (0x5FA << 16) | (aircr & (0x7 << 8)) | (1 << 2);
     SCB.aircr.set(reset);
 }
+
+/// The vector table that the core takes exceptions from.
+pub unsafe fn vector_table() -> *const usize {
+    SCB.vtor.get() as *const usize
+}
+
+/// Take exceptions from the vector table at `table`, which must be aligned
+/// to its size rounded up to a power of two, and to at least 128 bytes.
+pub unsafe fn set_vector_table(table: *const usize) {
+    SCB.vtor.set(table as u32);
+    support::barrier();
+}
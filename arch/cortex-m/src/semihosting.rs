@@ -0,0 +1,66 @@
+//! ARM semihosting.
+//!
+//! Semihosting lets the program use the console of the host through the
+//! debugger or the simulator it runs under, with no peripheral to set up. The
+//! program makes a request with a `bkpt 0xAB` instruction, which the debugger
+//! or simulator catches. Without one attached, the breakpoint is a fault, so
+//! this is only for simulator runs and debug builds.
+//!
+//! `capsules::semihosting_uart` provides a UART for the console and for
+//! kernel debug output on top of `write`.
+
+/// Write a null terminated string to the console of the host
+const SYS_WRITE0: usize = 0x04;
+/// Report an exception to the host, which is used to stop
+const SYS_EXIT: usize = 0x18;
+
+/// Reason for `SYS_EXIT` when the program finished normally
+const ADP_STOPPED_APPLICATION_EXIT: usize = 0x20026;
+/// Reason for `SYS_EXIT` when the program failed
+const ADP_STOPPED_RUN_TIME_ERROR_UNKNOWN: usize = 0x20023;
+
+#[cfg(target_os = "none")]
+#[inline(always)]
+unsafe fn call(operation: usize, argument: usize) -> usize {
+    let ret;
+    asm!("bkpt 0xAB"
+         : "={r0}"(ret)
+         : "{r0}"(operation), "{r1}"(argument)
+         : "memory"
+         : "volatile");
+    ret
+}
+
+#[cfg(not(target_os = "none"))]
+unsafe fn call(_operation: usize, _argument: usize) -> usize {
+    0
+}
+
+/// Write `bytes` to the console of the host. This blocks until the host is
+/// done with them.
+pub fn write(bytes: &[u8]) {
+    // SYS_WRITE0 takes a null terminated string, so the bytes are written a
+    // chunk at a time from a buffer that holds the terminator
+    let mut buffer = [0; 64];
+    for chunk in bytes.chunks(buffer.len() - 1) {
+        buffer[..chunk.len()].copy_from_slice(chunk);
+        buffer[chunk.len()] = 0;
+        unsafe {
+            call(SYS_WRITE0, buffer.as_ptr() as usize);
+        }
+    }
+}
+
+/// Stop the simulator, or the program under the debugger. An exit code of 0
+/// stops it as finished normally, and any other code as failed.
+pub fn exit(code: u16) -> ! {
+    let reason = if code == 0 {
+        ADP_STOPPED_APPLICATION_EXIT
+    } else {
+        ADP_STOPPED_RUN_TIME_ERROR_UNKNOWN
+    };
+    unsafe {
+        call(SYS_EXIT, reason);
+    }
+    loop {}
+}
@@ -24,6 +24,19 @@ pub unsafe fn wfi() {
 /// WFI instruction (mock)
 pub unsafe fn wfi() {}
 
+#[cfg(target_os = "none")]
+#[inline(always)]
+/// DSB and ISB instructions, which make the effects of earlier register
+/// writes visible to the instructions after them
+pub unsafe fn barrier() {
+    asm!("dsb
+          isb" ::: "memory" : "volatile");
+}
+
+#[cfg(not(target_os = "none"))]
+/// DSB and ISB instructions (mock)
+pub unsafe fn barrier() {}
+
 #[cfg(not(target_os = "none"))]
 pub unsafe fn atomic<F, R>(f: F) -> R
 where
@@ -10,7 +10,10 @@
 pub use cortexm::support;
 
 pub use cortexm::nvic;
+pub use cortexm::scb;
+pub use cortexm::semihosting;
 pub use cortexm::syscall;
+pub use cortexm::systick;
 
 extern "C" {
     // _estack is not really a function, but it makes the types work
@@ -24,6 +27,35 @@ extern "C" {
     static mut _erelocate: u32;
 }
 
+#[cfg(not(target_os = "none"))]
+pub unsafe extern "C" fn systick_handler() {}
+
+#[cfg(target_os = "none")]
+#[naked]
+pub unsafe extern "C" fn systick_handler() {
+    asm!(
+        "
+    /* Mark that the systick handler was called meaning that the process */
+    /* stopped executing because it has exceeded its timeslice. */
+    ldr r0, =SYSTICK_EXPIRED
+    movs r1, #1
+    str r1, [r0, #0]
+
+    /* Set thread mode to privileged */
+    movs r0, #0
+    msr CONTROL, r0
+
+    /* ARMv6-M has no movw/movt, so load the return value from memory */
+    ldr r0, SEXC_RETURN_MSP
+    bx r0
+
+.align 4
+SEXC_RETURN_MSP:
+  .word 0xFFFFFFF9
+    "
+    : : : : "volatile" );
+}
+
 #[cfg(not(target_os = "none"))]
 pub unsafe extern "C" fn generic_isr() {}
 
@@ -11,8 +11,11 @@ pub mod mpu;
 // valid on cortex-m3.
 pub use cortexm::support;
 
+pub use cortexm::dwt;
 pub use cortexm::nvic;
+pub use cortexm::sampler;
 pub use cortexm::scb;
+pub use cortexm::semihosting;
 pub use cortexm::syscall;
 pub use cortexm::systick;
 
@@ -56,7 +59,8 @@ pub unsafe extern "C" fn generic_isr() {}
 
 #[cfg(target_os = "none")]
 #[naked]
-/// All ISRs are caught by this handler which disables the NVIC and switches to the kernel.
+/// All ISRs are caught by this handler which disables the NVIC, timestamps the
+/// interrupt for `kernel::interrupt_latency`, and switches to the kernel.
 pub unsafe extern "C" fn generic_isr() {
     asm!(
         "
@@ -90,10 +94,10 @@ pub unsafe extern "C" fn generic_isr() {
      * */
     lsrs r2, r0, #5 /* r2 = r0 / 32 */
 
-    /* r0 = 1 << (r0 & 31) */
+    /* r1 = 1 << (r0 & 31) */
     movs r3, #1        /* r3 = 1 */
-    and r0, r0, #31    /* r0 = r0 & 31 */
-    lsl r0, r3, r0     /* r0 = r3 << r0 */
+    and r1, r0, #31    /* r1 = r0 & 31 */
+    lsl r1, r3, r1     /* r1 = r3 << r1 */
 
     /* r3 = &NVIC.ICER */
     mov r3, #0xe180
@@ -103,14 +107,19 @@ pub unsafe extern "C" fn generic_isr() {
      *
      *  `r2` is r0 / 32
      *  `r3` is &NVIC.ICER
-     *  `r0` is 1 << (r0 & 31)
+     *  `r1` is 1 << (r0 & 31)
      *
      * So we just do:
      *
-     *  `*(r3 + r2 * 4) = r0`
+     *  `*(r3 + r2 * 4) = r1`
      *
      *  */
-    str r0, [r3, r2, lsl #2]"
+    str r1, [r3, r2, lsl #2]
+
+    /* Timestamp the interrupt, whose number is still in r0. This is a tail
+     * call, so the exception returns from it with lr as it is. */
+    ldr r1, =cortexm_interrupt_entered
+    bx r1"
     : : : : "volatile" );
 }
 
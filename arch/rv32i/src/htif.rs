@@ -0,0 +1,53 @@
+//! Host-target interface (HTIF) of the Spike simulator.
+//!
+//! HTIF is the RISC-V analog of ARM semihosting: the program makes requests
+//! to the simulator through the `tohost` variable, which the simulator finds
+//! by its symbol, and gets answers through `fromhost`. This gives the program
+//! the console of the host with no peripheral to set up. On anything that
+//! does not implement HTIF, nothing ever answers, and `write` blocks forever,
+//! so this is only for simulator runs.
+//!
+//! `capsules::semihosting_uart` provides a UART for the console and for
+//! kernel debug output on top of `write`.
+
+use core::ptr;
+
+/// The console device, and its command to write a character
+const DEVICE_CONSOLE: u64 = 1;
+const CONSOLE_WRITE: u64 = 1;
+
+#[no_mangle]
+#[allow(non_upper_case_globals)]
+static mut tohost: u64 = 0;
+
+#[no_mangle]
+#[allow(non_upper_case_globals)]
+static mut fromhost: u64 = 0;
+
+/// Make a request to the host, and wait for its answer.
+fn request(device: u64, command: u64, payload: u64) {
+    unsafe {
+        ptr::write_volatile(&mut tohost, device << 56 | command << 48 | payload);
+        while ptr::read_volatile(&fromhost) == 0 {}
+        ptr::write_volatile(&mut fromhost, 0);
+    }
+}
+
+/// Write `bytes` to the console of the host. This blocks until the host is
+/// done with them.
+pub fn write(bytes: &[u8]) {
+    for byte in bytes {
+        request(DEVICE_CONSOLE, CONSOLE_WRITE, *byte as u64);
+    }
+}
+
+/// Stop the simulator, with an exit code of 0 for a pass and any other code
+/// for a failure.
+pub fn exit(code: u16) -> ! {
+    unsafe {
+        // The system call device, with a payload whose lowest bit is set,
+        // is the exit code
+        ptr::write_volatile(&mut tohost, (code as u64) << 1 | 1);
+    }
+    loop {}
+}
@@ -5,6 +5,8 @@
 #![no_std]
 
 pub mod clic;
+pub mod cycle_counter;
+pub mod htif;
 pub mod machine_timer;
 pub mod plic;
 pub mod support;
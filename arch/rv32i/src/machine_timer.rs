@@ -1,5 +1,10 @@
 //! Create a timer using the Machine Timer registers.
+//!
+//! The frequency of `mtime` depends on the platform, so it is a parameter of
+//! `MachineTimer`. `MACHINETIMER` is the machine timer of a platform where
+//! `mtime` counts at 32 kHz.
 
+use core::marker::PhantomData;
 use kernel::common::cells::OptionalCell;
 use kernel::common::registers::{register_bitfields, ReadOnly, ReadWrite};
 use kernel::common::StaticRef;
@@ -27,16 +32,18 @@ register_bitfields![u64,
 
 pub static mut MACHINETIMER: MachineTimer = MachineTimer::new();
 
-pub struct MachineTimer {
+pub struct MachineTimer<F = hil::time::Freq32KHz> {
     registers: StaticRef<MachineTimerRegisters>,
     client: OptionalCell<&'static hil::time::Client>,
+    _frequency: PhantomData<F>,
 }
 
-impl MachineTimer {
-    const fn new() -> MachineTimer {
+impl<F: hil::time::Frequency> MachineTimer<F> {
+    pub const fn new() -> MachineTimer<F> {
         MachineTimer {
             registers: MTIME_BASE,
             client: OptionalCell::empty(),
+            _frequency: PhantomData,
         }
     }
 
@@ -61,8 +68,8 @@ impl MachineTimer {
     }
 }
 
-impl hil::time::Time for MachineTimer {
-    type Frequency = hil::time::Freq32KHz;
+impl<F: hil::time::Frequency> hil::time::Time for MachineTimer<F> {
+    type Frequency = F;
 
     fn disable(&self) {
         self.disable_machine_timer();
@@ -75,15 +82,25 @@ impl hil::time::Time for MachineTimer {
     }
 }
 
-impl hil::time::Alarm for MachineTimer {
+impl<F: hil::time::Frequency> hil::time::Alarm for MachineTimer<F> {
     fn now(&self) -> u32 {
         self.registers.mtime.get() as u32
     }
 
     fn set_alarm(&self, tics: u32) {
+        // mtimecmp compares all 64 bits of mtime, so extend `tics` to the
+        // next time that the low 32 bits of mtime reach it. A `tics` just
+        // behind `now()` fires right away.
+        let now = self.registers.mtime.get();
+        let delta = tics.wrapping_sub(now as u32);
+        let compare = if delta > (1 << 31) {
+            now
+        } else {
+            now + delta as u64
+        };
         self.registers
             .mtimecmp
-            .write(MTimeCmp::MTIMECMP.val(tics as u64));
+            .write(MTimeCmp::MTIMECMP.val(compare));
     }
 
     fn get_alarm(&self) -> u32 {
@@ -11,7 +11,7 @@ use kernel::component::Component;
 use kernel::hil;
 use kernel::hil::entropy::Entropy32;
 use kernel::hil::gpio::{
-    Configure, InterruptValuePin, InterruptValueWrapper, InterruptWithValue, Output, Pin,
+    Configure, InterruptValuePin, InterruptValueWrapper, InterruptWithValue, Output,
 };
 use kernel::hil::rng::Rng;
 #[allow(unused_imports)]
@@ -62,7 +62,7 @@ pub struct Platform {
     button: &'static capsules::button::Button<'static>,
     console: &'static capsules::console::Console<'static>,
     gpio: &'static capsules::gpio::GPIO<'static>,
-    led: &'static capsules::led::LED<'static>,
+    led: &'static capsules::led::LED<'static, VirtualMuxAlarm<'static, Rtc>>,
     rng: &'static capsules::rng::RngDriver<'static>,
     temp: &'static capsules::temperature::TemperatureSensor<'static>,
     ipc: kernel::ipc::IPC,
@@ -193,24 +193,28 @@ pub unsafe fn reset_handler() {
     );
 
     // LEDs
-    let led_pins = static_init!(
-        [(&'static Pin, capsules::led::ActivationMode); 4],
+    let leds = static_init!(
+        [capsules::led::Led<'static>; 4],
         [
-            (
+            capsules::led::Led::gpio(
                 &nrf5x::gpio::PORT[LED1_PIN],
-                capsules::led::ActivationMode::ActiveLow
+                capsules::led::ActivationMode::ActiveLow,
+                hil::led::LedColor::Unknown
             ),
-            (
+            capsules::led::Led::gpio(
                 &nrf5x::gpio::PORT[LED2_PIN],
-                capsules::led::ActivationMode::ActiveLow
+                capsules::led::ActivationMode::ActiveLow,
+                hil::led::LedColor::Unknown
             ),
-            (
+            capsules::led::Led::gpio(
                 &nrf5x::gpio::PORT[LED3_PIN],
-                capsules::led::ActivationMode::ActiveLow
+                capsules::led::ActivationMode::ActiveLow,
+                hil::led::LedColor::Unknown
             ),
-            (
+            capsules::led::Led::gpio(
                 &nrf5x::gpio::PORT[LED4_PIN],
-                capsules::led::ActivationMode::ActiveLow
+                capsules::led::ActivationMode::ActiveLow,
+                hil::led::LedColor::Unknown
             ),
         ]
     );
@@ -288,14 +292,6 @@ pub unsafe fn reset_handler() {
         pin.set_client(gpio);
     }
 
-    //
-    // LEDs
-    //
-    let led = static_init!(
-        capsules::led::LED<'static>,
-        capsules::led::LED::new(led_pins)
-    );
-
     //
     // Buttons
     //
@@ -322,6 +318,19 @@ pub unsafe fn reset_handler() {
     );
     rtc.set_client(mux_alarm);
 
+    //
+    // LEDs
+    //
+    let led_virtual_alarm = static_init!(
+        VirtualMuxAlarm<'static, Rtc>,
+        VirtualMuxAlarm::new(mux_alarm)
+    );
+    let led = static_init!(
+        capsules::led::LED<'static, VirtualMuxAlarm<'static, Rtc>>,
+        capsules::led::LED::new(leds, led_virtual_alarm)
+    );
+    led_virtual_alarm.set_client(led);
+
     //
     // Timer/Alarm
     //
@@ -397,13 +406,13 @@ pub unsafe fn reset_handler() {
         capsules::console::Console,
         capsules::console::Console::new(
             console_uart,
-            &mut capsules::console::WRITE_BUF,
             &mut capsules::console::READ_BUF,
             board_kernel.create_grant(&memory_allocation_capability)
         )
     );
     kernel::hil::uart::Transmit::set_transmit_client(console_uart, console);
     kernel::hil::uart::Receive::set_receive_client(console_uart, console);
+    board_kernel.set_process_state_observer(console, &process_management_capability);
 
     // Create virtual device for kernel debug.
     let debugger_uart = static_init!(UartDevice, UartDevice::new(uart_mux, false));
@@ -45,7 +45,10 @@ struct ArtyE21 {
         'static,
         VirtualMuxAlarm<'static, rv32i::machine_timer::MachineTimer>,
     >,
-    led: &'static capsules::led::LED<'static>,
+    led: &'static capsules::led::LED<
+        'static,
+        VirtualMuxAlarm<'static, rv32i::machine_timer::MachineTimer>,
+    >,
     button: &'static capsules::button::Button<'static>,
     // ipc: kernel::ipc::IPC,
 }
@@ -116,13 +119,13 @@ pub unsafe fn reset_handler() {
         capsules::console::Console<'static>,
         capsules::console::Console::new(
             console_uart,
-            &mut capsules::console::WRITE_BUF,
             &mut capsules::console::READ_BUF,
             board_kernel.create_grant(&memory_allocation_cap)
         )
     );
     hil::uart::Transmit::set_transmit_client(console_uart, console);
     hil::uart::Receive::set_receive_client(console_uart, console);
+    board_kernel.set_process_state_observer(console, &process_mgmt_cap);
 
     // Create a shared virtualization mux layer on top of a single hardware
     // alarm.
@@ -162,33 +165,35 @@ pub unsafe fn reset_handler() {
     // virtual_alarm_test.set_client(timertest);
 
     // LEDs
-    let led_pins = static_init!(
-        [(
-            &'static kernel::hil::gpio::Pin,
-            capsules::led::ActivationMode
-        ); 3],
+    let leds = static_init!(
+        [capsules::led::Led<'static>; 3],
         [
-            (
-                // Red
+            capsules::led::Led::gpio(
                 &arty_e21::gpio::PORT[0],
-                capsules::led::ActivationMode::ActiveHigh
+                capsules::led::ActivationMode::ActiveHigh,
+                hil::led::LedColor::Red
             ),
-            (
-                // Green
+            capsules::led::Led::gpio(
                 &arty_e21::gpio::PORT[1],
-                capsules::led::ActivationMode::ActiveHigh
+                capsules::led::ActivationMode::ActiveHigh,
+                hil::led::LedColor::Green
             ),
-            (
-                // Blue
+            capsules::led::Led::gpio(
                 &arty_e21::gpio::PORT[2],
-                capsules::led::ActivationMode::ActiveHigh
+                capsules::led::ActivationMode::ActiveHigh,
+                hil::led::LedColor::Blue
             ),
         ]
     );
+    let led_virtual_alarm = static_init!(
+        VirtualMuxAlarm<'static, rv32i::machine_timer::MachineTimer>,
+        VirtualMuxAlarm::new(mux_alarm)
+    );
     let led = static_init!(
-        capsules::led::LED<'static>,
-        capsules::led::LED::new(led_pins)
+        capsules::led::LED<'static, VirtualMuxAlarm<'static, rv32i::machine_timer::MachineTimer>>,
+        capsules::led::LED::new(leds, led_virtual_alarm)
     );
+    led_virtual_alarm.set_client(led);
 
     // BUTTONs
     let button_pins = static_init!(
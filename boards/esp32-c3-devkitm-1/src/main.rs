@@ -0,0 +1,283 @@
+//! Board file for the Espressif ESP32-C3-DevKitM-1
+//!
+//! - <https://docs.espressif.com/projects/esp-idf/en/latest/esp32c3/hw-reference/esp32c3/user-guide-devkitm-1.html>
+
+#![no_std]
+#![no_main]
+#![feature(const_fn, in_band_lifetimes)]
+#![deny(missing_docs)]
+
+use capsules::virtual_alarm::{MuxAlarm, VirtualMuxAlarm};
+use capsules::virtual_uart::{MuxUart, UartDevice};
+use kernel::capabilities;
+use kernel::hil;
+use kernel::Platform;
+use kernel::{create_capability, debug, static_init};
+
+use esp32_c3::gpio::signal;
+
+/// Support routines for debugging I/O.
+pub mod io;
+
+// Number of concurrent processes this platform supports.
+const NUM_PROCS: usize = 4;
+
+// How should the kernel respond when a process faults.
+const FAULT_RESPONSE: kernel::procs::FaultResponse = kernel::procs::FaultResponse::Panic;
+
+// RAM to be shared by all application processes.
+#[link_section = ".app_memory"]
+static mut APP_MEMORY: [u8; 32768] = [0; 32768];
+
+// Actual memory for holding the active process structures.
+static mut PROCESSES: [Option<&'static kernel::procs::ProcessType>; NUM_PROCS] =
+    [None, None, None, None];
+
+// Force the emission of the `.apps` section in the kernel elf image, which
+// `make flash-app` fills with an application
+#[used]
+#[link_section = ".app.hack"]
+static APP_HACK: u8 = 0;
+
+/// Dummy buffer that causes the linker to reserve enough space for the stack.
+#[no_mangle]
+#[link_section = ".stack_buffer"]
+pub static mut STACK_MEMORY: [u8; 0x1000] = [0; 0x1000];
+
+/// A structure representing this platform that holds references to all
+/// capsules for this platform.
+struct Esp32C3DevKitM1 {
+    console: &'static capsules::console::Console<'static>,
+    gpio: &'static capsules::gpio::GPIO<'static>,
+    alarm: &'static capsules::alarm::AlarmDriver<
+        'static,
+        VirtualMuxAlarm<'static, esp32_c3::timg::TimG>,
+    >,
+    button: &'static capsules::button::Button<'static>,
+}
+
+/// Mapping of integer syscalls to objects that implement syscalls.
+impl Platform for Esp32C3DevKitM1 {
+    fn with_driver<F, R>(&self, driver_num: usize, f: F) -> R
+    where
+        F: FnOnce(Option<&kernel::Driver>) -> R,
+    {
+        match driver_num {
+            capsules::console::DRIVER_NUM => f(Some(self.console)),
+            capsules::gpio::DRIVER_NUM => f(Some(self.gpio)),
+            capsules::alarm::DRIVER_NUM => f(Some(self.alarm)),
+            capsules::button::DRIVER_NUM => f(Some(self.button)),
+            _ => f(None),
+        }
+    }
+}
+
+/// Reset Handler.
+///
+/// This function is called from the arch crate after some very basic RISC-V
+/// setup.
+#[no_mangle]
+pub unsafe fn reset_handler() {
+    // Basic setup of the platform.
+    rv32i::init_memory();
+
+    let chip = static_init!(
+        esp32_c3::chip::Esp32C3,
+        esp32_c3::chip::Esp32C3::new(&esp32_c3::intc::INTC)
+    );
+    chip.initialize();
+
+    let process_mgmt_cap = create_capability!(capabilities::ProcessManagementCapability);
+    let main_loop_cap = create_capability!(capabilities::MainLoopCapability);
+    let memory_allocation_cap = create_capability!(capabilities::MemoryAllocationCapability);
+
+    let board_kernel = static_init!(kernel::Kernel, kernel::Kernel::new(&PROCESSES));
+
+    // UART0 is connected to the USB to serial converter of the board through
+    // GPIO21 (TX) and GPIO20 (RX)
+    esp32_c3::gpio::PORT[21].connect_output_signal(signal::U0TXD_OUT);
+    esp32_c3::gpio::PORT[20].connect_input_signal(signal::U0RXD_IN);
+
+    // Create a shared UART channel for the console and for kernel debug.
+    let uart_mux = static_init!(
+        MuxUart<'static>,
+        MuxUart::new(
+            &esp32_c3::uart::UART0,
+            &mut capsules::virtual_uart::RX_BUF,
+            115200
+        )
+    );
+    uart_mux.initialize();
+    io::WRITER.set_initialized();
+
+    hil::uart::Transmit::set_transmit_client(&esp32_c3::uart::UART0, uart_mux);
+    hil::uart::Receive::set_receive_client(&esp32_c3::uart::UART0, uart_mux);
+
+    // Create a UartDevice for the console.
+    let console_uart = static_init!(UartDevice, UartDevice::new(uart_mux, true));
+    console_uart.setup();
+    let console = static_init!(
+        capsules::console::Console<'static>,
+        capsules::console::Console::new(
+            console_uart,
+            &mut capsules::console::READ_BUF,
+            board_kernel.create_grant(&memory_allocation_cap)
+        )
+    );
+    hil::uart::Transmit::set_transmit_client(console_uart, console);
+    hil::uart::Receive::set_receive_client(console_uart, console);
+    board_kernel.set_process_state_observer(console, &process_mgmt_cap);
+
+    // Create virtual device for kernel debug.
+    let debugger_uart = static_init!(UartDevice, UartDevice::new(uart_mux, false));
+    debugger_uart.setup();
+    let debugger = static_init!(
+        kernel::debug::DebugWriter,
+        kernel::debug::DebugWriter::new(
+            debugger_uart,
+            &mut kernel::debug::OUTPUT_BUF,
+            &mut kernel::debug::INTERNAL_BUF,
+        )
+    );
+    hil::uart::Transmit::set_transmit_client(debugger_uart, debugger);
+
+    let debug_wrapper = static_init!(
+        kernel::debug::DebugWriterWrapper,
+        kernel::debug::DebugWriterWrapper::new(debugger)
+    );
+    kernel::debug::set_debug_writer_wrapper(debug_wrapper);
+
+    // Create a shared virtualization mux layer on top of the timer of the
+    // first timer group.
+    esp32_c3::timg::TIMG0.start();
+    let mux_alarm = static_init!(
+        MuxAlarm<'static, esp32_c3::timg::TimG>,
+        MuxAlarm::new(&esp32_c3::timg::TIMG0)
+    );
+    esp32_c3::timg::TIMG0.set_client(mux_alarm);
+
+    // Alarm
+    let virtual_alarm_user = static_init!(
+        VirtualMuxAlarm<'static, esp32_c3::timg::TimG>,
+        VirtualMuxAlarm::new(mux_alarm)
+    );
+    let alarm = static_init!(
+        capsules::alarm::AlarmDriver<'static, VirtualMuxAlarm<'static, esp32_c3::timg::TimG>>,
+        capsules::alarm::AlarmDriver::new(
+            virtual_alarm_user,
+            board_kernel.create_grant(&memory_allocation_cap)
+        )
+    );
+    virtual_alarm_user.set_client(alarm);
+
+    // The BOOT button, on GPIO9, pulls the pin low when pressed
+    let button_pins = static_init!(
+        [(
+            &'static kernel::hil::gpio::InterruptValuePin,
+            capsules::button::GpioMode
+        ); 1],
+        [(
+            static_init!(
+                kernel::hil::gpio::InterruptValueWrapper,
+                kernel::hil::gpio::InterruptValueWrapper::new(&esp32_c3::gpio::PORT[9])
+            )
+            .finalize(),
+            capsules::button::GpioMode::LowWhenPressed
+        )]
+    );
+    let button = static_init!(
+        capsules::button::Button<'static>,
+        capsules::button::Button::new(
+            button_pins,
+            board_kernel.create_grant(&memory_allocation_cap)
+        )
+    );
+    for &(btn, _) in button_pins.iter() {
+        btn.set_client(button);
+    }
+
+    // GPIO driver controlling the header pins that are not strapping pins, or
+    // used by the flash, the USB port, the RGB LED or UART0
+    let gpio_pins = static_init!(
+        [&'static kernel::hil::gpio::InterruptValuePin; 8],
+        [
+            static_init!(
+                kernel::hil::gpio::InterruptValueWrapper,
+                kernel::hil::gpio::InterruptValueWrapper::new(&esp32_c3::gpio::PORT[0])
+            )
+            .finalize(),
+            static_init!(
+                kernel::hil::gpio::InterruptValueWrapper,
+                kernel::hil::gpio::InterruptValueWrapper::new(&esp32_c3::gpio::PORT[1])
+            )
+            .finalize(),
+            static_init!(
+                kernel::hil::gpio::InterruptValueWrapper,
+                kernel::hil::gpio::InterruptValueWrapper::new(&esp32_c3::gpio::PORT[3])
+            )
+            .finalize(),
+            static_init!(
+                kernel::hil::gpio::InterruptValueWrapper,
+                kernel::hil::gpio::InterruptValueWrapper::new(&esp32_c3::gpio::PORT[4])
+            )
+            .finalize(),
+            static_init!(
+                kernel::hil::gpio::InterruptValueWrapper,
+                kernel::hil::gpio::InterruptValueWrapper::new(&esp32_c3::gpio::PORT[5])
+            )
+            .finalize(),
+            static_init!(
+                kernel::hil::gpio::InterruptValueWrapper,
+                kernel::hil::gpio::InterruptValueWrapper::new(&esp32_c3::gpio::PORT[6])
+            )
+            .finalize(),
+            static_init!(
+                kernel::hil::gpio::InterruptValueWrapper,
+                kernel::hil::gpio::InterruptValueWrapper::new(&esp32_c3::gpio::PORT[7])
+            )
+            .finalize(),
+            static_init!(
+                kernel::hil::gpio::InterruptValueWrapper,
+                kernel::hil::gpio::InterruptValueWrapper::new(&esp32_c3::gpio::PORT[10])
+            )
+            .finalize(),
+        ]
+    );
+    let gpio = static_init!(
+        capsules::gpio::GPIO<'static>,
+        capsules::gpio::GPIO::new(gpio_pins, board_kernel.create_grant(&memory_allocation_cap))
+    );
+    for pin in gpio_pins.iter() {
+        pin.set_client(gpio);
+    }
+
+    chip.enable_all_interrupts();
+
+    let devkit = Esp32C3DevKitM1 {
+        console: console,
+        gpio: gpio,
+        alarm: alarm,
+        button: button,
+    };
+
+    debug!("Initialization complete. Entering main loop.");
+
+    extern "C" {
+        /// Beginning of the ROM region containing app images.
+        ///
+        /// This symbol is defined in the linker script.
+        static _sapps: u8;
+    }
+
+    kernel::procs::load_processes(
+        board_kernel,
+        chip,
+        &_sapps as *const u8,
+        &mut APP_MEMORY,
+        &mut PROCESSES,
+        FAULT_RESPONSE,
+        &process_mgmt_cap,
+    );
+
+    board_kernel.kernel_loop(&devkit, chip, None, &main_loop_cap);
+}
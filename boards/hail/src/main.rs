@@ -48,6 +48,76 @@ static mut APP_MEMORY: [u8; 49152] = [0; 49152];
 // Actual memory for holding the active process structures.
 static mut PROCESSES: [Option<&'static kernel::procs::ProcessType>; NUM_PROCS] = [None; NUM_PROCS];
 
+// Description of the board that processes can read.
+static BOARD: capsules::board_info::BoardDescriptor = capsules::board_info::BoardDescriptor {
+    name: "hail",
+    chip: "sam4l",
+    drivers: &[
+        capsules::console::DRIVER_NUM,
+        capsules::gpio::DRIVER_NUM,
+        capsules::alarm::DRIVER_NUM,
+        capsules::spi::DRIVER_NUM,
+        capsules::nrf51822_serialization::DRIVER_NUM,
+        capsules::ambient_light::DRIVER_NUM,
+        capsules::adc::DRIVER_NUM,
+        capsules::led::DRIVER_NUM,
+        capsules::button::DRIVER_NUM,
+        capsules::humidity::DRIVER_NUM,
+        capsules::temperature::DRIVER_NUM,
+        capsules::ninedof::DRIVER_NUM,
+        capsules::rng::DRIVER_NUM,
+        capsules::crc::DRIVER_NUM,
+        capsules::dac::DRIVER_NUM,
+        capsules::board_info::DRIVER_NUM,
+        kernel::ipc::DRIVER_NUM,
+    ],
+    pins: &[
+        capsules::board_info::PinName {
+            number: 0,
+            name: "D0",
+        },
+        capsules::board_info::PinName {
+            number: 1,
+            name: "D1",
+        },
+        capsules::board_info::PinName {
+            number: 2,
+            name: "D6",
+        },
+        capsules::board_info::PinName {
+            number: 3,
+            name: "D7",
+        },
+    ],
+    sensors: &[
+        capsules::board_info::Sensor {
+            kind: capsules::board_info::SensorKind::Temperature,
+            driver: capsules::temperature::DRIVER_NUM,
+            name: "SI7021",
+        },
+        capsules::board_info::Sensor {
+            kind: capsules::board_info::SensorKind::Humidity,
+            driver: capsules::humidity::DRIVER_NUM,
+            name: "SI7021",
+        },
+        capsules::board_info::Sensor {
+            kind: capsules::board_info::SensorKind::AmbientLight,
+            driver: capsules::ambient_light::DRIVER_NUM,
+            name: "ISL29035",
+        },
+        capsules::board_info::Sensor {
+            kind: capsules::board_info::SensorKind::Accelerometer,
+            driver: capsules::ninedof::DRIVER_NUM,
+            name: "FXOS8700CQ",
+        },
+        capsules::board_info::Sensor {
+            kind: capsules::board_info::SensorKind::Magnetometer,
+            driver: capsules::ninedof::DRIVER_NUM,
+            name: "FXOS8700CQ",
+        },
+    ],
+};
+
 /// Dummy buffer that causes the linker to reserve enough space for the stack.
 #[no_mangle]
 #[link_section = ".stack_buffer"]
@@ -69,12 +139,13 @@ struct Hail {
     spi: &'static capsules::spi::Spi<'static, VirtualSpiMasterDevice<'static, sam4l::spi::SpiHw>>,
     nrf51822: &'static capsules::nrf51822_serialization::Nrf51822Serialization<'static>,
     adc: &'static capsules::adc::Adc<'static, sam4l::adc::Adc>,
-    led: &'static capsules::led::LED<'static>,
+    led: &'static capsules::led::LED<'static, VirtualMuxAlarm<'static, sam4l::ast::Ast<'static>>>,
     button: &'static capsules::button::Button<'static>,
     rng: &'static capsules::rng::RngDriver<'static>,
     ipc: kernel::ipc::IPC,
     crc: &'static capsules::crc::Crc<'static, sam4l::crccu::Crccu<'static>>,
     dac: &'static capsules::dac::Dac<'static>,
+    board_info: &'static capsules::board_info::BoardInfo,
 }
 
 /// Mapping of integer syscalls to objects that implement syscalls.
@@ -104,6 +175,8 @@ impl Platform for Hail {
 
             capsules::dac::DRIVER_NUM => f(Some(self.dac)),
 
+            capsules::board_info::DRIVER_NUM => f(Some(self.board_info)),
+
             kernel::ipc::DRIVER_NUM => f(Some(&self.ipc)),
             _ => f(None),
         }
@@ -234,13 +307,13 @@ pub unsafe fn reset_handler() {
         capsules::console::Console<'static>,
         capsules::console::Console::new(
             console_uart,
-            &mut capsules::console::WRITE_BUF,
             &mut capsules::console::READ_BUF,
             board_kernel.create_grant(&memory_allocation_capability)
         )
     );
     hil::uart::Transmit::set_transmit_client(console_uart, console);
     hil::uart::Receive::set_receive_client(console_uart, console);
+    board_kernel.set_process_state_observer(console, &process_management_capability);
 
     // Setup the process inspection console
     let process_console_uart = static_init!(UartDevice, UartDevice::new(uart_mux, true));
@@ -415,30 +488,35 @@ pub unsafe fn reset_handler() {
     syscall_spi_device.set_client(spi_syscalls);
 
     // LEDs
-    let led_pins = static_init!(
-        [(
-            &'static kernel::hil::gpio::Pin,
-            capsules::led::ActivationMode
-        ); 3],
+    let leds = static_init!(
+        [capsules::led::Led<'static>; 3],
         [
-            (
+            capsules::led::Led::gpio(
                 &sam4l::gpio::PA[13],
-                capsules::led::ActivationMode::ActiveLow
-            ), // Red
-            (
+                capsules::led::ActivationMode::ActiveLow,
+                hil::led::LedColor::Red
+            ),
+            capsules::led::Led::gpio(
                 &sam4l::gpio::PA[15],
-                capsules::led::ActivationMode::ActiveLow
-            ), // Green
-            (
+                capsules::led::ActivationMode::ActiveLow,
+                hil::led::LedColor::Green
+            ),
+            capsules::led::Led::gpio(
                 &sam4l::gpio::PA[14],
-                capsules::led::ActivationMode::ActiveLow
+                capsules::led::ActivationMode::ActiveLow,
+                hil::led::LedColor::Blue
             ),
         ]
-    ); // Blue
+    );
+    let led_virtual_alarm = static_init!(
+        VirtualMuxAlarm<'static, sam4l::ast::Ast>,
+        VirtualMuxAlarm::new(mux_alarm)
+    );
     let led = static_init!(
-        capsules::led::LED<'static>,
-        capsules::led::LED::new(led_pins)
+        capsules::led::LED<'static, VirtualMuxAlarm<'static, sam4l::ast::Ast>>,
+        capsules::led::LED::new(leds, led_virtual_alarm)
     );
+    led_virtual_alarm.set_client(led);
 
     // BUTTONs
     let button_pins = static_init!(
@@ -561,6 +639,15 @@ pub unsafe fn reset_handler() {
         capsules::dac::Dac::new(&sam4l::dac::DAC)
     );
 
+    // Board description
+    let board_info = static_init!(
+        capsules::board_info::BoardInfo,
+        capsules::board_info::BoardInfo::new(
+            &BOARD,
+            board_kernel.create_grant(&memory_allocation_capability)
+        )
+    );
+
     // // DEBUG Restart All Apps
     // //
     // // Uncomment to enable a button press to restart all apps.
@@ -600,6 +687,7 @@ pub unsafe fn reset_handler() {
         ipc: kernel::ipc::IPC::new(board_kernel, &memory_allocation_capability),
         crc: crc,
         dac: dac,
+        board_info: board_info,
     };
 
     // Create virtual device for kernel debug.
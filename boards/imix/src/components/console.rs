@@ -47,6 +47,7 @@ impl Component for ConsoleComponent {
 
     unsafe fn finalize(&mut self) -> Self::Output {
         let grant_cap = create_capability!(capabilities::MemoryAllocationCapability);
+        let process_mgmt_cap = create_capability!(capabilities::ProcessManagementCapability);
 
         // Create virtual device for console.
         let console_uart = static_init!(UartDevice, UartDevice::new(self.uart_mux, true));
@@ -56,13 +57,14 @@ impl Component for ConsoleComponent {
             console::Console<'static>,
             console::Console::new(
                 console_uart,
-                &mut console::WRITE_BUF,
                 &mut console::READ_BUF,
                 self.board_kernel.create_grant(&grant_cap)
             )
         );
         hil::uart::Transmit::set_transmit_client(console_uart, console);
         hil::uart::Receive::set_receive_client(console_uart, console);
+        self.board_kernel
+            .set_process_state_observer(console, &process_mgmt_cap);
 
         // Create virtual device for kernel debug.
         let debugger_uart = static_init!(UartDevice, UartDevice::new(self.uart_mux, false));
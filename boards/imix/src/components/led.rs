@@ -6,7 +6,7 @@
 //! Usage
 //! -----
 //! ```rust
-//! let led = LedComponent::new().finalize();
+//! let led = LedComponent::new(mux_alarm).finalize();
 //! ```
 
 // Author: Philip Levis <pal@cs.stanford.edu>
@@ -15,26 +15,42 @@
 #![allow(dead_code)] // Components are intended to be conditionally included
 
 use capsules::led;
+use capsules::virtual_alarm::{MuxAlarm, VirtualMuxAlarm};
 use kernel::component::Component;
+use kernel::hil::led::LedColor;
 use kernel::static_init;
 
-pub struct LedComponent {}
+pub struct LedComponent {
+    alarm_mux: &'static MuxAlarm<'static, sam4l::ast::Ast<'static>>,
+}
 
 impl LedComponent {
-    pub fn new() -> LedComponent {
-        LedComponent {}
+    pub fn new(mux: &'static MuxAlarm<'static, sam4l::ast::Ast>) -> LedComponent {
+        LedComponent { alarm_mux: mux }
     }
 }
 
 impl Component for LedComponent {
-    type Output = &'static led::LED<'static>;
+    type Output = &'static led::LED<'static, VirtualMuxAlarm<'static, sam4l::ast::Ast<'static>>>;
 
     unsafe fn finalize(&mut self) -> Self::Output {
-        let led_pins = static_init!(
-            [(&'static kernel::hil::gpio::Pin, led::ActivationMode); 1],
-            [(&sam4l::gpio::PC[10], led::ActivationMode::ActiveHigh),]
+        let leds = static_init!(
+            [led::Led<'static>; 1],
+            [led::Led::gpio(
+                &sam4l::gpio::PC[10],
+                led::ActivationMode::ActiveHigh,
+                LedColor::Unknown
+            )]
+        );
+        let led_virtual_alarm = static_init!(
+            VirtualMuxAlarm<'static, sam4l::ast::Ast>,
+            VirtualMuxAlarm::new(self.alarm_mux)
+        );
+        let led = static_init!(
+            led::LED<'static, VirtualMuxAlarm<'static, sam4l::ast::Ast>>,
+            led::LED::new(&leds[..], led_virtual_alarm)
         );
-        let led = static_init!(led::LED<'static>, led::LED::new(&led_pins[..]));
+        led_virtual_alarm.set_client(led);
         led
     }
 }
@@ -19,6 +19,7 @@
 #![allow(dead_code)] // Components are intended to be conditionally included
 
 use capsules::ieee802154::device::MacDevice;
+use capsules::net::buffer::PacketPool;
 use capsules::net::ieee802154::MacAddress;
 use capsules::net::ipv6::ip_utils::IPAddr;
 use capsules::net::ipv6::ipv6::{IP6Packet, IPPayload, TransportHeader};
@@ -31,6 +32,7 @@ use capsules::net::udp::udp_send::{UDPSendStruct, UDPSender};
 use capsules::virtual_alarm::{MuxAlarm, VirtualMuxAlarm};
 
 use kernel::capabilities;
+use kernel::common::cells::TakeCell;
 use kernel::component::Component;
 use kernel::create_capability;
 use kernel::hil::radio;
@@ -42,12 +44,14 @@ const PAYLOAD_LEN: usize = 200; //The max size UDP message that can be sent by u
 //
 //   1. RF233_BUF: buffer the IP6_Sender uses to pass frames to the radio after fragmentation
 //   2. SIXLOWPAN_RX_BUF: Buffer to hold full IP packets after they are decompressed by 6LoWPAN
-//   3. UDP_DGRAM: The payload of the IP6_Packet, which holds full IP Packets before they are tx'd
+//   3. UDP_POOL_BUF: Pool buffer that app payloads are copied into, and which the IP6_Sender
+//      sends from. The IP6_Packet has no payload buffer of its own, as every packet it sends
+//      is in a pool buffer
 
 const UDP_HDR_SIZE: usize = 8;
 static mut RF233_BUF: [u8; radio::MAX_BUF_SIZE] = [0x00; radio::MAX_BUF_SIZE];
 static mut SIXLOWPAN_RX_BUF: [u8; 1280] = [0x00; 1280];
-static mut UDP_DGRAM: [u8; PAYLOAD_LEN - UDP_HDR_SIZE] = [0; PAYLOAD_LEN - UDP_HDR_SIZE];
+static mut UDP_POOL_BUF: [u8; PAYLOAD_LEN - UDP_HDR_SIZE] = [0; PAYLOAD_LEN - UDP_HDR_SIZE];
 
 pub struct UDPComponent {
     board_kernel: &'static kernel::Kernel,
@@ -129,7 +133,8 @@ impl Component for UDPComponent {
         let tr_hdr = TransportHeader::UDP(UDPHeader::new());
         let ip_pyld: IPPayload = IPPayload {
             header: tr_hdr,
-            payload: &mut UDP_DGRAM,
+            payload: &mut [],
+            offset: 0,
         };
         let ip6_dg = static_init!(IP6Packet<'static>, IP6Packet::new(ip_pyld));
 
@@ -150,6 +155,13 @@ impl Component for UDPComponent {
         );
         ipsender_virtual_alarm.set_client(ip_send);
 
+        let pool_bufs = static_init!(
+            [TakeCell<'static, [u8]>; 1],
+            [TakeCell::new(&mut UDP_POOL_BUF)]
+        );
+        let pool = static_init!(PacketPool<'static>, PacketPool::new(pool_bufs));
+        ip_send.set_pool(pool);
+
         // Set src IP of the sender to be the address configured via the sam4l.
         // Userland apps can change this if they so choose.
         ip_send.set_addr(self.interface_list[2]);
@@ -186,6 +198,7 @@ impl Component for UDPComponent {
                 PAYLOAD_LEN
             )
         );
+        udp_driver.set_pool(pool);
         udp_send.set_client(udp_driver);
         udp_recv.set_client(udp_driver);
         udp_driver
@@ -100,6 +100,7 @@ pub unsafe fn initialize_all(
     let ip_pyld: IPPayload = IPPayload {
         header: TransportHeader::ICMP(icmp_hdr),
         payload: &mut ICMP_PAYLOAD,
+        offset: 0,
     };
 
     let ip6_dg = static_init!(IP6Packet<'static>, IP6Packet::new(ip_pyld));
@@ -208,6 +208,7 @@ pub unsafe fn initialize_all(
     let ip_pyld: IPPayload = IPPayload {
         header: tr_hdr,
         payload: &mut UDP_DGRAM,
+        offset: 0,
     };
 
     let mut ip6_dg: IP6Packet = IP6Packet {
@@ -12,6 +12,7 @@ mod components;
 use capsules::alarm::AlarmDriver;
 use capsules::net::ieee802154::MacAddress;
 use capsules::net::ipv6::ip_utils::IPAddr;
+use capsules::uart_switch::{UartBackend, UartSwitch};
 use capsules::virtual_alarm::{MuxAlarm, VirtualMuxAlarm};
 use capsules::virtual_i2c::MuxI2C;
 use capsules::virtual_spi::{MuxSpiMaster, VirtualSpiMasterDevice};
@@ -24,6 +25,7 @@ use kernel::hil::radio;
 use kernel::hil::radio::{RadioConfig, RadioData};
 use kernel::hil::spi::SpiMaster;
 use kernel::hil::Controller;
+use kernel::process_info::ProcessInfoEntry;
 #[allow(unused_imports)]
 use kernel::{create_capability, debug, debug_gpio, static_init};
 
@@ -107,6 +109,9 @@ static mut APP_MEMORY: [u8; 32768] = [0; 32768];
 
 static mut PROCESSES: [Option<&'static kernel::procs::ProcessType>; NUM_PROCS] = [None; NUM_PROCS];
 
+// Description of the processes for debuggers, see `tools/gdb/tock.py`.
+static mut PROCESS_INFO: [ProcessInfoEntry; NUM_PROCS] = [ProcessInfoEntry::EMPTY; NUM_PROCS];
+
 /// Dummy buffer that causes the linker to reserve enough space for the stack.
 #[no_mangle]
 #[link_section = ".stack_buffer"]
@@ -124,7 +129,7 @@ struct Imix {
     humidity: &'static capsules::humidity::HumiditySensor<'static>,
     ambient_light: &'static capsules::ambient_light::AmbientLight<'static>,
     adc: &'static capsules::adc::Adc<'static, sam4l::adc::Adc>,
-    led: &'static capsules::led::LED<'static>,
+    led: &'static capsules::led::LED<'static, VirtualMuxAlarm<'static, sam4l::ast::Ast<'static>>>,
     button: &'static capsules::button::Button<'static>,
     rng: &'static capsules::rng::RngDriver<'static>,
     analog_comparator: &'static capsules::analog_comparator::AnalogComparator<
@@ -296,23 +301,36 @@ pub unsafe fn reset_handler() {
     let board_kernel = static_init!(kernel::Kernel, kernel::Kernel::new(&PROCESSES));
 
     // # CONSOLE
-    // Create a shared UART channel for the consoles and for kernel debug.
+    // The consoles and kernel debug start on USART3, and the process console
+    // can move them to USART0, on the header pins.
     sam4l::usart::USART3.set_mode(sam4l::usart::UsartMode::Uart);
+    sam4l::usart::USART0.set_mode(sam4l::usart::UsartMode::Uart);
+    let uart_backends = static_init!(
+        [UartBackend<'static>; 2],
+        [
+            UartBackend::new("usart3", &sam4l::usart::USART3),
+            UartBackend::new("usart0", &sam4l::usart::USART0),
+        ]
+    );
+    let uart_switch = static_init!(UartSwitch<'static>, UartSwitch::new(uart_backends));
+    for backend in uart_backends.iter() {
+        hil::uart::Transmit::set_transmit_client(backend.uart, uart_switch);
+        hil::uart::Receive::set_receive_client(backend.uart, uart_switch);
+    }
+
+    // Create a shared UART channel for the consoles and for kernel debug.
     let uart_mux = static_init!(
         MuxUart<'static>,
-        MuxUart::new(
-            &sam4l::usart::USART3,
-            &mut capsules::virtual_uart::RX_BUF,
-            115200
-        )
+        MuxUart::new(uart_switch, &mut capsules::virtual_uart::RX_BUF, 115200)
     );
 
     uart_mux.initialize();
 
-    hil::uart::Transmit::set_transmit_client(&sam4l::usart::USART3, uart_mux);
-    hil::uart::Receive::set_receive_client(&sam4l::usart::USART3, uart_mux);
+    hil::uart::Transmit::set_transmit_client(uart_switch, uart_mux);
+    hil::uart::Receive::set_receive_client(uart_switch, uart_mux);
 
     let pconsole = ProcessConsoleComponent::new(board_kernel, uart_mux).finalize();
+    pconsole.set_uart_switch(uart_switch);
     let console = ConsoleComponent::new(board_kernel, uart_mux).finalize();
 
     // Allow processes to communicate over BLE through the nRF51822
@@ -360,7 +378,7 @@ pub unsafe fn reset_handler() {
 
     let adc = AdcComponent::new().finalize();
     let gpio = GpioComponent::new(board_kernel).finalize();
-    let led = LedComponent::new().finalize();
+    let led = LedComponent::new(mux_alarm).finalize();
     let button = ButtonComponent::new(board_kernel).finalize();
     let crc = CrcComponent::new(board_kernel).finalize();
     let analog_comparator = AcComponent::new().finalize();
@@ -465,6 +483,7 @@ pub unsafe fn reset_handler() {
         /// Beginning of the ROM region containing app images.
         static _sapps: u8;
     }
+    kernel::process_info::set_entries(&mut PROCESS_INFO, &process_mgmt_cap);
     kernel::procs::load_processes(
         board_kernel,
         chip,
@@ -474,6 +493,7 @@ pub unsafe fn reset_handler() {
         FAULT_RESPONSE,
         &process_mgmt_cap,
     );
+    kernel::process_info::print_load_map(&PROCESSES, &process_mgmt_cap);
 
     board_kernel.kernel_loop(&imix, chip, Some(&imix.ipc), &main_cap);
 }
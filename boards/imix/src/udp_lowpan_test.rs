@@ -120,6 +120,7 @@ pub unsafe fn initialize_all(
     let ip_pyld: IPPayload = IPPayload {
         header: tr_hdr,
         payload: &mut UDP_PAYLOAD,
+        offset: 0,
     };
 
     let ip6_dg = static_init!(IP6Packet<'static>, IP6Packet::new(ip_pyld));
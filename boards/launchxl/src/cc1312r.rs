@@ -1,4 +1,4 @@
-use super::Pinmap;
+use super::{PinLabels, Pinmap};
 use enum_primitive::cast::FromPrimitive;
 use enum_primitive::enum_from_primitive;
 
@@ -28,6 +28,10 @@ pub enum PinFn {
     Adc7 = 23,
     Pwm0 = 18,
     Pwm1 = 19,
+    Ssi0Tx = 8,
+    Ssi0Rx = 9,
+    Ssi0Clk = 10,
+    Ssi0Fss = 11,
 }
 }
 
@@ -51,6 +55,17 @@ pub static PINMAP: Pinmap = Pinmap {
     a7: PinFn::Adc7 as usize,
     pwm0: PinFn::Pwm0 as usize,
     pwm1: PinFn::Pwm1 as usize,
+    ssi0_rx: PinFn::Ssi0Rx as usize,
+    ssi0_tx: PinFn::Ssi0Tx as usize,
+    ssi0_clk: PinFn::Ssi0Clk as usize,
+    ssi0_fss: PinFn::Ssi0Fss as usize,
+    labels: PinLabels {
+        red_led: "DIO6/RLED",
+        green_led: "DIO7/GLED",
+        button1: "DIO13/BTN-1",
+        button2: "DIO14/BTN-2",
+        gpio0: "DIO22/GPIO0",
+    },
 };
 
 // Booster pack standard pinout
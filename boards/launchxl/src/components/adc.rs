@@ -0,0 +1,63 @@
+//! Component for the ADC on the launchxl boards.
+//!
+//! This provides one Component, AdcComponent, which implements
+//! a userspace syscall interface to the cc26x2 ADC. It provides
+//! 8 ADC channels, A0-A7, which are DIO30 down to DIO23 on the
+//! headers of both the CC1312R and CC1352P launchpads. The ADC
+//! is calibrated with the factory trims of the chip.
+//!
+//! Usage
+//! -----
+//! ```rust
+//! let adc = AdcComponent::new().finalize();
+//! ```
+
+#![allow(dead_code)] // Components are intended to be conditionally included
+
+use capsules::adc;
+use cc26x2::adc::Channel;
+use kernel::component::Component;
+use kernel::hil::adc::Calibration;
+use kernel::static_init;
+
+pub struct AdcComponent {}
+
+impl AdcComponent {
+    pub fn new() -> AdcComponent {
+        AdcComponent {}
+    }
+}
+
+impl Component for AdcComponent {
+    type Output = &'static adc::Adc<'static, cc26x2::adc::Adc>;
+
+    unsafe fn finalize(&mut self) -> Self::Output {
+        let adc_channels = static_init!(
+            [&'static Channel; 8],
+            [
+                &Channel::Dio30, // A0
+                &Channel::Dio29, // A1
+                &Channel::Dio28, // A2
+                &Channel::Dio27, // A3
+                &Channel::Dio26, // A4
+                &Channel::Dio25, // A5
+                &Channel::Dio24, // A6
+                &Channel::Dio23, // A7
+            ]
+        );
+        let adc = static_init!(
+            adc::Adc<'static, cc26x2::adc::Adc>,
+            adc::Adc::new(
+                &cc26x2::adc::ADC,
+                adc_channels,
+                &mut adc::ADC_BUFFER1,
+                &mut adc::ADC_BUFFER2,
+                &mut adc::ADC_BUFFER3
+            )
+        );
+        cc26x2::adc::ADC.set_client(adc);
+        cc26x2::adc::ADC.calibrate();
+
+        adc
+    }
+}
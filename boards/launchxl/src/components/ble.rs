@@ -0,0 +1,77 @@
+//! Component for the BLE advertising radio on the launchxl boards.
+//!
+//! The radio core runs one radio mode at a time, so this takes the radio
+//! core over from the IEEE 802.15.4 radio.
+//!
+//! Usage
+//! -----
+//! ```rust
+//! let ble_radio = BleComponent::new(board_kernel, &cc26x2::ble_radio::RADIO, mux_alarm).finalize();
+//! ```
+
+#![allow(dead_code)] // Components are intended to be conditionally included
+
+use capsules::virtual_alarm::{MuxAlarm, VirtualMuxAlarm};
+use cc26x2::rtc::Rtc;
+
+use kernel::capabilities;
+use kernel::component::Component;
+use kernel::hil::ble_advertising::BleAdvertisementDriver;
+use kernel::{create_capability, static_init};
+
+type Radio = cc26x2::ble_radio::Radio;
+
+pub struct BleComponent {
+    board_kernel: &'static kernel::Kernel,
+    radio: &'static Radio,
+    mux_alarm: &'static MuxAlarm<'static, Rtc>,
+}
+
+impl BleComponent {
+    pub fn new(
+        board_kernel: &'static kernel::Kernel,
+        radio: &'static Radio,
+        mux_alarm: &'static MuxAlarm<'static, Rtc>,
+    ) -> BleComponent {
+        BleComponent {
+            board_kernel: board_kernel,
+            radio: radio,
+            mux_alarm: mux_alarm,
+        }
+    }
+}
+
+impl Component for BleComponent {
+    type Output = &'static capsules::ble_advertising_driver::BLE<
+        'static,
+        Radio,
+        VirtualMuxAlarm<'static, Rtc>,
+    >;
+
+    unsafe fn finalize(&mut self) -> Self::Output {
+        let grant_cap = create_capability!(capabilities::MemoryAllocationCapability);
+
+        // The radio core hands its interrupts to the radio
+        cc26x2::rfc::RFC.set_client(self.radio);
+
+        let ble_radio_virtual_alarm = static_init!(
+            VirtualMuxAlarm<'static, Rtc>,
+            VirtualMuxAlarm::new(self.mux_alarm)
+        );
+
+        let ble_radio = static_init!(
+            capsules::ble_advertising_driver::BLE<'static, Radio, VirtualMuxAlarm<'static, Rtc>>,
+            capsules::ble_advertising_driver::BLE::new(
+                self.radio,
+                self.board_kernel.create_grant(&grant_cap),
+                &mut capsules::ble_advertising_driver::BUF,
+                ble_radio_virtual_alarm
+            )
+        );
+        self.radio.set_receive_client(ble_radio);
+        self.radio.set_transmit_client(ble_radio);
+        ble_radio_virtual_alarm.set_client(ble_radio);
+
+        ble_radio
+    }
+}
@@ -0,0 +1,96 @@
+//! Component for the launchxl board buttons.
+//!
+//! This provides one Component, ButtonComponent, which implements a
+//! userspace syscall interface to the two on-board buttons, BTN-1 and
+//! BTN-2. Buttons that bounce can have the hysteresis of their inputs
+//! enabled, so that a press interrupts once.
+//!
+//! Usage
+//! -----
+//! ```rust
+//! let button = ButtonComponent::new(board_kernel, pinmap)
+//!     .with_input_hysteresis(true)
+//!     .finalize();
+//! ```
+
+#![allow(dead_code)] // Components are intended to be conditionally included
+
+use capsules::button;
+use kernel::capabilities;
+use kernel::component::Component;
+use kernel::create_capability;
+use kernel::hil::gpio;
+use kernel::static_init;
+
+use crate::Pinmap;
+
+pub struct ButtonComponent {
+    board_kernel: &'static kernel::Kernel,
+    pinmap: &'static Pinmap,
+    input_hysteresis: bool,
+}
+
+impl ButtonComponent {
+    const NUM_PINS: usize = 2;
+    pub fn new(board_kernel: &'static kernel::Kernel, pinmap: &'static Pinmap) -> ButtonComponent {
+        ButtonComponent {
+            board_kernel: board_kernel,
+            pinmap: pinmap,
+            input_hysteresis: false,
+        }
+    }
+
+    /// Enable the hysteresis of the button inputs, for buttons that bounce.
+    pub fn with_input_hysteresis(mut self, enabled: bool) -> ButtonComponent {
+        self.input_hysteresis = enabled;
+        self
+    }
+}
+
+impl Component for ButtonComponent {
+    type Output = &'static button::Button<'static>;
+
+    unsafe fn finalize(&mut self) -> Self::Output {
+        let grant_cap = create_capability!(capabilities::MemoryAllocationCapability);
+
+        let button_pins = static_init!(
+            [(&'static gpio::InterruptValuePin, button::GpioMode); ButtonComponent::NUM_PINS],
+            [
+                (
+                    static_init!(
+                        gpio::InterruptValueWrapper,
+                        gpio::InterruptValueWrapper::new(&cc26x2::gpio::PORT[self.pinmap.button1])
+                    )
+                    .finalize(),
+                    button::GpioMode::LowWhenPressed
+                ),
+                (
+                    static_init!(
+                        gpio::InterruptValueWrapper,
+                        gpio::InterruptValueWrapper::new(&cc26x2::gpio::PORT[self.pinmap.button2])
+                    )
+                    .finalize(),
+                    button::GpioMode::LowWhenPressed
+                )
+            ]
+        );
+
+        if self.input_hysteresis {
+            for &pin in [self.pinmap.button1, self.pinmap.button2].iter() {
+                cc26x2::gpio::PORT[pin].enable_input_hysteresis();
+            }
+        }
+
+        let button = static_init!(
+            button::Button<'static>,
+            button::Button::new(button_pins, self.board_kernel.create_grant(&grant_cap))
+        );
+
+        for (pin, _) in button_pins.iter() {
+            pin.set_client(button);
+            pin.set_floating_state(gpio::FloatingState::PullUp);
+        }
+
+        button
+    }
+}
@@ -0,0 +1,156 @@
+//! Component for the on-chip flash of the launchxl boards.
+//!
+//! This provides one Component, FlashComponent, which shares the flash
+//! between two userspace syscall interfaces: non-volatile storage, in the
+//! 64 KB that follow the apps, and app flash, which lets apps write their
+//! own flash region. App flash gets the app pages for itself, so that
+//! non-volatile storage cannot write them.
+//!
+//! Erasing a sector stalls the CPU, so erases wait until the IEEE 802.15.4
+//! radio is not busy and the userspace alarm is not about to fire.
+//!
+//! Usage
+//! -----
+//! ```rust
+//! let (nonvolatile_storage, app_flash) =
+//!     FlashComponent::new(board_kernel, mux_alarm, userspace_alarm).finalize();
+//! ```
+
+#![allow(dead_code)] // Components are intended to be conditionally included
+
+use capsules::app_flash_driver::AppFlash;
+use capsules::flash_erase_policy::IdleErasePolicy;
+use capsules::nonvolatile_storage_driver::NonvolatileStorage;
+use capsules::nonvolatile_to_pages::NonvolatileToPages;
+use capsules::virtual_alarm::{MuxAlarm, VirtualMuxAlarm};
+use capsules::virtual_flash::{FlashUser, MuxFlash};
+use cc26x2::flash::{Cc26x2Page, Flash, PAGE_SIZE};
+use cc26x2::ieee802154_radio::Radio;
+use cc26x2::rtc::Rtc;
+use kernel::capabilities;
+use kernel::component::Component;
+use kernel::create_capability;
+use kernel::hil;
+use kernel::static_init;
+
+// The app region and the storage after it, see chip_layout.ld
+const APPS_START: usize = 0x30000;
+const APPS_LEN: usize = 0x10000;
+const STORAGE_START: usize = 0x40000;
+const STORAGE_LEN: usize = 0x10000;
+
+// Upper bound of the erase time of a sector
+const ERASE_MS: u32 = 10;
+
+pub struct FlashComponent {
+    board_kernel: &'static kernel::Kernel,
+    alarm_mux: &'static MuxAlarm<'static, Rtc>,
+    userspace_alarm: &'static VirtualMuxAlarm<'static, Rtc>,
+}
+
+impl FlashComponent {
+    pub fn new(
+        board_kernel: &'static kernel::Kernel,
+        mux: &'static MuxAlarm<'static, Rtc>,
+        userspace_alarm: &'static VirtualMuxAlarm<'static, Rtc>,
+    ) -> FlashComponent {
+        FlashComponent {
+            board_kernel: board_kernel,
+            alarm_mux: mux,
+            userspace_alarm: userspace_alarm,
+        }
+    }
+}
+
+impl Component for FlashComponent {
+    type Output = (
+        &'static NonvolatileStorage<'static>,
+        &'static AppFlash<'static>,
+    );
+
+    unsafe fn finalize(&mut self) -> Self::Output {
+        let grant_cap = create_capability!(capabilities::MemoryAllocationCapability);
+
+        let mux_flash = static_init!(
+            MuxFlash<'static, Flash>,
+            MuxFlash::new(&cc26x2::flash::FLASH)
+        );
+        hil::flash::HasClient::set_client(&cc26x2::flash::FLASH, mux_flash);
+
+        let policy_alarm = static_init!(
+            VirtualMuxAlarm<'static, Rtc>,
+            VirtualMuxAlarm::new(self.alarm_mux)
+        );
+        let critical_alarms = static_init!(
+            [&'static VirtualMuxAlarm<'static, Rtc>; 1],
+            [self.userspace_alarm]
+        );
+        let erase_policy = static_init!(
+            IdleErasePolicy<'static, Radio, VirtualMuxAlarm<'static, Rtc>>,
+            IdleErasePolicy::new(
+                &cc26x2::ieee802154_radio::RADIO,
+                policy_alarm,
+                critical_alarms,
+                ERASE_MS
+            )
+        );
+        policy_alarm.set_client(erase_policy);
+        mux_flash.set_erase_policy(erase_policy);
+
+        static mut STORAGE_PAGEBUFFER: Cc26x2Page = Cc26x2Page::new();
+        let storage_flash = static_init!(FlashUser<'static, Flash>, FlashUser::new(mux_flash));
+        let storage_to_pages = static_init!(
+            NonvolatileToPages<'static, FlashUser<'static, Flash>>,
+            NonvolatileToPages::new(storage_flash, &mut STORAGE_PAGEBUFFER)
+        );
+        hil::flash::HasClient::set_client(storage_flash, storage_to_pages);
+
+        extern "C" {
+            /// Kernel storage region, allocated with the storage_volume!
+            /// macro in common/utils.rs
+            static _sstorage: u8;
+            static _estorage: u8;
+        }
+        let kernel_start = &_sstorage as *const u8 as usize;
+        let kernel_len = &_estorage as *const u8 as usize - kernel_start;
+
+        let nonvolatile_storage = static_init!(
+            NonvolatileStorage<'static>,
+            NonvolatileStorage::new(
+                storage_to_pages,
+                self.board_kernel.create_grant(&grant_cap),
+                STORAGE_START,
+                STORAGE_LEN,
+                kernel_start,
+                kernel_len,
+                &mut capsules::nonvolatile_storage_driver::BUFFER
+            )
+        );
+        hil::nonvolatile_storage::NonvolatileStorage::set_client(
+            storage_to_pages,
+            nonvolatile_storage,
+        );
+
+        static mut APP_PAGEBUFFER: Cc26x2Page = Cc26x2Page::new();
+        static mut APP_FLASH_BUFFER: [u8; 512] = [0; 512];
+        let app_flash_user = static_init!(FlashUser<'static, Flash>, FlashUser::new(mux_flash));
+        app_flash_user.set_region(APPS_START / PAGE_SIZE, APPS_LEN / PAGE_SIZE);
+        let app_to_pages = static_init!(
+            NonvolatileToPages<'static, FlashUser<'static, Flash>>,
+            NonvolatileToPages::new(app_flash_user, &mut APP_PAGEBUFFER)
+        );
+        hil::flash::HasClient::set_client(app_flash_user, app_to_pages);
+
+        let app_flash = static_init!(
+            AppFlash<'static>,
+            AppFlash::new(
+                app_to_pages,
+                self.board_kernel.create_grant(&grant_cap),
+                &mut APP_FLASH_BUFFER
+            )
+        );
+        hil::nonvolatile_storage::NonvolatileStorage::set_client(app_to_pages, app_flash);
+
+        (nonvolatile_storage, app_flash)
+    }
+}
@@ -0,0 +1,88 @@
+//! Component for the I2C bus on the launchxl boards.
+//!
+//! This provides two Components. I2CMuxComponent shares the I2C0 bus on the
+//! headers between the kernel drivers of the sensors attached to it, e.g. of
+//! a sensor BoosterPack. Each driver gets an `I2CDevice` for its address from
+//! the returned mux. I2CMasterDriverComponent provides the userspace syscall
+//! interface to the bus, on an `I2CDevice` of the mux, so that processes
+//! share the bus with those drivers.
+//!
+//! Usage
+//! -----
+//! ```rust
+//! let mux_i2c = I2CMuxComponent::new().finalize();
+//! let si7021_i2c = static_init!(I2CDevice, I2CDevice::new(mux_i2c, 0x40));
+//! let i2c_master = I2CMasterDriverComponent::new(board_kernel, mux_i2c).finalize();
+//! ```
+
+#![allow(dead_code)] // Components are intended to be conditionally included
+
+use capsules::i2c_master::I2CMasterDriver;
+use capsules::virtual_i2c::{I2CDevice, MuxI2C};
+use kernel::capabilities;
+use kernel::component::Component;
+use kernel::create_capability;
+use kernel::hil::i2c::I2CMaster;
+use kernel::static_init;
+
+pub struct I2CMuxComponent {}
+
+impl I2CMuxComponent {
+    pub fn new() -> I2CMuxComponent {
+        I2CMuxComponent {}
+    }
+}
+
+impl Component for I2CMuxComponent {
+    type Output = &'static MuxI2C<'static>;
+
+    unsafe fn finalize(&mut self) -> Self::Output {
+        cc26x2::i2c::I2C0.initialize();
+
+        let mux_i2c = static_init!(MuxI2C<'static>, MuxI2C::new(&cc26x2::i2c::I2C0));
+        cc26x2::i2c::I2C0.set_client(mux_i2c);
+        cc26x2::i2c::I2C0.enable();
+
+        mux_i2c
+    }
+}
+
+pub struct I2CMasterDriverComponent {
+    board_kernel: &'static kernel::Kernel,
+    mux_i2c: &'static MuxI2C<'static>,
+}
+
+impl I2CMasterDriverComponent {
+    pub fn new(
+        board_kernel: &'static kernel::Kernel,
+        mux_i2c: &'static MuxI2C<'static>,
+    ) -> I2CMasterDriverComponent {
+        I2CMasterDriverComponent {
+            board_kernel: board_kernel,
+            mux_i2c: mux_i2c,
+        }
+    }
+}
+
+impl Component for I2CMasterDriverComponent {
+    type Output = &'static I2CMasterDriver<I2CDevice<'static>>;
+
+    unsafe fn finalize(&mut self) -> Self::Output {
+        let grant_cap = create_capability!(capabilities::MemoryAllocationCapability);
+
+        // Processes give the address of each transaction, so the address of
+        // the device is only a placeholder
+        let i2c_device = static_init!(I2CDevice<'static>, I2CDevice::new(self.mux_i2c, 0));
+        let i2c_master = static_init!(
+            I2CMasterDriver<I2CDevice<'static>>,
+            I2CMasterDriver::new(
+                i2c_device,
+                &mut capsules::i2c_master::BUF,
+                self.board_kernel.create_grant(&grant_cap)
+            )
+        );
+        i2c_device.set_client(i2c_master);
+
+        i2c_master
+    }
+}
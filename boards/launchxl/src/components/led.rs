@@ -0,0 +1,69 @@
+//! Component for the launchxl board LEDs.
+//!
+//! This provides one Component, LedComponent, which implements
+//! a userspace syscall interface to the red and green on-board LEDs.
+//!
+//! Usage
+//! -----
+//! ```rust
+//! let led = LedComponent::new(mux_alarm, pinmap).finalize();
+//! ```
+
+#![allow(dead_code)] // Components are intended to be conditionally included
+
+use capsules::led;
+use capsules::virtual_alarm::{MuxAlarm, VirtualMuxAlarm};
+use kernel::component::Component;
+use kernel::hil::led::LedColor;
+use kernel::static_init;
+
+use crate::Pinmap;
+
+pub struct LedComponent {
+    alarm_mux: &'static MuxAlarm<'static, cc26x2::rtc::Rtc>,
+    pinmap: &'static Pinmap,
+}
+
+impl LedComponent {
+    pub fn new(
+        mux: &'static MuxAlarm<'static, cc26x2::rtc::Rtc>,
+        pinmap: &'static Pinmap,
+    ) -> LedComponent {
+        LedComponent {
+            alarm_mux: mux,
+            pinmap: pinmap,
+        }
+    }
+}
+
+impl Component for LedComponent {
+    type Output = &'static led::LED<'static, VirtualMuxAlarm<'static, cc26x2::rtc::Rtc>>;
+
+    unsafe fn finalize(&mut self) -> Self::Output {
+        let leds = static_init!(
+            [led::Led<'static>; 2],
+            [
+                led::Led::gpio(
+                    &cc26x2::gpio::PORT[self.pinmap.red_led],
+                    led::ActivationMode::ActiveHigh,
+                    LedColor::Red
+                ),
+                led::Led::gpio(
+                    &cc26x2::gpio::PORT[self.pinmap.green_led],
+                    led::ActivationMode::ActiveHigh,
+                    LedColor::Green
+                ),
+            ]
+        );
+        let led_virtual_alarm = static_init!(
+            VirtualMuxAlarm<'static, cc26x2::rtc::Rtc>,
+            VirtualMuxAlarm::new(self.alarm_mux)
+        );
+        let led = static_init!(
+            led::LED<'static, VirtualMuxAlarm<'static, cc26x2::rtc::Rtc>>,
+            led::LED::new(&leds[..], led_virtual_alarm)
+        );
+        led_virtual_alarm.set_client(led);
+        led
+    }
+}
@@ -0,0 +1,23 @@
+pub mod adc;
+pub mod ble;
+pub mod button;
+pub mod flash;
+pub mod i2c;
+pub mod led;
+pub mod prop;
+pub mod pwm;
+pub mod radio;
+pub mod rng;
+pub mod spi;
+
+pub use self::adc::AdcComponent;
+pub use self::ble::BleComponent;
+pub use self::button::ButtonComponent;
+pub use self::flash::FlashComponent;
+pub use self::i2c::{I2CMasterDriverComponent, I2CMuxComponent};
+pub use self::led::LedComponent;
+pub use self::prop::PropRadioComponent;
+pub use self::pwm::PwmComponent;
+pub use self::radio::RadioComponent;
+pub use self::rng::RngComponent;
+pub use self::spi::SpiSlaveComponent;
@@ -0,0 +1,63 @@
+//! Component for the proprietary 2.4 GHz radio on the launchxl boards.
+//!
+//! This provides one Component, PropRadioComponent, which implements a
+//! userspace syscall interface to send and receive raw packets. The radio
+//! core runs one radio mode at a time, so this takes the radio core over
+//! from the IEEE 802.15.4 radio.
+//!
+//! Usage
+//! -----
+//! ```rust
+//! let packet_radio = PropRadioComponent::new(board_kernel, &cc26x2::rfc::prop::RADIO).finalize();
+//! ```
+
+#![allow(dead_code)] // Components are intended to be conditionally included
+
+use capsules::packet_radio::PacketRadioDriver;
+
+use kernel::capabilities;
+use kernel::component::Component;
+use kernel::hil::packet_radio::PacketRadio;
+use kernel::{create_capability, static_init};
+
+type Radio = cc26x2::rfc::prop::PropRadio;
+
+pub struct PropRadioComponent {
+    board_kernel: &'static kernel::Kernel,
+    radio: &'static Radio,
+}
+
+impl PropRadioComponent {
+    pub fn new(board_kernel: &'static kernel::Kernel, radio: &'static Radio) -> PropRadioComponent {
+        PropRadioComponent {
+            board_kernel: board_kernel,
+            radio: radio,
+        }
+    }
+}
+
+impl Component for PropRadioComponent {
+    type Output = &'static PacketRadioDriver<'static>;
+
+    unsafe fn finalize(&mut self) -> Self::Output {
+        let grant_cap = create_capability!(capabilities::MemoryAllocationCapability);
+
+        // The radio core hands its interrupts to the radio
+        cc26x2::rfc::RFC.set_client(self.radio);
+
+        let packet_radio = static_init!(
+            PacketRadioDriver<'static>,
+            PacketRadioDriver::new(
+                self.radio,
+                self.board_kernel.create_grant(&grant_cap),
+                &mut capsules::packet_radio::TX_BUF,
+                &mut capsules::packet_radio::RX_BUF
+            )
+        );
+        self.radio.set_transmit_client(packet_radio);
+        self.radio.set_receive_client(packet_radio);
+        self.radio.start();
+
+        packet_radio
+    }
+}
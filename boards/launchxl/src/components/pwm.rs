@@ -0,0 +1,50 @@
+//! Component for the PWM outputs on the launchxl boards.
+//!
+//! This provides one Component, PwmComponent, which gives userspace the
+//! two timer signals, GPT0A and GPT0B, that the PWM0 and PWM1 header pins
+//! are routed to, e.g. to dim an LED or to drive a servo.
+//!
+//! Usage
+//! -----
+//! ```rust
+//! let pwm = PwmComponent::new().finalize();
+//! ```
+
+#![allow(dead_code)] // Components are intended to be conditionally included
+
+use cc26x2::pwm;
+use kernel::component::Component;
+use kernel::static_init;
+
+pub struct PwmComponent {}
+
+impl PwmComponent {
+    pub fn new() -> PwmComponent {
+        PwmComponent {}
+    }
+}
+
+impl Component for PwmComponent {
+    type Output = &'static capsules::pwm::Pwm<'static>;
+
+    unsafe fn finalize(&mut self) -> Self::Output {
+        let pwm_pins = static_init!(
+            [pwm::Signal<'static>; 2],
+            [
+                pwm::Signal::new(pwm::Timer::GPT0A), // PWM0
+                pwm::Signal::new(pwm::Timer::GPT0B), // PWM1
+            ]
+        );
+        let pwm_channels = static_init!(
+            [capsules::pwm::Channel<'static>; 2],
+            [
+                capsules::pwm::Channel::new(&pwm_pins[0]),
+                capsules::pwm::Channel::new(&pwm_pins[1]),
+            ]
+        );
+        static_init!(
+            capsules::pwm::Pwm<'static>,
+            capsules::pwm::Pwm::new(pwm_channels)
+        )
+    }
+}
@@ -0,0 +1,128 @@
+//! Component for the IEEE 802.15.4 radio on the launchxl boards.
+//!
+//! This provides one Component, RadioComponent, which implements a
+//! userspace syscall interface to a full 802.15.4 stack with a
+//! always-on MAC implementation, on top of the 2.4 GHz radio and the AES
+//! engine of the cc26x2, which secures frames with CCM* in hardware.
+//!
+//! Usage
+//! -----
+//! ```rust
+//! let (radio_driver, mux_mac) = RadioComponent::new(
+//!     board_kernel,
+//!     &cc26x2::ieee802154_radio::RADIO,
+//!     PAN_ID,
+//!     SRC_MAC,
+//! )
+//! .finalize();
+//! ```
+
+#![allow(dead_code)] // Components are intended to be conditionally included
+
+use capsules::ieee802154::device::MacDevice;
+use capsules::ieee802154::mac::{AwakeMac, Mac};
+
+use kernel::capabilities;
+use kernel::component::Component;
+use kernel::create_capability;
+use kernel::hil::radio;
+use kernel::hil::radio::RadioData;
+use kernel::hil::symmetric_encryption::{AES128, AES128CCM};
+use kernel::static_init;
+
+type Radio = cc26x2::ieee802154_radio::Radio;
+type Aes = cc26x2::crypto::Aes<'static>;
+
+pub struct RadioComponent {
+    board_kernel: &'static kernel::Kernel,
+    radio: &'static Radio,
+    pan_id: capsules::net::ieee802154::PanID,
+    short_addr: u16,
+}
+
+impl RadioComponent {
+    pub fn new(
+        board_kernel: &'static kernel::Kernel,
+        radio: &'static Radio,
+        pan_id: capsules::net::ieee802154::PanID,
+        addr: u16,
+    ) -> RadioComponent {
+        RadioComponent {
+            board_kernel: board_kernel,
+            radio: radio,
+            pan_id: pan_id,
+            short_addr: addr,
+        }
+    }
+}
+
+// The system call interface ("radio") requires one buffer, which it copies
+// application transmissions into or copies out to application buffers for
+// reception.
+static mut RADIO_BUF: [u8; radio::MAX_BUF_SIZE] = [0x00; radio::MAX_BUF_SIZE];
+
+// The buffer received frames are copied into.
+static mut RADIO_RX_BUF: [u8; radio::MAX_BUF_SIZE] = [0x00; radio::MAX_BUF_SIZE];
+
+impl Component for RadioComponent {
+    type Output = (
+        &'static capsules::ieee802154::RadioDriver<'static>,
+        &'static capsules::ieee802154::virtual_mac::MuxMac<'static>,
+    );
+
+    unsafe fn finalize(&mut self) -> Self::Output {
+        let grant_cap = create_capability!(capabilities::MemoryAllocationCapability);
+
+        let aes = &cc26x2::crypto::AES;
+        aes.enable();
+
+        // The radio core hands its interrupts to the radio
+        cc26x2::rfc::RFC.set_client(self.radio);
+
+        // Keeps the radio on permanently; pass-through layer
+        let awake_mac: &AwakeMac<Radio> =
+            static_init!(AwakeMac<'static, Radio>, AwakeMac::new(self.radio));
+        self.radio.set_transmit_client(awake_mac);
+        self.radio.set_receive_client(awake_mac, &mut RADIO_RX_BUF);
+
+        let mac_device = static_init!(
+            capsules::ieee802154::framer::Framer<'static, AwakeMac<'static, Radio>, Aes>,
+            capsules::ieee802154::framer::Framer::new(awake_mac, aes)
+        );
+        AES128CCM::set_client(aes, mac_device);
+        awake_mac.set_transmit_client(mac_device);
+        awake_mac.set_receive_client(mac_device);
+        awake_mac.set_config_client(mac_device);
+
+        let mux_mac = static_init!(
+            capsules::ieee802154::virtual_mac::MuxMac<'static>,
+            capsules::ieee802154::virtual_mac::MuxMac::new(mac_device)
+        );
+        mac_device.set_transmit_client(mux_mac);
+        mac_device.set_receive_client(mux_mac);
+
+        let radio_mac = static_init!(
+            capsules::ieee802154::virtual_mac::MacUser<'static>,
+            capsules::ieee802154::virtual_mac::MacUser::new(mux_mac)
+        );
+        mux_mac.add_user(radio_mac);
+
+        let radio_driver = static_init!(
+            capsules::ieee802154::RadioDriver<'static>,
+            capsules::ieee802154::RadioDriver::new(
+                radio_mac,
+                self.board_kernel.create_grant(&grant_cap),
+                &mut RADIO_BUF
+            )
+        );
+
+        mac_device.set_key_procedure(radio_driver);
+        mac_device.set_device_procedure(radio_driver);
+        radio_mac.set_transmit_client(radio_driver);
+        radio_mac.set_receive_client(radio_driver);
+        radio_mac.set_pan(self.pan_id);
+        radio_mac.set_address(self.short_addr);
+
+        (radio_driver, mux_mac)
+    }
+}
@@ -0,0 +1,55 @@
+//! Component for SPI slave mode on the launchxl boards.
+//!
+//! This provides one Component, SpiSlaveComponent, which implements a
+//! userspace syscall interface to SSI0 as an SPI slave, so that the board
+//! can act as a peripheral of another MCU on the BoosterPack SPI pins.
+//!
+//! Usage
+//! -----
+//! ```rust
+//! let spi_slave = SpiSlaveComponent::new().finalize();
+//! ```
+
+#![allow(dead_code)] // Components are intended to be conditionally included
+
+use capsules::spi::SpiSlave;
+use capsules::virtual_spi::VirtualSpiSlaveDevice;
+use kernel::component::Component;
+use kernel::hil;
+use kernel::static_init;
+
+pub struct SpiSlaveComponent {}
+
+impl SpiSlaveComponent {
+    pub fn new() -> SpiSlaveComponent {
+        SpiSlaveComponent {}
+    }
+}
+
+impl Component for SpiSlaveComponent {
+    type Output = &'static SpiSlave<'static, VirtualSpiSlaveDevice<'static, cc26x2::ssi::Ssi>>;
+
+    unsafe fn finalize(&mut self) -> Self::Output {
+        let ssi = &cc26x2::ssi::SSI0;
+        hil::spi::SpiSlave::init(ssi);
+
+        let spi_slave_device = static_init!(
+            VirtualSpiSlaveDevice<'static, cc26x2::ssi::Ssi>,
+            VirtualSpiSlaveDevice::new(ssi)
+        );
+        hil::spi::SpiSlave::set_client(ssi, Some(spi_slave_device));
+
+        let spi_slave = static_init!(
+            SpiSlave<'static, VirtualSpiSlaveDevice<'static, cc26x2::ssi::Ssi>>,
+            SpiSlave::new(spi_slave_device)
+        );
+
+        static mut SPI_READ_BUF: [u8; 64] = [0; 64];
+        static mut SPI_WRITE_BUF: [u8; 64] = [0; 64];
+
+        spi_slave.config_buffers(&mut SPI_READ_BUF, &mut SPI_WRITE_BUF);
+        spi_slave_device.set_client(spi_slave);
+
+        spi_slave
+    }
+}
@@ -12,18 +12,29 @@ use kernel::{create_capability, debug, debug_gpio, static_init};
 
 use capsules::virtual_uart::{MuxUart, UartDevice};
 use cc26x2::aon;
+use cc26x2::ioc;
+use cc26x2::peripheral_interrupts::NvicIrq;
 use cc26x2::prcm;
 use cc26x2::pwm;
 use kernel::capabilities;
+use kernel::component::Component;
 use kernel::hil;
-use kernel::hil::entropy::Entropy32;
 use kernel::hil::gpio;
-use kernel::hil::i2c::I2CMaster;
-use kernel::hil::rng::Rng;
+use kernel::hil::radio::RadioConfig;
+use kernel::interrupt_latency::IrqLatency;
+use kernel::process_info::ProcessInfoEntry;
+
+use components::{
+    AdcComponent, BleComponent, ButtonComponent, FlashComponent, I2CMasterDriverComponent,
+    I2CMuxComponent, LedComponent, PropRadioComponent, PwmComponent, RadioComponent, RngComponent,
+    SpiSlaveComponent,
+};
 
 #[macro_use]
 pub mod io;
 
+mod components;
+
 #[allow(dead_code)]
 mod ccfg_test;
 #[allow(dead_code)]
@@ -34,6 +45,13 @@ mod uart_echo;
 // High frequency oscillator speed
 pub const HFREQ: u32 = 48 * 1_000_000;
 
+// Addressing of the IEEE 802.15.4 radio
+const PAN_ID: u16 = 0xABCD;
+const SRC_MAC: u16 = 0xf00f;
+
+// Reset if the kernel loop stalls for this long
+const WATCHDOG_PERIOD_MS: usize = 2000;
+
 // How should the kernel respond when a process faults.
 const FAULT_RESPONSE: kernel::procs::FaultResponse = kernel::procs::FaultResponse::Panic;
 
@@ -41,9 +59,19 @@ const FAULT_RESPONSE: kernel::procs::FaultResponse = kernel::procs::FaultRespons
 const NUM_PROCS: usize = 3;
 static mut PROCESSES: [Option<&'static kernel::procs::ProcessType>; NUM_PROCS] = [None, None, None];
 
+// Buffers of the sampling profiler and the process table it attributes
+// samples with, which the `profiler` feature enables.
+static mut PROFILE_TRACE: [usize; 64] = [0; 64];
+static mut PROFILE_PROCESSES: [u32; NUM_PROCS] = [0; NUM_PROCS];
+static mut PROFILE_KERNEL: [u32; 256] = [0; 256];
+static mut PROCESS_INFO: [ProcessInfoEntry; NUM_PROCS] = [ProcessInfoEntry::EMPTY; NUM_PROCS];
+
+/// The rate of the sampling profiler, in Hz
+const PROFILE_RATE: u32 = 1000;
+
 #[link_section = ".app_memory"]
 // Give half of RAM to be dedicated APP memory
-static mut APP_MEMORY: [u8; 0x10000] = [0; 0x10000];
+static mut APP_MEMORY: [u8; 0xA000] = [0; 0xA000];
 
 /// Dummy buffer that causes the linker to reserve enough space for the stack.
 #[no_mangle]
@@ -52,7 +80,10 @@ pub static mut STACK_MEMORY: [u8; 0x1000] = [0; 0x1000];
 
 pub struct Platform {
     gpio: &'static capsules::gpio::GPIO<'static>,
-    led: &'static capsules::led::LED<'static>,
+    led: &'static capsules::led::LED<
+        'static,
+        capsules::virtual_alarm::VirtualMuxAlarm<'static, cc26x2::rtc::Rtc>,
+    >,
     console: &'static capsules::console::Console<'static>,
     button: &'static capsules::button::Button<'static>,
     alarm: &'static capsules::alarm::AlarmDriver<
@@ -60,7 +91,31 @@ pub struct Platform {
         capsules::virtual_alarm::VirtualMuxAlarm<'static, cc26x2::rtc::Rtc>,
     >,
     rng: &'static capsules::rng::RngDriver<'static>,
-    i2c_master: &'static capsules::i2c_master::I2CMasterDriver<cc26x2::i2c::I2CMaster<'static>>,
+    i2c_master:
+        &'static capsules::i2c_master::I2CMasterDriver<capsules::virtual_i2c::I2CDevice<'static>>,
+    adc: &'static capsules::adc::Adc<'static, cc26x2::adc::Adc>,
+    spi_slave: &'static capsules::spi::SpiSlave<
+        'static,
+        capsules::virtual_spi::VirtualSpiSlaveDevice<'static, cc26x2::ssi::Ssi>,
+    >,
+    nonvolatile_storage: &'static capsules::nonvolatile_storage_driver::NonvolatileStorage<'static>,
+    app_flash: &'static capsules::app_flash_driver::AppFlash<'static>,
+    cycle_counter:
+        &'static capsules::cycle_counter::CycleCounter<'static, cortexm4::dwt::CycleCounter>,
+    pwm: &'static capsules::pwm::Pwm<'static>,
+    temp: &'static capsules::temperature::TemperatureSensor<'static>,
+    voltage: &'static capsules::voltage::VoltageSensor<'static>,
+    /// The radio core runs either the IEEE 802.15.4 radio or, with the `ble`
+    /// or `prop` feature, the BLE advertising or the proprietary radio
+    radio: Option<&'static capsules::ieee802154::RadioDriver<'static>>,
+    ble_radio: Option<
+        &'static capsules::ble_advertising_driver::BLE<
+            'static,
+            cc26x2::ble_radio::Radio,
+            capsules::virtual_alarm::VirtualMuxAlarm<'static, cc26x2::rtc::Rtc>,
+        >,
+    >,
+    packet_radio: Option<&'static capsules::packet_radio::PacketRadioDriver<'static>>,
     ipc: kernel::ipc::IPC,
 }
 
@@ -77,6 +132,21 @@ impl kernel::Platform for Platform {
             capsules::alarm::DRIVER_NUM => f(Some(self.alarm)),
             capsules::rng::DRIVER_NUM => f(Some(self.rng)),
             capsules::i2c_master::DRIVER_NUM => f(Some(self.i2c_master)),
+            capsules::adc::DRIVER_NUM => f(Some(self.adc)),
+            capsules::spi::DRIVER_NUM => f(Some(self.spi_slave)),
+            capsules::nonvolatile_storage_driver::DRIVER_NUM => f(Some(self.nonvolatile_storage)),
+            capsules::app_flash_driver::DRIVER_NUM => f(Some(self.app_flash)),
+            capsules::cycle_counter::DRIVER_NUM => f(Some(self.cycle_counter)),
+            capsules::pwm::DRIVER_NUM => f(Some(self.pwm)),
+            capsules::temperature::DRIVER_NUM => f(Some(self.temp)),
+            capsules::voltage::DRIVER_NUM => f(Some(self.voltage)),
+            capsules::ieee802154::DRIVER_NUM => f(self.radio.map_or(None, |radio| Some(radio))),
+            capsules::ble_advertising_driver::DRIVER_NUM => {
+                f(self.ble_radio.map_or(None, |ble_radio| Some(ble_radio)))
+            }
+            capsules::packet_radio::DRIVER_NUM => {
+                f(self.packet_radio.map_or(None, |radio| Some(radio)))
+            }
             kernel::ipc::DRIVER_NUM => f(Some(&self.ipc)),
             _ => f(None),
         }
@@ -106,34 +176,65 @@ pub struct Pinmap {
     a7: usize,
     pwm0: usize,
     pwm1: usize,
+    ssi0_rx: usize,
+    ssi0_tx: usize,
+    ssi0_clk: usize,
+    ssi0_fss: usize,
+    labels: PinLabels,
 }
 
-unsafe fn configure_pins(pin: &Pinmap) {
-    cc26x2::gpio::PORT[pin.uart0_rx].enable_uart0_rx();
-    cc26x2::gpio::PORT[pin.uart0_tx].enable_uart0_tx();
-
-    cc26x2::gpio::PORT[pin.i2c0_scl].enable_i2c_scl();
-    cc26x2::gpio::PORT[pin.i2c0_sda].enable_i2c_sda();
-
-    cc26x2::gpio::PORT[pin.red_led].enable_gpio();
-    cc26x2::gpio::PORT[pin.green_led].enable_gpio();
+/// Names of the pins that are printed when debugging.
+pub struct PinLabels {
+    red_led: &'static str,
+    green_led: &'static str,
+    button1: &'static str,
+    button2: &'static str,
+    gpio0: &'static str,
+}
 
-    cc26x2::gpio::PORT[pin.button1].enable_gpio();
-    cc26x2::gpio::PORT[pin.button2].enable_gpio();
+/// Routes the pins to their functions. Fails if the pinmap routes a pin to
+/// two functions.
+unsafe fn configure_pins(pin: &Pinmap) -> Result<(), ioc::PinmuxError> {
+    cc26x2::gpio::PORT[pin.uart0_rx].enable_uart0_rx()?;
+    cc26x2::gpio::PORT[pin.uart0_tx].enable_uart0_tx()?;
 
-    cc26x2::gpio::PORT[pin.gpio0].enable_gpio();
+    cc26x2::gpio::PORT[pin.i2c0_scl].enable_i2c_scl()?;
+    cc26x2::gpio::PORT[pin.i2c0_sda].enable_i2c_sda()?;
 
-    cc26x2::gpio::PORT[pin.a7].enable_analog_input();
-    cc26x2::gpio::PORT[pin.a6].enable_analog_input();
-    cc26x2::gpio::PORT[pin.a5].enable_analog_input();
-    cc26x2::gpio::PORT[pin.a4].enable_analog_input();
-    cc26x2::gpio::PORT[pin.a3].enable_analog_input();
-    cc26x2::gpio::PORT[pin.a2].enable_analog_input();
-    cc26x2::gpio::PORT[pin.a1].enable_analog_input();
-    cc26x2::gpio::PORT[pin.a0].enable_analog_input();
+    // The LEDs switch rarely, so keep their edges slow and quiet
+    for &led in [pin.red_led, pin.green_led].iter() {
+        cc26x2::gpio::PORT[led].enable_gpio()?;
+        cc26x2::gpio::PORT[led].set_drive_strength(cc26x2::gpio::DriveStrength::Min);
+        cc26x2::gpio::PORT[led].set_slew_rate_reduction(true);
+    }
 
-    cc26x2::gpio::PORT[pin.pwm0].enable_pwm(pwm::Timer::GPT0A);
-    cc26x2::gpio::PORT[pin.pwm1].enable_pwm(pwm::Timer::GPT0B);
+    cc26x2::gpio::PORT[pin.button1].enable_gpio()?;
+    cc26x2::gpio::PORT[pin.button2].enable_gpio()?;
+
+    cc26x2::gpio::PORT[pin.gpio0].enable_gpio()?;
+
+    cc26x2::gpio::PORT[pin.a7].enable_analog_input()?;
+    cc26x2::gpio::PORT[pin.a6].enable_analog_input()?;
+    cc26x2::gpio::PORT[pin.a5].enable_analog_input()?;
+    cc26x2::gpio::PORT[pin.a4].enable_analog_input()?;
+    cc26x2::gpio::PORT[pin.a3].enable_analog_input()?;
+    cc26x2::gpio::PORT[pin.a2].enable_analog_input()?;
+    cc26x2::gpio::PORT[pin.a1].enable_analog_input()?;
+    cc26x2::gpio::PORT[pin.a0].enable_analog_input()?;
+
+    cc26x2::gpio::PORT[pin.pwm0].enable_pwm(pwm::Timer::GPT0A)?;
+    cc26x2::gpio::PORT[pin.pwm1].enable_pwm(pwm::Timer::GPT0B)?;
+
+    // The SPI pins of the BoosterPack headers, with the launchxl as the
+    // slave of another MCU
+    cc26x2::gpio::PORT[pin.ssi0_rx].enable_ssi0_rx()?;
+    cc26x2::gpio::PORT[pin.ssi0_tx].enable_ssi0_tx()?;
+    cc26x2::gpio::PORT[pin.ssi0_clk].enable_ssi0_slave_clk()?;
+    cc26x2::gpio::PORT[pin.ssi0_fss].enable_ssi0_slave_fss()?;
+    // The master samples the data output at the full SPI clock rate
+    cc26x2::gpio::PORT[pin.ssi0_tx].set_drive_strength(cc26x2::gpio::DriveStrength::Max);
+
+    Ok(())
 }
 
 #[no_mangle]
@@ -166,7 +267,7 @@ pub unsafe fn reset_handler() {
     // Enable the GPIO clocks
     prcm::Clock::enable_gpio();
 
-    let pinmap: &Pinmap;
+    let pinmap: &'static Pinmap;
     let chip_id = (cc26x2::rom::HAPI.get_chip_id)();
 
     if chip_id == cc1352p::CHIP_ID {
@@ -175,68 +276,18 @@ pub unsafe fn reset_handler() {
         pinmap = &cc1312r::PINMAP;
     }
 
-    configure_pins(pinmap);
+    configure_pins(pinmap).expect("conflicting pin functions");
+    // After a wake up from shutdown the pins hold their levels until now
+    aon::AON.release_io_latches();
 
-    // LEDs
-    let led_pins = static_init!(
-        [(
-            &'static kernel::hil::gpio::Pin,
-            capsules::led::ActivationMode
-        ); 2],
-        [
-            (
-                &cc26x2::gpio::PORT[pinmap.red_led],
-                capsules::led::ActivationMode::ActiveHigh
-            ), // Red
-            (
-                &cc26x2::gpio::PORT[pinmap.green_led],
-                capsules::led::ActivationMode::ActiveHigh
-            ), // Green
-        ]
-    );
-    let led = static_init!(
-        capsules::led::LED<'static>,
-        capsules::led::LED::new(led_pins)
-    );
-
-    // BUTTONS
-    let button_pins = static_init!(
-        [(&'static gpio::InterruptValuePin, capsules::button::GpioMode); 2],
-        [
-            (
-                static_init!(
-                    gpio::InterruptValueWrapper,
-                    gpio::InterruptValueWrapper::new(&cc26x2::gpio::PORT[pinmap.button1])
-                )
-                .finalize(),
-                capsules::button::GpioMode::LowWhenPressed
-            ),
-            (
-                static_init!(
-                    gpio::InterruptValueWrapper,
-                    gpio::InterruptValueWrapper::new(&cc26x2::gpio::PORT[pinmap.button2])
-                )
-                .finalize(),
-                capsules::button::GpioMode::LowWhenPressed
-            )
-        ]
-    );
-
-    let button = static_init!(
-        capsules::button::Button<'static>,
-        capsules::button::Button::new(
-            button_pins,
-            board_kernel.create_grant(&memory_allocation_capability)
-        )
-    );
-
-    for (pin, _) in button_pins.iter() {
-        pin.set_client(button);
-        pin.set_floating_state(hil::gpio::FloatingState::PullUp);
-    }
+    // The buttons of the launchpads bounce
+    let button = ButtonComponent::new(board_kernel, pinmap)
+        .with_input_hysteresis(true)
+        .finalize();
 
     // UART
     cc26x2::uart::UART0.initialize();
+    cc26x2::oscillator::OSC.add_client(&cc26x2::uart::UART0);
 
     // Create a shared UART channel for the console and for kernel debug.
     let uart_mux = static_init!(
@@ -250,6 +301,7 @@ pub unsafe fn reset_handler() {
     uart_mux.initialize();
     hil::uart::Receive::set_receive_client(&cc26x2::uart::UART0, uart_mux);
     hil::uart::Transmit::set_transmit_client(&cc26x2::uart::UART0, uart_mux);
+    uart_mux.set_receive_advanced(&cc26x2::uart::UART0);
 
     // Create a UartDevice for the console.
     let console_uart = static_init!(UartDevice, UartDevice::new(uart_mux, true));
@@ -259,13 +311,13 @@ pub unsafe fn reset_handler() {
         capsules::console::Console<'static>,
         capsules::console::Console::new(
             console_uart,
-            &mut capsules::console::WRITE_BUF,
             &mut capsules::console::READ_BUF,
             board_kernel.create_grant(&memory_allocation_capability)
         )
     );
     kernel::hil::uart::Transmit::set_transmit_client(console_uart, console);
     kernel::hil::uart::Receive::set_receive_client(console_uart, console);
+    board_kernel.set_process_state_observer(console, &process_management_capability);
 
     // Create virtual device for kernel debug.
     let debugger_uart = static_init!(UartDevice, UartDevice::new(uart_mux, false));
@@ -286,31 +338,23 @@ pub unsafe fn reset_handler() {
     );
     kernel::debug::set_debug_writer_wrapper(debug_wrapper);
 
-    cc26x2::i2c::I2C0.initialize();
-
-    let i2c_master = static_init!(
-        capsules::i2c_master::I2CMasterDriver<cc26x2::i2c::I2CMaster<'static>>,
-        capsules::i2c_master::I2CMasterDriver::new(
-            &cc26x2::i2c::I2C0,
-            &mut capsules::i2c_master::BUF,
-            board_kernel.create_grant(&memory_allocation_capability)
-        )
-    );
-
-    cc26x2::i2c::I2C0.set_client(i2c_master);
-    cc26x2::i2c::I2C0.enable();
+    // The I2C bus on the headers is shared by processes and by the kernel
+    // drivers of the sensors attached to it
+    let mux_i2c = I2CMuxComponent::new().finalize();
+    let i2c_master = I2CMasterDriverComponent::new(board_kernel, mux_i2c).finalize();
 
     // Setup for remaining GPIO pins
+    let gpio0 = static_init!(
+        gpio::InterruptValueWrapper,
+        gpio::InterruptValueWrapper::new(&cc26x2::gpio::PORT[pinmap.gpio0])
+    )
+    .finalize();
     let gpio_pins = static_init!(
         [&'static kernel::hil::gpio::InterruptValuePin; 1],
         [
             // This is the order they appear on the launchxl headers.
             // Pins 5, 8, 11, 29, 30
-            static_init!(
-                gpio::InterruptValueWrapper,
-                gpio::InterruptValueWrapper::new(&cc26x2::gpio::PORT[pinmap.gpio0])
-            )
-            .finalize()
+            gpio0
         ]
     );
     let gpio = static_init!(
@@ -325,6 +369,35 @@ pub unsafe fn reset_handler() {
         pin.set_client(gpio);
     }
 
+    // Name the pins so that debugging output shows where they are on the board
+    let pin_labels = static_init!(
+        [kernel::debug::PinLabel; 5],
+        [
+            kernel::debug::PinLabel {
+                pin: &cc26x2::gpio::PORT[pinmap.red_led],
+                label: pinmap.labels.red_led,
+            },
+            kernel::debug::PinLabel {
+                pin: &cc26x2::gpio::PORT[pinmap.green_led],
+                label: pinmap.labels.green_led,
+            },
+            kernel::debug::PinLabel {
+                pin: &cc26x2::gpio::PORT[pinmap.button1],
+                label: pinmap.labels.button1,
+            },
+            kernel::debug::PinLabel {
+                pin: &cc26x2::gpio::PORT[pinmap.button2],
+                label: pinmap.labels.button2,
+            },
+            // The GPIO driver's pin, so that the driver finds its name
+            kernel::debug::PinLabel {
+                pin: gpio0,
+                label: pinmap.labels.gpio0,
+            },
+        ]
+    );
+    kernel::debug::assign_pin_labels(pin_labels);
+
     let rtc = &cc26x2::rtc::RTC;
     rtc.start();
 
@@ -334,6 +407,8 @@ pub unsafe fn reset_handler() {
     );
     rtc.set_client(mux_alarm);
 
+    let led = LedComponent::new(mux_alarm, pinmap).finalize();
+
     let virtual_alarm1 = static_init!(
         capsules::virtual_alarm::VirtualMuxAlarm<'static, cc26x2::rtc::Rtc>,
         capsules::virtual_alarm::VirtualMuxAlarm::new(mux_alarm)
@@ -350,35 +425,68 @@ pub unsafe fn reset_handler() {
     );
     virtual_alarm1.set_client(alarm);
 
-    let entropy_to_random = static_init!(
-        capsules::rng::Entropy32ToRandom<'static>,
-        capsules::rng::Entropy32ToRandom::new(&cc26x2::trng::TRNG)
+    let rng = RngComponent::new(board_kernel).finalize();
+
+    let adc = AdcComponent::new().finalize();
+
+    let spi_slave = SpiSlaveComponent::new().finalize();
+
+    let (nonvolatile_storage, app_flash) =
+        FlashComponent::new(board_kernel, mux_alarm, virtual_alarm1).finalize();
+
+    let pwm = PwmComponent::new().finalize();
+
+    // Only the cc1352p has a 2.4 GHz radio, which BLE, the proprietary mode
+    // and IEEE 802.15.4 all run on
+    let (radio, ble_radio, packet_radio) = if chip_id != cc1352p::CHIP_ID {
+        (None, None, None)
+    } else if cfg!(feature = "ble") {
+        let ble_radio =
+            BleComponent::new(board_kernel, &cc26x2::ble_radio::RADIO, mux_alarm).finalize();
+        (None, Some(ble_radio), None)
+    } else if cfg!(feature = "prop") {
+        let packet_radio =
+            PropRadioComponent::new(board_kernel, &cc26x2::rfc::prop::RADIO).finalize();
+        (None, None, Some(packet_radio))
+    } else {
+        let (radio, _mux_mac) = RadioComponent::new(
+            board_kernel,
+            &cc26x2::ieee802154_radio::RADIO,
+            PAN_ID,
+            SRC_MAC,
+        )
+        .finalize();
+        cc26x2::ieee802154_radio::RADIO.start();
+        (Some(radio), None, None)
+    };
+
+    let cycle_counter = static_init!(
+        capsules::cycle_counter::CycleCounter<'static, cortexm4::dwt::CycleCounter>,
+        capsules::cycle_counter::CycleCounter::new(
+            &cortexm4::dwt::CYCLE_COUNTER,
+            board_kernel.create_grant(&memory_allocation_capability)
+        )
     );
-    let rng = static_init!(
-        capsules::rng::RngDriver<'static>,
-        capsules::rng::RngDriver::new(
-            entropy_to_random,
+
+    // The battery monitor measures the temperature of the die and the supply
+    // voltage, which apps can report as the battery level
+    cc26x2::aon_batmon::BATMON.enable();
+    let temp = static_init!(
+        capsules::temperature::TemperatureSensor<'static>,
+        capsules::temperature::TemperatureSensor::new(
+            &cc26x2::aon_batmon::BATMON,
             board_kernel.create_grant(&memory_allocation_capability)
         )
     );
-    cc26x2::trng::TRNG.set_client(entropy_to_random);
-    entropy_to_random.set_client(rng);
-
-    let pwm_channels = [
-        pwm::Signal::new(pwm::Timer::GPT0A),
-        pwm::Signal::new(pwm::Timer::GPT0B),
-        pwm::Signal::new(pwm::Timer::GPT1A),
-        pwm::Signal::new(pwm::Timer::GPT1B),
-        pwm::Signal::new(pwm::Timer::GPT2A),
-        pwm::Signal::new(pwm::Timer::GPT2B),
-        pwm::Signal::new(pwm::Timer::GPT3A),
-        pwm::Signal::new(pwm::Timer::GPT3B),
-    ];
-
-    // all PWM channels are enabled
-    for pwm_channel in pwm_channels.iter() {
-        pwm_channel.enable();
-    }
+    hil::sensors::TemperatureDriver::set_client(&cc26x2::aon_batmon::BATMON, temp);
+    let voltage = static_init!(
+        capsules::voltage::VoltageSensor<'static>,
+        capsules::voltage::VoltageSensor::new(
+            &cc26x2::aon_batmon::BATMON,
+            board_kernel.create_grant(&memory_allocation_capability)
+        )
+    );
+    hil::sensors::VoltageDriver::set_client(&cc26x2::aon_batmon::BATMON, voltage);
 
     let ipc = kernel::ipc::IPC::new(board_kernel, &memory_allocation_capability);
 
@@ -390,6 +498,17 @@ pub unsafe fn reset_handler() {
         alarm,
         rng,
         i2c_master,
+        adc,
+        spi_slave,
+        nonvolatile_storage,
+        app_flash,
+        cycle_counter,
+        pwm,
+        temp,
+        voltage,
+        radio,
+        ble_radio,
+        packet_radio,
         ipc,
     };
 
@@ -398,6 +517,60 @@ pub unsafe fn reset_handler() {
     extern "C" {
         /// Beginning of the ROM region containing app images.
         static _sapps: u8;
+        /// The kernel text.
+        static _stext: u8;
+        static _etext: u8;
+    }
+
+    // The profiler is printed by the process console, which shares the UART
+    // with the console, and samples with GPT3, which is not used for PWM
+    if cfg!(feature = "profiler") {
+        kernel::process_info::set_entries(&mut PROCESS_INFO, &process_management_capability);
+        kernel::profiler::enable(
+            &mut PROFILE_TRACE,
+            &mut PROFILE_PROCESSES,
+            &mut PROFILE_KERNEL,
+            &_stext as *const u8 as usize,
+            &_etext as *const u8 as usize,
+            &process_management_capability,
+        );
+
+        let process_console_uart = static_init!(UartDevice, UartDevice::new(uart_mux, true));
+        process_console_uart.setup();
+        pub struct ProcessConsoleCapability;
+        unsafe impl capabilities::ProcessManagementCapability for ProcessConsoleCapability {}
+        let process_console = static_init!(
+            capsules::process_console::ProcessConsole<'static, ProcessConsoleCapability>,
+            capsules::process_console::ProcessConsole::new(
+                process_console_uart,
+                &mut capsules::process_console::WRITE_BUF,
+                &mut capsules::process_console::READ_BUF,
+                &mut capsules::process_console::COMMAND_BUF,
+                board_kernel,
+                ProcessConsoleCapability,
+            )
+        );
+        hil::uart::Transmit::set_transmit_client(process_console_uart, process_console);
+        hil::uart::Receive::set_receive_client(process_console_uart, process_console);
+        process_console.start();
+
+        cc26x2::sampler::start(3, PROFILE_RATE);
+    }
+
+    // The RTC is the only counter that always runs, so latencies are
+    // measured in its ticks of about 15us
+    if cfg!(feature = "interrupt_latency") {
+        let uart0_latency = static_init!(
+            IrqLatency,
+            IrqLatency::new(NvicIrq::Uart0 as u32, "irq.uart0.latency_us")
+        );
+        let gpio_latency = static_init!(
+            IrqLatency,
+            IrqLatency::new(NvicIrq::Gpio as u32, "irq.gpio.latency_us")
+        );
+        kernel::interrupt_latency::set_clock(&cc26x2::rtc::RTC);
+        kernel::interrupt_latency::watch(uart0_latency);
+        kernel::interrupt_latency::watch(gpio_latency);
     }
 
     kernel::procs::load_processes(
@@ -410,5 +583,6 @@ pub unsafe fn reset_handler() {
         &process_management_capability,
     );
 
+    board_kernel.set_watchdog(&cc26x2::wdt::WDT, WATCHDOG_PERIOD_MS, &main_loop_capability);
     board_kernel.kernel_loop(&launchxl, chip, Some(&launchxl.ipc), &main_loop_capability);
 }
@@ -0,0 +1,59 @@
+use core::fmt::Write;
+use core::panic::PanicInfo;
+
+use kernel::debug;
+use kernel::hil::led;
+use kernel::hil::uart;
+use kernel::hil::uart::Configure;
+
+use crate::PROCESSES;
+
+/// Writer is used by kernel::debug to panic message to the serial port.
+pub struct Writer {
+    initialized: bool,
+}
+
+/// Global static for debug writer
+pub static mut WRITER: Writer = Writer { initialized: false };
+
+impl Writer {
+    /// Indicate that the UART has already been configured by the console.
+    pub fn set_initialized(&mut self) {
+        self.initialized = true;
+    }
+}
+
+impl Write for Writer {
+    fn write_str(&mut self, s: &str) -> ::core::fmt::Result {
+        let uart = unsafe { &crate::UART };
+
+        if !self.initialized {
+            self.initialized = true;
+
+            uart.configure(uart::Parameters {
+                baud_rate: 115200,
+                stop_bits: uart::StopBits::One,
+                parity: uart::Parity::None,
+                hw_flow_control: false,
+                width: uart::Width::Eight,
+            });
+        }
+
+        for c in s.bytes() {
+            uart.send_byte(c);
+        }
+
+        Ok(())
+    }
+}
+
+/// Panic handler.
+#[no_mangle]
+#[panic_handler]
+pub unsafe extern "C" fn panic_fmt(info: &PanicInfo) -> ! {
+    // The simulated SoC has no LEDs to signal the panic with
+    let leds: &mut [&mut led::LedHigh] = &mut [];
+    let writer = &mut WRITER;
+
+    debug::panic(leds, writer, info, &rv32i::support::nop, &PROCESSES)
+}
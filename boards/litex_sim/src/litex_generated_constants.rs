@@ -0,0 +1,26 @@
+//! Addresses and interrupt lines of the simulated SoC.
+//!
+//! LiteX assigns these when it generates the SoC, so they must match the
+//! `csr.csv` of the build that the kernel runs on. These are the values of
+//! `litex_sim --csr-data-width=32 --with-ethernet`.
+
+#![allow(dead_code)]
+
+// CSRs
+pub const CSR_BASE: usize = 0xf000_0000;
+pub const CSR_CTRL_BASE: usize = CSR_BASE + 0x0000;
+pub const CSR_ETHMAC_BASE: usize = CSR_BASE + 0x0800;
+pub const CSR_ETHPHY_BASE: usize = CSR_BASE + 0x1000;
+pub const CSR_IDENTIFIER_MEM_BASE: usize = CSR_BASE + 0x1800;
+pub const CSR_TIMER0_BASE: usize = CSR_BASE + 0x2000;
+pub const CSR_UART_BASE: usize = CSR_BASE + 0x2800;
+
+// Memory regions
+pub const MEM_ETHMAC_BASE: usize = 0xb000_0000;
+pub const ETHMAC_RX_SLOTS: usize = 2;
+pub const ETHMAC_TX_SLOTS: usize = 2;
+
+// Interrupt lines
+pub const UART_INTERRUPT: u32 = 0;
+pub const TIMER0_INTERRUPT: u32 = 1;
+pub const ETHMAC_INTERRUPT: u32 = 2;
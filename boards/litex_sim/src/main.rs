@@ -0,0 +1,306 @@
+//! Board file for a LiteX SoC simulated with Verilator
+//!
+//! - <https://github.com/enjoy-digital/litex>
+//!
+//! The simulated SoC has a VexRiscv CPU, a UART, a timer and a LiteEth
+//! Ethernet MAC. The console is on the UART, and the UDP stack runs over
+//! Ethernet, so the kernel and the network stack can be tested without any
+//! hardware.
+
+#![no_std]
+#![no_main]
+#![feature(const_fn, in_band_lifetimes)]
+#![deny(missing_docs)]
+
+use capsules::net::ethernet::EthernetIP6;
+use capsules::net::ieee802154::MacAddress;
+use capsules::net::ipv6::ip_utils::IPAddr;
+use capsules::net::ipv6::ipv6::{IP6Packet, IPPayload, TransportHeader};
+use capsules::net::ipv6::ipv6_recv::{IP6Receiver, IP6RecvStruct};
+use capsules::net::ipv6::ipv6_send::IP6Sender;
+use capsules::net::udp::udp::UDPHeader;
+use capsules::net::udp::udp_recv::UDPReceiver;
+use capsules::net::udp::udp_send::{UDPSendStruct, UDPSender};
+use capsules::virtual_alarm::{MuxAlarm, VirtualMuxAlarm};
+use capsules::virtual_uart::{MuxUart, UartDevice};
+use kernel::capabilities;
+use kernel::hil;
+use kernel::hil::ethernet::Ethernet;
+use kernel::hil::time::Freq1MHz;
+use kernel::Platform;
+use kernel::{create_capability, debug, static_init};
+
+use litex::deferred_call_tasks::DeferredCallTask;
+
+/// Support routines for debugging I/O.
+pub mod io;
+
+mod litex_generated_constants;
+
+use litex_generated_constants as socc;
+
+// Number of concurrent processes this platform supports.
+const NUM_PROCS: usize = 4;
+
+// How should the kernel respond when a process faults.
+const FAULT_RESPONSE: kernel::procs::FaultResponse = kernel::procs::FaultResponse::Panic;
+
+// RAM to be shared by all application processes.
+#[link_section = ".app_memory"]
+static mut APP_MEMORY: [u8; 0x10000] = [0; 0x10000];
+
+// Actual memory for holding the active process structures.
+static mut PROCESSES: [Option<&'static kernel::procs::ProcessType>; NUM_PROCS] =
+    [None, None, None, None];
+
+// Force the emission of the `.apps` section in the kernel elf image, which
+// `make sim` fills with an application
+#[used]
+#[link_section = ".app.hack"]
+static APP_HACK: u8 = 0;
+
+/// Dummy buffer that causes the linker to reserve enough space for the stack.
+#[no_mangle]
+#[link_section = ".stack_buffer"]
+pub static mut STACK_MEMORY: [u8; 0x2000] = [0; 0x2000];
+
+// The max size UDP message that can be sent by userland apps
+const PAYLOAD_LEN: usize = 200;
+const UDP_HDR_SIZE: usize = 8;
+static mut UDP_DGRAM: [u8; PAYLOAD_LEN - UDP_HDR_SIZE] = [0; PAYLOAD_LEN - UDP_HDR_SIZE];
+
+// LiteEth has no MAC address of its own. This one is locally administered.
+const MAC_ADDRESS: [u8; 6] = [0x02, 0x00, 0x00, 0x00, 0x00, 0x01];
+
+type Timer = litex::timer::Timer<Freq1MHz>;
+
+static mut UART: litex::uart::Uart = unsafe { litex::uart::Uart::new(socc::CSR_UART_BASE) };
+static mut TIMER0: Timer = unsafe { Timer::new(socc::CSR_TIMER0_BASE) };
+static mut LITEETH: litex::liteeth::LiteEth = unsafe {
+    litex::liteeth::LiteEth::new(
+        socc::CSR_ETHMAC_BASE,
+        socc::MEM_ETHMAC_BASE,
+        socc::ETHMAC_RX_SLOTS,
+        MAC_ADDRESS,
+    )
+};
+
+/// Dispatch of the interrupt lines and deferred calls of the simulated SoC.
+struct LiteXSimInterruptablePeripherals;
+
+impl litex::chip::InterruptService for LiteXSimInterruptablePeripherals {
+    unsafe fn service_interrupt(&self, interrupt: u32) -> bool {
+        match interrupt {
+            socc::UART_INTERRUPT => UART.handle_interrupt(),
+            socc::TIMER0_INTERRUPT => TIMER0.handle_interrupt(),
+            socc::ETHMAC_INTERRUPT => LITEETH.handle_interrupt(),
+            _ => return false,
+        }
+        true
+    }
+
+    unsafe fn service_deferred_call(&self, task: DeferredCallTask) -> bool {
+        match task {
+            DeferredCallTask::Uart => UART.handle_deferred_call(),
+        }
+        true
+    }
+}
+
+/// A structure representing this platform that holds references to all
+/// capsules for this platform.
+struct LiteXSim {
+    console: &'static capsules::console::Console<'static>,
+    alarm: &'static capsules::alarm::AlarmDriver<'static, VirtualMuxAlarm<'static, Timer>>,
+    udp_driver: &'static capsules::net::udp::UDPDriver<'static>,
+}
+
+/// Mapping of integer syscalls to objects that implement syscalls.
+impl Platform for LiteXSim {
+    fn with_driver<F, R>(&self, driver_num: usize, f: F) -> R
+    where
+        F: FnOnce(Option<&kernel::Driver>) -> R,
+    {
+        match driver_num {
+            capsules::console::DRIVER_NUM => f(Some(self.console)),
+            capsules::alarm::DRIVER_NUM => f(Some(self.alarm)),
+            capsules::net::udp::DRIVER_NUM => f(Some(self.udp_driver)),
+            _ => f(None),
+        }
+    }
+}
+
+/// Reset Handler.
+///
+/// This function is called from the arch crate after some very basic RISC-V
+/// setup.
+#[no_mangle]
+pub unsafe fn reset_handler() {
+    // Basic setup of the platform.
+    rv32i::init_memory();
+
+    let interrupt_service = static_init!(
+        LiteXSimInterruptablePeripherals,
+        LiteXSimInterruptablePeripherals
+    );
+    let chip = static_init!(
+        litex::chip::LiteXVexRiscv<LiteXSimInterruptablePeripherals>,
+        litex::chip::LiteXVexRiscv::new(interrupt_service)
+    );
+    chip.initialize();
+
+    let process_mgmt_cap = create_capability!(capabilities::ProcessManagementCapability);
+    let main_loop_cap = create_capability!(capabilities::MainLoopCapability);
+    let memory_allocation_cap = create_capability!(capabilities::MemoryAllocationCapability);
+
+    let board_kernel = static_init!(kernel::Kernel, kernel::Kernel::new(&PROCESSES));
+
+    // Create a shared UART channel for the console and for kernel debug.
+    let uart_mux = static_init!(
+        MuxUart<'static>,
+        MuxUart::new(&UART, &mut capsules::virtual_uart::RX_BUF, 115200)
+    );
+    uart_mux.initialize();
+    io::WRITER.set_initialized();
+
+    hil::uart::Transmit::set_transmit_client(&UART, uart_mux);
+    hil::uart::Receive::set_receive_client(&UART, uart_mux);
+
+    // Create a UartDevice for the console.
+    let console_uart = static_init!(UartDevice, UartDevice::new(uart_mux, true));
+    console_uart.setup();
+    let console = static_init!(
+        capsules::console::Console<'static>,
+        capsules::console::Console::new(
+            console_uart,
+            &mut capsules::console::READ_BUF,
+            board_kernel.create_grant(&memory_allocation_cap)
+        )
+    );
+    hil::uart::Transmit::set_transmit_client(console_uart, console);
+    hil::uart::Receive::set_receive_client(console_uart, console);
+    board_kernel.set_process_state_observer(console, &process_mgmt_cap);
+
+    // Create virtual device for kernel debug.
+    let debugger_uart = static_init!(UartDevice, UartDevice::new(uart_mux, false));
+    debugger_uart.setup();
+    let debugger = static_init!(
+        kernel::debug::DebugWriter,
+        kernel::debug::DebugWriter::new(
+            debugger_uart,
+            &mut kernel::debug::OUTPUT_BUF,
+            &mut kernel::debug::INTERNAL_BUF,
+        )
+    );
+    hil::uart::Transmit::set_transmit_client(debugger_uart, debugger);
+
+    let debug_wrapper = static_init!(
+        kernel::debug::DebugWriterWrapper,
+        kernel::debug::DebugWriterWrapper::new(debugger)
+    );
+    kernel::debug::set_debug_writer_wrapper(debug_wrapper);
+
+    // Create a shared virtualization mux layer on top of the timer.
+    let mux_alarm = static_init!(MuxAlarm<'static, Timer>, MuxAlarm::new(&TIMER0));
+    TIMER0.set_client(mux_alarm);
+
+    // Alarm
+    let virtual_alarm_user = static_init!(
+        VirtualMuxAlarm<'static, Timer>,
+        VirtualMuxAlarm::new(mux_alarm)
+    );
+    let alarm = static_init!(
+        capsules::alarm::AlarmDriver<'static, VirtualMuxAlarm<'static, Timer>>,
+        capsules::alarm::AlarmDriver::new(
+            virtual_alarm_user,
+            board_kernel.create_grant(&memory_allocation_cap)
+        )
+    );
+    virtual_alarm_user.set_client(alarm);
+
+    // UDP over Ethernet. The interface has the link local address derived
+    // from the MAC address.
+    let local_ip_ifaces = static_init!(
+        [IPAddr; 1],
+        [IPAddr::generate_from_mac(MacAddress::Long([
+            MAC_ADDRESS[0],
+            MAC_ADDRESS[1],
+            MAC_ADDRESS[2],
+            0xff,
+            0xfe,
+            MAC_ADDRESS[3],
+            MAC_ADDRESS[4],
+            MAC_ADDRESS[5],
+        ]))]
+    );
+
+    let ip_pyld: IPPayload = IPPayload {
+        header: TransportHeader::UDP(UDPHeader::new()),
+        payload: &mut UDP_DGRAM,
+        offset: 0,
+    };
+    let ip6_dg = static_init!(IP6Packet<'static>, IP6Packet::new(ip_pyld));
+
+    let ip_send = static_init!(
+        EthernetIP6<'static>,
+        EthernetIP6::new(&LITEETH, ip6_dg, &mut capsules::net::ethernet::TX_BUF)
+    );
+    LITEETH.set_transmit_client(ip_send);
+    LITEETH.set_receive_client(ip_send);
+    ip_send.set_addr(local_ip_ifaces[0]);
+
+    let udp_send = static_init!(
+        UDPSendStruct<'static, EthernetIP6<'static>>,
+        UDPSendStruct::new(ip_send)
+    );
+    ip_send.set_client(udp_send);
+
+    let ip_receive = static_init!(IP6RecvStruct<'static>, IP6RecvStruct::new());
+    ip_send.set_receive_client(ip_receive);
+
+    let udp_recv = static_init!(UDPReceiver<'static>, UDPReceiver::new());
+    ip_receive.set_client(udp_recv);
+
+    let udp_driver = static_init!(
+        capsules::net::udp::UDPDriver<'static>,
+        capsules::net::udp::UDPDriver::new(
+            udp_send,
+            udp_recv,
+            board_kernel.create_grant(&memory_allocation_cap),
+            local_ip_ifaces,
+            PAYLOAD_LEN
+        )
+    );
+    udp_send.set_client(udp_driver);
+    udp_recv.set_client(udp_driver);
+    LITEETH.enable();
+
+    chip.enable_all_interrupts();
+
+    let litex_sim = LiteXSim {
+        console: console,
+        alarm: alarm,
+        udp_driver: udp_driver,
+    };
+
+    debug!("Initialization complete. Entering main loop.");
+
+    extern "C" {
+        /// Beginning of the ROM region containing app images.
+        ///
+        /// This symbol is defined in the linker script.
+        static _sapps: u8;
+    }
+
+    kernel::procs::load_processes(
+        board_kernel,
+        chip,
+        &_sapps as *const u8,
+        &mut APP_MEMORY,
+        &mut PROCESSES,
+        FAULT_RESPONSE,
+        &process_mgmt_cap,
+    );
+
+    board_kernel.kernel_loop(&litex_sim, chip, None, &main_loop_cap);
+}
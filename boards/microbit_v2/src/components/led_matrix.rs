@@ -0,0 +1,145 @@
+//! Component for the LED matrix of the micro:bit v2.
+//!
+//! This provides one Component, `LedMatrixComponent`, which scans the 5x5
+//! LED matrix with an alarm and gives userspace access to each of its LEDs,
+//! with brightness, through the LED syscall driver. LEDs are numbered row by
+//! row from the top left.
+//!
+//! Usage
+//! -----
+//! ```rust
+//! let led = LedMatrixComponent::new(mux_alarm, &ROWS, &COLS).finalize();
+//! ```
+
+#![allow(dead_code)] // Components are intended to be conditionally included
+
+use capsules::led;
+use capsules::led_matrix::{LedMatrix, LedMatrixLed};
+use capsules::virtual_alarm::{MuxAlarm, VirtualMuxAlarm};
+use core::cell::Cell;
+use kernel::component::Component;
+use kernel::hil::gpio;
+use kernel::hil::led::LedColor;
+use kernel::static_init;
+use nrf5x::rtc::Rtc;
+
+type Matrix = LedMatrix<'static, VirtualMuxAlarm<'static, Rtc>>;
+
+pub struct LedMatrixComponent {
+    alarm_mux: &'static MuxAlarm<'static, Rtc>,
+    rows: &'static [&'static gpio::Pin; 5],
+    cols: &'static [&'static gpio::Pin; 5],
+}
+
+impl LedMatrixComponent {
+    pub fn new(
+        alarm_mux: &'static MuxAlarm<'static, Rtc>,
+        rows: &'static [&'static gpio::Pin; 5],
+        cols: &'static [&'static gpio::Pin; 5],
+    ) -> LedMatrixComponent {
+        LedMatrixComponent {
+            alarm_mux: alarm_mux,
+            rows: rows,
+            cols: cols,
+        }
+    }
+}
+
+impl Component for LedMatrixComponent {
+    type Output = &'static led::LED<'static, VirtualMuxAlarm<'static, Rtc>>;
+
+    unsafe fn finalize(&mut self) -> Self::Output {
+        let brightness = static_init!([Cell<u8>; 25], Default::default());
+        let matrix_alarm = static_init!(
+            VirtualMuxAlarm<'static, Rtc>,
+            VirtualMuxAlarm::new(self.alarm_mux)
+        );
+        // The rows drive the anodes of the LEDs and the columns their
+        // cathodes
+        let matrix = static_init!(
+            Matrix,
+            LedMatrix::new(
+                self.rows,
+                led::ActivationMode::ActiveHigh,
+                self.cols,
+                led::ActivationMode::ActiveLow,
+                brightness,
+                matrix_alarm
+            )
+        );
+        matrix_alarm.set_client(matrix);
+        matrix.init();
+
+        let matrix_leds = static_init!(
+            [LedMatrixLed<'static, VirtualMuxAlarm<'static, Rtc>>; 25],
+            [
+                LedMatrixLed::new(matrix, 0, 0),
+                LedMatrixLed::new(matrix, 0, 1),
+                LedMatrixLed::new(matrix, 0, 2),
+                LedMatrixLed::new(matrix, 0, 3),
+                LedMatrixLed::new(matrix, 0, 4),
+                LedMatrixLed::new(matrix, 1, 0),
+                LedMatrixLed::new(matrix, 1, 1),
+                LedMatrixLed::new(matrix, 1, 2),
+                LedMatrixLed::new(matrix, 1, 3),
+                LedMatrixLed::new(matrix, 1, 4),
+                LedMatrixLed::new(matrix, 2, 0),
+                LedMatrixLed::new(matrix, 2, 1),
+                LedMatrixLed::new(matrix, 2, 2),
+                LedMatrixLed::new(matrix, 2, 3),
+                LedMatrixLed::new(matrix, 2, 4),
+                LedMatrixLed::new(matrix, 3, 0),
+                LedMatrixLed::new(matrix, 3, 1),
+                LedMatrixLed::new(matrix, 3, 2),
+                LedMatrixLed::new(matrix, 3, 3),
+                LedMatrixLed::new(matrix, 3, 4),
+                LedMatrixLed::new(matrix, 4, 0),
+                LedMatrixLed::new(matrix, 4, 1),
+                LedMatrixLed::new(matrix, 4, 2),
+                LedMatrixLed::new(matrix, 4, 3),
+                LedMatrixLed::new(matrix, 4, 4),
+            ]
+        );
+        let leds = static_init!(
+            [led::Led<'static>; 25],
+            [
+                led::Led::dimmable(&matrix_leds[0], LedColor::Red),
+                led::Led::dimmable(&matrix_leds[1], LedColor::Red),
+                led::Led::dimmable(&matrix_leds[2], LedColor::Red),
+                led::Led::dimmable(&matrix_leds[3], LedColor::Red),
+                led::Led::dimmable(&matrix_leds[4], LedColor::Red),
+                led::Led::dimmable(&matrix_leds[5], LedColor::Red),
+                led::Led::dimmable(&matrix_leds[6], LedColor::Red),
+                led::Led::dimmable(&matrix_leds[7], LedColor::Red),
+                led::Led::dimmable(&matrix_leds[8], LedColor::Red),
+                led::Led::dimmable(&matrix_leds[9], LedColor::Red),
+                led::Led::dimmable(&matrix_leds[10], LedColor::Red),
+                led::Led::dimmable(&matrix_leds[11], LedColor::Red),
+                led::Led::dimmable(&matrix_leds[12], LedColor::Red),
+                led::Led::dimmable(&matrix_leds[13], LedColor::Red),
+                led::Led::dimmable(&matrix_leds[14], LedColor::Red),
+                led::Led::dimmable(&matrix_leds[15], LedColor::Red),
+                led::Led::dimmable(&matrix_leds[16], LedColor::Red),
+                led::Led::dimmable(&matrix_leds[17], LedColor::Red),
+                led::Led::dimmable(&matrix_leds[18], LedColor::Red),
+                led::Led::dimmable(&matrix_leds[19], LedColor::Red),
+                led::Led::dimmable(&matrix_leds[20], LedColor::Red),
+                led::Led::dimmable(&matrix_leds[21], LedColor::Red),
+                led::Led::dimmable(&matrix_leds[22], LedColor::Red),
+                led::Led::dimmable(&matrix_leds[23], LedColor::Red),
+                led::Led::dimmable(&matrix_leds[24], LedColor::Red),
+            ]
+        );
+
+        let led_virtual_alarm = static_init!(
+            VirtualMuxAlarm<'static, Rtc>,
+            VirtualMuxAlarm::new(self.alarm_mux)
+        );
+        let led = static_init!(
+            led::LED<'static, VirtualMuxAlarm<'static, Rtc>>,
+            led::LED::new(&leds[..], led_virtual_alarm)
+        );
+        led_virtual_alarm.set_client(led);
+        led
+    }
+}
@@ -0,0 +1,70 @@
+//! Component for the LSM303AGR accelerometer and magnetometer of the
+//! micro:bit v2.
+//!
+//! This provides one Component, `NineDofComponent`, which sets up the
+//! LSM303AGR on the internal I2C bus and provides a system call interface to
+//! it.
+//!
+//! Usage
+//! -----
+//! ```rust
+//! let ninedof = NineDofComponent::new(board_kernel, mux_i2c).finalize();
+//! ```
+
+#![allow(dead_code)] // Components are intended to be conditionally included
+
+use capsules::lsm303agr::{self, Lsm303agr};
+use capsules::ninedof::NineDof;
+use capsules::virtual_i2c::{I2CDevice, MuxI2C};
+use kernel::capabilities;
+use kernel::component::Component;
+use kernel::create_capability;
+use kernel::hil;
+use kernel::static_init;
+
+pub struct NineDofComponent {
+    board_kernel: &'static kernel::Kernel,
+    i2c_mux: &'static MuxI2C<'static>,
+}
+
+impl NineDofComponent {
+    pub fn new(
+        board_kernel: &'static kernel::Kernel,
+        i2c: &'static MuxI2C<'static>,
+    ) -> NineDofComponent {
+        NineDofComponent {
+            board_kernel: board_kernel,
+            i2c_mux: i2c,
+        }
+    }
+}
+
+impl Component for NineDofComponent {
+    type Output = &'static NineDof<'static>;
+
+    unsafe fn finalize(&mut self) -> Self::Output {
+        let grant_cap = create_capability!(capabilities::MemoryAllocationCapability);
+
+        let accel_i2c = static_init!(
+            I2CDevice,
+            I2CDevice::new(self.i2c_mux, lsm303agr::ACCELEROMETER_ADDRESS)
+        );
+        let mag_i2c = static_init!(
+            I2CDevice,
+            I2CDevice::new(self.i2c_mux, lsm303agr::MAGNETOMETER_ADDRESS)
+        );
+        let sensor = static_init!(
+            Lsm303agr<'static>,
+            Lsm303agr::new(accel_i2c, mag_i2c, &mut lsm303agr::BUF)
+        );
+        accel_i2c.set_client(sensor);
+        mag_i2c.set_client(sensor);
+
+        let ninedof = static_init!(
+            NineDof<'static>,
+            NineDof::new(sensor, self.board_kernel.create_grant(&grant_cap))
+        );
+        hil::sensors::NineDof::set_client(sensor, ninedof);
+        ninedof
+    }
+}
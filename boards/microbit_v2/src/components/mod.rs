@@ -0,0 +1,5 @@
+pub mod led_matrix;
+pub mod lsm303agr;
+
+pub use self::led_matrix::LedMatrixComponent;
+pub use self::lsm303agr::NineDofComponent;
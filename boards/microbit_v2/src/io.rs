@@ -0,0 +1,54 @@
+use core::fmt::Write;
+use core::panic::PanicInfo;
+use cortexm4;
+use kernel::debug;
+use kernel::hil::led;
+use kernel::hil::uart::{self, Configure};
+
+use crate::PROCESSES;
+
+struct Writer {
+    initialized: bool,
+}
+
+static mut WRITER: Writer = Writer { initialized: false };
+
+impl Write for Writer {
+    fn write_str(&mut self, s: &str) -> ::core::fmt::Result {
+        let uart = unsafe { &mut nrf52::uart::UARTE0 };
+        if !self.initialized {
+            self.initialized = true;
+            uart.configure(uart::Parameters {
+                baud_rate: 115200,
+                stop_bits: uart::StopBits::One,
+                parity: uart::Parity::None,
+                hw_flow_control: false,
+                width: uart::Width::Eight,
+            });
+        }
+        for c in s.bytes() {
+            unsafe {
+                uart.send_byte(c);
+            }
+            while !uart.tx_ready() {}
+        }
+        Ok(())
+    }
+}
+
+#[cfg(not(test))]
+#[no_mangle]
+#[panic_handler]
+/// Panic handler
+pub unsafe extern "C" fn panic_fmt(pi: &PanicInfo) -> ! {
+    // Blink the LED in the middle of the matrix, by driving its row high
+    // and blinking its column, which is active low
+    const LED_ROW3_PIN: usize = 15;
+    const LED_COL3_PIN: usize = 31;
+    let row = &mut led::LedHigh::new(&mut nrf5x::gpio::PORT[LED_ROW3_PIN]);
+    led::Led::init(row);
+    led::Led::on(row);
+    let led = &mut led::LedLow::new(&mut nrf5x::gpio::PORT[LED_COL3_PIN]);
+    let writer = &mut WRITER;
+    debug::panic(&mut [led], writer, pi, &cortexm4::support::nop, &PROCESSES)
+}
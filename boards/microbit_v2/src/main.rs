@@ -0,0 +1,470 @@
+//! Tock kernel for the BBC micro:bit v2.
+//!
+//! It is based on the nRF52833 SoC (Cortex M4 core with a BLE transceiver),
+//! with a 5x5 LED matrix, two buttons, a microphone, a speaker and an
+//! LSM303AGR accelerometer and magnetometer on board. The on-board debugger
+//! also provides a serial port over USB, which is used for the console.
+
+#![no_std]
+#![no_main]
+#![deny(missing_docs)]
+
+use capsules::virtual_alarm::VirtualMuxAlarm;
+use capsules::virtual_uart::{MuxUart, UartDevice};
+use kernel::capabilities;
+use kernel::component::Component;
+use kernel::hil;
+use kernel::hil::entropy::Entropy32;
+use kernel::hil::gpio::{Configure, Output};
+use kernel::hil::rng::Rng;
+#[allow(unused_imports)]
+use kernel::{create_capability, debug, debug_gpio, debug_verbose, static_init};
+use nrf5x::rtc::Rtc;
+
+use nrf52dk_base::nrf52_components::BLEComponent;
+
+mod components;
+use components::{LedMatrixComponent, NineDofComponent};
+
+// The rows and columns of the LED matrix
+const LED_ROW_PINS: [usize; 5] = [21, 22, 15, 24, 19];
+const LED_COL_PINS: [usize; 5] = [28, 11, 31, 37, 30]; // P1.05 is the fourth
+
+const BUTTON_A_PIN: usize = 14;
+const BUTTON_B_PIN: usize = 23;
+
+const UART_TXD: usize = 6;
+const UART_RXD: usize = 40; // P1.08
+
+// The internal I2C bus, with the LSM303AGR
+const I2C_SCL: usize = 8;
+const I2C_SDA: usize = 16;
+
+const SPEAKER_PIN: usize = 0;
+
+// The microphone is powered through its run pin
+const MIC_RUN_PIN: usize = 20;
+
+/// UART Writer
+#[macro_use]
+pub mod io;
+
+// State for loading and holding applications.
+// How should the kernel respond when a process faults.
+const FAULT_RESPONSE: kernel::procs::FaultResponse = kernel::procs::FaultResponse::Panic;
+
+// Number of concurrent processes this platform supports.
+const NUM_PROCS: usize = 4;
+
+#[link_section = ".app_memory"]
+static mut APP_MEMORY: [u8; 65536] = [0; 65536];
+
+static mut PROCESSES: [Option<&'static kernel::procs::ProcessType>; NUM_PROCS] = [None; NUM_PROCS];
+
+/// Dummy buffer that causes the linker to reserve enough space for the stack.
+#[no_mangle]
+#[link_section = ".stack_buffer"]
+pub static mut STACK_MEMORY: [u8; 0x1000] = [0; 0x1000];
+
+/// Supported drivers by the platform
+pub struct Platform {
+    ble_radio: &'static capsules::ble_advertising_driver::BLE<
+        'static,
+        nrf52::ble_radio::Radio,
+        VirtualMuxAlarm<'static, Rtc>,
+    >,
+    button: &'static capsules::button::Button<'static>,
+    console: &'static capsules::console::Console<'static>,
+    gpio: &'static capsules::gpio::GPIO<'static>,
+    led: &'static capsules::led::LED<'static, VirtualMuxAlarm<'static, Rtc>>,
+    rng: &'static capsules::rng::RngDriver<'static>,
+    temp: &'static capsules::temperature::TemperatureSensor<'static>,
+    ninedof: &'static capsules::ninedof::NineDof<'static>,
+    microphone: &'static capsules::microphone::Microphone<'static>,
+    buzzer: &'static capsules::buzzer_driver::Buzzer<'static, VirtualMuxAlarm<'static, Rtc>>,
+    ipc: kernel::ipc::IPC,
+    alarm: &'static capsules::alarm::AlarmDriver<'static, VirtualMuxAlarm<'static, Rtc>>,
+}
+
+impl kernel::Platform for Platform {
+    fn with_driver<F, R>(&self, driver_num: usize, f: F) -> R
+    where
+        F: FnOnce(Option<&kernel::Driver>) -> R,
+    {
+        match driver_num {
+            capsules::console::DRIVER_NUM => f(Some(self.console)),
+            capsules::gpio::DRIVER_NUM => f(Some(self.gpio)),
+            capsules::alarm::DRIVER_NUM => f(Some(self.alarm)),
+            capsules::led::DRIVER_NUM => f(Some(self.led)),
+            capsules::button::DRIVER_NUM => f(Some(self.button)),
+            capsules::rng::DRIVER_NUM => f(Some(self.rng)),
+            capsules::ble_advertising_driver::DRIVER_NUM => f(Some(self.ble_radio)),
+            capsules::temperature::DRIVER_NUM => f(Some(self.temp)),
+            capsules::ninedof::DRIVER_NUM => f(Some(self.ninedof)),
+            capsules::microphone::DRIVER_NUM => f(Some(self.microphone)),
+            capsules::buzzer_driver::DRIVER_NUM => f(Some(self.buzzer)),
+            kernel::ipc::DRIVER_NUM => f(Some(&self.ipc)),
+            _ => f(None),
+        }
+    }
+}
+
+/// Entry point in the vector table called on hard reset.
+#[no_mangle]
+pub unsafe fn reset_handler() {
+    // Loads relocations and clears BSS
+    nrf52::init();
+
+    // Create capabilities that the board needs to call certain protected kernel
+    // functions.
+    let process_management_capability =
+        create_capability!(capabilities::ProcessManagementCapability);
+    let main_loop_capability = create_capability!(capabilities::MainLoopCapability);
+    let memory_allocation_capability = create_capability!(capabilities::MemoryAllocationCapability);
+
+    let board_kernel = static_init!(kernel::Kernel, kernel::Kernel::new(&PROCESSES));
+
+    // GPIOs, on the large rings and the pins of the edge connector that are
+    // not used by the board
+    let gpio_pins = static_init!(
+        [&'static kernel::hil::gpio::InterruptValuePin; 8],
+        [
+            static_init!(
+                kernel::hil::gpio::InterruptValueWrapper,
+                kernel::hil::gpio::InterruptValueWrapper::new(&nrf5x::gpio::PORT[2])
+            )
+            .finalize(), // P0
+            static_init!(
+                kernel::hil::gpio::InterruptValueWrapper,
+                kernel::hil::gpio::InterruptValueWrapper::new(&nrf5x::gpio::PORT[3])
+            )
+            .finalize(), // P1
+            static_init!(
+                kernel::hil::gpio::InterruptValueWrapper,
+                kernel::hil::gpio::InterruptValueWrapper::new(&nrf5x::gpio::PORT[4])
+            )
+            .finalize(), // P2
+            static_init!(
+                kernel::hil::gpio::InterruptValueWrapper,
+                kernel::hil::gpio::InterruptValueWrapper::new(&nrf5x::gpio::PORT[12])
+            )
+            .finalize(), // P12
+            static_init!(
+                kernel::hil::gpio::InterruptValueWrapper,
+                kernel::hil::gpio::InterruptValueWrapper::new(&nrf5x::gpio::PORT[17])
+            )
+            .finalize(), // P13
+            static_init!(
+                kernel::hil::gpio::InterruptValueWrapper,
+                kernel::hil::gpio::InterruptValueWrapper::new(&nrf5x::gpio::PORT[1])
+            )
+            .finalize(), // P14
+            static_init!(
+                kernel::hil::gpio::InterruptValueWrapper,
+                kernel::hil::gpio::InterruptValueWrapper::new(&nrf5x::gpio::PORT[13])
+            )
+            .finalize(), // P15
+            static_init!(
+                kernel::hil::gpio::InterruptValueWrapper,
+                kernel::hil::gpio::InterruptValueWrapper::new(&nrf5x::gpio::PORT[34])
+            )
+            .finalize(), // P16, P1.02
+        ]
+    );
+
+    let gpio = static_init!(
+        capsules::gpio::GPIO<'static>,
+        capsules::gpio::GPIO::new(
+            gpio_pins,
+            board_kernel.create_grant(&memory_allocation_capability)
+        )
+    );
+    for pin in gpio_pins.iter() {
+        pin.set_client(gpio);
+    }
+
+    // Buttons, which have pull-up resistors on the board
+    let button_pins = static_init!(
+        [(
+            &'static kernel::hil::gpio::InterruptValuePin,
+            capsules::button::GpioMode
+        ); 2],
+        [
+            (
+                static_init!(
+                    kernel::hil::gpio::InterruptValueWrapper,
+                    kernel::hil::gpio::InterruptValueWrapper::new(&nrf5x::gpio::PORT[BUTTON_A_PIN])
+                )
+                .finalize(),
+                capsules::button::GpioMode::LowWhenPressed
+            ),
+            (
+                static_init!(
+                    kernel::hil::gpio::InterruptValueWrapper,
+                    kernel::hil::gpio::InterruptValueWrapper::new(&nrf5x::gpio::PORT[BUTTON_B_PIN])
+                )
+                .finalize(),
+                capsules::button::GpioMode::LowWhenPressed
+            ),
+        ]
+    );
+
+    let button = static_init!(
+        capsules::button::Button<'static>,
+        capsules::button::Button::new(
+            button_pins,
+            board_kernel.create_grant(&memory_allocation_capability)
+        )
+    );
+    for (pin, _) in button_pins.iter() {
+        pin.set_client(button);
+    }
+
+    let rtc = &nrf5x::rtc::RTC;
+    rtc.start();
+    let mux_alarm = static_init!(
+        capsules::virtual_alarm::MuxAlarm<'static, nrf5x::rtc::Rtc>,
+        capsules::virtual_alarm::MuxAlarm::new(&nrf5x::rtc::RTC)
+    );
+    rtc.set_client(mux_alarm);
+
+    // LEDs
+    let led_rows = static_init!(
+        [&'static kernel::hil::gpio::Pin; 5],
+        [
+            &nrf5x::gpio::PORT[LED_ROW_PINS[0]],
+            &nrf5x::gpio::PORT[LED_ROW_PINS[1]],
+            &nrf5x::gpio::PORT[LED_ROW_PINS[2]],
+            &nrf5x::gpio::PORT[LED_ROW_PINS[3]],
+            &nrf5x::gpio::PORT[LED_ROW_PINS[4]],
+        ]
+    );
+    let led_cols = static_init!(
+        [&'static kernel::hil::gpio::Pin; 5],
+        [
+            &nrf5x::gpio::PORT[LED_COL_PINS[0]],
+            &nrf5x::gpio::PORT[LED_COL_PINS[1]],
+            &nrf5x::gpio::PORT[LED_COL_PINS[2]],
+            &nrf5x::gpio::PORT[LED_COL_PINS[3]],
+            &nrf5x::gpio::PORT[LED_COL_PINS[4]],
+        ]
+    );
+    let led = LedMatrixComponent::new(mux_alarm, led_rows, led_cols).finalize();
+
+    let virtual_alarm1 = static_init!(
+        VirtualMuxAlarm<'static, Rtc>,
+        VirtualMuxAlarm::new(mux_alarm)
+    );
+    let alarm = static_init!(
+        capsules::alarm::AlarmDriver<'static, VirtualMuxAlarm<'static, Rtc>>,
+        capsules::alarm::AlarmDriver::new(
+            virtual_alarm1,
+            board_kernel.create_grant(&memory_allocation_capability)
+        )
+    );
+    virtual_alarm1.set_client(alarm);
+
+    // Create a shared UART channel for the console and for kernel debug.
+    let uart_mux = static_init!(
+        MuxUart<'static>,
+        MuxUart::new(
+            &nrf52::uart::UARTE0,
+            &mut capsules::virtual_uart::RX_BUF,
+            115200
+        )
+    );
+    uart_mux.initialize();
+    hil::uart::Transmit::set_transmit_client(&nrf52::uart::UARTE0, uart_mux);
+    hil::uart::Receive::set_receive_client(&nrf52::uart::UARTE0, uart_mux);
+
+    nrf52::uart::UARTE0.initialize(
+        nrf5x::pinmux::Pinmux::new(UART_TXD as u32),
+        nrf5x::pinmux::Pinmux::new(UART_RXD as u32),
+        None,
+        None,
+    );
+
+    // Create a UartDevice for the console.
+    let console_uart = static_init!(UartDevice, UartDevice::new(uart_mux, true));
+    console_uart.setup();
+    let console = static_init!(
+        capsules::console::Console<'static>,
+        capsules::console::Console::new(
+            console_uart,
+            &mut capsules::console::READ_BUF,
+            board_kernel.create_grant(&memory_allocation_capability)
+        )
+    );
+    hil::uart::Transmit::set_transmit_client(console_uart, console);
+    hil::uart::Receive::set_receive_client(console_uart, console);
+    board_kernel.set_process_state_observer(console, &process_management_capability);
+
+    // Create virtual device for kernel debug.
+    let debugger_uart = static_init!(UartDevice, UartDevice::new(uart_mux, false));
+    debugger_uart.setup();
+    let debugger = static_init!(
+        kernel::debug::DebugWriter,
+        kernel::debug::DebugWriter::new(
+            debugger_uart,
+            &mut kernel::debug::OUTPUT_BUF,
+            &mut kernel::debug::INTERNAL_BUF,
+        )
+    );
+    hil::uart::Transmit::set_transmit_client(debugger_uart, debugger);
+
+    let debug_wrapper = static_init!(
+        kernel::debug::DebugWriterWrapper,
+        kernel::debug::DebugWriterWrapper::new(debugger)
+    );
+    kernel::debug::set_debug_writer_wrapper(debug_wrapper);
+
+    let ble_radio = BLEComponent::new(board_kernel, &nrf52::ble_radio::RADIO, mux_alarm).finalize();
+
+    let temp = static_init!(
+        capsules::temperature::TemperatureSensor<'static>,
+        capsules::temperature::TemperatureSensor::new(
+            &mut nrf5x::temperature::TEMP,
+            board_kernel.create_grant(&memory_allocation_capability)
+        )
+    );
+    kernel::hil::sensors::TemperatureDriver::set_client(&nrf5x::temperature::TEMP, temp);
+
+    let entropy_to_random = static_init!(
+        capsules::rng::Entropy32ToRandom<'static>,
+        capsules::rng::Entropy32ToRandom::new(&nrf5x::trng::TRNG)
+    );
+    let rng = static_init!(
+        capsules::rng::RngDriver<'static>,
+        capsules::rng::RngDriver::new(
+            entropy_to_random,
+            board_kernel.create_grant(&memory_allocation_capability)
+        )
+    );
+    nrf5x::trng::TRNG.set_client(entropy_to_random);
+    entropy_to_random.set_client(rng);
+
+    //
+    // I2C Devices
+    //
+
+    // Create shared mux for the I2C bus
+    let i2c_mux = static_init!(
+        capsules::virtual_i2c::MuxI2C<'static>,
+        capsules::virtual_i2c::MuxI2C::new(&nrf52::i2c::TWIM0)
+    );
+    nrf52::i2c::TWIM0.configure(
+        nrf5x::pinmux::Pinmux::new(I2C_SCL as u32),
+        nrf5x::pinmux::Pinmux::new(I2C_SDA as u32),
+    );
+    nrf52::i2c::TWIM0.set_client(i2c_mux);
+
+    let ninedof = NineDofComponent::new(board_kernel, i2c_mux).finalize();
+
+    //
+    // Microphone
+    //
+    nrf5x::gpio::PORT[MIC_RUN_PIN].make_output();
+    nrf5x::gpio::PORT[MIC_RUN_PIN].set();
+
+    let adc_microphone = static_init!(
+        capsules::adc_microphone::AdcMicrophone<'static, nrf52::adc::Adc>,
+        capsules::adc_microphone::AdcMicrophone::new(
+            &nrf52::adc::ADC,
+            &nrf52::adc::AdcChannel::AnalogInput3,
+            &mut capsules::adc_microphone::ADC_BUFFER1,
+            &mut capsules::adc_microphone::ADC_BUFFER2
+        )
+    );
+    nrf52::adc::ADC.set_highspeed_client(adc_microphone);
+
+    let microphone = static_init!(
+        capsules::microphone::Microphone<'static>,
+        capsules::microphone::Microphone::new(
+            adc_microphone,
+            &mut capsules::microphone::BUFFER1,
+            &mut capsules::microphone::BUFFER2,
+            board_kernel.create_grant(&memory_allocation_capability)
+        )
+    );
+    hil::audio::AudioInput::set_client(adc_microphone, microphone);
+
+    //
+    // Speaker
+    //
+    let mux_pwm = static_init!(
+        capsules::virtual_pwm::MuxPwm<'static, nrf52::pwm::Pwm>,
+        capsules::virtual_pwm::MuxPwm::new(&nrf52::pwm::PWM0)
+    );
+    let virtual_pwm_buzzer = static_init!(
+        capsules::virtual_pwm::PwmPinUser<'static, nrf52::pwm::Pwm>,
+        capsules::virtual_pwm::PwmPinUser::new(
+            mux_pwm,
+            nrf5x::pinmux::Pinmux::new(SPEAKER_PIN as u32)
+        )
+    );
+    virtual_pwm_buzzer.add_to_mux();
+
+    let virtual_alarm_buzzer = static_init!(
+        VirtualMuxAlarm<'static, Rtc>,
+        VirtualMuxAlarm::new(mux_alarm)
+    );
+    let buzzer = static_init!(
+        capsules::buzzer_driver::Buzzer<'static, VirtualMuxAlarm<'static, Rtc>>,
+        capsules::buzzer_driver::Buzzer::new(
+            virtual_pwm_buzzer,
+            virtual_alarm_buzzer,
+            capsules::buzzer_driver::DEFAULT_MAX_BUZZ_TIME_MS,
+            board_kernel.create_grant(&memory_allocation_capability)
+        )
+    );
+    virtual_alarm_buzzer.set_client(buzzer);
+
+    // Start all of the clocks. The micro:bit has no 32 kHz crystal, so the
+    // low frequency clock runs from the RC oscillator. Low power operation
+    // will require a better approach than this.
+    nrf52::clock::CLOCK.low_stop();
+    nrf52::clock::CLOCK.high_stop();
+
+    nrf52::clock::CLOCK.low_set_source(nrf52::clock::LowClockSource::RC);
+    nrf52::clock::CLOCK.low_start();
+    nrf52::clock::CLOCK.high_set_source(nrf52::clock::HighClockSource::XTAL);
+    nrf52::clock::CLOCK.high_start();
+    while !nrf52::clock::CLOCK.low_started() {}
+    while !nrf52::clock::CLOCK.high_started() {}
+
+    let platform = Platform {
+        button: button,
+        ble_radio: ble_radio,
+        console: console,
+        led: led,
+        gpio: gpio,
+        rng: rng,
+        temp: temp,
+        alarm: alarm,
+        ninedof: ninedof,
+        microphone: microphone,
+        buzzer: buzzer,
+        ipc: kernel::ipc::IPC::new(board_kernel, &memory_allocation_capability),
+    };
+
+    let chip = static_init!(nrf52::chip::NRF52, nrf52::chip::NRF52::new());
+
+    debug!("Initialization complete. Entering main loop\r");
+    debug!("{}", &nrf52::ficr::FICR_INSTANCE);
+
+    extern "C" {
+        /// Beginning of the ROM region containing app images.
+        static _sapps: u8;
+    }
+    kernel::procs::load_processes(
+        board_kernel,
+        chip,
+        &_sapps as *const u8,
+        &mut APP_MEMORY,
+        &mut PROCESSES,
+        FAULT_RESPONSE,
+        &process_management_capability,
+    );
+
+    board_kernel.kernel_loop(&platform, chip, Some(&platform.ipc), &main_loop_capability);
+}
@@ -0,0 +1,32 @@
+use core::fmt::Write;
+use core::panic::PanicInfo;
+use cortexm4;
+use kernel::debug;
+use kernel::hil::led;
+
+use crate::PROCESSES;
+
+/// The dongle has no UART to the host, and its console over USB cannot be
+/// used once the kernel has panicked, so panic messages are dropped and the
+/// LED blinking is the only sign of a panic.
+struct Writer {}
+
+static mut WRITER: Writer = Writer {};
+
+impl Write for Writer {
+    fn write_str(&mut self, _s: &str) -> ::core::fmt::Result {
+        Ok(())
+    }
+}
+
+#[cfg(not(test))]
+#[no_mangle]
+#[panic_handler]
+/// Panic handler
+pub unsafe extern "C" fn panic_fmt(pi: &PanicInfo) -> ! {
+    // The red LED of LED2
+    const LED2_R_PIN: usize = 8;
+    let led = &mut led::LedLow::new(&mut nrf5x::gpio::PORT[LED2_R_PIN]);
+    let writer = &mut WRITER;
+    debug::panic(&mut [led], writer, pi, &cortexm4::support::nop, &PROCESSES)
+}
@@ -0,0 +1,429 @@
+//! Tock kernel for the Nordic Semiconductor nRF52840 Dongle (PCA10059).
+//!
+//! It is based on the nRF52840 SoC (Cortex M4 core with a BLE and 802.15.4
+//! transceiver). The dongle plugs straight into a USB port and has no
+//! USB-to-UART bridge, so the console is a virtual serial port over the
+//! nRF52840's own USB device.
+
+#![no_std]
+#![no_main]
+#![deny(missing_docs)]
+
+use capsules::virtual_alarm::VirtualMuxAlarm;
+use capsules::virtual_uart::{MuxUart, UartDevice};
+use kernel::capabilities;
+use kernel::common::dynamic_deferred_call::{DynamicDeferredCall, DynamicDeferredCallClientState};
+use kernel::component::Component;
+use kernel::hil;
+use kernel::hil::entropy::Entropy32;
+use kernel::hil::rng::Rng;
+#[allow(unused_imports)]
+use kernel::{create_capability, debug, debug_gpio, debug_verbose, static_init};
+use nrf5x::rtc::Rtc;
+
+use nrf52dk_base::nrf52_components::{BLEComponent, Ieee802154Component, UsbCdcComponent};
+
+// The nRF52840 Dongle LEDs: LED1 is green, and LED2 has red, green and blue
+// parts.
+const LED1_PIN: usize = 6;
+const LED2_R_PIN: usize = 8;
+const LED2_G_PIN: usize = 41; // P1.09
+const LED2_B_PIN: usize = 12;
+
+// The nRF52840 Dongle buttons: SW1 is a user button and SW2 resets the chip.
+const BUTTON1_PIN: usize = 38; // P1.06
+const BUTTON_RST_PIN: usize = 18;
+
+// Constants related to the configuration of the 15.4 network stack
+const SRC_MAC: u16 = 0xf00f;
+const PAN_ID: u16 = 0xABCD;
+
+/// UART Writer
+#[macro_use]
+pub mod io;
+
+// State for loading and holding applications.
+// How should the kernel respond when a process faults.
+const FAULT_RESPONSE: kernel::procs::FaultResponse = kernel::procs::FaultResponse::Panic;
+
+// Number of concurrent processes this platform supports.
+const NUM_PROCS: usize = 8;
+
+#[link_section = ".app_memory"]
+static mut APP_MEMORY: [u8; 245760] = [0; 245760];
+
+static mut PROCESSES: [Option<&'static kernel::procs::ProcessType>; NUM_PROCS] =
+    [None, None, None, None, None, None, None, None];
+
+/// Dummy buffer that causes the linker to reserve enough space for the stack.
+#[no_mangle]
+#[link_section = ".stack_buffer"]
+pub static mut STACK_MEMORY: [u8; 0x1000] = [0; 0x1000];
+
+/// Supported drivers by the platform
+pub struct Platform {
+    ble_radio: &'static capsules::ble_advertising_driver::BLE<
+        'static,
+        nrf52::ble_radio::Radio,
+        VirtualMuxAlarm<'static, Rtc>,
+    >,
+    ieee802154_radio: &'static capsules::ieee802154::RadioDriver<'static>,
+    button: &'static capsules::button::Button<'static>,
+    console: &'static capsules::console::Console<'static>,
+    gpio: &'static capsules::gpio::GPIO<'static>,
+    led: &'static capsules::led::LED<'static, VirtualMuxAlarm<'static, Rtc>>,
+    rng: &'static capsules::rng::RngDriver<'static>,
+    temp: &'static capsules::temperature::TemperatureSensor<'static>,
+    ipc: kernel::ipc::IPC,
+    alarm: &'static capsules::alarm::AlarmDriver<'static, VirtualMuxAlarm<'static, Rtc>>,
+}
+
+impl kernel::Platform for Platform {
+    fn with_driver<F, R>(&self, driver_num: usize, f: F) -> R
+    where
+        F: FnOnce(Option<&kernel::Driver>) -> R,
+    {
+        match driver_num {
+            capsules::console::DRIVER_NUM => f(Some(self.console)),
+            capsules::gpio::DRIVER_NUM => f(Some(self.gpio)),
+            capsules::alarm::DRIVER_NUM => f(Some(self.alarm)),
+            capsules::led::DRIVER_NUM => f(Some(self.led)),
+            capsules::button::DRIVER_NUM => f(Some(self.button)),
+            capsules::rng::DRIVER_NUM => f(Some(self.rng)),
+            capsules::ble_advertising_driver::DRIVER_NUM => f(Some(self.ble_radio)),
+            capsules::ieee802154::DRIVER_NUM => f(Some(self.ieee802154_radio)),
+            capsules::temperature::DRIVER_NUM => f(Some(self.temp)),
+            kernel::ipc::DRIVER_NUM => f(Some(&self.ipc)),
+            _ => f(None),
+        }
+    }
+}
+
+/// Entry point in the vector table called on hard reset.
+#[no_mangle]
+pub unsafe fn reset_handler() {
+    // Loads relocations and clears BSS
+    nrf52::init();
+
+    // Make non-volatile memory writable and activate the reset button
+    let uicr = nrf52::uicr::Uicr::new();
+    nrf52::nvmc::NVMC.erase_uicr();
+    nrf52::nvmc::NVMC.configure_writeable();
+    while !nrf52::nvmc::NVMC.is_ready() {}
+    uicr.set_psel0_reset_pin(BUTTON_RST_PIN);
+    while !nrf52::nvmc::NVMC.is_ready() {}
+    uicr.set_psel1_reset_pin(BUTTON_RST_PIN);
+
+    // Create capabilities that the board needs to call certain protected kernel
+    // functions.
+    let process_management_capability =
+        create_capability!(capabilities::ProcessManagementCapability);
+    let main_loop_capability = create_capability!(capabilities::MainLoopCapability);
+    let memory_allocation_capability = create_capability!(capabilities::MemoryAllocationCapability);
+
+    let board_kernel = static_init!(kernel::Kernel, kernel::Kernel::new(&PROCESSES));
+
+    // Configure kernel debug gpios as early as possible
+    kernel::debug::assign_gpios(
+        Some(&nrf5x::gpio::PORT[LED2_R_PIN]),
+        Some(&nrf5x::gpio::PORT[LED2_G_PIN]),
+        Some(&nrf5x::gpio::PORT[LED2_B_PIN]),
+    );
+
+    // Start all of the clocks. The USB device needs the crystal oscillator,
+    // so this is done before it is set up. Low power operation will require
+    // a better approach than this.
+    nrf52::clock::CLOCK.low_stop();
+    nrf52::clock::CLOCK.high_stop();
+
+    nrf52::clock::CLOCK.low_set_source(nrf52::clock::LowClockSource::XTAL);
+    nrf52::clock::CLOCK.low_start();
+    nrf52::clock::CLOCK.high_set_source(nrf52::clock::HighClockSource::XTAL);
+    nrf52::clock::CLOCK.high_start();
+    while !nrf52::clock::CLOCK.low_started() {}
+    while !nrf52::clock::CLOCK.high_started() {}
+
+    let dynamic_deferred_call_clients =
+        static_init!([DynamicDeferredCallClientState; 2], Default::default());
+    let dynamic_deferred_caller = static_init!(
+        DynamicDeferredCall,
+        DynamicDeferredCall::new(dynamic_deferred_call_clients)
+    );
+    DynamicDeferredCall::set_global_instance(dynamic_deferred_caller);
+
+    // GPIOs, on the pads along the edges of the dongle
+    let gpio_pins = static_init!(
+        [&'static kernel::hil::gpio::InterruptValuePin; 12],
+        [
+            static_init!(
+                kernel::hil::gpio::InterruptValueWrapper,
+                kernel::hil::gpio::InterruptValueWrapper::new(&nrf5x::gpio::PORT[2])
+            )
+            .finalize(),
+            static_init!(
+                kernel::hil::gpio::InterruptValueWrapper,
+                kernel::hil::gpio::InterruptValueWrapper::new(&nrf5x::gpio::PORT[29])
+            )
+            .finalize(),
+            static_init!(
+                kernel::hil::gpio::InterruptValueWrapper,
+                kernel::hil::gpio::InterruptValueWrapper::new(&nrf5x::gpio::PORT[31])
+            )
+            .finalize(),
+            static_init!(
+                kernel::hil::gpio::InterruptValueWrapper,
+                kernel::hil::gpio::InterruptValueWrapper::new(&nrf5x::gpio::PORT[45])
+            )
+            .finalize(), // P1.13
+            static_init!(
+                kernel::hil::gpio::InterruptValueWrapper,
+                kernel::hil::gpio::InterruptValueWrapper::new(&nrf5x::gpio::PORT[47])
+            )
+            .finalize(), // P1.15
+            static_init!(
+                kernel::hil::gpio::InterruptValueWrapper,
+                kernel::hil::gpio::InterruptValueWrapper::new(&nrf5x::gpio::PORT[42])
+            )
+            .finalize(), // P1.10
+            static_init!(
+                kernel::hil::gpio::InterruptValueWrapper,
+                kernel::hil::gpio::InterruptValueWrapper::new(&nrf5x::gpio::PORT[13])
+            )
+            .finalize(),
+            static_init!(
+                kernel::hil::gpio::InterruptValueWrapper,
+                kernel::hil::gpio::InterruptValueWrapper::new(&nrf5x::gpio::PORT[15])
+            )
+            .finalize(),
+            static_init!(
+                kernel::hil::gpio::InterruptValueWrapper,
+                kernel::hil::gpio::InterruptValueWrapper::new(&nrf5x::gpio::PORT[17])
+            )
+            .finalize(),
+            static_init!(
+                kernel::hil::gpio::InterruptValueWrapper,
+                kernel::hil::gpio::InterruptValueWrapper::new(&nrf5x::gpio::PORT[20])
+            )
+            .finalize(),
+            static_init!(
+                kernel::hil::gpio::InterruptValueWrapper,
+                kernel::hil::gpio::InterruptValueWrapper::new(&nrf5x::gpio::PORT[22])
+            )
+            .finalize(),
+            static_init!(
+                kernel::hil::gpio::InterruptValueWrapper,
+                kernel::hil::gpio::InterruptValueWrapper::new(&nrf5x::gpio::PORT[24])
+            )
+            .finalize(),
+        ]
+    );
+
+    let gpio = static_init!(
+        capsules::gpio::GPIO<'static>,
+        capsules::gpio::GPIO::new(
+            gpio_pins,
+            board_kernel.create_grant(&memory_allocation_capability)
+        )
+    );
+    for pin in gpio_pins.iter() {
+        pin.set_client(gpio);
+    }
+
+    // Buttons
+    let button_pins = static_init!(
+        [(
+            &'static kernel::hil::gpio::InterruptValuePin,
+            capsules::button::GpioMode
+        ); 1],
+        [(
+            static_init!(
+                kernel::hil::gpio::InterruptValueWrapper,
+                kernel::hil::gpio::InterruptValueWrapper::new(&nrf5x::gpio::PORT[BUTTON1_PIN])
+            )
+            .finalize(),
+            capsules::button::GpioMode::LowWhenPressed
+        )]
+    );
+    for &(btn, _) in button_pins.iter() {
+        btn.set_floating_state(kernel::hil::gpio::FloatingState::PullUp);
+    }
+
+    let button = static_init!(
+        capsules::button::Button<'static>,
+        capsules::button::Button::new(
+            button_pins,
+            board_kernel.create_grant(&memory_allocation_capability)
+        )
+    );
+    for (pin, _) in button_pins.iter() {
+        pin.set_client(button);
+    }
+
+    let rtc = &nrf5x::rtc::RTC;
+    rtc.start();
+    let mux_alarm = static_init!(
+        capsules::virtual_alarm::MuxAlarm<'static, nrf5x::rtc::Rtc>,
+        capsules::virtual_alarm::MuxAlarm::new(&nrf5x::rtc::RTC)
+    );
+    rtc.set_client(mux_alarm);
+
+    // LEDs
+    let leds = static_init!(
+        [capsules::led::Led<'static>; 4],
+        [
+            capsules::led::Led::gpio(
+                &nrf5x::gpio::PORT[LED1_PIN],
+                capsules::led::ActivationMode::ActiveLow,
+                kernel::hil::led::LedColor::Green
+            ),
+            capsules::led::Led::gpio(
+                &nrf5x::gpio::PORT[LED2_R_PIN],
+                capsules::led::ActivationMode::ActiveLow,
+                kernel::hil::led::LedColor::Red
+            ),
+            capsules::led::Led::gpio(
+                &nrf5x::gpio::PORT[LED2_G_PIN],
+                capsules::led::ActivationMode::ActiveLow,
+                kernel::hil::led::LedColor::Green
+            ),
+            capsules::led::Led::gpio(
+                &nrf5x::gpio::PORT[LED2_B_PIN],
+                capsules::led::ActivationMode::ActiveLow,
+                kernel::hil::led::LedColor::Blue
+            ),
+        ]
+    );
+    let led_virtual_alarm = static_init!(
+        VirtualMuxAlarm<'static, Rtc>,
+        VirtualMuxAlarm::new(mux_alarm)
+    );
+    let led = static_init!(
+        capsules::led::LED<'static, VirtualMuxAlarm<'static, Rtc>>,
+        capsules::led::LED::new(leds, led_virtual_alarm)
+    );
+    led_virtual_alarm.set_client(led);
+
+    let virtual_alarm1 = static_init!(
+        VirtualMuxAlarm<'static, Rtc>,
+        VirtualMuxAlarm::new(mux_alarm)
+    );
+    let alarm = static_init!(
+        capsules::alarm::AlarmDriver<'static, VirtualMuxAlarm<'static, Rtc>>,
+        capsules::alarm::AlarmDriver::new(
+            virtual_alarm1,
+            board_kernel.create_grant(&memory_allocation_capability)
+        )
+    );
+    virtual_alarm1.set_client(alarm);
+
+    // A serial port over USB, shared by the console and kernel debug output
+    let cdc = UsbCdcComponent::new(&nrf52::usbd::USBD, dynamic_deferred_caller).finalize();
+    let uart_mux = static_init!(
+        MuxUart<'static>,
+        MuxUart::new(cdc, &mut capsules::virtual_uart::RX_BUF, 115200)
+    );
+    uart_mux.initialize();
+    hil::uart::Transmit::set_transmit_client(cdc, uart_mux);
+    hil::uart::Receive::set_receive_client(cdc, uart_mux);
+
+    // Create a UartDevice for the console.
+    let console_uart = static_init!(UartDevice, UartDevice::new(uart_mux, true));
+    console_uart.setup();
+    let console = static_init!(
+        capsules::console::Console<'static>,
+        capsules::console::Console::new(
+            console_uart,
+            &mut capsules::console::READ_BUF,
+            board_kernel.create_grant(&memory_allocation_capability)
+        )
+    );
+    hil::uart::Transmit::set_transmit_client(console_uart, console);
+    hil::uart::Receive::set_receive_client(console_uart, console);
+    board_kernel.set_process_state_observer(console, &process_management_capability);
+
+    // Create virtual device for kernel debug.
+    let debugger_uart = static_init!(UartDevice, UartDevice::new(uart_mux, false));
+    debugger_uart.setup();
+    let debugger = static_init!(
+        kernel::debug::DebugWriter,
+        kernel::debug::DebugWriter::new(
+            debugger_uart,
+            &mut kernel::debug::OUTPUT_BUF,
+            &mut kernel::debug::INTERNAL_BUF,
+        )
+    );
+    hil::uart::Transmit::set_transmit_client(debugger_uart, debugger);
+
+    let debug_wrapper = static_init!(
+        kernel::debug::DebugWriterWrapper,
+        kernel::debug::DebugWriterWrapper::new(debugger)
+    );
+    kernel::debug::set_debug_writer_wrapper(debug_wrapper);
+
+    let ble_radio = BLEComponent::new(board_kernel, &nrf52::ble_radio::RADIO, mux_alarm).finalize();
+
+    let (ieee802154_radio, _) = Ieee802154Component::new(
+        board_kernel,
+        &nrf52::ieee802154_radio::RADIO,
+        PAN_ID,
+        SRC_MAC,
+    )
+    .finalize();
+
+    let temp = static_init!(
+        capsules::temperature::TemperatureSensor<'static>,
+        capsules::temperature::TemperatureSensor::new(
+            &mut nrf5x::temperature::TEMP,
+            board_kernel.create_grant(&memory_allocation_capability)
+        )
+    );
+    kernel::hil::sensors::TemperatureDriver::set_client(&nrf5x::temperature::TEMP, temp);
+
+    let entropy_to_random = static_init!(
+        capsules::rng::Entropy32ToRandom<'static>,
+        capsules::rng::Entropy32ToRandom::new(&nrf5x::trng::TRNG)
+    );
+    let rng = static_init!(
+        capsules::rng::RngDriver<'static>,
+        capsules::rng::RngDriver::new(
+            entropy_to_random,
+            board_kernel.create_grant(&memory_allocation_capability)
+        )
+    );
+    nrf5x::trng::TRNG.set_client(entropy_to_random);
+    entropy_to_random.set_client(rng);
+
+    let platform = Platform {
+        button: button,
+        ble_radio: ble_radio,
+        ieee802154_radio: ieee802154_radio,
+        console: console,
+        led: led,
+        gpio: gpio,
+        rng: rng,
+        temp: temp,
+        alarm: alarm,
+        ipc: kernel::ipc::IPC::new(board_kernel, &memory_allocation_capability),
+    };
+
+    let chip = static_init!(nrf52::chip::NRF52, nrf52::chip::NRF52::new());
+
+    debug!("Initialization complete. Entering main loop\r");
+    debug!("{}", &nrf52::ficr::FICR_INSTANCE);
+
+    extern "C" {
+        /// Beginning of the ROM region containing app images.
+        static _sapps: u8;
+    }
+    kernel::procs::load_processes(
+        board_kernel,
+        chip,
+        &_sapps as *const u8,
+        &mut APP_MEMORY,
+        &mut PROCESSES,
+        FAULT_RESPONSE,
+        &process_management_capability,
+    );
+
+    board_kernel.kernel_loop(&platform, chip, Some(&platform.ipc), &main_loop_capability);
+}
@@ -192,27 +192,28 @@ pub unsafe fn reset_handler() {
     );
 
     // LEDs
-    let led_pins = static_init!(
-        [(
-            &'static kernel::hil::gpio::Pin,
-            capsules::led::ActivationMode
-        ); 4],
+    let leds = static_init!(
+        [capsules::led::Led<'static>; 4],
         [
-            (
+            capsules::led::Led::gpio(
                 &nrf5x::gpio::PORT[LED1_PIN],
-                capsules::led::ActivationMode::ActiveLow
+                capsules::led::ActivationMode::ActiveLow,
+                kernel::hil::led::LedColor::Green
             ),
-            (
+            capsules::led::Led::gpio(
                 &nrf5x::gpio::PORT[LED2_PIN],
-                capsules::led::ActivationMode::ActiveLow
+                capsules::led::ActivationMode::ActiveLow,
+                kernel::hil::led::LedColor::Green
             ),
-            (
+            capsules::led::Led::gpio(
                 &nrf5x::gpio::PORT[LED3_PIN],
-                capsules::led::ActivationMode::ActiveLow
+                capsules::led::ActivationMode::ActiveLow,
+                kernel::hil::led::LedColor::Green
             ),
-            (
+            capsules::led::Led::gpio(
                 &nrf5x::gpio::PORT[LED4_PIN],
-                capsules::led::ActivationMode::ActiveLow
+                capsules::led::ActivationMode::ActiveLow,
+                kernel::hil::led::LedColor::Green
             ),
         ]
     );
@@ -271,7 +272,7 @@ pub unsafe fn reset_handler() {
         LED1_PIN,
         LED2_PIN,
         LED3_PIN,
-        led_pins,
+        leds,
         &UartPins::new(UART_RTS, UART_TXD, UART_CTS, UART_RXD),
         &SpiPins::new(SPI_MOSI, SPI_MISO, SPI_CLK),
         &None,
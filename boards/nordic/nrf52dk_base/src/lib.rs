@@ -83,7 +83,7 @@ pub struct Platform {
     button: &'static capsules::button::Button<'static>,
     console: &'static capsules::console::Console<'static>,
     gpio: &'static capsules::gpio::GPIO<'static>,
-    led: &'static capsules::led::LED<'static>,
+    led: &'static capsules::led::LED<'static, VirtualMuxAlarm<'static, Rtc>>,
     rng: &'static capsules::rng::RngDriver<'static>,
     temp: &'static capsules::temperature::TemperatureSensor<'static>,
     ipc: kernel::ipc::IPC,
@@ -129,10 +129,7 @@ pub unsafe fn setup_board(
     debug_pin1_index: usize,
     debug_pin2_index: usize,
     debug_pin3_index: usize,
-    led_pins: &'static mut [(
-        &'static kernel::hil::gpio::Pin,
-        capsules::led::ActivationMode,
-    )],
+    leds: &'static [capsules::led::Led<'static>],
     uart_pins: &UartPins,
     spi_pins: &SpiPins,
     mx25r6435f: &Option<SpiMX25R6435FPins>,
@@ -179,12 +176,6 @@ pub unsafe fn setup_board(
         pin.set_client(gpio);
     }
 
-    // LEDs
-    let led = static_init!(
-        capsules::led::LED<'static>,
-        capsules::led::LED::new(led_pins)
-    );
-
     // Buttons
     let button = static_init!(
         capsules::button::Button<'static>,
@@ -206,6 +197,17 @@ pub unsafe fn setup_board(
     );
     rtc.set_client(mux_alarm);
 
+    // LEDs
+    let led_virtual_alarm = static_init!(
+        capsules::virtual_alarm::VirtualMuxAlarm<'static, nrf5x::rtc::Rtc>,
+        capsules::virtual_alarm::VirtualMuxAlarm::new(mux_alarm)
+    );
+    let led = static_init!(
+        capsules::led::LED<'static, VirtualMuxAlarm<'static, Rtc>>,
+        capsules::led::LED::new(leds, led_virtual_alarm)
+    );
+    led_virtual_alarm.set_client(led);
+
     let virtual_alarm1 = static_init!(
         capsules::virtual_alarm::VirtualMuxAlarm<'static, nrf5x::rtc::Rtc>,
         capsules::virtual_alarm::VirtualMuxAlarm::new(mux_alarm)
@@ -242,20 +244,20 @@ pub unsafe fn setup_board(
     nrf52::uart::UARTE0.initialize(
         nrf5x::pinmux::Pinmux::new(uart_pins.txd as u32),
         nrf5x::pinmux::Pinmux::new(uart_pins.rxd as u32),
-        nrf5x::pinmux::Pinmux::new(uart_pins.cts as u32),
-        nrf5x::pinmux::Pinmux::new(uart_pins.rts as u32),
+        Some(nrf5x::pinmux::Pinmux::new(uart_pins.cts as u32)),
+        Some(nrf5x::pinmux::Pinmux::new(uart_pins.rts as u32)),
     );
     let console = static_init!(
         capsules::console::Console<'static>,
         capsules::console::Console::new(
             console_uart,
-            &mut capsules::console::WRITE_BUF,
             &mut capsules::console::READ_BUF,
             board_kernel.create_grant(&memory_allocation_capability)
         )
     );
     kernel::hil::uart::Transmit::set_transmit_client(console_uart, console);
     kernel::hil::uart::Receive::set_receive_client(console_uart, console);
+    board_kernel.set_process_state_observer(console, &process_management_capability);
 
     // Create virtual device for kernel debug.
     let debugger_uart = static_init!(UartDevice, UartDevice::new(uart_mux, false));
@@ -1,5 +1,7 @@
 pub mod ble;
 pub mod ieee802154;
+pub mod usb_cdc;
 
 pub use self::ble::BLEComponent;
 pub use self::ieee802154::Ieee802154Component;
+pub use self::usb_cdc::UsbCdcComponent;
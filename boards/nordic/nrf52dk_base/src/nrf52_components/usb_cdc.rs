@@ -0,0 +1,61 @@
+//! Component for a virtual serial port over the nRF52840's USB device.
+//!
+//! This provides one Component, `UsbCdcComponent`, which sets up a CDC-ACM
+//! serial port on the USBD peripheral and attaches it to the bus. The serial
+//! port implements the UART HIL, so it can be given to a `MuxUart` for the
+//! console and kernel debug output.
+//!
+//! The serial port completes its transfers from a deferred call, so the
+//! board's `DynamicDeferredCall` must have a slot free for it.
+//!
+//! Usage
+//! -----
+//! ```rust
+//! let cdc = UsbCdcComponent::new(&nrf52::usbd::USBD, dynamic_deferred_caller).finalize();
+//! ```
+
+#![allow(dead_code)] // Components are intended to be conditionally included
+
+use capsules::usb_cdc::CdcAcm;
+use kernel::common::dynamic_deferred_call::DynamicDeferredCall;
+use kernel::component::Component;
+use kernel::hil;
+use kernel::static_init;
+
+pub struct UsbCdcComponent {
+    usbd: &'static nrf52::usbd::Usbd,
+    deferred_caller: &'static DynamicDeferredCall,
+}
+
+impl UsbCdcComponent {
+    pub fn new(
+        usbd: &'static nrf52::usbd::Usbd,
+        deferred_caller: &'static DynamicDeferredCall,
+    ) -> UsbCdcComponent {
+        UsbCdcComponent {
+            usbd: usbd,
+            deferred_caller: deferred_caller,
+        }
+    }
+}
+
+impl Component for UsbCdcComponent {
+    type Output = &'static CdcAcm<'static, nrf52::usbd::Usbd>;
+
+    unsafe fn finalize(&mut self) -> Self::Output {
+        let cdc = static_init!(
+            CdcAcm<'static, nrf52::usbd::Usbd>,
+            CdcAcm::new(self.usbd, self.deferred_caller)
+        );
+        cdc.initialize_callback_handle(
+            self.deferred_caller
+                .register(cdc)
+                .expect("no deferred call slot available"),
+        );
+        self.usbd.set_client(cdc);
+
+        hil::usb::Client::enable(cdc);
+        hil::usb::Client::attach(cdc);
+        cdc
+    }
+}
@@ -50,7 +50,10 @@ const NUM_LEDS: usize = 1;
 struct NucleoF429ZI {
     console: &'static capsules::console::Console<'static>,
     ipc: kernel::ipc::IPC,
-    led: &'static capsules::led::LED<'static>,
+    led: &'static capsules::led::LED<
+        'static,
+        VirtualMuxAlarm<'static, stm32f4xx::tim2::Tim2<'static>>,
+    >,
     button: &'static capsules::button::Button<'static>,
     alarm: &'static capsules::alarm::AlarmDriver<
         'static,
@@ -240,13 +243,13 @@ pub unsafe fn reset_handler() {
         capsules::console::Console,
         capsules::console::Console::new(
             console_uart,
-            &mut capsules::console::WRITE_BUF,
             &mut capsules::console::READ_BUF,
             board_kernel.create_grant(&memory_allocation_capability)
         )
     );
     hil::uart::Transmit::set_transmit_client(console_uart, console);
     hil::uart::Receive::set_receive_client(console_uart, console);
+    board_kernel.set_process_state_observer(console, &process_management_capability);
 
     // // Setup the process inspection console
     // let process_console_uart = static_init!(UartDevice, UartDevice::new(mux_uart, true));
@@ -271,20 +274,14 @@ pub unsafe fn reset_handler() {
     // LEDs
 
     // Clock to Port A is enabled in `set_pin_primary_functions()`
-    let led_pins = static_init!(
-        [(
-            &'static kernel::hil::gpio::Pin,
-            capsules::led::ActivationMode
-        ); NUM_LEDS],
-        [(
+    let leds = static_init!(
+        [capsules::led::Led<'static>; NUM_LEDS],
+        [capsules::led::Led::gpio(
             stm32f4xx::gpio::PinId::PB07.get_pin().as_ref().unwrap(),
-            capsules::led::ActivationMode::ActiveHigh
+            capsules::led::ActivationMode::ActiveHigh,
+            hil::led::LedColor::Blue
         )]
     );
-    let led = static_init!(
-        capsules::led::LED<'static>,
-        capsules::led::LED::new(&led_pins[..])
-    );
 
     // BUTTONs
     let button_pins = static_init!(
@@ -336,6 +333,16 @@ pub unsafe fn reset_handler() {
     );
     virtual_alarm.set_client(alarm);
 
+    let led_virtual_alarm = static_init!(
+        VirtualMuxAlarm<'static, stm32f4xx::tim2::Tim2>,
+        VirtualMuxAlarm::new(mux_alarm)
+    );
+    let led = static_init!(
+        capsules::led::LED<'static, VirtualMuxAlarm<'static, stm32f4xx::tim2::Tim2>>,
+        capsules::led::LED::new(&leds[..], led_virtual_alarm)
+    );
+    led_virtual_alarm.set_client(led);
+
     let nucleo_f429zi = NucleoF429ZI {
         console: console,
         ipc: kernel::ipc::IPC::new(board_kernel, &memory_allocation_capability),
@@ -5,7 +5,9 @@ use cortexm4;
 
 use kernel::debug;
 use kernel::hil::led;
+#[cfg(not(feature = "semihosting"))]
 use kernel::hil::uart;
+#[cfg(not(feature = "semihosting"))]
 use kernel::hil::uart::Configure;
 
 use stm32f4xx;
@@ -30,6 +32,13 @@ impl Writer {
 }
 
 impl Write for Writer {
+    #[cfg(feature = "semihosting")]
+    fn write_str(&mut self, s: &str) -> ::core::fmt::Result {
+        cortexm4::semihosting::write(s.as_bytes());
+        Ok(())
+    }
+
+    #[cfg(not(feature = "semihosting"))]
     fn write_str(&mut self, s: &str) -> ::core::fmt::Result {
         let uart = unsafe { &mut stm32f4xx::usart::USART2 };
 
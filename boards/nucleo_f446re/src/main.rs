@@ -10,6 +10,8 @@
 use capsules::virtual_alarm::{MuxAlarm, VirtualMuxAlarm};
 use capsules::virtual_uart::{MuxUart, UartDevice};
 use kernel::capabilities;
+#[cfg(feature = "semihosting")]
+use kernel::common::dynamic_deferred_call::{DynamicDeferredCall, DynamicDeferredCallClientState};
 use kernel::hil;
 use kernel::hil::gpio::Configure;
 use kernel::Platform;
@@ -54,7 +56,10 @@ const NUM_BUTTONS: usize = 1;
 struct NucleoF446RE {
     console: &'static capsules::console::Console<'static>,
     ipc: kernel::ipc::IPC,
-    led: &'static capsules::led::LED<'static>,
+    led: &'static capsules::led::LED<
+        'static,
+        VirtualMuxAlarm<'static, stm32f4xx::tim2::Tim2<'static>>,
+    >,
     button: &'static capsules::button::Button<'static>,
     alarm: &'static capsules::alarm::AlarmDriver<
         'static,
@@ -190,23 +195,50 @@ pub unsafe fn reset_handler() {
 
     // UART
 
+    // The console and kernel debug output go to USART2, or, with the
+    // `semihosting` feature, to the debugger.
+    #[cfg(not(feature = "semihosting"))]
+    let uart: &'static hil::uart::Uart<'static> = {
+        stm32f4xx::usart::USART2.enable_clock();
+        &stm32f4xx::usart::USART2
+    };
+    #[cfg(feature = "semihosting")]
+    let uart: &'static hil::uart::Uart<'static> = {
+        let dynamic_deferred_call_clients =
+            static_init!([DynamicDeferredCallClientState; 1], Default::default());
+        let dynamic_deferred_caller = static_init!(
+            DynamicDeferredCall,
+            DynamicDeferredCall::new(dynamic_deferred_call_clients)
+        );
+        DynamicDeferredCall::set_global_instance(dynamic_deferred_caller);
+
+        let semihosting_uart = static_init!(
+            capsules::semihosting_uart::SemihostingUart<'static>,
+            capsules::semihosting_uart::SemihostingUart::new(
+                cortexm4::semihosting::write,
+                dynamic_deferred_caller
+            )
+        );
+        semihosting_uart.initialize_callback_handle(
+            dynamic_deferred_caller
+                .register(semihosting_uart)
+                .expect("no deferred call slot available"),
+        );
+        semihosting_uart
+    };
+
     // Create a shared UART channel for kernel debug.
-    stm32f4xx::usart::USART2.enable_clock();
     let mux_uart = static_init!(
         MuxUart<'static>,
-        MuxUart::new(
-            &stm32f4xx::usart::USART2,
-            &mut capsules::virtual_uart::RX_BUF,
-            115200
-        )
+        MuxUart::new(uart, &mut capsules::virtual_uart::RX_BUF, 115200)
     );
     mux_uart.initialize();
     // `mux_uart.initialize()` configures the underlying USART, so we need to
     // tell `send_byte()` not to configure the USART again.
     io::WRITER.set_initialized();
 
-    hil::uart::Transmit::set_transmit_client(&stm32f4xx::usart::USART2, mux_uart);
-    hil::uart::Receive::set_receive_client(&stm32f4xx::usart::USART2, mux_uart);
+    hil::uart::Transmit::set_transmit_client(uart, mux_uart);
+    hil::uart::Receive::set_receive_client(uart, mux_uart);
 
     // Create a virtual device for kernel debug.
     let debugger_uart = static_init!(UartDevice, UartDevice::new(mux_uart, false));
@@ -241,13 +273,13 @@ pub unsafe fn reset_handler() {
         capsules::console::Console,
         capsules::console::Console::new(
             console_uart,
-            &mut capsules::console::WRITE_BUF,
             &mut capsules::console::READ_BUF,
             board_kernel.create_grant(&memory_allocation_capability)
         )
     );
     hil::uart::Transmit::set_transmit_client(console_uart, console);
     hil::uart::Receive::set_receive_client(console_uart, console);
+    board_kernel.set_process_state_observer(console, &process_management_capability);
 
     // // Setup the process inspection console
     // let process_console_uart = static_init!(UartDevice, UartDevice::new(mux_uart, true));
@@ -272,20 +304,14 @@ pub unsafe fn reset_handler() {
     // LEDs
 
     // Clock to Port A is enabled in `set_pin_primary_functions()`
-    let led_pins = static_init!(
-        [(
-            &'static kernel::hil::gpio::Pin,
-            capsules::led::ActivationMode
-        ); 1],
-        [(
+    let leds = static_init!(
+        [capsules::led::Led<'static>; 1],
+        [capsules::led::Led::gpio(
             stm32f4xx::gpio::PinId::PA05.get_pin().as_ref().unwrap(),
-            capsules::led::ActivationMode::ActiveHigh
+            capsules::led::ActivationMode::ActiveHigh,
+            hil::led::LedColor::Green
         )]
     );
-    let led = static_init!(
-        capsules::led::LED<'static>,
-        capsules::led::LED::new(&led_pins[..])
-    );
 
     // BUTTONs
     let button_pins = static_init!(
@@ -337,6 +363,16 @@ pub unsafe fn reset_handler() {
     );
     virtual_alarm.set_client(alarm);
 
+    let led_virtual_alarm = static_init!(
+        VirtualMuxAlarm<'static, stm32f4xx::tim2::Tim2>,
+        VirtualMuxAlarm::new(mux_alarm)
+    );
+    let led = static_init!(
+        capsules::led::LED<'static, VirtualMuxAlarm<'static, stm32f4xx::tim2::Tim2>>,
+        capsules::led::LED::new(&leds[..], led_virtual_alarm)
+    );
+    led_virtual_alarm.set_client(led);
+
     let nucleo_f446re = NucleoF446RE {
         console: console,
         ipc: kernel::ipc::IPC::new(board_kernel, &memory_allocation_capability),
@@ -0,0 +1,61 @@
+//! Second stage bootloader.
+//!
+//! The boot ROM copies the first 256 bytes of the flash to RAM, checks the
+//! CRC32 in their last four bytes, and runs them. They must set up the SSI
+//! controller to execute code from the flash, and then jump to the vector
+//! table that follows them.
+//!
+//! This one configures plain serial reads (command 03h), which every flash
+//! chip supports. It is built from:
+//!
+//! ```text
+//!     push {lr}
+//!     ldr r3, =0x18000000         @ XIP_SSI_BASE
+//!     movs r1, #0
+//!     str r1, [r3, #0x08]         @ SSIENR: disable the SSI
+//!     movs r1, #4
+//!     str r1, [r3, #0x14]         @ BAUDR: clk_sys / 4
+//!     ldr r1, =0x001f0300
+//!     str r1, [r3, #0x00]         @ CTRLR0: 32 bit frames, EEPROM read
+//!     ldr r1, =0x03000218
+//!     ldr r0, =0x180000f4
+//!     str r1, [r0]                @ SPI_CTRLR0: command 03h, 24 bit address
+//!     movs r1, #0
+//!     str r1, [r3, #0x04]         @ CTRLR1: one data frame per read
+//!     movs r1, #1
+//!     str r1, [r3, #0x08]         @ SSIENR: enable the SSI
+//!     pop {r0}
+//!     cmp r0, #0                  @ Called by the boot ROM?
+//!     beq vector_into_flash
+//!     bx r0
+//! vector_into_flash:
+//!     ldr r0, =0x10000100
+//!     ldr r1, =0xe000ed08
+//!     str r0, [r1]                @ VTOR
+//!     ldmia r0, {r0, r1}
+//!     msr msp, r0
+//!     bx r1
+//! ```
+
+/// The assembled second stage bootloader, padded to 252 bytes and followed
+/// by its CRC32.
+#[link_section = ".flash_bootloader"]
+#[used]
+pub static FLASH_BOOTLOADER: [u8; 256] = [
+    0x00, 0xb5, 0x0c, 0x4b, 0x00, 0x21, 0x99, 0x60, 0x04, 0x21, 0x59, 0x61, 0x0a, 0x49, 0x19, 0x60,
+    0x0a, 0x49, 0x0b, 0x48, 0x01, 0x60, 0x00, 0x21, 0x59, 0x60, 0x01, 0x21, 0x99, 0x60, 0x01, 0xbc,
+    0x00, 0x28, 0x00, 0xd0, 0x00, 0x47, 0x07, 0x48, 0x07, 0x49, 0x08, 0x60, 0x03, 0xc8, 0x80, 0xf3,
+    0x08, 0x88, 0x08, 0x47, 0x00, 0x00, 0x00, 0x18, 0x00, 0x03, 0x1f, 0x00, 0x18, 0x02, 0x00, 0x03,
+    0xf4, 0x00, 0x00, 0x18, 0x00, 0x01, 0x00, 0x10, 0x08, 0xed, 0x00, 0xe0, 0x00, 0x00, 0x00, 0x00,
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x2c, 0xec, 0x21, 0x0d,
+];
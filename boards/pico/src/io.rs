@@ -0,0 +1,67 @@
+use core::fmt::Write;
+use core::panic::PanicInfo;
+
+use cortexm0;
+
+use kernel::debug;
+use kernel::hil::led;
+use kernel::hil::uart;
+use kernel::hil::uart::Configure;
+
+use crate::PROCESSES;
+
+/// Writer is used by kernel::debug to panic message to the serial port.
+pub struct Writer {
+    initialized: bool,
+}
+
+/// Global static for debug writer
+pub static mut WRITER: Writer = Writer { initialized: false };
+
+impl Writer {
+    /// Indicate that UART0 has already been configured by the console.
+    pub fn set_initialized(&mut self) {
+        self.initialized = true;
+    }
+}
+
+impl Write for Writer {
+    fn write_str(&mut self, s: &str) -> ::core::fmt::Result {
+        let uart = unsafe { &mut rp2040::uart::UART0 };
+
+        if !self.initialized {
+            self.initialized = true;
+
+            uart.configure(uart::Parameters {
+                baud_rate: 115200,
+                stop_bits: uart::StopBits::One,
+                parity: uart::Parity::None,
+                hw_flow_control: false,
+                width: uart::Width::Eight,
+            });
+        }
+
+        for c in s.bytes() {
+            uart.send_byte(c);
+        }
+
+        Ok(())
+    }
+}
+
+/// Panic handler.
+#[no_mangle]
+#[panic_handler]
+pub unsafe extern "C" fn panic_fmt(info: &PanicInfo) -> ! {
+    // The green LED is connected to GPIO25
+    let led = &mut led::LedHigh::new(&mut rp2040::gpio::PORT[25]);
+    let writer = &mut WRITER;
+
+    debug::panic(
+        &mut [led],
+        writer,
+        info,
+        &cortexm0::support::nop,
+        &PROCESSES,
+    )
+}
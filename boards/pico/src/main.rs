@@ -0,0 +1,481 @@
+//! Board file for the Raspberry Pi Pico
+//!
+//! - <https://www.raspberrypi.org/documentation/rp2040/getting-started/>
+
+#![no_std]
+#![no_main]
+#![feature(asm, core_intrinsics)]
+#![deny(missing_docs)]
+
+use capsules::virtual_alarm::{MuxAlarm, VirtualMuxAlarm};
+use capsules::virtual_spi::{MuxSpiMaster, VirtualSpiMasterDevice};
+use capsules::virtual_uart::{MuxUart, UartDevice};
+use kernel::capabilities;
+use kernel::hil;
+use kernel::hil::gpio::{Configure, FloatingState};
+use kernel::hil::spi::SpiMaster;
+use kernel::Platform;
+use kernel::{create_capability, debug, static_init};
+
+use rp2040::gpio::GpioFunction;
+use rp2040::resets::Peripheral;
+
+/// Second stage bootloader, which sets up execute-in-place from the flash.
+pub mod flash_bootloader;
+
+/// Support routines for debugging I/O.
+pub mod io;
+
+// Number of concurrent processes this platform supports.
+const NUM_PROCS: usize = 4;
+
+// Actual memory for holding the active process structures.
+static mut PROCESSES: [Option<&'static kernel::procs::ProcessType>; NUM_PROCS] =
+    [None, None, None, None];
+
+// How should the kernel respond when a process faults.
+const FAULT_RESPONSE: kernel::procs::FaultResponse = kernel::procs::FaultResponse::Panic;
+
+// RAM to be shared by all application processes.
+#[link_section = ".app_memory"]
+static mut APP_MEMORY: [u8; 131072] = [0; 131072];
+
+// Force the emission of the `.apps` segment in the kernel elf image
+// NOTE: This will cause the kernel to overwrite any existing apps when flashed!
+#[used]
+#[link_section = ".app.hack"]
+static APP_HACK: u8 = 0;
+
+/// Dummy buffer that causes the linker to reserve enough space for the stack.
+#[no_mangle]
+#[link_section = ".stack_buffer"]
+pub static mut STACK_MEMORY: [u8; 0x1000] = [0; 0x1000];
+
+static mut SPI_READ_BUF: [u8; 64] = [0; 64];
+static mut SPI_WRITE_BUF: [u8; 64] = [0; 64];
+
+/// A structure representing this platform that holds references to all
+/// capsules for this platform.
+struct Pico {
+    console: &'static capsules::console::Console<'static>,
+    ipc: kernel::ipc::IPC,
+    led: &'static capsules::led::LED<'static, VirtualMuxAlarm<'static, rp2040::timer::Timer>>,
+    alarm: &'static capsules::alarm::AlarmDriver<
+        'static,
+        VirtualMuxAlarm<'static, rp2040::timer::Timer>,
+    >,
+    gpio: &'static capsules::gpio::GPIO<'static>,
+    adc: &'static capsules::adc::Adc<'static, rp2040::adc::Adc>,
+    i2c_master: &'static capsules::i2c_master::I2CMasterDriver<rp2040::i2c::I2c>,
+    spi: &'static capsules::spi::Spi<'static, VirtualSpiMasterDevice<'static, rp2040::spi::Spi>>,
+}
+
+/// Mapping of integer syscalls to objects that implement syscalls.
+impl Platform for Pico {
+    fn with_driver<F, R>(&self, driver_num: usize, f: F) -> R
+    where
+        F: FnOnce(Option<&kernel::Driver>) -> R,
+    {
+        match driver_num {
+            capsules::console::DRIVER_NUM => f(Some(self.console)),
+            capsules::led::DRIVER_NUM => f(Some(self.led)),
+            capsules::alarm::DRIVER_NUM => f(Some(self.alarm)),
+            capsules::gpio::DRIVER_NUM => f(Some(self.gpio)),
+            capsules::adc::DRIVER_NUM => f(Some(self.adc)),
+            capsules::i2c_master::DRIVER_NUM => f(Some(self.i2c_master)),
+            capsules::spi::DRIVER_NUM => f(Some(self.spi)),
+            kernel::ipc::DRIVER_NUM => f(Some(&self.ipc)),
+            _ => f(None),
+        }
+    }
+}
+
+/// The peripherals that the board uses, which are held in reset until the
+/// clocks are set up.
+const PERIPHERALS: [Peripheral; 8] = [
+    Peripheral::Adc,
+    Peripheral::Dma,
+    Peripheral::I2c0,
+    Peripheral::IOBank0,
+    Peripheral::PadsBank0,
+    Peripheral::Spi0,
+    Peripheral::Timer,
+    Peripheral::Uart0,
+];
+
+/// Helper function called during bring-up that configures the clocks and
+/// takes the peripherals out of reset.
+unsafe fn setup_clocks() {
+    use rp2040::clocks::CLOCKS;
+    use rp2040::resets::RESETS;
+    use rp2040::watchdog::WATCHDOG;
+
+    RESETS.reset(&PERIPHERALS);
+
+    // clk_sys runs at 125 MHz from PLL_SYS, and clk_usb, clk_adc and clk_rtc
+    // from the 48 MHz PLL_USB
+    CLOCKS.init();
+
+    RESETS.unreset(&PERIPHERALS, true);
+
+    // The timer counts the microsecond ticks of the watchdog
+    WATCHDOG.start_tick();
+}
+
+/// Helper function called during bring-up that configures multiplexed I/O.
+unsafe fn set_pin_primary_functions() {
+    use rp2040::gpio::PORT;
+
+    // The green LED is connected to GPIO25. Configure it as
+    // `debug_gpio!(0, ...)`
+    PORT[25].make_output();
+    kernel::debug::assign_gpios(Some(&PORT[25]), None, None);
+
+    // GPIO0 and GPIO1 are UART0 TX and RX
+    PORT[0].set_function(GpioFunction::Uart);
+    PORT[1].set_function(GpioFunction::Uart);
+
+    // GPIO4 and GPIO5 are I2C0 SDA and SCL
+    for pin in [4, 5].iter() {
+        PORT[*pin].set_floating_state(FloatingState::PullUp);
+        PORT[*pin].set_function(GpioFunction::I2c);
+    }
+
+    // GPIO16, GPIO18 and GPIO19 are SPI0 RX, SCK and TX. GPIO17, the chip
+    // select, is driven as a GPIO by the SPI driver.
+    PORT[16].set_function(GpioFunction::Spi);
+    PORT[18].set_function(GpioFunction::Spi);
+    PORT[19].set_function(GpioFunction::Spi);
+
+    // GPIO26 to GPIO28 are ADC inputs 0 to 2. GPIO29 (ADC input 3) measures
+    // VSYS / 3.
+    for pin in 26..30 {
+        PORT[pin].deactivate_to_low_power();
+    }
+}
+
+/// Helper function for miscellaneous peripheral functions
+unsafe fn setup_peripherals() {
+    use rp2040::interrupts;
+
+    rp2040::adc::ADC.init();
+    rp2040::i2c::I2C0.init(100_000);
+
+    for interrupt in [
+        interrupts::TIMER_IRQ_0,
+        interrupts::DMA_IRQ_0,
+        interrupts::IO_IRQ_BANK0,
+        interrupts::SPI0_IRQ,
+        interrupts::UART0_IRQ,
+        interrupts::ADC_IRQ_FIFO,
+        interrupts::I2C0_IRQ,
+    ]
+    .iter()
+    {
+        cortexm0::nvic::Nvic::new(*interrupt).enable();
+    }
+}
+
+/// Reset Handler.
+///
+/// This symbol is loaded into vector table by the RP2040 chip crate.
+/// When the chip first powers on or later does a hard reset, after the core
+/// initializes all the hardware, the address of this function is loaded and
+/// execution begins here.
+#[no_mangle]
+pub unsafe fn reset_handler() {
+    rp2040::init();
+
+    setup_clocks();
+
+    set_pin_primary_functions();
+
+    setup_peripherals();
+
+    let board_kernel = static_init!(kernel::Kernel, kernel::Kernel::new(&PROCESSES));
+
+    let chip = static_init!(rp2040::chip::Rp2040, rp2040::chip::Rp2040::new());
+
+    // UART
+
+    // Create a shared UART channel for the console and for kernel debug.
+    let mux_uart = static_init!(
+        MuxUart<'static>,
+        MuxUart::new(
+            &rp2040::uart::UART0,
+            &mut capsules::virtual_uart::RX_BUF,
+            115200
+        )
+    );
+    mux_uart.initialize();
+    // `mux_uart.initialize()` configures UART0, so `io::WRITER` does not
+    // need to configure it again.
+    io::WRITER.set_initialized();
+
+    hil::uart::Transmit::set_transmit_client(&rp2040::uart::UART0, mux_uart);
+    hil::uart::Receive::set_receive_client(&rp2040::uart::UART0, mux_uart);
+
+    // Create a virtual device for kernel debug.
+    let debugger_uart = static_init!(UartDevice, UartDevice::new(mux_uart, false));
+    debugger_uart.setup();
+    let debugger = static_init!(
+        kernel::debug::DebugWriter,
+        kernel::debug::DebugWriter::new(
+            debugger_uart,
+            &mut kernel::debug::OUTPUT_BUF,
+            &mut kernel::debug::INTERNAL_BUF,
+        )
+    );
+    hil::uart::Transmit::set_transmit_client(debugger_uart, debugger);
+
+    let debug_wrapper = static_init!(
+        kernel::debug::DebugWriterWrapper,
+        kernel::debug::DebugWriterWrapper::new(debugger)
+    );
+    kernel::debug::set_debug_writer_wrapper(debug_wrapper);
+
+    // Create capabilities that the board needs to call certain protected kernel
+    // functions.
+    let memory_allocation_capability = create_capability!(capabilities::MemoryAllocationCapability);
+    let main_loop_capability = create_capability!(capabilities::MainLoopCapability);
+    let process_management_capability =
+        create_capability!(capabilities::ProcessManagementCapability);
+
+    // Create a UartDevice for console
+    let console_uart = static_init!(UartDevice, UartDevice::new(mux_uart, true));
+    console_uart.setup();
+    let console = static_init!(
+        capsules::console::Console,
+        capsules::console::Console::new(
+            console_uart,
+            &mut capsules::console::READ_BUF,
+            board_kernel.create_grant(&memory_allocation_capability)
+        )
+    );
+    hil::uart::Transmit::set_transmit_client(console_uart, console);
+    hil::uart::Receive::set_receive_client(console_uart, console);
+    board_kernel.set_process_state_observer(console, &process_management_capability);
+
+    // ALARM
+
+    let mux_alarm = static_init!(
+        MuxAlarm<'static, rp2040::timer::Timer>,
+        MuxAlarm::new(&rp2040::timer::TIMER)
+    );
+    rp2040::timer::TIMER.set_client(mux_alarm);
+
+    let virtual_alarm = static_init!(
+        VirtualMuxAlarm<'static, rp2040::timer::Timer>,
+        VirtualMuxAlarm::new(mux_alarm)
+    );
+    let alarm = static_init!(
+        capsules::alarm::AlarmDriver<'static, VirtualMuxAlarm<'static, rp2040::timer::Timer>>,
+        capsules::alarm::AlarmDriver::new(
+            virtual_alarm,
+            board_kernel.create_grant(&memory_allocation_capability)
+        )
+    );
+    virtual_alarm.set_client(alarm);
+
+    // LEDs
+
+    let leds = static_init!(
+        [capsules::led::Led<'static>; 1],
+        [capsules::led::Led::gpio(
+            &rp2040::gpio::PORT[25],
+            capsules::led::ActivationMode::ActiveHigh,
+            hil::led::LedColor::Green
+        )]
+    );
+    let led_virtual_alarm = static_init!(
+        VirtualMuxAlarm<'static, rp2040::timer::Timer>,
+        VirtualMuxAlarm::new(mux_alarm)
+    );
+    let led = static_init!(
+        capsules::led::LED<'static, VirtualMuxAlarm<'static, rp2040::timer::Timer>>,
+        capsules::led::LED::new(&leds[..], led_virtual_alarm)
+    );
+    led_virtual_alarm.set_client(led);
+
+    // GPIO
+
+    // The pins of the header that no other driver uses
+    let gpio_pins = static_init!(
+        [&'static kernel::hil::gpio::InterruptValuePin; 15],
+        [
+            static_init!(
+                kernel::hil::gpio::InterruptValueWrapper,
+                kernel::hil::gpio::InterruptValueWrapper::new(&rp2040::gpio::PORT[2])
+            )
+            .finalize(), // GP2
+            static_init!(
+                kernel::hil::gpio::InterruptValueWrapper,
+                kernel::hil::gpio::InterruptValueWrapper::new(&rp2040::gpio::PORT[3])
+            )
+            .finalize(), // GP3
+            static_init!(
+                kernel::hil::gpio::InterruptValueWrapper,
+                kernel::hil::gpio::InterruptValueWrapper::new(&rp2040::gpio::PORT[6])
+            )
+            .finalize(), // GP6
+            static_init!(
+                kernel::hil::gpio::InterruptValueWrapper,
+                kernel::hil::gpio::InterruptValueWrapper::new(&rp2040::gpio::PORT[7])
+            )
+            .finalize(), // GP7
+            static_init!(
+                kernel::hil::gpio::InterruptValueWrapper,
+                kernel::hil::gpio::InterruptValueWrapper::new(&rp2040::gpio::PORT[8])
+            )
+            .finalize(), // GP8
+            static_init!(
+                kernel::hil::gpio::InterruptValueWrapper,
+                kernel::hil::gpio::InterruptValueWrapper::new(&rp2040::gpio::PORT[9])
+            )
+            .finalize(), // GP9
+            static_init!(
+                kernel::hil::gpio::InterruptValueWrapper,
+                kernel::hil::gpio::InterruptValueWrapper::new(&rp2040::gpio::PORT[10])
+            )
+            .finalize(), // GP10
+            static_init!(
+                kernel::hil::gpio::InterruptValueWrapper,
+                kernel::hil::gpio::InterruptValueWrapper::new(&rp2040::gpio::PORT[11])
+            )
+            .finalize(), // GP11
+            static_init!(
+                kernel::hil::gpio::InterruptValueWrapper,
+                kernel::hil::gpio::InterruptValueWrapper::new(&rp2040::gpio::PORT[12])
+            )
+            .finalize(), // GP12
+            static_init!(
+                kernel::hil::gpio::InterruptValueWrapper,
+                kernel::hil::gpio::InterruptValueWrapper::new(&rp2040::gpio::PORT[13])
+            )
+            .finalize(), // GP13
+            static_init!(
+                kernel::hil::gpio::InterruptValueWrapper,
+                kernel::hil::gpio::InterruptValueWrapper::new(&rp2040::gpio::PORT[14])
+            )
+            .finalize(), // GP14
+            static_init!(
+                kernel::hil::gpio::InterruptValueWrapper,
+                kernel::hil::gpio::InterruptValueWrapper::new(&rp2040::gpio::PORT[15])
+            )
+            .finalize(), // GP15
+            static_init!(
+                kernel::hil::gpio::InterruptValueWrapper,
+                kernel::hil::gpio::InterruptValueWrapper::new(&rp2040::gpio::PORT[20])
+            )
+            .finalize(), // GP20
+            static_init!(
+                kernel::hil::gpio::InterruptValueWrapper,
+                kernel::hil::gpio::InterruptValueWrapper::new(&rp2040::gpio::PORT[21])
+            )
+            .finalize(), // GP21
+            static_init!(
+                kernel::hil::gpio::InterruptValueWrapper,
+                kernel::hil::gpio::InterruptValueWrapper::new(&rp2040::gpio::PORT[22])
+            )
+            .finalize(), // GP22
+        ]
+    );
+    let gpio = static_init!(
+        capsules::gpio::GPIO<'static>,
+        capsules::gpio::GPIO::new(
+            gpio_pins,
+            board_kernel.create_grant(&memory_allocation_capability)
+        )
+    );
+    for pin in gpio_pins.iter() {
+        pin.set_client(gpio);
+    }
+
+    // ADC
+
+    let adc_channels = static_init!(
+        [&'static rp2040::adc::Channel; 5],
+        [
+            &rp2040::adc::Channel::Channel0, // GPIO26
+            &rp2040::adc::Channel::Channel1, // GPIO27
+            &rp2040::adc::Channel::Channel2, // GPIO28
+            &rp2040::adc::Channel::Channel3, // VSYS / 3
+            &rp2040::adc::Channel::Channel4, // Temperature sensor
+        ]
+    );
+    let adc = static_init!(
+        capsules::adc::Adc<'static, rp2040::adc::Adc>,
+        capsules::adc::Adc::new(
+            &rp2040::adc::ADC,
+            adc_channels,
+            &mut capsules::adc::ADC_BUFFER1,
+            &mut capsules::adc::ADC_BUFFER2,
+            &mut capsules::adc::ADC_BUFFER3
+        )
+    );
+    rp2040::adc::ADC.set_client(adc);
+
+    // I2C
+
+    let i2c_master = static_init!(
+        capsules::i2c_master::I2CMasterDriver<rp2040::i2c::I2c>,
+        capsules::i2c_master::I2CMasterDriver::new(
+            &rp2040::i2c::I2C0,
+            &mut capsules::i2c_master::BUF,
+            board_kernel.create_grant(&memory_allocation_capability)
+        )
+    );
+    rp2040::i2c::I2C0.set_client(i2c_master);
+
+    // SPI
+
+    let mux_spi = static_init!(
+        MuxSpiMaster<'static, rp2040::spi::Spi>,
+        MuxSpiMaster::new(&rp2040::spi::SPI0)
+    );
+    rp2040::spi::SPI0.set_client(mux_spi);
+    rp2040::spi::SPI0.init();
+
+    let syscall_spi_device = static_init!(
+        VirtualSpiMasterDevice<'static, rp2040::spi::Spi>,
+        VirtualSpiMasterDevice::new(mux_spi, &rp2040::gpio::PORT[17])
+    );
+    let spi = static_init!(
+        capsules::spi::Spi<'static, VirtualSpiMasterDevice<'static, rp2040::spi::Spi>>,
+        capsules::spi::Spi::new(syscall_spi_device)
+    );
+    spi.config_buffers(&mut SPI_READ_BUF, &mut SPI_WRITE_BUF);
+    syscall_spi_device.set_client(spi);
+
+    let pico = Pico {
+        console: console,
+        ipc: kernel::ipc::IPC::new(board_kernel, &memory_allocation_capability),
+        led: led,
+        alarm: alarm,
+        gpio: gpio,
+        adc: adc,
+        i2c_master: i2c_master,
+        spi: spi,
+    };
+
+    debug!("Initialization complete. Entering main loop");
+
+    extern "C" {
+        /// Beginning of the ROM region containing app images.
+        ///
+        /// This symbol is defined in the linker script.
+        static _sapps: u8;
+    }
+
+    kernel::procs::load_processes(
+        board_kernel,
+        chip,
+        &_sapps as *const u8,
+        &mut APP_MEMORY,
+        &mut PROCESSES,
+        FAULT_RESPONSE,
+        &process_management_capability,
+    );
+
+    board_kernel.kernel_loop(&pico, chip, Some(&pico.ipc), &main_loop_capability);
+}
@@ -0,0 +1,67 @@
+use core::fmt::Write;
+use core::panic::PanicInfo;
+
+use kernel::debug;
+use kernel::hil::uart;
+use kernel::hil::uart::Configure;
+
+use qemu_rv32_virt_chip::test_finisher;
+use qemu_rv32_virt_chip::uart::UART0;
+
+use crate::PROCESSES;
+
+/// Writer is used by kernel::debug to panic message to the serial port.
+pub struct Writer {
+    initialized: bool,
+}
+
+/// Global static for debug writer
+pub static mut WRITER: Writer = Writer { initialized: false };
+
+impl Writer {
+    /// Indicate that the UART has already been configured by the console.
+    pub fn set_initialized(&mut self) {
+        self.initialized = true;
+    }
+}
+
+impl Write for Writer {
+    fn write_str(&mut self, s: &str) -> ::core::fmt::Result {
+        let uart = unsafe { &UART0 };
+
+        if !self.initialized {
+            self.initialized = true;
+
+            uart.configure(uart::Parameters {
+                baud_rate: 115200,
+                stop_bits: uart::StopBits::One,
+                parity: uart::Parity::None,
+                hw_flow_control: false,
+                width: uart::Width::Eight,
+            });
+        }
+
+        for c in s.bytes() {
+            uart.send_byte(c);
+        }
+
+        Ok(())
+    }
+}
+
+/// Panic handler.
+///
+/// There are no LEDs to blink, so once it has printed the panic, the handler
+/// stops QEMU with a failure. A test run that panics then fails right away,
+/// rather than when the script that runs it gives up.
+#[no_mangle]
+#[panic_handler]
+pub unsafe extern "C" fn panic_fmt(info: &PanicInfo) -> ! {
+    let writer = &mut WRITER;
+
+    debug::panic_begin(&rv32i::support::nop);
+    debug::panic_banner(writer, info);
+    debug::flush(writer);
+    debug::panic_process_info(&PROCESSES, writer);
+    test_finisher::exit(1)
+}
@@ -0,0 +1,249 @@
+//! Board file for the QEMU RISC-V `virt` machine
+//!
+//! - <https://github.com/qemu/qemu/blob/master/hw/riscv/virt.c>
+//!
+//! The board runs in `qemu-system-riscv32`, so that contributors can run the
+//! kernel and applications end to end without any hardware. Along with the
+//! console and the alarm, it has the test harness capsule, which collects the
+//! results of test applications and stops QEMU with an exit status that says
+//! whether every test passed.
+
+#![no_std]
+#![no_main]
+#![feature(const_fn, in_band_lifetimes)]
+#![deny(missing_docs)]
+
+use capsules::test_harness::TestExit;
+use capsules::virtual_alarm::{MuxAlarm, VirtualMuxAlarm};
+use capsules::virtual_uart::{MuxUart, UartDevice};
+use kernel::capabilities;
+use kernel::hil;
+use kernel::hil::time::Freq10MHz;
+use kernel::process_info::ProcessInfoEntry;
+use kernel::Platform;
+use kernel::{create_capability, debug, static_init};
+use rv32i::machine_timer::MachineTimer;
+
+use qemu_rv32_virt_chip::chip::MACHINETIMER;
+use qemu_rv32_virt_chip::test_finisher;
+use qemu_rv32_virt_chip::uart::UART0;
+
+/// Support routines for debugging I/O.
+pub mod io;
+
+// Number of concurrent processes this platform supports.
+const NUM_PROCS: usize = 4;
+
+// How should the kernel respond when a process faults. A fault fails the
+// tests, and the panic stops QEMU.
+const FAULT_RESPONSE: kernel::procs::FaultResponse = kernel::procs::FaultResponse::Panic;
+
+// RAM to be shared by all application processes.
+#[link_section = ".app_memory"]
+static mut APP_MEMORY: [u8; 0x10000] = [0; 0x10000];
+
+// Actual memory for holding the active process structures.
+static mut PROCESSES: [Option<&'static kernel::procs::ProcessType>; NUM_PROCS] =
+    [None, None, None, None];
+
+// Description of the processes for debuggers, see `tools/gdb/tock.py`.
+static mut PROCESS_INFO: [ProcessInfoEntry; NUM_PROCS] = [ProcessInfoEntry::EMPTY; NUM_PROCS];
+
+// Force the emission of the `.apps` section in the kernel elf image, into
+// which `make run` loads the applications
+#[used]
+#[link_section = ".app.hack"]
+static APP_HACK: u8 = 0;
+
+/// Dummy buffer that causes the linker to reserve enough space for the stack.
+#[no_mangle]
+#[link_section = ".stack_buffer"]
+pub static mut STACK_MEMORY: [u8; 0x2000] = [0; 0x2000];
+
+type Timer = MachineTimer<Freq10MHz>;
+
+/// Stops QEMU at the end of the tests, once the debug output is written out.
+struct QemuTestExit;
+
+impl TestExit for QemuTestExit {
+    fn exit(&self, code: u16) -> ! {
+        unsafe {
+            kernel::debug::flush(&mut io::WRITER);
+        }
+        test_finisher::exit(code)
+    }
+}
+
+/// A structure representing this platform that holds references to all
+/// capsules for this platform.
+struct QemuRv32Virt {
+    console: &'static capsules::console::Console<'static>,
+    alarm: &'static capsules::alarm::AlarmDriver<'static, VirtualMuxAlarm<'static, Timer>>,
+    ipc: kernel::ipc::IPC,
+    test_harness:
+        &'static capsules::test_harness::TestHarness<'static, VirtualMuxAlarm<'static, Timer>>,
+    cycle_counter:
+        &'static capsules::cycle_counter::CycleCounter<'static, rv32i::cycle_counter::CycleCounter>,
+}
+
+/// Mapping of integer syscalls to objects that implement syscalls.
+impl Platform for QemuRv32Virt {
+    fn with_driver<F, R>(&self, driver_num: usize, f: F) -> R
+    where
+        F: FnOnce(Option<&kernel::Driver>) -> R,
+    {
+        match driver_num {
+            capsules::console::DRIVER_NUM => f(Some(self.console)),
+            capsules::alarm::DRIVER_NUM => f(Some(self.alarm)),
+            kernel::ipc::DRIVER_NUM => f(Some(&self.ipc)),
+            capsules::test_harness::DRIVER_NUM => f(Some(self.test_harness)),
+            capsules::cycle_counter::DRIVER_NUM => f(Some(self.cycle_counter)),
+            _ => f(None),
+        }
+    }
+}
+
+/// Reset Handler.
+///
+/// This function is called from the arch crate after some very basic RISC-V
+/// setup.
+#[no_mangle]
+pub unsafe fn reset_handler() {
+    // Basic setup of the platform.
+    rv32i::init_memory();
+
+    let chip = static_init!(
+        qemu_rv32_virt_chip::chip::QemuRv32Virt,
+        qemu_rv32_virt_chip::chip::QemuRv32Virt::new()
+    );
+    chip.initialize();
+
+    let process_mgmt_cap = create_capability!(capabilities::ProcessManagementCapability);
+    let main_loop_cap = create_capability!(capabilities::MainLoopCapability);
+    let memory_allocation_cap = create_capability!(capabilities::MemoryAllocationCapability);
+
+    let board_kernel = static_init!(kernel::Kernel, kernel::Kernel::new(&PROCESSES));
+
+    // Create a shared UART channel for the console and for kernel debug.
+    let uart_mux = static_init!(
+        MuxUart<'static>,
+        MuxUart::new(&UART0, &mut capsules::virtual_uart::RX_BUF, 115200)
+    );
+    uart_mux.initialize();
+    io::WRITER.set_initialized();
+
+    hil::uart::Transmit::set_transmit_client(&UART0, uart_mux);
+    hil::uart::Receive::set_receive_client(&UART0, uart_mux);
+
+    // Create a UartDevice for the console.
+    let console_uart = static_init!(UartDevice, UartDevice::new(uart_mux, true));
+    console_uart.setup();
+    let console = static_init!(
+        capsules::console::Console<'static>,
+        capsules::console::Console::new(
+            console_uart,
+            &mut capsules::console::READ_BUF,
+            board_kernel.create_grant(&memory_allocation_cap)
+        )
+    );
+    hil::uart::Transmit::set_transmit_client(console_uart, console);
+    hil::uart::Receive::set_receive_client(console_uart, console);
+    board_kernel.set_process_state_observer(console, &process_mgmt_cap);
+
+    // Create virtual device for kernel debug.
+    let debugger_uart = static_init!(UartDevice, UartDevice::new(uart_mux, false));
+    debugger_uart.setup();
+    let debugger = static_init!(
+        kernel::debug::DebugWriter,
+        kernel::debug::DebugWriter::new(
+            debugger_uart,
+            &mut kernel::debug::OUTPUT_BUF,
+            &mut kernel::debug::INTERNAL_BUF,
+        )
+    );
+    hil::uart::Transmit::set_transmit_client(debugger_uart, debugger);
+
+    let debug_wrapper = static_init!(
+        kernel::debug::DebugWriterWrapper,
+        kernel::debug::DebugWriterWrapper::new(debugger)
+    );
+    kernel::debug::set_debug_writer_wrapper(debug_wrapper);
+
+    // Create a shared virtualization mux layer on top of the machine timer.
+    let mux_alarm = static_init!(MuxAlarm<'static, Timer>, MuxAlarm::new(&MACHINETIMER));
+    MACHINETIMER.set_client(mux_alarm);
+
+    // Alarm
+    let virtual_alarm_user = static_init!(
+        VirtualMuxAlarm<'static, Timer>,
+        VirtualMuxAlarm::new(mux_alarm)
+    );
+    let alarm = static_init!(
+        capsules::alarm::AlarmDriver<'static, VirtualMuxAlarm<'static, Timer>>,
+        capsules::alarm::AlarmDriver::new(
+            virtual_alarm_user,
+            board_kernel.create_grant(&memory_allocation_cap)
+        )
+    );
+    virtual_alarm_user.set_client(alarm);
+
+    // Test harness, which has an alarm of its own for its alarm test and its
+    // timeout.
+    let test_exit = static_init!(QemuTestExit, QemuTestExit);
+    let test_harness_alarm = static_init!(
+        VirtualMuxAlarm<'static, Timer>,
+        VirtualMuxAlarm::new(mux_alarm)
+    );
+    let test_harness = static_init!(
+        capsules::test_harness::TestHarness<'static, VirtualMuxAlarm<'static, Timer>>,
+        capsules::test_harness::TestHarness::new(
+            test_harness_alarm,
+            board_kernel.create_grant(&memory_allocation_cap),
+            test_exit
+        )
+    );
+    test_harness_alarm.set_client(test_harness);
+
+    let cycle_counter = static_init!(
+        capsules::cycle_counter::CycleCounter<'static, rv32i::cycle_counter::CycleCounter>,
+        capsules::cycle_counter::CycleCounter::new(
+            &rv32i::cycle_counter::CYCLE_COUNTER,
+            board_kernel.create_grant(&memory_allocation_cap)
+        )
+    );
+
+    chip.enable_all_interrupts();
+
+    let qemu_rv32_virt = QemuRv32Virt {
+        console: console,
+        alarm: alarm,
+        ipc: kernel::ipc::IPC::new(board_kernel, &memory_allocation_cap),
+        test_harness: test_harness,
+        cycle_counter: cycle_counter,
+    };
+
+    debug!("Initialization complete. Entering main loop.");
+
+    extern "C" {
+        /// Beginning of the ROM region containing app images.
+        ///
+        /// This symbol is defined in the linker script.
+        static _sapps: u8;
+    }
+
+    kernel::process_info::set_entries(&mut PROCESS_INFO, &process_mgmt_cap);
+    kernel::procs::load_processes(
+        board_kernel,
+        chip,
+        &_sapps as *const u8,
+        &mut APP_MEMORY,
+        &mut PROCESSES,
+        FAULT_RESPONSE,
+        &process_mgmt_cap,
+    );
+
+    // Every process that was loaded is expected to report to the harness.
+    test_harness.start(PROCESSES.iter().filter(|process| process.is_some()).count());
+
+    board_kernel.kernel_loop(&qemu_rv32_virt, chip, None, &main_loop_cap);
+}
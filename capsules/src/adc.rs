@@ -852,6 +852,19 @@ impl<A: hil::adc::Adc + hil::adc::AdcHighSpeed> Driver for Adc<'a, A> {
             // Stop sampling
             5 => self.stop_sampling(),
 
+            // The resolution of samples, in bits
+            101 => ReturnCode::SuccessWithValue {
+                value: self.adc.get_resolution_bits(),
+            },
+
+            // The reference voltage, in millivolts
+            102 => self
+                .adc
+                .get_voltage_reference_mv()
+                .map_or(ReturnCode::ENOSUPPORT, |reference| {
+                    ReturnCode::SuccessWithValue { value: reference }
+                }),
+
             // default
             _ => ReturnCode::ENOSUPPORT,
         }
@@ -0,0 +1,225 @@
+//! Audio capture from an analog microphone sampled by a high-speed ADC.
+//!
+//! Implements `hil::audio::AudioInput` on top of `hil::adc::AdcHighSpeed`,
+//! for microphones with an analog output (e.g. an electret microphone and
+//! preamplifier) connected to an ADC channel. The ADC fills two buffers of
+//! its own, and each full one is converted to signed 16-bit samples in one of
+//! the client's buffers before being given back to the ADC, so capture is
+//! continuous. If the client has not given a buffer back when the ADC fills
+//! one, those samples are dropped.
+//!
+//! Samples are the ADC's left-justified values with the offset removed, so
+//! half of the ADC's range reads as zero. Microphone preamplifiers bias their
+//! output to the middle of the range, so this is close to the microphone's
+//! zero level, but clients should not rely on there being no DC offset.
+//!
+//! Usage
+//! -----
+//!
+//! ```rust
+//! let adc_microphone = static_init!(
+//!     capsules::adc_microphone::AdcMicrophone<'static, sam4l::adc::Adc>,
+//!     capsules::adc_microphone::AdcMicrophone::new(
+//!         &sam4l::adc::ADC0,
+//!         &sam4l::adc::CHANNEL_AD1,
+//!         &mut capsules::adc_microphone::ADC_BUFFER1,
+//!         &mut capsules::adc_microphone::ADC_BUFFER2)
+//! );
+//! sam4l::adc::ADC0.set_client(adc_microphone);
+//! ```
+
+use core::cell::Cell;
+use kernel::common::cells::{OptionalCell, TakeCell};
+use kernel::hil;
+use kernel::hil::audio::SAMPLE_LEN;
+use kernel::ReturnCode;
+
+/// Buffers for the ADC, which bound the number of samples per buffer.
+pub static mut ADC_BUFFER1: [u16; 256] = [0; 256];
+pub static mut ADC_BUFFER2: [u16; 256] = [0; 256];
+
+/// Highest sample rate accepted, well above what speech recognition needs.
+pub const MAX_SAMPLE_RATE_HZ: u32 = 48000;
+
+pub struct AdcMicrophone<'a, A: hil::adc::AdcHighSpeed> {
+    adc: &'a A,
+    channel: &'a <A as hil::adc::Adc>::Channel,
+    client: OptionalCell<&'a hil::audio::InputClient>,
+    adc_buffer1: TakeCell<'static, [u16]>,
+    adc_buffer2: TakeCell<'static, [u16]>,
+    /// Client buffers waiting to be filled
+    buffers: [TakeCell<'static, [u8]>; 2],
+    samples: Cell<usize>,
+    running: Cell<bool>,
+}
+
+impl<A: hil::adc::AdcHighSpeed> AdcMicrophone<'a, A> {
+    pub fn new(
+        adc: &'a A,
+        channel: &'a <A as hil::adc::Adc>::Channel,
+        adc_buffer1: &'static mut [u16],
+        adc_buffer2: &'static mut [u16],
+    ) -> AdcMicrophone<'a, A> {
+        AdcMicrophone {
+            adc: adc,
+            channel: channel,
+            client: OptionalCell::empty(),
+            adc_buffer1: TakeCell::new(adc_buffer1),
+            adc_buffer2: TakeCell::new(adc_buffer2),
+            buffers: [TakeCell::empty(), TakeCell::empty()],
+            samples: Cell::new(0),
+            running: Cell::new(false),
+        }
+    }
+
+    /// Stores a buffer for the ADC that it does not need anymore.
+    fn replace_adc_buffer(&self, buffer: &'static mut [u16]) {
+        if self.adc_buffer1.is_none() {
+            self.adc_buffer1.replace(buffer);
+        } else {
+            self.adc_buffer2.replace(buffer);
+        }
+    }
+
+    /// Takes the two client buffers, if there are any.
+    fn take_buffers(&self) -> (Option<&'static mut [u8]>, Option<&'static mut [u8]>) {
+        (self.buffers[0].take(), self.buffers[1].take())
+    }
+}
+
+impl<A: hil::adc::AdcHighSpeed> hil::audio::AudioInput<'a> for AdcMicrophone<'a, A> {
+    fn set_client(&self, client: &'a hil::audio::InputClient) {
+        self.client.set(client);
+    }
+
+    fn sample_rate_supported(&self, sample_rate_hz: u32) -> bool {
+        sample_rate_hz > 0 && sample_rate_hz <= MAX_SAMPLE_RATE_HZ
+    }
+
+    fn start(
+        &self,
+        sample_rate_hz: u32,
+        buffer1: &'static mut [u8],
+        buffer2: &'static mut [u8],
+        samples: usize,
+    ) -> (
+        ReturnCode,
+        Option<&'static mut [u8]>,
+        Option<&'static mut [u8]>,
+    ) {
+        if !self.sample_rate_supported(sample_rate_hz) {
+            return (ReturnCode::EINVAL, Some(buffer1), Some(buffer2));
+        }
+        if self.running.get() {
+            return (ReturnCode::EBUSY, Some(buffer1), Some(buffer2));
+        }
+        let (adc_buffer1, adc_buffer2) = match (self.adc_buffer1.take(), self.adc_buffer2.take()) {
+            (Some(adc_buffer1), Some(adc_buffer2)) => (adc_buffer1, adc_buffer2),
+            (adc_buffer1, adc_buffer2) => {
+                adc_buffer1.map(|buffer| self.adc_buffer1.replace(buffer));
+                adc_buffer2.map(|buffer| self.adc_buffer2.replace(buffer));
+                return (ReturnCode::EBUSY, Some(buffer1), Some(buffer2));
+            }
+        };
+        if samples == 0
+            || samples > adc_buffer1.len()
+            || samples > adc_buffer2.len()
+            || samples * SAMPLE_LEN > buffer1.len()
+            || samples * SAMPLE_LEN > buffer2.len()
+        {
+            self.adc_buffer1.replace(adc_buffer1);
+            self.adc_buffer2.replace(adc_buffer2);
+            return (ReturnCode::ESIZE, Some(buffer1), Some(buffer2));
+        }
+
+        let (result, adc_buffer1, adc_buffer2) = self.adc.sample_highspeed(
+            self.channel,
+            sample_rate_hz,
+            adc_buffer1,
+            samples,
+            adc_buffer2,
+            samples,
+        );
+        adc_buffer1.map(|buffer| self.replace_adc_buffer(buffer));
+        adc_buffer2.map(|buffer| self.replace_adc_buffer(buffer));
+        if result != ReturnCode::SUCCESS {
+            return (result, Some(buffer1), Some(buffer2));
+        }
+
+        self.buffers[0].replace(buffer1);
+        self.buffers[1].replace(buffer2);
+        self.samples.set(samples);
+        self.running.set(true);
+        (ReturnCode::SUCCESS, None, None)
+    }
+
+    fn provide_buffer(&self, buffer: &'static mut [u8]) -> (ReturnCode, Option<&'static mut [u8]>) {
+        if !self.running.get() {
+            return (ReturnCode::EOFF, Some(buffer));
+        }
+        if buffer.len() < self.samples.get() * SAMPLE_LEN {
+            return (ReturnCode::ESIZE, Some(buffer));
+        }
+        match self.buffers.iter().find(|slot| slot.is_none()) {
+            Some(slot) => {
+                slot.replace(buffer);
+                (ReturnCode::SUCCESS, None)
+            }
+            None => (ReturnCode::ENOMEM, Some(buffer)),
+        }
+    }
+
+    fn stop(
+        &self,
+    ) -> (
+        ReturnCode,
+        Option<&'static mut [u8]>,
+        Option<&'static mut [u8]>,
+    ) {
+        if !self.running.get() {
+            return (ReturnCode::EOFF, None, None);
+        }
+        self.running.set(false);
+        let result = self.adc.stop_sampling();
+        let (_, adc_buffer1, adc_buffer2) = self.adc.retrieve_buffers();
+        adc_buffer1.map(|buffer| self.replace_adc_buffer(buffer));
+        adc_buffer2.map(|buffer| self.replace_adc_buffer(buffer));
+        let (buffer1, buffer2) = self.take_buffers();
+        (result, buffer1, buffer2)
+    }
+}
+
+impl<A: hil::adc::AdcHighSpeed> hil::adc::Client for AdcMicrophone<'a, A> {
+    fn sample_ready(&self, _sample: u16) {}
+}
+
+impl<A: hil::adc::AdcHighSpeed> hil::adc::HighSpeedClient for AdcMicrophone<'a, A> {
+    fn samples_ready(&self, adc_buffer: &'static mut [u16], length: usize) {
+        if !self.running.get() {
+            self.replace_adc_buffer(adc_buffer);
+            return;
+        }
+
+        let buffer = self.buffers[0].take().or_else(|| self.buffers[1].take());
+        let buffer = buffer.map(|buffer| {
+            // Removing the offset from the left-justified unsigned value
+            // gives a signed value
+            for (sample, raw) in buffer
+                .chunks_mut(SAMPLE_LEN)
+                .zip(adc_buffer[..length].iter())
+            {
+                sample.copy_from_slice(&((raw ^ 0x8000) as i16).to_le_bytes());
+            }
+            buffer
+        });
+
+        // Give the ADC its buffer back first, so that it does not run out
+        let (_, adc_buffer) = self.adc.provide_buffer(adc_buffer, self.samples.get());
+        adc_buffer.map(|adc_buffer| self.replace_adc_buffer(adc_buffer));
+
+        buffer.map(|buffer| {
+            self.client
+                .map(move |client| client.samples_ready(buffer, length))
+        });
+    }
+}
@@ -55,6 +55,7 @@ use crate::net::stream::SResult;
 use crate::net::stream::{encode_bytes, encode_u16};
 use core::cell::Cell;
 use kernel::common::cells::{OptionalCell, TakeCell};
+use kernel::common::secure::{constant_time_eq, zeroize, SecretCell};
 use kernel::hil::symmetric_encryption;
 use kernel::hil::symmetric_encryption::{
     AES128Ctr, AES128, AES128CBC, AES128_BLOCK_SIZE, AES128_KEY_SIZE, CCM_NONCE_LENGTH,
@@ -81,7 +82,7 @@ pub struct AES128CCM<'a, A: AES128<'a> + AES128Ctr + AES128CBC> {
 
     buf: TakeCell<'static, [u8]>,
     pos: Cell<(usize, usize, usize, usize)>,
-    key: Cell<[u8; AES128_KEY_SIZE]>,
+    key: SecretCell<[u8; AES128_KEY_SIZE]>,
     nonce: Cell<[u8; CCM_NONCE_LENGTH]>,
     saved_tag: Cell<[u8; AES128_BLOCK_SIZE]>,
 }
@@ -99,7 +100,7 @@ impl<A: AES128<'a> + AES128Ctr + AES128CBC> AES128CCM<'a, A> {
             encrypting: Cell::new(false),
             buf: TakeCell::empty(),
             pos: Cell::new((0, 0, 0, 0)),
-            key: Cell::new(Default::default()),
+            key: SecretCell::new(Default::default()),
             nonce: Cell::new(Default::default()),
             saved_tag: Cell::new(Default::default()),
         }
@@ -230,7 +231,10 @@ impl<A: AES128<'a> + AES128Ctr + AES128CBC> AES128CCM<'a, A> {
         if res != ReturnCode::SUCCESS {
             return res;
         }
-        let res = self.aes.set_key(&self.key.get());
+        let res = self
+            .key
+            .map(|key| self.aes.set_key(key))
+            .unwrap_or(ReturnCode::FAIL);
         if res != ReturnCode::SUCCESS {
             return res;
         }
@@ -334,10 +338,10 @@ impl<A: AES128<'a> + AES128Ctr + AES128CBC> AES128CCM<'a, A> {
                     } else {
                         // Compare the computed encrypted tag to the received
                         // encrypted tag
-                        buf[m_end..m_end + mic_len]
-                            .iter()
-                            .zip(cbuf[tag_off..tag_off + mic_len].iter())
-                            .all(|(a, b)| *a == *b)
+                        constant_time_eq(
+                            &buf[m_end..m_end + mic_len],
+                            &cbuf[tag_off..tag_off + mic_len],
+                        )
                     }
                 },
             )
@@ -371,10 +375,10 @@ impl<A: AES128<'a> + AES128Ctr + AES128CBC> AES128CCM<'a, A> {
 
                     // Compare the computed encrypted tag to the received
                     // encrypted tag
-                    buf[m_off + m_len..m_off + m_len + mic_len]
-                        .iter()
-                        .zip(cbuf[tag_off..tag_off + mic_len].iter())
-                        .all(|(a, b)| *a == *b)
+                    constant_time_eq(
+                        &buf[m_off + m_len..m_off + m_len + mic_len],
+                        &cbuf[tag_off..tag_off + mic_len],
+                    )
                 },
             )
         });
@@ -427,7 +431,8 @@ impl<A: AES128<'a> + AES128Ctr + AES128CBC> symmetric_encryption::AES128CCM<'a>
         } else {
             let mut new_key = [0u8; AES128_KEY_SIZE];
             new_key.copy_from_slice(key);
-            self.key.set(new_key);
+            self.key.replace(new_key);
+            zeroize(&mut new_key);
             ReturnCode::SUCCESS
         }
     }
@@ -1,6 +1,11 @@
 //! Provides userspace applications with a alarm API.
+//!
+//! If the underlying alarm has a 64-bit clock (`Alarm::now64`), alarms can be
+//! set further ahead than a period of its 32-bit `now()`. Otherwise they can
+//! be set up to a period of the 32-bit clock ahead.
 
 use core::cell::Cell;
+use core::cmp;
 use kernel::hil::time::{self, Alarm, Frequency};
 use kernel::{AppId, Callback, Driver, Grant, ReturnCode};
 
@@ -11,9 +16,26 @@ pub const DRIVER_NUM: usize = driver::NUM::Alarm as usize;
 #[derive(Copy, Clone, Debug)]
 enum Expiration {
     Disabled,
-    Abs(u32),
+    /// Fires `dt` tics after `reference`.
+    Relative {
+        reference: u64,
+        dt: u64,
+    },
 }
 
+impl Expiration {
+    /// The value of the 32-bit clock the alarm fires at, which identifies
+    /// it to userspace.
+    fn id(reference: u64, dt: u64) -> u32 {
+        reference.wrapping_add(dt) as u32
+    }
+}
+
+/// The furthest ahead the underlying alarm is set. `set_alarm` only takes the
+/// low 32 bits of the time, so alarms further ahead than this on a 64-bit
+/// clock fire early, and are set again for the time left.
+const MAX_ALARM_DISTANCE: u64 = 1 << 31;
+
 #[derive(Copy, Clone)]
 pub struct AlarmData {
     expiration: Expiration,
@@ -33,7 +55,6 @@ pub struct AlarmDriver<'a, A: Alarm> {
     alarm: &'a A,
     num_armed: Cell<usize>,
     app_alarm: Grant<AlarmData>,
-    prev: Cell<u32>,
 }
 
 impl<A: Alarm> AlarmDriver<'a, A> {
@@ -42,28 +63,45 @@ impl<A: Alarm> AlarmDriver<'a, A> {
             alarm: alarm,
             num_armed: Cell::new(0),
             app_alarm: grant,
-            prev: Cell::new(0),
         }
     }
 
-    fn reset_active_alarm(&self, now: u32) -> Option<u32> {
-        self.prev.set(now);
-        let mut next_alarm = u32::max_value();
-        let mut next_dist = u32::max_value();
+    /// The 64-bit clock of the underlying alarm, or its 32-bit clock if it
+    /// does not have one.
+    fn now(&self) -> u64 {
+        self.alarm
+            .now64()
+            .unwrap_or_else(|| self.alarm.now() as u64)
+    }
+
+    /// The tics from `reference` to `now`. A 64-bit clock never goes back,
+    /// so `now` is only less than `reference` when a 32-bit clock wrapped
+    /// around in between, and the difference is taken on 32 bits.
+    fn elapsed(now: u64, reference: u64) -> u64 {
+        if now >= reference {
+            now - reference
+        } else {
+            (now as u32).wrapping_sub(reference as u32) as u64
+        }
+    }
+
+    /// Set the underlying alarm for the earliest expiration, or
+    /// `MAX_ALARM_DISTANCE` from `now` if that is sooner, and return the
+    /// tics from `now` until it fires.
+    fn reset_active_alarm(&self, now: u64) -> Option<u64> {
+        let mut next_alarm = u64::max_value();
         for alarm in self.app_alarm.iter() {
             alarm.enter(|alarm, _| match alarm.expiration {
-                Expiration::Abs(exp) => {
-                    let t_dist = exp.wrapping_sub(now);
-                    if next_dist > t_dist {
-                        next_alarm = exp;
-                        next_dist = t_dist;
-                    }
+                Expiration::Relative { reference, dt } => {
+                    let left = dt.saturating_sub(Self::elapsed(now, reference));
+                    next_alarm = cmp::min(next_alarm, left);
                 }
                 Expiration::Disabled => {}
             });
         }
-        if next_alarm != u32::max_value() {
-            self.alarm.set_alarm(next_alarm);
+        if next_alarm != u64::max_value() {
+            let next_alarm = cmp::min(next_alarm, MAX_ALARM_DISTANCE);
+            self.alarm.set_alarm((now + next_alarm) as u32);
             Some(next_alarm)
         } else {
             None
@@ -99,8 +137,12 @@ impl<A: Alarm> Driver for AlarmDriver<'a, A> {
     /// - `1`: Return the clock frequency in Hz.
     /// - `2`: Read the the current clock value
     /// - `3`: Stop the alarm if it is outstanding
-    /// - `4`: Set an alarm to fire at a given clock value `time`.
-    fn command(&self, cmd_type: usize, data: usize, _: usize, caller_id: AppId) -> ReturnCode {
+    /// - `4`: Set an alarm to fire at a given clock value `time`, within a
+    ///        period of the clock.
+    /// - `5`: Set an alarm to fire `data | data2 << 32` tics from now. The
+    ///        returned value is the clock value it fires at, which identifies
+    ///        it to command `3`. Without a 64-bit clock, `data2` must be 0.
+    fn command(&self, cmd_type: usize, data: usize, data2: usize, caller_id: AppId) -> ReturnCode {
         // Returns the error code to return to the user and whether we need to
         // reset which is the next active alarm. We only _don't_ reset if we're
         // disabling the underlying alarm anyway, if the underlying alarm is
@@ -108,7 +150,7 @@ impl<A: Alarm> Driver for AlarmDriver<'a, A> {
         // (i.e. no change to the alarms).
         self.app_alarm
             .enter(caller_id, |td, _alloc| {
-                let now = self.alarm.now();
+                let now = self.now();
                 let (return_code, reset) = match cmd_type {
                     0 /* check if present */ => (ReturnCode::SuccessWithValue { value: 1 }, false),
                     1 /* Get clock frequency */ => {
@@ -116,7 +158,7 @@ impl<A: Alarm> Driver for AlarmDriver<'a, A> {
                         (ReturnCode::SuccessWithValue { value: freq }, false)
                     },
                     2 /* capture time */ => {
-                        (ReturnCode::SuccessWithValue { value: now as usize },
+                        (ReturnCode::SuccessWithValue { value: now as u32 as usize },
                          false)
                     },
                     3 /* Stop */ => {
@@ -126,7 +168,8 @@ impl<A: Alarm> Driver for AlarmDriver<'a, A> {
                                 // Request to stop when already stopped
                                 (ReturnCode::EALREADY, false)
                             },
-                            Expiration::Abs(exp) if exp != alarm_id => {
+                            Expiration::Relative { reference, dt }
+                                if Expiration::id(reference, dt) != alarm_id => {
                                 // Request to stop invalid alarm id
                                 (ReturnCode::EINVAL, false)
                             },
@@ -144,9 +187,24 @@ impl<A: Alarm> Driver for AlarmDriver<'a, A> {
                         if let Expiration::Disabled = td.expiration {
                             self.num_armed.set(self.num_armed.get() + 1);
                         }
-                        td.expiration = Expiration::Abs(time as u32);
+                        let dt = (time as u32).wrapping_sub(now as u32);
+                        td.expiration = Expiration::Relative { reference: now, dt: dt as u64 };
                         (ReturnCode::SuccessWithValue { value: time }, true)
                     },
+                    5 /* Set relative expiration */ => {
+                        let dt = data as u64 | (data2 as u64) << 32;
+                        if dt > u32::max_value() as u64 && self.alarm.now64().is_none() {
+                            // Further than the 32-bit clock can tell
+                            (ReturnCode::EINVAL, false)
+                        } else {
+                            if let Expiration::Disabled = td.expiration {
+                                self.num_armed.set(self.num_armed.get() + 1);
+                            }
+                            td.expiration = Expiration::Relative { reference: now, dt: dt };
+                            let id = Expiration::id(now, dt) as usize;
+                            (ReturnCode::SuccessWithValue { value: id }, true)
+                        }
+                    },
                     _ => (ReturnCode::ENOSUPPORT, false)
                 };
                 if reset {
@@ -158,22 +216,18 @@ impl<A: Alarm> Driver for AlarmDriver<'a, A> {
     }
 }
 
-fn has_expired(alarm: u32, now: u32, prev: u32) -> bool {
-    now.wrapping_sub(prev) >= alarm.wrapping_sub(prev)
-}
-
 impl<A: Alarm> time::Client for AlarmDriver<'a, A> {
     fn fired(&self) {
-        let now = self.alarm.now();
+        let now = self.now();
         self.app_alarm.each(|alarm| {
-            if let Expiration::Abs(exp) = alarm.expiration {
-                let expired = has_expired(exp, now, self.prev.get());
-                if expired {
+            if let Expiration::Relative { reference, dt } = alarm.expiration {
+                if Self::elapsed(now, reference) >= dt {
                     alarm.expiration = Expiration::Disabled;
                     self.num_armed.set(self.num_armed.get() - 1);
+                    let id = Expiration::id(reference, dt) as usize;
                     alarm
                         .callback
-                        .map(|mut cb| cb.schedule(now as usize, exp as usize, 0));
+                        .map(|mut cb| cb.schedule(now as u32 as usize, id, 0));
                 }
             }
         });
@@ -183,8 +237,7 @@ impl<A: Alarm> time::Client for AlarmDriver<'a, A> {
         if self.num_armed.get() == 0 {
             self.alarm.disable();
         } else if let Some(next_alarm) = self.reset_active_alarm(now) {
-            let new_now = self.alarm.now();
-            if has_expired(next_alarm, new_now, now) {
+            if Self::elapsed(self.now(), now) >= next_alarm {
                 self.fired();
             }
         } else {
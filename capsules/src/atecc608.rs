@@ -0,0 +1,514 @@
+//! Driver for the Microchip ATECC608 secure element.
+//!
+//! <https://www.microchip.com/wwwproducts/en/ATECC608A>
+//!
+//! The ATECC608 stores up to 16 keys in slots that can be configured so that
+//! private keys never leave the device. This driver implements the
+//! `hil::ecdsa::EcdsaP256` and `hil::hkdf::Hkdf` interfaces on top of it, so
+//! that signing and key derivation keys do not need to be stored in the
+//! microcontroller's flash. Key IDs are slot numbers. The device must have
+//! been configured and locked beforehand, with each slot allowing the
+//! operations that are used on it (e.g. KDF output in the clear for slots
+//! used with `expand`).
+//!
+//! Each operation wakes the device, sends one or two commands and reads their
+//! responses, and puts the device back to sleep, so that it never runs into
+//! its watchdog timeout. Commands are delimited by the device's CRC-16, which
+//! is checked on every response.
+//!
+//! HKDF-Expand is computed with the device's KDF command in HKDF mode, which
+//! computes HMAC-SHA256 keyed by the slot over the message. The driver
+//! appends the block counter to the info string, so that the result is the
+//! first block of HKDF-Expand.
+//!
+//! Usage
+//! -----
+//!
+//! ```rust
+//! let atecc608_i2c = static_init!(
+//!     capsules::virtual_i2c::I2CDevice,
+//!     capsules::virtual_i2c::I2CDevice::new(i2c_bus, 0x60)
+//! );
+//! // Writing to address 0 holds SDA low long enough to wake the device
+//! let atecc608_wake_i2c = static_init!(
+//!     capsules::virtual_i2c::I2CDevice,
+//!     capsules::virtual_i2c::I2CDevice::new(i2c_bus, 0x00)
+//! );
+//! let atecc608_virtual_alarm = static_init!(
+//!     VirtualMuxAlarm<'static, sam4l::ast::Ast>,
+//!     VirtualMuxAlarm::new(mux_alarm)
+//! );
+//! let atecc608 = static_init!(
+//!     capsules::atecc608::Atecc608<'static, VirtualMuxAlarm<'static, sam4l::ast::Ast>>,
+//!     capsules::atecc608::Atecc608::new(
+//!         atecc608_i2c,
+//!         atecc608_wake_i2c,
+//!         atecc608_virtual_alarm,
+//!         &mut capsules::atecc608::BUFFER
+//!     )
+//! );
+//! atecc608_i2c.set_client(atecc608);
+//! atecc608_wake_i2c.set_client(atecc608);
+//! atecc608_virtual_alarm.set_client(atecc608);
+//! ```
+
+use core::cell::Cell;
+use core::cmp;
+use kernel::common::cells::{OptionalCell, TakeCell};
+use kernel::hil::ecdsa::{self, HASH_LEN, PUBLIC_KEY_LEN, SIGNATURE_LEN};
+use kernel::hil::hkdf;
+use kernel::hil::i2c;
+use kernel::hil::time::{self, Frequency};
+use kernel::ReturnCode;
+
+/// Buffer for I2C messages, large enough for the longest command
+pub static mut BUFFER: [u8; 140] = [0; 140];
+
+/// Longest info string accepted by `expand`, as the device's KDF message is
+/// limited to 128 bytes including the block counter.
+pub const MAX_INFO_LEN: usize = 127;
+
+/// Number of key slots
+const NUM_SLOTS: usize = 16;
+
+/// Word addresses, the first byte of every write
+#[allow(dead_code)]
+enum WordAddress {
+    Reset = 0x00,
+    Sleep = 0x01,
+    Idle = 0x02,
+    Command = 0x03,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Opcode {
+    GenKey = 0x40,
+    Kdf = 0x56,
+    Nonce = 0x16,
+    Sign = 0x41,
+    Verify = 0x45,
+}
+
+impl Opcode {
+    /// Maximum execution time, in milliseconds.
+    fn execution_time(self) -> u32 {
+        match self {
+            Opcode::GenKey => 115,
+            Opcode::Kdf => 165,
+            Opcode::Nonce => 20,
+            Opcode::Sign => 115,
+            Opcode::Verify => 105,
+        }
+    }
+}
+
+/// Status codes, returned in a 4 byte response
+const STATUS_SUCCESS: u8 = 0x00;
+const STATUS_MISCOMPARE: u8 = 0x01;
+
+/// Response sent by the device after waking up
+const WAKE_RESPONSE: [u8; 4] = [0x04, 0x11, 0x33, 0x43];
+
+/// Length of a response holding only a status code
+const STATUS_RESPONSE_LEN: usize = 4;
+
+/// Time from the wake pulse until the device is ready, in milliseconds
+const WAKE_DELAY_MS: u32 = 2;
+
+/// Time to wait before reading a response again if the device is still busy,
+/// in milliseconds, and the number of times to do so
+const RETRY_DELAY_MS: u32 = 5;
+const MAX_RETRIES: u8 = 5;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Operation {
+    Sign(u16),
+    Verify,
+    PublicKey(u16),
+    Expand(u16, usize),
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum State {
+    Idle,
+    Wake,
+    WakeDelay,
+    ReadWake,
+    Command,
+    Execute,
+    ReadResponse,
+    Sleep,
+}
+
+/// The device's CRC-16: polynomial 0x8005, initial value 0, with the bits of
+/// each byte processed least significant first.
+fn crc16(data: &[u8]) -> u16 {
+    let mut crc: u16 = 0;
+    for &byte in data {
+        for bit in 0..8 {
+            let data_bit = (byte >> bit) & 1 == 1;
+            let crc_bit = crc & 0x8000 != 0;
+            crc <<= 1;
+            if data_bit != crc_bit {
+                crc ^= 0x8005;
+            }
+        }
+    }
+    crc
+}
+
+pub struct Atecc608<'a, A: time::Alarm> {
+    i2c: &'a i2c::I2CDevice,
+    wake_i2c: &'a i2c::I2CDevice,
+    alarm: &'a A,
+    ecdsa_client: OptionalCell<&'a ecdsa::Client>,
+    hkdf_client: OptionalCell<&'a hkdf::Client>,
+    state: Cell<State>,
+    operation: Cell<Operation>,
+    /// Index of the command of the current operation being run
+    step: Cell<usize>,
+    retries: Cell<u8>,
+    /// Outcome of the current operation, reported once the device is asleep
+    result: Cell<ReturnCode>,
+    valid: Cell<bool>,
+    buffer: TakeCell<'static, [u8]>,
+    client_buffer: TakeCell<'static, [u8]>,
+}
+
+impl<A: time::Alarm> Atecc608<'a, A> {
+    pub fn new(
+        i2c: &'a i2c::I2CDevice,
+        wake_i2c: &'a i2c::I2CDevice,
+        alarm: &'a A,
+        buffer: &'static mut [u8],
+    ) -> Atecc608<'a, A> {
+        Atecc608 {
+            i2c: i2c,
+            wake_i2c: wake_i2c,
+            alarm: alarm,
+            ecdsa_client: OptionalCell::empty(),
+            hkdf_client: OptionalCell::empty(),
+            state: Cell::new(State::Idle),
+            operation: Cell::new(Operation::Verify),
+            step: Cell::new(0),
+            retries: Cell::new(0),
+            result: Cell::new(ReturnCode::SUCCESS),
+            valid: Cell::new(false),
+            buffer: TakeCell::new(buffer),
+            client_buffer: TakeCell::empty(),
+        }
+    }
+
+    fn set_delay(&self, ms: u32) {
+        let interval = <A::Frequency>::ms_to_tics(ms);
+        self.alarm
+            .set_alarm(self.alarm.now().wrapping_add(interval));
+    }
+
+    /// Starts `operation` on `buffer`, beginning with waking the device.
+    fn start(
+        &self,
+        operation: Operation,
+        buffer: &'static mut [u8],
+    ) -> (ReturnCode, Option<&'static mut [u8]>) {
+        if self.state.get() != State::Idle {
+            return (ReturnCode::EBUSY, Some(buffer));
+        }
+        match self.buffer.take() {
+            None => (ReturnCode::EBUSY, Some(buffer)),
+            Some(i2c_buffer) => {
+                self.operation.set(operation);
+                self.step.set(0);
+                self.valid.set(false);
+                self.client_buffer.replace(buffer);
+
+                self.i2c.enable();
+                self.wake_i2c.enable();
+                i2c_buffer[0] = 0;
+                self.wake_i2c.write(i2c_buffer, 1);
+                self.state.set(State::Wake);
+                (ReturnCode::SUCCESS, None)
+            }
+        }
+    }
+
+    /// Returns the opcode, parameters and data length of the current command,
+    /// or `None` if the operation has no more commands.
+    fn command(&self) -> Option<(Opcode, u8, u16, usize)> {
+        match (self.operation.get(), self.step.get()) {
+            // Load the hash into TempKey, then use it
+            (Operation::Sign(_), 0) | (Operation::Verify, 0) => {
+                Some((Opcode::Nonce, 0x03, 0, HASH_LEN))
+            }
+            // External message in TempKey
+            (Operation::Sign(slot), 1) => Some((Opcode::Sign, 0x80, slot, 0)),
+            // External public key of type P-256
+            (Operation::Verify, 1) => {
+                Some((Opcode::Verify, 0x02, 0x0004, SIGNATURE_LEN + PUBLIC_KEY_LEN))
+            }
+            // Public key computed from the private key
+            (Operation::PublicKey(slot), 0) => Some((Opcode::GenKey, 0x00, slot, 0)),
+            // HKDF from the slot, with the result output in the clear. The
+            // data is 4 bytes of details, giving the message length, followed
+            // by the message
+            (Operation::Expand(slot, info_len), 0) => {
+                Some((Opcode::Kdf, 0x4e, slot, 4 + info_len + 1))
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns the length of the data in the response to the current
+    /// command, other than a status code.
+    fn response_data_len(&self) -> usize {
+        match self.command() {
+            Some((Opcode::Sign, _, _, _)) => SIGNATURE_LEN,
+            Some((Opcode::GenKey, _, _, _)) => PUBLIC_KEY_LEN,
+            Some((Opcode::Kdf, _, _, _)) => hkdf::OUTPUT_LEN,
+            _ => 0,
+        }
+    }
+
+    /// Sends the current command of the operation.
+    fn send_command(&self, buffer: &'static mut [u8]) {
+        let (opcode, param1, param2, data_len) = match self.command() {
+            Some(command) => command,
+            None => return self.finish(buffer, ReturnCode::SUCCESS),
+        };
+
+        // Word address, count, opcode, param1, param2, data, CRC
+        let count = 7 + data_len;
+        buffer[0] = WordAddress::Command as u8;
+        buffer[1] = count as u8;
+        buffer[2] = opcode as u8;
+        buffer[3] = param1;
+        buffer[4] = param2 as u8;
+        buffer[5] = (param2 >> 8) as u8;
+        self.client_buffer.map(|client_buffer| {
+            let data = &mut buffer[6..6 + data_len];
+            match self.operation.get() {
+                Operation::Sign(_) | Operation::Verify if opcode == Opcode::Nonce => {
+                    data.copy_from_slice(&client_buffer[..HASH_LEN]);
+                }
+                Operation::Verify => {
+                    data.copy_from_slice(&client_buffer[HASH_LEN..HASH_LEN + data_len]);
+                }
+                Operation::Expand(_, info_len) => {
+                    // Message in the input data, of length info_len + 1
+                    data[..4].copy_from_slice(&[0, 0, 0, (info_len + 1) as u8]);
+                    data[4..4 + info_len].copy_from_slice(&client_buffer[..info_len]);
+                    data[4 + info_len] = 0x01;
+                }
+                _ => {}
+            }
+        });
+        let crc = crc16(&buffer[1..1 + count - 2]);
+        buffer[count - 1] = crc as u8;
+        buffer[count] = (crc >> 8) as u8;
+
+        self.state.set(State::Command);
+        self.i2c.write(buffer, (count + 1) as u8);
+    }
+
+    /// Handles a complete response to the current command.
+    fn handle_response(&self, buffer: &'static mut [u8]) {
+        let count = buffer[0] as usize;
+        let data_len = self.response_data_len();
+        if count < STATUS_RESPONSE_LEN || count > buffer.len() {
+            return self.finish(buffer, ReturnCode::FAIL);
+        }
+        let crc = crc16(&buffer[..count - 2]);
+        if buffer[count - 2] != crc as u8 || buffer[count - 1] != (crc >> 8) as u8 {
+            return self.finish(buffer, ReturnCode::FAIL);
+        }
+
+        if data_len > 0 && count == data_len + 3 {
+            self.client_buffer.map(|client_buffer| {
+                client_buffer[..data_len].copy_from_slice(&buffer[1..1 + data_len]);
+            });
+        } else if count == STATUS_RESPONSE_LEN {
+            // A miscompare is the normal result of verifying an invalid
+            // signature, rather than an error
+            let verify = self.operation.get() == Operation::Verify && self.step.get() == 1;
+            match buffer[1] {
+                STATUS_SUCCESS if data_len == 0 => self.valid.set(verify),
+                STATUS_MISCOMPARE if verify => self.valid.set(false),
+                _ => return self.finish(buffer, ReturnCode::FAIL),
+            }
+        } else {
+            return self.finish(buffer, ReturnCode::FAIL);
+        }
+
+        self.step.set(self.step.get() + 1);
+        self.send_command(buffer);
+    }
+
+    /// Puts the device to sleep, after which the client is called with
+    /// `result`.
+    fn finish(&self, buffer: &'static mut [u8], result: ReturnCode) {
+        self.result.set(result);
+        buffer[0] = WordAddress::Sleep as u8;
+        self.state.set(State::Sleep);
+        self.i2c.write(buffer, 1);
+    }
+
+    fn operation_done(&self) {
+        let result = self.result.get();
+        let operation = self.operation.get();
+        self.client_buffer.take().map(|buffer| match operation {
+            Operation::Sign(_) => self
+                .ecdsa_client
+                .map(move |client| client.sign_done(result, buffer)),
+            Operation::Verify => {
+                let valid = self.valid.get();
+                self.ecdsa_client
+                    .map(move |client| client.verify_done(result, valid, buffer))
+            }
+            Operation::PublicKey(_) => self
+                .ecdsa_client
+                .map(move |client| client.public_key_done(result, buffer)),
+            Operation::Expand(_, _) => self
+                .hkdf_client
+                .map(move |client| client.expand_done(result, buffer)),
+        });
+    }
+}
+
+impl<A: time::Alarm> i2c::I2CClient for Atecc608<'a, A> {
+    fn command_complete(&self, buffer: &'static mut [u8], error: i2c::Error) {
+        match self.state.get() {
+            State::Wake => {
+                // The wake pulse is never acknowledged
+                self.wake_i2c.disable();
+                self.buffer.replace(buffer);
+                self.state.set(State::WakeDelay);
+                self.set_delay(WAKE_DELAY_MS);
+            }
+            State::ReadWake => {
+                if error != i2c::Error::CommandComplete || buffer[..4] != WAKE_RESPONSE {
+                    self.finish(buffer, ReturnCode::FAIL);
+                } else {
+                    self.send_command(buffer);
+                }
+            }
+            State::Command => {
+                if error != i2c::Error::CommandComplete {
+                    self.finish(buffer, ReturnCode::FAIL);
+                } else {
+                    let execution_time = self
+                        .command()
+                        .map_or(0, |(opcode, _, _, _)| opcode.execution_time());
+                    self.buffer.replace(buffer);
+                    self.retries.set(0);
+                    self.state.set(State::Execute);
+                    self.set_delay(execution_time);
+                }
+            }
+            State::ReadResponse => {
+                if error == i2c::Error::CommandComplete {
+                    self.handle_response(buffer);
+                } else if self.retries.get() < MAX_RETRIES {
+                    // The device does not acknowledge reads until it has
+                    // finished executing the command
+                    self.retries.set(self.retries.get() + 1);
+                    self.buffer.replace(buffer);
+                    self.state.set(State::Execute);
+                    self.set_delay(RETRY_DELAY_MS);
+                } else {
+                    self.finish(buffer, ReturnCode::FAIL);
+                }
+            }
+            State::Sleep => {
+                self.buffer.replace(buffer);
+                self.i2c.disable();
+                self.state.set(State::Idle);
+                self.operation_done();
+            }
+            State::Idle | State::WakeDelay | State::Execute => {
+                self.buffer.replace(buffer);
+            }
+        }
+    }
+}
+
+impl<A: time::Alarm> time::Client for Atecc608<'a, A> {
+    fn fired(&self) {
+        self.buffer.take().map(|buffer| match self.state.get() {
+            State::WakeDelay => {
+                self.state.set(State::ReadWake);
+                self.i2c.read(buffer, WAKE_RESPONSE.len() as u8);
+            }
+            State::Execute => {
+                // Count, data or status, and CRC
+                let len = cmp::max(self.response_data_len() + 3, STATUS_RESPONSE_LEN);
+                self.state.set(State::ReadResponse);
+                self.i2c.read(buffer, len as u8);
+            }
+            _ => {
+                self.buffer.replace(buffer);
+            }
+        });
+    }
+}
+
+impl<A: time::Alarm> ecdsa::EcdsaP256<'a> for Atecc608<'a, A> {
+    fn set_client(&self, client: &'a ecdsa::Client) {
+        self.ecdsa_client.set(client);
+    }
+
+    fn sign(
+        &self,
+        key_id: usize,
+        buffer: &'static mut [u8],
+    ) -> (ReturnCode, Option<&'static mut [u8]>) {
+        if key_id >= NUM_SLOTS {
+            return (ReturnCode::EINVAL, Some(buffer));
+        }
+        if buffer.len() < SIGNATURE_LEN {
+            return (ReturnCode::ESIZE, Some(buffer));
+        }
+        self.start(Operation::Sign(key_id as u16), buffer)
+    }
+
+    fn verify(&self, buffer: &'static mut [u8]) -> (ReturnCode, Option<&'static mut [u8]>) {
+        if buffer.len() < HASH_LEN + SIGNATURE_LEN + PUBLIC_KEY_LEN {
+            return (ReturnCode::ESIZE, Some(buffer));
+        }
+        self.start(Operation::Verify, buffer)
+    }
+
+    fn public_key(
+        &self,
+        key_id: usize,
+        buffer: &'static mut [u8],
+    ) -> (ReturnCode, Option<&'static mut [u8]>) {
+        if key_id >= NUM_SLOTS {
+            return (ReturnCode::EINVAL, Some(buffer));
+        }
+        if buffer.len() < PUBLIC_KEY_LEN {
+            return (ReturnCode::ESIZE, Some(buffer));
+        }
+        self.start(Operation::PublicKey(key_id as u16), buffer)
+    }
+}
+
+impl<A: time::Alarm> hkdf::Hkdf<'a> for Atecc608<'a, A> {
+    fn set_client(&self, client: &'a hkdf::Client) {
+        self.hkdf_client.set(client);
+    }
+
+    fn expand(
+        &self,
+        key_id: usize,
+        buffer: &'static mut [u8],
+        info_len: usize,
+    ) -> (ReturnCode, Option<&'static mut [u8]>) {
+        if key_id >= NUM_SLOTS {
+            return (ReturnCode::EINVAL, Some(buffer));
+        }
+        if info_len > MAX_INFO_LEN || info_len > buffer.len() || buffer.len() < hkdf::OUTPUT_LEN {
+            return (ReturnCode::ESIZE, Some(buffer));
+        }
+        self.start(Operation::Expand(key_id as u16, info_len), buffer)
+    }
+}
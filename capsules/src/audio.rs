@@ -0,0 +1,308 @@
+//! Provides userspace with access to an audio output, to play short clips and
+//! tones.
+//!
+//! A process plays a clip by allowing a buffer of PCM samples (signed 16-bit
+//! little endian, mono) and giving their number and sample rate. The samples
+//! are copied into a kernel buffer and played in chunks of its size, so the
+//! process buffer must stay allowed until playback ends. A process can also
+//! play a sine tone of a given frequency and duration, which is synthesized
+//! in the kernel and needs no buffer.
+//!
+//! One process can play at a time; requests from others return `EBUSY` until
+//! it is done.
+//!
+//! Usage
+//! -----
+//!
+//! ```rust
+//! let audio = static_init!(
+//!     capsules::audio::AudioDriver<'static>,
+//!     capsules::audio::AudioDriver::new(
+//!         &sam4l::dac::DAC,
+//!         &mut capsules::audio::BUFFER,
+//!         kernel::Grant::create())
+//! );
+//! hil::audio::AudioOutput::set_client(&sam4l::dac::DAC, audio);
+//! ```
+
+use core::cell::Cell;
+use core::cmp;
+use kernel::common::cells::{OptionalCell, TakeCell};
+use kernel::hil::audio::{self, SAMPLE_LEN};
+use kernel::{AppId, AppSlice, Callback, Driver, Grant, ReturnCode, Shared};
+
+/// Syscall driver number.
+use crate::driver;
+pub const DRIVER_NUM: usize = driver::NUM::Audio as usize;
+
+/// Buffer that samples are played from, 256 samples long.
+pub static mut BUFFER: [u8; 512] = [0; 512];
+
+/// Sample rate of synthesized tones.
+pub const TONE_SAMPLE_RATE_HZ: u32 = 8000;
+
+/// Longest tone that can be played, in milliseconds.
+pub const MAX_TONE_DURATION_MS: usize = 5000;
+
+/// One period of a sine wave, at 3/4 of full scale.
+const SINE: [i16; 32] = [
+    0, 4795, 9405, 13654, 17378, 20434, 22705, 24104, 24576, 24104, 22705, 20434, 17378, 13654,
+    9405, 4795, 0, -4795, -9405, -13654, -17378, -20434, -22705, -24104, -24576, -24104, -22705,
+    -20434, -17378, -13654, -9405, -4795,
+];
+
+#[derive(Clone, Copy, PartialEq)]
+enum Source {
+    /// The allowed buffer of the playing process
+    Clip,
+    /// A sine wave, advancing the phase by `step` each sample
+    Tone { step: u32 },
+}
+
+#[derive(Default)]
+pub struct App {
+    callback: Option<Callback>,
+    buffer: Option<AppSlice<Shared, u8>>,
+}
+
+pub struct AudioDriver<'a> {
+    output: &'a audio::AudioOutput<'a>,
+    apps: Grant<App>,
+    buffer: TakeCell<'static, [u8]>,
+    /// The process whose clip or tone is playing
+    active_app: OptionalCell<AppId>,
+    source: Cell<Source>,
+    sample_rate_hz: Cell<u32>,
+    /// Index of the next sample of the clip
+    position: Cell<usize>,
+    /// Phase of the tone, a full period being 2^32
+    phase: Cell<u32>,
+    /// Number of samples that have not been played yet
+    remaining: Cell<usize>,
+}
+
+impl AudioDriver<'a> {
+    pub fn new(
+        output: &'a audio::AudioOutput<'a>,
+        buffer: &'static mut [u8],
+        grant: Grant<App>,
+    ) -> AudioDriver<'a> {
+        AudioDriver {
+            output: output,
+            apps: grant,
+            buffer: TakeCell::new(buffer),
+            active_app: OptionalCell::empty(),
+            source: Cell::new(Source::Clip),
+            sample_rate_hz: Cell::new(0),
+            position: Cell::new(0),
+            phase: Cell::new(0),
+            remaining: Cell::new(0),
+        }
+    }
+
+    /// Starts playing `samples` samples from `source` for `appid`.
+    fn start(
+        &self,
+        appid: AppId,
+        source: Source,
+        samples: usize,
+        sample_rate_hz: u32,
+    ) -> ReturnCode {
+        if self.active_app.is_some() {
+            return ReturnCode::EBUSY;
+        }
+        if !self.output.sample_rate_supported(sample_rate_hz) {
+            return ReturnCode::EINVAL;
+        }
+        if samples == 0 {
+            return ReturnCode::ESIZE;
+        }
+        self.active_app.set(appid);
+        self.source.set(source);
+        self.sample_rate_hz.set(sample_rate_hz);
+        self.position.set(0);
+        self.phase.set(0);
+        self.remaining.set(samples);
+
+        let result = self.play_next();
+        if result != ReturnCode::SUCCESS {
+            self.active_app.clear();
+        }
+        result
+    }
+
+    /// Fills the kernel buffer with the next chunk of samples and plays it.
+    fn play_next(&self) -> ReturnCode {
+        let buffer = match self.buffer.take() {
+            Some(buffer) => buffer,
+            None => return ReturnCode::EBUSY,
+        };
+        let samples = cmp::min(self.remaining.get(), buffer.len() / SAMPLE_LEN);
+        let chunk = &mut buffer[..samples * SAMPLE_LEN];
+
+        let filled = match self.source.get() {
+            Source::Clip => self.active_app.map_or(ReturnCode::FAIL, |appid| {
+                self.apps
+                    .enter(*appid, |app, _| {
+                        app.buffer.as_ref().map_or(ReturnCode::ENOMEM, |clip| {
+                            let start = self.position.get() * SAMPLE_LEN;
+                            if start + chunk.len() > clip.len() {
+                                return ReturnCode::ESIZE;
+                            }
+                            chunk.copy_from_slice(&clip.as_ref()[start..start + chunk.len()]);
+                            ReturnCode::SUCCESS
+                        })
+                    })
+                    .unwrap_or_else(|err| err.into())
+            }),
+            Source::Tone { step } => {
+                let mut phase = self.phase.get();
+                for sample in chunk.chunks_mut(SAMPLE_LEN) {
+                    sample.copy_from_slice(&SINE[(phase >> 27) as usize].to_le_bytes());
+                    phase = phase.wrapping_add(step);
+                }
+                self.phase.set(phase);
+                ReturnCode::SUCCESS
+            }
+        };
+        if filled != ReturnCode::SUCCESS {
+            self.buffer.replace(buffer);
+            return filled;
+        }
+
+        self.position.set(self.position.get() + samples);
+        self.remaining.set(self.remaining.get() - samples);
+        let (result, buffer) = self.output.play(buffer, samples, self.sample_rate_hz.get());
+        buffer.map(|buffer| self.buffer.replace(buffer));
+        result
+    }
+
+    /// Ends playback, telling the process that was playing.
+    fn finish(&self, result: ReturnCode) {
+        self.active_app.take().map(|appid| {
+            let _ = self.apps.enter(appid, |app, _| {
+                app.callback
+                    .map(|mut callback| callback.schedule(usize::from(result), 0, 0));
+            });
+        });
+    }
+}
+
+impl audio::OutputClient for AudioDriver<'a> {
+    fn playback_done(&self, buffer: &'static mut [u8], result: ReturnCode) {
+        self.buffer.replace(buffer);
+        if result == ReturnCode::SUCCESS && self.remaining.get() > 0 {
+            let result = self.play_next();
+            if result != ReturnCode::SUCCESS {
+                self.finish(result);
+            }
+        } else {
+            self.finish(result);
+        }
+    }
+}
+
+impl Driver for AudioDriver<'a> {
+    /// Setup the buffer of samples to play.
+    ///
+    /// ### `allow_num`
+    ///
+    /// - `0`: The clip to play, as signed 16-bit little endian samples.
+    fn allow(
+        &self,
+        appid: AppId,
+        allow_num: usize,
+        slice: Option<AppSlice<Shared, u8>>,
+    ) -> ReturnCode {
+        match allow_num {
+            0 => self
+                .apps
+                .enter(appid, |app, _| {
+                    app.buffer = slice;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or_else(|err| err.into()),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    /// Subscribe to the end of playback.
+    ///
+    /// ### `subscribe_num`
+    ///
+    /// - `0`: Set the callback called when a clip or tone ends, with
+    ///        `SUCCESS` if it was played to the end or an error code
+    ///        otherwise.
+    fn subscribe(
+        &self,
+        subscribe_num: usize,
+        callback: Option<Callback>,
+        appid: AppId,
+    ) -> ReturnCode {
+        match subscribe_num {
+            0 => self
+                .apps
+                .enter(appid, |app, _| {
+                    app.callback = callback;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or_else(|err| err.into()),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    /// Play clips and tones.
+    ///
+    /// ### `command_num`
+    ///
+    /// - `0`: Driver check.
+    /// - `1`: Play the first `arg1` samples of the allowed buffer at a
+    ///        sample rate of `arg2` Hz.
+    /// - `2`: Play a tone of `arg1` Hz for `arg2` milliseconds.
+    /// - `3`: Stop playing.
+    fn command(&self, command_num: usize, arg1: usize, arg2: usize, appid: AppId) -> ReturnCode {
+        match command_num {
+            0 => ReturnCode::SUCCESS,
+            1 => {
+                let clip_len = self
+                    .apps
+                    .enter(appid, |app, _| {
+                        app.buffer
+                            .as_ref()
+                            .map_or(Err(ReturnCode::ENOMEM), |clip| Ok(clip.len()))
+                    })
+                    .unwrap_or_else(|err| Err(err.into()));
+                match clip_len {
+                    Ok(len) if arg1 > len / SAMPLE_LEN => ReturnCode::ESIZE,
+                    Ok(_) => self.start(appid, Source::Clip, arg1, arg2 as u32),
+                    Err(err) => err,
+                }
+            }
+            2 => {
+                // Tones must be below the Nyquist frequency
+                if arg1 == 0 || arg1 as u32 >= TONE_SAMPLE_RATE_HZ / 2 {
+                    return ReturnCode::EINVAL;
+                }
+                if arg2 > MAX_TONE_DURATION_MS {
+                    return ReturnCode::ESIZE;
+                }
+                let step = ((arg1 as u64) << 32) / TONE_SAMPLE_RATE_HZ as u64;
+                let samples = arg2 * TONE_SAMPLE_RATE_HZ as usize / 1000;
+                self.start(
+                    appid,
+                    Source::Tone { step: step as u32 },
+                    samples,
+                    TONE_SAMPLE_RATE_HZ,
+                )
+            }
+            3 => {
+                if !self.active_app.map_or(false, |active| *active == appid) {
+                    return ReturnCode::EOFF;
+                }
+                self.remaining.set(0);
+                self.output.stop()
+            }
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+}
@@ -288,8 +288,8 @@ impl App {
         self.alarm_data.t0 = now;
         let nonce = self.random_nonce() % 10;
 
-        let period_ms = (self.advertisement_interval_ms + nonce) * F::frequency() / 1000;
-        self.alarm_data.expiration = Expiration::Abs(now.wrapping_add(period_ms));
+        let period = F::ms_to_tics(self.advertisement_interval_ms + nonce);
+        self.alarm_data.expiration = Expiration::Abs(now.wrapping_add(period));
     }
 }
 
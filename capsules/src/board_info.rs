@@ -0,0 +1,274 @@
+//! Provides userspace with a description of the board it is running on.
+//!
+//! The board describes itself with a `BoardDescriptor`: its name, its chip,
+//! the syscall drivers it provides, the names of the pins of the GPIO driver,
+//! and the sensors it has. This is a `static` in the board's main file, so it
+//! lives in flash. Processes read it through this driver, so that one app
+//! binary can adapt to the board, e.g. by finding which GPIO pin is labeled
+//! `D6` or whether there is a humidity sensor, rather than being built for
+//! each board.
+//!
+//! The descriptor is read by processes in an encoded form, as a header
+//! followed by a list of records:
+//!
+//! | Offset | Type    | Field                                      |
+//! |--------|---------|--------------------------------------------|
+//! | 0      | [u8; 4] | Magic, `TKBD`                              |
+//! | 4      | u8      | Format version, 1                          |
+//! | 5      | u8      | Reserved, 0                                |
+//! | 6      | u16     | Length of the records                      |
+//!
+//! Each record is its type (one byte), the length of its value (one byte),
+//! and its value. All numbers are little endian and names are UTF-8, not
+//! null terminated.
+//!
+//! | Type | Record      | Value                                                 |
+//! |------|-------------|-------------------------------------------------------|
+//! | 1    | Board name  | The name                                              |
+//! | 2    | Chip name   | The name                                              |
+//! | 3    | Driver      | The driver number, as a u32                           |
+//! | 4    | Pin         | The pin number, as a u16, then the name               |
+//! | 5    | Sensor      | The kind (u8), the driver number (u32), then the name |
+//!
+//! Usage
+//! -----
+//!
+//! ```rust
+//! static BOARD: capsules::board_info::BoardDescriptor =
+//!     capsules::board_info::BoardDescriptor {
+//!         name: "hail",
+//!         chip: "sam4l",
+//!         drivers: &[capsules::console::DRIVER_NUM, capsules::led::DRIVER_NUM],
+//!         pins: &[capsules::board_info::PinName { number: 0, name: "D0" }],
+//!         sensors: &[capsules::board_info::Sensor {
+//!             kind: capsules::board_info::SensorKind::Temperature,
+//!             driver: capsules::temperature::DRIVER_NUM,
+//!             name: "SI7021",
+//!         }],
+//!     };
+//!
+//! let board_info = static_init!(
+//!     capsules::board_info::BoardInfo,
+//!     capsules::board_info::BoardInfo::new(
+//!         &BOARD,
+//!         board_kernel.create_grant(&memory_allocation_capability))
+//! );
+//! ```
+
+use core::cmp;
+use kernel::{AppId, AppSlice, Driver, Grant, ReturnCode, Shared};
+
+/// Syscall driver number.
+use crate::driver;
+pub const DRIVER_NUM: usize = driver::NUM::BoardInfo as usize;
+
+const MAGIC: [u8; 4] = *b"TKBD";
+const VERSION: u8 = 1;
+const HEADER_LEN: usize = 8;
+
+const RECORD_BOARD_NAME: u8 = 1;
+const RECORD_CHIP_NAME: u8 = 2;
+const RECORD_DRIVER: u8 = 3;
+const RECORD_PIN: u8 = 4;
+const RECORD_SENSOR: u8 = 5;
+
+/// The kinds of sensors a board can have.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SensorKind {
+    Temperature = 1,
+    Humidity = 2,
+    AmbientLight = 3,
+    Pressure = 4,
+    Accelerometer = 5,
+    Magnetometer = 6,
+    Gyroscope = 7,
+    Microphone = 8,
+}
+
+/// The name of a pin of the GPIO driver, e.g. as printed on the board.
+pub struct PinName {
+    /// The number of the pin in the GPIO driver
+    pub number: usize,
+    pub name: &'static str,
+}
+
+/// A sensor on the board, and the driver through which it can be read.
+pub struct Sensor {
+    pub kind: SensorKind,
+    pub driver: usize,
+    /// The name of the part, e.g. `SI7021`
+    pub name: &'static str,
+}
+
+/// The description of a board.
+pub struct BoardDescriptor {
+    pub name: &'static str,
+    pub chip: &'static str,
+    /// Numbers of the syscall drivers that the board provides
+    pub drivers: &'static [usize],
+    pub pins: &'static [PinName],
+    pub sensors: &'static [Sensor],
+}
+
+/// Writes the bytes of the encoded descriptor that fall in a window of it,
+/// and counts all of them.
+struct Encoder<'b> {
+    buffer: &'b mut [u8],
+    /// Offset in the encoded descriptor of the start of `buffer`
+    offset: usize,
+    /// Offset of the next byte
+    position: usize,
+}
+
+impl Encoder<'b> {
+    fn put(&mut self, byte: u8) {
+        if self.position >= self.offset {
+            if let Some(slot) = self.buffer.get_mut(self.position - self.offset) {
+                *slot = byte;
+            }
+        }
+        self.position += 1;
+    }
+
+    fn put_all(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.put(byte);
+        }
+    }
+
+    /// Writes a record, truncating its value to the 255 bytes that fit.
+    fn record(&mut self, record_type: u8, prefix: &[u8], name: &str) {
+        let name_len = cmp::min(name.len(), 255 - prefix.len());
+        self.put(record_type);
+        self.put((prefix.len() + name_len) as u8);
+        self.put_all(prefix);
+        self.put_all(&name.as_bytes()[..name_len]);
+    }
+}
+
+impl BoardDescriptor {
+    /// Writes the encoded descriptor, starting from `offset`, into `buffer`.
+    /// Returns the length of the whole encoded descriptor.
+    fn encode(&self, offset: usize, buffer: &mut [u8]) -> usize {
+        let mut records = Encoder {
+            buffer: buffer,
+            offset: offset,
+            position: HEADER_LEN,
+        };
+        self.encode_records(&mut records);
+        let records_len = records.position - HEADER_LEN;
+
+        let mut header = Encoder {
+            buffer: records.buffer,
+            offset: offset,
+            position: 0,
+        };
+        header.put_all(&MAGIC);
+        header.put(VERSION);
+        header.put(0);
+        header.put_all(&(records_len as u16).to_le_bytes());
+        HEADER_LEN + records_len
+    }
+
+    fn encode_records(&self, encoder: &mut Encoder) {
+        encoder.record(RECORD_BOARD_NAME, &[], self.name);
+        encoder.record(RECORD_CHIP_NAME, &[], self.chip);
+        for &driver in self.drivers.iter() {
+            encoder.record(RECORD_DRIVER, &(driver as u32).to_le_bytes(), "");
+        }
+        for pin in self.pins.iter() {
+            encoder.record(RECORD_PIN, &(pin.number as u16).to_le_bytes(), pin.name);
+        }
+        for sensor in self.sensors.iter() {
+            let driver = (sensor.driver as u32).to_le_bytes();
+            encoder.record(
+                RECORD_SENSOR,
+                &[
+                    sensor.kind as u8,
+                    driver[0],
+                    driver[1],
+                    driver[2],
+                    driver[3],
+                ],
+                sensor.name,
+            );
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct App {
+    buffer: Option<AppSlice<Shared, u8>>,
+}
+
+pub struct BoardInfo {
+    descriptor: &'static BoardDescriptor,
+    apps: Grant<App>,
+}
+
+impl BoardInfo {
+    pub fn new(descriptor: &'static BoardDescriptor, grant: Grant<App>) -> BoardInfo {
+        BoardInfo {
+            descriptor: descriptor,
+            apps: grant,
+        }
+    }
+}
+
+impl Driver for BoardInfo {
+    /// Setup the buffer the descriptor is read into.
+    ///
+    /// ### `allow_num`
+    ///
+    /// - `0`: The buffer the encoded descriptor is copied into.
+    fn allow(
+        &self,
+        appid: AppId,
+        allow_num: usize,
+        slice: Option<AppSlice<Shared, u8>>,
+    ) -> ReturnCode {
+        match allow_num {
+            0 => self
+                .apps
+                .enter(appid, |app, _| {
+                    app.buffer = slice;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or_else(|err| err.into()),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    /// Read the board descriptor.
+    ///
+    /// ### `command_num`
+    ///
+    /// - `0`: Driver check.
+    /// - `1`: Returns the length of the encoded descriptor.
+    /// - `2`: Copy the encoded descriptor, starting at offset `arg1`, into
+    ///        the allowed buffer. Returns the number of bytes copied.
+    /// - `3`: Returns 1 if the board provides driver `arg1`, 0 otherwise.
+    fn command(&self, command_num: usize, arg1: usize, _: usize, appid: AppId) -> ReturnCode {
+        match command_num {
+            0 => ReturnCode::SUCCESS,
+            1 => ReturnCode::SuccessWithValue {
+                value: self.descriptor.encode(0, &mut []),
+            },
+            2 => self
+                .apps
+                .enter(appid, |app, _| {
+                    app.buffer.as_mut().map_or(ReturnCode::ENOMEM, |buffer| {
+                        let len = self.descriptor.encode(arg1, buffer.as_mut());
+                        ReturnCode::SuccessWithValue {
+                            value: cmp::min(len.saturating_sub(arg1), buffer.len()),
+                        }
+                    })
+                })
+                .unwrap_or_else(|err| err.into()),
+            3 => ReturnCode::SuccessWithValue {
+                value: self.descriptor.drivers.contains(&arg1) as usize,
+            },
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+}
@@ -0,0 +1,619 @@
+//! Dual bank (A/B) firmware images, and a system call driver that allows an
+//! update process to install an image in the other bank.
+//!
+//! The flash holds two banks, each with a kernel and its apps. The kernel
+//! runs from one of them while an update is written to the other, so that a
+//! failed or interrupted update never leaves the device without an image that
+//! boots. Which bank boots is decided by the bootloader, from a boot record
+//! that this capsule and the bootloader share in flash.
+//!
+//! A bank is `Empty`, on `Trial`, `Healthy` or `Bad`. Once the new image is
+//! written, `set_pending` makes its bank the one to boot, on trial with
+//! `MAX_TRIES` tries. After the system has run without trouble, the kernel
+//! marks the bank it runs from healthy with `mark_healthy`, and from then on
+//! the bootloader keeps booting it. The previous bank stays healthy, as the
+//! one to fall back to.
+//!
+//! The record is kept in a ring of flash pages like the counters of
+//! `monotonic_counter`: each change writes a new record, with the next
+//! generation number and a CRC, to the next page of the ring, so a record
+//! torn by a reset is ignored and the previous one holds. A record is:
+//!
+//! ```text
+//! 0   magic "BOOT" (0x424f4f54)       u32, little endian
+//! 4   generation                      u32
+//! 8   bank to boot, 0 = A, 1 = B      u8
+//! 9   state of bank A, then B         u8 each: 0 empty, 1 trial,
+//!                                     2 healthy, 3 bad
+//! 11  tries left of bank A, then B    u8 each
+//! 13  reserved, zero                  3 bytes
+//! 16  CRC32 of bytes 0 to 15          u32
+//! ```
+//!
+//! At each reset the bootloader takes the valid record with the highest
+//! generation. If the bank to boot is healthy, it boots it. If it is on
+//! trial with tries left, it writes a record with one try less, as the next
+//! generation in the next page of the ring, and boots it. Otherwise it writes
+//! a record that marks the bank bad and makes the other bank the one to boot,
+//! and boots that one if it is healthy. Without any valid record it boots
+//! bank A.
+//!
+//! `TrialConfirm` decides when a bank on trial is healthy: once the kernel
+//! has run for a while with no process faulting.
+//!
+//! Usage
+//! -----
+//!
+//! ```rust
+//! pub static mut PAGEBUFFER: nrf52::nvmc::NrfPage = nrf52::nvmc::NrfPage::new();
+//! let banks = static_init!(
+//!     capsules::boot_banks::FlashBootBanks<'static, nrf52::nvmc::Nvmc>,
+//!     capsules::boot_banks::FlashBootBanks::new(
+//!         &nrf52::nvmc::NVMC,
+//!         &mut PAGEBUFFER,
+//!         0x7c,    // First page of the ring
+//!         2,       // Number of pages in the ring
+//!         capsules::boot_banks::Bank::A
+//!     )
+//! );
+//! hil::flash::HasClient::set_client(&nrf52::nvmc::NVMC, banks);
+//! let banks_driver = static_init!(
+//!     capsules::boot_banks::BootBanksDriver<'static>,
+//!     capsules::boot_banks::BootBanksDriver::new(banks, kernel::Grant::create())
+//! );
+//! banks.set_client(banks_driver);
+//! banks.initialize();
+//!
+//! let confirm_alarm = static_init!(
+//!     VirtualMuxAlarm<'static, nrf5x::rtc::Rtc>,
+//!     VirtualMuxAlarm::new(mux_alarm)
+//! );
+//! let confirm = static_init!(
+//!     capsules::boot_banks::TrialConfirm<'static, VirtualMuxAlarm<'static, nrf5x::rtc::Rtc>>,
+//!     capsules::boot_banks::TrialConfirm::new(banks, confirm_alarm, board_kernel, 60000)
+//! );
+//! confirm_alarm.set_client(confirm);
+//! confirm.start();
+//! ```
+//!
+//! Syscall Interface
+//! -----------------
+//!
+//! - Stability: 0 - Draft
+//!
+//! Banks are 0 for A and 1 for B, and states are numbered as in the record.
+//!
+//! ### Subscribe
+//!
+//! - `0`: Called with the result when a change requested by the process is
+//!        persistent.
+//!
+//! ### Command
+//!
+//! - `0`: Driver check.
+//! - `1`: Returns the bank the kernel runs from.
+//! - `2`: Returns the state of bank `arg1`.
+//! - `3`: Boots bank `arg1` on trial from the next reset.
+//! - `4`: Marks the running bank healthy.
+
+use core::cell::Cell;
+use kernel::common::cells::{OptionalCell, TakeCell};
+use kernel::hil;
+use kernel::hil::crc::CrcAlg;
+use kernel::hil::time::{self, Frequency};
+use kernel::{AppId, Callback, Driver, Grant, ReturnCode};
+
+use crate::software_crc;
+
+/// Syscall driver number.
+use crate::driver;
+pub const DRIVER_NUM: usize = driver::NUM::BootBanks as usize;
+
+/// How many resets a bank on trial gets to be marked healthy
+pub const MAX_TRIES: u8 = 3;
+
+/// Marks the start of a record ("BOOT")
+const MAGIC: u32 = 0x424f4f54;
+
+const RECORD_LEN: usize = 20;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Bank {
+    A = 0,
+    B = 1,
+}
+
+impl Bank {
+    pub fn other(self) -> Bank {
+        match self {
+            Bank::A => Bank::B,
+            Bank::B => Bank::A,
+        }
+    }
+
+    fn from_usize(n: usize) -> Option<Bank> {
+        match n {
+            0 => Some(Bank::A),
+            1 => Some(Bank::B),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum BankState {
+    Empty = 0,
+    Trial = 1,
+    Healthy = 2,
+    Bad = 3,
+}
+
+impl BankState {
+    fn from_u8(n: u8) -> Option<BankState> {
+        match n {
+            0 => Some(BankState::Empty),
+            1 => Some(BankState::Trial),
+            2 => Some(BankState::Healthy),
+            3 => Some(BankState::Bad),
+            _ => None,
+        }
+    }
+}
+
+/// The contents of a boot record, but for its generation.
+#[derive(Clone, Copy, PartialEq)]
+struct Record {
+    boot: Bank,
+    states: [BankState; 2],
+    tries: [u8; 2],
+}
+
+/// Writes `record` to the start of `buf`.
+fn encode_record(buf: &mut [u8], generation: u32, record: &Record) {
+    buf[0..4].copy_from_slice(&MAGIC.to_le_bytes());
+    buf[4..8].copy_from_slice(&generation.to_le_bytes());
+    buf[8] = record.boot as u8;
+    buf[9] = record.states[0] as u8;
+    buf[10] = record.states[1] as u8;
+    buf[11] = record.tries[0];
+    buf[12] = record.tries[1];
+    buf[13..16].copy_from_slice(&[0; 3]);
+    let crc = record_crc(&buf[..RECORD_LEN - 4]);
+    buf[RECORD_LEN - 4..RECORD_LEN].copy_from_slice(&crc.to_le_bytes());
+}
+
+/// Returns the generation and contents of the record at the start of `buf`,
+/// or `None` if there is no valid record.
+fn decode_record(buf: &[u8]) -> Option<(u32, Record)> {
+    let read_u32 = |offset: usize| {
+        let mut bytes = [0; 4];
+        bytes.copy_from_slice(&buf[offset..offset + 4]);
+        u32::from_le_bytes(bytes)
+    };
+    if read_u32(0) != MAGIC || read_u32(RECORD_LEN - 4) != record_crc(&buf[..RECORD_LEN - 4]) {
+        return None;
+    }
+    let boot = Bank::from_usize(buf[8] as usize)?;
+    let states = [BankState::from_u8(buf[9])?, BankState::from_u8(buf[10])?];
+    Some((
+        read_u32(4),
+        Record {
+            boot: boot,
+            states: states,
+            tries: [buf[11], buf[12]],
+        },
+    ))
+}
+
+fn record_crc(data: &[u8]) -> u32 {
+    CrcAlg::Crc32
+        .params()
+        .and_then(|params| software_crc::compute_crc(&params, data))
+        .unwrap_or(0)
+}
+
+/// The boot record of dual bank firmware images, as seen from the running
+/// kernel.
+pub trait BootBanks<'a> {
+    fn set_client(&self, client: &'a BootBanksClient);
+
+    /// The bank the running kernel was booted from.
+    fn running_bank(&self) -> Bank;
+
+    /// Returns the state of `bank`, or `None` if the record has not been
+    /// restored.
+    fn state(&self, bank: Bank) -> Option<BankState>;
+
+    /// Has the bootloader boot `bank`, which must hold a complete image, on
+    /// trial from the next reset. If this returns `SUCCESS`, the client's
+    /// `record_written` is called once the change is persistent. Returns
+    /// `EINVAL` for the running bank, `EBUSY` if another change is in
+    /// progress or the record is being restored, and `FAIL` if it could not
+    /// be restored.
+    fn set_pending(&self, bank: Bank) -> ReturnCode;
+
+    /// Marks the running bank healthy, so that the bootloader keeps booting
+    /// it. Returns `EALREADY` if it is healthy and booted already, and
+    /// otherwise behaves like `set_pending`.
+    fn mark_healthy(&self) -> ReturnCode;
+}
+
+pub trait BootBanksClient {
+    /// Called when a change of the boot record completes.
+    fn record_written(&self, result: ReturnCode);
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum State {
+    Uninitialized,
+    /// Reading the page at this offset in the ring
+    Loading(usize),
+    Idle,
+    /// Writing the page at this offset in the ring
+    Writing(usize),
+    /// The record could not be restored
+    Failed,
+}
+
+pub struct FlashBootBanks<'a, F: hil::flash::Flash + 'static> {
+    driver: &'a F,
+    client: OptionalCell<&'a BootBanksClient>,
+    pagebuffer: TakeCell<'static, F::Page>,
+    first_page: usize,
+    num_pages: usize,
+    running: Bank,
+    state: Cell<State>,
+    record: Cell<Record>,
+    /// Generation of the newest record
+    generation: Cell<u32>,
+    /// Offset in the ring of the newest record, if there is one
+    newest: OptionalCell<usize>,
+    /// The record being written
+    pending: OptionalCell<Record>,
+}
+
+impl<F: hil::flash::Flash> FlashBootBanks<'a, F> {
+    /// Creates the boot record of a kernel running from bank `running`,
+    /// stored in the `num_pages` flash pages starting at `first_page`, which
+    /// neither bank may overlap. At least two pages are needed, so that the
+    /// previous record is kept while a new one is written.
+    pub fn new(
+        driver: &'a F,
+        pagebuffer: &'static mut F::Page,
+        first_page: usize,
+        num_pages: usize,
+        running: Bank,
+    ) -> FlashBootBanks<'a, F> {
+        assert!(num_pages >= 2);
+        assert!(pagebuffer.as_mut().len() >= RECORD_LEN);
+        let mut states = [BankState::Empty; 2];
+        states[running as usize] = BankState::Healthy;
+        FlashBootBanks {
+            driver: driver,
+            client: OptionalCell::empty(),
+            pagebuffer: TakeCell::new(pagebuffer),
+            first_page: first_page,
+            num_pages: num_pages,
+            running: running,
+            state: Cell::new(State::Uninitialized),
+            // Without a record, the running image is the factory image
+            record: Cell::new(Record {
+                boot: running,
+                states: states,
+                tries: [0; 2],
+            }),
+            generation: Cell::new(0),
+            newest: OptionalCell::empty(),
+            pending: OptionalCell::empty(),
+        }
+    }
+
+    /// Restores the boot record from flash. Until this completes the states
+    /// of the banks cannot be read or changed.
+    pub fn initialize(&self) -> ReturnCode {
+        if self.state.get() != State::Uninitialized {
+            return ReturnCode::EALREADY;
+        }
+        self.pagebuffer
+            .take()
+            .map_or(ReturnCode::ERESERVE, |pagebuffer| {
+                self.state.set(State::Loading(0));
+                let result = self.driver.read_page(self.first_page, pagebuffer);
+                if result != ReturnCode::SUCCESS {
+                    self.state.set(State::Failed);
+                }
+                result
+            })
+    }
+
+    fn check_ready(&self) -> ReturnCode {
+        match self.state.get() {
+            State::Idle => ReturnCode::SUCCESS,
+            State::Loading(_) | State::Writing(_) => ReturnCode::EBUSY,
+            State::Uninitialized | State::Failed => ReturnCode::FAIL,
+        }
+    }
+
+    /// Writes `record` as the next record of the ring.
+    fn update(&self, record: Record) -> ReturnCode {
+        self.pagebuffer
+            .take()
+            .map_or(ReturnCode::ERESERVE, |pagebuffer| {
+                encode_record(
+                    pagebuffer.as_mut(),
+                    self.generation.get().wrapping_add(1),
+                    &record,
+                );
+
+                let offset = self
+                    .newest
+                    .map_or(0, |newest| (*newest + 1) % self.num_pages);
+                self.pending.set(record);
+                self.state.set(State::Writing(offset));
+                let result = self.driver.write_page(self.first_page + offset, pagebuffer);
+                if result != ReturnCode::SUCCESS {
+                    self.pending.take();
+                    self.state.set(State::Idle);
+                }
+                result
+            })
+    }
+}
+
+impl<F: hil::flash::Flash> BootBanks<'a> for FlashBootBanks<'a, F> {
+    fn set_client(&self, client: &'a BootBanksClient) {
+        self.client.set(client);
+    }
+
+    fn running_bank(&self) -> Bank {
+        self.running
+    }
+
+    fn state(&self, bank: Bank) -> Option<BankState> {
+        match self.state.get() {
+            State::Idle | State::Writing(_) => Some(self.record.get().states[bank as usize]),
+            _ => None,
+        }
+    }
+
+    fn set_pending(&self, bank: Bank) -> ReturnCode {
+        let ready = self.check_ready();
+        if ready != ReturnCode::SUCCESS {
+            return ready;
+        }
+        if bank == self.running {
+            return ReturnCode::EINVAL;
+        }
+        let mut record = self.record.get();
+        record.boot = bank;
+        record.states[bank as usize] = BankState::Trial;
+        record.tries[bank as usize] = MAX_TRIES;
+        self.update(record)
+    }
+
+    fn mark_healthy(&self) -> ReturnCode {
+        let ready = self.check_ready();
+        if ready != ReturnCode::SUCCESS {
+            return ready;
+        }
+        let mut record = self.record.get();
+        let running = self.running as usize;
+        if record.boot == self.running && record.states[running] == BankState::Healthy {
+            return ReturnCode::EALREADY;
+        }
+        record.boot = self.running;
+        record.states[running] = BankState::Healthy;
+        record.tries[running] = 0;
+        self.update(record)
+    }
+}
+
+impl<F: hil::flash::Flash> hil::flash::Client<F> for FlashBootBanks<'a, F> {
+    fn read_complete(&self, pagebuffer: &'static mut F::Page, error: hil::flash::Error) {
+        let offset = match self.state.get() {
+            State::Loading(offset) => offset,
+            _ => {
+                self.pagebuffer.replace(pagebuffer);
+                return;
+            }
+        };
+        if error != hil::flash::Error::CommandComplete {
+            self.pagebuffer.replace(pagebuffer);
+            self.state.set(State::Failed);
+            return;
+        }
+
+        decode_record(pagebuffer.as_mut()).map(|(generation, record)| {
+            let newer = self
+                .newest
+                .map_or(true, |_| generation > self.generation.get());
+            if newer {
+                self.generation.set(generation);
+                self.newest.set(offset);
+                self.record.set(record);
+            }
+        });
+
+        if offset + 1 < self.num_pages {
+            self.state.set(State::Loading(offset + 1));
+            self.driver
+                .read_page(self.first_page + offset + 1, pagebuffer);
+        } else {
+            self.pagebuffer.replace(pagebuffer);
+            self.state.set(State::Idle);
+        }
+    }
+
+    fn write_complete(&self, pagebuffer: &'static mut F::Page, error: hil::flash::Error) {
+        self.pagebuffer.replace(pagebuffer);
+        let offset = match self.state.get() {
+            State::Writing(offset) => offset,
+            _ => return,
+        };
+        self.state.set(State::Idle);
+        self.pending.take().map(|record| {
+            let result = if error == hil::flash::Error::CommandComplete {
+                self.generation.set(self.generation.get().wrapping_add(1));
+                self.newest.set(offset);
+                self.record.set(record);
+                ReturnCode::SUCCESS
+            } else {
+                ReturnCode::FAIL
+            };
+            self.client.map(|client| client.record_written(result));
+        });
+    }
+
+    fn erase_complete(&self, _error: hil::flash::Error) {}
+}
+
+/// Marks the running bank healthy once it has been on trial for `trial_ms`
+/// without any process faulting. A bank that does not make it is booted
+/// again until it runs out of tries, after which the bootloader falls back
+/// to the other bank.
+pub struct TrialConfirm<'a, A: time::Alarm> {
+    banks: &'a BootBanks<'a>,
+    alarm: &'a A,
+    kernel: &'static kernel::Kernel,
+    trial_ms: u32,
+}
+
+impl<A: time::Alarm> TrialConfirm<'a, A> {
+    pub fn new(
+        banks: &'a BootBanks<'a>,
+        alarm: &'a A,
+        kernel: &'static kernel::Kernel,
+        trial_ms: u32,
+    ) -> TrialConfirm<'a, A> {
+        TrialConfirm {
+            banks: banks,
+            alarm: alarm,
+            kernel: kernel,
+            trial_ms: trial_ms,
+        }
+    }
+
+    /// Starts the trial, at boot.
+    pub fn start(&self) {
+        let interval = <A::Frequency>::ms_to_tics(self.trial_ms);
+        self.alarm
+            .set_alarm(self.alarm.now().wrapping_add(interval));
+    }
+}
+
+impl<A: time::Alarm> time::Client for TrialConfirm<'a, A> {
+    fn fired(&self) {
+        let running = self.banks.running_bank();
+        if self.banks.state(running) == Some(BankState::Trial)
+            && self.kernel.process_fault_count() == 0
+        {
+            self.banks.mark_healthy();
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct App {
+    callback: Option<Callback>,
+}
+
+pub struct BootBanksDriver<'a> {
+    banks: &'a BootBanks<'a>,
+    apps: Grant<App>,
+    /// The process whose change is in progress
+    current_app: OptionalCell<AppId>,
+}
+
+impl BootBanksDriver<'a> {
+    pub fn new(banks: &'a BootBanks<'a>, apps: Grant<App>) -> BootBanksDriver<'a> {
+        BootBanksDriver {
+            banks: banks,
+            apps: apps,
+            current_app: OptionalCell::empty(),
+        }
+    }
+
+    /// Starts a change of the boot record on behalf of `appid`.
+    fn change(&self, appid: AppId, pending: Option<Bank>) -> ReturnCode {
+        if self.current_app.is_some() {
+            return ReturnCode::EBUSY;
+        }
+        let result = match pending {
+            Some(bank) => self.banks.set_pending(bank),
+            None => self.banks.mark_healthy(),
+        };
+        if result == ReturnCode::SUCCESS {
+            self.current_app.set(appid);
+        }
+        result
+    }
+}
+
+impl BootBanksClient for BootBanksDriver<'a> {
+    fn record_written(&self, result: ReturnCode) {
+        self.current_app.take().map(|appid| {
+            let _ = self.apps.enter(appid, |app, _| {
+                app.callback
+                    .map(|mut callback| callback.schedule(usize::from(result), 0, 0));
+            });
+        });
+    }
+}
+
+impl Driver for BootBanksDriver<'a> {
+    /// Subscribe to changes of the boot record.
+    ///
+    /// ### `subscribe_num`
+    ///
+    /// - `0`: Set the callback called with the result when a change
+    ///        completes.
+    fn subscribe(
+        &self,
+        subscribe_num: usize,
+        callback: Option<Callback>,
+        appid: AppId,
+    ) -> ReturnCode {
+        match subscribe_num {
+            0 => self
+                .apps
+                .enter(appid, |app, _| {
+                    app.callback = callback;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or_else(|err| err.into()),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    /// Read and change the states of the banks.
+    ///
+    /// ### `command_num`
+    ///
+    /// - `0`: Driver check.
+    /// - `1`: Returns the bank the kernel runs from.
+    /// - `2`: Returns the state of bank `arg1`.
+    /// - `3`: Boots bank `arg1` on trial from the next reset.
+    /// - `4`: Marks the running bank healthy.
+    fn command(&self, command_num: usize, arg1: usize, _: usize, appid: AppId) -> ReturnCode {
+        match command_num {
+            0 => ReturnCode::SUCCESS,
+            1 => ReturnCode::SuccessWithValue {
+                value: self.banks.running_bank() as usize,
+            },
+            2 => match Bank::from_usize(arg1) {
+                Some(bank) => self.banks.state(bank).map_or(ReturnCode::EBUSY, |state| {
+                    ReturnCode::SuccessWithValue {
+                        value: state as usize,
+                    }
+                }),
+                None => ReturnCode::EINVAL,
+            },
+            3 => match Bank::from_usize(arg1) {
+                Some(bank) => self.change(appid, Some(bank)),
+                None => ReturnCode::EINVAL,
+            },
+            4 => self.change(appid, None),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+}
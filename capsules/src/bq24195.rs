@@ -0,0 +1,292 @@
+//! Driver for the TI BQ24195 battery charger.
+//!
+//! <http://www.ti.com/product/BQ24195>
+//!
+//! > The bq24195 is a highly-integrated switch-mode battery charge management
+//! > and system power path management device for 1 cell Li-Ion and
+//! > Li-polymer battery in a wide range of tablet and other portable devices.
+//!
+//! The charger keeps charging on its own with the settings in its registers.
+//! This driver implements `hil::charger::Charger` to turn charging on and
+//! off, limit the input current and read the status. The charger pulses its
+//! INT pin when its status changes or it detects a fault, and the driver then
+//! reads the status and passes it to the client.
+//!
+//! `initialize` turns off the I2C watchdog of the charger, which otherwise
+//! resets its registers to their defaults when they are not written for 40
+//! seconds.
+//!
+//! Usage
+//! -----
+//!
+//! ```rust
+//! let bq24195_i2c = static_init!(I2CDevice, I2CDevice::new(i2c_bus, 0x6B));
+//! let bq24195 = static_init!(
+//!     capsules::bq24195::BQ24195<'static>,
+//!     capsules::bq24195::BQ24195::new(
+//!         bq24195_i2c,
+//!         &sam4l::gpio::PA[04],
+//!         &mut capsules::bq24195::BUFFER
+//!     )
+//! );
+//! bq24195_i2c.set_client(bq24195);
+//! sam4l::gpio::PA[04].set_client(bq24195);
+//! bq24195.initialize();
+//! ```
+
+use core::cell::Cell;
+use kernel::common::cells::{OptionalCell, TakeCell};
+use kernel::hil::charger::{self, ChargeState, ChargerFault, ChargerStatus, InputSource};
+use kernel::hil::gpio;
+use kernel::hil::i2c;
+use kernel::ReturnCode;
+
+pub static mut BUFFER: [u8; 2] = [0; 2];
+
+#[allow(dead_code)]
+enum Registers {
+    InputSourceControl = 0x00,
+    PowerOnConfiguration = 0x01,
+    ChargeCurrentControl = 0x02,
+    ChargeTerminationTimerControl = 0x05,
+    SystemStatus = 0x08,
+    Fault = 0x09,
+}
+
+/// IINLIM, in the input source control register
+const INPUT_LIMIT_MASK: u8 = 0x07;
+/// The input current limits, in mA, of each value of IINLIM
+const INPUT_LIMITS: [u32; 8] = [100, 150, 500, 900, 1200, 1500, 2000, 3000];
+
+/// CHG_CONFIG, in the power-on configuration register
+const CHG_CONFIG_MASK: u8 = 0x30;
+const CHG_CONFIG_CHARGE: u8 = 0x10;
+
+/// WATCHDOG, in the charge termination/timer control register
+const WATCHDOG_MASK: u8 = 0x30;
+
+#[derive(Clone, Copy, PartialEq)]
+enum State {
+    Idle,
+
+    /// Reading the system status and fault registers
+    ReadingStatus,
+
+    /// Reading `register`, to change its bits in `mask` to `value`. The
+    /// client is told when the change is done if `report` is set, which it
+    /// is not for `initialize`.
+    Updating {
+        register: u8,
+        mask: u8,
+        value: u8,
+        report: bool,
+    },
+    /// Writing the changed register
+    Writing {
+        report: bool,
+    },
+}
+
+pub struct BQ24195<'a> {
+    i2c: &'a i2c::I2CDevice,
+    interrupt_pin: &'a gpio::InterruptPin,
+    client: OptionalCell<&'a charger::Client>,
+    state: Cell<State>,
+    buffer: TakeCell<'static, [u8]>,
+    /// Set when the status changes while the I2C bus is busy, to read it when
+    /// it is done
+    status_pending: Cell<bool>,
+}
+
+impl BQ24195<'a> {
+    pub fn new(
+        i2c: &'a i2c::I2CDevice,
+        interrupt_pin: &'a gpio::InterruptPin,
+        buffer: &'static mut [u8],
+    ) -> BQ24195<'a> {
+        BQ24195 {
+            i2c: i2c,
+            interrupt_pin: interrupt_pin,
+            client: OptionalCell::empty(),
+            state: Cell::new(State::Idle),
+            buffer: TakeCell::new(buffer),
+            status_pending: Cell::new(false),
+        }
+    }
+
+    /// Turns off the I2C watchdog of the charger and starts listening to its
+    /// INT pin.
+    pub fn initialize(&self) -> ReturnCode {
+        self.interrupt_pin.make_input();
+        self.interrupt_pin
+            .enable_interrupts(gpio::InterruptEdge::FallingEdge);
+        self.update(
+            Registers::ChargeTerminationTimerControl,
+            WATCHDOG_MASK,
+            0,
+            false,
+        )
+    }
+
+    fn update(&self, register: Registers, mask: u8, value: u8, report: bool) -> ReturnCode {
+        if self.state.get() != State::Idle {
+            return ReturnCode::EBUSY;
+        }
+        self.buffer.take().map_or(ReturnCode::ENOMEM, |buffer| {
+            let register = register as u8;
+            self.i2c.enable();
+            buffer[0] = register;
+            self.i2c.write_read(buffer, 1, 1);
+            self.state.set(State::Updating {
+                register: register,
+                mask: mask,
+                value: value,
+                report: report,
+            });
+            ReturnCode::SUCCESS
+        })
+    }
+
+    fn start_status_read(&self) -> ReturnCode {
+        if self.state.get() != State::Idle {
+            self.status_pending.set(true);
+            return ReturnCode::SUCCESS;
+        }
+        self.buffer.take().map_or(ReturnCode::ENOMEM, |buffer| {
+            self.i2c.enable();
+            buffer[0] = Registers::SystemStatus as u8;
+            self.i2c.write_read(buffer, 1, 2);
+            self.state.set(State::ReadingStatus);
+            ReturnCode::SUCCESS
+        })
+    }
+
+    fn decode_status(system_status: u8, fault: u8) -> ChargerStatus {
+        let input = match (system_status >> 6) & 0x3 {
+            1 => InputSource::UsbHost,
+            2 => InputSource::Adapter,
+            3 => InputSource::Otg,
+            _ => InputSource::Unknown,
+        };
+        let state = match (system_status >> 4) & 0x3 {
+            1 => ChargeState::PreCharge,
+            2 => ChargeState::FastCharge,
+            3 => ChargeState::Done,
+            _ => ChargeState::NotCharging,
+        };
+        let fault = if fault & 0x40 != 0 {
+            Some(ChargerFault::Boost)
+        } else if fault & 0x08 != 0 {
+            Some(ChargerFault::BatteryOvervoltage)
+        } else if fault & 0x07 != 0 {
+            Some(ChargerFault::BatteryTemperature)
+        } else {
+            match (fault >> 4) & 0x3 {
+                1 => Some(ChargerFault::Input),
+                2 => Some(ChargerFault::Thermal),
+                3 => Some(ChargerFault::SafetyTimer),
+                _ => None,
+            }
+        };
+        ChargerStatus {
+            state: state,
+            input: input,
+            power_good: system_status & 0x04 != 0,
+            fault: fault,
+        }
+    }
+
+    fn done(&self, buffer: &'static mut [u8]) {
+        self.buffer.replace(buffer);
+        self.i2c.disable();
+        self.state.set(State::Idle);
+        if self.status_pending.take() {
+            self.start_status_read();
+        }
+    }
+}
+
+impl i2c::I2CClient for BQ24195<'a> {
+    fn command_complete(&self, buffer: &'static mut [u8], error: i2c::Error) {
+        match self.state.get() {
+            State::Idle => {
+                self.buffer.replace(buffer);
+            }
+            State::ReadingStatus => {
+                let status = BQ24195::decode_status(buffer[0], buffer[1]);
+                self.done(buffer);
+                if error == i2c::Error::CommandComplete {
+                    self.client.map(|client| client.status(status));
+                }
+            }
+            State::Updating {
+                register,
+                mask,
+                value,
+                report,
+            } => {
+                if error != i2c::Error::CommandComplete {
+                    self.done(buffer);
+                    if report {
+                        self.client
+                            .map(|client| client.command_done(ReturnCode::FAIL));
+                    }
+                    return;
+                }
+                buffer[1] = (buffer[0] & !mask) | (value & mask);
+                buffer[0] = register;
+                self.i2c.write(buffer, 2);
+                self.state.set(State::Writing { report });
+            }
+            State::Writing { report } => {
+                self.done(buffer);
+                if report {
+                    let result = if error == i2c::Error::CommandComplete {
+                        ReturnCode::SUCCESS
+                    } else {
+                        ReturnCode::FAIL
+                    };
+                    self.client.map(|client| client.command_done(result));
+                }
+            }
+        }
+    }
+}
+
+impl gpio::Client for BQ24195<'a> {
+    fn fired(&self) {
+        self.start_status_read();
+    }
+}
+
+impl charger::Charger<'a> for BQ24195<'a> {
+    fn set_client(&self, client: &'a charger::Client) {
+        self.client.set(client);
+    }
+
+    fn set_charging(&self, enable: bool) -> ReturnCode {
+        let value = if enable { CHG_CONFIG_CHARGE } else { 0 };
+        self.update(
+            Registers::PowerOnConfiguration,
+            CHG_CONFIG_MASK,
+            value,
+            true,
+        )
+    }
+
+    fn set_input_current_limit(&self, limit_ma: u32) -> ReturnCode {
+        match INPUT_LIMITS.iter().rposition(|limit| *limit <= limit_ma) {
+            None => ReturnCode::EINVAL,
+            Some(iinlim) => self.update(
+                Registers::InputSourceControl,
+                INPUT_LIMIT_MASK,
+                iinlim as u8,
+                true,
+            ),
+        }
+    }
+
+    fn read_status(&self) -> ReturnCode {
+        self.start_status_read()
+    }
+}
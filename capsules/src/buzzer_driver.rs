@@ -135,7 +135,7 @@ impl<A: hil::time::Alarm> Buzzer<'a, A> {
                 }
 
                 // Now start a timer so we know when to stop the PWM.
-                let interval = (duration_ms as u32) * <A::Frequency>::frequency() / 1000;
+                let interval = <A::Frequency>::ms_to_tics(duration_ms as u32);
                 let tics = self.alarm.now().wrapping_add(interval);
                 self.alarm.set_alarm(tics);
                 ReturnCode::SUCCESS
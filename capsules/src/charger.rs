@@ -0,0 +1,165 @@
+//! Provides userspace with access to the battery charger.
+//!
+//! Processes can read the status of the charger, be told when it changes, and
+//! turn charging on and off or limit the input current, e.g. to follow what a
+//! USB host allows. Only one command runs on the charger at a time; the
+//! process that started it is told when it is done.
+//!
+//! Usage
+//! -----
+//!
+//! ```rust
+//! let charger = static_init!(
+//!     capsules::charger::ChargerDriver<'static>,
+//!     capsules::charger::ChargerDriver::new(bq24195, kernel::Grant::create())
+//! );
+//! kernel::hil::charger::Charger::set_client(bq24195, charger);
+//! ```
+//!
+//! Syscall Interface
+//! -----------------
+//!
+//! - Stability: 1 - Experimental
+//!
+//! ### Subscribe
+//!
+//! - `0`: Called with `(0, status, fault)` with the status of the charger,
+//!   after command `1` and whenever it changes, and with `(1, result, 0)`
+//!   when command `2` or `3` is done. `status` is the charge state (0 not
+//!   charging, 1 pre-charge, 2 fast charge, 3 done) in bits 0-3, the input
+//!   (0 unknown, 1 USB host, 2 adapter, 3 OTG) in bits 4-7, and whether the
+//!   input is good in bit 8. `fault` is 0 for none, or 1 + the fault (input,
+//!   thermal, safety timer, battery overvoltage, battery temperature, boost).
+//!
+//! ### Command
+//!
+//! - `0`: Driver check.
+//! - `1`: Read the status of the charger.
+//! - `2`: Turn charging on (`data` is 1) or off (`data` is 0).
+//! - `3`: Limit the input current to `data` mA, or to the highest limit the
+//!   charger supports below it.
+
+use kernel::common::cells::OptionalCell;
+use kernel::hil::charger::{self, ChargeState, ChargerFault, ChargerStatus, InputSource};
+use kernel::{AppId, Callback, Driver, Grant, ReturnCode};
+
+/// Syscall driver number.
+use crate::driver;
+pub const DRIVER_NUM: usize = driver::NUM::Charger as usize;
+
+#[derive(Default)]
+pub struct App {
+    callback: Option<Callback>,
+}
+
+pub struct ChargerDriver<'a> {
+    charger: &'a charger::Charger<'a>,
+    apps: Grant<App>,
+    /// The process whose command is running
+    owner: OptionalCell<AppId>,
+}
+
+impl ChargerDriver<'a> {
+    pub fn new(charger: &'a charger::Charger<'a>, grant: Grant<App>) -> ChargerDriver<'a> {
+        ChargerDriver {
+            charger: charger,
+            apps: grant,
+            owner: OptionalCell::empty(),
+        }
+    }
+
+    fn encode_status(status: &ChargerStatus) -> usize {
+        let state = match status.state {
+            ChargeState::NotCharging => 0,
+            ChargeState::PreCharge => 1,
+            ChargeState::FastCharge => 2,
+            ChargeState::Done => 3,
+        };
+        let input = match status.input {
+            InputSource::Unknown => 0,
+            InputSource::UsbHost => 1,
+            InputSource::Adapter => 2,
+            InputSource::Otg => 3,
+        };
+        state | input << 4 | (status.power_good as usize) << 8
+    }
+
+    fn encode_fault(fault: Option<ChargerFault>) -> usize {
+        match fault {
+            None => 0,
+            Some(ChargerFault::Input) => 1,
+            Some(ChargerFault::Thermal) => 2,
+            Some(ChargerFault::SafetyTimer) => 3,
+            Some(ChargerFault::BatteryOvervoltage) => 4,
+            Some(ChargerFault::BatteryTemperature) => 5,
+            Some(ChargerFault::Boost) => 6,
+        }
+    }
+
+    /// Runs `command` on the charger for `appid`, unless another process's
+    /// command is running.
+    fn start<F: FnOnce() -> ReturnCode>(&self, appid: AppId, command: F) -> ReturnCode {
+        if self.owner.is_some() {
+            return ReturnCode::EBUSY;
+        }
+        let result = command();
+        if result == ReturnCode::SUCCESS {
+            self.owner.set(appid);
+        }
+        result
+    }
+}
+
+impl charger::Client for ChargerDriver<'a> {
+    fn status(&self, status: ChargerStatus) {
+        let encoded = ChargerDriver::encode_status(&status);
+        let fault = ChargerDriver::encode_fault(status.fault);
+        self.apps.each(|app| {
+            app.callback.map(|mut cb| cb.schedule(0, encoded, fault));
+        });
+    }
+
+    fn command_done(&self, result: ReturnCode) {
+        self.owner.take().map(|appid| {
+            let _ = self.apps.enter(appid, |app, _| {
+                app.callback
+                    .map(|mut cb| cb.schedule(1, usize::from(result), 0));
+            });
+        });
+    }
+}
+
+impl Driver for ChargerDriver<'a> {
+    fn subscribe(
+        &self,
+        subscribe_num: usize,
+        callback: Option<Callback>,
+        app_id: AppId,
+    ) -> ReturnCode {
+        match subscribe_num {
+            0 => self
+                .apps
+                .enter(app_id, |app, _| {
+                    app.callback = callback;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or_else(|err| err.into()),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    fn command(&self, command_num: usize, data: usize, _: usize, appid: AppId) -> ReturnCode {
+        match command_num {
+            0 /* check if present */ => ReturnCode::SUCCESS,
+
+            // The status goes to every process
+            1 => self.charger.read_status(),
+
+            2 => self.start(appid, || self.charger.set_charging(data != 0)),
+
+            3 => self.start(appid, || self.charger.set_input_current_limit(data as u32)),
+
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+}
@@ -0,0 +1,639 @@
+//! Typed configuration values, changed in transactions and persisted in an
+//! emulated EEPROM, and a system call driver that allows processes to read,
+//! change and watch them.
+//!
+//! The board declares the configuration as a schema: a version and a list of
+//! keys, each with a type and a default value. A key is identified by its
+//! index in the list, and stored in an EEPROM cell of its own, after a header
+//! cell that holds the version of the schema the values were written with.
+//!
+//! Keys are only ever added to the end of the list, along with a new schema
+//! version that each new key records as the version it was added in. At boot
+//! the stored values of keys that the stored schema already had are kept, and
+//! every other key, or a key whose stored value is not valid for its type,
+//! starts from its default. Keys that are no longer used stay in the list, so
+//! that the keys after them keep their index.
+//!
+//! Values are changed in a transaction: `begin` opens it, `set` stages values
+//! and `commit` persists all of them at once, so after a reset either every
+//! value of a transaction is seen or none is. Only one transaction is open at
+//! a time, and until it is committed readers see the committed values. Once
+//! a commit is persistent, each client is told of every value it changed.
+//!
+//! Usage
+//! -----
+//!
+//! ```rust
+//! static CONFIG_KEYS: [capsules::config_store::ConfigKey; 2] = [
+//!     // Sensor sampling period in ms, since schema version 1
+//!     capsules::config_store::ConfigKey {
+//!         kind: capsules::config_store::ConfigType::U16,
+//!         default: 1000,
+//!         since: 1,
+//!     },
+//!     // Temperature offset in tenths of a degree, added in version 2
+//!     capsules::config_store::ConfigKey {
+//!         kind: capsules::config_store::ConfigType::I16,
+//!         default: 0,
+//!         since: 2,
+//!     },
+//! ];
+//!
+//! let config = static_init!(
+//!     capsules::config_store::EepromConfigStore<'static>,
+//!     capsules::config_store::EepromConfigStore::new(eeprom, &CONFIG_KEYS, 2)
+//! );
+//! eeprom.set_client(config);
+//! let config_driver = static_init!(
+//!     capsules::config_store::ConfigStoreDriver<'static>,
+//!     capsules::config_store::ConfigStoreDriver::new(config, kernel::Grant::create())
+//! );
+//! let clients = static_init!(
+//!     [&'static capsules::config_store::ConfigClient; 1],
+//!     [config_driver]
+//! );
+//! config.set_clients(clients);
+//! eeprom.initialize();
+//! ```
+//!
+//! Syscall Interface
+//! -----------------
+//!
+//! - Stability: 0 - Draft
+//!
+//! ### Allow
+//!
+//! - `0`: The buffer that values are read into, as 4 byte little endian
+//!        integers.
+//!
+//! ### Subscribe
+//!
+//! - `0`: Called with the key and its new value when a committed transaction
+//!        changed a value.
+//! - `1`: Called with the result when the commit of the process's
+//!        transaction completes.
+//!
+//! ### Command
+//!
+//! - `0`: Driver check.
+//! - `1`: Returns the number of keys.
+//! - `2`: Writes the value of key `arg1` to the allowed buffer.
+//! - `3`: Returns the type of key `arg1`.
+//! - `4`: Begins a transaction.
+//! - `5`: Stages value `arg2` for key `arg1`.
+//! - `6`: Commits the transaction.
+//! - `7`: Aborts the transaction.
+
+use core::cell::Cell;
+use kernel::common::cells::OptionalCell;
+use kernel::{AppId, AppSlice, Callback, Driver, Grant, ReturnCode, Shared};
+
+use crate::eeprom_emulation::{self, Eeprom, EepromClient};
+
+/// Syscall driver number.
+use crate::driver;
+pub const DRIVER_NUM: usize = driver::NUM::ConfigStore as usize;
+
+/// The header cell takes the first EEPROM cell
+pub const MAX_KEYS: usize = eeprom_emulation::NUM_CELLS - 1;
+
+/// Length of a value, as exchanged with processes.
+pub const VALUE_LEN: usize = 4;
+
+/// Marks the header cell, whose low 16 bits are the schema version
+const HEADER_MAGIC: u32 = 0xC0F6;
+
+/// The type of a configuration value. Values are stored as 32-bit integers,
+/// signed ones in two's complement.
+#[derive(Clone, Copy, PartialEq)]
+pub enum ConfigType {
+    Bool = 0,
+    U8 = 1,
+    U16 = 2,
+    U32 = 3,
+    I8 = 4,
+    I16 = 5,
+    I32 = 6,
+}
+
+impl ConfigType {
+    /// Returns whether `value` is a value of this type.
+    pub fn is_valid(self, value: u32) -> bool {
+        let signed = value as i32;
+        match self {
+            ConfigType::Bool => value <= 1,
+            ConfigType::U8 => value <= 0xff,
+            ConfigType::U16 => value <= 0xffff,
+            ConfigType::I8 => signed >= -0x80 && signed <= 0x7f,
+            ConfigType::I16 => signed >= -0x8000 && signed <= 0x7fff,
+            ConfigType::U32 | ConfigType::I32 => true,
+        }
+    }
+}
+
+/// A key of the configuration schema.
+pub struct ConfigKey {
+    pub kind: ConfigType,
+    pub default: u32,
+    /// The schema version the key was added in
+    pub since: u16,
+}
+
+/// Typed configuration values, changed in transactions.
+pub trait ConfigStore<'a> {
+    /// Sets the capsules told of commits and changed values.
+    fn set_clients(&self, clients: &'a [&'a ConfigClient]);
+
+    fn num_keys(&self) -> usize;
+
+    /// Returns the type of `key`, or `None` if there is no such key.
+    fn kind(&self, key: usize) -> Option<ConfigType>;
+
+    /// Returns the committed value of `key`, or `None` if there is no such
+    /// key or the values have not been restored.
+    fn get(&self, key: usize) -> Option<u32>;
+
+    /// Opens a transaction. Returns `EBUSY` if a transaction is already open
+    /// or the values are being restored, and `FAIL` if they could not be
+    /// restored.
+    fn begin(&self) -> ReturnCode;
+
+    /// Stages `value` for `key` in the open transaction. Returns `EINVAL` if
+    /// there is no such key or `value` is not of its type, `EBUSY` if the
+    /// transaction is being committed and `EOFF` if no transaction is open.
+    fn set(&self, key: usize, value: u32) -> ReturnCode;
+
+    /// Persists the values staged in the open transaction. If this returns
+    /// `SUCCESS`, the clients' `committed` is called once they are
+    /// persistent, and the transaction is closed unless the commit failed.
+    /// Returns `EALREADY` and closes the transaction if it changes no value.
+    fn commit(&self) -> ReturnCode;
+
+    /// Closes the open transaction, discarding its staged values. Returns
+    /// `EBUSY` if the transaction is being committed.
+    fn abort(&self) -> ReturnCode;
+}
+
+/// A capsule that changes or follows the configuration.
+pub trait ConfigClient {
+    /// Called when the commit of the open transaction completes, which only
+    /// concerns the client that began it. If `result` is not `SUCCESS` the
+    /// transaction is still open, with the values it staged.
+    fn committed(&self, result: ReturnCode);
+
+    /// Called for each value that a committed transaction changed.
+    fn config_changed(&self, key: usize, value: u32);
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum State {
+    /// Waiting for the EEPROM to restore its cells
+    Loading,
+    /// Persisting the values reset by a schema change
+    Migrating,
+    Idle,
+    Committing,
+    /// The values could not be restored
+    Failed,
+}
+
+pub struct EepromConfigStore<'a> {
+    eeprom: &'a Eeprom<'a>,
+    keys: &'a [ConfigKey],
+    version: u16,
+    state: Cell<State>,
+    clients: OptionalCell<&'a [&'a ConfigClient]>,
+    /// Whether a transaction is open
+    open: Cell<bool>,
+    /// The values staged in the open transaction. While it is committed,
+    /// they hold the previous values instead, which are put back if the
+    /// commit fails.
+    staged: [Cell<Option<u32>>; MAX_KEYS],
+    /// The mask of the keys changed by the commit in progress
+    changed: Cell<u32>,
+}
+
+impl EepromConfigStore<'a> {
+    /// Creates a store for the `keys` of schema `version`, in `eeprom`, of
+    /// which it must be the only client.
+    pub fn new(
+        eeprom: &'a Eeprom<'a>,
+        keys: &'a [ConfigKey],
+        version: u16,
+    ) -> EepromConfigStore<'a> {
+        assert!(keys.len() <= MAX_KEYS && keys.len() < eeprom.num_cells());
+        assert!(keys.iter().all(|key| key.kind.is_valid(key.default)));
+        EepromConfigStore {
+            eeprom: eeprom,
+            keys: keys,
+            version: version,
+            state: Cell::new(State::Loading),
+            clients: OptionalCell::empty(),
+            open: Cell::new(false),
+            staged: Default::default(),
+            changed: Cell::new(0),
+        }
+    }
+
+    fn cell(key: usize) -> usize {
+        key + 1
+    }
+
+    /// Brings the restored values up to the schema, and starts persisting
+    /// them if that changed any.
+    fn migrate(&self) {
+        let stored_version = self
+            .eeprom
+            .read(0)
+            .filter(|header| header >> 16 == HEADER_MAGIC)
+            .map(|header| header as u16);
+        let mut migrated = false;
+        for (index, key) in self.keys.iter().enumerate() {
+            let cell = Self::cell(index);
+            let keep = match (stored_version, self.eeprom.read(cell)) {
+                (Some(version), Some(value)) => key.since <= version && key.kind.is_valid(value),
+                _ => false,
+            };
+            if !keep {
+                self.eeprom.write(cell, key.default);
+                migrated = true;
+            }
+        }
+        if stored_version != Some(self.version) {
+            self.eeprom
+                .write(0, HEADER_MAGIC << 16 | self.version as u32);
+            migrated = true;
+        }
+
+        if migrated && self.eeprom.commit() == ReturnCode::SUCCESS {
+            self.state.set(State::Migrating);
+        } else {
+            // Values that could not be persisted are persisted by the next
+            // commit
+            self.state.set(State::Idle);
+        }
+    }
+
+    /// Swaps the values of the keys changed by the commit in progress between
+    /// the EEPROM and `staged`.
+    fn swap_changed(&self) {
+        for (index, staged) in self.staged.iter().enumerate() {
+            if self.changed.get() & (1 << index) != 0 {
+                let cell = Self::cell(index);
+                let current = self.eeprom.read(cell);
+                staged.get().map(|value| self.eeprom.write(cell, value));
+                staged.set(current);
+            }
+        }
+    }
+
+    fn close(&self) {
+        for staged in self.staged.iter() {
+            staged.set(None);
+        }
+        self.open.set(false);
+    }
+}
+
+impl ConfigStore<'a> for EepromConfigStore<'a> {
+    fn set_clients(&self, clients: &'a [&'a ConfigClient]) {
+        self.clients.set(clients);
+    }
+
+    fn num_keys(&self) -> usize {
+        self.keys.len()
+    }
+
+    fn kind(&self, key: usize) -> Option<ConfigType> {
+        self.keys.get(key).map(|key| key.kind)
+    }
+
+    fn get(&self, key: usize) -> Option<u32> {
+        if key >= self.keys.len() {
+            return None;
+        }
+        match self.state.get() {
+            State::Migrating | State::Idle => self.eeprom.read(Self::cell(key)),
+            // The EEPROM already holds the values being committed
+            State::Committing => self.staged[key]
+                .get()
+                .or_else(|| self.eeprom.read(Self::cell(key))),
+            State::Loading | State::Failed => None,
+        }
+    }
+
+    fn begin(&self) -> ReturnCode {
+        match self.state.get() {
+            State::Migrating | State::Idle if !self.open.get() => {
+                self.open.set(true);
+                ReturnCode::SUCCESS
+            }
+            State::Failed => ReturnCode::FAIL,
+            _ => ReturnCode::EBUSY,
+        }
+    }
+
+    fn set(&self, key: usize, value: u32) -> ReturnCode {
+        if !self.open.get() {
+            return ReturnCode::EOFF;
+        }
+        if self.state.get() == State::Committing {
+            return ReturnCode::EBUSY;
+        }
+        match self.keys.get(key) {
+            Some(config_key) if config_key.kind.is_valid(value) => {
+                self.staged[key].set(Some(value));
+                ReturnCode::SUCCESS
+            }
+            _ => ReturnCode::EINVAL,
+        }
+    }
+
+    fn commit(&self) -> ReturnCode {
+        if !self.open.get() {
+            return ReturnCode::EOFF;
+        }
+        if self.state.get() != State::Idle {
+            return ReturnCode::EBUSY;
+        }
+
+        let mut changed = 0;
+        for (index, staged) in self.staged.iter().enumerate() {
+            let different = staged.get().map_or(false, |value| {
+                self.eeprom.read(Self::cell(index)) != Some(value)
+            });
+            if different {
+                changed |= 1 << index;
+            }
+        }
+        if changed == 0 {
+            self.close();
+            return ReturnCode::EALREADY;
+        }
+
+        self.changed.set(changed);
+        self.swap_changed();
+        let result = self.eeprom.commit();
+        if result == ReturnCode::SUCCESS {
+            self.state.set(State::Committing);
+        } else {
+            self.swap_changed();
+        }
+        result
+    }
+
+    fn abort(&self) -> ReturnCode {
+        if self.state.get() == State::Committing {
+            return ReturnCode::EBUSY;
+        }
+        self.close();
+        ReturnCode::SUCCESS
+    }
+}
+
+impl EepromClient for EepromConfigStore<'a> {
+    fn initialized(&self, result: ReturnCode) {
+        if result == ReturnCode::SUCCESS {
+            self.migrate();
+        } else {
+            self.state.set(State::Failed);
+        }
+    }
+
+    fn committed(&self, result: ReturnCode) {
+        let state = self.state.get();
+        self.state.set(State::Idle);
+        if state != State::Committing {
+            return;
+        }
+
+        if result == ReturnCode::SUCCESS {
+            self.close();
+        } else {
+            self.swap_changed();
+        }
+        self.clients.map(|clients| {
+            for client in clients.iter() {
+                client.committed(result);
+            }
+            if result != ReturnCode::SUCCESS {
+                return;
+            }
+            let changed = self.changed.get();
+            for index in (0..self.keys.len()).filter(|index| changed & (1 << *index) != 0) {
+                let value = self.eeprom.read(Self::cell(index)).unwrap_or(0);
+                for client in clients.iter() {
+                    client.config_changed(index, value);
+                }
+            }
+        });
+    }
+}
+
+#[derive(Default)]
+pub struct App {
+    changed_callback: Option<Callback>,
+    committed_callback: Option<Callback>,
+    buffer: Option<AppSlice<Shared, u8>>,
+}
+
+pub struct ConfigStoreDriver<'a> {
+    config: &'a ConfigStore<'a>,
+    apps: Grant<App>,
+    /// The process whose transaction is open
+    current_app: OptionalCell<AppId>,
+}
+
+impl ConfigStoreDriver<'a> {
+    pub fn new(config: &'a ConfigStore<'a>, apps: Grant<App>) -> ConfigStoreDriver<'a> {
+        ConfigStoreDriver {
+            config: config,
+            apps: apps,
+            current_app: OptionalCell::empty(),
+        }
+    }
+
+    /// Aborts the transaction of a process that no longer exists.
+    fn abort_stale(&self) {
+        let stale = self
+            .current_app
+            .map_or(false, |appid| self.apps.enter(*appid, |_, _| ()).is_err());
+        if stale && self.config.abort() == ReturnCode::SUCCESS {
+            self.current_app.clear();
+        }
+    }
+
+    /// Returns whether `appid` holds the open transaction, or the error to
+    /// return to it.
+    fn check_owner(&self, appid: AppId) -> ReturnCode {
+        self.current_app.map_or(ReturnCode::EOFF, |owner| {
+            if *owner == appid {
+                ReturnCode::SUCCESS
+            } else {
+                ReturnCode::EBUSY
+            }
+        })
+    }
+
+    fn begin(&self, appid: AppId) -> ReturnCode {
+        self.abort_stale();
+        if self.current_app.is_some() {
+            return ReturnCode::EBUSY;
+        }
+        let result = self.config.begin();
+        if result == ReturnCode::SUCCESS {
+            self.current_app.set(appid);
+        }
+        result
+    }
+}
+
+impl ConfigClient for ConfigStoreDriver<'a> {
+    fn committed(&self, result: ReturnCode) {
+        self.current_app.map(|appid| {
+            let _ = self.apps.enter(*appid, |app, _| {
+                app.committed_callback
+                    .map(|mut callback| callback.schedule(usize::from(result), 0, 0));
+            });
+        });
+        if result == ReturnCode::SUCCESS {
+            self.current_app.clear();
+        }
+    }
+
+    fn config_changed(&self, key: usize, value: u32) {
+        self.apps.each(|app| {
+            app.changed_callback
+                .map(|mut callback| callback.schedule(key, value as usize, 0));
+        });
+    }
+}
+
+impl Driver for ConfigStoreDriver<'a> {
+    /// Setup the buffer that values are read into.
+    ///
+    /// ### `allow_num`
+    ///
+    /// - `0`: The buffer that values are read into, as 4 byte little endian
+    ///        integers.
+    fn allow(
+        &self,
+        appid: AppId,
+        allow_num: usize,
+        slice: Option<AppSlice<Shared, u8>>,
+    ) -> ReturnCode {
+        match allow_num {
+            0 => self
+                .apps
+                .enter(appid, |app, _| {
+                    app.buffer = slice;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or_else(|err| err.into()),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    /// Subscribe to configuration changes and commits.
+    ///
+    /// ### `subscribe_num`
+    ///
+    /// - `0`: Set the callback called with the key and the new value of each
+    ///        value changed by a committed transaction.
+    /// - `1`: Set the callback called with the result when the commit of the
+    ///        process's transaction completes.
+    fn subscribe(
+        &self,
+        subscribe_num: usize,
+        callback: Option<Callback>,
+        appid: AppId,
+    ) -> ReturnCode {
+        match subscribe_num {
+            0 => self
+                .apps
+                .enter(appid, |app, _| {
+                    app.changed_callback = callback;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or_else(|err| err.into()),
+            1 => self
+                .apps
+                .enter(appid, |app, _| {
+                    app.committed_callback = callback;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or_else(|err| err.into()),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    /// Read and change the configuration.
+    ///
+    /// ### `command_num`
+    ///
+    /// - `0`: Driver check.
+    /// - `1`: Returns the number of keys.
+    /// - `2`: Writes the value of key `arg1` to the allowed buffer.
+    /// - `3`: Returns the type of key `arg1`.
+    /// - `4`: Begins a transaction.
+    /// - `5`: Stages value `arg2` for key `arg1`.
+    /// - `6`: Commits the transaction.
+    /// - `7`: Aborts the transaction.
+    fn command(&self, command_num: usize, arg1: usize, arg2: usize, appid: AppId) -> ReturnCode {
+        match command_num {
+            0 => ReturnCode::SUCCESS,
+            1 => ReturnCode::SuccessWithValue {
+                value: self.config.num_keys(),
+            },
+            2 => {
+                let value = match self.config.get(arg1) {
+                    Some(value) => value,
+                    None if arg1 < self.config.num_keys() => return ReturnCode::EBUSY,
+                    None => return ReturnCode::EINVAL,
+                };
+                self.apps
+                    .enter(appid, |app, _| {
+                        app.buffer.as_mut().map_or(ReturnCode::ENOMEM, |buffer| {
+                            if buffer.len() < VALUE_LEN {
+                                return ReturnCode::ESIZE;
+                            }
+                            buffer.as_mut()[..VALUE_LEN].copy_from_slice(&value.to_le_bytes());
+                            ReturnCode::SUCCESS
+                        })
+                    })
+                    .unwrap_or_else(|err| err.into())
+            }
+            3 => self.config.kind(arg1).map_or(ReturnCode::EINVAL, |kind| {
+                ReturnCode::SuccessWithValue {
+                    value: kind as usize,
+                }
+            }),
+            4 => self.begin(appid),
+            5 => match self.check_owner(appid) {
+                ReturnCode::SUCCESS => self.config.set(arg1, arg2 as u32),
+                err => err,
+            },
+            6 => match self.check_owner(appid) {
+                ReturnCode::SUCCESS => {
+                    let result = self.config.commit();
+                    if result == ReturnCode::EALREADY {
+                        self.current_app.clear();
+                    }
+                    result
+                }
+                err => err,
+            },
+            7 => match self.check_owner(appid) {
+                ReturnCode::SUCCESS => {
+                    let result = self.config.abort();
+                    if result == ReturnCode::SUCCESS {
+                        self.current_app.clear();
+                    }
+                    result
+                }
+                err => err,
+            },
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+}
@@ -3,17 +3,18 @@
 //! Setup
 //! -----
 //!
-//! You need a device that provides the `hil::uart::UART` trait.
+//! You need a device that provides the `hil::uart::UartDataAdvanced` trait,
+//! such as a `virtual_uart::UartDevice`.
 //!
 //! ```rust
 //! let console = static_init!(
 //!     Console<usart::USART>,
 //!     Console::new(&usart::USART0,
 //!                  115200,
-//!                  &mut console::WRITE_BUF,
 //!                  &mut console::READ_BUF,
 //!                  kernel::Grant::create()));
 //! hil::uart::UART::set_client(&usart::USART0, console);
+//! board_kernel.set_process_state_observer(console, &process_mgmt_cap);
 //! ```
 //!
 //! Usage
@@ -33,11 +34,26 @@
 //! When the buffer has been written successfully, the buffer is released from
 //! the driver. Successive writes must call `allow` each time a buffer is to be
 //! written.
+//!
+//! The UART transmits straight from the buffer of the process, which the
+//! console lends it as a `LentAppSlice`, so writes are not copied through a
+//! kernel buffer. Until the write is done, the process cannot `allow` another
+//! write buffer, and should not change the one being written. The console
+//! must be the `ProcessStateObserver` of the kernel, so that it aborts the
+//! write when the process faults, before the kernel resets the process.
+//!
+//! A read of `len` bytes (`command(CONSOLE_DRIVER_NUM, 2, len)`) completes
+//! once `len` bytes have been received, or once the line has been idle for
+//! `RX_TIMEOUT` bit periods after at least one byte. The read callback gets
+//! the number of bytes received, so a process can read lines of any length
+//! up to `len` without knowing how long they are. UARTs that cannot time out
+//! complete reads only when they are full, as before.
 
 use core::cmp;
-use kernel::common::cells::{OptionalCell, TakeCell};
+use kernel::common::cells::{MapCell, OptionalCell, TakeCell};
 use kernel::hil::uart;
-use kernel::{AppId, AppSlice, Callback, Driver, Grant, ReturnCode, Shared};
+use kernel::{AppId, AppSlice, Callback, Driver, Grant, LentAppSlice, ReturnCode, Shared};
+use kernel::{ProcessEvent, ProcessStateObserver};
 
 /// Syscall driver number.
 use crate::driver;
@@ -48,7 +64,6 @@ pub struct App {
     write_callback: Option<Callback>,
     write_buffer: Option<AppSlice<Shared, u8>>,
     write_len: usize,
-    write_remaining: usize, // How many bytes didn't fit in the buffer and still need to be printed.
     pending_write: bool,
 
     read_callback: Option<Callback>,
@@ -56,22 +71,27 @@ pub struct App {
     read_len: usize,
 }
 
-pub static mut WRITE_BUF: [u8; 64] = [0; 64];
 pub static mut READ_BUF: [u8; 64] = [0; 64];
 
+/// Bit periods the line must be idle for before a read completes with the
+/// bytes received so far.
+const RX_TIMEOUT: u8 = 32;
+
 pub struct Console<'a> {
-    uart: &'a uart::UartData<'a>,
+    uart: &'a uart::UartDataAdvanced<'a>,
     apps: Grant<App>,
     tx_in_progress: OptionalCell<AppId>,
-    tx_buffer: TakeCell<'static, [u8]>,
+    /// The write buffer, while the UART transmits from it. It is kept here
+    /// rather than in the grant, which the kernel resets if the process
+    /// faults before the UART returns the buffer.
+    tx_lent: MapCell<LentAppSlice<u8>>,
     rx_in_progress: OptionalCell<AppId>,
     rx_buffer: TakeCell<'static, [u8]>,
 }
 
 impl Console<'a> {
     pub fn new(
-        uart: &'a uart::UartData<'a>,
-        tx_buffer: &'static mut [u8],
+        uart: &'a uart::UartDataAdvanced<'a>,
         rx_buffer: &'static mut [u8],
         grant: Grant<App>,
     ) -> Console<'a> {
@@ -79,7 +99,7 @@ impl Console<'a> {
             uart: uart,
             apps: grant,
             tx_in_progress: OptionalCell::empty(),
-            tx_buffer: TakeCell::new(tx_buffer),
+            tx_lent: MapCell::empty(),
             rx_in_progress: OptionalCell::empty(),
             rx_buffer: TakeCell::new(rx_buffer),
         }
@@ -87,64 +107,69 @@ impl Console<'a> {
 
     /// Internal helper function for setting up a new send transaction
     fn send_new(&self, app_id: AppId, app: &mut App, len: usize) -> ReturnCode {
-        match app.write_buffer.take() {
-            Some(slice) => {
-                app.write_len = cmp::min(len, slice.len());
-                app.write_remaining = app.write_len;
-                self.send(app_id, app, slice);
-                ReturnCode::SUCCESS
-            }
-            None => ReturnCode::EBUSY,
+        if app.write_buffer.is_none() || app.pending_write {
+            return ReturnCode::EBUSY;
         }
+        app.write_len = len;
+        self.send(app_id, app)
     }
 
-    /// Internal helper function for continuing a previously set up transaction
-    /// Returns true if this send is still active, or false if it has completed
-    fn send_continue(&self, app_id: AppId, app: &mut App) -> Result<bool, ReturnCode> {
-        if app.write_remaining > 0 {
-            app.write_buffer
-                .take()
-                .map_or(Err(ReturnCode::ERESERVE), |slice| {
-                    self.send(app_id, app, slice);
-                    Ok(true)
-                })
-        } else {
-            Ok(false)
+    /// Internal helper function for sending the write buffer of an app. If
+    /// another write is in progress, it will be sent later.
+    fn send(&self, app_id: AppId, app: &mut App) -> ReturnCode {
+        if self.tx_in_progress.is_some() || self.tx_lent.is_some() {
+            app.pending_write = true;
+            return ReturnCode::SUCCESS;
         }
-    }
-
-    /// Internal helper function for sending data for an existing transaction.
-    /// Cannot fail. If can't send now, it will schedule for sending later.
-    fn send(&self, app_id: AppId, app: &mut App, slice: AppSlice<Shared, u8>) {
-        if self.tx_in_progress.is_none() {
-            self.tx_in_progress.set(app_id);
-            self.tx_buffer.take().map(|buffer| {
-                let mut transaction_len = app.write_remaining;
-                for (i, c) in slice.as_ref()[slice.len() - app.write_remaining..slice.len()]
-                    .iter()
-                    .enumerate()
-                {
-                    if buffer.len() <= i {
-                        break;
+        match app.write_buffer.take() {
+            Some(slice) => {
+                app.write_len = cmp::min(app.write_len, slice.len());
+                // The UART only reads the buffer, as the source of the
+                // transmission, and returns it to `transmitted_buffer`,
+                // which reclaims it. If the process faults first,
+                // `process_event` aborts the transmission.
+                #[allow(unsafe_code)]
+                let (lent, buffer) = unsafe { slice.lend() };
+                self.tx_lent.put(lent);
+                self.tx_in_progress.set(app_id);
+                match self.uart.transmit_buffer(buffer, app.write_len) {
+                    (ReturnCode::SUCCESS, _) => ReturnCode::SUCCESS,
+                    (rcode, buffer) => {
+                        self.tx_in_progress.clear();
+                        // Keep the buffer allowed, so the app can retry
+                        let lent = self.tx_lent.take();
+                        app.write_buffer = buffer.and_then(|buffer| {
+                            lent.and_then(move |lent| lent.reclaim(buffer).ok())
+                        });
+                        rcode
                     }
-                    buffer[i] = *c;
                 }
+            }
+            None => ReturnCode::ERESERVE,
+        }
+    }
 
-                // Check if everything we wanted to print
-                // fit in the buffer.
-                if app.write_remaining > buffer.len() {
-                    transaction_len = buffer.len();
-                    app.write_remaining -= buffer.len();
-                    app.write_buffer = Some(slice);
-                } else {
-                    app.write_remaining = 0;
+    /// Start the write of the next app that waits for the UART.
+    fn send_pending(&self) {
+        for cntr in self.apps.iter() {
+            let started_tx = cntr.enter(|app, _| {
+                if !app.pending_write {
+                    return false;
+                }
+                app.pending_write = false;
+                match self.send(app.appid(), app) {
+                    ReturnCode::SUCCESS => true,
+                    rcode => {
+                        app.write_len = 0;
+                        app.write_callback
+                            .map(|mut cb| cb.schedule(isize::from(rcode) as usize, 0, 0));
+                        false
+                    }
                 }
-
-                let (_err, _opt) = self.uart.transmit_buffer(buffer, transaction_len);
             });
-        } else {
-            app.pending_write = true;
-            app.write_buffer = Some(slice);
+            if started_tx {
+                break;
+            }
         }
     }
 
@@ -168,7 +193,9 @@ impl Console<'a> {
                     app.read_len = read_len;
                     self.rx_buffer.take().map(|buffer| {
                         self.rx_in_progress.set(app_id);
-                        let (_err, _opt) = self.uart.receive_buffer(buffer, app.read_len);
+                        let (_err, _opt) =
+                            self.uart
+                                .receive_automatic(buffer, app.read_len, RX_TIMEOUT);
                     });
                     ReturnCode::SUCCESS
                 }
@@ -198,6 +225,10 @@ impl Driver for Console<'a> {
             1 => self
                 .apps
                 .enter(appid, |app, _| {
+                    // The UART transmits from the buffer being written
+                    if self.tx_in_progress.map_or(false, |id| *id == appid) {
+                        return ReturnCode::EBUSY;
+                    }
                     app.write_buffer = slice;
                     ReturnCode::SUCCESS
                 })
@@ -277,65 +308,42 @@ impl Driver for Console<'a> {
 }
 
 impl uart::TransmitClient for Console<'a> {
-    fn transmitted_buffer(&self, buffer: &'static mut [u8], _tx_len: usize, _rcode: ReturnCode) {
-        // Either print more from the AppSlice or send a callback to the
-        // application.
-        self.tx_buffer.replace(buffer);
+    fn transmitted_buffer(&self, buffer: &'static mut [u8], _tx_len: usize, rcode: ReturnCode) {
+        // The write buffer is released once it has been written
+        let _ = self.tx_lent.take().map(move |lent| lent.reclaim(buffer));
+
+        // Signal the app, unless it faulted during the write
         self.tx_in_progress.take().map(|appid| {
             self.apps.enter(appid, |app, _| {
-                match self.send_continue(appid, app) {
-                    Ok(more_to_send) => {
-                        if !more_to_send {
-                            // Go ahead and signal the application
-                            let written = app.write_len;
-                            app.write_len = 0;
-                            app.write_callback.map(|mut cb| {
-                                cb.schedule(written, 0, 0);
-                            });
-                        }
-                    }
-                    Err(return_code) => {
-                        // XXX This shouldn't ever happen?
-                        app.write_len = 0;
-                        app.write_remaining = 0;
-                        app.pending_write = false;
-                        let r0 = isize::from(return_code) as usize;
-                        app.write_callback.map(|mut cb| {
-                            cb.schedule(r0, 0, 0);
-                        });
-                    }
-                }
+                let written = app.write_len;
+                app.write_len = 0;
+                let r0 = if rcode == ReturnCode::SUCCESS {
+                    written
+                } else {
+                    isize::from(rcode) as usize
+                };
+                app.write_callback.map(|mut cb| {
+                    cb.schedule(r0, 0, 0);
+                });
             })
         });
 
-        // If we are not printing more from the current AppSlice,
-        // see if any other applications have pending messages.
-        if self.tx_in_progress.is_none() {
-            for cntr in self.apps.iter() {
-                let started_tx = cntr.enter(|app, _| {
-                    if app.pending_write {
-                        app.pending_write = false;
-                        match self.send_continue(app.appid(), app) {
-                            Ok(more_to_send) => more_to_send,
-                            Err(return_code) => {
-                                // XXX This shouldn't ever happen?
-                                app.write_len = 0;
-                                app.write_remaining = 0;
-                                app.pending_write = false;
-                                let r0 = isize::from(return_code) as usize;
-                                app.write_callback.map(|mut cb| {
-                                    cb.schedule(r0, 0, 0);
-                                });
-                                false
-                            }
-                        }
-                    } else {
-                        false
-                    }
-                });
-                if started_tx {
-                    break;
-                }
+        // See if any other applications have pending messages.
+        self.send_pending();
+    }
+}
+
+impl ProcessStateObserver for Console<'a> {
+    fn process_event(&self, appid: AppId, _name: &'static str, event: ProcessEvent) {
+        // The kernel resets a faulted process, and its memory becomes that of
+        // its next run, so the UART must stop transmitting from it. The UART
+        // then returns the buffer to `transmitted_buffer`, which drops the
+        // loan without signaling the process.
+        if event == ProcessEvent::Faulted && self.tx_in_progress.map_or(false, |id| *id == appid) {
+            self.tx_in_progress.clear();
+            if self.uart.transmit_abort() == ReturnCode::SUCCESS {
+                // The UART was not transmitting, and will not return the buffer
+                self.tx_lent.take();
             }
         }
     }
@@ -15,9 +15,19 @@
 //!
 //! ```
 //!
+//! Algorithms that the hardware does not support are computed by a fallback
+//! implementation, if one is set. The `SoftwareCrc` capsule supports every
+//! general purpose algorithm, and can also be used on its own on chips that
+//! have no CRC unit:
+//!
+//! ```rust
+//! software_crc.set_client(crc);
+//! crc.set_fallback(software_crc);
+//! ```
+//!
 //! ## CRC Algorithms
 //!
-//! The capsule supports two general purpose CRC algorithms, as well as a few
+//! The capsule supports four general purpose CRC algorithms, as well as a few
 //! hardware specific algorithms implemented on the Atmel SAM4L.
 //!
 //! In the values used to identify polynomials below, more-significant bits
@@ -39,6 +49,21 @@
 //! Bit-reverses and then bit-inverts the output. It *may* be equivalent to
 //! various CRC functions using the same name.
 //!
+//! ### CRC-8
+//!
+//! __Polynomial__: `0x07`
+//!
+//! Starts from zero and does no post-processing on the output value. The
+//! result is placed in the low-order eight bits of the returned value.
+//!
+//! ### CRC-16-CCITT
+//!
+//! __Polynomial__: `0x1021`
+//!
+//! Starts from `0xFFFF` and does no post-processing on the output value. The
+//! result is placed in the low-order sixteen bits of the returned value.
+//! This is the variant also known as CRC-16/CCITT-FALSE.
+//!
 //! ### SAM4L-16
 //!
 //! __Polynomial__: `0x1021`
@@ -91,6 +116,7 @@ pub struct Crc<'a, C: hil::crc::CRC> {
     crc_unit: &'a C,
     apps: Grant<App>,
     serving_app: OptionalCell<AppId>,
+    fallback: OptionalCell<&'a hil::crc::CRC>,
 }
 
 impl<C: hil::crc::CRC> Crc<'a, C> {
@@ -113,9 +139,16 @@ impl<C: hil::crc::CRC> Crc<'a, C> {
             crc_unit: crc_unit,
             apps: apps,
             serving_app: OptionalCell::empty(),
+            fallback: OptionalCell::empty(),
         }
     }
 
+    /// Sets an implementation that computes the algorithms `crc_unit` does
+    /// not support. Its results must also be delivered to this driver.
+    pub fn set_fallback(&self, fallback: &'a hil::crc::CRC) {
+        self.fallback.set(fallback);
+    }
+
     fn serve_waiting_apps(&self) {
         if self.serving_app.is_some() {
             // A computation is in progress
@@ -128,7 +161,12 @@ impl<C: hil::crc::CRC> Crc<'a, C> {
             app.enter(|app, _| {
                 if let Some(alg) = app.waiting {
                     if let Some(buffer) = app.buffer.take() {
-                        let r = self.crc_unit.compute(buffer.as_ref(), alg);
+                        let mut r = self.crc_unit.compute(buffer.as_ref(), alg);
+                        if r == ReturnCode::ENOSUPPORT {
+                            r = self.fallback.map_or(ReturnCode::ENOSUPPORT, |fallback| {
+                                fallback.compute(buffer.as_ref(), alg)
+                            });
+                        }
                         if r == ReturnCode::SUCCESS {
                             // The unit is now computing a CRC for this app
                             self.serving_app.set(app.appid());
@@ -284,6 +322,18 @@ impl<C: hil::crc::CRC> Driver for Crc<'a, C> {
     ///   * `4: SAM4L-32C`  This algorithm uses the same polynomial as
     ///   `CRC-32C`, but does no post-processing on the output value.  It
     ///   can be performed purely in hardware on the SAM4L.
+    ///
+    ///   * `5: CRC-8`  This algorithm uses polynomial 0x07, starts from
+    ///   zero and does no post-processing on the output value.  The result
+    ///   is placed in the low-order eight bits of the returned value.
+    ///
+    ///   * `6: CRC-16-CCITT`  This algorithm uses polynomial 0x1021, starts
+    ///   from 0xFFFF and does no post-processing on the output value.  The
+    ///   result is placed in the low-order sixteen bits of the returned value.
+    ///
+    /// Algorithms the CRC unit does not support are computed by the fallback
+    /// implementation, if the board has set one.  Otherwise the callback
+    /// receives the status `ENOSUPPORT`.
     fn command(&self, command_num: usize, algorithm: usize, _: usize, appid: AppId) -> ReturnCode {
         match command_num {
             // This driver is present
@@ -346,6 +396,8 @@ fn alg_from_user_int(i: usize) -> Option<hil::crc::CrcAlg> {
         2 => Some(CrcAlg::Sam4L16),
         3 => Some(CrcAlg::Sam4L32),
         4 => Some(CrcAlg::Sam4L32C),
+        5 => Some(CrcAlg::Crc8),
+        6 => Some(CrcAlg::Crc16CCITT),
         _ => None,
     }
 }
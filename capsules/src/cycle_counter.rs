@@ -0,0 +1,132 @@
+//! Provides userspace with a stopwatch of CPU cycles, for microbenchmarks.
+//!
+//! Each process has its own stopwatch on top of the cycle counter of the
+//! chip, which it starts, reads laps of, and stops. The counter counts every
+//! cycle, so a measurement includes the time the kernel and other processes
+//! run in between, as well as a few hundred cycles for each system call.
+//! Measurements are 32 bits wide and wrap around, after about 90 seconds at
+//! 48 MHz.
+//!
+//! Usage
+//! -----
+//!
+//! ```rust
+//! let cycle_counter = static_init!(
+//!     capsules::cycle_counter::CycleCounter<'static, cortexm4::dwt::CycleCounter>,
+//!     capsules::cycle_counter::CycleCounter::new(
+//!         &cortexm4::dwt::CYCLE_COUNTER,
+//!         board_kernel.create_grant(&memory_allocation_capability)
+//!     )
+//! );
+//! ```
+//!
+//! Syscall Interface
+//! -----------------
+//!
+//! - Stability: 1 - Experimental
+//!
+//! ### Command
+//!
+//! - `0`: Driver check. Returns `ENOSUPPORT` if the chip has no cycle
+//!   counter.
+//! - `1`: Start the stopwatch of the process. Returns `EALREADY` if it runs.
+//! - `2`: Stop the stopwatch. Returns the cycles since it was started, or
+//!   `EOFF` if it does not run.
+//! - `3`: Take a lap. Returns the cycles since the stopwatch was started or
+//!   the last lap was taken, or `EOFF` if it does not run.
+//! - `4`: Returns the low 32 bits of the cycle counter itself.
+
+use kernel::hil::cycle_counter;
+use kernel::{AppId, Driver, Grant, ReturnCode};
+
+/// Syscall driver number.
+use crate::driver;
+pub const DRIVER_NUM: usize = driver::NUM::CycleCounter as usize;
+
+#[derive(Default)]
+pub struct App {
+    running: bool,
+    /// The count when the stopwatch was started
+    start: u32,
+    /// The count when the last lap was taken, or the stopwatch started
+    lap: u32,
+}
+
+pub struct CycleCounter<'a, C: cycle_counter::CycleCounter> {
+    counter: &'a C,
+    apps: Grant<App>,
+}
+
+impl<C: cycle_counter::CycleCounter> CycleCounter<'a, C> {
+    pub fn new(counter: &'a C, grant: Grant<App>) -> CycleCounter<'a, C> {
+        CycleCounter {
+            counter: counter,
+            apps: grant,
+        }
+    }
+
+    fn start(&self, appid: AppId) -> ReturnCode {
+        let result = self.counter.enable();
+        if result != ReturnCode::SUCCESS {
+            return result;
+        }
+        self.apps
+            .enter(appid, |app, _| {
+                if app.running {
+                    return ReturnCode::EALREADY;
+                }
+                let now = self.counter.count();
+                app.running = true;
+                app.start = now;
+                app.lap = now;
+                ReturnCode::SUCCESS
+            })
+            .unwrap_or_else(|err| err.into())
+    }
+
+    fn stop(&self, appid: AppId) -> ReturnCode {
+        let now = self.counter.count();
+        self.apps
+            .enter(appid, |app, _| {
+                if !app.running {
+                    return ReturnCode::EOFF;
+                }
+                app.running = false;
+                ReturnCode::SuccessWithValue {
+                    value: now.wrapping_sub(app.start) as usize,
+                }
+            })
+            .unwrap_or_else(|err| err.into())
+    }
+
+    fn lap(&self, appid: AppId) -> ReturnCode {
+        let now = self.counter.count();
+        self.apps
+            .enter(appid, |app, _| {
+                if !app.running {
+                    return ReturnCode::EOFF;
+                }
+                let lap = now.wrapping_sub(app.lap);
+                app.lap = now;
+                ReturnCode::SuccessWithValue {
+                    value: lap as usize,
+                }
+            })
+            .unwrap_or_else(|err| err.into())
+    }
+}
+
+impl<C: cycle_counter::CycleCounter> Driver for CycleCounter<'a, C> {
+    fn command(&self, command_num: usize, _: usize, _: usize, appid: AppId) -> ReturnCode {
+        match command_num {
+            0 => self.counter.enable(),
+            1 => self.start(appid),
+            2 => self.stop(appid),
+            3 => self.lap(appid),
+            4 => ReturnCode::SuccessWithValue {
+                value: self.counter.count() as usize,
+            },
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+}
@@ -0,0 +1,128 @@
+//! BLAKE2s (RFC 7693), unkeyed, with a 32 byte digest.
+
+use crate::digest::DigestEngine;
+
+const BLOCK_LEN: usize = 64;
+const DIGEST_LEN: usize = 32;
+
+const IV: [u32; 8] = [
+    0x6A09E667, 0xBB67AE85, 0x3C6EF372, 0xA54FF53A, 0x510E527F, 0x9B05688C, 0x1F83D9AB, 0x5BE0CD19,
+];
+
+const SIGMA: [[usize; 16]; 10] = [
+    [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15],
+    [14, 10, 4, 8, 9, 15, 13, 6, 1, 12, 0, 2, 11, 7, 5, 3],
+    [11, 8, 12, 0, 5, 2, 15, 13, 10, 14, 3, 6, 7, 1, 9, 4],
+    [7, 9, 3, 1, 13, 12, 11, 14, 2, 6, 5, 10, 4, 0, 15, 8],
+    [9, 0, 5, 7, 2, 4, 10, 15, 14, 1, 11, 12, 6, 8, 3, 13],
+    [2, 12, 6, 10, 0, 11, 8, 3, 4, 13, 7, 5, 15, 14, 1, 9],
+    [12, 5, 1, 15, 14, 13, 4, 10, 0, 7, 6, 3, 9, 2, 8, 11],
+    [13, 11, 7, 14, 12, 1, 3, 9, 5, 0, 15, 4, 8, 6, 2, 10],
+    [6, 15, 14, 9, 11, 3, 0, 8, 12, 2, 13, 7, 1, 4, 10, 5],
+    [10, 2, 8, 4, 7, 6, 1, 5, 15, 11, 9, 14, 3, 12, 13, 0],
+];
+
+/// The BLAKE2s mixing function.
+fn mix(v: &mut [u32; 16], a: usize, b: usize, c: usize, d: usize, x: u32, y: u32) {
+    v[a] = v[a].wrapping_add(v[b]).wrapping_add(x);
+    v[d] = (v[d] ^ v[a]).rotate_right(16);
+    v[c] = v[c].wrapping_add(v[d]);
+    v[b] = (v[b] ^ v[c]).rotate_right(12);
+    v[a] = v[a].wrapping_add(v[b]).wrapping_add(y);
+    v[d] = (v[d] ^ v[a]).rotate_right(8);
+    v[c] = v[c].wrapping_add(v[d]);
+    v[b] = (v[b] ^ v[c]).rotate_right(7);
+}
+
+pub struct Blake2s256 {
+    h: [u32; 8],
+    /// Number of bytes compressed so far
+    t: u64,
+    buf: [u8; BLOCK_LEN],
+    buf_len: usize,
+}
+
+impl Blake2s256 {
+    pub fn new() -> Blake2s256 {
+        let mut blake2s = Blake2s256 {
+            h: IV,
+            t: 0,
+            buf: [0; BLOCK_LEN],
+            buf_len: 0,
+        };
+        blake2s.reset();
+        blake2s
+    }
+
+    fn compress(&mut self, last: bool) {
+        let mut m = [0u32; 16];
+        for (i, word) in m.iter_mut().enumerate() {
+            *word = (self.buf[4 * i] as u32)
+                | (self.buf[4 * i + 1] as u32) << 8
+                | (self.buf[4 * i + 2] as u32) << 16
+                | (self.buf[4 * i + 3] as u32) << 24;
+        }
+
+        let mut v = [0u32; 16];
+        v[..8].copy_from_slice(&self.h);
+        v[8..].copy_from_slice(&IV);
+        v[12] ^= self.t as u32;
+        v[13] ^= (self.t >> 32) as u32;
+        if last {
+            v[14] = !v[14];
+        }
+
+        for s in SIGMA.iter() {
+            mix(&mut v, 0, 4, 8, 12, m[s[0]], m[s[1]]);
+            mix(&mut v, 1, 5, 9, 13, m[s[2]], m[s[3]]);
+            mix(&mut v, 2, 6, 10, 14, m[s[4]], m[s[5]]);
+            mix(&mut v, 3, 7, 11, 15, m[s[6]], m[s[7]]);
+            mix(&mut v, 0, 5, 10, 15, m[s[8]], m[s[9]]);
+            mix(&mut v, 1, 6, 11, 12, m[s[10]], m[s[11]]);
+            mix(&mut v, 2, 7, 8, 13, m[s[12]], m[s[13]]);
+            mix(&mut v, 3, 4, 9, 14, m[s[14]], m[s[15]]);
+        }
+
+        for i in 0..8 {
+            self.h[i] ^= v[i] ^ v[i + 8];
+        }
+    }
+}
+
+impl DigestEngine for Blake2s256 {
+    const DIGEST_LEN: usize = DIGEST_LEN;
+
+    fn reset(&mut self) {
+        self.h = IV;
+        // Parameter block: digest length, no key, fanout and depth of 1
+        self.h[0] ^= 0x01010000 | DIGEST_LEN as u32;
+        self.t = 0;
+        self.buf_len = 0;
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        for &byte in data {
+            // The last block is only compressed in `finalize`, as it must be
+            // flagged as such
+            if self.buf_len == BLOCK_LEN {
+                self.t += BLOCK_LEN as u64;
+                self.compress(false);
+                self.buf_len = 0;
+            }
+            self.buf[self.buf_len] = byte;
+            self.buf_len += 1;
+        }
+    }
+
+    fn finalize(&mut self, digest: &mut [u8]) {
+        self.t += self.buf_len as u64;
+        for byte in self.buf[self.buf_len..].iter_mut() {
+            *byte = 0;
+        }
+        self.compress(true);
+        for (i, byte) in digest[..DIGEST_LEN].iter_mut().enumerate() {
+            *byte = (self.h[i / 4] >> (8 * (i % 4))) as u8;
+        }
+        self.reset();
+    }
+}
@@ -0,0 +1,156 @@
+//! Software implementations of the digest interface.
+//!
+//! `SoftwareDigest` implements `hil::digest::Digest` on top of any
+//! `DigestEngine`, computing the digest within each call and delivering the
+//! completion callbacks from a deferred call, as a hardware unit would. Boards
+//! pick the algorithm by choosing the engine:
+//!
+//! - `blake2s::Blake2s256`: BLAKE2s with a 32 byte digest. It is fast on
+//!   32-bit microcontrollers and needs little RAM, which suits
+//!   memory-constrained devices.
+//! - `sha3::Sha3_256`: SHA3-256, for products that need a standardized hash.
+//!
+//! Usage
+//! -----
+//!
+//! ```
+//! let digest = static_init!(
+//!     capsules::digest::SoftwareDigest<'static, capsules::digest::sha3::Sha3_256>,
+//!     capsules::digest::SoftwareDigest::new(
+//!         capsules::digest::sha3::Sha3_256::new(),
+//!         dynamic_deferred_caller
+//!     )
+//! );
+//! digest.initialize_callback_handle(
+//!     dynamic_deferred_caller.register(digest).expect("no deferred call slot available")
+//! );
+//! digest.set_client(digest_client);
+//! ```
+
+pub mod blake2s;
+pub mod sha3;
+
+use kernel::common::cells::{MapCell, OptionalCell, TakeCell};
+use kernel::common::dynamic_deferred_call::{
+    DeferredCallHandle, DynamicDeferredCall, DynamicDeferredCallClient,
+};
+use kernel::hil::digest;
+use kernel::ReturnCode;
+
+/// A hash function that is computed synchronously.
+pub trait DigestEngine {
+    /// Length of the digest, in bytes
+    const DIGEST_LEN: usize;
+
+    /// Discards all data added so far.
+    fn reset(&mut self);
+
+    /// Adds `data` to the digest.
+    fn update(&mut self, data: &[u8]);
+
+    /// Writes the digest of the data added so far to the first `DIGEST_LEN`
+    /// bytes of `digest`, and resets the engine.
+    fn finalize(&mut self, digest: &mut [u8]);
+}
+
+pub struct SoftwareDigest<'a, E: DigestEngine> {
+    engine: MapCell<E>,
+    client: OptionalCell<&'a digest::Client>,
+    deferred_caller: &'a DynamicDeferredCall,
+    deferred_call_handle: OptionalCell<DeferredCallHandle>,
+    /// Buffer of a completed `add_data`, to be returned to the client
+    data: TakeCell<'static, [u8]>,
+    /// Buffer of a completed `run`, to be returned to the client
+    digest: TakeCell<'static, [u8]>,
+}
+
+impl<E: DigestEngine> SoftwareDigest<'a, E> {
+    pub fn new(engine: E, deferred_caller: &'a DynamicDeferredCall) -> SoftwareDigest<'a, E> {
+        SoftwareDigest {
+            engine: MapCell::new(engine),
+            client: OptionalCell::empty(),
+            deferred_caller: deferred_caller,
+            deferred_call_handle: OptionalCell::empty(),
+            data: TakeCell::empty(),
+            digest: TakeCell::empty(),
+        }
+    }
+
+    pub fn initialize_callback_handle(&self, handle: DeferredCallHandle) {
+        self.deferred_call_handle.replace(handle);
+    }
+
+    fn busy(&self) -> bool {
+        self.data.is_some() || self.digest.is_some()
+    }
+
+    /// Schedules the completion callback. Returns `false` if no deferred call
+    /// handle has been set.
+    fn schedule_callback(&self) -> bool {
+        self.deferred_call_handle.map_or(false, |handle| {
+            self.deferred_caller.set(*handle);
+            true
+        })
+    }
+}
+
+impl<E: DigestEngine> digest::Digest<'a> for SoftwareDigest<'a, E> {
+    fn set_client(&self, client: &'a digest::Client) {
+        self.client.set(client);
+    }
+
+    fn digest_len(&self) -> usize {
+        E::DIGEST_LEN
+    }
+
+    fn add_data(
+        &self,
+        data: &'static mut [u8],
+        length: usize,
+    ) -> (ReturnCode, Option<&'static mut [u8]>) {
+        if self.busy() {
+            return (ReturnCode::EBUSY, Some(data));
+        }
+        if length > data.len() {
+            return (ReturnCode::ESIZE, Some(data));
+        }
+        if !self.schedule_callback() {
+            return (ReturnCode::FAIL, Some(data));
+        }
+        self.engine.map(|engine| engine.update(&data[..length]));
+        self.data.replace(data);
+        (ReturnCode::SUCCESS, None)
+    }
+
+    fn run(&self, digest: &'static mut [u8]) -> (ReturnCode, Option<&'static mut [u8]>) {
+        if self.busy() {
+            return (ReturnCode::EBUSY, Some(digest));
+        }
+        if digest.len() < E::DIGEST_LEN {
+            return (ReturnCode::ESIZE, Some(digest));
+        }
+        if !self.schedule_callback() {
+            return (ReturnCode::FAIL, Some(digest));
+        }
+        self.engine.map(|engine| engine.finalize(digest));
+        self.digest.replace(digest);
+        (ReturnCode::SUCCESS, None)
+    }
+
+    fn clear_data(&self) {
+        self.engine.map(|engine| engine.reset());
+    }
+}
+
+impl<E: DigestEngine> DynamicDeferredCallClient for SoftwareDigest<'a, E> {
+    fn call(&self, _handle: DeferredCallHandle) {
+        self.data.take().map(|data| {
+            self.client
+                .map(move |client| client.add_data_done(ReturnCode::SUCCESS, data));
+        });
+        self.digest.take().map(|digest| {
+            self.client
+                .map(move |client| client.hash_done(ReturnCode::SUCCESS, digest));
+        });
+    }
+}
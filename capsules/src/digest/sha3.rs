@@ -0,0 +1,135 @@
+//! SHA3-256 (FIPS 202).
+
+use crate::digest::DigestEngine;
+
+/// Number of bytes absorbed per permutation, 1600 bits minus twice the
+/// digest length
+const RATE: usize = 136;
+const DIGEST_LEN: usize = 32;
+
+const ROUND_CONSTANTS: [u64; 24] = [
+    0x0000000000000001,
+    0x0000000000008082,
+    0x800000000000808A,
+    0x8000000080008000,
+    0x000000000000808B,
+    0x0000000080000001,
+    0x8000000080008081,
+    0x8000000000008009,
+    0x000000000000008A,
+    0x0000000000000088,
+    0x0000000080008009,
+    0x000000008000000A,
+    0x000000008000808B,
+    0x800000000000008B,
+    0x8000000000008089,
+    0x8000000000008003,
+    0x8000000000008002,
+    0x8000000000000080,
+    0x000000000000800A,
+    0x800000008000000A,
+    0x8000000080008081,
+    0x8000000000008080,
+    0x0000000080000001,
+    0x8000000080008008,
+];
+
+/// Rotation of each lane in the rho step, in the order the pi step visits
+/// the lanes
+const RHO_ROTATIONS: [u32; 24] = [
+    1, 3, 6, 10, 15, 21, 28, 36, 45, 55, 2, 14, 27, 41, 56, 8, 25, 43, 62, 18, 39, 61, 20, 44,
+];
+
+/// Order in which the pi step visits the lanes
+const PI_LANES: [usize; 24] = [
+    10, 7, 11, 17, 18, 3, 5, 16, 8, 21, 24, 4, 15, 23, 19, 13, 12, 2, 20, 14, 22, 9, 6, 1,
+];
+
+/// The Keccak-f[1600] permutation.
+fn keccak_f(state: &mut [u64; 25]) {
+    let mut c = [0u64; 5];
+    for round_constant in ROUND_CONSTANTS.iter() {
+        // Theta
+        for x in 0..5 {
+            c[x] = state[x] ^ state[x + 5] ^ state[x + 10] ^ state[x + 15] ^ state[x + 20];
+        }
+        for x in 0..5 {
+            let d = c[(x + 4) % 5] ^ c[(x + 1) % 5].rotate_left(1);
+            for y in (0..25).step_by(5) {
+                state[y + x] ^= d;
+            }
+        }
+
+        // Rho and pi
+        let mut lane = state[1];
+        for (&to, &rotation) in PI_LANES.iter().zip(RHO_ROTATIONS.iter()) {
+            let next = state[to];
+            state[to] = lane.rotate_left(rotation);
+            lane = next;
+        }
+
+        // Chi
+        for y in (0..25).step_by(5) {
+            c.copy_from_slice(&state[y..y + 5]);
+            for x in 0..5 {
+                state[y + x] ^= !c[(x + 1) % 5] & c[(x + 2) % 5];
+            }
+        }
+
+        // Iota
+        state[0] ^= round_constant;
+    }
+}
+
+#[allow(non_camel_case_types)]
+pub struct Sha3_256 {
+    state: [u64; 25],
+    /// Position within the current block
+    offset: usize,
+}
+
+impl Sha3_256 {
+    pub fn new() -> Sha3_256 {
+        Sha3_256 {
+            state: [0; 25],
+            offset: 0,
+        }
+    }
+
+    fn xor_byte(&mut self, offset: usize, byte: u8) {
+        self.state[offset / 8] ^= (byte as u64) << (8 * (offset % 8));
+    }
+}
+
+impl DigestEngine for Sha3_256 {
+    const DIGEST_LEN: usize = DIGEST_LEN;
+
+    fn reset(&mut self) {
+        self.state = [0; 25];
+        self.offset = 0;
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        for &byte in data {
+            let offset = self.offset;
+            self.xor_byte(offset, byte);
+            self.offset += 1;
+            if self.offset == RATE {
+                keccak_f(&mut self.state);
+                self.offset = 0;
+            }
+        }
+    }
+
+    fn finalize(&mut self, digest: &mut [u8]) {
+        // SHA-3 domain separation bits followed by pad10*1
+        let offset = self.offset;
+        self.xor_byte(offset, 0x06);
+        self.xor_byte(RATE - 1, 0x80);
+        keccak_f(&mut self.state);
+        for (i, byte) in digest[..DIGEST_LEN].iter_mut().enumerate() {
+            *byte = (self.state[i / 8] >> (8 * (i % 8))) as u8;
+        }
+        self.reset();
+    }
+}
@@ -10,13 +10,22 @@ pub enum NUM {
     AmbientLight = 0x60002,
     AnalogComparator = 0x00007,
     AppFlash =  0x50000,
+    Audio = 0x90001,
     BleAdvertising = 0x030000,
+    BoardInfo = 0x10001,
+    BootBanks = 0x50004,
     Button = 0x00000003,
+    Charger = 0x80005,
+    ConfigStore = 0x50003,
     Console = 0x00000001,
     Crc = 0x40002,
+    CycleCounter = 0x90009,
     Dac = 0x00000006,
+    EnergyHarvester = 0x80006,
+    ErrorLog = 0x90006,
     Gpio = 0x00000004,
     GpioAsync = 0x80003,
+    Haptics = 0x90008,
     Humidity= 0x60001,
     I2cMaster = 0x40006,
     I2cMasterSlave = 0x20006,
@@ -24,16 +33,31 @@ pub enum NUM {
     Lps25hb = 0x70004,
     Ltc294x = 0x80000,
     Max17205 = 0x80001,
+    Measurement = 0x40003,
+    Microphone = 0x90002,
+    MlInference = 0xA0000,
+    MonotonicCounter = 0x40005,
+    NetStats = 0x30003,
+    Nfc = 0x30004,
     NINEDOF = 0x60004,
     NvmStorage = 0x50001,
     Nrf51822Serialization = 0x80004,
+    PacketRadio = 0x30005,
     Pca9544a = 0x80002,
+    PulseCapture = 0x00011,
+    Pwm = 0x00010,
     Rng = 0x40001,
     SdCard = 0x50002,
+    Tamper = 0x40004,
+    TestHarness = 0x90003,
+    SelfTest = 0x90005,
     Spi = 0x20001,
+    Swd = 0x90004,
     Temperature = 0x60000,
+    ThermalThrottle = 0x90007,
     Tmp006 = 0x70001,
     Tsl2561 = 0x70000,
     UsbUser = 0x20005,
+    Voltage = 0x60005,
 }
 }
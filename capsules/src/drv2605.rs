@@ -0,0 +1,219 @@
+//! Driver for the TI DRV2605 haptic driver.
+//!
+//! <http://www.ti.com/product/DRV2605>
+//!
+//! > The DRV2605 device is a haptic driver designed for Linear Resonant
+//! > Actuator (LRA) and Eccentric Rotating Mass (ERM) actuators. [...] The
+//! > DRV2605 device comes with a library of over 100 licensed effects.
+//!
+//! This driver implements `hil::haptics::Haptics` with the effects of the
+//! internal ROM libraries, played on an I2C trigger. Up to eight effects are
+//! loaded into the waveform sequencer and played in a row. The libraries are
+//! 1 to 5 and 7 for ERM actuators and 6 for LRA actuators.
+//!
+//! Usage
+//! -----
+//!
+//! ```rust
+//! let drv2605_i2c = static_init!(I2CDevice, I2CDevice::new(i2c_bus, 0x5A));
+//! let drv2605 = static_init!(
+//!     capsules::drv2605::DRV2605<'static>,
+//!     capsules::drv2605::DRV2605::new(
+//!         drv2605_i2c,
+//!         capsules::drv2605::Actuator::Erm,
+//!         &mut capsules::drv2605::BUFFER
+//!     )
+//! );
+//! drv2605_i2c.set_client(drv2605);
+//! drv2605.initialize();
+//! ```
+
+use core::cell::Cell;
+use kernel::common::cells::{OptionalCell, TakeCell};
+use kernel::hil::haptics;
+use kernel::hil::i2c;
+use kernel::ReturnCode;
+
+/// The register address and the eight effects of the waveform sequencer
+pub static mut BUFFER: [u8; 1 + MAX_EFFECTS] = [0; 1 + MAX_EFFECTS];
+
+/// The length of the waveform sequencer
+pub const MAX_EFFECTS: usize = 8;
+
+#[allow(dead_code)]
+enum Registers {
+    Status = 0x00,
+    Mode = 0x01,
+    LibrarySelection = 0x03,
+    WaveformSequencer = 0x04,
+    Go = 0x0C,
+    FeedbackControl = 0x1A,
+}
+
+/// The feedback control register, with the default loop gain and back-EMF
+/// gain, for each actuator
+const FEEDBACK_ERM: u8 = 0x36;
+const FEEDBACK_LRA: u8 = 0xB6;
+
+/// The libraries of effects in ROM
+const LIBRARY_MAX: u8 = 7;
+
+/// The kind of actuator the DRV2605 drives.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Actuator {
+    /// Eccentric rotating mass
+    Erm,
+    /// Linear resonant actuator
+    Lra,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum State {
+    Idle,
+
+    /// Taking the device out of standby, with internal trigger mode
+    SettingMode,
+    SettingFeedback,
+
+    /// Loading the waveform sequencer, to start it afterwards
+    LoadingSequence,
+    /// Writing the GO bit, to start or stop the sequencer
+    WritingGo,
+    SelectingLibrary,
+}
+
+pub struct DRV2605<'a> {
+    i2c: &'a i2c::I2CDevice,
+    actuator: Actuator,
+    client: OptionalCell<&'a haptics::Client>,
+    state: Cell<State>,
+    buffer: TakeCell<'static, [u8]>,
+}
+
+impl DRV2605<'a> {
+    pub fn new(
+        i2c: &'a i2c::I2CDevice,
+        actuator: Actuator,
+        buffer: &'static mut [u8],
+    ) -> DRV2605<'a> {
+        DRV2605 {
+            i2c: i2c,
+            actuator: actuator,
+            client: OptionalCell::empty(),
+            state: Cell::new(State::Idle),
+            buffer: TakeCell::new(buffer),
+        }
+    }
+
+    /// Takes the device out of standby and configures it for the actuator.
+    pub fn initialize(&self) -> ReturnCode {
+        self.write(State::SettingMode, &[Registers::Mode as u8, 0])
+    }
+
+    /// Writes `data`, starting with the register address, to the device.
+    fn write(&self, state: State, data: &[u8]) -> ReturnCode {
+        if self.state.get() != State::Idle {
+            return ReturnCode::EBUSY;
+        }
+        self.buffer.take().map_or(ReturnCode::ENOMEM, |buffer| {
+            buffer[..data.len()].copy_from_slice(data);
+            self.i2c.enable();
+            self.i2c.write(buffer, data.len() as u8);
+            self.state.set(state);
+            ReturnCode::SUCCESS
+        })
+    }
+
+    fn done(&self, buffer: &'static mut [u8], error: i2c::Error, report: bool) {
+        self.buffer.replace(buffer);
+        self.i2c.disable();
+        self.state.set(State::Idle);
+        if report {
+            let result = if error == i2c::Error::CommandComplete {
+                ReturnCode::SUCCESS
+            } else {
+                ReturnCode::FAIL
+            };
+            self.client.map(|client| client.command_done(result));
+        }
+    }
+}
+
+impl i2c::I2CClient for DRV2605<'a> {
+    fn command_complete(&self, buffer: &'static mut [u8], error: i2c::Error) {
+        match self.state.get() {
+            State::Idle => {
+                self.buffer.replace(buffer);
+            }
+            State::SettingMode => {
+                if error != i2c::Error::CommandComplete {
+                    self.done(buffer, error, false);
+                    return;
+                }
+                buffer[0] = Registers::FeedbackControl as u8;
+                buffer[1] = match self.actuator {
+                    Actuator::Erm => FEEDBACK_ERM,
+                    Actuator::Lra => FEEDBACK_LRA,
+                };
+                self.i2c.write(buffer, 2);
+                self.state.set(State::SettingFeedback);
+            }
+            State::SettingFeedback => {
+                self.done(buffer, error, false);
+            }
+            State::LoadingSequence => {
+                if error != i2c::Error::CommandComplete {
+                    self.done(buffer, error, true);
+                    return;
+                }
+                buffer[0] = Registers::Go as u8;
+                buffer[1] = 1;
+                self.i2c.write(buffer, 2);
+                self.state.set(State::WritingGo);
+            }
+            State::WritingGo | State::SelectingLibrary => {
+                self.done(buffer, error, true);
+            }
+        }
+    }
+}
+
+impl haptics::Haptics<'a> for DRV2605<'a> {
+    fn set_client(&self, client: &'a haptics::Client) {
+        self.client.set(client);
+    }
+
+    fn max_effects(&self) -> usize {
+        MAX_EFFECTS
+    }
+
+    fn play(&self, effects: &[u8]) -> ReturnCode {
+        if effects.is_empty() || effects.len() > MAX_EFFECTS {
+            return ReturnCode::EINVAL;
+        }
+        // A zero ends the sequence early
+        let mut sequence = [0; 1 + MAX_EFFECTS];
+        sequence[0] = Registers::WaveformSequencer as u8;
+        sequence[1..1 + effects.len()].copy_from_slice(effects);
+        let len = if effects.len() < MAX_EFFECTS {
+            effects.len() + 2
+        } else {
+            effects.len() + 1
+        };
+        self.write(State::LoadingSequence, &sequence[..len])
+    }
+
+    fn stop(&self) -> ReturnCode {
+        self.write(State::WritingGo, &[Registers::Go as u8, 0])
+    }
+
+    fn set_library(&self, library: u8) -> ReturnCode {
+        if library == 0 || library > LIBRARY_MAX {
+            return ReturnCode::EINVAL;
+        }
+        self.write(
+            State::SelectingLibrary,
+            &[Registers::LibrarySelection as u8, library],
+        )
+    }
+}
@@ -0,0 +1,342 @@
+//! Emulates a small EEPROM of 32-bit cells on two pages of flash.
+//!
+//! Calibration constants, configuration and similar small values are
+//! rewritten far more often than flash pages can be erased, and must survive
+//! a reset in the middle of an update. `FlashEeprom` keeps the cells in RAM
+//! and persists all of them at once on `commit`, as a record holding the
+//! cells, a generation number and a CRC. Records alternate between the two
+//! pages, so each page is erased on every other commit, and the previous
+//! record is never erased by a commit: if power is lost while a page is
+//! written, the torn record fails its CRC check and the previous one is used.
+//! At boot, `initialize` reads both pages and restores the valid record with
+//! the higher generation.
+//!
+//! Writes only change the cells in RAM, so a client that updates several
+//! cells commits them together, and wears the flash once for all of them.
+//! Cells that were never written read as `None`, like the erased cells of an
+//! EEPROM.
+//!
+//! Usage
+//! -----
+//!
+//! ```rust
+//! pub static mut PAGEBUFFER: nrf52::nvmc::NrfPage = nrf52::nvmc::NrfPage::new();
+//! let eeprom = static_init!(
+//!     capsules::eeprom_emulation::FlashEeprom<'static, nrf52::nvmc::Nvmc>,
+//!     capsules::eeprom_emulation::FlashEeprom::new(
+//!         &nrf52::nvmc::NVMC,
+//!         &mut PAGEBUFFER,
+//!         0x7c     // The first of the two pages
+//!     )
+//! );
+//! hil::flash::HasClient::set_client(&nrf52::nvmc::NVMC, eeprom);
+//! eeprom.set_client(calibration);
+//! eeprom.initialize();
+//! ```
+
+use core::cell::Cell;
+use kernel::common::cells::{OptionalCell, TakeCell};
+use kernel::hil;
+use kernel::hil::crc::CrcAlg;
+use kernel::ReturnCode;
+
+use crate::software_crc;
+
+pub const NUM_CELLS: usize = 32;
+
+/// Marks the start of a record ("EEPR")
+const MAGIC: u32 = 0x45455052;
+
+/// Magic, generation, the mask of written cells, the cells and CRC
+const RECORD_LEN: usize = 12 + NUM_CELLS * 4 + 4;
+
+fn read_u32(buf: &[u8], offset: usize) -> u32 {
+    let mut bytes = [0; 4];
+    bytes.copy_from_slice(&buf[offset..offset + 4]);
+    u32::from_le_bytes(bytes)
+}
+
+/// Writes a record of `cells` to the start of `buf`.
+fn encode_record(buf: &mut [u8], generation: u32, written: u32, cells: &[u32; NUM_CELLS]) {
+    buf[0..4].copy_from_slice(&MAGIC.to_le_bytes());
+    buf[4..8].copy_from_slice(&generation.to_le_bytes());
+    buf[8..12].copy_from_slice(&written.to_le_bytes());
+    for (i, cell) in cells.iter().enumerate() {
+        let offset = 12 + i * 4;
+        buf[offset..offset + 4].copy_from_slice(&cell.to_le_bytes());
+    }
+    let crc = record_crc(&buf[..RECORD_LEN - 4]);
+    buf[RECORD_LEN - 4..RECORD_LEN].copy_from_slice(&crc.to_le_bytes());
+}
+
+/// Returns the generation, the mask of written cells and the cells of the
+/// record at the start of `buf`, or `None` if there is no valid record.
+fn decode_record(buf: &[u8]) -> Option<(u32, u32, [u32; NUM_CELLS])> {
+    if read_u32(buf, 0) != MAGIC
+        || read_u32(buf, RECORD_LEN - 4) != record_crc(&buf[..RECORD_LEN - 4])
+    {
+        return None;
+    }
+    let mut cells = [0; NUM_CELLS];
+    for (i, cell) in cells.iter_mut().enumerate() {
+        *cell = read_u32(buf, 12 + i * 4);
+    }
+    Some((read_u32(buf, 4), read_u32(buf, 8), cells))
+}
+
+fn record_crc(data: &[u8]) -> u32 {
+    CrcAlg::Crc32
+        .params()
+        .and_then(|params| software_crc::compute_crc(&params, data))
+        .unwrap_or(0)
+}
+
+/// A small EEPROM of 32-bit cells, for calibration data and settings.
+pub trait Eeprom<'a> {
+    fn set_client(&self, client: &'a EepromClient);
+
+    fn num_cells(&self) -> usize;
+
+    /// Returns the value of cell `index`, or `None` if there is no such cell,
+    /// it was never written or the cells have not been restored.
+    fn read(&self, index: usize) -> Option<u32>;
+
+    /// Sets cell `index` to `value`, until the next commit persists it.
+    /// Cells can be written while a commit is in progress, for the commit
+    /// after it. Returns `EINVAL` if there is no such cell, `EBUSY` if the
+    /// cells are being restored and `FAIL` if they could not be restored.
+    fn write(&self, index: usize, value: u32) -> ReturnCode;
+
+    /// Persists the cells written since the last commit. If this returns
+    /// `SUCCESS`, the client's `committed` is called once they are
+    /// persistent. Returns `EALREADY` if no cell was written, and `EBUSY` if
+    /// another commit is in progress or the cells are being restored.
+    fn commit(&self) -> ReturnCode;
+}
+
+pub trait EepromClient {
+    /// Called once `initialize` restored the cells, or with `FAIL` if they
+    /// could not be read.
+    fn initialized(&self, result: ReturnCode);
+
+    /// Called when a commit completes. If `result` is not `SUCCESS`, the
+    /// cells are still to be committed.
+    fn committed(&self, result: ReturnCode);
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum State {
+    Uninitialized,
+    /// Reading this page of the two
+    Loading(usize),
+    Idle,
+    /// Writing this page of the two
+    Writing(usize),
+    /// The cells could not be restored
+    Failed,
+}
+
+pub struct FlashEeprom<'a, F: hil::flash::Flash + 'static> {
+    driver: &'a F,
+    client: OptionalCell<&'a EepromClient>,
+    pagebuffer: TakeCell<'static, F::Page>,
+    first_page: usize,
+    state: Cell<State>,
+    cells: [Cell<u32>; NUM_CELLS],
+    /// The mask of the cells that were ever written
+    written: Cell<u32>,
+    /// Whether cells were written since the last commit
+    dirty: Cell<bool>,
+    /// Generation of the newest record
+    generation: Cell<u32>,
+    /// The page of the newest record, if there is one
+    newest: OptionalCell<usize>,
+}
+
+impl<F: hil::flash::Flash> FlashEeprom<'a, F> {
+    /// Creates an EEPROM stored in the flash pages `first_page` and
+    /// `first_page + 1`, which must not be used for anything else.
+    pub fn new(
+        driver: &'a F,
+        pagebuffer: &'static mut F::Page,
+        first_page: usize,
+    ) -> FlashEeprom<'a, F> {
+        assert!(pagebuffer.as_mut().len() >= RECORD_LEN);
+        FlashEeprom {
+            driver: driver,
+            client: OptionalCell::empty(),
+            pagebuffer: TakeCell::new(pagebuffer),
+            first_page: first_page,
+            state: Cell::new(State::Uninitialized),
+            cells: Default::default(),
+            written: Cell::new(0),
+            dirty: Cell::new(false),
+            generation: Cell::new(0),
+            newest: OptionalCell::empty(),
+        }
+    }
+
+    /// Restores the cells from flash. Until the client's `initialized` is
+    /// called the cells cannot be read or written.
+    pub fn initialize(&self) -> ReturnCode {
+        if self.state.get() != State::Uninitialized {
+            return ReturnCode::EALREADY;
+        }
+        self.pagebuffer
+            .take()
+            .map_or(ReturnCode::ERESERVE, |pagebuffer| {
+                self.state.set(State::Loading(0));
+                let result = self.driver.read_page(self.first_page, pagebuffer);
+                if result != ReturnCode::SUCCESS {
+                    self.state.set(State::Failed);
+                }
+                result
+            })
+    }
+}
+
+impl<F: hil::flash::Flash> Eeprom<'a> for FlashEeprom<'a, F> {
+    fn set_client(&self, client: &'a EepromClient) {
+        self.client.set(client);
+    }
+
+    fn num_cells(&self) -> usize {
+        NUM_CELLS
+    }
+
+    fn read(&self, index: usize) -> Option<u32> {
+        match self.state.get() {
+            State::Idle | State::Writing(_) if index < NUM_CELLS => {
+                if self.written.get() & (1 << index) != 0 {
+                    Some(self.cells[index].get())
+                } else {
+                    None
+                }
+            }
+            _ => None,
+        }
+    }
+
+    fn write(&self, index: usize, value: u32) -> ReturnCode {
+        if index >= NUM_CELLS {
+            return ReturnCode::EINVAL;
+        }
+        match self.state.get() {
+            State::Idle | State::Writing(_) => {
+                self.cells[index].set(value);
+                self.written.set(self.written.get() | 1 << index);
+                self.dirty.set(true);
+                ReturnCode::SUCCESS
+            }
+            State::Loading(_) => ReturnCode::EBUSY,
+            State::Uninitialized | State::Failed => ReturnCode::FAIL,
+        }
+    }
+
+    fn commit(&self) -> ReturnCode {
+        match self.state.get() {
+            State::Idle => {}
+            State::Loading(_) | State::Writing(_) => return ReturnCode::EBUSY,
+            State::Uninitialized | State::Failed => return ReturnCode::FAIL,
+        }
+        if !self.dirty.get() {
+            return ReturnCode::EALREADY;
+        }
+        self.pagebuffer
+            .take()
+            .map_or(ReturnCode::ERESERVE, |pagebuffer| {
+                let mut cells = [0; NUM_CELLS];
+                for (cell, value) in self.cells.iter().zip(cells.iter_mut()) {
+                    *value = cell.get();
+                }
+                encode_record(
+                    pagebuffer.as_mut(),
+                    self.generation.get().wrapping_add(1),
+                    self.written.get(),
+                    &cells,
+                );
+
+                // Never overwrite the newest record
+                let page = self.newest.map_or(0, |newest| *newest ^ 1);
+                self.dirty.set(false);
+                self.state.set(State::Writing(page));
+                let result = self.driver.write_page(self.first_page + page, pagebuffer);
+                if result != ReturnCode::SUCCESS {
+                    self.dirty.set(true);
+                    self.state.set(State::Idle);
+                }
+                result
+            })
+    }
+}
+
+impl<F: hil::flash::Flash> hil::flash::Client<F> for FlashEeprom<'a, F> {
+    fn read_complete(&self, pagebuffer: &'static mut F::Page, error: hil::flash::Error) {
+        let page = match self.state.get() {
+            State::Loading(page) => page,
+            _ => {
+                self.pagebuffer.replace(pagebuffer);
+                return;
+            }
+        };
+        if error != hil::flash::Error::CommandComplete {
+            self.pagebuffer.replace(pagebuffer);
+            self.state.set(State::Failed);
+            self.client
+                .map(|client| client.initialized(ReturnCode::FAIL));
+            return;
+        }
+
+        decode_record(pagebuffer.as_mut()).map(|(generation, written, cells)| {
+            // Generations only wrap after 2^32 commits, more than any flash
+            // can endure
+            let newer = self
+                .newest
+                .map_or(true, |_| generation > self.generation.get());
+            if newer {
+                self.generation.set(generation);
+                self.newest.set(page);
+                self.written.set(written);
+                for (cell, value) in self.cells.iter().zip(cells.iter()) {
+                    cell.set(*value);
+                }
+            }
+        });
+
+        if page == 0 {
+            self.state.set(State::Loading(1));
+            let result = self.driver.read_page(self.first_page + 1, pagebuffer);
+            if result != ReturnCode::SUCCESS {
+                self.state.set(State::Failed);
+                self.client
+                    .map(|client| client.initialized(ReturnCode::FAIL));
+            }
+        } else {
+            // If neither page has a valid record, no cell was ever committed
+            self.pagebuffer.replace(pagebuffer);
+            self.state.set(State::Idle);
+            self.client
+                .map(|client| client.initialized(ReturnCode::SUCCESS));
+        }
+    }
+
+    fn write_complete(&self, pagebuffer: &'static mut F::Page, error: hil::flash::Error) {
+        self.pagebuffer.replace(pagebuffer);
+        let page = match self.state.get() {
+            State::Writing(page) => page,
+            _ => return,
+        };
+        self.state.set(State::Idle);
+        let result = if error == hil::flash::Error::CommandComplete {
+            self.generation.set(self.generation.get().wrapping_add(1));
+            self.newest.set(page);
+            ReturnCode::SUCCESS
+        } else {
+            self.dirty.set(true);
+            ReturnCode::FAIL
+        };
+        self.client.map(|client| client.committed(result));
+    }
+
+    fn erase_complete(&self, _error: hil::flash::Error) {}
+}
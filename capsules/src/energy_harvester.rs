@@ -0,0 +1,245 @@
+//! Keeps a system powered by an energy harvester above brown-out.
+//!
+//! On a batteryless device, such as one powered by a solar cell and a
+//! capacitor, the supply voltage sags whenever loads draw more than the
+//! harvester provides. `EnergyHarvester` periodically samples the voltage of
+//! the harvester's storage with the ADC and sets a `hil::power::PowerLevel`
+//! from it: `Full` while it is above `reduced_mv`, `Reduced` below that, and
+//! `Minimal` below `minimal_mv`. Each change is passed to the kernel's power
+//! consumers, e.g. the radio, so they duty cycle, and to processes, so they
+//! sample their sensors less often. A level is only raised again once the
+//! voltage is `HYSTERESIS_MV` above its threshold, so that loads do not
+//! toggle with the noise on the supply.
+//!
+//! The voltage is usually measured through a divider; `divider` is the ratio
+//! of the voltage to the voltage at the pin. The capsule must be the only
+//! client of the ADC.
+//!
+//! Usage
+//! -----
+//!
+//! ```rust
+//! let consumers = static_init!(
+//!     [&'static kernel::hil::power::PowerConsumer; 1],
+//!     [radio_duty_cycler]
+//! );
+//! let harvester_alarm = static_init!(
+//!     VirtualMuxAlarm<'static, sam4l::ast::Ast>,
+//!     VirtualMuxAlarm::new(mux_alarm)
+//! );
+//! let harvester = static_init!(
+//!     capsules::energy_harvester::EnergyHarvester<
+//!         'static,
+//!         sam4l::adc::Adc,
+//!         VirtualMuxAlarm<'static, sam4l::ast::Ast>,
+//!     >,
+//!     capsules::energy_harvester::EnergyHarvester::new(
+//!         &sam4l::adc::ADC0,
+//!         &sam4l::adc::CHANNEL_AD1,
+//!         harvester_alarm,
+//!         consumers,
+//!         2,    // 1:2 divider
+//!         2800, // Reduced below 2.8 V
+//!         2400, // Minimal below 2.4 V
+//!         1000, // Sample every second
+//!         kernel::Grant::create()
+//!     )
+//! );
+//! sam4l::adc::ADC0.set_client(harvester);
+//! harvester_alarm.set_client(harvester);
+//! harvester.start();
+//! ```
+//!
+//! Syscall Interface
+//! -----------------
+//!
+//! - Stability: 1 - Experimental
+//!
+//! Levels are 0 for `Minimal`, 1 for `Reduced` and 2 for `Full`.
+//!
+//! ### Subscribe
+//!
+//! - `0`: Called with `(level, voltage_mv)` when the level changes.
+//!
+//! ### Command
+//!
+//! - `0`: Driver check.
+//! - `1`: Returns the level.
+//! - `2`: Returns the last sampled voltage, in mV.
+
+use core::cell::Cell;
+use kernel::hil::adc;
+use kernel::hil::power::{PowerConsumer, PowerLevel};
+use kernel::hil::time::{self, Frequency};
+use kernel::{AppId, Callback, Driver, Grant, ReturnCode};
+
+/// Syscall driver number.
+use crate::driver;
+pub const DRIVER_NUM: usize = driver::NUM::EnergyHarvester as usize;
+
+/// How far above its threshold the voltage must be to raise a level
+pub const HYSTERESIS_MV: u32 = 100;
+
+#[derive(Default)]
+pub struct App {
+    callback: Option<Callback>,
+}
+
+pub struct EnergyHarvester<'a, A: adc::Adc, T: time::Alarm> {
+    adc: &'a A,
+    channel: &'a A::Channel,
+    alarm: &'a T,
+    consumers: &'a [&'a PowerConsumer],
+    divider: u32,
+    reduced_mv: u32,
+    minimal_mv: u32,
+    period_ms: u32,
+    level: Cell<PowerLevel>,
+    voltage_mv: Cell<u32>,
+    apps: Grant<App>,
+}
+
+impl<A: adc::Adc, T: time::Alarm> EnergyHarvester<'a, A, T> {
+    pub fn new(
+        adc: &'a A,
+        channel: &'a A::Channel,
+        alarm: &'a T,
+        consumers: &'a [&'a PowerConsumer],
+        divider: u32,
+        reduced_mv: u32,
+        minimal_mv: u32,
+        period_ms: u32,
+        grant: Grant<App>,
+    ) -> EnergyHarvester<'a, A, T> {
+        EnergyHarvester {
+            adc: adc,
+            channel: channel,
+            alarm: alarm,
+            consumers: consumers,
+            divider: divider,
+            reduced_mv: reduced_mv,
+            minimal_mv: minimal_mv,
+            period_ms: period_ms,
+            level: Cell::new(PowerLevel::Full),
+            voltage_mv: Cell::new(0),
+            apps: grant,
+        }
+    }
+
+    /// Takes the first sample, and samples every `period_ms` after it.
+    /// Returns ENOSUPPORT if the ADC cannot tell its reference voltage.
+    pub fn start(&self) -> ReturnCode {
+        if self.adc.get_voltage_reference_mv().is_none() {
+            return ReturnCode::ENOSUPPORT;
+        }
+        self.sample();
+        ReturnCode::SUCCESS
+    }
+
+    fn sample(&self) {
+        if self.adc.sample(self.channel) != ReturnCode::SUCCESS {
+            self.schedule_sample();
+        }
+    }
+
+    fn schedule_sample(&self) {
+        let interval = <T::Frequency>::ms_to_tics(self.period_ms);
+        self.alarm
+            .set_alarm(self.alarm.now().wrapping_add(interval));
+    }
+
+    /// The voltage below which the level drops below `level`, or, while it
+    /// is below `level`, the voltage it must reach to rise to it.
+    fn threshold(&self, level: PowerLevel, threshold_mv: u32) -> u32 {
+        if self.level.get() < level {
+            threshold_mv + HYSTERESIS_MV
+        } else {
+            threshold_mv
+        }
+    }
+
+    fn level_for(&self, voltage_mv: u32) -> PowerLevel {
+        if voltage_mv < self.threshold(PowerLevel::Reduced, self.minimal_mv) {
+            PowerLevel::Minimal
+        } else if voltage_mv < self.threshold(PowerLevel::Full, self.reduced_mv) {
+            PowerLevel::Reduced
+        } else {
+            PowerLevel::Full
+        }
+    }
+
+    fn encode_level(level: PowerLevel) -> usize {
+        match level {
+            PowerLevel::Minimal => 0,
+            PowerLevel::Reduced => 1,
+            PowerLevel::Full => 2,
+        }
+    }
+}
+
+impl<A: adc::Adc, T: time::Alarm> adc::Client for EnergyHarvester<'a, A, T> {
+    fn sample_ready(&self, sample: u16) {
+        self.schedule_sample();
+
+        // Samples are left justified, so full scale is 2^16
+        let reference_mv = self.adc.get_voltage_reference_mv().unwrap_or(0) as u32;
+        let voltage_mv = ((sample as u32 * reference_mv) >> 16) * self.divider;
+        self.voltage_mv.set(voltage_mv);
+
+        let level = self.level_for(voltage_mv);
+        if level == self.level.get() {
+            return;
+        }
+        self.level.set(level);
+        for consumer in self.consumers.iter() {
+            consumer.set_power_level(level);
+        }
+        let encoded = EnergyHarvester::<A, T>::encode_level(level);
+        self.apps.each(|app| {
+            app.callback
+                .map(|mut cb| cb.schedule(encoded, voltage_mv as usize, 0));
+        });
+    }
+}
+
+impl<A: adc::Adc, T: time::Alarm> time::Client for EnergyHarvester<'a, A, T> {
+    fn fired(&self) {
+        self.sample();
+    }
+}
+
+impl<A: adc::Adc, T: time::Alarm> Driver for EnergyHarvester<'a, A, T> {
+    fn subscribe(
+        &self,
+        subscribe_num: usize,
+        callback: Option<Callback>,
+        app_id: AppId,
+    ) -> ReturnCode {
+        match subscribe_num {
+            0 => self
+                .apps
+                .enter(app_id, |app, _| {
+                    app.callback = callback;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or_else(|err| err.into()),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    fn command(&self, command_num: usize, _: usize, _: usize, _: AppId) -> ReturnCode {
+        match command_num {
+            0 /* check if present */ => ReturnCode::SUCCESS,
+
+            1 => ReturnCode::SuccessWithValue {
+                value: EnergyHarvester::<A, T>::encode_level(self.level.get()),
+            },
+
+            2 => ReturnCode::SuccessWithValue {
+                value: self.voltage_mv.get() as usize,
+            },
+
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+}
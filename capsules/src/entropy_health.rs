@@ -0,0 +1,467 @@
+//! Continuous health tests for hardware entropy sources.
+//!
+//! `HealthTestedEntropy32` sits between a hardware entropy source and its
+//! consumer (typically `rng::Entropy32ToRandom`) and runs the two continuous
+//! health tests from NIST SP 800-90B, section 4.4, on every byte of entropy
+//! that passes through it:
+//!
+//! - The repetition count test, which detects a source that gets stuck on a
+//!   single value.
+//! - The adaptive proportion test, which detects a source whose output is
+//!   dominated by one value over a window of samples.
+//!
+//! Before any entropy is delivered from a source, a startup test discards and
+//! tests `HealthTestConfig::startup_samples` bytes from it.
+//!
+//! If a test fails, the failing source is stopped and is never used again, and
+//! the `HealthTestClient` is told which source failed and why. If a fallback
+//! source was provided, it is started (including its startup test) and
+//! entropy requests are served from it from then on. If no healthy source
+//! remains, outstanding and future requests fail with `FAIL`.
+//!
+//! The default cutoffs assume that the source provides at least 4 bits of
+//! entropy per byte, and give a false positive rate of about 2^-20 per test.
+//! Sources with a different entropy estimate should provide their own
+//! `HealthTestConfig`, with cutoffs computed as described in SP 800-90B.
+//!
+//! Usage
+//! -----
+//!
+//! ```rust
+//! let entropy = static_init!(
+//!     capsules::entropy_health::HealthTestedEntropy32<'static>,
+//!     capsules::entropy_health::HealthTestedEntropy32::new(
+//!         &sam4l::trng::TRNG,
+//!         None,
+//!         capsules::entropy_health::DEFAULT_CONFIG
+//!     )
+//! );
+//! entropy.set_health_client(health_monitor);
+//! let entropy_to_random = static_init!(
+//!     capsules::rng::Entropy32ToRandom<'static>,
+//!     capsules::rng::Entropy32ToRandom::new(entropy)
+//! );
+//! entropy.set_client(entropy_to_random);
+//! ```
+
+use core::cell::Cell;
+use core::iter;
+use kernel::common::cells::OptionalCell;
+use kernel::hil::entropy;
+use kernel::hil::entropy::Entropy32;
+use kernel::ReturnCode;
+
+/// Cutoffs for the health tests. All counts are in bytes.
+#[derive(Copy, Clone, Debug)]
+pub struct HealthTestConfig {
+    /// The repetition count test fails when this many identical bytes are seen
+    /// in a row.
+    pub rct_cutoff: u16,
+    /// Number of bytes in each window of the adaptive proportion test.
+    pub apt_window: u16,
+    /// The adaptive proportion test fails when the first byte of a window
+    /// occurs this many times within the window.
+    pub apt_cutoff: u16,
+    /// Number of bytes tested and discarded when a source is started.
+    pub startup_samples: u16,
+}
+
+/// Cutoffs for a source providing at least 4 bits of entropy per byte.
+pub const DEFAULT_CONFIG: HealthTestConfig = HealthTestConfig {
+    rct_cutoff: 6,
+    apt_window: 512,
+    apt_cutoff: 62,
+    startup_samples: 1024,
+};
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Source {
+    Primary,
+    Fallback,
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum HealthTest {
+    RepetitionCount,
+    AdaptiveProportion,
+}
+
+pub trait HealthTestClient {
+    /// Called when `source` fails `test`. The source has been disabled by the
+    /// time this is called.
+    fn health_test_failed(&self, source: Source, test: HealthTest);
+}
+
+pub struct HealthTestedEntropy32<'a> {
+    primary: &'a Entropy32<'a>,
+    fallback: Option<&'a Entropy32<'a>>,
+    config: HealthTestConfig,
+    client: OptionalCell<&'a entropy::Client32>,
+    health_client: OptionalCell<&'a HealthTestClient>,
+    /// Source entropy is currently drawn from, or `None` if all have failed
+    active: Cell<Option<Source>>,
+    /// Words still to be discarded by the startup test
+    startup_remaining: Cell<usize>,
+    /// Set when a test fails while entropy is being delivered
+    failed: Cell<Option<HealthTest>>,
+    rct_value: Cell<u8>,
+    rct_count: Cell<u16>,
+    apt_value: Cell<u8>,
+    apt_count: Cell<u16>,
+    /// Bytes seen in the current adaptive proportion window, 0 if no window
+    /// has been started
+    apt_seen: Cell<u16>,
+}
+
+impl HealthTestedEntropy32<'a> {
+    pub fn new(
+        primary: &'a Entropy32<'a>,
+        fallback: Option<&'a Entropy32<'a>>,
+        config: HealthTestConfig,
+    ) -> HealthTestedEntropy32<'a> {
+        let entropy = HealthTestedEntropy32 {
+            primary: primary,
+            fallback: fallback,
+            config: config,
+            client: OptionalCell::empty(),
+            health_client: OptionalCell::empty(),
+            active: Cell::new(Some(Source::Primary)),
+            startup_remaining: Cell::new(0),
+            failed: Cell::new(None),
+            rct_value: Cell::new(0),
+            rct_count: Cell::new(0),
+            apt_value: Cell::new(0),
+            apt_count: Cell::new(0),
+            apt_seen: Cell::new(0),
+        };
+        entropy.reset_tests();
+        entropy
+    }
+
+    pub fn set_health_client(&self, client: &'a HealthTestClient) {
+        self.health_client.set(client);
+    }
+
+    /// Returns the source entropy is currently drawn from, or `None` if every
+    /// source has failed a health test.
+    pub fn active_source(&self) -> Option<Source> {
+        self.active.get()
+    }
+
+    fn source(&self, source: Source) -> Option<&'a Entropy32<'a>> {
+        match source {
+            Source::Primary => Some(self.primary),
+            Source::Fallback => self.fallback,
+        }
+    }
+
+    /// Clears all test state and schedules a new startup test, for when a
+    /// source is (re)started.
+    fn reset_tests(&self) {
+        self.startup_remaining
+            .set((self.config.startup_samples as usize + 3) / 4);
+        self.failed.set(None);
+        self.rct_count.set(0);
+        self.apt_seen.set(0);
+    }
+
+    /// Runs both tests on one byte.
+    fn test_sample(&self, sample: u8) -> Result<(), HealthTest> {
+        if self.rct_count.get() > 0 && sample == self.rct_value.get() {
+            let count = self.rct_count.get() + 1;
+            self.rct_count.set(count);
+            if count >= self.config.rct_cutoff {
+                return Err(HealthTest::RepetitionCount);
+            }
+        } else {
+            self.rct_value.set(sample);
+            self.rct_count.set(1);
+        }
+
+        let seen = self.apt_seen.get();
+        if seen == 0 {
+            self.apt_value.set(sample);
+            self.apt_count.set(1);
+        } else if sample == self.apt_value.get() {
+            let count = self.apt_count.get() + 1;
+            self.apt_count.set(count);
+            if count >= self.config.apt_cutoff {
+                return Err(HealthTest::AdaptiveProportion);
+            }
+        }
+        self.apt_seen.set((seen + 1) % self.config.apt_window);
+        Ok(())
+    }
+
+    /// Runs both tests on each byte of a word.
+    fn test_word(&self, word: u32) -> Result<(), HealthTest> {
+        for i in 0..4 {
+            self.test_sample((word >> (8 * i)) as u8)?;
+        }
+        Ok(())
+    }
+
+    /// Disables the active source after it failed `test` and switches to the
+    /// fallback, if there is one that has not failed yet. The failed source
+    /// is stopped by returning `Done` from its callback.
+    fn source_failed(&self, test: HealthTest) -> entropy::Continue {
+        let failed = match self.active.get() {
+            Some(source) => source,
+            None => return entropy::Continue::Done,
+        };
+        let next = match failed {
+            Source::Primary if self.fallback.is_some() => Some(Source::Fallback),
+            _ => None,
+        };
+        self.active.set(next);
+        self.health_client
+            .map(|client| client.health_test_failed(failed, test));
+
+        let started = next
+            .and_then(|source| self.source(source))
+            .map_or(false, |source| {
+                self.reset_tests();
+                source.get() == ReturnCode::SUCCESS
+            });
+        if !started {
+            self.active.set(None);
+            self.client
+                .map(|client| client.entropy_available(&mut iter::empty(), ReturnCode::FAIL));
+        }
+        entropy::Continue::Done
+    }
+}
+
+impl Entropy32<'a> for HealthTestedEntropy32<'a> {
+    fn get(&self) -> ReturnCode {
+        self.active
+            .get()
+            .and_then(|source| self.source(source))
+            .map_or(ReturnCode::FAIL, |source| source.get())
+    }
+
+    fn cancel(&self) -> ReturnCode {
+        self.active
+            .get()
+            .and_then(|source| self.source(source))
+            .map_or(ReturnCode::SUCCESS, |source| source.cancel())
+    }
+
+    fn set_client(&'a self, client: &'a entropy::Client32) {
+        self.primary.set_client(self);
+        self.fallback.map(|fallback| fallback.set_client(self));
+        self.client.set(client);
+    }
+}
+
+impl entropy::Client32 for HealthTestedEntropy32<'a> {
+    fn entropy_available(
+        &self,
+        entropy: &mut Iterator<Item = u32>,
+        error: ReturnCode,
+    ) -> entropy::Continue {
+        if self.active.get().is_none() {
+            return entropy::Continue::Done;
+        }
+        if error != ReturnCode::SUCCESS {
+            return self.client.map_or(entropy::Continue::Done, |client| {
+                client.entropy_available(entropy, error)
+            });
+        }
+
+        // Startup test: test and discard samples before using the source
+        while self.startup_remaining.get() > 0 {
+            match entropy.next() {
+                None => return entropy::Continue::More,
+                Some(word) => {
+                    if let Err(test) = self.test_word(word) {
+                        return self.source_failed(test);
+                    }
+                    self.startup_remaining.set(self.startup_remaining.get() - 1);
+                }
+            }
+        }
+
+        let result = self.client.map_or(entropy::Continue::Done, |client| {
+            client.entropy_available(
+                &mut HealthTestedIter {
+                    tester: self,
+                    entropy: entropy,
+                },
+                ReturnCode::SUCCESS,
+            )
+        });
+        match self.failed.get() {
+            Some(test) => self.source_failed(test),
+            None => result,
+        }
+    }
+}
+
+/// Yields words from the source that passed the health tests, ending as soon
+/// as one fails.
+struct HealthTestedIter<'a, 'b: 'a> {
+    tester: &'a HealthTestedEntropy32<'b>,
+    entropy: &'a mut Iterator<Item = u32>,
+}
+
+impl Iterator for HealthTestedIter<'a, 'b> {
+    type Item = u32;
+
+    fn next(&mut self) -> Option<u32> {
+        if self.tester.failed.get().is_some() {
+            return None;
+        }
+        let word = self.entropy.next()?;
+        match self.tester.test_word(word) {
+            Ok(()) => Some(word),
+            Err(test) => {
+                self.tester.failed.set(Some(test));
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{HealthTest, HealthTestClient, HealthTestedEntropy32, Source, DEFAULT_CONFIG};
+    use core::cell::Cell;
+    use core::iter;
+    use kernel::hil::entropy::{self, Entropy32};
+    use kernel::ReturnCode;
+
+    struct TestSource {
+        started: Cell<usize>,
+    }
+
+    impl TestSource {
+        fn new() -> TestSource {
+            TestSource {
+                started: Cell::new(0),
+            }
+        }
+    }
+
+    impl Entropy32<'a> for TestSource {
+        fn get(&self) -> ReturnCode {
+            self.started.set(self.started.get() + 1);
+            ReturnCode::SUCCESS
+        }
+
+        fn cancel(&self) -> ReturnCode {
+            ReturnCode::SUCCESS
+        }
+
+        fn set_client(&'a self, _client: &'a entropy::Client32) {}
+    }
+
+    struct FailureLog {
+        failure: Cell<Option<(Source, HealthTest)>>,
+    }
+
+    impl HealthTestClient for FailureLog {
+        fn health_test_failed(&self, source: Source, test: HealthTest) {
+            self.failure.set(Some((source, test)));
+        }
+    }
+
+    /// A byte that is never 0, and differs from the byte before it.
+    fn filler(i: usize) -> u8 {
+        (i % 255 + 1) as u8
+    }
+
+    #[test]
+    fn repetition_count_cutoff() {
+        let source = TestSource::new();
+        let tester = HealthTestedEntropy32::new(&source, None, DEFAULT_CONFIG);
+        // 5 identical bytes in a row pass, the 6th fails
+        for _ in 0..5 {
+            assert_eq!(tester.test_sample(0x42), Ok(()));
+        }
+        assert_eq!(tester.test_sample(0x42), Err(HealthTest::RepetitionCount));
+    }
+
+    #[test]
+    fn repetition_count_resets_on_new_value() {
+        let source = TestSource::new();
+        let tester = HealthTestedEntropy32::new(&source, None, DEFAULT_CONFIG);
+        for _ in 0..10 {
+            for _ in 0..5 {
+                assert_eq!(tester.test_sample(0x42), Ok(()));
+            }
+            assert_eq!(tester.test_sample(0x24), Ok(()));
+        }
+    }
+
+    #[test]
+    fn adaptive_proportion_cutoff() {
+        let source = TestSource::new();
+        let tester = HealthTestedEntropy32::new(&source, None, DEFAULT_CONFIG);
+        // The first byte of the window and 60 more occurrences of it pass,
+        // the 62nd occurrence fails
+        assert_eq!(tester.test_sample(0), Ok(()));
+        for i in 0..60 {
+            assert_eq!(tester.test_sample(filler(i)), Ok(()));
+            assert_eq!(tester.test_sample(0), Ok(()));
+        }
+        assert_eq!(tester.test_sample(filler(60)), Ok(()));
+        assert_eq!(tester.test_sample(0), Err(HealthTest::AdaptiveProportion));
+    }
+
+    #[test]
+    fn adaptive_proportion_resets_each_window() {
+        let source = TestSource::new();
+        let tester = HealthTestedEntropy32::new(&source, None, DEFAULT_CONFIG);
+        // Two windows of 512 bytes, each with 61 zeros
+        for _ in 0..2 {
+            assert_eq!(tester.test_sample(0), Ok(()));
+            for i in 0..60 {
+                assert_eq!(tester.test_sample(filler(i)), Ok(()));
+                assert_eq!(tester.test_sample(0), Ok(()));
+            }
+            for i in 121..512 {
+                assert_eq!(tester.test_sample(filler(i)), Ok(()));
+            }
+        }
+    }
+
+    #[test]
+    fn stuck_source_falls_back() {
+        let primary = TestSource::new();
+        let fallback = TestSource::new();
+        let log = FailureLog {
+            failure: Cell::new(None),
+        };
+        let tester = HealthTestedEntropy32::new(&primary, Some(&fallback), DEFAULT_CONFIG);
+        tester.set_health_client(&log);
+
+        // A stuck source fails the startup test, and the fallback is started
+        let result = entropy::Client32::entropy_available(
+            &tester,
+            &mut iter::repeat(0x5a5a5a5a),
+            ReturnCode::SUCCESS,
+        );
+        assert_eq!(result, entropy::Continue::Done);
+        assert_eq!(
+            log.failure.get(),
+            Some((Source::Primary, HealthTest::RepetitionCount))
+        );
+        assert_eq!(tester.active_source(), Some(Source::Fallback));
+        assert_eq!(fallback.started.get(), 1);
+
+        // Once the fallback fails too, no source is left
+        let result = entropy::Client32::entropy_available(
+            &tester,
+            &mut iter::repeat(0),
+            ReturnCode::SUCCESS,
+        );
+        assert_eq!(result, entropy::Continue::Done);
+        assert_eq!(
+            log.failure.get(),
+            Some((Source::Fallback, HealthTest::RepetitionCount))
+        );
+        assert_eq!(tester.active_source(), None);
+        assert_eq!(Entropy32::get(&tester), ReturnCode::FAIL);
+    }
+}
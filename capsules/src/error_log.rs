@@ -0,0 +1,464 @@
+//! Error log kept in a ring of flash pages.
+//!
+//! `FlashErrorLog` implements `kernel::error_log::ErrorLog`, so that the
+//! errors logged with `error_log!` persist across reboots. Entries of at
+//! least the persisted severity (`Warning` by default) are appended to the
+//! newest page of a dedicated region of flash; once it is full, the log moves
+//! on to the next page, and after the last page it wraps around and
+//! overwrites the oldest one.
+//!
+//! The most recent entries, persisted or not, are also kept in RAM, where the
+//! process console (`errlog`) and apps read them. When the kernel boots, the
+//! log scans its flash region to find where it left off and to reload the
+//! most recent entries, so that they are there after a crash. Entries logged
+//! while it scans or writes wait in a queue; if the queue is full, they are
+//! dropped and counted.
+//!
+//! Each entry takes 16 bytes of flash: its sequence number, severity, code
+//! and data, as little-endian words.
+//!
+//! Usage
+//! -----
+//!
+//! ```rust
+//! static mut ERROR_LOG_RECENT: [kernel::error_log::Entry; 32] =
+//!     [kernel::error_log::Entry::EMPTY; 32];
+//! static mut ERROR_LOG_PENDING: [kernel::error_log::Entry; 8] =
+//!     [kernel::error_log::Entry::EMPTY; 8];
+//! static mut ERROR_LOG_PAGE: sam4l::flashcalw::Sam4lPage = sam4l::flashcalw::Sam4lPage::new();
+//!
+//! let error_log = static_init!(
+//!     capsules::error_log::FlashErrorLog<'static, sam4l::flashcalw::FLASHCALW>,
+//!     capsules::error_log::FlashErrorLog::new(
+//!         &sam4l::flashcalw::FLASH_CONTROLLER,
+//!         0x3E000 / 512, // first page of the region
+//!         4,             // pages in the region
+//!         &mut ERROR_LOG_PAGE,
+//!         &mut ERROR_LOG_RECENT,
+//!         &mut ERROR_LOG_PENDING,
+//!         kernel::Grant::create()
+//!     )
+//! );
+//! hil::flash::HasClient::set_client(&sam4l::flashcalw::FLASH_CONTROLLER, error_log);
+//! kernel::error_log::set_error_log(error_log);
+//! error_log.initialize();
+//! ```
+//!
+//! Syscall Interface
+//! -----------------
+//!
+//! - Allow 0: a buffer for entries.
+//! - Command 0: whether the driver exists.
+//! - Command 1: the number of recent entries.
+//! - Command 2: copy the recent entries, oldest first, into the buffer, in
+//!   the same format as in flash, and return how many fit.
+
+use core::cell::Cell;
+use kernel::common::cells::TakeCell;
+use kernel::error_log::{Entry, ErrorLog, Severity};
+use kernel::hil;
+use kernel::{AppId, AppSlice, Driver, Grant, ReturnCode, Shared};
+
+/// Syscall driver number.
+use crate::driver;
+pub const DRIVER_NUM: usize = driver::NUM::ErrorLog as usize;
+
+const ENTRY_SIZE: usize = 16;
+
+/// The sequence number of a slot of flash with no entry.
+const ERASED: u32 = 0xFFFFFFFF;
+
+#[derive(Copy, Clone, PartialEq)]
+enum State {
+    /// Not initialized yet
+    Off,
+    /// Reading each page, to find the newest entry
+    Scanning,
+    /// Reading the newest page, to append to it
+    Loading,
+    Idle,
+    Writing,
+}
+
+#[derive(Default)]
+pub struct App {
+    buffer: Option<AppSlice<Shared, u8>>,
+}
+
+pub struct FlashErrorLog<'a, F: hil::flash::Flash + 'static> {
+    flash: &'a F,
+    first_page: usize,
+    pages: usize,
+    /// The newest page, as it is in flash or is being written
+    page: TakeCell<'static, F::Page>,
+    /// Ring of the most recent entries
+    recent: TakeCell<'static, [Entry]>,
+    recent_len: Cell<usize>,
+    recent_next: Cell<usize>,
+    /// Queue of entries waiting to be numbered and written
+    pending: TakeCell<'static, [Entry]>,
+    pending_len: Cell<usize>,
+    pending_head: Cell<usize>,
+    dropped: Cell<u32>,
+    apps: Grant<App>,
+    state: Cell<State>,
+    /// The page being scanned, or the newest page, in the region
+    page_index: Cell<usize>,
+    head_page: Cell<usize>,
+    /// Entries in the newest page
+    head_fill: Cell<usize>,
+    /// The newest entry found while scanning
+    newest: Cell<Option<u32>>,
+    next_sequence: Cell<u32>,
+    persist: Cell<Severity>,
+}
+
+impl<F: hil::flash::Flash> FlashErrorLog<'a, F> {
+    pub fn new(
+        flash: &'a F,
+        first_page: usize,
+        pages: usize,
+        page: &'static mut F::Page,
+        recent: &'static mut [Entry],
+        pending: &'static mut [Entry],
+        grant: Grant<App>,
+    ) -> FlashErrorLog<'a, F> {
+        FlashErrorLog {
+            flash: flash,
+            first_page: first_page,
+            pages: pages,
+            page: TakeCell::new(page),
+            recent: TakeCell::new(recent),
+            recent_len: Cell::new(0),
+            recent_next: Cell::new(0),
+            pending: TakeCell::new(pending),
+            pending_len: Cell::new(0),
+            pending_head: Cell::new(0),
+            dropped: Cell::new(0),
+            apps: grant,
+            state: Cell::new(State::Off),
+            page_index: Cell::new(0),
+            head_page: Cell::new(0),
+            head_fill: Cell::new(0),
+            newest: Cell::new(None),
+            next_sequence: Cell::new(0),
+            persist: Cell::new(Severity::Warning),
+        }
+    }
+
+    /// Only persist entries of at least `severity`. Entries below it are
+    /// only kept in RAM.
+    pub fn set_persist_severity(&self, severity: Severity) {
+        self.persist.set(severity);
+    }
+
+    /// The number of entries that were dropped because too many were logged
+    /// at once.
+    pub fn dropped(&self) -> u32 {
+        self.dropped.get()
+    }
+
+    /// Scan the flash region for the entries of previous boots. Entries are
+    /// only written out once this is done.
+    pub fn initialize(&self) -> ReturnCode {
+        if self.state.get() != State::Off || self.pages == 0 {
+            return ReturnCode::EALREADY;
+        }
+        self.state.set(State::Scanning);
+        self.page_index.set(0);
+        self.read_page(0)
+    }
+
+    fn read_page(&self, index: usize) -> ReturnCode {
+        self.page.take().map_or(ReturnCode::EBUSY, |page| {
+            self.flash.read_page(self.first_page + index, page)
+        })
+    }
+
+    fn entries_per_page(page: &mut F::Page) -> usize {
+        page.as_mut().len() / ENTRY_SIZE
+    }
+
+    fn decode(slot: &[u8]) -> Option<Entry> {
+        let word = |i: usize| u32::from_le_bytes([slot[i], slot[i + 1], slot[i + 2], slot[i + 3]]);
+        let sequence = word(0);
+        if sequence == ERASED {
+            return None;
+        }
+        Severity::from_u8(slot[4]).map(|severity| Entry {
+            sequence: sequence,
+            severity: severity,
+            code: word(8),
+            data: word(12),
+        })
+    }
+
+    fn encode(entry: &Entry, slot: &mut [u8]) {
+        slot[0..4].copy_from_slice(&entry.sequence.to_le_bytes());
+        slot[4] = entry.severity as u8;
+        slot[5..8].copy_from_slice(&[0xFF; 3]);
+        slot[8..12].copy_from_slice(&entry.code.to_le_bytes());
+        slot[12..16].copy_from_slice(&entry.data.to_le_bytes());
+    }
+
+    /// Keep `entry`, found while scanning, if it is among the most recent.
+    /// While scanning, the recent entries are sorted, oldest first.
+    fn insert_scanned(&self, entry: Entry) {
+        self.recent.map(|recent| {
+            let mut len = self.recent_len.get();
+            if len == recent.len() {
+                if recent.is_empty() || entry.sequence <= recent[0].sequence {
+                    return;
+                }
+                // Forget the oldest
+                for i in 1..len {
+                    recent[i - 1] = recent[i];
+                }
+                len -= 1;
+            }
+            let mut i = len;
+            while i > 0 && recent[i - 1].sequence > entry.sequence {
+                recent[i] = recent[i - 1];
+                i -= 1;
+            }
+            recent[i] = entry;
+            self.recent_len.set(len + 1);
+        });
+    }
+
+    fn push_recent(&self, entry: Entry) {
+        self.recent.map(|recent| {
+            if recent.is_empty() {
+                return;
+            }
+            let next = self.recent_next.get();
+            recent[next] = entry;
+            self.recent_next.set((next + 1) % recent.len());
+            self.recent_len
+                .set(core::cmp::min(self.recent_len.get() + 1, recent.len()));
+        });
+    }
+
+    fn scan(&self, page: &mut F::Page) {
+        let index = self.page_index.get();
+        let per_page = Self::entries_per_page(page);
+        for (slot_index, slot) in page.as_mut().chunks(ENTRY_SIZE).take(per_page).enumerate() {
+            match Self::decode(slot) {
+                Some(entry) => {
+                    self.insert_scanned(entry);
+                    if self
+                        .newest
+                        .get()
+                        .map_or(true, |newest| entry.sequence > newest)
+                    {
+                        self.newest.set(Some(entry.sequence));
+                        self.head_page.set(index);
+                        self.head_fill.set(slot_index + 1);
+                    }
+                }
+                // Entries fill each page from its start
+                None => break,
+            }
+        }
+    }
+
+    /// The scan is done: resume after the newest entry.
+    fn resume(&self, page: &'static mut F::Page) {
+        self.recent.map(|recent| {
+            if !recent.is_empty() {
+                self.recent_next.set(self.recent_len.get() % recent.len());
+            }
+        });
+        self.next_sequence
+            .set(self.newest.get().map_or(0, |newest| newest.wrapping_add(1)));
+
+        if self.head_fill.get() > 0 && self.head_fill.get() < Self::entries_per_page(page) {
+            // Append to the newest page
+            self.page.replace(page);
+            self.state.set(State::Loading);
+            if self.read_page(self.head_page.get()) != ReturnCode::SUCCESS {
+                self.state.set(State::Idle);
+            }
+        } else {
+            if self.head_fill.get() > 0 {
+                self.head_page.set((self.head_page.get() + 1) % self.pages);
+            }
+            self.start_page(page);
+            self.page.replace(page);
+            self.state.set(State::Idle);
+            self.process_pending();
+        }
+    }
+
+    fn start_page(&self, page: &mut F::Page) {
+        for byte in page.as_mut().iter_mut() {
+            *byte = 0xFF;
+        }
+        self.head_fill.set(0);
+    }
+
+    /// Number the entries that are waiting, and write out the first one to
+    /// persist.
+    fn process_pending(&self) {
+        while self.state.get() == State::Idle && self.pending_len.get() > 0 {
+            let entry = self.pending.map_or(None, |pending| {
+                let head = self.pending_head.get();
+                self.pending_head.set((head + 1) % pending.len());
+                self.pending_len.set(self.pending_len.get() - 1);
+                Some(pending[head])
+            });
+            let mut entry = match entry {
+                Some(entry) => entry,
+                None => return,
+            };
+            entry.sequence = self.next_sequence.get();
+            self.next_sequence.set(entry.sequence.wrapping_add(1));
+            self.push_recent(entry);
+
+            if entry.severity >= self.persist.get() {
+                self.page.take().map(|page| {
+                    let fill = self.head_fill.get();
+                    Self::encode(
+                        &entry,
+                        &mut page.as_mut()[fill * ENTRY_SIZE..(fill + 1) * ENTRY_SIZE],
+                    );
+                    self.head_fill.set(fill + 1);
+                    self.state.set(State::Writing);
+                    let rcode = self
+                        .flash
+                        .write_page(self.first_page + self.head_page.get(), page);
+                    if rcode != ReturnCode::SUCCESS {
+                        // The flash hands the page back even if it fails
+                        self.state.set(State::Idle);
+                    }
+                });
+            }
+        }
+    }
+}
+
+impl<F: hil::flash::Flash> ErrorLog for FlashErrorLog<'a, F> {
+    fn log(&self, severity: Severity, code: u32, data: u32) {
+        let queued = self.pending.map_or(false, |pending| {
+            let len = self.pending_len.get();
+            if len == pending.len() {
+                return false;
+            }
+            pending[(self.pending_head.get() + len) % pending.len()] = Entry {
+                sequence: 0,
+                severity: severity,
+                code: code,
+                data: data,
+            };
+            self.pending_len.set(len + 1);
+            true
+        });
+        if !queued {
+            self.dropped.set(self.dropped.get().saturating_add(1));
+        }
+        self.process_pending();
+    }
+
+    fn recent(&self, f: &mut FnMut(&Entry)) {
+        self.recent.map(|recent| {
+            let len = self.recent_len.get();
+            let oldest = if len < recent.len() {
+                0
+            } else {
+                self.recent_next.get()
+            };
+            for i in 0..len {
+                f(&recent[(oldest + i) % recent.len()]);
+            }
+        });
+    }
+}
+
+impl<F: hil::flash::Flash> hil::flash::Client<F> for FlashErrorLog<'a, F> {
+    fn read_complete(&self, page: &'static mut F::Page, _error: hil::flash::Error) {
+        match self.state.get() {
+            State::Scanning => {
+                self.scan(page);
+                let next = self.page_index.get() + 1;
+                if next < self.pages {
+                    self.page_index.set(next);
+                    self.page.replace(page);
+                    if self.read_page(next) != ReturnCode::SUCCESS {
+                        self.page.take().map(|page| self.resume(page));
+                    }
+                } else {
+                    self.resume(page);
+                }
+            }
+            State::Loading => {
+                self.page.replace(page);
+                self.state.set(State::Idle);
+                self.process_pending();
+            }
+            _ => {
+                self.page.replace(page);
+            }
+        }
+    }
+
+    fn write_complete(&self, page: &'static mut F::Page, _error: hil::flash::Error) {
+        if self.head_fill.get() == Self::entries_per_page(page) {
+            // Rotate to the next page, overwriting the oldest entries
+            self.head_page.set((self.head_page.get() + 1) % self.pages);
+            self.start_page(page);
+        }
+        self.page.replace(page);
+        self.state.set(State::Idle);
+        self.process_pending();
+    }
+
+    fn erase_complete(&self, _error: hil::flash::Error) {}
+}
+
+impl<F: hil::flash::Flash> Driver for FlashErrorLog<'a, F> {
+    fn allow(
+        &self,
+        appid: AppId,
+        allow_num: usize,
+        slice: Option<AppSlice<Shared, u8>>,
+    ) -> ReturnCode {
+        match allow_num {
+            0 => self
+                .apps
+                .enter(appid, |app, _| {
+                    app.buffer = slice;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or_else(|err| err.into()),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    fn command(&self, command_num: usize, _: usize, _: usize, appid: AppId) -> ReturnCode {
+        match command_num {
+            0 => ReturnCode::SUCCESS,
+            1 => ReturnCode::SuccessWithValue {
+                value: self.recent_len.get(),
+            },
+            2 => self
+                .apps
+                .enter(appid, |app, _| {
+                    app.buffer.as_mut().map_or(ReturnCode::ENOMEM, |buffer| {
+                        let mut slots = buffer.as_mut().chunks_mut(ENTRY_SIZE);
+                        let mut count = 0;
+                        self.recent(&mut |entry| {
+                            if let Some(slot) = slots.next() {
+                                if slot.len() == ENTRY_SIZE {
+                                    Self::encode(entry, slot);
+                                    count += 1;
+                                }
+                            }
+                        });
+                        ReturnCode::SuccessWithValue { value: count }
+                    })
+                })
+                .unwrap_or_else(|err| err.into()),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+}
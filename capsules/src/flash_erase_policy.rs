@@ -0,0 +1,116 @@
+//! Holds flash erases back while the radio or a latency-critical alarm needs
+//! the CPU.
+//!
+//! On many chips erasing a page stalls every read of the flash, and so the
+//! CPU, for several milliseconds. `IdleErasePolicy` is an `ErasePolicy` for
+//! `virtual_flash::MuxFlash` that only lets a page be erased while the radio
+//! is not busy and none of the alarms the board marks as critical fires
+//! within `erase_ms`, the time one page takes to erase. Otherwise it tries
+//! again after `erase_ms`. So that erases are not starved, a page is erased
+//! anyway once it has waited for `MAX_RETRIES` tries.
+//!
+//! Usage
+//! -----
+//!
+//! ```rust
+//! let policy_alarm = static_init!(
+//!     VirtualMuxAlarm<'static, cc26x2::rtc::Rtc>,
+//!     VirtualMuxAlarm::new(mux_alarm)
+//! );
+//! let critical_alarms = static_init!(
+//!     [&'static VirtualMuxAlarm<'static, cc26x2::rtc::Rtc>; 1],
+//!     [userspace_alarm]
+//! );
+//! let erase_policy = static_init!(
+//!     capsules::flash_erase_policy::IdleErasePolicy<
+//!         'static,
+//!         cc26x2::ieee802154_radio::Radio,
+//!         VirtualMuxAlarm<'static, cc26x2::rtc::Rtc>,
+//!     >,
+//!     capsules::flash_erase_policy::IdleErasePolicy::new(
+//!         &cc26x2::ieee802154_radio::RADIO,
+//!         policy_alarm,
+//!         critical_alarms,
+//!         10 // ms per page
+//!     )
+//! );
+//! policy_alarm.set_client(erase_policy);
+//! mux_flash.set_erase_policy(erase_policy);
+//! ```
+
+use core::cell::Cell;
+use kernel::common::cells::OptionalCell;
+use kernel::hil::radio;
+use kernel::hil::time::{self, Frequency};
+
+use crate::virtual_flash::{ErasePolicy, ErasePolicyClient};
+
+/// How many tries an erase waits for before it is allowed anyway
+pub const MAX_RETRIES: usize = 50;
+
+pub struct IdleErasePolicy<'a, R: radio::RadioConfig, A: time::Alarm> {
+    radio: &'a R,
+    /// Wakes the policy up to try again
+    alarm: &'a A,
+    critical_alarms: &'a [&'a A],
+    erase_ms: u32,
+    /// The tries since an erase was last allowed
+    retries: Cell<usize>,
+    client: OptionalCell<&'a ErasePolicyClient>,
+}
+
+impl<R: radio::RadioConfig, A: time::Alarm> IdleErasePolicy<'a, R, A> {
+    pub fn new(
+        radio: &'a R,
+        alarm: &'a A,
+        critical_alarms: &'a [&'a A],
+        erase_ms: u32,
+    ) -> IdleErasePolicy<'a, R, A> {
+        IdleErasePolicy {
+            radio: radio,
+            alarm: alarm,
+            critical_alarms: critical_alarms,
+            erase_ms: erase_ms,
+            retries: Cell::new(0),
+            client: OptionalCell::empty(),
+        }
+    }
+
+    /// Returns whether a critical alarm fires before a page could be erased.
+    fn alarm_due(&self) -> bool {
+        let now = self.alarm.now();
+        let erase_tics = <A::Frequency>::ms_to_tics(self.erase_ms);
+        self.critical_alarms.iter().any(|alarm| {
+            // An alarm that is already late wraps around to a large value
+            let left = alarm.get_alarm().wrapping_sub(now);
+            alarm.is_armed() && (left <= erase_tics || left > u32::max_value() / 2)
+        })
+    }
+}
+
+impl<R: radio::RadioConfig, A: time::Alarm> ErasePolicy<'a> for IdleErasePolicy<'a, R, A> {
+    fn set_client(&self, client: &'a ErasePolicyClient) {
+        self.client.set(client);
+    }
+
+    fn erase_allowed(&self) -> bool {
+        let idle = !self.radio.busy() && !self.alarm_due();
+        if idle || self.retries.get() >= MAX_RETRIES {
+            self.retries.set(0);
+            return true;
+        }
+        if !self.alarm.is_armed() {
+            let interval = <A::Frequency>::ms_to_tics(self.erase_ms);
+            self.alarm
+                .set_alarm(self.alarm.now().wrapping_add(interval));
+        }
+        false
+    }
+}
+
+impl<R: radio::RadioConfig, A: time::Alarm> time::Client for IdleErasePolicy<'a, R, A> {
+    fn fired(&self) {
+        self.retries.set(self.retries.get() + 1);
+        self.client.map(|client| client.erase_window_opened());
+    }
+}
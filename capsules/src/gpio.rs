@@ -26,6 +26,10 @@
 //! }
 //! ```
 //!
+//! Pins named by the board with `kernel::debug::assign_pin_labels()` are
+//! printed by name by `debug_pins()`. As the driver is given pins wrapped in
+//! `InterruptValueWrapper`s, it is the wrappers that should be named.
+//!
 //! Syscall Interface
 //! -----------------
 //!
@@ -41,14 +45,18 @@
 //! ### Subscribes
 //!
 //! The GPIO interface provides only one callback, which is used for pins that
-//! have had interrupts enabled.
+//! have had interrupts enabled. It is subscribed either to each interrupt,
+//! or, for pins that may bounce or toggle fast, to coalesced interrupts: the
+//! interrupts that happen while the callback is queued are merged into it,
+//! and it is passed the set of pins that fired rather than a single pin.
 
 /// Syscall driver number.
 use crate::driver;
 pub const DRIVER_NUM: usize = driver::NUM::Gpio as usize;
 
+use kernel::debug;
 use kernel::hil::gpio;
-use kernel::{AppId, Callback, Driver, Grant, ReturnCode};
+use kernel::{AppId, Callback, Coalesce, Driver, Grant, ReturnCode};
 
 pub struct GPIO<'a> {
     pins: &'a [&'a gpio::InterruptValuePin],
@@ -69,6 +77,19 @@ impl<'a> GPIO<'a> {
         }
     }
 
+    /// Prints the configuration and level of each pin, with the name the
+    /// board has given it, if any, to help with bringing up a board.
+    pub fn debug_pins(&self) {
+        for (i, pin) in self.pins.iter().enumerate() {
+            debug!(
+                "GPIO {:<3}{:<20}{}",
+                i,
+                debug::pin_label(*pin).unwrap_or(""),
+                debug::PinState(*pin)
+            );
+        }
+    }
+
     fn configure_input_pin(&self, pin_num: u32, config: usize) -> ReturnCode {
         let pin = self.pins[pin_num as usize];
         pin.make_input();
@@ -119,9 +140,15 @@ impl<'a> gpio::ClientWithValue for GPIO<'a> {
         let pins = self.pins.as_ref();
         let pin_state = pins[pin_num as usize].read();
 
-        // schedule callback with the pin number and value
+        // schedule callback with the pin number and value, or with the bit of
+        // the pin if it coalesces
         self.apps.each(|callback| {
-            callback.map(|mut cb| cb.schedule(pin_num as usize, pin_state as usize, 0));
+            callback.map(|mut cb| match cb.coalescing() {
+                Coalesce::Bitmap => 1usize
+                    .checked_shl(pin_num)
+                    .map_or(false, |bit| cb.schedule(bit, 0, 0)),
+                _ => cb.schedule(pin_num as usize, pin_state as usize, 0),
+            });
         });
     }
 }
@@ -133,6 +160,10 @@ impl<'a> Driver for GPIO<'a> {
     ///
     /// - `0`: Subscribe to interrupts from all pins with interrupts enabled.
     ///        The callback signature is `fn(pin_num: usize, pin_state: bool)`
+    /// - `1`: Subscribe to coalesced interrupts from the first 32 pins with
+    ///        interrupts enabled, instead of to each interrupt. The callback
+    ///        signature is `fn(pins: usize)`, with a bit set for each pin
+    ///        that fired since the callback was last queued.
     fn subscribe(
         &self,
         subscribe_num: usize,
@@ -150,6 +181,16 @@ impl<'a> Driver for GPIO<'a> {
                 })
                 .unwrap_or_else(|err| err.into()),
 
+            // subscribe to coalesced interrupts, in place of the callback
+            // of each interrupt
+            1 => self
+                .apps
+                .enter(app_id, |app, _| {
+                    **app = callback.map(|cb| cb.coalesced(Coalesce::Bitmap));
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or_else(|err| err.into()),
+
             // default
             _ => ReturnCode::ENOSUPPORT,
         }
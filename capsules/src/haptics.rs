@@ -0,0 +1,138 @@
+//! Provides userspace with access to a haptic driver.
+//!
+//! Processes can play vibration patterns made of the effects in the driver's
+//! library, stop them, and select the library. Only one command runs on the
+//! driver at a time; the process that started it is told when it is done.
+//!
+//! Usage
+//! -----
+//!
+//! ```rust
+//! let haptics = static_init!(
+//!     capsules::haptics::HapticsDriver<'static>,
+//!     capsules::haptics::HapticsDriver::new(drv2605, kernel::Grant::create())
+//! );
+//! kernel::hil::haptics::Haptics::set_client(drv2605, haptics);
+//! ```
+//!
+//! Syscall Interface
+//! -----------------
+//!
+//! - Stability: 1 - Experimental
+//!
+//! ### Subscribe
+//!
+//! - `0`: Called with `(result)` when a command is done.
+//!
+//! ### Command
+//!
+//! - `0`: Driver check.
+//! - `1`: Play the effects in the bytes of `data`, lowest byte first, up to
+//!   the first zero byte.
+//! - `2`: Stop playing.
+//! - `3`: Select library `data`.
+
+use core::mem;
+use kernel::common::cells::OptionalCell;
+use kernel::hil::haptics;
+use kernel::{AppId, Callback, Driver, Grant, ReturnCode};
+
+/// Syscall driver number.
+use crate::driver;
+pub const DRIVER_NUM: usize = driver::NUM::Haptics as usize;
+
+#[derive(Default)]
+pub struct App {
+    callback: Option<Callback>,
+}
+
+pub struct HapticsDriver<'a> {
+    haptics: &'a haptics::Haptics<'a>,
+    apps: Grant<App>,
+    /// The process whose command is running
+    owner: OptionalCell<AppId>,
+}
+
+impl HapticsDriver<'a> {
+    pub fn new(haptics: &'a haptics::Haptics<'a>, grant: Grant<App>) -> HapticsDriver<'a> {
+        HapticsDriver {
+            haptics: haptics,
+            apps: grant,
+            owner: OptionalCell::empty(),
+        }
+    }
+
+    /// Runs `command` on the driver for `appid`, unless another process's
+    /// command is running.
+    fn start<F: FnOnce() -> ReturnCode>(&self, appid: AppId, command: F) -> ReturnCode {
+        if self.owner.is_some() {
+            return ReturnCode::EBUSY;
+        }
+        let result = command();
+        if result == ReturnCode::SUCCESS {
+            self.owner.set(appid);
+        }
+        result
+    }
+
+    fn play(&self, packed: usize) -> ReturnCode {
+        let mut effects = [0; mem::size_of::<usize>()];
+        let mut len = 0;
+        for effect in effects.iter_mut() {
+            *effect = (packed >> (8 * len)) as u8;
+            if *effect == 0 {
+                break;
+            }
+            len += 1;
+        }
+        if len > self.haptics.max_effects() {
+            return ReturnCode::EINVAL;
+        }
+        self.haptics.play(&effects[..len])
+    }
+}
+
+impl haptics::Client for HapticsDriver<'a> {
+    fn command_done(&self, result: ReturnCode) {
+        self.owner.take().map(|appid| {
+            let _ = self.apps.enter(appid, |app, _| {
+                app.callback
+                    .map(|mut cb| cb.schedule(usize::from(result), 0, 0));
+            });
+        });
+    }
+}
+
+impl Driver for HapticsDriver<'a> {
+    fn subscribe(
+        &self,
+        subscribe_num: usize,
+        callback: Option<Callback>,
+        app_id: AppId,
+    ) -> ReturnCode {
+        match subscribe_num {
+            0 => self
+                .apps
+                .enter(app_id, |app, _| {
+                    app.callback = callback;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or_else(|err| err.into()),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    fn command(&self, command_num: usize, data: usize, _: usize, appid: AppId) -> ReturnCode {
+        match command_num {
+            0 /* check if present */ => ReturnCode::SUCCESS,
+
+            1 => self.start(appid, || self.play(data)),
+
+            2 => self.start(appid, || self.haptics.stop()),
+
+            3 => self.start(appid, || self.haptics.set_library(data as u8)),
+
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+}
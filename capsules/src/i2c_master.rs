@@ -220,3 +220,11 @@ impl<I: i2c::I2CMaster> i2c::I2CHwMasterClient for I2CMasterDriver<I> {
         self.buf.put(Some(buffer));
     }
 }
+
+/// The driver is also the client of a `virtual_i2c::I2CDevice` that it uses
+/// as its `I2CMaster`, to share the bus with kernel drivers.
+impl<I: i2c::I2CMaster> i2c::I2CClient for I2CMasterDriver<I> {
+    fn command_complete(&self, buffer: &'static mut [u8], error: i2c::Error) {
+        i2c::I2CHwMasterClient::command_complete(self, buffer, error);
+    }
+}
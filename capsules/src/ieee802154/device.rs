@@ -13,6 +13,7 @@
 
 use crate::ieee802154::framer::Frame;
 use crate::net::ieee802154::{Header, KeyId, MacAddress, PanID, SecurityLevel};
+use kernel::hil::radio::TxInfo;
 use kernel::ReturnCode;
 
 pub trait MacDevice<'a> {
@@ -73,6 +74,11 @@ pub trait MacDevice<'a> {
     /// transmission process fails, the buffer inside the frame is returned so
     /// that it can be re-used.
     fn transmit(&self, frame: Frame) -> (ReturnCode, Option<&'static mut [u8]>);
+
+    /// Returns the link-layer details (retries, acknowledgement RSSI) of the
+    /// most recently completed transmission. This is only meaningful when
+    /// called from the `send_done` callback.
+    fn get_tx_info(&self) -> TxInfo;
 }
 
 /// Trait to be implemented by any user of the IEEE 802.15.4 device that
@@ -3,17 +3,28 @@
 //! Implements a userspace interface for sending and receiving IEEE 802.15.4
 //! frames. Also provides a minimal list-based interface for managing keys and
 //! known link neighbors, which is needed for 802.15.4 security.
+//!
+//! Any number of processes can receive frames at the same time. Each process
+//! can restrict the frames it is given to those sent to a particular PAN or
+//! from a particular source address, and chooses the security level and key
+//! used for each frame it transmits. The board can limit the rate at which
+//! each process transmits by setting a `RateLimiter` with `set_rate_limiter`.
 
 use crate::ieee802154::{device, framer};
 use crate::net::ieee802154::{AddressMode, Header, KeyId, MacAddress, PanID, SecurityLevel};
+use crate::net::rate_limit::RateLimiter;
 use crate::net::stream::{decode_bytes, decode_u8, encode_bytes, encode_u8, SResult};
+use crate::tamper::Wipe;
 use core::cell::Cell;
 use core::cmp::min;
 use kernel::common::cells::{MapCell, OptionalCell, TakeCell};
+use kernel::common::secure::zeroize;
+use kernel::hil::radio::TxInfo;
 use kernel::{AppId, AppSlice, Callback, Driver, Grant, ReturnCode, Shared};
 
 const MAX_NEIGHBORS: usize = 4;
 const MAX_KEYS: usize = 4;
+const BROADCAST_PAN: PanID = 0xffff;
 
 /// Syscall number
 pub const DRIVER_NUM: usize = 0x30001;
@@ -151,6 +162,25 @@ pub struct App {
     app_write: Option<AppSlice<Shared, u8>>,
     app_cfg: Option<AppSlice<Shared, u8>>,
     pending_tx: Option<(u16, Option<(SecurityLevel, KeyId)>)>,
+    /// Only frames sent to this PAN (or to the broadcast PAN) are received
+    rx_pan_filter: Option<PanID>,
+    /// Only frames sent from this address are received
+    rx_src_filter: Option<MacAddress>,
+}
+
+impl App {
+    /// Whether a received frame passes this app's receive filters.
+    fn accepts(&self, header: &Header) -> bool {
+        let pan_ok = self.rx_pan_filter.map_or(true, |pan| {
+            header
+                .dst_pan
+                .map_or(false, |dst_pan| dst_pan == pan || dst_pan == BROADCAST_PAN)
+        });
+        let src_ok = self
+            .rx_src_filter
+            .map_or(true, |addr| header.src_addr == Some(addr));
+        pan_ok && src_ok
+    }
 }
 
 impl Default for App {
@@ -162,6 +192,8 @@ impl Default for App {
             app_write: None,
             app_cfg: None,
             pending_tx: None,
+            rx_pan_filter: None,
+            rx_src_filter: None,
         }
     }
 }
@@ -189,6 +221,9 @@ pub struct RadioDriver<'a> {
 
     /// Buffer that stores the IEEE 802.15.4 frame to be transmitted.
     kernel_tx: TakeCell<'static, [u8]>,
+
+    /// Per-process transmission rate limits
+    rate_limiter: OptionalCell<&'a RateLimiter>,
 }
 
 impl RadioDriver<'a> {
@@ -206,9 +241,15 @@ impl RadioDriver<'a> {
             apps: grant,
             current_app: OptionalCell::empty(),
             kernel_tx: TakeCell::new(kernel_tx),
+            rate_limiter: OptionalCell::empty(),
         }
     }
 
+    /// Sets the limiter that every transmitted frame is charged to.
+    pub fn set_rate_limiter(&self, rate_limiter: &'a RateLimiter) {
+        self.rate_limiter.set(rate_limiter);
+    }
+
     // Neighbor management functions
 
     /// Add a new neighbor to the end of the list if there is still space
@@ -301,6 +342,8 @@ impl RadioDriver<'a> {
                 for i in index..(num_keys - 1) {
                     keys[i] = keys[i + 1];
                 }
+                // Do not leave a copy of the removed key behind
+                zeroize(&mut keys[num_keys - 1].key);
             });
             self.num_keys.set(num_keys - 1);
             ReturnCode::SUCCESS
@@ -523,6 +566,18 @@ impl framer::KeyProcedure for RadioDriver<'a> {
     }
 }
 
+impl Wipe for RadioDriver<'a> {
+    /// Erases and removes all keys.
+    fn wipe(&self) {
+        self.keys.map(|keys| {
+            for key in keys.iter_mut() {
+                zeroize(&mut key.key);
+            }
+        });
+        self.num_keys.set(0);
+    }
+}
+
 impl Driver for RadioDriver<'a> {
     /// Setup buffers to read/write from.
     ///
@@ -557,8 +612,15 @@ impl Driver for RadioDriver<'a> {
     ///
     /// ### `subscribe_num`
     ///
-    /// - `0`: Setup callback for when frame is received.
-    /// - `1`: Setup callback for when frame is transmitted.
+    /// - `0`: Setup callback for when frame is received. Only frames that
+    ///        pass the receive filters set with commands 27 and 28 are
+    ///        delivered.
+    /// - `1`: Setup callback for when frame is transmitted. The callback
+    ///        receives the result, whether the frame was acknowledged, and the
+    ///        link-layer details of the transmission: the number of retries in
+    ///        bits 0-7 and the RSSI of the acknowledgement (in dBm, as a signed
+    ///        byte) in bits 8-15. Bits 16 and 17 are set if the radio reported
+    ///        the retries and the RSSI respectively.
     fn subscribe(
         &self,
         subscribe_num: usize,
@@ -627,7 +689,21 @@ impl Driver for RadioDriver<'a> {
     ///                      9 bytes: the key ID (might not use all bytes) +
     ///                      16 bytes: the key.
     /// - `25`: Remove the key at an index.
-    fn command(&self, command_num: usize, arg1: usize, _: usize, appid: AppId) -> ReturnCode {
+    /// - `26`: Transmit the frame in the write buffer to the short address in
+    ///        `arg1`, secured as requested for this frame.
+    ///        app_cfg (in): 1 byte: the security level +
+    ///                      1 byte: the key ID mode +
+    ///                      9 bytes: the key ID (might not use all bytes).
+    ///        Returns EBUSY if the process already has a pending frame, or if
+    ///        the payload exceeds what its rate limit currently allows.
+    /// - `27`: Set the receive PAN filter. If `arg1` is nonzero, only frames
+    ///        sent to the PAN in `arg2` or to the broadcast PAN are received,
+    ///        otherwise frames for any PAN are received.
+    /// - `28`: Set the receive source address filter. `arg1` is the address
+    ///        mode: 0 receives frames from any source, 2 only from the short
+    ///        address in `arg2`, and 3 only from a long address.
+    ///        app_cfg (in, mode 3 only): 8 bytes: the long MAC address.
+    fn command(&self, command_num: usize, arg1: usize, arg2: usize, appid: AppId) -> ReturnCode {
         match command_num {
             0 => ReturnCode::SUCCESS,
             1 => {
@@ -787,11 +863,50 @@ impl Driver for RadioDriver<'a> {
                     if next_tx.is_none() {
                         return ReturnCode::EINVAL;
                     }
+                    let len = app.app_write.as_ref().map_or(0, |payload| payload.len());
+                    if !self
+                        .rate_limiter
+                        .map_or(true, |limiter| limiter.consume(appid.idx(), len))
+                    {
+                        return ReturnCode::EBUSY;
+                    }
                     app.pending_tx = next_tx;
 
                     self.do_next_tx_sync(appid)
                 })
             }
+            27 => self.do_with_app(appid, |app| {
+                app.rx_pan_filter = if arg1 != 0 { Some(arg2 as PanID) } else { None };
+                ReturnCode::SUCCESS
+            }),
+            28 => match AddressMode::from_mode(arg1 as u16) {
+                Some(AddressMode::NotPresent) => self.do_with_app(appid, |app| {
+                    app.rx_src_filter = None;
+                    ReturnCode::SUCCESS
+                }),
+                Some(AddressMode::Short) => self.do_with_app(appid, |app| {
+                    app.rx_src_filter = Some(MacAddress::Short(arg2 as u16));
+                    ReturnCode::SUCCESS
+                }),
+                Some(AddressMode::Long) => self.do_with_app(appid, |app| {
+                    let addr_long = app.app_cfg.as_ref().and_then(|cfg| {
+                        if cfg.len() != 8 {
+                            return None;
+                        }
+                        let mut addr_long = [0u8; 8];
+                        addr_long.copy_from_slice(cfg.as_ref());
+                        Some(addr_long)
+                    });
+                    match addr_long {
+                        Some(addr_long) => {
+                            app.rx_src_filter = Some(MacAddress::Long(addr_long));
+                            ReturnCode::SUCCESS
+                        }
+                        None => ReturnCode::EINVAL,
+                    }
+                }),
+                None => ReturnCode::EINVAL,
+            },
             _ => ReturnCode::ENOSUPPORT,
         }
     }
@@ -800,17 +915,30 @@ impl Driver for RadioDriver<'a> {
 impl device::TxClient for RadioDriver<'a> {
     fn send_done(&self, spi_buf: &'static mut [u8], acked: bool, result: ReturnCode) {
         self.kernel_tx.replace(spi_buf);
+        let tx_info = encode_tx_info(&self.mac.get_tx_info());
         self.current_app.take().map(|appid| {
             let _ = self.apps.enter(appid, |app, _| {
                 app.tx_callback
                     .take()
-                    .map(|mut cb| cb.schedule(result.into(), acked as usize, 0));
+                    .map(|mut cb| cb.schedule(result.into(), acked as usize, tx_info));
             });
         });
         self.do_next_tx_async();
     }
 }
 
+/// Encodes the link-layer details of a transmission into a single usize.
+#[inline]
+fn encode_tx_info(info: &TxInfo) -> usize {
+    let retries = info
+        .retries
+        .map_or(0, |retries| (1 << 16) | retries as usize);
+    let ack_rssi = info
+        .ack_rssi
+        .map_or(0, |rssi| (1 << 17) | ((rssi as u8 as usize) << 8));
+    retries | ack_rssi
+}
+
 /// Encode two PAN IDs into a single usize.
 #[inline]
 fn encode_pans(dst_pan: &Option<PanID>, src_pan: &Option<PanID>) -> usize {
@@ -830,6 +958,9 @@ fn encode_address(addr: &Option<MacAddress>) -> usize {
 impl device::RxClient for RadioDriver<'a> {
     fn receive<'b>(&self, buf: &'b [u8], header: Header<'b>, data_offset: usize, data_len: usize) {
         self.apps.each(|app| {
+            if !app.accepts(&header) {
+                return;
+            }
             app.app_read.take().as_mut().map(|rbuf| {
                 let rbuf = rbuf.as_mut();
                 let len = min(rbuf.len(), data_offset + data_len);
@@ -74,6 +74,7 @@ use crate::ieee802154::mac::Mac;
 use crate::net::ieee802154::{
     FrameType, FrameVersion, Header, KeyId, MacAddress, PanID, Security, SecurityLevel,
 };
+use crate::net::stats::{counter, NetStats};
 use crate::net::stream::SResult;
 use crate::net::stream::{encode_bytes, encode_u32, encode_u8};
 use core::cell::Cell;
@@ -312,6 +313,9 @@ pub struct Framer<'a, M: Mac, A: AES128CCM<'a>> {
     /// `None`, except when transitioning between states.
     rx_state: MapCell<RxState>,
     rx_client: OptionalCell<&'a RxClient>,
+
+    /// Network statistics updated with the frames sent and received
+    stats: OptionalCell<&'a NetStats>,
 }
 
 impl<M: Mac, A: AES128CCM<'a>> Framer<'a, M, A> {
@@ -326,9 +330,20 @@ impl<M: Mac, A: AES128CCM<'a>> Framer<'a, M, A> {
             tx_client: OptionalCell::empty(),
             rx_state: MapCell::new(RxState::Idle),
             rx_client: OptionalCell::empty(),
+            stats: OptionalCell::empty(),
         }
     }
 
+    /// Sets the network statistics that frames sent and received are
+    /// counted in.
+    pub fn set_stats(&self, stats: &'a NetStats) {
+        self.stats.set(stats);
+    }
+
+    fn count(&self, counter: usize) {
+        self.stats.map(|stats| stats.incr(counter));
+    }
+
     /// Sets the IEEE 802.15.4 key lookup procedure to be used.
     pub fn set_key_procedure(&self, key_procedure: &'a KeyProcedure) {
         self.key_procedure.set(key_procedure);
@@ -783,11 +798,20 @@ impl<M: Mac, A: AES128CCM<'a>> MacDevice<'a> for Framer<'a, M, A> {
             }
         }
     }
+
+    fn get_tx_info(&self) -> radio::TxInfo {
+        self.mac.get_tx_info()
+    }
 }
 
 impl<M: Mac, A: AES128CCM<'a>> radio::TxClient for Framer<'a, M, A> {
     fn send_done(&self, buf: &'static mut [u8], acked: bool, result: ReturnCode) {
         self.data_sequence.set(self.data_sequence.get() + 1);
+        if result == ReturnCode::SUCCESS {
+            self.count(counter::FRAMES_SENT);
+        } else {
+            self.count(counter::FRAMES_SEND_FAILED);
+        }
         self.tx_client.map(move |client| {
             client.send_done(buf, acked, result);
         });
@@ -798,9 +822,11 @@ impl<M: Mac, A: AES128CCM<'a>> radio::RxClient for Framer<'a, M, A> {
     fn receive(&self, buf: &'static mut [u8], frame_len: usize, crc_valid: bool, _: ReturnCode) {
         // Drop all frames with invalid CRC
         if !crc_valid {
+            self.count(counter::CRC_ERRORS);
             self.mac.set_receive_buffer(buf);
             return;
         }
+        self.count(counter::FRAMES_RECEIVED);
 
         self.rx_state.take().map(move |state| {
             let next_state = match state {
@@ -814,6 +840,7 @@ impl<M: Mac, A: AES128CCM<'a>> radio::RxClient for Framer<'a, M, A> {
                     // this MAC layer provided a receive buffer to the
                     // radio, but if this occurs then we have no choice but
                     // to drop the frame.
+                    self.count(counter::FRAMES_DROPPED);
                     self.mac.set_receive_buffer(buf);
                     other_state
                 }
@@ -885,6 +912,7 @@ impl<M: Mac, A: AES128CCM<'a>> CCMClient for Framer<'a, M, A> {
                         let next_state = if tag_is_valid {
                             RxState::ReadyToYield(info, buf)
                         } else {
+                            self.count(counter::FRAMES_DROPPED);
                             RxState::ReadyToReturn(buf)
                         };
                         self.rx_state.replace(next_state);
@@ -60,6 +60,10 @@ pub trait Mac {
         full_mac_frame: &'static mut [u8],
         frame_len: usize,
     ) -> (ReturnCode, Option<&'static mut [u8]>);
+
+    /// Returns the link-layer details of the most recently completed
+    /// transmission. Only meaningful during the `send_done` callback.
+    fn get_tx_info(&self) -> radio::TxInfo;
 }
 
 ///
@@ -145,6 +149,10 @@ impl<R: radio::Radio> Mac for AwakeMac<'a, R> {
     ) -> (ReturnCode, Option<&'static mut [u8]>) {
         self.radio.transmit(full_mac_frame, frame_len)
     }
+
+    fn get_tx_info(&self) -> radio::TxInfo {
+        self.radio.get_tx_info()
+    }
 }
 
 impl<R: radio::Radio> radio::TxClient for AwakeMac<'a, R> {
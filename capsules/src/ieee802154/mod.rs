@@ -1,6 +1,7 @@
 pub mod device;
 pub mod framer;
 pub mod mac;
+pub mod regulatory;
 pub mod virtual_mac;
 pub mod xmac;
 
@@ -0,0 +1,380 @@
+//! Regional radio regulation profiles.
+//!
+//! A `RegulatoryProfile` collects the limits that a region places on an
+//! IEEE 802.15.4 radio: which channels may be used, the maximum transmit
+//! power, and the fraction of time the radio may spend transmitting. The
+//! `RegulatedRadio` wraps a `radio::Radio` and enforces a profile on every
+//! configuration change and transmission that passes through it, so that the
+//! MAC layers and everything above them stay within the limits without any
+//! region-specific constants of their own. Products that ship to several
+//! regions only need to pass a different profile to the `RegulatedRadio` in
+//! each board build.
+//!
+//! The profiles defined here are starting points. The limits that apply to a
+//! product depend on its antenna and certification, so boards should define
+//! their own profile where these do not fit.
+//!
+//! Usage
+//! -----
+//!
+//! ```
+//! let regulated_radio = static_init!(
+//!     capsules::ieee802154::regulatory::RegulatedRadio<
+//!         'static,
+//!         capsules::rf233::RF233<'static, VirtualSpiMasterDevice<'static, sam4l::spi::SpiHw>>,
+//!         VirtualMuxAlarm<'static, sam4l::ast::Ast>,
+//!     >,
+//!     capsules::ieee802154::regulatory::RegulatedRadio::new(
+//!         rf233,
+//!         regulatory_alarm,
+//!         &capsules::ieee802154::regulatory::ETSI_2450
+//!     )
+//! );
+//! let awake_mac = static_init!(
+//!     capsules::ieee802154::mac::AwakeMac<'static, RegulatedRadio<'static, ...>>,
+//!     capsules::ieee802154::mac::AwakeMac::new(regulated_radio)
+//! );
+//! ```
+
+use core::cell::Cell;
+use kernel::hil::radio;
+use kernel::hil::time::{self, Frequency};
+use kernel::ReturnCode;
+
+/// A limit on the fraction of time the radio may spend transmitting.
+#[derive(Copy, Clone, Debug)]
+pub struct DutyCycle {
+    /// Maximum share of airtime, in thousandths
+    pub permille: u16,
+    /// Period over which the airtime is measured, in seconds
+    pub window_s: u32,
+}
+
+/// The limits a region places on the radio.
+#[derive(Copy, Clone, Debug)]
+pub struct RegulatoryProfile {
+    /// Short name of the region, for diagnostics
+    pub region: &'static str,
+    /// Channels that may be used. Bit `n` is set if channel `n` of channel
+    /// page 0 is allowed.
+    pub channel_mask: u32,
+    /// Maximum transmit power, in dBm
+    pub max_tx_power: i8,
+    /// Duty-cycle limit, if the region has one
+    pub duty_cycle: Option<DutyCycle>,
+}
+
+impl RegulatoryProfile {
+    pub fn channel_allowed(&self, channel: u8) -> bool {
+        channel < 32 && self.channel_mask & (1 << channel) != 0
+    }
+
+    /// The lowest channel that may be used, if any.
+    pub fn first_channel(&self) -> Option<u8> {
+        if self.channel_mask == 0 {
+            None
+        } else {
+            Some(self.channel_mask.trailing_zeros() as u8)
+        }
+    }
+}
+
+/// Channels 11-26, the 2.4 GHz band
+const CHANNELS_2450: u32 = 0x07ff_f800;
+/// Channels 1-10, the 915 MHz band
+const CHANNELS_915: u32 = 0x0000_07fe;
+/// Channel 0, the 868 MHz band
+const CHANNELS_868: u32 = 0x0000_0001;
+
+/// The 2.4 GHz band in Europe (ETSI EN 300 328)
+pub const ETSI_2450: RegulatoryProfile = RegulatoryProfile {
+    region: "EU",
+    channel_mask: CHANNELS_2450,
+    max_tx_power: 10,
+    duty_cycle: None,
+};
+
+/// The 868 MHz band in Europe (ETSI EN 300 220), which limits devices to 1%
+/// of airtime
+pub const ETSI_868: RegulatoryProfile = RegulatoryProfile {
+    region: "EU",
+    channel_mask: CHANNELS_868,
+    max_tx_power: 14,
+    duty_cycle: Some(DutyCycle {
+        permille: 10,
+        window_s: 3600,
+    }),
+};
+
+/// The 2.4 GHz band in the United States (FCC part 15.247)
+pub const FCC_2450: RegulatoryProfile = RegulatoryProfile {
+    region: "US",
+    channel_mask: CHANNELS_2450,
+    max_tx_power: 20,
+    duty_cycle: None,
+};
+
+/// The 915 MHz band in the United States (FCC part 15.247)
+pub const FCC_915: RegulatoryProfile = RegulatoryProfile {
+    region: "US",
+    channel_mask: CHANNELS_915,
+    max_tx_power: 20,
+    duty_cycle: None,
+};
+
+/// Synchronization header (preamble and SFD) plus PHY header, in bytes
+const PHY_OVERHEAD: u32 = 6;
+
+/// Time to transmit one byte on `channel`, in microseconds.
+fn byte_time_us(channel: u8) -> u32 {
+    match channel {
+        // BPSK at 20 kb/s
+        0 => 400,
+        // BPSK at 40 kb/s
+        1..=10 => 200,
+        // O-QPSK at 250 kb/s
+        _ => 32,
+    }
+}
+
+/// A radio that enforces a `RegulatoryProfile`.
+///
+/// Channels outside of the profile are refused, and transmit powers above the
+/// maximum are lowered to it. When the profile has a duty-cycle limit, the
+/// airtime of each frame is taken from a budget that refills at the allowed
+/// rate, and frames that do not fit in the remaining budget are refused with
+/// `EBUSY`.
+pub struct RegulatedRadio<'a, R: radio::Radio, A: time::Alarm> {
+    radio: &'a R,
+    alarm: &'a A,
+    profile: &'a RegulatoryProfile,
+
+    /// Remaining airtime, in microseconds
+    airtime_budget: Cell<u32>,
+    /// Time at which the airtime budget was last brought up to date
+    last_refill: Cell<u32>,
+}
+
+impl<R: radio::Radio, A: time::Alarm> RegulatedRadio<'a, R, A> {
+    pub fn new(
+        radio: &'a R,
+        alarm: &'a A,
+        profile: &'a RegulatoryProfile,
+    ) -> RegulatedRadio<'a, R, A> {
+        let regulated = RegulatedRadio {
+            radio: radio,
+            alarm: alarm,
+            profile: profile,
+            airtime_budget: Cell::new(0),
+            last_refill: Cell::new(alarm.now()),
+        };
+        regulated.airtime_budget.set(regulated.max_airtime_budget());
+        regulated
+    }
+
+    pub fn get_profile(&self) -> &'a RegulatoryProfile {
+        self.profile
+    }
+
+    /// Airtime that can be used in a single burst, in microseconds.
+    fn max_airtime_budget(&self) -> u32 {
+        self.profile.duty_cycle.map_or(0, |duty_cycle| {
+            let budget = duty_cycle.window_s as u64 * 1_000_000 * duty_cycle.permille as u64 / 1000;
+            if budget > u32::max_value() as u64 {
+                u32::max_value()
+            } else {
+                budget as u32
+            }
+        })
+    }
+
+    /// Adds the airtime that has accumulated since the last refill.
+    fn refill_airtime(&self, duty_cycle: DutyCycle) {
+        let now = self.alarm.now();
+        let elapsed = now.wrapping_sub(self.last_refill.get()) as u64;
+        let freq = A::Frequency::frequency() as u64;
+        let earned = elapsed * 1_000_000 * duty_cycle.permille as u64 / (freq * 1000);
+        let budget = self.airtime_budget.get() as u64 + earned;
+        let max_budget = self.max_airtime_budget() as u64;
+        if budget >= max_budget {
+            self.airtime_budget.set(max_budget as u32);
+            self.last_refill.set(now);
+        } else if earned > 0 {
+            // Only advance by the time it took to earn whole microseconds, so
+            // that frequent calls do not lose the fractions in between
+            self.airtime_budget.set(budget as u32);
+            let used = earned * freq * 1000 / (1_000_000 * duty_cycle.permille as u64);
+            self.last_refill
+                .set(self.last_refill.get().wrapping_add(used as u32));
+        }
+    }
+
+    /// Approximate time needed to send a frame of `frame_len` bytes on the
+    /// current channel, in microseconds.
+    fn frame_airtime(&self, frame_len: usize) -> u32 {
+        (frame_len as u32 + PHY_OVERHEAD) * byte_time_us(self.radio.get_channel())
+    }
+
+    /// Takes `airtime` microseconds from the budget. Returns `false`, and takes
+    /// nothing, if there is not enough left.
+    fn consume_airtime(&self, airtime: u32) -> bool {
+        let duty_cycle = match self.profile.duty_cycle {
+            Some(duty_cycle) => duty_cycle,
+            None => return true,
+        };
+        self.refill_airtime(duty_cycle);
+        let budget = self.airtime_budget.get();
+        if airtime <= budget {
+            self.airtime_budget.set(budget - airtime);
+            true
+        } else {
+            false
+        }
+    }
+}
+
+impl<R: radio::Radio, A: time::Alarm> radio::Radio for RegulatedRadio<'a, R, A> {}
+
+impl<R: radio::Radio, A: time::Alarm> radio::RadioConfig for RegulatedRadio<'a, R, A> {
+    fn initialize(
+        &self,
+        spi_buf: &'static mut [u8],
+        reg_write: &'static mut [u8],
+        reg_read: &'static mut [u8],
+    ) -> ReturnCode {
+        self.radio.initialize(spi_buf, reg_write, reg_read)
+    }
+
+    fn reset(&self) -> ReturnCode {
+        self.radio.reset()
+    }
+
+    fn start(&self) -> ReturnCode {
+        self.radio.start()
+    }
+
+    fn stop(&self) -> ReturnCode {
+        self.radio.stop()
+    }
+
+    fn is_on(&self) -> bool {
+        self.radio.is_on()
+    }
+
+    fn busy(&self) -> bool {
+        self.radio.busy()
+    }
+
+    fn set_power_client(&self, client: &'static radio::PowerClient) {
+        self.radio.set_power_client(client);
+    }
+
+    /// Commits the configuration, first bringing the channel and transmit
+    /// power within the profile in case the radio was configured without going
+    /// through this wrapper.
+    fn config_commit(&self) {
+        if !self.profile.channel_allowed(self.radio.get_channel()) {
+            self.profile
+                .first_channel()
+                .map(|channel| self.radio.set_channel(channel));
+        }
+        if self.radio.get_tx_power() > self.profile.max_tx_power {
+            self.radio.set_tx_power(self.profile.max_tx_power);
+        }
+        self.radio.config_commit();
+    }
+
+    fn set_config_client(&self, client: &'static radio::ConfigClient) {
+        self.radio.set_config_client(client);
+    }
+
+    fn get_address(&self) -> u16 {
+        self.radio.get_address()
+    }
+
+    fn get_address_long(&self) -> [u8; 8] {
+        self.radio.get_address_long()
+    }
+
+    fn get_pan(&self) -> u16 {
+        self.radio.get_pan()
+    }
+
+    fn get_tx_power(&self) -> i8 {
+        self.radio.get_tx_power()
+    }
+
+    fn get_channel(&self) -> u8 {
+        self.radio.get_channel()
+    }
+
+    fn set_address(&self, addr: u16) {
+        self.radio.set_address(addr);
+    }
+
+    fn set_address_long(&self, addr: [u8; 8]) {
+        self.radio.set_address_long(addr);
+    }
+
+    fn set_pan(&self, id: u16) {
+        self.radio.set_pan(id);
+    }
+
+    /// Sets the transmit power, lowered to the maximum of the profile.
+    fn set_tx_power(&self, power: i8) -> ReturnCode {
+        if power > self.profile.max_tx_power {
+            self.radio.set_tx_power(self.profile.max_tx_power)
+        } else {
+            self.radio.set_tx_power(power)
+        }
+    }
+
+    /// Sets the channel. Returns `EINVAL` if the profile does not allow it.
+    fn set_channel(&self, chan: u8) -> ReturnCode {
+        if self.profile.channel_allowed(chan) {
+            self.radio.set_channel(chan)
+        } else {
+            ReturnCode::EINVAL
+        }
+    }
+}
+
+impl<R: radio::Radio, A: time::Alarm> radio::RadioData for RegulatedRadio<'a, R, A> {
+    fn set_transmit_client(&self, client: &'static radio::TxClient) {
+        self.radio.set_transmit_client(client);
+    }
+
+    fn set_receive_client(
+        &self,
+        client: &'static radio::RxClient,
+        receive_buffer: &'static mut [u8],
+    ) {
+        self.radio.set_receive_client(client, receive_buffer);
+    }
+
+    fn set_receive_buffer(&self, receive_buffer: &'static mut [u8]) {
+        self.radio.set_receive_buffer(receive_buffer);
+    }
+
+    fn transmit(
+        &self,
+        spi_buf: &'static mut [u8],
+        frame_len: usize,
+    ) -> (ReturnCode, Option<&'static mut [u8]>) {
+        let airtime = self.frame_airtime(frame_len);
+        if !self.consume_airtime(airtime) {
+            return (ReturnCode::EBUSY, Some(spi_buf));
+        }
+        let (result, spi_buf) = self.radio.transmit(spi_buf, frame_len);
+        if result != ReturnCode::SUCCESS {
+            // The frame was never sent, so give its airtime back
+            self.airtime_budget
+                .set(self.airtime_budget.get().saturating_add(airtime));
+        }
+        (result, spi_buf)
+    }
+
+    fn get_tx_info(&self) -> radio::TxInfo {
+        self.radio.get_tx_info()
+    }
+}
@@ -31,6 +31,7 @@ use crate::net::ieee802154::{Header, KeyId, MacAddress, PanID, SecurityLevel};
 use core::cell::Cell;
 use kernel::common::cells::{MapCell, OptionalCell};
 use kernel::common::{List, ListLink, ListNode};
+use kernel::hil::radio::TxInfo;
 use kernel::ReturnCode;
 
 /// IEE 802.15.4 MAC device muxer that keeps a list of MAC users and sequences
@@ -298,4 +299,8 @@ impl device::MacDevice<'a> for MacUser<'a> {
                 }
             })
     }
+
+    fn get_tx_info(&self) -> TxInfo {
+        self.mux.mac.get_tx_info()
+    }
 }
@@ -456,6 +456,10 @@ impl<R: radio::Radio, A: Alarm> Mac for XMac<'a, R, A> {
 
         (ReturnCode::SUCCESS, None)
     }
+
+    fn get_tx_info(&self) -> radio::TxInfo {
+        self.radio.get_tx_info()
+    }
 }
 
 // Core of the XMAC protocol - when the timer fires, the protocol state
@@ -4,23 +4,36 @@
 //! to know which of the GPIO pins exposed across the syscall interface are
 //! LEDs.
 //!
-//! This capsule takes an array of pins and the polarity of the LED (active high
-//! or active low). This allows the board to configure how the underlying GPIO
-//! must be controlled to turn on and off LEDs, such that the syscall driver
-//! interface can be agnostic to the LED polarity.
+//! This capsule takes an array of LEDs, each of which is either an on/off LED
+//! on a GPIO pin, with the polarity of the LED (active high or active low), or
+//! an LED whose brightness can be set, such as one driven by PWM. This allows
+//! the board to configure how the LEDs must be controlled, such that the
+//! syscall driver interface can be agnostic to the LED polarity and hardware.
+//! Each LED also has a color, so that applications can find the LED they want
+//! on any board.
+//!
+//! LEDs can also blink in a pattern, which the capsule plays with an alarm
+//! so that applications do not have to wake up to blink an LED.
 //!
 //! Usage
 //! -----
 //!
 //! ```rust
-//! let led_pins = static_init!(
-//!     [(&'static sam4l::gpio::GPIOPin, capsules::led::ActivationMode); 3],
-//!     [(&sam4l::gpio::PA[13], capsules::led::ActivationMode::ActiveLow),   // Red
-//!      (&sam4l::gpio::PA[15], capsules::led::ActivationMode::ActiveLow),   // Green
-//!      (&sam4l::gpio::PA[14], capsules::led::ActivationMode::ActiveLow)]); // Blue
+//! let leds = static_init!(
+//!     [capsules::led::Led<'static>; 3],
+//!     [capsules::led::Led::gpio(&sam4l::gpio::PA[13], capsules::led::ActivationMode::ActiveLow,
+//!          kernel::hil::led::LedColor::Red),
+//!      capsules::led::Led::gpio(&sam4l::gpio::PA[15], capsules::led::ActivationMode::ActiveLow,
+//!          kernel::hil::led::LedColor::Green),
+//!      capsules::led::Led::dimmable(led_pwm, kernel::hil::led::LedColor::White)]);
+//! let led_alarm = static_init!(
+//!     capsules::virtual_alarm::VirtualMuxAlarm<'static, sam4l::ast::Ast>,
+//!     capsules::virtual_alarm::VirtualMuxAlarm::new(mux_alarm)
+//! );
 //! let led = static_init!(
-//!     capsules::led::LED<'static, sam4l::gpio::GPIOPin>,
-//!     capsules::led::LED::new(led_pins));
+//!     capsules::led::LED<'static, capsules::virtual_alarm::VirtualMuxAlarm<'static, sam4l::ast::Ast>>,
+//!     capsules::led::LED::new(leds, led_alarm));
+//! led_alarm.set_client(led);
 //! ```
 //!
 //! Syscall Interface
@@ -47,14 +60,38 @@
 //! - `3`: Toggle the on/off state of the LED.
 //!   - `data`: The index of the LED. Starts at 0.
 //!   - Return: `SUCCESS` if the LED index was valid, `EINVAL` otherwise.
+//! - `4`: Set the brightness of the LED.
+//!   - `data`: The index of the LED. Starts at 0.
+//!   - `data2`: The brightness, from 0 to 255.
+//!   - Return: `SUCCESS` if the LED index and brightness were valid, `EINVAL`
+//!     otherwise.
+//! - `5`: Blink the LED in a pattern.
+//!   - `data`: The index of the LED. Starts at 0.
+//!   - `data2`: The pattern, or 0 to stop blinking.
+//!   - Return: `SUCCESS` if the LED index was valid, `EINVAL` otherwise.
+//! - `6`: Return the largest brightness of the LED.
+//!   - `data`: The index of the LED. Starts at 0.
+//!   - Return: 1 for LEDs that can only be on or off, 255 otherwise.
+//! - `7`: Return the color of the LED.
+//!   - `data`: The index of the LED. Starts at 0.
+//!   - Return: The color, as numbered in `kernel::hil::led::LedColor`.
 
+use core::cell::Cell;
 use kernel::hil::gpio;
+use kernel::hil::led::{LedBrightness, LedColor, MAX_BRIGHTNESS};
+use kernel::hil::time::{self, Frequency};
 use kernel::{AppId, Driver, ReturnCode};
 
 /// Syscall driver number.
 use crate::driver;
 pub const DRIVER_NUM: usize = driver::NUM::Led as usize;
 
+/// Duration of each step of a blink pattern.
+pub const BLINK_STEP_MS: u32 = 125;
+
+/// Number of steps in a blink pattern, one per bit.
+const BLINK_STEPS: u32 = 32;
+
 /// Whether the LEDs are active high or active low on this platform.
 #[derive(Clone, Copy)]
 pub enum ActivationMode {
@@ -62,30 +99,145 @@ pub enum ActivationMode {
     ActiveLow,
 }
 
-/// Holds the array of GPIO pins attached to the LEDs and implements a `Driver`
-/// interface to control them.
-pub struct LED<'a> {
-    pins_init: &'a [(&'a gpio::Pin, ActivationMode)],
+/// How an LED is driven.
+#[derive(Clone, Copy)]
+enum Output<'a> {
+    Gpio(&'a gpio::Pin, ActivationMode),
+    Dimmable(&'a LedBrightness),
+}
+
+/// An LED and its state.
+pub struct Led<'a> {
+    output: Output<'a>,
+    color: LedColor,
+    on: Cell<bool>,
+    /// Brightness of the LED when it is on
+    brightness: Cell<usize>,
+    /// Blink pattern, starting from the least significant bit, or 0 if the
+    /// LED is not blinking
+    pattern: Cell<u32>,
+}
+
+impl Led<'a> {
+    /// An LED on a GPIO pin, which can only be on or off.
+    pub fn gpio(pin: &'a gpio::Pin, mode: ActivationMode, color: LedColor) -> Led<'a> {
+        Led::new(Output::Gpio(pin, mode), color)
+    }
+
+    /// An LED whose brightness can be set.
+    pub fn dimmable(led: &'a LedBrightness, color: LedColor) -> Led<'a> {
+        Led::new(Output::Dimmable(led), color)
+    }
+
+    fn new(output: Output<'a>, color: LedColor) -> Led<'a> {
+        Led {
+            output: output,
+            color: color,
+            on: Cell::new(false),
+            brightness: Cell::new(MAX_BRIGHTNESS),
+            pattern: Cell::new(0),
+        }
+    }
+
+    /// Turns the LED on at its brightness, or off.
+    fn set(&self, on: bool) {
+        self.on.set(on);
+        match self.output {
+            Output::Gpio(pin, mode) => match (mode, on) {
+                (ActivationMode::ActiveHigh, true) | (ActivationMode::ActiveLow, false) => {
+                    pin.set()
+                }
+                (ActivationMode::ActiveHigh, false) | (ActivationMode::ActiveLow, true) => {
+                    pin.clear()
+                }
+            },
+            Output::Dimmable(led) => {
+                led.set_brightness(if on { self.brightness.get() } else { 0 });
+            }
+        }
+    }
+
+    fn max_brightness(&self) -> usize {
+        match self.output {
+            Output::Gpio(..) => 1,
+            Output::Dimmable(_) => MAX_BRIGHTNESS,
+        }
+    }
 }
 
-impl<'a> LED<'a> {
-    pub fn new(pins_init: &'a [(&'a gpio::Pin, ActivationMode)]) -> LED<'a> {
+/// Holds the array of LEDs and implements a `Driver` interface to control
+/// them.
+pub struct LED<'a, A: time::Alarm> {
+    leds: &'a [Led<'a>],
+    alarm: &'a A,
+    /// Step of the blink patterns that is showing
+    step: Cell<u32>,
+}
+
+impl<A: time::Alarm> LED<'a, A> {
+    pub fn new(leds: &'a [Led<'a>], alarm: &'a A) -> LED<'a, A> {
         // Make all pins output and off
-        for &(pin, mode) in pins_init.as_ref().iter() {
-            pin.make_output();
-            match mode {
-                ActivationMode::ActiveHigh => pin.clear(),
-                ActivationMode::ActiveLow => pin.set(),
+        for led in leds.iter() {
+            if let Output::Gpio(pin, _) = led.output {
+                pin.make_output();
             }
+            led.set(false);
         }
 
         LED {
-            pins_init: pins_init,
+            leds: leds,
+            alarm: alarm,
+            step: Cell::new(0),
+        }
+    }
+
+    /// Turns an LED on or off, and stops it blinking.
+    fn set(&self, led: &Led, on: bool) {
+        led.pattern.set(0);
+        led.set(on);
+    }
+
+    /// Starts blinking an LED in `pattern`, in step with the other blinking
+    /// LEDs.
+    fn blink(&self, led: &Led, pattern: u32) {
+        if pattern == 0 {
+            self.set(led, false);
+            return;
+        }
+        if !self.alarm.is_armed() {
+            self.step.set(0);
+            self.schedule_step();
+        }
+        led.pattern.set(pattern);
+        led.set(pattern & (1 << self.step.get()) != 0);
+    }
+
+    fn schedule_step(&self) {
+        let interval = <A::Frequency>::frequency() / 1000 * BLINK_STEP_MS;
+        self.alarm
+            .set_alarm(self.alarm.now().wrapping_add(interval));
+    }
+}
+
+impl<A: time::Alarm> time::Client for LED<'a, A> {
+    fn fired(&self) {
+        let step = (self.step.get() + 1) % BLINK_STEPS;
+        self.step.set(step);
+        let mut blinking = false;
+        for led in self.leds.iter() {
+            let pattern = led.pattern.get();
+            if pattern != 0 {
+                led.set(pattern & (1 << step) != 0);
+                blinking = true;
+            }
+        }
+        if blinking {
+            self.schedule_step();
         }
     }
 }
 
-impl<'a> Driver for LED<'a> {
+impl<A: time::Alarm> Driver for LED<'a, A> {
     /// Control the LEDs.
     ///
     /// ### `command_num`
@@ -98,53 +250,82 @@ impl<'a> Driver for LED<'a> {
     ///        if the LED index is not valid.
     /// - `3`: Toggle the LED at index specified by `data` on or off. Returns
     ///        `EINVAL` if the LED index is not valid.
-    fn command(&self, command_num: usize, data: usize, _: usize, _: AppId) -> ReturnCode {
-        let pins_init = self.pins_init.as_ref();
+    /// - `4`: Set the brightness of the LED at index specified by `data` to
+    ///        `data2`, from 0 (off) to 255. LEDs that can only be on or off
+    ///        are on for any brightness above 0. Returns `EINVAL` if the LED
+    ///        index or the brightness is not valid.
+    /// - `5`: Blink the LED at index specified by `data` in the pattern
+    ///        `data2`. Each bit of the pattern, from the least significant,
+    ///        is whether the LED is on for 125 ms, and the pattern repeats
+    ///        every 4 seconds. A pattern of 0 stops blinking and turns the LED
+    ///        off. Turning the LED on or off, toggling it or setting its
+    ///        brightness also stops blinking. Returns `EINVAL` if the LED index
+    ///        is not valid.
+    /// - `6`: Returns the largest brightness of the LED at index specified by
+    ///        `data`: 1 if it can only be on or off, and 255 otherwise.
+    ///        Returns `EINVAL` if the LED index is not valid.
+    /// - `7`: Returns the color of the LED at index specified by `data`, as
+    ///        numbered in `LedColor`. Returns `EINVAL` if the LED index is not
+    ///        valid.
+    fn command(&self, command_num: usize, data: usize, data2: usize, _: AppId) -> ReturnCode {
+        // get number of LEDs
+        if command_num == 0 {
+            return ReturnCode::SuccessWithValue {
+                value: self.leds.len() as usize,
+            };
+        }
+        let led = match self.leds.get(data) {
+            Some(led) => led,
+            None if command_num <= 7 => return ReturnCode::EINVAL, /* impossible LED */
+            None => return ReturnCode::ENOSUPPORT,
+        };
         match command_num {
-            // get number of LEDs
-            0 => ReturnCode::SuccessWithValue {
-                value: pins_init.len() as usize,
-            },
-
             // on
             1 => {
-                if data >= pins_init.len() {
-                    ReturnCode::EINVAL /* impossible pin */
-                } else {
-                    let (pin, mode) = pins_init[data];
-                    match mode {
-                        ActivationMode::ActiveHigh => pin.set(),
-                        ActivationMode::ActiveLow => pin.clear(),
-                    }
-                    ReturnCode::SUCCESS
-                }
+                self.set(led, true);
+                ReturnCode::SUCCESS
             }
 
             // off
             2 => {
-                if data >= pins_init.len() {
-                    ReturnCode::EINVAL /* impossible pin */
-                } else {
-                    let (pin, mode) = pins_init[data];
-                    match mode {
-                        ActivationMode::ActiveHigh => pin.clear(),
-                        ActivationMode::ActiveLow => pin.set(),
-                    }
-                    ReturnCode::SUCCESS
-                }
+                self.set(led, false);
+                ReturnCode::SUCCESS
             }
 
             // toggle
             3 => {
-                if data >= pins_init.len() {
-                    ReturnCode::EINVAL /* impossible pin */
-                } else {
-                    let (pin, _) = pins_init[data];
-                    pin.toggle();
-                    ReturnCode::SUCCESS
+                self.set(led, !led.on.get());
+                ReturnCode::SUCCESS
+            }
+
+            // brightness
+            4 => {
+                if data2 > MAX_BRIGHTNESS {
+                    return ReturnCode::EINVAL;
                 }
+                if data2 > 0 {
+                    led.brightness.set(data2);
+                }
+                self.set(led, data2 > 0);
+                ReturnCode::SUCCESS
+            }
+
+            // blink
+            5 => {
+                self.blink(led, data2 as u32);
+                ReturnCode::SUCCESS
             }
 
+            // maximum brightness
+            6 => ReturnCode::SuccessWithValue {
+                value: led.max_brightness(),
+            },
+
+            // color
+            7 => ReturnCode::SuccessWithValue {
+                value: led.color as usize,
+            },
+
             // default
             _ => ReturnCode::ENOSUPPORT,
         }
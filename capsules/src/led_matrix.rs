@@ -0,0 +1,208 @@
+//! Drives a matrix of LEDs, such as the 5x5 display of the micro:bit.
+//!
+//! The LEDs of a matrix share row and column pins, so only the LEDs of one
+//! row can be lit at a time. This capsule lights the rows one after the other
+//! with an alarm, fast enough that the whole matrix appears lit. Each row is
+//! lit for a number of steps, and each LED for as many of them as its
+//! brightness asks, so that the LEDs can be dimmed without PWM.
+//!
+//! Each LED of the matrix is a `LedMatrixLed`, which implements
+//! `hil::led::LedBrightness`, so that the LEDs can be given to the LED
+//! syscall driver with `capsules::led::Led::dimmable`. The matrix only runs
+//! the alarm while any LED is on.
+//!
+//! Usage
+//! -----
+//!
+//! ```rust
+//! let rows = static_init!(
+//!     [&'static kernel::hil::gpio::Pin; 2],
+//!     [&nrf5x::gpio::PORT[21], &nrf5x::gpio::PORT[22]]);
+//! let cols = static_init!(
+//!     [&'static kernel::hil::gpio::Pin; 2],
+//!     [&nrf5x::gpio::PORT[28], &nrf5x::gpio::PORT[11]]);
+//! let brightness = static_init!([Cell<u8>; 4], Default::default());
+//! let matrix_alarm = static_init!(
+//!     VirtualMuxAlarm<'static, Rtc>,
+//!     VirtualMuxAlarm::new(mux_alarm));
+//! let matrix = static_init!(
+//!     capsules::led_matrix::LedMatrix<'static, VirtualMuxAlarm<'static, Rtc>>,
+//!     capsules::led_matrix::LedMatrix::new(
+//!         rows, capsules::led::ActivationMode::ActiveHigh,
+//!         cols, capsules::led::ActivationMode::ActiveLow,
+//!         brightness, matrix_alarm));
+//! matrix_alarm.set_client(matrix);
+//! matrix.init();
+//! let matrix_led = static_init!(
+//!     capsules::led_matrix::LedMatrixLed<'static, VirtualMuxAlarm<'static, Rtc>>,
+//!     capsules::led_matrix::LedMatrixLed::new(matrix, 0, 1));
+//! ```
+
+use core::cell::Cell;
+use kernel::hil::gpio;
+use kernel::hil::led::{LedBrightness, MAX_BRIGHTNESS};
+use kernel::hil::time::{self, Frequency};
+use kernel::ReturnCode;
+
+use crate::led::ActivationMode;
+
+/// How many times a second the whole matrix is lit.
+pub const REFRESH_HZ: u32 = 60;
+
+/// Number of steps each row is lit for, and so the number of brightness
+/// levels that can be told apart.
+const LEVELS: usize = 8;
+
+pub struct LedMatrix<'a, A: time::Alarm> {
+    rows: &'a [&'a gpio::Pin],
+    row_mode: ActivationMode,
+    cols: &'a [&'a gpio::Pin],
+    col_mode: ActivationMode,
+    /// Brightness of each LED, row by row
+    brightness: &'a [Cell<u8>],
+    alarm: &'a A,
+    /// Row that is lit
+    row: Cell<usize>,
+    /// Step of the row that is lit
+    step: Cell<usize>,
+    running: Cell<bool>,
+}
+
+impl<A: time::Alarm> LedMatrix<'a, A> {
+    /// `brightness` must have a cell for every LED of the matrix.
+    pub fn new(
+        rows: &'a [&'a gpio::Pin],
+        row_mode: ActivationMode,
+        cols: &'a [&'a gpio::Pin],
+        col_mode: ActivationMode,
+        brightness: &'a [Cell<u8>],
+        alarm: &'a A,
+    ) -> LedMatrix<'a, A> {
+        LedMatrix {
+            rows: rows,
+            row_mode: row_mode,
+            cols: cols,
+            col_mode: col_mode,
+            brightness: brightness,
+            alarm: alarm,
+            row: Cell::new(0),
+            step: Cell::new(0),
+            running: Cell::new(false),
+        }
+    }
+
+    /// Makes all pins outputs and turns all LEDs off.
+    pub fn init(&self) {
+        for pin in self.rows.iter().chain(self.cols.iter()) {
+            pin.make_output();
+        }
+        for row in self.rows.iter() {
+            Self::drive(*row, self.row_mode, false);
+        }
+        for col in self.cols.iter() {
+            Self::drive(*col, self.col_mode, false);
+        }
+    }
+
+    pub fn rows(&self) -> usize {
+        self.rows.len()
+    }
+
+    pub fn cols(&self) -> usize {
+        self.cols.len()
+    }
+
+    /// Sets the brightness of the LED at `row` and `col`, from 0 (off) to
+    /// `MAX_BRIGHTNESS`.
+    pub fn set_brightness(&self, row: usize, col: usize, brightness: usize) -> ReturnCode {
+        if row >= self.rows.len() || col >= self.cols.len() || brightness > MAX_BRIGHTNESS {
+            return ReturnCode::EINVAL;
+        }
+        self.brightness[row * self.cols.len() + col].set(brightness as u8);
+        if brightness > 0 && !self.running.get() {
+            self.running.set(true);
+            self.row.set(0);
+            self.step.set(0);
+            self.light_row();
+            self.schedule_step();
+        }
+        ReturnCode::SUCCESS
+    }
+
+    fn drive(pin: &gpio::Pin, mode: ActivationMode, on: bool) {
+        match (mode, on) {
+            (ActivationMode::ActiveHigh, true) | (ActivationMode::ActiveLow, false) => pin.set(),
+            (ActivationMode::ActiveHigh, false) | (ActivationMode::ActiveLow, true) => pin.clear(),
+        }
+    }
+
+    /// Number of steps of its row that the LED at `index` is lit for.
+    fn level(&self, index: usize) -> usize {
+        let brightness = self.brightness[index].get() as usize;
+        (brightness * LEVELS + MAX_BRIGHTNESS - 1) / MAX_BRIGHTNESS
+    }
+
+    /// Lights the LEDs of the current row that are lit for the current step.
+    fn light_row(&self) {
+        let row = self.row.get();
+        let step = self.step.get();
+        for (col, pin) in self.cols.iter().enumerate() {
+            let on = self.level(row * self.cols.len() + col) > step;
+            Self::drive(*pin, self.col_mode, on);
+        }
+        Self::drive(self.rows[row], self.row_mode, true);
+    }
+
+    fn schedule_step(&self) {
+        let steps_per_second = REFRESH_HZ * (self.rows.len() * LEVELS) as u32;
+        let interval = core::cmp::max(<A::Frequency>::frequency() / steps_per_second, 1);
+        self.alarm
+            .set_alarm(self.alarm.now().wrapping_add(interval));
+    }
+}
+
+impl<A: time::Alarm> time::Client for LedMatrix<'a, A> {
+    fn fired(&self) {
+        let mut step = self.step.get() + 1;
+        if step == LEVELS {
+            step = 0;
+            Self::drive(self.rows[self.row.get()], self.row_mode, false);
+            self.row.set((self.row.get() + 1) % self.rows.len());
+        }
+        self.step.set(step);
+
+        if step == 0 && self.brightness.iter().all(|b| b.get() == 0) {
+            // Nothing to show, so stop scanning until an LED is turned on
+            for col in self.cols.iter() {
+                Self::drive(*col, self.col_mode, false);
+            }
+            self.running.set(false);
+            return;
+        }
+        self.light_row();
+        self.schedule_step();
+    }
+}
+
+/// One LED of a matrix.
+pub struct LedMatrixLed<'a, A: time::Alarm> {
+    matrix: &'a LedMatrix<'a, A>,
+    row: usize,
+    col: usize,
+}
+
+impl<A: time::Alarm> LedMatrixLed<'a, A> {
+    pub fn new(matrix: &'a LedMatrix<'a, A>, row: usize, col: usize) -> LedMatrixLed<'a, A> {
+        LedMatrixLed {
+            matrix: matrix,
+            row: row,
+            col: col,
+        }
+    }
+}
+
+impl<A: time::Alarm> LedBrightness for LedMatrixLed<'a, A> {
+    fn set_brightness(&self, brightness: usize) -> ReturnCode {
+        self.matrix.set_brightness(self.row, self.col, brightness)
+    }
+}
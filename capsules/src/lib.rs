@@ -1,5 +1,5 @@
 #![feature(const_fn, in_band_lifetimes)]
-#![forbid(unsafe_code)]
+#![deny(unsafe_code)]
 #![no_std]
 
 pub mod test;
@@ -8,52 +8,94 @@ pub mod test;
 pub mod net;
 
 pub mod adc;
+pub mod adc_microphone;
 pub mod aes_ccm;
 pub mod alarm;
 pub mod ambient_light;
 pub mod analog_comparator;
 pub mod analog_sensor;
+pub mod audio;
 pub mod app_flash_driver;
+pub mod atecc608;
 pub mod ble_advertising_driver;
+pub mod board_info;
+pub mod boot_banks;
+pub mod bq24195;
 pub mod button;
 pub mod buzzer_driver;
+pub mod charger;
+pub mod config_store;
 pub mod console;
 pub mod crc;
+pub mod cycle_counter;
 pub mod dac;
+pub mod digest;
 pub mod debug_process_restart;
 pub mod driver;
+pub mod drv2605;
+pub mod eeprom_emulation;
+pub mod energy_harvester;
+pub mod entropy_health;
+pub mod error_log;
+pub mod flash_erase_policy;
 pub mod fm25cl;
 pub mod fxos8700cq;
 pub mod gpio;
 pub mod gpio_async;
+pub mod haptics;
 pub mod humidity;
 pub mod i2c_master;
 pub mod i2c_master_slave_driver;
 pub mod ieee802154;
 pub mod isl29035;
 pub mod led;
+pub mod led_matrix;
 pub mod lps25hb;
+pub mod lsm303agr;
 pub mod ltc294x;
 pub mod max17205;
+pub mod measurement;
+pub mod microphone;
+pub mod ml_inference;
+pub mod monotonic_counter;
 pub mod mcp230xx;
 pub mod mx25r6435f;
+pub mod nfc_t4t;
 pub mod ninedof;
 pub mod nonvolatile_storage_driver;
 pub mod nonvolatile_to_pages;
 pub mod nrf51822_serialization;
+pub mod packet_radio;
 pub mod pca9544a;
+pub mod pps;
 pub mod process_console;
+pub mod process_monitor;
+pub mod pulse_capture;
+pub mod pwm;
+pub mod pwm_audio;
 pub mod rf233;
 pub mod rf233_const;
 pub mod rng;
 pub mod sdcard;
 pub mod segger_rtt;
+pub mod self_test;
+pub mod semihosting_uart;
 pub mod si7021;
+pub mod soft_i2c;
+pub mod soft_uart;
+pub mod software_crc;
 pub mod spi;
+pub mod swd;
+pub mod syscall_trace;
+pub mod tamper;
 pub mod temperature;
+pub mod test_harness;
+pub mod thermal_throttle;
 pub mod tmp006;
 pub mod tsl2561;
+pub mod uart_switch;
 pub mod usb;
+pub mod usb_cdc;
 pub mod usb_user;
 pub mod usbc_client;
 pub mod virtual_alarm;
@@ -62,3 +104,4 @@ pub mod virtual_i2c;
 pub mod virtual_pwm;
 pub mod virtual_spi;
 pub mod virtual_uart;
+pub mod voltage;
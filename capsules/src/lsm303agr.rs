@@ -0,0 +1,234 @@
+//! Driver for the LSM303AGR accelerometer and magnetometer.
+//!
+//! <https://www.st.com/resource/en/datasheet/lsm303agr.pdf>
+//!
+//! The accelerometer and the magnetometer are separate devices on the I2C
+//! bus. The driver turns each of them on for a single reading and provides
+//! the x, y, and z values to a callback function: the acceleration in mg and
+//! the magnetic field in mgauss. It implements the `hil::sensors::NineDof`
+//! trait.
+//!
+//! Usage
+//! -----
+//!
+//! ```rust
+//! let accel_i2c = static_init!(I2CDevice, I2CDevice::new(i2c_bus, 0x19));
+//! let mag_i2c = static_init!(I2CDevice, I2CDevice::new(i2c_bus, 0x1e));
+//! let lsm303agr = static_init!(
+//!     capsules::lsm303agr::Lsm303agr<'static>,
+//!     capsules::lsm303agr::Lsm303agr::new(accel_i2c, mag_i2c,
+//!                                         &mut capsules::lsm303agr::BUF));
+//! accel_i2c.set_client(lsm303agr);
+//! mag_i2c.set_client(lsm303agr);
+//! ```
+
+use core::cell::Cell;
+use kernel::common::cells::{OptionalCell, TakeCell};
+use kernel::hil;
+use kernel::hil::i2c::{Error, I2CClient, I2CDevice};
+use kernel::ReturnCode;
+
+pub static mut BUF: [u8; 6] = [0; 6];
+
+/// I2C address of the accelerometer
+pub const ACCELEROMETER_ADDRESS: u8 = 0x19;
+/// I2C address of the magnetometer
+pub const MAGNETOMETER_ADDRESS: u8 = 0x1e;
+
+enum Registers {
+    CtrlReg1A = 0x20,
+    CtrlReg4A = 0x23,
+    StatusRegA = 0x27,
+    OutXLA = 0x28,
+    CfgRegAM = 0x60,
+    StatusRegM = 0x67,
+    OutXLRegM = 0x68,
+}
+
+/// Set in the register address of the accelerometer to read several
+/// registers in a row
+const AUTO_INCREMENT: u8 = 0x80;
+
+/// Data rate of 100 Hz, with the x, y and z axes enabled
+const CTRL_REG1_A_ON: u8 = 0x57;
+/// High resolution mode, with a range of +/- 2 g and 1 mg per digit
+const CTRL_REG4_A_HR: u8 = 0x08;
+/// Single measurement mode of the magnetometer, which goes back to idle
+/// after the measurement
+const CFG_REG_A_M_SINGLE: u8 = 0x01;
+/// New x, y and z data are available, in both status registers
+const STATUS_ZYXDA: u8 = 0x08;
+
+#[derive(Clone, Copy, PartialEq)]
+enum State {
+    /// Both sensors are powered down
+    Disabled,
+
+    /// Setting the resolution of the accelerometer
+    ReadAccelSetup,
+
+    /// Turning the accelerometer on
+    ReadAccelStart,
+
+    /// Waiting for the acceleration sample to be ready
+    ReadAccelWait,
+
+    /// Reading accelerometer data
+    ReadAccelReading,
+
+    /// Powering the accelerometer down
+    ReadAccelDeactivating(i16, i16, i16),
+
+    /// Starting a single measurement of the magnetometer
+    ReadMagStart,
+
+    /// Waiting for the magnetometer sample to be ready
+    ReadMagWait,
+
+    /// Reading magnetometer data
+    ReadMagValues,
+}
+
+pub struct Lsm303agr<'a> {
+    accel_i2c: &'a I2CDevice,
+    mag_i2c: &'a I2CDevice,
+    state: Cell<State>,
+    buffer: TakeCell<'static, [u8]>,
+    callback: OptionalCell<&'static hil::sensors::NineDofClient>,
+}
+
+impl Lsm303agr<'a> {
+    pub fn new(
+        accel_i2c: &'a I2CDevice,
+        mag_i2c: &'a I2CDevice,
+        buffer: &'static mut [u8],
+    ) -> Lsm303agr<'a> {
+        Lsm303agr {
+            accel_i2c: accel_i2c,
+            mag_i2c: mag_i2c,
+            state: Cell::new(State::Disabled),
+            buffer: TakeCell::new(buffer),
+            callback: OptionalCell::empty(),
+        }
+    }
+
+    fn start_read_accel(&self) -> ReturnCode {
+        if self.state.get() != State::Disabled {
+            return ReturnCode::EBUSY;
+        }
+        self.buffer.take().map_or(ReturnCode::EBUSY, |buf| {
+            self.accel_i2c.enable();
+            buf[0] = Registers::CtrlReg4A as u8;
+            buf[1] = CTRL_REG4_A_HR;
+            self.accel_i2c.write(buf, 2);
+            self.state.set(State::ReadAccelSetup);
+            ReturnCode::SUCCESS
+        })
+    }
+
+    fn start_read_magnetometer(&self) -> ReturnCode {
+        if self.state.get() != State::Disabled {
+            return ReturnCode::EBUSY;
+        }
+        self.buffer.take().map_or(ReturnCode::EBUSY, |buf| {
+            self.mag_i2c.enable();
+            buf[0] = Registers::CfgRegAM as u8;
+            buf[1] = CFG_REG_A_M_SINGLE;
+            self.mag_i2c.write(buf, 2);
+            self.state.set(State::ReadMagStart);
+            ReturnCode::SUCCESS
+        })
+    }
+
+    /// Reads the x, y and z values, which are little endian.
+    fn values(buffer: &[u8]) -> (i16, i16, i16) {
+        let x = ((buffer[1] as u16) << 8 | buffer[0] as u16) as i16;
+        let y = ((buffer[3] as u16) << 8 | buffer[2] as u16) as i16;
+        let z = ((buffer[5] as u16) << 8 | buffer[4] as u16) as i16;
+        (x, y, z)
+    }
+}
+
+impl I2CClient for Lsm303agr<'a> {
+    fn command_complete(&self, buffer: &'static mut [u8], _error: Error) {
+        match self.state.get() {
+            State::ReadAccelSetup => {
+                buffer[0] = Registers::CtrlReg1A as u8;
+                buffer[1] = CTRL_REG1_A_ON;
+                self.accel_i2c.write(buffer, 2);
+                self.state.set(State::ReadAccelStart);
+            }
+            State::ReadAccelStart | State::ReadAccelWait => {
+                if self.state.get() == State::ReadAccelWait && buffer[0] & STATUS_ZYXDA != 0 {
+                    buffer[0] = Registers::OutXLA as u8 | AUTO_INCREMENT;
+                    self.accel_i2c.write_read(buffer, 1, 6);
+                    self.state.set(State::ReadAccelReading);
+                } else {
+                    // Poll until the sample is ready
+                    buffer[0] = Registers::StatusRegA as u8;
+                    self.accel_i2c.write_read(buffer, 1, 1);
+                    self.state.set(State::ReadAccelWait);
+                }
+            }
+            State::ReadAccelReading => {
+                // The samples are 12 bits, left justified, of 1 mg each
+                let (x, y, z) = Self::values(buffer);
+
+                buffer[0] = Registers::CtrlReg1A as u8;
+                buffer[1] = 0; // Power down.
+                self.accel_i2c.write(buffer, 2);
+                self.state
+                    .set(State::ReadAccelDeactivating(x >> 4, y >> 4, z >> 4));
+            }
+            State::ReadAccelDeactivating(x, y, z) => {
+                self.accel_i2c.disable();
+                self.state.set(State::Disabled);
+                self.buffer.replace(buffer);
+                self.callback.map(|cb| {
+                    cb.callback(x as usize, y as usize, z as usize);
+                });
+            }
+            State::ReadMagStart | State::ReadMagWait => {
+                if self.state.get() == State::ReadMagWait && buffer[0] & STATUS_ZYXDA != 0 {
+                    buffer[0] = Registers::OutXLRegM as u8;
+                    self.mag_i2c.write_read(buffer, 1, 6);
+                    self.state.set(State::ReadMagValues);
+                } else {
+                    // Poll until the sample is ready
+                    buffer[0] = Registers::StatusRegM as u8;
+                    self.mag_i2c.write_read(buffer, 1, 1);
+                    self.state.set(State::ReadMagWait);
+                }
+            }
+            State::ReadMagValues => {
+                // Each digit is 1.5 mgauss
+                let (x, y, z) = Self::values(buffer);
+                let (x, y, z) = (x as isize * 3 / 2, y as isize * 3 / 2, z as isize * 3 / 2);
+
+                // The magnetometer goes back to idle after a single
+                // measurement.
+                self.mag_i2c.disable();
+                self.state.set(State::Disabled);
+                self.buffer.replace(buffer);
+
+                self.callback
+                    .map(|cb| cb.callback(x as usize, y as usize, z as usize));
+            }
+            State::Disabled => {}
+        }
+    }
+}
+
+impl hil::sensors::NineDof for Lsm303agr<'a> {
+    fn set_client(&self, client: &'static hil::sensors::NineDofClient) {
+        self.callback.set(client);
+    }
+
+    fn read_accelerometer(&self) -> ReturnCode {
+        self.start_read_accel()
+    }
+
+    fn read_magnetometer(&self) -> ReturnCode {
+        self.start_read_magnetometer()
+    }
+}
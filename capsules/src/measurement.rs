@@ -0,0 +1,299 @@
+//! Measurement registers recording what software is running, and a system
+//! call driver that allows processes to read and extend them.
+//!
+//! Like the platform configuration registers of a TPM, each register can
+//! only be extended, never written directly: extending register `i` with a
+//! measurement `m` sets it to `H(register[i] || m)`. The final value of a
+//! register therefore depends on every measurement extended into it and on
+//! their order, and software that runs later cannot remove the record of
+//! software that ran before it. Registers start at zero on every boot.
+//!
+//! The board measures the kernel and the process binaries as it boots, before
+//! any process runs. A remote party can then be given the register values (or
+//! their `composite` digest, signed by an attestation capsule through the
+//! `Measurements` trait) as evidence of what is running, and compare them with
+//! the values expected from known-good images.
+//!
+//! The registers in `register` are reserved for measurements made by the
+//! kernel. Processes can extend the remaining registers, e.g. with
+//! measurements of their configuration, but cannot extend the reserved ones.
+//!
+//! Usage
+//! -----
+//!
+//! ```rust
+//! let measurements = static_init!(
+//!     capsules::measurement::MeasurementRegisters<capsules::digest::sha3::Sha3_256>,
+//!     capsules::measurement::MeasurementRegisters::new(
+//!         capsules::digest::sha3::Sha3_256::new()
+//!     )
+//! );
+//! // `kernel_image` and `app_flash` are slices covering the kernel text and
+//! // the process binaries, built from the linker symbols
+//! measurements.measure(capsules::measurement::register::KERNEL, kernel_image);
+//! measurements.measure(capsules::measurement::register::APPS, app_flash);
+//! let measurement_driver = static_init!(
+//!     capsules::measurement::MeasurementDriver<'static>,
+//!     capsules::measurement::MeasurementDriver::new(measurements, kernel::Grant::create())
+//! );
+//! ```
+
+use core::cell::Cell;
+use kernel::common::cells::MapCell;
+use kernel::{AppId, AppSlice, Driver, Grant, ReturnCode, Shared};
+
+use crate::digest::DigestEngine;
+
+/// Syscall driver number.
+use crate::driver;
+pub const DRIVER_NUM: usize = driver::NUM::Measurement as usize;
+
+pub const NUM_REGISTERS: usize = 8;
+
+/// Size of the storage for each register. Only the first `DIGEST_LEN` bytes
+/// of the digest engine in use are significant.
+pub const REGISTER_LEN: usize = 32;
+
+/// Registers reserved for measurements made by the kernel.
+pub mod register {
+    /// The kernel image
+    pub const KERNEL: usize = 0;
+    /// The process binaries, in the order they are loaded
+    pub const APPS: usize = 1;
+    /// Board configuration, such as the contents of a configuration page
+    pub const CONFIG: usize = 2;
+    /// Reserved for future use by the kernel
+    pub const RESERVED: usize = 3;
+
+    /// The first register that processes can extend.
+    pub const FIRST_PROCESS_REGISTER: usize = 4;
+}
+
+/// Access to a set of measurement registers, for capsules such as an
+/// attestation service that report them.
+pub trait Measurements {
+    /// Length of each register value, in bytes.
+    fn register_len(&self) -> usize;
+
+    /// Extends register `index` with `measurement`. Returns `EINVAL` if there
+    /// is no such register.
+    fn extend(&self, index: usize, measurement: &[u8]) -> ReturnCode;
+
+    /// Copies the value of register `index` to `buf`, returning the number of
+    /// bytes copied. Returns `None` if there is no such register or `buf` is
+    /// shorter than `register_len()`.
+    fn read(&self, index: usize, buf: &mut [u8]) -> Option<usize>;
+
+    /// Returns the number of measurements extended into register `index`.
+    fn extend_count(&self, index: usize) -> Option<u32>;
+
+    /// Writes a digest of all registers, in order, to `buf`, returning its
+    /// length. Returns `None` if `buf` is shorter than `register_len()`.
+    fn composite(&self, buf: &mut [u8]) -> Option<usize>;
+}
+
+pub struct MeasurementRegisters<E: DigestEngine> {
+    engine: MapCell<E>,
+    registers: MapCell<[[u8; REGISTER_LEN]; NUM_REGISTERS]>,
+    counts: [Cell<u32>; NUM_REGISTERS],
+}
+
+impl<E: DigestEngine> MeasurementRegisters<E> {
+    /// Creates a set of registers that all start at zero. The digest length
+    /// of `engine` must be at most `REGISTER_LEN`.
+    pub fn new(engine: E) -> MeasurementRegisters<E> {
+        assert!(E::DIGEST_LEN <= REGISTER_LEN);
+        MeasurementRegisters {
+            engine: MapCell::new(engine),
+            registers: MapCell::new([[0; REGISTER_LEN]; NUM_REGISTERS]),
+            counts: Default::default(),
+        }
+    }
+
+    /// Extends register `index` with the digest of `data`, e.g. an image to
+    /// be measured. Returns `EINVAL` if there is no such register.
+    pub fn measure(&self, index: usize, data: &[u8]) -> ReturnCode {
+        let mut digest = [0; REGISTER_LEN];
+        let hashed = self.engine.map(|engine| {
+            engine.reset();
+            engine.update(data);
+            engine.finalize(&mut digest);
+        });
+        match hashed {
+            Some(()) => self.extend(index, &digest[..E::DIGEST_LEN]),
+            None => ReturnCode::FAIL,
+        }
+    }
+}
+
+impl<E: DigestEngine> Measurements for MeasurementRegisters<E> {
+    fn register_len(&self) -> usize {
+        E::DIGEST_LEN
+    }
+
+    fn extend(&self, index: usize, measurement: &[u8]) -> ReturnCode {
+        if index >= NUM_REGISTERS {
+            return ReturnCode::EINVAL;
+        }
+        let extended = self.engine.and_then(|engine| {
+            self.registers.map(|registers| {
+                let register = &mut registers[index][..E::DIGEST_LEN];
+                engine.reset();
+                engine.update(register);
+                engine.update(measurement);
+                engine.finalize(register);
+            })
+        });
+        match extended {
+            Some(()) => {
+                let count = &self.counts[index];
+                count.set(count.get().saturating_add(1));
+                ReturnCode::SUCCESS
+            }
+            None => ReturnCode::FAIL,
+        }
+    }
+
+    fn read(&self, index: usize, buf: &mut [u8]) -> Option<usize> {
+        if index >= NUM_REGISTERS || buf.len() < E::DIGEST_LEN {
+            return None;
+        }
+        self.registers.map(|registers| {
+            buf[..E::DIGEST_LEN].copy_from_slice(&registers[index][..E::DIGEST_LEN]);
+            E::DIGEST_LEN
+        })
+    }
+
+    fn extend_count(&self, index: usize) -> Option<u32> {
+        self.counts.get(index).map(|count| count.get())
+    }
+
+    fn composite(&self, buf: &mut [u8]) -> Option<usize> {
+        if buf.len() < E::DIGEST_LEN {
+            return None;
+        }
+        self.engine.and_then(|engine| {
+            self.registers.map(|registers| {
+                engine.reset();
+                for register in registers.iter() {
+                    engine.update(&register[..E::DIGEST_LEN]);
+                }
+                engine.finalize(buf);
+                E::DIGEST_LEN
+            })
+        })
+    }
+}
+
+#[derive(Default)]
+pub struct App {
+    buffer: Option<AppSlice<Shared, u8>>,
+}
+
+pub struct MeasurementDriver<'a> {
+    measurements: &'a Measurements,
+    apps: Grant<App>,
+}
+
+impl MeasurementDriver<'a> {
+    pub fn new(measurements: &'a Measurements, apps: Grant<App>) -> MeasurementDriver<'a> {
+        MeasurementDriver {
+            measurements: measurements,
+            apps: apps,
+        }
+    }
+}
+
+impl Driver for MeasurementDriver<'a> {
+    /// Setup the buffer used to read registers and extend them.
+    ///
+    /// ### `allow_num`
+    ///
+    /// - `0`: The buffer that register values and composite digests are
+    ///        written to, and that measurements are extended from.
+    fn allow(
+        &self,
+        appid: AppId,
+        allow_num: usize,
+        slice: Option<AppSlice<Shared, u8>>,
+    ) -> ReturnCode {
+        match allow_num {
+            0 => self
+                .apps
+                .enter(appid, |app, _| {
+                    app.buffer = slice;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or_else(|err| err.into()),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    /// Read and extend the measurement registers.
+    ///
+    /// ### `command_num`
+    ///
+    /// - `0`: Driver check.
+    /// - `1`: Returns the number of registers.
+    /// - `2`: Returns the length of each register value, in bytes.
+    /// - `3`: Writes the value of register `arg1` to the allowed buffer and
+    ///        returns its length.
+    /// - `4`: Extends register `arg1` with the first `arg2` bytes of the
+    ///        allowed buffer. Only registers from
+    ///        `register::FIRST_PROCESS_REGISTER` on can be extended.
+    /// - `5`: Returns the number of measurements extended into register
+    ///        `arg1`.
+    /// - `6`: Writes a digest of all registers to the allowed buffer and
+    ///        returns its length.
+    fn command(&self, command_num: usize, arg1: usize, arg2: usize, appid: AppId) -> ReturnCode {
+        match command_num {
+            0 => ReturnCode::SUCCESS,
+            1 => ReturnCode::SuccessWithValue {
+                value: NUM_REGISTERS,
+            },
+            2 => ReturnCode::SuccessWithValue {
+                value: self.measurements.register_len(),
+            },
+            3 | 6 => self
+                .apps
+                .enter(appid, |app, _| {
+                    app.buffer.as_mut().map_or(ReturnCode::ENOMEM, |buffer| {
+                        let len = if command_num == 3 {
+                            if arg1 >= NUM_REGISTERS {
+                                return ReturnCode::EINVAL;
+                            }
+                            self.measurements.read(arg1, buffer.as_mut())
+                        } else {
+                            self.measurements.composite(buffer.as_mut())
+                        };
+                        len.map_or(ReturnCode::ESIZE, |len| ReturnCode::SuccessWithValue {
+                            value: len,
+                        })
+                    })
+                })
+                .unwrap_or_else(|err| err.into()),
+            4 => {
+                if arg1 < register::FIRST_PROCESS_REGISTER || arg1 >= NUM_REGISTERS {
+                    return ReturnCode::EINVAL;
+                }
+                self.apps
+                    .enter(appid, |app, _| {
+                        app.buffer.as_ref().map_or(ReturnCode::ENOMEM, |buffer| {
+                            if arg2 > buffer.len() {
+                                return ReturnCode::ESIZE;
+                            }
+                            self.measurements.extend(arg1, &buffer.as_ref()[..arg2])
+                        })
+                    })
+                    .unwrap_or_else(|err| err.into())
+            }
+            5 => self
+                .measurements
+                .extend_count(arg1)
+                .map_or(ReturnCode::EINVAL, |count| ReturnCode::SuccessWithValue {
+                    value: count as usize,
+                }),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+}
@@ -0,0 +1,245 @@
+//! Provides userspace with access to a microphone, delivering fixed-size
+//! frames of audio.
+//!
+//! A process starts capture by giving a sample rate and a frame size, and is
+//! then called back with each frame of samples (signed 16-bit little endian,
+//! mono) copied into its allowed buffer, until it stops capture. Frames are
+//! captured continuously, as keyword spotting and similar applications need,
+//! and are numbered so that the process can tell if it has missed any by
+//! taking longer than a frame to process the previous one.
+//!
+//! The microphone belongs to one process at a time: while a process is
+//! capturing, requests to start capture from other processes return `EBUSY`.
+//! The microphone is released when the process stops capture, or when it can
+//! no longer be called back, e.g. because it has faulted.
+//!
+//! Usage
+//! -----
+//!
+//! ```rust
+//! let microphone = static_init!(
+//!     capsules::microphone::Microphone<'static>,
+//!     capsules::microphone::Microphone::new(
+//!         adc_microphone,
+//!         &mut capsules::microphone::BUFFER1,
+//!         &mut capsules::microphone::BUFFER2,
+//!         kernel::Grant::create())
+//! );
+//! hil::audio::AudioInput::set_client(adc_microphone, microphone);
+//! ```
+
+use core::cell::Cell;
+use core::cmp;
+use kernel::common::cells::{OptionalCell, TakeCell};
+use kernel::hil::audio::{self, SAMPLE_LEN};
+use kernel::{AppId, AppSlice, Callback, Driver, Grant, ReturnCode, Shared};
+
+/// Syscall driver number.
+use crate::driver;
+pub const DRIVER_NUM: usize = driver::NUM::Microphone as usize;
+
+/// Buffers that frames are captured into, each up to 256 samples long.
+pub static mut BUFFER1: [u8; 512] = [0; 512];
+pub static mut BUFFER2: [u8; 512] = [0; 512];
+
+#[derive(Default)]
+pub struct App {
+    callback: Option<Callback>,
+    buffer: Option<AppSlice<Shared, u8>>,
+}
+
+pub struct Microphone<'a> {
+    input: &'a audio::AudioInput<'a>,
+    apps: Grant<App>,
+    buffer1: TakeCell<'static, [u8]>,
+    buffer2: TakeCell<'static, [u8]>,
+    /// The largest frame that fits in the buffers, in samples
+    max_frame_samples: usize,
+    /// The process that is capturing
+    owner: OptionalCell<AppId>,
+    /// Number of the next frame
+    frame: Cell<usize>,
+}
+
+impl Microphone<'a> {
+    pub fn new(
+        input: &'a audio::AudioInput<'a>,
+        buffer1: &'static mut [u8],
+        buffer2: &'static mut [u8],
+        grant: Grant<App>,
+    ) -> Microphone<'a> {
+        Microphone {
+            input: input,
+            apps: grant,
+            max_frame_samples: cmp::min(buffer1.len(), buffer2.len()) / SAMPLE_LEN,
+            buffer1: TakeCell::new(buffer1),
+            buffer2: TakeCell::new(buffer2),
+            owner: OptionalCell::empty(),
+            frame: Cell::new(0),
+        }
+    }
+
+    /// Stores a frame buffer that is not being used by the microphone.
+    fn replace_buffer(&self, buffer: &'static mut [u8]) {
+        if self.buffer1.is_none() {
+            self.buffer1.replace(buffer);
+        } else {
+            self.buffer2.replace(buffer);
+        }
+    }
+
+    fn start(&self, appid: AppId, sample_rate_hz: u32, samples: usize) -> ReturnCode {
+        if self.owner.is_some() {
+            return ReturnCode::EBUSY;
+        }
+        if samples == 0 || samples > self.max_frame_samples {
+            return ReturnCode::ESIZE;
+        }
+        let buffer_len = self
+            .apps
+            .enter(appid, |app, _| {
+                app.buffer
+                    .as_ref()
+                    .map_or(Err(ReturnCode::ENOMEM), |buffer| Ok(buffer.len()))
+            })
+            .unwrap_or_else(|err| Err(err.into()));
+        match buffer_len {
+            Ok(len) if len < samples * SAMPLE_LEN => return ReturnCode::ESIZE,
+            Ok(_) => {}
+            Err(err) => return err,
+        }
+
+        match (self.buffer1.take(), self.buffer2.take()) {
+            (Some(buffer1), Some(buffer2)) => {
+                let (result, buffer1, buffer2) =
+                    self.input.start(sample_rate_hz, buffer1, buffer2, samples);
+                buffer1.map(|buffer| self.replace_buffer(buffer));
+                buffer2.map(|buffer| self.replace_buffer(buffer));
+                if result == ReturnCode::SUCCESS {
+                    self.owner.set(appid);
+                    self.frame.set(0);
+                }
+                result
+            }
+            (buffer1, buffer2) => {
+                buffer1.map(|buffer| self.replace_buffer(buffer));
+                buffer2.map(|buffer| self.replace_buffer(buffer));
+                ReturnCode::EBUSY
+            }
+        }
+    }
+
+    fn stop(&self) -> ReturnCode {
+        self.owner.clear();
+        let (result, buffer1, buffer2) = self.input.stop();
+        buffer1.map(|buffer| self.replace_buffer(buffer));
+        buffer2.map(|buffer| self.replace_buffer(buffer));
+        result
+    }
+}
+
+impl audio::InputClient for Microphone<'a> {
+    fn samples_ready(&self, buffer: &'static mut [u8], samples: usize) {
+        let frame = self.frame.get();
+        self.frame.set(frame.wrapping_add(1));
+
+        let delivered = self.owner.map_or(false, |owner| {
+            self.apps
+                .enter(*owner, |app, _| {
+                    app.buffer.as_mut().map(|app_buffer| {
+                        let len = cmp::min(samples * SAMPLE_LEN, app_buffer.len());
+                        app_buffer.as_mut()[..len].copy_from_slice(&buffer[..len]);
+                    });
+                    app.callback
+                        .map(|mut callback| callback.schedule(samples, frame, 0));
+                })
+                .is_ok()
+        });
+
+        let (_, buffer) = self.input.provide_buffer(buffer);
+        buffer.map(|buffer| self.replace_buffer(buffer));
+
+        // Release the microphone if its owner is gone
+        if !delivered && self.owner.is_some() {
+            self.stop();
+        }
+    }
+}
+
+impl Driver for Microphone<'a> {
+    /// Setup the buffer that frames are copied into.
+    ///
+    /// ### `allow_num`
+    ///
+    /// - `0`: The frame buffer, at least as long as a frame.
+    fn allow(
+        &self,
+        appid: AppId,
+        allow_num: usize,
+        slice: Option<AppSlice<Shared, u8>>,
+    ) -> ReturnCode {
+        match allow_num {
+            0 => self
+                .apps
+                .enter(appid, |app, _| {
+                    app.buffer = slice;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or_else(|err| err.into()),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    /// Subscribe to captured frames.
+    ///
+    /// ### `subscribe_num`
+    ///
+    /// - `0`: Set the callback called for each frame, with the number of
+    ///        samples and the frame number.
+    fn subscribe(
+        &self,
+        subscribe_num: usize,
+        callback: Option<Callback>,
+        appid: AppId,
+    ) -> ReturnCode {
+        match subscribe_num {
+            0 => self
+                .apps
+                .enter(appid, |app, _| {
+                    app.callback = callback;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or_else(|err| err.into()),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    /// Control capture.
+    ///
+    /// ### `command_num`
+    ///
+    /// - `0`: Driver check.
+    /// - `1`: Returns the largest frame size, in samples.
+    /// - `2`: Returns whether a sample rate of `arg1` Hz is supported.
+    /// - `3`: Start capturing at `arg1` Hz, in frames of `arg2` samples.
+    /// - `4`: Stop capturing.
+    fn command(&self, command_num: usize, arg1: usize, arg2: usize, appid: AppId) -> ReturnCode {
+        match command_num {
+            0 => ReturnCode::SUCCESS,
+            1 => ReturnCode::SuccessWithValue {
+                value: self.max_frame_samples,
+            },
+            2 => ReturnCode::SuccessWithValue {
+                value: self.input.sample_rate_supported(arg1 as u32) as usize,
+            },
+            3 => self.start(appid, arg1 as u32, arg2),
+            4 => {
+                if !self.owner.map_or(false, |owner| *owner == appid) {
+                    return ReturnCode::EOFF;
+                }
+                self.stop()
+            }
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+}
@@ -0,0 +1,677 @@
+//! Runs small quantized neural networks for userspace, e.g. to recognize
+//! gestures or keywords.
+//!
+//! Models are stored by processes in their own flash, in the format below,
+//! and loaded by giving their address and length. Loading checks the model
+//! and returns a handle, which any process can then use to run the model, so
+//! that a model loaded by one process can be shared by others. A process that
+//! knows the ID of a model but not its handle can look it up. Models stay
+//! loaded until the process that loaded them unloads them.
+//!
+//! To run a model, a process allows an input and an output buffer and gives
+//! the handle of the model. The network is evaluated a few outputs at a time
+//! from deferred calls, so that it does not hold up the rest of the kernel,
+//! and the process is called back once the output has been copied into its
+//! buffer. One model runs at a time; runs requested by other processes in the
+//! meantime are queued.
+//!
+//! Model format
+//! ------------
+//!
+//! Models are quantized in the style of TensorFlow Lite for Microcontrollers:
+//! activations and weights are `i8`s, activations mapping to real values as
+//! `scale * (value - zero point)`, and weights symmetrically (with a zero
+//! point of 0). All values are little endian. A model starts with a 12 byte
+//! header:
+//!
+//! | Offset | Type    | Field                                      |
+//! |--------|---------|--------------------------------------------|
+//! | 0      | [u8; 4] | Magic, `TMDL`                              |
+//! | 4      | u8      | Format version, 1                          |
+//! | 5      | u8      | Number of layers                           |
+//! | 6      | u16     | Length of the input                        |
+//! | 8      | u32     | Model ID                                   |
+//!
+//! which is followed by the layers. Each layer is a 16 byte header:
+//!
+//! | Offset | Type | Field                                             |
+//! |--------|------|---------------------------------------------------|
+//! | 0      | u8   | Operator: 0 fully connected, 1 1D convolution     |
+//! | 1      | u8   | Flags: bit 0 applies a ReLU to the output         |
+//! | 2      | u8   | Kernel size, 1 for fully connected layers         |
+//! | 3      | u8   | Stride, 1 for fully connected layers              |
+//! | 4      | u16  | Input channels                                    |
+//! | 6      | u16  | Output channels                                   |
+//! | 8      | i8   | Input zero point                                  |
+//! | 9      | i8   | Output zero point                                 |
+//! | 10     | u8   | Output shift                                      |
+//! | 11     | u8   | Reserved, 0                                       |
+//! | 12     | i32  | Output multiplier, in Q31                         |
+//!
+//! followed by its weights, as `i8`s ordered by output channel, then kernel
+//! position, then input channel, and then its biases, one `i32` per output
+//! channel.
+//!
+//! A fully connected layer takes its whole input, whose length must be its
+//! number of input channels. A convolution takes its input as a sequence of
+//! positions of `input channels` values each, e.g. the axes of an
+//! accelerometer over time, and slides its kernel along them without padding.
+//! Each output is `bias + Σ weight * (input - input zero point)`, multiplied
+//! by the multiplier, shifted right by the shift, offset by the output zero
+//! point and saturated to an `i8`.
+//!
+//! Usage
+//! -----
+//!
+//! ```rust
+//! let ml_inference = static_init!(
+//!     capsules::ml_inference::MlInference<'static>,
+//!     capsules::ml_inference::MlInference::new(
+//!         &mut capsules::ml_inference::ARENA,
+//!         dynamic_deferred_caller,
+//!         kernel::Grant::create())
+//! );
+//! ml_inference.initialize_callback_handle(
+//!     dynamic_deferred_caller.register(ml_inference).expect("no deferred call slot available")
+//! );
+//! ```
+
+use core::cell::Cell;
+use core::cmp;
+use kernel::common::cells::{OptionalCell, TakeCell};
+use kernel::common::dynamic_deferred_call::{
+    DeferredCallHandle, DynamicDeferredCall, DynamicDeferredCallClient,
+};
+use kernel::{AppId, AppSlice, Callback, Driver, Grant, ReturnCode, Shared};
+
+/// Syscall driver number.
+use crate::driver;
+pub const DRIVER_NUM: usize = driver::NUM::MlInference as usize;
+
+/// Memory for the activations of the running model. Each half holds the
+/// input or output of a layer, so this bounds the length of those.
+pub static mut ARENA: [i8; 1024] = [0; 1024];
+
+/// Number of models that can be loaded at once.
+pub const MAX_MODELS: usize = 4;
+
+/// Number of outputs computed in each deferred call.
+const OUTPUTS_PER_CALL: usize = 32;
+
+const MAGIC: [u8; 4] = *b"TMDL";
+const VERSION: u8 = 1;
+const HEADER_LEN: usize = 12;
+const LAYER_HEADER_LEN: usize = 16;
+
+const OP_FULLY_CONNECTED: u8 = 0;
+const OP_CONV_1D: u8 = 1;
+const FLAG_RELU: u8 = 0x01;
+
+fn read_u16(data: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([data[offset], data[offset + 1]])
+}
+
+fn read_u32(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([
+        data[offset],
+        data[offset + 1],
+        data[offset + 2],
+        data[offset + 3],
+    ])
+}
+
+/// Scales an accumulator to an output value.
+fn requantize(acc: i32, multiplier: i32, shift: u32, zero_point: i32, relu: bool) -> i8 {
+    // Rounding multiplication by the Q31 multiplier, then rounding shift
+    let scaled = (acc as i64 * multiplier as i64 + (1 << 30)) >> 31;
+    let scaled = if shift > 0 {
+        (scaled + (1 << (shift - 1))) >> shift
+    } else {
+        scaled
+    };
+    let min = if relu { zero_point as i64 } else { -128 };
+    cmp::max(min, cmp::min(127, scaled + zero_point as i64)) as i8
+}
+
+#[derive(Clone, Copy)]
+struct Layer {
+    relu: bool,
+    kernel_size: usize,
+    stride: usize,
+    input_channels: usize,
+    output_channels: usize,
+    input_zero_point: i32,
+    output_zero_point: i32,
+    shift: u32,
+    multiplier: i32,
+    weights: &'static [u8],
+    biases: &'static [u8],
+    /// Number of positions that the kernel is applied at
+    positions: usize,
+}
+
+impl Layer {
+    /// Parses the layer at the start of `data`, which takes an input of
+    /// `input_len` values. Returns the layer and the data after it.
+    fn parse(data: &'static [u8], input_len: usize) -> Result<(Layer, &'static [u8]), ReturnCode> {
+        if data.len() < LAYER_HEADER_LEN {
+            return Err(ReturnCode::EINVAL);
+        }
+        let op = data[0];
+        let kernel_size = data[2] as usize;
+        let stride = data[3] as usize;
+        let input_channels = read_u16(data, 4) as usize;
+        let output_channels = read_u16(data, 6) as usize;
+        let shift = data[10] as u32;
+        let multiplier = read_u32(data, 12) as i32;
+        if input_channels == 0
+            || output_channels == 0
+            || kernel_size == 0
+            || stride == 0
+            || shift > 31
+            || multiplier <= 0
+            || input_len % input_channels != 0
+        {
+            return Err(ReturnCode::EINVAL);
+        }
+        let input_positions = input_len / input_channels;
+        let valid = match op {
+            OP_FULLY_CONNECTED => input_positions == 1 && kernel_size == 1 && stride == 1,
+            OP_CONV_1D => input_positions >= kernel_size,
+            _ => false,
+        };
+        if !valid {
+            return Err(ReturnCode::EINVAL);
+        }
+
+        let weights_len = output_channels * kernel_size * input_channels;
+        let biases_len = output_channels * 4;
+        let rest = &data[LAYER_HEADER_LEN..];
+        if rest.len() < weights_len + biases_len {
+            return Err(ReturnCode::EINVAL);
+        }
+        let layer = Layer {
+            relu: data[1] & FLAG_RELU != 0,
+            kernel_size: kernel_size,
+            stride: stride,
+            input_channels: input_channels,
+            output_channels: output_channels,
+            input_zero_point: data[8] as i8 as i32,
+            output_zero_point: data[9] as i8 as i32,
+            shift: shift,
+            multiplier: multiplier,
+            weights: &rest[..weights_len],
+            biases: &rest[weights_len..weights_len + biases_len],
+            positions: (input_positions - kernel_size) / stride + 1,
+        };
+        Ok((layer, &rest[weights_len + biases_len..]))
+    }
+
+    fn output_len(&self) -> usize {
+        self.positions * self.output_channels
+    }
+
+    /// Computes output `index` of the layer from `input`.
+    fn compute(&self, input: &[i8], index: usize) -> i8 {
+        let position = index / self.output_channels;
+        let channel = index % self.output_channels;
+        let window_len = self.kernel_size * self.input_channels;
+        let start = position * self.stride * self.input_channels;
+        let window = &input[start..start + window_len];
+        let weights = &self.weights[channel * window_len..(channel + 1) * window_len];
+
+        let mut acc = read_u32(self.biases, channel * 4) as i32;
+        for (&weight, &value) in weights.iter().zip(window.iter()) {
+            acc = acc.wrapping_add(weight as i8 as i32 * (value as i32 - self.input_zero_point));
+        }
+        requantize(
+            acc,
+            self.multiplier,
+            self.shift,
+            self.output_zero_point,
+            self.relu,
+        )
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Model {
+    /// The process that loaded the model, and can unload it
+    owner: AppId,
+    id: u32,
+    data: &'static [u8],
+    input_len: usize,
+    output_len: usize,
+}
+
+#[derive(Default)]
+pub struct App {
+    callback: Option<Callback>,
+    input: Option<AppSlice<Shared, u8>>,
+    output: Option<AppSlice<Shared, u8>>,
+    /// The model that the process is waiting to run
+    pending: Option<usize>,
+}
+
+pub struct MlInference<'a> {
+    apps: Grant<App>,
+    models: [OptionalCell<Model>; MAX_MODELS],
+    arena: TakeCell<'static, [i8]>,
+    deferred_caller: &'a DynamicDeferredCall,
+    deferred_call_handle: OptionalCell<DeferredCallHandle>,
+    /// The process whose run is in progress
+    running: OptionalCell<AppId>,
+    /// Handle of the running model
+    handle: Cell<usize>,
+    /// The layer being computed
+    layer: OptionalCell<Layer>,
+    /// The layers after the current one
+    rest: Cell<&'static [u8]>,
+    layers_left: Cell<usize>,
+    /// Index of the next output of the current layer
+    output_index: Cell<usize>,
+    /// Which half of the arena holds the input of the current layer
+    input_half: Cell<usize>,
+}
+
+impl MlInference<'a> {
+    pub fn new(
+        arena: &'static mut [i8],
+        deferred_caller: &'a DynamicDeferredCall,
+        grant: Grant<App>,
+    ) -> MlInference<'a> {
+        MlInference {
+            apps: grant,
+            models: [
+                OptionalCell::empty(),
+                OptionalCell::empty(),
+                OptionalCell::empty(),
+                OptionalCell::empty(),
+            ],
+            arena: TakeCell::new(arena),
+            deferred_caller: deferred_caller,
+            deferred_call_handle: OptionalCell::empty(),
+            running: OptionalCell::empty(),
+            handle: Cell::new(0),
+            layer: OptionalCell::empty(),
+            rest: Cell::new(&[]),
+            layers_left: Cell::new(0),
+            output_index: Cell::new(0),
+            input_half: Cell::new(0),
+        }
+    }
+
+    pub fn initialize_callback_handle(&self, handle: DeferredCallHandle) {
+        self.deferred_call_handle.replace(handle);
+    }
+
+    /// Length of each half of the arena.
+    fn half_len(&self) -> usize {
+        self.arena.map_or(0, |arena| arena.len() / 2)
+    }
+
+    fn model(&self, handle: usize) -> Option<Model> {
+        self.models
+            .get(handle)
+            .and_then(|slot| slot.map(|model| *model))
+    }
+
+    /// Checks that `data` is a model that can be run, and returns it.
+    fn parse_model(&self, owner: AppId, data: &'static [u8]) -> Result<Model, ReturnCode> {
+        if data.len() < HEADER_LEN || data[0..4] != MAGIC || data[4] != VERSION {
+            return Err(ReturnCode::EINVAL);
+        }
+        let layers = data[5] as usize;
+        let input_len = read_u16(data, 6) as usize;
+        if layers == 0 || input_len == 0 {
+            return Err(ReturnCode::EINVAL);
+        }
+        let half_len = self.half_len();
+        if input_len > half_len {
+            return Err(ReturnCode::ESIZE);
+        }
+
+        let mut rest = &data[HEADER_LEN..];
+        let mut len = input_len;
+        for _ in 0..layers {
+            let (layer, next) = Layer::parse(rest, len)?;
+            len = layer.output_len();
+            if len > half_len {
+                return Err(ReturnCode::ESIZE);
+            }
+            rest = next;
+        }
+        Ok(Model {
+            owner: owner,
+            id: read_u32(data, 8),
+            data: data,
+            input_len: input_len,
+            output_len: len,
+        })
+    }
+
+    /// Loads the model at `address` in the flash of `appid`, and returns its
+    /// handle.
+    fn load(&self, appid: AppId, address: usize, length: usize) -> ReturnCode {
+        let (flash_start, flash_end) = appid.get_editable_flash_range();
+        if address < flash_start || address > flash_end || length > flash_end - address {
+            return ReturnCode::EINVAL;
+        }
+        let offset = address - flash_start;
+        let data = &appid.get_editable_flash()[offset..offset + length];
+        let model = match self.parse_model(appid, data) {
+            Ok(model) => model,
+            Err(err) => return err,
+        };
+
+        // A process that restarts loads the same model again
+        for handle in 0..MAX_MODELS {
+            if let Some(existing) = self.model(handle) {
+                if existing.id == model.id {
+                    if existing.data.as_ptr() == data.as_ptr() && existing.data.len() == length {
+                        return ReturnCode::SuccessWithValue { value: handle };
+                    }
+                    return ReturnCode::EALREADY;
+                }
+            }
+        }
+        match self.models.iter().position(|slot| slot.is_none()) {
+            Some(handle) => {
+                self.models[handle].set(model);
+                ReturnCode::SuccessWithValue { value: handle }
+            }
+            None => ReturnCode::ENOMEM,
+        }
+    }
+
+    fn unload(&self, appid: AppId, handle: usize) -> ReturnCode {
+        match self.model(handle) {
+            Some(model) if model.owner == appid => {
+                if self.running.is_some() && self.handle.get() == handle {
+                    return ReturnCode::EBUSY;
+                }
+                self.models[handle].clear();
+                ReturnCode::SUCCESS
+            }
+            _ => ReturnCode::EINVAL,
+        }
+    }
+
+    /// Runs model `handle` for `appid`, or queues the run if another is in
+    /// progress.
+    fn run(&self, appid: AppId, handle: usize) -> ReturnCode {
+        let model = match self.model(handle) {
+            Some(model) => model,
+            None => return ReturnCode::EINVAL,
+        };
+        if self.running.map_or(false, |running| *running == appid) {
+            return ReturnCode::EBUSY;
+        }
+        self.apps
+            .enter(appid, |app, _| {
+                if app.pending.is_some() {
+                    return ReturnCode::EBUSY;
+                }
+                match (app.input.as_ref(), app.output.as_ref()) {
+                    (Some(input), Some(output)) => {
+                        if input.len() < model.input_len || output.len() < model.output_len {
+                            return ReturnCode::ESIZE;
+                        }
+                    }
+                    _ => return ReturnCode::ENOMEM,
+                }
+                if self.running.is_some() {
+                    app.pending = Some(handle);
+                    return ReturnCode::SUCCESS;
+                }
+                self.start(appid, app, handle, &model)
+            })
+            .unwrap_or_else(|err| err.into())
+    }
+
+    /// Copies the input of `app` into the arena and starts computing the
+    /// first layer of `model`.
+    fn start(&self, appid: AppId, app: &mut App, handle: usize, model: &Model) -> ReturnCode {
+        let layer = match Layer::parse(&model.data[HEADER_LEN..], model.input_len) {
+            Ok((layer, rest)) => {
+                self.rest.set(rest);
+                layer
+            }
+            Err(err) => return err,
+        };
+        let copied = self.arena.map_or(false, |arena| {
+            app.input.as_ref().map_or(false, |input| {
+                if input.len() < model.input_len {
+                    return false;
+                }
+                for (value, &byte) in arena
+                    .iter_mut()
+                    .zip(input.as_ref()[..model.input_len].iter())
+                {
+                    *value = byte as i8;
+                }
+                true
+            })
+        });
+        if !copied {
+            return ReturnCode::ESIZE;
+        }
+        let deferred_call_handle = match self.deferred_call_handle.map(|handle| *handle) {
+            Some(deferred_call_handle) => deferred_call_handle,
+            None => return ReturnCode::FAIL,
+        };
+
+        self.running.set(appid);
+        self.handle.set(handle);
+        self.layer.set(layer);
+        self.layers_left.set(model.data[5] as usize);
+        self.output_index.set(0);
+        self.input_half.set(0);
+        self.deferred_caller.set(deferred_call_handle);
+        ReturnCode::SUCCESS
+    }
+
+    /// Computes the next outputs of the current layer. Returns `true` once
+    /// the last layer is done.
+    fn step(&self) -> Result<bool, ReturnCode> {
+        let layer = self.layer.map(|layer| *layer).ok_or(ReturnCode::FAIL)?;
+        let half_len = self.half_len();
+        let input_half = self.input_half.get();
+        let start = self.output_index.get();
+        let end = cmp::min(start + OUTPUTS_PER_CALL, layer.output_len());
+
+        self.arena.map(|arena| {
+            let (first, second) = arena.split_at_mut(half_len);
+            let (input, output) = if input_half == 0 {
+                (first, second)
+            } else {
+                (second, first)
+            };
+            for index in start..end {
+                output[index] = layer.compute(input, index);
+            }
+        });
+        self.output_index.set(end);
+        if end < layer.output_len() {
+            return Ok(false);
+        }
+
+        // On to the next layer, whose input is this layer's output
+        self.input_half.set(1 - input_half);
+        self.layers_left.set(self.layers_left.get() - 1);
+        if self.layers_left.get() == 0 {
+            return Ok(true);
+        }
+        let (next, rest) = Layer::parse(self.rest.get(), layer.output_len())?;
+        self.layer.set(next);
+        self.rest.set(rest);
+        self.output_index.set(0);
+        Ok(false)
+    }
+
+    /// Ends the current run, copying the output to the process that ran the
+    /// model, and starts the next queued run.
+    fn finish(&self, result: ReturnCode) {
+        let handle = self.handle.get();
+        let output_len = self.model(handle).map_or(0, |model| model.output_len);
+        let half_len = self.half_len();
+        let output_start = self.input_half.get() * half_len;
+        self.layer.clear();
+        self.running.take().map(|appid| {
+            let _ = self.apps.enter(appid, |app, _| {
+                if result == ReturnCode::SUCCESS {
+                    self.arena.map(|arena| {
+                        app.output.as_mut().map(|output| {
+                            let len = cmp::min(output_len, output.len());
+                            for (byte, &value) in output.as_mut()[..len]
+                                .iter_mut()
+                                .zip(arena[output_start..output_start + len].iter())
+                            {
+                                *byte = value as u8;
+                            }
+                        });
+                    });
+                }
+                app.callback
+                    .map(|mut callback| callback.schedule(usize::from(result), handle, output_len));
+            });
+        });
+
+        for cntr in self.apps.iter() {
+            let started = cntr.enter(|app, _| {
+                let handle = match app.pending.take() {
+                    Some(handle) => handle,
+                    None => return false,
+                };
+                let appid = app.appid();
+                let result = match self.model(handle) {
+                    Some(model) => self.start(appid, app, handle, &model),
+                    None => ReturnCode::EINVAL,
+                };
+                if result != ReturnCode::SUCCESS {
+                    app.callback
+                        .map(|mut callback| callback.schedule(usize::from(result), handle, 0));
+                }
+                result == ReturnCode::SUCCESS
+            });
+            if started {
+                break;
+            }
+        }
+    }
+}
+
+impl DynamicDeferredCallClient for MlInference<'a> {
+    fn call(&self, _handle: DeferredCallHandle) {
+        if self.running.is_none() {
+            return;
+        }
+        match self.step() {
+            Ok(true) => self.finish(ReturnCode::SUCCESS),
+            Ok(false) => {
+                self.deferred_call_handle
+                    .map(|handle| self.deferred_caller.set(*handle));
+            }
+            Err(err) => self.finish(err),
+        }
+    }
+}
+
+impl Driver for MlInference<'a> {
+    /// Setup the input and output buffers.
+    ///
+    /// ### `allow_num`
+    ///
+    /// - `0`: The input of the model, as `i8`s.
+    /// - `1`: The buffer that the output of the model is copied into.
+    fn allow(
+        &self,
+        appid: AppId,
+        allow_num: usize,
+        slice: Option<AppSlice<Shared, u8>>,
+    ) -> ReturnCode {
+        match allow_num {
+            0 => self
+                .apps
+                .enter(appid, |app, _| {
+                    app.input = slice;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or_else(|err| err.into()),
+            1 => self
+                .apps
+                .enter(appid, |app, _| {
+                    app.output = slice;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or_else(|err| err.into()),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    /// Subscribe to the end of runs.
+    ///
+    /// ### `subscribe_num`
+    ///
+    /// - `0`: Set the callback called when a run ends, with the result, the
+    ///        handle of the model and the length of its output.
+    fn subscribe(
+        &self,
+        subscribe_num: usize,
+        callback: Option<Callback>,
+        appid: AppId,
+    ) -> ReturnCode {
+        match subscribe_num {
+            0 => self
+                .apps
+                .enter(appid, |app, _| {
+                    app.callback = callback;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or_else(|err| err.into()),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    /// Load and run models.
+    ///
+    /// ### `command_num`
+    ///
+    /// - `0`: Driver check.
+    /// - `1`: Load the model of `arg2` bytes at address `arg1` in the flash
+    ///        of the process, and return its handle.
+    /// - `2`: Unload model `arg1`.
+    /// - `3`: Return the handle of the loaded model with ID `arg1`.
+    /// - `4`: Return the input length of model `arg1`.
+    /// - `5`: Return the output length of model `arg1`.
+    /// - `6`: Run model `arg1` on the input buffer.
+    fn command(&self, command_num: usize, arg1: usize, arg2: usize, appid: AppId) -> ReturnCode {
+        match command_num {
+            0 => ReturnCode::SUCCESS,
+            1 => self.load(appid, arg1, arg2),
+            2 => self.unload(appid, arg1),
+            3 => (0..MAX_MODELS)
+                .find(|&handle| {
+                    self.model(handle)
+                        .map_or(false, |model| model.id == arg1 as u32)
+                })
+                .map_or(ReturnCode::EINVAL, |handle| ReturnCode::SuccessWithValue {
+                    value: handle,
+                }),
+            4 => {
+                self.model(arg1)
+                    .map_or(ReturnCode::EINVAL, |model| ReturnCode::SuccessWithValue {
+                        value: model.input_len,
+                    })
+            }
+            5 => {
+                self.model(arg1)
+                    .map_or(ReturnCode::EINVAL, |model| ReturnCode::SuccessWithValue {
+                        value: model.output_len,
+                    })
+            }
+            6 => self.run(appid, arg1),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+}
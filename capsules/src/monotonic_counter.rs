@@ -0,0 +1,528 @@
+//! Strictly monotonic counters persisted in flash, and a system call driver
+//! that allows processes to read and advance them.
+//!
+//! Like the monotonic counters of a TPM, a counter can only ever go up. This
+//! lets an update system enforce anti-rollback: it stores the security
+//! version of the running image in a counter, and refuses to install any
+//! image with a lower version, even one that is correctly signed.
+//!
+//! A counter's new value is returned only once it has been written to flash,
+//! so a value that has been reported can never be lost by a reset. Each update
+//! writes a record holding all of the counters, a generation number and a
+//! CRC to the next page of a ring of flash pages. The ring spreads wear across
+//! its pages, so a ring of `n` pages supports `n` times as many updates as the
+//! flash endurance of a single page. It also means that the previous record is
+//! never erased by an update: if power is lost while a page is written, the
+//! torn record fails its CRC check and the previous one is used. At boot,
+//! `initialize` reads every page of the ring and restores the valid record
+//! with the highest generation. If a page cannot be read the counters are not
+//! restored at all, rather than risk restoring old values.
+//!
+//! Counters can only be protected from rollback by this capsule as far as the
+//! flash they are stored in is protected. An attacker able to erase or write
+//! the ring directly, e.g. through a debug port, can reset them.
+//!
+//! Usage
+//! -----
+//!
+//! ```rust
+//! pub static mut PAGEBUFFER: nrf52::nvmc::NrfPage = nrf52::nvmc::NrfPage::new();
+//! let counters = static_init!(
+//!     capsules::monotonic_counter::FlashMonotonicCounters<'static, nrf52::nvmc::Nvmc>,
+//!     capsules::monotonic_counter::FlashMonotonicCounters::new(
+//!         &nrf52::nvmc::NVMC,
+//!         &mut PAGEBUFFER,
+//!         0x7e,    // First page of the ring
+//!         2        // Number of pages in the ring
+//!     )
+//! );
+//! hil::flash::HasClient::set_client(&nrf52::nvmc::NVMC, counters);
+//! let counter_driver = static_init!(
+//!     capsules::monotonic_counter::MonotonicCounterDriver<'static>,
+//!     capsules::monotonic_counter::MonotonicCounterDriver::new(counters, kernel::Grant::create())
+//! );
+//! counters.set_client(counter_driver);
+//! counters.initialize();
+//! ```
+
+use core::cell::Cell;
+use kernel::common::cells::{OptionalCell, TakeCell};
+use kernel::hil;
+use kernel::hil::crc::CrcAlg;
+use kernel::{AppId, AppSlice, Callback, Driver, Grant, ReturnCode, Shared};
+
+use crate::software_crc;
+
+/// Syscall driver number.
+use crate::driver;
+pub const DRIVER_NUM: usize = driver::NUM::MonotonicCounter as usize;
+
+pub const NUM_COUNTERS: usize = 4;
+
+/// Length of a counter value, as exchanged with processes.
+pub const COUNTER_LEN: usize = 8;
+
+/// Marks the start of a record ("MCTR")
+const MAGIC: u32 = 0x4d435452;
+
+/// Magic, generation, counters and CRC
+const RECORD_LEN: usize = 8 + NUM_COUNTERS * COUNTER_LEN + 4;
+
+/// Writes a record of `counters` to the start of `buf`.
+fn encode_record(buf: &mut [u8], generation: u32, counters: &[u64; NUM_COUNTERS]) {
+    buf[0..4].copy_from_slice(&MAGIC.to_le_bytes());
+    buf[4..8].copy_from_slice(&generation.to_le_bytes());
+    for (i, counter) in counters.iter().enumerate() {
+        let offset = 8 + i * COUNTER_LEN;
+        buf[offset..offset + COUNTER_LEN].copy_from_slice(&counter.to_le_bytes());
+    }
+    let crc = record_crc(&buf[..RECORD_LEN - 4]);
+    buf[RECORD_LEN - 4..RECORD_LEN].copy_from_slice(&crc.to_le_bytes());
+}
+
+/// Returns the generation and counters of the record at the start of `buf`,
+/// or `None` if there is no valid record.
+fn decode_record(buf: &[u8]) -> Option<(u32, [u64; NUM_COUNTERS])> {
+    let read_u32 = |offset: usize| {
+        let mut bytes = [0; 4];
+        bytes.copy_from_slice(&buf[offset..offset + 4]);
+        u32::from_le_bytes(bytes)
+    };
+    if read_u32(0) != MAGIC || read_u32(RECORD_LEN - 4) != record_crc(&buf[..RECORD_LEN - 4]) {
+        return None;
+    }
+    let mut counters = [0; NUM_COUNTERS];
+    for (i, counter) in counters.iter_mut().enumerate() {
+        let offset = 8 + i * COUNTER_LEN;
+        let mut bytes = [0; COUNTER_LEN];
+        bytes.copy_from_slice(&buf[offset..offset + COUNTER_LEN]);
+        *counter = u64::from_le_bytes(bytes);
+    }
+    Some((read_u32(4), counters))
+}
+
+fn record_crc(data: &[u8]) -> u32 {
+    CrcAlg::Crc32
+        .params()
+        .and_then(|params| software_crc::compute_crc(&params, data))
+        .unwrap_or(0)
+}
+
+/// A set of monotonic counters, for capsules such as an update system that
+/// enforce anti-rollback.
+pub trait MonotonicCounters<'a> {
+    fn set_client(&self, client: &'a MonotonicCounterClient);
+
+    fn num_counters(&self) -> usize;
+
+    /// Returns the value of counter `index`, or `None` if there is no such
+    /// counter or the counters have not been restored.
+    fn read(&self, index: usize) -> Option<u64>;
+
+    /// Increments counter `index` by one. If this returns `SUCCESS`, the
+    /// client's `counter_updated` is called once the new value is persistent.
+    /// Returns `EBUSY` if another update is in progress or the counters are
+    /// being restored, `FAIL` if they could not be restored, and `EINVAL` if
+    /// there is no such counter or it has reached its maximum value.
+    fn increment(&self, index: usize) -> ReturnCode;
+
+    /// Advances counter `index` to `value`. Returns `EINVAL` if `value` is
+    /// lower than the current value, as counters never go back, and
+    /// `EALREADY` if it is equal to it. Otherwise behaves like `increment`.
+    fn advance(&self, index: usize, value: u64) -> ReturnCode;
+}
+
+pub trait MonotonicCounterClient {
+    /// Called when an update of counter `index` completes. `value` is the value
+    /// of the counter, which is unchanged if `result` is not `SUCCESS`.
+    fn counter_updated(&self, index: usize, value: u64, result: ReturnCode);
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum State {
+    Uninitialized,
+    /// Reading the page at this offset in the ring
+    Loading(usize),
+    Idle,
+    /// Writing the page at this offset in the ring
+    Writing(usize),
+    /// The counters could not be restored
+    Failed,
+}
+
+pub struct FlashMonotonicCounters<'a, F: hil::flash::Flash + 'static> {
+    driver: &'a F,
+    client: OptionalCell<&'a MonotonicCounterClient>,
+    pagebuffer: TakeCell<'static, F::Page>,
+    first_page: usize,
+    num_pages: usize,
+    state: Cell<State>,
+    counters: [Cell<u64>; NUM_COUNTERS],
+    /// Generation of the newest record
+    generation: Cell<u32>,
+    /// Offset in the ring of the newest record, if there is one
+    newest: OptionalCell<usize>,
+    /// Counter index and value of the update being written
+    pending: OptionalCell<(usize, u64)>,
+}
+
+impl<F: hil::flash::Flash> FlashMonotonicCounters<'a, F> {
+    /// Creates counters stored in the `num_pages` flash pages starting at
+    /// `first_page`, which must not be used for anything else. At least two
+    /// pages are needed, so that the previous record is kept while a new one
+    /// is written.
+    pub fn new(
+        driver: &'a F,
+        pagebuffer: &'static mut F::Page,
+        first_page: usize,
+        num_pages: usize,
+    ) -> FlashMonotonicCounters<'a, F> {
+        assert!(num_pages >= 2);
+        assert!(pagebuffer.as_mut().len() >= RECORD_LEN);
+        FlashMonotonicCounters {
+            driver: driver,
+            client: OptionalCell::empty(),
+            pagebuffer: TakeCell::new(pagebuffer),
+            first_page: first_page,
+            num_pages: num_pages,
+            state: Cell::new(State::Uninitialized),
+            counters: Default::default(),
+            generation: Cell::new(0),
+            newest: OptionalCell::empty(),
+            pending: OptionalCell::empty(),
+        }
+    }
+
+    /// Restores the counters from flash. Until this completes the counters
+    /// cannot be read or updated.
+    pub fn initialize(&self) -> ReturnCode {
+        if self.state.get() != State::Uninitialized {
+            return ReturnCode::EALREADY;
+        }
+        self.pagebuffer
+            .take()
+            .map_or(ReturnCode::ERESERVE, |pagebuffer| {
+                self.state.set(State::Loading(0));
+                let result = self.driver.read_page(self.first_page, pagebuffer);
+                if result != ReturnCode::SUCCESS {
+                    self.state.set(State::Failed);
+                }
+                result
+            })
+    }
+
+    /// Returns whether counter `index` can be updated now.
+    fn check_ready(&self, index: usize) -> ReturnCode {
+        if index >= NUM_COUNTERS {
+            return ReturnCode::EINVAL;
+        }
+        match self.state.get() {
+            State::Idle => ReturnCode::SUCCESS,
+            State::Loading(_) | State::Writing(_) => ReturnCode::EBUSY,
+            State::Uninitialized | State::Failed => ReturnCode::FAIL,
+        }
+    }
+
+    /// Writes a record with counter `index` set to `value`.
+    fn update(&self, index: usize, value: u64) -> ReturnCode {
+        self.pagebuffer
+            .take()
+            .map_or(ReturnCode::ERESERVE, |pagebuffer| {
+                let mut counters = [0; NUM_COUNTERS];
+                for (counter, cell) in counters.iter_mut().zip(self.counters.iter()) {
+                    *counter = cell.get();
+                }
+                counters[index] = value;
+                encode_record(
+                    pagebuffer.as_mut(),
+                    self.generation.get().wrapping_add(1),
+                    &counters,
+                );
+
+                let offset = self
+                    .newest
+                    .map_or(0, |newest| (*newest + 1) % self.num_pages);
+                self.pending.set((index, value));
+                self.state.set(State::Writing(offset));
+                let result = self.driver.write_page(self.first_page + offset, pagebuffer);
+                if result != ReturnCode::SUCCESS {
+                    self.pending.take();
+                    self.state.set(State::Idle);
+                }
+                result
+            })
+    }
+}
+
+impl<F: hil::flash::Flash> MonotonicCounters<'a> for FlashMonotonicCounters<'a, F> {
+    fn set_client(&self, client: &'a MonotonicCounterClient) {
+        self.client.set(client);
+    }
+
+    fn num_counters(&self) -> usize {
+        NUM_COUNTERS
+    }
+
+    fn read(&self, index: usize) -> Option<u64> {
+        match self.state.get() {
+            State::Idle | State::Writing(_) => self.counters.get(index).map(|c| c.get()),
+            _ => None,
+        }
+    }
+
+    fn increment(&self, index: usize) -> ReturnCode {
+        let ready = self.check_ready(index);
+        if ready != ReturnCode::SUCCESS {
+            return ready;
+        }
+        self.counters[index]
+            .get()
+            .checked_add(1)
+            .map_or(ReturnCode::EINVAL, |value| self.update(index, value))
+    }
+
+    fn advance(&self, index: usize, value: u64) -> ReturnCode {
+        let ready = self.check_ready(index);
+        if ready != ReturnCode::SUCCESS {
+            return ready;
+        }
+        let current = self.counters[index].get();
+        if value < current {
+            ReturnCode::EINVAL
+        } else if value == current {
+            ReturnCode::EALREADY
+        } else {
+            self.update(index, value)
+        }
+    }
+}
+
+impl<F: hil::flash::Flash> hil::flash::Client<F> for FlashMonotonicCounters<'a, F> {
+    fn read_complete(&self, pagebuffer: &'static mut F::Page, error: hil::flash::Error) {
+        let offset = match self.state.get() {
+            State::Loading(offset) => offset,
+            _ => {
+                self.pagebuffer.replace(pagebuffer);
+                return;
+            }
+        };
+        if error != hil::flash::Error::CommandComplete {
+            self.pagebuffer.replace(pagebuffer);
+            self.state.set(State::Failed);
+            return;
+        }
+
+        decode_record(pagebuffer.as_mut()).map(|(generation, counters)| {
+            // Generations only wrap after 2^32 updates, more than any flash
+            // can endure
+            let newer = self
+                .newest
+                .map_or(true, |_| generation > self.generation.get());
+            if newer {
+                self.generation.set(generation);
+                self.newest.set(offset);
+                for (cell, counter) in self.counters.iter().zip(counters.iter()) {
+                    cell.set(*counter);
+                }
+            }
+        });
+
+        if offset + 1 < self.num_pages {
+            self.state.set(State::Loading(offset + 1));
+            self.driver
+                .read_page(self.first_page + offset + 1, pagebuffer);
+        } else {
+            // Pages without a valid record have never been written, so if
+            // none has one the counters start at zero
+            self.pagebuffer.replace(pagebuffer);
+            self.state.set(State::Idle);
+        }
+    }
+
+    fn write_complete(&self, pagebuffer: &'static mut F::Page, error: hil::flash::Error) {
+        self.pagebuffer.replace(pagebuffer);
+        let offset = match self.state.get() {
+            State::Writing(offset) => offset,
+            _ => return,
+        };
+        self.state.set(State::Idle);
+        self.pending.take().map(|(index, value)| {
+            let result = if error == hil::flash::Error::CommandComplete {
+                self.generation.set(self.generation.get().wrapping_add(1));
+                self.newest.set(offset);
+                self.counters[index].set(value);
+                ReturnCode::SUCCESS
+            } else {
+                ReturnCode::FAIL
+            };
+            let value = self.counters[index].get();
+            self.client
+                .map(|client| client.counter_updated(index, value, result));
+        });
+    }
+
+    fn erase_complete(&self, _error: hil::flash::Error) {}
+}
+
+#[derive(Default)]
+pub struct App {
+    callback: Option<Callback>,
+    buffer: Option<AppSlice<Shared, u8>>,
+}
+
+pub struct MonotonicCounterDriver<'a> {
+    counters: &'a MonotonicCounters<'a>,
+    apps: Grant<App>,
+    /// The process whose update is in progress
+    current_app: OptionalCell<AppId>,
+}
+
+impl MonotonicCounterDriver<'a> {
+    pub fn new(
+        counters: &'a MonotonicCounters<'a>,
+        apps: Grant<App>,
+    ) -> MonotonicCounterDriver<'a> {
+        MonotonicCounterDriver {
+            counters: counters,
+            apps: apps,
+            current_app: OptionalCell::empty(),
+        }
+    }
+
+    /// Starts an update of counter `index` on behalf of `appid`.
+    fn update(&self, appid: AppId, index: usize, value: Option<u64>) -> ReturnCode {
+        if self.current_app.is_some() {
+            return ReturnCode::EBUSY;
+        }
+        let result = match value {
+            Some(value) => self.counters.advance(index, value),
+            None => self.counters.increment(index),
+        };
+        if result == ReturnCode::SUCCESS {
+            self.current_app.set(appid);
+        }
+        result
+    }
+}
+
+impl MonotonicCounterClient for MonotonicCounterDriver<'a> {
+    fn counter_updated(&self, index: usize, value: u64, result: ReturnCode) {
+        self.current_app.take().map(|appid| {
+            let _ = self.apps.enter(appid, |app, _| {
+                app.callback.map(|mut callback| {
+                    callback.schedule(usize::from(result), index, value as usize)
+                });
+            });
+        });
+    }
+}
+
+impl Driver for MonotonicCounterDriver<'a> {
+    /// Setup the buffer that counter values are exchanged through.
+    ///
+    /// ### `allow_num`
+    ///
+    /// - `0`: The buffer that counter values are read into, and that the
+    ///        value to advance a counter to is taken from, as 8 byte little
+    ///        endian integers.
+    fn allow(
+        &self,
+        appid: AppId,
+        allow_num: usize,
+        slice: Option<AppSlice<Shared, u8>>,
+    ) -> ReturnCode {
+        match allow_num {
+            0 => self
+                .apps
+                .enter(appid, |app, _| {
+                    app.buffer = slice;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or_else(|err| err.into()),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    /// Subscribe to counter updates.
+    ///
+    /// ### `subscribe_num`
+    ///
+    /// - `0`: Set the callback called when an update completes, with the
+    ///        result, the counter index and the low 32 bits of its value.
+    fn subscribe(
+        &self,
+        subscribe_num: usize,
+        callback: Option<Callback>,
+        appid: AppId,
+    ) -> ReturnCode {
+        match subscribe_num {
+            0 => self
+                .apps
+                .enter(appid, |app, _| {
+                    app.callback = callback;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or_else(|err| err.into()),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    /// Read and advance the counters.
+    ///
+    /// ### `command_num`
+    ///
+    /// - `0`: Driver check.
+    /// - `1`: Returns the number of counters.
+    /// - `2`: Writes the value of counter `arg1` to the allowed buffer.
+    /// - `3`: Increments counter `arg1`.
+    /// - `4`: Advances counter `arg1` to the value in the allowed buffer.
+    fn command(&self, command_num: usize, arg1: usize, _: usize, appid: AppId) -> ReturnCode {
+        match command_num {
+            0 => ReturnCode::SUCCESS,
+            1 => ReturnCode::SuccessWithValue {
+                value: self.counters.num_counters(),
+            },
+            2 => {
+                let value = match self.counters.read(arg1) {
+                    Some(value) => value,
+                    None if arg1 < self.counters.num_counters() => return ReturnCode::EBUSY,
+                    None => return ReturnCode::EINVAL,
+                };
+                self.apps
+                    .enter(appid, |app, _| {
+                        app.buffer.as_mut().map_or(ReturnCode::ENOMEM, |buffer| {
+                            if buffer.len() < COUNTER_LEN {
+                                return ReturnCode::ESIZE;
+                            }
+                            buffer.as_mut()[..COUNTER_LEN].copy_from_slice(&value.to_le_bytes());
+                            ReturnCode::SUCCESS
+                        })
+                    })
+                    .unwrap_or_else(|err| err.into())
+            }
+            3 => self.update(appid, arg1, None),
+            4 => {
+                let value = self
+                    .apps
+                    .enter(appid, |app, _| {
+                        app.buffer
+                            .as_ref()
+                            .map_or(Err(ReturnCode::ENOMEM), |buffer| {
+                                if buffer.len() < COUNTER_LEN {
+                                    return Err(ReturnCode::ESIZE);
+                                }
+                                let mut bytes = [0; COUNTER_LEN];
+                                bytes.copy_from_slice(&buffer.as_ref()[..COUNTER_LEN]);
+                                Ok(u64::from_le_bytes(bytes))
+                            })
+                    })
+                    .unwrap_or_else(|err| Err(err.into()));
+                match value {
+                    Ok(value) => self.update(appid, arg1, Some(value)),
+                    Err(err) => err,
+                }
+            }
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+}
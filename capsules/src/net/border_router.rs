@@ -0,0 +1,351 @@
+//! Border router that bridges the IPv6 over 6LoWPAN stack to a host computer
+//! using SLIP (RFC 1055) over a UART.
+//!
+//! The border router sits on the IPv6 receive path, between the
+//! `IP6RecvStruct` and its upper layer (typically the RPL-lite routing layer
+//! or the UDP receiver). Packets received from the mesh that are destined for
+//! addresses outside of the mesh prefix are framed with SLIP and sent to the
+//! host, while all other packets are passed up to the client. IPv6 packets
+//! received from the host are delivered to the client if they are destined for
+//! this node, or are sent into the mesh if they are destined for another node
+//! within the mesh prefix.
+//!
+//! Any `UartData` device can be used as the link to the host, including a
+//! virtual UART shared with the console as long as nothing else reads from it,
+//! or a USB CDC serial port.
+//!
+//! On a Linux host, the link can be brought up with the standard tools, e.g.
+//! for a mesh prefix of `fd00::/64`:
+//!
+//! ```text
+//! slattach -s 115200 -p slip /dev/ttyACM0 &
+//! ip link set sl0 up
+//! ip -6 addr add fd00:1::1/64 dev sl0
+//! ip -6 route add fd00::/64 dev sl0
+//! ```
+//!
+//! Usage
+//! -----
+//!
+//! ```
+//! let border_router = static_init!(
+//!     capsules::net::border_router::BorderRouter<'static>,
+//!     capsules::net::border_router::BorderRouter::new(
+//!         slip_uart,
+//!         br_ip_send,
+//!         &LOCAL_IP_IFACES,
+//!         &mut capsules::net::border_router::TX_BUF,
+//!         &mut capsules::net::border_router::RX_BUF,
+//!         packet_pool
+//!     )
+//! );
+//! hil::uart::Transmit::set_transmit_client(slip_uart, border_router);
+//! hil::uart::Receive::set_receive_client(slip_uart, border_router);
+//! br_ip_send.set_client(border_router);
+//! br_ip_send.set_pool(packet_pool);
+//! br_ip_send.set_router(rpl);
+//! ip_receive.set_client(border_router);
+//! border_router.set_client(rpl);
+//! border_router.set_mesh_prefix(MESH_PREFIX, 64);
+//! border_router.start();
+//! ```
+
+use crate::net::buffer::{PacketBuffer, PacketPool};
+use crate::net::ipv6::ip_utils::IPAddr;
+use crate::net::ipv6::ipv6::{IP6Header, TransportHeader};
+use crate::net::ipv6::ipv6_recv::IP6RecvClient;
+use crate::net::ipv6::ipv6_send::{IP6SendClient, IP6Sender};
+use core::cell::Cell;
+use kernel::common::cells::{MapCell, OptionalCell, TakeCell};
+use kernel::hil::uart;
+use kernel::ReturnCode;
+
+/// Special characters used by SLIP framing
+mod slip {
+    pub const END: u8 = 0xc0;
+    pub const ESC: u8 = 0xdb;
+    pub const ESC_END: u8 = 0xdc;
+    pub const ESC_ESC: u8 = 0xdd;
+}
+
+/// Largest IPv6 packet that is bridged, which is the IPv6 minimum MTU
+pub const MAX_PACKET_LEN: usize = 1280;
+/// Size of the SLIP transmit buffer, which must be able to hold a packet in
+/// which every byte is escaped, plus the framing bytes
+pub const TX_BUF_LEN: usize = 2 * MAX_PACKET_LEN + 2;
+
+const IP6_HDR_LEN: usize = 40;
+
+pub static mut TX_BUF: [u8; TX_BUF_LEN] = [0; TX_BUF_LEN];
+pub static mut RX_BUF: [u8; 1] = [0; 1];
+
+pub struct BorderRouter<'a> {
+    uart: &'a uart::UartData<'a>,
+    ip_sender: &'a IP6Sender<'a>,
+    client: OptionalCell<&'a IP6RecvClient>,
+    local_addrs: &'static [IPAddr],
+    mesh_prefix: Cell<IPAddr>,
+    mesh_prefix_len: Cell<u8>,
+
+    tx_buf: TakeCell<'static, [u8]>,
+    // Incoming SLIP data is read one byte at a time, as frame boundaries are
+    // only known once the END character has been received
+    rx_buf: TakeCell<'static, [u8]>,
+    pool: &'a PacketPool<'a>,
+    // The frame being received from the host
+    rx_packet: MapCell<PacketBuffer>,
+    rx_escaped: Cell<bool>,
+    // Set when a frame from the host does not fit in a packet buffer, or no
+    // buffer is available, in which case the rest of the frame is discarded
+    rx_overflow: Cell<bool>,
+    // Whether a packet from the host is being sent into the mesh
+    sending: Cell<bool>,
+}
+
+impl BorderRouter<'a> {
+    pub fn new(
+        uart: &'a uart::UartData<'a>,
+        ip_sender: &'a IP6Sender<'a>,
+        local_addrs: &'static [IPAddr],
+        tx_buf: &'static mut [u8],
+        rx_buf: &'static mut [u8],
+        pool: &'a PacketPool<'a>,
+    ) -> BorderRouter<'a> {
+        BorderRouter {
+            uart: uart,
+            ip_sender: ip_sender,
+            client: OptionalCell::empty(),
+            local_addrs: local_addrs,
+            mesh_prefix: Cell::new(IPAddr::new()),
+            mesh_prefix_len: Cell::new(0),
+            tx_buf: TakeCell::new(tx_buf),
+            rx_buf: TakeCell::new(rx_buf),
+            pool: pool,
+            rx_packet: MapCell::empty(),
+            rx_escaped: Cell::new(false),
+            rx_overflow: Cell::new(false),
+            sending: Cell::new(false),
+        }
+    }
+
+    /// Sets the upper layer that receives packets destined for this node or
+    /// for other nodes in the mesh.
+    pub fn set_client(&self, client: &'a IP6RecvClient) {
+        self.client.set(client);
+    }
+
+    /// Sets the prefix that is routed to the mesh. Packets for addresses
+    /// outside of this prefix are sent to the host. Until a prefix is set,
+    /// every packet from the mesh is sent to the host.
+    pub fn set_mesh_prefix(&self, prefix: IPAddr, prefix_len: u8) {
+        self.mesh_prefix.set(prefix);
+        self.mesh_prefix_len.set(prefix_len);
+    }
+
+    /// Starts receiving packets from the host.
+    pub fn start(&self) -> ReturnCode {
+        self.rx_buf.take().map_or(ReturnCode::EALREADY, |rx_buf| {
+            let (result, rx_buf) = self.uart.receive_buffer(rx_buf, 1);
+            rx_buf.map(|rx_buf| self.rx_buf.replace(rx_buf));
+            result
+        })
+    }
+
+    fn is_local(&self, addr: IPAddr) -> bool {
+        self.local_addrs.iter().any(|&local| local == addr)
+    }
+
+    fn is_in_mesh(&self, addr: IPAddr) -> bool {
+        let prefix_len = self.mesh_prefix_len.get();
+        prefix_len != 0 && addr.matches_prefix(&self.mesh_prefix.get(), prefix_len)
+    }
+
+    /// Frames a packet with SLIP and sends it to the host. Packets are dropped
+    /// if a previous packet is still being sent.
+    fn send_to_host(&self, header: IP6Header, payload: &[u8]) {
+        self.tx_buf.take().map(|tx_buf| {
+            let mut hdr_buf = [0 as u8; IP6_HDR_LEN];
+            let _ = header.encode(&mut hdr_buf);
+
+            let mut len = 0;
+            tx_buf[len] = slip::END;
+            len += 1;
+            for &byte in hdr_buf.iter().chain(payload.iter()) {
+                if len + 3 > tx_buf.len() {
+                    // Too large to be sent
+                    self.tx_buf.replace(tx_buf);
+                    return;
+                }
+                match byte {
+                    slip::END => {
+                        tx_buf[len] = slip::ESC;
+                        tx_buf[len + 1] = slip::ESC_END;
+                        len += 2;
+                    }
+                    slip::ESC => {
+                        tx_buf[len] = slip::ESC;
+                        tx_buf[len + 1] = slip::ESC_ESC;
+                        len += 2;
+                    }
+                    _ => {
+                        tx_buf[len] = byte;
+                        len += 1;
+                    }
+                }
+            }
+            tx_buf[len] = slip::END;
+            len += 1;
+
+            let (_, tx_buf) = self.uart.transmit_buffer(tx_buf, len);
+            tx_buf.map(|tx_buf| self.tx_buf.replace(tx_buf));
+        });
+    }
+
+    /// Handles a single byte of SLIP data from the host.
+    fn receive_byte(&self, byte: u8) {
+        if byte == slip::END {
+            self.rx_packet.take().map(|packet| {
+                if !packet.is_empty() && !self.rx_overflow.get() {
+                    self.receive_from_host(packet);
+                } else {
+                    self.pool.free(packet);
+                }
+            });
+            self.rx_escaped.set(false);
+            self.rx_overflow.set(false);
+            return;
+        }
+        if byte == slip::ESC {
+            self.rx_escaped.set(true);
+            return;
+        }
+
+        let byte = if self.rx_escaped.get() {
+            self.rx_escaped.set(false);
+            match byte {
+                slip::ESC_END => slip::END,
+                slip::ESC_ESC => slip::ESC,
+                // Protocol violation, but RFC 1055 suggests keeping the byte
+                _ => byte,
+            }
+        } else {
+            byte
+        };
+        if self.rx_overflow.get() {
+            return;
+        }
+        if self.rx_packet.is_none() {
+            // Frames are received straight into a pool buffer, so that they
+            // can be sent into the mesh without being copied again
+            match self.pool.alloc(0) {
+                Some(packet) => self.rx_packet.put(packet),
+                None => {
+                    self.rx_overflow.set(true);
+                    return;
+                }
+            }
+        }
+        self.rx_packet.map(|packet| {
+            if packet.append(&[byte]) != ReturnCode::SUCCESS {
+                self.rx_overflow.set(true);
+            }
+        });
+    }
+
+    /// Handles a complete IPv6 packet received from the host.
+    fn receive_from_host(&self, mut packet: PacketBuffer) {
+        let header = IP6Header::decode(packet.data())
+            .done()
+            .map(|(_, header)| header)
+            .filter(|header| {
+                header.get_version() == 6
+                    && IP6_HDR_LEN + header.get_payload_len() as usize <= packet.len()
+            });
+        let mut header = match header {
+            Some(header) => header,
+            None => {
+                self.pool.free(packet);
+                return;
+            }
+        };
+        packet.truncate(IP6_HDR_LEN + header.get_payload_len() as usize);
+        packet.consume(IP6_HDR_LEN);
+
+        let dst_addr = header.get_dst_addr();
+        if dst_addr.is_multicast() || dst_addr.is_unicast_link_local() || self.is_local(dst_addr) {
+            self.client
+                .map(|client| client.receive(header, packet.data()));
+        } else if self.is_in_mesh(dst_addr) {
+            let hop_limit = header.get_hop_limit();
+            if hop_limit > 1 && !self.sending.get() {
+                header.set_hop_limit(hop_limit - 1);
+                let transport_header =
+                    TransportHeader::decode(header.get_next_header(), packet.data());
+                if let Some((hdr_size, transport_header)) = transport_header {
+                    packet.consume(hdr_size);
+                    let (result, unsent) =
+                        self.ip_sender.send_buffer(header, transport_header, packet);
+                    if result == ReturnCode::SUCCESS {
+                        self.sending.set(true);
+                    }
+                    unsent.map(|packet| self.pool.free(packet));
+                    return;
+                }
+            }
+        }
+        // Packets for other destinations would be routed straight back to
+        // the host, so they are dropped
+        self.pool.free(packet);
+    }
+}
+
+impl IP6RecvClient for BorderRouter<'a> {
+    fn receive(&self, header: IP6Header, payload: &[u8]) {
+        let dst_addr = header.get_dst_addr();
+        if dst_addr.is_multicast()
+            || dst_addr.is_unicast_link_local()
+            || self.is_local(dst_addr)
+            || self.is_in_mesh(dst_addr)
+        {
+            self.client.map(|client| client.receive(header, payload));
+        } else {
+            let hop_limit = header.get_hop_limit();
+            if hop_limit > 1 {
+                let mut header = header;
+                header.set_hop_limit(hop_limit - 1);
+                self.send_to_host(header, payload);
+            }
+        }
+    }
+}
+
+impl IP6SendClient for BorderRouter<'a> {
+    fn send_done(&self, _result: ReturnCode) {
+        self.sending.set(false);
+    }
+}
+
+impl uart::TransmitClient for BorderRouter<'a> {
+    fn transmitted_buffer(&self, tx_buffer: &'static mut [u8], _tx_len: usize, _rval: ReturnCode) {
+        self.tx_buf.replace(tx_buffer);
+    }
+}
+
+impl uart::ReceiveClient for BorderRouter<'a> {
+    fn received_buffer(
+        &self,
+        rx_buffer: &'static mut [u8],
+        rx_len: usize,
+        rval: ReturnCode,
+        error: uart::Error,
+    ) {
+        if rval == ReturnCode::SUCCESS && error == uart::Error::None && rx_len == 1 {
+            self.receive_byte(rx_buffer[0]);
+        } else {
+            // Discard the frame in progress
+            self.rx_overflow.set(true);
+        }
+        let (_, rx_buf) = self.uart.receive_buffer(rx_buffer, 1);
+        rx_buf.map(|rx_buf| self.rx_buf.replace(rx_buf));
+    }
+}
@@ -0,0 +1,196 @@
+//! Shared packet buffers for the networking stack.
+//!
+//! Rather than every layer of the stack owning a static buffer that each
+//! packet is copied into, layers can pass a [PacketBuffer](struct.PacketBuffer.html)
+//! down (or up) the stack by value. A `PacketBuffer` wraps a static buffer and
+//! tracks the region of it holding valid data. Space can be reserved in front
+//! of the data (headroom) so that lower layers can prepend their headers in
+//! place, and layers can strip their headers from received packets with
+//! `consume` without moving the remaining data.
+//!
+//! Buffers are allocated from, and returned to, a
+//! [PacketPool](struct.PacketPool.html) which is shared by all layers, so the
+//! stack only needs enough buffers for the packets that are actually in
+//! flight at the same time.
+//!
+//! Usage
+//! -----
+//!
+//! ```
+//! static mut PACKET_BUF0: [u8; 1280] = [0; 1280];
+//! static mut PACKET_BUF1: [u8; 1280] = [0; 1280];
+//!
+//! let pool_bufs = static_init!(
+//!     [TakeCell<'static, [u8]>; 2],
+//!     [TakeCell::new(&mut PACKET_BUF0), TakeCell::new(&mut PACKET_BUF1)]
+//! );
+//! let pool = static_init!(
+//!     capsules::net::buffer::PacketPool<'static>,
+//!     capsules::net::buffer::PacketPool::new(pool_bufs)
+//! );
+//! ip_send.set_pool(pool);
+//! ```
+
+use kernel::common::cells::TakeCell;
+use kernel::ReturnCode;
+
+/// A static buffer holding a single packet, with headroom reserved in front
+/// of the packet data.
+pub struct PacketBuffer {
+    buf: &'static mut [u8],
+    // The packet data is buf[head..tail]
+    head: usize,
+    tail: usize,
+}
+
+impl PacketBuffer {
+    /// Creates an empty packet buffer, reserving `headroom` bytes at the
+    /// start of `buf` for headers that are prepended later.
+    pub fn new(buf: &'static mut [u8], headroom: usize) -> PacketBuffer {
+        let head = if headroom < buf.len() {
+            headroom
+        } else {
+            buf.len()
+        };
+        PacketBuffer {
+            buf: buf,
+            head: head,
+            tail: head,
+        }
+    }
+
+    /// Empties the buffer and reserves `headroom` bytes in front of the data.
+    pub fn reset(&mut self, headroom: usize) {
+        let head = if headroom < self.buf.len() {
+            headroom
+        } else {
+            self.buf.len()
+        };
+        self.head = head;
+        self.tail = head;
+    }
+
+    /// Returns the number of bytes available in front of the data.
+    pub fn headroom(&self) -> usize {
+        self.head
+    }
+
+    /// Returns the number of bytes available after the data.
+    pub fn tailroom(&self) -> usize {
+        self.buf.len() - self.tail
+    }
+
+    /// Returns the length of the packet data.
+    pub fn len(&self) -> usize {
+        self.tail - self.head
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.head == self.tail
+    }
+
+    /// Returns the packet data.
+    pub fn data(&self) -> &[u8] {
+        &self.buf[self.head..self.tail]
+    }
+
+    /// Returns the packet data for modification in place.
+    pub fn data_mut(&mut self) -> &mut [u8] {
+        &mut self.buf[self.head..self.tail]
+    }
+
+    /// Grows the data by `len` bytes at the front, returning the new bytes so
+    /// that a header can be written into them. Returns `None` if there is not
+    /// enough headroom.
+    pub fn prepend(&mut self, len: usize) -> Option<&mut [u8]> {
+        if len > self.head {
+            return None;
+        }
+        self.head -= len;
+        Some(&mut self.buf[self.head..self.head + len])
+    }
+
+    /// Grows the data by `len` bytes at the end, returning the new bytes so
+    /// that they can be written directly. Returns `None` if there is not
+    /// enough tailroom.
+    pub fn extend(&mut self, len: usize) -> Option<&mut [u8]> {
+        if len > self.tailroom() {
+            return None;
+        }
+        self.tail += len;
+        Some(&mut self.buf[self.tail - len..self.tail])
+    }
+
+    /// Copies `data` to the end of the packet data. Returns ESIZE if there is
+    /// not enough tailroom.
+    pub fn append(&mut self, data: &[u8]) -> ReturnCode {
+        match self.extend(data.len()) {
+            Some(dst) => {
+                dst.copy_from_slice(data);
+                ReturnCode::SUCCESS
+            }
+            None => ReturnCode::ESIZE,
+        }
+    }
+
+    /// Removes `len` bytes from the front of the data, e.g. once a header has
+    /// been decoded. The removed bytes become headroom. Returns ESIZE if the
+    /// data is shorter than `len`.
+    pub fn consume(&mut self, len: usize) -> ReturnCode {
+        if len > self.len() {
+            return ReturnCode::ESIZE;
+        }
+        self.head += len;
+        ReturnCode::SUCCESS
+    }
+
+    /// Shortens the data to `len` bytes, dropping any trailing bytes.
+    pub fn truncate(&mut self, len: usize) {
+        if len < self.len() {
+            self.tail = self.head + len;
+        }
+    }
+
+    /// Returns the underlying static buffer, discarding the packet state.
+    pub fn into_inner(self) -> &'static mut [u8] {
+        self.buf
+    }
+}
+
+/// A fixed set of packet buffers shared by the layers of the networking
+/// stack.
+pub struct PacketPool<'a> {
+    buffers: &'a [TakeCell<'static, [u8]>],
+}
+
+impl PacketPool<'a> {
+    pub fn new(buffers: &'a [TakeCell<'static, [u8]>]) -> PacketPool<'a> {
+        PacketPool { buffers: buffers }
+    }
+
+    /// Takes an empty buffer from the pool, reserving `headroom` bytes in
+    /// front of the data. Returns `None` if every buffer is in use.
+    pub fn alloc(&self, headroom: usize) -> Option<PacketBuffer> {
+        self.buffers
+            .iter()
+            .filter_map(|slot| slot.take())
+            .next()
+            .map(|buf| PacketBuffer::new(buf, headroom))
+    }
+
+    /// Returns a buffer to the pool.
+    pub fn free(&self, buffer: PacketBuffer) {
+        let mut buf = Some(buffer.into_inner());
+        for slot in self.buffers.iter() {
+            if slot.is_none() {
+                slot.put(buf.take());
+                break;
+            }
+        }
+    }
+
+    /// Returns the number of buffers that are currently available.
+    pub fn available(&self) -> usize {
+        self.buffers.iter().filter(|slot| slot.is_some()).count()
+    }
+}
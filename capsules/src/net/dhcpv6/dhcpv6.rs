@@ -0,0 +1,593 @@
+//! A stateful DHCPv6 client (RFC 8415), which allows a node to obtain a
+//! global address, a delegated prefix and DNS configuration from a DHCPv6
+//! server in a managed network.
+//!
+//! The client runs the four message exchange to obtain a lease:
+//!
+//! - It multicasts a Solicit, retransmitting it with exponential backoff
+//!   until a server answers with an Advertise offering a binding. Advertises
+//!   are collected until the first retransmission timeout, and the one with
+//!   the highest preference is chosen (an Advertise with the maximum
+//!   preference is chosen immediately).
+//! - It then sends a Request to the chosen server, and the server commits the
+//!   binding with a Reply.
+//!
+//! Once the lease is obtained, the client sends a Renew to the server after
+//! T1 seconds, and multicasts a Rebind to any server after T2 seconds if the
+//! server did not answer. If the lease reaches the end of its valid lifetime
+//! without being extended, the client reports that the lease expired and
+//! starts soliciting again.
+//!
+//! The client always requests an address (IA_NA), and can also request a
+//! delegated prefix (IA_PD), e.g. for a border router. Only a single address
+//! and a single prefix are supported. Since there is no synchronous source of
+//! randomness available, transaction IDs are derived from the alarm and
+//! retransmission timeouts are not randomized.
+//!
+//! Usage
+//! -----
+//!
+//! `DHCP6Client` sits between the UDP receive path and its upper layer, and
+//! passes all datagrams that are not addressed to the DHCPv6 client port
+//! through. It sends its messages with its own `UDPSendStruct` and
+//! `IP6SendStruct`, which must use a link-local source address.
+//!
+//! ```
+//! let dhcp = static_init!(
+//!     capsules::net::dhcpv6::dhcpv6::DHCP6Client<
+//!         'static,
+//!         VirtualMuxAlarm<'static, sam4l::ast::Ast>,
+//!     >,
+//!     capsules::net::dhcpv6::dhcpv6::DHCP6Client::new(
+//!         dhcp_virtual_alarm,
+//!         dhcp_udp_send,
+//!         eui64
+//!     )
+//! );
+//! dhcp_virtual_alarm.set_client(dhcp);
+//! dhcp_ip_send.set_addr(link_local_addr);
+//! udp_recv.set_client(dhcp);
+//! dhcp.set_recv_client(udp_driver);
+//! dhcp.set_client(net_config);
+//! dhcp.start();
+//! ```
+
+use crate::net::dhcpv6::dhcpv6_msg::{self, dhcp_opt, msg_type, status};
+use crate::net::dhcpv6::dhcpv6_msg::{DHCP6Message, IAAddress, IAPrefix};
+use crate::net::dhcpv6::dhcpv6_msg::{MAX_DNS_SERVERS, MAX_DUID_LEN};
+use crate::net::ipv6::ip_utils::IPAddr;
+use crate::net::stream::SResult;
+use crate::net::udp::udp_recv::UDPRecvClient;
+use crate::net::udp::udp_send::UDPSender;
+use core::cell::Cell;
+use core::cmp;
+use kernel::common::cells::OptionalCell;
+use kernel::hil::time::{self, Frequency};
+use kernel::ReturnCode;
+
+// Retransmission parameters (RFC 8415, section 7.6), in seconds
+const SOL_TIMEOUT_S: u32 = 1;
+const SOL_MAX_RT_S: u32 = 3600;
+const REQ_TIMEOUT_S: u32 = 1;
+const REQ_MAX_RT_S: u32 = 30;
+const REQ_MAX_RC: u8 = 10;
+const REN_TIMEOUT_S: u32 = 10;
+const REN_MAX_RT_S: u32 = 600;
+const REB_TIMEOUT_S: u32 = 10;
+const REB_MAX_RT_S: u32 = 600;
+
+// The alarm is never set more than this many seconds ahead, so that the
+// number of tics fits in a u32. Longer waits (e.g. until T1) are split into
+// several alarms.
+const MAX_ALARM_S: u32 = 60;
+
+// An Advertise with this preference is chosen without waiting for others
+const MAX_PREFERENCE: u8 = 255;
+
+// Identifier of the single IA_NA and IA_PD requested by the client
+const IAID: u32 = 1;
+
+// Largest message this implementation sends: a Request carrying a server
+// identifier, an IA_NA with an address and an IA_PD with a prefix.
+const MAX_MSG_LEN: usize = dhcpv6_msg::HEADER_LEN
+    // Client identifier, server identifier, elapsed time, option request,
+    // IA_NA, IA Address, IA_PD and IA Prefix options
+    + 8 * dhcpv6_msg::OPT_HEADER_LEN
+    + dhcpv6_msg::DUID_LL_LEN
+    + MAX_DUID_LEN
+    + 2
+    + 2
+    + 2 * dhcpv6_msg::IA_LEN
+    + dhcpv6_msg::IAADDR_LEN
+    + dhcpv6_msg::IAPREFIX_LEN;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum DHCP6State {
+    /// The client has not been started
+    Idle,
+    /// Looking for a server, by multicasting Solicit messages
+    Soliciting,
+    /// Requesting the binding offered by the chosen server
+    Requesting,
+    /// A lease has been obtained
+    Bound,
+    /// Extending the lease with the server that granted it
+    Renewing,
+    /// Extending the lease with any server
+    Rebinding,
+}
+
+/// The configuration obtained from a DHCPv6 server.
+#[derive(Copy, Clone, Debug)]
+pub struct DHCP6Lease {
+    pub address: Option<IAAddress>,
+    pub prefix: Option<IAPrefix>,
+    dns_servers: [IPAddr; MAX_DNS_SERVERS],
+    num_dns_servers: usize,
+    /// Seconds after the lease was obtained at which it is renewed
+    pub t1: u32,
+    /// Seconds after the lease was obtained at which it is rebound
+    pub t2: u32,
+}
+
+impl DHCP6Lease {
+    /// Returns the DNS servers sent by the server, if any.
+    pub fn dns_servers(&self) -> &[IPAddr] {
+        &self.dns_servers[..self.num_dns_servers]
+    }
+
+    /// Returns the number of seconds after which the lease is no longer
+    /// valid, which is the shortest valid lifetime of its address and
+    /// prefix.
+    pub fn valid_lifetime(&self) -> u32 {
+        let address = self.address.map_or(u32::max_value(), |a| a.valid_lifetime);
+        let prefix = self.prefix.map_or(u32::max_value(), |p| p.valid_lifetime);
+        cmp::min(address, prefix)
+    }
+
+    fn preferred_lifetime(&self) -> u32 {
+        let address = self
+            .address
+            .map_or(u32::max_value(), |a| a.preferred_lifetime);
+        let prefix = self
+            .prefix
+            .map_or(u32::max_value(), |p| p.preferred_lifetime);
+        cmp::min(address, prefix)
+    }
+}
+
+/// The upper layer that configures the node with the leases obtained by the
+/// `DHCP6Client` implements this trait.
+pub trait DHCP6LeaseClient {
+    /// Called when a lease is obtained, and every time it is extended.
+    fn lease_obtained(&self, lease: &DHCP6Lease);
+
+    /// Called when the lease reaches the end of its valid lifetime without
+    /// being extended. The address and prefix of the lease must no longer
+    /// be used.
+    fn lease_expired(&self, lease: &DHCP6Lease);
+}
+
+pub struct DHCP6Client<'a, A: time::Alarm> {
+    alarm: &'a A,
+    udp_sender: &'a UDPSender<'a>,
+    client: OptionalCell<&'a DHCP6LeaseClient>,
+    recv_client: OptionalCell<&'a UDPRecvClient>,
+    duid: [u8; dhcpv6_msg::DUID_LL_LEN],
+    request_prefix: Cell<bool>,
+
+    state: Cell<DHCP6State>,
+    // Current message exchange
+    xid: Cell<u32>,
+    exchange_start: Cell<u32>,
+    retransmit_timeout_s: Cell<u32>,
+    retransmit_count: Cell<u8>,
+    // The best Advertise received while soliciting, and then the last message
+    // from the server the lease was obtained from
+    server: OptionalCell<DHCP6Message>,
+
+    lease: OptionalCell<DHCP6Lease>,
+    lease_elapsed_s: Cell<u32>,
+    // Number of seconds until the alarm fires
+    alarm_interval_s: Cell<u32>,
+}
+
+impl<A: time::Alarm> DHCP6Client<'a, A> {
+    /// Creates a client which identifies itself to servers with a DUID
+    /// built from the EUI-64 of the node.
+    pub fn new(alarm: &'a A, udp_sender: &'a UDPSender<'a>, eui64: [u8; 8]) -> DHCP6Client<'a, A> {
+        DHCP6Client {
+            alarm: alarm,
+            udp_sender: udp_sender,
+            client: OptionalCell::empty(),
+            recv_client: OptionalCell::empty(),
+            duid: dhcpv6_msg::duid_from_eui64(&eui64),
+            request_prefix: Cell::new(false),
+            state: Cell::new(DHCP6State::Idle),
+            xid: Cell::new(0),
+            exchange_start: Cell::new(0),
+            retransmit_timeout_s: Cell::new(0),
+            retransmit_count: Cell::new(0),
+            server: OptionalCell::empty(),
+            lease: OptionalCell::empty(),
+            lease_elapsed_s: Cell::new(0),
+            alarm_interval_s: Cell::new(0),
+        }
+    }
+
+    /// Sets the upper layer that is notified about leases.
+    pub fn set_client(&self, client: &'a DHCP6LeaseClient) {
+        self.client.set(client);
+    }
+
+    /// Sets the upper layer that receives the UDP datagrams that are not
+    /// addressed to the DHCPv6 client.
+    pub fn set_recv_client(&self, client: &'a UDPRecvClient) {
+        self.recv_client.set(client);
+    }
+
+    /// Sets whether the client also requests a delegated prefix. This only
+    /// takes effect for leases obtained after it is called.
+    pub fn set_request_prefix(&self, request_prefix: bool) {
+        self.request_prefix.set(request_prefix);
+    }
+
+    /// Starts looking for a DHCPv6 server. Returns `EALREADY` if the client
+    /// is already running.
+    pub fn start(&self) -> ReturnCode {
+        if self.state.get() != DHCP6State::Idle {
+            return ReturnCode::EALREADY;
+        }
+        self.begin_solicit();
+        ReturnCode::SUCCESS
+    }
+
+    /// Stops the client and forgets the current lease, without releasing it
+    /// to the server.
+    pub fn stop(&self) {
+        self.alarm.disable();
+        self.state.set(DHCP6State::Idle);
+        self.server.clear();
+        self.lease.clear();
+    }
+
+    pub fn get_state(&self) -> DHCP6State {
+        self.state.get()
+    }
+
+    /// Returns the current lease, if any.
+    pub fn get_lease(&self) -> Option<DHCP6Lease> {
+        self.lease.map(|lease| *lease)
+    }
+
+    fn seconds_to_tics(seconds: u32) -> u32 {
+        seconds * A::Frequency::frequency()
+    }
+
+    fn set_timer(&self, seconds: u32) {
+        let seconds = cmp::min(cmp::max(seconds, 1), MAX_ALARM_S);
+        self.alarm_interval_s.set(seconds);
+        let tics = self
+            .alarm
+            .now()
+            .wrapping_add(Self::seconds_to_tics(seconds));
+        self.alarm.set_alarm(tics);
+    }
+
+    /// Picks a new transaction ID. This is not cryptographically random, but
+    /// only needs to be unlikely to repeat.
+    fn next_xid(&self) -> u32 {
+        let mixed = self.xid.get().wrapping_mul(1103515245).wrapping_add(12345) ^ self.alarm.now();
+        mixed & 0x00ff_ffff
+    }
+
+    /// Returns the time since the start of the current exchange, in
+    /// hundredths of a second.
+    fn elapsed_time(&self) -> u16 {
+        let tics = self.alarm.now().wrapping_sub(self.exchange_start.get()) as u64;
+        let centiseconds = tics * 100 / A::Frequency::frequency() as u64;
+        cmp::min(centiseconds, 0xffff) as u16
+    }
+
+    fn begin_solicit(&self) {
+        self.server.clear();
+        self.begin_exchange(DHCP6State::Soliciting, SOL_TIMEOUT_S);
+    }
+
+    fn begin_exchange(&self, state: DHCP6State, timeout_s: u32) {
+        self.state.set(state);
+        self.xid.set(self.next_xid());
+        self.exchange_start.set(self.alarm.now());
+        self.retransmit_timeout_s.set(timeout_s);
+        self.retransmit_count.set(0);
+        self.transmit();
+    }
+
+    fn retransmit(&self, max_rt_s: u32) {
+        let timeout = self.retransmit_timeout_s.get().saturating_mul(2);
+        self.retransmit_timeout_s.set(cmp::min(timeout, max_rt_s));
+        self.transmit();
+    }
+
+    /// Sends the message of the current exchange and waits for the
+    /// retransmission timeout, or until the lease needs to move to the next
+    /// state. Send failures are handled by retransmitting.
+    fn transmit(&self) {
+        let message_type = match self.state.get() {
+            DHCP6State::Soliciting => msg_type::SOLICIT,
+            DHCP6State::Requesting => msg_type::REQUEST,
+            DHCP6State::Renewing => msg_type::RENEW,
+            DHCP6State::Rebinding => msg_type::REBIND,
+            DHCP6State::Idle | DHCP6State::Bound => return,
+        };
+        self.send_message(message_type);
+        self.retransmit_count
+            .set(self.retransmit_count.get().saturating_add(1));
+
+        let mut timeout = self.retransmit_timeout_s.get();
+        self.lease.map(|lease| {
+            let deadline = match self.state.get() {
+                DHCP6State::Renewing => lease.t2,
+                _ => lease.valid_lifetime(),
+            };
+            let remaining = deadline.saturating_sub(self.lease_elapsed_s.get());
+            timeout = cmp::min(timeout, remaining);
+        });
+        self.set_timer(timeout);
+    }
+
+    fn send_message(&self, message_type: u8) -> ReturnCode {
+        // The Request carries the binding offered by the chosen server, while
+        // the Renew and Rebind carry the binding of the lease
+        let (address, prefix) = match message_type {
+            msg_type::REQUEST => self
+                .server
+                .map(|server| self.offered_binding(server))
+                .unwrap_or((None, None)),
+            msg_type::RENEW | msg_type::REBIND => self
+                .lease
+                .map(|lease| (lease.address, lease.prefix))
+                .unwrap_or((None, None)),
+            _ => (None, None),
+        };
+        let request_prefix = match message_type {
+            msg_type::SOLICIT => self.request_prefix.get(),
+            _ => prefix.is_some(),
+        };
+        // Only the Request and Renew are addressed to a specific server
+        let server = match message_type {
+            msg_type::REQUEST | msg_type::RENEW => match self.server.map(|server| *server) {
+                Some(server) => Some(server),
+                None => return ReturnCode::FAIL,
+            },
+            _ => None,
+        };
+
+        let mut buf = [0 as u8; MAX_MSG_LEN];
+        let len = self.encode_message(
+            &mut buf,
+            message_type,
+            server.as_ref().map(|server| server.server_id()),
+            address.as_ref(),
+            prefix.as_ref(),
+            request_prefix,
+        );
+        match len.done() {
+            Some((len, _)) => self.udp_sender.send_to(
+                dhcpv6_msg::ALL_DHCP_SERVERS,
+                dhcpv6_msg::SERVER_PORT,
+                dhcpv6_msg::CLIENT_PORT,
+                &buf[..len],
+            ),
+            None => ReturnCode::ESIZE,
+        }
+    }
+
+    fn encode_message(
+        &self,
+        buf: &mut [u8],
+        message_type: u8,
+        server_id: Option<&[u8]>,
+        address: Option<&IAAddress>,
+        prefix: Option<&IAPrefix>,
+        request_prefix: bool,
+    ) -> SResult<usize> {
+        let mut off = enc_consume!(buf, 0; dhcpv6_msg::encode_header, message_type, self.xid.get());
+        off = enc_consume!(buf, off; dhcpv6_msg::encode_option, dhcp_opt::CLIENTID, &self.duid);
+        if let Some(server_id) = server_id {
+            off = enc_consume!(buf, off; dhcpv6_msg::encode_option, dhcp_opt::SERVERID, server_id);
+        }
+        off = enc_consume!(buf, off; dhcpv6_msg::encode_elapsed_time, self.elapsed_time());
+        off = enc_consume!(buf, off; dhcpv6_msg::encode_oro);
+        // Solicit an address even though none is known yet
+        if message_type == msg_type::SOLICIT || address.is_some() {
+            off = enc_consume!(buf, off; dhcpv6_msg::encode_ia_na, IAID, address);
+        }
+        if request_prefix {
+            off = enc_consume!(buf, off; dhcpv6_msg::encode_ia_pd, IAID, prefix);
+        }
+        stream_done!(off, off);
+    }
+
+    /// Returns the address and prefix bound to the client in a message from
+    /// a server, if the client asked for them.
+    fn offered_binding(&self, msg: &DHCP6Message) -> (Option<IAAddress>, Option<IAPrefix>) {
+        let address = msg
+            .ia_na
+            .filter(|ia| ia.iaid == IAID && ia.status == status::SUCCESS)
+            .and_then(|ia| ia.address)
+            .filter(|address| address.valid_lifetime > 0);
+        let prefix = msg
+            .ia_pd
+            .filter(|ia| ia.iaid == IAID && ia.status == status::SUCCESS)
+            .and_then(|ia| ia.prefix)
+            .filter(|prefix| prefix.valid_lifetime > 0);
+        (address, prefix)
+    }
+
+    fn receive_advertise(&self, msg: DHCP6Message) {
+        if msg.status != status::SUCCESS || msg.server_id_len == 0 {
+            return;
+        }
+        match self.offered_binding(&msg) {
+            (None, None) => return,
+            _ => {}
+        }
+        let better = self
+            .server
+            .map_or(true, |server| msg.preference > server.preference);
+        if better {
+            self.server.set(msg);
+        }
+        if msg.preference == MAX_PREFERENCE {
+            self.begin_exchange(DHCP6State::Requesting, REQ_TIMEOUT_S);
+        }
+    }
+
+    fn receive_reply(&self, msg: DHCP6Message) {
+        if msg.status != status::SUCCESS {
+            // The server could not process the message; keep retransmitting
+            // and let the timers decide when to give up
+            return;
+        }
+        let (address, prefix) = self.offered_binding(&msg);
+        if address.is_none() && prefix.is_none() {
+            // The server has no binding for the client (any more)
+            if let Some(lease) = self.lease.take() {
+                self.client.map(|client| client.lease_expired(&lease));
+            }
+            self.begin_solicit();
+            return;
+        }
+
+        let mut lease = DHCP6Lease {
+            address: address,
+            prefix: prefix,
+            dns_servers: msg.dns_servers,
+            num_dns_servers: msg.num_dns_servers,
+            t1: 0,
+            t2: 0,
+        };
+        // Take the renewal times from the IA that was granted, or choose them
+        // if the server left them to the client
+        let ia = if address.is_some() {
+            msg.ia_na
+        } else {
+            msg.ia_pd
+        };
+        let preferred = lease.preferred_lifetime();
+        let (t1, t2) = ia.map_or((0, 0), |ia| (ia.t1, ia.t2));
+        lease.t1 = if t1 == 0 { preferred / 2 } else { t1 };
+        lease.t2 = if t2 == 0 {
+            (preferred / 5).saturating_mul(4)
+        } else {
+            t2
+        };
+        lease.t2 = cmp::max(lease.t1, lease.t2);
+
+        self.server.set(msg);
+        self.lease.set(lease);
+        self.lease_elapsed_s.set(0);
+        self.state.set(DHCP6State::Bound);
+        self.set_timer(lease.t1);
+        self.client.map(|client| client.lease_obtained(&lease));
+    }
+
+    fn lease_timer_fired(&self) {
+        let lease = match self.lease.map(|lease| *lease) {
+            Some(lease) => lease,
+            None => {
+                self.begin_solicit();
+                return;
+            }
+        };
+        let elapsed = self.lease_elapsed_s.get();
+        if elapsed >= lease.valid_lifetime() {
+            self.lease.clear();
+            self.client.map(|client| client.lease_expired(&lease));
+            self.begin_solicit();
+            return;
+        }
+        match self.state.get() {
+            DHCP6State::Bound => {
+                if elapsed >= lease.t1 {
+                    self.begin_exchange(DHCP6State::Renewing, REN_TIMEOUT_S);
+                } else {
+                    self.set_timer(lease.t1 - elapsed);
+                }
+            }
+            DHCP6State::Renewing => {
+                if elapsed >= lease.t2 {
+                    self.begin_exchange(DHCP6State::Rebinding, REB_TIMEOUT_S);
+                } else {
+                    self.retransmit(REN_MAX_RT_S);
+                }
+            }
+            DHCP6State::Rebinding => self.retransmit(REB_MAX_RT_S),
+            _ => {}
+        }
+    }
+}
+
+impl<A: time::Alarm> time::Client for DHCP6Client<'a, A> {
+    fn fired(&self) {
+        if self.lease.is_some() {
+            self.lease_elapsed_s.set(
+                self.lease_elapsed_s
+                    .get()
+                    .saturating_add(self.alarm_interval_s.get()),
+            );
+        }
+        match self.state.get() {
+            DHCP6State::Idle => {}
+            DHCP6State::Soliciting => {
+                if self.server.is_some() {
+                    self.begin_exchange(DHCP6State::Requesting, REQ_TIMEOUT_S);
+                } else {
+                    self.retransmit(SOL_MAX_RT_S);
+                }
+            }
+            DHCP6State::Requesting => {
+                if self.retransmit_count.get() >= REQ_MAX_RC {
+                    self.begin_solicit();
+                } else {
+                    self.retransmit(REQ_MAX_RT_S);
+                }
+            }
+            DHCP6State::Bound | DHCP6State::Renewing | DHCP6State::Rebinding => {
+                self.lease_timer_fired();
+            }
+        }
+    }
+}
+
+impl<A: time::Alarm> UDPRecvClient for DHCP6Client<'a, A> {
+    fn receive(
+        &self,
+        src_addr: IPAddr,
+        dst_addr: IPAddr,
+        src_port: u16,
+        dst_port: u16,
+        payload: &[u8],
+    ) {
+        if dst_port != dhcpv6_msg::CLIENT_PORT {
+            self.recv_client
+                .map(|client| client.receive(src_addr, dst_addr, src_port, dst_port, payload));
+            return;
+        }
+        let msg = match DHCP6Message::decode(payload, &self.duid).done() {
+            Some((_, msg)) => msg,
+            None => return,
+        };
+        if msg.xid != self.xid.get() || !msg.client_id_matches {
+            return;
+        }
+        match (self.state.get(), msg.msg_type) {
+            (DHCP6State::Soliciting, msg_type::ADVERTISE) => self.receive_advertise(msg),
+            (DHCP6State::Requesting, msg_type::REPLY)
+            | (DHCP6State::Renewing, msg_type::REPLY)
+            | (DHCP6State::Rebinding, msg_type::REPLY) => self.receive_reply(msg),
+            _ => {}
+        }
+    }
+}
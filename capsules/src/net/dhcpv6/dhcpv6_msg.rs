@@ -0,0 +1,385 @@
+//! This file contains the structs and methods used to encode and decode the
+//! subset of DHCPv6 (RFC 8415) messages used by the DHCPv6 client.
+//!
+//! Every DHCPv6 message starts with a one octet message type and a three
+//! octet transaction ID, followed by a sequence of options. Each option
+//! starts with a two octet option code and a two octet option length, and
+//! some options (IA_NA and IA_PD) carry further options of their own.
+//!
+//! Only the options needed to obtain a single address, a single delegated
+//! prefix and a list of DNS servers are understood. All other options in
+//! received messages are skipped.
+
+use crate::net::ipv6::ip_utils::IPAddr;
+use crate::net::stream::SResult;
+use crate::net::stream::{decode_bytes, decode_u16, decode_u32, decode_u8};
+use crate::net::stream::{encode_bytes, encode_u16, encode_u32, encode_u8};
+
+/// UDP port that DHCPv6 clients listen on
+pub const CLIENT_PORT: u16 = 546;
+/// UDP port that DHCPv6 servers and relay agents listen on
+pub const SERVER_PORT: u16 = 547;
+
+/// The All_DHCP_Relay_Agents_and_Servers link-local multicast address,
+/// ff02::1:2
+pub const ALL_DHCP_SERVERS: IPAddr = IPAddr([
+    0xff, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x02,
+]);
+
+/// Message types of the supported DHCPv6 messages
+pub mod msg_type {
+    pub const SOLICIT: u8 = 1;
+    pub const ADVERTISE: u8 = 2;
+    pub const REQUEST: u8 = 3;
+    pub const RENEW: u8 = 5;
+    pub const REBIND: u8 = 6;
+    pub const REPLY: u8 = 7;
+}
+
+/// Option codes of the supported DHCPv6 options
+pub mod dhcp_opt {
+    pub const CLIENTID: u16 = 1;
+    pub const SERVERID: u16 = 2;
+    pub const IA_NA: u16 = 3;
+    pub const IAADDR: u16 = 5;
+    pub const ORO: u16 = 6;
+    pub const PREFERENCE: u16 = 7;
+    pub const ELAPSED_TIME: u16 = 8;
+    pub const STATUS_CODE: u16 = 13;
+    pub const DNS_SERVERS: u16 = 23;
+    pub const IA_PD: u16 = 25;
+    pub const IAPREFIX: u16 = 26;
+}
+
+/// Status codes carried in Status Code options
+pub mod status {
+    pub const SUCCESS: u16 = 0;
+    pub const UNSPEC_FAIL: u16 = 1;
+    pub const NO_ADDRS_AVAIL: u16 = 2;
+    pub const NO_BINDING: u16 = 3;
+    pub const NOT_ON_LINK: u16 = 4;
+    pub const USE_MULTICAST: u16 = 5;
+    pub const NO_PREFIX_AVAIL: u16 = 6;
+}
+
+/// DUID type 3: link-layer address
+pub const DUID_LL: u16 = 3;
+/// Hardware type of EUI-64 addresses, as used by IEEE 802.15.4
+pub const HW_TYPE_EUI64: u16 = 27;
+/// Length of a DUID-LL built from an EUI-64
+pub const DUID_LL_LEN: usize = 12;
+/// Longest DUID allowed, including the DUID type
+pub const MAX_DUID_LEN: usize = 130;
+/// Maximum number of DNS servers kept from a reply
+pub const MAX_DNS_SERVERS: usize = 2;
+
+/// Length of the message type and transaction ID
+pub const HEADER_LEN: usize = 4;
+/// Length of an option code and option length
+pub const OPT_HEADER_LEN: usize = 4;
+/// Length of the fixed part of an IA_NA or IA_PD option
+pub const IA_LEN: usize = 12;
+/// Length of the data of an IA Address option without sub-options
+pub const IAADDR_LEN: usize = 24;
+/// Length of the data of an IA Prefix option without sub-options
+pub const IAPREFIX_LEN: usize = 25;
+
+/// Builds a DUID-LL from the EUI-64 of the node.
+pub fn duid_from_eui64(eui64: &[u8; 8]) -> [u8; DUID_LL_LEN] {
+    let mut duid = [0 as u8; DUID_LL_LEN];
+    duid[0..2].copy_from_slice(&DUID_LL.to_be_bytes());
+    duid[2..4].copy_from_slice(&HW_TYPE_EUI64.to_be_bytes());
+    duid[4..].copy_from_slice(eui64);
+    duid
+}
+
+/// Serializes the message type and transaction ID. Only the lower 24 bits
+/// of `xid` are used.
+pub fn encode_header(buf: &mut [u8], msg_type: u8, xid: u32) -> SResult<usize> {
+    stream_len_cond!(buf, HEADER_LEN);
+
+    let mut off = enc_consume!(buf, 0; encode_u8, msg_type);
+    off = enc_consume!(buf, off; encode_u8, (xid >> 16) as u8);
+    off = enc_consume!(buf, off; encode_u16, xid as u16);
+    stream_done!(off, off);
+}
+
+/// Serializes an option with the given code and data.
+pub fn encode_option(buf: &mut [u8], code: u16, data: &[u8]) -> SResult<usize> {
+    stream_len_cond!(buf, OPT_HEADER_LEN + data.len());
+
+    let mut off = enc_consume!(buf, 0; encode_u16, code);
+    off = enc_consume!(buf, off; encode_u16, data.len() as u16);
+    off = enc_consume!(buf, off; encode_bytes, data);
+    stream_done!(off, off);
+}
+
+/// Serializes an Elapsed Time option. `elapsed` is in hundredths of a
+/// second.
+pub fn encode_elapsed_time(buf: &mut [u8], elapsed: u16) -> SResult<usize> {
+    encode_option(buf, dhcp_opt::ELAPSED_TIME, &elapsed.to_be_bytes())
+}
+
+/// Serializes an Option Request option asking for the DNS servers.
+pub fn encode_oro(buf: &mut [u8]) -> SResult<usize> {
+    encode_option(buf, dhcp_opt::ORO, &dhcp_opt::DNS_SERVERS.to_be_bytes())
+}
+
+/// Serializes an IA_NA option, including an IA Address option for
+/// `address` if it is provided. The T1 and T2 times are left to the server.
+pub fn encode_ia_na(buf: &mut [u8], iaid: u32, address: Option<&IAAddress>) -> SResult<usize> {
+    let inner_len = address.map_or(0, |_| OPT_HEADER_LEN + IAADDR_LEN);
+    stream_len_cond!(buf, OPT_HEADER_LEN + IA_LEN + inner_len);
+
+    let mut off = enc_consume!(buf, 0; encode_u16, dhcp_opt::IA_NA);
+    off = enc_consume!(buf, off; encode_u16, (IA_LEN + inner_len) as u16);
+    off = enc_consume!(buf, off; encode_u32, iaid);
+    // T1 and T2
+    off = enc_consume!(buf, off; encode_u32, 0);
+    off = enc_consume!(buf, off; encode_u32, 0);
+    if let Some(address) = address {
+        off = enc_consume!(buf, off; encode_u16, dhcp_opt::IAADDR);
+        off = enc_consume!(buf, off; encode_u16, IAADDR_LEN as u16);
+        off = enc_consume!(buf, off; encode_bytes, &address.addr.0);
+        off = enc_consume!(buf, off; encode_u32, address.preferred_lifetime);
+        off = enc_consume!(buf, off; encode_u32, address.valid_lifetime);
+    }
+    stream_done!(off, off);
+}
+
+/// Serializes an IA_PD option, including an IA Prefix option for `prefix`
+/// if it is provided. The T1 and T2 times are left to the server.
+pub fn encode_ia_pd(buf: &mut [u8], iaid: u32, prefix: Option<&IAPrefix>) -> SResult<usize> {
+    let inner_len = prefix.map_or(0, |_| OPT_HEADER_LEN + IAPREFIX_LEN);
+    stream_len_cond!(buf, OPT_HEADER_LEN + IA_LEN + inner_len);
+
+    let mut off = enc_consume!(buf, 0; encode_u16, dhcp_opt::IA_PD);
+    off = enc_consume!(buf, off; encode_u16, (IA_LEN + inner_len) as u16);
+    off = enc_consume!(buf, off; encode_u32, iaid);
+    // T1 and T2
+    off = enc_consume!(buf, off; encode_u32, 0);
+    off = enc_consume!(buf, off; encode_u32, 0);
+    if let Some(prefix) = prefix {
+        off = enc_consume!(buf, off; encode_u16, dhcp_opt::IAPREFIX);
+        off = enc_consume!(buf, off; encode_u16, IAPREFIX_LEN as u16);
+        off = enc_consume!(buf, off; encode_u32, prefix.preferred_lifetime);
+        off = enc_consume!(buf, off; encode_u32, prefix.valid_lifetime);
+        off = enc_consume!(buf, off; encode_u8, prefix.prefix_len);
+        off = enc_consume!(buf, off; encode_bytes, &prefix.prefix.0);
+    }
+    stream_done!(off, off);
+}
+
+/// Deserializes the header of the option at the start of `buf`, returning
+/// the option code and the length of the option data, which must fit in the
+/// buffer.
+fn decode_option_header(buf: &[u8]) -> SResult<(u16, usize)> {
+    let (off, code) = dec_try!(buf, 0; decode_u16);
+    let (off, len) = dec_try!(buf, off; decode_u16);
+    stream_len_cond!(buf, off + len as usize);
+    stream_done!(off, (code, len as usize));
+}
+
+/// Deserializes a Status Code option, ignoring the status message.
+fn decode_status(data: &[u8]) -> SResult<u16> {
+    let (off, code) = dec_try!(data, 0; decode_u16);
+    stream_done!(off, code);
+}
+
+/// An address leased to the client.
+#[derive(Copy, Clone, Debug)]
+pub struct IAAddress {
+    pub addr: IPAddr,
+    /// Preferred lifetime in seconds
+    pub preferred_lifetime: u32,
+    /// Valid lifetime in seconds
+    pub valid_lifetime: u32,
+}
+
+impl IAAddress {
+    fn decode(data: &[u8]) -> SResult<IAAddress> {
+        stream_len_cond!(data, IAADDR_LEN);
+
+        let mut addr = IPAddr::new();
+        let off = dec_consume!(data, 0; decode_bytes, &mut addr.0);
+        let (off, preferred_lifetime) = dec_try!(data, off; decode_u32);
+        let (off, valid_lifetime) = dec_try!(data, off; decode_u32);
+        stream_done!(
+            off,
+            IAAddress {
+                addr: addr,
+                preferred_lifetime: preferred_lifetime,
+                valid_lifetime: valid_lifetime,
+            }
+        );
+    }
+}
+
+/// A prefix delegated to the client.
+#[derive(Copy, Clone, Debug)]
+pub struct IAPrefix {
+    pub prefix: IPAddr,
+    pub prefix_len: u8,
+    /// Preferred lifetime in seconds
+    pub preferred_lifetime: u32,
+    /// Valid lifetime in seconds
+    pub valid_lifetime: u32,
+}
+
+impl IAPrefix {
+    fn decode(data: &[u8]) -> SResult<IAPrefix> {
+        stream_len_cond!(data, IAPREFIX_LEN);
+
+        let (off, preferred_lifetime) = dec_try!(data, 0; decode_u32);
+        let (off, valid_lifetime) = dec_try!(data, off; decode_u32);
+        let (off, prefix_len) = dec_try!(data, off; decode_u8);
+        let mut prefix = IPAddr::new();
+        let off = dec_consume!(data, off; decode_bytes, &mut prefix.0);
+        stream_done!(
+            off,
+            IAPrefix {
+                prefix: prefix,
+                prefix_len: prefix_len,
+                preferred_lifetime: preferred_lifetime,
+                valid_lifetime: valid_lifetime,
+            }
+        );
+    }
+}
+
+/// The contents of an IA_NA or IA_PD option. Only the first address or
+/// prefix in the IA is kept.
+#[derive(Copy, Clone, Debug)]
+pub struct IA {
+    pub iaid: u32,
+    /// Time in seconds after which the client should renew the lease
+    pub t1: u32,
+    /// Time in seconds after which the client should rebind the lease
+    pub t2: u32,
+    pub status: u16,
+    pub address: Option<IAAddress>,
+    pub prefix: Option<IAPrefix>,
+}
+
+impl IA {
+    fn decode(data: &[u8]) -> SResult<IA> {
+        let (off, iaid) = dec_try!(data, 0; decode_u32);
+        let (off, t1) = dec_try!(data, off; decode_u32);
+        let (mut off, t2) = dec_try!(data, off; decode_u32);
+        let mut ia = IA {
+            iaid: iaid,
+            t1: t1,
+            t2: t2,
+            status: status::SUCCESS,
+            address: None,
+            prefix: None,
+        };
+        while off < data.len() {
+            let (data_off, (code, len)) = dec_try!(data, off; decode_option_header);
+            let opt_data = &data[data_off..data_off + len];
+            match code {
+                dhcp_opt::STATUS_CODE => {
+                    let (_, code) = dec_try!(decode_status(opt_data));
+                    ia.status = code;
+                }
+                dhcp_opt::IAADDR if ia.address.is_none() => {
+                    let (_, address) = dec_try!(IAAddress::decode(opt_data));
+                    ia.address = Some(address);
+                }
+                dhcp_opt::IAPREFIX if ia.prefix.is_none() => {
+                    let (_, prefix) = dec_try!(IAPrefix::decode(opt_data));
+                    ia.prefix = Some(prefix);
+                }
+                _ => {}
+            }
+            off = data_off + len;
+        }
+        stream_done!(off, ia);
+    }
+}
+
+/// A received Advertise or Reply message.
+#[derive(Copy, Clone)]
+pub struct DHCP6Message {
+    pub msg_type: u8,
+    pub xid: u32,
+    pub server_id: [u8; MAX_DUID_LEN],
+    pub server_id_len: usize,
+    /// Whether the message carried a Client Identifier option matching the
+    /// DUID passed to `decode`
+    pub client_id_matches: bool,
+    pub preference: u8,
+    pub status: u16,
+    pub ia_na: Option<IA>,
+    pub ia_pd: Option<IA>,
+    pub dns_servers: [IPAddr; MAX_DNS_SERVERS],
+    pub num_dns_servers: usize,
+}
+
+impl DHCP6Message {
+    /// Returns the DUID of the server that sent this message.
+    pub fn server_id(&self) -> &[u8] {
+        &self.server_id[..self.server_id_len]
+    }
+
+    /// Deserializes a message sent by a server to the client with the
+    /// given DUID.
+    pub fn decode(buf: &[u8], client_duid: &[u8]) -> SResult<DHCP6Message> {
+        let (off, msg_type) = dec_try!(buf, 0; decode_u8);
+        let (off, xid_high) = dec_try!(buf, off; decode_u8);
+        let (mut off, xid_low) = dec_try!(buf, off; decode_u16);
+        let mut msg = DHCP6Message {
+            msg_type: msg_type,
+            xid: (xid_high as u32) << 16 | xid_low as u32,
+            server_id: [0; MAX_DUID_LEN],
+            server_id_len: 0,
+            client_id_matches: false,
+            preference: 0,
+            status: status::SUCCESS,
+            ia_na: None,
+            ia_pd: None,
+            dns_servers: [IPAddr::new(); MAX_DNS_SERVERS],
+            num_dns_servers: 0,
+        };
+        while off < buf.len() {
+            let (data_off, (code, len)) = dec_try!(buf, off; decode_option_header);
+            let opt_data = &buf[data_off..data_off + len];
+            match code {
+                dhcp_opt::CLIENTID => msg.client_id_matches = opt_data == client_duid,
+                dhcp_opt::SERVERID => {
+                    stream_cond!(len > 0 && len <= MAX_DUID_LEN);
+                    msg.server_id[..len].copy_from_slice(opt_data);
+                    msg.server_id_len = len;
+                }
+                dhcp_opt::PREFERENCE => {
+                    let (_, preference) = dec_try!(decode_u8(opt_data));
+                    msg.preference = preference;
+                }
+                dhcp_opt::STATUS_CODE => {
+                    let (_, code) = dec_try!(decode_status(opt_data));
+                    msg.status = code;
+                }
+                dhcp_opt::IA_NA if msg.ia_na.is_none() => {
+                    let (_, ia) = dec_try!(IA::decode(opt_data));
+                    msg.ia_na = Some(ia);
+                }
+                dhcp_opt::IA_PD if msg.ia_pd.is_none() => {
+                    let (_, ia) = dec_try!(IA::decode(opt_data));
+                    msg.ia_pd = Some(ia);
+                }
+                dhcp_opt::DNS_SERVERS => {
+                    let room = MAX_DNS_SERVERS - msg.num_dns_servers;
+                    for server in opt_data.chunks_exact(16).take(room) {
+                        msg.dns_servers[msg.num_dns_servers]
+                            .0
+                            .copy_from_slice(server);
+                        msg.num_dns_servers += 1;
+                    }
+                }
+                _ => {}
+            }
+            off = data_off + len;
+        }
+        stream_done!(off, msg);
+    }
+}
@@ -0,0 +1,2 @@
+pub mod dhcpv6;
+pub mod dhcpv6_msg;
@@ -0,0 +1,253 @@
+//! IPv6 over Ethernet (RFC 2464).
+//!
+//! `EthernetIP6` connects the IPv6 layer to an Ethernet MAC, in place of the
+//! 6LoWPAN layer and 802.15.4 radio. It implements `IP6Sender`, so that UDP
+//! and ICMPv6 can send through it, and passes received IPv6 packets to an
+//! `IP6RecvStruct` (through the `SixlowpanRxClient` interface, which takes
+//! complete IPv6 packets).
+//!
+//! Neighbor discovery is not implemented. Packets to multicast addresses are
+//! sent to the corresponding Ethernet multicast address, and all other
+//! packets to the gateway MAC address, which is the broadcast address until
+//! `set_gateway_mac` is called. Since IPv6 packets on Ethernet are not
+//! fragmented, packets can be at most as large as the transmit buffer allows.
+//!
+//! Usage
+//! -----
+//!
+//! ```
+//! let ip_send = static_init!(
+//!     capsules::net::ethernet::EthernetIP6<'static>,
+//!     capsules::net::ethernet::EthernetIP6::new(
+//!         liteeth,
+//!         ip6_dg,
+//!         &mut capsules::net::ethernet::TX_BUF
+//!     )
+//! );
+//! hil::ethernet::Ethernet::set_transmit_client(liteeth, ip_send);
+//! hil::ethernet::Ethernet::set_receive_client(liteeth, ip_send);
+//! ip_send.set_receive_client(ip_receive);
+//! ip_send.set_addr(local_ip_ifaces[0]);
+//! ```
+
+use crate::net::buffer::{PacketBuffer, PacketPool};
+use crate::net::ieee802154::MacAddress;
+use crate::net::ipv6::ip_utils::IPAddr;
+use crate::net::ipv6::ipv6::{IP6Header, IP6Packet, TransportHeader};
+use crate::net::ipv6::ipv6_send::{IP6SendClient, IP6Sender};
+use crate::net::sixlowpan::sixlowpan_state::SixlowpanRxClient;
+use core::cell::Cell;
+use kernel::common::cells::{OptionalCell, TakeCell};
+use kernel::hil::ethernet;
+use kernel::ReturnCode;
+
+/// EtherType of IPv6
+const ETHERTYPE_IPV6: u16 = 0x86DD;
+
+/// Size of a transmit buffer that holds any IPv6 packet of the minimum MTU
+pub const TX_BUF_LEN: usize = ethernet::HEADER_LEN + 1280;
+
+pub static mut TX_BUF: [u8; TX_BUF_LEN] = [0; TX_BUF_LEN];
+
+pub struct EthernetIP6<'a> {
+    mac: &'a ethernet::Ethernet<'a>,
+    ip6_packet: TakeCell<'static, IP6Packet<'static>>,
+    tx_buf: TakeCell<'static, [u8]>,
+    src_addr: Cell<IPAddr>,
+    gateway: Cell<[u8; 6]>,
+    client: OptionalCell<&'a IP6SendClient>,
+    rx_client: OptionalCell<&'a SixlowpanRxClient>,
+    pool: OptionalCell<&'a PacketPool<'a>>,
+}
+
+impl EthernetIP6<'a> {
+    pub fn new(
+        mac: &'a ethernet::Ethernet<'a>,
+        ip6_packet: &'static mut IP6Packet<'static>,
+        tx_buf: &'static mut [u8],
+    ) -> EthernetIP6<'a> {
+        EthernetIP6 {
+            mac: mac,
+            ip6_packet: TakeCell::new(ip6_packet),
+            tx_buf: TakeCell::new(tx_buf),
+            src_addr: Cell::new(IPAddr::new()),
+            gateway: Cell::new(ethernet::BROADCAST),
+            client: OptionalCell::empty(),
+            rx_client: OptionalCell::empty(),
+            pool: OptionalCell::empty(),
+        }
+    }
+
+    /// Sets the layer that received IPv6 packets are passed to, usually an
+    /// `IP6RecvStruct`.
+    pub fn set_receive_client(&self, client: &'a SixlowpanRxClient) {
+        self.rx_client.set(client);
+    }
+
+    /// Sets the MAC address that packets to unicast addresses are sent to.
+    pub fn set_gateway_mac(&self, gateway: [u8; 6]) {
+        self.gateway.set(gateway);
+    }
+
+    /// Sets the pool that buffers passed to `send_buffer` are returned to.
+    /// `send_buffer` is only supported once a pool has been set.
+    pub fn set_pool(&self, pool: &'a PacketPool<'a>) {
+        self.pool.set(pool);
+    }
+
+    fn dst_mac_addr(&self, dst: IPAddr) -> [u8; 6] {
+        if dst.is_multicast() {
+            // RFC 2464, section 7
+            [0x33, 0x33, dst.0[12], dst.0[13], dst.0[14], dst.0[15]]
+        } else {
+            self.gateway.get()
+        }
+    }
+
+    /// Encodes the IPv6 packet into an Ethernet frame and transmits it.
+    fn send_packet(&self) -> ReturnCode {
+        let tx_buf = match self.tx_buf.take() {
+            Some(tx_buf) => tx_buf,
+            None => return ReturnCode::EBUSY,
+        };
+        let encoded = self.ip6_packet.map_or(None, |ip6_packet| {
+            let total_len = ip6_packet.get_total_len() as usize;
+            if ethernet::HEADER_LEN + total_len > tx_buf.len() {
+                return None;
+            }
+            let dst_mac = self.dst_mac_addr(ip6_packet.header.get_dst_addr());
+            tx_buf[0..6].copy_from_slice(&dst_mac);
+            tx_buf[6..12].copy_from_slice(&self.mac.mac_address());
+            tx_buf[12] = (ETHERTYPE_IPV6 >> 8) as u8;
+            tx_buf[13] = ETHERTYPE_IPV6 as u8;
+            ip6_packet
+                .encode(&mut tx_buf[ethernet::HEADER_LEN..])
+                .done()
+                .map(|(len, _)| ethernet::HEADER_LEN + len)
+        });
+        match encoded {
+            Some(len) => {
+                let (result, tx_buf) = self.mac.transmit(tx_buf, len);
+                tx_buf.map(|tx_buf| self.tx_buf.replace(tx_buf));
+                result
+            }
+            None => {
+                self.tx_buf.replace(tx_buf);
+                ReturnCode::ESIZE
+            }
+        }
+    }
+}
+
+impl IP6Sender<'a> for EthernetIP6<'a> {
+    fn set_client(&self, client: &'a IP6SendClient) {
+        self.client.set(client);
+    }
+
+    fn set_addr(&self, src_addr: IPAddr) {
+        self.src_addr.set(src_addr);
+    }
+
+    /// 802.15.4 addresses have no meaning on Ethernet, see `set_gateway_mac`.
+    fn set_gateway(&self, _gateway: MacAddress) {}
+
+    fn set_header(&mut self, ip6_header: IP6Header) {
+        self.ip6_packet
+            .map(|ip6_packet| ip6_packet.header = ip6_header);
+    }
+
+    fn send_to(
+        &self,
+        dst: IPAddr,
+        transport_header: TransportHeader,
+        payload: &[u8],
+    ) -> ReturnCode {
+        if self.tx_buf.is_none() {
+            return ReturnCode::EBUSY;
+        }
+        self.ip6_packet.map(|ip6_packet| {
+            ip6_packet.header = IP6Header::default();
+            ip6_packet.header.src_addr = self.src_addr.get();
+            ip6_packet.header.dst_addr = dst;
+            ip6_packet.set_payload(transport_header, payload);
+            ip6_packet.set_transport_checksum();
+        });
+        self.send_packet()
+    }
+
+    fn send_with_header(
+        &self,
+        ip6_header: IP6Header,
+        transport_header: TransportHeader,
+        payload: &[u8],
+    ) -> ReturnCode {
+        if self.tx_buf.is_none() {
+            return ReturnCode::EBUSY;
+        }
+        self.ip6_packet.map(|ip6_packet| {
+            ip6_packet.header = ip6_header;
+            ip6_packet.set_payload(transport_header, payload);
+        });
+        self.send_packet()
+    }
+
+    fn send_buffer(
+        &self,
+        ip6_header: IP6Header,
+        transport_header: TransportHeader,
+        buf: PacketBuffer,
+    ) -> (ReturnCode, Option<PacketBuffer>) {
+        if self.pool.is_none() {
+            return (ReturnCode::ENOSUPPORT, Some(buf));
+        }
+        if self.tx_buf.is_none() {
+            return (ReturnCode::EBUSY, Some(buf));
+        }
+        // The packet is copied into the frame, so the buffer can go back to
+        // the pool right away
+        self.ip6_packet.map(|ip6_packet| {
+            ip6_packet.header = ip6_header;
+            ip6_packet.set_payload(transport_header, buf.data());
+            if ip6_packet.header.get_src_addr().is_unspecified() {
+                ip6_packet.header.src_addr = self.src_addr.get();
+                ip6_packet.set_transport_checksum();
+            }
+        });
+        let result = self.send_packet();
+        if result != ReturnCode::SUCCESS {
+            return (result, Some(buf));
+        }
+        self.pool.map(move |pool| pool.free(buf));
+        (result, None)
+    }
+}
+
+impl ethernet::TxClient for EthernetIP6<'a> {
+    fn transmit_done(&self, frame: &'static mut [u8], result: ReturnCode) {
+        self.tx_buf.replace(frame);
+        self.client.map(|client| client.send_done(result));
+    }
+}
+
+impl ethernet::RxClient for EthernetIP6<'a> {
+    fn received_frame(&self, frame: &[u8]) {
+        if frame.len() <= ethernet::HEADER_LEN {
+            return;
+        }
+        let ethertype = (frame[12] as u16) << 8 | frame[13] as u16;
+        if ethertype != ETHERTYPE_IPV6 {
+            return;
+        }
+        let packet = &frame[ethernet::HEADER_LEN..];
+        // Frames are padded to the minimum Ethernet frame size, so the
+        // length of the packet comes from its header
+        let len = IP6Header::decode(packet)
+            .done()
+            .map_or(0, |(_, header)| header.get_total_len() as usize);
+        if len == 0 || len > packet.len() {
+            return;
+        }
+        self.rx_client
+            .map(|client| client.receive(packet, len, ReturnCode::SUCCESS));
+    }
+}
@@ -7,9 +7,10 @@
 //!
 //! - Author: Conor McAvity <cmcavity@stanford.edu>
 
+use crate::net::buffer::PacketBuffer;
 use crate::net::icmpv6::icmpv6::ICMP6Header;
 use crate::net::ipv6::ip_utils::IPAddr;
-use crate::net::ipv6::ipv6::TransportHeader;
+use crate::net::ipv6::ipv6::{IP6Header, TransportHeader};
 use crate::net::ipv6::ipv6_send::{IP6SendClient, IP6Sender};
 use kernel::common::cells::OptionalCell;
 use kernel::ReturnCode;
@@ -45,7 +46,29 @@ pub trait ICMP6Sender<'a> {
     /// This function returns a code reporting either success or any
     /// synchronous errors. Note that any asynchronous errors are returned
     /// via the callback.
-    fn send(&self, dest: IPAddr, icmp_header: ICMP6Header, buf: &'a [u8]) -> ReturnCode;
+    fn send(&self, dest: IPAddr, icmp_header: ICMP6Header, buf: &[u8]) -> ReturnCode;
+
+    /// Constructs and sends an IP packet from provided ICMPv6 header and
+    /// the payload held in a `PacketBuffer`, without copying the payload
+    /// into the buffer of the IP layer.
+    ///
+    /// # Arguments
+    ///
+    /// `dest` - The destination IP address
+    /// `icmp_header` - The ICMPv6 header to be sent
+    /// `buf` - The buffer containing the ICMPv6 payload
+    ///
+    /// # Return Value
+    ///
+    /// As for `IP6Sender::send_buffer`: once the packet is sent, the buffer
+    /// is returned to the `PacketPool` of the IP layer. If it is not sent,
+    /// the buffer is returned along with the error.
+    fn send_buffer(
+        &self,
+        dest: IPAddr,
+        icmp_header: ICMP6Header,
+        buf: PacketBuffer,
+    ) -> (ReturnCode, Option<PacketBuffer>);
 }
 
 /// A struct that implements the `ICMP6Sender` trait.
@@ -68,12 +91,26 @@ impl<T: IP6Sender<'a>> ICMP6Sender<'a> for ICMP6SendStruct<'a, T> {
         self.client.set(client);
     }
 
-    fn send(&self, dest: IPAddr, mut icmp_header: ICMP6Header, buf: &'a [u8]) -> ReturnCode {
+    fn send(&self, dest: IPAddr, mut icmp_header: ICMP6Header, buf: &[u8]) -> ReturnCode {
         let total_len = buf.len() + icmp_header.get_hdr_size();
         icmp_header.set_len(total_len as u16);
         let transport_header = TransportHeader::ICMP(icmp_header);
         self.ip_send_struct.send_to(dest, transport_header, buf)
     }
+
+    fn send_buffer(
+        &self,
+        dest: IPAddr,
+        icmp_header: ICMP6Header,
+        buf: PacketBuffer,
+    ) -> (ReturnCode, Option<PacketBuffer>) {
+        // The IP layer fills in the source address, and the checksum,
+        // since it is left unspecified here
+        let mut ip6_header = IP6Header::default();
+        ip6_header.dst_addr = dest;
+        self.ip_send_struct
+            .send_buffer(ip6_header, TransportHeader::ICMP(icmp_header), buf)
+    }
 }
 
 impl<T: IP6Sender<'a>> IP6SendClient for ICMP6SendStruct<'a, T> {
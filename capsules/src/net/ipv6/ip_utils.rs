@@ -6,6 +6,7 @@ use crate::net::icmpv6::icmpv6::{ICMP6Header, ICMP6HeaderOptions};
 use crate::net::ieee802154::MacAddress;
 use crate::net::ipv6::ipv6::IP6Header;
 use crate::net::udp::udp::UDPHeader;
+use core::cmp;
 
 #[derive(Copy, Clone, PartialEq)]
 pub enum MacAddr {
@@ -74,6 +75,25 @@ impl IPAddr {
         ip_addr
     }
 
+    /// Method for recovering the 15.4 MAC address a link local address was
+    /// generated from. This is the inverse of `generate_from_mac`, and returns
+    /// `None` if the address is not a unicast link local address
+    pub fn get_link_local_mac(&self) -> Option<MacAddress> {
+        if !self.is_unicast_link_local() {
+            return None;
+        }
+        if self.0[8..14] == [0x00, 0x00, 0x00, 0xff, 0xfe, 0x00] {
+            Some(MacAddress::Short(
+                ((self.0[14] as u16) << 8) | (self.0[15] as u16),
+            ))
+        } else {
+            let mut long_addr = [0 as u8; 8];
+            long_addr.copy_from_slice(&self.0[8..16]);
+            long_addr[0] ^= 0b00000010;
+            Some(MacAddress::Long(long_addr))
+        }
+    }
+
     pub fn is_unspecified(&self) -> bool {
         self.0.iter().all(|&b| b == 0)
     }
@@ -111,6 +131,22 @@ impl IPAddr {
     pub fn is_multicast(&self) -> bool {
         self.0[0] == 0xff
     }
+
+    /// Returns whether the first `prefix_len` bits of this address match
+    /// those of `prefix`
+    pub fn matches_prefix(&self, prefix: &IPAddr, prefix_len: u8) -> bool {
+        let prefix_len = cmp::min(prefix_len, 128);
+        let full_bytes = (prefix_len / 8) as usize;
+        let remaining = (prefix_len & 0x7) as usize;
+        if self.0[0..full_bytes] != prefix.0[0..full_bytes] {
+            return false;
+        }
+        if remaining != 0 {
+            let mask = (0xff as u8) << (8 - remaining);
+            return (self.0[full_bytes] & mask) == (prefix.0[full_bytes] & mask);
+        }
+        true
+    }
 }
 
 pub fn compute_udp_checksum(
@@ -201,6 +237,10 @@ pub fn compute_icmp_checksum(
             sum += unused >> 16; // upper 16 bits
             sum += unused & 0xffff; // lower 16 bits
         }
+        ICMP6HeaderOptions::Type155 { base } => {
+            sum += base >> 16; // upper 16 bits
+            sum += base & 0xffff; // lower 16 bits
+        }
         ICMP6HeaderOptions::Type128 { id, seqno } | ICMP6HeaderOptions::Type129 { id, seqno } => {
             sum += id as u32;
             sum += seqno as u32;
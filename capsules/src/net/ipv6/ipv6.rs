@@ -309,11 +309,47 @@ pub enum TransportHeader {
     /* Raw(RawIPPacket<'a>), */
 }
 
+impl TransportHeader {
+    /// This function decodes the transport header at the start of the
+    /// payload of a received IPv6 packet, so that the packet can be sent
+    /// onwards (e.g. when forwarding it to another node).
+    ///
+    /// # Arguments
+    ///
+    /// `next_header` - The next header field of the packet's `IP6Header`
+    /// `buf` - The payload of the packet, starting with the transport header
+    ///
+    /// # Return Value
+    ///
+    /// `Option<(usize, TransportHeader)>` - The size of the transport header
+    /// and the decoded header, or `None` if the header could not be decoded
+    /// or the transport protocol is not supported
+    pub fn decode(next_header: u8, buf: &[u8]) -> Option<(usize, TransportHeader)> {
+        match next_header {
+            ip6_nh::UDP => UDPHeader::decode(buf).done().map(|(_, udp_header)| {
+                (udp_header.get_hdr_size(), TransportHeader::UDP(udp_header))
+            }),
+            ip6_nh::ICMP => ICMP6Header::decode(buf).done().map(|(_, mut icmp_header)| {
+                icmp_header.set_len(buf.len() as u16);
+                (
+                    icmp_header.get_hdr_size(),
+                    TransportHeader::ICMP(icmp_header),
+                )
+            }),
+            _ => None,
+        }
+        .filter(|&(hdr_size, _)| hdr_size <= buf.len())
+    }
+}
+
 /// The `IPPayload` struct contains a `TransportHeader` and a mutable buffer
-/// (the payload).
+/// (the payload). The transport payload starts `offset` bytes into the
+/// buffer, which allows a payload to be sent from a `PacketBuffer` without
+/// first moving it past any headroom.
 pub struct IPPayload<'a> {
     pub header: TransportHeader,
     pub payload: &'a mut [u8],
+    pub offset: usize,
 }
 
 impl IPPayload<'a> {
@@ -327,6 +363,7 @@ impl IPPayload<'a> {
         IPPayload {
             header: header,
             payload: payload,
+            offset: 0,
         }
     }
 
@@ -348,23 +385,52 @@ impl IPPayload<'a> {
             // TODO: Error
         }
         self.payload[..payload.len()].copy_from_slice(&payload);
+        self.offset = 0;
+        self.set_header(transport_header, payload.len())
+    }
+
+    /// This function sets the `TransportHeader` for a payload of length
+    /// `payload_len` that has already been placed in the payload buffer,
+    /// starting at `offset`.
+    ///
+    /// # Arguments
+    ///
+    /// `transport_header` - The new `TransportHeader` header for the payload
+    /// `payload_len` - The length of the transport payload
+    ///
+    /// # Return Value
+    ///
+    /// `(u8, u16)` - Returns a tuple of the `ip6_nh` type of the
+    /// `transport_header` and the total length of the `IPPayload`
+    /// (when serialized)
+    pub fn set_header(
+        &mut self,
+        transport_header: TransportHeader,
+        payload_len: usize,
+    ) -> (u8, u16) {
         match transport_header {
             TransportHeader::UDP(mut udp_header) => {
-                let length = (payload.len() + udp_header.get_hdr_size()) as u16;
+                let length = (payload_len + udp_header.get_hdr_size()) as u16;
                 udp_header.set_len(length);
-                self.header = transport_header;
+                self.header = TransportHeader::UDP(udp_header);
                 (ip6_nh::UDP, length)
             }
             TransportHeader::ICMP(mut icmp_header) => {
-                let length = (payload.len() + icmp_header.get_hdr_size()) as u16;
+                let length = (payload_len + icmp_header.get_hdr_size()) as u16;
                 icmp_header.set_len(length);
-                self.header = transport_header;
+                self.header = TransportHeader::ICMP(icmp_header);
                 (ip6_nh::ICMP, length)
             }
-            _ => (ip6_nh::NO_NEXT, payload.len() as u16),
+            _ => (ip6_nh::NO_NEXT, payload_len as u16),
         }
     }
 
+    /// Returns the transport payload, skipping any headroom at the start of
+    /// the payload buffer
+    pub fn get_payload(&self) -> &[u8] {
+        &self.payload[self.offset..]
+    }
+
     /// This function encodes the `IPPayload` as a byte array
     ///
     /// # Arguments
@@ -385,7 +451,7 @@ impl IPPayload<'a> {
             }
         };
         let payload_length = self.get_payload_length();
-        let offset = enc_consume!(buf, offset; encode_bytes, &self.payload[..payload_length]);
+        let offset = enc_consume!(buf, offset; encode_bytes, &self.get_payload()[..payload_length]);
         stream_done!(offset, offset)
     }
 
@@ -439,7 +505,7 @@ impl IP6Packet<'a> {
     }
 
     pub fn get_payload(&self) -> &[u8] {
-        self.payload.payload
+        self.payload.get_payload()
     }
 
     pub fn get_total_hdr_size(&self) -> usize {
@@ -464,12 +530,16 @@ impl IP6Packet<'a> {
                     &self.header,
                     &udp_header,
                     udp_header.get_len(),
-                    self.payload.payload,
+                    &self.payload.payload[self.payload.offset..],
                 );
                 udp_header.set_cksum(cksum);
             }
             TransportHeader::ICMP(ref mut icmp_header) => {
-                let cksum = compute_icmp_checksum(&self.header, &icmp_header, self.payload.payload);
+                let cksum = compute_icmp_checksum(
+                    &self.header,
+                    &icmp_header,
+                    &self.payload.payload[self.payload.offset..],
+                );
                 icmp_header.set_cksum(cksum);
             }
             _ => {
@@ -498,6 +568,21 @@ impl IP6Packet<'a> {
         self.header.set_payload_len(payload_len);
     }
 
+    /// This function is the zero-copy equivalent of `set_payload`, for a
+    /// transport payload that has already been placed in the payload buffer
+    /// at `IPPayload.offset`. It sets the transport header and the
+    /// `IP6Header` next header and payload length fields accordingly.
+    ///
+    /// # Arguments
+    ///
+    /// `transport_header` - The `TransportHeader` to be set as the next header
+    /// `payload_len` - The length of the transport payload
+    pub fn set_payload_in_place(&mut self, transport_header: TransportHeader, payload_len: usize) {
+        let (next_header, payload_len) = self.payload.set_header(transport_header, payload_len);
+        self.header.set_next_header(next_header);
+        self.header.set_payload_len(payload_len);
+    }
+
     // TODO: Do we need a decode equivalent? I don't think so, but we might
 
     pub fn encode(&self, buf: &mut [u8]) -> SResult<usize> {
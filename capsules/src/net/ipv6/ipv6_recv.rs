@@ -1,5 +1,7 @@
+use crate::net::ipv6::ip_utils::ip6_nh;
 use crate::net::ipv6::ipv6::IP6Header;
 use crate::net::sixlowpan::sixlowpan_state::SixlowpanRxClient;
+use crate::net::stats::{counter, NetStats};
 use kernel::common::cells::OptionalCell;
 use kernel::debug;
 use kernel::ReturnCode;
@@ -43,6 +45,7 @@ pub trait IP6Receiver<'a> {
 
 pub struct IP6RecvStruct<'a> {
     client: OptionalCell<&'a IP6RecvClient>,
+    stats: OptionalCell<&'a NetStats>,
 }
 
 impl<'a> IP6Receiver<'a> for IP6RecvStruct<'a> {
@@ -55,8 +58,19 @@ impl<'a> IP6RecvStruct<'a> {
     pub fn new() -> IP6RecvStruct<'a> {
         IP6RecvStruct {
             client: OptionalCell::empty(),
+            stats: OptionalCell::empty(),
         }
     }
+
+    /// Sets the network statistics that received and dropped packets are
+    /// counted in.
+    pub fn set_stats(&self, stats: &'a NetStats) {
+        self.stats.set(stats);
+    }
+
+    fn count(&self, counter: usize) {
+        self.stats.map(|stats| stats.incr(counter));
+    }
 }
 
 impl<'a> SixlowpanRxClient for IP6RecvStruct<'a> {
@@ -70,16 +84,23 @@ impl<'a> SixlowpanRxClient for IP6RecvStruct<'a> {
                 let checksum_result = ip6_header.check_transport_checksum(&buf[offset..len]);
                 if checksum_result == ReturnCode::FAIL {
                     debug!("dropped!: {:?}", checksum_result);
+                    if ip6_header.get_next_header() == ip6_nh::UDP {
+                        self.count(counter::UDP_CHECKSUM_ERRORS);
+                    } else {
+                        self.count(counter::IP_PACKETS_DROPPED);
+                    }
                     return; //Dropped.
                 }
                 // Note: Protocols for which checksum verification is not implemented (TCP, etc.)
                 // are automatically assumed as fine, rather than dropped
 
+                self.count(counter::IP_PACKETS_RECEIVED);
                 self.client
                     .map(|client| client.receive(ip6_header, &buf[offset..len]));
             }
             None => {
                 // TODO: Report the error somewhere...
+                self.count(counter::IP_PACKETS_DROPPED);
             }
         }
     }
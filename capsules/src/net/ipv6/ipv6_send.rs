@@ -17,11 +17,13 @@
 // interface.
 
 use crate::ieee802154::device::{MacDevice, TxClient};
+use crate::net::buffer::{PacketBuffer, PacketPool};
 use crate::net::ieee802154::MacAddress;
 use crate::net::ipv6::ip_utils::IPAddr;
 use crate::net::ipv6::ipv6::{IP6Header, IP6Packet, TransportHeader};
 use crate::net::sixlowpan::sixlowpan_state::TxState;
 use core::cell::Cell;
+use core::mem;
 use kernel::common::cells::{OptionalCell, TakeCell};
 use kernel::debug;
 use kernel::hil::time::{self, Frequency};
@@ -79,6 +81,52 @@ pub trait IP6Sender<'a> {
     /// `payload` - The transport payload for the packet being sent
     fn send_to(&self, dst: IPAddr, transport_header: TransportHeader, payload: &[u8])
         -> ReturnCode;
+
+    /// This method sends a packet using a complete, caller-provided
+    /// `IP6Header`, rather than constructing a header from the configured
+    /// source address. This is used to forward packets on behalf of other
+    /// nodes, where the original source address must be preserved.
+    ///
+    /// # Arguments
+    /// `ip6_header` - The `IP6Header` for the packet being sent
+    /// `transport_header` - The `TransportHeader` for the packet being sent
+    /// `payload` - The transport payload for the packet being sent
+    fn send_with_header(
+        &self,
+        ip6_header: IP6Header,
+        transport_header: TransportHeader,
+        payload: &[u8],
+    ) -> ReturnCode;
+
+    /// This method sends a transport payload held in a `PacketBuffer`
+    /// without copying it. If the source address of `ip6_header` is
+    /// unspecified, it is set to the configured source address and the
+    /// transport checksum is computed; otherwise the header is sent as is,
+    /// which allows packets to be forwarded.
+    ///
+    /// Once the packet has been sent, the buffer is returned to the
+    /// `PacketPool` of the `IP6Sender`. If the packet is not sent, the buffer
+    /// is returned to the caller along with the error.
+    ///
+    /// # Arguments
+    /// `ip6_header` - The `IP6Header` for the packet being sent
+    /// `transport_header` - The `TransportHeader` for the packet being sent
+    /// `buf` - A buffer containing the transport payload
+    fn send_buffer(
+        &self,
+        ip6_header: IP6Header,
+        transport_header: TransportHeader,
+        buf: PacketBuffer,
+    ) -> (ReturnCode, Option<PacketBuffer>);
+}
+
+/// This trait is implemented by routing layers that can select the link-layer
+/// next hop for a given destination. When no router is set, or the router has
+/// no opinion about a destination, packets are sent to the configured gateway.
+pub trait IP6Router {
+    /// Returns the MAC address of the next hop towards `dst`, or `None` if the
+    /// packet should be sent to the default gateway.
+    fn next_hop(&self, dst: IPAddr) -> Option<MacAddress>;
 }
 
 /// This struct is a specific implementation of the `IP6Sender` trait. This
@@ -94,9 +142,13 @@ pub struct IP6SendStruct<'a, A: time::Alarm> {
     tx_buf: TakeCell<'static, [u8]>,
     sixlowpan: TxState<'a>,
     radio: &'a MacDevice<'a>,
-    dst_mac_addr: MacAddress,
     src_mac_addr: MacAddress,
     client: OptionalCell<&'a IP6SendClient>,
+    router: OptionalCell<&'a IP6Router>,
+    pool: OptionalCell<&'a PacketPool<'a>>,
+    // The payload buffer of `ip6_packet`, while a buffer from the pool is
+    // being sent in its place
+    packet_payload: TakeCell<'static, [u8]>,
 }
 
 impl<A: time::Alarm> IP6Sender<'a> for IP6SendStruct<'a, A> {
@@ -123,16 +175,104 @@ impl<A: time::Alarm> IP6Sender<'a> for IP6SendStruct<'a, A> {
         transport_header: TransportHeader,
         payload: &[u8],
     ) -> ReturnCode {
+        if self.packet_payload.is_some() {
+            return ReturnCode::EBUSY;
+        }
+        let result = self.attach_pool_buffer(payload.len());
+        if result != ReturnCode::SUCCESS {
+            return result;
+        }
         self.sixlowpan.init(
             self.src_mac_addr,
-            self.dst_mac_addr,
+            self.next_hop_mac_addr(dst),
             self.radio.get_pan(),
             None,
         );
         self.init_packet(dst, transport_header, payload);
         let ret = self.send_next_fragment();
+        if ret != ReturnCode::SUCCESS {
+            self.free_pool_buffer();
+        }
+        ret
+    }
+
+    fn send_with_header(
+        &self,
+        ip6_header: IP6Header,
+        transport_header: TransportHeader,
+        payload: &[u8],
+    ) -> ReturnCode {
+        if self.packet_payload.is_some() {
+            return ReturnCode::EBUSY;
+        }
+        let result = self.attach_pool_buffer(payload.len());
+        if result != ReturnCode::SUCCESS {
+            return result;
+        }
+        self.sixlowpan.init(
+            self.src_mac_addr,
+            self.next_hop_mac_addr(ip6_header.get_dst_addr()),
+            self.radio.get_pan(),
+            None,
+        );
+        self.ip6_packet.map(|ip6_packet| {
+            ip6_packet.header = ip6_header;
+            ip6_packet.set_payload(transport_header, payload);
+        });
+        let ret = self.send_next_fragment();
+        if ret != ReturnCode::SUCCESS {
+            self.free_pool_buffer();
+        }
         ret
     }
+
+    fn send_buffer(
+        &self,
+        ip6_header: IP6Header,
+        transport_header: TransportHeader,
+        buf: PacketBuffer,
+    ) -> (ReturnCode, Option<PacketBuffer>) {
+        if self.pool.is_none() {
+            return (ReturnCode::ENOSUPPORT, Some(buf));
+        }
+        if self.tx_buf.is_none() || self.packet_payload.is_some() {
+            return (ReturnCode::EBUSY, Some(buf));
+        }
+        self.sixlowpan.init(
+            self.src_mac_addr,
+            self.next_hop_mac_addr(ip6_header.get_dst_addr()),
+            self.radio.get_pan(),
+            None,
+        );
+
+        let headroom = buf.headroom();
+        let payload_len = buf.len();
+        self.ip6_packet.map(|ip6_packet| {
+            // Send directly from the pool buffer by swapping it in for the
+            // packet's own payload buffer until the send completes
+            let packet_payload = mem::replace(&mut ip6_packet.payload.payload, buf.into_inner());
+            self.packet_payload.replace(packet_payload);
+            ip6_packet.payload.offset = headroom;
+            ip6_packet.header = ip6_header;
+            ip6_packet.set_payload_in_place(transport_header, payload_len);
+            if ip6_packet.header.get_src_addr().is_unspecified() {
+                ip6_packet.header.src_addr = self.src_addr.get();
+                ip6_packet.set_transport_checksum();
+            }
+        });
+
+        let result = self.send_next_fragment();
+        if result != ReturnCode::SUCCESS {
+            // Hand the buffer back to the caller as it was passed in
+            let buf = self.restore_packet_payload().map(|buf| {
+                let mut buf = PacketBuffer::new(buf, headroom);
+                buf.extend(payload_len);
+                buf
+            });
+            return (result, buf);
+        }
+        (result, None)
+    }
 }
 
 impl<A: time::Alarm> IP6SendStruct<'a, A> {
@@ -153,12 +293,76 @@ impl<A: time::Alarm> IP6SendStruct<'a, A> {
             tx_buf: TakeCell::new(tx_buf),
             sixlowpan: sixlowpan,
             radio: radio,
-            dst_mac_addr: dst_mac_addr,
             src_mac_addr: src_mac_addr,
             client: OptionalCell::empty(),
+            router: OptionalCell::empty(),
+            pool: OptionalCell::empty(),
+            packet_payload: TakeCell::empty(),
+        }
+    }
+
+    /// Sets the pool that buffers passed to `send_buffer` are returned to.
+    /// `send_buffer` is only supported once a pool has been set. With a pool,
+    /// `send_to` and `send_with_header` also copy the payload into a buffer
+    /// from the pool, so the payload buffer of the `IP6Packet` passed to
+    /// `new` can be empty.
+    pub fn set_pool(&self, pool: &'a PacketPool<'a>) {
+        self.pool.set(pool);
+    }
+
+    /// Swaps the packet's own payload buffer back in after sending from a
+    /// pool buffer, returning the pool buffer.
+    fn restore_packet_payload(&self) -> Option<&'static mut [u8]> {
+        let packet_payload = self.packet_payload.take()?;
+        self.ip6_packet.map(move |ip6_packet| {
+            ip6_packet.payload.offset = 0;
+            mem::replace(&mut ip6_packet.payload.payload, packet_payload)
+        })
+    }
+
+    /// If there is a pool, swaps one of its buffers in for the packet's own
+    /// payload buffer, for a payload of `payload_len` bytes to be copied
+    /// into. Returns EBUSY if every buffer of the pool is in use, and ESIZE
+    /// if the payload does not fit.
+    fn attach_pool_buffer(&self, payload_len: usize) -> ReturnCode {
+        self.pool.map_or(ReturnCode::SUCCESS, |pool| {
+            let buf = match pool.alloc(0) {
+                Some(buf) => buf,
+                None => return ReturnCode::EBUSY,
+            };
+            if buf.tailroom() < payload_len {
+                pool.free(buf);
+                return ReturnCode::ESIZE;
+            }
+            self.ip6_packet.map(|ip6_packet| {
+                let packet_payload =
+                    mem::replace(&mut ip6_packet.payload.payload, buf.into_inner());
+                self.packet_payload.replace(packet_payload);
+            });
+            ReturnCode::SUCCESS
+        })
+    }
+
+    /// Returns the pool buffer being sent from, if any, to the pool.
+    fn free_pool_buffer(&self) {
+        if let Some(buf) = self.restore_packet_payload() {
+            self.pool
+                .map(move |pool| pool.free(PacketBuffer::new(buf, 0)));
         }
     }
 
+    /// Sets the routing layer used to select the next hop MAC address for
+    /// each outgoing packet.
+    pub fn set_router(&self, router: &'a IP6Router) {
+        self.router.set(router);
+    }
+
+    fn next_hop_mac_addr(&self, dst: IPAddr) -> MacAddress {
+        self.router
+            .and_then(|router| router.next_hop(dst))
+            .unwrap_or(self.gateway.get())
+    }
+
     fn init_packet(&self, dst_addr: IPAddr, transport_header: TransportHeader, payload: &[u8]) {
         self.ip6_packet.map(|ip6_packet| {
             ip6_packet.header = IP6Header::default();
@@ -217,6 +421,7 @@ impl<A: time::Alarm> IP6SendStruct<'a, A> {
     }
 
     fn send_completed(&self, result: ReturnCode) {
+        self.free_pool_buffer();
         self.client.map(move |client| client.send_done(result));
     }
 }
@@ -0,0 +1,201 @@
+//! Periodically publish the registered metrics over UDP.
+//!
+//! Every interval, `MetricsExporter` sends the metrics registered with
+//! `kernel::metrics` to a collector, as text, one metric per line:
+//!
+//! ```text
+//! kernel.syscalls c 18422
+//! radio.queue g 3
+//! ```
+//!
+//! where `c` marks a counter and `g` a gauge. If the metrics do not fit in
+//! one datagram, the report is split over several, each holding whole lines.
+//!
+//! Usage
+//! -----
+//!
+//! ```rust
+//! static mut METRICS_BUF: [u8; 200] = [0; 200];
+//!
+//! let metrics_exporter = static_init!(
+//!     capsules::net::metrics_export::MetricsExporter<'static, VirtualMuxAlarm<'static, sam4l::ast::Ast>>,
+//!     capsules::net::metrics_export::MetricsExporter::new(
+//!         udp_send,
+//!         VirtualMuxAlarm::new(mux_alarm),
+//!         &mut METRICS_BUF,
+//!         COLLECTOR_ADDR,
+//!         8125,  // collector port
+//!         16123, // source port
+//!         60000, // interval in ms
+//!     )
+//! );
+//! udp_send.set_client(metrics_exporter);
+//! metrics_exporter.alarm.set_client(metrics_exporter);
+//! board_kernel.register_metrics();
+//! metrics_exporter.start();
+//! ```
+
+use crate::net::ipv6::ip_utils::IPAddr;
+use crate::net::udp::udp_send::{UDPSendClient, UDPSender};
+use core::cell::Cell;
+use kernel::common::cells::TakeCell;
+use kernel::hil::time::{self, Alarm, Frequency};
+use kernel::metrics::{self, Kind, Metric};
+use kernel::ReturnCode;
+
+pub struct MetricsExporter<'a, A: Alarm> {
+    udp_sender: &'a UDPSender<'a>,
+    pub alarm: A,
+    buffer: TakeCell<'static, [u8]>,
+    dest: IPAddr,
+    dst_port: u16,
+    src_port: u16,
+    interval_ms: u32,
+    /// The index of the first metric not sent yet in this report
+    next_metric: Cell<usize>,
+    /// Whether a datagram is being sent
+    sending: Cell<bool>,
+}
+
+impl<A: Alarm> MetricsExporter<'a, A> {
+    pub fn new(
+        udp_sender: &'a UDPSender<'a>,
+        alarm: A,
+        buffer: &'static mut [u8],
+        dest: IPAddr,
+        dst_port: u16,
+        src_port: u16,
+        interval_ms: u32,
+    ) -> MetricsExporter<'a, A> {
+        MetricsExporter {
+            udp_sender: udp_sender,
+            alarm: alarm,
+            buffer: TakeCell::new(buffer),
+            dest: dest,
+            dst_port: dst_port,
+            src_port: src_port,
+            interval_ms: interval_ms,
+            next_metric: Cell::new(0),
+            sending: Cell::new(false),
+        }
+    }
+
+    /// Start publishing, with the first report one interval from now.
+    pub fn start(&self) {
+        self.schedule();
+    }
+
+    /// Publish a report now, unless one is being sent.
+    pub fn publish(&self) -> ReturnCode {
+        if self.sending.get() {
+            return ReturnCode::EBUSY;
+        }
+        self.next_metric.set(0);
+        self.send_remaining()
+    }
+
+    fn schedule(&self) {
+        let ticks = (self.interval_ms as u64 * <A::Frequency>::frequency() as u64 / 1000) as u32;
+        self.alarm.set_alarm(self.alarm.now().wrapping_add(ticks));
+    }
+
+    /// Send the datagrams of the metrics that are not sent yet, one at a
+    /// time.
+    fn send_remaining(&self) -> ReturnCode {
+        while !self.sending.get() && metrics::iter().nth(self.next_metric.get()).is_some() {
+            let rcode = self.send_next();
+            if rcode != ReturnCode::SUCCESS {
+                return rcode;
+            }
+        }
+        ReturnCode::SUCCESS
+    }
+
+    /// Send the metrics from `next_metric` on that fit in a datagram.
+    fn send_next(&self) -> ReturnCode {
+        let first = self.next_metric.get();
+        self.buffer.map_or(ReturnCode::ENOMEM, |buffer| {
+            let mut len = 0;
+            let mut count = 0;
+            for metric in metrics::iter().skip(first) {
+                match Self::write_line(metric, &mut buffer[len..]) {
+                    Some(line_len) => {
+                        len += line_len;
+                        count += 1;
+                    }
+                    None => break,
+                }
+            }
+            if count == 0 {
+                // The line of the next metric does not fit even alone
+                self.next_metric.set(first + 1);
+                return ReturnCode::SUCCESS;
+            }
+
+            self.next_metric.set(first + count);
+            let rcode =
+                self.udp_sender
+                    .send_to(self.dest, self.dst_port, self.src_port, &buffer[..len]);
+            if rcode == ReturnCode::SUCCESS {
+                self.sending.set(true);
+            }
+            rcode
+        })
+    }
+
+    /// Write the line of `metric` to the start of `buffer`, if it fits, and
+    /// return its length.
+    fn write_line(metric: &Metric, buffer: &mut [u8]) -> Option<usize> {
+        let kind = match metric.kind() {
+            Kind::Counter => b'c',
+            Kind::Gauge => b'g',
+        };
+        let name = metric.name().as_bytes();
+
+        let mut digits = [0; 10];
+        let mut value = metric.get();
+        let mut num_digits = 0;
+        loop {
+            digits[num_digits] = b'0' + (value % 10) as u8;
+            num_digits += 1;
+            value /= 10;
+            if value == 0 {
+                break;
+            }
+        }
+
+        let len = name.len() + 3 + num_digits + 1;
+        if len > buffer.len() {
+            return None;
+        }
+        buffer[..name.len()].copy_from_slice(name);
+        let mut i = name.len();
+        buffer[i] = b' ';
+        buffer[i + 1] = kind;
+        buffer[i + 2] = b' ';
+        i += 3;
+        for &digit in digits[..num_digits].iter().rev() {
+            buffer[i] = digit;
+            i += 1;
+        }
+        buffer[i] = b'\n';
+        Some(len)
+    }
+}
+
+impl<A: Alarm> time::Client for MetricsExporter<'a, A> {
+    fn fired(&self) {
+        self.schedule();
+        // A report that is still being sent is not interrupted
+        let _ = self.publish();
+    }
+}
+
+impl<A: Alarm> UDPSendClient for MetricsExporter<'a, A> {
+    fn send_done(&self, _result: ReturnCode) {
+        self.sending.set(false);
+        // A failed datagram is not sent again: the next report has the same
+        // counters
+        let _ = self.send_remaining();
+    }
+}
@@ -5,9 +5,18 @@ pub mod sixlowpan;
 pub mod util;
 #[macro_use]
 pub mod stream;
+pub mod border_router;
+pub mod buffer;
+pub mod dhcpv6;
+pub mod ethernet;
 pub mod icmpv6;
 pub mod ieee802154;
 pub mod ipv6;
+pub mod metrics_export;
+pub mod rate_limit;
+pub mod remote_console;
+pub mod rpl;
+pub mod stats;
 pub mod tcp;
 pub mod thread;
 pub mod udp;
@@ -0,0 +1,135 @@
+//! Per-process token-bucket rate limiting of network transmissions.
+//!
+//! A `TokenBucketLimiter` keeps a token bucket for each process that the board
+//! has given a limit. Every byte of payload a process asks to transmit takes
+//! one token from its bucket, and the bucket refills at the configured rate up
+//! to its burst size. Transmissions that would take more tokens than are left
+//! are refused, so that one chatty process can neither starve the others of
+//! radio time nor exceed a duty-cycle budget. Processes without a limit are
+//! never refused.
+//!
+//! A single limiter can be shared by the UDP and the raw 802.15.4 system call
+//! drivers, in which case both draw from the same per-process budget.
+//!
+//! Usage
+//! -----
+//!
+//! ```
+//! let buckets = static_init!(
+//!     [capsules::net::rate_limit::TokenBucket; NUM_PROCS],
+//!     Default::default()
+//! );
+//! let rate_limiter = static_init!(
+//!     capsules::net::rate_limit::TokenBucketLimiter<'static, VirtualMuxAlarm<'static, Ast>>,
+//!     capsules::net::rate_limit::TokenBucketLimiter::new(limiter_alarm, buckets)
+//! );
+//! // Limit the first process to 100 bytes/s, with bursts of up to 1 KiB
+//! rate_limiter.set_limit(0, 100, 1024);
+//! udp_driver.set_rate_limiter(rate_limiter);
+//! radio_driver.set_rate_limiter(rate_limiter);
+//! ```
+
+use core::cell::Cell;
+use kernel::hil::time::{self, Frequency};
+
+/// Interface used by the system call drivers to charge transmissions to the
+/// process that requested them.
+pub trait RateLimiter {
+    /// Takes `len` tokens from the bucket of the process with index
+    /// `app_idx`. Returns `false`, and takes nothing, if the process may not
+    /// transmit `len` bytes now.
+    fn consume(&self, app_idx: usize, len: usize) -> bool;
+}
+
+/// The token bucket of a single process.
+#[derive(Default)]
+pub struct TokenBucket {
+    /// Refill rate in bytes per second, or `None` if the process is not
+    /// limited
+    rate: Cell<Option<u32>>,
+    /// Maximum number of tokens the bucket holds
+    burst: Cell<u32>,
+    tokens: Cell<u32>,
+    /// Time at which the tokens were last brought up to date
+    last_refill: Cell<u32>,
+}
+
+pub struct TokenBucketLimiter<'a, A: time::Alarm> {
+    alarm: &'a A,
+    buckets: &'a [TokenBucket],
+}
+
+impl<A: time::Alarm> TokenBucketLimiter<'a, A> {
+    /// Creates a limiter with a bucket for each of the first `buckets.len()`
+    /// processes. Processes with a higher index are never limited.
+    pub fn new(alarm: &'a A, buckets: &'a [TokenBucket]) -> TokenBucketLimiter<'a, A> {
+        TokenBucketLimiter {
+            alarm: alarm,
+            buckets: buckets,
+        }
+    }
+
+    /// Limits the process with index `app_idx` to `rate` bytes per second,
+    /// with bursts of up to `burst` bytes. The bucket starts out full. A rate
+    /// of 0 allows the process a single burst and nothing after that.
+    pub fn set_limit(&self, app_idx: usize, rate: u32, burst: u32) {
+        self.buckets.get(app_idx).map(|bucket| {
+            bucket.rate.set(Some(rate));
+            bucket.burst.set(burst);
+            bucket.tokens.set(burst);
+            bucket.last_refill.set(self.alarm.now());
+        });
+    }
+
+    /// Removes the limit of the process with index `app_idx`.
+    pub fn clear_limit(&self, app_idx: usize) {
+        self.buckets
+            .get(app_idx)
+            .map(|bucket| bucket.rate.set(None));
+    }
+
+    /// Adds the tokens that have accumulated since the last refill. Time spent
+    /// idle with a bucket that is not full is under-counted if it exceeds the
+    /// wrap-around period of the alarm.
+    fn refill(&self, bucket: &TokenBucket, rate: u32) {
+        let now = self.alarm.now();
+        let burst = bucket.burst.get();
+        let freq = A::Frequency::frequency() as u64;
+        let elapsed = now.wrapping_sub(bucket.last_refill.get()) as u64;
+        let added = elapsed * rate as u64 / freq;
+        let tokens = bucket.tokens.get() as u64 + added;
+        if tokens >= burst as u64 {
+            bucket.tokens.set(burst);
+            bucket.last_refill.set(now);
+        } else if added > 0 {
+            // Only advance by the time it took to earn whole tokens, so that
+            // frequent calls do not lose the fractions in between
+            bucket.tokens.set(tokens as u32);
+            let earned = (added * freq / rate as u64) as u32;
+            bucket
+                .last_refill
+                .set(bucket.last_refill.get().wrapping_add(earned));
+        }
+    }
+}
+
+impl<A: time::Alarm> RateLimiter for TokenBucketLimiter<'a, A> {
+    fn consume(&self, app_idx: usize, len: usize) -> bool {
+        let bucket = match self.buckets.get(app_idx) {
+            Some(bucket) => bucket,
+            None => return true,
+        };
+        let rate = match bucket.rate.get() {
+            Some(rate) => rate,
+            None => return true,
+        };
+        self.refill(bucket, rate);
+        let tokens = bucket.tokens.get() as usize;
+        if len <= tokens {
+            bucket.tokens.set((tokens - len) as u32);
+            true
+        } else {
+            false
+        }
+    }
+}
@@ -0,0 +1,316 @@
+//! Process console commands over UDP, for devices in the field.
+//!
+//! `RemoteConsole` runs the commands of a `ProcessConsole` that it receives
+//! on a UDP port and sends what they print back to the sender, so that
+//! operators can list, stop and restart processes without a serial
+//! connection.
+//!
+//! Commands are authenticated with a key kept by an `hil::hkdf::Hkdf`
+//! implementation, such as the ATECC608, which the operator shares. Each
+//! request is:
+//!
+//! ```text
+//! +----------------+-----------------+--------------------+
+//! | sequence (LE)  | tag             | command (ASCII)    |
+//! | 4 bytes        | 16 bytes        | up to 32 bytes     |
+//! +----------------+-----------------+--------------------+
+//! ```
+//!
+//! where the tag is the first 16 bytes of HKDF-Expand of the key with the
+//! sequence number and the command as the info string, which is the
+//! HMAC-SHA256 of the info string and a block counter of 1. Sequence numbers
+//! must increase from one command to the next, so that commands cannot be
+//! replayed. The sequence number of the last accepted command is kept in a
+//! `MonotonicCounters` counter, which persists it in flash before the command
+//! runs, so a command captured before a reboot cannot be replayed after it
+//! either. Requests that do not authenticate, or whose sequence number
+//! cannot be recorded, are dropped without a reply.
+//!
+//! The reply starts with the sequence number of the command, followed by the
+//! lines the command printed, as far as they fit in the reply buffer. Replies
+//! are not authenticated.
+//!
+//! Usage
+//! -----
+//!
+//! The counters must not be shared with another client, such as the
+//! `MonotonicCounterDriver`, as they only have one.
+//!
+//! ```rust
+//! static mut REMOTE_CONSOLE_MAC_BUF: [u8; 36] = [0; 36];
+//! static mut REMOTE_CONSOLE_REPLY_BUF: [u8; 512] = [0; 512];
+//!
+//! let remote_console = static_init!(
+//!     capsules::net::remote_console::RemoteConsole<'static, Capability>,
+//!     capsules::net::remote_console::RemoteConsole::new(
+//!         pconsole,
+//!         udp_send,
+//!         atecc608,
+//!         5,    // key slot
+//!         remote_console_counters,
+//!         0,    // counter index
+//!         2323, // UDP port
+//!         &mut REMOTE_CONSOLE_MAC_BUF,
+//!         &mut REMOTE_CONSOLE_REPLY_BUF,
+//!     )
+//! );
+//! udp_recv.set_client(remote_console);
+//! udp_send.set_client(remote_console);
+//! atecc608.set_client(remote_console);
+//! remote_console_counters.set_client(remote_console);
+//! ```
+
+use crate::monotonic_counter::{MonotonicCounterClient, MonotonicCounters};
+use crate::net::ipv6::ip_utils::IPAddr;
+use crate::net::udp::udp_recv::UDPRecvClient;
+use crate::net::udp::udp_send::{UDPSendClient, UDPSender};
+use crate::process_console::{self, ProcessConsole};
+use core::cell::Cell;
+use core::fmt::{self, Write};
+use kernel::capabilities::ProcessManagementCapability;
+use kernel::common::cells::{OptionalCell, TakeCell};
+use kernel::common::secure::constant_time_eq;
+use kernel::hil::hkdf;
+use kernel::ReturnCode;
+
+/// The longest command, as for the serial console.
+pub const MAX_COMMAND_LEN: usize = 32;
+
+const SEQUENCE_LEN: usize = 4;
+const TAG_LEN: usize = 16;
+
+/// A request waiting for its tag to be checked.
+#[derive(Copy, Clone)]
+struct Request {
+    src_addr: IPAddr,
+    src_port: u16,
+    sequence: u32,
+    tag: [u8; TAG_LEN],
+    command: [u8; MAX_COMMAND_LEN],
+    command_len: usize,
+}
+
+pub struct RemoteConsole<'a, C: ProcessManagementCapability> {
+    console: &'a ProcessConsole<'a, C>,
+    udp_sender: &'a UDPSender<'a>,
+    hkdf: &'a hkdf::Hkdf<'a>,
+    key_id: usize,
+    /// Holds the sequence number of the last command that was run
+    counters: &'a MonotonicCounters<'a>,
+    counter_index: usize,
+    port: u16,
+    /// Where the info string goes, and the tag comes back
+    mac_buffer: TakeCell<'static, [u8]>,
+    reply_buffer: TakeCell<'static, [u8]>,
+    /// The request being authenticated, or whose sequence number is being
+    /// recorded
+    request: OptionalCell<Request>,
+    /// Whether a reply is being sent
+    sending: Cell<bool>,
+}
+
+impl<C: ProcessManagementCapability> RemoteConsole<'a, C> {
+    /// `mac_buffer` must hold the sequence number and the longest command,
+    /// and the output of `hkdf`.
+    pub fn new(
+        console: &'a ProcessConsole<'a, C>,
+        udp_sender: &'a UDPSender<'a>,
+        hkdf: &'a hkdf::Hkdf<'a>,
+        key_id: usize,
+        counters: &'a MonotonicCounters<'a>,
+        counter_index: usize,
+        port: u16,
+        mac_buffer: &'static mut [u8],
+        reply_buffer: &'static mut [u8],
+    ) -> RemoteConsole<'a, C> {
+        RemoteConsole {
+            console: console,
+            udp_sender: udp_sender,
+            hkdf: hkdf,
+            key_id: key_id,
+            counters: counters,
+            counter_index: counter_index,
+            port: port,
+            mac_buffer: TakeCell::new(mac_buffer),
+            reply_buffer: TakeCell::new(reply_buffer),
+            request: OptionalCell::empty(),
+            sending: Cell::new(false),
+        }
+    }
+
+    /// Start checking the tag of `request`.
+    fn authenticate(&self, request: Request) -> ReturnCode {
+        let info_len = SEQUENCE_LEN + request.command_len;
+        let buffer = match self.mac_buffer.take() {
+            Some(buffer) => buffer,
+            None => return ReturnCode::EBUSY,
+        };
+        if buffer.len() < info_len {
+            self.mac_buffer.replace(buffer);
+            return ReturnCode::ESIZE;
+        }
+        buffer[..SEQUENCE_LEN].copy_from_slice(&request.sequence.to_le_bytes());
+        buffer[SEQUENCE_LEN..info_len].copy_from_slice(&request.command[..request.command_len]);
+
+        match self.hkdf.expand(self.key_id, buffer, info_len) {
+            (ReturnCode::SUCCESS, _) => {
+                self.request.set(request);
+                ReturnCode::SUCCESS
+            }
+            (rcode, buffer) => {
+                buffer.map(|buffer| self.mac_buffer.replace(buffer));
+                rcode
+            }
+        }
+    }
+
+    /// Returns whether `sequence` is later than that of the last command
+    /// that was run. It is not if the counter cannot be read, as then a
+    /// command could be replayed.
+    fn is_fresh(&self, sequence: u32) -> bool {
+        self.counters
+            .read(self.counter_index)
+            .map_or(false, |last| sequence as u64 > last)
+    }
+
+    /// Run the command of `request`, and send what it prints back.
+    fn run(&self, request: &Request) {
+        self.reply_buffer.map(|buffer| {
+            let len = {
+                let output = ReplyOutput {
+                    buffer: TakeCell::new(buffer),
+                    len: Cell::new(0),
+                };
+                output.append(&request.sequence.to_le_bytes());
+                match core::str::from_utf8(&request.command[..request.command_len]) {
+                    Ok(command) => self.console.run_command(command, &output),
+                    Err(_) => {
+                        process_console::Output::line(&output, format_args!("Invalid command"))
+                    }
+                }
+                output.len.get()
+            };
+            let rcode = self.udp_sender.send_to(
+                request.src_addr,
+                request.src_port,
+                self.port,
+                &buffer[..len],
+            );
+            self.sending.set(rcode == ReturnCode::SUCCESS);
+        });
+    }
+}
+
+/// Collects the lines that a command prints into the reply, dropping what
+/// does not fit.
+struct ReplyOutput<'b> {
+    buffer: TakeCell<'b, [u8]>,
+    len: Cell<usize>,
+}
+
+impl ReplyOutput<'b> {
+    fn append(&self, bytes: &[u8]) {
+        self.buffer.map(|buffer| {
+            let start = self.len.get();
+            let len = core::cmp::min(bytes.len(), buffer.len() - start);
+            buffer[start..start + len].copy_from_slice(&bytes[..len]);
+            self.len.set(start + len);
+        });
+    }
+}
+
+impl Write for &ReplyOutput<'b> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.append(s.as_bytes());
+        Ok(())
+    }
+}
+
+impl process_console::Output for ReplyOutput<'b> {
+    fn line(&self, args: fmt::Arguments) {
+        let mut writer = self;
+        let _ = writer.write_fmt(args);
+        self.append(b"\n");
+    }
+}
+
+impl<C: ProcessManagementCapability> UDPRecvClient for RemoteConsole<'a, C> {
+    fn receive(
+        &self,
+        src_addr: IPAddr,
+        _dst_addr: IPAddr,
+        src_port: u16,
+        dst_port: u16,
+        payload: &[u8],
+    ) {
+        if dst_port != self.port || self.request.is_some() || self.sending.get() {
+            return;
+        }
+        let header_len = SEQUENCE_LEN + TAG_LEN;
+        if payload.len() <= header_len || payload.len() > header_len + MAX_COMMAND_LEN {
+            return;
+        }
+
+        let sequence = u32::from_le_bytes([payload[0], payload[1], payload[2], payload[3]]);
+        if !self.is_fresh(sequence) {
+            return;
+        }
+        let mut request = Request {
+            src_addr: src_addr,
+            src_port: src_port,
+            sequence: sequence,
+            tag: [0; TAG_LEN],
+            command: [0; MAX_COMMAND_LEN],
+            command_len: payload.len() - header_len,
+        };
+        request
+            .tag
+            .copy_from_slice(&payload[SEQUENCE_LEN..header_len]);
+        request.command[..request.command_len].copy_from_slice(&payload[header_len..]);
+        let _ = self.authenticate(request);
+    }
+}
+
+impl<C: ProcessManagementCapability> hkdf::Client for RemoteConsole<'a, C> {
+    fn expand_done(&self, result: ReturnCode, buffer: &'static mut [u8]) {
+        let authentic = result == ReturnCode::SUCCESS
+            && self.request.map_or(false, |request| {
+                constant_time_eq(&buffer[..TAG_LEN], &request.tag)
+            });
+        self.mac_buffer.replace(buffer);
+
+        self.request.take().map(|request| {
+            // The sequence number is checked again, in case a command with a
+            // later one ran in the meantime. The command runs once its
+            // sequence number is persistent.
+            if authentic
+                && self.is_fresh(request.sequence)
+                && self
+                    .counters
+                    .advance(self.counter_index, request.sequence as u64)
+                    == ReturnCode::SUCCESS
+            {
+                self.request.set(request);
+            }
+        });
+    }
+}
+
+impl<C: ProcessManagementCapability> MonotonicCounterClient for RemoteConsole<'a, C> {
+    fn counter_updated(&self, index: usize, value: u64, result: ReturnCode) {
+        if index != self.counter_index {
+            return;
+        }
+        self.request.take().map(|request| {
+            if result == ReturnCode::SUCCESS && value == request.sequence as u64 {
+                self.run(&request);
+            }
+        });
+    }
+}
+
+impl<C: ProcessManagementCapability> UDPSendClient for RemoteConsole<'a, C> {
+    fn send_done(&self, _result: ReturnCode) {
+        self.sending.set(false);
+    }
+}
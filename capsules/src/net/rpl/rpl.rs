@@ -0,0 +1,686 @@
+//! RPL-lite: a minimal, storing-mode mesh routing layer for the 6LoWPAN
+//! stack, loosely following RPL (RFC 6550).
+//!
+//! RPL-lite builds a single destination-oriented DAG (DODAG) rooted at a
+//! border router, so that nodes which are not in radio range of the root can
+//! still reach it (and be reached from it) over multiple hops.
+//!
+//! - The root periodically multicasts DIO beacons advertising its rank.
+//!   Every attached node re-advertises the DODAG with its own rank, using a
+//!   simplified Trickle timer that doubles the beacon interval while the
+//!   topology is stable and resets it whenever the node's parent changes.
+//! - Each node keeps a small neighbor table of the DIOs it has heard, and
+//!   selects the neighbor with the lowest rank as its preferred parent
+//!   (Objective Function Zero with hop count as the metric). The preferred
+//!   parent is the default upward route.
+//! - Each attached node periodically unicasts a DAO to its parent, listing
+//!   its own address and every destination in its routing table. The parent
+//!   installs routes to these targets via the sender, which provides the
+//!   downward routes.
+//! - Unattached nodes multicast DIS messages to solicit DIOs.
+//!
+//! Static routes can also be installed with `add_route`, which allows simple
+//! static source-routed deployments without running the protocol at all.
+//!
+//! Usage
+//! -----
+//!
+//! `Rpl` sits between the IPv6 receive path and its upper layer, and
+//! provides next hop selection to the IPv6 send path of every sender on the
+//! node. It sends its control messages and forwarded packets with its own
+//! `IP6SendStruct`, whose `ip6_packet` payload buffer must be large enough to
+//! hold forwarded packets.
+//!
+//! ```
+//! let rpl = static_init!(
+//!     capsules::net::rpl::rpl::Rpl<'static, VirtualMuxAlarm<'static, sam4l::ast::Ast>>,
+//!     capsules::net::rpl::rpl::Rpl::new(
+//!         rpl_virtual_alarm,
+//!         rpl_icmp_send,
+//!         rpl_ip_send,
+//!         &LOCAL_IP_IFACES,
+//!         RPL_INSTANCE_ID
+//!     )
+//! );
+//! rpl_virtual_alarm.set_client(rpl);
+//! rpl_icmp_send.set_client(rpl);
+//! rpl_ip_send.set_router(rpl);
+//! udp_ip_send.set_router(rpl);
+//! ip_receive.set_client(rpl);
+//! rpl.set_client(udp_recv);
+//! // Only on the border router:
+//! rpl.set_root(LOCAL_IP_IFACES[2]);
+//! rpl.start();
+//! ```
+
+use crate::net::icmpv6::icmpv6::{ICMP6Header, ICMP6Type};
+use crate::net::icmpv6::icmpv6_send::{ICMP6SendClient, ICMP6Sender};
+use crate::net::ieee802154::MacAddress;
+use crate::net::ipv6::ip_utils::{ip6_nh, IPAddr};
+use crate::net::ipv6::ipv6::{IP6Header, TransportHeader};
+use crate::net::ipv6::ipv6_recv::IP6RecvClient;
+use crate::net::ipv6::ipv6_send::{IP6Router, IP6Sender};
+use crate::net::rpl::rpl_msg::{self, rpl_code, DAOOption, DIO};
+use core::cell::Cell;
+use kernel::common::cells::{MapCell, OptionalCell};
+use kernel::hil::time::{self, Frequency};
+use kernel::ReturnCode;
+
+/// Maximum number of neighbors tracked as potential parents
+pub const MAX_NEIGHBORS: usize = 4;
+/// Maximum number of downward (and static) routes
+pub const MAX_ROUTES: usize = 16;
+
+/// The all-RPL-nodes link-local multicast address, ff02::1a
+pub const ALL_RPL_NODES: IPAddr = IPAddr([
+    0xff, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x1a,
+]);
+
+// Trickle-lite parameters. The DIO interval starts at DIO_INTERVAL_MIN_S and
+// doubles up to DIO_INTERVAL_DOUBLINGS times while the topology is stable.
+const DIO_INTERVAL_MIN_S: u32 = 1;
+const DIO_INTERVAL_DOUBLINGS: u32 = 6;
+
+// Neighbors that have not been heard from in this long are discarded. Note
+// that this must fit in a u32 worth of alarm tics.
+const NEIGHBOR_LIFETIME_S: u32 = 180;
+
+// DAO path lifetimes are expressed in units of ROUTE_LIFETIME_UNIT_S
+const ROUTE_LIFETIME_UNIT_S: u32 = 60;
+const DAO_PATH_LIFETIME: u8 = 3;
+
+// A new parent must improve on the current parent's rank by at least this much
+// before the node switches to it, to avoid oscillating between parents.
+const PARENT_SWITCH_THRESHOLD: u16 = rpl_msg::MIN_HOP_RANK_INCREASE / 2;
+
+// Largest DAO this implementation sends: the header is followed by one target
+// for this node, a target for every route, and a transit option.
+const DAO_MAX_LEN: usize = (MAX_ROUTES + 1) * rpl_msg::TARGET_OPT_LEN + rpl_msg::TRANSIT_OPT_LEN;
+
+#[derive(Copy, Clone, Debug)]
+struct Neighbor {
+    addr: IPAddr,
+    rank: u16,
+    last_heard: u32,
+}
+
+/// An entry of the routing table.
+#[derive(Copy, Clone, Debug)]
+pub struct Route {
+    /// Destination address of the route
+    pub target: IPAddr,
+    /// Link-layer address of the neighbor packets to `target` are sent to
+    pub next_hop: MacAddress,
+    // Static routes never expire
+    is_static: bool,
+    refreshed: u32,
+    lifetime_s: u32,
+}
+
+pub struct Rpl<'a, A: time::Alarm> {
+    alarm: &'a A,
+    icmp_sender: &'a ICMP6Sender<'a>,
+    ip_sender: &'a IP6Sender<'a>,
+    client: OptionalCell<&'a IP6RecvClient>,
+    local_addrs: &'static [IPAddr],
+
+    // DODAG state
+    instance_id: u8,
+    is_root: Cell<bool>,
+    dodag_id: Cell<IPAddr>,
+    version: Cell<u8>,
+    rank: Cell<u16>,
+    dtsn: Cell<u8>,
+    parent: OptionalCell<IPAddr>,
+    neighbors: MapCell<[Option<Neighbor>; MAX_NEIGHBORS]>,
+    routes: MapCell<[Option<Route>; MAX_ROUTES]>,
+
+    dio_doublings: Cell<u32>,
+    dao_seqno: Cell<u8>,
+    dao_pending: Cell<bool>,
+    // Whether a control message or forwarded packet is being sent
+    busy: Cell<bool>,
+}
+
+impl<A: time::Alarm> Rpl<'a, A> {
+    pub fn new(
+        alarm: &'a A,
+        icmp_sender: &'a ICMP6Sender<'a>,
+        ip_sender: &'a IP6Sender<'a>,
+        local_addrs: &'static [IPAddr],
+        instance_id: u8,
+    ) -> Rpl<'a, A> {
+        Rpl {
+            alarm: alarm,
+            icmp_sender: icmp_sender,
+            ip_sender: ip_sender,
+            client: OptionalCell::empty(),
+            local_addrs: local_addrs,
+            instance_id: instance_id,
+            is_root: Cell::new(false),
+            dodag_id: Cell::new(IPAddr::new()),
+            version: Cell::new(0),
+            rank: Cell::new(rpl_msg::INFINITE_RANK),
+            dtsn: Cell::new(0),
+            parent: OptionalCell::empty(),
+            neighbors: MapCell::new(Default::default()),
+            routes: MapCell::new(Default::default()),
+            dio_doublings: Cell::new(0),
+            dao_seqno: Cell::new(0),
+            dao_pending: Cell::new(false),
+            busy: Cell::new(false),
+        }
+    }
+
+    /// Sets the upper layer that receives packets destined for this node.
+    pub fn set_client(&self, client: &'a IP6RecvClient) {
+        self.client.set(client);
+    }
+
+    /// Makes this node the root of a new DODAG identified by `dodag_id`,
+    /// which should be a global address of this node. Calling this again
+    /// starts a new DODAG version, which forces all nodes to re-select their
+    /// parents.
+    pub fn set_root(&self, dodag_id: IPAddr) {
+        self.is_root.set(true);
+        self.dodag_id.set(dodag_id);
+        self.version.set(self.version.get().wrapping_add(1));
+        self.rank.set(rpl_msg::ROOT_RANK);
+        self.parent.clear();
+        self.reset_trickle();
+    }
+
+    /// Starts sending DIO beacons (or DIS solicitations, if this node is not
+    /// yet attached to a DODAG).
+    pub fn start(&self) {
+        self.reset_trickle();
+    }
+
+    /// Returns the current rank of this node, which is
+    /// `rpl_msg::INFINITE_RANK` if the node is not attached to a DODAG.
+    pub fn get_rank(&self) -> u16 {
+        self.rank.get()
+    }
+
+    /// Returns the address of the preferred parent, if any.
+    pub fn get_parent(&self) -> Option<IPAddr> {
+        self.parent.map(|parent| *parent)
+    }
+
+    /// Installs a static route to `target` via the neighbor with the link
+    /// layer address `next_hop`. Returns `ENOMEM` if the routing table is
+    /// full.
+    pub fn add_route(&self, target: IPAddr, next_hop: MacAddress) -> ReturnCode {
+        let now = self.alarm.now();
+        self.update_route(target, next_hop, true, now, 0)
+    }
+
+    /// Removes the route to `target`, returning `EINVAL` if there was none.
+    pub fn remove_route(&self, target: IPAddr) -> ReturnCode {
+        self.routes.map_or(ReturnCode::EINVAL, |routes| {
+            for entry in routes.iter_mut() {
+                if entry.map_or(false, |route| route.target == target) {
+                    *entry = None;
+                    return ReturnCode::SUCCESS;
+                }
+            }
+            ReturnCode::EINVAL
+        })
+    }
+
+    fn is_joined(&self) -> bool {
+        self.rank.get() != rpl_msg::INFINITE_RANK
+    }
+
+    fn is_local(&self, addr: IPAddr) -> bool {
+        self.local_addrs.iter().any(|local| *local == addr)
+    }
+
+    fn seconds_to_tics(seconds: u32) -> u32 {
+        seconds * A::Frequency::frequency()
+    }
+
+    fn reset_trickle(&self) {
+        self.dio_doublings.set(0);
+        self.schedule_next_interval();
+    }
+
+    fn schedule_next_interval(&self) {
+        let interval = DIO_INTERVAL_MIN_S << self.dio_doublings.get();
+        let tics = self
+            .alarm
+            .now()
+            .wrapping_add(Self::seconds_to_tics(interval));
+        self.alarm.set_alarm(tics);
+    }
+
+    fn update_route(
+        &self,
+        target: IPAddr,
+        next_hop: MacAddress,
+        is_static: bool,
+        now: u32,
+        lifetime_s: u32,
+    ) -> ReturnCode {
+        let new_route = Route {
+            target: target,
+            next_hop: next_hop,
+            is_static: is_static,
+            refreshed: now,
+            lifetime_s: lifetime_s,
+        };
+        self.routes.map_or(ReturnCode::ENOMEM, |routes| {
+            // Prefer refreshing an existing route to the same target
+            let position = routes
+                .iter()
+                .position(|entry| entry.map_or(false, |route| route.target == target))
+                .or_else(|| routes.iter().position(|entry| entry.is_none()));
+            match position {
+                Some(index) => {
+                    // Never let a learned route override a static one
+                    if routes[index].map_or(false, |route| route.is_static && !is_static) {
+                        return ReturnCode::EALREADY;
+                    }
+                    routes[index] = Some(new_route);
+                    ReturnCode::SUCCESS
+                }
+                None => ReturnCode::ENOMEM,
+            }
+        })
+    }
+
+    /// Discards neighbors and routes that have not been refreshed within
+    /// their lifetimes.
+    fn expire_state(&self, now: u32) {
+        let neighbor_lifetime = Self::seconds_to_tics(NEIGHBOR_LIFETIME_S);
+        self.neighbors.map(|neighbors| {
+            for entry in neighbors.iter_mut() {
+                let expired = entry.map_or(false, |neighbor| {
+                    now.wrapping_sub(neighbor.last_heard) > neighbor_lifetime
+                });
+                if expired {
+                    *entry = None;
+                }
+            }
+        });
+        self.routes.map(|routes| {
+            for entry in routes.iter_mut() {
+                let expired = entry.map_or(false, |route| {
+                    !route.is_static
+                        && now.wrapping_sub(route.refreshed)
+                            > Self::seconds_to_tics(route.lifetime_s)
+                });
+                if expired {
+                    *entry = None;
+                }
+            }
+        });
+    }
+
+    /// Selects the preferred parent from the neighbor table and updates the
+    /// rank of this node accordingly. Returns whether the parent changed.
+    fn select_parent(&self) -> bool {
+        if self.is_root.get() {
+            return false;
+        }
+        let current = self.parent.map(|parent| *parent);
+        let (best, current_rank) = self
+            .neighbors
+            .map(|neighbors| {
+                let mut best: Option<Neighbor> = None;
+                let mut current_rank = None;
+                for neighbor in neighbors.iter().filter_map(|entry| *entry) {
+                    if Some(neighbor.addr) == current {
+                        current_rank = Some(neighbor.rank);
+                    }
+                    if neighbor.rank == rpl_msg::INFINITE_RANK {
+                        continue;
+                    }
+                    if best.map_or(true, |b| neighbor.rank < b.rank) {
+                        best = Some(neighbor);
+                    }
+                }
+                (best, current_rank)
+            })
+            .unwrap_or((None, None));
+
+        // Keep the current parent unless the best neighbor is significantly
+        // better
+        let new_parent = match (best, current_rank) {
+            (Some(best), Some(rank)) if rank != rpl_msg::INFINITE_RANK => {
+                if best.rank.saturating_add(PARENT_SWITCH_THRESHOLD) < rank {
+                    Some((best.addr, best.rank))
+                } else {
+                    current.map(|addr| (addr, rank))
+                }
+            }
+            (Some(best), _) => Some((best.addr, best.rank)),
+            (None, _) => None,
+        };
+
+        match new_parent {
+            Some((addr, rank)) => {
+                self.rank
+                    .set(rank.saturating_add(rpl_msg::MIN_HOP_RANK_INCREASE));
+                self.parent.set(addr);
+            }
+            None => {
+                self.rank.set(rpl_msg::INFINITE_RANK);
+                self.parent.clear();
+            }
+        }
+        new_parent.map(|(addr, _)| addr) != current
+    }
+
+    fn send_dio(&self) -> ReturnCode {
+        let dio = DIO {
+            instance_id: self.instance_id,
+            version: self.version.get(),
+            rank: self.rank.get(),
+            grounded: true,
+            mop: rpl_msg::MOP_STORING,
+            dtsn: self.dtsn.get(),
+            dodag_id: self.dodag_id.get(),
+        };
+        let mut body = [0 as u8; rpl_msg::DIO_BODY_LEN];
+        match dio.encode_body(&mut body).done() {
+            Some(_) => self.send_control(ALL_RPL_NODES, dio.icmp_header(), &body),
+            None => ReturnCode::FAIL,
+        }
+    }
+
+    fn send_dis(&self) -> ReturnCode {
+        self.send_control(ALL_RPL_NODES, rpl_msg::dis_header(), &[])
+    }
+
+    /// Sends a DAO to the preferred parent advertising this node and every
+    /// destination reachable through it.
+    fn send_dao(&self) -> ReturnCode {
+        let parent = match self.parent.map(|parent| *parent) {
+            Some(parent) => parent,
+            None => return ReturnCode::EOFF,
+        };
+        let mut buf = [0 as u8; DAO_MAX_LEN];
+        let mut off = 0;
+        for addr in self.local_addrs.iter() {
+            // Link-local addresses are not routable, and only one target for
+            // this node fits in the buffer
+            if !addr.is_unicast_link_local() && !addr.is_multicast() {
+                if let Some((len, _)) = DAOOption::encode_target(&mut buf[off..], addr).done() {
+                    off += len;
+                }
+                break;
+            }
+        }
+        self.routes.map(|routes| {
+            for route in routes.iter().filter_map(|entry| *entry) {
+                if let Some((len, _)) =
+                    DAOOption::encode_target(&mut buf[off..], &route.target).done()
+                {
+                    off += len;
+                }
+            }
+        });
+        let seqno = self.dao_seqno.get().wrapping_add(1);
+        self.dao_seqno.set(seqno);
+        match DAOOption::encode_transit(&mut buf[off..], seqno, DAO_PATH_LIFETIME).done() {
+            Some((len, _)) => off += len,
+            None => return ReturnCode::ESIZE,
+        }
+        let icmp_header = rpl_msg::dao_header(self.instance_id, seqno);
+        self.send_control(parent, icmp_header, &buf[..off])
+    }
+
+    fn send_control(&self, dst: IPAddr, icmp_header: ICMP6Header, body: &[u8]) -> ReturnCode {
+        if self.busy.get() {
+            return ReturnCode::EBUSY;
+        }
+        let result = self.icmp_sender.send(dst, icmp_header, body);
+        if result == ReturnCode::SUCCESS {
+            self.busy.set(true);
+        }
+        result
+    }
+
+    fn receive_dio(&self, src_addr: IPAddr, dio: DIO) {
+        if self.is_root.get() || dio.instance_id != self.instance_id {
+            return;
+        }
+        if dio.mop != rpl_msg::MOP_STORING {
+            return;
+        }
+        if self.is_joined() {
+            if dio.dodag_id != self.dodag_id.get() {
+                // Only a single DODAG is supported
+                return;
+            }
+            // A newer version of the DODAG invalidates all existing parents
+            // (global repair)
+            if (dio.version.wrapping_sub(self.version.get()) as i8) > 0 {
+                self.neighbors.map(|neighbors| {
+                    for entry in neighbors.iter_mut() {
+                        *entry = None;
+                    }
+                });
+                self.parent.clear();
+                self.version.set(dio.version);
+            } else if dio.version != self.version.get() {
+                return;
+            }
+        } else {
+            self.dodag_id.set(dio.dodag_id);
+            self.version.set(dio.version);
+        }
+
+        let now = self.alarm.now();
+        let new_neighbor = Neighbor {
+            addr: src_addr,
+            rank: dio.rank,
+            last_heard: now,
+        };
+        self.neighbors.map(|neighbors| {
+            let position = neighbors
+                .iter()
+                .position(|entry| entry.map_or(false, |n| n.addr == src_addr))
+                .or_else(|| neighbors.iter().position(|entry| entry.is_none()))
+                .or_else(|| {
+                    // Replace the worst neighbor, if the new one is better
+                    let worst = (0..MAX_NEIGHBORS)
+                        .max_by_key(|&i| neighbors[i].map_or(rpl_msg::INFINITE_RANK, |n| n.rank));
+                    worst.filter(|&i| neighbors[i].map_or(true, |n| n.rank > dio.rank))
+                });
+            position.map(|index| neighbors[index] = Some(new_neighbor));
+        });
+
+        if self.select_parent() {
+            self.dao_pending.set(true);
+            self.reset_trickle();
+        }
+    }
+
+    fn receive_dao(&self, src_addr: IPAddr, icmp_header: ICMP6Header, body: &[u8]) {
+        if !self.is_joined() || rpl_msg::dao_instance_id(&icmp_header) != Some(self.instance_id) {
+            return;
+        }
+        let next_hop = match src_addr.get_link_local_mac() {
+            Some(mac) => mac,
+            None => return,
+        };
+
+        // Targets precede the transit option that carries their lifetime, so
+        // first find the lifetime and then install every target.
+        let mut lifetime = None;
+        let mut off = 0;
+        while off < body.len() {
+            match DAOOption::decode(&body[off..]).done() {
+                Some((len, DAOOption::Transit { path_lifetime })) => {
+                    lifetime = Some(path_lifetime);
+                    off += len;
+                }
+                Some((len, _)) => off += len,
+                None => return,
+            }
+        }
+        let lifetime_s = match lifetime {
+            // A zero lifetime is a No-Path DAO, which removes the targets
+            Some(lifetime) => lifetime as u32 * ROUTE_LIFETIME_UNIT_S,
+            None => return,
+        };
+
+        let now = self.alarm.now();
+        let mut off = 0;
+        while off < body.len() {
+            match DAOOption::decode(&body[off..]).done() {
+                Some((len, DAOOption::Target(target))) => {
+                    if !self.is_local(target) {
+                        if lifetime_s == 0 {
+                            self.remove_route(target);
+                        } else {
+                            self.update_route(target, next_hop, false, now, lifetime_s);
+                        }
+                    }
+                    off += len;
+                }
+                Some((len, _)) => off += len,
+                None => return,
+            }
+        }
+
+        // Propagate the new downward routes towards the root
+        if !self.is_root.get() {
+            self.dao_pending.set(true);
+        }
+    }
+
+    fn receive_control(&self, src_addr: IPAddr, icmp_header: ICMP6Header, body: &[u8]) {
+        match icmp_header.get_code() {
+            rpl_code::DIS => {
+                // Respond to solicitations with a quick DIO
+                if self.is_joined() {
+                    self.reset_trickle();
+                }
+            }
+            rpl_code::DIO => {
+                DIO::decode(&icmp_header, body)
+                    .done()
+                    .map(|(_, dio)| self.receive_dio(src_addr, dio));
+            }
+            rpl_code::DAO => self.receive_dao(src_addr, icmp_header, body),
+            _ => {}
+        }
+    }
+
+    /// Forwards a packet that is not destined for this node towards its
+    /// destination. Packets are dropped if the node is busy sending another
+    /// packet.
+    fn forward(&self, mut ip6_header: IP6Header, payload: &[u8]) {
+        let hop_limit = ip6_header.get_hop_limit();
+        if hop_limit <= 1 || self.busy.get() {
+            return;
+        }
+        ip6_header.set_hop_limit(hop_limit - 1);
+
+        TransportHeader::decode(ip6_header.get_next_header(), payload).map(
+            |(hdr_size, transport_header)| {
+                let result = self.ip_sender.send_with_header(
+                    ip6_header,
+                    transport_header,
+                    &payload[hdr_size..],
+                );
+                if result == ReturnCode::SUCCESS {
+                    self.busy.set(true);
+                }
+            },
+        );
+    }
+}
+
+impl<A: time::Alarm> time::Client for Rpl<'a, A> {
+    fn fired(&self) {
+        let now = self.alarm.now();
+        self.expire_state(now);
+        if self.select_parent() {
+            self.dao_pending.set(true);
+            self.dio_doublings.set(0);
+        }
+
+        if self.is_joined() {
+            // Refresh downward routes once per interval
+            if !self.is_root.get() {
+                self.dao_pending.set(true);
+            }
+            if self.send_dio() != ReturnCode::SUCCESS && self.dao_pending.get() {
+                self.dao_pending.set(false);
+                self.send_dao();
+            }
+        } else {
+            self.send_dis();
+        }
+
+        if self.dio_doublings.get() < DIO_INTERVAL_DOUBLINGS {
+            self.dio_doublings.set(self.dio_doublings.get() + 1);
+        }
+        self.schedule_next_interval();
+    }
+}
+
+impl<A: time::Alarm> ICMP6SendClient for Rpl<'a, A> {
+    fn send_done(&self, _result: ReturnCode) {
+        self.busy.set(false);
+        if self.dao_pending.get() && !self.is_root.get() && self.is_joined() {
+            self.dao_pending.set(false);
+            self.send_dao();
+        }
+    }
+}
+
+impl<A: time::Alarm> IP6RecvClient for Rpl<'a, A> {
+    fn receive(&self, header: IP6Header, payload: &[u8]) {
+        if header.get_next_header() == ip6_nh::ICMP {
+            let control = ICMP6Header::decode(payload)
+                .done()
+                .and_then(|(_, icmp_header)| match icmp_header.get_type() {
+                    ICMP6Type::Type155 => Some(icmp_header),
+                    _ => None,
+                });
+            if let Some(icmp_header) = control {
+                if payload.len() >= icmp_header.get_hdr_size() {
+                    self.receive_control(
+                        header.get_src_addr(),
+                        icmp_header,
+                        &payload[icmp_header.get_hdr_size()..],
+                    );
+                }
+                return;
+            }
+        }
+
+        let dst_addr = header.get_dst_addr();
+        if dst_addr.is_multicast() || dst_addr.is_unicast_link_local() || self.is_local(dst_addr) {
+            self.client.map(|client| client.receive(header, payload));
+        } else {
+            self.forward(header, payload);
+        }
+    }
+}
+
+impl<A: time::Alarm> IP6Router for Rpl<'a, A> {
+    fn next_hop(&self, dst: IPAddr) -> Option<MacAddress> {
+        if dst.is_multicast() {
+            return None;
+        }
+        if dst.is_unicast_link_local() {
+            return dst.get_link_local_mac();
+        }
+        let route = self
+            .routes
+            .and_then(|routes| {
+                routes
+                    .iter()
+                    .filter_map(|entry| *entry)
+                    .find(|route| route.target == dst)
+            })
+            .map(|route| route.next_hop);
+        route.or_else(|| self.parent.and_then(|parent| parent.get_link_local_mac()))
+    }
+}
@@ -0,0 +1,230 @@
+//! This file contains the structs and methods used to encode and decode the
+//! subset of RPL (RFC 6550) control messages used by the RPL-lite routing
+//! layer. RPL control messages are carried as ICMPv6 messages of type 155,
+//! with the ICMPv6 code selecting the specific control message.
+//!
+//! The first four octets of each control message base are carried in the
+//! `ICMP6HeaderOptions::Type155` header word, as the `ICMP6Header` struct
+//! always represents an 8 byte header. The remainder of the message base and
+//! any options follow in the ICMPv6 payload.
+//!
+//! Only the following messages and options are supported:
+//!
+//! - DODAG Information Solicitation (DIS), with no options
+//! - DODAG Information Object (DIO), with no options
+//! - Destination Advertisement Object (DAO), in storing mode, carrying one or
+//!   more RPL Target options followed by a single Transit Information option
+
+use crate::net::icmpv6::icmpv6::{ICMP6Header, ICMP6HeaderOptions, ICMP6Type};
+use crate::net::ipv6::ip_utils::IPAddr;
+use crate::net::stream::SResult;
+use crate::net::stream::{decode_bytes, decode_u8};
+use crate::net::stream::{encode_bytes, encode_u8};
+
+/// ICMPv6 codes of the supported RPL control messages
+pub mod rpl_code {
+    pub const DIS: u8 = 0x00;
+    pub const DIO: u8 = 0x01;
+    pub const DAO: u8 = 0x02;
+}
+
+/// Option types of the supported RPL control message options
+pub mod rpl_opt {
+    pub const PAD1: u8 = 0x00;
+    pub const PADN: u8 = 0x01;
+    pub const TARGET: u8 = 0x05;
+    pub const TRANSIT: u8 = 0x06;
+}
+
+/// Rank increase per hop, as used by the Objective Function Zero
+pub const MIN_HOP_RANK_INCREASE: u16 = 256;
+/// Rank advertised by the DODAG root
+pub const ROOT_RANK: u16 = MIN_HOP_RANK_INCREASE;
+/// Rank of a node that is not attached to a DODAG
+pub const INFINITE_RANK: u16 = 0xffff;
+/// Mode of operation 2: storing mode without multicast support
+pub const MOP_STORING: u8 = 2;
+
+/// Length of the DIO base following the ICMPv6 header word
+pub const DIO_BODY_LEN: usize = 20;
+/// Length of an RPL Target option carrying a full IPv6 address
+pub const TARGET_OPT_LEN: usize = 20;
+/// Length of a storing mode Transit Information option
+pub const TRANSIT_OPT_LEN: usize = 6;
+
+/// Returns the ICMPv6 header for a DIS message. The header word contains the
+/// (zero) flags and reserved fields, followed by a zero-length PadN option to
+/// fill the header to 8 octets.
+pub fn dis_header() -> ICMP6Header {
+    let mut icmp_header = ICMP6Header::new(ICMP6Type::Type155);
+    icmp_header.set_code(rpl_code::DIS);
+    icmp_header.set_options(ICMP6HeaderOptions::Type155 {
+        base: (rpl_opt::PADN as u32) << 8,
+    });
+    icmp_header
+}
+
+/// A DODAG Information Object, which advertises a DODAG and the rank of the
+/// sender within it.
+#[derive(Copy, Clone, Debug)]
+pub struct DIO {
+    pub instance_id: u8,
+    pub version: u8,
+    pub rank: u16,
+    pub grounded: bool,
+    pub mop: u8,
+    pub dtsn: u8,
+    pub dodag_id: IPAddr,
+}
+
+impl DIO {
+    /// Returns the ICMPv6 header for this DIO, carrying the instance ID,
+    /// version and rank.
+    pub fn icmp_header(&self) -> ICMP6Header {
+        let mut icmp_header = ICMP6Header::new(ICMP6Type::Type155);
+        icmp_header.set_code(rpl_code::DIO);
+        icmp_header.set_options(ICMP6HeaderOptions::Type155 {
+            base: (self.instance_id as u32) << 24 | (self.version as u32) << 16 | self.rank as u32,
+        });
+        icmp_header
+    }
+
+    /// Serializes the remainder of the DIO base (everything following the
+    /// ICMPv6 header) into the provided buffer.
+    pub fn encode_body(&self, buf: &mut [u8]) -> SResult<usize> {
+        stream_len_cond!(buf, DIO_BODY_LEN);
+
+        let g_mop_prf = (if self.grounded { 0x80 } else { 0 }) | ((self.mop & 0x7) << 3);
+        let mut off = enc_consume!(buf, 0; encode_u8, g_mop_prf);
+        off = enc_consume!(buf, off; encode_u8, self.dtsn);
+        // Flags and reserved
+        off = enc_consume!(buf, off; encode_u8, 0);
+        off = enc_consume!(buf, off; encode_u8, 0);
+        off = enc_consume!(buf, off; encode_bytes, &self.dodag_id.0);
+        stream_done!(off, off);
+    }
+
+    /// Deserializes a DIO from a received ICMPv6 header and the ICMPv6
+    /// payload that followed it.
+    pub fn decode(icmp_header: &ICMP6Header, buf: &[u8]) -> SResult<DIO> {
+        stream_cond!(icmp_header.get_code() == rpl_code::DIO);
+        let base = match icmp_header.get_options() {
+            ICMP6HeaderOptions::Type155 { base } => base,
+            _ => stream_err!(),
+        };
+        stream_len_cond!(buf, DIO_BODY_LEN);
+
+        let (off, g_mop_prf) = dec_try!(buf, 0; decode_u8);
+        let (off, dtsn) = dec_try!(buf, off; decode_u8);
+        let mut dodag_id = IPAddr::new();
+        let off = dec_consume!(buf, off + 2; decode_bytes, &mut dodag_id.0);
+        stream_done!(
+            off,
+            DIO {
+                instance_id: (base >> 24) as u8,
+                version: (base >> 16) as u8,
+                rank: base as u16,
+                grounded: g_mop_prf & 0x80 != 0,
+                mop: (g_mop_prf >> 3) & 0x7,
+                dtsn: dtsn,
+                dodag_id: dodag_id,
+            }
+        );
+    }
+}
+
+/// Returns the ICMPv6 header for a DAO without the DODAGID field and
+/// without requesting an acknowledgement.
+pub fn dao_header(instance_id: u8, seqno: u8) -> ICMP6Header {
+    let mut icmp_header = ICMP6Header::new(ICMP6Type::Type155);
+    icmp_header.set_code(rpl_code::DAO);
+    icmp_header.set_options(ICMP6HeaderOptions::Type155 {
+        base: (instance_id as u32) << 24 | seqno as u32,
+    });
+    icmp_header
+}
+
+/// Returns the RPL instance ID of a received DAO.
+pub fn dao_instance_id(icmp_header: &ICMP6Header) -> Option<u8> {
+    match icmp_header.get_options() {
+        ICMP6HeaderOptions::Type155 { base } if icmp_header.get_code() == rpl_code::DAO => {
+            Some((base >> 24) as u8)
+        }
+        _ => None,
+    }
+}
+
+/// The options of a DAO message that are understood by RPL-lite.
+#[derive(Copy, Clone, Debug)]
+pub enum DAOOption {
+    Pad,
+    /// A destination reachable through the sender of the DAO
+    Target(IPAddr),
+    /// The lifetime, in lifetime units, of the preceding targets
+    Transit {
+        path_lifetime: u8,
+    },
+    /// Any other option, which is skipped
+    Unknown,
+}
+
+impl DAOOption {
+    /// Serializes a Target option for a full-length IPv6 address.
+    pub fn encode_target(buf: &mut [u8], target: &IPAddr) -> SResult<usize> {
+        stream_len_cond!(buf, TARGET_OPT_LEN);
+
+        let mut off = enc_consume!(buf, 0; encode_u8, rpl_opt::TARGET);
+        off = enc_consume!(buf, off; encode_u8, (TARGET_OPT_LEN - 2) as u8);
+        // Flags
+        off = enc_consume!(buf, off; encode_u8, 0);
+        // Prefix length
+        off = enc_consume!(buf, off; encode_u8, 128);
+        off = enc_consume!(buf, off; encode_bytes, &target.0);
+        stream_done!(off, off);
+    }
+
+    /// Serializes a storing mode Transit Information option.
+    pub fn encode_transit(buf: &mut [u8], path_seqno: u8, path_lifetime: u8) -> SResult<usize> {
+        stream_len_cond!(buf, TRANSIT_OPT_LEN);
+
+        let mut off = enc_consume!(buf, 0; encode_u8, rpl_opt::TRANSIT);
+        off = enc_consume!(buf, off; encode_u8, (TRANSIT_OPT_LEN - 2) as u8);
+        // Flags and path control
+        off = enc_consume!(buf, off; encode_u8, 0);
+        off = enc_consume!(buf, off; encode_u8, 0);
+        off = enc_consume!(buf, off; encode_u8, path_seqno);
+        off = enc_consume!(buf, off; encode_u8, path_lifetime);
+        stream_done!(off, off);
+    }
+
+    /// Deserializes the next option of a DAO, returning the offset of the
+    /// option following it.
+    pub fn decode(buf: &[u8]) -> SResult<DAOOption> {
+        let (off, opt_type) = dec_try!(buf, 0; decode_u8);
+        if opt_type == rpl_opt::PAD1 {
+            stream_done!(off, DAOOption::Pad);
+        }
+        let (off, opt_len) = dec_try!(buf, off; decode_u8);
+        let opt_len = opt_len as usize;
+        stream_len_cond!(buf, off + opt_len);
+
+        let option = match opt_type {
+            rpl_opt::PADN => DAOOption::Pad,
+            rpl_opt::TARGET => {
+                // Only full length targets are supported
+                stream_cond!(opt_len == TARGET_OPT_LEN - 2 && buf[off + 1] == 128);
+                let mut target = IPAddr::new();
+                dec_consume!(buf, off + 2; decode_bytes, &mut target.0);
+                DAOOption::Target(target)
+            }
+            rpl_opt::TRANSIT => {
+                stream_cond!(opt_len >= TRANSIT_OPT_LEN - 2);
+                DAOOption::Transit {
+                    path_lifetime: buf[off + 3],
+                }
+            }
+            _ => DAOOption::Unknown,
+        };
+        stream_done!(off + opt_len, option);
+    }
+}
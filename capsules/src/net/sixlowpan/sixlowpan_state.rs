@@ -231,10 +231,11 @@ use crate::net::ieee802154::{Header, KeyId, MacAddress, PanID, SecurityLevel};
 use crate::net::ipv6::ipv6::IP6Packet;
 use crate::net::sixlowpan::sixlowpan_compression;
 use crate::net::sixlowpan::sixlowpan_compression::{is_lowpan, ContextStore};
+use crate::net::stats::{counter, NetStats};
 use crate::net::util::{slice_to_u16, u16_to_slice};
 use core::cell::Cell;
 use core::cmp::min;
-use kernel::common::cells::{MapCell, TakeCell};
+use kernel::common::cells::{MapCell, OptionalCell, TakeCell};
 use kernel::common::list::{List, ListLink, ListNode};
 use kernel::hil::radio;
 use kernel::hil::time;
@@ -791,6 +792,8 @@ pub struct Sixlowpan<'a, A: time::Alarm, C: ContextStore> {
 
     // Receive state
     rx_states: List<'a, RxState<'a>>,
+
+    stats: OptionalCell<&'a NetStats>,
 }
 
 // This function is called after receiving a frame
@@ -809,6 +812,14 @@ impl<A: time::Alarm, C: ContextStore> RxClient for Sixlowpan<'a, A, C> {
             src_mac_addr,
             dst_mac_addr,
         );
+        if returncode != ReturnCode::SUCCESS {
+            let counter = if is_fragment(&buf[data_offset..data_offset + data_len]) {
+                counter::REASSEMBLY_FAILURES
+            } else {
+                counter::FRAMES_DROPPED
+            };
+            self.stats.map(|stats| stats.incr(counter));
+        }
         // Reception completed if rx_state is not None. Note that this can
         // also occur for some fail states (e.g. dropping an invalid packet)
         rx_state.map(|state| state.end_receive(self.rx_client.get(), returncode));
@@ -868,7 +879,26 @@ impl<A: time::Alarm, C: ContextStore> Sixlowpan<'a, A, C> {
             rx_client: Cell::new(None),
 
             rx_states: List::new(),
+            stats: OptionalCell::empty(),
+        }
+    }
+
+    /// Sets the network statistics that dropped frames and failed
+    /// reassemblies are counted in.
+    pub fn set_stats(&self, stats: &'a NetStats) {
+        self.stats.set(stats);
+    }
+
+    // Checks whether an `RxState` is still busy, counting reassemblies that
+    // are abandoned because they timed out.
+    fn is_rx_state_busy(&self, state: &RxState<'a>) -> bool {
+        let was_busy = state.busy.get();
+        let busy = state.is_busy(self.clock.now(), A::Frequency::frequency());
+        if was_busy && !busy {
+            self.stats
+                .map(|stats| stats.incr(counter::REASSEMBLY_FAILURES));
         }
+        busy
     }
 
     fn receive_frame(
@@ -909,7 +939,7 @@ impl<A: time::Alarm, C: ContextStore> Sixlowpan<'a, A, C> {
         let rx_state = self
             .rx_states
             .iter()
-            .find(|state| !state.is_busy(self.clock.now(), A::Frequency::frequency()));
+            .find(|state| !self.is_rx_state_busy(state));
         rx_state.map_or((None, ReturnCode::ENOMEM), |state| {
             state.start_receive(
                 src_mac_addr,
@@ -979,7 +1009,7 @@ impl<A: time::Alarm, C: ContextStore> Sixlowpan<'a, A, C> {
             rx_state = self
                 .rx_states
                 .iter()
-                .find(|state| !state.is_busy(self.clock.now(), A::Frequency::frequency()));
+                .find(|state| !self.is_rx_state_busy(state));
             // Initialize new state
             rx_state.map(|state| {
                 state.start_receive(
@@ -0,0 +1,135 @@
+//! Counters for the networking stack, and a system call driver that allows
+//! processes to read them.
+//!
+//! A single `NetStats` instance is shared by the layers of the stack. Each
+//! layer is given a reference to it with its `set_stats` method, and counts
+//! the events it sees (frames sent and received, frames dropped, CRC errors,
+//! reassembly failures, checksum errors) so that deployed devices can report
+//! the health of their network. Layers that have no `NetStats` set do not
+//! count anything.
+//!
+//! Usage
+//! -----
+//!
+//! ```
+//! let net_stats = static_init!(
+//!     capsules::net::stats::NetStats,
+//!     capsules::net::stats::NetStats::new()
+//! );
+//! framer.set_stats(net_stats);
+//! sixlowpan.set_stats(net_stats);
+//! ip_receive.set_stats(net_stats);
+//! let net_stats_driver = static_init!(
+//!     capsules::net::stats::NetStatsDriver<'static>,
+//!     capsules::net::stats::NetStatsDriver::new(net_stats)
+//! );
+//! ```
+
+use core::cell::Cell;
+use kernel::{AppId, Driver, ReturnCode};
+
+/// Syscall driver number.
+use crate::driver;
+pub const DRIVER_NUM: usize = driver::NUM::NetStats as usize;
+
+/// Indices of the counters kept by `NetStats`. These are also the counter
+/// numbers used by the system call interface, so they must not be reordered.
+pub mod counter {
+    /// 802.15.4 frames that were transmitted successfully
+    pub const FRAMES_SENT: usize = 0;
+    /// 802.15.4 frames that could not be transmitted
+    pub const FRAMES_SEND_FAILED: usize = 1;
+    /// 802.15.4 frames received with a valid CRC
+    pub const FRAMES_RECEIVED: usize = 2;
+    /// Received frames that were dropped, e.g. because the receive pipeline
+    /// was busy or the frame could not be decompressed
+    pub const FRAMES_DROPPED: usize = 3;
+    /// Received frames with an invalid CRC
+    pub const CRC_ERRORS: usize = 4;
+    /// 6LoWPAN datagrams that could not be reassembled from their fragments
+    pub const REASSEMBLY_FAILURES: usize = 5;
+    /// IPv6 packets received and passed to the upper layers
+    pub const IP_PACKETS_RECEIVED: usize = 6;
+    /// Received IPv6 packets that were malformed or failed their transport
+    /// checksum (other than UDP)
+    pub const IP_PACKETS_DROPPED: usize = 7;
+    /// Received UDP datagrams with an invalid checksum
+    pub const UDP_CHECKSUM_ERRORS: usize = 8;
+
+    pub const NUM_COUNTERS: usize = 9;
+}
+
+/// A set of counters for the events seen by the networking stack. Counters
+/// saturate rather than wrapping around.
+pub struct NetStats {
+    counters: [Cell<u32>; counter::NUM_COUNTERS],
+}
+
+impl NetStats {
+    pub fn new() -> NetStats {
+        NetStats {
+            counters: Default::default(),
+        }
+    }
+
+    /// Increments the counter with index `counter`.
+    pub fn incr(&self, counter: usize) {
+        self.counters.get(counter).map(|count| {
+            count.set(count.get().saturating_add(1));
+        });
+    }
+
+    /// Returns the value of the counter with index `counter`, or `None` if
+    /// there is no such counter.
+    pub fn get(&self, counter: usize) -> Option<u32> {
+        self.counters.get(counter).map(|count| count.get())
+    }
+
+    /// Sets all counters back to zero.
+    pub fn reset(&self) {
+        for count in self.counters.iter() {
+            count.set(0);
+        }
+    }
+}
+
+pub struct NetStatsDriver<'a> {
+    stats: &'a NetStats,
+}
+
+impl NetStatsDriver<'a> {
+    pub fn new(stats: &'a NetStats) -> NetStatsDriver<'a> {
+        NetStatsDriver { stats: stats }
+    }
+}
+
+impl Driver for NetStatsDriver<'a> {
+    /// Read the network statistics.
+    ///
+    /// ### `command_num`
+    ///
+    /// - `0`: Driver check.
+    /// - `1`: Returns the number of counters.
+    /// - `2`: Returns the value of the counter with the index given in `data`,
+    ///        as defined in `capsules::net::stats::counter`. Returns `EINVAL`
+    ///        if there is no such counter.
+    /// - `3`: Resets all counters to zero.
+    fn command(&self, command_num: usize, data: usize, _: usize, _: AppId) -> ReturnCode {
+        match command_num {
+            0 => ReturnCode::SUCCESS,
+            1 => ReturnCode::SuccessWithValue {
+                value: counter::NUM_COUNTERS,
+            },
+            2 => self.stats.get(data).map_or(ReturnCode::EINVAL, |value| {
+                ReturnCode::SuccessWithValue {
+                    value: value as usize,
+                }
+            }),
+            3 => {
+                self.stats.reset();
+                ReturnCode::SUCCESS
+            }
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+}
@@ -5,8 +5,34 @@
 //! and bind to UDP ports for receiving packets.
 //! Also exposes a list of interface addresses to the application (currently
 //! hard-coded).
+//!
+//! For on-device network debugging, the board can designate a single
+//! process that is allowed to enable promiscuous mode. In promiscuous mode
+//! that process receives every inbound UDP datagram, regardless of the port
+//! it is addressed to, along with the source and destination address and
+//! port of the datagram. Designating the process requires a
+//! `NetworkDiagnosticsCapability`:
+//!
+//! ```
+//! struct NetDiagCap;
+//! unsafe impl capabilities::NetworkDiagnosticsCapability for NetDiagCap {}
+//!
+//! // Allow the app named "netdiag" to observe all UDP traffic
+//! udp_driver.set_promiscuous_app("netdiag", &NetDiagCap);
+//! ```
+//!
+//! The board can also limit the rate at which each process transmits by
+//! setting a `RateLimiter` with `set_rate_limiter`.
+//!
+//! If the board gives the driver a `PacketPool` with `set_pool`, the same
+//! pool that the IP layer returns sent buffers to, payloads are copied from
+//! the process into a pool buffer, which is then passed down the stack and
+//! sent as is. Otherwise they are copied into the packet buffer of the IP
+//! layer.
 
+use crate::net::buffer::PacketPool;
 use crate::net::ipv6::ip_utils::IPAddr;
+use crate::net::rate_limit::RateLimiter;
 use crate::net::stream::encode_u16;
 use crate::net::stream::encode_u8;
 use crate::net::stream::SResult;
@@ -14,6 +40,8 @@ use crate::net::udp::udp_recv::{UDPReceiver, UDPRecvClient};
 use crate::net::udp::udp_send::{UDPSendClient, UDPSender};
 use core::cell::Cell;
 use core::{cmp, mem};
+use kernel::capabilities::NetworkDiagnosticsCapability;
+use kernel::common::cells::OptionalCell;
 use kernel::{debug, AppId, AppSlice, Callback, Driver, Grant, ReturnCode, Shared};
 
 /// Syscall number
@@ -64,6 +92,7 @@ pub struct App {
     app_rx_cfg: Option<AppSlice<Shared, u8>>,
     pending_tx: Option<[UDPEndpoint; 2]>,
     bound_port: Option<UDPEndpoint>,
+    promiscuous: bool,
 }
 
 #[allow(dead_code)]
@@ -84,6 +113,15 @@ pub struct UDPDriver<'a> {
 
     /// Maximum length payload that an app can transmit via this driver
     max_tx_pyld_len: usize,
+
+    /// Package name of the process that is allowed to enable promiscuous mode
+    promiscuous_app: OptionalCell<&'static str>,
+
+    /// Per-process transmission rate limits
+    rate_limiter: OptionalCell<&'a RateLimiter>,
+
+    /// Pool of the buffers that payloads are sent from
+    pool: OptionalCell<&'a PacketPool<'a>>,
 }
 
 impl<'a> UDPDriver<'a> {
@@ -101,9 +139,64 @@ impl<'a> UDPDriver<'a> {
             current_app: Cell::new(None),
             interface_list: interface_list,
             max_tx_pyld_len: max_tx_pyld_len,
+            promiscuous_app: OptionalCell::empty(),
+            rate_limiter: OptionalCell::empty(),
+            pool: OptionalCell::empty(),
         }
     }
 
+    /// Sets the pool that payloads are sent from. It must be the pool of the
+    /// IP layer, which buffers are returned to once they are sent.
+    pub fn set_pool(&self, pool: &'a PacketPool<'a>) {
+        self.pool.set(pool);
+    }
+
+    /// Sends `payload` from a pool buffer if the driver has a pool, or
+    /// through the packet buffer of the IP layer otherwise.
+    fn send_payload(
+        &self,
+        dst_addr: IPAddr,
+        dst_port: u16,
+        src_port: u16,
+        payload: &[u8],
+    ) -> ReturnCode {
+        self.pool.map_or_else(
+            || self.sender.send_to(dst_addr, dst_port, src_port, payload),
+            |pool| {
+                let mut buf = match pool.alloc(0) {
+                    Some(buf) => buf,
+                    None => return ReturnCode::EBUSY,
+                };
+                let result = buf.append(payload);
+                if result != ReturnCode::SUCCESS {
+                    pool.free(buf);
+                    return result;
+                }
+                let (result, unsent) = self.sender.send_buffer(dst_addr, dst_port, src_port, buf);
+                unsent.map(|buf| pool.free(buf));
+                result
+            },
+        )
+    }
+
+    /// Sets the limiter that every transmission is charged to. Without one,
+    /// processes can transmit as fast as the network stack allows.
+    pub fn set_rate_limiter(&self, rate_limiter: &'a RateLimiter) {
+        self.rate_limiter.set(rate_limiter);
+    }
+
+    /// Designates the process with package name `app_name` as the only
+    /// process that may enable promiscuous mode and observe all inbound UDP
+    /// datagrams. The name comes from the app's TBF header, so it does not
+    /// depend on the order in which the apps are loaded.
+    pub fn set_promiscuous_app(
+        &self,
+        app_name: &'static str,
+        _capability: &NetworkDiagnosticsCapability,
+    ) {
+        self.promiscuous_app.set(app_name);
+    }
+
     /// Utility function to perform an action on an app in a system call.
     #[inline]
     fn do_with_app<F>(&self, appid: AppId, closure: F) -> ReturnCode
@@ -241,13 +334,12 @@ impl<'a> UDPDriver<'a> {
             let dst_port = addr_ports[1].port;
             let src_port = addr_ports[0].port;
 
-            // Send UDP payload. Payload will be copied into IP6Packet in kernel mem.
+            // Send UDP payload. Payload will be copied into kernel mem.
             let result = app
                 .app_write
                 .as_ref()
                 .map_or(ReturnCode::ENOMEM, |payload| {
-                    self.sender
-                        .send_to(dst_addr, dst_port, src_port, payload.as_ref())
+                    self.send_payload(dst_addr, dst_port, src_port, payload.as_ref())
                 });
             if result == ReturnCode::SUCCESS {
                 self.current_app.set(Some(appid));
@@ -348,8 +440,8 @@ impl<'a> Driver for UDPDriver<'a> {
     /// ### `subscribe_num`
     ///
     /// - `0`: Setup callback for when packet is received. If no port has
-    ///        been bound, return ERESERVE to indicate that port binding is
-    ///        is a prerequisite to reception.
+    ///        been bound and promiscuous mode is not enabled, return ERESERVE
+    ///        to indicate that port binding is is a prerequisite to reception.
     /// - `1`: Setup callback for when packet is transmitted. Notably,
     ///        this callback receives the result of the send_done callback
     ///        from udp_send.rs, which does not currently pass information
@@ -362,7 +454,7 @@ impl<'a> Driver for UDPDriver<'a> {
     ) -> ReturnCode {
         match subscribe_num {
             0 => self.do_with_app(app_id, |app| {
-                if app.bound_port.is_some() {
+                if app.bound_port.is_some() || app.promiscuous {
                     app.rx_callback = callback;
                     ReturnCode::SUCCESS
                 } else {
@@ -403,6 +495,8 @@ impl<'a> Driver for UDPDriver<'a> {
     ///        Currently, only will transmit if the app has bound to the port passed in the tx_cfg
     ///        buf as the source address. If no port is bound, returns ERESERVE, if it tries to
     ///        send on a port other than the port which is bound, returns EINVALID.
+    ///        If the board has set a rate limit for the app and the payload exceeds what the
+    ///        app may currently send, returns EBUSY without queueing the packet.
     ///
     ///        Notably, the currently transmit implementation allows for starvation - an
     ///        an app with a lower app id can send constantly and starve an app with a
@@ -423,6 +517,11 @@ impl<'a> Driver for UDPDriver<'a> {
     /// - `4`: Returns the maximum payload that can be transmitted by apps using this driver.
     ///        This represents the size of the payload buffer in the kernel. Apps can use this
     ///        syscall to ensure they do not attempt to send too-large messages.
+    /// - `5`: Enable (`arg1` != 0) or disable (`arg1` == 0) promiscuous mode.
+    ///        In promiscuous mode the app receives every inbound datagram, and
+    ///        the destination address and port are written to rx_cfg after the
+    ///        source address and port. Returns ENOSUPPORT if the app has not
+    ///        been designated by the board as allowed to use promiscuous mode.
 
     fn command(&self, command_num: usize, arg1: usize, _: usize, appid: AppId) -> ReturnCode {
         match command_num {
@@ -475,6 +574,13 @@ impl<'a> Driver for UDPDriver<'a> {
                     if next_tx.is_none() {
                         return ReturnCode::EINVAL;
                     }
+                    let len = app.app_write.as_ref().map_or(0, |payload| payload.len());
+                    if !self
+                        .rate_limiter
+                        .map_or(true, |limiter| limiter.consume(appid.idx(), len))
+                    {
+                        return ReturnCode::EBUSY;
+                    }
                     app.pending_tx = next_tx;
                     self.do_next_tx_immediate(appid)
                 })
@@ -544,6 +650,21 @@ impl<'a> Driver for UDPDriver<'a> {
             4 => ReturnCode::SuccessWithValue {
                 value: self.max_tx_pyld_len,
             },
+            5 => {
+                if !self
+                    .promiscuous_app
+                    .map_or(false, |name| *name == appid.get_process_name())
+                {
+                    return ReturnCode::ENOSUPPORT;
+                }
+                self.do_with_app(appid, |app| {
+                    app.promiscuous = arg1 != 0;
+                    if !app.promiscuous && app.bound_port.is_none() {
+                        app.rx_callback = None;
+                    }
+                    ReturnCode::SUCCESS
+                })
+            }
             _ => ReturnCode::ENOSUPPORT,
         }
     }
@@ -572,10 +693,10 @@ impl<'a> UDPRecvClient for UDPDriver<'a> {
         payload: &[u8],
     ) {
         self.apps.each(|app| {
-            if app.bound_port.is_some() {
+            if app.bound_port.is_some() || app.promiscuous {
                 let appid = app.appid();
                 self.do_with_app(app.appid(), |app| {
-                    let mut for_me = false;
+                    let mut for_me = app.promiscuous;
                     app.bound_port.as_ref().map(|requested_addr| {
                         if requested_addr.addr == dst_addr && requested_addr.port == dst_port {
                             for_me = true;
@@ -596,8 +717,17 @@ impl<'a> UDPRecvClient for UDPDriver<'a> {
                                     port: src_port,
                                 };
                                 let cfg_len = 2 * mem::size_of::<UDPEndpoint>();
+                                let promiscuous = app.promiscuous;
                                 self.do_with_rx_cfg_mut(appid, cfg_len, |cfg| {
                                     sender_addr.encode(cfg, 0);
+                                    if promiscuous {
+                                        // Also tell the app where the packet was going
+                                        let dst = UDPEndpoint {
+                                            addr: dst_addr,
+                                            port: dst_port,
+                                        };
+                                        dst.encode(cfg, mem::size_of::<UDPEndpoint>());
+                                    }
                                     ReturnCode::SUCCESS
                                 });
                                 app.rx_callback.map(|mut cb| cb.schedule(len, 0, 0));
@@ -5,8 +5,9 @@
 //! upper layer to allow them to receive the `send_done` callback once
 //! transmission has completed.
 
+use crate::net::buffer::PacketBuffer;
 use crate::net::ipv6::ip_utils::IPAddr;
-use crate::net::ipv6::ipv6::TransportHeader;
+use crate::net::ipv6::ipv6::{IP6Header, TransportHeader};
 use crate::net::ipv6::ipv6_send::{IP6SendClient, IP6Sender};
 use crate::net::udp::udp::UDPHeader;
 use kernel::common::cells::OptionalCell;
@@ -59,6 +60,28 @@ pub trait UDPSender<'a> {
     /// Returns any synchronous errors or success. Note that any asynchrounous
     /// errors are returned via the callback.
     fn send(&self, dest: IPAddr, udp_header: UDPHeader, buf: &[u8]) -> ReturnCode;
+
+    /// This function constructs a `UDPHeader` and sends the payload held in
+    /// a `PacketBuffer` to the provided destination IP address, without
+    /// copying it into the buffer of the IP layer.
+    ///
+    /// # Arguments
+    /// `dest` - IPv6 address to send the UDP packet to
+    /// `dst_port` - Destination port to send the packet to
+    /// `src_port` - Port to send the packet from
+    /// `buf` - A buffer containing the UDP payload
+    ///
+    /// # Return Value
+    /// As for `IP6Sender::send_buffer`: once the packet is sent, the buffer
+    /// is returned to the `PacketPool` of the IP layer. If it is not sent,
+    /// the buffer is returned along with the error.
+    fn send_buffer(
+        &self,
+        dest: IPAddr,
+        dst_port: u16,
+        src_port: u16,
+        buf: PacketBuffer,
+    ) -> (ReturnCode, Option<PacketBuffer>);
 }
 
 /// This is a specific instantiation of the `UDPSender` trait. Note
@@ -89,6 +112,24 @@ impl<T: IP6Sender<'a>> UDPSender<'a> for UDPSendStruct<'a, T> {
         let transport_header = TransportHeader::UDP(udp_header);
         self.ip_send_struct.send_to(dest, transport_header, buf)
     }
+
+    fn send_buffer(
+        &self,
+        dest: IPAddr,
+        dst_port: u16,
+        src_port: u16,
+        buf: PacketBuffer,
+    ) -> (ReturnCode, Option<PacketBuffer>) {
+        let mut udp_header = UDPHeader::new();
+        udp_header.set_dst_port(dst_port);
+        udp_header.set_src_port(src_port);
+        // The IP layer fills in the source address, and the checksum,
+        // since it is left unspecified here
+        let mut ip6_header = IP6Header::default();
+        ip6_header.dst_addr = dest;
+        self.ip_send_struct
+            .send_buffer(ip6_header, TransportHeader::UDP(udp_header), buf)
+    }
 }
 
 impl<T: IP6Sender<'a>> UDPSendStruct<'a, T> {
@@ -0,0 +1,546 @@
+//! Emulates an NFC Forum Type 4 tag, to hand NDEF messages to phones.
+//!
+//! A phone held to the device reads the NDEF message that processes give the
+//! capsule, e.g. a Bluetooth pairing record or a URL, and may write a message
+//! of its own, e.g. the credentials of a network to provision the device
+//! with. Processes are told when a reader's field comes and goes, when the
+//! message was read and when a new one was written.
+//!
+//! The capsule runs on a `hil::nfc::NfcTag` and implements the ISO-DEP
+//! (ISO/IEC 14443-4) blocks of the tag and the commands of the Type 4 tag
+//! NDEF application: the selection of the application and of its capability
+//! container and NDEF files, and the reading and writing of the files. The
+//! NDEF file, the length of the message followed by the message, is kept in
+//! the buffer given to the capsule, which also bounds the length of messages.
+//! Chained blocks are not supported; the capability container limits the
+//! commands of readers to frames that fit in `FRAME_SIZE`.
+//!
+//! Usage
+//! -----
+//!
+//! ```rust
+//! let nfc = static_init!(
+//!     capsules::nfc_t4t::NfcT4t<'static>,
+//!     capsules::nfc_t4t::NfcT4t::new(
+//!         &nrf52::nfct::NFCT,
+//!         &mut capsules::nfc_t4t::RX_BUFFER,
+//!         &mut capsules::nfc_t4t::TX_BUFFER,
+//!         &mut capsules::nfc_t4t::NDEF_FILE,
+//!         kernel::Grant::create()
+//!     )
+//! );
+//! kernel::hil::nfc::NfcTag::set_client(&nrf52::nfct::NFCT, nfc);
+//! ```
+//!
+//! Syscall Interface
+//! -----------------
+//!
+//! - Stability: 1 - Experimental
+//!
+//! ### Allow
+//!
+//! - `0`: The buffer NDEF messages are copied from and into.
+//!
+//! ### Subscribe
+//!
+//! - `0`: Called with `(event, length)` on the events of the tag: 0 when a
+//!        reader's field is detected, 1 when it is lost, 2 when a reader has
+//!        read the whole NDEF message, and 3 when it has written a message of
+//!        `length` bytes.
+//!
+//! ### Command
+//!
+//! - `0`: Driver check.
+//! - `1`: Sets the NDEF message to the first `data` bytes of the allowed
+//!        buffer.
+//! - `2`: Copies the NDEF message into the allowed buffer, and returns its
+//!        length.
+//! - `3`: Enables the tag.
+//! - `4`: Disables the tag.
+//! - `5`: Makes the NDEF message read-only to readers if `data` is 1, or
+//!        writable if it is 0.
+
+use core::cell::Cell;
+use core::cmp;
+use kernel::common::cells::TakeCell;
+use kernel::hil::nfc;
+use kernel::{AppId, AppSlice, Callback, Driver, Grant, ReturnCode, Shared};
+
+/// Syscall driver number.
+use crate::driver;
+pub const DRIVER_NUM: usize = driver::NUM::Nfc as usize;
+
+/// The longest frame the tag receives, as advertised in its ATS, without the
+/// CRC
+pub const FRAME_SIZE: usize = 62;
+
+pub static mut RX_BUFFER: [u8; FRAME_SIZE] = [0; FRAME_SIZE];
+pub static mut TX_BUFFER: [u8; FRAME_SIZE] = [0; FRAME_SIZE];
+
+/// The NDEF file: a 2 byte big endian length and the message
+pub static mut NDEF_FILE: [u8; 256] = [0; 256];
+
+/// The answer to RATS: 64 byte frames, 106 kbps only, a frame waiting time of
+/// about 38 ms, and no CID or NAD
+const ATS: [u8; 5] = [0x05, 0x75, 0x80, 0x70, 0x00];
+
+/// The name of the NDEF application
+const NDEF_APPLICATION: [u8; 7] = [0xD2, 0x76, 0x00, 0x00, 0x85, 0x01, 0x01];
+const CC_FILE_ID: [u8; 2] = [0xE1, 0x03];
+const NDEF_FILE_ID: [u8; 2] = [0xE1, 0x04];
+const CC_FILE_SIZE: usize = 15;
+
+/// The longest data of READ BINARY and UPDATE BINARY, so that the frames
+/// carrying them fit in `FRAME_SIZE`
+const MAX_LE: usize = FRAME_SIZE - 3;
+const MAX_LC: usize = FRAME_SIZE - 6;
+
+mod pcb {
+    pub const RATS: u8 = 0xE0;
+    pub const DESELECT: u8 = 0xC2;
+    pub const BLOCK_NUMBER: u8 = 0x01;
+    pub const CHAINING: u8 = 0x10;
+
+    pub fn is_i_block(pcb: u8) -> bool {
+        pcb & 0xE2 == 0x02
+    }
+
+    pub fn is_r_block(pcb: u8) -> bool {
+        pcb & 0xE6 == 0xA2
+    }
+}
+
+mod ins {
+    pub const SELECT: u8 = 0xA4;
+    pub const READ_BINARY: u8 = 0xB0;
+    pub const UPDATE_BINARY: u8 = 0xD6;
+}
+
+mod status {
+    pub const OK: u16 = 0x9000;
+    pub const WRONG_LENGTH: u16 = 0x6700;
+    pub const SECURITY_STATUS: u16 = 0x6982;
+    pub const NO_CURRENT_FILE: u16 = 0x6986;
+    pub const NOT_FOUND: u16 = 0x6A82;
+    pub const WRONG_OFFSET: u16 = 0x6B00;
+    pub const INS_NOT_SUPPORTED: u16 = 0x6D00;
+    pub const CLA_NOT_SUPPORTED: u16 = 0x6E00;
+}
+
+mod event {
+    pub const FIELD_DETECTED: usize = 0;
+    pub const FIELD_LOST: usize = 1;
+    pub const NDEF_READ: usize = 2;
+    pub const NDEF_WRITTEN: usize = 3;
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum File {
+    None,
+    CapabilityContainer,
+    Ndef,
+}
+
+#[derive(Default)]
+pub struct App {
+    callback: Option<Callback>,
+    buffer: Option<AppSlice<Shared, u8>>,
+}
+
+pub struct NfcT4t<'a> {
+    tag: &'a nfc::NfcTag,
+    rx_buffer: TakeCell<'static, [u8]>,
+    tx_buffer: TakeCell<'static, [u8]>,
+    ndef: TakeCell<'static, [u8]>,
+    enabled: Cell<bool>,
+    read_only: Cell<bool>,
+    application_selected: Cell<bool>,
+    file: Cell<File>,
+    /// The length of the last response, to send it again when the reader
+    /// asks for it
+    response_len: Cell<usize>,
+    /// Whether the reader deselected the tag, which sleeps once it has
+    /// answered
+    deselected: Cell<bool>,
+    apps: Grant<App>,
+}
+
+impl NfcT4t<'a> {
+    pub fn new(
+        tag: &'a nfc::NfcTag,
+        rx_buffer: &'static mut [u8],
+        tx_buffer: &'static mut [u8],
+        ndef: &'static mut [u8],
+        grant: Grant<App>,
+    ) -> NfcT4t<'a> {
+        NfcT4t {
+            tag: tag,
+            rx_buffer: TakeCell::new(rx_buffer),
+            tx_buffer: TakeCell::new(tx_buffer),
+            ndef: TakeCell::new(ndef),
+            enabled: Cell::new(false),
+            read_only: Cell::new(false),
+            application_selected: Cell::new(false),
+            file: Cell::new(File::None),
+            response_len: Cell::new(0),
+            deselected: Cell::new(false),
+            apps: grant,
+        }
+    }
+
+    fn enable(&self) -> ReturnCode {
+        if self.enabled.get() {
+            return ReturnCode::EALREADY;
+        }
+        // The reception buffer stays with the tag until it is enabled again
+        if let Some(buffer) = self.rx_buffer.take() {
+            let (result, buffer) = self.tag.receive(buffer);
+            buffer.map(|buffer| self.rx_buffer.replace(buffer));
+            if result != ReturnCode::SUCCESS {
+                return result;
+            }
+        }
+        let result = self.tag.enable();
+        if result == ReturnCode::SUCCESS {
+            self.enabled.set(true);
+        }
+        result
+    }
+
+    fn disable(&self) -> ReturnCode {
+        if !self.enabled.get() {
+            return ReturnCode::EALREADY;
+        }
+        self.enabled.set(false);
+        self.reset();
+        self.tag.disable()
+    }
+
+    fn reset(&self) {
+        self.application_selected.set(false);
+        self.file.set(File::None);
+        self.response_len.set(0);
+        self.deselected.set(false);
+    }
+
+    fn notify(&self, event: usize, len: usize) {
+        self.apps.each(|app| {
+            app.callback.map(|mut cb| cb.schedule(event, len, 0));
+        });
+    }
+
+    /// The length of the NDEF file, which includes the length of the message
+    fn ndef_file_size(&self) -> usize {
+        self.ndef.map_or(0, |ndef| cmp::min(ndef.len(), 0xFFFF))
+    }
+
+    fn message_len(ndef: &[u8]) -> usize {
+        ((ndef[0] as usize) << 8) | ndef[1] as usize
+    }
+
+    fn capability_container(&self) -> [u8; CC_FILE_SIZE] {
+        let size = self.ndef_file_size();
+        let write_access = if self.read_only.get() { 0xFF } else { 0x00 };
+        [
+            0x00,
+            CC_FILE_SIZE as u8,
+            // Mapping version 2.0
+            0x20,
+            (MAX_LE >> 8) as u8,
+            MAX_LE as u8,
+            (MAX_LC >> 8) as u8,
+            MAX_LC as u8,
+            // The NDEF file control TLV
+            0x04,
+            0x06,
+            NDEF_FILE_ID[0],
+            NDEF_FILE_ID[1],
+            (size >> 8) as u8,
+            size as u8,
+            0x00,
+            write_access,
+        ]
+    }
+
+    /// Answers a block from the reader into `response`, and returns the
+    /// length of the answer, or 0 if there is none.
+    fn answer_block(&self, block: &[u8], response: &mut [u8]) -> usize {
+        let pcb = block[0];
+        if pcb == pcb::RATS {
+            self.reset();
+            response[..ATS.len()].copy_from_slice(&ATS);
+            ATS.len()
+        } else if pcb == pcb::DESELECT {
+            self.deselected.set(true);
+            response[0] = pcb::DESELECT;
+            1
+        } else if pcb::is_r_block(pcb) {
+            // The reader missed the last response
+            self.response_len.get()
+        } else if pcb::is_i_block(pcb) && pcb & pcb::CHAINING == 0 {
+            response[0] = 0x02 | (pcb & pcb::BLOCK_NUMBER);
+            let (status, len) = self.answer_apdu(&block[1..], &mut response[1..]);
+            response[1 + len] = (status >> 8) as u8;
+            response[2 + len] = status as u8;
+            3 + len
+        } else {
+            0
+        }
+    }
+
+    /// Answers a command APDU, writing the data of the answer into
+    /// `response`. Returns the status word and the length of the data.
+    fn answer_apdu(&self, apdu: &[u8], response: &mut [u8]) -> (u16, usize) {
+        if apdu.len() < 4 {
+            return (status::WRONG_LENGTH, 0);
+        }
+        if apdu[0] != 0x00 {
+            return (status::CLA_NOT_SUPPORTED, 0);
+        }
+        let (p1, p2) = (apdu[2], apdu[3]);
+        let offset = ((p1 as usize) << 8) | p2 as usize;
+        let data = match apdu.get(4) {
+            Some(&lc) if apdu.len() >= 5 + lc as usize => &apdu[5..5 + lc as usize],
+            _ => &[],
+        };
+
+        match apdu[1] {
+            ins::SELECT => (self.select(p1, data), 0),
+            ins::READ_BINARY => {
+                // Le is the last byte, and 0 for as much as possible
+                let le = match apdu.get(4) {
+                    Some(&le) if le != 0 => le as usize,
+                    _ => MAX_LE,
+                };
+                self.read_binary(offset, cmp::min(le, MAX_LE), response)
+            }
+            ins::UPDATE_BINARY => (self.update_binary(offset, data), 0),
+            _ => (status::INS_NOT_SUPPORTED, 0),
+        }
+    }
+
+    fn select(&self, p1: u8, name: &[u8]) -> u16 {
+        match p1 {
+            // By name
+            0x04 => {
+                let found = name == NDEF_APPLICATION;
+                self.application_selected.set(found);
+                self.file.set(File::None);
+                if found {
+                    status::OK
+                } else {
+                    status::NOT_FOUND
+                }
+            }
+            // By file identifier
+            0x00 if self.application_selected.get() => {
+                if name == CC_FILE_ID {
+                    self.file.set(File::CapabilityContainer);
+                    status::OK
+                } else if name == NDEF_FILE_ID {
+                    self.file.set(File::Ndef);
+                    status::OK
+                } else {
+                    status::NOT_FOUND
+                }
+            }
+            _ => status::NOT_FOUND,
+        }
+    }
+
+    fn read_binary(&self, offset: usize, le: usize, response: &mut [u8]) -> (u16, usize) {
+        let cc = self.capability_container();
+        let mut read = |file: &[u8]| {
+            if offset > file.len() {
+                return (status::WRONG_OFFSET, 0);
+            }
+            let len = cmp::min(le, file.len() - offset);
+            response[..len].copy_from_slice(&file[offset..offset + len]);
+            (status::OK, len)
+        };
+
+        match self.file.get() {
+            File::None => (status::NO_CURRENT_FILE, 0),
+            File::CapabilityContainer => read(&cc),
+            File::Ndef => self.ndef.map_or((status::NOT_FOUND, 0), |ndef| {
+                let ndef = &ndef[..self.ndef_file_size()];
+                let (status, len) = read(ndef);
+                // The message is read once its last byte is
+                let end = 2 + NfcT4t::message_len(ndef);
+                if len > 0 && offset < end && offset + len >= end {
+                    self.notify(event::NDEF_READ, end - 2);
+                }
+                (status, len)
+            }),
+        }
+    }
+
+    fn update_binary(&self, offset: usize, data: &[u8]) -> u16 {
+        if self.file.get() != File::Ndef || self.read_only.get() {
+            return status::SECURITY_STATUS;
+        }
+        let size = self.ndef_file_size();
+        if offset + data.len() > size {
+            return status::WRONG_OFFSET;
+        }
+        self.ndef.map_or(status::NOT_FOUND, |ndef| {
+            ndef[offset..offset + data.len()].copy_from_slice(data);
+            // Writers clear the length, write the message and then write
+            // its length, so the message is complete once it is not 0
+            if offset < 2 && offset + data.len() >= 2 {
+                let len = NfcT4t::message_len(ndef);
+                if len != 0 && 2 + len <= size {
+                    self.notify(event::NDEF_WRITTEN, len);
+                }
+            }
+            status::OK
+        })
+    }
+
+    /// Copies the first `len` bytes of `slice` in as the NDEF message.
+    fn set_message(&self, slice: &AppSlice<Shared, u8>, len: usize) -> ReturnCode {
+        if len > slice.len() || 2 + len > self.ndef_file_size() {
+            return ReturnCode::ESIZE;
+        }
+        self.ndef.map_or(ReturnCode::ENOMEM, |ndef| {
+            ndef[0] = (len >> 8) as u8;
+            ndef[1] = len as u8;
+            ndef[2..2 + len].copy_from_slice(&slice.as_ref()[..len]);
+            ReturnCode::SUCCESS
+        })
+    }
+
+    /// Copies the NDEF message into `slice`, and returns its length.
+    fn get_message(&self, slice: &mut AppSlice<Shared, u8>) -> ReturnCode {
+        let size = self.ndef_file_size();
+        self.ndef.map_or(ReturnCode::ENOMEM, |ndef| {
+            let len = NfcT4t::message_len(ndef);
+            if 2 + len > size {
+                return ReturnCode::FAIL;
+            }
+            if len > slice.len() {
+                return ReturnCode::ESIZE;
+            }
+            slice.as_mut()[..len].copy_from_slice(&ndef[2..2 + len]);
+            ReturnCode::SuccessWithValue { value: len }
+        })
+    }
+}
+
+impl nfc::Client for NfcT4t<'a> {
+    fn field_detected(&self) {
+        self.notify(event::FIELD_DETECTED, 0);
+    }
+
+    fn field_lost(&self) {
+        self.reset();
+        self.notify(event::FIELD_LOST, 0);
+    }
+
+    fn selected(&self) {
+        self.reset();
+    }
+
+    fn frame_received(&self, buffer: &'static mut [u8], len: usize, result: ReturnCode) {
+        // Erroneous frames are ignored, and the reader sends them again
+        if result == ReturnCode::SUCCESS && len > 0 {
+            self.tx_buffer.take().map(|tx_buffer| {
+                let response_len = self.answer_block(&buffer[..len], tx_buffer);
+                if response_len == 0 {
+                    self.tx_buffer.replace(tx_buffer);
+                    return;
+                }
+                self.response_len.set(response_len);
+                let (_, tx_buffer) = self.tag.transmit(tx_buffer, response_len);
+                tx_buffer.map(|tx_buffer| self.tx_buffer.replace(tx_buffer));
+            });
+        }
+
+        let (_, buffer) = self.tag.receive(buffer);
+        buffer.map(|buffer| self.rx_buffer.replace(buffer));
+    }
+
+    fn frame_transmitted(&self, buffer: &'static mut [u8], _result: ReturnCode) {
+        self.tx_buffer.replace(buffer);
+        if self.deselected.get() {
+            self.reset();
+            self.tag.sleep();
+        }
+    }
+}
+
+impl Driver for NfcT4t<'a> {
+    fn allow(
+        &self,
+        appid: AppId,
+        allow_num: usize,
+        slice: Option<AppSlice<Shared, u8>>,
+    ) -> ReturnCode {
+        match allow_num {
+            0 => self
+                .apps
+                .enter(appid, |app, _| {
+                    app.buffer = slice;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or_else(|err| err.into()),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    fn subscribe(
+        &self,
+        subscribe_num: usize,
+        callback: Option<Callback>,
+        app_id: AppId,
+    ) -> ReturnCode {
+        match subscribe_num {
+            0 => self
+                .apps
+                .enter(app_id, |app, _| {
+                    app.callback = callback;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or_else(|err| err.into()),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    fn command(&self, command_num: usize, data: usize, _: usize, appid: AppId) -> ReturnCode {
+        match command_num {
+            0 /* check if present */ => ReturnCode::SUCCESS,
+
+            1 => self
+                .apps
+                .enter(appid, |app, _| {
+                    app.buffer
+                        .as_ref()
+                        .map_or(ReturnCode::ENOMEM, |slice| self.set_message(slice, data))
+                })
+                .unwrap_or_else(|err| err.into()),
+
+            2 => self
+                .apps
+                .enter(appid, |app, _| {
+                    app.buffer
+                        .as_mut()
+                        .map_or(ReturnCode::ENOMEM, |slice| self.get_message(slice))
+                })
+                .unwrap_or_else(|err| err.into()),
+
+            3 => self.enable(),
+
+            4 => self.disable(),
+
+            5 => match data {
+                0 | 1 => {
+                    self.read_only.set(data == 1);
+                    ReturnCode::SUCCESS
+                }
+                _ => ReturnCode::EINVAL,
+            },
+
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+}
@@ -0,0 +1,296 @@
+//! Provides userspace with access to a radio that sends and receives raw
+//! packets, with a data rate, sync word and channel of its choosing, to talk
+//! to devices with proprietary protocols.
+//!
+//! The physical layer is shared by all processes: a process that changes it
+//! changes it for the others too. One packet is sent at a time; a process
+//! that sends while another one's packet is being sent gets `EBUSY`. Every
+//! packet received is given to all the processes that listen.
+//!
+//! Usage
+//! -----
+//!
+//! You need a device that provides the `hil::packet_radio::PacketRadio`
+//! trait.
+//!
+//! ```rust
+//! let packet_radio = static_init!(
+//!     capsules::packet_radio::PacketRadioDriver<'static>,
+//!     capsules::packet_radio::PacketRadioDriver::new(
+//!         &cc26x2::rfc::prop::RADIO,
+//!         board_kernel.create_grant(&grant_cap),
+//!         &mut capsules::packet_radio::TX_BUF,
+//!         &mut capsules::packet_radio::RX_BUF
+//!     )
+//! );
+//! cc26x2::rfc::prop::RADIO.set_transmit_client(packet_radio);
+//! cc26x2::rfc::prop::RADIO.set_receive_client(packet_radio);
+//! ```
+//!
+//! Syscall Interface
+//! -----------------
+//!
+//! ### `allow`
+//!
+//! - `0`: The packet to send.
+//! - `1`: The buffer that received packets are written into.
+//!
+//! ### `subscribe`
+//!
+//! - `0`: Called when a packet is sent, with its `ReturnCode`.
+//! - `1`: Called when a packet is received, with its length and its RSSI in
+//!   dBm. Packets longer than the buffer are cut short.
+//!
+//! ### `command`
+//!
+//! - `0`: Whether the driver exists.
+//! - `1`: Send the first `arg1` bytes of the packet buffer.
+//! - `2`: Listen for packets.
+//! - `3`: Stop listening.
+//! - `4`: Set the data rate to `arg1` bits per second.
+//! - `5`: Set the channel to `arg1`.
+//! - `6`: Set the sync word to `arg1`.
+//! - `7`: The longest packet the radio sends and receives.
+
+use core::cmp;
+use kernel::common::cells::{OptionalCell, TakeCell};
+use kernel::hil::packet_radio::{self, PacketRadio};
+use kernel::{AppId, AppSlice, Callback, Driver, Grant, ReturnCode, Shared};
+
+/// Syscall driver number.
+use crate::driver;
+pub const DRIVER_NUM: usize = driver::NUM::PacketRadio as usize;
+
+/// Buffers for the radio, as long as the longest packet it sends or
+/// receives.
+pub static mut TX_BUF: [u8; 255] = [0; 255];
+pub static mut RX_BUF: [u8; 255] = [0; 255];
+
+#[derive(Default)]
+pub struct App {
+    tx_callback: Option<Callback>,
+    rx_callback: Option<Callback>,
+    tx_buffer: Option<AppSlice<Shared, u8>>,
+    rx_buffer: Option<AppSlice<Shared, u8>>,
+    listening: bool,
+}
+
+pub struct PacketRadioDriver<'a> {
+    radio: &'a PacketRadio,
+    apps: Grant<App>,
+    /// The app whose packet is being sent
+    tx_app: OptionalCell<AppId>,
+    tx_buf: TakeCell<'static, [u8]>,
+    /// The receive buffer, while the radio does not have it
+    rx_buf: TakeCell<'static, [u8]>,
+}
+
+impl PacketRadioDriver<'a> {
+    pub fn new(
+        radio: &'a PacketRadio,
+        grant: Grant<App>,
+        tx_buf: &'static mut [u8],
+        rx_buf: &'static mut [u8],
+    ) -> PacketRadioDriver<'a> {
+        PacketRadioDriver {
+            radio: radio,
+            apps: grant,
+            tx_app: OptionalCell::empty(),
+            tx_buf: TakeCell::new(tx_buf),
+            rx_buf: TakeCell::new(rx_buf),
+        }
+    }
+
+    fn transmit(&self, appid: AppId, len: usize) -> ReturnCode {
+        if self.tx_app.is_some() {
+            return ReturnCode::EBUSY;
+        }
+        self.apps
+            .enter(appid, |app, _| {
+                let packet = match app.tx_buffer {
+                    Some(ref packet) => packet,
+                    None => return ReturnCode::ERESERVE,
+                };
+                if len > packet.len() {
+                    return ReturnCode::ESIZE;
+                }
+                self.tx_buf.take().map_or(ReturnCode::EBUSY, |buf| {
+                    if len > buf.len() {
+                        self.tx_buf.replace(buf);
+                        return ReturnCode::ESIZE;
+                    }
+                    buf[..len].copy_from_slice(&packet.as_ref()[..len]);
+                    match self.radio.transmit(buf, len) {
+                        (ReturnCode::SUCCESS, _) => {
+                            self.tx_app.set(appid);
+                            ReturnCode::SUCCESS
+                        }
+                        (rcode, buf) => {
+                            buf.map(|buf| self.tx_buf.replace(buf));
+                            rcode
+                        }
+                    }
+                })
+            })
+            .unwrap_or_else(|err| err.into())
+    }
+
+    /// Give the radio the receive buffer while an app listens, and take it
+    /// back once none does.
+    fn update_receive(&self) {
+        let listening = self
+            .apps
+            .iter()
+            .any(|cntr| cntr.enter(|app, _| app.listening));
+        if listening {
+            self.rx_buf.take().map(|buf| {
+                if let (_, Some(buf)) = self.radio.receive(buf) {
+                    self.rx_buf.replace(buf);
+                }
+            });
+        } else if self.rx_buf.is_none() {
+            self.radio
+                .receive_cancel()
+                .map(|buf| self.rx_buf.replace(buf));
+        }
+    }
+
+    fn set_listening(&self, appid: AppId, listening: bool) -> ReturnCode {
+        let rcode = self
+            .apps
+            .enter(appid, |app, _| {
+                if listening && app.rx_buffer.is_none() {
+                    return ReturnCode::ERESERVE;
+                }
+                app.listening = listening;
+                ReturnCode::SUCCESS
+            })
+            .unwrap_or_else(|err| err.into());
+        if rcode == ReturnCode::SUCCESS {
+            self.update_receive();
+        }
+        rcode
+    }
+
+    fn configure<F: FnOnce(&mut packet_radio::Config)>(&self, change: F) -> ReturnCode {
+        let mut config = self.radio.get_config();
+        change(&mut config);
+        self.radio.configure(config)
+    }
+}
+
+impl packet_radio::TxClient for PacketRadioDriver<'a> {
+    fn transmit_done(&self, buf: &'static mut [u8], result: ReturnCode) {
+        self.tx_buf.replace(buf);
+        self.tx_app.take().map(|appid| {
+            let _ = self.apps.enter(appid, |app, _| {
+                app.tx_callback
+                    .map(|mut cb| cb.schedule(isize::from(result) as usize, 0, 0));
+            });
+        });
+    }
+}
+
+impl packet_radio::RxClient for PacketRadioDriver<'a> {
+    fn packet_received(&self, buf: &'static mut [u8], len: usize, rssi: i8, result: ReturnCode) {
+        if result == ReturnCode::SUCCESS {
+            for cntr in self.apps.iter() {
+                cntr.enter(|app, _| {
+                    if !app.listening {
+                        return;
+                    }
+                    let copied = app.rx_buffer.as_mut().map(|buffer| {
+                        let copied = cmp::min(len, buffer.len());
+                        buffer.as_mut()[..copied].copy_from_slice(&buf[..copied]);
+                        copied
+                    });
+                    copied.map(|copied| {
+                        app.rx_callback
+                            .map(|mut cb| cb.schedule(copied, rssi as isize as usize, 0))
+                    });
+                });
+            }
+        }
+        self.rx_buf.replace(buf);
+        self.update_receive();
+    }
+}
+
+impl Driver for PacketRadioDriver<'a> {
+    fn allow(
+        &self,
+        appid: AppId,
+        allow_num: usize,
+        slice: Option<AppSlice<Shared, u8>>,
+    ) -> ReturnCode {
+        match allow_num {
+            0 => self
+                .apps
+                .enter(appid, |app, _| {
+                    app.tx_buffer = slice;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or_else(|err| err.into()),
+            1 => {
+                let rcode = self
+                    .apps
+                    .enter(appid, |app, _| {
+                        // An app without a buffer cannot listen
+                        app.listening = app.listening && slice.is_some();
+                        app.rx_buffer = slice;
+                        ReturnCode::SUCCESS
+                    })
+                    .unwrap_or_else(|err| err.into());
+                self.update_receive();
+                rcode
+            }
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    fn subscribe(
+        &self,
+        subscribe_num: usize,
+        callback: Option<Callback>,
+        appid: AppId,
+    ) -> ReturnCode {
+        match subscribe_num {
+            0 => self
+                .apps
+                .enter(appid, |app, _| {
+                    app.tx_callback = callback;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or_else(|err| err.into()),
+            1 => self
+                .apps
+                .enter(appid, |app, _| {
+                    app.rx_callback = callback;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or_else(|err| err.into()),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    fn command(&self, command_num: usize, arg1: usize, _: usize, appid: AppId) -> ReturnCode {
+        match command_num {
+            0 => ReturnCode::SUCCESS,
+            1 => self.transmit(appid, arg1),
+            2 => self.set_listening(appid, true),
+            3 => self.set_listening(appid, false),
+            4 => self.configure(|config| config.data_rate = arg1 as u32),
+            5 => {
+                if arg1 > u8::max_value() as usize {
+                    return ReturnCode::EINVAL;
+                }
+                self.configure(|config| config.channel = arg1 as u8)
+            }
+            6 => self.configure(|config| config.sync_word = arg1 as u32),
+            7 => ReturnCode::SuccessWithValue {
+                value: self.radio.max_packet_len(),
+            },
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+}
@@ -0,0 +1,198 @@
+//! Discipline of the kernel time base against a pulse-per-second input.
+//!
+//! A GPS receiver, or another precise time source, raises a pulse on its PPS
+//! output at the start of every second. `Pps` timestamps each pulse with an
+//! alarm of the kernel, and compares the clock of the alarm against them:
+//!
+//! - The frequency error is how much faster or slower than its nominal
+//!   frequency the clock runs, in parts per billion. It is averaged over the
+//!   last pulses, as each one is timestamped with some jitter.
+//! - The offset is how many tics before or after the clock predicted it each
+//!   pulse arrived, from the previous pulse and the frequency error.
+//!
+//! Once a few pulses in a row arrived about a second apart, the capsule is
+//! locked, and `time_at` converts tics of the alarm into seconds since the
+//! first of these pulses and nanoseconds into the second. Sensors sampled on
+//! different devices disciplined by the same source are then timestamped on
+//! the same time scale. A pulse that is not about a second after the previous
+//! one, such as when the receiver loses its fix, starts over.
+//!
+//! Pulses are timestamped when the kernel services the interrupt of the pin,
+//! so the latency of the kernel adds jitter to the timestamps. The estimates
+//! are averaged to smooth it out, but the offset of each pulse includes it.
+//!
+//! Usage
+//! -----
+//!
+//! ```rust
+//! let pps = static_init!(
+//!     capsules::pps::Pps<'static, VirtualMuxAlarm<'static, sam4l::ast::Ast>>,
+//!     capsules::pps::Pps::new(&sam4l::gpio::PA[13], pps_alarm)
+//! );
+//! sam4l::gpio::PA[13].set_client(pps);
+//! pps.start();
+//! ```
+
+use core::cell::Cell;
+use kernel::common::cells::OptionalCell;
+use kernel::hil::gpio;
+use kernel::hil::time::{Alarm, Frequency};
+
+/// Fractional bits of the estimate of the tics in a second.
+const PERIOD_SHIFT: u32 = 8;
+
+/// The estimate of the tics in a second moves by 1 / 2^`FILTER_SHIFT` of its
+/// error at each pulse.
+const FILTER_SHIFT: u32 = 3;
+
+/// Pulses further than 1 / `TOLERANCE` of a second away from where the
+/// nominal frequency puts them are not the next pulse.
+const TOLERANCE: u32 = 1000;
+
+/// Pulses in a row needed to lock.
+const LOCK_PULSES: u32 = 4;
+
+/// Notified of each pulse.
+pub trait PpsClient {
+    /// A pulse arrived, `seconds` seconds after the first pulse since the
+    /// capsule locked, if it is. `offset` and `frequency_error` are the
+    /// estimates after this pulse, in tics and in parts per billion.
+    fn pulse(&self, locked: bool, seconds: u32, offset: i32, frequency_error: i32);
+}
+
+pub struct Pps<'a, A: Alarm> {
+    pin: &'a gpio::InterruptPin,
+    alarm: &'a A,
+    client: OptionalCell<&'a PpsClient>,
+    /// When the last pulse arrived
+    last_pulse: OptionalCell<u32>,
+    /// Estimate of the tics in a second, with `PERIOD_SHIFT` fractional bits
+    period: Cell<u64>,
+    /// Pulses in a row about a second apart
+    pulses: Cell<u32>,
+    offset: Cell<i32>,
+}
+
+impl<A: Alarm> Pps<'a, A> {
+    pub fn new(pin: &'a gpio::InterruptPin, alarm: &'a A) -> Pps<'a, A> {
+        Pps {
+            pin: pin,
+            alarm: alarm,
+            client: OptionalCell::empty(),
+            last_pulse: OptionalCell::empty(),
+            period: Cell::new(Self::nominal_period()),
+            pulses: Cell::new(0),
+            offset: Cell::new(0),
+        }
+    }
+
+    pub fn set_client(&self, client: &'a PpsClient) {
+        self.client.set(client);
+    }
+
+    /// Start timestamping the pulses, on the rising edges of the pin.
+    pub fn start(&self) {
+        self.pin.make_input();
+        self.pin.enable_interrupts(gpio::InterruptEdge::RisingEdge);
+    }
+
+    pub fn stop(&self) {
+        self.pin.disable_interrupts();
+        self.last_pulse.clear();
+        self.pulses.set(0);
+    }
+
+    fn nominal_period() -> u64 {
+        (<A::Frequency>::frequency() as u64) << PERIOD_SHIFT
+    }
+
+    /// Whether enough pulses in a row arrived about a second apart for the
+    /// estimates to hold.
+    pub fn is_locked(&self) -> bool {
+        self.pulses.get() >= LOCK_PULSES
+    }
+
+    /// How many tics before (negative) or after (positive) the clock
+    /// predicted it the last pulse arrived.
+    pub fn offset(&self) -> i32 {
+        self.offset.get()
+    }
+
+    /// How much faster (positive) or slower (negative) than its nominal
+    /// frequency the clock runs, in parts per billion.
+    pub fn frequency_error(&self) -> i32 {
+        let nominal = Self::nominal_period();
+        let error = self.period.get() as i64 - nominal as i64;
+        (error * 1_000_000_000 / nominal as i64) as i32
+    }
+
+    /// The measured number of tics of the clock in a second.
+    pub fn tics_per_second(&self) -> u32 {
+        ((self.period.get() + (1 << (PERIOD_SHIFT - 1))) >> PERIOD_SHIFT) as u32
+    }
+
+    /// The time at `tics` of the alarm, as seconds since the first pulse
+    /// since the capsule locked and nanoseconds into the second. `tics` must
+    /// be after the last pulse, and `None` if the capsule is not locked.
+    pub fn time_at(&self, tics: u32) -> Option<(u32, u32)> {
+        if !self.is_locked() {
+            return None;
+        }
+        self.last_pulse.map(|last_pulse| {
+            let elapsed = (tics.wrapping_sub(*last_pulse) as u64) << PERIOD_SHIFT;
+            let period = self.period.get();
+            let seconds = self.pulses.get() - LOCK_PULSES + (elapsed / period) as u32;
+            let nanoseconds = (elapsed % period) * 1_000_000_000 / period;
+            (seconds, nanoseconds as u32)
+        })
+    }
+
+    fn pulse(&self, now: u32) {
+        let last_pulse = self.last_pulse.replace(now);
+        let interval = match last_pulse {
+            Some(last_pulse) => now.wrapping_sub(last_pulse),
+            None => return,
+        };
+
+        let nominal = <A::Frequency>::frequency();
+        let deviation = if interval > nominal {
+            interval - nominal
+        } else {
+            nominal - interval
+        };
+        if deviation > nominal / TOLERANCE {
+            // A missed or spurious pulse, start over from this one
+            self.pulses.set(0);
+            self.period.set(Self::nominal_period());
+            return;
+        }
+
+        let measured = (interval as u64) << PERIOD_SHIFT;
+        let predicted = self.period.get();
+        self.offset
+            .set(((measured as i64 - predicted as i64) >> PERIOD_SHIFT) as i32);
+        if self.pulses.get() == 0 {
+            self.period.set(measured);
+        } else {
+            let correction = (measured as i64 - predicted as i64) >> FILTER_SHIFT;
+            self.period.set((predicted as i64 + correction) as u64);
+        }
+        self.pulses.set(self.pulses.get().saturating_add(1));
+
+        let seconds = self.pulses.get().saturating_sub(LOCK_PULSES);
+        self.client.map(|client| {
+            client.pulse(
+                self.is_locked(),
+                seconds,
+                self.offset.get(),
+                self.frequency_error(),
+            )
+        });
+    }
+}
+
+impl<A: Alarm> gpio::Client for Pps<'a, A> {
+    fn fired(&self) {
+        self.pulse(self.alarm.now());
+    }
+}
@@ -5,13 +5,21 @@
 //! --------
 //!
 //! This module provides a simple text-based console to inspect and control
-//! which processes are running. The console has five commands:
+//! which processes are running. The console has eleven commands:
 //!  - 'help' prints the available commands and arguments
 //!  - 'status' prints the current system status
 //!  - 'list' lists the current processes with their IDs and running state
 //!  - 'stop n' stops the process with name n
 //!  - 'start n' starts the stopped process with name n
 //!  - 'fault n' forces the process with name n into a fault state
+//!  - 'pins' prints the state of the pins the board has named
+//!  - 'selftest [n]' runs the self test with name n, or all of them, if the
+//!    board gave the console its `SelfTests`
+//!  - 'errlog' prints the most recent entries of the error log
+//!  - 'profile [reset]' prints the samples of the profiler by region and the
+//!    hot spots of the kernel, or forgets them, if the board enabled it
+//!  - 'console [n]' moves the console to the UART with name n, or prints
+//!    the UARTs it can use, if the board gave the console its `UartSwitch`
 //!
 //! Setup
 //! -----
@@ -89,18 +97,56 @@
 //! stop blink
 //! Process blink stopped
 //! ```
+//!
+//! and see the state of the pins the board has named with
+//! `kernel::debug::assign_pin_labels()`:
+//!
+//! ```text
+//! pins
+//! DIO6/RLED           Output, low
+//! DIO15/BTN-1         Input, high
+//! ```
 
 use core::cell::Cell;
 use core::cmp;
+use core::fmt;
 use core::str;
 use kernel::capabilities::ProcessManagementCapability;
-use kernel::common::cells::TakeCell;
+use kernel::common::cells::{OptionalCell, TakeCell};
 use kernel::debug;
 use kernel::hil::uart;
 use kernel::introspection::KernelInfo;
+use kernel::profiler;
 use kernel::Kernel;
 use kernel::ReturnCode;
 
+use crate::self_test::SelfTests;
+use crate::uart_switch::UartSwitch;
+
+/// The number of hot spots of the kernel that `profile` prints.
+const PROFILE_HOT_SPOTS: usize = 10;
+
+/// Where the output of commands goes, one line at a time.
+pub trait Output {
+    fn line(&self, args: fmt::Arguments);
+}
+
+/// The output of commands typed on the console, which goes to the debug
+/// buffer.
+struct DebugOutput;
+
+impl Output for DebugOutput {
+    fn line(&self, args: fmt::Arguments) {
+        debug!("{}", args);
+    }
+}
+
+macro_rules! out {
+    ($out:expr, $($arg:tt)+) => {
+        $out.line(format_args!($($arg)+))
+    };
+}
+
 // Since writes are character echoes, we do not need more than 4 bytes:
 // the longest write is 3 bytes for a backspace (backspace, space, backspace).
 pub static mut WRITE_BUF: [u8; 4] = [0; 4];
@@ -122,6 +168,8 @@ pub struct ProcessConsole<'a, C: ProcessManagementCapability> {
     running: Cell<bool>,
     kernel: &'static Kernel,
     capability: C,
+    self_tests: OptionalCell<&'a SelfTests<'a>>,
+    uart_switch: OptionalCell<&'a UartSwitch<'a>>,
 }
 
 impl<'a, C: ProcessManagementCapability> ProcessConsole<'a, C> {
@@ -144,9 +192,22 @@ impl<'a, C: ProcessManagementCapability> ProcessConsole<'a, C> {
             running: Cell::new(false),
             kernel: kernel,
             capability: capability,
+            self_tests: OptionalCell::empty(),
+            uart_switch: OptionalCell::empty(),
         }
     }
 
+    /// Let the `selftest` command run the self tests of the board.
+    pub fn set_self_tests(&self, self_tests: &'a SelfTests<'a>) {
+        self.self_tests.set(self_tests);
+    }
+
+    /// Let the `console` command move the console between the UARTs of
+    /// `uart_switch`.
+    pub fn set_uart_switch(&self, uart_switch: &'a UartSwitch<'a>) {
+        self.uart_switch.set(uart_switch);
+    }
+
     pub fn start(&self) -> ReturnCode {
         if self.running.get() == false {
             self.rx_buffer.take().map(|buffer| {
@@ -177,86 +238,7 @@ impl<'a, C: ProcessManagementCapability> ProcessConsole<'a, C> {
             if terminator > 0 {
                 let cmd_str = str::from_utf8(&command[0..terminator]);
                 match cmd_str {
-                    Ok(s) => {
-                        let clean_str = s.trim();
-                        if clean_str.starts_with("help") {
-                            debug!("Welcome to the process console.");
-                            debug!("Valid commands are: help status list stop start");
-                        } else if clean_str.starts_with("start") {
-                            let argument = clean_str.split_whitespace().nth(1);
-                            argument.map(|name| {
-                                self.kernel.process_each_capability(
-                                    &self.capability,
-                                    |_i, proc| {
-                                        let proc_name = proc.get_process_name();
-                                        if proc_name == name {
-                                            proc.resume();
-                                            debug!("Process {} resumed.", name);
-                                        }
-                                    },
-                                );
-                            });
-                        } else if clean_str.starts_with("stop") {
-                            let argument = clean_str.split_whitespace().nth(1);
-                            argument.map(|name| {
-                                self.kernel.process_each_capability(
-                                    &self.capability,
-                                    |_i, proc| {
-                                        let proc_name = proc.get_process_name();
-                                        if proc_name == name {
-                                            proc.stop();
-                                            debug!("Process {} stopped", proc_name);
-                                        }
-                                    },
-                                );
-                            });
-                        } else if clean_str.starts_with("fault") {
-                            let argument = clean_str.split_whitespace().nth(1);
-                            argument.map(|name| {
-                                self.kernel.process_each_capability(
-                                    &self.capability,
-                                    |_i, proc| {
-                                        let proc_name = proc.get_process_name();
-                                        if proc_name == name {
-                                            proc.set_fault_state();
-                                            debug!("Process {} now faulted", proc_name);
-                                        }
-                                    },
-                                );
-                            });
-                        } else if clean_str.starts_with("list") {
-                            debug!(" PID    Name                Quanta  Syscalls  Dropped Callbacks    State");
-                            self.kernel
-                                .process_each_capability(&self.capability, |i, proc| {
-                                    let pname = proc.get_process_name();
-                                    debug!(
-                                        "  {:02}\t{:<20}{:6}{:10}{:19}  {:?}",
-                                        i,
-                                        pname,
-                                        proc.debug_timeslice_expiration_count(),
-                                        proc.debug_syscall_count(),
-                                        proc.debug_dropped_callback_count(),
-                                        proc.get_state()
-                                    );
-                                });
-                        } else if clean_str.starts_with("status") {
-                            let info: KernelInfo = KernelInfo::new(self.kernel);
-                            debug!(
-                                "Total processes: {}",
-                                info.number_loaded_processes(&self.capability)
-                            );
-                            debug!(
-                                "Active processes: {}",
-                                info.number_active_processes(&self.capability)
-                            );
-                            debug!(
-                                "Timeslice expirations: {}",
-                                info.timeslice_expirations(&self.capability)
-                            );
-                        } else {
-                            debug!("Valid commands are: help status list stop start fault");
-                        }
-                    }
+                    Ok(s) => self.run_command(s, &DebugOutput),
                     Err(_e) => debug!("Invalid command: {:?}", command),
                 }
             }
@@ -267,6 +249,184 @@ impl<'a, C: ProcessManagementCapability> ProcessConsole<'a, C> {
         self.command_index.set(0);
     }
 
+    /// Run `command`, and write what it prints to `out`. This is how
+    /// `capsules::net::remote_console` runs commands it receives.
+    pub fn run_command(&self, command: &str, out: &Output) {
+        let clean_str = command.trim();
+        if clean_str.starts_with("help") {
+            out!(out, "Welcome to the process console.");
+            out!(
+                out,
+                "Valid commands are: help status list stop start fault pins selftest errlog profile console"
+            );
+        } else if clean_str.starts_with("start") {
+            let argument = clean_str.split_whitespace().nth(1);
+            argument.map(|name| {
+                self.kernel
+                    .process_each_capability(&self.capability, |_i, proc| {
+                        let proc_name = proc.get_process_name();
+                        if proc_name == name {
+                            proc.resume();
+                            out!(out, "Process {} resumed.", name);
+                        }
+                    });
+            });
+        } else if clean_str.starts_with("stop") {
+            let argument = clean_str.split_whitespace().nth(1);
+            argument.map(|name| {
+                self.kernel
+                    .process_each_capability(&self.capability, |_i, proc| {
+                        let proc_name = proc.get_process_name();
+                        if proc_name == name {
+                            proc.stop();
+                            out!(out, "Process {} stopped", proc_name);
+                        }
+                    });
+            });
+        } else if clean_str.starts_with("fault") {
+            let argument = clean_str.split_whitespace().nth(1);
+            argument.map(|name| {
+                self.kernel
+                    .process_each_capability(&self.capability, |_i, proc| {
+                        let proc_name = proc.get_process_name();
+                        if proc_name == name {
+                            proc.set_fault_state();
+                            out!(out, "Process {} now faulted", proc_name);
+                        }
+                    });
+            });
+        } else if clean_str.starts_with("list") {
+            out!(
+                out,
+                " PID    Name                Quanta  Syscalls  Dropped Callbacks    State"
+            );
+            self.kernel
+                .process_each_capability(&self.capability, |i, proc| {
+                    let pname = proc.get_process_name();
+                    out!(
+                        out,
+                        "  {:02}\t{:<20}{:6}{:10}{:19}  {:?}",
+                        i,
+                        pname,
+                        proc.debug_timeslice_expiration_count(),
+                        proc.debug_syscall_count(),
+                        proc.debug_dropped_callback_count(),
+                        proc.get_state()
+                    );
+                });
+        } else if clean_str.starts_with("status") {
+            let info: KernelInfo = KernelInfo::new(self.kernel);
+            out!(
+                out,
+                "Total processes: {}",
+                info.number_loaded_processes(&self.capability)
+            );
+            out!(
+                out,
+                "Active processes: {}",
+                info.number_active_processes(&self.capability)
+            );
+            out!(
+                out,
+                "Timeslice expirations: {}",
+                info.timeslice_expirations(&self.capability)
+            );
+        } else if clean_str.starts_with("pins") {
+            for label in debug::pin_labels().iter() {
+                out!(out, "{:<20}{}", label.label, debug::PinState(label.pin));
+            }
+        } else if clean_str.starts_with("selftest") {
+            let argument = clean_str.split_whitespace().nth(1);
+            if self.self_tests.is_none() {
+                out!(out, "No self tests on this board");
+            }
+            self.self_tests.map(|self_tests| {
+                if self_tests.run_from_console(argument) == ReturnCode::EBUSY {
+                    out!(out, "Self tests are already running");
+                }
+            });
+        } else if clean_str.starts_with("errlog") {
+            kernel::error_log::recent(&mut |entry| {
+                out!(
+                    out,
+                    "{:>8} {:<8?} {:#010x} {:#010x}",
+                    entry.sequence,
+                    entry.severity,
+                    entry.code,
+                    entry.data
+                );
+            });
+        } else if clean_str.starts_with("profile") {
+            let argument = clean_str.split_whitespace().nth(1);
+            if !profiler::enabled() {
+                out!(out, "The profiler is not enabled on this board");
+            } else if argument == Some("reset") {
+                profiler::reset();
+                out!(out, "Profile reset");
+            } else {
+                let samples = profiler::samples();
+                out!(out, "Samples: {}", samples);
+                profiler::regions(&mut |region, count| {
+                    let percent = count as u64 * 100 / (samples as u64).max(1);
+                    match region {
+                        profiler::Region::Kernel => {
+                            out!(out, "  {:<20}{:8} {:3}%", "kernel", count, percent)
+                        }
+                        profiler::Region::Other => {
+                            out!(out, "  {:<20}{:8} {:3}%", "other", count, percent)
+                        }
+                        profiler::Region::Process(index) => {
+                            self.kernel
+                                .process_each_capability(&self.capability, |i, proc| {
+                                    if i == index {
+                                        out!(
+                                            out,
+                                            "  {:<20}{:8} {:3}%",
+                                            proc.get_process_name(),
+                                            count,
+                                            percent
+                                        );
+                                    }
+                                })
+                        }
+                    }
+                });
+                out!(out, "Kernel hot spots:");
+                profiler::hot_spots(PROFILE_HOT_SPOTS, &mut |start, end, count| {
+                    out!(out, "  {:#010x}-{:#010x}{:8}", start, end, count);
+                });
+            }
+        } else if clean_str.starts_with("console") {
+            let argument = clean_str.split_whitespace().nth(1);
+            if self.uart_switch.is_none() {
+                out!(out, "The console cannot move on this board");
+            }
+            self.uart_switch.map(|uart_switch| match argument {
+                None => {
+                    out!(out, "Console on {}", uart_switch.selected());
+                    for name in uart_switch.names() {
+                        out!(out, "  {}", name);
+                    }
+                }
+                Some(name) => {
+                    // Printed before the switch, so it goes out on the old
+                    // UART
+                    if uart_switch.names().any(|n| n == name) {
+                        out!(out, "Moving the console to {}", name);
+                    }
+                    if uart_switch.select(name) != ReturnCode::SUCCESS {
+                        out!(out, "No UART named {}", name);
+                    }
+                }
+            });
+        } else {
+            out!(
+                out,
+                "Valid commands are: help status list stop start fault pins selftest errlog profile console"
+            );
+        }
+    }
+
     fn write_byte(&self, byte: u8) -> ReturnCode {
         if self.tx_in_progress.get() {
             ReturnCode::EBUSY
@@ -0,0 +1,125 @@
+//! Monitor of the changes in the state of processes.
+//!
+//! `ProcessMonitor` observes processes as the kernel loads, runs, stops and
+//! restarts them, and as they yield and fault, and logs each change with the
+//! time it happened. This shows what the processes of a running system are
+//! doing, and is the basis for a live monitor on the host. It observes the
+//! processes as the kernel's `ProcessStateObserver`, which only a holder of
+//! the `ProcessManagementCapability` can set.
+//!
+//! Changes go to the debug console, or, if the board gives the capsule a
+//! buffer, into that buffer, where the newest changes replace the oldest
+//! ones. Processes yield and run again very often, so `set_verbose(false)`
+//! leaves those changes out, and only logs processes being created, stopped,
+//! resumed, faulting and restarting.
+//!
+//! Usage
+//! -----
+//!
+//! ```rust
+//! let process_monitor = static_init!(
+//!     capsules::process_monitor::ProcessMonitor<'static, VirtualMuxAlarm<'static, sam4l::ast::Ast>>,
+//!     capsules::process_monitor::ProcessMonitor::new(process_monitor_alarm)
+//! );
+//! board_kernel.set_process_state_observer(process_monitor, &process_mgmt_cap);
+//! ```
+
+use core::cell::Cell;
+use kernel::common::cells::MapCell;
+use kernel::common::{Queue, RingBuffer};
+use kernel::debug;
+use kernel::hil::time::Alarm;
+use kernel::{AppId, ProcessEvent, ProcessStateObserver};
+
+/// A change in the state of a process.
+#[derive(Copy, Clone)]
+pub struct MonitorRecord {
+    /// When the change happened, in tics of the alarm
+    pub time: u32,
+    /// Index of the process
+    pub process: usize,
+    pub name: &'static str,
+    pub event: ProcessEvent,
+}
+
+impl MonitorRecord {
+    /// A record to initialize buffers with.
+    pub const EMPTY: MonitorRecord = MonitorRecord {
+        time: 0,
+        process: 0,
+        name: "",
+        event: ProcessEvent::Created,
+    };
+}
+
+pub struct ProcessMonitor<'a, A: Alarm> {
+    alarm: &'a A,
+    /// Whether to log processes running and yielding
+    verbose: Cell<bool>,
+    buffer: MapCell<RingBuffer<'static, MonitorRecord>>,
+}
+
+impl<A: Alarm> ProcessMonitor<'a, A> {
+    /// The alarm only serves as a clock, and is never set.
+    pub fn new(alarm: &'a A) -> ProcessMonitor<'a, A> {
+        ProcessMonitor {
+            alarm: alarm,
+            verbose: Cell::new(true),
+            buffer: MapCell::empty(),
+        }
+    }
+
+    /// Whether to log processes running and yielding, which they do often.
+    pub fn set_verbose(&self, verbose: bool) {
+        self.verbose.set(verbose);
+    }
+
+    /// Keep the records in `buffer` rather than logging them to the debug
+    /// console.
+    pub fn set_buffer(&self, buffer: &'static mut [MonitorRecord]) {
+        self.buffer.replace(RingBuffer::new(buffer));
+    }
+
+    /// Remove the records from the buffer, and call `f` on each of them,
+    /// oldest first.
+    pub fn drain<F: FnMut(&MonitorRecord)>(&self, mut f: F) {
+        self.buffer.map(|buffer| {
+            while let Some(record) = buffer.dequeue() {
+                f(&record);
+            }
+        });
+    }
+}
+
+impl<A: Alarm> ProcessStateObserver for ProcessMonitor<'a, A> {
+    fn process_event(&self, appid: AppId, name: &'static str, event: ProcessEvent) {
+        let frequent = match event {
+            ProcessEvent::Running | ProcessEvent::Yielded => true,
+            _ => false,
+        };
+        if frequent && !self.verbose.get() {
+            return;
+        }
+
+        let record = MonitorRecord {
+            time: self.alarm.now(),
+            process: appid.idx(),
+            name: name,
+            event: event,
+        };
+        let buffered = self.buffer.map_or(false, |buffer| {
+            // The newest records replace the oldest ones
+            if buffer.is_full() {
+                buffer.dequeue();
+            }
+            buffer.enqueue(record);
+            true
+        });
+        if !buffered {
+            debug!(
+                "[{}] process {} ({}): {:?}",
+                record.time, record.process, record.name, record.event
+            );
+        }
+    }
+}
@@ -0,0 +1,243 @@
+//! Provides userspace with measurements of pulses and periods of an input.
+//!
+//! The capsule times the edges of an input with a capture timer, for sensors
+//! that output their reading as a time: the echo pulse of an ultrasonic
+//! rangefinder is as long as the sound took, and the period of a tachometer
+//! signal is the time of a revolution. A process measures the width of the
+//! next pulse, or the total time of a number of periods, and is called back
+//! with the result in ticks of the timer, whose frequency command `4`
+//! returns. Only one measurement runs at a time. Measurements of pulses or
+//! periods longer than the timer wraps around in are wrong.
+//!
+//! Usage
+//! -----
+//!
+//! ```rust
+//! cc26x2::gpio::PORT[21].enable_capture(cc26x2::pwm::Timer::GPT1A).unwrap();
+//! let capture = &cc26x2::capture::CAPTURE[cc26x2::pwm::Timer::GPT1A as usize];
+//! let pulse_capture = static_init!(
+//!     capsules::pulse_capture::PulseCaptureDriver<'static>,
+//!     capsules::pulse_capture::PulseCaptureDriver::new(
+//!         capture,
+//!         board_kernel.create_grant(&memory_allocation_capability)
+//!     )
+//! );
+//! kernel::hil::pulse_capture::PulseCapture::set_client(capture, pulse_capture);
+//! ```
+//!
+//! Syscall Interface
+//! -----------------
+//!
+//! - Stability: 1 - Experimental
+//!
+//! ### Subscribe
+//!
+//! - `0`: Called with `(ticks, periods)` when a measurement is done.
+//!   `periods` is 0 for the width of a pulse.
+//!
+//! ### Command
+//!
+//! - `0`: Driver check.
+//! - `1`: Measure the width of the next pulse, which is high if `data` is 0,
+//!   and low otherwise.
+//! - `2`: Measure the time of the next `data` periods, from rising edge to
+//!   rising edge. Returns `EINVAL` if `data` is 0.
+//! - `3`: Cancel the measurement of the process.
+//! - `4`: Returns the frequency of the ticks, in Hz.
+
+use core::cell::Cell;
+use kernel::common::cells::OptionalCell;
+use kernel::hil::pulse_capture::{self, Edge};
+use kernel::{AppId, Callback, Driver, Grant, ReturnCode};
+
+/// Syscall driver number.
+use crate::driver;
+pub const DRIVER_NUM: usize = driver::NUM::PulseCapture as usize;
+
+#[derive(Default)]
+pub struct App {
+    callback: Option<Callback>,
+}
+
+#[derive(Copy, Clone, PartialEq)]
+enum Measurement {
+    /// Waiting for the leading edge of a pulse
+    PulseStart { high: bool },
+    /// Waiting for the trailing edge of the pulse that started at `start`
+    PulseEnd { start: u32 },
+    /// Waiting for the first rising edge of `periods` periods
+    PeriodsStart { periods: u32 },
+    /// Counting periods, with `remaining` to go, since `start`
+    Periods {
+        periods: u32,
+        remaining: u32,
+        start: u32,
+        ticks: u32,
+    },
+}
+
+pub struct PulseCaptureDriver<'a> {
+    capture: &'a pulse_capture::PulseCapture<'a>,
+    apps: Grant<App>,
+    /// The process whose measurement is running
+    owner: OptionalCell<AppId>,
+    measurement: Cell<Option<Measurement>>,
+}
+
+impl PulseCaptureDriver<'a> {
+    pub fn new(
+        capture: &'a pulse_capture::PulseCapture<'a>,
+        grant: Grant<App>,
+    ) -> PulseCaptureDriver<'a> {
+        PulseCaptureDriver {
+            capture: capture,
+            apps: grant,
+            owner: OptionalCell::empty(),
+            measurement: Cell::new(None),
+        }
+    }
+
+    /// Starts `measurement` for `appid`, unless another measurement is
+    /// running.
+    fn start(&self, appid: AppId, measurement: Measurement, edge: Edge) -> ReturnCode {
+        if self.owner.is_some() {
+            return ReturnCode::EBUSY;
+        }
+        let result = self.capture.start(edge);
+        if result == ReturnCode::SUCCESS {
+            self.owner.set(appid);
+            self.measurement.set(Some(measurement));
+        }
+        result
+    }
+
+    fn cancel(&self, appid: AppId) -> ReturnCode {
+        match self.owner.map(|owner| *owner == appid) {
+            Some(true) => {
+                self.owner.clear();
+                self.measurement.set(None);
+                self.capture.stop()
+            }
+            Some(false) => ReturnCode::EBUSY,
+            None => ReturnCode::EALREADY,
+        }
+    }
+
+    /// The time from `start` to `end`, which may have wrapped around.
+    fn elapsed(&self, start: u32, end: u32) -> u32 {
+        end.wrapping_sub(start) & self.capture.max_timestamp()
+    }
+
+    fn done(&self, ticks: u32, periods: u32) {
+        self.capture.stop();
+        self.measurement.set(None);
+        self.owner.take().map(|appid| {
+            let _ = self.apps.enter(appid, |app, _| {
+                app.callback
+                    .map(|mut cb| cb.schedule(ticks as usize, periods as usize, 0));
+            });
+        });
+    }
+}
+
+impl pulse_capture::Client for PulseCaptureDriver<'a> {
+    fn edge_captured(&self, timestamp: u32) {
+        let measurement = match self.measurement.get() {
+            Some(measurement) => measurement,
+            None => return,
+        };
+        match measurement {
+            Measurement::PulseStart { high } => {
+                self.measurement
+                    .set(Some(Measurement::PulseEnd { start: timestamp }));
+                self.capture
+                    .start(if high { Edge::Falling } else { Edge::Rising });
+            }
+            Measurement::PulseEnd { start } => self.done(self.elapsed(start, timestamp), 0),
+            Measurement::PeriodsStart { periods } => {
+                self.measurement.set(Some(Measurement::Periods {
+                    periods: periods,
+                    remaining: periods,
+                    start: timestamp,
+                    ticks: 0,
+                }));
+            }
+            Measurement::Periods {
+                periods,
+                remaining,
+                start,
+                ticks,
+            } => {
+                // Each period is taken on its own, so only they must be
+                // shorter than a wrap around
+                let ticks = ticks.saturating_add(self.elapsed(start, timestamp));
+                if remaining == 1 {
+                    self.done(ticks, periods);
+                } else {
+                    self.measurement.set(Some(Measurement::Periods {
+                        periods: periods,
+                        remaining: remaining - 1,
+                        start: timestamp,
+                        ticks: ticks,
+                    }));
+                }
+            }
+        }
+    }
+}
+
+impl Driver for PulseCaptureDriver<'a> {
+    fn subscribe(
+        &self,
+        subscribe_num: usize,
+        callback: Option<Callback>,
+        app_id: AppId,
+    ) -> ReturnCode {
+        match subscribe_num {
+            0 => self
+                .apps
+                .enter(app_id, |app, _| {
+                    app.callback = callback;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or_else(|err| err.into()),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    fn command(&self, command_num: usize, data: usize, _: usize, appid: AppId) -> ReturnCode {
+        match command_num {
+            0 /* check if present */ => ReturnCode::SUCCESS,
+
+            1 => {
+                let high = data == 0;
+                self.start(
+                    appid,
+                    Measurement::PulseStart { high: high },
+                    if high { Edge::Rising } else { Edge::Falling },
+                )
+            }
+
+            2 => {
+                if data == 0 {
+                    return ReturnCode::EINVAL;
+                }
+                self.start(
+                    appid,
+                    Measurement::PeriodsStart {
+                        periods: data as u32,
+                    },
+                    Edge::Rising,
+                )
+            }
+
+            3 => self.cancel(appid),
+
+            4 => ReturnCode::SuccessWithValue {
+                value: self.capture.frequency() as usize,
+            },
+
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+}
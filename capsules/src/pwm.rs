@@ -0,0 +1,142 @@
+//! Provides userspace with PWM outputs, e.g. to dim LEDs or to drive servos.
+//!
+//! The board gives the capsule the PWM pins that processes may use, each in
+//! a `Channel`. A pin keeps running at its frequency until it is stopped. The
+//! duty cycle is set separately, and is applied at once if the pin runs.
+//! Like LEDs, the pins are shared by all processes.
+//!
+//! Usage
+//! -----
+//!
+//! ```rust
+//! let pwm_channels = static_init!(
+//!     [capsules::pwm::Channel<'static>; 2],
+//!     [
+//!         capsules::pwm::Channel::new(&pwm_pins[0]),
+//!         capsules::pwm::Channel::new(&pwm_pins[1]),
+//!     ]
+//! );
+//! let pwm = static_init!(
+//!     capsules::pwm::Pwm<'static>,
+//!     capsules::pwm::Pwm::new(pwm_channels)
+//! );
+//! ```
+//!
+//! Syscall Interface
+//! -----------------
+//!
+//! - Stability: 1 - Experimental
+//!
+//! ### Command
+//!
+//! - `0`: Returns the number of PWM pins.
+//! - `1`: Start pin `data` at frequency `data2`, in Hz. Returns `EINVAL` if
+//!   the pin does not exist or the frequency is out of its range.
+//! - `2`: Set the duty cycle of pin `data` to `data2`, out of the maximum
+//!   that command `6` returns. Pins start with a duty cycle of 50%. Returns
+//!   `EINVAL` if the pin does not exist or the duty cycle is too large.
+//! - `3`: Stop pin `data`. Returns `EOFF` if it does not run.
+//! - `4`: Returns the highest frequency of pin `data`, in Hz.
+//! - `5`: Returns the lowest frequency of pin `data`, in Hz.
+//! - `6`: Returns the duty cycle that is 100% on pin `data`.
+
+use core::cell::Cell;
+use kernel::hil::pwm::PwmPin;
+use kernel::{AppId, Driver, ReturnCode};
+
+/// Syscall driver number.
+use crate::driver;
+pub const DRIVER_NUM: usize = driver::NUM::Pwm as usize;
+
+/// A PWM pin that processes can use.
+pub struct Channel<'a> {
+    pin: &'a PwmPin,
+    /// The frequency the pin runs at, or 0 if it is stopped
+    frequency_hz: Cell<usize>,
+    duty_cycle: Cell<usize>,
+}
+
+impl Channel<'a> {
+    pub fn new(pin: &'a PwmPin) -> Channel<'a> {
+        Channel {
+            pin: pin,
+            frequency_hz: Cell::new(0),
+            duty_cycle: Cell::new(pin.get_maximum_duty_cycle() / 2),
+        }
+    }
+
+    fn start(&self, frequency_hz: usize) -> ReturnCode {
+        if frequency_hz < self.pin.get_minimum_frequency_hz()
+            || frequency_hz > self.pin.get_maximum_frequency_hz()
+        {
+            return ReturnCode::EINVAL;
+        }
+        let result = self.pin.start(frequency_hz, self.duty_cycle.get());
+        if result == ReturnCode::SUCCESS {
+            self.frequency_hz.set(frequency_hz);
+        }
+        result
+    }
+
+    fn set_duty_cycle(&self, duty_cycle: usize) -> ReturnCode {
+        if duty_cycle > self.pin.get_maximum_duty_cycle() {
+            return ReturnCode::EINVAL;
+        }
+        self.duty_cycle.set(duty_cycle);
+        match self.frequency_hz.get() {
+            0 => ReturnCode::SUCCESS,
+            frequency_hz => self.pin.start(frequency_hz, duty_cycle),
+        }
+    }
+
+    fn stop(&self) -> ReturnCode {
+        if self.frequency_hz.get() == 0 {
+            return ReturnCode::EOFF;
+        }
+        self.frequency_hz.set(0);
+        self.pin.stop()
+    }
+}
+
+pub struct Pwm<'a> {
+    channels: &'a [Channel<'a>],
+}
+
+impl Pwm<'a> {
+    pub fn new(channels: &'a [Channel<'a>]) -> Pwm<'a> {
+        Pwm { channels: channels }
+    }
+}
+
+impl Driver for Pwm<'a> {
+    fn command(&self, command_num: usize, data: usize, data2: usize, _: AppId) -> ReturnCode {
+        match command_num {
+            0 => {
+                return ReturnCode::SuccessWithValue {
+                    value: self.channels.len(),
+                }
+            }
+            1..=6 => {}
+            _ => return ReturnCode::ENOSUPPORT,
+        }
+        let channel = match self.channels.get(data) {
+            Some(channel) => channel,
+            None => return ReturnCode::EINVAL,
+        };
+        match command_num {
+            1 => channel.start(data2),
+            2 => channel.set_duty_cycle(data2),
+            3 => channel.stop(),
+            4 => ReturnCode::SuccessWithValue {
+                value: channel.pin.get_maximum_frequency_hz(),
+            },
+            5 => ReturnCode::SuccessWithValue {
+                value: channel.pin.get_minimum_frequency_hz(),
+            },
+            6 => ReturnCode::SuccessWithValue {
+                value: channel.pin.get_maximum_duty_cycle(),
+            },
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+}
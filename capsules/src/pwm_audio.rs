@@ -0,0 +1,183 @@
+//! Audio playback over a PWM pin.
+//!
+//! Plays PCM audio on any `PwmPin`, for boards with a speaker or piezo but
+//! no DAC. The PWM runs at a fixed carrier frequency, well above the audible
+//! range, and its duty cycle is changed to each sample's value at the sample
+//! rate; a low-pass filter (or the speaker itself) recovers the audio.
+//!
+//! PWM peripherals typically offer far fewer duty cycle levels than 16-bit
+//! samples have, so each sample's quantization error is carried over to the
+//! next one. This error-diffusion dithering keeps the average output exact
+//! and pushes the quantization noise up in frequency, where the filter
+//! removes it.
+//!
+//! Samples are paced by an alarm, which costs an interrupt per sample, so this
+//! is intended for low sample rates (e.g. 8 kHz) and short clips. Sample rates
+//! are limited to the alarm frequency.
+//!
+//! Usage
+//! -----
+//!
+//! ```rust
+//! let virtual_pwm_audio = static_init!(
+//!     capsules::virtual_pwm::PwmPinUser<'static, nrf52::pwm::Pwm>,
+//!     capsules::virtual_pwm::PwmPinUser::new(mux_pwm, nrf5x::pinmux::Pinmux::new(31))
+//! );
+//! virtual_pwm_audio.add_to_mux();
+//! let virtual_alarm_audio = static_init!(
+//!     capsules::virtual_alarm::VirtualMuxAlarm<'static, nrf5x::rtc::Rtc>,
+//!     capsules::virtual_alarm::VirtualMuxAlarm::new(mux_alarm)
+//! );
+//! let pwm_audio = static_init!(
+//!     capsules::pwm_audio::PwmAudio<
+//!         'static,
+//!         capsules::virtual_alarm::VirtualMuxAlarm<'static, nrf5x::rtc::Rtc>>,
+//!     capsules::pwm_audio::PwmAudio::new(
+//!         virtual_pwm_audio,
+//!         virtual_alarm_audio,
+//!         capsules::pwm_audio::DEFAULT_CARRIER_FREQUENCY_HZ)
+//! );
+//! virtual_alarm_audio.set_client(pwm_audio);
+//! ```
+
+use core::cell::Cell;
+use core::cmp;
+use kernel::common::cells::{OptionalCell, TakeCell};
+use kernel::hil;
+use kernel::hil::audio::SAMPLE_LEN;
+use kernel::hil::time::Frequency;
+use kernel::ReturnCode;
+
+/// A carrier frequency above the audible range.
+pub const DEFAULT_CARRIER_FREQUENCY_HZ: usize = 62500;
+
+pub struct PwmAudio<'a, A: hil::time::Alarm> {
+    pwm_pin: &'a hil::pwm::PwmPin,
+    alarm: &'a A,
+    carrier_frequency_hz: usize,
+    client: OptionalCell<&'a hil::audio::OutputClient>,
+    buffer: TakeCell<'static, [u8]>,
+    samples: Cell<usize>,
+    /// Index of the next sample to play
+    position: Cell<usize>,
+    sample_rate_hz: Cell<u32>,
+    /// Alarm time at which the first sample was played
+    start: Cell<u32>,
+    /// Quantization error carried over to the next sample, in units of
+    /// 1/65536 of a duty cycle level
+    error: Cell<i64>,
+}
+
+impl<A: hil::time::Alarm> PwmAudio<'a, A> {
+    pub fn new(
+        pwm_pin: &'a hil::pwm::PwmPin,
+        alarm: &'a A,
+        carrier_frequency_hz: usize,
+    ) -> PwmAudio<'a, A> {
+        PwmAudio {
+            pwm_pin: pwm_pin,
+            alarm: alarm,
+            carrier_frequency_hz: carrier_frequency_hz,
+            client: OptionalCell::empty(),
+            buffer: TakeCell::empty(),
+            samples: Cell::new(0),
+            position: Cell::new(0),
+            sample_rate_hz: Cell::new(0),
+            start: Cell::new(0),
+            error: Cell::new(0),
+        }
+    }
+
+    /// Sets the duty cycle for the next sample and schedules the one after
+    /// it, or ends playback if there are no samples left.
+    fn play_next(&self) {
+        let position = self.position.get();
+        if position == self.samples.get() {
+            return self.playback_done(ReturnCode::SUCCESS);
+        }
+
+        let sample = self.buffer.map_or(0, |buffer| {
+            let offset = position * SAMPLE_LEN;
+            i16::from_le_bytes([buffer[offset], buffer[offset + 1]])
+        });
+        let max = self.pwm_pin.get_maximum_duty_cycle() as i64;
+        let target = (sample as i64 + 0x8000) * max + self.error.get();
+        let duty_cycle = if target < 0 {
+            0
+        } else {
+            cmp::min(target >> 16, max)
+        };
+        self.error.set(target - (duty_cycle << 16));
+        self.pwm_pin
+            .start(self.carrier_frequency_hz, duty_cycle as usize);
+
+        // Sample times are computed from the start of playback, so that
+        // rounding errors do not accumulate
+        self.position.set(position + 1);
+        let offset = (position as u64 + 1) * <A::Frequency>::frequency() as u64
+            / self.sample_rate_hz.get() as u64;
+        self.alarm
+            .set_alarm(self.start.get().wrapping_add(offset as u32));
+    }
+
+    fn playback_done(&self, result: ReturnCode) {
+        self.pwm_pin.stop();
+        self.buffer.take().map(|buffer| {
+            self.client
+                .map(move |client| client.playback_done(buffer, result))
+        });
+    }
+}
+
+impl<A: hil::time::Alarm> hil::audio::AudioOutput<'a> for PwmAudio<'a, A> {
+    fn set_client(&self, client: &'a hil::audio::OutputClient) {
+        self.client.set(client);
+    }
+
+    fn sample_rate_supported(&self, sample_rate_hz: u32) -> bool {
+        sample_rate_hz > 0
+            && sample_rate_hz <= <A::Frequency>::frequency()
+            && sample_rate_hz as usize <= self.carrier_frequency_hz
+    }
+
+    fn play(
+        &self,
+        buffer: &'static mut [u8],
+        samples: usize,
+        sample_rate_hz: u32,
+    ) -> (ReturnCode, Option<&'static mut [u8]>) {
+        if !self.sample_rate_supported(sample_rate_hz) {
+            return (ReturnCode::EINVAL, Some(buffer));
+        }
+        if samples == 0 || samples > buffer.len() / SAMPLE_LEN {
+            return (ReturnCode::ESIZE, Some(buffer));
+        }
+        if self.buffer.is_some() {
+            return (ReturnCode::EBUSY, Some(buffer));
+        }
+
+        self.buffer.replace(buffer);
+        self.samples.set(samples);
+        self.position.set(0);
+        self.sample_rate_hz.set(sample_rate_hz);
+        self.start.set(self.alarm.now());
+        self.error.set(0);
+        self.play_next();
+        (ReturnCode::SUCCESS, None)
+    }
+
+    fn stop(&self) -> ReturnCode {
+        if self.buffer.is_none() {
+            return ReturnCode::EOFF;
+        }
+        self.alarm.disable();
+        self.playback_done(ReturnCode::ECANCEL);
+        ReturnCode::SUCCESS
+    }
+}
+
+impl<A: hil::time::Alarm> hil::time::Client for PwmAudio<'a, A> {
+    fn fired(&self) {
+        self.play_next();
+    }
+}
@@ -1375,4 +1375,10 @@ impl<S: spi::SpiMasterDevice> radio::RadioData for RF233<'a, S> {
         }
         (ReturnCode::SUCCESS, None)
     }
+
+    fn get_tx_info(&self) -> radio::TxInfo {
+        // Retries and the acknowledgement RSSI are not read back from the
+        // radio
+        radio::TxInfo::default()
+    }
 }
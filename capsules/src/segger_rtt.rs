@@ -81,7 +81,6 @@
 //!     capsules::console::Console::new(
 //!         rtt,
 //!         0, // Baud rate is meaningless with RTT
-//!         &mut capsules::console::WRITE_BUF,
 //!         &mut capsules::console::READ_BUF,
 //!         kernel::Grant::create()
 //!     )
@@ -188,6 +187,7 @@ impl<'a, A: hil::time::Alarm> SeggerRtt<'a, A> {
 
 impl<'a, A: hil::time::Alarm> uart::Uart<'a> for SeggerRtt<'a, A> {}
 impl<'a, A: hil::time::Alarm> uart::UartData<'a> for SeggerRtt<'a, A> {}
+impl<'a, A: hil::time::Alarm> uart::UartDataAdvanced<'a> for SeggerRtt<'a, A> {}
 
 impl<'a, A: hil::time::Alarm> uart::Transmit<'a> for SeggerRtt<'a, A> {
     fn set_transmit_client(&self, client: &'a uart::TransmitClient) {
@@ -278,3 +278,15 @@ impl<'a, A: hil::time::Alarm> uart::Receive<'a> for SeggerRtt<'a, A> {
         ReturnCode::SUCCESS
     }
 }
+
+// Dummy implementation so this can be the UART of a console.
+impl<'a, A: hil::time::Alarm> uart::ReceiveAdvanced<'a> for SeggerRtt<'a, A> {
+    fn receive_automatic(
+        &self,
+        buffer: &'static mut [u8],
+        _len: usize,
+        _interbyte_timeout: u8,
+    ) -> (ReturnCode, Option<&'static mut [u8]>) {
+        (ReturnCode::FAIL, Some(buffer))
+    }
+}
@@ -0,0 +1,351 @@
+//! Built-in self tests, for manufacturing and return triage.
+//!
+//! Drivers implement `SelfTest` for the checks that tell whether their
+//! hardware works: that a sensor answers with its ID, that the CRC of a flash
+//! region matches, that a radio receives its own frames. The board gives the
+//! tests to `SelfTests`, which runs one or all of them, one after another,
+//! when asked by the process console (`selftest [name]`) or by an app through
+//! its syscall interface.
+//!
+//! Each test reports an `Outcome` and a detail word whose meaning is up to
+//! the test, such as the address of the first bad word of RAM or the ID a
+//! sensor answered with. Results for the console are printed with `debug!`;
+//! results for apps go to their callbacks.
+//!
+//! `RamTest` is a test that any board can use: a march test over a scratch
+//! buffer.
+//!
+//! Usage
+//! -----
+//!
+//! ```rust
+//! let ram_test = static_init!(
+//!     capsules::self_test::RamTest<'static>,
+//!     capsules::self_test::RamTest::new(&mut RAM_TEST_BUFFER)
+//! );
+//! let tests = static_init!(
+//!     [&'static capsules::self_test::SelfTest<'static>; 2],
+//!     [ram_test, si7021]
+//! );
+//! let self_tests = static_init!(
+//!     capsules::self_test::SelfTests<'static>,
+//!     capsules::self_test::SelfTests::new(tests, kernel::Grant::create())
+//! );
+//! self_tests.initialize();
+//! pconsole.set_self_tests(self_tests);
+//! ```
+//!
+//! Syscall Interface
+//! -----------------
+//!
+//! - Allow 0: a buffer for the name of a test.
+//! - Subscribe 0: called with the index of each test that ran, its outcome
+//!   (0 passed, 1 failed, 2 could not run) and its detail word.
+//! - Subscribe 1: called at the end of a run, with the number of tests that
+//!   passed and that did not.
+//! - Command 0: whether the driver exists.
+//! - Command 1: the number of tests.
+//! - Command 2: run the test with the index in the first argument.
+//! - Command 3: run all the tests.
+//! - Command 4: copy the name of the test with the index in the first
+//!   argument into the buffer, and return its length.
+
+use core::cell::Cell;
+use kernel::common::cells::{OptionalCell, TakeCell};
+use kernel::debug;
+use kernel::{AppId, AppSlice, Callback, Driver, Grant, ReturnCode, Shared};
+
+/// Syscall driver number.
+use crate::driver;
+pub const DRIVER_NUM: usize = driver::NUM::SelfTest as usize;
+
+/// How a test went.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Outcome {
+    Passed = 0,
+    Failed = 1,
+    /// The test could not run, for instance because the hardware it tests
+    /// is in use
+    NotRun = 2,
+}
+
+/// A check of some hardware, implemented by its driver.
+pub trait SelfTest<'a> {
+    /// A short name to ask for the test by.
+    fn name(&self) -> &'static str;
+
+    fn set_client(&self, client: &'a SelfTestClient);
+
+    /// Start the test, which reports its outcome to the client once done,
+    /// possibly before this returns. If this does not return `SUCCESS`, the
+    /// test does not run, and does not report.
+    fn run(&self) -> ReturnCode;
+}
+
+pub trait SelfTestClient {
+    /// The test is done. What `detail` means is up to the test.
+    fn test_done(&self, outcome: Outcome, detail: u32);
+}
+
+#[derive(Copy, Clone, PartialEq)]
+enum Requester {
+    Console,
+    App(AppId),
+}
+
+#[derive(Default)]
+pub struct App {
+    result_callback: Option<Callback>,
+    done_callback: Option<Callback>,
+    buffer: Option<AppSlice<Shared, u8>>,
+}
+
+pub struct SelfTests<'a> {
+    tests: &'a [&'a SelfTest<'a>],
+    apps: Grant<App>,
+    requester: OptionalCell<Requester>,
+    /// The test that is running, and the last one of the run
+    current: Cell<usize>,
+    last: Cell<usize>,
+    passed: Cell<usize>,
+    failed: Cell<usize>,
+}
+
+impl SelfTests<'a> {
+    pub fn new(tests: &'a [&'a SelfTest<'a>], grant: Grant<App>) -> SelfTests<'a> {
+        SelfTests {
+            tests: tests,
+            apps: grant,
+            requester: OptionalCell::empty(),
+            current: Cell::new(0),
+            last: Cell::new(0),
+            passed: Cell::new(0),
+            failed: Cell::new(0),
+        }
+    }
+
+    /// Become the client of every test.
+    pub fn initialize(&'a self) {
+        for test in self.tests.iter() {
+            test.set_client(self);
+        }
+    }
+
+    /// Run the test named `name`, or all of them, for the console.
+    pub fn run_from_console(&self, name: Option<&str>) -> ReturnCode {
+        match name {
+            None => self.run(Requester::Console, 0, self.tests.len()),
+            Some(name) => match self.tests.iter().position(|test| test.name() == name) {
+                Some(index) => self.run(Requester::Console, index, index + 1),
+                None => {
+                    debug!("No self test named {}", name);
+                    ReturnCode::EINVAL
+                }
+            },
+        }
+    }
+
+    /// Run the tests from `first` up to `end`.
+    fn run(&self, requester: Requester, first: usize, end: usize) -> ReturnCode {
+        if self.requester.is_some() {
+            return ReturnCode::EBUSY;
+        }
+        if first >= end || end > self.tests.len() {
+            return ReturnCode::EINVAL;
+        }
+        self.requester.set(requester);
+        self.current.set(first);
+        self.last.set(end - 1);
+        self.passed.set(0);
+        self.failed.set(0);
+        self.start_current();
+        ReturnCode::SUCCESS
+    }
+
+    fn start_current(&self) {
+        let test = self.tests[self.current.get()];
+        let rcode = test.run();
+        if rcode != ReturnCode::SUCCESS {
+            self.test_done(Outcome::NotRun, usize::from(rcode) as u32);
+        }
+    }
+
+    fn report(&self, index: usize, outcome: Outcome, detail: u32) {
+        self.requester.map(|requester| match *requester {
+            Requester::Console => {
+                debug!(
+                    "Self test {:<16} {:?} ({:#x})",
+                    self.tests[index].name(),
+                    outcome,
+                    detail
+                );
+            }
+            Requester::App(appid) => {
+                let _ = self.apps.enter(appid, |app, _| {
+                    app.result_callback.map(|mut callback| {
+                        callback.schedule(index, outcome as usize, detail as usize)
+                    });
+                });
+            }
+        });
+    }
+
+    fn finish(&self) {
+        let passed = self.passed.get();
+        let failed = self.failed.get();
+        self.requester.take().map(|requester| match requester {
+            Requester::Console => debug!("Self tests: {} passed, {} failed", passed, failed),
+            Requester::App(appid) => {
+                let _ = self.apps.enter(appid, |app, _| {
+                    app.done_callback
+                        .map(|mut callback| callback.schedule(passed, failed, 0));
+                });
+            }
+        });
+    }
+}
+
+impl SelfTestClient for SelfTests<'a> {
+    fn test_done(&self, outcome: Outcome, detail: u32) {
+        let index = self.current.get();
+        if outcome == Outcome::Passed {
+            self.passed.set(self.passed.get() + 1);
+        } else {
+            self.failed.set(self.failed.get() + 1);
+        }
+        self.report(index, outcome, detail);
+
+        if index == self.last.get() {
+            self.finish();
+        } else {
+            self.current.set(index + 1);
+            self.start_current();
+        }
+    }
+}
+
+impl Driver for SelfTests<'a> {
+    fn allow(
+        &self,
+        appid: AppId,
+        allow_num: usize,
+        slice: Option<AppSlice<Shared, u8>>,
+    ) -> ReturnCode {
+        match allow_num {
+            0 => self
+                .apps
+                .enter(appid, |app, _| {
+                    app.buffer = slice;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or_else(|err| err.into()),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    fn subscribe(
+        &self,
+        subscribe_num: usize,
+        callback: Option<Callback>,
+        app_id: AppId,
+    ) -> ReturnCode {
+        self.apps
+            .enter(app_id, |app, _| match subscribe_num {
+                0 => {
+                    app.result_callback = callback;
+                    ReturnCode::SUCCESS
+                }
+                1 => {
+                    app.done_callback = callback;
+                    ReturnCode::SUCCESS
+                }
+                _ => ReturnCode::ENOSUPPORT,
+            })
+            .unwrap_or_else(|err| err.into())
+    }
+
+    fn command(&self, command_num: usize, data: usize, _: usize, appid: AppId) -> ReturnCode {
+        match command_num {
+            0 => ReturnCode::SUCCESS,
+            1 => ReturnCode::SuccessWithValue {
+                value: self.tests.len(),
+            },
+            2 => self.run(Requester::App(appid), data, data.saturating_add(1)),
+            3 => self.run(Requester::App(appid), 0, self.tests.len()),
+            4 => match self.tests.get(data) {
+                Some(test) => self
+                    .apps
+                    .enter(appid, |app, _| {
+                        app.buffer.as_mut().map_or(ReturnCode::ENOMEM, |buffer| {
+                            let name = test.name().as_bytes();
+                            let len = core::cmp::min(name.len(), buffer.len());
+                            buffer.as_mut()[..len].copy_from_slice(&name[..len]);
+                            ReturnCode::SuccessWithValue { value: len }
+                        })
+                    })
+                    .unwrap_or_else(|err| err.into()),
+                None => ReturnCode::EINVAL,
+            },
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+}
+
+/// A march test over a scratch buffer of RAM. Its detail word is the index
+/// of the first word that read back wrong.
+pub struct RamTest<'a> {
+    buffer: TakeCell<'static, [u32]>,
+    client: OptionalCell<&'a SelfTestClient>,
+}
+
+impl RamTest<'a> {
+    pub fn new(buffer: &'static mut [u32]) -> RamTest<'a> {
+        RamTest {
+            buffer: TakeCell::new(buffer),
+            client: OptionalCell::empty(),
+        }
+    }
+
+    /// March C-: write zeros up, then read zeros and write ones up, read ones
+    /// and write zeros up, and the same two down, then read zeros. This
+    /// finds stuck bits, and words whose writes disturb other words.
+    fn march(buffer: &mut [u32]) -> Result<(), usize> {
+        let len = buffer.len();
+        for word in buffer.iter_mut() {
+            *word = 0;
+        }
+        let steps = [(0, !0, true), (!0, 0, true), (0, !0, false), (!0, 0, false)];
+        for &(expected, write, up) in steps.iter() {
+            for n in 0..len {
+                let i = if up { n } else { len - 1 - n };
+                if buffer[i] != expected {
+                    return Err(i);
+                }
+                buffer[i] = write;
+            }
+        }
+        match buffer.iter().position(|&word| word != 0) {
+            Some(i) => Err(i),
+            None => Ok(()),
+        }
+    }
+}
+
+impl SelfTest<'a> for RamTest<'a> {
+    fn name(&self) -> &'static str {
+        "ram"
+    }
+
+    fn set_client(&self, client: &'a SelfTestClient) {
+        self.client.set(client);
+    }
+
+    fn run(&self) -> ReturnCode {
+        let result = self.buffer.map_or(Err(0), |buffer| Self::march(buffer));
+        self.client.map(|client| match result {
+            Ok(()) => client.test_done(Outcome::Passed, 0),
+            Err(index) => client.test_done(Outcome::Failed, index as u32),
+        });
+        ReturnCode::SUCCESS
+    }
+}
@@ -0,0 +1,196 @@
+//! UART on top of semihosting, for simulator runs and debug builds.
+//!
+//! `SemihostingUart` writes its output to the console of the host, through
+//! the semihosting function of the architecture, such as
+//! `cortexm::semihosting::write` or `rv32i::htif::write`. It can take the
+//! place of a UART for the console and for kernel debug output, so that runs
+//! in a simulator or with a debugger attached get output with no peripheral
+//! to set up.
+//!
+//! Semihosting calls block until the host is done, so a transmission is over
+//! by the time `transmit_buffer` returns. The client is still called back
+//! from a deferred call, as it would be by a UART. Nothing is ever received:
+//! a reception only ends when it is aborted.
+//!
+//! Usage
+//! -----
+//!
+//! ```
+//! let semihosting_uart = static_init!(
+//!     capsules::semihosting_uart::SemihostingUart<'static>,
+//!     capsules::semihosting_uart::SemihostingUart::new(
+//!         cortexm4::semihosting::write,
+//!         dynamic_deferred_caller
+//!     )
+//! );
+//! semihosting_uart.initialize_callback_handle(
+//!     dynamic_deferred_caller
+//!         .register(semihosting_uart)
+//!         .expect("no deferred call slot available")
+//! );
+//!
+//! // Use it as the UART of the console and of kernel debug output
+//! let uart_mux = static_init!(
+//!     MuxUart<'static>,
+//!     MuxUart::new(semihosting_uart, &mut capsules::virtual_uart::RX_BUF, 115200)
+//! );
+//! hil::uart::Transmit::set_transmit_client(semihosting_uart, uart_mux);
+//! hil::uart::Receive::set_receive_client(semihosting_uart, uart_mux);
+//! ```
+
+use core::cell::Cell;
+use kernel::common::cells::{OptionalCell, TakeCell};
+use kernel::common::dynamic_deferred_call::{
+    DeferredCallHandle, DynamicDeferredCall, DynamicDeferredCallClient,
+};
+use kernel::hil::uart;
+use kernel::ReturnCode;
+
+pub struct SemihostingUart<'a> {
+    write: fn(&[u8]),
+    tx_client: OptionalCell<&'a uart::TransmitClient>,
+    rx_client: OptionalCell<&'a uart::ReceiveClient>,
+    deferred_caller: &'a DynamicDeferredCall,
+    deferred_call_handle: OptionalCell<DeferredCallHandle>,
+    /// Buffer that was written, waiting to be returned to the client
+    tx_buffer: TakeCell<'static, [u8]>,
+    tx_len: Cell<usize>,
+    /// Buffer of the reception, which never receives anything
+    rx_buffer: TakeCell<'static, [u8]>,
+    /// Whether the reception was aborted, and its buffer must be returned
+    rx_aborted: Cell<bool>,
+}
+
+impl SemihostingUart<'a> {
+    pub fn new(write: fn(&[u8]), deferred_caller: &'a DynamicDeferredCall) -> SemihostingUart<'a> {
+        SemihostingUart {
+            write: write,
+            tx_client: OptionalCell::empty(),
+            rx_client: OptionalCell::empty(),
+            deferred_caller: deferred_caller,
+            deferred_call_handle: OptionalCell::empty(),
+            tx_buffer: TakeCell::empty(),
+            tx_len: Cell::new(0),
+            rx_buffer: TakeCell::empty(),
+            rx_aborted: Cell::new(false),
+        }
+    }
+
+    pub fn initialize_callback_handle(&self, handle: DeferredCallHandle) {
+        self.deferred_call_handle.replace(handle);
+    }
+
+    fn set_deferred_call(&self) -> ReturnCode {
+        self.deferred_call_handle
+            .map_or(ReturnCode::FAIL, |handle| {
+                self.deferred_caller.set(*handle);
+                ReturnCode::SUCCESS
+            })
+    }
+}
+
+impl uart::Configure for SemihostingUart<'a> {
+    fn configure(&self, _params: uart::Parameters) -> ReturnCode {
+        // The host console has no parameters
+        ReturnCode::SUCCESS
+    }
+}
+
+impl uart::Transmit<'a> for SemihostingUart<'a> {
+    fn set_transmit_client(&self, client: &'a uart::TransmitClient) {
+        self.tx_client.set(client);
+    }
+
+    fn transmit_buffer(
+        &self,
+        tx_buffer: &'static mut [u8],
+        tx_len: usize,
+    ) -> (ReturnCode, Option<&'static mut [u8]>) {
+        if self.tx_buffer.is_some() {
+            return (ReturnCode::EBUSY, Some(tx_buffer));
+        }
+        if tx_len > tx_buffer.len() {
+            return (ReturnCode::ESIZE, Some(tx_buffer));
+        }
+        let rcode = self.set_deferred_call();
+        if rcode != ReturnCode::SUCCESS {
+            return (rcode, Some(tx_buffer));
+        }
+
+        (self.write)(&tx_buffer[..tx_len]);
+        self.tx_len.set(tx_len);
+        self.tx_buffer.replace(tx_buffer);
+        (ReturnCode::SUCCESS, None)
+    }
+
+    fn transmit_word(&self, _word: u32) -> ReturnCode {
+        ReturnCode::FAIL
+    }
+
+    fn transmit_abort(&self) -> ReturnCode {
+        // The write is already over, and the callback is on its way
+        if self.tx_buffer.is_some() {
+            ReturnCode::FAIL
+        } else {
+            ReturnCode::SUCCESS
+        }
+    }
+}
+
+impl uart::Receive<'a> for SemihostingUart<'a> {
+    fn set_receive_client(&self, client: &'a uart::ReceiveClient) {
+        self.rx_client.set(client);
+    }
+
+    fn receive_buffer(
+        &self,
+        rx_buffer: &'static mut [u8],
+        rx_len: usize,
+    ) -> (ReturnCode, Option<&'static mut [u8]>) {
+        if self.rx_buffer.is_some() {
+            return (ReturnCode::EBUSY, Some(rx_buffer));
+        }
+        if rx_len > rx_buffer.len() {
+            return (ReturnCode::ESIZE, Some(rx_buffer));
+        }
+        self.rx_buffer.replace(rx_buffer);
+        (ReturnCode::SUCCESS, None)
+    }
+
+    fn receive_word(&self) -> ReturnCode {
+        ReturnCode::FAIL
+    }
+
+    fn receive_abort(&self) -> ReturnCode {
+        if self.rx_buffer.is_none() || self.rx_aborted.get() {
+            return ReturnCode::SUCCESS;
+        }
+        if self.set_deferred_call() != ReturnCode::SUCCESS {
+            return ReturnCode::FAIL;
+        }
+        self.rx_aborted.set(true);
+        ReturnCode::EBUSY
+    }
+}
+
+impl uart::UartData<'a> for SemihostingUart<'a> {}
+impl uart::Uart<'a> for SemihostingUart<'a> {}
+
+impl DynamicDeferredCallClient for SemihostingUart<'a> {
+    fn call(&self, _handle: DeferredCallHandle) {
+        self.tx_buffer.take().map(|tx_buffer| {
+            self.tx_client.map(move |client| {
+                client.transmitted_buffer(tx_buffer, self.tx_len.get(), ReturnCode::SUCCESS)
+            });
+        });
+
+        if self.rx_aborted.get() {
+            self.rx_aborted.set(false);
+            self.rx_buffer.take().map(|rx_buffer| {
+                self.rx_client.map(move |client| {
+                    client.received_buffer(rx_buffer, 0, ReturnCode::ECANCEL, uart::Error::Aborted)
+                });
+            });
+        }
+    }
+}
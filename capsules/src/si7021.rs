@@ -35,9 +35,11 @@
 
 use core::cell::Cell;
 use kernel::common::cells::{OptionalCell, TakeCell};
+use kernel::common::state_machine::StateCell;
 use kernel::hil::i2c;
 use kernel::hil::time;
 use kernel::hil::time::Frequency;
+use kernel::state_machine;
 use kernel::ReturnCode;
 
 // Buffer to use for I2C messages
@@ -63,26 +65,27 @@ enum Registers {
     ReadFirmwareVersionB = 0xb8,
 }
 
-/// States of the I2C protocol with the LPS331AP.
-#[derive(Clone, Copy, PartialEq)]
-enum State {
-    Idle,
-    WaitTemp,
-    WaitRh,
+state_machine! {
+    /// States of the I2C protocol with the SI7021.
+    enum State {
+        Idle => [SelectElectronicId1, TakeTempMeasurementInit, TakeRhMeasurementInit],
+        WaitTemp => [ReadTempMeasurement],
+        WaitRh => [ReadRhMeasurement],
 
-    /// States to read the internal ID
-    SelectElectronicId1,
-    ReadElectronicId1,
-    SelectElectronicId2,
-    ReadElectronicId2,
+        /// States to read the internal ID
+        SelectElectronicId1 => [ReadElectronicId1],
+        ReadElectronicId1 => [SelectElectronicId2],
+        SelectElectronicId2 => [ReadElectronicId2],
+        ReadElectronicId2 => [Idle],
 
-    /// States to take the current measurement
-    TakeTempMeasurementInit,
-    TakeRhMeasurementInit,
-    ReadRhMeasurement,
-    ReadTempMeasurement,
-    GotTempMeasurement,
-    GotRhMeasurement,
+        /// States to take the current measurement
+        TakeTempMeasurementInit => [WaitTemp],
+        TakeRhMeasurementInit => [WaitRh],
+        ReadRhMeasurement => [GotRhMeasurement],
+        ReadTempMeasurement => [GotTempMeasurement],
+        GotTempMeasurement => [TakeRhMeasurementInit, Idle],
+        GotRhMeasurement => [TakeTempMeasurementInit, Idle],
+    }
 }
 
 #[derive(PartialEq, Eq, Copy, Clone)]
@@ -97,7 +100,7 @@ pub struct SI7021<'a, A: time::Alarm> {
     alarm: &'a A,
     temp_callback: OptionalCell<&'static kernel::hil::sensors::TemperatureClient>,
     humidity_callback: OptionalCell<&'static kernel::hil::sensors::HumidityClient>,
-    state: Cell<State>,
+    state: StateCell<State>,
     on_deck: Cell<OnDeck>,
     buffer: TakeCell<'static, [u8]>,
 }
@@ -110,13 +113,16 @@ impl<A: time::Alarm> SI7021<'a, A> {
             alarm: alarm,
             temp_callback: OptionalCell::empty(),
             humidity_callback: OptionalCell::empty(),
-            state: Cell::new(State::Idle),
+            state: StateCell::new(State::Idle),
             on_deck: Cell::new(OnDeck::Nothing),
             buffer: TakeCell::new(buffer),
         }
     }
 
     pub fn read_id(&self) {
+        if self.state.transition(State::SelectElectronicId1).is_err() {
+            return;
+        }
         self.buffer.take().map(|buffer| {
             // turn on i2c to send commands
             self.i2c.enable();
@@ -124,7 +130,6 @@ impl<A: time::Alarm> SI7021<'a, A> {
             buffer[0] = Registers::ReadElectronicIdByteOneA as u8;
             buffer[1] = Registers::ReadElectronicIdByteOneB as u8;
             self.i2c.write(buffer, 2);
-            self.state.set(State::SelectElectronicId1);
         });
     }
 
@@ -142,7 +147,7 @@ impl<A: time::Alarm> SI7021<'a, A> {
     fn set_idle(&self, buffer: &'static mut [u8]) {
         self.buffer.replace(buffer);
         self.i2c.disable();
-        self.state.set(State::Idle);
+        self.state.advance(State::Idle);
     }
 }
 
@@ -151,7 +156,7 @@ impl<A: time::Alarm> i2c::I2CClient for SI7021<'a, A> {
         match self.state.get() {
             State::SelectElectronicId1 => {
                 self.i2c.read(buffer, 8);
-                self.state.set(State::ReadElectronicId1);
+                self.state.advance(State::ReadElectronicId1);
             }
             State::ReadElectronicId1 => {
                 buffer[6] = buffer[0];
@@ -165,30 +170,30 @@ impl<A: time::Alarm> i2c::I2CClient for SI7021<'a, A> {
                 buffer[0] = Registers::ReadElectronicIdByteTwoA as u8;
                 buffer[1] = Registers::ReadElectronicIdByteTwoB as u8;
                 self.i2c.write(buffer, 2);
-                self.state.set(State::SelectElectronicId2);
+                self.state.advance(State::SelectElectronicId2);
             }
             State::SelectElectronicId2 => {
                 self.i2c.read(buffer, 6);
-                self.state.set(State::ReadElectronicId2);
+                self.state.advance(State::ReadElectronicId2);
             }
             State::ReadElectronicId2 => {
                 self.set_idle(buffer);
             }
             State::TakeTempMeasurementInit => {
                 self.init_measurement(buffer);
-                self.state.set(State::WaitTemp);
+                self.state.advance(State::WaitTemp);
             }
             State::TakeRhMeasurementInit => {
                 self.init_measurement(buffer);
-                self.state.set(State::WaitRh);
+                self.state.advance(State::WaitRh);
             }
             State::ReadRhMeasurement => {
                 self.i2c.read(buffer, 2);
-                self.state.set(State::GotRhMeasurement);
+                self.state.advance(State::GotRhMeasurement);
             }
             State::ReadTempMeasurement => {
                 self.i2c.read(buffer, 2);
-                self.state.set(State::GotTempMeasurement);
+                self.state.advance(State::GotTempMeasurement);
             }
             State::GotTempMeasurement => {
                 // Temperature in hundredths of degrees centigrade
@@ -202,7 +207,7 @@ impl<A: time::Alarm> i2c::I2CClient for SI7021<'a, A> {
                         self.on_deck.set(OnDeck::Nothing);
                         buffer[0] = Registers::MeasRelativeHumidityNoHoldMode as u8;
                         self.i2c.write(buffer, 1);
-                        self.state.set(State::TakeRhMeasurementInit);
+                        self.state.advance(State::TakeRhMeasurementInit);
                     }
                     _ => {
                         self.set_idle(buffer);
@@ -221,7 +226,7 @@ impl<A: time::Alarm> i2c::I2CClient for SI7021<'a, A> {
                         self.on_deck.set(OnDeck::Nothing);
                         buffer[0] = Registers::MeasTemperatureNoHoldMode as u8;
                         self.i2c.write(buffer, 1);
-                        self.state.set(State::TakeTempMeasurementInit);
+                        self.state.advance(State::TakeTempMeasurementInit);
                     }
                     _ => {
                         self.set_idle(buffer);
@@ -235,25 +240,27 @@ impl<A: time::Alarm> i2c::I2CClient for SI7021<'a, A> {
 
 impl<A: time::Alarm> kernel::hil::sensors::TemperatureDriver for SI7021<'a, A> {
     fn read_temperature(&self) -> kernel::ReturnCode {
-        self.buffer
-            .take()
-            .map(|buffer| {
-                // turn on i2c to send commands
-                self.i2c.enable();
-
-                buffer[0] = Registers::MeasTemperatureNoHoldMode as u8;
-                self.i2c.write(buffer, 1);
-                self.state.set(State::TakeTempMeasurementInit);
+        if self
+            .state
+            .transition(State::TakeTempMeasurementInit)
+            .is_err()
+        {
+            // Take the measurement once the current one is over
+            return if self.on_deck.get() != OnDeck::Nothing {
+                ReturnCode::EBUSY
+            } else {
+                self.on_deck.set(OnDeck::Temperature);
                 ReturnCode::SUCCESS
-            })
-            .unwrap_or_else(|| {
-                if self.on_deck.get() != OnDeck::Nothing {
-                    ReturnCode::EBUSY
-                } else {
-                    self.on_deck.set(OnDeck::Temperature);
-                    ReturnCode::SUCCESS
-                }
-            })
+            };
+        }
+        self.buffer.take().map_or(ReturnCode::FAIL, |buffer| {
+            // turn on i2c to send commands
+            self.i2c.enable();
+
+            buffer[0] = Registers::MeasTemperatureNoHoldMode as u8;
+            self.i2c.write(buffer, 1);
+            ReturnCode::SUCCESS
+        })
     }
 
     fn set_client(&self, client: &'static kernel::hil::sensors::TemperatureClient) {
@@ -263,25 +270,23 @@ impl<A: time::Alarm> kernel::hil::sensors::TemperatureDriver for SI7021<'a, A> {
 
 impl<A: time::Alarm> kernel::hil::sensors::HumidityDriver for SI7021<'a, A> {
     fn read_humidity(&self) -> kernel::ReturnCode {
-        self.buffer
-            .take()
-            .map(|buffer| {
-                // turn on i2c to send commands
-                self.i2c.enable();
-
-                buffer[0] = Registers::MeasRelativeHumidityNoHoldMode as u8;
-                self.i2c.write(buffer, 1);
-                self.state.set(State::TakeRhMeasurementInit);
+        if self.state.transition(State::TakeRhMeasurementInit).is_err() {
+            // Take the measurement once the current one is over
+            return if self.on_deck.get() != OnDeck::Nothing {
+                ReturnCode::EBUSY
+            } else {
+                self.on_deck.set(OnDeck::Humidity);
                 ReturnCode::SUCCESS
-            })
-            .unwrap_or_else(|| {
-                if self.on_deck.get() != OnDeck::Nothing {
-                    ReturnCode::EBUSY
-                } else {
-                    self.on_deck.set(OnDeck::Humidity);
-                    ReturnCode::SUCCESS
-                }
-            })
+            };
+        }
+        self.buffer.take().map_or(ReturnCode::FAIL, |buffer| {
+            // turn on i2c to send commands
+            self.i2c.enable();
+
+            buffer[0] = Registers::MeasRelativeHumidityNoHoldMode as u8;
+            self.i2c.write(buffer, 1);
+            ReturnCode::SUCCESS
+        })
     }
 
     fn set_client(&self, client: &'static kernel::hil::sensors::HumidityClient) {
@@ -297,8 +302,8 @@ impl<A: time::Alarm> time::Client for SI7021<'a, A> {
 
             self.i2c.read(buffer, 2);
             match self.state.get() {
-                State::WaitRh => self.state.set(State::ReadRhMeasurement),
-                State::WaitTemp => self.state.set(State::ReadTempMeasurement),
+                State::WaitRh => self.state.advance(State::ReadRhMeasurement),
+                State::WaitTemp => self.state.advance(State::ReadTempMeasurement),
                 _ => (),
             }
         });
@@ -0,0 +1,369 @@
+//! I2C master over two GPIO pins, with the clock timed by an alarm.
+//!
+//! `SoftI2C` implements the I2C master HIL in software, so that devices can
+//! hang off any two pins when the I2C peripherals of the chip are all used,
+//! or one is wedged. The pins emulate open-drain outputs: a line is pulled
+//! low by making its pin an output that is cleared, and released by making
+//! it an input, so the lines need pull-up resistors. `enable` turns on the
+//! internal pull-ups of the pins, which are only strong enough for short
+//! buses at low speeds.
+//!
+//! Each half period of the clock is one alarm, so the speed is bounded by the
+//! frequency of the alarm: 10 kHz or less with a 32 kHz alarm. Devices can
+//! stretch the clock by holding it low, which the master waits for, up to
+//! `MAX_STRETCH` half periods. A bus that stays stuck, or a data line that
+//! reads low when the master released it, ends the transfer with
+//! `ArbitrationLost`.
+//!
+//! Usage
+//! -----
+//!
+//! ```rust
+//! let soft_i2c = static_init!(
+//!     capsules::soft_i2c::SoftI2C<'static, VirtualMuxAlarm<'static, sam4l::ast::Ast>>,
+//!     capsules::soft_i2c::SoftI2C::new(
+//!         &sam4l::gpio::PA[16],
+//!         &sam4l::gpio::PA[17],
+//!         soft_i2c_alarm
+//!     )
+//! );
+//! soft_i2c_alarm.set_client(soft_i2c);
+//! soft_i2c.set_speed(5_000);
+//! let mux_i2c = static_init!(MuxI2C<'static>, MuxI2C::new(soft_i2c));
+//! soft_i2c.set_client(mux_i2c);
+//! ```
+
+use core::cell::Cell;
+use kernel::common::cells::{OptionalCell, TakeCell};
+use kernel::hil::gpio;
+use kernel::hil::i2c::{self, Error};
+use kernel::hil::time::{self, Alarm, Frequency};
+
+/// Half periods of the clock that a device may hold it low for.
+pub const MAX_STRETCH: u32 = 1000;
+
+/// What the master does when the alarm next fires.
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Step {
+    Idle,
+    /// Pull the data line low with the clock high: a start condition
+    Start,
+    /// Pull the clock low after the start condition, and drive the first bit
+    StartHold,
+    /// Release the clock for the current bit
+    Rise,
+    /// Sample the current bit, pull the clock low, and drive the next bit
+    Fall,
+    /// Release the clock before a repeated start
+    RestartRise,
+    /// Release the clock with the data line low
+    StopRise,
+    /// Release the data line with the clock high: a stop condition
+    StopRelease,
+}
+
+/// The byte being transferred.
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Phase {
+    Address,
+    Write,
+    Read,
+}
+
+pub struct SoftI2C<'a, A: Alarm> {
+    scl: &'a gpio::Pin,
+    sda: &'a gpio::Pin,
+    alarm: &'a A,
+    client: OptionalCell<&'a i2c::I2CHwMasterClient>,
+    /// Half a period of the clock, in tics
+    half_period: Cell<u32>,
+
+    step: Cell<Step>,
+    phase: Cell<Phase>,
+    /// Half periods the clock has been stretched for
+    stretch: Cell<u32>,
+    buffer: TakeCell<'static, [u8]>,
+    addr: Cell<u8>,
+    write_len: Cell<usize>,
+    read_len: Cell<usize>,
+    /// Whether the transfer is past the writes, and the address is for a read
+    reading: Cell<bool>,
+    index: Cell<usize>,
+    byte: Cell<u8>,
+    /// Bit of the byte on the bus, the ninth one being the acknowledgement
+    bit: Cell<u8>,
+    error: Cell<Error>,
+}
+
+impl<A: Alarm> SoftI2C<'a, A> {
+    pub fn new(scl: &'a gpio::Pin, sda: &'a gpio::Pin, alarm: &'a A) -> SoftI2C<'a, A> {
+        let soft_i2c = SoftI2C {
+            scl: scl,
+            sda: sda,
+            alarm: alarm,
+            client: OptionalCell::empty(),
+            half_period: Cell::new(1),
+            step: Cell::new(Step::Idle),
+            phase: Cell::new(Phase::Address),
+            stretch: Cell::new(0),
+            buffer: TakeCell::empty(),
+            addr: Cell::new(0),
+            write_len: Cell::new(0),
+            read_len: Cell::new(0),
+            reading: Cell::new(false),
+            index: Cell::new(0),
+            byte: Cell::new(0),
+            bit: Cell::new(0),
+            error: Cell::new(Error::CommandComplete),
+        };
+        soft_i2c.set_speed(100_000);
+        soft_i2c
+    }
+
+    pub fn set_client(&self, client: &'a i2c::I2CHwMasterClient) {
+        self.client.set(client);
+    }
+
+    /// Set the frequency of the clock, in Hz. It is 100 kHz by default, and
+    /// is rounded down to what the alarm can time.
+    pub fn set_speed(&self, hz: u32) {
+        let half_period = <A::Frequency>::frequency() / hz.saturating_mul(2).max(1);
+        self.half_period.set(half_period.max(1));
+    }
+
+    fn release(pin: &gpio::Pin) {
+        pin.make_input();
+    }
+
+    fn pull_low(pin: &gpio::Pin) {
+        pin.clear();
+        pin.make_output();
+    }
+
+    fn drive_sda(&self, high: bool) {
+        if high {
+            Self::release(self.sda);
+        } else {
+            Self::pull_low(self.sda);
+        }
+    }
+
+    /// The level the master drives the data line to for the current bit. It
+    /// releases the line for the bits the device drives.
+    fn out_bit(&self) -> bool {
+        let bit = self.bit.get();
+        match self.phase.get() {
+            Phase::Address | Phase::Write => bit == 8 || (self.byte.get() >> (7 - bit)) & 1 == 1,
+            // Acknowledge every byte but the last one
+            Phase::Read => bit < 8 || self.index.get() + 1 == self.read_len.get(),
+        }
+    }
+
+    fn start_transfer(
+        &self,
+        addr: u8,
+        buffer: &'static mut [u8],
+        write_len: usize,
+        read_len: usize,
+    ) {
+        self.buffer.replace(buffer);
+        self.addr.set(addr);
+        self.write_len.set(write_len);
+        self.read_len.set(read_len);
+        self.reading.set(write_len == 0);
+        self.error.set(Error::CommandComplete);
+        self.stretch.set(0);
+        self.step.set(Step::Start);
+        self.wait();
+    }
+
+    fn wait(&self) {
+        let now = self.alarm.now();
+        self.alarm
+            .set_alarm(now.wrapping_add(self.half_period.get()));
+    }
+
+    /// Whether the clock is high, or a device is stretching it.
+    fn clock_released(&self) -> bool {
+        if self.scl.read() {
+            self.stretch.set(0);
+            return true;
+        }
+        self.stretch.set(self.stretch.get() + 1);
+        if self.stretch.get() > MAX_STRETCH {
+            self.fail(Error::ArbitrationLost);
+        } else {
+            self.wait();
+        }
+        false
+    }
+
+    /// Drive the first bit of the byte in `phase`, with the clock low.
+    fn load(&self, phase: Phase, byte: u8) {
+        self.phase.set(phase);
+        self.byte.set(byte);
+        self.bit.set(0);
+        self.drive_sda(self.out_bit());
+        self.step.set(Step::Rise);
+    }
+
+    /// Move on from a byte once it is acknowledged, with the clock low.
+    fn next_byte(&self) {
+        match self.phase.get() {
+            Phase::Address => {
+                self.index.set(0);
+                if self.reading.get() {
+                    self.load(Phase::Read, 0);
+                    return;
+                }
+            }
+            Phase::Write => self.index.set(self.index.get() + 1),
+            Phase::Read => {
+                let index = self.index.get();
+                self.buffer.map(|buffer| buffer[index] = self.byte.get());
+                self.index.set(index + 1);
+                if index + 1 < self.read_len.get() {
+                    self.load(Phase::Read, 0);
+                } else {
+                    self.stop();
+                }
+                return;
+            }
+        }
+
+        let index = self.index.get();
+        if index < self.write_len.get() {
+            let byte = self.buffer.map_or(0, |buffer| buffer[index]);
+            self.load(Phase::Write, byte);
+        } else if self.read_len.get() > 0 {
+            self.reading.set(true);
+            Self::release(self.sda);
+            self.step.set(Step::RestartRise);
+        } else {
+            self.stop();
+        }
+    }
+
+    fn stop(&self) {
+        Self::pull_low(self.sda);
+        self.step.set(Step::StopRise);
+    }
+
+    /// End the transfer without a stop condition, as the bus is not ours.
+    fn fail(&self, error: Error) {
+        Self::release(self.scl);
+        Self::release(self.sda);
+        self.finish(error);
+    }
+
+    fn finish(&self, error: Error) {
+        self.step.set(Step::Idle);
+        self.buffer.take().map(|buffer| {
+            self.client
+                .map(move |client| client.command_complete(buffer, error));
+        });
+    }
+}
+
+impl<A: Alarm> i2c::I2CMaster for SoftI2C<'a, A> {
+    fn enable(&self) {
+        self.scl.set_floating_state(gpio::FloatingState::PullUp);
+        self.sda.set_floating_state(gpio::FloatingState::PullUp);
+        Self::release(self.scl);
+        Self::release(self.sda);
+    }
+
+    fn disable(&self) {
+        self.scl.deactivate_to_low_power();
+        self.sda.deactivate_to_low_power();
+    }
+
+    fn write_read(&self, addr: u8, data: &'static mut [u8], write_len: u8, read_len: u8) {
+        self.start_transfer(addr, data, write_len as usize, read_len as usize);
+    }
+
+    fn write(&self, addr: u8, data: &'static mut [u8], len: u8) {
+        self.start_transfer(addr, data, len as usize, 0);
+    }
+
+    fn read(&self, addr: u8, buffer: &'static mut [u8], len: u8) {
+        self.start_transfer(addr, buffer, 0, len as usize);
+    }
+}
+
+impl<A: Alarm> time::Client for SoftI2C<'a, A> {
+    fn fired(&self) {
+        match self.step.get() {
+            Step::Idle => return,
+            Step::Start => {
+                if !self.clock_released() {
+                    return;
+                }
+                if !self.sda.read() {
+                    // Another master, or a device stuck in a transfer
+                    self.fail(Error::ArbitrationLost);
+                    return;
+                }
+                Self::pull_low(self.sda);
+                self.step.set(Step::StartHold);
+            }
+            Step::StartHold => {
+                Self::pull_low(self.scl);
+                let address = self.addr.get() << 1 | self.reading.get() as u8;
+                self.load(Phase::Address, address);
+            }
+            Step::Rise => {
+                Self::release(self.scl);
+                self.step.set(Step::Fall);
+            }
+            Step::Fall => {
+                if !self.clock_released() {
+                    return;
+                }
+                let sda = self.sda.read();
+                let bit = self.bit.get();
+                if bit < 8 {
+                    if self.phase.get() == Phase::Read {
+                        self.byte.set(self.byte.get() << 1 | sda as u8);
+                    } else if self.out_bit() && !sda {
+                        self.fail(Error::ArbitrationLost);
+                        return;
+                    }
+                }
+
+                Self::pull_low(self.scl);
+                if bit < 8 {
+                    self.bit.set(bit + 1);
+                    self.drive_sda(self.out_bit());
+                    self.step.set(Step::Rise);
+                } else if self.phase.get() != Phase::Read && sda {
+                    self.error.set(if self.phase.get() == Phase::Address {
+                        Error::AddressNak
+                    } else {
+                        Error::DataNak
+                    });
+                    self.stop();
+                } else {
+                    self.next_byte();
+                }
+            }
+            Step::RestartRise => {
+                Self::release(self.scl);
+                self.step.set(Step::Start);
+            }
+            Step::StopRise => {
+                Self::release(self.scl);
+                self.step.set(Step::StopRelease);
+            }
+            Step::StopRelease => {
+                if !self.clock_released() {
+                    return;
+                }
+                Self::release(self.sda);
+                self.finish(self.error.get());
+                return;
+            }
+        }
+        self.wait();
+    }
+}
@@ -0,0 +1,464 @@
+//! UART over two GPIO pins, with bits timed by an alarm.
+//!
+//! `SoftUart` implements the UART HIL in software, for boards that need one
+//! more serial port than they have UARTs. It drives the bits of each frame on
+//! the TX pin at the times an alarm gives, and samples the RX pin in the
+//! middle of each bit, starting from the falling edge of the start bit.
+//!
+//! The bits of a frame are timed from its start, so rounding the bit period to
+//! tics of the alarm does not add up over the frame. Still, the alarm must
+//! tick several times per bit, and the kernel services GPIO and alarm
+//! interrupts with some latency, so this is only suitable for low baud rates:
+//! at most a few thousand bits per second with a 32 kHz alarm. The TX pin
+//! sends and the RX pin receives at the same time, through one alarm.
+//!
+//! Usage
+//! -----
+//!
+//! ```rust
+//! let soft_uart = static_init!(
+//!     capsules::soft_uart::SoftUart<'static, VirtualMuxAlarm<'static, sam4l::ast::Ast>>,
+//!     capsules::soft_uart::SoftUart::new(
+//!         &sam4l::gpio::PA[20],
+//!         &sam4l::gpio::PA[21],
+//!         soft_uart_alarm
+//!     )
+//! );
+//! soft_uart_alarm.set_client(soft_uart);
+//! sam4l::gpio::PA[21].set_client(soft_uart);
+//! soft_uart.configure(hil::uart::Parameters {
+//!     baud_rate: 1200,
+//!     width: hil::uart::Width::Eight,
+//!     parity: hil::uart::Parity::None,
+//!     stop_bits: hil::uart::StopBits::One,
+//!     hw_flow_control: false,
+//! });
+//! ```
+
+use core::cell::Cell;
+use kernel::common::cells::{OptionalCell, TakeCell};
+use kernel::hil::gpio;
+use kernel::hil::time::{self, Alarm, Frequency};
+use kernel::hil::uart;
+use kernel::ReturnCode;
+
+/// Fractional bits of the bit period.
+const PERIOD_SHIFT: u32 = 8;
+
+/// Fewest tics of the alarm in a bit, to sample it near its middle.
+const MIN_TICS_PER_BIT: u32 = 4;
+
+#[derive(Copy, Clone, PartialEq)]
+enum TxState {
+    Idle,
+    Transmitting,
+    /// Transmitting, until the next bit, where it stops
+    Aborting,
+}
+
+#[derive(Copy, Clone, PartialEq)]
+enum RxState {
+    Idle,
+    /// Waiting for the falling edge of a start bit
+    WaitStart,
+    /// Sampling the bits of a frame
+    Receiving,
+    /// Waiting for a bit period before returning the buffer
+    Aborting,
+}
+
+pub struct SoftUart<'a, A: Alarm> {
+    tx_pin: &'a gpio::Pin,
+    rx_pin: &'a gpio::InterruptPin,
+    alarm: &'a A,
+    tx_client: OptionalCell<&'a uart::TransmitClient>,
+    rx_client: OptionalCell<&'a uart::ReceiveClient>,
+
+    /// Length of a bit, in tics with `PERIOD_SHIFT` fractional bits, or 0
+    /// until configured
+    period: Cell<u32>,
+    width: Cell<u32>,
+    parity: Cell<uart::Parity>,
+    stop_bits: Cell<u32>,
+
+    tx_state: Cell<TxState>,
+    tx_buffer: TakeCell<'static, [u8]>,
+    tx_len: Cell<usize>,
+    tx_index: Cell<usize>,
+    /// Start of the frame being sent, and its next bit
+    tx_start: Cell<u32>,
+    tx_bit: Cell<u32>,
+
+    rx_state: Cell<RxState>,
+    rx_buffer: TakeCell<'static, [u8]>,
+    rx_len: Cell<usize>,
+    rx_index: Cell<usize>,
+    /// Falling edge of the start bit of the frame being received, its next
+    /// bit, and the bits so far
+    rx_start: Cell<u32>,
+    rx_bit: Cell<u32>,
+    rx_frame: Cell<u32>,
+}
+
+impl<A: Alarm> SoftUart<'a, A> {
+    pub fn new(
+        tx_pin: &'a gpio::Pin,
+        rx_pin: &'a gpio::InterruptPin,
+        alarm: &'a A,
+    ) -> SoftUart<'a, A> {
+        SoftUart {
+            tx_pin: tx_pin,
+            rx_pin: rx_pin,
+            alarm: alarm,
+            tx_client: OptionalCell::empty(),
+            rx_client: OptionalCell::empty(),
+            period: Cell::new(0),
+            width: Cell::new(8),
+            parity: Cell::new(uart::Parity::None),
+            stop_bits: Cell::new(1),
+            tx_state: Cell::new(TxState::Idle),
+            tx_buffer: TakeCell::empty(),
+            tx_len: Cell::new(0),
+            tx_index: Cell::new(0),
+            tx_start: Cell::new(0),
+            tx_bit: Cell::new(0),
+            rx_state: Cell::new(RxState::Idle),
+            rx_buffer: TakeCell::empty(),
+            rx_len: Cell::new(0),
+            rx_index: Cell::new(0),
+            rx_start: Cell::new(0),
+            rx_bit: Cell::new(0),
+            rx_frame: Cell::new(0),
+        }
+    }
+
+    /// Number of bits in a frame, from the start bit to the last stop bit.
+    fn frame_bits(&self) -> u32 {
+        let parity = if self.parity.get() == uart::Parity::None {
+            0
+        } else {
+            1
+        };
+        1 + self.width.get() + parity + self.stop_bits.get()
+    }
+
+    /// The value of the parity bit for `data`, if there is one.
+    fn parity_bit(&self, data: u32) -> u32 {
+        let ones = data.count_ones() & 1;
+        match self.parity.get() {
+            uart::Parity::Even => ones,
+            uart::Parity::Odd => ones ^ 1,
+            uart::Parity::None => 0,
+        }
+    }
+
+    /// The bits of the frame of `byte`, starting with the start bit.
+    fn frame(&self, byte: u8) -> u32 {
+        let width = self.width.get();
+        let data = byte as u32 & ((1 << width) - 1);
+        let mut frame = data << 1;
+        let mut next = 1 + width;
+        if self.parity.get() != uart::Parity::None {
+            frame |= self.parity_bit(data) << next;
+            next += 1;
+        }
+        for _ in 0..self.stop_bits.get() {
+            frame |= 1 << next;
+            next += 1;
+        }
+        frame
+    }
+
+    /// Tics from the start of a frame to `bit` of it.
+    fn bit_time(&self, bit: u32) -> u32 {
+        ((bit as u64 * self.period.get() as u64) >> PERIOD_SHIFT) as u32
+    }
+
+    /// Set the alarm for the next bit to send or sample, after handling the
+    /// bits that are due.
+    fn schedule(&self) {
+        loop {
+            let now = self.alarm.now();
+            let mut next: Option<u32> = None;
+
+            if self.tx_state.get() != TxState::Idle {
+                let dt = self.bit_time(self.tx_bit.get());
+                let remaining = time::remaining(now, self.tx_start.get(), dt);
+                if remaining == 0 {
+                    self.tx_next_bit();
+                    continue;
+                }
+                next = Some(remaining);
+            }
+
+            if self.rx_state.get() == RxState::Receiving || self.rx_state.get() == RxState::Aborting
+            {
+                // Sample in the middle of the bit
+                let dt = self.bit_time(self.rx_bit.get()) + self.bit_time(1) / 2;
+                let remaining = time::remaining(now, self.rx_start.get(), dt);
+                if remaining == 0 {
+                    self.rx_next_bit();
+                    continue;
+                }
+                next = Some(next.map_or(remaining, |tx| core::cmp::min(tx, remaining)));
+            }
+
+            match next {
+                Some(remaining) => self.alarm.set_alarm(now.wrapping_add(remaining)),
+                None => self.alarm.disable(),
+            }
+            return;
+        }
+    }
+
+    /// Drive the next bit of the frame being sent, or start the next frame.
+    fn tx_next_bit(&self) {
+        if self.tx_state.get() == TxState::Aborting {
+            self.tx_pin.set();
+            self.tx_done(ReturnCode::ECANCEL);
+            return;
+        }
+
+        let bit = self.tx_bit.get();
+        if bit == self.frame_bits() {
+            // The last stop bit is over
+            self.tx_index.set(self.tx_index.get() + 1);
+            if self.tx_index.get() == self.tx_len.get() {
+                self.tx_done(ReturnCode::SUCCESS);
+                return;
+            }
+            self.tx_start
+                .set(self.tx_start.get().wrapping_add(self.bit_time(bit)));
+            self.tx_bit.set(0);
+            return;
+        }
+
+        let frame = self
+            .tx_buffer
+            .map_or(0, |buffer| self.frame(buffer[self.tx_index.get()]));
+        if (frame >> bit) & 1 == 1 {
+            self.tx_pin.set();
+        } else {
+            self.tx_pin.clear();
+        }
+        self.tx_bit.set(bit + 1);
+    }
+
+    fn tx_done(&self, rcode: ReturnCode) {
+        self.tx_state.set(TxState::Idle);
+        self.tx_buffer.take().map(|buffer| {
+            self.tx_client
+                .map(move |client| client.transmitted_buffer(buffer, self.tx_index.get(), rcode));
+        });
+    }
+
+    /// Sample the next bit of the frame being received.
+    fn rx_next_bit(&self) {
+        if self.rx_state.get() == RxState::Aborting {
+            self.rx_done(ReturnCode::ECANCEL, uart::Error::Aborted);
+            return;
+        }
+
+        let bit = self.rx_bit.get();
+        if self.rx_pin.read() {
+            self.rx_frame.set(self.rx_frame.get() | 1 << bit);
+        }
+
+        // Sample up to the first stop bit, the others are idle time
+        let last = self.frame_bits() - self.stop_bits.get();
+        if bit < last {
+            self.rx_bit.set(bit + 1);
+            return;
+        }
+
+        let frame = self.rx_frame.get();
+        let width = self.width.get();
+        let data = (frame >> 1) & ((1 << width) - 1);
+        if (frame >> last) & 1 == 0 {
+            self.rx_done(ReturnCode::FAIL, uart::Error::FramingError);
+            return;
+        }
+        if self.parity.get() != uart::Parity::None
+            && (frame >> (1 + width)) & 1 != self.parity_bit(data)
+        {
+            self.rx_done(ReturnCode::FAIL, uart::Error::ParityError);
+            return;
+        }
+
+        self.rx_buffer
+            .map(|buffer| buffer[self.rx_index.get()] = data as u8);
+        self.rx_index.set(self.rx_index.get() + 1);
+        if self.rx_index.get() == self.rx_len.get() {
+            self.rx_done(ReturnCode::SUCCESS, uart::Error::None);
+        } else {
+            self.rx_state.set(RxState::WaitStart);
+            self.rx_pin
+                .enable_interrupts(gpio::InterruptEdge::FallingEdge);
+        }
+    }
+
+    fn rx_done(&self, rcode: ReturnCode, error: uart::Error) {
+        self.rx_pin.disable_interrupts();
+        self.rx_state.set(RxState::Idle);
+        self.rx_buffer.take().map(|buffer| {
+            self.rx_client.map(move |client| {
+                client.received_buffer(buffer, self.rx_index.get(), rcode, error)
+            });
+        });
+    }
+}
+
+impl<A: Alarm> uart::Configure for SoftUart<'a, A> {
+    fn configure(&self, params: uart::Parameters) -> ReturnCode {
+        if params.baud_rate == 0 {
+            return ReturnCode::EINVAL;
+        }
+        let frequency = <A::Frequency>::frequency();
+        if params.hw_flow_control || frequency / params.baud_rate < MIN_TICS_PER_BIT {
+            return ReturnCode::ENOSUPPORT;
+        }
+        if self.tx_state.get() != TxState::Idle || self.rx_state.get() != RxState::Idle {
+            return ReturnCode::EBUSY;
+        }
+
+        let period = ((frequency as u64) << PERIOD_SHIFT) / params.baud_rate as u64;
+        self.period.set(period as u32);
+        self.width.set(params.width as u32);
+        self.parity.set(params.parity);
+        self.stop_bits.set(params.stop_bits as u32);
+
+        // The line is high when idle
+        self.tx_pin.make_output();
+        self.tx_pin.set();
+        self.rx_pin.make_input();
+        ReturnCode::SUCCESS
+    }
+}
+
+impl<A: Alarm> uart::Transmit<'a> for SoftUart<'a, A> {
+    fn set_transmit_client(&self, client: &'a uart::TransmitClient) {
+        self.tx_client.set(client);
+    }
+
+    fn transmit_buffer(
+        &self,
+        tx_buffer: &'static mut [u8],
+        tx_len: usize,
+    ) -> (ReturnCode, Option<&'static mut [u8]>) {
+        if self.period.get() == 0 {
+            return (ReturnCode::EOFF, Some(tx_buffer));
+        }
+        if self.tx_state.get() != TxState::Idle {
+            return (ReturnCode::EBUSY, Some(tx_buffer));
+        }
+        if tx_len > tx_buffer.len() {
+            return (ReturnCode::ESIZE, Some(tx_buffer));
+        }
+        if tx_len == 0 {
+            return (ReturnCode::EINVAL, Some(tx_buffer));
+        }
+
+        self.tx_buffer.replace(tx_buffer);
+        self.tx_len.set(tx_len);
+        self.tx_index.set(0);
+        self.tx_start.set(self.alarm.now());
+        self.tx_bit.set(0);
+        self.tx_state.set(TxState::Transmitting);
+        self.schedule();
+        (ReturnCode::SUCCESS, None)
+    }
+
+    fn transmit_word(&self, _word: u32) -> ReturnCode {
+        ReturnCode::FAIL
+    }
+
+    fn transmit_abort(&self) -> ReturnCode {
+        match self.tx_state.get() {
+            TxState::Idle => ReturnCode::SUCCESS,
+            TxState::Transmitting => {
+                self.tx_state.set(TxState::Aborting);
+                ReturnCode::EBUSY
+            }
+            TxState::Aborting => ReturnCode::EBUSY,
+        }
+    }
+}
+
+impl<A: Alarm> uart::Receive<'a> for SoftUart<'a, A> {
+    fn set_receive_client(&self, client: &'a uart::ReceiveClient) {
+        self.rx_client.set(client);
+    }
+
+    fn receive_buffer(
+        &self,
+        rx_buffer: &'static mut [u8],
+        rx_len: usize,
+    ) -> (ReturnCode, Option<&'static mut [u8]>) {
+        if self.period.get() == 0 {
+            return (ReturnCode::EOFF, Some(rx_buffer));
+        }
+        if self.rx_state.get() != RxState::Idle {
+            return (ReturnCode::EBUSY, Some(rx_buffer));
+        }
+        if rx_len > rx_buffer.len() {
+            return (ReturnCode::ESIZE, Some(rx_buffer));
+        }
+        if rx_len == 0 {
+            return (ReturnCode::EINVAL, Some(rx_buffer));
+        }
+
+        self.rx_buffer.replace(rx_buffer);
+        self.rx_len.set(rx_len);
+        self.rx_index.set(0);
+        self.rx_state.set(RxState::WaitStart);
+        self.rx_pin
+            .enable_interrupts(gpio::InterruptEdge::FallingEdge);
+        (ReturnCode::SUCCESS, None)
+    }
+
+    fn receive_word(&self) -> ReturnCode {
+        ReturnCode::FAIL
+    }
+
+    fn receive_abort(&self) -> ReturnCode {
+        match self.rx_state.get() {
+            RxState::Idle => ReturnCode::SUCCESS,
+            RxState::Aborting => ReturnCode::EBUSY,
+            RxState::WaitStart | RxState::Receiving => {
+                // Return the buffer once a bit period has passed, rather than
+                // from within this call
+                self.rx_pin.disable_interrupts();
+                self.rx_state.set(RxState::Aborting);
+                self.rx_start.set(self.alarm.now());
+                self.rx_bit.set(0);
+                self.schedule();
+                ReturnCode::EBUSY
+            }
+        }
+    }
+}
+
+impl<A: Alarm> uart::UartData<'a> for SoftUart<'a, A> {}
+impl<A: Alarm> uart::Uart<'a> for SoftUart<'a, A> {}
+
+impl<A: Alarm> time::Client for SoftUart<'a, A> {
+    fn fired(&self) {
+        self.schedule();
+    }
+}
+
+impl<A: Alarm> gpio::Client for SoftUart<'a, A> {
+    fn fired(&self) {
+        if self.rx_state.get() != RxState::WaitStart {
+            return;
+        }
+        // The falling edge of a start bit. Sample it in its middle, to make
+        // sure it was not a glitch, and then each bit after it.
+        self.rx_pin.disable_interrupts();
+        self.rx_start.set(self.alarm.now());
+        self.rx_bit.set(0);
+        self.rx_frame.set(0);
+        self.rx_state.set(RxState::Receiving);
+        self.schedule();
+    }
+}
@@ -0,0 +1,222 @@
+//! Software implementation of the CRC interface.
+//!
+//! `SoftwareCrc` can compute any CRC that is described by `CrcParams`, which
+//! makes it a fallback for chips without a CRC unit, or for algorithms that the
+//! CRC unit of a chip does not support. The CRC is computed bit by bit within
+//! `compute`, and the result is delivered to the client from a deferred call,
+//! as a hardware unit would.
+//!
+//! Usage
+//! -----
+//!
+//! ```
+//! let software_crc = static_init!(
+//!     capsules::software_crc::SoftwareCrc<'static>,
+//!     capsules::software_crc::SoftwareCrc::new(dynamic_deferred_caller)
+//! );
+//! software_crc.initialize_callback_handle(
+//!     dynamic_deferred_caller.register(software_crc).expect("no deferred call slot available")
+//! );
+//! software_crc.set_client(crc);
+//! crc.set_fallback(software_crc);
+//! ```
+
+use core::cell::Cell;
+use kernel::common::cells::OptionalCell;
+use kernel::common::dynamic_deferred_call::{
+    DeferredCallHandle, DynamicDeferredCall, DynamicDeferredCallClient,
+};
+use kernel::hil::crc::{self, CrcAlg, CrcParams};
+use kernel::ReturnCode;
+
+/// Reverses the order of the lowest `width` bits of `value`.
+fn reflect(value: u32, width: u8) -> u32 {
+    let mut reflected = 0;
+    for i in 0..width {
+        if value & (1 << i) != 0 {
+            reflected |= 1 << (width - 1 - i);
+        }
+    }
+    reflected
+}
+
+/// Computes the CRC of `data` described by `params`. Returns `None` if the
+/// width is not between 1 and 32 bits.
+pub fn compute_crc(params: &CrcParams, data: &[u8]) -> Option<u32> {
+    if params.width == 0 || params.width > 32 {
+        return None;
+    }
+    let mask = if params.width == 32 {
+        0xFFFFFFFF
+    } else {
+        (1 << params.width) - 1
+    };
+    let top = 1 << (params.width - 1);
+    let poly = params.poly & mask;
+
+    let mut crc = params.init & mask;
+    for &byte in data {
+        let byte = if params.reflect_in {
+            reflect(byte as u32, 8)
+        } else {
+            byte as u32
+        };
+        for i in (0..8).rev() {
+            let input = byte & (1 << i) != 0;
+            let msb = crc & top != 0;
+            crc = (crc << 1) & mask;
+            if input != msb {
+                crc ^= poly;
+            }
+        }
+    }
+    if params.reflect_out {
+        crc = reflect(crc, params.width);
+    }
+    Some((crc ^ params.xor_out) & mask)
+}
+
+pub struct SoftwareCrc<'a> {
+    client: OptionalCell<&'a crc::Client>,
+    deferred_caller: &'a DynamicDeferredCall,
+    deferred_call_handle: OptionalCell<DeferredCallHandle>,
+    /// Result waiting to be delivered to the client
+    result: OptionalCell<u32>,
+    busy: Cell<bool>,
+}
+
+impl SoftwareCrc<'a> {
+    pub fn new(deferred_caller: &'a DynamicDeferredCall) -> SoftwareCrc<'a> {
+        SoftwareCrc {
+            client: OptionalCell::empty(),
+            deferred_caller: deferred_caller,
+            deferred_call_handle: OptionalCell::empty(),
+            result: OptionalCell::empty(),
+            busy: Cell::new(false),
+        }
+    }
+
+    pub fn initialize_callback_handle(&self, handle: DeferredCallHandle) {
+        self.deferred_call_handle.replace(handle);
+    }
+
+    pub fn set_client(&self, client: &'a crc::Client) {
+        self.client.set(client);
+    }
+}
+
+impl crc::CRC for SoftwareCrc<'a> {
+    fn compute(&self, data: &[u8], alg: CrcAlg) -> ReturnCode {
+        if self.busy.get() {
+            return ReturnCode::EBUSY;
+        }
+        let result = match alg.params() {
+            Some(params) => compute_crc(&params, data),
+            None => return ReturnCode::ENOSUPPORT,
+        };
+        match result {
+            Some(result) => {
+                let handle = match self.deferred_call_handle.map(|handle| *handle) {
+                    Some(handle) => handle,
+                    None => return ReturnCode::FAIL,
+                };
+                self.result.set(result);
+                self.busy.set(true);
+                self.deferred_caller.set(handle);
+                ReturnCode::SUCCESS
+            }
+            None => ReturnCode::EINVAL,
+        }
+    }
+
+    fn disable(&self) {}
+}
+
+impl DynamicDeferredCallClient for SoftwareCrc<'a> {
+    fn call(&self, _handle: DeferredCallHandle) {
+        self.busy.set(false);
+        self.result.take().map(|result| {
+            self.client.map(|client| client.receive_result(result));
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::compute_crc;
+    use kernel::hil::crc::{CrcAlg, CrcParams};
+
+    /// The input of the standard check values of CRC catalogues
+    const CHECK_INPUT: &[u8] = b"123456789";
+
+    fn check(alg: CrcAlg) -> Option<u32> {
+        compute_crc(&alg.params().unwrap(), CHECK_INPUT)
+    }
+
+    #[test]
+    fn crc32() {
+        assert_eq!(check(CrcAlg::Crc32), Some(0xCBF43926));
+    }
+
+    #[test]
+    fn crc32c() {
+        assert_eq!(check(CrcAlg::Crc32C), Some(0xE3069283));
+    }
+
+    #[test]
+    fn crc8() {
+        assert_eq!(check(CrcAlg::Crc8), Some(0xF4));
+    }
+
+    #[test]
+    fn crc16_ccitt() {
+        assert_eq!(check(CrcAlg::Crc16CCITT), Some(0x29B1));
+    }
+
+    #[test]
+    fn custom() {
+        // CRC-16/X-25, which reflects its input and output
+        let x25 = CrcParams {
+            width: 16,
+            poly: 0x1021,
+            init: 0xFFFF,
+            reflect_in: true,
+            reflect_out: true,
+            xor_out: 0xFFFF,
+        };
+        assert_eq!(check(CrcAlg::Custom(x25)), Some(0x906E));
+
+        // CRC-5/USB, which is narrower than a byte
+        let usb = CrcParams {
+            width: 5,
+            poly: 0x05,
+            init: 0x1F,
+            reflect_in: true,
+            reflect_out: true,
+            xor_out: 0x1F,
+        };
+        assert_eq!(check(CrcAlg::Custom(usb)), Some(0x19));
+    }
+
+    #[test]
+    fn empty_input() {
+        let params = CrcAlg::Crc32.params().unwrap();
+        assert_eq!(compute_crc(&params, &[]), Some(0x00000000));
+    }
+
+    #[test]
+    fn invalid_width() {
+        let mut params = CrcAlg::Crc32.params().unwrap();
+        params.width = 0;
+        assert_eq!(compute_crc(&params, CHECK_INPUT), None);
+        params.width = 33;
+        assert_eq!(compute_crc(&params, CHECK_INPUT), None);
+    }
+
+    #[test]
+    fn hardware_specific() {
+        assert!(CrcAlg::Sam4L16.params().is_none());
+        assert!(CrcAlg::Sam4L32.params().is_none());
+        assert!(CrcAlg::Sam4L32C.params().is_none());
+    }
+}
@@ -0,0 +1,446 @@
+//! SWD master over two GPIO pins, for programming and debugging a companion
+//! microcontroller.
+//!
+//! Products with a co-processor need a way to update its firmware in the
+//! field. This capsule bit-bangs the Serial Wire Debug protocol of ARM
+//! Cortex-M cores on the SWCLK and SWDIO pins of the co-processor, and gives
+//! an updater app access to the memory of the co-processor through its
+//! AHB-AP, along with halting, resuming and resetting its core.
+//!
+//! Programming the flash of the co-processor is specific to its flash
+//! controller, so the app does it with memory accesses: it halts the core,
+//! writes the registers of the flash controller to erase pages and enable
+//! writes, and then writes the firmware blob into flash through the memory of
+//! the target.
+//!
+//! Each command runs to completion before it returns, with the pins toggled
+//! as fast as the CPU toggles them. Large transfers hold the kernel for a few
+//! milliseconds, so the app should split blobs into chunks of a kilobyte or
+//! so.
+//!
+//! Usage
+//! -----
+//!
+//! ```rust
+//! let swd = static_init!(
+//!     capsules::swd::Swd<'static>,
+//!     capsules::swd::Swd::new(
+//!         &sam4l::gpio::PA[18],
+//!         &sam4l::gpio::PA[19],
+//!         kernel::Grant::create()
+//!     )
+//! );
+//! ```
+//!
+//! Syscall Interface
+//! -----------------
+//!
+//! - Allow 0: the buffer that memory is read into and written from.
+//! - Subscribe 0: called with the value read by `connect` (the IDCODE of the
+//!   debug port) and by the read word command.
+//! - Command 0: whether the driver exists.
+//! - Command 1: connect to the target and power up its debug port. The app
+//!   that connects owns the driver until it disconnects.
+//! - Command 2: halt the core.
+//! - Command 3: resume the core.
+//! - Command 4: reset the target. If the first argument is not 0, the core
+//!   stays halted at its reset vector.
+//! - Command 5: read the word at the address in the first argument.
+//! - Command 6: write the second argument to the word at the address in the
+//!   first argument.
+//! - Command 7: read the number of bytes in the second argument, from the
+//!   address in the first argument, into the buffer.
+//! - Command 8: write the number of bytes in the second argument from the
+//!   buffer to the address in the first argument.
+//! - Command 9: disconnect from the target, and release the pins.
+//!
+//! Addresses and lengths must be multiples of 4. Commands fail with `FAIL`
+//! when the target reports a fault, and with `EBUSY` when it keeps asking to
+//! wait.
+
+use kernel::common::cells::OptionalCell;
+use kernel::hil::gpio;
+use kernel::{AppId, AppSlice, Callback, Driver, Grant, ReturnCode, Shared};
+
+use crate::driver;
+pub const DRIVER_NUM: usize = driver::NUM::Swd as usize;
+
+/// Times a transfer is retried when the target acknowledges it with WAIT.
+const WAIT_RETRIES: usize = 100;
+
+// Responses of the target to a request
+const ACK_OK: u32 = 0b001;
+const ACK_WAIT: u32 = 0b010;
+const ACK_FAULT: u32 = 0b100;
+
+// Debug port registers
+const DP_IDCODE: u8 = 0x0;
+const DP_ABORT: u8 = 0x0;
+const DP_CTRL_STAT: u8 = 0x4;
+const DP_SELECT: u8 = 0x8;
+const DP_RDBUFF: u8 = 0xC;
+
+/// Clears the sticky error flags of the debug port.
+const ABORT_CLEAR_ERRORS: u32 = 0x1E;
+const CTRL_STAT_POWER_UP_REQ: u32 = 1 << 30 | 1 << 28;
+const CTRL_STAT_POWER_UP_ACK: u32 = 1 << 31 | 1 << 29;
+
+// Registers of the AHB-AP, the memory access port of Cortex-M cores
+const AP_CSW: u8 = 0x0;
+const AP_TAR: u8 = 0x4;
+const AP_DRW: u8 = 0xC;
+
+/// Word accesses, with the address incremented after each.
+const CSW_WORD_INCREMENT: u32 = 0x23000012;
+
+/// The address register only increments within a 1 KiB block.
+const TAR_BLOCK: u32 = 0x400;
+
+// Debug registers of the core
+const DHCSR: u32 = 0xE000EDF0;
+const DEMCR: u32 = 0xE000EDFC;
+const AIRCR: u32 = 0xE000ED0C;
+const DHCSR_HALT: u32 = 0xA05F0003;
+const DHCSR_RUN: u32 = 0xA05F0001;
+const DEMCR_VC_CORERESET: u32 = 1;
+const AIRCR_SYSRESETREQ: u32 = 0x05FA0004;
+
+#[derive(Default)]
+pub struct App {
+    callback: Option<Callback>,
+    buffer: Option<AppSlice<Shared, u8>>,
+}
+
+pub struct Swd<'a> {
+    swclk: &'a gpio::Pin,
+    swdio: &'a gpio::Pin,
+    apps: Grant<App>,
+    /// The app that connected to the target
+    owner: OptionalCell<AppId>,
+}
+
+impl Swd<'a> {
+    pub fn new(swclk: &'a gpio::Pin, swdio: &'a gpio::Pin, grant: Grant<App>) -> Swd<'a> {
+        Swd {
+            swclk: swclk,
+            swdio: swdio,
+            apps: grant,
+            owner: OptionalCell::empty(),
+        }
+    }
+
+    // Bits are driven with the clock low and sampled by the target on its
+    // rising edge, and the target drives bits on the rising edge, which the
+    // master samples with the clock low. The clock idles high.
+
+    fn write_bits(&self, value: u32, count: usize) {
+        for i in 0..count {
+            if (value >> i) & 1 == 1 {
+                self.swdio.set();
+            } else {
+                self.swdio.clear();
+            }
+            self.swclk.clear();
+            self.swclk.set();
+        }
+    }
+
+    fn read_bits(&self, count: usize) -> u32 {
+        let mut value = 0;
+        for i in 0..count {
+            self.swclk.clear();
+            if self.swdio.read() {
+                value |= 1 << i;
+            }
+            self.swclk.set();
+        }
+        value
+    }
+
+    /// Hand SWDIO over to the target, or take it back, with a clock cycle.
+    fn turnaround(&self, drive: bool) {
+        if !drive {
+            self.swdio.make_input();
+        }
+        self.swclk.clear();
+        self.swclk.set();
+        if drive {
+            self.swdio.make_output();
+        }
+    }
+
+    /// A read (`value` is ignored) or a write of register `addr` of the debug
+    /// port or, if `ap`, of the selected access port.
+    fn transfer(&self, ap: bool, read: bool, addr: u8, value: u32) -> Result<u32, ReturnCode> {
+        let fields = ap as u32 | (read as u32) << 1 | ((addr as u32 >> 2) & 0x3) << 2;
+        let parity = fields.count_ones() & 1;
+        // Start bit, the fields, parity, stop bit and park bit
+        let request = 1 | fields << 1 | parity << 5 | 1 << 7;
+
+        for _ in 0..WAIT_RETRIES {
+            self.write_bits(request, 8);
+            self.turnaround(false);
+            let ack = self.read_bits(3);
+
+            match ack {
+                ACK_OK if read => {
+                    let data = self.read_bits(32);
+                    let parity = self.read_bits(1);
+                    self.turnaround(true);
+                    self.write_bits(0, 8);
+                    if data.count_ones() & 1 != parity {
+                        return Err(ReturnCode::FAIL);
+                    }
+                    return Ok(data);
+                }
+                ACK_OK => {
+                    self.turnaround(true);
+                    self.write_bits(value, 32);
+                    self.write_bits(value.count_ones() & 1, 1);
+                    self.write_bits(0, 8);
+                    return Ok(0);
+                }
+                ACK_WAIT => self.turnaround(true),
+                ACK_FAULT => {
+                    self.turnaround(true);
+                    let _ = self.transfer(false, false, DP_ABORT, ABORT_CLEAR_ERRORS);
+                    return Err(ReturnCode::FAIL);
+                }
+                _ => {
+                    // No target, or it lost track of the protocol
+                    self.turnaround(true);
+                    return Err(ReturnCode::FAIL);
+                }
+            }
+        }
+        Err(ReturnCode::EBUSY)
+    }
+
+    fn read_dp(&self, addr: u8) -> Result<u32, ReturnCode> {
+        self.transfer(false, true, addr, 0)
+    }
+
+    fn write_dp(&self, addr: u8, value: u32) -> Result<(), ReturnCode> {
+        self.transfer(false, false, addr, value).map(|_| ())
+    }
+
+    fn write_ap(&self, addr: u8, value: u32) -> Result<(), ReturnCode> {
+        self.transfer(true, false, addr, value).map(|_| ())
+    }
+
+    /// Reads of the access port are posted: each returns the result of the
+    /// previous one, so read the result from RDBUFF instead.
+    fn read_ap(&self, addr: u8) -> Result<u32, ReturnCode> {
+        self.transfer(true, true, addr, 0)?;
+        self.read_dp(DP_RDBUFF)
+    }
+
+    /// Reset the debug port, switch it from JTAG to SWD, and power it up.
+    /// Returns its IDCODE.
+    fn connect(&self) -> Result<u32, ReturnCode> {
+        self.swclk.set();
+        self.swclk.make_output();
+        self.swdio.set();
+        self.swdio.make_output();
+
+        // A line reset is at least 50 cycles with SWDIO high, and the switch
+        // sequence goes between two of them
+        self.write_bits(0xFFFFFFFF, 32);
+        self.write_bits(0xFFFFFFFF, 24);
+        self.write_bits(0xE79E, 16);
+        self.write_bits(0xFFFFFFFF, 32);
+        self.write_bits(0xFFFFFFFF, 24);
+        self.write_bits(0, 8);
+
+        let idcode = self.read_dp(DP_IDCODE)?;
+        self.write_dp(DP_ABORT, ABORT_CLEAR_ERRORS)?;
+        self.write_dp(DP_SELECT, 0)?;
+        self.write_dp(DP_CTRL_STAT, CTRL_STAT_POWER_UP_REQ)?;
+        let mut powered = false;
+        for _ in 0..WAIT_RETRIES {
+            if self.read_dp(DP_CTRL_STAT)? & CTRL_STAT_POWER_UP_ACK == CTRL_STAT_POWER_UP_ACK {
+                powered = true;
+                break;
+            }
+        }
+        if !powered {
+            return Err(ReturnCode::EBUSY);
+        }
+        self.write_ap(AP_CSW, CSW_WORD_INCREMENT)?;
+        Ok(idcode)
+    }
+
+    fn disconnect(&self) {
+        self.swclk.make_input();
+        self.swdio.make_input();
+    }
+
+    fn read_word(&self, addr: u32) -> Result<u32, ReturnCode> {
+        self.write_ap(AP_TAR, addr)?;
+        self.read_ap(AP_DRW)
+    }
+
+    fn write_word(&self, addr: u32, value: u32) -> Result<(), ReturnCode> {
+        self.write_ap(AP_TAR, addr)?;
+        self.write_ap(AP_DRW, value)
+    }
+
+    /// Read `buffer.len() / 4` words from `addr` into `buffer`.
+    fn read_memory(&self, addr: u32, buffer: &mut [u8]) -> Result<(), ReturnCode> {
+        for (i, word) in buffer.chunks_mut(4).enumerate() {
+            let word_addr = addr.wrapping_add(i as u32 * 4);
+            if i == 0 || word_addr % TAR_BLOCK == 0 {
+                self.write_ap(AP_TAR, word_addr)?;
+            }
+            let value = self.read_ap(AP_DRW)?;
+            word.copy_from_slice(&value.to_le_bytes());
+        }
+        Ok(())
+    }
+
+    /// Write the words of `buffer` to `addr`.
+    fn write_memory(&self, addr: u32, buffer: &[u8]) -> Result<(), ReturnCode> {
+        for (i, word) in buffer.chunks(4).enumerate() {
+            let word_addr = addr.wrapping_add(i as u32 * 4);
+            if i == 0 || word_addr % TAR_BLOCK == 0 {
+                self.write_ap(AP_TAR, word_addr)?;
+            }
+            let value = u32::from_le_bytes([word[0], word[1], word[2], word[3]]);
+            self.write_ap(AP_DRW, value)?;
+        }
+        Ok(())
+    }
+
+    fn reset(&self, halt: bool) -> Result<(), ReturnCode> {
+        if halt {
+            self.write_word(DHCSR, DHCSR_HALT)?;
+            self.write_word(DEMCR, DEMCR_VC_CORERESET)?;
+        } else {
+            self.write_word(DEMCR, 0)?;
+        }
+        self.write_word(AIRCR, AIRCR_SYSRESETREQ)
+    }
+
+    /// Whether `appid` can use the target: it connected, or the app that did
+    /// is gone.
+    fn owns(&self, appid: AppId) -> bool {
+        self.owner.map_or(true, |owner| {
+            *owner == appid || self.apps.enter(*owner, |_, _| ()).is_err()
+        })
+    }
+
+    /// Run a memory transfer of `len` bytes between `addr` and the buffer of
+    /// `appid`.
+    fn buffer_transfer(&self, appid: AppId, addr: usize, len: usize, read: bool) -> ReturnCode {
+        if addr % 4 != 0 || len % 4 != 0 {
+            return ReturnCode::EINVAL;
+        }
+        self.apps
+            .enter(appid, |app, _| {
+                app.buffer.as_mut().map_or(ReturnCode::ENOMEM, |buffer| {
+                    if len > buffer.len() {
+                        return ReturnCode::ESIZE;
+                    }
+                    let window = &mut buffer.as_mut()[..len];
+                    let result = if read {
+                        self.read_memory(addr as u32, window)
+                    } else {
+                        self.write_memory(addr as u32, window)
+                    };
+                    result.err().unwrap_or(ReturnCode::SUCCESS)
+                })
+            })
+            .unwrap_or_else(|err| err.into())
+    }
+
+    /// Pass `value` to the callback of `appid`.
+    fn report(&self, appid: AppId, value: u32) {
+        let _ = self.apps.enter(appid, |app, _| {
+            app.callback.map(|mut callback| {
+                callback.schedule(From::from(ReturnCode::SUCCESS), value as usize, 0)
+            });
+        });
+    }
+}
+
+impl Driver for Swd<'a> {
+    fn allow(
+        &self,
+        appid: AppId,
+        allow_num: usize,
+        slice: Option<AppSlice<Shared, u8>>,
+    ) -> ReturnCode {
+        match allow_num {
+            0 => self
+                .apps
+                .enter(appid, |app, _| {
+                    app.buffer = slice;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or_else(|err| err.into()),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    fn subscribe(
+        &self,
+        subscribe_num: usize,
+        callback: Option<Callback>,
+        app_id: AppId,
+    ) -> ReturnCode {
+        match subscribe_num {
+            0 => self
+                .apps
+                .enter(app_id, |app, _| {
+                    app.callback = callback;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or_else(|err| err.into()),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    fn command(&self, command_num: usize, data: usize, data2: usize, appid: AppId) -> ReturnCode {
+        if command_num == 0 {
+            return ReturnCode::SUCCESS;
+        }
+        if !self.owns(appid) {
+            return ReturnCode::EBUSY;
+        }
+        if command_num != 1 && self.owner.is_none() {
+            return ReturnCode::EOFF;
+        }
+
+        let result = match command_num {
+            1 => self.connect().map(|idcode| {
+                self.owner.set(appid);
+                self.report(appid, idcode);
+            }),
+            2 => self.write_word(DHCSR, DHCSR_HALT),
+            3 => self.write_word(DHCSR, DHCSR_RUN),
+            4 => self.reset(data != 0),
+            5 => {
+                if data % 4 != 0 {
+                    return ReturnCode::EINVAL;
+                }
+                self.read_word(data as u32)
+                    .map(|value| self.report(appid, value))
+            }
+            6 => {
+                if data % 4 != 0 {
+                    return ReturnCode::EINVAL;
+                }
+                self.write_word(data as u32, data2 as u32)
+            }
+            7 => return self.buffer_transfer(appid, data, data2, true),
+            8 => return self.buffer_transfer(appid, data, data2, false),
+            9 => {
+                self.disconnect();
+                self.owner.clear();
+                Ok(())
+            }
+            _ => return ReturnCode::ENOSUPPORT,
+        };
+        result.err().unwrap_or(ReturnCode::SUCCESS)
+    }
+}
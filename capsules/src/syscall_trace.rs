@@ -0,0 +1,284 @@
+//! Trace of the system calls of selected processes.
+//!
+//! `SyscallTrace` logs the system calls that processes make, with their
+//! arguments and what they returned, to make bugs in the interaction between
+//! applications and the kernel visible. It observes the system calls as the
+//! kernel's `SyscallTracer`, which only a holder of the
+//! `SyscallTracingCapability` can set.
+//!
+//! Each process slot has a `Filter`, which selects which of the system calls
+//! of its process are traced: none, all of them, or only those to one driver.
+//! Processes are not traced until the board sets their filter.
+//!
+//! Records go to the debug console, or, if the board gives the capsule a
+//! buffer, into that buffer, where the newest records replace the oldest
+//! ones. At most `max_per_second` system calls are traced every second, so
+//! that a busy process does not flood the output; the number of system calls
+//! that were not traced is logged once tracing resumes.
+//!
+//! Usage
+//! -----
+//!
+//! ```rust
+//! struct SyscallTracingCap;
+//! unsafe impl capabilities::SyscallTracingCapability for SyscallTracingCap {}
+//!
+//! let syscall_trace_filters = static_init!(
+//!     [Cell<capsules::syscall_trace::Filter>; NUM_PROCS],
+//!     Default::default()
+//! );
+//! let syscall_trace = static_init!(
+//!     capsules::syscall_trace::SyscallTrace<'static, VirtualMuxAlarm<'static, sam4l::ast::Ast>>,
+//!     capsules::syscall_trace::SyscallTrace::new(syscall_trace_alarm, syscall_trace_filters, 50)
+//! );
+//! board_kernel.set_syscall_tracer(syscall_trace, &SyscallTracingCap);
+//!
+//! // Trace the commands that the first process sends to the console
+//! syscall_trace.set_filter(0, capsules::syscall_trace::Filter::Driver(0x1));
+//! ```
+
+use core::cell::Cell;
+use kernel::common::cells::MapCell;
+use kernel::common::{Queue, RingBuffer};
+use kernel::debug;
+use kernel::hil::time::{Alarm, Frequency};
+use kernel::syscall::{Syscall, SyscallTracer};
+use kernel::{AppId, ReturnCode};
+
+/// Which system calls of a process are traced.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Filter {
+    /// None
+    Off,
+    /// All of them
+    All,
+    /// Only the ones to the given driver. Yields and memory operations have
+    /// no driver, so they are not traced.
+    Driver(usize),
+}
+
+impl Default for Filter {
+    fn default() -> Filter {
+        Filter::Off
+    }
+}
+
+/// A traced system call.
+#[derive(Copy, Clone)]
+pub struct TraceRecord {
+    /// When the kernel handled the system call, in tics of the alarm
+    pub time: u32,
+    /// Index of the process that made the system call
+    pub process: usize,
+    pub syscall: Syscall,
+    /// What the system call returned, `None` for a yield
+    pub result: Option<ReturnCode>,
+}
+
+impl TraceRecord {
+    /// A record to initialize buffers with.
+    pub const EMPTY: TraceRecord = TraceRecord {
+        time: 0,
+        process: 0,
+        syscall: Syscall::YIELD,
+        result: None,
+    };
+}
+
+pub struct SyscallTrace<'a, A: Alarm> {
+    alarm: &'a A,
+    filters: &'a [Cell<Filter>],
+    max_per_second: u32,
+    /// Start of the current second, and how many system calls were traced in
+    /// it
+    window_start: Cell<u32>,
+    window_count: Cell<u32>,
+    /// System calls that were not traced because of the rate limit
+    dropped: Cell<usize>,
+    buffer: MapCell<RingBuffer<'static, TraceRecord>>,
+}
+
+impl<A: Alarm> SyscallTrace<'a, A> {
+    /// `filters` holds the filter of each process slot, and traces at most
+    /// `max_per_second` system calls every second. The alarm only serves as a
+    /// clock, and is never set.
+    pub fn new(
+        alarm: &'a A,
+        filters: &'a [Cell<Filter>],
+        max_per_second: u32,
+    ) -> SyscallTrace<'a, A> {
+        SyscallTrace {
+            alarm: alarm,
+            filters: filters,
+            max_per_second: max_per_second,
+            window_start: Cell::new(0),
+            window_count: Cell::new(0),
+            dropped: Cell::new(0),
+            buffer: MapCell::empty(),
+        }
+    }
+
+    /// Trace the system calls of the process in slot `process_index` that
+    /// `filter` selects.
+    pub fn set_filter(&self, process_index: usize, filter: Filter) {
+        self.filters.get(process_index).map(|cell| cell.set(filter));
+    }
+
+    /// Keep the records in `buffer` rather than logging them to the debug
+    /// console.
+    pub fn set_buffer(&self, buffer: &'static mut [TraceRecord]) {
+        self.buffer.replace(RingBuffer::new(buffer));
+    }
+
+    /// Remove the records from the buffer, and call `f` on each of them,
+    /// oldest first.
+    pub fn drain<F: FnMut(&TraceRecord)>(&self, mut f: F) {
+        self.buffer.map(|buffer| {
+            while let Some(record) = buffer.dequeue() {
+                f(&record);
+            }
+        });
+    }
+
+    /// The number of system calls that were not traced because of the rate
+    /// limit, since the last time tracing resumed.
+    pub fn dropped(&self) -> usize {
+        self.dropped.get()
+    }
+
+    fn selected(&self, process_index: usize, syscall: &Syscall) -> bool {
+        let filter = self
+            .filters
+            .get(process_index)
+            .map_or(Filter::Off, |cell| cell.get());
+        match filter {
+            Filter::Off => false,
+            Filter::All => true,
+            Filter::Driver(driver) => driver_number(syscall) == Some(driver),
+        }
+    }
+
+    /// Whether the rate limit allows one more record, at time `now`.
+    fn within_rate(&self, now: u32) -> bool {
+        if now.wrapping_sub(self.window_start.get()) >= <A::Frequency>::frequency() {
+            self.window_start.set(now);
+            self.window_count.set(0);
+            if self.dropped.get() > 0 {
+                debug!(
+                    "syscall trace: {} system calls not traced",
+                    self.dropped.get()
+                );
+                self.dropped.set(0);
+            }
+        }
+        if self.window_count.get() >= self.max_per_second {
+            self.dropped.set(self.dropped.get() + 1);
+            false
+        } else {
+            self.window_count.set(self.window_count.get() + 1);
+            true
+        }
+    }
+
+    fn log(&self, record: &TraceRecord) {
+        let process = record.process;
+        match record.result {
+            None => debug!(
+                "[{}] process {}: {}",
+                record.time,
+                process,
+                Call(&record.syscall)
+            ),
+            Some(result) => debug!(
+                "[{}] process {}: {} -> {:?}",
+                record.time,
+                process,
+                Call(&record.syscall),
+                result
+            ),
+        }
+    }
+}
+
+/// The driver that a system call is for, if it has one.
+fn driver_number(syscall: &Syscall) -> Option<usize> {
+    match *syscall {
+        Syscall::SUBSCRIBE { driver_number, .. } => Some(driver_number),
+        Syscall::COMMAND { driver_number, .. } => Some(driver_number),
+        Syscall::ALLOW { driver_number, .. } => Some(driver_number),
+        Syscall::YIELD | Syscall::MEMOP { .. } | Syscall::BATCH { .. } => None,
+    }
+}
+
+/// Formats a system call the way an application makes it.
+struct Call<'a>(&'a Syscall);
+
+impl core::fmt::Display for Call<'a> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match *self.0 {
+            Syscall::YIELD => write!(f, "yield()"),
+            Syscall::SUBSCRIBE {
+                driver_number,
+                subdriver_number,
+                callback_ptr,
+                appdata,
+            } => write!(
+                f,
+                "subscribe({:#x}, {}, {:p}, {:#x})",
+                driver_number, subdriver_number, callback_ptr, appdata
+            ),
+            Syscall::COMMAND {
+                driver_number,
+                subdriver_number,
+                arg0,
+                arg1,
+            } => write!(
+                f,
+                "command({:#x}, {}, {:#x}, {:#x})",
+                driver_number, subdriver_number, arg0, arg1
+            ),
+            Syscall::ALLOW {
+                driver_number,
+                subdriver_number,
+                allow_address,
+                allow_size,
+            } => write!(
+                f,
+                "allow({:#x}, {}, {:p}, {})",
+                driver_number, subdriver_number, allow_address, allow_size
+            ),
+            Syscall::MEMOP { operand, arg0 } => write!(f, "memop({}, {:#x})", operand, arg0),
+            Syscall::BATCH { commands, count } => write!(f, "batch({:p}, {})", commands, count),
+        }
+    }
+}
+
+impl<A: Alarm> SyscallTracer for SyscallTrace<'a, A> {
+    fn syscall(&self, appid: AppId, syscall: Syscall, result: Option<ReturnCode>) {
+        if !self.selected(appid.idx(), &syscall) {
+            return;
+        }
+        let now = self.alarm.now();
+        if !self.within_rate(now) {
+            return;
+        }
+
+        let record = TraceRecord {
+            time: now,
+            process: appid.idx(),
+            syscall: syscall,
+            result: result,
+        };
+        let buffered = self.buffer.map_or(false, |buffer| {
+            // The newest records replace the oldest ones
+            if buffer.is_full() {
+                buffer.dequeue();
+            }
+            buffer.enqueue(record);
+            true
+        });
+        if !buffered {
+            self.log(&record);
+        }
+    }
+}
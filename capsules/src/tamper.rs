@@ -0,0 +1,211 @@
+//! Responds to tamper events by wiping secrets, and provides a system call
+//! driver that tells processes about them.
+//!
+//! `TamperMonitor` is the client of the chip's tamper detector. When an event
+//! is detected from a source the board has chosen to wipe on, every
+//! registered `Wipe` target (e.g. the 802.15.4 key list or a software key
+//! store) erases its secrets before anything else happens. Processes that
+//! have subscribed are then notified. Events that were latched while the
+//! device was off are handled the same way by `check_latched`, which the
+//! board should call once setup is complete.
+//!
+//! Tamper events are not cleared by this capsule: a latched event stays
+//! latched, and can be read by processes, until the board clears it.
+//!
+//! Usage
+//! -----
+//!
+//! ```rust
+//! let tamper = static_init!(
+//!     capsules::tamper::TamperMonitor<'static>,
+//!     capsules::tamper::TamperMonitor::new(&stm32f4xx::tamper::TAMPER, kernel::Grant::create())
+//! );
+//! stm32f4xx::tamper::TAMPER.set_client(tamper);
+//! tamper.add_wipe_target(radio_driver);
+//! tamper.wipe_on(TamperSource::CaseOpen, true);
+//! tamper.enable(TamperSource::CaseOpen);
+//! tamper.enable(TamperSource::VoltageGlitch);
+//! tamper.check_latched();
+//! ```
+
+use core::cell::Cell;
+use kernel::common::cells::OptionalCell;
+use kernel::hil::tamper::{self, TamperEvent, TamperSource};
+use kernel::{AppId, Callback, Driver, Grant, ReturnCode};
+
+/// Syscall driver number.
+use crate::driver;
+pub const DRIVER_NUM: usize = driver::NUM::Tamper as usize;
+
+/// Maximum number of wipe targets.
+pub const MAX_WIPE_TARGETS: usize = 4;
+
+/// Sources in the order of their numbers in the system call interface.
+const SOURCES: [TamperSource; 4] = [
+    TamperSource::CaseOpen,
+    TamperSource::VoltageGlitch,
+    TamperSource::ClockGlitch,
+    TamperSource::Temperature,
+];
+
+fn source_num(source: TamperSource) -> usize {
+    SOURCES.iter().position(|s| *s == source).unwrap_or(0)
+}
+
+/// Something holding secrets that must be erased when the device is tampered
+/// with.
+pub trait Wipe {
+    /// Erases all secrets. This must complete synchronously.
+    fn wipe(&self);
+}
+
+#[derive(Default)]
+pub struct App {
+    callback: Option<Callback>,
+}
+
+pub struct TamperMonitor<'a> {
+    detector: &'a tamper::Tamper<'a>,
+    apps: Grant<App>,
+    wipe_targets: [OptionalCell<&'a Wipe>; MAX_WIPE_TARGETS],
+    /// Bit `n` is set if events from source `n` trigger a wipe
+    wipe_sources: Cell<u8>,
+}
+
+impl TamperMonitor<'a> {
+    pub fn new(detector: &'a tamper::Tamper<'a>, grant: Grant<App>) -> TamperMonitor<'a> {
+        TamperMonitor {
+            detector: detector,
+            apps: grant,
+            wipe_targets: [
+                OptionalCell::empty(),
+                OptionalCell::empty(),
+                OptionalCell::empty(),
+                OptionalCell::empty(),
+            ],
+            wipe_sources: Cell::new(0),
+        }
+    }
+
+    /// Adds a target to be wiped on tamper. Returns `ENOMEM` if there are
+    /// already `MAX_WIPE_TARGETS` targets.
+    pub fn add_wipe_target(&self, target: &'a Wipe) -> ReturnCode {
+        self.wipe_targets
+            .iter()
+            .find(|slot| slot.is_none())
+            .map_or(ReturnCode::ENOMEM, |slot| {
+                slot.set(target);
+                ReturnCode::SUCCESS
+            })
+    }
+
+    /// Selects whether events from `source` wipe the targets.
+    pub fn wipe_on(&self, source: TamperSource, wipe: bool) {
+        let bit = 1 << source_num(source);
+        let sources = self.wipe_sources.get();
+        self.wipe_sources
+            .set(if wipe { sources | bit } else { sources & !bit });
+    }
+
+    pub fn enable(&self, source: TamperSource) -> ReturnCode {
+        self.detector.enable(source)
+    }
+
+    pub fn disable(&self, source: TamperSource) -> ReturnCode {
+        self.detector.disable(source)
+    }
+
+    /// Handles events that were latched before the monitor was set up, e.g.
+    /// while the device was off.
+    pub fn check_latched(&self) {
+        for source in SOURCES.iter() {
+            self.detector
+                .latched(*source)
+                .map(|event| self.handle_event(event));
+        }
+    }
+
+    fn handle_event(&self, event: TamperEvent) {
+        if self.wipe_sources.get() & (1 << source_num(event.source)) != 0 {
+            for target in self.wipe_targets.iter() {
+                target.map(|target| target.wipe());
+            }
+        }
+        self.apps.each(|app| {
+            app.callback.map(|mut callback| {
+                callback.schedule(
+                    source_num(event.source),
+                    event.timestamp.is_some() as usize,
+                    event.timestamp.unwrap_or(0) as usize,
+                )
+            });
+        });
+    }
+}
+
+impl tamper::Client for TamperMonitor<'a> {
+    fn tamper_detected(&self, event: TamperEvent) {
+        self.handle_event(event);
+    }
+}
+
+impl Driver for TamperMonitor<'a> {
+    /// Subscribe to tamper events.
+    ///
+    /// ### `subscribe_num`
+    ///
+    /// - `0`: Set the callback called for each tamper event, with the source
+    ///        number, whether the event has a timestamp, and the timestamp.
+    fn subscribe(
+        &self,
+        subscribe_num: usize,
+        callback: Option<Callback>,
+        app_id: AppId,
+    ) -> ReturnCode {
+        match subscribe_num {
+            0 => self
+                .apps
+                .enter(app_id, |app, _| {
+                    app.callback = callback;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or_else(|err| err.into()),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    /// Read latched tamper events.
+    ///
+    /// Sources are numbered 0 (case open), 1 (voltage glitch), 2 (clock
+    /// glitch) and 3 (temperature).
+    ///
+    /// ### `command_num`
+    ///
+    /// - `0`: Driver check.
+    /// - `1`: Returns 1 if an event from source `data` is latched, 0
+    ///        otherwise.
+    /// - `2`: Returns the timestamp of the latched event from source `data`.
+    ///        Returns `EINVAL` if no event is latched and `ENOSUPPORT` if the
+    ///        event has no timestamp.
+    fn command(&self, command_num: usize, data: usize, _: usize, _: AppId) -> ReturnCode {
+        match command_num {
+            0 => ReturnCode::SUCCESS,
+            1 => SOURCES.get(data).map_or(ReturnCode::EINVAL, |source| {
+                ReturnCode::SuccessWithValue {
+                    value: self.detector.latched(*source).is_some() as usize,
+                }
+            }),
+            2 => SOURCES
+                .get(data)
+                .and_then(|source| self.detector.latched(*source))
+                .map_or(ReturnCode::EINVAL, |event| {
+                    event.timestamp.map_or(ReturnCode::ENOSUPPORT, |timestamp| {
+                        ReturnCode::SuccessWithValue {
+                            value: timestamp as usize,
+                        }
+                    })
+                }),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+}
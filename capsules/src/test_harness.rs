@@ -0,0 +1,306 @@
+//! Harness for end-to-end kernel tests that run in an emulator.
+//!
+//! Test applications exercise the system call interface, alarms and IPC,
+//! and report the result of each of their tests to the harness. The harness
+//! also tests the alarm it is given from within the kernel. Once every test
+//! application is done, or the harness times out, it prints a summary to the
+//! debug console and stops the emulator through `TestExit`, with an exit code
+//! of 0 only if every test passed. If no application reported anything by
+//! the timeout, the harness leaves the system running, so that a board can
+//! also run applications that are not tests.
+//!
+//! Besides reporting results, the system call interface gives applications
+//! something to test the system call paths against: a callback that echoes
+//! the arguments of a command, and the sum of the bytes of an allowed buffer.
+//!
+//! Usage
+//! -----
+//!
+//! ```rust
+//! let test_harness_alarm = static_init!(
+//!     VirtualMuxAlarm<'static, Timer>,
+//!     VirtualMuxAlarm::new(mux_alarm)
+//! );
+//! let test_harness = static_init!(
+//!     capsules::test_harness::TestHarness<'static, VirtualMuxAlarm<'static, Timer>>,
+//!     capsules::test_harness::TestHarness::new(
+//!         test_harness_alarm,
+//!         board_kernel.create_grant(&memory_allocation_cap),
+//!         test_exit,
+//!     )
+//! );
+//! test_harness_alarm.set_client(test_harness);
+//!
+//! // Once the processes are loaded
+//! test_harness.start(number_of_processes);
+//! ```
+
+use core::cell::Cell;
+use kernel::debug;
+use kernel::hil::time::{self, Alarm, Frequency};
+use kernel::{AppId, AppSlice, Callback, Driver, Grant, ReturnCode, Shared};
+
+/// Syscall driver number.
+use crate::driver;
+pub const DRIVER_NUM: usize = driver::NUM::TestHarness as usize;
+
+/// How long the alarm test waits for, in milliseconds
+const ALARM_TEST_MS: u32 = 10;
+
+/// How long the test applications have to finish, in seconds
+const TIMEOUT_S: u32 = 60;
+
+/// Stops the emulator at the end of the tests.
+pub trait TestExit {
+    /// Stop with `code`, 0 meaning that every test passed. Any debug output
+    /// that is still buffered should be written out first.
+    fn exit(&self, code: u16) -> !;
+}
+
+#[derive(Default)]
+pub struct App {
+    callback: Option<Callback>,
+    buffer: Option<AppSlice<Shared, u8>>,
+    passed: usize,
+    failed: usize,
+    done: bool,
+}
+
+#[derive(Copy, Clone, PartialEq)]
+enum State {
+    Idle,
+    /// The kernel alarm test is running, since the given time
+    AlarmTest(u32),
+    /// Waiting for the test applications to be done
+    WaitApps,
+}
+
+pub struct TestHarness<'a, A: Alarm> {
+    alarm: &'a A,
+    apps: Grant<App>,
+    exit: &'a TestExit,
+    state: Cell<State>,
+    expected_apps: Cell<usize>,
+    kernel_passed: Cell<usize>,
+    kernel_failed: Cell<usize>,
+}
+
+impl<A: Alarm> TestHarness<'a, A> {
+    pub fn new(alarm: &'a A, grant: Grant<App>, exit: &'a TestExit) -> TestHarness<'a, A> {
+        TestHarness {
+            alarm: alarm,
+            apps: grant,
+            exit: exit,
+            state: Cell::new(State::Idle),
+            expected_apps: Cell::new(0),
+            kernel_passed: Cell::new(0),
+            kernel_failed: Cell::new(0),
+        }
+    }
+
+    /// Start the tests, with `expected_apps` test applications to wait for.
+    pub fn start(&self, expected_apps: usize) {
+        self.expected_apps.set(expected_apps);
+        let now = self.alarm.now();
+        self.state.set(State::AlarmTest(now));
+        self.alarm
+            .set_alarm(now.wrapping_add(Self::ms_to_tics(ALARM_TEST_MS)));
+    }
+
+    fn ms_to_tics(ms: u32) -> u32 {
+        (ms as u64 * <A::Frequency>::frequency() as u64 / 1000) as u32
+    }
+
+    fn report_kernel_test(&self, name: &str, passed: bool) {
+        if passed {
+            self.kernel_passed.set(self.kernel_passed.get() + 1);
+        } else {
+            self.kernel_failed.set(self.kernel_failed.get() + 1);
+            debug!("test harness: kernel test {} failed", name);
+        }
+    }
+
+    /// Finish the tests once every expected test application is done.
+    fn check_done(&self) {
+        if self.state.get() != State::WaitApps {
+            return;
+        }
+        let done = self
+            .apps
+            .iter()
+            .map(|app| app.enter(|app, _| app.done))
+            .filter(|done| *done)
+            .count();
+        if done >= self.expected_apps.get() {
+            self.finish(false);
+        }
+    }
+
+    fn finish(&self, timed_out: bool) -> ! {
+        self.alarm.disable();
+
+        let mut passed = self.kernel_passed.get();
+        let mut failed = self.kernel_failed.get();
+        let mut done = 0;
+        for app in self.apps.iter() {
+            app.enter(|app, _| {
+                passed += app.passed;
+                failed += app.failed;
+                if app.done {
+                    done += 1;
+                }
+            });
+        }
+        if timed_out {
+            debug!(
+                "test harness: timed out, {} of {} applications done",
+                done,
+                self.expected_apps.get()
+            );
+            failed += self.expected_apps.get().saturating_sub(done);
+        }
+
+        debug!("test harness: {} passed, {} failed", passed, failed);
+        self.exit.exit(if failed == 0 { 0 } else { 1 })
+    }
+}
+
+impl<A: Alarm> time::Client for TestHarness<'a, A> {
+    fn fired(&self) {
+        match self.state.get() {
+            State::AlarmTest(start) => {
+                // The alarm must not fire early, nor much later than asked
+                let elapsed = self.alarm.now().wrapping_sub(start);
+                let expected = Self::ms_to_tics(ALARM_TEST_MS);
+                self.report_kernel_test(
+                    "alarm",
+                    elapsed >= expected && elapsed < expected + Self::ms_to_tics(1000),
+                );
+
+                self.state.set(State::WaitApps);
+                self.alarm.set_alarm(
+                    self.alarm
+                        .now()
+                        .wrapping_add(Self::ms_to_tics(TIMEOUT_S * 1000)),
+                );
+                self.check_done();
+            }
+            State::WaitApps => {
+                // Without any report, the processes are not test applications
+                let reported = self
+                    .apps
+                    .iter()
+                    .any(|app| app.enter(|app, _| app.passed + app.failed > 0 || app.done));
+                if reported {
+                    self.finish(true);
+                } else {
+                    self.state.set(State::Idle);
+                    debug!("test harness: no test applications, not stopping");
+                }
+            }
+            State::Idle => {}
+        }
+    }
+}
+
+impl<A: Alarm> Driver for TestHarness<'a, A> {
+    /// Subscribe to the echo callback.
+    ///
+    /// ### `subscribe_num`
+    ///
+    /// - `0`: Callback for command `2`, called with its two arguments.
+    fn subscribe(
+        &self,
+        subscribe_num: usize,
+        callback: Option<Callback>,
+        app_id: AppId,
+    ) -> ReturnCode {
+        match subscribe_num {
+            0 => self
+                .apps
+                .enter(app_id, |app, _| {
+                    app.callback = callback;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or_else(|err| err.into()),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    /// Share a buffer with the harness.
+    ///
+    /// ### `allow_num`
+    ///
+    /// - `0`: Buffer that command `3` sums.
+    fn allow(
+        &self,
+        app_id: AppId,
+        allow_num: usize,
+        slice: Option<AppSlice<Shared, u8>>,
+    ) -> ReturnCode {
+        match allow_num {
+            0 => self
+                .apps
+                .enter(app_id, |app, _| {
+                    app.buffer = slice;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or_else(|err| err.into()),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    /// Report results, and exercise the system call interface.
+    ///
+    /// ### `command_num`
+    ///
+    /// - `0`: Driver check.
+    /// - `1`: Report the result of test `data`: passed if `data2` is 0, or
+    ///   failed with code `data2`.
+    /// - `2`: Call the echo callback with `data` and `data2`.
+    /// - `3`: Return the sum of the bytes of the allowed buffer.
+    /// - `4`: Signal that the application ran all of its tests.
+    fn command(&self, command_num: usize, data: usize, data2: usize, app_id: AppId) -> ReturnCode {
+        let rcode = self
+            .apps
+            .enter(app_id, |app, _| match command_num {
+                0 => ReturnCode::SUCCESS,
+                1 => {
+                    if data2 == 0 {
+                        app.passed += 1;
+                    } else {
+                        app.failed += 1;
+                        debug!(
+                            "test harness: process {} test {} failed with code {}",
+                            app_id.idx(),
+                            data,
+                            data2
+                        );
+                    }
+                    ReturnCode::SUCCESS
+                }
+                2 => app.callback.map_or(ReturnCode::ENOMEM, |mut callback| {
+                    callback.schedule(data, data2, 0);
+                    ReturnCode::SUCCESS
+                }),
+                3 => app.buffer.as_ref().map_or(ReturnCode::ENOMEM, |buffer| {
+                    let sum = buffer
+                        .as_ref()
+                        .iter()
+                        .fold(0usize, |sum, byte| sum.wrapping_add(*byte as usize));
+                    ReturnCode::SuccessWithValue { value: sum }
+                }),
+                4 => {
+                    app.done = true;
+                    ReturnCode::SUCCESS
+                }
+                _ => ReturnCode::ENOSUPPORT,
+            })
+            .unwrap_or_else(|err| err.into());
+
+        if command_num == 4 && rcode == ReturnCode::SUCCESS {
+            self.check_done();
+        }
+        rcode
+    }
+}
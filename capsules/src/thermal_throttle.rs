@@ -0,0 +1,280 @@
+//! Throttles the system when it runs hot.
+//!
+//! `ThermalThrottle` periodically reads a temperature sensor, usually the one
+//! inside the chip, and sets a `hil::power::PowerLevel` from it: `Full` while
+//! the temperature is below `warm`, `Reduced` above that, and `Minimal` above
+//! `hot`. Each change is passed to the kernel's power consumers, e.g. a
+//! `RadioPowerLimit` that caps the transmit power of the radio or a capsule
+//! that lowers its duty cycle, and to processes, so they can back off too. A
+//! level is only raised again once the temperature is `HYSTERESIS` below its
+//! threshold, so that loads do not toggle around it.
+//!
+//! Temperatures are in hundredths of degrees Celsius, as the temperature HIL
+//! reports them. The capsule must be the only client of the sensor.
+//!
+//! Usage
+//! -----
+//!
+//! ```rust
+//! let radio_limit = static_init!(
+//!     capsules::thermal_throttle::RadioPowerLimit<'static>,
+//!     capsules::thermal_throttle::RadioPowerLimit::new(&nrf52::radio::RADIO, 0, -20)
+//! );
+//! let consumers = static_init!(
+//!     [&'static kernel::hil::power::PowerConsumer; 1],
+//!     [radio_limit]
+//! );
+//! let throttle_alarm = static_init!(
+//!     VirtualMuxAlarm<'static, nrf5x::rtc::Rtc>,
+//!     VirtualMuxAlarm::new(mux_alarm)
+//! );
+//! let throttle = static_init!(
+//!     capsules::thermal_throttle::ThermalThrottle<
+//!         'static,
+//!         VirtualMuxAlarm<'static, nrf5x::rtc::Rtc>,
+//!     >,
+//!     capsules::thermal_throttle::ThermalThrottle::new(
+//!         &nrf5x::temperature::TEMP,
+//!         throttle_alarm,
+//!         consumers,
+//!         7000, // Reduced above 70 C
+//!         8500, // Minimal above 85 C
+//!         5000, // Read every 5 seconds
+//!         kernel::Grant::create()
+//!     )
+//! );
+//! kernel::hil::sensors::TemperatureDriver::set_client(&nrf5x::temperature::TEMP, throttle);
+//! throttle_alarm.set_client(throttle);
+//! throttle.start();
+//! ```
+//!
+//! Syscall Interface
+//! -----------------
+//!
+//! - Stability: 1 - Experimental
+//!
+//! Levels are 0 for `Minimal`, 1 for `Reduced` and 2 for `Full`.
+//!
+//! ### Subscribe
+//!
+//! - `0`: Called with `(level, temperature)` when the level changes.
+//!
+//! ### Command
+//!
+//! - `0`: Driver check.
+//! - `1`: Returns the level.
+//! - `2`: Returns the last temperature read.
+
+use core::cell::Cell;
+use kernel::common::cells::OptionalCell;
+use kernel::hil::power::{PowerConsumer, PowerLevel};
+use kernel::hil::radio;
+use kernel::hil::sensors;
+use kernel::hil::time::{self, Frequency};
+use kernel::{AppId, Callback, Driver, Grant, ReturnCode};
+
+/// Syscall driver number.
+use crate::driver;
+pub const DRIVER_NUM: usize = driver::NUM::ThermalThrottle as usize;
+
+/// How far below its threshold the temperature must be to raise a level
+pub const HYSTERESIS: i32 = 200;
+
+#[derive(Default)]
+pub struct App {
+    callback: Option<Callback>,
+}
+
+pub struct ThermalThrottle<'a, A: time::Alarm> {
+    sensor: &'a sensors::TemperatureDriver,
+    alarm: &'a A,
+    consumers: &'a [&'a PowerConsumer],
+    warm: i32,
+    hot: i32,
+    period_ms: u32,
+    level: Cell<PowerLevel>,
+    temperature: Cell<i32>,
+    apps: Grant<App>,
+}
+
+impl<A: time::Alarm> ThermalThrottle<'a, A> {
+    pub fn new(
+        sensor: &'a sensors::TemperatureDriver,
+        alarm: &'a A,
+        consumers: &'a [&'a PowerConsumer],
+        warm: i32,
+        hot: i32,
+        period_ms: u32,
+        grant: Grant<App>,
+    ) -> ThermalThrottle<'a, A> {
+        ThermalThrottle {
+            sensor: sensor,
+            alarm: alarm,
+            consumers: consumers,
+            warm: warm,
+            hot: hot,
+            period_ms: period_ms,
+            level: Cell::new(PowerLevel::Full),
+            temperature: Cell::new(0),
+            apps: grant,
+        }
+    }
+
+    /// Reads the temperature now, and every `period_ms` after it.
+    pub fn start(&self) {
+        self.read();
+    }
+
+    fn read(&self) {
+        if self.sensor.read_temperature() != ReturnCode::SUCCESS {
+            self.schedule_read();
+        }
+    }
+
+    fn schedule_read(&self) {
+        let interval = <A::Frequency>::ms_to_tics(self.period_ms);
+        self.alarm
+            .set_alarm(self.alarm.now().wrapping_add(interval));
+    }
+
+    /// The temperature above which the level drops below `level`, or, while
+    /// it is below `level`, the temperature it must fall to to rise to it.
+    fn threshold(&self, level: PowerLevel, threshold: i32) -> i32 {
+        if self.level.get() < level {
+            threshold - HYSTERESIS
+        } else {
+            threshold
+        }
+    }
+
+    fn level_for(&self, temperature: i32) -> PowerLevel {
+        if temperature > self.threshold(PowerLevel::Reduced, self.hot) {
+            PowerLevel::Minimal
+        } else if temperature > self.threshold(PowerLevel::Full, self.warm) {
+            PowerLevel::Reduced
+        } else {
+            PowerLevel::Full
+        }
+    }
+
+    fn encode_level(level: PowerLevel) -> usize {
+        match level {
+            PowerLevel::Minimal => 0,
+            PowerLevel::Reduced => 1,
+            PowerLevel::Full => 2,
+        }
+    }
+}
+
+impl<A: time::Alarm> sensors::TemperatureClient for ThermalThrottle<'a, A> {
+    fn callback(&self, value: usize) {
+        self.schedule_read();
+
+        let temperature = value as i32;
+        self.temperature.set(temperature);
+
+        let level = self.level_for(temperature);
+        if level == self.level.get() {
+            return;
+        }
+        self.level.set(level);
+        for consumer in self.consumers.iter() {
+            consumer.set_power_level(level);
+        }
+        let encoded = ThermalThrottle::<A>::encode_level(level);
+        self.apps.each(|app| {
+            app.callback
+                .map(|mut cb| cb.schedule(encoded, temperature as usize, 0));
+        });
+    }
+}
+
+impl<A: time::Alarm> time::Client for ThermalThrottle<'a, A> {
+    fn fired(&self) {
+        self.read();
+    }
+}
+
+impl<A: time::Alarm> Driver for ThermalThrottle<'a, A> {
+    fn subscribe(
+        &self,
+        subscribe_num: usize,
+        callback: Option<Callback>,
+        app_id: AppId,
+    ) -> ReturnCode {
+        match subscribe_num {
+            0 => self
+                .apps
+                .enter(app_id, |app, _| {
+                    app.callback = callback;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or_else(|err| err.into()),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    fn command(&self, command_num: usize, _: usize, _: usize, _: AppId) -> ReturnCode {
+        match command_num {
+            0 /* check if present */ => ReturnCode::SUCCESS,
+
+            1 => ReturnCode::SuccessWithValue {
+                value: ThermalThrottle::<A>::encode_level(self.level.get()),
+            },
+
+            2 => ReturnCode::SuccessWithValue {
+                value: self.temperature.get() as usize,
+            },
+
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+}
+
+/// Caps the transmit power of a radio below `PowerLevel::Full`, and restores
+/// the power it had once the level is `Full` again.
+pub struct RadioPowerLimit<'a> {
+    radio: &'a radio::RadioConfig,
+    /// The cap at `PowerLevel::Reduced`, in dBm
+    reduced: i8,
+    /// The cap at `PowerLevel::Minimal`, in dBm
+    minimal: i8,
+    /// The power before it was capped
+    saved: OptionalCell<i8>,
+}
+
+impl RadioPowerLimit<'a> {
+    pub fn new(radio: &'a radio::RadioConfig, reduced: i8, minimal: i8) -> RadioPowerLimit<'a> {
+        RadioPowerLimit {
+            radio: radio,
+            reduced: reduced,
+            minimal: minimal,
+            saved: OptionalCell::empty(),
+        }
+    }
+}
+
+impl PowerConsumer for RadioPowerLimit<'a> {
+    fn set_power_level(&self, level: PowerLevel) {
+        let power = match level {
+            PowerLevel::Full => match self.saved.take() {
+                Some(power) => power,
+                None => return,
+            },
+            PowerLevel::Reduced | PowerLevel::Minimal => {
+                let cap = if level == PowerLevel::Reduced {
+                    self.reduced
+                } else {
+                    self.minimal
+                };
+                if self.saved.is_none() {
+                    self.saved.set(self.radio.get_tx_power());
+                }
+                core::cmp::min(self.saved.unwrap_or(cap), cap)
+            }
+        };
+        if self.radio.set_tx_power(power) == ReturnCode::SUCCESS {
+            self.radio.config_commit();
+        }
+    }
+}
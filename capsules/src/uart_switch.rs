@@ -0,0 +1,287 @@
+//! Switch a UART user between several UARTs at runtime.
+//!
+//! `UartSwitch` implements `hil::uart::Uart` on top of one of a set of named
+//! backends, such as hardware UARTs, `usb_cdc::CdcAcm` or
+//! `segger_rtt::SeggerRtt`, and can be moved to another one while the
+//! system runs. Placed under the `MuxUart` of the console, it moves the
+//! console, the process console and kernel debug output together, so that a
+//! deployed device can free a UART that an application needs.
+//!
+//! A switch requested during a transmission happens once the transmission
+//! is done. A read in progress is aborted on the old backend and started
+//! again on the new one; bytes the old backend received for it are passed
+//! up as a short read. If the new backend cannot receive (as with
+//! `SeggerRtt`), the read waits until the switch moves to one that can.
+//!
+//! Usage
+//! -----
+//!
+//! ```rust
+//! let backends = static_init!(
+//!     [UartBackend<'static>; 2],
+//!     [
+//!         UartBackend::new("usart3", &sam4l::usart::USART3),
+//!         UartBackend::new("usart0", &sam4l::usart::USART0),
+//!     ]
+//! );
+//! let uart_switch = static_init!(UartSwitch<'static>, UartSwitch::new(backends));
+//! for backend in backends.iter() {
+//!     hil::uart::Transmit::set_transmit_client(backend.uart, uart_switch);
+//!     hil::uart::Receive::set_receive_client(backend.uart, uart_switch);
+//! }
+//!
+//! let uart_mux = static_init!(
+//!     MuxUart<'static>,
+//!     MuxUart::new(uart_switch, &mut capsules::virtual_uart::RX_BUF, 115200)
+//! );
+//! hil::uart::Transmit::set_transmit_client(uart_switch, uart_mux);
+//! hil::uart::Receive::set_receive_client(uart_switch, uart_mux);
+//!
+//! uart_switch.select("usart0");
+//! ```
+
+use core::cell::Cell;
+use kernel::common::cells::{OptionalCell, TakeCell};
+use kernel::hil::uart;
+use kernel::ReturnCode;
+
+/// A UART that a `UartSwitch` can use, and the name it is selected by.
+pub struct UartBackend<'a> {
+    pub name: &'static str,
+    pub uart: &'a uart::Uart<'a>,
+}
+
+impl UartBackend<'a> {
+    pub const fn new(name: &'static str, uart: &'a uart::Uart<'a>) -> UartBackend<'a> {
+        UartBackend {
+            name: name,
+            uart: uart,
+        }
+    }
+}
+
+pub struct UartSwitch<'a> {
+    backends: &'a [UartBackend<'a>],
+    current: Cell<usize>,
+    /// A backend to switch to once the transmission is done
+    pending: OptionalCell<usize>,
+    params: OptionalCell<uart::Parameters>,
+    tx_client: OptionalCell<&'a uart::TransmitClient>,
+    rx_client: OptionalCell<&'a uart::ReceiveClient>,
+    transmitting: Cell<bool>,
+    /// The length of the read of the client, while it is not done
+    rx_len: OptionalCell<usize>,
+    /// The buffer of the read, while no backend can receive it
+    rx_buffer: TakeCell<'static, [u8]>,
+    /// Set while the read is aborted on the old backend, to start it again
+    /// on the new one
+    rx_moving: Cell<bool>,
+}
+
+impl UartSwitch<'a> {
+    /// `backends` must not be empty. The first one is selected.
+    pub fn new(backends: &'a [UartBackend<'a>]) -> UartSwitch<'a> {
+        UartSwitch {
+            backends: backends,
+            current: Cell::new(0),
+            pending: OptionalCell::empty(),
+            params: OptionalCell::empty(),
+            tx_client: OptionalCell::empty(),
+            rx_client: OptionalCell::empty(),
+            transmitting: Cell::new(false),
+            rx_len: OptionalCell::empty(),
+            rx_buffer: TakeCell::empty(),
+            rx_moving: Cell::new(false),
+        }
+    }
+
+    /// The names of the backends, in order.
+    pub fn names(&self) -> impl Iterator<Item = &'static str> + 'a {
+        let backends = self.backends;
+        backends.iter().map(|backend| backend.name)
+    }
+
+    /// The name of the selected backend. During a transmission, this is the
+    /// backend that will be switched to after it.
+    pub fn selected(&self) -> &'static str {
+        let index = self.pending.unwrap_or_else(|| self.current.get());
+        self.backends[index].name
+    }
+
+    /// Move to the backend called `name`. Returns EINVAL if there is none.
+    pub fn select(&self, name: &str) -> ReturnCode {
+        match self
+            .backends
+            .iter()
+            .position(|backend| backend.name == name)
+        {
+            None => ReturnCode::EINVAL,
+            Some(index) => {
+                if self.transmitting.get() {
+                    self.pending.set(index);
+                } else {
+                    self.switch(index);
+                }
+                ReturnCode::SUCCESS
+            }
+        }
+    }
+
+    fn uart(&self) -> &'a uart::Uart<'a> {
+        self.backends[self.current.get()].uart
+    }
+
+    fn switch(&self, index: usize) {
+        self.pending.clear();
+        if index == self.current.get() {
+            return;
+        }
+        let old = self.uart();
+        self.current.set(index);
+        self.params.map(|params| self.uart().configure(*params));
+
+        if self.rx_buffer.is_some() {
+            self.rx_buffer.take().map(|buffer| self.start_read(buffer));
+        } else if self.rx_len.is_some() && !self.rx_moving.get() {
+            // The read starts again on the new backend when the old one
+            // returns its buffer
+            if old.receive_abort() != ReturnCode::SUCCESS {
+                self.rx_moving.set(true);
+            }
+        }
+    }
+
+    /// Start the read of the client on the selected backend, or keep its
+    /// buffer if the backend cannot receive.
+    fn start_read(&self, buffer: &'static mut [u8]) {
+        let len = self.rx_len.unwrap_or(0);
+        let (rcode, buffer) = self.uart().receive_buffer(buffer, len);
+        if rcode != ReturnCode::SUCCESS {
+            buffer.map(|buffer| self.rx_buffer.replace(buffer));
+        }
+    }
+}
+
+impl uart::Uart<'a> for UartSwitch<'a> {}
+impl uart::UartData<'a> for UartSwitch<'a> {}
+
+impl uart::Configure for UartSwitch<'a> {
+    fn configure(&self, params: uart::Parameters) -> ReturnCode {
+        // Applied to the other backends when they are selected
+        self.params.set(params);
+        self.uart().configure(params)
+    }
+}
+
+impl uart::Transmit<'a> for UartSwitch<'a> {
+    fn set_transmit_client(&self, client: &'a uart::TransmitClient) {
+        self.tx_client.set(client);
+    }
+
+    fn transmit_buffer(
+        &self,
+        tx_data: &'static mut [u8],
+        tx_len: usize,
+    ) -> (ReturnCode, Option<&'static mut [u8]>) {
+        if self.transmitting.get() {
+            return (ReturnCode::EBUSY, Some(tx_data));
+        }
+        let (rcode, buffer) = self.uart().transmit_buffer(tx_data, tx_len);
+        self.transmitting.set(rcode == ReturnCode::SUCCESS);
+        (rcode, buffer)
+    }
+
+    fn transmit_word(&self, word: u32) -> ReturnCode {
+        if self.transmitting.get() {
+            return ReturnCode::EBUSY;
+        }
+        let rcode = self.uart().transmit_word(word);
+        self.transmitting.set(rcode == ReturnCode::SUCCESS);
+        rcode
+    }
+
+    fn transmit_abort(&self) -> ReturnCode {
+        self.uart().transmit_abort()
+    }
+}
+
+impl uart::Receive<'a> for UartSwitch<'a> {
+    fn set_receive_client(&self, client: &'a uart::ReceiveClient) {
+        self.rx_client.set(client);
+    }
+
+    fn receive_buffer(
+        &self,
+        rx_buffer: &'static mut [u8],
+        rx_len: usize,
+    ) -> (ReturnCode, Option<&'static mut [u8]>) {
+        if self.rx_len.is_some() {
+            return (ReturnCode::EBUSY, Some(rx_buffer));
+        }
+        let (rcode, buffer) = self.uart().receive_buffer(rx_buffer, rx_len);
+        if rcode == ReturnCode::SUCCESS {
+            self.rx_len.set(rx_len);
+        }
+        (rcode, buffer)
+    }
+
+    fn receive_word(&self) -> ReturnCode {
+        ReturnCode::FAIL
+    }
+
+    fn receive_abort(&self) -> ReturnCode {
+        if self.rx_moving.get() {
+            // The old backend is already returning the buffer, which now
+            // goes to the client
+            self.rx_moving.set(false);
+            return ReturnCode::EBUSY;
+        }
+        match self.rx_buffer.take() {
+            Some(buffer) => {
+                self.rx_len.clear();
+                self.rx_client.map(move |client| {
+                    client.received_buffer(buffer, 0, ReturnCode::ECANCEL, uart::Error::Aborted)
+                });
+                ReturnCode::EBUSY
+            }
+            None => self.uart().receive_abort(),
+        }
+    }
+}
+
+impl uart::TransmitClient for UartSwitch<'a> {
+    fn transmitted_buffer(&self, tx_buffer: &'static mut [u8], tx_len: usize, rcode: ReturnCode) {
+        self.transmitting.set(false);
+        // Switch before the client can start another transmission
+        self.pending.take().map(|index| self.switch(index));
+        self.tx_client
+            .map(move |client| client.transmitted_buffer(tx_buffer, tx_len, rcode));
+    }
+
+    fn transmitted_word(&self, rcode: ReturnCode) {
+        self.transmitting.set(false);
+        self.pending.take().map(|index| self.switch(index));
+        self.tx_client.map(|client| client.transmitted_word(rcode));
+    }
+}
+
+impl uart::ReceiveClient for UartSwitch<'a> {
+    fn received_buffer(
+        &self,
+        rx_buffer: &'static mut [u8],
+        rx_len: usize,
+        rcode: ReturnCode,
+        error: uart::Error,
+    ) {
+        if self.rx_moving.get() {
+            self.rx_moving.set(false);
+            if rx_len == 0 {
+                self.start_read(rx_buffer);
+                return;
+            }
+        }
+        self.rx_len.clear();
+        self.rx_client
+            .map(move |client| client.received_buffer(rx_buffer, rx_len, rcode, error));
+    }
+}
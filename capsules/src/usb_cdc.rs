@@ -0,0 +1,640 @@
+//! A virtual serial port over USB (CDC-ACM)
+//!
+//! Implements the UART HIL on top of a USB device controller, using the USB
+//! Communications Device Class (CDC) Abstract Control Model, which hosts
+//! drive without any extra software. This gives boards that have no
+//! USB-to-UART bridge, like the nRF52840 Dongle, a console: the virtual
+//! UART multiplexer, and through it the console and `debug!`, can use it in
+//! place of a hardware UART.
+//!
+//! The baud rate and other line settings have no meaning over USB, so they
+//! are accepted and ignored. Data sent before the host opens the port waits
+//! until the host reads it.
+//!
+//! Transfers are completed from a deferred call, as they would be from an
+//! interrupt of a hardware UART.
+//!
+//! Usage
+//! -----
+//!
+//! ```rust
+//! let cdc = static_init!(
+//!     capsules::usb_cdc::CdcAcm<'static, nrf52::usbd::Usbd>,
+//!     capsules::usb_cdc::CdcAcm::new(&nrf52::usbd::USBD, dynamic_deferred_caller)
+//! );
+//! cdc.initialize_callback_handle(
+//!     dynamic_deferred_caller.register(cdc).expect("no deferred call slot available"),
+//! );
+//! nrf52::usbd::USBD.set_client(cdc);
+//! hil::usb::Client::enable(cdc);
+//! hil::usb::Client::attach(cdc);
+//!
+//! let uart_mux = static_init!(
+//!     MuxUart<'static>,
+//!     MuxUart::new(cdc, &mut capsules::virtual_uart::RX_BUF, 115200)
+//! );
+//! ```
+
+use crate::usb::Descriptor;
+use crate::usb::DescriptorType;
+use crate::usb::DeviceDescriptor;
+use crate::usb::LanguagesDescriptor;
+use crate::usb::RequestType;
+use crate::usb::SetupData;
+use crate::usb::StandardDeviceRequest;
+use crate::usb::StringDescriptor;
+use core::cell::Cell;
+use core::cmp::min;
+use kernel::common::cells::{OptionalCell, TakeCell, VolatileCell};
+use kernel::common::dynamic_deferred_call::{
+    DeferredCallHandle, DynamicDeferredCall, DynamicDeferredCallClient,
+};
+use kernel::hil;
+use kernel::hil::uart;
+use kernel::ReturnCode;
+
+const VENDOR_ID: u16 = 0x6667;
+const PRODUCT_ID: u16 = 0xabce;
+
+static LANGUAGES: &'static [u16] = &[
+    0x0409, // English (United States)
+];
+
+// String descriptors are composed in `descriptor_storage`, so strings can be
+// at most 15 characters long
+static STRINGS: &'static [&'static str] = &[
+    "Tock",         // Manufacturer
+    "Tock CDC-ACM", // Product
+];
+
+const DESCRIPTOR_BUFLEN: usize = 32;
+
+/// Size of the packets of the default control endpoint and of the data
+/// endpoints
+const PACKET_SIZE: usize = 64;
+const NOTIFY_PACKET_SIZE: usize = 8;
+
+/// Endpoint for notifications to the host, which this port never sends
+const NOTIFY_ENDPOINT: usize = 1;
+/// Endpoint for data to the host
+const DATA_IN_ENDPOINT: usize = 2;
+/// Endpoint for data from the host
+const DATA_OUT_ENDPOINT: usize = 3;
+
+/// The configuration descriptor, followed by the descriptors of the
+/// communication interface, with its functional descriptors and notification
+/// endpoint, and of the data interface, with its two endpoints.
+#[rustfmt::skip]
+static CONFIGURATION_DESCRIPTOR: [u8; 67] = [
+    // Configuration: 67 bytes in total, 2 interfaces, bus powered, 100 mA
+    9, 2, 67, 0, 2, 1, 0, 0x80, 50,
+    // Interface 0: communication class, abstract control model
+    9, 4, 0, 0, 1, 0x02, 0x02, 0x01, 0,
+    // Header functional descriptor: CDC 1.10
+    5, 0x24, 0x00, 0x10, 0x01,
+    // Call management functional descriptor: no call management
+    5, 0x24, 0x01, 0x00, 0x01,
+    // Abstract control management functional descriptor: line coding
+    4, 0x24, 0x02, 0x02,
+    // Union functional descriptor: interface 0 controls interface 1
+    5, 0x24, 0x06, 0x00, 0x01,
+    // Endpoint 1 IN: interrupt
+    7, 5, 0x80 | NOTIFY_ENDPOINT as u8, 0x03, NOTIFY_PACKET_SIZE as u8, 0, 255,
+    // Interface 1: data class
+    9, 4, 1, 0, 2, 0x0A, 0x00, 0x00, 0,
+    // Endpoint 2 IN: bulk
+    7, 5, 0x80 | DATA_IN_ENDPOINT as u8, 0x02, PACKET_SIZE as u8, 0, 0,
+    // Endpoint 3 OUT: bulk
+    7, 5, DATA_OUT_ENDPOINT as u8, 0x02, PACKET_SIZE as u8, 0, 0,
+];
+
+/// Class-specific requests of the abstract control model
+const SET_LINE_CODING: u8 = 0x20;
+const GET_LINE_CODING: u8 = 0x21;
+const SET_CONTROL_LINE_STATE: u8 = 0x22;
+
+/// 115200 baud, 1 stop bit, no parity, 8 data bits
+const DEFAULT_LINE_CODING: [u8; 7] = [0x00, 0xC2, 0x01, 0x00, 0, 0, 8];
+
+#[derive(Copy, Clone)]
+enum State {
+    Init,
+
+    /// We are doing a Control In transfer of some data in
+    /// `descriptor_storage`, with the given extent remaining to send
+    CtrlIn(usize, usize),
+
+    /// We are doing a Control In transfer of the rest of a static descriptor
+    CtrlInStatic(&'static [u8]),
+
+    /// We are receiving the line coding from the host
+    CtrlOut,
+
+    SetAddress,
+}
+
+pub struct CdcAcm<'a, C: 'a> {
+    controller: &'a C,
+    deferred_caller: &'a DynamicDeferredCall,
+    deferred_call_handle: OptionalCell<DeferredCallHandle>,
+
+    state: Cell<State>,
+    ctrl_buffer: [VolatileCell<u8>; PACKET_SIZE],
+    notify_buffer: [VolatileCell<u8>; NOTIFY_PACKET_SIZE],
+    in_buffer: [VolatileCell<u8>; PACKET_SIZE],
+    out_buffer: [VolatileCell<u8>; PACKET_SIZE],
+    descriptor_storage: [Cell<u8>; DESCRIPTOR_BUFLEN],
+    line_coding: [Cell<u8>; 7],
+
+    tx_client: OptionalCell<&'a uart::TransmitClient>,
+    tx_buffer: TakeCell<'static, [u8]>,
+    tx_len: Cell<usize>,
+    tx_offset: Cell<usize>,
+    /// The last packet was full, so the host needs an empty one to know that
+    /// the transfer has ended
+    tx_zlp: Cell<bool>,
+    /// The host has taken all of the transmitted data
+    tx_done: Cell<bool>,
+    delayed_in: Cell<bool>,
+
+    rx_client: OptionalCell<&'a uart::ReceiveClient>,
+    rx_buffer: TakeCell<'static, [u8]>,
+    rx_len: Cell<usize>,
+    rx_offset: Cell<usize>,
+    /// The result of the reception, once it is done
+    rx_done: OptionalCell<ReturnCode>,
+    /// Bytes of the last packet from the host that have been received
+    out_offset: Cell<usize>,
+    delayed_out: Cell<bool>,
+}
+
+impl<C: hil::usb::UsbController> CdcAcm<'a, C> {
+    pub fn new(controller: &'a C, deferred_caller: &'a DynamicDeferredCall) -> Self {
+        let line_coding: [Cell<u8>; 7] = Default::default();
+        for (cell, byte) in line_coding.iter().zip(DEFAULT_LINE_CODING.iter()) {
+            cell.set(*byte);
+        }
+        CdcAcm {
+            controller: controller,
+            deferred_caller: deferred_caller,
+            deferred_call_handle: OptionalCell::empty(),
+            state: Cell::new(State::Init),
+            ctrl_buffer: [VolatileCell::new(0); PACKET_SIZE],
+            notify_buffer: [VolatileCell::new(0); NOTIFY_PACKET_SIZE],
+            in_buffer: [VolatileCell::new(0); PACKET_SIZE],
+            out_buffer: [VolatileCell::new(0); PACKET_SIZE],
+            descriptor_storage: Default::default(),
+            line_coding: line_coding,
+            tx_client: OptionalCell::empty(),
+            tx_buffer: TakeCell::empty(),
+            tx_len: Cell::new(0),
+            tx_offset: Cell::new(0),
+            tx_zlp: Cell::new(false),
+            tx_done: Cell::new(false),
+            delayed_in: Cell::new(false),
+            rx_client: OptionalCell::empty(),
+            rx_buffer: TakeCell::empty(),
+            rx_len: Cell::new(0),
+            rx_offset: Cell::new(0),
+            rx_done: OptionalCell::empty(),
+            out_offset: Cell::new(0),
+            delayed_out: Cell::new(false),
+        }
+    }
+
+    pub fn initialize_callback_handle(&self, handle: DeferredCallHandle) {
+        self.deferred_call_handle.replace(handle);
+    }
+
+    fn schedule_callback(&self) {
+        self.deferred_call_handle
+            .map(|handle| self.deferred_caller.set(*handle));
+    }
+
+    /// Sets up a Control In transfer of the first bytes of
+    /// `descriptor_storage`.
+    fn ctrl_in_storage(&self, len: usize, requested_length: u16) -> hil::usb::CtrlSetupResult {
+        let end = min(len, requested_length as usize);
+        self.state.set(State::CtrlIn(0, end));
+        hil::usb::CtrlSetupResult::Ok
+    }
+
+    fn standard_request(&self, request: StandardDeviceRequest) -> hil::usb::CtrlSetupResult {
+        match request {
+            StandardDeviceRequest::GetDescriptor {
+                descriptor_type,
+                descriptor_index,
+                lang_id,
+                requested_length,
+            } => match descriptor_type {
+                DescriptorType::Device if descriptor_index == 0 => {
+                    let d = DeviceDescriptor {
+                        class: 0x02, // Communications device class
+                        max_packet_size_ep0: PACKET_SIZE as u8,
+                        vendor_id: VENDOR_ID,
+                        product_id: PRODUCT_ID,
+                        manufacturer_string: 1,
+                        product_string: 2,
+                        ..DeviceDescriptor::default()
+                    };
+                    let len = d.write_to(&self.descriptor_storage);
+                    self.ctrl_in_storage(len, requested_length)
+                }
+                DescriptorType::Device => hil::usb::CtrlSetupResult::ErrInvalidDeviceIndex,
+                DescriptorType::Configuration if descriptor_index == 0 => {
+                    let end = min(CONFIGURATION_DESCRIPTOR.len(), requested_length as usize);
+                    self.state
+                        .set(State::CtrlInStatic(&CONFIGURATION_DESCRIPTOR[..end]));
+                    hil::usb::CtrlSetupResult::Ok
+                }
+                DescriptorType::Configuration => {
+                    hil::usb::CtrlSetupResult::ErrInvalidConfigurationIndex
+                }
+                DescriptorType::String => {
+                    let len = match descriptor_index {
+                        0 => LanguagesDescriptor { langs: LANGUAGES }
+                            .write_to(&self.descriptor_storage),
+                        i if i > 0 && (i as usize) <= STRINGS.len() && lang_id == LANGUAGES[0] => {
+                            StringDescriptor {
+                                string: STRINGS[i as usize - 1],
+                            }
+                            .write_to(&self.descriptor_storage)
+                        }
+                        _ => return hil::usb::CtrlSetupResult::ErrInvalidStringIndex,
+                    };
+                    self.ctrl_in_storage(len, requested_length)
+                }
+                DescriptorType::DeviceQualifier => {
+                    // We are full-speed only, so we must respond with a
+                    // request error
+                    hil::usb::CtrlSetupResult::ErrNoDeviceQualifier
+                }
+                _ => hil::usb::CtrlSetupResult::ErrUnrecognizedDescriptorType,
+            },
+            StandardDeviceRequest::SetAddress { device_address } => {
+                // Load the address we've been assigned ...
+                self.controller.set_address(device_address);
+
+                // ... and when this request gets to the Status stage we will
+                // actually enable the address.
+                self.state.set(State::SetAddress);
+                hil::usb::CtrlSetupResult::Ok
+            }
+            StandardDeviceRequest::SetConfiguration { .. } => hil::usb::CtrlSetupResult::Ok,
+            StandardDeviceRequest::GetConfiguration => {
+                self.descriptor_storage[0].set(1);
+                self.ctrl_in_storage(1, 1)
+            }
+            StandardDeviceRequest::GetStatus { .. } => {
+                // Not self powered, no remote wakeup, and no halted endpoint
+                self.descriptor_storage[0].set(0);
+                self.descriptor_storage[1].set(0);
+                self.ctrl_in_storage(2, 2)
+            }
+            _ => hil::usb::CtrlSetupResult::ErrUnrecognizedRequestType,
+        }
+    }
+
+    fn class_request(&self, setup_data: SetupData) -> hil::usb::CtrlSetupResult {
+        match setup_data.request_code {
+            SET_LINE_CODING => {
+                self.state.set(State::CtrlOut);
+                hil::usb::CtrlSetupResult::Ok
+            }
+            GET_LINE_CODING => {
+                for (i, byte) in self.line_coding.iter().enumerate() {
+                    self.descriptor_storage[i].set(byte.get());
+                }
+                self.ctrl_in_storage(self.line_coding.len(), setup_data.length)
+            }
+            // The port works the same whether or not a terminal is open on
+            // the host
+            SET_CONTROL_LINE_STATE => hil::usb::CtrlSetupResult::Ok,
+            _ => hil::usb::CtrlSetupResult::ErrNonstandardRequest,
+        }
+    }
+}
+
+impl<C: hil::usb::UsbController> hil::usb::Client for CdcAcm<'a, C> {
+    fn enable(&self) {
+        // Set up the default control endpoint
+        self.controller.endpoint_set_buffer(0, &self.ctrl_buffer);
+        self.controller
+            .enable_as_device(hil::usb::DeviceSpeed::Full); // must be Full for Bulk transfers
+        self.controller.endpoint_ctrl_out_enable(0);
+
+        // The notification endpoint is an interrupt endpoint for the host,
+        // but it never sends anything, so it is set up like a bulk one
+        self.controller
+            .endpoint_set_buffer(NOTIFY_ENDPOINT, &self.notify_buffer);
+        self.controller.endpoint_bulk_in_enable(NOTIFY_ENDPOINT);
+
+        self.controller
+            .endpoint_set_buffer(DATA_IN_ENDPOINT, &self.in_buffer);
+        self.controller.endpoint_bulk_in_enable(DATA_IN_ENDPOINT);
+
+        self.controller
+            .endpoint_set_buffer(DATA_OUT_ENDPOINT, &self.out_buffer);
+        self.controller.endpoint_bulk_out_enable(DATA_OUT_ENDPOINT);
+    }
+
+    fn attach(&self) {
+        self.controller.attach();
+    }
+
+    fn bus_reset(&self) {
+        self.state.set(State::Init);
+        self.out_offset.set(0);
+        self.delayed_in.set(false);
+        self.delayed_out.set(false);
+    }
+
+    /// Handle a Control Setup transaction
+    fn ctrl_setup(&self, endpoint: usize) -> hil::usb::CtrlSetupResult {
+        if endpoint != 0 {
+            // We only support the default Control endpoint
+            return hil::usb::CtrlSetupResult::ErrInvalidDeviceIndex;
+        }
+        SetupData::get(&self.ctrl_buffer[..8]).map_or(
+            hil::usb::CtrlSetupResult::ErrNoParse,
+            |setup_data| match setup_data.request_type.request_type() {
+                RequestType::Standard => setup_data.get_standard_request().map_or(
+                    hil::usb::CtrlSetupResult::ErrUnrecognizedRequestType,
+                    |request| self.standard_request(request),
+                ),
+                RequestType::Class => self.class_request(setup_data),
+                _ => hil::usb::CtrlSetupResult::ErrNonstandardRequest,
+            },
+        )
+    }
+
+    /// Handle a Control In transaction
+    fn ctrl_in(&self, endpoint: usize) -> hil::usb::CtrlInResult {
+        if endpoint != 0 {
+            return hil::usb::CtrlInResult::Error;
+        }
+        let buf = &self.ctrl_buffer;
+
+        // Copy a packet into the endpoint buffer
+        match self.state.get() {
+            State::CtrlIn(start, end) => {
+                let packet_bytes = min(PACKET_SIZE, end.saturating_sub(start));
+                let packet = &self.descriptor_storage[start..start + packet_bytes];
+                for (dst, src) in buf.iter().zip(packet.iter()) {
+                    dst.set(src.get());
+                }
+                let start = start + packet_bytes;
+                self.state.set(State::CtrlIn(start, end));
+                hil::usb::CtrlInResult::Packet(packet_bytes, start >= end)
+            }
+            State::CtrlInStatic(data) => {
+                let packet_bytes = min(PACKET_SIZE, data.len());
+                for (dst, src) in buf.iter().zip(data[..packet_bytes].iter()) {
+                    dst.set(*src);
+                }
+                let rest = &data[packet_bytes..];
+                self.state.set(State::CtrlInStatic(rest));
+                hil::usb::CtrlInResult::Packet(packet_bytes, rest.is_empty())
+            }
+            _ => hil::usb::CtrlInResult::Error,
+        }
+    }
+
+    /// Handle a Control Out transaction
+    fn ctrl_out(&self, endpoint: usize, packet_bytes: u32) -> hil::usb::CtrlOutResult {
+        match self.state.get() {
+            State::CtrlOut if endpoint == 0 => {
+                for (cell, byte) in self
+                    .line_coding
+                    .iter()
+                    .zip(self.ctrl_buffer.iter().take(packet_bytes as usize))
+                {
+                    cell.set(byte.get());
+                }
+                hil::usb::CtrlOutResult::Ok
+            }
+            _ => hil::usb::CtrlOutResult::Halted,
+        }
+    }
+
+    fn ctrl_status(&self, _endpoint: usize) {
+        // Entered Status stage
+    }
+
+    /// Handle the completion of a Control transfer
+    fn ctrl_status_complete(&self, _endpoint: usize) {
+        match self.state.get() {
+            State::SetAddress => {
+                self.controller.enable_address();
+            }
+            _ => {}
+        };
+        self.state.set(State::Init);
+    }
+
+    /// Handle a Bulk IN transaction
+    fn bulk_in(&self, endpoint: usize) -> hil::usb::BulkInResult {
+        if endpoint != DATA_IN_ENDPOINT || self.tx_done.get() {
+            return hil::usb::BulkInResult::Delay;
+        }
+        self.tx_buffer.map_or_else(
+            || {
+                // Nothing to send
+                self.delayed_in.set(true);
+                hil::usb::BulkInResult::Delay
+            },
+            |buffer| {
+                let offset = self.tx_offset.get();
+                let remaining = self.tx_len.get() - offset;
+                if remaining > 0 {
+                    let packet_bytes = min(PACKET_SIZE, remaining);
+                    for (dst, byte) in self
+                        .in_buffer
+                        .iter()
+                        .zip(buffer[offset..offset + packet_bytes].iter())
+                    {
+                        dst.set(*byte);
+                    }
+                    self.tx_offset.set(offset + packet_bytes);
+                    self.tx_zlp
+                        .set(packet_bytes == PACKET_SIZE && packet_bytes == remaining);
+                    hil::usb::BulkInResult::Packet(packet_bytes)
+                } else if self.tx_zlp.take() {
+                    hil::usb::BulkInResult::Packet(0)
+                } else {
+                    // The host has taken all of the data
+                    self.tx_done.set(true);
+                    self.delayed_in.set(true);
+                    self.schedule_callback();
+                    hil::usb::BulkInResult::Delay
+                }
+            },
+        )
+    }
+
+    /// Handle a Bulk OUT transaction
+    fn bulk_out(&self, endpoint: usize, packet_bytes: u32) -> hil::usb::BulkOutResult {
+        if endpoint != DATA_OUT_ENDPOINT {
+            return hil::usb::BulkOutResult::Error;
+        }
+        if self.rx_done.is_some() {
+            // Wait for the client to take the data it has received
+            self.delayed_out.set(true);
+            return hil::usb::BulkOutResult::Delay;
+        }
+        let packet_bytes = min(packet_bytes as usize, PACKET_SIZE);
+        self.rx_buffer.map_or_else(
+            || {
+                // Wait for the client to ask for data
+                self.delayed_out.set(true);
+                hil::usb::BulkOutResult::Delay
+            },
+            |buffer| {
+                let mut out_offset = self.out_offset.get();
+                let mut rx_offset = self.rx_offset.get();
+                while out_offset < packet_bytes && rx_offset < self.rx_len.get() {
+                    buffer[rx_offset] = self.out_buffer[out_offset].get();
+                    out_offset += 1;
+                    rx_offset += 1;
+                }
+                self.rx_offset.set(rx_offset);
+
+                if rx_offset == self.rx_len.get() {
+                    self.rx_done.set(ReturnCode::SUCCESS);
+                    self.schedule_callback();
+                }
+                if out_offset < packet_bytes {
+                    // Keep the rest of the packet for the next reception
+                    self.out_offset.set(out_offset);
+                    self.delayed_out.set(true);
+                    hil::usb::BulkOutResult::Delay
+                } else {
+                    self.out_offset.set(0);
+                    hil::usb::BulkOutResult::Ok
+                }
+            },
+        )
+    }
+}
+
+impl<C: hil::usb::UsbController> DynamicDeferredCallClient for CdcAcm<'a, C> {
+    fn call(&self, _handle: DeferredCallHandle) {
+        if self.tx_done.take() {
+            self.tx_buffer.take().map(|buffer| {
+                self.tx_client.map(move |client| {
+                    client.transmitted_buffer(buffer, self.tx_len.get(), ReturnCode::SUCCESS);
+                });
+            });
+        }
+        if let Some(rcode) = self.rx_done.take() {
+            let error = if rcode == ReturnCode::SUCCESS {
+                uart::Error::None
+            } else {
+                uart::Error::Aborted
+            };
+            self.rx_buffer.take().map(|buffer| {
+                self.rx_client.map(move |client| {
+                    client.received_buffer(buffer, self.rx_offset.get(), rcode, error);
+                });
+            });
+        }
+    }
+}
+
+impl<C: hil::usb::UsbController> uart::Uart<'a> for CdcAcm<'a, C> {}
+impl<C: hil::usb::UsbController> uart::UartData<'a> for CdcAcm<'a, C> {}
+
+impl<C: hil::usb::UsbController> uart::Configure for CdcAcm<'a, C> {
+    fn configure(&self, _params: uart::Parameters) -> ReturnCode {
+        // Line settings have no meaning over USB
+        ReturnCode::SUCCESS
+    }
+}
+
+impl<C: hil::usb::UsbController> uart::Transmit<'a> for CdcAcm<'a, C> {
+    fn set_transmit_client(&self, client: &'a uart::TransmitClient) {
+        self.tx_client.set(client);
+    }
+
+    fn transmit_buffer(
+        &self,
+        tx_buffer: &'static mut [u8],
+        tx_len: usize,
+    ) -> (ReturnCode, Option<&'static mut [u8]>) {
+        if self.tx_buffer.is_some() {
+            return (ReturnCode::EBUSY, Some(tx_buffer));
+        }
+        if tx_len > tx_buffer.len() {
+            return (ReturnCode::ESIZE, Some(tx_buffer));
+        }
+        self.tx_buffer.replace(tx_buffer);
+        self.tx_len.set(tx_len);
+        self.tx_offset.set(0);
+        self.tx_zlp.set(false);
+
+        // In case we reported Delay before, alert the controller that we now
+        // have data to send
+        if self.delayed_in.take() {
+            self.controller.endpoint_bulk_resume(DATA_IN_ENDPOINT);
+        }
+        (ReturnCode::SUCCESS, None)
+    }
+
+    fn transmit_word(&self, _word: u32) -> ReturnCode {
+        ReturnCode::FAIL
+    }
+
+    fn transmit_abort(&self) -> ReturnCode {
+        if self.tx_buffer.is_none() {
+            ReturnCode::SUCCESS
+        } else {
+            // Data that the controller has taken cannot be taken back
+            ReturnCode::FAIL
+        }
+    }
+}
+
+impl<C: hil::usb::UsbController> uart::Receive<'a> for CdcAcm<'a, C> {
+    fn set_receive_client(&self, client: &'a uart::ReceiveClient) {
+        self.rx_client.set(client);
+    }
+
+    fn receive_buffer(
+        &self,
+        rx_buffer: &'static mut [u8],
+        rx_len: usize,
+    ) -> (ReturnCode, Option<&'static mut [u8]>) {
+        if self.rx_buffer.is_some() {
+            return (ReturnCode::EBUSY, Some(rx_buffer));
+        }
+        if rx_len > rx_buffer.len() {
+            return (ReturnCode::ESIZE, Some(rx_buffer));
+        }
+        self.rx_buffer.replace(rx_buffer);
+        self.rx_len.set(rx_len);
+        self.rx_offset.set(0);
+
+        // In case we reported Delay before, alert the controller that we can
+        // now receive data
+        if self.delayed_out.take() {
+            self.controller.endpoint_bulk_resume(DATA_OUT_ENDPOINT);
+        }
+        (ReturnCode::SUCCESS, None)
+    }
+
+    fn receive_word(&self) -> ReturnCode {
+        ReturnCode::FAIL
+    }
+
+    fn receive_abort(&self) -> ReturnCode {
+        if self.rx_buffer.is_none() {
+            ReturnCode::SUCCESS
+        } else {
+            if self.rx_done.is_none() {
+                self.rx_done.set(ReturnCode::ECANCEL);
+                self.schedule_callback();
+            }
+            ReturnCode::EBUSY
+        }
+    }
+}
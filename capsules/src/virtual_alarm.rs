@@ -69,6 +69,10 @@ impl<Alrm: Alarm> Alarm for VirtualMuxAlarm<'a, Alrm> {
         self.mux.alarm.now()
     }
 
+    fn now64(&self) -> Option<u64> {
+        self.mux.alarm.now64()
+    }
+
     fn set_alarm(&self, when: u32) {
         let enabled = self.mux.enabled.get();
 
@@ -81,7 +85,10 @@ impl<Alrm: Alarm> Alarm for VirtualMuxAlarm<'a, Alrm> {
             let cur_alarm = self.mux.alarm.get_alarm();
             let now = self.now();
 
-            if cur_alarm.wrapping_sub(now) > when.wrapping_sub(now) {
+            // Within the slack of the mux, the new alarm waits for the
+            // current one rather than causing another wakeup
+            let latest = when.wrapping_sub(now).saturating_add(self.mux.slack.get());
+            if cur_alarm.wrapping_sub(now) > latest {
                 self.mux.prev.set(self.mux.alarm.now());
                 self.mux.alarm.set_alarm(when);
             }
@@ -110,6 +117,9 @@ pub struct MuxAlarm<'a, Alrm: Alarm> {
     virtual_alarms: List<'a, VirtualMuxAlarm<'a, Alrm>>,
     enabled: Cell<usize>,
     prev: Cell<u32>,
+    /// How late an alarm may fire, in tics, so that it fires along with a
+    /// later one
+    slack: Cell<u32>,
     alarm: &'a Alrm,
 }
 
@@ -119,13 +129,23 @@ impl<Alrm: Alarm> MuxAlarm<'a, Alrm> {
             virtual_alarms: List::new(),
             enabled: Cell::new(0),
             prev: Cell::new(0),
+            slack: Cell::new(0),
             alarm: alarm,
         }
     }
+
+    /// Coalesce alarms that expire within `slack` tics of each other into a
+    /// single wakeup of the hardware alarm. Alarms then fire up to `slack`
+    /// tics late, but a device with many periodic alarms wakes up less often.
+    /// The slack is 0 by default, so that alarms fire on time.
+    pub fn set_slack(&self, slack: u32) {
+        self.slack.set(slack);
+    }
 }
 
+/// Whether `alarm`, which was set at or after `prev`, expired at `now`.
 fn has_expired(alarm: u32, now: u32, prev: u32) -> bool {
-    now.wrapping_sub(prev) >= alarm.wrapping_sub(prev)
+    time::expired(now, prev, alarm.wrapping_sub(prev))
 }
 
 impl<Alrm: Alarm> time::Client for MuxAlarm<'a, Alrm> {
@@ -156,13 +176,26 @@ impl<Alrm: Alarm> time::Client for MuxAlarm<'a, Alrm> {
             .virtual_alarms
             .iter()
             .filter(|cur| cur.armed.get())
-            .min_by_key(|cur| cur.when.get().wrapping_sub(now));
+            .map(|cur| cur.when.get().wrapping_sub(now))
+            .min();
 
         self.prev.set(now);
-        // If there is an alarm to fire, set the underlying alarm to it
-        if let Some(valrm) = next {
-            self.alarm.set_alarm(valrm.when.get());
-            if has_expired(valrm.when.get(), self.alarm.now(), prev) {
+        // If there is an alarm to fire, set the underlying alarm to it, or to
+        // the latest alarm within the slack after it, so that they all fire
+        // in the same wakeup
+        if let Some(soonest) = next {
+            let latest = soonest.saturating_add(self.slack.get());
+            let when = self
+                .virtual_alarms
+                .iter()
+                .filter(|cur| cur.armed.get())
+                .map(|cur| cur.when.get().wrapping_sub(now))
+                .filter(|&dt| dt <= latest)
+                .max()
+                .unwrap_or(soonest)
+                .wrapping_add(now);
+            self.alarm.set_alarm(when);
+            if has_expired(when, self.alarm.now(), prev) {
                 self.fired();
             }
         } else {
@@ -8,6 +8,25 @@
 //! must use a `FlashUser` instance to contain the per-user state for the
 //! virtualization.
 //!
+//! A user can claim a region of pages with `set_region`, so that users such
+//! as a log and an update capsule cannot corrupt each other's data. Regions
+//! do not overlap. A user with a region can only write and erase the pages of
+//! its region, and a user without one can only write and erase the pages
+//! that no user claimed. Reads are not restricted, as the flash is memory
+//! mapped and can be read directly anyway.
+//!
+//! Each user has at most one request waiting for the flash: a request made
+//! while the previous one has not been issued to the hardware yet returns
+//! `EBUSY`.
+//!
+//! Erasing a page can keep the flash, and on some chips the whole CPU, busy
+//! for milliseconds. Erases are therefore background work: pending reads and
+//! writes are issued before them, and a user erases a range of pages with
+//! `erase_pages`, which issues one page at a time so that other requests are
+//! served in between. A board can also give the mux an `ErasePolicy`, which
+//! holds erases back while latency-critical work, like a radio transfer, is
+//! going on.
+//!
 //! Usage
 //! -----
 //!
@@ -22,6 +41,12 @@
 //! let virtual_flash = static_init!(
 //!     capsules::virtual_flash::FlashUser<'static, sam4l::flashcalw::FLASHCALW>,
 //!     capsules::virtual_flash::FlashUser::new(mux_flash));
+//!
+//! // Optionally, give the user pages 0x3c0 to 0x3ff for itself.
+//! virtual_flash.set_region(0x3c0, 0x40);
+//!
+//! // Optionally, only erase while `policy` allows it.
+//! mux_flash.set_erase_policy(policy);
 //! ```
 
 use core::cell::Cell;
@@ -30,6 +55,21 @@ use kernel::common::{List, ListLink, ListNode};
 use kernel::hil;
 use kernel::ReturnCode;
 
+/// Decides when the flash may be erased.
+pub trait ErasePolicy<'a> {
+    /// Sets the client told when erases are allowed again.
+    fn set_client(&self, client: &'a ErasePolicyClient);
+
+    /// Returns whether a page may be erased now. If this returns false, the
+    /// policy must call `erase_window_opened` on its client later, after
+    /// which it is asked again.
+    fn erase_allowed(&self) -> bool;
+}
+
+pub trait ErasePolicyClient {
+    fn erase_window_opened(&self);
+}
+
 /// Handle keeping a list of active users of flash hardware and serialize their
 /// requests. After each completed request the list is checked to see if there
 /// is another flash user with an outstanding read, write, or erase request.
@@ -37,6 +77,7 @@ pub struct MuxFlash<'a, F: hil::flash::Flash + 'static> {
     flash: &'a F,
     users: List<'a, FlashUser<'a, F>>,
     inflight: OptionalCell<&'a FlashUser<'a, F>>,
+    erase_policy: OptionalCell<&'a ErasePolicy<'a>>,
 }
 
 impl<F: hil::flash::Flash> hil::flash::Client<F> for MuxFlash<'a, F> {
@@ -68,47 +109,77 @@ impl<F: hil::flash::Flash> MuxFlash<'a, F> {
             flash: flash,
             users: List::new(),
             inflight: OptionalCell::empty(),
+            erase_policy: OptionalCell::empty(),
         }
     }
 
+    /// Only issues erases while `policy` allows them.
+    pub fn set_erase_policy(&'a self, policy: &'a ErasePolicy<'a>) {
+        policy.set_client(self);
+        self.erase_policy.set(policy);
+    }
+
+    /// Returns the user whose region contains `page_number`, if any.
+    fn owner(&self, page_number: usize) -> Option<&'a FlashUser<'a, F>> {
+        self.users.iter().find(|user| {
+            user.region.map_or(false, |(start, end)| {
+                *start <= page_number && page_number < *end
+            })
+        })
+    }
+
     /// Scan the list of users and find the first user that has a pending
-    /// request, then issue that request to the flash hardware.
+    /// read or write request, or else a pending erase request that the erase
+    /// policy allows, then issue that request to the flash hardware.
     fn do_next_op(&self) {
-        if self.inflight.is_none() {
-            let mnode = self
-                .users
-                .iter()
-                .find(|node| node.operation.get() != Op::Idle);
-            mnode.map(|node| {
-                node.buffer.take().map_or_else(
-                    || {
-                        // Don't need a buffer for erase.
-                        match node.operation.get() {
-                            Op::Erase(page_number) => {
-                                self.flash.erase_page(page_number);
-                            }
-                            _ => {}
-                        };
-                    },
-                    |buf| {
-                        match node.operation.get() {
-                            Op::Write(page_number) => {
-                                self.flash.write_page(page_number, buf);
-                            }
-                            Op::Read(page_number) => {
-                                self.flash.read_page(page_number, buf);
-                            }
-                            Op::Erase(page_number) => {
-                                self.flash.erase_page(page_number);
-                            }
-                            Op::Idle => {} // Can't get here...
-                        }
-                    },
-                );
-                node.operation.set(Op::Idle);
-                self.inflight.set(node);
-            });
+        if self.inflight.is_some() {
+            return;
         }
+        let mnode = self
+            .users
+            .iter()
+            .find(|node| match node.operation.get() {
+                Op::Read(_) | Op::Write(_) => true,
+                Op::Erase(_) | Op::Idle => false,
+            })
+            .or_else(|| {
+                let erase = self
+                    .users
+                    .iter()
+                    .find(|node| node.operation.get() != Op::Idle);
+                // Only ask the policy when there is something to erase, as
+                // a refusal makes it wait for a window
+                erase.filter(|_| {
+                    self.erase_policy
+                        .map_or(true, |policy| policy.erase_allowed())
+                })
+            });
+        mnode.map(|node| {
+            match node.operation.get() {
+                Op::Write(page_number) => {
+                    node.buffer
+                        .take()
+                        .map(|buf| self.flash.write_page(page_number, buf));
+                }
+                Op::Read(page_number) => {
+                    node.buffer
+                        .take()
+                        .map(|buf| self.flash.read_page(page_number, buf));
+                }
+                Op::Erase(page_number) => {
+                    self.flash.erase_page(page_number);
+                }
+                Op::Idle => {} // Can't get here...
+            }
+            node.operation.set(Op::Idle);
+            self.inflight.set(node);
+        });
+    }
+}
+
+impl<F: hil::flash::Flash> ErasePolicyClient for MuxFlash<'a, F> {
+    fn erase_window_opened(&self) {
+        self.do_next_op();
     }
 }
 
@@ -128,6 +199,12 @@ pub struct FlashUser<'a, F: hil::flash::Flash + 'static> {
     mux: &'a MuxFlash<'a, F>,
     buffer: TakeCell<'static, F::Page>,
     operation: Cell<Op>,
+    /// The next page of the range being erased and the page after the range
+    erase_range: OptionalCell<(usize, usize)>,
+    /// The first page of the user's region and the page after it
+    region: OptionalCell<(usize, usize)>,
+    /// Whether the user is in the list of the mux
+    registered: Cell<bool>,
     next: ListLink<'a, FlashUser<'a, F>>,
     client: OptionalCell<&'a hil::flash::Client<FlashUser<'a, F>>>,
 }
@@ -138,17 +215,89 @@ impl<F: hil::flash::Flash> FlashUser<'a, F> {
             mux: mux,
             buffer: TakeCell::empty(),
             operation: Cell::new(Op::Idle),
+            erase_range: OptionalCell::empty(),
+            region: OptionalCell::empty(),
+            registered: Cell::new(false),
             next: ListLink::empty(),
             client: OptionalCell::empty(),
         }
     }
+
+    /// Claims the `num_pages` pages starting at `first_page` for this user.
+    /// Returns `EBUSY` if another user claimed any of them, and `EALREADY` if
+    /// this user already has a region.
+    pub fn set_region(&'a self, first_page: usize, num_pages: usize) -> ReturnCode {
+        if self.region.is_some() {
+            return ReturnCode::EALREADY;
+        }
+        let end = match first_page.checked_add(num_pages) {
+            Some(end) if num_pages > 0 => end,
+            _ => return ReturnCode::EINVAL,
+        };
+        let overlaps = self.mux.users.iter().any(|user| {
+            user.region.map_or(false, |(start, user_end)| {
+                first_page < *user_end && *start < end
+            })
+        });
+        if overlaps {
+            return ReturnCode::EBUSY;
+        }
+        self.region.set((first_page, end));
+        self.register();
+        ReturnCode::SUCCESS
+    }
+
+    fn register(&'a self) {
+        if !self.registered.get() {
+            self.registered.set(true);
+            self.mux.users.push_head(self);
+        }
+    }
+
+    /// Returns whether this user may write and erase `page_number`.
+    fn can_modify(&self, page_number: usize) -> bool {
+        self.region.map_or_else(
+            || self.mux.owner(page_number).is_none(),
+            |(start, end)| *start <= page_number && page_number < *end,
+        )
+    }
+
+    /// Erases the `num_pages` pages starting at `first_page`, one page at a
+    /// time, and calls the client's `erase_complete` once all of them are
+    /// erased or one of them failed.
+    pub fn erase_pages(&self, first_page: usize, num_pages: usize) -> ReturnCode {
+        let end = match first_page.checked_add(num_pages) {
+            Some(end) if num_pages > 0 => end,
+            _ => return ReturnCode::EINVAL,
+        };
+        if !(first_page..end).all(|page_number| self.can_modify(page_number)) {
+            return ReturnCode::EINVAL;
+        }
+        let result = self.request(Op::Erase(first_page), None);
+        if result == ReturnCode::SUCCESS {
+            self.erase_range.set((first_page + 1, end));
+        }
+        result
+    }
+
+    /// Queues `operation`, unless another one is waiting or a range of pages
+    /// is being erased.
+    fn request(&self, operation: Op, buf: Option<&'static mut F::Page>) -> ReturnCode {
+        if self.operation.get() != Op::Idle || self.erase_range.is_some() {
+            return ReturnCode::EBUSY;
+        }
+        buf.map(|buf| self.buffer.replace(buf));
+        self.operation.set(operation);
+        self.mux.do_next_op();
+        ReturnCode::SUCCESS
+    }
 }
 
 impl<F: hil::flash::Flash, C: hil::flash::Client<Self>> hil::flash::HasClient<'a, C>
     for FlashUser<'a, F>
 {
     fn set_client(&'a self, client: &'a C) {
-        self.mux.users.push_head(self);
+        self.register();
         self.client.set(client);
     }
 }
@@ -167,6 +316,14 @@ impl<F: hil::flash::Flash> hil::flash::Client<F> for FlashUser<'a, F> {
     }
 
     fn erase_complete(&self, error: hil::flash::Error) {
+        if let Some((next, end)) = self.erase_range.take() {
+            if next < end && error == hil::flash::Error::CommandComplete {
+                // The mux issues the next page once it is done with this one
+                self.erase_range.set((next + 1, end));
+                self.operation.set(Op::Erase(next));
+                return;
+            }
+        }
         self.client.map(move |client| {
             client.erase_complete(error);
         });
@@ -183,22 +340,17 @@ impl<F: hil::flash::Flash> hil::flash::Flash for FlashUser<'a, F> {
     type Page = F::Page;
 
     fn read_page(&self, page_number: usize, buf: &'static mut Self::Page) -> ReturnCode {
-        self.buffer.replace(buf);
-        self.operation.set(Op::Read(page_number));
-        self.mux.do_next_op();
-        ReturnCode::SUCCESS
+        self.request(Op::Read(page_number), Some(buf))
     }
 
     fn write_page(&self, page_number: usize, buf: &'static mut Self::Page) -> ReturnCode {
-        self.buffer.replace(buf);
-        self.operation.set(Op::Write(page_number));
-        self.mux.do_next_op();
-        ReturnCode::SUCCESS
+        if !self.can_modify(page_number) {
+            return ReturnCode::EINVAL;
+        }
+        self.request(Op::Write(page_number), Some(buf))
     }
 
     fn erase_page(&self, page_number: usize) -> ReturnCode {
-        self.operation.set(Op::Erase(page_number));
-        self.mux.do_next_op();
-        ReturnCode::SUCCESS
+        self.erase_pages(page_number, 1)
     }
 }
@@ -1,7 +1,15 @@
 //! Virtualize an I2C master bus.
 //!
 //! `MuxI2C` provides shared access to a single I2C Master Bus for multiple
-//! users. `I2CDevice` provides access to a specific I2C address.
+//! users. `I2CDevice` provides access to a specific I2C address. An
+//! `I2CDevice` is also an `I2CMaster`, which addresses each transaction to
+//! the address it is given, so that a user that talks to any device on the
+//! bus, such as the userspace I2C master driver, can share the bus too.
+//!
+//! Transactions are served in turn, except that a transaction given a deadline
+//! with `I2CDeviceDeadline::set_deadline()`, such as a periodic sensor read,
+//! goes before those without one, and the earliest deadline first. This keeps
+//! the jitter of periodic samples low when the bus is busy.
 
 use core::cell::Cell;
 use kernel::common::cells::{OptionalCell, TakeCell};
@@ -50,24 +58,41 @@ impl MuxI2C<'a> {
         }
     }
 
+    /// The device whose pending transaction goes next: the one with the
+    /// earliest deadline, or else the first one pending.
+    fn next_device(&self) -> Option<&'a I2CDevice<'a>> {
+        self.devices
+            .iter()
+            .filter(|node| node.operation.get() != Op::Idle)
+            .fold(None, |next: Option<&'a I2CDevice<'a>>, node| match next {
+                None => Some(node),
+                Some(next) => match (next.deadline.get(), node.deadline.get()) {
+                    (None, Some(_)) => Some(node),
+                    // Deadlines wrap around, so compare their difference
+                    (Some(first), Some(deadline)) if (deadline.wrapping_sub(first) as i32) < 0 => {
+                        Some(node)
+                    }
+                    _ => Some(next),
+                },
+            })
+    }
+
     fn do_next_op(&self) {
         if self.inflight.is_none() {
-            let mnode = self
-                .devices
-                .iter()
-                .find(|node| node.operation.get() != Op::Idle);
+            let mnode = self.next_device();
             mnode.map(|node| {
                 node.buffer.take().map(|buf| {
                     match node.operation.get() {
-                        Op::Write(len) => self.i2c.write(node.addr, buf, len),
-                        Op::Read(len) => self.i2c.read(node.addr, buf, len),
+                        Op::Write(len) => self.i2c.write(node.addr.get(), buf, len),
+                        Op::Read(len) => self.i2c.read(node.addr.get(), buf, len),
                         Op::WriteRead(wlen, rlen) => {
-                            self.i2c.write_read(node.addr, buf, wlen, rlen)
+                            self.i2c.write_read(node.addr.get(), buf, wlen, rlen)
                         }
                         Op::Idle => {} // Can't get here...
                     }
                 });
                 node.operation.set(Op::Idle);
+                node.deadline.set(None);
                 self.inflight.set(node);
             });
         }
@@ -84,10 +109,11 @@ enum Op {
 
 pub struct I2CDevice<'a> {
     mux: &'a MuxI2C<'a>,
-    addr: u8,
+    addr: Cell<u8>,
     enabled: Cell<bool>,
     buffer: TakeCell<'static, [u8]>,
     operation: Cell<Op>,
+    deadline: Cell<Option<u32>>,
     next: ListLink<'a, I2CDevice<'a>>,
     client: OptionalCell<&'a I2CClient>,
 }
@@ -96,10 +122,11 @@ impl I2CDevice<'a> {
     pub const fn new(mux: &'a MuxI2C<'a>, addr: u8) -> I2CDevice<'a> {
         I2CDevice {
             mux: mux,
-            addr: addr,
+            addr: Cell::new(addr),
             enabled: Cell::new(false),
             buffer: TakeCell::empty(),
             operation: Cell::new(Op::Idle),
+            deadline: Cell::new(None),
             next: ListLink::empty(),
             client: OptionalCell::empty(),
         }
@@ -158,3 +185,36 @@ impl i2c::I2CDevice for I2CDevice<'a> {
         self.mux.do_next_op();
     }
 }
+
+impl i2c::I2CDeviceDeadline for I2CDevice<'a> {
+    fn set_deadline(&self, deadline: u32) {
+        self.deadline.set(Some(deadline));
+    }
+}
+
+/// Transactions of the device as an `I2CMaster` go to the address passed
+/// with each one, rather than to the address of the device.
+impl i2c::I2CMaster for I2CDevice<'a> {
+    fn enable(&self) {
+        i2c::I2CDevice::enable(self);
+    }
+
+    fn disable(&self) {
+        i2c::I2CDevice::disable(self);
+    }
+
+    fn write_read(&self, addr: u8, data: &'static mut [u8], write_len: u8, read_len: u8) {
+        self.addr.set(addr);
+        i2c::I2CDevice::write_read(self, data, write_len, read_len);
+    }
+
+    fn write(&self, addr: u8, data: &'static mut [u8], len: u8) {
+        self.addr.set(addr);
+        i2c::I2CDevice::write(self, data, len);
+    }
+
+    fn read(&self, addr: u8, buffer: &'static mut [u8], len: u8) {
+        self.addr.set(addr);
+        i2c::I2CDevice::read(self, buffer, len);
+    }
+}
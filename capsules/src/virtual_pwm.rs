@@ -150,6 +150,10 @@ impl<P: hil::pwm::Pwm> hil::pwm::PwmPin for PwmPinUser<'a, P> {
         self.mux.pwm.get_maximum_frequency_hz()
     }
 
+    fn get_minimum_frequency_hz(&self) -> usize {
+        self.mux.pwm.get_minimum_frequency_hz()
+    }
+
     fn get_maximum_duty_cycle(&self) -> usize {
         self.mux.pwm.get_maximum_duty_cycle()
     }
@@ -1,4 +1,10 @@
 //! Virtualize a SPI master bus to enable multiple users of the SPI bus.
+//!
+//! A `VirtualSpiMasterDevice` also makes chained transfers, of segments with
+//! buffers of their own, by transferring the segments back to back with the
+//! chip select held between them with `SpiMaster::hold_low()`. A device joins
+//! the bus when its `set_client()` is called, which devices that only make
+//! chained transfers need to do as well.
 
 use core::cell::Cell;
 use kernel::common::cells::{OptionalCell, TakeCell};
@@ -22,8 +28,18 @@ impl<Spi: hil::spi::SpiMaster> hil::spi::SpiMasterClient for MuxSpiMaster<'a, Sp
         len: usize,
     ) {
         self.inflight.take().map(move |device| {
-            self.do_next_op();
-            device.read_write_done(write_buffer, read_buffer, len);
+            if device.chain.is_some() {
+                let index = device.segment.get();
+                device.chain.map(move |segments| {
+                    segments[index].write = Some(write_buffer);
+                    segments[index].read = read_buffer;
+                });
+                device.segment.set(index + 1);
+                self.next_segment(device);
+            } else {
+                self.do_next_op();
+                device.read_write_done(write_buffer, read_buffer, len);
+            }
         });
     }
 }
@@ -65,6 +81,10 @@ impl<Spi: hil::spi::SpiMaster> MuxSpiMaster<'a, Spi> {
                             self.spi.read_write_bytes(txbuffer, rxbuffer, len);
                         });
                     }
+                    Op::Chain => {
+                        node.segment.set(0);
+                        self.next_segment(node);
+                    }
                     Op::SetPolarity(pol) => {
                         self.spi.set_clock(pol);
                     }
@@ -79,6 +99,46 @@ impl<Spi: hil::spi::SpiMaster> MuxSpiMaster<'a, Spi> {
             });
         }
     }
+
+    /// Start the next segment of the chained transfer of `device`, or end
+    /// the transfer after its last segment.
+    fn next_segment(&self, device: &'a VirtualSpiMasterDevice<'a, Spi>) {
+        let index = device.segment.get();
+        let count = device.chain.map_or(0, |segments| segments.len());
+        if index >= count {
+            self.end_chain(device, ReturnCode::SUCCESS);
+            return;
+        }
+        let rcode = device.chain.map_or(ReturnCode::FAIL, |segments| {
+            let segment = &mut segments[index];
+            if segment.hold_chip_select && index + 1 < count {
+                self.spi.hold_low();
+            } else {
+                self.spi.release_low();
+            }
+            match segment.write.take() {
+                Some(write) => self
+                    .spi
+                    .read_write_bytes(write, segment.read.take(), segment.len),
+                None => ReturnCode::EINVAL,
+            }
+        });
+        if rcode == ReturnCode::SUCCESS {
+            self.inflight.set(device);
+        } else {
+            self.end_chain(device, rcode);
+        }
+    }
+
+    fn end_chain(&self, device: &'a VirtualSpiMasterDevice<'a, Spi>, result: ReturnCode) {
+        self.spi.release_low();
+        self.do_next_op();
+        device.chain.take().map(|segments| {
+            device
+                .chain_client
+                .map(move |client| client.transfer_chain_done(segments, result));
+        });
+    }
 }
 
 #[derive(Copy, Clone, PartialEq)]
@@ -86,6 +146,7 @@ enum Op {
     Idle,
     Configure(hil::spi::ClockPolarity, hil::spi::ClockPhase, u32),
     ReadWriteBytes(usize),
+    Chain,
     SetPolarity(hil::spi::ClockPolarity),
     SetPhase(hil::spi::ClockPhase),
     SetRate(u32),
@@ -97,8 +158,13 @@ pub struct VirtualSpiMasterDevice<'a, Spi: hil::spi::SpiMaster> {
     txbuffer: TakeCell<'static, [u8]>,
     rxbuffer: TakeCell<'static, [u8]>,
     operation: Cell<Op>,
+    /// The segments of the chained transfer, while it is pending
+    chain: TakeCell<'static, [hil::spi::Segment]>,
+    /// The segment being transferred
+    segment: Cell<usize>,
     next: ListLink<'a, VirtualSpiMasterDevice<'a, Spi>>,
     client: OptionalCell<&'a hil::spi::SpiMasterClient>,
+    chain_client: OptionalCell<&'a hil::spi::SpiMasterChainClient>,
 }
 
 impl<Spi: hil::spi::SpiMaster> VirtualSpiMasterDevice<'a, Spi> {
@@ -112,8 +178,11 @@ impl<Spi: hil::spi::SpiMaster> VirtualSpiMasterDevice<'a, Spi> {
             txbuffer: TakeCell::empty(),
             rxbuffer: TakeCell::empty(),
             operation: Cell::new(Op::Idle),
+            chain: TakeCell::empty(),
+            segment: Cell::new(0),
             next: ListLink::empty(),
             client: OptionalCell::empty(),
+            chain_client: OptionalCell::empty(),
         }
     }
 
@@ -191,6 +260,36 @@ impl<Spi: hil::spi::SpiMaster> hil::spi::SpiMasterDevice for VirtualSpiMasterDev
     }
 }
 
+impl<Spi: hil::spi::SpiMaster> hil::spi::SpiMasterDeviceChain for VirtualSpiMasterDevice<'a, Spi> {
+    fn set_chain_client(&self, client: &'static hil::spi::SpiMasterChainClient) {
+        self.chain_client.set(client);
+    }
+
+    fn transfer_chain(
+        &self,
+        segments: &'static mut [hil::spi::Segment],
+    ) -> (ReturnCode, Option<&'static mut [hil::spi::Segment]>) {
+        if self.operation.get() != Op::Idle || self.chain.is_some() {
+            return (ReturnCode::EBUSY, Some(segments));
+        }
+        if segments.is_empty() || segments.iter().any(|segment| segment.write.is_none()) {
+            return (ReturnCode::EINVAL, Some(segments));
+        }
+        let too_long = segments.iter().any(|segment| {
+            let write_len = segment.write.as_ref().map_or(0, |write| write.len());
+            let read_len = segment.read.as_ref().map_or(segment.len, |read| read.len());
+            segment.len > write_len || segment.len > read_len
+        });
+        if too_long {
+            return (ReturnCode::ESIZE, Some(segments));
+        }
+        self.chain.replace(segments);
+        self.operation.set(Op::Chain);
+        self.mux.do_next_op();
+        (ReturnCode::SUCCESS, None)
+    }
+}
+
 pub struct VirtualSpiSlaveDevice<'a, Spi: hil::spi::SpiSlave> {
     spi: &'a Spi,
     client: OptionalCell<&'a hil::spi::SpiSlaveClient>,
@@ -10,6 +10,19 @@
 //! `MuxUart` provides shared access to a single UART bus for multiple users.
 //! `UartDevice` provides access for a single client.
 //!
+//! If the UART can queue a buffer behind the one it transmits, with
+//! `transmit_continue`, the mux queues the next device's buffer as soon as one
+//! is waiting, so that the transmissions of the devices follow each other
+//! without a gap.
+//!
+//! A `UartDevice` can also receive with `receive_automatic`, to get the bytes
+//! received so far once the line is idle. If the board gave the mux the
+//! `hil::uart::ReceiveAdvanced` side of the UART with `set_receive_advanced`,
+//! the mux receives automatically whenever a device does, with the shortest
+//! timeout that any of them asked for. Otherwise the UART receives until the
+//! buffer is full, and an automatic device gets its bytes when the reads of
+//! the mux complete.
+//!
 //! Usage
 //! -----
 //!
@@ -21,6 +34,8 @@
 //! )
 //! hil::uart::UART::set_receive_client(&sam4l::usart::USART0, uart_mux);
 //! hil::uart::UART::set_transmit_client(&sam4l::usart::USART0, uart_mux);
+//! // Only if the UART implements `hil::uart::ReceiveAdvanced`
+//! uart_mux.set_receive_advanced(&sam4l::usart::USART0);
 //!
 //! // Create a UartDevice for the console.
 //! let console_uart = static_init!(UartDevice, UartDevice::new(uart_mux, true));
@@ -30,7 +45,6 @@
 //!     capsules::console::Console::new(
 //!         console_uart,
 //!         115200,
-//!         &mut capsules::console::WRITE_BUF,
 //!         &mut capsules::console::READ_BUF,
 //!         kernel::Grant::create()
 //!     )
@@ -52,9 +66,15 @@ pub static mut RX_BUF: [u8; RX_BUF_LEN] = [0; RX_BUF_LEN];
 
 pub struct MuxUart<'a> {
     uart: &'a uart::Uart<'a>,
+    /// The same UART, if it can receive until the line is idle
+    advanced: OptionalCell<&'a uart::ReceiveAdvanced<'a>>,
     speed: u32,
     devices: List<'a, UartDevice<'a>>,
     inflight: OptionalCell<&'a UartDevice<'a>>,
+    /// The device whose buffer the UART transmits after that of `inflight`
+    queued: OptionalCell<&'a UartDevice<'a>>,
+    /// Whether `inflight` transmits a buffer, which a queued buffer can follow
+    inflight_buffer: Cell<bool>,
     buffer: TakeCell<'static, [u8]>,
     completing_read: Cell<bool>,
 }
@@ -62,7 +82,14 @@ pub struct MuxUart<'a> {
 impl<'a> uart::TransmitClient for MuxUart<'a> {
     fn transmitted_buffer(&self, tx_buffer: &'static mut [u8], tx_len: usize, rcode: ReturnCode) {
         self.inflight.map(move |device| {
-            self.inflight.clear();
+            // The UART has started the queued buffer already
+            match self.queued.take() {
+                Some(queued) => {
+                    self.inflight.set(queued);
+                    self.inflight_buffer.set(true);
+                }
+                None => self.inflight.clear(),
+            }
             device.transmitted_buffer(tx_buffer, tx_len, rcode);
         });
         self.do_next_op();
@@ -120,7 +147,11 @@ impl<'a> uart::ReceiveClient for MuxUart<'a> {
                     // If this finishes the read, signal to the caller,
                     // otherwise update state so next read will fill in
                     // more data.
-                    if remaining == 0 {
+                    // An automatic read completes with any bytes received
+                    let timed_out = device.rx_timeout.get().is_some()
+                        && position > 0
+                        && state == UartDeviceReceiveState::Receiving;
+                    if remaining == 0 || timed_out {
                         device.state.set(UartDeviceReceiveState::Idle);
                         device.received_buffer(rxbuf, position, rcode, error);
                         // Need to check if receive was called in callback
@@ -158,14 +189,23 @@ impl<'a> MuxUart<'a> {
     pub fn new(uart: &'a uart::Uart<'a>, buffer: &'static mut [u8], speed: u32) -> MuxUart<'a> {
         MuxUart {
             uart: uart,
+            advanced: OptionalCell::empty(),
             speed: speed,
             devices: List::new(),
             inflight: OptionalCell::empty(),
+            queued: OptionalCell::empty(),
+            inflight_buffer: Cell::new(false),
             buffer: TakeCell::new(buffer),
             completing_read: Cell::new(false),
         }
     }
 
+    /// Receive with `uart` when a device receives automatically. `uart` must be
+    /// the UART the mux was created with.
+    pub fn set_receive_advanced(&self, uart: &'a uart::ReceiveAdvanced<'a>) {
+        self.advanced.set(uart);
+    }
+
     pub fn initialize(&self) {
         self.uart.configure(uart::Parameters {
             baud_rate: self.speed,
@@ -183,6 +223,7 @@ impl<'a> MuxUart<'a> {
                 node.tx_buffer.take().map(|buf| {
                     node.operation.map(move |op| match op {
                         Operation::Transmit { len } => {
+                            self.inflight_buffer.set(true);
                             let (rcode, rbuf) = self.uart.transmit_buffer(buf, *len);
                             if rcode != ReturnCode::SUCCESS {
                                 node.tx_client.map(|client| {
@@ -192,6 +233,7 @@ impl<'a> MuxUart<'a> {
                             }
                         }
                         Operation::TransmitWord { word } => {
+                            self.inflight_buffer.set(false);
                             let rcode = self.uart.transmit_word(*word);
                             if rcode != ReturnCode::SUCCESS {
                                 node.tx_client.map(|client| {
@@ -206,6 +248,48 @@ impl<'a> MuxUart<'a> {
                 self.inflight.set(node);
             });
         }
+        self.queue_next_op();
+    }
+
+    /// Queue the next buffer to transmit behind the one in flight, if the
+    /// UART can.
+    fn queue_next_op(&self) {
+        if self.inflight.is_none() || !self.inflight_buffer.get() || self.queued.is_some() {
+            return;
+        }
+        let mnode = self.devices.iter().find(|node| {
+            node.operation.map_or(false, |op| match op {
+                Operation::Transmit { .. } => true,
+                Operation::TransmitWord { .. } => false,
+            })
+        });
+        mnode.map(|node| {
+            let len = node.operation.map_or(0, |op| match op {
+                Operation::Transmit { len } => *len,
+                Operation::TransmitWord { .. } => 0,
+            });
+            node.tx_buffer.take().map(|buf| {
+                match self.uart.transmit_continue(buf, len) {
+                    (ReturnCode::SUCCESS, _) => {
+                        node.operation.clear();
+                        self.queued.set(node);
+                    }
+                    // Transmit it once the buffer in flight is done instead
+                    (ReturnCode::ENOSUPPORT, Some(buf)) | (ReturnCode::EBUSY, Some(buf)) => {
+                        node.tx_buffer.replace(buf);
+                    }
+                    (rcode, rbuf) => {
+                        node.operation.clear();
+                        rbuf.map(|buf| {
+                            node.tx_client.map(move |client| {
+                                node.transmitting.set(false);
+                                client.transmitted_buffer(buf, 0, rcode);
+                            });
+                        });
+                    }
+                }
+            });
+        });
     }
 
     /// Starts a new UART reception, return value denotes whether starting
@@ -232,11 +316,29 @@ impl<'a> MuxUart<'a> {
             },
             |rxbuf| {
                 let len = cmp::min(rx_len, rxbuf.len());
-                self.uart.receive_buffer(rxbuf, len);
+                match self.receive_timeout() {
+                    Some(timeout) if self.advanced.is_some() => {
+                        self.advanced
+                            .map(move |advanced| advanced.receive_automatic(rxbuf, len, timeout));
+                    }
+                    _ => {
+                        self.uart.receive_buffer(rxbuf, len);
+                    }
+                }
                 false
             },
         )
     }
+
+    /// The shortest timeout of the devices that receive automatically, if
+    /// any do.
+    fn receive_timeout(&self) -> Option<u8> {
+        self.devices
+            .iter()
+            .filter(|device| device.receiver && device.rx_buffer.is_some())
+            .filter_map(|device| device.rx_timeout.get())
+            .min()
+    }
 }
 
 #[derive(Copy, Clone, PartialEq)]
@@ -261,6 +363,8 @@ pub struct UartDevice<'a> {
     rx_buffer: TakeCell<'static, [u8]>,
     rx_position: Cell<usize>,
     rx_len: Cell<usize>,
+    /// The interbyte timeout, if the device receives automatically
+    rx_timeout: Cell<Option<u8>>,
     operation: OptionalCell<Operation>,
     next: ListLink<'a, UartDevice<'a>>,
     rx_client: OptionalCell<&'a uart::ReceiveClient>,
@@ -268,6 +372,7 @@ pub struct UartDevice<'a> {
 }
 
 impl uart::UartData<'a> for UartDevice<'a> {}
+impl uart::UartDataAdvanced<'a> for UartDevice<'a> {}
 
 impl<'a> UartDevice<'a> {
     pub const fn new(mux: &'a MuxUart<'a>, receiver: bool) -> UartDevice<'a> {
@@ -280,6 +385,7 @@ impl<'a> UartDevice<'a> {
             rx_buffer: TakeCell::empty(),
             rx_position: Cell::new(0),
             rx_len: Cell::new(0),
+            rx_timeout: Cell::new(None),
             operation: OptionalCell::empty(),
             next: ListLink::empty(),
             rx_client: OptionalCell::empty(),
@@ -291,6 +397,26 @@ impl<'a> UartDevice<'a> {
     pub fn setup(&'a self) {
         self.mux.devices.push_head(self);
     }
+
+    fn receive(
+        &self,
+        rx_buffer: &'static mut [u8],
+        rx_len: usize,
+        timeout: Option<u8>,
+    ) -> (ReturnCode, Option<&'static mut [u8]>) {
+        if self.rx_buffer.is_some() {
+            (ReturnCode::EBUSY, Some(rx_buffer))
+        } else {
+            self.rx_buffer.replace(rx_buffer);
+            self.rx_len.set(rx_len);
+            self.rx_position.set(0);
+            self.rx_timeout.set(timeout);
+            self.state.set(UartDeviceReceiveState::Idle);
+            self.mux.start_receive(rx_len);
+            self.state.set(UartDeviceReceiveState::Receiving);
+            (ReturnCode::SUCCESS, None)
+        }
+    }
 }
 
 impl<'a> uart::TransmitClient for UartDevice<'a> {
@@ -378,17 +504,7 @@ impl<'a> uart::Receive<'a> for UartDevice<'a> {
         rx_buffer: &'static mut [u8],
         rx_len: usize,
     ) -> (ReturnCode, Option<&'static mut [u8]>) {
-        if self.rx_buffer.is_some() {
-            (ReturnCode::EBUSY, Some(rx_buffer))
-        } else {
-            self.rx_buffer.replace(rx_buffer);
-            self.rx_len.set(rx_len);
-            self.rx_position.set(0);
-            self.state.set(UartDeviceReceiveState::Idle);
-            self.mux.start_receive(rx_len);
-            self.state.set(UartDeviceReceiveState::Receiving);
-            (ReturnCode::SUCCESS, None)
-        }
+        self.receive(rx_buffer, rx_len, None)
     }
 
     // This virtualized device will abort its read: other devices
@@ -403,3 +519,16 @@ impl<'a> uart::Receive<'a> for UartDevice<'a> {
         ReturnCode::FAIL
     }
 }
+
+impl<'a> uart::ReceiveAdvanced<'a> for UartDevice<'a> {
+    /// Receive data until the buffer is full, or the line has been idle for
+    /// `interbyte_timeout` bit periods after at least one byte.
+    fn receive_automatic(
+        &self,
+        rx_buffer: &'static mut [u8],
+        rx_len: usize,
+        interbyte_timeout: u8,
+    ) -> (ReturnCode, Option<&'static mut [u8]>) {
+        self.receive(rx_buffer, rx_len, Some(interbyte_timeout))
+    }
+}
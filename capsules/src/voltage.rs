@@ -0,0 +1,142 @@
+//! Provides userspace with access to voltage sensors, such as a monitor of
+//! the supply voltage, to report how charged the battery is.
+//!
+//! Userspace Interface
+//! -------------------
+//!
+//! ### `subscribe` System Call
+//!
+//! The `subscribe` system call supports the single `subscribe_number` zero,
+//! which is used to provide a callback that will return back the result of
+//! a voltage reading, in millivolts.
+//!
+//! ### `command` System Call
+//!
+//! The `command` system call support one argument `cmd` which is used to specify the specific
+//! operation, currently the following cmd's are supported:
+//!
+//! * `0`: check whether the driver exist
+//! * `1`: read the voltage
+//!
+//! The possible return from the 'command' system call indicates the following:
+//!
+//! * `SUCCESS`:    The operation has been successful.
+//! * `EBUSY`:      The driver is busy.
+//! * `ENOSUPPORT`: Invalid `cmd`.
+//! * `ENOMEM`:     No sufficient memory available.
+//!
+//! Usage
+//! -----
+//!
+//! You need a device that provides the `hil::sensors::VoltageDriver` trait.
+//!
+//! ```rust
+//! let voltage = static_init!(
+//!     capsules::voltage::VoltageSensor<'static>,
+//!     capsules::voltage::VoltageSensor::new(
+//!         &cc26x2::aon_batmon::BATMON,
+//!         kernel::Grant::create()
+//!     )
+//! );
+//! kernel::hil::sensors::VoltageDriver::set_client(&cc26x2::aon_batmon::BATMON, voltage);
+//! ```
+
+use core::cell::Cell;
+use kernel::hil;
+use kernel::ReturnCode;
+use kernel::{AppId, Callback, Driver, Grant};
+
+/// Syscall driver number.
+use crate::driver;
+pub const DRIVER_NUM: usize = driver::NUM::Voltage as usize;
+
+#[derive(Default)]
+pub struct App {
+    callback: Option<Callback>,
+    subscribed: bool,
+}
+
+pub struct VoltageSensor<'a> {
+    driver: &'a hil::sensors::VoltageDriver,
+    apps: Grant<App>,
+    busy: Cell<bool>,
+}
+
+impl VoltageSensor<'a> {
+    pub fn new(driver: &'a hil::sensors::VoltageDriver, grant: Grant<App>) -> VoltageSensor<'a> {
+        VoltageSensor {
+            driver: driver,
+            apps: grant,
+            busy: Cell::new(false),
+        }
+    }
+
+    /// Read the voltage for `appid`. If a read is in progress, the app is
+    /// given its result too.
+    fn enqueue_command(&self, appid: AppId) -> ReturnCode {
+        self.apps
+            .enter(appid, |app, _| {
+                app.subscribed = true;
+                if self.busy.get() {
+                    return ReturnCode::SUCCESS;
+                }
+                let rcode = self.driver.read_voltage();
+                if rcode == ReturnCode::SUCCESS {
+                    self.busy.set(true);
+                } else {
+                    app.subscribed = false;
+                }
+                rcode
+            })
+            .unwrap_or_else(|err| err.into())
+    }
+
+    fn configure_callback(&self, callback: Option<Callback>, app_id: AppId) -> ReturnCode {
+        self.apps
+            .enter(app_id, |app, _| {
+                app.callback = callback;
+                ReturnCode::SUCCESS
+            })
+            .unwrap_or_else(|err| err.into())
+    }
+}
+
+impl hil::sensors::VoltageClient for VoltageSensor<'a> {
+    fn callback(&self, millivolts: usize) {
+        self.busy.set(false);
+        for cntr in self.apps.iter() {
+            cntr.enter(|app, _| {
+                if app.subscribed {
+                    app.subscribed = false;
+                    app.callback.map(|mut cb| cb.schedule(millivolts, 0, 0));
+                }
+            });
+        }
+    }
+}
+
+impl Driver for VoltageSensor<'a> {
+    fn subscribe(
+        &self,
+        subscribe_num: usize,
+        callback: Option<Callback>,
+        app_id: AppId,
+    ) -> ReturnCode {
+        match subscribe_num {
+            // subscribe to voltage readings with callback
+            0 => self.configure_callback(callback, app_id),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    fn command(&self, command_num: usize, _: usize, _: usize, appid: AppId) -> ReturnCode {
+        match command_num {
+            // check whether the driver exists
+            0 => ReturnCode::SUCCESS,
+
+            // read the voltage
+            1 => self.enqueue_command(appid),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+}
@@ -0,0 +1,364 @@
+//! ADC - Analog to digital converter for the cc26x2 family
+//!
+//! The ADC lives in the sensor controller (AUX) domain, which `aux` powers
+//! and clocks while a sample is taken. This driver takes
+//! single samples, triggered by software, of one of the analog capable pins
+//! (DIO23 to DIO30). Samples are 12 bits and scaled to the internal fixed
+//! reference, so that the full range is 4.3 V.
+//!
+//! The gain and offset of each chip are measured in the factory, and stored
+//! in FCFG1. Once the ADC is calibrated with `hil::adc::Calibration`, it
+//! corrects the samples with them, so that the full range is 4.3 V exactly
+//! rather than roughly.
+//!
+//! Usage
+//! -----
+//!
+//! ```rust
+//! cc26x2::gpio::PORT[30].enable_analog_input().unwrap();
+//! cc26x2::adc::ADC.set_client(client);
+//! cc26x2::adc::ADC.sample(&cc26x2::adc::Channel::Dio30);
+//! ```
+
+use kernel::common::cells::OptionalCell;
+use kernel::common::deferred_call::DeferredCall;
+use kernel::common::registers::{register_bitfields, ReadOnly, ReadWrite, WriteOnly};
+use kernel::common::StaticRef;
+use kernel::hil;
+use kernel::ReturnCode;
+
+use crate::aux;
+use crate::deferred_call_tasks::DeferredCallTask;
+use crate::memory_map::FCFG1_BASE;
+
+#[repr(C)]
+struct AuxAnaIfRegisters {
+    _reserved0: [u32; 4],
+    adc_ctl: ReadWrite<u32, AdcCtl::Register>,
+    adc_fifo_stat: ReadOnly<u32, AdcFifoStat::Register>,
+    adc_fifo: ReadOnly<u32>,
+    adc_trig: WriteOnly<u32>,
+}
+
+#[repr(C)]
+struct AuxEvCtlRegisters {
+    _reserved0: [u32; 14],
+    ev_to_mcu_flags_clr: WriteOnly<u32, EvToMcuFlags::Register>,
+}
+
+/// The analog configuration of the AUX domain, through the analog digital
+/// interface (ADI). Its registers are 8 bits wide.
+#[repr(C)]
+struct Adi4AuxRegisters {
+    _mux0: ReadWrite<u8>,
+    _mux1: ReadWrite<u8>,
+    mux2: ReadWrite<u8>,
+    mux3: ReadWrite<u8>,
+    _reserved0: [u8; 3],
+    mux4: ReadWrite<u8>,
+    adc0: ReadWrite<u8, Adc0::Register>,
+    _adc1: ReadWrite<u8>,
+    adc_ref0: ReadWrite<u8, AdcRef0::Register>,
+    _adc_ref1: ReadWrite<u8>,
+}
+
+/// The factory trims of the ADC, for the fixed reference, in FCFG1
+#[repr(C)]
+struct AdcTrimRegisters {
+    abs_gain: ReadOnly<u32, AbsGain::Register>,
+    _rel_gain: ReadOnly<u32>,
+    _reserved0: ReadOnly<u32>,
+    offset_int: ReadOnly<u32, OffsetInt::Register>,
+}
+
+register_bitfields![
+    u32,
+    AbsGain [
+        GAIN OFFSET(0) NUMBITS(16) []
+    ],
+    OffsetInt [
+        // A signed offset
+        ABS_OFFSET OFFSET(0) NUMBITS(8) []
+    ],
+    AdcCtl [
+        START_SRC OFFSET(8) NUMBITS(6) [
+            NoEvent = 0x3F
+        ],
+        CMD OFFSET(0) NUMBITS(2) [
+            Disable = 0,
+            Enable = 1,
+            Flush = 3
+        ]
+    ],
+    AdcFifoStat [
+        EMPTY OFFSET(0) NUMBITS(1) []
+    ],
+    EvToMcuFlags [
+        ADC_IRQ OFFSET(9) NUMBITS(1) []
+    ]
+];
+
+register_bitfields![
+    u8,
+    Adc0 [
+        // The sample time is 2^(SMPL_CYCLE_EXP + 1) cycles of the 6 MHz clock
+        SMPL_CYCLE_EXP OFFSET(3) NUMBITS(4) [],
+        RESET_N OFFSET(1) NUMBITS(1) [],
+        EN OFFSET(0) NUMBITS(1) []
+    ],
+    AdcRef0 [
+        // Reference source, 0 for the fixed 4.3 V reference and 1 for VDDS
+        SRC OFFSET(3) NUMBITS(1) [],
+        EN OFFSET(0) NUMBITS(1) []
+    ]
+];
+
+const AUX_ANAIF_BASE: StaticRef<AuxAnaIfRegisters> =
+    unsafe { StaticRef::new(0x400C_9000 as *const AuxAnaIfRegisters) };
+const AUX_EVCTL_BASE: StaticRef<AuxEvCtlRegisters> =
+    unsafe { StaticRef::new(0x400C_5000 as *const AuxEvCtlRegisters) };
+const ADI4_AUX_BASE: StaticRef<Adi4AuxRegisters> =
+    unsafe { StaticRef::new(0x400C_B000 as *const Adi4AuxRegisters) };
+const ADC_TRIM_BASE: StaticRef<AdcTrimRegisters> =
+    unsafe { StaticRef::new((FCFG1_BASE + 0x3BC) as *const AdcTrimRegisters) };
+
+/// Tells the calibration client that the calibration is done
+static DEFERRED_CALL: DeferredCall<DeferredCallTask> =
+    unsafe { DeferredCall::new(DeferredCallTask::Adc) };
+
+/// The largest sample, of 12 bits
+const MAX_SAMPLE: u32 = 0xFFF;
+
+/// Sample time of 2^8 cycles, about 43 us
+const SAMPLE_CYCLE_EXP: u8 = 7;
+
+/// The analog capable pins, which are connected to the ADC through the
+/// input multiplexer of the AUX domain.
+#[derive(Copy, Clone, Debug)]
+pub enum Channel {
+    Dio23 = 23,
+    Dio24 = 24,
+    Dio25 = 25,
+    Dio26 = 26,
+    Dio27 = 27,
+    Dio28 = 28,
+    Dio29 = 29,
+    Dio30 = 30,
+}
+
+impl Channel {
+    /// The bit of the `MUX3` register, and of the `MUX1` register of
+    /// comparator A, that selects the pin. DIO23 to DIO30 are AUXIO26 down
+    /// to AUXIO19, which are bits 0 to 7.
+    pub(crate) fn mux_bit(&self) -> u8 {
+        1 << (*self as u8 - Channel::Dio23 as u8)
+    }
+}
+
+pub static mut ADC: Adc = Adc::new();
+
+pub struct Adc {
+    anaif: StaticRef<AuxAnaIfRegisters>,
+    evctl: StaticRef<AuxEvCtlRegisters>,
+    adi: StaticRef<Adi4AuxRegisters>,
+    trim: StaticRef<AdcTrimRegisters>,
+    client: OptionalCell<&'static hil::adc::Client>,
+    calibration_client: OptionalCell<&'static hil::adc::CalibrationClient>,
+    calibration: OptionalCell<hil::adc::CalibrationValues>,
+    busy: OptionalCell<Channel>,
+}
+
+impl Adc {
+    const fn new() -> Adc {
+        Adc {
+            anaif: AUX_ANAIF_BASE,
+            evctl: AUX_EVCTL_BASE,
+            adi: ADI4_AUX_BASE,
+            trim: ADC_TRIM_BASE,
+            client: OptionalCell::empty(),
+            calibration_client: OptionalCell::empty(),
+            calibration: OptionalCell::empty(),
+            busy: OptionalCell::empty(),
+        }
+    }
+
+    pub fn set_client(&self, client: &'static hil::adc::Client) {
+        self.client.set(client);
+    }
+
+    fn enable(&self, channel: Channel) {
+        let anaif = &*self.anaif;
+        let adi = &*self.adi;
+
+        unsafe {
+            aux::AUX.request();
+            aux::AUX.enable_adc_clock();
+        }
+
+        adi.adc_ref0.write(AdcRef0::EN::SET + AdcRef0::SRC::CLEAR);
+        adi.adc0
+            .write(Adc0::SMPL_CYCLE_EXP.val(SAMPLE_CYCLE_EXP) + Adc0::EN::SET);
+        adi.adc0.modify(Adc0::RESET_N::SET);
+
+        // Connect only the pin to the ADC. MUX0 and MUX1 select the inputs
+        // of comparator A, which may be in use.
+        adi.mux2.set(0);
+        adi.mux4.set(0);
+        adi.mux3.set(channel.mux_bit());
+
+        // Samples are started by writing the trigger register
+        anaif.adc_ctl.write(AdcCtl::CMD::Flush);
+        anaif
+            .adc_ctl
+            .write(AdcCtl::START_SRC::NoEvent + AdcCtl::CMD::Enable);
+    }
+
+    fn disable(&self) {
+        let anaif = &*self.anaif;
+        let adi = &*self.adi;
+
+        anaif.adc_ctl.write(AdcCtl::CMD::Disable);
+        adi.adc0.set(0);
+        adi.adc_ref0.set(0);
+        adi.mux3.set(0);
+        unsafe {
+            aux::AUX.disable_adc_clock();
+            aux::AUX.release();
+        }
+    }
+
+    pub fn handle_interrupt(&self) {
+        let anaif = &*self.anaif;
+        self.evctl
+            .ev_to_mcu_flags_clr
+            .write(EvToMcuFlags::ADC_IRQ::SET);
+
+        if anaif.adc_fifo_stat.is_set(AdcFifoStat::EMPTY) {
+            return;
+        }
+        let sample = self.correct(anaif.adc_fifo.get() & MAX_SAMPLE);
+        self.disable();
+
+        if self.busy.take().is_some() {
+            // Left justify to meet HIL requirements.
+            let value = (sample as u16) << 4;
+            self.client.map(|client| client.sample_ready(value));
+        }
+    }
+}
+
+impl Adc {
+    /// Correct a raw sample with the calibration, if the ADC is calibrated.
+    fn correct(&self, sample: u32) -> u32 {
+        self.calibration.map_or(sample, |calibration| {
+            let offset_sample = (sample as i32 + calibration.offset as i32).max(0) as u32;
+            // Round to the nearest step
+            let corrected = (offset_sample * calibration.gain as u32 + (1 << 14)) >> 15;
+            corrected.min(MAX_SAMPLE)
+        })
+    }
+
+    pub fn handle_deferred_call(&self) {
+        self.calibration_client
+            .map(|client| client.calibration_done(ReturnCode::SUCCESS));
+    }
+}
+
+impl hil::adc::Adc for Adc {
+    type Channel = Channel;
+
+    fn sample(&self, channel: &Self::Channel) -> ReturnCode {
+        if self.busy.is_some() {
+            return ReturnCode::EBUSY;
+        }
+        self.busy.set(*channel);
+        self.enable(*channel);
+
+        self.evctl
+            .ev_to_mcu_flags_clr
+            .write(EvToMcuFlags::ADC_IRQ::SET);
+        self.anaif.adc_trig.set(1);
+        ReturnCode::SUCCESS
+    }
+
+    fn sample_continuous(&self, _channel: &Self::Channel, _frequency: u32) -> ReturnCode {
+        ReturnCode::ENOSUPPORT
+    }
+
+    fn stop_sampling(&self) -> ReturnCode {
+        if self.busy.take().is_some() {
+            self.disable();
+        }
+        ReturnCode::SUCCESS
+    }
+
+    fn get_resolution_bits(&self) -> usize {
+        12
+    }
+
+    fn get_voltage_reference_mv(&self) -> Option<usize> {
+        Some(4300)
+    }
+}
+
+impl hil::adc::Calibration for Adc {
+    fn set_calibration_client(&self, client: &'static hil::adc::CalibrationClient) {
+        self.calibration_client.set(client);
+    }
+
+    /// Load the factory trims of the ADC. They are ready at once, but the
+    /// client is told later, as if the ADC had taken time to measure them.
+    fn calibrate(&self) -> ReturnCode {
+        if self.busy.is_some() {
+            return ReturnCode::EBUSY;
+        }
+        self.calibration.set(hil::adc::CalibrationValues {
+            gain: self.trim.abs_gain.read(AbsGain::GAIN) as u16,
+            offset: self.trim.offset_int.read(OffsetInt::ABS_OFFSET) as u8 as i8 as i16,
+        });
+        DEFERRED_CALL.set();
+        ReturnCode::SUCCESS
+    }
+
+    fn get_calibration(&self) -> Option<hil::adc::CalibrationValues> {
+        self.calibration.map(|values| *values)
+    }
+}
+
+/// Sampling into buffers is not supported, but is required by the ADC
+/// syscall driver.
+impl hil::adc::AdcHighSpeed for Adc {
+    fn sample_highspeed(
+        &self,
+        _channel: &Self::Channel,
+        _frequency: u32,
+        buffer1: &'static mut [u16],
+        _length1: usize,
+        buffer2: &'static mut [u16],
+        _length2: usize,
+    ) -> (
+        ReturnCode,
+        Option<&'static mut [u16]>,
+        Option<&'static mut [u16]>,
+    ) {
+        (ReturnCode::ENOSUPPORT, Some(buffer1), Some(buffer2))
+    }
+
+    fn provide_buffer(
+        &self,
+        buf: &'static mut [u16],
+        _length: usize,
+    ) -> (ReturnCode, Option<&'static mut [u16]>) {
+        (ReturnCode::ENOSUPPORT, Some(buf))
+    }
+
+    fn retrieve_buffers(
+        &self,
+    ) -> (
+        ReturnCode,
+        Option<&'static mut [u16]>,
+        Option<&'static mut [u16]>,
+    ) {
+        (ReturnCode::SUCCESS, None, None)
+    }
+}
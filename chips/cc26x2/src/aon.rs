@@ -2,8 +2,14 @@
 //!
 //! AON is a set of peripherals which is _always on_ (eg. the RTC, MCU, etc).
 //!
-//! The current configuration disables all wake-up selectors, since the
-//! MCU never go to sleep and is always active.
+//! The MCU wakes up from standby on the RTC channel 1 event, which alarms
+//! use, and on GPIO edges. While the MCU is in standby the IOs are frozen,
+//! so that the pins keep their state while the GPIO module is powered off.
+//!
+//! In shutdown only the IO pads stay powered, and they latch their state.
+//! The chip wakes up from it through a reset, after which the pads stay
+//! latched until `release_io_latches()`, so that a board can configure its
+//! pins before they follow the GPIO module again.
 use crate::rtc;
 use kernel::common::registers::{register_bitfields, ReadOnly, ReadWrite};
 use kernel::common::StaticRef;
@@ -11,6 +17,7 @@ use kernel::common::StaticRef;
 #[repr(C)]
 pub struct AonIocRegisters {
     _reserved0: [u32; 3],
+    ioc_latch: ReadWrite<u32, IocLatch::Register>,
     ioc_clk32k_ctl: ReadWrite<u32, IocClk::Register>,
 }
 
@@ -24,12 +31,18 @@ pub struct AonEventRegisters {
 
 #[repr(C)]
 struct AonPmCtlRegisters {
-    aux_clk: ReadWrite<u32, AuxClk::Register>,
+    _reserved0: u32,
+    _aux_clk: ReadWrite<u32, AuxClk::Register>,
     ram_cfg: ReadWrite<u32, RamCfg::Register>,
+    _reserved1: u32,
     pwr_ctl: ReadWrite<u32, PwrCtl::Register>,
-    pwr_stat: ReadOnly<u32, PwrStat::Register>,
+    _pwr_stat: ReadOnly<u32, PwrStat::Register>,
     shutdown: ReadWrite<u32, Shutdown::Register>,
-    _recharge: [u32; 4],
+    _recharge_cfg: ReadWrite<u32>,
+    _recharge_stat: ReadOnly<u32>,
+    _osc_cfg: ReadWrite<u32>,
+    reset_ctl: ReadWrite<u32, ResetCtl::Register>,
+    sleep_ctl: ReadWrite<u32, SleepCtl::Register>,
 }
 
 register_bitfields![
@@ -73,12 +86,27 @@ register_bitfields![
         AUX_RESET_DONE OFFSET(0) NUMBITS(1) []
     ],
     Shutdown [
-        // Controls whether MCU & AUX requesting to be powered off
-        // will enable a transition to powerdown (0 = Enabled, 1 = Disabled)
-        PWR_DWN_DIS     OFFSET(0) NUMBITS(1) []
+        // 1 = enter shutdown
+        EN     OFFSET(0) NUMBITS(1) []
     ],
-    IocClk [
+    ResetCtl [
+        // The last reset was a wake up from shutdown
+        WU_FROM_SD      OFFSET(14) NUMBITS(1) [],
+        // ... caused by an IO, rather than by the debugger
+        GPIO_WU_FROM_SD OFFSET(13) NUMBITS(1) []
+    ],
+    SleepCtl [
+        // 0 = the IO pads latch their state in shutdown, and until this is
+        // set after the wake up; 1 = they follow the MCU
+        IO_PAD_SLEEP_DIS OFFSET(0) NUMBITS(1) []
+    ],
+    IocLatch [
+        // 0 = the IOs keep their state, 1 = they follow the MCU
         EN  OFFSET(0) NUMBITS(1) []
+    ],
+    IocClk [
+        // 0 = the 32 kHz clock is output on the IOs configured for it
+        OE_N  OFFSET(0) NUMBITS(1) []
     ]
 
 ];
@@ -109,8 +137,8 @@ impl Aon {
         // Default to no events at all
         regs.aux_wu_sel.set(0x3F3F3F3F);
 
-        // Set RTC CH1 as a wakeup source by default
-        regs.mcu_wu_sel.set(0x3F3F3F24);
+        // Wake up on RTC CH1 and on edges of the IOs
+        regs.mcu_wu_sel.set(0x3F3F2024);
 
         // Disable RTC combined event
         regs.rtc_sel.set(0x0000003F);
@@ -138,9 +166,20 @@ impl Aon {
     pub fn lfclk_enable(&self, enable: bool) {
         let regs = AON_IOC_BASE;
         if enable {
-            regs.ioc_clk32k_ctl.write(IocClk::EN::SET);
+            regs.ioc_clk32k_ctl.write(IocClk::OE_N::CLEAR);
         } else {
-            regs.ioc_clk32k_ctl.write(IocClk::EN::CLEAR);
+            regs.ioc_clk32k_ctl.write(IocClk::OE_N::SET);
+        }
+    }
+
+    /// Freezes the IOs in their current state, or lets them follow the MCU
+    /// again.
+    pub fn set_io_freeze(&self, frozen: bool) {
+        let regs = AON_IOC_BASE;
+        if frozen {
+            regs.ioc_latch.write(IocLatch::EN::CLEAR);
+        } else {
+            regs.ioc_latch.write(IocLatch::EN::SET);
         }
     }
 
@@ -166,9 +205,27 @@ impl Aon {
         });
     }
 
+    /// Latches the IO pads and requests shutdown, which the chip enters once
+    /// the request reaches the AON domain.
     pub fn shutdown(&self) {
         let regs = AON_PMCTL_BASE;
-        regs.shutdown.modify(Shutdown::PWR_DWN_DIS::SET);
+        regs.sleep_ctl.write(SleepCtl::IO_PAD_SLEEP_DIS::CLEAR);
+        regs.shutdown.write(Shutdown::EN::SET);
+    }
+
+    /// Returns whether the chip was reset by an IO waking it up from
+    /// shutdown. The GPIO event of the pin that did is set.
+    pub fn woke_from_shutdown(&self) -> bool {
+        let regs = AON_PMCTL_BASE;
+        regs.reset_ctl.is_set(ResetCtl::WU_FROM_SD)
+            && regs.reset_ctl.is_set(ResetCtl::GPIO_WU_FROM_SD)
+    }
+
+    /// Lets the IO pads follow the GPIO module again after a wake up from
+    /// shutdown. Does nothing otherwise.
+    pub fn release_io_latches(&self) {
+        let regs = AON_PMCTL_BASE;
+        regs.sleep_ctl.write(SleepCtl::IO_PAD_SLEEP_DIS::SET);
     }
     /// Await a cycle of the AON domain in order
     /// to sync with it.
@@ -0,0 +1,206 @@
+//! Battery and temperature monitor (BATMON)
+//!
+//! The BATMON is part of the always on domain, and measures the supply
+//! voltage (VDDS) and the temperature of the die. Once `enable()`d it
+//! measures both continuously, and flags each new measurement.
+//!
+//! A read completes, from a deferred call, with the first measurement taken
+//! after it was started, so a client never gets a value older than its
+//! request. The temperature is reported in hundredths of degrees Celsius,
+//! with a resolution of a degree, and the voltage in millivolts, with a
+//! resolution of 1/256 V.
+//!
+//! The same monitor is both a `TemperatureDriver` and a `VoltageDriver`:
+//!
+//! ```rust
+//! cc26x2::aon_batmon::BATMON.enable();
+//! let temp = static_init!(
+//!     capsules::temperature::TemperatureSensor<'static>,
+//!     capsules::temperature::TemperatureSensor::new(
+//!         &cc26x2::aon_batmon::BATMON,
+//!         kernel::Grant::create()
+//!     )
+//! );
+//! hil::sensors::TemperatureDriver::set_client(&cc26x2::aon_batmon::BATMON, temp);
+//! ```
+
+use crate::deferred_call_tasks::DeferredCallTask;
+use crate::memory_map::AON_BATMON_BASE;
+use core::cell::Cell;
+use kernel::common::cells::OptionalCell;
+use kernel::common::deferred_call::DeferredCall;
+use kernel::common::registers::{register_bitfields, ReadOnly, ReadWrite};
+use kernel::common::StaticRef;
+use kernel::hil::sensors;
+use kernel::ReturnCode;
+
+#[repr(C)]
+struct BatmonRegisters {
+    ctl: ReadWrite<u32, Control::Register>,
+    meascfg: ReadWrite<u32, MeasureConfig::Register>,
+    _reserved0: u32,
+    // Trims, which the boot code loads from the factory configuration
+    _tempp0: ReadWrite<u32>,
+    _tempp1: ReadWrite<u32>,
+    _tempp2: ReadWrite<u32>,
+    _batmonp0: ReadWrite<u32>,
+    _batmonp1: ReadWrite<u32>,
+    _iostrp0: ReadWrite<u32>,
+    _flashpumpp0: ReadWrite<u32>,
+    bat: ReadOnly<u32, Battery::Register>,
+    batupd: ReadWrite<u32, Update::Register>,
+    temp: ReadOnly<u32, Temperature::Register>,
+    tempupd: ReadWrite<u32, Update::Register>,
+}
+
+register_bitfields![
+    u32,
+    Control [
+        CALC_EN OFFSET(1) NUMBITS(1) [],
+        MEAS_EN OFFSET(0) NUMBITS(1) []
+    ],
+    MeasureConfig [
+        // How many SCLK_LF periods apart measurements are taken
+        PER OFFSET(0) NUMBITS(2) [
+            Continuous = 0x0,
+            Cycles8 = 0x1,
+            Cycles16 = 0x2,
+            Cycles32 = 0x3
+        ]
+    ],
+    Battery [
+        // Volts
+        INT OFFSET(8) NUMBITS(3) [],
+        // 1/256 V
+        FRAC OFFSET(0) NUMBITS(8) []
+    ],
+    Update [
+        // Set by a new measurement, cleared by writing 1
+        STAT OFFSET(0) NUMBITS(1) []
+    ],
+    Temperature [
+        // Degrees Celsius, as a two's complement number
+        INT OFFSET(8) NUMBITS(9) []
+    ]
+];
+
+const BATMON_BASE: StaticRef<BatmonRegisters> =
+    unsafe { StaticRef::new(AON_BATMON_BASE as *const BatmonRegisters) };
+
+static DEFERRED_CALL: DeferredCall<DeferredCallTask> =
+    unsafe { DeferredCall::new(DeferredCallTask::Batmon) };
+
+pub static mut BATMON: Batmon = Batmon::new();
+
+pub struct Batmon {
+    registers: StaticRef<BatmonRegisters>,
+    temperature_client: OptionalCell<&'static sensors::TemperatureClient>,
+    voltage_client: OptionalCell<&'static sensors::VoltageClient>,
+    reading_temperature: Cell<bool>,
+    reading_voltage: Cell<bool>,
+}
+
+impl Batmon {
+    const fn new() -> Batmon {
+        Batmon {
+            registers: BATMON_BASE,
+            temperature_client: OptionalCell::empty(),
+            voltage_client: OptionalCell::empty(),
+            reading_temperature: Cell::new(false),
+            reading_voltage: Cell::new(false),
+        }
+    }
+
+    /// Start measuring continuously.
+    pub fn enable(&self) {
+        self.registers.meascfg.write(MeasureConfig::PER::Continuous);
+        self.registers
+            .ctl
+            .write(Control::MEAS_EN::SET + Control::CALC_EN::SET);
+    }
+
+    /// Stop measuring, e.g. to save power before a long sleep.
+    pub fn disable(&self) {
+        self.registers
+            .ctl
+            .write(Control::MEAS_EN::CLEAR + Control::CALC_EN::CLEAR);
+    }
+
+    fn is_enabled(&self) -> bool {
+        self.registers.ctl.is_set(Control::MEAS_EN)
+    }
+
+    /// The last temperature measured, in degrees Celsius.
+    pub fn temperature(&self) -> i32 {
+        let raw = self.registers.temp.read(Temperature::INT);
+        // Sign extend the 9 bit value
+        ((raw << 23) as i32) >> 23
+    }
+
+    /// The last supply voltage measured, in millivolts.
+    pub fn voltage(&self) -> u32 {
+        let bat = self.registers.bat.extract();
+        bat.read(Battery::INT) * 1000 + bat.read(Battery::FRAC) * 1000 / 256
+    }
+
+    /// Start a read. The measurement flag is cleared, so that the read
+    /// completes with the next measurement.
+    fn start_read(
+        &self,
+        reading: &Cell<bool>,
+        update: &ReadWrite<u32, Update::Register>,
+    ) -> ReturnCode {
+        if reading.get() {
+            return ReturnCode::EBUSY;
+        }
+        if !self.is_enabled() {
+            self.enable();
+        }
+        update.write(Update::STAT::SET);
+        reading.set(true);
+        DEFERRED_CALL.set();
+        ReturnCode::SUCCESS
+    }
+
+    /// Complete the reads that have a new measurement, and check again later
+    /// for those that do not have one yet.
+    pub fn handle_deferred_call(&self) {
+        if self.reading_temperature.get() && self.registers.tempupd.is_set(Update::STAT) {
+            self.reading_temperature.set(false);
+            let value = self.temperature() * 100;
+            self.temperature_client
+                .map(|client| client.callback(value as usize));
+        }
+        if self.reading_voltage.get() && self.registers.batupd.is_set(Update::STAT) {
+            self.reading_voltage.set(false);
+            let value = self.voltage();
+            self.voltage_client
+                .map(|client| client.callback(value as usize));
+        }
+        if self.reading_temperature.get() || self.reading_voltage.get() {
+            DEFERRED_CALL.set();
+        }
+    }
+}
+
+impl sensors::TemperatureDriver for Batmon {
+    fn set_client(&self, client: &'static sensors::TemperatureClient) {
+        self.temperature_client.set(client);
+    }
+
+    /// The value passed to the client is negative below 0 °C, as a two's
+    /// complement number.
+    fn read_temperature(&self) -> ReturnCode {
+        self.start_read(&self.reading_temperature, &self.registers.tempupd)
+    }
+}
+
+impl sensors::VoltageDriver for Batmon {
+    fn set_client(&self, client: &'static sensors::VoltageClient) {
+        self.voltage_client.set(client);
+    }
+
+    fn read_voltage(&self) -> ReturnCode {
+        self.start_read(&self.reading_voltage, &self.registers.batupd)
+    }
+}
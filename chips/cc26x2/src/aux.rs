@@ -0,0 +1,269 @@
+//! AUX - the sensor controller domain of the cc26x2 family
+//!
+//! The AUX domain holds the analog peripherals: the ADC (see `adc`) and
+//! comparator A. It has its own power and clock control. `Aux` counts the
+//! drivers that use the domain, keeps it active while any of them do, and
+//! lets it drop to its low power mode, clocked from the low frequency clock,
+//! once they are all done.
+//!
+//! Comparator A compares one of the analog capable pins with an internal
+//! reference. Its output is an event of the AUX domain, which stays
+//! asserted while the pin is above the reference, so interrupt-based
+//! comparison stops after the first interrupt and has to be started again.
+//!
+//! Usage
+//! -----
+//!
+//! ```rust
+//! cc26x2::gpio::PORT[30].enable_analog_input().unwrap();
+//! cc26x2::aux::COMPA.set_client(client);
+//! let channel = cc26x2::aux::CompChannel {
+//!     input: cc26x2::adc::Channel::Dio30,
+//!     reference: cc26x2::aux::Reference::Dcoupl,
+//! };
+//! cc26x2::aux::COMPA.start_comparing(&channel);
+//! ```
+
+use core::cell::Cell;
+use kernel::common::cells::OptionalCell;
+use kernel::common::registers::{register_bitfields, ReadOnly, ReadWrite, WriteOnly};
+use kernel::common::StaticRef;
+use kernel::hil::analog_comparator;
+use kernel::ReturnCode;
+
+use crate::adc;
+
+#[repr(C)]
+struct AuxSysIfRegisters {
+    op_mode_req: ReadWrite<u32, OpMode::Register>,
+    op_mode_ack: ReadOnly<u32, OpMode::Register>,
+    _reserved0: [u32; 19],
+    adc_clk_ctl: ReadWrite<u32, ClkCtl::Register>,
+}
+
+#[repr(C)]
+struct AuxEvCtlRegisters {
+    _reserved0: [u32; 12],
+    ev_to_mcu_flags: ReadOnly<u32, EvToMcuFlags::Register>,
+    _reserved1: [u32; 1],
+    ev_to_mcu_flags_clr: WriteOnly<u32, EvToMcuFlags::Register>,
+}
+
+/// The part of the analog configuration of the AUX domain (see `adc`) that
+/// belongs to comparator A
+#[repr(C)]
+struct Adi4AuxCompRegisters {
+    mux0: ReadWrite<u8, Mux0::Register>,
+    mux1: ReadWrite<u8>,
+    _reserved0: [u8; 3],
+    comp: ReadWrite<u8, Comp::Register>,
+}
+
+register_bitfields![
+    u32,
+    OpMode [
+        MODE OFFSET(0) NUMBITS(2) [
+            Active = 0,
+            LowPower = 1,
+            PowerDownActive = 2,
+            PowerDownLowPower = 3
+        ]
+    ],
+    ClkCtl [
+        ACK OFFSET(1) NUMBITS(1) [],
+        REQ OFFSET(0) NUMBITS(1) []
+    ],
+    EvToMcuFlags [
+        AUX_COMPA OFFSET(1) NUMBITS(1) []
+    ]
+];
+
+register_bitfields![
+    u8,
+    Mux0 [
+        COMPA_REF OFFSET(0) NUMBITS(4) []
+    ],
+    Comp [
+        COMPA_EN OFFSET(0) NUMBITS(1) []
+    ]
+];
+
+const AUX_SYSIF_BASE: StaticRef<AuxSysIfRegisters> =
+    unsafe { StaticRef::new(0x400C_6000 as *const AuxSysIfRegisters) };
+const AUX_EVCTL_BASE: StaticRef<AuxEvCtlRegisters> =
+    unsafe { StaticRef::new(0x400C_5000 as *const AuxEvCtlRegisters) };
+const ADI4_AUX_BASE: StaticRef<Adi4AuxCompRegisters> =
+    unsafe { StaticRef::new(0x400C_B000 as *const Adi4AuxCompRegisters) };
+
+pub static mut AUX: Aux = Aux::new();
+
+pub struct Aux {
+    sysif: StaticRef<AuxSysIfRegisters>,
+    /// The number of drivers using the domain
+    users: Cell<usize>,
+}
+
+impl Aux {
+    const fn new() -> Aux {
+        Aux {
+            sysif: AUX_SYSIF_BASE,
+            users: Cell::new(0),
+        }
+    }
+
+    /// Keeps the AUX domain active until a matching `release`.
+    pub fn request(&self) {
+        self.users.set(self.users.get() + 1);
+        // The operational mode can only change to a neighbouring one, so low
+        // power modes step through another first
+        loop {
+            let next = match self.sysif.op_mode_ack.read_as_enum(OpMode::MODE) {
+                Some(OpMode::MODE::Value::Active) => break,
+                Some(OpMode::MODE::Value::PowerDownLowPower) => OpMode::MODE::LowPower,
+                _ => OpMode::MODE::Active,
+            };
+            self.set_mode(next);
+        }
+    }
+
+    /// Lets the AUX domain drop to its low power mode, once no driver
+    /// requests it.
+    pub fn release(&self) {
+        let users = self.users.get().saturating_sub(1);
+        self.users.set(users);
+        if users == 0 {
+            self.set_mode(OpMode::MODE::LowPower);
+        }
+    }
+
+    fn set_mode(&self, mode: kernel::common::registers::FieldValue<u32, OpMode::Register>) {
+        let sysif = &*self.sysif;
+        sysif.op_mode_req.write(mode);
+        while sysif.op_mode_ack.get() != sysif.op_mode_req.get() {}
+    }
+
+    /// Turns on the clock of the ADC. The domain must be requested.
+    pub fn enable_adc_clock(&self) {
+        let sysif = &*self.sysif;
+        sysif.adc_clk_ctl.write(ClkCtl::REQ::SET);
+        while !sysif.adc_clk_ctl.is_set(ClkCtl::ACK) {}
+    }
+
+    pub fn disable_adc_clock(&self) {
+        self.sysif.adc_clk_ctl.write(ClkCtl::REQ::CLEAR);
+    }
+}
+
+/// The internal references that comparator A compares a pin with.
+#[derive(Copy, Clone, Debug)]
+pub enum Reference {
+    /// The output of the internal regulator, about 1.27 V
+    Dcoupl = 0x1,
+    Vss = 0x2,
+    Vdds = 0x4,
+    /// The reference of the ADC
+    AdcVrefP = 0x8,
+}
+
+/// A comparison of the pin `input` with `reference`. The output of the
+/// comparator is true when the pin is above the reference.
+#[derive(Copy, Clone, Debug)]
+pub struct CompChannel {
+    pub input: adc::Channel,
+    pub reference: Reference,
+}
+
+pub static mut COMPA: Comparator = Comparator::new();
+
+pub struct Comparator {
+    evctl: StaticRef<AuxEvCtlRegisters>,
+    adi: StaticRef<Adi4AuxCompRegisters>,
+    client: OptionalCell<&'static analog_comparator::Client>,
+    comparing: Cell<bool>,
+}
+
+impl Comparator {
+    const fn new() -> Comparator {
+        Comparator {
+            evctl: AUX_EVCTL_BASE,
+            adi: ADI4_AUX_BASE,
+            client: OptionalCell::empty(),
+            comparing: Cell::new(false),
+        }
+    }
+
+    pub fn set_client(&self, client: &'static analog_comparator::Client) {
+        self.client.set(client);
+    }
+
+    fn enable(&self, channel: &CompChannel) {
+        let adi = &*self.adi;
+        unsafe { AUX.request() };
+        adi.mux0
+            .modify(Mux0::COMPA_REF.val(channel.reference as u8));
+        adi.mux1.set(channel.input.mux_bit());
+        adi.comp.modify(Comp::COMPA_EN::SET);
+    }
+
+    fn disable(&self) {
+        let adi = &*self.adi;
+        adi.comp.modify(Comp::COMPA_EN::CLEAR);
+        adi.mux1.set(0);
+        adi.mux0.modify(Mux0::COMPA_REF.val(0));
+        unsafe { AUX.release() };
+    }
+
+    /// Whether the event of the comparator is asserted. Its flag is set
+    /// again right after it is cleared while the event is asserted.
+    fn output(&self) -> bool {
+        let evctl = &*self.evctl;
+        evctl
+            .ev_to_mcu_flags_clr
+            .write(EvToMcuFlags::AUX_COMPA::SET);
+        evctl.ev_to_mcu_flags.is_set(EvToMcuFlags::AUX_COMPA)
+    }
+
+    pub fn handle_interrupt(&self) {
+        self.evctl
+            .ev_to_mcu_flags_clr
+            .write(EvToMcuFlags::AUX_COMPA::SET);
+        if self.comparing.take() {
+            self.disable();
+            // There is a single comparator
+            self.client.map(|client| client.fired(0));
+        }
+    }
+}
+
+impl analog_comparator::AnalogComparator for Comparator {
+    type Channel = CompChannel;
+
+    fn comparison(&self, channel: &Self::Channel) -> bool {
+        if self.comparing.get() {
+            return self.output();
+        }
+        self.enable(channel);
+        let output = self.output();
+        self.disable();
+        output
+    }
+
+    fn start_comparing(&self, channel: &Self::Channel) -> ReturnCode {
+        if self.comparing.get() {
+            return ReturnCode::EBUSY;
+        }
+        self.enable(channel);
+        self.evctl
+            .ev_to_mcu_flags_clr
+            .write(EvToMcuFlags::AUX_COMPA::SET);
+        self.comparing.set(true);
+        ReturnCode::SUCCESS
+    }
+
+    fn stop_comparing(&self, _channel: &Self::Channel) -> ReturnCode {
+        if self.comparing.take() {
+            self.disable();
+        }
+        ReturnCode::SUCCESS
+    }
+}
@@ -0,0 +1,481 @@
+//! BLE advertising radio driver for the cc26x2 family
+//!
+//! The radio is run by the radio core (see `rfc`), with one radio operation
+//! for each advertisement sent and each packet received. Advertisements are
+//! sent by the non-connectable advertiser operation, so they all go out as
+//! `ADV_NONCONN_IND`, which is what `capsules::ble_advertising_driver` needs
+//! as it does not support connections. Packets are received by the generic
+//! receive operation, which stops after the first packet on the advertising
+//! channel.
+//!
+//! As in the nRF52 driver, packets are copied into and out of a buffer of the
+//! driver, laid out as they are sent: the header, the length and up to 37
+//! bytes of payload, which starts with the address of the advertiser.
+//!
+//! The radio core runs one radio mode at a time, so a board uses either this
+//! driver or `ieee802154_radio`.
+//!
+//! Usage
+//! -----
+//!
+//! ```rust
+//! cc26x2::rfc::RFC.set_client(&cc26x2::ble_radio::RADIO);
+//! let ble_radio = static_init!(
+//!     capsules::ble_advertising_driver::BLE<'static, cc26x2::ble_radio::Radio, VirtualMuxAlarm<'static, Rtc>>,
+//!     capsules::ble_advertising_driver::BLE::new(
+//!         &cc26x2::ble_radio::RADIO,
+//!         board_kernel.create_grant(&grant_cap),
+//!         &mut capsules::ble_advertising_driver::BUF,
+//!         ble_radio_virtual_alarm
+//!     )
+//! );
+//! cc26x2::ble_radio::RADIO.set_receive_client(ble_radio);
+//! cc26x2::ble_radio::RADIO.set_transmit_client(ble_radio);
+//! ```
+
+use core::cell::Cell;
+use kernel::common::cells::{OptionalCell, VolatileCell};
+use kernel::hil::ble_advertising::{self, RadioChannel};
+use kernel::ReturnCode;
+
+use crate::rfc::{self, CommandHeader, DataQueue, RxEntry};
+
+const CMD_BLE_ADV_NC: u16 = 0x1805;
+const CMD_BLE_GENERIC_RX: u16 = 0x1809;
+
+/// Status of BLE operations that are done successfully
+const BLE_DONE_OK: u16 = 0x1400;
+
+/// `mode` of the radio setup for BLE
+const MODE_BLE: u8 = 0x00;
+
+const DEFAULT_TX_POWER: i8 = 0;
+
+/// The header, the length and the longest payload of an advertising packet
+pub const PAYLOAD_LENGTH: usize = 39;
+/// The header, the length and the address of the advertiser
+const ADV_HEADER_LENGTH: usize = 8;
+/// Bit of the header set when the address of the advertiser is random
+const TX_ADD: u8 = 1 << 6;
+/// Bit of the advertiser options set when its address is random
+const ADV_CONFIG_RANDOM_ADDRESS: u8 = 1 << 2;
+
+const ADVERTISING_ACCESS_ADDRESS: u32 = 0x8E89_BED6;
+const ADVERTISING_CRC_INIT: u8 = 0x55;
+
+/// Options of the receive operation: ignored packets are flushed, and the
+/// length byte and a status byte with the CRC result are kept with each
+/// packet.
+const RX_CONFIG: u8 = 0x49;
+
+/// Size of the data of the entry: the length, the packet and the status
+const RX_ENTRY_LEN: usize = 1 + PAYLOAD_LENGTH + 1;
+
+static mut PAYLOAD: [u8; PAYLOAD_LENGTH] = [0; PAYLOAD_LENGTH];
+
+/// The layout shared by the BLE radio operations, whose own parameters are
+/// in a separate structure.
+#[allow(dead_code)]
+#[repr(C)]
+struct CmdBle {
+    header: CommandHeader,
+    start_trigger: VolatileCell<u8>,
+    condition: VolatileCell<u8>,
+    channel: VolatileCell<u8>,
+    whitening: VolatileCell<u8>,
+    params: VolatileCell<u32>,
+    output: VolatileCell<u32>,
+}
+
+#[allow(dead_code)]
+#[repr(C)]
+struct AdvParams {
+    rx_queue: VolatileCell<u32>,
+    rx_config: VolatileCell<u8>,
+    adv_config: VolatileCell<u8>,
+    adv_len: VolatileCell<u8>,
+    scan_rsp_len: VolatileCell<u8>,
+    adv_data: VolatileCell<u32>,
+    scan_rsp_data: VolatileCell<u32>,
+    device_address: VolatileCell<u32>,
+    white_list: VolatileCell<u32>,
+    behavior: VolatileCell<u8>,
+    _reserved0: VolatileCell<u8>,
+    _reserved1: VolatileCell<u8>,
+    end_trigger: VolatileCell<u8>,
+    end_time: VolatileCell<u32>,
+}
+
+#[allow(dead_code)]
+#[repr(C)]
+struct GenericRxParams {
+    rx_queue: VolatileCell<u32>,
+    rx_config: VolatileCell<u8>,
+    repeat: VolatileCell<u8>,
+    _reserved0: VolatileCell<u16>,
+    access_address: VolatileCell<u32>,
+    crc_init0: VolatileCell<u8>,
+    crc_init1: VolatileCell<u8>,
+    crc_init2: VolatileCell<u8>,
+    end_trigger: VolatileCell<u8>,
+    end_time: VolatileCell<u32>,
+}
+
+#[derive(Copy, Clone, PartialEq)]
+enum Operation {
+    Idle,
+    Advertising,
+    Receiving,
+}
+
+pub struct Radio {
+    setup_cmd: rfc::CmdRadioSetup,
+    adv_cmd: CmdBle,
+    adv_params: AdvParams,
+    rx_cmd: CmdBle,
+    rx_params: GenericRxParams,
+    /// The operations write counters here, which are not used
+    output: [VolatileCell<u32>; 4],
+    /// The address of the advertiser, as the advertiser operation reads it
+    device_address: [VolatileCell<u16>; 3],
+    rx_queue: DataQueue,
+    rx_entry: RxEntry<[VolatileCell<u8>; RX_ENTRY_LEN]>,
+    front_end: Cell<u16>,
+    overrides: Cell<&'static [u32]>,
+    tx_client: OptionalCell<&'static ble_advertising::TxClient>,
+    rx_client: OptionalCell<&'static ble_advertising::RxClient>,
+    operation: Cell<Operation>,
+    tx_power: Cell<i8>,
+    /// Set when the transmit power changes, to run the radio setup again
+    /// before the next operation
+    setup_pending: Cell<bool>,
+}
+
+pub static mut RADIO: Radio = Radio::new();
+
+impl Radio {
+    const fn new() -> Radio {
+        Radio {
+            setup_cmd: rfc::CmdRadioSetup {
+                header: CommandHeader::new(rfc::CMD_RADIO_SETUP),
+                start_trigger: VolatileCell::new(rfc::trigger::NOW),
+                condition: VolatileCell::new(rfc::condition::NEVER),
+                mode: VolatileCell::new(MODE_BLE),
+                lo_divider: VolatileCell::new(0),
+                config: VolatileCell::new(rfc::DEFAULT_FRONT_END),
+                tx_power: VolatileCell::new(0),
+                reg_override: VolatileCell::new(0),
+            },
+            adv_cmd: CmdBle {
+                header: CommandHeader::new(CMD_BLE_ADV_NC),
+                start_trigger: VolatileCell::new(rfc::trigger::NOW),
+                condition: VolatileCell::new(rfc::condition::NEVER),
+                channel: VolatileCell::new(0),
+                // The default whitening of the channel
+                whitening: VolatileCell::new(0),
+                params: VolatileCell::new(0),
+                output: VolatileCell::new(0),
+            },
+            adv_params: AdvParams {
+                rx_queue: VolatileCell::new(0),
+                rx_config: VolatileCell::new(0),
+                adv_config: VolatileCell::new(0),
+                adv_len: VolatileCell::new(0),
+                scan_rsp_len: VolatileCell::new(0),
+                adv_data: VolatileCell::new(0),
+                scan_rsp_data: VolatileCell::new(0),
+                device_address: VolatileCell::new(0),
+                white_list: VolatileCell::new(0),
+                behavior: VolatileCell::new(0),
+                _reserved0: VolatileCell::new(0),
+                _reserved1: VolatileCell::new(0),
+                end_trigger: VolatileCell::new(rfc::trigger::NEVER),
+                end_time: VolatileCell::new(0),
+            },
+            rx_cmd: CmdBle {
+                header: CommandHeader::new(CMD_BLE_GENERIC_RX),
+                start_trigger: VolatileCell::new(rfc::trigger::NOW),
+                condition: VolatileCell::new(rfc::condition::NEVER),
+                channel: VolatileCell::new(0),
+                whitening: VolatileCell::new(0),
+                params: VolatileCell::new(0),
+                output: VolatileCell::new(0),
+            },
+            rx_params: GenericRxParams {
+                rx_queue: VolatileCell::new(0),
+                rx_config: VolatileCell::new(RX_CONFIG),
+                // Stop after the first packet
+                repeat: VolatileCell::new(0),
+                _reserved0: VolatileCell::new(0),
+                access_address: VolatileCell::new(ADVERTISING_ACCESS_ADDRESS),
+                crc_init0: VolatileCell::new(ADVERTISING_CRC_INIT),
+                crc_init1: VolatileCell::new(ADVERTISING_CRC_INIT),
+                crc_init2: VolatileCell::new(ADVERTISING_CRC_INIT),
+                end_trigger: VolatileCell::new(rfc::trigger::NEVER),
+                end_time: VolatileCell::new(0),
+            },
+            output: [
+                VolatileCell::new(0),
+                VolatileCell::new(0),
+                VolatileCell::new(0),
+                VolatileCell::new(0),
+            ],
+            device_address: [
+                VolatileCell::new(0),
+                VolatileCell::new(0),
+                VolatileCell::new(0),
+            ],
+            rx_queue: DataQueue::new(),
+            rx_entry: RxEntry::new([VolatileCell::new(0); RX_ENTRY_LEN]),
+            front_end: Cell::new(rfc::DEFAULT_FRONT_END),
+            overrides: Cell::new(&[rfc::END_OVERRIDE]),
+            tx_client: OptionalCell::empty(),
+            rx_client: OptionalCell::empty(),
+            operation: Cell::new(Operation::Idle),
+            tx_power: Cell::new(DEFAULT_TX_POWER),
+            setup_pending: Cell::new(false),
+        }
+    }
+
+    fn rfc(&self) -> &'static rfc::RFCore {
+        unsafe { &rfc::RFC }
+    }
+
+    /// Sets the front end configuration of the radio setup and the register
+    /// overrides, which are specific to the board. The overrides end with
+    /// `rfc::END_OVERRIDE`. They take effect when the radio is next set up.
+    pub fn set_front_end(&self, config: u16, overrides: &'static [u32]) {
+        self.front_end.set(config);
+        self.overrides.set(overrides);
+        self.setup_pending.set(true);
+    }
+
+    fn setup(&self) -> ReturnCode {
+        let setup = &self.setup_cmd;
+        setup.config.set(self.front_end.get());
+        setup
+            .tx_power
+            .set(rfc::tx_power_setting(rfc::TX_POWER_2_4_GHZ, self.tx_power.get()).unwrap_or(0));
+        setup.reg_override.set(self.overrides.get().as_ptr() as u32);
+        let result = self.rfc().run_command(&setup.header);
+        if result != ReturnCode::SUCCESS || setup.header.status.get() != rfc::status::DONE_OK {
+            return ReturnCode::FAIL;
+        }
+        self.setup_pending.set(false);
+        ReturnCode::SUCCESS
+    }
+
+    /// Powers the radio up if it is off, or stops the operation that runs,
+    /// so that the next one can start.
+    fn prepare(&self) -> ReturnCode {
+        if !self.rfc().is_on() {
+            let result = self
+                .rfc()
+                .enable(rfc::event::LAST_COMMAND_DONE | rfc::event::RX_ENTRY_DONE);
+            if result != ReturnCode::SUCCESS {
+                return result;
+            }
+            let result = self.setup();
+            if result != ReturnCode::SUCCESS {
+                self.rfc().disable();
+            }
+            return result;
+        }
+
+        self.stop_operation();
+        if self.setup_pending.get() {
+            self.setup()
+        } else {
+            ReturnCode::SUCCESS
+        }
+    }
+
+    fn stop_operation(&self) {
+        let header = match self.operation.get() {
+            Operation::Idle => return,
+            Operation::Advertising => &self.adv_cmd.header,
+            Operation::Receiving => &self.rx_cmd.header,
+        };
+        self.operation.set(Operation::Idle);
+        if header.is_done() {
+            return;
+        }
+        if self.rfc().send_direct(rfc::cmd::ABORT) == ReturnCode::SUCCESS {
+            while !header.is_done() {}
+        }
+    }
+
+    fn start_advertising(&self, len: usize, channel: RadioChannel) -> ReturnCode {
+        let result = self.prepare();
+        if result != ReturnCode::SUCCESS {
+            return result;
+        }
+
+        let payload = unsafe { &PAYLOAD };
+        for (i, address) in self.device_address.iter().enumerate() {
+            let offset = 2 + 2 * i;
+            address.set(u16::from_le_bytes([payload[offset], payload[offset + 1]]));
+        }
+
+        let params = &self.adv_params;
+        params.adv_config.set(if payload[0] & TX_ADD != 0 {
+            ADV_CONFIG_RANDOM_ADDRESS
+        } else {
+            0
+        });
+        params.adv_len.set((len - ADV_HEADER_LENGTH) as u8);
+        params
+            .adv_data
+            .set(payload[ADV_HEADER_LENGTH..].as_ptr() as u32);
+        params
+            .device_address
+            .set(&self.device_address as *const _ as u32);
+
+        let adv = &self.adv_cmd;
+        adv.channel.set(channel.get_channel_index() as u8);
+        adv.params.set(params as *const AdvParams as u32);
+        adv.output.set(&self.output as *const _ as u32);
+        let result = self.rfc().send_command(&adv.header);
+        if result == ReturnCode::SUCCESS {
+            self.operation.set(Operation::Advertising);
+        }
+        result
+    }
+
+    fn start_receiving(&self, channel: RadioChannel) -> ReturnCode {
+        let result = self.prepare();
+        if result != ReturnCode::SUCCESS {
+            return result;
+        }
+
+        self.rx_queue.set_single_entry(&self.rx_entry);
+
+        let params = &self.rx_params;
+        params
+            .rx_queue
+            .set(&self.rx_queue as *const DataQueue as u32);
+
+        let rx = &self.rx_cmd;
+        rx.channel.set(channel.get_channel_index() as u8);
+        rx.params.set(params as *const GenericRxParams as u32);
+        rx.output.set(&self.output as *const _ as u32);
+        let result = self.rfc().send_command(&rx.header);
+        if result == ReturnCode::SUCCESS {
+            self.operation.set(Operation::Receiving);
+        }
+        result
+    }
+
+    /// Hands the packet in the receive queue to the client.
+    fn receive_packet(&self) {
+        let entry = &self.rx_entry;
+        if entry.status.get() != rfc::entry::FINISHED {
+            return;
+        }
+        self.operation.set(Operation::Idle);
+
+        // The data is the length, the packet, and a status byte
+        let len = entry.data[0].get() as usize;
+        let result = if len >= 3 && len < RX_ENTRY_LEN {
+            let payload = unsafe { &mut PAYLOAD };
+            for (byte, data) in payload.iter_mut().zip(entry.data[1..len].iter()) {
+                *byte = data.get();
+            }
+            if entry.data[len].get() & rfc::RX_CRC_ERROR == 0 {
+                ReturnCode::SUCCESS
+            } else {
+                ReturnCode::FAIL
+            }
+        } else {
+            ReturnCode::ESIZE
+        };
+        entry.status.set(rfc::entry::PENDING);
+        self.receive_done(result);
+    }
+
+    fn receive_done(&self, result: ReturnCode) {
+        self.rx_client.map(|client| unsafe {
+            client.receive_event(&mut PAYLOAD, PAYLOAD[1].saturating_add(2), result)
+        });
+    }
+}
+
+impl rfc::Client for Radio {
+    fn cpe_events(&self, events: u32) {
+        if events & rfc::event::RX_ENTRY_DONE != 0 && self.operation.get() == Operation::Receiving {
+            self.receive_packet();
+        }
+        if events & rfc::event::LAST_COMMAND_DONE != 0 {
+            match self.operation.get() {
+                Operation::Advertising if self.adv_cmd.header.is_done() => {
+                    self.operation.set(Operation::Idle);
+                    let result = if self.adv_cmd.header.status.get() == BLE_DONE_OK {
+                        ReturnCode::SUCCESS
+                    } else {
+                        ReturnCode::FAIL
+                    };
+                    self.tx_client.map(|client| client.transmit_event(result));
+                }
+                Operation::Receiving if self.rx_cmd.header.is_done() => {
+                    // The operation stopped without a packet
+                    self.operation.set(Operation::Idle);
+                    unsafe {
+                        PAYLOAD[1] = 0;
+                    }
+                    self.receive_done(ReturnCode::FAIL);
+                }
+                _ => (),
+            }
+        }
+    }
+}
+
+impl ble_advertising::BleAdvertisementDriver for Radio {
+    fn transmit_advertisement(
+        &self,
+        buf: &'static mut [u8],
+        len: usize,
+        channel: RadioChannel,
+    ) -> &'static mut [u8] {
+        let len = core::cmp::min(len, core::cmp::min(buf.len(), PAYLOAD_LENGTH));
+        let payload = unsafe { &mut PAYLOAD };
+        payload[..len].copy_from_slice(&buf[..len]);
+
+        // As the client cannot be called back from here, an advertisement
+        // that cannot be sent is dropped, as is a packet that cannot be
+        // received
+        if len >= ADV_HEADER_LENGTH {
+            let _ = self.start_advertising(len, channel);
+        }
+        buf
+    }
+
+    fn receive_advertisement(&self, channel: RadioChannel) {
+        let _ = self.start_receiving(channel);
+    }
+
+    fn set_receive_client(&self, client: &'static ble_advertising::RxClient) {
+        self.rx_client.set(client);
+    }
+
+    fn set_transmit_client(&self, client: &'static ble_advertising::TxClient) {
+        self.tx_client.set(client);
+    }
+}
+
+impl ble_advertising::BleConfig for Radio {
+    /// Sets the transmit power in dBm, as a two's complement byte.
+    fn set_tx_power(&self, power: u8) -> ReturnCode {
+        let power = power as i8;
+        match rfc::tx_power_setting(rfc::TX_POWER_2_4_GHZ, power) {
+            Some(_) => {
+                if power != self.tx_power.get() {
+                    self.tx_power.set(power);
+                    self.setup_pending.set(true);
+                }
+                ReturnCode::SUCCESS
+            }
+            None => ReturnCode::ENOSUPPORT,
+        }
+    }
+}
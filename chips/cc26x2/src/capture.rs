@@ -0,0 +1,150 @@
+//! Edge-time capture on the general purpose timers
+//!
+//! Each timer half can timestamp the edges of a pin that is routed to it with
+//! `GPIOPin::enable_capture()`. The half counts up at the system clock, with
+//! its prescaler extending it to 24 bits, so timestamps wrap around about
+//! every 350 ms. A half cannot capture while it generates PWM, and setting up
+//! capture on one half sets the whole timer to 16-bit mode.
+//!
+//! ```rust
+//! cc26x2::gpio::PORT[21].enable_capture(cc26x2::pwm::Timer::GPT1A).unwrap();
+//! cc26x2::capture::CAPTURE[cc26x2::pwm::Timer::GPT1A as usize].set_client(client);
+//! ```
+
+use kernel::common::cells::OptionalCell;
+use kernel::common::StaticRef;
+use kernel::hil::pulse_capture::{self, Edge};
+use kernel::ReturnCode;
+
+use crate::gpt::{self, Ctl, Interrupt, Mode};
+use crate::prcm;
+use crate::pwm::Timer;
+
+/// Frequency of the clock the timers count
+const CLOCK_HZ: u32 = 48_000_000;
+
+/// The largest timestamp of a 16-bit timer extended by its 8-bit prescaler
+const MAX_TIMESTAMP: u32 = 0xFF_FFFF;
+
+pub static mut CAPTURE: [Capture; 8] = [
+    Capture::new(Timer::GPT0A),
+    Capture::new(Timer::GPT0B),
+    Capture::new(Timer::GPT1A),
+    Capture::new(Timer::GPT1B),
+    Capture::new(Timer::GPT2A),
+    Capture::new(Timer::GPT2B),
+    Capture::new(Timer::GPT3A),
+    Capture::new(Timer::GPT3B),
+];
+
+pub struct Capture {
+    timer: Timer,
+    client: OptionalCell<&'static pulse_capture::Client>,
+}
+
+impl Capture {
+    const fn new(timer: Timer) -> Capture {
+        Capture {
+            timer: timer,
+            client: OptionalCell::empty(),
+        }
+    }
+
+    fn gpt(&self) -> usize {
+        self.timer as usize / 2
+    }
+
+    /// Whether this is timer A of its timer, rather than timer B
+    fn is_a(&self) -> bool {
+        self.timer as usize % 2 == 0
+    }
+
+    fn registers(&self) -> StaticRef<gpt::Registers> {
+        gpt::GPT[self.gpt()]
+    }
+
+    pub fn handle_interrupt(&self) {
+        let regs = self.registers();
+        let timestamp = if self.is_a() {
+            regs.int_clr.write(Interrupt::CAE::SET);
+            regs.timer_a.get()
+        } else {
+            regs.int_clr.write(Interrupt::CBE::SET);
+            regs.timer_b.get()
+        };
+        // The prescaler is in bits 16 to 23
+        self.client
+            .map(|client| client.edge_captured(timestamp & MAX_TIMESTAMP));
+    }
+}
+
+impl pulse_capture::PulseCapture<'static> for Capture {
+    fn set_client(&self, client: &'static pulse_capture::Client) {
+        self.client.set(client);
+    }
+
+    fn start(&self, edge: Edge) -> ReturnCode {
+        prcm::Clock::enable_gpt(self.gpt());
+        let regs = self.registers();
+        let mode = Mode::MODE::CAPTURE
+            + Mode::CAPTURE_MODE::EDGE_TIME
+            + Mode::COUNT_DIRECTION::UP
+            + Mode::ALT_MODE::CAPTURE_COMPARE;
+        if self.is_a() {
+            regs.ctl.modify(Ctl::TIMER_A_EN::DISABLE);
+            regs.cfg.write(gpt::Cfg::BITS::_16);
+            regs.timer_a_mode.write(mode);
+            regs.timer_a_load.set(0xFFFF);
+            regs.timer_a_prescale.set(0xFF);
+            regs.int_clr.write(Interrupt::CAE::SET);
+            regs.int_mask.modify(Interrupt::CAE::SET);
+            regs.ctl.modify(
+                match edge {
+                    Edge::Rising => Ctl::TIMER_A_EVENT::POSITIVE_EDGE,
+                    Edge::Falling => Ctl::TIMER_A_EVENT::NEGATIVE_EDGE,
+                    Edge::Both => Ctl::TIMER_A_EVENT::BOTH_EDGES,
+                } + Ctl::TIMER_A_EN::ENABLE,
+            );
+        } else {
+            regs.ctl.modify(Ctl::TIMER_B_EN::DISABLE);
+            regs.cfg.write(gpt::Cfg::BITS::_16);
+            regs.timer_b_mode.write(mode);
+            regs.timer_b_load.set(0xFFFF);
+            regs.timer_b_prescale.set(0xFF);
+            regs.int_clr.write(Interrupt::CBE::SET);
+            regs.int_mask.modify(Interrupt::CBE::SET);
+            regs.ctl.modify(
+                match edge {
+                    Edge::Rising => Ctl::TIMER_B_EVENT::POSITIVE_EDGE,
+                    Edge::Falling => Ctl::TIMER_B_EVENT::NEGATIVE_EDGE,
+                    Edge::Both => Ctl::TIMER_B_EVENT::BOTH_EDGES,
+                } + Ctl::TIMER_B_EN::ENABLE,
+            );
+        }
+        ReturnCode::SUCCESS
+    }
+
+    fn stop(&self) -> ReturnCode {
+        let regs = self.registers();
+        if self.is_a() {
+            regs.int_mask.modify(Interrupt::CAE::CLEAR);
+            regs.ctl.modify(Ctl::TIMER_A_EN::DISABLE);
+        } else {
+            regs.int_mask.modify(Interrupt::CBE::CLEAR);
+            regs.ctl.modify(Ctl::TIMER_B_EN::DISABLE);
+        }
+        // The clock of the timer keeps the chip from standby
+        if !regs.ctl.is_set(Ctl::TIMER_A_EN) && !regs.ctl.is_set(Ctl::TIMER_B_EN) {
+            prcm::Clock::disable_gpt(self.gpt());
+        }
+        ReturnCode::SUCCESS
+    }
+
+    fn frequency(&self) -> u32 {
+        CLOCK_HZ
+    }
+
+    fn max_timestamp(&self) -> u32 {
+        MAX_TIMESTAMP
+    }
+}
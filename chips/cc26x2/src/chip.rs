@@ -1,10 +1,26 @@
+use crate::adc;
+use crate::aon;
+use crate::aon_batmon;
+use crate::aux;
+use crate::capture;
+use crate::crypto;
+use crate::deferred_call_tasks::DeferredCallTask;
+use crate::flash;
 use crate::gpio;
 use crate::i2c;
+use crate::oscillator;
 use crate::peripheral_interrupts::NvicIrq;
+use crate::prcm;
+use crate::rfc;
 use crate::rtc;
+use crate::ssi;
+use crate::trng;
 use crate::uart;
+use crate::wdt;
 use cortexm4::{self, nvic};
 use enum_primitive::cast::FromPrimitive;
+use kernel::common::deferred_call;
+use kernel::interrupt_latency;
 
 pub struct Cc26X2 {
     mpu: cortexm4::mpu::MPU,
@@ -23,6 +39,28 @@ impl Cc26X2 {
             systick: cortexm4::systick::SysTick::new_with_calibration(hfreq),
         }
     }
+
+    /// Enters standby, which draws microamps rather than the milliamps of
+    /// sleep, until an AON wake up event. The GPIO outputs are kept, and the
+    /// crystal oscillator, which standby turns off, is started again if it
+    /// was in use.
+    unsafe fn standby(&self) {
+        let xosc = oscillator::OSC.is_hf_xosc();
+        oscillator::OSC.switch_to_hf_rcosc();
+        let port = gpio::PORT.save();
+        aon::AON.set_io_freeze(true);
+        // Let outstanding writes to the AON domain complete
+        aon::AON.sync();
+
+        prcm::Power::standby();
+
+        gpio::PORT.restore(&port);
+        aon::AON.set_io_freeze(false);
+        aon::AON.sync();
+        if xosc {
+            oscillator::OSC.switch_to_hf_xosc();
+        }
+    }
 }
 
 impl kernel::Chip for Cc26X2 {
@@ -44,14 +82,52 @@ impl kernel::Chip for Cc26X2 {
 
     fn service_pending_interrupts(&self) {
         unsafe {
-            while let Some(interrupt) = nvic::next_pending() {
+            loop {
+                if let Some(task) = deferred_call::DeferredCall::next_pending() {
+                    match task {
+                        DeferredCallTask::Flash => flash::FLASH.handle_interrupt(),
+                        DeferredCallTask::Oscillator => oscillator::OSC.handle_deferred_call(),
+                        DeferredCallTask::Uart0 => uart::UART0.handle_deferred_call(),
+                        DeferredCallTask::Uart1 => uart::UART1.handle_deferred_call(),
+                        DeferredCallTask::Batmon => aon_batmon::BATMON.handle_deferred_call(),
+                        DeferredCallTask::Adc => adc::ADC.handle_deferred_call(),
+                    }
+                    continue;
+                }
+                let interrupt = match nvic::next_pending() {
+                    Some(interrupt) => interrupt,
+                    None => break,
+                };
+                interrupt_latency::serviced(interrupt);
                 let irq = NvicIrq::from_u32(interrupt)
                     .expect("Pending IRQ flag not enumerated in NviqIrq");
                 match irq {
                     NvicIrq::Gpio => gpio::PORT.handle_interrupt(),
                     NvicIrq::AonRtc => rtc::RTC.handle_interrupt(),
                     NvicIrq::Uart0 => uart::UART0.handle_interrupt(),
+                    NvicIrq::Uart1 => uart::UART1.handle_interrupt(),
                     NvicIrq::I2c0 => i2c::I2C0.handle_interrupt(),
+                    NvicIrq::Ssi0 => ssi::SSI0.handle_interrupt(),
+                    NvicIrq::Ssi1 => ssi::SSI1.handle_interrupt(),
+                    NvicIrq::AuxAdc => adc::ADC.handle_interrupt(),
+                    NvicIrq::AuxCompA => aux::COMPA.handle_interrupt(),
+                    NvicIrq::Crypto => crypto::AES.handle_interrupt(),
+                    NvicIrq::Trng => trng::TRNG.handle_interrupt(),
+                    NvicIrq::Watchdog => wdt::WDT.handle_interrupt(),
+                    NvicIrq::Osc => oscillator::OSC.handle_interrupt(),
+                    NvicIrq::Gpt0a
+                    | NvicIrq::Gpt0b
+                    | NvicIrq::Gpt1a
+                    | NvicIrq::Gpt1b
+                    | NvicIrq::Gpt2a
+                    | NvicIrq::Gpt2b
+                    | NvicIrq::Gpt3a
+                    | NvicIrq::Gpt3b => capture::CAPTURE
+                        [interrupt as usize - NvicIrq::Gpt0a as usize]
+                        .handle_interrupt(),
+                    NvicIrq::RfCorePe1 | NvicIrq::RfCorePe2 => rfc::RFC.handle_interrupt(),
+                    // Commands to the radio core are acknowledged synchronously
+                    NvicIrq::RfCmdAck | NvicIrq::RfCoreHw => (),
                     // We need to ignore JTAG events since some debuggers emit these
                     NvicIrq::AonProg => (),
                     _ => panic!("Unhandled interrupt {:?}", irq),
@@ -64,12 +140,16 @@ impl kernel::Chip for Cc26X2 {
     }
 
     fn has_pending_interrupts(&self) -> bool {
-        unsafe { nvic::has_pending() }
+        unsafe { nvic::has_pending() || deferred_call::has_tasks() }
     }
 
     fn sleep(&self) {
         unsafe {
-            cortexm4::support::wfi();
+            if prcm::Clock::peripherals_requested() {
+                cortexm4::support::wfi();
+            } else {
+                self.standby();
+            }
         }
     }
 
@@ -0,0 +1,608 @@
+//! Crypto - AES engine of the cc26x2 family
+//!
+//! Encrypts and decrypts with AES-128 in CTR and CBC modes, and with CCM*
+//! in a single pass through the engine. The keys are loaded into the key
+//! store of the crypto engine, and the data is moved in and out of the
+//! engine by its DMA, so buffers are processed without the CPU. The counter,
+//! or chaining value, is saved at the end of each `crypt()`, so a message can
+//! be processed in several calls.
+//!
+//! `AES128CCM` computes the tag in the engine too, so the 802.15.4 framer can
+//! use it directly instead of `capsules::aes_ccm`. It keeps its key in a
+//! separate area of the key store, so it can be used alongside the other
+//! modes. The engine cannot compute tags shorter than 4 bytes, so CCM* with
+//! no tag is done in CTR mode, and is only supported with encryption.
+//!
+//! Usage
+//! -----
+//!
+//! ```rust
+//! cc26x2::crypto::AES.enable();
+//! cc26x2::crypto::AES.set_client(client);
+//! cc26x2::crypto::AES.set_key(&key);
+//! cc26x2::crypto::AES.set_iv(&iv);
+//! cc26x2::crypto::AES.set_mode_aes128ctr(true);
+//! cc26x2::crypto::AES.start_message();
+//! cc26x2::crypto::AES.crypt(None, buf, 0, 32);
+//!
+//! AES128CCM::set_client(&cc26x2::crypto::AES, ccm_client);
+//! AES128CCM::set_key(&cc26x2::crypto::AES, &key);
+//! cc26x2::crypto::AES.set_nonce(&nonce);
+//! AES128CCM::crypt(&cc26x2::crypto::AES, frame, a_off, m_off, m_len, 8, true, true);
+//! ```
+
+use core::cell::Cell;
+use kernel::common::cells::{OptionalCell, TakeCell};
+use kernel::common::registers::{register_bitfields, FieldValue, ReadOnly, ReadWrite, WriteOnly};
+use kernel::common::secure::constant_time_eq;
+use kernel::common::StaticRef;
+use kernel::hil::symmetric_encryption::{
+    self, AES128_BLOCK_SIZE, AES128_KEY_SIZE, CCM_NONCE_LENGTH,
+};
+use kernel::ReturnCode;
+
+use crate::prcm;
+
+#[repr(C)]
+struct CryptoRegisters {
+    dma_ch0_ctl: ReadWrite<u32, DmaCtl::Register>,
+    dma_ch0_ext_addr: ReadWrite<u32>,
+    _reserved0: u32,
+    dma_ch0_len: ReadWrite<u32>,
+    _reserved1: [u32; 4],
+    dma_ch1_ctl: ReadWrite<u32, DmaCtl::Register>,
+    dma_ch1_ext_addr: ReadWrite<u32>,
+    _reserved2: u32,
+    dma_ch1_len: ReadWrite<u32>,
+    _reserved3: [u32; 244],
+    key_write_area: ReadWrite<u32>,
+    key_written_area: ReadWrite<u32>,
+    key_size: ReadWrite<u32, KeySize::Register>,
+    key_read_area: ReadWrite<u32, KeyReadArea::Register>,
+    _reserved4: [u32; 76],
+    aes_iv: [ReadWrite<u32>; 4],
+    aes_ctl: ReadWrite<u32, AesCtl::Register>,
+    aes_data_len0: ReadWrite<u32>,
+    aes_data_len1: ReadWrite<u32>,
+    aes_auth_len: ReadWrite<u32>,
+    _aes_data_in_out: [ReadWrite<u32>; 4],
+    aes_tag_out: [ReadOnly<u32>; 4],
+    _reserved5: [u32; 96],
+    alg_sel: ReadWrite<u32, AlgSel::Register>,
+    _reserved6: [u32; 31],
+    irq_type: ReadWrite<u32, IrqType::Register>,
+    irq_en: ReadWrite<u32, Irq::Register>,
+    irq_clr: WriteOnly<u32, Irq::Register>,
+    _irq_set: WriteOnly<u32, Irq::Register>,
+    irq_stat: ReadOnly<u32, Irq::Register>,
+}
+
+register_bitfields![
+    u32,
+    DmaCtl [
+        EN OFFSET(0) NUMBITS(1) []
+    ],
+    KeySize [
+        SIZE OFFSET(0) NUMBITS(2) [
+            Bits128 = 1
+        ]
+    ],
+    KeyReadArea [
+        BUSY OFFSET(31) NUMBITS(1) [],
+        NUMBER OFFSET(0) NUMBITS(4) []
+    ],
+    AesCtl [
+        SAVED_CONTEXT_RDY OFFSET(30) NUMBITS(1) [],
+        SAVE_CONTEXT OFFSET(29) NUMBITS(1) [],
+        // The length of the tag, as (M - 2) / 2
+        CCM_M OFFSET(22) NUMBITS(3) [],
+        // The length of the length field, as L - 1
+        CCM_L OFFSET(19) NUMBITS(3) [],
+        CCM OFFSET(18) NUMBITS(1) [],
+        CTR_WIDTH OFFSET(7) NUMBITS(2) [
+            Bits128 = 3
+        ],
+        CTR OFFSET(6) NUMBITS(1) [],
+        CBC OFFSET(5) NUMBITS(1) [],
+        // Set to encrypt, clear to decrypt
+        DIR OFFSET(2) NUMBITS(1) []
+    ],
+    AlgSel [
+        AES OFFSET(1) NUMBITS(1) [],
+        KEY_STORE OFFSET(0) NUMBITS(1) []
+    ],
+    IrqType [
+        LEVEL OFFSET(0) NUMBITS(1) []
+    ],
+    Irq [
+        DMA_BUS_ERR OFFSET(31) NUMBITS(1) [],
+        KEY_ST_RD_ERR OFFSET(30) NUMBITS(1) [],
+        KEY_ST_WR_ERR OFFSET(29) NUMBITS(1) [],
+        DMA_IN_DONE OFFSET(1) NUMBITS(1) [],
+        RESULT_AVAIL OFFSET(0) NUMBITS(1) []
+    ]
+];
+
+const CRYPTO_BASE: StaticRef<CryptoRegisters> =
+    unsafe { StaticRef::new(0x4002_4000 as *const CryptoRegisters) };
+
+/// The area of the key store that holds the key
+const KEY_AREA: u32 = 0;
+/// The area of the key store that holds the key for CCM*
+const CCM_KEY_AREA: u32 = 1;
+
+/// The key, as read by the DMA of the crypto engine when it is loaded
+static mut KEY: [u32; 4] = [0; 4];
+
+/// The length of the length field of CCM* in 802.15.4, which leaves 13 bytes
+/// for the nonce
+const CCM_L: usize = 2;
+
+#[derive(Copy, Clone, PartialEq)]
+enum Mode {
+    Ctr,
+    Cbc,
+}
+
+pub static mut AES: Aes = Aes::new();
+
+pub struct Aes<'a> {
+    registers: StaticRef<CryptoRegisters>,
+    client: OptionalCell<&'a symmetric_encryption::Client<'a>>,
+    source: TakeCell<'a, [u8]>,
+    dest: TakeCell<'a, [u8]>,
+    mode: Cell<Mode>,
+    encrypting: Cell<bool>,
+    /// The IV set by the client, which each message starts from
+    iv: Cell<[u32; 4]>,
+    /// The counter or chaining value at the end of the last `crypt()`
+    context: Cell<[u32; 4]>,
+    ccm_client: OptionalCell<&'a symmetric_encryption::CCMClient>,
+    nonce: Cell<[u8; CCM_NONCE_LENGTH]>,
+    /// The buffer of the CCM* operation in progress
+    ccm_buf: TakeCell<'static, [u8]>,
+    /// The offset and length of the tag of the CCM* operation in progress
+    mic: Cell<(usize, usize)>,
+    /// The offset and length of the message of the CCM* operation in
+    /// progress, while its authenticated data is fed to the engine
+    ccm_message: OptionalCell<(usize, usize)>,
+    ccm_encrypting: Cell<bool>,
+}
+
+impl<'a> Aes<'a> {
+    const fn new() -> Aes<'a> {
+        Aes {
+            registers: CRYPTO_BASE,
+            client: OptionalCell::empty(),
+            source: TakeCell::empty(),
+            dest: TakeCell::empty(),
+            mode: Cell::new(Mode::Ctr),
+            encrypting: Cell::new(true),
+            iv: Cell::new([0; 4]),
+            context: Cell::new([0; 4]),
+            ccm_client: OptionalCell::empty(),
+            nonce: Cell::new([0; CCM_NONCE_LENGTH]),
+            ccm_buf: TakeCell::empty(),
+            mic: Cell::new((0, 0)),
+            ccm_message: OptionalCell::empty(),
+            ccm_encrypting: Cell::new(true),
+        }
+    }
+
+    fn busy(&self) -> bool {
+        self.dest.is_some() || self.ccm_buf.is_some()
+    }
+
+    /// Loads `key` into `area` of the key store. This takes a few cycles, so
+    /// it is done synchronously.
+    fn load_key(&self, area: u32, key: &[u8]) -> ReturnCode {
+        if key.len() != AES128_KEY_SIZE {
+            return ReturnCode::EINVAL;
+        }
+        if self.busy() {
+            return ReturnCode::EBUSY;
+        }
+        unsafe {
+            for (word, bytes) in KEY.iter_mut().zip(key.chunks(4)) {
+                *word = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+            }
+        }
+
+        let regs = &*self.registers;
+
+        regs.alg_sel.write(AlgSel::KEY_STORE::SET);
+        regs.irq_clr
+            .write(Irq::RESULT_AVAIL::SET + Irq::DMA_IN_DONE::SET);
+        regs.key_size.write(KeySize::SIZE::Bits128);
+        regs.key_write_area.set(1 << area);
+
+        regs.dma_ch0_ctl.write(DmaCtl::EN::SET);
+        unsafe {
+            regs.dma_ch0_ext_addr.set(KEY.as_ptr() as u32);
+        }
+        regs.dma_ch0_len.set(AES128_KEY_SIZE as u32);
+
+        while !regs.irq_stat.is_set(Irq::RESULT_AVAIL) {}
+        let failed =
+            regs.irq_stat.is_set(Irq::DMA_BUS_ERR) || regs.irq_stat.is_set(Irq::KEY_ST_WR_ERR);
+        regs.irq_clr
+            .write(Irq::RESULT_AVAIL::SET + Irq::DMA_IN_DONE::SET);
+        regs.alg_sel.set(0);
+        unsafe {
+            KEY = [0; 4];
+        }
+
+        if failed || regs.key_written_area.get() & (1 << area) == 0 {
+            ReturnCode::FAIL
+        } else {
+            ReturnCode::SUCCESS
+        }
+    }
+
+    /// Starts the DMA from `source` through the engine to `dest`.
+    fn start(&self, source: *const u8, dest: *mut u8, len: usize) {
+        let mode = match self.mode.get() {
+            Mode::Ctr => AesCtl::CTR::SET + AesCtl::CTR_WIDTH::Bits128,
+            Mode::Cbc => AesCtl::CBC::SET,
+        };
+        self.configure(
+            KEY_AREA,
+            self.context.get(),
+            mode + AesCtl::DIR.val(self.encrypting.get() as u32),
+            0,
+            len,
+        );
+        self.start_dma(source, dest, len);
+    }
+
+    /// Prepares the engine for an operation over `auth_len` bytes of
+    /// authenticated data and `len` bytes of data, with the key in
+    /// `key_area`.
+    fn configure(
+        &self,
+        key_area: u32,
+        iv: [u32; 4],
+        mode: FieldValue<u32, AesCtl::Register>,
+        auth_len: usize,
+        len: usize,
+    ) {
+        let regs = &*self.registers;
+
+        regs.alg_sel.write(AlgSel::AES::SET);
+        regs.irq_clr
+            .write(Irq::RESULT_AVAIL::SET + Irq::DMA_IN_DONE::SET);
+
+        regs.key_read_area.write(KeyReadArea::NUMBER.val(key_area));
+        while regs.key_read_area.is_set(KeyReadArea::BUSY) {}
+
+        for (register, word) in regs.aes_iv.iter().zip(iv.iter()) {
+            register.set(*word);
+        }
+        regs.aes_ctl.write(mode + AesCtl::SAVE_CONTEXT::SET);
+        regs.aes_data_len0.set(len as u32);
+        regs.aes_data_len1.set(0);
+        regs.aes_auth_len.set(auth_len as u32);
+
+        regs.irq_en.write(Irq::RESULT_AVAIL::SET);
+    }
+
+    /// Feeds `len` bytes of authenticated data at `source` to the engine.
+    /// They are not written out. If a message follows, the interrupt for the
+    /// end of the input DMA starts it.
+    fn feed_auth_data(&self, source: *const u8, len: usize) {
+        let regs = &*self.registers;
+        if self.ccm_message.is_some() {
+            regs.irq_en
+                .write(Irq::RESULT_AVAIL::SET + Irq::DMA_IN_DONE::SET);
+        }
+        regs.dma_ch0_ctl.write(DmaCtl::EN::SET);
+        regs.dma_ch0_ext_addr.set(source as u32);
+        regs.dma_ch0_len.set(len as u32);
+    }
+
+    fn start_dma(&self, source: *const u8, dest: *mut u8, len: usize) {
+        let regs = &*self.registers;
+        // Writing the lengths starts the transfers
+        regs.dma_ch0_ctl.write(DmaCtl::EN::SET);
+        regs.dma_ch0_ext_addr.set(source as u32);
+        regs.dma_ch0_len.set(len as u32);
+        regs.dma_ch1_ctl.write(DmaCtl::EN::SET);
+        regs.dma_ch1_ext_addr.set(dest as u32);
+        regs.dma_ch1_len.set(len as u32);
+    }
+
+    /// Starts the CCM* operation on `buf`, or on the `m_len` bytes at `m_off`
+    /// in CTR mode if there is no tag.
+    fn start_ccm(
+        &self,
+        buf: &mut [u8],
+        a_off: usize,
+        m_off: usize,
+        m_len: usize,
+        mic_len: usize,
+        confidential: bool,
+        encrypting: bool,
+    ) -> ReturnCode {
+        // The counter blocks are flags (L - 1) | nonce | counter, starting
+        // from 0 for the tag
+        let mut counter = [0; AES128_BLOCK_SIZE];
+        counter[0] = (CCM_L - 1) as u8;
+        counter[1..1 + CCM_NONCE_LENGTH].copy_from_slice(&self.nonce.get());
+        let data = buf[m_off..].as_mut_ptr();
+
+        if mic_len == 0 {
+            // Only CCM* with encryption can do without a tag, which leaves
+            // it as CTR mode from the first counter after the tag's
+            if !confidential || m_len == 0 {
+                return ReturnCode::EINVAL;
+            }
+            counter[AES128_BLOCK_SIZE - 1] = 1;
+            self.configure(
+                CCM_KEY_AREA,
+                Aes::iv_words(&counter),
+                AesCtl::CTR::SET + AesCtl::CTR_WIDTH::Bits128 + AesCtl::DIR.val(encrypting as u32),
+                0,
+                m_len,
+            );
+            self.start_dma(data, data, m_len);
+            return ReturnCode::SUCCESS;
+        }
+
+        if mic_len < 4 || mic_len > 16 || mic_len % 2 != 0 {
+            return ReturnCode::EINVAL;
+        }
+        // Without confidentiality, the message is only authenticated
+        let (auth_len, len) = if confidential {
+            (m_off - a_off, m_len)
+        } else {
+            (m_off + m_len - a_off, 0)
+        };
+        if auth_len == 0 && len == 0 {
+            return ReturnCode::EINVAL;
+        }
+
+        self.configure(
+            CCM_KEY_AREA,
+            Aes::iv_words(&counter),
+            AesCtl::CCM::SET
+                + AesCtl::CCM_L.val(CCM_L as u32 - 1)
+                + AesCtl::CCM_M.val((mic_len as u32 - 2) / 2)
+                + AesCtl::CTR::SET
+                + AesCtl::CTR_WIDTH::Bits128
+                + AesCtl::DIR.val(encrypting as u32),
+            auth_len,
+            len,
+        );
+        if auth_len > 0 {
+            if len > 0 {
+                self.ccm_message.set((m_off, len));
+            }
+            self.feed_auth_data(buf[a_off..].as_ptr(), auth_len);
+        } else {
+            self.start_dma(data, data, len);
+        }
+        ReturnCode::SUCCESS
+    }
+
+    fn iv_words(iv: &[u8; AES128_BLOCK_SIZE]) -> [u32; 4] {
+        let mut words = [0; 4];
+        for (word, bytes) in words.iter_mut().zip(iv.chunks(4)) {
+            *word = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+        }
+        words
+    }
+
+    /// Writes the tag after the message, or checks the one there, and
+    /// returns the buffer to the CCM* client.
+    fn ccm_done(&self, buf: &'static mut [u8], failed: bool) {
+        let regs = &*self.registers;
+        let (mic_off, mic_len) = self.mic.get();
+
+        let mut tag = [0; AES128_BLOCK_SIZE];
+        if mic_len > 0 {
+            while !regs.aes_ctl.is_set(AesCtl::SAVED_CONTEXT_RDY) {}
+            for (bytes, register) in tag.chunks_mut(4).zip(regs.aes_tag_out.iter()) {
+                bytes.copy_from_slice(&register.get().to_le_bytes());
+            }
+        }
+        regs.alg_sel.set(0);
+
+        let mic = &mut buf[mic_off..mic_off + mic_len];
+        let tag_is_valid = if failed {
+            false
+        } else if self.ccm_encrypting.get() {
+            mic.copy_from_slice(&tag[..mic_len]);
+            true
+        } else {
+            constant_time_eq(mic, &tag[..mic_len])
+        };
+        let result = if failed {
+            ReturnCode::FAIL
+        } else {
+            ReturnCode::SUCCESS
+        };
+        self.ccm_client
+            .map(move |client| client.crypt_done(buf, result, tag_is_valid));
+    }
+
+    pub fn handle_interrupt(&self) {
+        let regs = &*self.registers;
+        let failed = regs.irq_stat.is_set(Irq::DMA_BUS_ERR);
+
+        // The authenticated data of a CCM* operation is in, so its message
+        // follows
+        if let Some((m_off, len)) = self.ccm_message.take() {
+            if !failed {
+                regs.irq_clr.write(Irq::DMA_IN_DONE::SET);
+                regs.irq_en.write(Irq::RESULT_AVAIL::SET);
+                self.ccm_buf.map(|buf| {
+                    let data = buf[m_off..].as_mut_ptr();
+                    self.start_dma(data, data, len);
+                });
+                return;
+            }
+        }
+
+        regs.irq_en.set(0);
+        regs.irq_clr
+            .write(Irq::RESULT_AVAIL::SET + Irq::DMA_IN_DONE::SET);
+
+        if let Some(buf) = self.ccm_buf.take() {
+            self.ccm_done(buf, failed);
+            return;
+        }
+
+        // Keep the counter or chaining value for the next call
+        if regs.aes_ctl.is_set(AesCtl::SAVED_CONTEXT_RDY) {
+            let mut context = [0; 4];
+            for (word, register) in context.iter_mut().zip(regs.aes_iv.iter()) {
+                *word = register.get();
+            }
+            self.context.set(context);
+        }
+        regs.alg_sel.set(0);
+
+        self.dest.take().map(|dest| {
+            let source = self.source.take();
+            self.client
+                .map(move |client| client.crypt_done(source, dest));
+        });
+    }
+}
+
+impl<'a> symmetric_encryption::AES128<'a> for Aes<'a> {
+    fn enable(&self) {
+        // The crypto engine resides in the peripheral power domain
+        if !prcm::Power::is_enabled(prcm::PowerDomain::Peripherals) {
+            prcm::Power::enable_domain(prcm::PowerDomain::Peripherals);
+        }
+        prcm::Clock::enable_crypto();
+
+        let regs = &*self.registers;
+        regs.irq_type.write(IrqType::LEVEL::SET);
+        regs.irq_en.set(0);
+    }
+
+    fn disable(&self) {
+        let regs = &*self.registers;
+        regs.irq_en.set(0);
+        regs.alg_sel.set(0);
+    }
+
+    fn set_client(&'a self, client: &'a symmetric_encryption::Client<'a>) {
+        self.client.set(client);
+    }
+
+    fn set_key(&self, key: &[u8]) -> ReturnCode {
+        self.load_key(KEY_AREA, key)
+    }
+
+    fn set_iv(&self, iv: &[u8]) -> ReturnCode {
+        if iv.len() != AES128_BLOCK_SIZE {
+            return ReturnCode::EINVAL;
+        }
+        let mut block = [0; AES128_BLOCK_SIZE];
+        block.copy_from_slice(iv);
+        self.iv.set(Aes::iv_words(&block));
+        ReturnCode::SUCCESS
+    }
+
+    fn start_message(&self) {
+        if !self.busy() {
+            self.context.set(self.iv.get());
+        }
+    }
+
+    fn crypt(
+        &'a self,
+        source: Option<&'a mut [u8]>,
+        dest: &'a mut [u8],
+        start_index: usize,
+        stop_index: usize,
+    ) -> Option<(ReturnCode, Option<&'a mut [u8]>, &'a mut [u8])> {
+        if self.busy() {
+            return Some((ReturnCode::EBUSY, source, dest));
+        }
+        if start_index > stop_index
+            || stop_index > dest.len()
+            || (stop_index - start_index) % AES128_BLOCK_SIZE != 0
+        {
+            return Some((ReturnCode::EINVAL, source, dest));
+        }
+        let len = stop_index - start_index;
+        if source.as_ref().map_or(false, |source| source.len() != len) {
+            return Some((ReturnCode::EINVAL, source, dest));
+        }
+
+        let output = dest[start_index..].as_mut_ptr();
+        let input = source.as_ref().map_or(output as *const u8, |s| s.as_ptr());
+        self.dest.replace(dest);
+        if let Some(source) = source {
+            self.source.replace(source);
+        }
+
+        self.start(input, output, len);
+        None
+    }
+}
+
+impl<'a> symmetric_encryption::AES128Ctr for Aes<'a> {
+    fn set_mode_aes128ctr(&self, encrypting: bool) {
+        self.mode.set(Mode::Ctr);
+        self.encrypting.set(encrypting);
+    }
+}
+
+impl<'a> symmetric_encryption::AES128CBC for Aes<'a> {
+    fn set_mode_aes128cbc(&self, encrypting: bool) {
+        self.mode.set(Mode::Cbc);
+        self.encrypting.set(encrypting);
+    }
+}
+
+impl<'a> symmetric_encryption::AES128CCM<'a> for Aes<'a> {
+    fn set_client(&'a self, client: &'a symmetric_encryption::CCMClient) {
+        self.ccm_client.set(client);
+    }
+
+    fn set_key(&self, key: &[u8]) -> ReturnCode {
+        self.load_key(CCM_KEY_AREA, key)
+    }
+
+    fn set_nonce(&self, nonce: &[u8]) -> ReturnCode {
+        if nonce.len() != CCM_NONCE_LENGTH {
+            return ReturnCode::EINVAL;
+        }
+        let mut new_nonce = [0; CCM_NONCE_LENGTH];
+        new_nonce.copy_from_slice(nonce);
+        self.nonce.set(new_nonce);
+        ReturnCode::SUCCESS
+    }
+
+    fn crypt(
+        &self,
+        buf: &'static mut [u8],
+        a_off: usize,
+        m_off: usize,
+        m_len: usize,
+        mic_len: usize,
+        confidential: bool,
+        encrypting: bool,
+    ) -> (ReturnCode, Option<&'static mut [u8]>) {
+        if self.busy() {
+            return (ReturnCode::EBUSY, Some(buf));
+        }
+        if !(a_off <= m_off && m_off + m_len + mic_len <= buf.len()) {
+            return (ReturnCode::EINVAL, Some(buf));
+        }
+
+        let result = self.start_ccm(buf, a_off, m_off, m_len, mic_len, confidential, encrypting);
+        if result != ReturnCode::SUCCESS {
+            return (result, Some(buf));
+        }
+        self.mic.set((m_off + m_len, mic_len));
+        self.ccm_encrypting.set(encrypting);
+        self.ccm_buf.replace(buf);
+        (ReturnCode::SUCCESS, None)
+    }
+}
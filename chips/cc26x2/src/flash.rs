@@ -0,0 +1,281 @@
+//! Flash controller, cc26x2 family
+//!
+//! Pages are the 8 KB sectors of the flash. Reads copy from the memory
+//! mapped flash, while erasing and programming go through the flash
+//! functions of the ROM driver library, which block until the operation
+//! completes. The flash cannot be read meanwhile, so interrupts are disabled
+//! and the VIMS cache and line buffers are turned off for the duration, and
+//! the cache comes back empty. Completions are signalled from a deferred
+//! call.
+//!
+//! The last sector holds the customer configuration (CCFG), which this
+//! driver refuses to write or erase, as a bad CCFG can lock up the chip.
+
+use core::cell::Cell;
+use core::ops::{Index, IndexMut};
+use core::slice;
+use kernel::common::cells::{OptionalCell, TakeCell};
+use kernel::common::deferred_call::DeferredCall;
+use kernel::common::registers::{register_bitfields, ReadOnly, ReadWrite};
+use kernel::common::StaticRef;
+use kernel::hil;
+use kernel::ReturnCode;
+
+use crate::deferred_call_tasks::DeferredCallTask;
+use crate::memory_map::{FLASHMEM_BASE, FLASH_BASE, VIMS_BASE};
+use crate::rom;
+
+#[repr(C)]
+struct FlashRegisters {
+    _reserved0: [u32; 9],
+    cfg: ReadWrite<u32, Config::Register>,
+    _syscode_start: ReadWrite<u32>,
+    flash_size: ReadOnly<u32, FlashSize::Register>,
+}
+
+#[repr(C)]
+struct VimsRegisters {
+    stat: ReadOnly<u32, VimsStatus::Register>,
+    ctl: ReadWrite<u32, VimsControl::Register>,
+}
+
+register_bitfields![
+    u32,
+    Config [
+        DIS_STANDBY OFFSET(1) NUMBITS(1) []
+    ],
+    FlashSize [
+        SECTORS OFFSET(0) NUMBITS(8) []
+    ],
+    VimsStatus [
+        MODE_CHANGING OFFSET(3) NUMBITS(1) [],
+        MODE OFFSET(0) NUMBITS(2) [
+            Gpram = 0x0,
+            Cache = 0x1,
+            Off = 0x3
+        ]
+    ],
+    VimsControl [
+        IDCODE_LB_DIS OFFSET(5) NUMBITS(1) [],
+        SYSBUS_LB_DIS OFFSET(4) NUMBITS(1) [],
+        MODE OFFSET(0) NUMBITS(2) [
+            Gpram = 0x0,
+            Cache = 0x1,
+            Off = 0x3
+        ]
+    ]
+];
+
+const FLASH_REGS: StaticRef<FlashRegisters> =
+    unsafe { StaticRef::new(FLASH_BASE as *const FlashRegisters) };
+
+const VIMS_REGS: StaticRef<VimsRegisters> =
+    unsafe { StaticRef::new(VIMS_BASE as *const VimsRegisters) };
+
+static DEFERRED_CALL: DeferredCall<DeferredCallTask> =
+    unsafe { DeferredCall::new(DeferredCallTask::Flash) };
+
+pub const PAGE_SIZE: usize = 8192;
+
+/// A buffer the size of a flash page, which users of the
+/// `hil::flash::Flash` interface pass to it.
+///
+/// ```
+/// static mut PAGEBUFFER: Cc26x2Page = Cc26x2Page::new();
+/// ```
+pub struct Cc26x2Page(pub [u8; PAGE_SIZE]);
+
+impl Cc26x2Page {
+    pub const fn new() -> Cc26x2Page {
+        Cc26x2Page([0; PAGE_SIZE])
+    }
+}
+
+impl Index<usize> for Cc26x2Page {
+    type Output = u8;
+
+    fn index(&self, idx: usize) -> &u8 {
+        &self.0[idx]
+    }
+}
+
+impl IndexMut<usize> for Cc26x2Page {
+    fn index_mut(&mut self, idx: usize) -> &mut u8 {
+        &mut self.0[idx]
+    }
+}
+
+impl AsMut<[u8]> for Cc26x2Page {
+    fn as_mut(&mut self) -> &mut [u8] {
+        &mut self.0
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum State {
+    Ready,
+    Read,
+    Write,
+    Erase,
+}
+
+pub static mut FLASH: Flash = Flash::new();
+
+pub struct Flash {
+    registers: StaticRef<FlashRegisters>,
+    vims: StaticRef<VimsRegisters>,
+    client: OptionalCell<&'static hil::flash::Client<Flash>>,
+    buffer: TakeCell<'static, Cc26x2Page>,
+    state: Cell<State>,
+    /// Whether the operation to signal succeeded
+    succeeded: Cell<bool>,
+}
+
+impl Flash {
+    const fn new() -> Flash {
+        Flash {
+            registers: FLASH_REGS,
+            vims: VIMS_REGS,
+            client: OptionalCell::empty(),
+            buffer: TakeCell::empty(),
+            state: Cell::new(State::Ready),
+            succeeded: Cell::new(false),
+        }
+    }
+
+    /// The number of pages of the flash, including the CCFG page.
+    pub fn num_pages(&self) -> usize {
+        self.registers.flash_size.read(FlashSize::SECTORS) as usize
+    }
+
+    fn address(page_number: usize) -> usize {
+        FLASHMEM_BASE + page_number * PAGE_SIZE
+    }
+
+    /// Returns whether `page_number` exists and is not the CCFG page.
+    fn is_writable(&self, page_number: usize) -> bool {
+        page_number + 1 < self.num_pages()
+    }
+
+    /// Runs the ROM function `operation`, which returns 0 on success, with
+    /// the flash out of use by anything else. Returns whether it succeeded.
+    fn program<F: FnOnce(&rom::FLASH_API) -> u32>(&self, operation: F) -> bool {
+        let vims = &*self.vims;
+        unsafe {
+            cortexm4::support::atomic(|| {
+                let ctl = vims.ctl.get();
+                // In GPRAM mode the cache holds RAM, not flash contents
+                if vims.ctl.matches_all(VimsControl::MODE::Cache) {
+                    vims.ctl.modify(VimsControl::MODE::Off);
+                    while !vims.stat.matches_all(VimsStatus::MODE::Off) {}
+                }
+                vims.ctl
+                    .modify(VimsControl::IDCODE_LB_DIS::SET + VimsControl::SYSBUS_LB_DIS::SET);
+
+                let status = operation(rom::flash_api());
+
+                // The ROM functions may disable standby of the flash bank
+                self.registers.cfg.modify(Config::DIS_STANDBY::CLEAR);
+                vims.ctl.set(ctl);
+                while vims.stat.is_set(VimsStatus::MODE_CHANGING) {}
+                status == 0
+            })
+        }
+    }
+
+    fn erase_sector(&self, page_number: usize) -> bool {
+        let address = Self::address(page_number) as u32;
+        self.program(|api| unsafe { (api.sector_erase)(address) })
+    }
+
+    fn complete(&self, state: State, succeeded: bool) {
+        self.state.set(state);
+        self.succeeded.set(succeeded);
+        DEFERRED_CALL.set();
+    }
+
+    pub fn handle_interrupt(&self) {
+        let state = self.state.get();
+        self.state.set(State::Ready);
+        let error = if self.succeeded.get() {
+            hil::flash::Error::CommandComplete
+        } else {
+            hil::flash::Error::FlashError
+        };
+
+        match state {
+            State::Read => {
+                self.client.map(|client| {
+                    self.buffer
+                        .take()
+                        .map(|buffer| client.read_complete(buffer, error));
+                });
+            }
+            State::Write => {
+                self.client.map(|client| {
+                    self.buffer
+                        .take()
+                        .map(|buffer| client.write_complete(buffer, error));
+                });
+            }
+            State::Erase => {
+                self.client.map(|client| client.erase_complete(error));
+            }
+            State::Ready => {}
+        }
+    }
+}
+
+impl<C: hil::flash::Client<Self>> hil::flash::HasClient<'static, C> for Flash {
+    fn set_client(&self, client: &'static C) {
+        self.client.set(client);
+    }
+}
+
+impl hil::flash::Flash for Flash {
+    type Page = Cc26x2Page;
+
+    fn read_page(&self, page_number: usize, buf: &'static mut Self::Page) -> ReturnCode {
+        if self.state.get() != State::Ready {
+            return ReturnCode::EBUSY;
+        }
+        if page_number >= self.num_pages() {
+            return ReturnCode::EINVAL;
+        }
+        let page =
+            unsafe { slice::from_raw_parts(Self::address(page_number) as *const u8, PAGE_SIZE) };
+        buf.0.copy_from_slice(page);
+        self.buffer.replace(buf);
+        self.complete(State::Read, true);
+        ReturnCode::SUCCESS
+    }
+
+    fn write_page(&self, page_number: usize, buf: &'static mut Self::Page) -> ReturnCode {
+        if self.state.get() != State::Ready {
+            return ReturnCode::EBUSY;
+        }
+        if !self.is_writable(page_number) {
+            return ReturnCode::EINVAL;
+        }
+        // Programming only clears bits, so the page is erased first
+        let address = Self::address(page_number) as u32;
+        let succeeded = self.erase_sector(page_number)
+            && self
+                .program(|api| unsafe { (api.program)(buf.0.as_ptr(), address, PAGE_SIZE as u32) });
+        self.buffer.replace(buf);
+        self.complete(State::Write, succeeded);
+        ReturnCode::SUCCESS
+    }
+
+    fn erase_page(&self, page_number: usize) -> ReturnCode {
+        if self.state.get() != State::Ready {
+            return ReturnCode::EBUSY;
+        }
+        if !self.is_writable(page_number) {
+            return ReturnCode::EINVAL;
+        }
+        let succeeded = self.erase_sector(page_number);
+        self.complete(State::Erase, succeeded);
+        ReturnCode::SUCCESS
+    }
+}
@@ -4,6 +4,7 @@
 //!
 //! Configures the GPIO pins, and interfaces with the HIL for gpio.
 
+use core::cell::Cell;
 use core::ops::{Index, IndexMut};
 use kernel::common::cells::OptionalCell;
 use kernel::common::registers::{FieldValue, ReadWrite, WriteOnly};
@@ -28,7 +29,9 @@ const GPIO_BASE: StaticRef<GpioRegisters> =
 
 #[repr(C)]
 struct GpioRegisters {
-    _reserved0: [u8; 0x90],
+    _reserved0: [u8; 0x80],
+    pub dout: ReadWrite<u32>,
+    _reserved_dout: [u8; 0xC],
     pub dout_set: WriteOnly<u32>,
     _reserved1: [u8; 0xC],
     pub dout_clr: WriteOnly<u32>,
@@ -42,6 +45,23 @@ struct GpioRegisters {
     pub evflags: ReadWrite<u32>,
 }
 
+/// The level an input changes to that wakes the chip up from shutdown
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum WakeupPolarity {
+    Low,
+    High,
+}
+
+/// How strongly an output drives its pin. `Min`, `Med` and `Max` are the
+/// strengths set in the AON IOC, and `Auto` follows the battery voltage.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum DriveStrength {
+    Auto,
+    Min,
+    Med,
+    Max,
+}
+
 pub struct GPIOPin {
     registers: StaticRef<GpioRegisters>,
     ioc_registers: StaticRef<ioc::Registers>,
@@ -63,6 +83,14 @@ impl GPIOPin {
 
     pub fn set_client(&self, client: &'static gpio::Client) {
         self.client.set(client);
+        let port = unsafe { &PORT };
+        port.clients.set(port.clients.get() | self.pin_mask);
+
+        // Unmask the interrupt if it was enabled before the client registered
+        let pin_ioc = &self.ioc_registers.cfg[self.pin];
+        if !pin_ioc.matches_all(ioc::Config::EDGE_DET::None) {
+            pin_ioc.modify(ioc::Config::EDGE_IRQ_EN::SET);
+        }
     }
 
     pub fn handle_interrupt(&self) {
@@ -71,6 +99,18 @@ impl GPIOPin {
         });
     }
 
+    /// Whether an edge was detected on the pin since its event was last
+    /// cleared.
+    pub fn is_pending(&self) -> bool {
+        self.registers.evflags.get() & self.pin_mask != 0
+    }
+
+    /// Clear the event of the pin, leaving those of the other pins.
+    pub fn clear_pending(&self) {
+        // Bits are cleared by writing 1 to them
+        self.registers.evflags.set(self.pin_mask);
+    }
+
     fn toggle(&self) -> bool {
         let regs = &*self.registers;
         regs.dout_tgl.set(self.pin_mask);
@@ -109,18 +149,53 @@ impl GPIOPin {
     }
 
     // Rewrite of using the IOC_STD_OUTPUT macro
-    fn standard_input(&self, port_id: FieldValue<u32, ioc::Config::Register>) {
+    fn standard_input(
+        &self,
+        function: ioc::Function,
+        port_id: FieldValue<u32, ioc::Config::Register>,
+    ) -> Result<(), ioc::PinmuxError> {
+        self.claim(function)?;
         self.standard_io(port_id, ioc::Config::INPUT_EN::SET);
+        Ok(())
     }
 
     // Rewrite of using the IOC_STD_OUTPUT macro
-    fn standard_output(&self, port_id: FieldValue<u32, ioc::Config::Register>) {
+    fn standard_output(
+        &self,
+        function: ioc::Function,
+        port_id: FieldValue<u32, ioc::Config::Register>,
+    ) -> Result<(), ioc::PinmuxError> {
+        self.claim(function)?;
         self.standard_io(port_id, ioc::Config::INPUT_EN::CLEAR);
+        Ok(())
+    }
+
+    /// Records in `ioc::PINMUX` that the pin is routed to `function`, unless
+    /// it is routed to another function already.
+    fn claim(&self, function: ioc::Function) -> Result<(), ioc::PinmuxError> {
+        unsafe { ioc::PINMUX.claim(self.pin, function) }
+    }
+
+    /// The function the pin is routed to, if any.
+    pub fn function(&self) -> Option<ioc::Function> {
+        unsafe { ioc::PINMUX.function(self.pin) }
+    }
+
+    /// Hands the pin back: disconnects it from its function, with its input
+    /// and output disabled, so that it can be routed to another function.
+    pub fn release(&self) {
+        let regs = &*self.registers;
+        regs.doe.set(regs.doe.get() & !self.pin_mask);
+        let pin_ioc = &self.ioc_registers.cfg[self.pin];
+        pin_ioc.write(ioc::Config::PORT_ID::GPIO + ioc::Config::PULL::None);
+        unsafe { ioc::PINMUX.release(self.pin) };
     }
 
-    pub fn enable_gpio(&self) {
+    pub fn enable_gpio(&self) -> Result<(), ioc::PinmuxError> {
+        self.claim(ioc::Function::Gpio)?;
         let pin_ioc = &self.ioc_registers.cfg[self.pin];
         pin_ioc.modify(ioc::Config::PORT_ID::GPIO);
+        Ok(())
     }
 
     fn enable_output(&self) {
@@ -144,12 +219,128 @@ impl GPIOPin {
             hil::gpio::InterruptEdge::EitherEdge => ioc::Config::EDGE_DET::BothEdges,
         };
 
-        pin_ioc.modify(ioc_edge_mode + ioc::Config::EDGE_IRQ_EN::SET);
+        // Events are still detected on a pin without a client, but its
+        // interrupt stays masked until one registers
+        if self.client.is_some() {
+            pin_ioc.modify(ioc_edge_mode + ioc::Config::EDGE_IRQ_EN::SET);
+        } else {
+            pin_ioc.modify(ioc_edge_mode + ioc::Config::EDGE_IRQ_EN::CLEAR);
+        }
+    }
+
+    /// Set how the output drives the pin. An open output also enables the
+    /// input, so that the pin reads the level of the line. Routing the pin to
+    /// a peripheral with an `enable_*` function resets the mode.
+    pub fn set_io_mode(&self, mode: gpio::IoMode) {
+        let pin_ioc = &self.ioc_registers.cfg[self.pin];
+        match mode {
+            gpio::IoMode::PushPull => pin_ioc.modify(ioc::Config::IO_MODE::Normal),
+            gpio::IoMode::OpenDrain => {
+                pin_ioc.modify(ioc::Config::IO_MODE::OpenDrain + ioc::Config::INPUT_EN::SET)
+            }
+            gpio::IoMode::OpenSource => {
+                pin_ioc.modify(ioc::Config::IO_MODE::OpenSource + ioc::Config::INPUT_EN::SET)
+            }
+        }
+    }
+
+    pub fn io_mode(&self) -> gpio::IoMode {
+        match self.ioc_registers.cfg[self.pin].read_as_enum(ioc::Config::IO_MODE) {
+            Some(ioc::Config::IO_MODE::Value::OpenDrain)
+            | Some(ioc::Config::IO_MODE::Value::OpenDrainInverted) => gpio::IoMode::OpenDrain,
+            Some(ioc::Config::IO_MODE::Value::OpenSource)
+            | Some(ioc::Config::IO_MODE::Value::OpenSourceInverted) => gpio::IoMode::OpenSource,
+            _ => gpio::IoMode::PushPull,
+        }
+    }
+
+    fn is_output_enabled(&self) -> bool {
+        self.registers.doe.get() & self.pin_mask != 0
+    }
+
+    fn set_output_enabled(&self, enabled: bool) {
+        let regs = &*self.registers;
+        if enabled {
+            regs.doe.set(regs.doe.get() | self.pin_mask);
+        } else {
+            regs.doe.set(regs.doe.get() & !self.pin_mask);
+        }
     }
 
     pub fn disable_interrupt(&self) {
         let pin_ioc = &self.ioc_registers.cfg[self.pin];
-        pin_ioc.modify(ioc::Config::EDGE_IRQ_EN::CLEAR);
+        pin_ioc.modify(ioc::Config::EDGE_DET::None + ioc::Config::EDGE_IRQ_EN::CLEAR);
+    }
+
+    /// Wakes the chip up from shutdown, see `prcm::Power::shutdown()`, when
+    /// the input goes to `polarity`. The pin must be an input, and should be
+    /// pulled to the other level. Configuring the function of the pin
+    /// afterwards disables the wake up again.
+    pub fn enable_wakeup(&self, polarity: WakeupPolarity) {
+        let pin_ioc = &self.ioc_registers.cfg[self.pin];
+        pin_ioc.modify(match polarity {
+            WakeupPolarity::Low => ioc::Config::WAKEUP_CFG::WakeupGoingLow,
+            WakeupPolarity::High => ioc::Config::WAKEUP_CFG::WakeupGoingHigh,
+        });
+    }
+
+    pub fn disable_wakeup(&self) {
+        let pin_ioc = &self.ioc_registers.cfg[self.pin];
+        pin_ioc.modify(ioc::Config::WAKEUP_CFG::NoWakeup);
+    }
+
+    /// Set how strongly the output drives the pin. A stronger drive gives
+    /// faster edges, for fast signals or long wires. Routing the pin to a
+    /// peripheral with an `enable_*` function resets the strength, so boards
+    /// set it after that.
+    pub fn set_drive_strength(&self, strength: DriveStrength) {
+        let pin_ioc = &self.ioc_registers.cfg[self.pin];
+        pin_ioc.modify(match strength {
+            DriveStrength::Auto => ioc::Config::DRIVE_STRENGTH::Auto,
+            DriveStrength::Min => ioc::Config::DRIVE_STRENGTH::Min,
+            DriveStrength::Med => ioc::Config::DRIVE_STRENGTH::Med,
+            DriveStrength::Max => ioc::Config::DRIVE_STRENGTH::Max,
+        });
+    }
+
+    pub fn drive_strength(&self) -> DriveStrength {
+        match self.ioc_registers.cfg[self.pin].read_as_enum(ioc::Config::DRIVE_STRENGTH) {
+            Some(ioc::Config::DRIVE_STRENGTH::Value::Min) => DriveStrength::Min,
+            Some(ioc::Config::DRIVE_STRENGTH::Value::Med) => DriveStrength::Med,
+            Some(ioc::Config::DRIVE_STRENGTH::Value::Max) => DriveStrength::Max,
+            _ => DriveStrength::Auto,
+        }
+    }
+
+    /// Slow the edges of the output down, which lowers the noise it couples
+    /// into neighbouring signals, for outputs such as LEDs that switch
+    /// rarely. Like the drive strength, it is reset by the `enable_*`
+    /// functions.
+    pub fn set_slew_rate_reduction(&self, reduce: bool) {
+        let pin_ioc = &self.ioc_registers.cfg[self.pin];
+        if reduce {
+            pin_ioc.modify(ioc::Config::SLEW_RED::SET);
+        } else {
+            pin_ioc.modify(ioc::Config::SLEW_RED::CLEAR);
+        }
+    }
+
+    pub fn is_slew_rate_reduced(&self) -> bool {
+        self.ioc_registers.cfg[self.pin].is_set(ioc::Config::SLEW_RED)
+    }
+
+    /// Enable the hysteresis (Schmitt trigger) of the input, so that a noisy
+    /// or slowly changing signal, such as that of a mechanical button, reads
+    /// and interrupts once per change rather than many times. Like the drive
+    /// strength, it is reset by the `enable_*` functions.
+    pub fn enable_input_hysteresis(&self) {
+        let pin_ioc = &self.ioc_registers.cfg[self.pin];
+        pin_ioc.modify(ioc::Config::HYST_EN::SET);
+    }
+
+    pub fn disable_input_hysteresis(&self) {
+        let pin_ioc = &self.ioc_registers.cfg[self.pin];
+        pin_ioc.modify(ioc::Config::HYST_EN::CLEAR);
     }
 
     fn set_i2c_input(&self, port_id: FieldValue<u32, ioc::Config::Register>) {
@@ -168,13 +359,17 @@ impl GPIOPin {
     }
 
     /// Configures pin for I2C SDA
-    pub fn enable_i2c_sda(&self) {
+    pub fn enable_i2c_sda(&self) -> Result<(), ioc::PinmuxError> {
+        self.claim(ioc::Function::I2cSda)?;
         self.set_i2c_input(ioc::Config::PORT_ID::I2C_MSSDA);
+        Ok(())
     }
 
     /// Configures pin for I2C SDA
-    pub fn enable_i2c_scl(&self) {
+    pub fn enable_i2c_scl(&self) -> Result<(), ioc::PinmuxError> {
+        self.claim(ioc::Function::I2cScl)?;
         self.set_i2c_input(ioc::Config::PORT_ID::I2C_MSSCL);
+        Ok(())
     }
 
     fn pwm_output(&self, port_id: FieldValue<u32, ioc::Config::Register>) {
@@ -192,77 +387,158 @@ impl GPIOPin {
         );
     }
 
-    // Configures pin for PWM
-    // In addition, The PORT_EVENT must be connected to the timer periperhal
-    pub fn enable_pwm(&self, pwm: pwm::Timer) {
-        let port_id;
-        match pwm {
+    /// Connects the event of this pin to `timer` and returns the port ID
+    /// that connects the pin to the event.
+    fn route_to_timer(&self, timer: pwm::Timer) -> FieldValue<u32, ioc::Config::Register> {
+        match timer {
             pwm::Timer::GPT0A => {
                 event::REG.gpt0a_sel.write(event::Gpt0A::EVENT::PORT_EVENT0);
-                port_id = ioc::Config::PORT_ID::PORT_EVENT0;
+                ioc::Config::PORT_ID::PORT_EVENT0
             }
             pwm::Timer::GPT0B => {
                 event::REG.gpt0b_sel.write(event::Gpt0B::EVENT::PORT_EVENT1);
-                port_id = ioc::Config::PORT_ID::PORT_EVENT1;
+                ioc::Config::PORT_ID::PORT_EVENT1
             }
             pwm::Timer::GPT1A => {
                 event::REG.gpt1a_sel.write(event::Gpt1A::EVENT::PORT_EVENT2);
-                port_id = ioc::Config::PORT_ID::PORT_EVENT2;
+                ioc::Config::PORT_ID::PORT_EVENT2
             }
             pwm::Timer::GPT1B => {
                 event::REG.gpt1b_sel.write(event::Gpt1B::EVENT::PORT_EVENT3);
-                port_id = ioc::Config::PORT_ID::PORT_EVENT3;
+                ioc::Config::PORT_ID::PORT_EVENT3
             }
             pwm::Timer::GPT2A => {
                 event::REG.gpt2a_sel.write(event::Gpt2A::EVENT::PORT_EVENT4);
-                port_id = ioc::Config::PORT_ID::PORT_EVENT4;
+                ioc::Config::PORT_ID::PORT_EVENT4
             }
             pwm::Timer::GPT2B => {
                 event::REG.gpt2b_sel.write(event::Gpt2B::EVENT::PORT_EVENT5);
-                port_id = ioc::Config::PORT_ID::PORT_EVENT5;
+                ioc::Config::PORT_ID::PORT_EVENT5
             }
             pwm::Timer::GPT3A => {
                 event::REG.gpt3a_sel.write(event::Gpt3A::EVENT::PORT_EVENT6);
-                port_id = ioc::Config::PORT_ID::PORT_EVENT6;
+                ioc::Config::PORT_ID::PORT_EVENT6
             }
             pwm::Timer::GPT3B => {
                 event::REG.gpt3b_sel.write(event::Gpt3B::EVENT::PORT_EVENT7);
-                port_id = ioc::Config::PORT_ID::PORT_EVENT7;
+                ioc::Config::PORT_ID::PORT_EVENT7
             }
         }
+    }
+
+    // Configures pin for PWM
+    // In addition, The PORT_EVENT must be connected to the timer periperhal
+    pub fn enable_pwm(&self, pwm: pwm::Timer) -> Result<(), ioc::PinmuxError> {
+        self.claim(ioc::Function::Pwm(pwm))?;
+        let port_id = self.route_to_timer(pwm);
         self.pwm_output(port_id);
+        Ok(())
+    }
+
+    /// Configures pin as the input of `timer`, whose edges it timestamps with
+    /// `capture::CAPTURE`.
+    pub fn enable_capture(&self, timer: pwm::Timer) -> Result<(), ioc::PinmuxError> {
+        self.claim(ioc::Function::Capture(timer))?;
+        let port_id = self.route_to_timer(timer);
+        self.standard_io(port_id, ioc::Config::INPUT_EN::SET);
+        Ok(())
     }
 
     /// Configures pin for UART0 receive (RX).
-    pub fn enable_uart0_rx(&self) {
-        self.standard_input(ioc::Config::PORT_ID::UART0_RX);
+    pub fn enable_uart0_rx(&self) -> Result<(), ioc::PinmuxError> {
+        self.standard_input(ioc::Function::Uart0Rx, ioc::Config::PORT_ID::UART0_RX)
     }
 
     // Configures pin for UART0 transmit (TX).
-    pub fn enable_uart0_tx(&self) {
-        self.standard_output(ioc::Config::PORT_ID::UART0_TX);
+    pub fn enable_uart0_tx(&self) -> Result<(), ioc::PinmuxError> {
+        self.standard_output(ioc::Function::Uart0Tx, ioc::Config::PORT_ID::UART0_TX)
+    }
+
+    /// Configures pin for UART0 clear to send (CTS), for flow control.
+    pub fn enable_uart0_cts(&self) -> Result<(), ioc::PinmuxError> {
+        self.standard_input(ioc::Function::Uart0Cts, ioc::Config::PORT_ID::UART0_CTS)
+    }
+
+    /// Configures pin for UART0 request to send (RTS), for flow control.
+    pub fn enable_uart0_rts(&self) -> Result<(), ioc::PinmuxError> {
+        self.standard_output(ioc::Function::Uart0Rts, ioc::Config::PORT_ID::UART0_RTS)
     }
 
     // Configures pin for UART1 receive (RX).
-    pub fn enable_uart1_rx(&self) {
-        self.standard_input(ioc::Config::PORT_ID::UART1_RX);
+    pub fn enable_uart1_rx(&self) -> Result<(), ioc::PinmuxError> {
+        self.standard_input(ioc::Function::Uart1Rx, ioc::Config::PORT_ID::UART1_RX)
     }
 
     // Configures pin for UART1 transmit (TX).
-    pub fn enable_uart1_tx(&self) {
-        self.standard_output(ioc::Config::PORT_ID::UART1_TX);
+    pub fn enable_uart1_tx(&self) -> Result<(), ioc::PinmuxError> {
+        self.standard_output(ioc::Function::Uart1Tx, ioc::Config::PORT_ID::UART1_TX)
+    }
+
+    /// Configures pin for UART1 clear to send (CTS), for flow control.
+    pub fn enable_uart1_cts(&self) -> Result<(), ioc::PinmuxError> {
+        self.standard_input(ioc::Function::Uart1Cts, ioc::Config::PORT_ID::UART1_CTS)
+    }
+
+    /// Configures pin for UART1 request to send (RTS), for flow control.
+    pub fn enable_uart1_rts(&self) -> Result<(), ioc::PinmuxError> {
+        self.standard_output(ioc::Function::Uart1Rts, ioc::Config::PORT_ID::UART1_RTS)
+    }
+
+    /// Configures pin for SSI0 receive, which is MOSI in slave mode.
+    pub fn enable_ssi0_rx(&self) -> Result<(), ioc::PinmuxError> {
+        self.standard_input(ioc::Function::Ssi0Rx, ioc::Config::PORT_ID::SSI0_RX)
+    }
+
+    /// Configures pin for SSI0 transmit, which is MISO in slave mode.
+    pub fn enable_ssi0_tx(&self) -> Result<(), ioc::PinmuxError> {
+        self.standard_output(ioc::Function::Ssi0Tx, ioc::Config::PORT_ID::SSI0_TX)
+    }
+
+    /// Configures pin for the SSI0 chip select input of slave mode.
+    pub fn enable_ssi0_slave_fss(&self) -> Result<(), ioc::PinmuxError> {
+        self.standard_input(ioc::Function::Ssi0Fss, ioc::Config::PORT_ID::SSI0_FSS)
+    }
+
+    /// Configures pin for the SSI0 clock input of slave mode.
+    pub fn enable_ssi0_slave_clk(&self) -> Result<(), ioc::PinmuxError> {
+        self.standard_input(ioc::Function::Ssi0Clk, ioc::Config::PORT_ID::SSI0_CLK)
+    }
+
+    /// Configures pin for SSI1 receive, which is MOSI in slave mode.
+    pub fn enable_ssi1_rx(&self) -> Result<(), ioc::PinmuxError> {
+        self.standard_input(ioc::Function::Ssi1Rx, ioc::Config::PORT_ID::SSI1_RX)
+    }
+
+    /// Configures pin for SSI1 transmit, which is MISO in slave mode.
+    pub fn enable_ssi1_tx(&self) -> Result<(), ioc::PinmuxError> {
+        self.standard_output(ioc::Function::Ssi1Tx, ioc::Config::PORT_ID::SSI1_TX)
     }
 
-    pub fn enable_analog_input(&self) {
-        self.standard_input(ioc::Config::PORT_ID::AUX_DOMAIN_IO);
+    /// Configures pin for the SSI1 chip select input of slave mode.
+    pub fn enable_ssi1_slave_fss(&self) -> Result<(), ioc::PinmuxError> {
+        self.standard_input(ioc::Function::Ssi1Fss, ioc::Config::PORT_ID::SSI1_FSS)
     }
 
-    pub fn enable_analog_output(&self) {
-        self.standard_output(ioc::Config::PORT_ID::AUX_DOMAIN_IO);
+    /// Configures pin for the SSI1 clock input of slave mode.
+    pub fn enable_ssi1_slave_clk(&self) -> Result<(), ioc::PinmuxError> {
+        self.standard_input(ioc::Function::Ssi1Clk, ioc::Config::PORT_ID::SSI1_CLK)
+    }
+
+    /// Configures pin as an input of the analog domain, e.g. of the ADC.
+    /// Only DIO23 to DIO30 are analog capable.
+    pub fn enable_analog_input(&self) -> Result<(), ioc::PinmuxError> {
+        self.standard_input(ioc::Function::Analog, ioc::Config::PORT_ID::AUX_DOMAIN_IO)
+    }
+
+    /// Configures pin as an output of the analog domain.
+    /// Only DIO23 to DIO30 are analog capable.
+    pub fn enable_analog_output(&self) -> Result<(), ioc::PinmuxError> {
+        self.standard_output(ioc::Function::Analog, ioc::Config::PORT_ID::AUX_DOMAIN_IO)
     }
 
     // configure a pin as an input for 32kHz system clock
-    pub fn enable_32khz_system_clock_input(&self) {
+    pub fn enable_32khz_system_clock_input(&self) -> Result<(), ioc::PinmuxError> {
+        self.claim(ioc::Function::Clock32k)?;
         let pin_ioc = &self.ioc_registers.cfg[self.pin];
         pin_ioc.write(
             ioc::Config::PORT_ID::AON_CLK32K
@@ -275,6 +551,7 @@ impl GPIOPin {
                 + ioc::Config::WAKEUP_CFG::CLEAR
                 + ioc::Config::INPUT_EN::SET,
         );
+        Ok(())
     }
 }
 
@@ -299,18 +576,26 @@ impl gpio::Configure for GPIOPin {
     }
 
     fn make_output(&self) -> gpio::Configuration {
-        self.enable_gpio();
-        // Disable input in the io configuration
-        self.enable_output();
+        // Leave a pin that is routed to a peripheral as it is
+        if self.enable_gpio().is_err() {
+            return self.configuration();
+        }
+        // Disable input in the io configuration, unless the output is open
+        // and reads the line
+        if self.io_mode() == gpio::IoMode::PushPull {
+            self.enable_output();
+        }
         // Enable data output
-        let regs = &*self.registers;
-        regs.doe.set(regs.doe.get() | self.pin_mask);
-        gpio::Configuration::Output
+        self.set_output_enabled(true);
+        self.configuration()
     }
 
     fn make_input(&self) -> gpio::Configuration {
-        self.enable_gpio();
+        if self.enable_gpio().is_err() {
+            return self.configuration();
+        }
         self.enable_input();
+        self.set_output_enabled(false);
         gpio::Configuration::Input
     }
 
@@ -328,12 +613,13 @@ impl gpio::Configure for GPIOPin {
 
     fn is_output(&self) -> bool {
         let pin_ioc = &self.ioc_registers.cfg[self.pin];
-        !pin_ioc.is_set(ioc::Config::INPUT_EN)
+        !pin_ioc.is_set(ioc::Config::INPUT_EN) || self.is_output_enabled()
     }
 
     fn disable_output(&self) -> gpio::Configuration {
         // Disable output for this chip by making it an input
         self.enable_input();
+        self.set_output_enabled(false);
         self.configuration()
     }
 
@@ -359,6 +645,31 @@ impl gpio::Configure for GPIOPin {
     }
 }
 
+impl gpio::ConfigureInputOutput for GPIOPin {
+    fn make_input_output(&self) -> gpio::Configuration {
+        if self.enable_gpio().is_err() {
+            return gpio::Configure::configuration(self);
+        }
+        self.enable_input();
+        self.set_output_enabled(true);
+        gpio::Configuration::InputOutput
+    }
+
+    fn is_input_output(&self) -> bool {
+        gpio::Configure::is_input(self) && self.is_output_enabled()
+    }
+}
+
+impl gpio::ConfigureIoMode for GPIOPin {
+    fn set_io_mode(&self, mode: gpio::IoMode) {
+        GPIOPin::set_io_mode(self, mode);
+    }
+
+    fn io_mode(&self) -> gpio::IoMode {
+        GPIOPin::io_mode(self)
+    }
+}
+
 impl gpio::Input for GPIOPin {
     fn read(&self) -> bool {
         let regs = &*self.registers;
@@ -394,12 +705,14 @@ impl gpio::Interrupt for GPIOPin {
     }
 
     fn is_pending(&self) -> bool {
-        unimplemented!("Not supported by chip?");
+        GPIOPin::is_pending(self)
     }
 }
 
 pub struct Port {
     nvic: &'static nvic::Nvic,
+    /// The pins with a client, one bit per pin
+    clients: Cell<u32>,
     pins: [GPIOPin; NUM_PINS],
 }
 
@@ -417,20 +730,43 @@ impl IndexMut<usize> for Port {
     }
 }
 
+/// The outputs of the pins, which the GPIO module loses when the peripheral
+/// power domain is powered off.
+pub struct PortState {
+    dout: u32,
+    doe: u32,
+}
+
 impl Port {
+    pub fn save(&self) -> PortState {
+        let regs = GPIO_BASE;
+        PortState {
+            dout: regs.dout.get(),
+            doe: regs.doe.get(),
+        }
+    }
+
+    /// Restores the outputs saved with `save()`, which must happen before
+    /// the IOs are unfrozen so that the pins do not glitch.
+    pub fn restore(&self, state: &PortState) {
+        let regs = GPIO_BASE;
+        regs.dout.set(state.dout);
+        regs.doe.set(state.doe);
+    }
+
     pub fn handle_interrupt(&self) {
         let regs = GPIO_BASE;
-        let mut evflags = regs.evflags.get();
-        // Clear all interrupts by setting their bits to 1 in evflags
-        regs.evflags.set(evflags);
-
-        let mut count = 0;
-        while evflags != 0 && count < self.pins.len() {
-            if (evflags & 0b1) != 0 {
-                self.pins[count].handle_interrupt();
-            }
-            count += 1;
-            evflags >>= 1;
+        // The events of pins without a client stay latched for the client to
+        // find once it registers. Their interrupts are masked, so they do not
+        // fire again in the meantime.
+        let mut events = regs.evflags.get() & self.clients.get();
+
+        while events != 0 {
+            let pin = &self.pins[events.trailing_zeros() as usize];
+            pin.clear_pending();
+            pin.handle_interrupt();
+            // Clear the lowest set bit
+            events &= events - 1;
         }
 
         self.nvic.clear_pending();
@@ -443,6 +779,7 @@ const GPIO_NVIC: nvic::Nvic =
 
 pub static mut PORT: Port = Port {
     nvic: &GPIO_NVIC,
+    clients: Cell::new(0),
     pins: [
         GPIOPin::new(0),
         GPIOPin::new(1),
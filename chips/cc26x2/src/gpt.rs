@@ -55,10 +55,10 @@ pub struct Registers {
     pub ctl: ReadWrite<u32, Ctl::Register>,
     sync: ReadWrite<u32, Sync::Register>,
     _offset0: ReadOnly<u32>,
-    int_mask: ReadWrite<u32, Interrupt::Register>,
+    pub int_mask: ReadWrite<u32, Interrupt::Register>,
     int_raw: ReadWrite<u32, Interrupt::Register>,
     mask_int_stat: ReadWrite<u32, Interrupt::Register>,
-    int_clr: ReadWrite<u32, Interrupt::Register>,
+    pub int_clr: ReadWrite<u32, Interrupt::Register>,
     pub timer_a_load: ReadWrite<u32, Value32::Register>,
     pub timer_b_load: ReadWrite<u32, Value32::Register>,
     pub timer_a_match: ReadWrite<u32, Value32::Register>,
@@ -67,8 +67,8 @@ pub struct Registers {
     pub timer_b_prescale: ReadWrite<u32, Prescale::Register>,
     pub timer_a_prescale_match: ReadWrite<u32, Prescale::Register>,
     pub timer_b_prescale_match: ReadWrite<u32, Prescale::Register>,
-    timer_a: ReadWrite<u32>,
-    timer_b: ReadWrite<u32>,
+    pub timer_a: ReadWrite<u32>,
+    pub timer_b: ReadWrite<u32>,
     timer_a_value: ReadOnly<u32>,
     timer_b_value: ReadOnly<u32>,
     _offset1: ReadOnly<u32>,
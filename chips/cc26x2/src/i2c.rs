@@ -1,4 +1,9 @@
 //! I2C driver, cc26x2 family
+//!
+//! The controller is driven one byte at a time from its interrupt. Writes
+//! followed by reads keep the bus between the two, turning it around with a
+//! repeated start, as register reads of most sensors need. Transfers without
+//! data only address the device, which tells whether it is present.
 
 use core::cmp;
 use kernel::common::cells::{MapCell, OptionalCell};
@@ -169,29 +174,69 @@ impl<'a> I2CMaster<'a> {
     fn read_byte(&self, first: bool, last: bool) {
         self.registers.mstat_ctrl.ctrl().write(
             Control::RUN.val(1)
-                + Control::RUN.val(1)
                 + Control::ACK.val(!last as u32)
                 + Control::START.val(first as u32)
                 + Control::STOP.val(last as u32),
         );
     }
 
+    /// Addresses the device without transferring data. An interrupt becomes pending once the
+    /// device acknowledged its address or not.
+    fn quick_command(&self) {
+        self.registers
+            .mstat_ctrl
+            .ctrl()
+            .write(Control::RUN.val(1) + Control::START.val(1) + Control::STOP.val(1));
+    }
+
+    /// Ends a transfer that failed with a NACK by sending a STOP condition.
+    fn release_bus(&self) {
+        self.registers.mstat_ctrl.ctrl().write(Control::STOP.val(1));
+    }
+
+    /// Starts a transfer of `len` bytes of `buf`, or only addresses the device if `len` is 0.
+    fn start(&self, mode: TransferMode, buf: &'static mut [u8], len: usize) {
+        if len == 0 {
+            self.quick_command();
+        } else {
+            match mode {
+                TransferMode::Transmit => self.write_byte(buf[0], true, len == 1),
+                TransferMode::TransmitThenReceive(_) => self.write_byte(buf[0], true, false),
+                TransferMode::Receive => self.read_byte(true, len == 1),
+            }
+        }
+        // A transfer without data completes on the first interrupt, as a transmission
+        self.transfer.put(Transfer {
+            mode: if len == 0 {
+                TransferMode::Transmit
+            } else {
+                mode
+            },
+            buf: buf,
+            index: 0,
+            len: len,
+        });
+    }
+
     pub fn handle_interrupt(&self) {
         self.registers.micr.write(Interrupt::IM::SET);
         if let Some(mut transfer) = self.transfer.take() {
             let status = self.registers.mstat_ctrl.stat();
 
             if status.is_set(Status::ADRACK_N) {
+                self.release_bus();
                 self.client.map(move |client| {
                     client.command_complete(transfer.buf, i2c::Error::AddressNak);
                 });
                 return;
             } else if status.is_set(Status::DATACK_N) {
+                self.release_bus();
                 self.client.map(move |client| {
                     client.command_complete(transfer.buf, i2c::Error::DataNak);
                 });
                 return;
             } else if status.is_set(Status::ARBLST) {
+                // The controller has already left the bus to the winner
                 self.client.map(move |client| {
                     client.command_complete(transfer.buf, i2c::Error::ArbitrationLost);
                 });
@@ -229,11 +274,9 @@ impl<'a> I2CMaster<'a> {
                 TransferMode::TransmitThenReceive(read_len) => {
                     transfer.index += 1;
                     if transfer.len > transfer.index {
-                        self.write_byte(
-                            transfer.buf[transfer.index],
-                            false,
-                            transfer.len == transfer.index + 1,
-                        );
+                        // The bus is kept after the last byte, for the
+                        // repeated start of the read
+                        self.write_byte(transfer.buf[transfer.index], false, false);
                         self.transfer.put(transfer);
                     } else {
                         transfer.index = 0;
@@ -288,19 +331,23 @@ impl<'a> i2c::I2CMaster for I2CMaster<'a> {
     }
 
     fn write_read(&self, addr: u8, data: &'static mut [u8], write_len: u8, read_len: u8) {
+        let write_len = cmp::min(write_len as usize, data.len());
+        if write_len == 0 {
+            self.read(addr, data, read_len);
+            return;
+        }
+        if read_len == 0 {
+            self.write(addr, data, write_len as u8);
+            return;
+        }
         self.registers
             .msa
             .write(Address::RS::Transmit + Address::SA.val(addr as u32));
-        let len = cmp::min(write_len as usize, data.len());
-        if len > 0 {
-            self.write_byte(data[0], true, len == 1);
-            self.transfer.put(Transfer {
-                mode: TransferMode::TransmitThenReceive(read_len as usize),
-                buf: data,
-                index: 0,
-                len: len,
-            });
-        }
+        self.start(
+            TransferMode::TransmitThenReceive(read_len as usize),
+            data,
+            write_len,
+        );
     }
 
     fn write(&self, addr: u8, data: &'static mut [u8], len: u8) {
@@ -308,30 +355,17 @@ impl<'a> i2c::I2CMaster for I2CMaster<'a> {
             .msa
             .write(Address::RS::Transmit + Address::SA.val(addr as u32));
         let len = cmp::min(len as usize, data.len());
-        if len > 0 {
-            self.write_byte(data[0], true, len == 1);
-            self.transfer.put(Transfer {
-                mode: TransferMode::Transmit,
-                buf: data,
-                index: 0,
-                len: len,
-            });
-        }
+        self.start(TransferMode::Transmit, data, len);
     }
 
     fn read(&self, addr: u8, buffer: &'static mut [u8], len: u8) {
-        self.registers
-            .msa
-            .write(Address::RS::Receive + Address::SA.val(addr as u32));
         let len = cmp::min(len as usize, buffer.len());
-        if len > 0 {
-            self.read_byte(true, len == 1);
-            self.transfer.put(Transfer {
-                mode: TransferMode::Receive,
-                buf: buffer,
-                index: 0,
-                len: len,
-            });
-        }
+        let rs = if len == 0 {
+            Address::RS::Transmit
+        } else {
+            Address::RS::Receive
+        };
+        self.registers.msa.write(rs + Address::SA.val(addr as u32));
+        self.start(TransferMode::Receive, buffer, len);
     }
 }
@@ -0,0 +1,556 @@
+//! IEEE 802.15.4 radio driver for the cc26x2 family
+//!
+//! The radio is run by the radio core (see `rfc`). Once it is started, the
+//! radio keeps a receive operation running in the background, which filters
+//! frames by address and acknowledges them automatically. Frames are
+//! transmitted by a foreground operation, which runs on the channel of the
+//! receive operation while it is paused. When a frame requests an
+//! acknowledgement, the transmission is followed by an operation that waits
+//! for it, and the client is told whether it came.
+//!
+//! Frames are received into an entry of a data queue that the radio core
+//! writes to, and are copied from there into the receive buffer of the
+//! client.
+//!
+//! Usage
+//! -----
+//!
+//! ```rust
+//! cc26x2::rfc::RFC.set_client(&cc26x2::ieee802154_radio::RADIO);
+//! cc26x2::ieee802154_radio::RADIO.set_transmit_client(mac);
+//! cc26x2::ieee802154_radio::RADIO.set_receive_client(mac, &mut RX_BUF);
+//! cc26x2::ieee802154_radio::RADIO.start();
+//! ```
+
+use core::cell::Cell;
+use kernel::common::cells::{OptionalCell, TakeCell, VolatileCell};
+use kernel::hil::radio;
+use kernel::ReturnCode;
+
+use crate::rfc::{self, CommandHeader, DataQueue, RxEntry};
+
+const CMD_IEEE_RX: u16 = 0x2801;
+const CMD_IEEE_TX: u16 = 0x2C01;
+const CMD_IEEE_RX_ACK: u16 = 0x2C02;
+
+/// Status of IEEE 802.15.4 operations that are done successfully
+const IEEE_DONE_OK: u16 = 0x2400;
+/// Status of the wait for an acknowledgement when one was received, without
+/// or with its frame pending bit set
+const IEEE_DONE_ACK: u16 = 0x2403;
+const IEEE_DONE_ACKPEND: u16 = 0x2404;
+
+/// Bit of the first byte of the frame control field that requests an
+/// acknowledgement
+const FCF_ACK_REQUEST: u8 = 1 << 5;
+/// How long to wait for an acknowledgement, in ticks of the 4 MHz radio
+/// timer: the 54 symbols of macAckWaitDuration, of 16 us each
+const ACK_TIMEOUT: u32 = 54 * 16 * 4;
+
+/// `mode` of the radio setup for IEEE 802.15.4
+const MODE_IEEE802154: u8 = 0x01;
+
+const DEFAULT_TX_POWER: i8 = 0;
+
+const MIN_CHANNEL: u8 = 11;
+const MAX_CHANNEL: u8 = 26;
+
+/// Options of the receive operation: frames rejected by the filter are
+/// flushed, and the FCS and a status byte with the CRC result are kept
+/// after each frame.
+const RX_CONFIG: u8 = 0x2A;
+/// Options of the frame filter: frames are filtered by address, filtering
+/// stops the reception of a frame, and frames are acknowledged
+/// automatically. Frames of all versions up to 3 are accepted.
+const FRAME_FILTER: u16 = 0x0307;
+/// All frame types are accepted
+const FRAME_TYPES: u8 = 0xFF;
+
+/// Size of the data of the entry: the length, the frame and the status
+const RX_ENTRY_LEN: usize = 1 + radio::MAX_FRAME_SIZE + 1;
+
+#[allow(dead_code)]
+#[repr(C)]
+struct CmdIeeeRx {
+    header: CommandHeader,
+    start_trigger: VolatileCell<u8>,
+    condition: VolatileCell<u8>,
+    channel: VolatileCell<u8>,
+    rx_config: VolatileCell<u8>,
+    rx_queue: VolatileCell<u32>,
+    output: VolatileCell<u32>,
+    frame_filt_opt: VolatileCell<u16>,
+    frame_types: VolatileCell<u8>,
+    cca_opt: VolatileCell<u8>,
+    cca_rssi_thr: VolatileCell<i8>,
+    _reserved0: VolatileCell<u8>,
+    num_ext_entries: VolatileCell<u8>,
+    num_short_entries: VolatileCell<u8>,
+    ext_entry_list: VolatileCell<u32>,
+    short_entry_list: VolatileCell<u32>,
+    local_ext_addr: VolatileCell<u64>,
+    local_short_addr: VolatileCell<u16>,
+    local_pan_id: VolatileCell<u16>,
+    _reserved1: VolatileCell<u16>,
+    _reserved2: VolatileCell<u8>,
+    end_trigger: VolatileCell<u8>,
+    end_time: VolatileCell<u32>,
+}
+
+#[allow(dead_code)]
+#[repr(C)]
+struct CmdIeeeTx {
+    header: CommandHeader,
+    start_trigger: VolatileCell<u8>,
+    condition: VolatileCell<u8>,
+    tx_opt: VolatileCell<u8>,
+    payload_len: VolatileCell<u8>,
+    payload: VolatileCell<u32>,
+    time_stamp: VolatileCell<u32>,
+}
+
+#[allow(dead_code)]
+#[repr(C)]
+struct CmdIeeeRxAck {
+    header: CommandHeader,
+    start_trigger: VolatileCell<u8>,
+    condition: VolatileCell<u8>,
+    seq_no: VolatileCell<u8>,
+    end_trigger: VolatileCell<u8>,
+    end_time: VolatileCell<u32>,
+}
+
+pub struct Radio {
+    setup_cmd: rfc::CmdRadioSetup,
+    rx_cmd: CmdIeeeRx,
+    tx_cmd: CmdIeeeTx,
+    rx_ack_cmd: CmdIeeeRxAck,
+    rx_queue: DataQueue,
+    rx_entry: RxEntry<[VolatileCell<u8>; RX_ENTRY_LEN]>,
+    front_end: Cell<u16>,
+    overrides: Cell<&'static [u32]>,
+    tx_client: OptionalCell<&'static radio::TxClient>,
+    rx_client: OptionalCell<&'static radio::RxClient>,
+    config_client: OptionalCell<&'static radio::ConfigClient>,
+    power_client: OptionalCell<&'static radio::PowerClient>,
+    tx_buf: TakeCell<'static, [u8]>,
+    rx_buf: TakeCell<'static, [u8]>,
+    addr: Cell<u16>,
+    addr_long: Cell<[u8; 8]>,
+    pan: Cell<u16>,
+    channel: Cell<u8>,
+    tx_power: Cell<i8>,
+    /// Set when the configuration is committed during a transmission, to
+    /// restart the receive operation with it after the transmission
+    config_pending: Cell<bool>,
+}
+
+pub static mut RADIO: Radio = Radio::new();
+
+impl Radio {
+    const fn new() -> Radio {
+        Radio {
+            setup_cmd: rfc::CmdRadioSetup {
+                header: CommandHeader::new(rfc::CMD_RADIO_SETUP),
+                start_trigger: VolatileCell::new(rfc::trigger::NOW),
+                condition: VolatileCell::new(rfc::condition::NEVER),
+                mode: VolatileCell::new(MODE_IEEE802154),
+                lo_divider: VolatileCell::new(0),
+                config: VolatileCell::new(rfc::DEFAULT_FRONT_END),
+                tx_power: VolatileCell::new(0),
+                reg_override: VolatileCell::new(0),
+            },
+            rx_cmd: CmdIeeeRx {
+                header: CommandHeader::new(CMD_IEEE_RX),
+                start_trigger: VolatileCell::new(rfc::trigger::NOW),
+                condition: VolatileCell::new(rfc::condition::NEVER),
+                channel: VolatileCell::new(0),
+                rx_config: VolatileCell::new(RX_CONFIG),
+                rx_queue: VolatileCell::new(0),
+                output: VolatileCell::new(0),
+                frame_filt_opt: VolatileCell::new(FRAME_FILTER),
+                frame_types: VolatileCell::new(FRAME_TYPES),
+                cca_opt: VolatileCell::new(0),
+                cca_rssi_thr: VolatileCell::new(0),
+                _reserved0: VolatileCell::new(0),
+                num_ext_entries: VolatileCell::new(0),
+                num_short_entries: VolatileCell::new(0),
+                ext_entry_list: VolatileCell::new(0),
+                short_entry_list: VolatileCell::new(0),
+                local_ext_addr: VolatileCell::new(0),
+                local_short_addr: VolatileCell::new(0),
+                local_pan_id: VolatileCell::new(0),
+                _reserved1: VolatileCell::new(0),
+                _reserved2: VolatileCell::new(0),
+                end_trigger: VolatileCell::new(rfc::trigger::NEVER),
+                end_time: VolatileCell::new(0),
+            },
+            tx_cmd: CmdIeeeTx {
+                header: CommandHeader::new(CMD_IEEE_TX),
+                start_trigger: VolatileCell::new(rfc::trigger::NOW),
+                condition: VolatileCell::new(rfc::condition::NEVER),
+                tx_opt: VolatileCell::new(0),
+                payload_len: VolatileCell::new(0),
+                payload: VolatileCell::new(0),
+                time_stamp: VolatileCell::new(0),
+            },
+            rx_ack_cmd: CmdIeeeRxAck {
+                header: CommandHeader::new(CMD_IEEE_RX_ACK),
+                start_trigger: VolatileCell::new(rfc::trigger::NOW),
+                condition: VolatileCell::new(rfc::condition::NEVER),
+                seq_no: VolatileCell::new(0),
+                end_trigger: VolatileCell::new(rfc::trigger::REL_START),
+                end_time: VolatileCell::new(ACK_TIMEOUT),
+            },
+            rx_queue: DataQueue::new(),
+            rx_entry: RxEntry::new([VolatileCell::new(0); RX_ENTRY_LEN]),
+            front_end: Cell::new(rfc::DEFAULT_FRONT_END),
+            overrides: Cell::new(&[rfc::END_OVERRIDE]),
+            tx_client: OptionalCell::empty(),
+            rx_client: OptionalCell::empty(),
+            config_client: OptionalCell::empty(),
+            power_client: OptionalCell::empty(),
+            tx_buf: TakeCell::empty(),
+            rx_buf: TakeCell::empty(),
+            addr: Cell::new(0),
+            addr_long: Cell::new([0; 8]),
+            pan: Cell::new(0),
+            channel: Cell::new(MIN_CHANNEL),
+            tx_power: Cell::new(DEFAULT_TX_POWER),
+            config_pending: Cell::new(false),
+        }
+    }
+
+    fn rfc(&self) -> &'static rfc::RFCore {
+        unsafe { &rfc::RFC }
+    }
+
+    /// Sets the front end configuration of the radio setup and the register
+    /// overrides, which are specific to the board. The overrides end with
+    /// `rfc::END_OVERRIDE`. They take effect when the radio is next started.
+    pub fn set_front_end(&self, config: u16, overrides: &'static [u32]) {
+        self.front_end.set(config);
+        self.overrides.set(overrides);
+    }
+
+    fn power_up(&self) -> ReturnCode {
+        let result = self.rfc().enable(
+            rfc::event::LAST_FG_COMMAND_DONE
+                | rfc::event::RX_ENTRY_DONE
+                | rfc::event::LAST_COMMAND_DONE,
+        );
+        if result != ReturnCode::SUCCESS {
+            return result;
+        }
+
+        let setup = &self.setup_cmd;
+        setup.config.set(self.front_end.get());
+        setup
+            .tx_power
+            .set(rfc::tx_power_setting(rfc::TX_POWER_2_4_GHZ, self.tx_power.get()).unwrap_or(0));
+        setup.reg_override.set(self.overrides.get().as_ptr() as u32);
+        let result = self.rfc().run_command(&setup.header);
+        if result != ReturnCode::SUCCESS || setup.header.status.get() != rfc::status::DONE_OK {
+            self.rfc().disable();
+            return ReturnCode::FAIL;
+        }
+
+        self.start_rx()
+    }
+
+    fn start_rx(&self) -> ReturnCode {
+        self.rx_queue.set_single_entry(&self.rx_entry);
+
+        let rx = &self.rx_cmd;
+        rx.channel.set(self.channel.get());
+        rx.rx_queue.set(&self.rx_queue as *const DataQueue as u32);
+        // The long address is kept most significant byte first
+        rx.local_ext_addr
+            .set(u64::from_be_bytes(self.addr_long.get()));
+        rx.local_short_addr.set(self.addr.get());
+        rx.local_pan_id.set(self.pan.get());
+        self.rfc().send_command(&rx.header)
+    }
+
+    fn stop_rx(&self) {
+        let header = &self.rx_cmd.header;
+        if header.status.get() == rfc::status::IDLE || header.is_done() {
+            return;
+        }
+        if self.rfc().send_direct(rfc::cmd::ABORT) == ReturnCode::SUCCESS {
+            while !header.is_done() {}
+        }
+    }
+
+    fn restart_rx(&self) -> ReturnCode {
+        self.stop_rx();
+        self.start_rx()
+    }
+
+    /// Hands the frame in the receive queue to the client, if there is one.
+    fn receive_frame(&self) {
+        let entry = &self.rx_entry;
+        if entry.status.get() != rfc::entry::FINISHED {
+            return;
+        }
+
+        // The data is the length, the frame with its FCS, and a status byte
+        let len = entry.data[0].get() as usize;
+        if len > radio::MFR_SIZE && len < RX_ENTRY_LEN {
+            let psdu_len = len - 1;
+            let crc_valid = entry.data[len].get() & rfc::RX_CRC_ERROR == 0;
+
+            self.rx_buf.take().map(|buf| {
+                if radio::PSDU_OFFSET + psdu_len > buf.len() {
+                    self.rx_buf.replace(buf);
+                    return;
+                }
+                buf[radio::PSDU_OFFSET - 1] = psdu_len as u8;
+                for (byte, data) in buf[radio::PSDU_OFFSET..radio::PSDU_OFFSET + psdu_len]
+                    .iter_mut()
+                    .zip(entry.data[1..].iter())
+                {
+                    *byte = data.get();
+                }
+
+                let frame_len = psdu_len - radio::MFR_SIZE;
+                if self.rx_client.is_some() {
+                    self.rx_client.map(move |client| {
+                        client.receive(buf, frame_len, crc_valid, ReturnCode::SUCCESS)
+                    });
+                } else {
+                    self.rx_buf.replace(buf);
+                }
+            });
+        }
+        entry.status.set(rfc::entry::PENDING);
+    }
+
+    fn transmit_done(&self) {
+        let result = if self.tx_cmd.header.status.get() == IEEE_DONE_OK {
+            ReturnCode::SUCCESS
+        } else {
+            ReturnCode::FAIL
+        };
+        let acked = self.tx_cmd.header.next_op.get() != 0 && {
+            let status = self.rx_ack_cmd.header.status.get();
+            status == IEEE_DONE_ACK || status == IEEE_DONE_ACKPEND
+        };
+        if self.config_pending.take() {
+            self.restart_rx();
+            self.config_client
+                .map(|client| client.config_done(ReturnCode::SUCCESS));
+        }
+        self.tx_buf.take().map(|buf| {
+            self.tx_client
+                .map(move |client| client.send_done(buf, acked, result));
+        });
+    }
+}
+
+impl rfc::Client for Radio {
+    fn cpe_events(&self, events: u32) {
+        if events & rfc::event::RX_ENTRY_DONE != 0 {
+            self.receive_frame();
+        }
+        if events & rfc::event::LAST_FG_COMMAND_DONE != 0 && self.tx_buf.is_some() {
+            self.transmit_done();
+        }
+        // The receive operation stops if it fails, e.g. on an overflow
+        if events & rfc::event::LAST_COMMAND_DONE != 0
+            && self.rfc().is_on()
+            && self.rx_cmd.header.is_done()
+            && self.tx_buf.is_none()
+        {
+            self.start_rx();
+        }
+    }
+}
+
+impl radio::Radio for Radio {}
+
+impl radio::RadioConfig for Radio {
+    fn initialize(
+        &self,
+        _spi_buf: &'static mut [u8],
+        _reg_write: &'static mut [u8],
+        _reg_read: &'static mut [u8],
+    ) -> ReturnCode {
+        ReturnCode::SUCCESS
+    }
+
+    fn reset(&self) -> ReturnCode {
+        if self.rfc().is_on() {
+            self.rfc().disable();
+        }
+        self.power_up()
+    }
+
+    fn start(&self) -> ReturnCode {
+        if self.rfc().is_on() {
+            return ReturnCode::SUCCESS;
+        }
+        let result = self.power_up();
+        if result == ReturnCode::SUCCESS {
+            self.power_client.map(|client| client.changed(true));
+        }
+        result
+    }
+
+    fn stop(&self) -> ReturnCode {
+        if !self.rfc().is_on() {
+            return ReturnCode::SUCCESS;
+        }
+        if self.tx_buf.is_some() {
+            return ReturnCode::EBUSY;
+        }
+        self.stop_rx();
+        self.rfc().disable();
+        self.power_client.map(|client| client.changed(false));
+        ReturnCode::SUCCESS
+    }
+
+    fn is_on(&self) -> bool {
+        self.rfc().is_on()
+    }
+
+    fn busy(&self) -> bool {
+        self.tx_buf.is_some()
+    }
+
+    fn set_power_client(&self, client: &'static radio::PowerClient) {
+        self.power_client.set(client);
+    }
+
+    fn config_commit(&self) {
+        if !self.rfc().is_on() {
+            // Applied when the radio is started
+            self.config_client
+                .map(|client| client.config_done(ReturnCode::SUCCESS));
+        } else if self.tx_buf.is_some() {
+            self.config_pending.set(true);
+        } else {
+            let result = self.restart_rx();
+            self.config_client.map(|client| client.config_done(result));
+        }
+    }
+
+    fn set_config_client(&self, client: &'static radio::ConfigClient) {
+        self.config_client.set(client);
+    }
+
+    fn get_address(&self) -> u16 {
+        self.addr.get()
+    }
+
+    fn get_address_long(&self) -> [u8; 8] {
+        self.addr_long.get()
+    }
+
+    fn get_pan(&self) -> u16 {
+        self.pan.get()
+    }
+
+    fn get_tx_power(&self) -> i8 {
+        self.tx_power.get()
+    }
+
+    fn get_channel(&self) -> u8 {
+        self.channel.get()
+    }
+
+    fn set_address(&self, addr: u16) {
+        self.addr.set(addr);
+    }
+
+    fn set_address_long(&self, addr: [u8; 8]) {
+        self.addr_long.set(addr);
+    }
+
+    fn set_pan(&self, id: u16) {
+        self.pan.set(id);
+    }
+
+    fn set_tx_power(&self, power: i8) -> ReturnCode {
+        match rfc::tx_power_setting(rfc::TX_POWER_2_4_GHZ, power) {
+            Some(_) => {
+                self.tx_power.set(power);
+                ReturnCode::SUCCESS
+            }
+            None => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    fn set_channel(&self, chan: u8) -> ReturnCode {
+        if chan < MIN_CHANNEL || chan > MAX_CHANNEL {
+            return ReturnCode::EINVAL;
+        }
+        self.channel.set(chan);
+        ReturnCode::SUCCESS
+    }
+}
+
+impl radio::RadioData for Radio {
+    fn set_transmit_client(&self, client: &'static radio::TxClient) {
+        self.tx_client.set(client);
+    }
+
+    fn set_receive_client(&self, client: &'static radio::RxClient, buffer: &'static mut [u8]) {
+        self.rx_client.set(client);
+        self.rx_buf.replace(buffer);
+    }
+
+    fn set_receive_buffer(&self, buffer: &'static mut [u8]) {
+        self.rx_buf.replace(buffer);
+    }
+
+    fn transmit(
+        &self,
+        buf: &'static mut [u8],
+        frame_len: usize,
+    ) -> (ReturnCode, Option<&'static mut [u8]>) {
+        if !self.rfc().is_on() {
+            return (ReturnCode::EOFF, Some(buf));
+        } else if self.tx_buf.is_some() {
+            return (ReturnCode::EBUSY, Some(buf));
+        } else if radio::PSDU_OFFSET + frame_len > buf.len()
+            || frame_len + radio::MFR_SIZE > radio::MAX_FRAME_SIZE
+        {
+            return (ReturnCode::ESIZE, Some(buf));
+        }
+
+        // The radio core adds the PHY header and the FCS
+        let tx = &self.tx_cmd;
+        tx.tx_opt.set(0);
+        tx.payload_len.set(frame_len as u8);
+        tx.payload.set(buf[radio::PSDU_OFFSET..].as_ptr() as u32);
+
+        // Wait for the acknowledgement after a successful transmission, if
+        // the frame requests one. The receive operation in the background
+        // receives it.
+        if buf[radio::PSDU_OFFSET] & FCF_ACK_REQUEST != 0 && frame_len >= 3 {
+            let ack = &self.rx_ack_cmd;
+            ack.header.status.set(rfc::status::IDLE);
+            ack.seq_no.set(buf[radio::PSDU_OFFSET + 2]);
+            tx.header
+                .next_op
+                .set(&ack.header as *const CommandHeader as u32);
+            tx.condition.set(rfc::condition::STOP_ON_FALSE);
+        } else {
+            tx.header.next_op.set(0);
+            tx.condition.set(rfc::condition::NEVER);
+        }
+        let result = self.rfc().send_command(&tx.header);
+        if result != ReturnCode::SUCCESS {
+            return (result, Some(buf));
+        }
+        self.tx_buf.replace(buf);
+        (ReturnCode::SUCCESS, None)
+    }
+
+    fn get_tx_info(&self) -> radio::TxInfo {
+        // Frames are not retransmitted, and the radio core does not report
+        // the RSSI of acknowledgements
+        radio::TxInfo::default()
+    }
+}
@@ -1,8 +1,112 @@
+//! I/O controller (IOC)
+//!
+//! The IOC routes each pin to a peripheral, to the GPIO module or to the
+//! analog domain. `GPIOPin` configures the IOC of its pin with its
+//! `enable_*` functions, and records the function it routes the pin to in
+//! `PINMUX`, so that a pin is never routed to two functions at once: the
+//! second function gets an error, rather than silently taking the pin over
+//! from the first. A driver that is done with a pin `release()`s it, after
+//! which it can be routed to another function.
+
 use crate::gpio;
+use crate::pwm;
+use core::cell::Cell;
 use kernel::common::registers::{register_bitfields, ReadWrite};
 
-pub const MIN_ANALOG_CAPABLE: usize = 23 + 1;
-pub const MAX_ANALOG_CAPABLE: usize = 27;
+/// The pins that connect to the analog domain, DIO23 to DIO30
+pub const MIN_ANALOG_CAPABLE: usize = 23;
+pub const MAX_ANALOG_CAPABLE: usize = 30;
+
+pub fn is_analog_capable(pin: usize) -> bool {
+    pin >= MIN_ANALOG_CAPABLE && pin <= MAX_ANALOG_CAPABLE
+}
+
+/// The function a pin is routed to
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Function {
+    Gpio,
+    Analog,
+    I2cSda,
+    I2cScl,
+    /// The output of a timer
+    Pwm(pwm::Timer),
+    /// The input of a timer
+    Capture(pwm::Timer),
+    Uart0Rx,
+    Uart0Tx,
+    Uart0Cts,
+    Uart0Rts,
+    Uart1Rx,
+    Uart1Tx,
+    Uart1Cts,
+    Uart1Rts,
+    Ssi0Rx,
+    Ssi0Tx,
+    Ssi0Fss,
+    Ssi0Clk,
+    Ssi1Rx,
+    Ssi1Tx,
+    Ssi1Fss,
+    Ssi1Clk,
+    Clock32k,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum PinmuxError {
+    /// The pin is routed to another function already
+    InUse { pin: usize, function: Function },
+    /// The function is analog, and the pin does not connect to the analog
+    /// domain
+    NotAnalogCapable { pin: usize },
+}
+
+pub static mut PINMUX: Pinmux = Pinmux::new();
+
+/// Records the function each pin is routed to.
+pub struct Pinmux {
+    functions: Cell<[Option<Function>; gpio::NUM_PINS]>,
+}
+
+impl Pinmux {
+    const fn new() -> Pinmux {
+        Pinmux {
+            functions: Cell::new([None; gpio::NUM_PINS]),
+        }
+    }
+
+    /// The function `pin` is routed to, if any.
+    pub fn function(&self, pin: usize) -> Option<Function> {
+        self.functions.get()[pin]
+    }
+
+    /// Record that `pin` is routed to `function`. Claiming a pin for the
+    /// function it has already succeeds, so that a driver can configure a
+    /// pin it owns again.
+    pub fn claim(&self, pin: usize, function: Function) -> Result<(), PinmuxError> {
+        if function == Function::Analog && !is_analog_capable(pin) {
+            return Err(PinmuxError::NotAnalogCapable { pin: pin });
+        }
+        let mut functions = self.functions.get();
+        match functions[pin] {
+            Some(current) if current != function => Err(PinmuxError::InUse {
+                pin: pin,
+                function: current,
+            }),
+            _ => {
+                functions[pin] = Some(function);
+                self.functions.set(functions);
+                Ok(())
+            }
+        }
+    }
+
+    /// Record that `pin` is not routed to any function.
+    pub fn release(&self, pin: usize) {
+        let mut functions = self.functions.get();
+        functions[pin] = None;
+        self.functions.set(functions);
+    }
+}
 
 #[repr(C)]
 pub struct Registers {
@@ -22,9 +126,11 @@ register_bitfields![
             OpenSource = 0x6,
             OpenSourceInverted = 0x7
         ],
+        // Wakes the chip up from shutdown when the input changes
         WAKEUP_CFG OFFSET(27) NUMBITS (2) [
+            NoWakeup = 0b00,
             WakeupGoingLow = 0b10,
-            WakeupGoingHigh = 0b00
+            WakeupGoingHigh = 0b11
         ],
         EDGE_IRQ_EN OFFSET(18) NUMBITS(1) [], // Interrupt enable
         EDGE_DET    OFFSET(16) NUMBITS(2) [
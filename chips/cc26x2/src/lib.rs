@@ -3,22 +3,38 @@
 #![crate_name = "cc26x2"]
 #![crate_type = "rlib"]
 
+pub mod adc;
 pub mod aon;
+pub mod aon_batmon;
+pub mod aux;
+pub mod ble_radio;
+pub mod capture;
 pub mod ccfg;
 pub mod chip;
 pub mod crt1;
+pub mod crypto;
+pub mod deferred_call_tasks;
 pub mod event;
+pub mod flash;
 pub mod gpio;
 pub mod gpt;
 pub mod i2c;
+pub mod ieee802154_radio;
 pub mod ioc;
 pub mod memory_map;
+pub mod oscillator;
 pub mod peripheral_interrupts;
 pub mod prcm;
 pub mod pwm;
+pub mod rfc;
 pub mod rom;
 pub mod rtc;
+pub mod sampler;
+pub mod ssi;
+pub mod subghz_radio;
 pub mod trng;
 pub mod uart;
+pub mod udma;
+pub mod wdt;
 
 pub use crate::crt1::init;
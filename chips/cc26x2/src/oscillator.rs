@@ -0,0 +1,238 @@
+//! Oscillator control of the cc26x2 family
+//!
+//! The high frequency clock starts from the internal RC oscillator. The
+//! radio needs the more accurate 48 MHz crystal oscillator, which takes
+//! hundreds of microseconds to start. `request_hf_source()` switches between
+//! the two without waiting: the switch is done from the interrupt that the
+//! PRCM raises once the crystal is stable. The blocking
+//! `switch_to_hf_xosc()` and `switch_to_hf_rcosc()` are for callers that
+//! cannot wait, like standby, which turns the crystal off, so the clock is
+//! switched back to the RC oscillator before it.
+//!
+//! Peripherals whose timing is derived from the clock, like the UARTs,
+//! register as clients with `add_client()`. They are told, from a deferred
+//! call, whenever the source changed since they were last told, so a source
+//! that is switched away from and back again, as around standby, is not
+//! reported.
+//!
+//! ```rust
+//! cc26x2::oscillator::OSC.add_client(&cc26x2::uart::UART0);
+//! cc26x2::oscillator::OSC.request_hf_source(cc26x2::oscillator::HfSource::Xosc);
+//! ```
+
+use crate::deferred_call_tasks::DeferredCallTask;
+use crate::prcm::{self, OscInt};
+use crate::rom;
+use core::cell::Cell;
+use kernel::common::cells::OptionalCell;
+use kernel::common::deferred_call::DeferredCall;
+use kernel::common::registers::{register_bitfields, ReadOnly, ReadWrite};
+use kernel::common::StaticRef;
+use kernel::ReturnCode;
+
+#[repr(C)]
+struct DdiOscRegisters {
+    ctl0: ReadWrite<u32, Ctl0::Register>,
+    _reserved0: [u32; 14],
+    stat0: ReadOnly<u32, Stat0::Register>,
+}
+
+register_bitfields![
+    u32,
+    Ctl0 [
+        SCLK_HF_SRC_SEL OFFSET(0) NUMBITS(1) [
+            RCOSC = 0,
+            XOSC = 1
+        ]
+    ],
+    Stat0 [
+        SCLK_HF_SRC OFFSET(28) NUMBITS(1) [
+            RCOSC = 0,
+            XOSC = 1
+        ],
+        PENDING_SCLK_HF_SWITCHING OFFSET(0) NUMBITS(1) []
+    ]
+];
+
+const DDI0_OSC_BASE: StaticRef<DdiOscRegisters> =
+    unsafe { StaticRef::new(0x400C_A000 as *const DdiOscRegisters) };
+
+static DEFERRED_CALL: DeferredCall<DeferredCallTask> =
+    unsafe { DeferredCall::new(DeferredCallTask::Oscillator) };
+
+/// The number of clients that can be told of source changes
+pub const MAX_CLIENTS: usize = 4;
+
+/// A source of the high frequency clock
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum HfSource {
+    /// The internal RC oscillator
+    Rcosc,
+    /// The 48 MHz crystal oscillator, the 24 MHz crystal doubled
+    Xosc,
+}
+
+impl HfSource {
+    /// The nominal frequency of the clock from this source, in Hz. The RC
+    /// oscillator is trimmed to the same frequency as the crystal, but is
+    /// only accurate to about a percent.
+    pub fn frequency(&self) -> u32 {
+        match *self {
+            HfSource::Rcosc => 48_000_000,
+            HfSource::Xosc => 48_000_000,
+        }
+    }
+}
+
+pub trait HfClockClient {
+    /// The high frequency clock now runs from `source`.
+    fn hf_clock_changed(&self, source: HfSource);
+}
+
+pub static mut OSC: Oscillator = Oscillator::new();
+
+pub struct Oscillator {
+    registers: StaticRef<DdiOscRegisters>,
+    clients: [OptionalCell<&'static HfClockClient>; MAX_CLIENTS],
+    /// Whether a switch waits for the interrupt of the crystal
+    switching: Cell<bool>,
+    /// The source that clients were last told of
+    notified: Cell<HfSource>,
+}
+
+impl Oscillator {
+    const fn new() -> Oscillator {
+        Oscillator {
+            registers: DDI0_OSC_BASE,
+            clients: [
+                OptionalCell::empty(),
+                OptionalCell::empty(),
+                OptionalCell::empty(),
+                OptionalCell::empty(),
+            ],
+            switching: Cell::new(false),
+            notified: Cell::new(HfSource::Rcosc),
+        }
+    }
+
+    /// Registers `client` to be told of changes of the source. Returns
+    /// `ENOMEM` if `MAX_CLIENTS` are registered already.
+    pub fn add_client(&self, client: &'static HfClockClient) -> ReturnCode {
+        match self.clients.iter().find(|slot| slot.is_none()) {
+            Some(slot) => {
+                slot.set(client);
+                ReturnCode::SUCCESS
+            }
+            None => ReturnCode::ENOMEM,
+        }
+    }
+
+    pub fn hf_source(&self) -> HfSource {
+        if self.is_hf_xosc() {
+            HfSource::Xosc
+        } else {
+            HfSource::Rcosc
+        }
+    }
+
+    pub fn is_hf_xosc(&self) -> bool {
+        self.registers.stat0.matches_all(Stat0::SCLK_HF_SRC::XOSC)
+    }
+
+    /// Starts switching the high frequency clock to `source`. The clients
+    /// are told once it is done. Returns `EALREADY` if the clock runs from
+    /// `source`, and `EBUSY` while a switch to the crystal is in progress.
+    pub fn request_hf_source(&self, source: HfSource) -> ReturnCode {
+        if self.switching.get() {
+            return ReturnCode::EBUSY;
+        }
+        if self.hf_source() == source {
+            return ReturnCode::EALREADY;
+        }
+        match source {
+            // The RC oscillator always runs, so the switch is pending at once
+            HfSource::Rcosc => self.switch_to_hf_rcosc(),
+            HfSource::Xosc => {
+                prcm::clear_osc_interrupt(OscInt::HF_SRC);
+                self.registers.ctl0.modify(Ctl0::SCLK_HF_SRC_SEL::XOSC);
+                if self
+                    .registers
+                    .stat0
+                    .is_set(Stat0::PENDING_SCLK_HF_SWITCHING)
+                {
+                    self.finish_switch();
+                } else {
+                    self.switching.set(true);
+                    prcm::enable_osc_interrupt(OscInt::HF_SRC);
+                }
+            }
+        }
+        ReturnCode::SUCCESS
+    }
+
+    /// Switches the high frequency clock to the crystal oscillator, waiting
+    /// for the crystal to be ready.
+    pub fn switch_to_hf_xosc(&self) {
+        if self.is_hf_xosc() {
+            return;
+        }
+        self.registers.ctl0.modify(Ctl0::SCLK_HF_SRC_SEL::XOSC);
+        while !self
+            .registers
+            .stat0
+            .is_set(Stat0::PENDING_SCLK_HF_SWITCHING)
+        {}
+        self.finish_switch();
+    }
+
+    /// Switches the high frequency clock back to the RC oscillator.
+    pub fn switch_to_hf_rcosc(&self) {
+        if !self.is_hf_xosc() {
+            return;
+        }
+        self.registers.ctl0.modify(Ctl0::SCLK_HF_SRC_SEL::RCOSC);
+        while !self
+            .registers
+            .stat0
+            .is_set(Stat0::PENDING_SCLK_HF_SWITCHING)
+        {}
+        self.finish_switch();
+    }
+
+    /// Has the ROM do the pending switch, which waits for a safe time to do
+    /// it, and schedules telling the clients.
+    fn finish_switch(&self) {
+        if self.switching.get() {
+            prcm::disable_osc_interrupt(OscInt::HF_SRC);
+            self.switching.set(false);
+        }
+        unsafe {
+            (rom::HAPI.hf_source_safe_switch)();
+        }
+        DEFERRED_CALL.set();
+    }
+
+    /// The crystal is stable and the switch to it is pending.
+    pub fn handle_interrupt(&self) {
+        if prcm::osc_interrupt_is_set(OscInt::HF_SRC) {
+            prcm::clear_osc_interrupt(OscInt::HF_SRC);
+            // A blocking switch may have done it already
+            if self.switching.get() {
+                self.finish_switch();
+            }
+        }
+    }
+
+    /// Tells the clients of the source, if it changed since they were last
+    /// told.
+    pub fn handle_deferred_call(&self) {
+        let source = self.hf_source();
+        if source == self.notified.get() {
+            return;
+        }
+        self.notified.set(source);
+        for slot in self.clients.iter() {
+            slot.map(|client| client.hf_clock_changed(source));
+        }
+    }
+}
@@ -37,6 +37,7 @@ pub enum NvicIrq {
     AuxCompA = 31,
     AuxAdc = 32,
     Trng = 33,
+    Osc = 34,
     Uart1 = 36
 }
 }
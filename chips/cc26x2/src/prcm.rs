@@ -11,6 +11,13 @@
 //! It also manages the clocks attached to almost every peripheral, which needs to
 //! be enabled before usage.
 //!
+//! The run mode clock gates double as the record of which peripherals are in
+//! use: the MCU only enters standby, which powers off the serial and
+//! peripheral domains and so loses the state of their peripherals, while no
+//! peripheral but the GPIOs has its clock enabled.
+//!
+use crate::aon;
+use crate::oscillator;
 use kernel::common::registers::{register_bitfields, ReadOnly, ReadWrite, WriteOnly};
 use kernel::common::StaticRef;
 
@@ -60,9 +67,9 @@ struct PrcmRegisters {
     pub uart_clk_gate_deep_sleep: ReadWrite<u32, ClockGate2::Register>,
 
     // SSI Clock Gates for run, sleep, and deep sleep modes
-    pub ssi_clk_gate_run: ReadWrite<u32, ClockGate::Register>,
-    pub ssi_clk_gate_sleep: ReadWrite<u32, ClockGate::Register>,
-    pub ssi_clk_gate_deep_sleep: ReadWrite<u32, ClockGate::Register>,
+    pub ssi_clk_gate_run: ReadWrite<u32, ClockGate2::Register>,
+    pub ssi_clk_gate_sleep: ReadWrite<u32, ClockGate2::Register>,
+    pub ssi_clk_gate_deep_sleep: ReadWrite<u32, ClockGate2::Register>,
 
     // I2S Clock Gates for run, sleep, and deep sleep modes
     pub i2s_clk_gate_run: ReadWrite<u32, ClockGate::Register>,
@@ -95,7 +102,7 @@ struct PrcmRegisters {
 
     pub pd_ctl1_cpu: WriteOnly<u32, PowerDomainSingle::Register>,
     pub pd_ctl1_rfc: WriteOnly<u32, PowerDomainSingle::Register>,
-    pub pd_ctl1_vims: WriteOnly<u32, PowerDomainSingle::Register>,
+    pub pd_ctl1_vims: ReadWrite<u32, PowerDomainSingle::Register>,
 
     _reserved8: [ReadOnly<u8>; 0x04],
 
@@ -274,6 +281,7 @@ impl From<u32> for PowerDomain {
 }
 
 #[allow(non_camel_case_types)]
+#[derive(Copy, Clone)]
 pub enum OscInt {
     HF_SRC,
     LF_SRC,
@@ -285,9 +293,116 @@ pub enum OscInt {
     RCOSC_HF,
 }
 
+impl OscInt {
+    fn mask(self) -> u32 {
+        let bit = match self {
+            OscInt::HF_SRC => 7,
+            OscInt::LF_SRC => 6,
+            OscInt::XOSC_DLF => 5,
+            OscInt::XOSC_LF => 4,
+            OscInt::RCOSC_DLF => 3,
+            OscInt::RCOSC_LF => 2,
+            OscInt::XOSC_HF => 1,
+            OscInt::RCOSC_HF => 0,
+        };
+        1 << bit
+    }
+}
+
+/// Lets the oscillator event `int` raise the OSC_COMB interrupt.
+pub fn enable_osc_interrupt(int: OscInt) {
+    let regs = PRCM_BASE;
+    regs.osc_imsc.set(regs.osc_imsc.get() | int.mask());
+}
+
+pub fn disable_osc_interrupt(int: OscInt) {
+    let regs = PRCM_BASE;
+    regs.osc_imsc.set(regs.osc_imsc.get() & !int.mask());
+}
+
+/// Returns whether the oscillator event `int` happened since it was last
+/// cleared, whether or not it is enabled.
+pub fn osc_interrupt_is_set(int: OscInt) -> bool {
+    PRCM_BASE.osc_ris.get() & int.mask() != 0
+}
+
+pub fn clear_osc_interrupt(int: OscInt) {
+    PRCM_BASE.osc_icr.set(int.mask());
+}
+
 pub struct Power(());
 
 impl Power {
+    /// Enters standby until an AON wake up event, like an RTC channel or a
+    /// GPIO edge. The RF core, serial and peripheral domains are powered
+    /// off, the CPU domain is kept in retention and the MCU runs from the
+    /// uLDO. On wake up the serial and peripheral domains that were on are
+    /// powered on again, but the peripherals in them need to be set up again.
+    ///
+    /// The caller must make sure no peripheral is in use, freeze the IOs and
+    /// run with interrupts disabled, so that the wake up interrupt is handled
+    /// after this returns.
+    pub unsafe fn standby() {
+        let regs = PRCM_BASE;
+        let serial = Power::is_enabled(PowerDomain::Serial);
+        let peripherals = Power::is_enabled(PowerDomain::Peripherals);
+
+        regs.pd_ctl0.modify(
+            PowerDomain0::RFC_ON::CLEAR
+                + PowerDomain0::SERIAL_ON::CLEAR
+                + PowerDomain0::PERIPH_ON::CLEAR,
+        );
+        // With CPU_ON clear, deep sleep powers off the CPU domain
+        regs.pd_ctl1
+            .modify(PowerDomain1::RFC_ON::CLEAR + PowerDomain1::CPU_ON::CLEAR);
+        acquire_uldo();
+        while Power::is_enabled(PowerDomain::Serial) || Power::is_enabled(PowerDomain::Peripherals)
+        {
+        }
+        // Only power the VIMS along with the CPU
+        let vims = regs.pd_ctl1_vims.get();
+        regs.pd_ctl1_vims.write(PowerDomainSingle::ON::CLEAR);
+        prcm_commit();
+
+        cortexm4::scb::set_sleepdeep();
+        cortexm4::support::wfi();
+        cortexm4::scb::unset_sleepdeep();
+
+        release_uldo();
+        regs.pd_ctl1.modify(PowerDomain1::CPU_ON::SET);
+        regs.pd_ctl1_vims.set(vims);
+        if serial {
+            Power::enable_domain(PowerDomain::Serial);
+        }
+        if peripherals {
+            Power::enable_domain(PowerDomain::Peripherals);
+        }
+        // Power the clock gates of the domains again
+        prcm_commit();
+    }
+
+    /// Enters shutdown, which draws tens of nanoamps, until one of the IOs
+    /// configured with `GPIOPin::enable_wakeup()` changes to its level. All
+    /// state but the IO pads, which keep their levels, is lost: the chip
+    /// wakes up through a reset, after which `aon::AON.woke_from_shutdown()`
+    /// is true and the board needs to call `aon::AON.release_io_latches()`
+    /// once it configured its pins.
+    ///
+    /// A debugger that is attached keeps the chip out of shutdown.
+    pub unsafe fn shutdown() -> ! {
+        cortexm4::support::atomic(|| {
+            // The crystal oscillator is not stopped by the shutdown request
+            oscillator::OSC.switch_to_hf_rcosc();
+            aon::AON.set_io_freeze(true);
+            aon::AON.sync();
+            aon::AON.shutdown();
+            aon::AON.sync();
+            loop {
+                cortexm4::support::wfi();
+            }
+        })
+    }
+
     pub fn enable_domain(domain: PowerDomain) {
         let regs = PRCM_BASE;
 
@@ -357,6 +472,32 @@ impl Power {
 pub struct Clock(());
 
 impl Clock {
+    /// Returns whether any peripheral whose state standby would lose has its
+    /// clock enabled. The GPIOs are left out, as their outputs are kept.
+    pub fn peripherals_requested() -> bool {
+        let regs = PRCM_BASE;
+        regs.rfc_clk_gate.is_set(ClockGate::CLK_EN)
+            || regs.sec_dma_clk_run.matches_any(
+                SECDMAClockGate::DMA_CLK_EN::SET
+                    + SECDMAClockGate::TRNG_CLK_EN::SET
+                    + SECDMAClockGate::CRYPTO_CLK_EN::SET,
+            )
+            || regs.gpt_clk_gate_run.matches_any(
+                ClockGate4::CLK_EN0::SET
+                    + ClockGate4::CLK_EN1::SET
+                    + ClockGate4::CLK_EN2::SET
+                    + ClockGate4::CLK_EN3::SET,
+            )
+            || regs.i2c_clk_gate_run.is_set(ClockGate::CLK_EN)
+            || regs
+                .uart_clk_gate_run
+                .matches_any(ClockGate2::CLK_EN0::SET + ClockGate2::CLK_EN1::SET)
+            || regs
+                .ssi_clk_gate_run
+                .matches_any(ClockGate2::CLK_EN0::SET + ClockGate2::CLK_EN1::SET)
+            || regs.i2s_clk_gate_run.is_set(ClockGate::CLK_EN)
+    }
+
     pub fn enable_gpio() {
         let regs = PRCM_BASE;
         regs.gpio_clk_gate_run.modify(ClockGate::AM_EN::SET);
@@ -375,6 +516,27 @@ impl Clock {
         prcm_commit();
     }
 
+    pub fn enable_crypto() {
+        let regs = PRCM_BASE;
+        regs.sec_dma_clk_run
+            .modify(SECDMAClockGate::CRYPTO_CLK_EN::SET);
+        regs.sec_dma_clk_sleep
+            .modify(SECDMAClockGate::CRYPTO_CLK_EN::SET);
+
+        prcm_commit();
+    }
+
+    /// Enables the uDMA clock for run and sleep mode.
+    pub fn enable_dma() {
+        let regs = PRCM_BASE;
+        regs.sec_dma_clk_run
+            .modify(SECDMAClockGate::DMA_CLK_EN::SET);
+        regs.sec_dma_clk_sleep
+            .modify(SECDMAClockGate::DMA_CLK_EN::SET);
+
+        prcm_commit();
+    }
+
     /// Enables UART clocks for run, sleep and deep sleep mode.
     pub fn enable_uarts() {
         let regs = PRCM_BASE;
@@ -502,6 +664,28 @@ impl Clock {
         prcm_commit();
     }
 
+    /// Enables the clocks of SSI `num` for run, sleep and deep sleep mode.
+    pub fn enable_ssi(num: usize) {
+        let regs = PRCM_BASE;
+
+        match num {
+            0 => {
+                regs.ssi_clk_gate_run.modify(ClockGate2::CLK_EN0::SET);
+                regs.ssi_clk_gate_sleep.modify(ClockGate2::CLK_EN0::SET);
+                regs.ssi_clk_gate_deep_sleep
+                    .modify(ClockGate2::CLK_EN0::SET);
+            }
+            1 => {
+                regs.ssi_clk_gate_run.modify(ClockGate2::CLK_EN1::SET);
+                regs.ssi_clk_gate_sleep.modify(ClockGate2::CLK_EN1::SET);
+                regs.ssi_clk_gate_deep_sleep
+                    .modify(ClockGate2::CLK_EN1::SET);
+            }
+            _ => return,
+        }
+        prcm_commit();
+    }
+
     /// Enables I2C clocks for run, sleep and deep sleep mode.
     pub fn enable_i2c() {
         let regs = PRCM_BASE;
@@ -3,6 +3,22 @@ use enum_primitive::enum_from_primitive;
 
 use crate::gpt;
 use crate::prcm;
+use kernel::hil;
+use kernel::ReturnCode;
+
+/// Frequency of the clock the timers count
+const CLOCK_HZ: usize = 48_000_000;
+
+/// Largest period, in clock cycles, of a 16-bit timer extended by its
+/// 8-bit prescaler
+const MAX_PERIOD: usize = 0xFF_FFFF;
+
+/// Resolution of duty cycles given to `PwmPin::start()`
+const MAX_DUTY_CYCLE: usize = 0xFFFF;
+
+/// The PWM outputs of the eight timer halves, with the timer half as the pin.
+/// A pin of the chip is routed to one of them with `GPIOPin::enable_pwm()`.
+pub static PWM: Pwm = Pwm(());
 
 enum_from_primitive! {
 #[derive(Debug, PartialEq, Clone, Copy)]
@@ -128,4 +144,91 @@ impl<'a> Signal<'a> {
                 + gpt::Mode::REG_UPDATE_MODE::CYCLE,
         );
     }
+
+    /// Sets the period and the on period, in clock cycles. In PWM mode the
+    /// prescaler extends the timer to 24 bits.
+    fn set_counts(&self, period: u32, on_period: u32) {
+        self.prescale.write(gpt::Prescale::RATIO.val(period >> 16));
+        self.timer_load
+            .write(gpt::Value32::SET.val(period & 0xFFFF));
+        self.prescale_match
+            .write(gpt::Prescale::RATIO.val(on_period >> 16));
+        self.timer_match
+            .write(gpt::Value32::SET.val(on_period & 0xFFFF));
+    }
+}
+
+impl<'a> hil::pwm::PwmPin for Signal<'a> {
+    fn start(&self, frequency_hz: usize, duty_cycle: usize) -> ReturnCode {
+        if frequency_hz == 0
+            || frequency_hz > self.get_maximum_frequency_hz()
+            || duty_cycle > MAX_DUTY_CYCLE
+        {
+            return ReturnCode::EINVAL;
+        }
+        let period = CLOCK_HZ / frequency_hz;
+        if period > MAX_PERIOD {
+            return ReturnCode::EINVAL;
+        }
+        let on_period = (period as u64 * duty_cycle as u64 / MAX_DUTY_CYCLE as u64) as u32;
+
+        self.enable();
+        self.set_counts(period as u32, on_period);
+
+        // The output is inverted so that it is high for the on period
+        gpt::GPT[self.gpt as usize]
+            .ctl
+            .modify(self.ctl_enable_field.val(1) + self.ctl_output_invert_field.val(1));
+        ReturnCode::SUCCESS
+    }
+
+    fn stop(&self) -> ReturnCode {
+        let regs = &gpt::GPT[self.gpt as usize];
+        regs.ctl.modify(self.ctl_enable_field.val(0));
+        // The clock of the timer keeps the chip from standby
+        if !regs.ctl.is_set(gpt::Ctl::TIMER_A_EN) && !regs.ctl.is_set(gpt::Ctl::TIMER_B_EN) {
+            prcm::Clock::disable_gpt(self.gpt as usize);
+        }
+        ReturnCode::SUCCESS
+    }
+
+    fn get_maximum_frequency_hz(&self) -> usize {
+        // At least two clock cycles per period
+        CLOCK_HZ / 2
+    }
+
+    fn get_minimum_frequency_hz(&self) -> usize {
+        // Rounded up, so the period fits in the extended timer
+        (CLOCK_HZ + MAX_PERIOD - 1) / MAX_PERIOD
+    }
+
+    fn get_maximum_duty_cycle(&self) -> usize {
+        MAX_DUTY_CYCLE
+    }
+}
+
+pub struct Pwm(());
+
+impl hil::pwm::Pwm for Pwm {
+    type Pin = Timer;
+
+    fn start(&self, pin: &Timer, frequency_hz: usize, duty_cycle: usize) -> ReturnCode {
+        hil::pwm::PwmPin::start(&Signal::new(*pin), frequency_hz, duty_cycle)
+    }
+
+    fn stop(&self, pin: &Timer) -> ReturnCode {
+        hil::pwm::PwmPin::stop(&Signal::new(*pin))
+    }
+
+    fn get_maximum_frequency_hz(&self) -> usize {
+        hil::pwm::PwmPin::get_maximum_frequency_hz(&Signal::new(Timer::GPT0A))
+    }
+
+    fn get_minimum_frequency_hz(&self) -> usize {
+        hil::pwm::PwmPin::get_minimum_frequency_hz(&Signal::new(Timer::GPT0A))
+    }
+
+    fn get_maximum_duty_cycle(&self) -> usize {
+        MAX_DUTY_CYCLE
+    }
 }
@@ -0,0 +1,400 @@
+//! RFC - Radio core of the cc26x2 family
+//!
+//! The radio is run by a separate core, the command and packet engine (CPE),
+//! which the MCU talks to through a doorbell. The MCU writes a command to
+//! the doorbell, which the CPE acknowledges, and which is either a direct
+//! command, such as `PING`, that the CPE carries out immediately, or a
+//! pointer to a radio operation in RAM. Radio operations, such as setting up
+//! the radio or receiving packets, then run until they are done, which the
+//! CPE signals with interrupts, and write their status back into RAM.
+//!
+//! This module powers the core up and down, sends commands and dispatches
+//! the interrupts. It also defines what the radio modes share: the radio
+//! setup, the synthesizer, the settings of the power amplifier and the data
+//! queues that packets are received into. The radio operations of each radio
+//! mode are defined by the driver of that mode, e.g. `ieee802154_radio`,
+//! except for those of the proprietary mode, which several drivers use, and
+//! which are in `prop`.
+
+use core::cell::Cell;
+use core::mem;
+use kernel::common::cells::{OptionalCell, VolatileCell};
+use kernel::common::registers::{register_bitfields, ReadWrite};
+use kernel::common::StaticRef;
+use kernel::ReturnCode;
+
+use crate::oscillator;
+use crate::prcm;
+
+pub mod prop;
+
+#[repr(C)]
+struct RfcDbellRegisters {
+    cmdr: ReadWrite<u32>,
+    cmdsta: ReadWrite<u32, CmdSta::Register>,
+    _rfhwifg: ReadWrite<u32>,
+    _rfhwien: ReadWrite<u32>,
+    rfcpeifg: ReadWrite<u32>,
+    rfcpeien: ReadWrite<u32>,
+    rfcpeisl: ReadWrite<u32>,
+    rfackifg: ReadWrite<u32, AckIfg::Register>,
+}
+
+#[repr(C)]
+struct RfcPwrRegisters {
+    pwmclken: ReadWrite<u32>,
+}
+
+register_bitfields![
+    u32,
+    CmdSta [
+        RESULT OFFSET(0) NUMBITS(8) [
+            Pending = 0x00,
+            Done = 0x01
+        ]
+    ],
+    AckIfg [
+        ACKFLAG OFFSET(0) NUMBITS(1) []
+    ]
+];
+
+const RFC_DBELL_BASE: StaticRef<RfcDbellRegisters> =
+    unsafe { StaticRef::new(0x4004_1000 as *const RfcDbellRegisters) };
+const RFC_PWR_BASE: StaticRef<RfcPwrRegisters> =
+    unsafe { StaticRef::new(0x4004_0000 as *const RfcPwrRegisters) };
+
+/// Enables the clocks of all the modules of the radio core
+const PWMCLKEN_ALL: u32 = 0x7FF;
+
+/// Marks the end of the list of register overrides that a radio setup
+/// operation applies
+pub const END_OVERRIDE: u32 = 0xFFFF_FFFF;
+
+/// Front end configuration of the radio setup for a differential front end
+/// with the internal bias, as on the LaunchXL boards
+pub const DEFAULT_FRONT_END: u16 = 0x0008;
+
+/// Settings of the power amplifier for the transmit powers that a band
+/// supports, as pairs of the power in dBm and the `tx_power` of the radio
+/// setup, from the highest power
+pub type TxPowerTable = [(i8, u16)];
+
+/// Transmit powers in the 2.4 GHz band
+pub const TX_POWER_2_4_GHZ: &TxPowerTable = &[(5, 0x9330), (0, 0x30D3)];
+/// Transmit powers in the sub-GHz bands, as SmartRF Studio gives them for
+/// the CC1352R
+pub const TX_POWER_SUB_GHZ: &TxPowerTable = &[(13, 0xA73F), (-10, 0x04C0)];
+
+/// Returns the setting in `table` of the highest power that is not above
+/// `power`, or `None` if `power` is below all of them.
+pub fn tx_power_setting(table: &TxPowerTable, power: i8) -> Option<u16> {
+    table
+        .iter()
+        .find(|(dbm, _)| *dbm <= power)
+        .map(|(_, setting)| *setting)
+}
+
+/// Direct commands
+pub mod cmd {
+    pub const ABORT: u16 = 0x0401;
+    pub const STOP: u16 = 0x0402;
+    pub const START_RAT: u16 = 0x0405;
+    pub const PING: u16 = 0x0406;
+}
+
+/// Status of radio operations, as written back into their `status` field
+pub mod status {
+    pub const IDLE: u16 = 0x0000;
+    pub const PENDING: u16 = 0x0001;
+    pub const ACTIVE: u16 = 0x0002;
+    /// Status codes from this one onwards mean that the operation is done
+    pub const DONE_OK: u16 = 0x0400;
+    pub const DONE_STOPPED: u16 = 0x0402;
+    pub const DONE_ABORT: u16 = 0x0403;
+}
+
+/// Interrupts of the command and packet engine, in `RFCPEIFG`
+pub mod event {
+    pub const COMMAND_DONE: u32 = 1 << 0;
+    pub const LAST_COMMAND_DONE: u32 = 1 << 1;
+    pub const FG_COMMAND_DONE: u32 = 1 << 2;
+    pub const LAST_FG_COMMAND_DONE: u32 = 1 << 3;
+    pub const TX_DONE: u32 = 1 << 4;
+    pub const RX_OK: u32 = 1 << 16;
+    pub const RX_NOK: u32 = 1 << 17;
+    pub const RX_BUF_FULL: u32 = 1 << 22;
+    pub const RX_ENTRY_DONE: u32 = 1 << 23;
+    pub const INTERNAL_ERROR: u32 = 1 << 31;
+}
+
+/// Triggers of radio operations
+pub mod trigger {
+    pub const NOW: u8 = 0;
+    pub const NEVER: u8 = 1;
+    /// At a time relative to the start of the operation
+    pub const REL_START: u8 = 4;
+}
+
+/// Conditions for running the operation that follows a radio operation
+pub mod condition {
+    pub const NEVER: u8 = 1;
+    /// Run the next operation if this one ends with a true result
+    pub const STOP_ON_FALSE: u8 = 2;
+}
+
+/// The fields that start every radio operation. They are followed in each
+/// operation by its `start_trigger` and `condition` bytes, which are not part
+/// of this structure so that it has no padding.
+#[repr(C)]
+pub struct CommandHeader {
+    pub command_no: VolatileCell<u16>,
+    pub status: VolatileCell<u16>,
+    pub next_op: VolatileCell<u32>,
+    pub start_time: VolatileCell<u32>,
+}
+
+impl CommandHeader {
+    pub const fn new(command_no: u16) -> CommandHeader {
+        CommandHeader {
+            command_no: VolatileCell::new(command_no),
+            status: VolatileCell::new(status::IDLE),
+            next_op: VolatileCell::new(0),
+            start_time: VolatileCell::new(0),
+        }
+    }
+
+    /// Whether the operation has run and stopped, successfully or not.
+    pub fn is_done(&self) -> bool {
+        self.status.get() >= status::DONE_OK
+    }
+}
+
+pub const CMD_RADIO_SETUP: u16 = 0x0802;
+pub const CMD_FS: u16 = 0x0803;
+
+/// Sets up the radio for the IEEE 802.15.4 and BLE modes, in the 2.4 GHz
+/// band. The proprietary mode has its own setup, in `prop`.
+#[repr(C)]
+pub struct CmdRadioSetup {
+    pub header: CommandHeader,
+    pub start_trigger: VolatileCell<u8>,
+    pub condition: VolatileCell<u8>,
+    pub mode: VolatileCell<u8>,
+    pub lo_divider: VolatileCell<u8>,
+    pub config: VolatileCell<u16>,
+    pub tx_power: VolatileCell<u16>,
+    pub reg_override: VolatileCell<u32>,
+}
+
+/// Programs the synthesizer for a frequency.
+#[repr(C)]
+pub struct CmdFs {
+    pub header: CommandHeader,
+    pub start_trigger: VolatileCell<u8>,
+    pub condition: VolatileCell<u8>,
+    /// In MHz
+    pub frequency: VolatileCell<u16>,
+    /// The fraction of a MHz, in units of 2^-16 MHz
+    pub fract_freq: VolatileCell<u16>,
+    pub synth_conf: VolatileCell<u8>,
+    pub _reserved0: [VolatileCell<u8>; 3],
+    pub _reserved1: VolatileCell<u16>,
+}
+
+/// A queue of entries that received packets are written into. Entries are
+/// linked by their `next_entry`, so a single entry that points to itself is
+/// a circular queue.
+#[repr(C)]
+pub struct DataQueue {
+    pub current_entry: VolatileCell<u32>,
+    pub last_entry: VolatileCell<u32>,
+}
+
+impl DataQueue {
+    pub const fn new() -> DataQueue {
+        DataQueue {
+            current_entry: VolatileCell::new(0),
+            last_entry: VolatileCell::new(0),
+        }
+    }
+
+    /// Makes `rx_entry` the only entry of the queue, which is then circular,
+    /// and readies it for a packet.
+    pub fn set_single_entry<D>(&self, rx_entry: &RxEntry<D>) {
+        let addr = rx_entry as *const RxEntry<D> as u32;
+        rx_entry.next_entry.set(addr);
+        rx_entry.status.set(entry::PENDING);
+        self.current_entry.set(addr);
+        self.last_entry.set(0);
+    }
+}
+
+/// Status of the entries of a data queue
+pub mod entry {
+    pub const PENDING: u8 = 0;
+    pub const ACTIVE: u8 = 1;
+    pub const BUSY: u8 = 2;
+    pub const FINISHED: u8 = 3;
+}
+
+/// Config of the entries of a data queue: general entries with a one byte
+/// length at the start of each packet
+const RX_ENTRY_CONFIG: u8 = 0x04;
+
+/// Bit of the status byte that the receive operations can keep after each
+/// packet, set when the CRC fails
+pub const RX_CRC_ERROR: u8 = 0x80;
+
+/// An entry of a data queue, whose `data` the radio core writes a received
+/// packet into, after its length.
+// The radio core reads the fields that are never read here
+#[allow(dead_code)]
+#[repr(C)]
+pub struct RxEntry<D> {
+    next_entry: VolatileCell<u32>,
+    pub status: VolatileCell<u8>,
+    config: VolatileCell<u8>,
+    length: VolatileCell<u16>,
+    pub data: D,
+}
+
+impl<D> RxEntry<D> {
+    /// `data` is an array of `VolatileCell<u8>` for the length, the packet
+    /// and whatever the receive operation keeps after it.
+    pub const fn new(data: D) -> RxEntry<D> {
+        RxEntry {
+            next_entry: VolatileCell::new(0),
+            status: VolatileCell::new(entry::PENDING),
+            config: VolatileCell::new(RX_ENTRY_CONFIG),
+            length: VolatileCell::new(mem::size_of::<D>() as u16),
+            data: data,
+        }
+    }
+}
+
+/// The client of the radio core, which is given the interrupts of the
+/// command and packet engine.
+pub trait Client {
+    fn cpe_events(&self, events: u32);
+}
+
+pub struct RFCore {
+    dbell: StaticRef<RfcDbellRegisters>,
+    pwr: StaticRef<RfcPwrRegisters>,
+    client: OptionalCell<&'static Client>,
+    on: Cell<bool>,
+}
+
+pub static mut RFC: RFCore = RFCore::new();
+
+impl RFCore {
+    const fn new() -> RFCore {
+        RFCore {
+            dbell: RFC_DBELL_BASE,
+            pwr: RFC_PWR_BASE,
+            client: OptionalCell::empty(),
+            on: Cell::new(false),
+        }
+    }
+
+    pub fn set_client(&self, client: &'static Client) {
+        self.client.set(client);
+    }
+
+    pub fn is_on(&self) -> bool {
+        self.on.get()
+    }
+
+    /// Powers the radio core up and starts its timer. `events` are the
+    /// interrupts of the command and packet engine that the client is
+    /// interested in.
+    pub fn enable(&self, events: u32) -> ReturnCode {
+        // The synthesizer needs the crystal oscillator
+        unsafe {
+            oscillator::OSC.switch_to_hf_xosc();
+        }
+
+        prcm::Power::enable_domain(prcm::PowerDomain::RFC);
+        prcm::Clock::enable_rfc();
+        self.pwr.pwmclken.set(PWMCLKEN_ALL);
+
+        let dbell = &*self.dbell;
+        // All interrupts of the engine go to its first interrupt line
+        dbell.rfcpeisl.set(0);
+        dbell.rfcpeifg.set(0);
+        dbell.rfcpeien.set(events | event::INTERNAL_ERROR);
+        self.on.set(true);
+
+        let result = self.send_direct(cmd::PING);
+        if result != ReturnCode::SUCCESS {
+            self.disable();
+            return result;
+        }
+        self.send_direct(cmd::START_RAT)
+    }
+
+    pub fn disable(&self) {
+        let dbell = &*self.dbell;
+        dbell.rfcpeien.set(0);
+        dbell.rfcpeifg.set(0);
+
+        self.pwr.pwmclken.set(0);
+        prcm::Clock::disable_rfc();
+        prcm::Power::disable_domain(prcm::PowerDomain::RFC);
+        self.on.set(false);
+    }
+
+    /// Writes a command to the doorbell and waits for the engine to
+    /// acknowledge it.
+    fn send(&self, command: u32) -> ReturnCode {
+        if !self.on.get() {
+            return ReturnCode::EOFF;
+        }
+        let dbell = &*self.dbell;
+        dbell.rfackifg.set(0);
+        dbell.cmdr.set(command);
+        while !dbell.rfackifg.is_set(AckIfg::ACKFLAG) {}
+        dbell.rfackifg.set(0);
+
+        if dbell.cmdsta.matches_all(CmdSta::RESULT::Done) {
+            ReturnCode::SUCCESS
+        } else {
+            ReturnCode::FAIL
+        }
+    }
+
+    /// Sends a direct command, which is done once it is acknowledged.
+    pub fn send_direct(&self, command: u16) -> ReturnCode {
+        self.send(((command as u32) << 16) | 1)
+    }
+
+    /// Starts a radio operation. The operation, which must stay in place
+    /// until it is done, is at the start of a command structure, which must
+    /// be word aligned.
+    pub fn send_command(&self, command: &CommandHeader) -> ReturnCode {
+        command.status.set(status::IDLE);
+        self.send(command as *const CommandHeader as u32)
+    }
+
+    /// Starts a radio operation and waits until it is done. This is for
+    /// operations that are done quickly, like setting up the radio.
+    pub fn run_command(&self, command: &CommandHeader) -> ReturnCode {
+        let result = self.send_command(command);
+        if result != ReturnCode::SUCCESS {
+            return result;
+        }
+        while !command.is_done() {}
+        ReturnCode::SUCCESS
+    }
+
+    pub fn handle_interrupt(&self) {
+        let dbell = &*self.dbell;
+        let events = dbell.rfcpeifg.get() & dbell.rfcpeien.get();
+        // Flags are cleared by writing 0 to them
+        dbell.rfcpeifg.set(!events);
+
+        if events != 0 {
+            self.client.map(|client| client.cpe_events(events));
+        }
+    }
+}
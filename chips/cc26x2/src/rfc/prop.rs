@@ -0,0 +1,515 @@
+//! Proprietary mode of the radio core
+//!
+//! In the proprietary mode the radio core sends and receives packets with a
+//! physical layer that the MCU sets up: the modulation, the data rate, the
+//! sync word and the frequency. This module defines the radio operations of
+//! the mode, which `subghz_radio` also uses, and `PropRadio`, a driver that
+//! sends and receives raw packets in the 2.4 GHz band with them.
+//!
+//! `PropRadio` sends 2-GFSK packets at 250 kbps, 1 Mbps or 2 Mbps, with a
+//! 32 bit sync word, a one byte length and a 16 bit CRC. Channels are 1 MHz
+//! apart, channel `n` at 2400 + `n` MHz, up to 2483 MHz. The radio listens
+//! for packets while it is on and does not transmit, and hands them to the
+//! receive client while it has a buffer for them.
+//!
+//! The radio core runs one radio mode at a time, so a board uses either this
+//! driver or one of the other radio drivers.
+//!
+//! Usage
+//! -----
+//!
+//! ```rust
+//! cc26x2::rfc::RFC.set_client(&cc26x2::rfc::prop::RADIO);
+//! cc26x2::rfc::prop::RADIO.set_transmit_client(packet_radio);
+//! cc26x2::rfc::prop::RADIO.set_receive_client(packet_radio);
+//! cc26x2::rfc::prop::RADIO.start();
+//! ```
+
+use core::cell::Cell;
+use kernel::common::cells::{OptionalCell, TakeCell, VolatileCell};
+use kernel::hil::packet_radio::{self, Config};
+use kernel::ReturnCode;
+
+use crate::rfc::{self, CmdFs, CommandHeader, DataQueue, RxEntry};
+
+pub const CMD_PROP_TX: u16 = 0x3801;
+pub const CMD_PROP_RX: u16 = 0x3802;
+pub const CMD_PROP_RADIO_DIV_SETUP: u16 = 0x3807;
+
+/// Status of proprietary mode operations that are done successfully
+pub const PROP_DONE_OK: u16 = 0x3400;
+
+/// Use the default intermediate frequency of the PHY
+pub const DEFAULT_INT_FREQ: u16 = 0x8000;
+
+/// Sets up the radio for the proprietary mode, on any band.
+#[repr(C)]
+pub struct CmdPropRadioDivSetup {
+    pub header: CommandHeader,
+    pub start_trigger: VolatileCell<u8>,
+    pub condition: VolatileCell<u8>,
+    /// The modulation, and the deviation in 250 Hz steps
+    pub modulation: VolatileCell<u16>,
+    /// The prescaler and the rate word of the symbol rate
+    pub symbol_rate: VolatileCell<u32>,
+    pub rx_bw: VolatileCell<u8>,
+    /// Length of the preamble, in bytes
+    pub pream_conf: VolatileCell<u8>,
+    /// Length of the sync word, bit order and error correction
+    pub format_conf: VolatileCell<u16>,
+    pub config: VolatileCell<u16>,
+    pub tx_power: VolatileCell<u16>,
+    pub reg_override: VolatileCell<u32>,
+    /// In MHz
+    pub center_freq: VolatileCell<u16>,
+    pub int_freq: VolatileCell<u16>,
+    /// 0 in the 2.4 GHz band
+    pub lo_divider: VolatileCell<u8>,
+}
+
+/// Sends a packet.
+#[repr(C)]
+pub struct CmdPropTx {
+    pub header: CommandHeader,
+    pub start_trigger: VolatileCell<u8>,
+    pub condition: VolatileCell<u8>,
+    pub pkt_conf: VolatileCell<u8>,
+    pub pkt_len: VolatileCell<u8>,
+    pub sync_word: VolatileCell<u32>,
+    pub pkt: VolatileCell<u32>,
+}
+
+/// Receives packets into a data queue.
+#[repr(C)]
+pub struct CmdPropRx {
+    pub header: CommandHeader,
+    pub start_trigger: VolatileCell<u8>,
+    pub condition: VolatileCell<u8>,
+    pub pkt_conf: VolatileCell<u8>,
+    pub rx_conf: VolatileCell<u8>,
+    pub sync_word: VolatileCell<u32>,
+    pub max_pkt_len: VolatileCell<u8>,
+    pub address0: VolatileCell<u8>,
+    pub address1: VolatileCell<u8>,
+    pub end_trigger: VolatileCell<u8>,
+    pub end_time: VolatileCell<u32>,
+    pub queue: VolatileCell<u32>,
+    pub output: VolatileCell<u32>,
+}
+
+/// The center frequency the radio is set up for, in MHz
+const CENTER_FREQ: u16 = 2440;
+const BASE_FREQ: u16 = 2400;
+const MAX_CHANNEL: u8 = 83;
+
+const DEFAULT_TX_POWER: i8 = 0;
+
+const DEFAULT_CONFIG: Config = Config {
+    data_rate: 250_000,
+    sync_word: 0x930B_51DE,
+    channel: 40,
+};
+
+/// Packet options of the transmit operation: packets have a length byte and
+/// a CRC
+pub const TX_PKT_CONF: u8 = 0x18;
+/// Packet options of the receive operation: as for transmitting, and the
+/// operation goes on after each packet
+pub const RX_PKT_CONF: u8 = 0x1E;
+/// Options of the receive operation: packets that fail the CRC are flushed,
+/// and the RSSI and a status byte are kept after each packet.
+const RX_CONF: u8 = 0xA2;
+
+/// Longest packet, without its length and CRC, so that it fits with its
+/// length in the 255 bytes that the transmit operation takes
+pub const MAX_PACKET_LEN: usize = 254;
+
+/// The packet being sent, after its length byte, as the radio core sends
+/// the packet as it is in memory
+static mut TX_PACKET: [u8; 1 + MAX_PACKET_LEN] = [0; 1 + MAX_PACKET_LEN];
+
+/// Size of the data of the entry: the length, the packet, the RSSI and the
+/// status
+const RX_ENTRY_LEN: usize = 1 + MAX_PACKET_LEN + 2;
+
+/// The setup of the modem for a data rate, with the fields of the radio
+/// setup of the same names.
+struct RateSettings {
+    modulation: u16,
+    symbol_rate: u32,
+    rx_bw: u8,
+    pream_conf: u8,
+}
+
+/// A 32 bit sync word, sent MSB first
+const FORMAT_CONF: u16 = 0x00A0;
+
+fn rate_settings(data_rate: u32) -> Option<RateSettings> {
+    // The rate word is the rate in units of 24 MHz / (15 * 2^20)
+    match data_rate {
+        250_000 => Some(RateSettings {
+            modulation: 0x0FA1,
+            symbol_rate: 0x0280_000F,
+            rx_bw: 0x59,
+            pream_conf: 0x04,
+        }),
+        1_000_000 => Some(RateSettings {
+            modulation: 0x1F41,
+            symbol_rate: 0x0A00_000F,
+            rx_bw: 0x5A,
+            pream_conf: 0x04,
+        }),
+        2_000_000 => Some(RateSettings {
+            modulation: 0x3E81,
+            symbol_rate: 0x1400_000F,
+            rx_bw: 0x5B,
+            pream_conf: 0x08,
+        }),
+        _ => None,
+    }
+}
+
+pub struct PropRadio {
+    setup_cmd: CmdPropRadioDivSetup,
+    fs_cmd: CmdFs,
+    rx_cmd: CmdPropRx,
+    tx_cmd: CmdPropTx,
+    rx_queue: DataQueue,
+    rx_entry: RxEntry<[VolatileCell<u8>; RX_ENTRY_LEN]>,
+    config: Cell<Config>,
+    front_end: Cell<u16>,
+    overrides: Cell<&'static [u32]>,
+    tx_power: Cell<i8>,
+    tx_client: OptionalCell<&'static packet_radio::TxClient>,
+    rx_client: OptionalCell<&'static packet_radio::RxClient>,
+    tx_buf: TakeCell<'static, [u8]>,
+    rx_buf: TakeCell<'static, [u8]>,
+}
+
+pub static mut RADIO: PropRadio = PropRadio::new();
+
+impl PropRadio {
+    const fn new() -> PropRadio {
+        PropRadio {
+            setup_cmd: CmdPropRadioDivSetup {
+                header: CommandHeader::new(CMD_PROP_RADIO_DIV_SETUP),
+                start_trigger: VolatileCell::new(rfc::trigger::NOW),
+                condition: VolatileCell::new(rfc::condition::NEVER),
+                modulation: VolatileCell::new(0),
+                symbol_rate: VolatileCell::new(0),
+                rx_bw: VolatileCell::new(0),
+                pream_conf: VolatileCell::new(0),
+                format_conf: VolatileCell::new(FORMAT_CONF),
+                config: VolatileCell::new(rfc::DEFAULT_FRONT_END),
+                tx_power: VolatileCell::new(0),
+                reg_override: VolatileCell::new(0),
+                center_freq: VolatileCell::new(CENTER_FREQ),
+                int_freq: VolatileCell::new(DEFAULT_INT_FREQ),
+                lo_divider: VolatileCell::new(0),
+            },
+            fs_cmd: CmdFs {
+                header: CommandHeader::new(rfc::CMD_FS),
+                start_trigger: VolatileCell::new(rfc::trigger::NOW),
+                condition: VolatileCell::new(rfc::condition::NEVER),
+                frequency: VolatileCell::new(0),
+                fract_freq: VolatileCell::new(0),
+                synth_conf: VolatileCell::new(0),
+                _reserved0: [VolatileCell::new(0); 3],
+                _reserved1: VolatileCell::new(0),
+            },
+            rx_cmd: CmdPropRx {
+                header: CommandHeader::new(CMD_PROP_RX),
+                start_trigger: VolatileCell::new(rfc::trigger::NOW),
+                condition: VolatileCell::new(rfc::condition::NEVER),
+                pkt_conf: VolatileCell::new(RX_PKT_CONF),
+                rx_conf: VolatileCell::new(RX_CONF),
+                sync_word: VolatileCell::new(0),
+                max_pkt_len: VolatileCell::new(MAX_PACKET_LEN as u8),
+                address0: VolatileCell::new(0),
+                address1: VolatileCell::new(0),
+                end_trigger: VolatileCell::new(rfc::trigger::NEVER),
+                end_time: VolatileCell::new(0),
+                queue: VolatileCell::new(0),
+                output: VolatileCell::new(0),
+            },
+            tx_cmd: CmdPropTx {
+                header: CommandHeader::new(CMD_PROP_TX),
+                start_trigger: VolatileCell::new(rfc::trigger::NOW),
+                condition: VolatileCell::new(rfc::condition::NEVER),
+                pkt_conf: VolatileCell::new(TX_PKT_CONF),
+                pkt_len: VolatileCell::new(0),
+                sync_word: VolatileCell::new(0),
+                pkt: VolatileCell::new(0),
+            },
+            rx_queue: DataQueue::new(),
+            rx_entry: RxEntry::new([VolatileCell::new(0); RX_ENTRY_LEN]),
+            config: Cell::new(DEFAULT_CONFIG),
+            front_end: Cell::new(rfc::DEFAULT_FRONT_END),
+            overrides: Cell::new(&[rfc::END_OVERRIDE]),
+            tx_power: Cell::new(DEFAULT_TX_POWER),
+            tx_client: OptionalCell::empty(),
+            rx_client: OptionalCell::empty(),
+            tx_buf: TakeCell::empty(),
+            rx_buf: TakeCell::empty(),
+        }
+    }
+
+    fn rfc(&self) -> &'static rfc::RFCore {
+        unsafe { &rfc::RFC }
+    }
+
+    /// Sets the front end configuration of the radio setup and the register
+    /// overrides, which are specific to the board. The overrides end with
+    /// `rfc::END_OVERRIDE`. They take effect when the radio is next started.
+    pub fn set_front_end(&self, config: u16, overrides: &'static [u32]) {
+        self.front_end.set(config);
+        self.overrides.set(overrides);
+    }
+
+    /// Sets the transmit power, in dBm, which takes effect when the radio is
+    /// next set up. Returns `ENOSUPPORT` if the power is below the lowest
+    /// one supported.
+    pub fn set_tx_power(&self, power: i8) -> ReturnCode {
+        match rfc::tx_power_setting(rfc::TX_POWER_2_4_GHZ, power) {
+            Some(_) => {
+                self.tx_power.set(power);
+                ReturnCode::SUCCESS
+            }
+            None => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    /// Sets the modem up for the data rate, and tunes the synthesizer.
+    fn setup(&self) -> ReturnCode {
+        let config = self.config.get();
+        let rate = match rate_settings(config.data_rate) {
+            Some(rate) => rate,
+            None => return ReturnCode::ENOSUPPORT,
+        };
+        let setup = &self.setup_cmd;
+        setup.modulation.set(rate.modulation);
+        setup.symbol_rate.set(rate.symbol_rate);
+        setup.rx_bw.set(rate.rx_bw);
+        setup.pream_conf.set(rate.pream_conf);
+        setup.config.set(self.front_end.get());
+        setup
+            .tx_power
+            .set(rfc::tx_power_setting(rfc::TX_POWER_2_4_GHZ, self.tx_power.get()).unwrap_or(0));
+        setup.reg_override.set(self.overrides.get().as_ptr() as u32);
+        let result = self.rfc().run_command(&setup.header);
+        if result != ReturnCode::SUCCESS || setup.header.status.get() != rfc::status::DONE_OK {
+            return ReturnCode::FAIL;
+        }
+
+        let fs = &self.fs_cmd;
+        fs.frequency.set(BASE_FREQ + config.channel as u16);
+        let result = self.rfc().run_command(&fs.header);
+        if result != ReturnCode::SUCCESS || fs.header.status.get() != rfc::status::DONE_OK {
+            return ReturnCode::FAIL;
+        }
+        self.rx_cmd.sync_word.set(config.sync_word);
+        self.tx_cmd.sync_word.set(config.sync_word);
+        ReturnCode::SUCCESS
+    }
+
+    fn start_rx(&self) -> ReturnCode {
+        self.rx_queue.set_single_entry(&self.rx_entry);
+
+        let rx = &self.rx_cmd;
+        rx.queue.set(&self.rx_queue as *const DataQueue as u32);
+        self.rfc().send_command(&rx.header)
+    }
+
+    fn stop_rx(&self) {
+        let header = &self.rx_cmd.header;
+        if header.status.get() == rfc::status::IDLE || header.is_done() {
+            return;
+        }
+        if self.rfc().send_direct(rfc::cmd::ABORT) == ReturnCode::SUCCESS {
+            while !header.is_done() {}
+        }
+    }
+
+    /// Hands the packet in the receive queue to the client, if it has a
+    /// buffer for it.
+    fn receive_packet(&self) {
+        let entry = &self.rx_entry;
+        if entry.status.get() != rfc::entry::FINISHED {
+            return;
+        }
+
+        // The data is the length, the packet, the RSSI and a status byte.
+        // Packets that fail the CRC are not kept.
+        let len = entry.data[0].get() as usize;
+        if len >= 2 && len < RX_ENTRY_LEN {
+            let packet_len = len - 2;
+            let rssi = entry.data[1 + packet_len].get() as i8;
+            self.rx_buf.take().map(|buf| {
+                for (byte, data) in buf.iter_mut().zip(entry.data[1..=packet_len].iter()) {
+                    *byte = data.get();
+                }
+                self.rx_client.map(move |client| {
+                    client.packet_received(buf, packet_len, rssi, ReturnCode::SUCCESS)
+                });
+            });
+        }
+        entry.status.set(rfc::entry::PENDING);
+    }
+
+    fn transmit_done(&self) {
+        let result = if self.tx_cmd.header.status.get() == PROP_DONE_OK {
+            ReturnCode::SUCCESS
+        } else {
+            ReturnCode::FAIL
+        };
+        // The receive operation was stopped for the transmission
+        self.start_rx();
+        self.tx_buf.take().map(|buf| {
+            self.tx_client
+                .map(move |client| client.transmit_done(buf, result));
+        });
+    }
+}
+
+impl rfc::Client for PropRadio {
+    fn cpe_events(&self, events: u32) {
+        if events & rfc::event::RX_ENTRY_DONE != 0 {
+            self.receive_packet();
+        }
+        if events & rfc::event::LAST_COMMAND_DONE == 0 || !self.rfc().is_on() {
+            return;
+        }
+        if self.tx_buf.is_some() {
+            // Also signalled when the receive operation is stopped for the
+            // transmission
+            if self.tx_cmd.header.is_done() {
+                self.transmit_done();
+            }
+        } else if self.rx_cmd.header.is_done() {
+            // The receive operation stops if it fails, e.g. on an overflow
+            self.start_rx();
+        }
+    }
+}
+
+impl packet_radio::PacketRadio for PropRadio {
+    fn set_transmit_client(&self, client: &'static packet_radio::TxClient) {
+        self.tx_client.set(client);
+    }
+
+    fn set_receive_client(&self, client: &'static packet_radio::RxClient) {
+        self.rx_client.set(client);
+    }
+
+    fn max_packet_len(&self) -> usize {
+        MAX_PACKET_LEN
+    }
+
+    fn start(&self) -> ReturnCode {
+        if self.rfc().is_on() {
+            return ReturnCode::SUCCESS;
+        }
+        let result = self
+            .rfc()
+            .enable(rfc::event::RX_ENTRY_DONE | rfc::event::LAST_COMMAND_DONE);
+        if result != ReturnCode::SUCCESS {
+            return result;
+        }
+        let result = self.setup();
+        if result != ReturnCode::SUCCESS {
+            self.rfc().disable();
+            return result;
+        }
+        self.start_rx()
+    }
+
+    fn stop(&self) -> ReturnCode {
+        if !self.rfc().is_on() {
+            return ReturnCode::SUCCESS;
+        }
+        if self.tx_buf.is_some() {
+            return ReturnCode::EBUSY;
+        }
+        self.stop_rx();
+        self.rfc().disable();
+        ReturnCode::SUCCESS
+    }
+
+    fn is_on(&self) -> bool {
+        self.rfc().is_on()
+    }
+
+    fn configure(&self, config: Config) -> ReturnCode {
+        if rate_settings(config.data_rate).is_none() {
+            return ReturnCode::ENOSUPPORT;
+        }
+        if config.channel > MAX_CHANNEL {
+            return ReturnCode::EINVAL;
+        }
+        if self.tx_buf.is_some() {
+            return ReturnCode::EBUSY;
+        }
+        self.config.set(config);
+        if !self.rfc().is_on() {
+            return ReturnCode::SUCCESS;
+        }
+        self.stop_rx();
+        let result = self.setup();
+        if result != ReturnCode::SUCCESS {
+            return result;
+        }
+        self.start_rx()
+    }
+
+    fn get_config(&self) -> Config {
+        self.config.get()
+    }
+
+    fn transmit(
+        &self,
+        buf: &'static mut [u8],
+        len: usize,
+    ) -> (ReturnCode, Option<&'static mut [u8]>) {
+        if !self.rfc().is_on() {
+            return (ReturnCode::EOFF, Some(buf));
+        } else if self.tx_buf.is_some() {
+            return (ReturnCode::EBUSY, Some(buf));
+        } else if len > MAX_PACKET_LEN || len > buf.len() {
+            return (ReturnCode::ESIZE, Some(buf));
+        }
+
+        // The radio core can't transmit while the receive operation runs in
+        // this mode, so it is stopped until the transmission is done
+        self.stop_rx();
+        let packet = unsafe { &mut TX_PACKET };
+        packet[0] = len as u8;
+        packet[1..=len].copy_from_slice(&buf[..len]);
+        let tx = &self.tx_cmd;
+        tx.pkt_len.set((len + 1) as u8);
+        tx.pkt.set(packet.as_ptr() as u32);
+        let result = self.rfc().send_command(&tx.header);
+        if result != ReturnCode::SUCCESS {
+            self.start_rx();
+            return (result, Some(buf));
+        }
+        self.tx_buf.replace(buf);
+        (ReturnCode::SUCCESS, None)
+    }
+
+    fn receive(&self, buf: &'static mut [u8]) -> (ReturnCode, Option<&'static mut [u8]>) {
+        if !self.rfc().is_on() {
+            (ReturnCode::EOFF, Some(buf))
+        } else if self.rx_buf.is_some() {
+            (ReturnCode::EBUSY, Some(buf))
+        } else if buf.len() < MAX_PACKET_LEN {
+            (ReturnCode::ESIZE, Some(buf))
+        } else {
+            self.rx_buf.replace(buf);
+            (ReturnCode::SUCCESS, None)
+        }
+    }
+
+    fn receive_cancel(&self) -> Option<&'static mut [u8]> {
+        self.rx_buf.take()
+    }
+}
@@ -38,6 +38,29 @@ const ROM_HAPI_TABLE_ADDR: usize = 0x1000_0048;
 pub const HAPI: StaticRef<HARD_API> =
     unsafe { StaticRef::new(ROM_HAPI_TABLE_ADDR as *const HARD_API) };
 
+// The driver library flash functions, from the flash table of the ROM API
+// table. Functions that erase or program flash return 0 on success.
+#[repr(C)]
+pub struct FLASH_API {
+    _power_mode_set: unsafe extern "C" fn(u32, u32, u32),
+    _power_mode_get: unsafe extern "C" fn() -> u32,
+    _protection_set: unsafe extern "C" fn(u32, u32),
+    _protection_get: unsafe extern "C" fn(u32) -> u32,
+    _protection_save: unsafe extern "C" fn(u32) -> u32,
+    pub sector_erase: unsafe extern "C" fn(u32) -> u32,
+    pub program: unsafe extern "C" fn(*const u8, u32, u32) -> u32,
+}
+
+const ROM_API_TABLE_ADDR: usize = 0x1000_0180;
+const ROM_API_FLASH_TABLE_INDEX: usize = 10;
+
+pub fn flash_api() -> &'static FLASH_API {
+    unsafe {
+        let table = ROM_API_TABLE_ADDR as *const *const FLASH_API;
+        &**table.add(ROM_API_FLASH_TABLE_INDEX)
+    }
+}
+
 // Defines for input parameter to the select_comp_a_input function.
 // The define values can not be changed!
 enum_from_primitive! {
@@ -95,7 +95,10 @@ impl Rtc {
 
         regs.sync.get();
     }
-    fn read_counter(&self) -> u32 {
+    /// The time in tics since the RTC started. The RTC counts 32 bits of
+    /// seconds, so this does not wrap around for 136 years; `Alarm::now` is
+    /// its low 32 bits, which wrap around every 18 hours.
+    pub fn now64(&self) -> u64 {
         let regs = &*self.registers;
 
         /*
@@ -111,7 +114,7 @@ impl Rtc {
             after_subsec_read = regs.sec.get();
         }
 
-        return (current_sec << 16) | (current_subsec >> 16);
+        return (current_sec as u64) << 16 | (current_subsec >> 16) as u64;
     }
 
     pub fn is_running(&self) -> bool {
@@ -176,7 +179,11 @@ impl Time for Rtc {
 
 impl Alarm for Rtc {
     fn now(&self) -> u32 {
-        self.read_counter()
+        Rtc::now64(self) as u32
+    }
+
+    fn now64(&self) -> Option<u64> {
+        Some(Rtc::now64(self))
     }
 
     fn set_alarm(&self, tics: u32) {
@@ -0,0 +1,53 @@
+//! Sampling timer of `kernel::profiler`, from a general purpose timer
+//!
+//! The two halves of the timer are combined into a 32-bit periodic timer,
+//! whose interrupt is sent to `cortexm4::sampler`. The timer keeps the chip
+//! from going into standby, so a profile of a mostly idle board shows more
+//! time in the idle loop than it would have.
+//!
+//! The timer cannot be used for anything else, like PWM, while sampling.
+//!
+//! ```rust
+//! cc26x2::sampler::start(3, 1000);
+//! ```
+
+use crate::crt1;
+use crate::gpt::{self, Cfg, Ctl, Interrupt, Mode};
+use crate::peripheral_interrupts::NvicIrq;
+use crate::prcm;
+use cortexm4::{nvic, sampler};
+
+/// The frequency that the timers count at, the system clock
+const CLOCK_HZ: u32 = 48_000_000;
+
+/// The timer that samples
+static mut TIMER: usize = 0;
+
+/// Sample `rate` times a second with general purpose timer `timer`, which is
+/// 0 to 3.
+pub unsafe fn start(timer: usize, rate: u32) {
+    let regs = &*gpt::GPT[timer];
+    TIMER = timer;
+    prcm::Clock::enable_gpt(timer);
+
+    regs.ctl.modify(Ctl::TIMER_A_EN::DISABLE);
+    regs.cfg.write(Cfg::BITS::_32);
+    regs.timer_a_mode
+        .write(Mode::MODE::PERIODIC + Mode::COUNT_DIRECTION::DOWN);
+    regs.timer_a_load.set(CLOCK_HZ / rate - 1);
+    regs.int_clr.write(Interrupt::TAT::SET);
+    regs.int_mask.write(Interrupt::TAT::SET);
+
+    // Timer B of each timer follows timer A
+    let irq = NvicIrq::Gpt0a as usize + 2 * timer;
+    sampler::install(irq, crt1::BASE_VECTORS.len(), acknowledge);
+    nvic::Nvic::new(irq as u32).enable();
+
+    // Do not sample while the debugger halts the core
+    regs.ctl
+        .modify(Ctl::TIMER_A_STALL::ENABLE + Ctl::TIMER_A_EN::ENABLE);
+}
+
+unsafe fn acknowledge() {
+    gpt::GPT[TIMER].int_clr.write(Interrupt::TAT::SET);
+}
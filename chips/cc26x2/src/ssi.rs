@@ -0,0 +1,308 @@
+//! SSI driver, cc26x2 family
+//!
+//! The SSI is run as an SPI slave, in Motorola frame format with 8-bit
+//! frames. Transfers go through the 8-frame FIFOs: the transmit FIFO is
+//! topped up from the write buffer as the master clocks frames in, never
+//! holding more frames than the receive FIFO can take, and the receive FIFO
+//! is drained on its half-full and timeout interrupts. A transfer ends once
+//! `len` frames were exchanged.
+//!
+//! The SSI does not interrupt when the master asserts the chip select, so
+//! `chip_selected` is called when the first frame of a transfer arrives. In
+//! slave mode the SSI clock must be at least 12 times the SPI clock, so the
+//! master may clock at up to 4 MHz.
+
+use crate::memory_map::{SSI0_BASE, SSI1_BASE};
+use crate::prcm;
+use core::cell::Cell;
+use core::cmp;
+use kernel::common::cells::{OptionalCell, TakeCell};
+use kernel::common::registers::{register_bitfields, FieldValue, ReadOnly, ReadWrite, WriteOnly};
+use kernel::common::StaticRef;
+use kernel::hil::spi::{self, ClockPhase, ClockPolarity, SpiSlaveClient};
+use kernel::ReturnCode;
+
+#[repr(C)]
+struct SsiRegisters {
+    cr0: ReadWrite<u32, Control0::Register>,
+    cr1: ReadWrite<u32, Control1::Register>,
+    dr: ReadWrite<u32>,
+    sr: ReadOnly<u32, Status::Register>,
+    _cpsr: ReadWrite<u32>,
+    imsc: ReadWrite<u32, Interrupts::Register>,
+    _ris: ReadOnly<u32, Interrupts::Register>,
+    _mis: ReadOnly<u32, Interrupts::Register>,
+    icr: WriteOnly<u32, Interrupts::Register>,
+    _dmacr: ReadWrite<u32>,
+}
+
+register_bitfields![
+    u32,
+    Control0 [
+        SCR OFFSET(8) NUMBITS(8) [],
+        SPH OFFSET(7) NUMBITS(1) [],
+        SPO OFFSET(6) NUMBITS(1) [],
+        FRF OFFSET(4) NUMBITS(2) [
+            MotorolaSpi = 0x0,
+            TiSynchronousSerial = 0x1,
+            NationalMicrowire = 0x2
+        ],
+        DSS OFFSET(0) NUMBITS(4) [
+            Bits8 = 0x7
+        ]
+    ],
+    Control1 [
+        // Slave output disable
+        SOD OFFSET(3) NUMBITS(1) [],
+        // Slave mode
+        MS OFFSET(2) NUMBITS(1) [],
+        SSE OFFSET(1) NUMBITS(1) [],
+        LBM OFFSET(0) NUMBITS(1) []
+    ],
+    Status [
+        BSY OFFSET(4) NUMBITS(1) [],
+        RFF OFFSET(3) NUMBITS(1) [],
+        RNE OFFSET(2) NUMBITS(1) [],
+        TNF OFFSET(1) NUMBITS(1) [],
+        TFE OFFSET(0) NUMBITS(1) []
+    ],
+    Interrupts [
+        // The transmit FIFO is half full or less
+        TX OFFSET(3) NUMBITS(1) [],
+        // The receive FIFO is half full or more
+        RX OFFSET(2) NUMBITS(1) [],
+        // The receive FIFO was not read for 32 bit periods
+        RT OFFSET(1) NUMBITS(1) [],
+        // The receive FIFO overran
+        ROR OFFSET(0) NUMBITS(1) []
+    ]
+];
+
+const FIFO_DEPTH: usize = 8;
+
+const SSI0_REG: StaticRef<SsiRegisters> =
+    unsafe { StaticRef::new(SSI0_BASE as *const SsiRegisters) };
+
+const SSI1_REG: StaticRef<SsiRegisters> =
+    unsafe { StaticRef::new(SSI1_BASE as *const SsiRegisters) };
+
+pub static mut SSI0: Ssi = Ssi::new(&SSI0_REG, 0);
+pub static mut SSI1: Ssi = Ssi::new(&SSI1_REG, 1);
+
+pub struct Ssi {
+    registers: &'static StaticRef<SsiRegisters>,
+    num: usize,
+    slave_client: OptionalCell<&'static SpiSlaveClient>,
+    write_byte: Cell<u8>,
+    write_buffer: TakeCell<'static, [u8]>,
+    read_buffer: TakeCell<'static, [u8]>,
+    /// The length of the transfer in progress, or 0
+    len: Cell<usize>,
+    /// The number of frames put into the transmit FIFO
+    tx_index: Cell<usize>,
+    /// The number of frames taken from the receive FIFO
+    rx_index: Cell<usize>,
+}
+
+impl Ssi {
+    const fn new(registers: &'static StaticRef<SsiRegisters>, num: usize) -> Ssi {
+        Ssi {
+            registers: registers,
+            num: num,
+            slave_client: OptionalCell::empty(),
+            write_byte: Cell::new(0),
+            write_buffer: TakeCell::empty(),
+            read_buffer: TakeCell::empty(),
+            len: Cell::new(0),
+            tx_index: Cell::new(0),
+            rx_index: Cell::new(0),
+        }
+    }
+
+    fn power_domain(&self) -> prcm::PowerDomain {
+        // SSI0 is in the serial power domain, SSI1 in the peripheral one
+        if self.num == 0 {
+            prcm::PowerDomain::Serial
+        } else {
+            prcm::PowerDomain::Peripherals
+        }
+    }
+
+    fn power_and_clock(&self) {
+        prcm::Power::enable_domain(self.power_domain());
+        while !prcm::Power::is_enabled(self.power_domain()) {}
+        prcm::Clock::enable_ssi(self.num);
+    }
+
+    /// Changes the frame format, which is only possible while the SSI is
+    /// disabled.
+    fn modify_cr0(&self, field: FieldValue<u32, Control0::Register>) {
+        let regs = &*self.registers;
+        let enabled = regs.cr1.is_set(Control1::SSE);
+        regs.cr1.modify(Control1::SSE::CLEAR);
+        regs.cr0.modify(field);
+        if enabled {
+            regs.cr1.modify(Control1::SSE::SET);
+        }
+    }
+
+    /// Puts frames into the transmit FIFO, keeping no more frames in flight
+    /// than the receive FIFO can hold.
+    fn fill_tx_fifo(&self) {
+        let regs = &*self.registers;
+        let len = self.len.get();
+        while self.tx_index.get() < len
+            && self.tx_index.get() - self.rx_index.get() < FIFO_DEPTH
+            && regs.sr.is_set(Status::TNF)
+        {
+            let index = self.tx_index.get();
+            let byte = self
+                .write_buffer
+                .map_or(self.write_byte.get(), |buffer| buffer[index]);
+            regs.dr.set(byte as u32);
+            self.tx_index.set(index + 1);
+        }
+    }
+
+    /// Takes the frames of the transfer from the receive FIFO.
+    fn drain_rx_fifo(&self) {
+        let regs = &*self.registers;
+        let len = self.len.get();
+        while self.rx_index.get() < len && regs.sr.is_set(Status::RNE) {
+            let index = self.rx_index.get();
+            let byte = regs.dr.get() as u8;
+            if index == 0 {
+                self.slave_client.map(|client| client.chip_selected());
+            }
+            self.read_buffer.map(|buffer| buffer[index] = byte);
+            self.rx_index.set(index + 1);
+        }
+    }
+
+    fn flush_rx_fifo(&self) {
+        let regs = &*self.registers;
+        while regs.sr.is_set(Status::RNE) {
+            regs.dr.get();
+        }
+    }
+
+    pub fn handle_interrupt(&self) {
+        let regs = &*self.registers;
+        // Frames lost to an overrun leave the transfer waiting for the master
+        // to clock as many frames again
+        regs.icr.write(Interrupts::RT::SET + Interrupts::ROR::SET);
+
+        if self.len.get() == 0 {
+            self.flush_rx_fifo();
+            return;
+        }
+
+        self.drain_rx_fifo();
+        self.fill_tx_fifo();
+
+        let len = self.len.get();
+        if self.rx_index.get() == len {
+            regs.imsc.set(0);
+            self.len.set(0);
+            let write_buffer = self.write_buffer.take();
+            let read_buffer = self.read_buffer.take();
+            self.slave_client
+                .map(move |client| client.read_write_done(write_buffer, read_buffer, len));
+        }
+    }
+}
+
+impl spi::SpiSlave for Ssi {
+    fn init(&self) {
+        self.power_and_clock();
+        let regs = &*self.registers;
+        regs.cr1.set(0);
+        // Keeps the clock polarity and phase
+        regs.cr0
+            .modify(Control0::FRF::MotorolaSpi + Control0::DSS::Bits8);
+        regs.imsc.set(0);
+        regs.cr1.write(Control1::MS::SET + Control1::SSE::SET);
+    }
+
+    fn has_client(&self) -> bool {
+        self.slave_client.is_some()
+    }
+
+    fn set_client(&self, client: Option<&'static SpiSlaveClient>) {
+        match client {
+            Some(client) => self.slave_client.set(client),
+            None => self.slave_client.clear(),
+        }
+    }
+
+    fn set_write_byte(&self, write_byte: u8) {
+        self.write_byte.set(write_byte);
+    }
+
+    fn read_write_bytes(
+        &self,
+        write_buffer: Option<&'static mut [u8]>,
+        read_buffer: Option<&'static mut [u8]>,
+        len: usize,
+    ) -> ReturnCode {
+        if self.len.get() != 0 {
+            return ReturnCode::EBUSY;
+        }
+        let mut len = len;
+        write_buffer
+            .as_ref()
+            .map(|buffer| len = cmp::min(len, buffer.len()));
+        read_buffer
+            .as_ref()
+            .map(|buffer| len = cmp::min(len, buffer.len()));
+        if len == 0 {
+            return ReturnCode::EINVAL;
+        }
+
+        // Frames the master clocked in between transfers are not part of
+        // this one
+        self.flush_rx_fifo();
+        write_buffer.map(|buffer| self.write_buffer.replace(buffer));
+        read_buffer.map(|buffer| self.read_buffer.replace(buffer));
+        self.len.set(len);
+        self.tx_index.set(0);
+        self.rx_index.set(0);
+        self.fill_tx_fifo();
+
+        let regs = &*self.registers;
+        regs.icr.write(Interrupts::RT::SET + Interrupts::ROR::SET);
+        regs.imsc
+            .write(Interrupts::RX::SET + Interrupts::RT::SET + Interrupts::ROR::SET);
+        ReturnCode::SUCCESS
+    }
+
+    fn set_clock(&self, polarity: ClockPolarity) {
+        match polarity {
+            ClockPolarity::IdleLow => self.modify_cr0(Control0::SPO::CLEAR),
+            ClockPolarity::IdleHigh => self.modify_cr0(Control0::SPO::SET),
+        }
+    }
+
+    fn get_clock(&self) -> ClockPolarity {
+        if self.registers.cr0.is_set(Control0::SPO) {
+            ClockPolarity::IdleHigh
+        } else {
+            ClockPolarity::IdleLow
+        }
+    }
+
+    fn set_phase(&self, phase: ClockPhase) {
+        match phase {
+            ClockPhase::SampleLeading => self.modify_cr0(Control0::SPH::CLEAR),
+            ClockPhase::SampleTrailing => self.modify_cr0(Control0::SPH::SET),
+        }
+    }
+
+    fn get_phase(&self) -> ClockPhase {
+        if self.registers.cr0.is_set(Control0::SPH) {
+            ClockPhase::SampleTrailing
+        } else {
+            ClockPhase::SampleLeading
+        }
+    }
+}
@@ -0,0 +1,601 @@
+//! Sub-GHz radio driver for the CC1352
+//!
+//! Besides the 2.4 GHz band that `ieee802154_radio` uses, the radio of the
+//! CC1352 (and of the CC1312) works in the 868 MHz and 915 MHz bands. There
+//! it runs in the proprietary mode of the radio core (see `rfc::prop`), with
+//! one of two PHYs:
+//!
+//! - `Phy::Fsk50kbps`: 2-GFSK at 50 kbps with a 25 kHz deviation, the
+//!   mandatory mode of the IEEE 802.15.4g SUN FSK PHY.
+//! - `Phy::LongRange`: SimpleLink Long Range, 2-GFSK at 20 ksym/s with
+//!   forward error correction and spreading, for 5 kbps at a better
+//!   sensitivity.
+//!
+//! Channels are numbered from the bottom of the band with a 200 kHz spacing,
+//! as in IEEE 802.15.4g.
+//!
+//! The driver implements the same radio HIL as `ieee802154_radio`, so that
+//! the 802.15.4 stack runs on top of either. Frames are sent with a one byte
+//! length and a 16 bit CRC that the radio adds and checks. There is no
+//! address filtering or automatic acknowledgement in this mode, which is
+//! left to the MAC. Only one of the two radio drivers can be the client of
+//! the radio core at a time.
+//!
+//! Usage
+//! -----
+//!
+//! ```rust
+//! cc26x2::subghz_radio::RADIO.set_band(cc26x2::subghz_radio::Band::Eu868);
+//! cc26x2::subghz_radio::RADIO.set_phy(cc26x2::subghz_radio::Phy::LongRange);
+//! cc26x2::rfc::RFC.set_client(&cc26x2::subghz_radio::RADIO);
+//! cc26x2::subghz_radio::RADIO.set_transmit_client(mac);
+//! cc26x2::subghz_radio::RADIO.set_receive_client(mac, &mut RX_BUF);
+//! cc26x2::subghz_radio::RADIO.start();
+//! ```
+
+use core::cell::Cell;
+use kernel::common::cells::{OptionalCell, TakeCell, VolatileCell};
+use kernel::hil::radio;
+use kernel::ReturnCode;
+
+use crate::rfc::prop::{self, CmdPropRadioDivSetup, CmdPropRx, CmdPropTx};
+use crate::rfc::{self, CmdFs, CommandHeader, DataQueue, RxEntry};
+
+/// The synthesizer runs at 5 times the frequency in the sub-GHz bands
+const LO_DIVIDER: u8 = 5;
+
+const DEFAULT_TX_POWER: i8 = -10;
+
+const SYNC_WORD: u32 = 0x930B_51DE;
+
+/// Options of the receive operation: frames that fail the CRC are flushed,
+/// and a status byte is kept after each frame.
+const RX_CONF: u8 = 0x82;
+/// Longest frame, without its CRC
+const MAX_PAYLOAD_LEN: usize = radio::MAX_FRAME_SIZE - radio::MFR_SIZE;
+
+/// Size of the data of the entry: the length, the frame and the status
+const RX_ENTRY_LEN: usize = 1 + MAX_PAYLOAD_LEN + 1;
+
+/// The PHYs of the sub-GHz bands
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Phy {
+    Fsk50kbps,
+    LongRange,
+}
+
+/// The setup of the modem for a PHY, as in the settings of SmartRF Studio.
+/// Each field is the value of the field of the same name of the radio setup.
+struct PhySettings {
+    /// 2-GFSK, and the deviation in 250 Hz steps
+    modulation: u16,
+    /// The prescaler and the rate word of the symbol rate
+    symbol_rate: u32,
+    rx_bw: u8,
+    /// Length of the preamble, in bytes
+    pream_conf: u8,
+    /// Length of the sync word, bit order and error correction
+    format_conf: u16,
+}
+
+impl Phy {
+    fn settings(&self) -> PhySettings {
+        match *self {
+            Phy::Fsk50kbps => PhySettings {
+                modulation: 0x0321,
+                symbol_rate: 0x0080_000F,
+                rx_bw: 0x52,
+                pream_conf: 0x04,
+                format_conf: 0x00A0,
+            },
+            Phy::LongRange => PhySettings {
+                modulation: 0x00A1,
+                symbol_rate: 0x0033_330F,
+                rx_bw: 0x4C,
+                pream_conf: 0x05,
+                format_conf: 0x08A0,
+            },
+        }
+    }
+}
+
+/// The sub-GHz bands and their channel plans
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Band {
+    /// 863-870 MHz, 34 channels from 863.125 MHz
+    Eu868,
+    /// 902-928 MHz, 129 channels from 902.2 MHz
+    Us915,
+}
+
+/// Spacing of the channels of both bands
+const CHANNEL_SPACING_KHZ: u32 = 200;
+
+impl Band {
+    fn first_channel_khz(&self) -> u32 {
+        match *self {
+            Band::Eu868 => 863_125,
+            Band::Us915 => 902_200,
+        }
+    }
+
+    fn max_channel(&self) -> u8 {
+        match *self {
+            Band::Eu868 => 33,
+            Band::Us915 => 128,
+        }
+    }
+
+    /// The center frequency the radio is set up for, in MHz
+    fn center_mhz(&self) -> u16 {
+        match *self {
+            Band::Eu868 => 868,
+            Band::Us915 => 915,
+        }
+    }
+}
+
+pub struct Radio {
+    setup_cmd: CmdPropRadioDivSetup,
+    fs_cmd: CmdFs,
+    rx_cmd: CmdPropRx,
+    tx_cmd: CmdPropTx,
+    rx_queue: DataQueue,
+    rx_entry: RxEntry<[VolatileCell<u8>; RX_ENTRY_LEN]>,
+    phy: Cell<Phy>,
+    band: Cell<Band>,
+    front_end: Cell<u16>,
+    overrides: Cell<&'static [u32]>,
+    tx_client: OptionalCell<&'static radio::TxClient>,
+    rx_client: OptionalCell<&'static radio::RxClient>,
+    config_client: OptionalCell<&'static radio::ConfigClient>,
+    power_client: OptionalCell<&'static radio::PowerClient>,
+    tx_buf: TakeCell<'static, [u8]>,
+    rx_buf: TakeCell<'static, [u8]>,
+    addr: Cell<u16>,
+    addr_long: Cell<[u8; 8]>,
+    pan: Cell<u16>,
+    channel: Cell<u8>,
+    tx_power: Cell<i8>,
+    /// Set when the configuration is committed during a transmission, to
+    /// apply it after the transmission
+    config_pending: Cell<bool>,
+}
+
+pub static mut RADIO: Radio = Radio::new();
+
+impl Radio {
+    const fn new() -> Radio {
+        Radio {
+            setup_cmd: CmdPropRadioDivSetup {
+                header: CommandHeader::new(prop::CMD_PROP_RADIO_DIV_SETUP),
+                start_trigger: VolatileCell::new(rfc::trigger::NOW),
+                condition: VolatileCell::new(rfc::condition::NEVER),
+                modulation: VolatileCell::new(0),
+                symbol_rate: VolatileCell::new(0),
+                rx_bw: VolatileCell::new(0),
+                pream_conf: VolatileCell::new(0),
+                format_conf: VolatileCell::new(0),
+                config: VolatileCell::new(rfc::DEFAULT_FRONT_END),
+                tx_power: VolatileCell::new(0),
+                reg_override: VolatileCell::new(0),
+                center_freq: VolatileCell::new(0),
+                int_freq: VolatileCell::new(prop::DEFAULT_INT_FREQ),
+                lo_divider: VolatileCell::new(LO_DIVIDER),
+            },
+            fs_cmd: CmdFs {
+                header: CommandHeader::new(rfc::CMD_FS),
+                start_trigger: VolatileCell::new(rfc::trigger::NOW),
+                condition: VolatileCell::new(rfc::condition::NEVER),
+                frequency: VolatileCell::new(0),
+                fract_freq: VolatileCell::new(0),
+                synth_conf: VolatileCell::new(0),
+                _reserved0: [VolatileCell::new(0); 3],
+                _reserved1: VolatileCell::new(0),
+            },
+            rx_cmd: CmdPropRx {
+                header: CommandHeader::new(prop::CMD_PROP_RX),
+                start_trigger: VolatileCell::new(rfc::trigger::NOW),
+                condition: VolatileCell::new(rfc::condition::NEVER),
+                pkt_conf: VolatileCell::new(prop::RX_PKT_CONF),
+                rx_conf: VolatileCell::new(RX_CONF),
+                sync_word: VolatileCell::new(SYNC_WORD),
+                max_pkt_len: VolatileCell::new(MAX_PAYLOAD_LEN as u8),
+                address0: VolatileCell::new(0),
+                address1: VolatileCell::new(0),
+                end_trigger: VolatileCell::new(rfc::trigger::NEVER),
+                end_time: VolatileCell::new(0),
+                queue: VolatileCell::new(0),
+                output: VolatileCell::new(0),
+            },
+            tx_cmd: CmdPropTx {
+                header: CommandHeader::new(prop::CMD_PROP_TX),
+                start_trigger: VolatileCell::new(rfc::trigger::NOW),
+                condition: VolatileCell::new(rfc::condition::NEVER),
+                pkt_conf: VolatileCell::new(prop::TX_PKT_CONF),
+                pkt_len: VolatileCell::new(0),
+                sync_word: VolatileCell::new(SYNC_WORD),
+                pkt: VolatileCell::new(0),
+            },
+            rx_queue: DataQueue::new(),
+            rx_entry: RxEntry::new([VolatileCell::new(0); RX_ENTRY_LEN]),
+            phy: Cell::new(Phy::Fsk50kbps),
+            band: Cell::new(Band::Eu868),
+            front_end: Cell::new(rfc::DEFAULT_FRONT_END),
+            overrides: Cell::new(&[rfc::END_OVERRIDE]),
+            tx_client: OptionalCell::empty(),
+            rx_client: OptionalCell::empty(),
+            config_client: OptionalCell::empty(),
+            power_client: OptionalCell::empty(),
+            tx_buf: TakeCell::empty(),
+            rx_buf: TakeCell::empty(),
+            addr: Cell::new(0),
+            addr_long: Cell::new([0; 8]),
+            pan: Cell::new(0),
+            channel: Cell::new(0),
+            tx_power: Cell::new(DEFAULT_TX_POWER),
+            config_pending: Cell::new(false),
+        }
+    }
+
+    fn rfc(&self) -> &'static rfc::RFCore {
+        unsafe { &rfc::RFC }
+    }
+
+    /// Sets the PHY, which takes effect when the radio is next started.
+    pub fn set_phy(&self, phy: Phy) {
+        self.phy.set(phy);
+    }
+
+    /// Sets the band, which takes effect when the radio is next started.
+    /// The channel is reset to the first one of the band.
+    pub fn set_band(&self, band: Band) {
+        self.band.set(band);
+        self.channel.set(0);
+    }
+
+    /// Sets the front end configuration of the radio setup and the register
+    /// overrides, which are specific to the board and to the PHY. The
+    /// overrides end with `rfc::END_OVERRIDE`. They take effect when the
+    /// radio is next started.
+    pub fn set_front_end(&self, config: u16, overrides: &'static [u32]) {
+        self.front_end.set(config);
+        self.overrides.set(overrides);
+    }
+
+    fn power_up(&self) -> ReturnCode {
+        let result = self
+            .rfc()
+            .enable(rfc::event::RX_ENTRY_DONE | rfc::event::LAST_COMMAND_DONE);
+        if result != ReturnCode::SUCCESS {
+            return result;
+        }
+
+        let setup = &self.setup_cmd;
+        let phy = self.phy.get().settings();
+        setup.modulation.set(phy.modulation);
+        setup.symbol_rate.set(phy.symbol_rate);
+        setup.rx_bw.set(phy.rx_bw);
+        setup.pream_conf.set(phy.pream_conf);
+        setup.format_conf.set(phy.format_conf);
+        setup.config.set(self.front_end.get());
+        setup
+            .tx_power
+            .set(rfc::tx_power_setting(rfc::TX_POWER_SUB_GHZ, self.tx_power.get()).unwrap_or(0));
+        setup.reg_override.set(self.overrides.get().as_ptr() as u32);
+        setup.center_freq.set(self.band.get().center_mhz());
+        let result = self.rfc().run_command(&setup.header);
+        if result != ReturnCode::SUCCESS || setup.header.status.get() != rfc::status::DONE_OK {
+            self.rfc().disable();
+            return ReturnCode::FAIL;
+        }
+
+        let result = self.tune();
+        if result != ReturnCode::SUCCESS {
+            self.rfc().disable();
+            return result;
+        }
+        self.start_rx()
+    }
+
+    /// Programs the synthesizer for the frequency of the channel.
+    fn tune(&self) -> ReturnCode {
+        let band = self.band.get();
+        let khz = band.first_channel_khz() + self.channel.get() as u32 * CHANNEL_SPACING_KHZ;
+
+        let fs = &self.fs_cmd;
+        fs.frequency.set((khz / 1000) as u16);
+        // The fraction of a MHz, in units of 2^-16 MHz
+        fs.fract_freq.set((((khz % 1000) << 16) / 1000) as u16);
+        let result = self.rfc().run_command(&fs.header);
+        if result != ReturnCode::SUCCESS || fs.header.status.get() != rfc::status::DONE_OK {
+            return ReturnCode::FAIL;
+        }
+        ReturnCode::SUCCESS
+    }
+
+    fn start_rx(&self) -> ReturnCode {
+        self.rx_queue.set_single_entry(&self.rx_entry);
+
+        let rx = &self.rx_cmd;
+        rx.queue.set(&self.rx_queue as *const DataQueue as u32);
+        self.rfc().send_command(&rx.header)
+    }
+
+    fn stop_rx(&self) {
+        let header = &self.rx_cmd.header;
+        if header.status.get() == rfc::status::IDLE || header.is_done() {
+            return;
+        }
+        if self.rfc().send_direct(rfc::cmd::ABORT) == ReturnCode::SUCCESS {
+            while !header.is_done() {}
+        }
+    }
+
+    /// Applies a new channel, by stopping the receive operation, tuning the
+    /// synthesizer and starting the receive operation again.
+    fn retune(&self) -> ReturnCode {
+        self.stop_rx();
+        let result = self.tune();
+        if result != ReturnCode::SUCCESS {
+            return result;
+        }
+        self.start_rx()
+    }
+
+    /// Hands the frame in the receive queue to the client, if there is one.
+    fn receive_frame(&self) {
+        let entry = &self.rx_entry;
+        if entry.status.get() != rfc::entry::FINISHED {
+            return;
+        }
+
+        // The data is the length, the frame without its CRC, and a status
+        // byte. Frames that fail the CRC are not kept.
+        let len = entry.data[0].get() as usize;
+        if len > 1 && len < RX_ENTRY_LEN {
+            let payload_len = len - 1;
+
+            self.rx_buf.take().map(|buf| {
+                if radio::PSDU_OFFSET + payload_len + radio::MFR_SIZE > buf.len() {
+                    self.rx_buf.replace(buf);
+                    return;
+                }
+                // The PHY length of the HIL counts the FCS
+                buf[radio::PSDU_OFFSET - 1] = (payload_len + radio::MFR_SIZE) as u8;
+                for (byte, data) in buf[radio::PSDU_OFFSET..radio::PSDU_OFFSET + payload_len]
+                    .iter_mut()
+                    .zip(entry.data[1..].iter())
+                {
+                    *byte = data.get();
+                }
+
+                if self.rx_client.is_some() {
+                    self.rx_client.map(move |client| {
+                        client.receive(buf, payload_len, true, ReturnCode::SUCCESS)
+                    });
+                } else {
+                    self.rx_buf.replace(buf);
+                }
+            });
+        }
+        entry.status.set(rfc::entry::PENDING);
+    }
+
+    fn transmit_done(&self) {
+        let result = if self.tx_cmd.header.status.get() == prop::PROP_DONE_OK {
+            ReturnCode::SUCCESS
+        } else {
+            ReturnCode::FAIL
+        };
+        // The receive operation was stopped for the transmission
+        if self.config_pending.take() {
+            let result = self.retune();
+            self.config_client.map(|client| client.config_done(result));
+        } else {
+            self.start_rx();
+        }
+        self.tx_buf.take().map(|buf| {
+            self.tx_client
+                .map(move |client| client.send_done(buf, false, result));
+        });
+    }
+}
+
+impl rfc::Client for Radio {
+    fn cpe_events(&self, events: u32) {
+        if events & rfc::event::RX_ENTRY_DONE != 0 {
+            self.receive_frame();
+        }
+        if events & rfc::event::LAST_COMMAND_DONE == 0 || !self.rfc().is_on() {
+            return;
+        }
+        if self.tx_buf.is_some() {
+            // Also signalled when the receive operation is stopped for the
+            // transmission
+            if self.tx_cmd.header.is_done() {
+                self.transmit_done();
+            }
+        } else if self.rx_cmd.header.is_done() {
+            // The receive operation stops if it fails, e.g. on an overflow
+            self.start_rx();
+        }
+    }
+}
+
+impl radio::Radio for Radio {}
+
+impl radio::RadioConfig for Radio {
+    fn initialize(
+        &self,
+        _spi_buf: &'static mut [u8],
+        _reg_write: &'static mut [u8],
+        _reg_read: &'static mut [u8],
+    ) -> ReturnCode {
+        ReturnCode::SUCCESS
+    }
+
+    fn reset(&self) -> ReturnCode {
+        if self.rfc().is_on() {
+            self.rfc().disable();
+        }
+        self.power_up()
+    }
+
+    fn start(&self) -> ReturnCode {
+        if self.rfc().is_on() {
+            return ReturnCode::SUCCESS;
+        }
+        let result = self.power_up();
+        if result == ReturnCode::SUCCESS {
+            self.power_client.map(|client| client.changed(true));
+        }
+        result
+    }
+
+    fn stop(&self) -> ReturnCode {
+        if !self.rfc().is_on() {
+            return ReturnCode::SUCCESS;
+        }
+        if self.tx_buf.is_some() {
+            return ReturnCode::EBUSY;
+        }
+        self.stop_rx();
+        self.rfc().disable();
+        self.power_client.map(|client| client.changed(false));
+        ReturnCode::SUCCESS
+    }
+
+    fn is_on(&self) -> bool {
+        self.rfc().is_on()
+    }
+
+    fn busy(&self) -> bool {
+        self.tx_buf.is_some()
+    }
+
+    fn set_power_client(&self, client: &'static radio::PowerClient) {
+        self.power_client.set(client);
+    }
+
+    /// Only the channel takes effect at once. The addresses are used by the
+    /// MAC, and the transmit power when the radio is next started.
+    fn config_commit(&self) {
+        if !self.rfc().is_on() {
+            self.config_client
+                .map(|client| client.config_done(ReturnCode::SUCCESS));
+        } else if self.tx_buf.is_some() {
+            self.config_pending.set(true);
+        } else {
+            let result = self.retune();
+            self.config_client.map(|client| client.config_done(result));
+        }
+    }
+
+    fn set_config_client(&self, client: &'static radio::ConfigClient) {
+        self.config_client.set(client);
+    }
+
+    fn get_address(&self) -> u16 {
+        self.addr.get()
+    }
+
+    fn get_address_long(&self) -> [u8; 8] {
+        self.addr_long.get()
+    }
+
+    fn get_pan(&self) -> u16 {
+        self.pan.get()
+    }
+
+    fn get_tx_power(&self) -> i8 {
+        self.tx_power.get()
+    }
+
+    fn get_channel(&self) -> u8 {
+        self.channel.get()
+    }
+
+    fn set_address(&self, addr: u16) {
+        self.addr.set(addr);
+    }
+
+    fn set_address_long(&self, addr: [u8; 8]) {
+        self.addr_long.set(addr);
+    }
+
+    fn set_pan(&self, id: u16) {
+        self.pan.set(id);
+    }
+
+    fn set_tx_power(&self, power: i8) -> ReturnCode {
+        match rfc::tx_power_setting(rfc::TX_POWER_SUB_GHZ, power) {
+            Some(_) => {
+                self.tx_power.set(power);
+                ReturnCode::SUCCESS
+            }
+            None => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    fn set_channel(&self, chan: u8) -> ReturnCode {
+        if chan > self.band.get().max_channel() {
+            return ReturnCode::EINVAL;
+        }
+        self.channel.set(chan);
+        ReturnCode::SUCCESS
+    }
+}
+
+impl radio::RadioData for Radio {
+    fn set_transmit_client(&self, client: &'static radio::TxClient) {
+        self.tx_client.set(client);
+    }
+
+    fn set_receive_client(&self, client: &'static radio::RxClient, buffer: &'static mut [u8]) {
+        self.rx_client.set(client);
+        self.rx_buf.replace(buffer);
+    }
+
+    fn set_receive_buffer(&self, buffer: &'static mut [u8]) {
+        self.rx_buf.replace(buffer);
+    }
+
+    fn transmit(
+        &self,
+        buf: &'static mut [u8],
+        frame_len: usize,
+    ) -> (ReturnCode, Option<&'static mut [u8]>) {
+        if !self.rfc().is_on() {
+            return (ReturnCode::EOFF, Some(buf));
+        } else if self.tx_buf.is_some() {
+            return (ReturnCode::EBUSY, Some(buf));
+        } else if radio::PSDU_OFFSET + frame_len > buf.len() || frame_len > MAX_PAYLOAD_LEN {
+            return (ReturnCode::ESIZE, Some(buf));
+        }
+
+        // The radio core can't transmit while the receive operation runs in
+        // this mode, so it is stopped until the transmission is done
+        self.stop_rx();
+
+        // The packet starts with its length byte, which counts the frame but
+        // not the CRC that the radio core adds
+        buf[radio::PSDU_OFFSET - 1] = frame_len as u8;
+        let tx = &self.tx_cmd;
+        tx.pkt_len.set((frame_len + 1) as u8);
+        tx.pkt.set(buf[radio::PSDU_OFFSET - 1..].as_ptr() as u32);
+        let result = self.rfc().send_command(&tx.header);
+        if result != ReturnCode::SUCCESS {
+            self.start_rx();
+            return (result, Some(buf));
+        }
+        self.tx_buf.replace(buf);
+        (ReturnCode::SUCCESS, None)
+    }
+
+    fn get_tx_info(&self) -> radio::TxInfo {
+        // Acknowledgements are not waited for, so there are no retries or
+        // acknowledgement RSSI to report
+        radio::TxInfo::default()
+    }
+}
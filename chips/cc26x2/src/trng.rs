@@ -1,13 +1,14 @@
 //! TRNG - Random Number Generator for the cc26x2 family
 //!
-//! Generates a random number using hardware entropy.
-//!
+//! Generates random numbers from the jitter of free running oscillators. The
+//! TRNG produces 64 bits at a time, and interrupts when they are ready, so
+//! `Entropy32` clients are passed entropy as the TRNG generates it.
 
 use crate::prcm;
 use kernel::common::cells::OptionalCell;
 use kernel::common::registers::{register_bitfields, ReadOnly, ReadWrite, WriteOnly};
 use kernel::common::StaticRef;
-use kernel::hil::entropy;
+use kernel::hil::entropy::{self, Continue};
 use kernel::ReturnCode;
 
 #[repr(C)]
@@ -16,7 +17,7 @@ struct RngRegisters {
     out1: ReadOnly<u32>,
 
     irq_flag_stat: ReadOnly<u32, IrqStatus::Register>,
-    _irq_flag_mask: ReadOnly<u32>,
+    irq_flag_mask: ReadWrite<u32, IrqStatus::Register>,
     irq_flag_clr: WriteOnly<u32, IrqFlagClear::Register>,
 
     ctl: ReadWrite<u32, Control::Register>,
@@ -134,23 +135,59 @@ impl<'a> Trng<'a> {
         // Wait for a number to be ready
         while !regs.irq_flag_stat.is_set(IrqStatus::READY) {}
 
+        let number = ((regs.out0.get() as u64) << 32) | (regs.out1.get() as u64);
         // Initiate generation of a new number
         regs.irq_flag_clr.write(IrqFlagClear::READY::SET);
+        number
+    }
+
+    fn disable(&self) {
+        let regs = &*self.registers;
+        regs.irq_flag_mask.set(0);
+        regs.ctl.modify(Control::TRNG_EN::CLEAR);
+    }
+
+    pub fn handle_interrupt(&self) {
+        let regs = &*self.registers;
 
-        ((regs.out0.get() as u64) << 32) | (regs.out1.get() as u64)
+        // The flag stays set until the number is read, so the interrupt is
+        // masked if the client does not read it
+        regs.irq_flag_mask.set(0);
+
+        let result = self.client.map_or(Continue::Done, |client| {
+            client.entropy_available(
+                &mut TrngIter {
+                    trng: self,
+                    pending: None,
+                },
+                ReturnCode::SUCCESS,
+            )
+        });
+        match result {
+            Continue::Done => self.disable(),
+            Continue::More => regs.irq_flag_mask.write(IrqStatus::READY::SET),
+        }
     }
 }
 
-#[allow(dead_code)]
-struct TrngIter<'a, 'b: 'a>(&'a Trng<'b>);
+/// Yields both halves of each number that is ready.
+struct TrngIter<'a, 'b: 'a> {
+    trng: &'a Trng<'b>,
+    pending: Option<u32>,
+}
 
 impl<'a, 'b> Iterator for TrngIter<'a, 'b> {
     type Item = u32;
 
     fn next(&mut self) -> Option<u32> {
-        let regs = &*self.0.registers;
-        if regs.ctl.is_set(Control::TRNG_EN) {
-            Some((self.0.read_number_blocking() & 0xFFFF_FFFF) as u32)
+        if let Some(word) = self.pending.take() {
+            return Some(word);
+        }
+        let regs = &*self.trng.registers;
+        if regs.irq_flag_stat.is_set(IrqStatus::READY) {
+            let number = self.trng.read_number_blocking();
+            self.pending = Some(number as u32);
+            Some((number >> 32) as u32)
         } else {
             None
         }
@@ -159,12 +196,17 @@ impl<'a, 'b> Iterator for TrngIter<'a, 'b> {
 
 impl<'a> entropy::Entropy32<'a> for Trng<'a> {
     fn get(&self) -> ReturnCode {
-        self.enable();
+        let regs = &*self.registers;
+        if !regs.ctl.is_set(Control::TRNG_EN) {
+            self.enable();
+        }
+        regs.irq_flag_mask.write(IrqStatus::READY::SET);
         ReturnCode::SUCCESS
     }
 
     fn cancel(&self) -> ReturnCode {
-        ReturnCode::FAIL
+        self.disable();
+        ReturnCode::SUCCESS
     }
 
     fn set_client(&'a self, client: &'a entropy::Client32) {
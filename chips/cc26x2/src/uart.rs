@@ -1,14 +1,36 @@
 //! UART driver, cc26x2 family
+//!
+//! Buffers are moved between memory and the FIFOs of the UART by the uDMA
+//! controller, so a transfer raises a single interrupt once it is done,
+//! rather than one per byte. Single words still go through the FIFOs.
+//!
+//! The baud rate is derived from the high frequency clock. A UART that is
+//! registered as a client of `oscillator::OSC` derives it again when the
+//! source of the clock changes.
+//!
+//! `receive_automatic` completes a buffer early once the line has been idle
+//! for 32 bit periods, the fixed receive timeout of the UART. The uDMA then
+//! only moves bytes in bursts, when the RX FIFO is half full, so the last
+//! few bytes stay in the FIFO and raise the timeout, and the driver reads
+//! them itself.
+//!
+//! With hardware flow control, the UART holds RTS off while its RX FIFO is
+//! full and only transmits while CTS is asserted. The CTS and RTS pins are
+//! routed to it with `GPIOPin::enable_uart0_cts()` and the like. Bytes that
+//! arrive while nothing is being received are then kept in the FIFO, so
+//! that the peer stops sending, instead of being dropped.
+use crate::deferred_call_tasks::DeferredCallTask;
+use crate::oscillator::{self, HfClockClient, HfSource};
 use crate::prcm;
+use crate::udma::{self, Udma};
 use core::cell::Cell;
 use kernel::common::cells::{MapCell, OptionalCell};
+use kernel::common::deferred_call::DeferredCall;
 use kernel::common::registers::{register_bitfields, ReadOnly, ReadWrite, WriteOnly};
 use kernel::common::StaticRef;
 use kernel::hil::uart;
 use kernel::ReturnCode;
 
-const MCU_CLOCK: u32 = 48_000_000;
-
 #[repr(C)]
 struct UartRegisters {
     dr: ReadWrite<u32>,
@@ -25,11 +47,21 @@ struct UartRegisters {
     ris: ReadOnly<u32, Interrupts::Register>,
     mis: ReadOnly<u32, Interrupts::Register>,
     icr: WriteOnly<u32, Interrupts::Register>,
-    dmactl: ReadWrite<u32>,
+    dmactl: ReadWrite<u32, DmaControl::Register>,
 }
 
-pub static mut UART0: UART = UART::new(&UART0_REG);
-pub static mut UART1: UART = UART::new(&UART1_REG);
+pub static mut UART0: UART = UART::new(
+    &UART0_REG,
+    udma::Channel::Uart0Tx,
+    udma::Channel::Uart0Rx,
+    DeferredCallTask::Uart0,
+);
+pub static mut UART1: UART = UART::new(
+    &UART1_REG,
+    udma::Channel::Uart1Tx,
+    udma::Channel::Uart1Rx,
+    DeferredCallTask::Uart1,
+);
 
 register_bitfields![
     u32,
@@ -37,7 +69,9 @@ register_bitfields![
         UART_ENABLE OFFSET(0) NUMBITS(1) [],
         LB_ENABLE OFFSET(7) NUMBITS(1) [],
         TX_ENABLE OFFSET(8) NUMBITS(1) [],
-        RX_ENABLE OFFSET(9) NUMBITS(1) []
+        RX_ENABLE OFFSET(9) NUMBITS(1) [],
+        RTS_ENABLE OFFSET(14) NUMBITS(1) [],
+        CTS_ENABLE OFFSET(15) NUMBITS(1) []
     ],
     LineControl [
         FIFO_ENABLE OFFSET(4) NUMBITS(1) [],
@@ -78,6 +112,11 @@ register_bitfields![
         BE OFFSET(9) NUMBITS(1) [],                  // break error interrupt mask
         OE OFFSET(10) NUMBITS(1) [],                 // overrun error interrupt mask
         END_OF_TRANSMISSION OFFSET(11) NUMBITS(1) [] // end of transmission interrupt mask
+    ],
+    DmaControl [
+        RXDMAE OFFSET(0) NUMBITS(1) [],
+        TXDMAE OFFSET(1) NUMBITS(1) [],
+        DMAONERR OFFSET(2) NUMBITS(1) []
     ]
 ];
 
@@ -89,15 +128,17 @@ const UART0_REG: StaticRef<UartRegisters> =
 const UART1_REG: StaticRef<UartRegisters> =
     unsafe { StaticRef::new(UART1_BASE as *const UartRegisters) };
 
-/// Stores an ongoing TX transaction
+/// Stores an ongoing transaction
 struct Transaction {
-    /// The buffer containing the bytes to transmit as it should be returned to
-    /// the client
+    /// The buffer containing the bytes to transmit or receive as it should be
+    /// returned to the client
     buffer: &'static mut [u8],
-    /// The total amount to transmit
+    /// The total amount to transfer
     length: usize,
-    /// The index of the byte currently being sent
+    /// The index of the first byte of the DMA transfer in progress
     index: usize,
+    /// The number of bytes the DMA transfer in progress moves
+    chunk: usize,
 }
 
 pub struct UART<'a> {
@@ -105,22 +146,46 @@ pub struct UART<'a> {
     tx_client: OptionalCell<&'a uart::TransmitClient>,
     rx_client: OptionalCell<&'a uart::ReceiveClient>,
     tx: MapCell<Transaction>,
+    /// The buffer to transmit once `tx` is done
+    tx_next: MapCell<Transaction>,
     rx: MapCell<Transaction>,
     receiving_word: Cell<bool>,
+    /// Whether the buffer being received completes once the line is idle
+    rx_automatic: Cell<bool>,
+    /// The configured baud rate, 0 until the UART is configured
+    baud_rate: Cell<u32>,
+    /// Whether RTS and CTS are used
+    flow_control: Cell<bool>,
+    tx_dma: udma::Channel,
+    rx_dma: udma::Channel,
+    /// Receives a word that waited in the RX FIFO
+    deferred_call: DeferredCall<DeferredCallTask>,
 }
 
 impl<'a> UART<'a> {
-    const fn new(registers: &'static StaticRef<UartRegisters>) -> UART {
+    const fn new(
+        registers: &'static StaticRef<UartRegisters>,
+        tx_dma: udma::Channel,
+        rx_dma: udma::Channel,
+        task: DeferredCallTask,
+    ) -> UART {
         UART {
             registers,
+            tx_dma,
+            rx_dma,
+            deferred_call: unsafe { DeferredCall::new(task) },
 
             tx_client: OptionalCell::empty(),
             rx_client: OptionalCell::empty(),
 
             tx: MapCell::empty(),
+            tx_next: MapCell::empty(),
             rx: MapCell::empty(),
 
             receiving_word: Cell::new(false),
+            rx_automatic: Cell::new(false),
+            baud_rate: Cell::new(0),
+            flow_control: Cell::new(false),
         }
     }
 
@@ -129,6 +194,7 @@ impl<'a> UART<'a> {
     /// This function needs to be run before the UART module is used.
     pub fn initialize(&self) {
         self.power_and_clock();
+        Udma::initialize();
         self.enable_interrupts();
     }
 
@@ -138,9 +204,11 @@ impl<'a> UART<'a> {
         prcm::Clock::enable_uarts();
     }
 
-    fn set_baud_rate(&self, baud_rate: u32) {
+    /// Sets the divisors for `baud_rate` from a `clock` in Hz. They take
+    /// effect on the next write of the line control register.
+    fn set_baud_rate(&self, baud_rate: u32, clock: u32) {
         // Fractional baud rate divider
-        let div = (((MCU_CLOCK * 8) / baud_rate) + 1) / 2;
+        let div = (((clock * 8) / baud_rate) + 1) / 2;
         // Set the baud rate
         self.registers.ibrd.write(IntDivisor::DIVISOR.val(div / 64));
         self.registers
@@ -165,6 +233,13 @@ impl<'a> UART<'a> {
         );
     }
 
+    /// Stop interrupts for received bytes, which are left in the RX FIFO.
+    fn disable_rx_interrupts(&self) {
+        self.registers
+            .imsc
+            .modify(Interrupts::RX::CLEAR + Interrupts::RX_TIMEOUT::CLEAR);
+    }
+
     fn enable_interrupts(&self) {
         // set only interrupts used
         self.registers.imsc.modify(
@@ -176,68 +251,163 @@ impl<'a> UART<'a> {
 
     /// Clears all interrupts related to UART.
     pub fn handle_interrupt(&self) {
+        let timed_out = self.registers.mis.is_set(Interrupts::RX_TIMEOUT);
         // Clear interrupts
         self.registers.icr.write(Interrupts::ALL_INTERRUPTS::SET);
 
-        // Hardware RX FIFO is not empty
-        while self.rx_fifo_not_empty() {
-            // word read request was made
-            if self.receiving_word.get() {
-                let word = self.read();
-                self.receiving_word.set(false);
-                self.rx_client.map(move |client| {
-                    client.received_word(word, ReturnCode::SUCCESS, uart::Error::None);
-                });
-            }
-            // buffer read request was made
-            else if self.rx.is_some() {
-                self.rx.take().map(|mut rx| {
-                    // read in a byte
-                    if rx.index < rx.length {
-                        let byte = self.read() as u8;
-                        rx.buffer[rx.index] = byte;
-                        rx.index += 1;
-                    }
+        if Udma::is_done(self.rx_dma) {
+            Udma::clear_done(self.rx_dma);
+            self.rx.take().map(|mut rx| {
+                rx.index += rx.chunk;
+                if rx.index < rx.length {
+                    self.start_rx_dma(&mut rx);
+                    self.rx.put(rx);
+                } else {
+                    self.registers.dmactl.modify(DmaControl::RXDMAE::CLEAR);
+                    self.rx_client.map(move |client| {
+                        client.received_buffer(
+                            rx.buffer,
+                            rx.length,
+                            ReturnCode::SUCCESS,
+                            uart::Error::None,
+                        );
+                    });
+                }
+            });
+        }
 
-                    if rx.index == rx.length {
-                        self.rx_client.map(move |client| {
-                            client.received_buffer(
-                                rx.buffer,
-                                rx.index,
-                                ReturnCode::SUCCESS,
-                                uart::Error::None,
-                            );
-                        });
-                    } else {
-                        self.rx.put(rx);
+        if Udma::is_done(self.tx_dma) {
+            Udma::clear_done(self.tx_dma);
+            self.tx.take().map(|mut tx| {
+                tx.index += tx.chunk;
+                if tx.index < tx.length {
+                    self.start_tx_dma(&mut tx);
+                    self.tx.put(tx);
+                } else {
+                    // Start the queued buffer before the client hears of
+                    // this one, so that the line does not go idle
+                    match self.tx_next.take() {
+                        Some(mut next) => {
+                            self.start_tx_dma(&mut next);
+                            self.tx.put(next);
+                        }
+                        None => self.registers.dmactl.modify(DmaControl::TXDMAE::CLEAR),
                     }
-                });
-            }
-            // no current read request
-            else {
-                // read bytes into the void to avoid hardware RX buffer overflow
-                self.read();
-            }
+                    self.tx_client.map(move |client| {
+                        client.transmitted_buffer(tx.buffer, tx.length, ReturnCode::SUCCESS);
+                    });
+                }
+            });
         }
 
-        self.tx.take().map(|mut tx| {
-            // send out one byte at a time, IRQ when TX FIFO empty will bring us back
-            if self.tx_fifo_not_full() && tx.index < tx.length {
-                self.write(tx.buffer[tx.index] as u32);
-                tx.index += 1;
+        if timed_out && self.rx_automatic.get() {
+            self.rx.take().map(|rx| self.receive_timed_out(rx));
+        }
+
+        // The DMA drains the RX FIFO while a buffer is being received
+        if self.rx.is_none() && self.flow_control.get() {
+            self.receive_waiting_word();
+            if !self.receiving_word.get() {
+                self.disable_rx_interrupts();
             }
-            // request is done
-            if tx.index == tx.length {
-                self.tx_client.map(move |client| {
-                    client.transmitted_buffer(tx.buffer, tx.length, ReturnCode::SUCCESS);
-                });
-            } else {
-                // keep TX buffer as there is more left in request
-                self.tx.put(tx);
+        } else if self.rx.is_none() {
+            while self.rx_fifo_not_empty() {
+                // word read request was made
+                if self.receiving_word.get() {
+                    self.receive_waiting_word();
+                }
+                // no current read request
+                else {
+                    // read bytes into the void to avoid hardware RX buffer overflow
+                    self.read();
+                }
             }
+        }
+    }
+
+    /// Complete `rx` with the bytes received so far, as the line is idle.
+    fn receive_timed_out(&self, mut rx: Transaction) {
+        let mut received = rx.index + rx.chunk - Udma::stop(self.rx_dma);
+        Udma::clear_done(self.rx_dma);
+        // Bytes below the burst size are left in the FIFO
+        while received < rx.length && self.rx_fifo_not_empty() {
+            rx.buffer[received] = self.read() as u8;
+            received += 1;
+        }
+        if received == 0 {
+            // Nothing was received yet, so keep waiting
+            self.start_rx_dma(&mut rx);
+            self.rx.put(rx);
+            return;
+        }
+        self.registers.dmactl.modify(DmaControl::RXDMAE::CLEAR);
+        self.rx_client.map(move |client| {
+            client.received_buffer(rx.buffer, received, ReturnCode::SUCCESS, uart::Error::None);
         });
     }
 
+    /// Start receiving `len` bytes into `buffer`, up to the first idle line
+    /// if `automatic`.
+    fn receive(
+        &self,
+        buffer: &'static mut [u8],
+        len: usize,
+        automatic: bool,
+    ) -> (ReturnCode, Option<&'static mut [u8]>) {
+        if len == 0 || len > buffer.len() {
+            (ReturnCode::ESIZE, Some(buffer))
+        } else if self.rx.is_some() || self.receiving_word.get() {
+            (ReturnCode::EBUSY, Some(buffer))
+        } else {
+            let mut rx = Transaction {
+                buffer: buffer,
+                length: len,
+                index: 0,
+                chunk: 0,
+            };
+            self.rx_automatic.set(automatic);
+            Udma::set_burst_only(self.rx_dma, automatic);
+            self.start_rx_dma(&mut rx);
+            self.rx.put(rx);
+            self.registers.dmactl.modify(DmaControl::RXDMAE::SET);
+            self.enable_interrupts();
+
+            (ReturnCode::SUCCESS, None)
+        }
+    }
+
+    pub fn handle_deferred_call(&self) {
+        self.receive_waiting_word();
+    }
+
+    /// Pass the first byte of the RX FIFO to a pending word request.
+    fn receive_waiting_word(&self) {
+        if self.receiving_word.get() && self.rx_fifo_not_empty() {
+            let word = self.read();
+            self.receiving_word.set(false);
+            self.rx_client.map(move |client| {
+                client.received_word(word, ReturnCode::SUCCESS, uart::Error::None);
+            });
+        }
+    }
+
+    /// Move the next part of `tx` to the TX FIFO.
+    fn start_tx_dma(&self, tx: &mut Transaction) {
+        let register = &self.registers.dr as *const ReadWrite<u32> as usize;
+        tx.chunk =
+            Udma::transfer_to_peripheral(self.tx_dma, &tx.buffer[tx.index..tx.length], register);
+    }
+
+    /// Move the next part of `rx` from the RX FIFO.
+    fn start_rx_dma(&self, rx: &mut Transaction) {
+        let register = &self.registers.dr as *const ReadWrite<u32> as usize;
+        rx.chunk = Udma::transfer_from_peripheral(
+            self.rx_dma,
+            register,
+            &mut rx.buffer[rx.index..rx.length],
+        );
+    }
+
     pub fn write(&self, c: u32) {
         // Put byte in data register
         self.registers.dr.set(c);
@@ -264,6 +434,8 @@ impl<'a> UART<'a> {
 
 impl<'a> uart::Uart<'a> for UART<'a> {}
 impl<'a> uart::UartData<'a> for UART<'a> {}
+impl<'a> uart::UartAdvanced<'a> for UART<'a> {}
+impl<'a> uart::UartDataAdvanced<'a> for UART<'a> {}
 
 impl<'a> uart::Configure for UART<'a> {
     fn configure(&self, params: uart::Parameters) -> ReturnCode {
@@ -275,14 +447,12 @@ impl<'a> uart::Configure for UART<'a> {
         if params.parity != uart::Parity::None {
             return ReturnCode::ENOSUPPORT;
         }
-        if params.hw_flow_control != false {
-            return ReturnCode::ENOSUPPORT;
-        }
-
         // Disable the UART before configuring
         self.disable();
 
-        self.set_baud_rate(params.baud_rate);
+        let clock = unsafe { oscillator::OSC.hf_source().frequency() };
+        self.set_baud_rate(params.baud_rate, clock);
+        self.baud_rate.set(params.baud_rate);
 
         // Set word length
         let word_width = match params.width {
@@ -296,15 +466,36 @@ impl<'a> uart::Configure for UART<'a> {
 
         self.enable_interrupts();
 
-        // Enable UART, RX and TX
-        self.registers
-            .ctl
-            .write(Control::UART_ENABLE::SET + Control::RX_ENABLE::SET + Control::TX_ENABLE::SET);
+        // Enable UART, RX and TX, and RTS and CTS for flow control
+        self.flow_control.set(params.hw_flow_control);
+        let flow_control = if params.hw_flow_control {
+            Control::RTS_ENABLE::SET + Control::CTS_ENABLE::SET
+        } else {
+            Control::RTS_ENABLE::CLEAR + Control::CTS_ENABLE::CLEAR
+        };
+        self.registers.ctl.write(
+            Control::UART_ENABLE::SET
+                + Control::RX_ENABLE::SET
+                + Control::TX_ENABLE::SET
+                + flow_control,
+        );
 
         ReturnCode::SUCCESS
     }
 }
 
+impl<'a> HfClockClient for UART<'a> {
+    fn hf_clock_changed(&self, source: HfSource) {
+        let baud_rate = self.baud_rate.get();
+        if baud_rate == 0 {
+            return;
+        }
+        self.set_baud_rate(baud_rate, source.frequency());
+        // The character being sent completes with the old divisors
+        self.registers.lcrh.set(self.registers.lcrh.get());
+    }
+}
+
 impl<'a> uart::Transmit<'a> for UART<'a> {
     fn set_transmit_client(&self, client: &'a uart::TransmitClient) {
         self.tx_client.set(client);
@@ -321,15 +512,37 @@ impl<'a> uart::Transmit<'a> for UART<'a> {
         } else if self.tx.is_some() {
             (ReturnCode::EBUSY, Some(buffer))
         } else {
-            // we will send one byte, causing EOT interrupt
-            if self.tx_fifo_not_full() {
-                self.write(buffer[0] as u32);
-            }
+            let mut tx = Transaction {
+                buffer: buffer,
+                length: len,
+                index: 0,
+                chunk: 0,
+            };
+            self.start_tx_dma(&mut tx);
+            self.tx.put(tx);
             // Transaction will be continued in interrupt bottom half
-            self.tx.put(Transaction {
+            self.registers.dmactl.modify(DmaControl::TXDMAE::SET);
+            (ReturnCode::SUCCESS, None)
+        }
+    }
+
+    fn transmit_continue(
+        &self,
+        buffer: &'static mut [u8],
+        len: usize,
+    ) -> (ReturnCode, Option<&'static mut [u8]>) {
+        if len == 0 || len > buffer.len() {
+            (ReturnCode::ESIZE, Some(buffer))
+        } else if self.tx.is_none() {
+            self.transmit_buffer(buffer, len)
+        } else if self.tx_next.is_some() {
+            (ReturnCode::EBUSY, Some(buffer))
+        } else {
+            self.tx_next.put(Transaction {
                 buffer: buffer,
                 length: len,
-                index: 1,
+                index: 0,
+                chunk: 0,
             });
             (ReturnCode::SUCCESS, None)
         }
@@ -359,19 +572,7 @@ impl<'a> uart::Receive<'a> for UART<'a> {
         buffer: &'static mut [u8],
         len: usize,
     ) -> (ReturnCode, Option<&'static mut [u8]>) {
-        if len == 0 || len > buffer.len() {
-            (ReturnCode::ESIZE, Some(buffer))
-        } else if self.rx.is_some() || self.receiving_word.get() {
-            (ReturnCode::EBUSY, Some(buffer))
-        } else {
-            self.rx.put(Transaction {
-                buffer: buffer,
-                length: len,
-                index: 0,
-            });
-
-            (ReturnCode::SUCCESS, None)
-        }
+        self.receive(buffer, len, false)
     }
 
     fn receive_word(&self) -> ReturnCode {
@@ -379,6 +580,11 @@ impl<'a> uart::Receive<'a> for UART<'a> {
             ReturnCode::EBUSY
         } else {
             self.receiving_word.set(true);
+            self.enable_interrupts();
+            // A byte that waits in the FIFO raises no interrupt of its own
+            if self.flow_control.get() && self.rx_fifo_not_empty() {
+                self.deferred_call.set();
+            }
             ReturnCode::SUCCESS
         }
     }
@@ -387,3 +593,16 @@ impl<'a> uart::Receive<'a> for UART<'a> {
         ReturnCode::FAIL
     }
 }
+
+impl<'a> uart::ReceiveAdvanced<'a> for UART<'a> {
+    /// The UART times out after 32 bit periods, whatever
+    /// `interbyte_timeout` is.
+    fn receive_automatic(
+        &self,
+        rx_buffer: &'static mut [u8],
+        rx_len: usize,
+        _interbyte_timeout: u8,
+    ) -> (ReturnCode, Option<&'static mut [u8]>) {
+        self.receive(rx_buffer, rx_len, true)
+    }
+}
@@ -0,0 +1,239 @@
+//! Micro Direct Memory Access (uDMA) controller, cc26x2 family
+//!
+//! For details see the uDMA chapter of the cc13x2/cc26x2 Technical Reference
+//! Manual.
+//!
+//! The uDMA controller moves data between memory and peripherals without the
+//! CPU. Each peripheral that supports it has channels assigned to it, which
+//! the peripheral asks to move an item whenever it can take or give one, for
+//! instance whenever the TX FIFO of a UART has room. The controller reads how
+//! to move the data of each channel from a control table in RAM.
+//!
+//! When a transfer of a peripheral channel is complete, the controller sets
+//! the done flag of the channel and raises the interrupt of the peripheral,
+//! whose driver checks `is_done` in its interrupt handler.
+//!
+//! A transfer moves at most `MAX_TRANSFER` items; drivers split larger
+//! buffers into several transfers.
+
+use crate::memory_map::UDMA0_BASE;
+use crate::prcm;
+use kernel::common::cells::VolatileCell;
+use kernel::common::registers::{register_bitfields, ReadOnly, ReadWrite, WriteOnly};
+use kernel::common::StaticRef;
+
+/// The most items a single transfer moves.
+pub const MAX_TRANSFER: usize = 1024;
+
+const CHANNELS: usize = 32;
+
+#[repr(C)]
+struct UdmaRegisters {
+    status: ReadOnly<u32>,
+    cfg: WriteOnly<u32, Config::Register>,
+    ctrl: ReadWrite<u32>,
+    altctrl: ReadOnly<u32>,
+    waitonreq: ReadOnly<u32>,
+    softreq: WriteOnly<u32>,
+    setburst: ReadWrite<u32>,
+    clearburst: WriteOnly<u32>,
+    setreqmask: ReadWrite<u32>,
+    clearreqmask: WriteOnly<u32>,
+    setchannelen: ReadWrite<u32>,
+    clearchannelen: WriteOnly<u32>,
+    setchnlprialt: ReadWrite<u32>,
+    clearchnlprialt: WriteOnly<u32>,
+    setchnlpriority: ReadWrite<u32>,
+    clearchnlpriority: WriteOnly<u32>,
+    _reserved0: [u32; 0x3],
+    error: ReadWrite<u32>,
+    _reserved1: [u32; 0x12D],
+    reqdone: ReadWrite<u32>,
+    _reserved2: [u32; 0x6],
+    donemask: ReadWrite<u32>,
+}
+
+register_bitfields![
+    u32,
+    Config [
+        MASTERENABLE OFFSET(0) NUMBITS(1) []
+    ]
+];
+
+const UDMA_BASE: StaticRef<UdmaRegisters> =
+    unsafe { StaticRef::new(UDMA0_BASE as *const UdmaRegisters) };
+
+// Fields of the control word of a channel
+const DST_INC_BYTE: u32 = 0x0 << 30;
+const DST_INC_NONE: u32 = 0x3 << 30;
+const DST_SIZE_BYTE: u32 = 0x0 << 28;
+const SRC_INC_BYTE: u32 = 0x0 << 26;
+const SRC_INC_NONE: u32 = 0x3 << 26;
+const SRC_SIZE_BYTE: u32 = 0x0 << 24;
+/// Arbitrate after each item, so that a single request moves one item
+const ARB_1: u32 = 0x0 << 14;
+const XFERSIZE_SHIFT: u32 = 4;
+const XFERSIZE_MASK: u32 = 0x3FF;
+const MODE_MASK: u32 = 0x7;
+const MODE_BASIC: u32 = 0x1;
+
+/// Where the controller reads how to move the data of a channel.
+#[derive(Copy, Clone)]
+#[repr(C)]
+struct ChannelControl {
+    /// The last address the transfer reads from
+    src_end: VolatileCell<u32>,
+    /// The last address the transfer writes to
+    dst_end: VolatileCell<u32>,
+    control: VolatileCell<u32>,
+    _unused: VolatileCell<u32>,
+}
+
+/// The primary control structures of the channels. The alternate structures
+/// would follow them, but ping-pong and scatter-gather transfers are not
+/// used.
+#[repr(C, align(1024))]
+struct ControlTable([ChannelControl; CHANNELS]);
+
+static mut CONTROL_TABLE: ControlTable = ControlTable(
+    [ChannelControl {
+        src_end: VolatileCell::new(0),
+        dst_end: VolatileCell::new(0),
+        control: VolatileCell::new(0),
+        _unused: VolatileCell::new(0),
+    }; CHANNELS],
+);
+
+/// The channels of the peripherals that use the uDMA.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Channel {
+    Uart0Rx = 1,
+    Uart0Tx = 2,
+    Uart1Rx = 5,
+    Uart1Tx = 6,
+}
+
+impl Channel {
+    fn mask(self) -> u32 {
+        1 << (self as u32)
+    }
+
+    fn control(self) -> &'static ChannelControl {
+        unsafe { &CONTROL_TABLE.0[self as usize] }
+    }
+}
+
+pub struct Udma(());
+
+impl Udma {
+    /// Power and clock the controller, and point it at the control table.
+    /// Drivers call this before their first transfer; calling it again does
+    /// nothing more.
+    pub fn initialize() {
+        prcm::Power::enable_domain(prcm::PowerDomain::Peripherals);
+        prcm::Clock::enable_dma();
+
+        let regs = UDMA_BASE;
+        regs.ctrl
+            .set(unsafe { &CONTROL_TABLE as *const ControlTable as u32 });
+        regs.cfg.write(Config::MASTERENABLE::SET);
+    }
+
+    /// Start moving the bytes of `buffer` to the peripheral register at
+    /// `register`, up to `MAX_TRANSFER` of them. Returns how many the
+    /// transfer moves.
+    ///
+    /// The buffer must stay in place until the transfer is done or stopped.
+    pub fn transfer_to_peripheral(channel: Channel, buffer: &[u8], register: usize) -> usize {
+        let len = core::cmp::min(buffer.len(), MAX_TRANSFER);
+        if len == 0 {
+            return 0;
+        }
+        let control = channel.control();
+        control.src_end.set(&buffer[len - 1] as *const u8 as u32);
+        control.dst_end.set(register as u32);
+        control.control.set(
+            DST_INC_NONE
+                | DST_SIZE_BYTE
+                | SRC_INC_BYTE
+                | SRC_SIZE_BYTE
+                | ARB_1
+                | ((len as u32 - 1) << XFERSIZE_SHIFT)
+                | MODE_BASIC,
+        );
+        Self::enable(channel);
+        len
+    }
+
+    /// Start moving bytes from the peripheral register at `register` into
+    /// `buffer`, up to `MAX_TRANSFER` of them. Returns how many the transfer
+    /// moves.
+    ///
+    /// The buffer must stay in place until the transfer is done or stopped.
+    pub fn transfer_from_peripheral(channel: Channel, register: usize, buffer: &mut [u8]) -> usize {
+        let len = core::cmp::min(buffer.len(), MAX_TRANSFER);
+        if len == 0 {
+            return 0;
+        }
+        let control = channel.control();
+        control.src_end.set(register as u32);
+        control.dst_end.set(&buffer[len - 1] as *const u8 as u32);
+        control.control.set(
+            DST_INC_BYTE
+                | DST_SIZE_BYTE
+                | SRC_INC_NONE
+                | SRC_SIZE_BYTE
+                | ARB_1
+                | ((len as u32 - 1) << XFERSIZE_SHIFT)
+                | MODE_BASIC,
+        );
+        Self::enable(channel);
+        len
+    }
+
+    fn enable(channel: Channel) {
+        let regs = UDMA_BASE;
+        regs.reqdone.set(channel.mask());
+        regs.donemask.set(regs.donemask.get() | channel.mask());
+        regs.clearreqmask.set(channel.mask());
+        regs.setchannelen.set(channel.mask());
+    }
+
+    /// Make `channel` ignore the single requests of its peripheral, and move
+    /// items only on burst requests, or undo that. A UART makes a burst
+    /// request once its RX FIFO reaches its trigger level, so with bursts
+    /// only, the bytes below the level stay in the FIFO.
+    pub fn set_burst_only(channel: Channel, burst_only: bool) {
+        let regs = UDMA_BASE;
+        if burst_only {
+            regs.setburst.set(channel.mask());
+        } else {
+            regs.clearburst.set(channel.mask());
+        }
+    }
+
+    /// Stop the transfer of `channel`. Returns how many of its items it did
+    /// not move.
+    pub fn stop(channel: Channel) -> usize {
+        UDMA_BASE.clearchannelen.set(channel.mask());
+        // The controller counts the items down in the control word, and
+        // stops the channel once it moved them all
+        let control = channel.control().control.get();
+        if control & MODE_MASK == 0 {
+            0
+        } else {
+            ((control >> XFERSIZE_SHIFT) & XFERSIZE_MASK) as usize + 1
+        }
+    }
+
+    /// Whether the last transfer of `channel` is done.
+    pub fn is_done(channel: Channel) -> bool {
+        UDMA_BASE.reqdone.get() & channel.mask() != 0
+    }
+
+    /// Clear the done flag of `channel`, so that it no longer raises the
+    /// interrupt of its peripheral.
+    pub fn clear_done(channel: Channel) {
+        UDMA_BASE.reqdone.set(channel.mask());
+    }
+}
@@ -0,0 +1,143 @@
+//! Watchdog timer, cc26x2 family
+//!
+//! The watchdog counts down from its load value at the MCU clock divided by
+//! 32. The first time it reaches zero it raises an interrupt and reloads, and
+//! if that interrupt is still set the second time, it resets the chip. So a
+//! period is programmed as two halves, and servicing the watchdog reloads the
+//! counter and clears the interrupt.
+//!
+//! Once the watchdog is started it can only be stopped by a reset. `stop()`
+//! therefore reloads it with the longest timeout instead, about 95 minutes.
+//! A kernel that sleeps for longer than that without waking up is reset.
+//!
+//! The counter stalls while a debugger halts the CPU.
+
+use kernel::common::registers::{register_bitfields, ReadOnly, ReadWrite, WriteOnly};
+use kernel::common::StaticRef;
+use kernel::hil;
+
+use crate::memory_map::WDT_BASE;
+
+#[repr(C)]
+struct WdtRegisters {
+    load: ReadWrite<u32>,
+    _value: ReadOnly<u32>,
+    ctl: ReadWrite<u32, Control::Register>,
+    icr: WriteOnly<u32>,
+    _ris: ReadOnly<u32, Interrupt::Register>,
+    mis: ReadOnly<u32, Interrupt::Register>,
+    _reserved0: [u32; 256],
+    test: ReadWrite<u32, Test::Register>,
+    _int_caus: ReadOnly<u32>,
+    _reserved1: [u32; 504],
+    lock: ReadWrite<u32>,
+}
+
+register_bitfields![
+    u32,
+    Control [
+        INTTYPE OFFSET(2) NUMBITS(1) [
+            Maskable = 0,
+            NonMaskable = 1
+        ],
+        RESEN OFFSET(1) NUMBITS(1) [],
+        INTEN OFFSET(0) NUMBITS(1) []
+    ],
+    Interrupt [
+        WDTINT OFFSET(0) NUMBITS(1) []
+    ],
+    Test [
+        STALL OFFSET(8) NUMBITS(1) []
+    ]
+];
+
+const WDT_REGS: StaticRef<WdtRegisters> =
+    unsafe { StaticRef::new(WDT_BASE as *const WdtRegisters) };
+
+/// Written to the lock register to allow writes to the other registers
+const UNLOCK_KEY: u32 = 0x1ACC_E551;
+
+/// Counter ticks per millisecond, with the MCU clock at 48 MHz
+const TICKS_PER_MS: u64 = 48_000 / 32;
+
+pub static mut WDT: Wdt = Wdt::new();
+
+pub struct Wdt {
+    registers: StaticRef<WdtRegisters>,
+}
+
+impl Wdt {
+    const fn new() -> Wdt {
+        Wdt {
+            registers: WDT_REGS,
+        }
+    }
+
+    /// Runs `f` with the registers unlocked.
+    fn unlocked<F: FnOnce(&WdtRegisters)>(&self, f: F) {
+        let regs = &*self.registers;
+        regs.lock.set(UNLOCK_KEY);
+        f(regs);
+        // Writing anything but the key locks the registers again
+        regs.lock.set(0);
+    }
+
+    /// Sets the load value, half of the period, and reloads the counter with it.
+    fn reload(&self, load: u32) {
+        self.unlocked(|regs| {
+            regs.load.set(load);
+            regs.icr.set(1);
+        });
+    }
+
+    fn start(&self, period: usize) {
+        let ticks = (period as u64 * TICKS_PER_MS / 2).max(1);
+        let load = if ticks > u32::max_value() as u64 {
+            u32::max_value()
+        } else {
+            ticks as u32
+        };
+        self.reload(load);
+        self.unlocked(|regs| {
+            regs.test.modify(Test::STALL::SET);
+            regs.ctl
+                .write(Control::INTTYPE::Maskable + Control::RESEN::SET + Control::INTEN::SET);
+        });
+    }
+
+    fn stop(&self) {
+        if self.registers.ctl.is_set(Control::INTEN) {
+            self.reload(u32::max_value());
+        }
+    }
+
+    fn tickle(&self) {
+        if self.registers.ctl.is_set(Control::INTEN) {
+            self.unlocked(|regs| regs.icr.set(1));
+        }
+    }
+
+    /// The first half of the period passed without the watchdog being
+    /// serviced. As interrupts are only handled from the kernel loop, the
+    /// kernel is still running, so this services the watchdog; an interrupt
+    /// left set would fire again until the reset.
+    pub fn handle_interrupt(&self) {
+        if self.registers.mis.is_set(Interrupt::WDTINT) {
+            self.tickle();
+        }
+    }
+}
+
+impl hil::watchdog::Watchdog for Wdt {
+    fn start(&self, period: usize) {
+        self.start(period);
+    }
+
+    fn stop(&self) {
+        self.stop();
+    }
+
+    fn tickle(&self) {
+        self.tickle();
+    }
+}
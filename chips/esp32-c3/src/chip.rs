@@ -0,0 +1,209 @@
+use kernel;
+use kernel::debug;
+use rv32i;
+
+use crate::gpio;
+use crate::intc::Intc;
+use crate::interrupts;
+use crate::rtc_cntl;
+use crate::timg;
+use crate::uart;
+
+// The mtvec CSR of the ESP32-C3 is hardwired to vectored mode, so traps go
+// through this table: exceptions to its first entry, and CPU interrupt `n`
+// to entry `n`. All of them end up in the common trap handler of `rv32i`,
+// which reads mcause. The table must be aligned to 256 bytes.
+global_asm!(
+    "
+    .section .text._start_trap_vectored
+    .balign 256
+    .globl _start_trap_vectored
+_start_trap_vectored:
+    .rept 32
+    j _start_trap
+    .endr
+"
+);
+
+pub struct Esp32C3 {
+    userspace_kernel_boundary: rv32i::syscall::SysCall,
+    intc: &'static Intc,
+}
+
+impl Esp32C3 {
+    pub unsafe fn new(intc: &'static Intc) -> Esp32C3 {
+        Esp32C3 {
+            userspace_kernel_boundary: rv32i::syscall::SysCall::new(),
+            intc: intc,
+        }
+    }
+
+    pub fn enable_all_interrupts(&self) {
+        self.intc.enable_all();
+    }
+
+    /// Configure the PMP to allow all accesses in both machine mode (the
+    /// default) and in user mode.
+    ///
+    /// This needs to be replaced with a real PMP driver. See
+    /// https://github.com/tock/tock/issues/1135
+    pub unsafe fn disable_pmp(&self) {
+        asm!("
+            // Set the first region address to 0xFFFFFFFF. When using top-of-range mode
+            // this will include the entire address space.
+            lui  t0, %hi(0xFFFFFFFF)
+            addi t0, t0, %lo(0xFFFFFFFF)
+            csrw 0x3b0, t0    // CSR=pmpaddr0
+
+            // Set the first region to use top-of-range and allow everything.
+            // This is equivalent to:
+            // R=1, W=1, X=1, A=01, L=0
+            li   t0, 0x0F
+            csrw 0x3a0, t0    // CSR=pmpcfg0
+        "
+        :
+        :
+        :
+        : "volatile");
+    }
+
+    /// The ROM bootloader leaves the RTC watchdog, the super watchdog and the
+    /// watchdogs of both timer groups running. Tock does not feed them.
+    pub unsafe fn disable_watchdogs(&self) {
+        rtc_cntl::RTC_CNTL.disable_wdt();
+        rtc_cntl::RTC_CNTL.disable_super_wdt();
+        timg::TIMG0.disable_wdt();
+        timg::TIMG1.disable_wdt();
+    }
+
+    /// Setup the function that should run when a trap happens.
+    pub unsafe fn configure_trap_handler(&self) {
+        asm!("
+            // CSR 0x305 (mtvec, 'Machine trap-handler base address.') sets the
+            // address of the trap vector table. The mode bits are hardwired to
+            // vectored mode on this chip, but they are written for clarity.
+            lui  t0, %hi(_start_trap_vectored)
+            addi t0, t0, %lo(_start_trap_vectored)
+            ori  t0, t0, 0x01 // Set vectored mode
+            csrw 0x305, t0    // Write the mtvec CSR.
+        "
+        :
+        :
+        :
+        : "volatile");
+    }
+
+    /// Generic helper initialize function to setup all of the chip specific
+    /// operations. Different boards can call the functions that `initialize()`
+    /// calls directly if it needs to use a custom setup operation.
+    pub unsafe fn initialize(&self) {
+        self.disable_watchdogs();
+        self.disable_pmp();
+        self.configure_trap_handler();
+        self.intc.map_interrupts();
+    }
+}
+
+impl kernel::Chip for Esp32C3 {
+    type MPU = ();
+    type UserspaceKernelBoundary = rv32i::syscall::SysCall;
+    type SysTick = ();
+
+    fn mpu(&self) -> &Self::MPU {
+        &()
+    }
+
+    fn systick(&self) -> &Self::SysTick {
+        &()
+    }
+
+    fn userspace_kernel_boundary(&self) -> &rv32i::syscall::SysCall {
+        &self.userspace_kernel_boundary
+    }
+
+    fn service_pending_interrupts(&self) {
+        unsafe {
+            while let Some(interrupt) = self.intc.next_pending() {
+                match interrupt {
+                    interrupts::IRQ_UART0 => uart::UART0.handle_interrupt(),
+                    interrupts::IRQ_TIMG0 => timg::TIMG0.handle_interrupt(),
+                    interrupts::IRQ_TIMG1 => timg::TIMG1.handle_interrupt(),
+                    interrupts::IRQ_GPIO => gpio::PORT.handle_interrupt(),
+
+                    _ => debug!("Pidx {}", interrupt),
+                }
+
+                // Mark that we are done with this interrupt, which enables it
+                // again.
+                self.intc.complete(interrupt);
+            }
+        }
+    }
+
+    fn has_pending_interrupts(&self) -> bool {
+        self.intc.has_pending()
+    }
+
+    fn sleep(&self) {
+        unsafe {
+            rv32i::support::wfi();
+        }
+    }
+
+    unsafe fn atomic<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce() -> R,
+    {
+        rv32i::support::atomic(f)
+    }
+}
+
+/// Disable the CPU interrupt that caused a trap, and save it so that the
+/// kernel loop handles it.
+unsafe fn handle_interrupt(mcause: u32) {
+    let irq = mcause & 0x1f;
+    crate::intc::INTC.disable(irq);
+    crate::intc::INTC.save_interrupt(irq);
+}
+
+/// Trap handler for board/chip specific code.
+///
+/// For the ESP32-C3 this gets called when an interrupt occurs while the chip
+/// is in kernel mode.
+#[export_name = "_start_trap_rust"]
+pub extern "C" fn start_trap_rust() {
+    let mut mcause: i32;
+
+    unsafe {
+        asm!("
+            // Read the mcause CSR to determine why we entered the trap handler.
+            csrr $0, 0x342    // CSR=0x342=mcause
+        "
+        : "=r"(mcause)
+        :
+        :
+        : "volatile");
+    }
+
+    // Check if the trap was from an interrupt or some other exception.
+    if mcause < 0 {
+        // If the most significant bit is set (i.e. mcause is negative) then
+        // this was an interrupt, and the lowest bits are the CPU interrupt.
+        unsafe {
+            handle_interrupt(mcause as u32);
+        }
+    } else {
+        // Otherwise, the kernel encountered a fault...so panic!()?
+        panic!("kernel exception");
+    }
+}
+
+/// Function that gets called if an interrupt occurs while an app was running.
+/// mcause is passed in, and this function should correctly handle disabling the
+/// interrupt that fired so that it does not trigger again.
+#[export_name = "_disable_interrupt_trap_handler"]
+pub extern "C" fn disable_interrupt_trap_handler(mcause: u32) {
+    unsafe {
+        handle_interrupt(mcause);
+    }
+}
@@ -0,0 +1,396 @@
+//! GPIO pins of the ESP32-C3.
+//!
+//! Each of the 22 pins has a pad, configured in `IO_MUX`, and is driven by
+//! the GPIO matrix. A pin is a GPIO when its IO_MUX function is `GPIO` and
+//! its output signal is the GPIO output register, which the `hil::gpio`
+//! implementation selects. Peripherals such as the UART are routed to pins
+//! through the GPIO matrix with `connect_output_signal()` and
+//! `connect_input_signal()`.
+
+use core::ops::{Index, IndexMut};
+use kernel::common::cells::OptionalCell;
+use kernel::common::registers::{register_bitfields, ReadOnly, ReadWrite, WriteOnly};
+use kernel::common::StaticRef;
+use kernel::hil;
+
+const NUM_PINS: usize = 22;
+
+#[repr(C)]
+struct GpioRegisters {
+    /// Strapping and bit-select of the SDIO interface
+    bt_select: ReadWrite<u32>,
+    /// GPIO output value
+    out: ReadWrite<u32>,
+    /// Set bits of `out`
+    out_w1ts: WriteOnly<u32>,
+    /// Clear bits of `out`
+    out_w1tc: WriteOnly<u32>,
+    _reserved0: [u32; 3],
+    sdio_select: ReadWrite<u32>,
+    /// GPIO output enable
+    enable: ReadWrite<u32>,
+    /// Set bits of `enable`
+    enable_w1ts: WriteOnly<u32>,
+    /// Clear bits of `enable`
+    enable_w1tc: WriteOnly<u32>,
+    _reserved1: [u32; 3],
+    /// Values of the strapping pins at reset
+    strap: ReadOnly<u32>,
+    /// GPIO input value
+    in_: ReadOnly<u32>,
+    _reserved2: u32,
+    /// Pending pin interrupts
+    status: ReadWrite<u32>,
+    /// Set bits of `status`
+    status_w1ts: WriteOnly<u32>,
+    /// Clear bits of `status`
+    status_w1tc: WriteOnly<u32>,
+    _reserved3: [u32; 3],
+    /// Pending pin interrupts for the CPU
+    pcpu_int: ReadOnly<u32>,
+    pcpu_nmi_int: ReadOnly<u32>,
+    cpusdio_int: ReadOnly<u32>,
+    _reserved4: [u32; 3],
+    /// Interrupt configuration of each pin
+    pin: [ReadWrite<u32, PIN::Register>; NUM_PINS],
+    _reserved5: [u32; 32],
+    status_next: ReadOnly<u32>,
+    _reserved6: u32,
+    /// The pin that each peripheral input signal reads
+    func_in_sel_cfg: [ReadWrite<u32, FUNC_IN_SEL_CFG::Register>; 128],
+    _reserved7: [u32; 128],
+    /// The output signal that drives each pin
+    func_out_sel_cfg: [ReadWrite<u32, FUNC_OUT_SEL_CFG::Register>; NUM_PINS],
+}
+
+#[repr(C)]
+struct IoMuxRegisters {
+    pin_ctrl: ReadWrite<u32>,
+    gpio: [ReadWrite<u32, IO_MUX_GPIO::Register>; NUM_PINS],
+}
+
+register_bitfields![u32,
+    PIN [
+        /// Enable the interrupt of the pin for the CPU
+        INT_ENA OFFSET(13) NUMBITS(5) [
+            Disabled = 0,
+            Cpu = 1
+        ],
+        /// Wake up from light sleep on the pin
+        WAKEUP_ENABLE OFFSET(10) NUMBITS(1) [],
+        /// Interrupt type
+        INT_TYPE OFFSET(7) NUMBITS(3) [
+            Disabled = 0,
+            RisingEdge = 1,
+            FallingEdge = 2,
+            AnyEdge = 3,
+            LowLevel = 4,
+            HighLevel = 5
+        ],
+        /// Open drain output
+        PAD_DRIVER OFFSET(2) NUMBITS(1) []
+    ],
+    FUNC_IN_SEL_CFG [
+        /// Route the signal through the GPIO matrix rather than IO_MUX
+        SEL OFFSET(6) NUMBITS(1) [],
+        /// Invert the input
+        IN_INV_SEL OFFSET(5) NUMBITS(1) [],
+        /// The pin the signal reads; 0x1F and 0x1E are constant high and low
+        IN_SEL OFFSET(0) NUMBITS(5) []
+    ],
+    FUNC_OUT_SEL_CFG [
+        /// Invert the output enable
+        OEN_INV_SEL OFFSET(10) NUMBITS(1) [],
+        /// Take the output enable from `enable` rather than the peripheral
+        OEN_SEL OFFSET(9) NUMBITS(1) [],
+        /// Invert the output
+        OUT_INV_SEL OFFSET(8) NUMBITS(1) [],
+        /// The output signal of the pin; 128 is the `out` register
+        OUT_SEL OFFSET(0) NUMBITS(8) [
+            Gpio = 128
+        ]
+    ],
+    IO_MUX_GPIO [
+        /// Enable the glitch filter of the input
+        FILTER_EN OFFSET(15) NUMBITS(1) [],
+        /// IO_MUX function of the pin
+        MCU_SEL OFFSET(12) NUMBITS(3) [
+            Gpio = 1
+        ],
+        /// Drive strength
+        FUN_DRV OFFSET(10) NUMBITS(2) [],
+        /// Input enable
+        FUN_IE OFFSET(9) NUMBITS(1) [],
+        /// Pull up enable
+        FUN_WPU OFFSET(8) NUMBITS(1) [],
+        /// Pull down enable
+        FUN_WPD OFFSET(7) NUMBITS(1) [],
+        /// Input enable in sleep mode
+        MCU_IE OFFSET(4) NUMBITS(1) [],
+        /// Pull up enable in sleep mode
+        MCU_WPU OFFSET(3) NUMBITS(1) [],
+        /// Pull down enable in sleep mode
+        MCU_WPD OFFSET(2) NUMBITS(1) [],
+        /// Use the sleep mode configuration
+        SLP_SEL OFFSET(1) NUMBITS(1) [],
+        /// Output enable in sleep mode
+        MCU_OE OFFSET(0) NUMBITS(1) []
+    ]
+];
+
+const GPIO_BASE: StaticRef<GpioRegisters> =
+    unsafe { StaticRef::new(0x6000_4000 as *const GpioRegisters) };
+const IO_MUX_BASE: StaticRef<IoMuxRegisters> =
+    unsafe { StaticRef::new(0x6000_9000 as *const IoMuxRegisters) };
+
+/// Peripheral signals of the GPIO matrix, from table 5-2 of the ESP32-C3
+/// technical reference manual.
+pub mod signal {
+    pub const U0RXD_IN: usize = 6;
+    pub const U0TXD_OUT: usize = 6;
+    pub const U1RXD_IN: usize = 9;
+    pub const U1TXD_OUT: usize = 9;
+}
+
+pub struct GpioPin {
+    pin: usize,
+    client: OptionalCell<&'static hil::gpio::Client>,
+    registers: StaticRef<GpioRegisters>,
+    io_mux: StaticRef<IoMuxRegisters>,
+}
+
+impl GpioPin {
+    const fn new(pin: usize) -> GpioPin {
+        GpioPin {
+            pin: pin,
+            client: OptionalCell::empty(),
+            registers: GPIO_BASE,
+            io_mux: IO_MUX_BASE,
+        }
+    }
+
+    fn mask(&self) -> u32 {
+        1 << self.pin
+    }
+
+    /// Drive the pin with the output signal `signal` of a peripheral.
+    pub fn connect_output_signal(&self, signal: usize) {
+        self.io_mux.gpio[self.pin].modify(IO_MUX_GPIO::MCU_SEL::Gpio);
+        self.registers.func_out_sel_cfg[self.pin]
+            .write(FUNC_OUT_SEL_CFG::OUT_SEL.val(signal as u32));
+        self.registers.enable_w1ts.set(self.mask());
+    }
+
+    /// Feed the pin to the input signal `signal` of a peripheral.
+    pub fn connect_input_signal(&self, signal: usize) {
+        self.io_mux.gpio[self.pin].modify(IO_MUX_GPIO::MCU_SEL::Gpio + IO_MUX_GPIO::FUN_IE::SET);
+        self.registers.func_in_sel_cfg[signal]
+            .write(FUNC_IN_SEL_CFG::SEL::SET + FUNC_IN_SEL_CFG::IN_SEL.val(self.pin as u32));
+    }
+
+    fn is_gpio(&self) -> bool {
+        self.io_mux.gpio[self.pin].matches_all(IO_MUX_GPIO::MCU_SEL::Gpio)
+            && self.registers.func_out_sel_cfg[self.pin]
+                .matches_all(FUNC_OUT_SEL_CFG::OUT_SEL::Gpio)
+    }
+
+    /// Connect the pin to the GPIO registers.
+    fn select_gpio(&self) {
+        self.io_mux.gpio[self.pin].modify(IO_MUX_GPIO::MCU_SEL::Gpio);
+        self.registers.func_out_sel_cfg[self.pin].write(FUNC_OUT_SEL_CFG::OUT_SEL::Gpio);
+    }
+
+    fn handle_interrupt(&self) {
+        self.client.map(|client| {
+            client.fired();
+        });
+    }
+}
+
+impl hil::gpio::Configure for GpioPin {
+    fn set_floating_state(&self, mode: hil::gpio::FloatingState) {
+        let pad = &self.io_mux.gpio[self.pin];
+        match mode {
+            hil::gpio::FloatingState::PullUp => {
+                pad.modify(IO_MUX_GPIO::FUN_WPU::SET + IO_MUX_GPIO::FUN_WPD::CLEAR)
+            }
+            hil::gpio::FloatingState::PullDown => {
+                pad.modify(IO_MUX_GPIO::FUN_WPU::CLEAR + IO_MUX_GPIO::FUN_WPD::SET)
+            }
+            hil::gpio::FloatingState::PullNone => {
+                pad.modify(IO_MUX_GPIO::FUN_WPU::CLEAR + IO_MUX_GPIO::FUN_WPD::CLEAR)
+            }
+        }
+    }
+
+    fn floating_state(&self) -> hil::gpio::FloatingState {
+        let pad = &self.io_mux.gpio[self.pin];
+        if pad.is_set(IO_MUX_GPIO::FUN_WPU) {
+            hil::gpio::FloatingState::PullUp
+        } else if pad.is_set(IO_MUX_GPIO::FUN_WPD) {
+            hil::gpio::FloatingState::PullDown
+        } else {
+            hil::gpio::FloatingState::PullNone
+        }
+    }
+
+    fn make_output(&self) -> hil::gpio::Configuration {
+        if !self.is_gpio() {
+            self.select_gpio();
+        }
+        self.registers.enable_w1ts.set(self.mask());
+        hil::gpio::Configuration::Output
+    }
+
+    fn disable_output(&self) -> hil::gpio::Configuration {
+        self.registers.enable_w1tc.set(self.mask());
+        self.configuration()
+    }
+
+    fn make_input(&self) -> hil::gpio::Configuration {
+        if !self.is_gpio() {
+            self.select_gpio();
+        }
+        self.io_mux.gpio[self.pin].modify(IO_MUX_GPIO::FUN_IE::SET);
+        self.registers.enable_w1tc.set(self.mask());
+        hil::gpio::Configuration::Input
+    }
+
+    fn disable_input(&self) -> hil::gpio::Configuration {
+        self.io_mux.gpio[self.pin].modify(IO_MUX_GPIO::FUN_IE::CLEAR);
+        self.configuration()
+    }
+
+    fn configuration(&self) -> hil::gpio::Configuration {
+        if !self.is_gpio() {
+            return hil::gpio::Configuration::Function;
+        }
+        let output = self.registers.enable.get() & self.mask() != 0;
+        let input = self.io_mux.gpio[self.pin].is_set(IO_MUX_GPIO::FUN_IE);
+        match (input, output) {
+            (true, true) => hil::gpio::Configuration::InputOutput,
+            (false, true) => hil::gpio::Configuration::Output,
+            (true, false) => hil::gpio::Configuration::Input,
+            (false, false) => hil::gpio::Configuration::LowPower,
+        }
+    }
+
+    fn deactivate_to_low_power(&self) {
+        self.registers.enable_w1tc.set(self.mask());
+        self.io_mux.gpio[self.pin].modify(
+            IO_MUX_GPIO::FUN_IE::CLEAR + IO_MUX_GPIO::FUN_WPU::CLEAR + IO_MUX_GPIO::FUN_WPD::CLEAR,
+        );
+    }
+}
+
+impl hil::gpio::Input for GpioPin {
+    fn read(&self) -> bool {
+        self.registers.in_.get() & self.mask() != 0
+    }
+}
+
+impl hil::gpio::Output for GpioPin {
+    fn set(&self) {
+        self.registers.out_w1ts.set(self.mask());
+    }
+
+    fn clear(&self) {
+        self.registers.out_w1tc.set(self.mask());
+    }
+
+    fn toggle(&self) -> bool {
+        let value = self.registers.out.get() ^ self.mask();
+        self.registers.out.set(value);
+        value & self.mask() != 0
+    }
+}
+
+impl hil::gpio::Pin for GpioPin {}
+
+impl hil::gpio::Interrupt for GpioPin {
+    fn set_client(&self, client: &'static hil::gpio::Client) {
+        self.client.set(client);
+    }
+
+    fn is_pending(&self) -> bool {
+        self.registers.status.get() & self.mask() != 0
+    }
+
+    fn enable_interrupts(&self, mode: hil::gpio::InterruptEdge) {
+        let int_type = match mode {
+            hil::gpio::InterruptEdge::RisingEdge => PIN::INT_TYPE::RisingEdge,
+            hil::gpio::InterruptEdge::FallingEdge => PIN::INT_TYPE::FallingEdge,
+            hil::gpio::InterruptEdge::EitherEdge => PIN::INT_TYPE::AnyEdge,
+        };
+        // Forget edges seen before the interrupt was enabled
+        self.registers.status_w1tc.set(self.mask());
+        self.registers.pin[self.pin].modify(int_type + PIN::INT_ENA::Cpu);
+    }
+
+    fn disable_interrupts(&self) {
+        self.registers.pin[self.pin].modify(PIN::INT_TYPE::Disabled + PIN::INT_ENA::Disabled);
+        self.registers.status_w1tc.set(self.mask());
+    }
+}
+
+impl hil::gpio::InterruptPin for GpioPin {}
+
+pub struct Port {
+    pins: [GpioPin; NUM_PINS],
+}
+
+impl Index<usize> for Port {
+    type Output = GpioPin;
+
+    fn index(&self, index: usize) -> &GpioPin {
+        &self.pins[index]
+    }
+}
+
+impl IndexMut<usize> for Port {
+    fn index_mut(&mut self, index: usize) -> &mut GpioPin {
+        &mut self.pins[index]
+    }
+}
+
+impl Port {
+    /// GPIO interrupt: clear the pending pins, and call the handler of each
+    /// of them.
+    pub fn handle_interrupt(&self) {
+        let registers = &*self.pins[0].registers;
+        let pending = registers.status.get();
+        registers.status_w1tc.set(pending);
+        for (pin, gpio) in self.pins.iter().enumerate() {
+            if pending & (1 << pin) != 0 {
+                gpio.handle_interrupt();
+            }
+        }
+    }
+}
+
+pub static mut PORT: Port = Port {
+    pins: [
+        GpioPin::new(0),
+        GpioPin::new(1),
+        GpioPin::new(2),
+        GpioPin::new(3),
+        GpioPin::new(4),
+        GpioPin::new(5),
+        GpioPin::new(6),
+        GpioPin::new(7),
+        GpioPin::new(8),
+        GpioPin::new(9),
+        GpioPin::new(10),
+        GpioPin::new(11),
+        GpioPin::new(12),
+        GpioPin::new(13),
+        GpioPin::new(14),
+        GpioPin::new(15),
+        GpioPin::new(16),
+        GpioPin::new(17),
+        GpioPin::new(18),
+        GpioPin::new(19),
+        GpioPin::new(20),
+        GpioPin::new(21),
+    ],
+};
@@ -0,0 +1,132 @@
+//! Interrupt matrix of the ESP32-C3.
+//!
+//! Each peripheral interrupt source is mapped to a CPU interrupt, which
+//! makes the core trap to the `mcause` of that CPU interrupt. The trap
+//! handler disables the CPU interrupt and saves it as pending, and the chip
+//! handles and re-enables it from the kernel loop.
+
+use kernel::common::cells::VolatileCell;
+use kernel::common::registers::{ReadOnly, ReadWrite};
+use kernel::common::StaticRef;
+
+use crate::interrupts;
+
+#[repr(C)]
+struct IntcRegisters {
+    /// The CPU interrupt that each peripheral interrupt source maps to
+    map: [ReadWrite<u32>; interrupts::NUM_SOURCES],
+    /// Status of the peripheral interrupt sources
+    status: [ReadOnly<u32>; 2],
+    /// Clock gate of the interrupt matrix registers
+    clock_gate: ReadWrite<u32>,
+    /// Enable CPU interrupts, one bit each
+    cpu_int_enable: ReadWrite<u32>,
+    /// CPU interrupt type: 0 for level, 1 for edge
+    cpu_int_type: ReadWrite<u32>,
+    /// Clear pending edge CPU interrupts
+    cpu_int_clear: ReadWrite<u32>,
+    /// Pending status of the CPU interrupts
+    cpu_int_eip_status: ReadOnly<u32>,
+    /// Priority of each CPU interrupt, from 1 to 15
+    cpu_int_pri: [ReadWrite<u32>; 32],
+    /// CPU interrupts with a lower priority than this are masked
+    cpu_int_thresh: ReadWrite<u32>,
+}
+
+const INTC_BASE: StaticRef<IntcRegisters> =
+    unsafe { StaticRef::new(0x600C_2000 as *const IntcRegisters) };
+
+/// The peripheral interrupt sources that the chip handles, and their CPU
+/// interrupts
+const MAPPINGS: [(u32, u32); 4] = [
+    (interrupts::UART0, interrupts::IRQ_UART0),
+    (interrupts::TG0_T0_LEVEL, interrupts::IRQ_TIMG0),
+    (interrupts::TG1_T0_LEVEL, interrupts::IRQ_TIMG1),
+    (interrupts::GPIO, interrupts::IRQ_GPIO),
+];
+
+pub struct Intc {
+    registers: StaticRef<IntcRegisters>,
+    saved: VolatileCell<u32>,
+}
+
+pub static mut INTC: Intc = Intc::new();
+
+impl Intc {
+    const fn new() -> Intc {
+        Intc {
+            registers: INTC_BASE,
+            saved: VolatileCell::new(0),
+        }
+    }
+
+    /// Route the peripheral interrupts that the chip handles to their CPU
+    /// interrupts, as level interrupts of the same priority.
+    pub fn map_interrupts(&self) {
+        for (source, irq) in MAPPINGS.iter() {
+            self.registers.map[*source as usize].set(*irq);
+            self.registers.cpu_int_pri[*irq as usize].set(1);
+            let mask = 1 << *irq;
+            self.registers
+                .cpu_int_type
+                .set(self.registers.cpu_int_type.get() & !mask);
+        }
+        self.registers.cpu_int_thresh.set(1);
+    }
+
+    /// Enable all of the CPU interrupts that the chip handles.
+    pub fn enable_all(&self) {
+        for (_, irq) in MAPPINGS.iter() {
+            self.enable(*irq);
+        }
+    }
+
+    /// Disable all CPU interrupts.
+    pub fn disable_all(&self) {
+        self.registers.cpu_int_enable.set(0);
+    }
+
+    pub fn enable(&self, irq: u32) {
+        self.registers
+            .cpu_int_enable
+            .set(self.registers.cpu_int_enable.get() | (1 << irq));
+    }
+
+    pub fn disable(&self, irq: u32) {
+        self.registers
+            .cpu_int_enable
+            .set(self.registers.cpu_int_enable.get() & !(1 << irq));
+    }
+
+    /// Mark a CPU interrupt as pending, from the trap handler once it has
+    /// been disabled.
+    pub fn save_interrupt(&self, irq: u32) {
+        self.saved.set(self.saved.get() | (1 << irq));
+    }
+
+    /// Get the lowest CPU interrupt that is pending.
+    pub fn next_pending(&self) -> Option<u32> {
+        let saved = self.saved.get();
+        if saved == 0 {
+            None
+        } else {
+            Some(saved.trailing_zeros())
+        }
+    }
+
+    /// Signal that a pending CPU interrupt was handled, and enable it again.
+    pub fn complete(&self, irq: u32) {
+        unsafe {
+            // The trap handler may save another interrupt, so clear this one
+            // with interrupts disabled (mstatus.MIE)
+            asm!("csrc 0x300, $0" : : "r"(0x8) : : "volatile");
+            self.saved.set(self.saved.get() & !(1 << irq));
+            asm!("csrs 0x300, $0" : : "r"(0x8) : : "volatile");
+        }
+        self.enable(irq);
+    }
+
+    pub fn has_pending(&self) -> bool {
+        self.saved.get() != 0
+    }
+}
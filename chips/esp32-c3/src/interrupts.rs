@@ -0,0 +1,21 @@
+//! Interrupt sources of the peripherals, and the CPU interrupts that the
+//! interrupt matrix routes them to.
+
+// Peripheral interrupt sources
+pub const GPIO: u32 = 16;
+pub const UART0: u32 = 21;
+pub const UART1: u32 = 22;
+pub const TG0_T0_LEVEL: u32 = 32;
+pub const TG0_WDT_LEVEL: u32 = 33;
+pub const TG1_T0_LEVEL: u32 = 34;
+pub const TG1_WDT_LEVEL: u32 = 35;
+
+/// The number of peripheral interrupt sources
+pub const NUM_SOURCES: usize = 62;
+
+// CPU interrupts. CPU interrupt 0 does not exist, so sources that are mapped
+// to it never interrupt the core.
+pub const IRQ_UART0: u32 = 1;
+pub const IRQ_TIMG0: u32 = 2;
+pub const IRQ_TIMG1: u32 = 3;
+pub const IRQ_GPIO: u32 = 4;
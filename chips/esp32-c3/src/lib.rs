@@ -0,0 +1,20 @@
+//! Peripheral implementations for the Espressif ESP32-C3 MCU.
+//!
+//! ESP32-C3: <https://www.espressif.com/en/products/socs/esp32-c3>
+//!
+//! The ESP32-C3 has a single RV32IMC core. Its peripherals do not interrupt
+//! the core directly: the interrupt matrix routes each of them to one of 31
+//! CPU interrupts (see the `intc` module).
+
+#![feature(asm, const_fn, global_asm, in_band_lifetimes)]
+#![no_std]
+#![crate_name = "esp32_c3"]
+#![crate_type = "rlib"]
+
+pub mod chip;
+pub mod gpio;
+pub mod intc;
+pub mod interrupts;
+pub mod rtc_cntl;
+pub mod timg;
+pub mod uart;
@@ -0,0 +1,80 @@
+//! RTC control of the ESP32-C3.
+//!
+//! Only its watchdogs are used here. The boot ROM starts the RTC watchdog,
+//! which resets the whole chip, and the super watchdog runs unless it is fed
+//! or set to feed itself.
+
+use kernel::common::registers::{register_bitfields, ReadWrite};
+use kernel::common::StaticRef;
+
+#[repr(C)]
+struct RtcCntlRegisters {
+    _reserved0: [u32; 36],
+    /// RTC watchdog configuration
+    wdtconfig0: ReadWrite<u32, WDTCONFIG0::Register>,
+    /// Hold times of the RTC watchdog stages
+    wdtconfig1: ReadWrite<u32>,
+    wdtconfig2: ReadWrite<u32>,
+    wdtconfig3: ReadWrite<u32>,
+    wdtconfig4: ReadWrite<u32>,
+    /// Feed the RTC watchdog
+    wdtfeed: ReadWrite<u32>,
+    /// Write protection of the RTC watchdog registers
+    wdtwprotect: ReadWrite<u32>,
+    /// Super watchdog configuration
+    swd_conf: ReadWrite<u32, SWD_CONF::Register>,
+    /// Write protection of the super watchdog registers
+    swd_wprotect: ReadWrite<u32>,
+}
+
+register_bitfields![u32,
+    WDTCONFIG0 [
+        /// Enable the RTC watchdog
+        WDT_EN OFFSET(31) NUMBITS(1) [],
+        /// Enable the RTC watchdog while booting from flash
+        WDT_FLASHBOOT_MOD_EN OFFSET(12) NUMBITS(1) []
+    ],
+    SWD_CONF [
+        /// Feed the super watchdog automatically
+        SWD_AUTO_FEED_EN OFFSET(31) NUMBITS(1) []
+    ]
+];
+
+const RTC_CNTL_BASE: StaticRef<RtcCntlRegisters> =
+    unsafe { StaticRef::new(0x6000_8000 as *const RtcCntlRegisters) };
+
+/// Writing this to `wdtwprotect` unlocks the RTC watchdog registers
+const WDT_WRITE_KEY: u32 = 0x50D8_3AA1;
+/// Writing this to `swd_wprotect` unlocks the super watchdog registers
+const SWD_WRITE_KEY: u32 = 0x8F1D_312A;
+
+pub struct RtcCntl {
+    registers: StaticRef<RtcCntlRegisters>,
+}
+
+pub static mut RTC_CNTL: RtcCntl = RtcCntl::new();
+
+impl RtcCntl {
+    const fn new() -> RtcCntl {
+        RtcCntl {
+            registers: RTC_CNTL_BASE,
+        }
+    }
+
+    pub fn disable_wdt(&self) {
+        self.registers.wdtwprotect.set(WDT_WRITE_KEY);
+        self.registers
+            .wdtconfig0
+            .modify(WDTCONFIG0::WDT_EN::CLEAR + WDTCONFIG0::WDT_FLASHBOOT_MOD_EN::CLEAR);
+        self.registers.wdtwprotect.set(0);
+    }
+
+    /// The super watchdog cannot be disabled, so let it feed itself.
+    pub fn disable_super_wdt(&self) {
+        self.registers.swd_wprotect.set(SWD_WRITE_KEY);
+        self.registers
+            .swd_conf
+            .modify(SWD_CONF::SWD_AUTO_FEED_EN::SET);
+        self.registers.swd_wprotect.set(0);
+    }
+}
@@ -0,0 +1,202 @@
+//! Timer groups of the ESP32-C3.
+//!
+//! Each timer group has a 54 bit general purpose timer and a watchdog. The
+//! timer counts the 40 MHz crystal clock divided down to 16 kHz, and its
+//! alarm implements `hil::time::Alarm` on the lower 32 bits of the count.
+
+use kernel::common::cells::OptionalCell;
+use kernel::common::registers::{register_bitfields, ReadOnly, ReadWrite, WriteOnly};
+use kernel::common::StaticRef;
+use kernel::hil::time::{self, Alarm, Freq16KHz, Frequency, Time};
+
+/// The frequency of XTAL_CLK
+const XTAL_FREQUENCY: u32 = 40_000_000;
+
+#[repr(C)]
+struct TimgRegisters {
+    /// Timer 0 configuration
+    t0config: ReadWrite<u32, T0CONFIG::Register>,
+    /// Low 32 bits of the time-base counter, latched by `t0update`
+    t0lo: ReadOnly<u32>,
+    /// High 22 bits of the time-base counter, latched by `t0update`
+    t0hi: ReadOnly<u32>,
+    /// Latch the counter into `t0lo` and `t0hi`
+    t0update: ReadWrite<u32, T0UPDATE::Register>,
+    /// Low 32 bits of the alarm value
+    t0alarmlo: ReadWrite<u32>,
+    /// High 22 bits of the alarm value
+    t0alarmhi: ReadWrite<u32>,
+    /// Low 32 bits of the reload value
+    t0loadlo: ReadWrite<u32>,
+    /// High 22 bits of the reload value
+    t0loadhi: ReadWrite<u32>,
+    /// Reload the counter from the reload value
+    t0load: WriteOnly<u32>,
+    _reserved0: [u32; 9],
+    /// Watchdog configuration
+    wdtconfig0: ReadWrite<u32, WDTCONFIG0::Register>,
+    /// Prescaler and stage timeouts of the watchdog
+    wdtconfig: [ReadWrite<u32>; 5],
+    /// Feed the watchdog
+    wdtfeed: WriteOnly<u32>,
+    /// Write protection of the watchdog registers
+    wdtwprotect: ReadWrite<u32>,
+    /// RTC clock calibration
+    rtccalicfg: ReadWrite<u32>,
+    rtccalicfg1: ReadOnly<u32>,
+    /// Interrupt enable
+    int_ena: ReadWrite<u32, INT::Register>,
+    /// Raw interrupt status
+    int_raw: ReadOnly<u32, INT::Register>,
+    /// Masked interrupt status
+    int_st: ReadOnly<u32, INT::Register>,
+    /// Interrupt clear
+    int_clr: WriteOnly<u32, INT::Register>,
+}
+
+register_bitfields![u32,
+    T0CONFIG [
+        /// Enable the time-base counter
+        EN OFFSET(31) NUMBITS(1) [],
+        /// Count up rather than down
+        INCREASE OFFSET(30) NUMBITS(1) [],
+        /// Reload the counter on an alarm
+        AUTORELOAD OFFSET(29) NUMBITS(1) [],
+        /// Prescaler of the counter clock
+        DIVIDER OFFSET(13) NUMBITS(16) [],
+        /// Reset the prescaler
+        DIVCNT_RST OFFSET(12) NUMBITS(1) [],
+        /// Enable the alarm, cleared by hardware when it fires
+        ALARM_EN OFFSET(10) NUMBITS(1) [],
+        /// Count XTAL_CLK rather than APB_CLK
+        USE_XTAL OFFSET(9) NUMBITS(1) []
+    ],
+    T0UPDATE [
+        /// Latch the counter, cleared by hardware once done
+        UPDATE OFFSET(31) NUMBITS(1) []
+    ],
+    WDTCONFIG0 [
+        /// Enable the watchdog
+        WDT_EN OFFSET(31) NUMBITS(1) [],
+        /// Copy the configuration to the watchdog, cleared once done
+        WDT_CONF_UPDATE_EN OFFSET(22) NUMBITS(1) [],
+        /// Enable the watchdog while booting from flash
+        WDT_FLASHBOOT_MOD_EN OFFSET(14) NUMBITS(1) []
+    ],
+    INT [
+        /// Watchdog interrupt
+        WDT OFFSET(1) NUMBITS(1) [],
+        /// Timer 0 alarm interrupt
+        T0 OFFSET(0) NUMBITS(1) []
+    ]
+];
+
+const TIMG0_BASE: StaticRef<TimgRegisters> =
+    unsafe { StaticRef::new(0x6001_F000 as *const TimgRegisters) };
+const TIMG1_BASE: StaticRef<TimgRegisters> =
+    unsafe { StaticRef::new(0x6002_0000 as *const TimgRegisters) };
+
+/// Writing this to `wdtwprotect` unlocks the watchdog registers
+const WDT_WRITE_KEY: u32 = 0x50D8_3AA1;
+
+pub static mut TIMG0: TimG = TimG::new(TIMG0_BASE);
+pub static mut TIMG1: TimG = TimG::new(TIMG1_BASE);
+
+pub struct TimG {
+    registers: StaticRef<TimgRegisters>,
+    client: OptionalCell<&'static time::Client>,
+}
+
+impl TimG {
+    const fn new(base: StaticRef<TimgRegisters>) -> TimG {
+        TimG {
+            registers: base,
+            client: OptionalCell::empty(),
+        }
+    }
+
+    pub fn set_client(&self, client: &'static time::Client) {
+        self.client.set(client);
+    }
+
+    /// Start the counter from zero.
+    pub fn start(&self) {
+        // The prescaler may only change while the counter is disabled
+        self.registers.t0config.write(
+            T0CONFIG::USE_XTAL::SET
+                + T0CONFIG::DIVIDER.val(XTAL_FREQUENCY / Freq16KHz::frequency())
+                + T0CONFIG::INCREASE::SET,
+        );
+        self.registers.t0loadlo.set(0);
+        self.registers.t0loadhi.set(0);
+        self.registers.t0load.set(1);
+        self.registers.t0config.modify(T0CONFIG::EN::SET);
+    }
+
+    pub fn disable_wdt(&self) {
+        self.registers.wdtwprotect.set(WDT_WRITE_KEY);
+        self.registers
+            .wdtconfig0
+            .modify(WDTCONFIG0::WDT_EN::CLEAR + WDTCONFIG0::WDT_FLASHBOOT_MOD_EN::CLEAR);
+        self.registers
+            .wdtconfig0
+            .modify(WDTCONFIG0::WDT_CONF_UPDATE_EN::SET);
+        self.registers.wdtwprotect.set(0);
+    }
+
+    /// Read the full 54 bit counter.
+    fn now_u64(&self) -> u64 {
+        self.registers.t0update.write(T0UPDATE::UPDATE::SET);
+        while self.registers.t0update.is_set(T0UPDATE::UPDATE) {}
+        (self.registers.t0hi.get() as u64) << 32 | self.registers.t0lo.get() as u64
+    }
+
+    pub fn handle_interrupt(&self) {
+        self.registers.int_ena.modify(INT::T0::CLEAR);
+        self.registers.int_clr.write(INT::T0::SET);
+        self.client.map(|client| client.fired());
+    }
+}
+
+impl Time for TimG {
+    type Frequency = Freq16KHz;
+
+    fn disable(&self) {
+        self.registers.t0config.modify(T0CONFIG::ALARM_EN::CLEAR);
+        self.registers.int_ena.modify(INT::T0::CLEAR);
+        self.registers.int_clr.write(INT::T0::SET);
+    }
+
+    fn is_armed(&self) -> bool {
+        self.registers.int_ena.is_set(INT::T0)
+    }
+}
+
+impl Alarm for TimG {
+    fn now(&self) -> u32 {
+        self.now_u64() as u32
+    }
+
+    fn set_alarm(&self, tics: u32) {
+        // The alarm compares all 54 bits of the counter. It fires right away
+        // for a value that the counter has already passed, which is what an
+        // alarm at a `tics` just behind `now()` needs.
+        let now = self.now_u64();
+        let delta = tics.wrapping_sub(now as u32);
+        let alarm = if delta > (1 << 31) {
+            now
+        } else {
+            now + delta as u64
+        };
+
+        self.registers.t0alarmlo.set(alarm as u32);
+        self.registers.t0alarmhi.set((alarm >> 32) as u32);
+        self.registers.int_clr.write(INT::T0::SET);
+        self.registers.int_ena.modify(INT::T0::SET);
+        self.registers.t0config.modify(T0CONFIG::ALARM_EN::SET);
+    }
+
+    fn get_alarm(&self) -> u32 {
+        self.registers.t0alarmlo.get()
+    }
+}
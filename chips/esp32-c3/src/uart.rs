@@ -0,0 +1,460 @@
+//! UART driver for the ESP32-C3.
+//!
+//! Transmission and reception go through the 128 byte FIFOs, refilled and
+//! drained from the UART interrupt. The UART is clocked from the 40 MHz
+//! crystal, so its baud rate does not depend on the CPU clock. The pins of
+//! the UART are routed through the GPIO matrix by the board.
+
+use core::cell::Cell;
+use kernel::common::cells::{OptionalCell, TakeCell};
+use kernel::common::registers::{register_bitfields, ReadOnly, ReadWrite, WriteOnly};
+use kernel::common::StaticRef;
+use kernel::hil;
+use kernel::ReturnCode;
+
+/// The frequency of XTAL_CLK
+const XTAL_FREQUENCY: u32 = 40_000_000;
+
+/// The depth of the transmit and receive FIFOs
+const FIFO_DEPTH: u32 = 128;
+
+/// The transmit FIFO interrupts once it holds fewer bytes than this
+const TX_EMPTY_THRESHOLD: u32 = 16;
+
+#[repr(C)]
+pub struct UartRegisters {
+    /// Read from the receive FIFO, or write to the transmit FIFO
+    fifo: ReadWrite<u32>,
+    /// Raw interrupt status
+    int_raw: ReadOnly<u32, INTERRUPT::Register>,
+    /// Masked interrupt status
+    int_st: ReadOnly<u32, INTERRUPT::Register>,
+    /// Interrupt enable
+    int_ena: ReadWrite<u32, INTERRUPT::Register>,
+    /// Interrupt clear
+    int_clr: WriteOnly<u32, INTERRUPT::Register>,
+    /// Baud rate divisor, in sixteenths
+    clkdiv: ReadWrite<u32, CLKDIV::Register>,
+    /// Glitch filter of the receive line
+    rx_filt: ReadWrite<u32>,
+    /// FIFO fill levels and line states
+    status: ReadOnly<u32, STATUS::Register>,
+    /// Frame format and FIFO resets
+    conf0: ReadWrite<u32, CONF0::Register>,
+    /// FIFO interrupt thresholds and receive timeout
+    conf1: ReadWrite<u32, CONF1::Register>,
+    _reserved0: [u32; 20],
+    /// Clock source and divider of the UART core
+    clk_conf: ReadWrite<u32, CLK_CONF::Register>,
+    /// Version
+    date: ReadOnly<u32>,
+    /// Synchronization of the configuration to the UART core
+    id: ReadWrite<u32, ID::Register>,
+}
+
+register_bitfields![u32,
+    INTERRUPT [
+        /// The receiver timed out with bytes in the receive FIFO
+        RXFIFO_TOUT OFFSET(8) NUMBITS(1) [],
+        /// The receive FIFO overflowed
+        RXFIFO_OVF OFFSET(4) NUMBITS(1) [],
+        /// A stop bit was missing
+        FRM_ERR OFFSET(3) NUMBITS(1) [],
+        /// A parity bit was wrong
+        PARITY_ERR OFFSET(2) NUMBITS(1) [],
+        /// The transmit FIFO holds fewer bytes than its threshold
+        TXFIFO_EMPTY OFFSET(1) NUMBITS(1) [],
+        /// The receive FIFO holds more bytes than its threshold
+        RXFIFO_FULL OFFSET(0) NUMBITS(1) []
+    ],
+    CLKDIV [
+        /// Fractional part of the divisor
+        FRAG OFFSET(20) NUMBITS(4) [],
+        /// Integer part of the divisor
+        CLKDIV OFFSET(0) NUMBITS(12) []
+    ],
+    STATUS [
+        /// The number of bytes in the transmit FIFO
+        TXFIFO_CNT OFFSET(16) NUMBITS(10) [],
+        /// The number of bytes in the receive FIFO
+        RXFIFO_CNT OFFSET(0) NUMBITS(10) []
+    ],
+    CONF0 [
+        /// Reset the transmit FIFO
+        TXFIFO_RST OFFSET(18) NUMBITS(1) [],
+        /// Reset the receive FIFO
+        RXFIFO_RST OFFSET(17) NUMBITS(1) [],
+        /// Transmit hardware flow control
+        TX_FLOW_EN OFFSET(15) NUMBITS(1) [],
+        /// Loop the transmitter back into the receiver
+        LOOPBACK OFFSET(14) NUMBITS(1) [],
+        /// Stop bits
+        STOP_BIT_NUM OFFSET(4) NUMBITS(2) [
+            One = 1,
+            Two = 3
+        ],
+        /// Data bits
+        BIT_NUM OFFSET(2) NUMBITS(2) [
+            Bits5 = 0,
+            Bits6 = 1,
+            Bits7 = 2,
+            Bits8 = 3
+        ],
+        /// Parity enable
+        PARITY_EN OFFSET(1) NUMBITS(1) [],
+        /// Odd parity rather than even
+        PARITY OFFSET(0) NUMBITS(1) []
+    ],
+    CONF1 [
+        /// Raise RXFIFO_TOUT when the receiver is idle
+        RX_TOUT_EN OFFSET(21) NUMBITS(1) [],
+        /// Receive hardware flow control
+        RX_FLOW_EN OFFSET(20) NUMBITS(1) [],
+        /// Threshold of TXFIFO_EMPTY
+        TXFIFO_EMPTY_THRHD OFFSET(9) NUMBITS(9) [],
+        /// Threshold of RXFIFO_FULL
+        RXFIFO_FULL_THRHD OFFSET(0) NUMBITS(9) []
+    ],
+    CLK_CONF [
+        /// Enable the receiver clock
+        RX_SCLK_EN OFFSET(25) NUMBITS(1) [],
+        /// Enable the transmitter clock
+        TX_SCLK_EN OFFSET(24) NUMBITS(1) [],
+        /// Reset the UART core
+        RST_CORE OFFSET(23) NUMBITS(1) [],
+        /// Enable the core clock
+        SCLK_EN OFFSET(22) NUMBITS(1) [],
+        /// Core clock source
+        SCLK_SEL OFFSET(20) NUMBITS(2) [
+            Apb = 1,
+            RcFast = 2,
+            Xtal = 3
+        ],
+        /// Integer part of the core clock divisor, minus one
+        SCLK_DIV_NUM OFFSET(12) NUMBITS(8) [],
+        /// Numerator of the fractional part of the core clock divisor
+        SCLK_DIV_A OFFSET(6) NUMBITS(6) [],
+        /// Denominator of the fractional part of the core clock divisor
+        SCLK_DIV_B OFFSET(0) NUMBITS(6) []
+    ],
+    ID [
+        /// Copy the configuration to the UART core, cleared once done
+        REG_UPDATE OFFSET(31) NUMBITS(1) []
+    ]
+];
+
+const UART0_BASE: StaticRef<UartRegisters> =
+    unsafe { StaticRef::new(0x6000_0000 as *const UartRegisters) };
+
+pub static mut UART0: Uart = Uart::new(UART0_BASE);
+
+pub struct Uart<'a> {
+    registers: StaticRef<UartRegisters>,
+    tx_client: OptionalCell<&'a hil::uart::TransmitClient>,
+    rx_client: OptionalCell<&'a hil::uart::ReceiveClient>,
+    tx_buffer: TakeCell<'static, [u8]>,
+    tx_len: Cell<usize>,
+    tx_index: Cell<usize>,
+    rx_buffer: TakeCell<'static, [u8]>,
+    rx_len: Cell<usize>,
+    rx_index: Cell<usize>,
+}
+
+impl Uart<'a> {
+    const fn new(base: StaticRef<UartRegisters>) -> Uart<'a> {
+        Uart {
+            registers: base,
+            tx_client: OptionalCell::empty(),
+            rx_client: OptionalCell::empty(),
+            tx_buffer: TakeCell::empty(),
+            tx_len: Cell::new(0),
+            tx_index: Cell::new(0),
+            rx_buffer: TakeCell::empty(),
+            rx_len: Cell::new(0),
+            rx_index: Cell::new(0),
+        }
+    }
+
+    fn set_baud_rate(&self, baud_rate: u32) {
+        // The divisor is a 12.4 fixed point number: divisor = f_xtal /
+        // baud_rate
+        let divisor = XTAL_FREQUENCY * 16 / baud_rate;
+        self.registers
+            .clkdiv
+            .write(CLKDIV::CLKDIV.val(divisor >> 4) + CLKDIV::FRAG.val(divisor & 0xf));
+    }
+
+    /// Copy the configuration registers to the UART core, which runs from a
+    /// different clock.
+    fn update(&self) {
+        self.registers.id.modify(ID::REG_UPDATE::SET);
+        while self.registers.id.is_set(ID::REG_UPDATE) {}
+    }
+
+    fn tx_fifo_space(&self) -> u32 {
+        FIFO_DEPTH - self.registers.status.read(STATUS::TXFIFO_CNT)
+    }
+
+    /// Write bytes from the transmit buffer into the FIFO until either is
+    /// exhausted, and return whether the whole buffer has been written.
+    fn fill_fifo(&self) -> bool {
+        self.tx_buffer.map(|buffer| {
+            let mut space = self.tx_fifo_space();
+            while self.tx_index.get() < self.tx_len.get() && space > 0 {
+                self.registers.fifo.set(buffer[self.tx_index.get()] as u32);
+                self.tx_index.set(self.tx_index.get() + 1);
+                space -= 1;
+            }
+        });
+        self.tx_index.get() == self.tx_len.get()
+    }
+
+    fn transmit_done(&self) {
+        self.registers
+            .int_ena
+            .modify(INTERRUPT::TXFIFO_EMPTY::CLEAR);
+        self.tx_client.map(|client| {
+            self.tx_buffer.take().map(|buffer| {
+                client.transmitted_buffer(buffer, self.tx_len.get(), ReturnCode::SUCCESS);
+            });
+        });
+    }
+
+    /// Read bytes from the FIFO into the receive buffer, and signal the
+    /// client once it is full or an error occurs.
+    fn drain_fifo(&self, error: hil::uart::Error) {
+        self.rx_buffer.map(|buffer| {
+            let mut count = self.registers.status.read(STATUS::RXFIFO_CNT);
+            while self.rx_index.get() < self.rx_len.get() && count > 0 {
+                buffer[self.rx_index.get()] = self.registers.fifo.get() as u8;
+                self.rx_index.set(self.rx_index.get() + 1);
+                count -= 1;
+            }
+        });
+
+        if self.rx_index.get() == self.rx_len.get() || error != hil::uart::Error::None {
+            self.disable_receive_interrupts();
+            let rval = if error == hil::uart::Error::None {
+                ReturnCode::SUCCESS
+            } else {
+                ReturnCode::FAIL
+            };
+            self.rx_client.map(|client| {
+                self.rx_buffer.take().map(|buffer| {
+                    client.received_buffer(buffer, self.rx_index.get(), rval, error);
+                });
+            });
+        }
+    }
+
+    fn enable_receive_interrupts(&self) {
+        self.registers.int_ena.modify(
+            INTERRUPT::RXFIFO_FULL::SET
+                + INTERRUPT::RXFIFO_TOUT::SET
+                + INTERRUPT::RXFIFO_OVF::SET
+                + INTERRUPT::FRM_ERR::SET
+                + INTERRUPT::PARITY_ERR::SET,
+        );
+    }
+
+    fn disable_receive_interrupts(&self) {
+        self.registers.int_ena.modify(
+            INTERRUPT::RXFIFO_FULL::CLEAR
+                + INTERRUPT::RXFIFO_TOUT::CLEAR
+                + INTERRUPT::RXFIFO_OVF::CLEAR
+                + INTERRUPT::FRM_ERR::CLEAR
+                + INTERRUPT::PARITY_ERR::CLEAR,
+        );
+    }
+
+    pub fn handle_interrupt(&self) {
+        let status = self.registers.int_st.extract();
+        self.registers.int_clr.set(status.get());
+
+        if status.is_set(INTERRUPT::TXFIFO_EMPTY) {
+            if self.tx_buffer.is_some() && self.fill_fifo() {
+                self.transmit_done();
+            }
+        }
+
+        let error = if status.is_set(INTERRUPT::RXFIFO_OVF) {
+            hil::uart::Error::OverrunError
+        } else if status.is_set(INTERRUPT::PARITY_ERR) {
+            hil::uart::Error::ParityError
+        } else if status.is_set(INTERRUPT::FRM_ERR) {
+            hil::uart::Error::FramingError
+        } else {
+            hil::uart::Error::None
+        };
+        if error != hil::uart::Error::None
+            || status.is_set(INTERRUPT::RXFIFO_FULL)
+            || status.is_set(INTERRUPT::RXFIFO_TOUT)
+        {
+            self.drain_fifo(error);
+        }
+    }
+
+    /// Write a byte, waiting for room in the FIFO. This is used for panic
+    /// messages, and must not be mixed with `transmit_buffer`.
+    pub fn send_byte(&self, data: u8) {
+        while self.tx_fifo_space() == 0 {}
+        self.registers.fifo.set(data as u32);
+    }
+}
+
+impl hil::uart::UartData<'a> for Uart<'a> {}
+impl hil::uart::Uart<'a> for Uart<'a> {}
+
+impl hil::uart::Configure for Uart<'a> {
+    fn configure(&self, params: hil::uart::Parameters) -> ReturnCode {
+        if params.baud_rate == 0 || params.baud_rate > XTAL_FREQUENCY / 16 {
+            return ReturnCode::EINVAL;
+        }
+
+        self.registers.int_ena.set(0);
+        self.registers.clk_conf.write(
+            CLK_CONF::SCLK_SEL::Xtal
+                + CLK_CONF::SCLK_EN::SET
+                + CLK_CONF::TX_SCLK_EN::SET
+                + CLK_CONF::RX_SCLK_EN::SET,
+        );
+        self.set_baud_rate(params.baud_rate);
+
+        let width = match params.width {
+            hil::uart::Width::Six => CONF0::BIT_NUM::Bits6,
+            hil::uart::Width::Seven => CONF0::BIT_NUM::Bits7,
+            hil::uart::Width::Eight => CONF0::BIT_NUM::Bits8,
+        };
+        let parity = match params.parity {
+            hil::uart::Parity::None => CONF0::PARITY_EN::CLEAR,
+            hil::uart::Parity::Odd => CONF0::PARITY_EN::SET + CONF0::PARITY::SET,
+            hil::uart::Parity::Even => CONF0::PARITY_EN::SET + CONF0::PARITY::CLEAR,
+        };
+        let stop_bits = match params.stop_bits {
+            hil::uart::StopBits::One => CONF0::STOP_BIT_NUM::One,
+            hil::uart::StopBits::Two => CONF0::STOP_BIT_NUM::Two,
+        };
+        let tx_flow_control = if params.hw_flow_control {
+            CONF0::TX_FLOW_EN::SET
+        } else {
+            CONF0::TX_FLOW_EN::CLEAR
+        };
+        // CONF0 also holds the clock enable of the FIFO memory, which must
+        // stay set
+        self.registers
+            .conf0
+            .modify(width + parity + stop_bits + tx_flow_control + CONF0::LOOPBACK::CLEAR);
+
+        let rx_flow_control = if params.hw_flow_control {
+            CONF1::RX_FLOW_EN::SET
+        } else {
+            CONF1::RX_FLOW_EN::CLEAR
+        };
+        self.registers.conf1.write(
+            CONF1::RXFIFO_FULL_THRHD.val(1)
+                + CONF1::TXFIFO_EMPTY_THRHD.val(TX_EMPTY_THRESHOLD)
+                + CONF1::RX_TOUT_EN::SET
+                + rx_flow_control,
+        );
+
+        // Empty both FIFOs
+        self.registers
+            .conf0
+            .modify(CONF0::RXFIFO_RST::SET + CONF0::TXFIFO_RST::SET);
+        self.registers
+            .conf0
+            .modify(CONF0::RXFIFO_RST::CLEAR + CONF0::TXFIFO_RST::CLEAR);
+
+        self.update();
+        self.registers.int_clr.set(0xffff_ffff);
+
+        ReturnCode::SUCCESS
+    }
+}
+
+impl hil::uart::Transmit<'a> for Uart<'a> {
+    fn set_transmit_client(&self, client: &'a hil::uart::TransmitClient) {
+        self.tx_client.set(client);
+    }
+
+    fn transmit_buffer(
+        &self,
+        tx_data: &'static mut [u8],
+        tx_len: usize,
+    ) -> (ReturnCode, Option<&'static mut [u8]>) {
+        if self.tx_buffer.is_some() {
+            return (ReturnCode::EBUSY, Some(tx_data));
+        }
+        if tx_len == 0 || tx_len > tx_data.len() {
+            return (ReturnCode::ESIZE, Some(tx_data));
+        }
+
+        self.tx_buffer.replace(tx_data);
+        self.tx_len.set(tx_len);
+        self.tx_index.set(0);
+
+        // The transmit FIFO interrupt is a level, so it also reports the
+        // completion of a buffer that fit in the FIFO at once
+        self.fill_fifo();
+        self.registers.int_clr.write(INTERRUPT::TXFIFO_EMPTY::SET);
+        self.registers.int_ena.modify(INTERRUPT::TXFIFO_EMPTY::SET);
+
+        (ReturnCode::SUCCESS, None)
+    }
+
+    fn transmit_abort(&self) -> ReturnCode {
+        ReturnCode::FAIL
+    }
+
+    fn transmit_word(&self, _word: u32) -> ReturnCode {
+        ReturnCode::FAIL
+    }
+}
+
+impl hil::uart::Receive<'a> for Uart<'a> {
+    fn set_receive_client(&self, client: &'a hil::uart::ReceiveClient) {
+        self.rx_client.set(client);
+    }
+
+    fn receive_buffer(
+        &self,
+        rx_buffer: &'static mut [u8],
+        rx_len: usize,
+    ) -> (ReturnCode, Option<&'static mut [u8]>) {
+        if self.rx_buffer.is_some() {
+            return (ReturnCode::EBUSY, Some(rx_buffer));
+        }
+        if rx_len == 0 || rx_len > rx_buffer.len() {
+            return (ReturnCode::ESIZE, Some(rx_buffer));
+        }
+
+        self.rx_buffer.replace(rx_buffer);
+        self.rx_len.set(rx_len);
+        self.rx_index.set(0);
+        self.enable_receive_interrupts();
+
+        (ReturnCode::SUCCESS, None)
+    }
+
+    fn receive_abort(&self) -> ReturnCode {
+        self.disable_receive_interrupts();
+        match self.rx_buffer.take() {
+            Some(buffer) => {
+                self.rx_client.map(move |client| {
+                    client.received_buffer(
+                        buffer,
+                        self.rx_index.get(),
+                        ReturnCode::ECANCEL,
+                        hil::uart::Error::Aborted,
+                    );
+                });
+                ReturnCode::SUCCESS
+            }
+            None => ReturnCode::SUCCESS,
+        }
+    }
+
+    fn receive_word(&self) -> ReturnCode {
+        ReturnCode::FAIL
+    }
+}
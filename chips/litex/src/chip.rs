@@ -0,0 +1,179 @@
+//! Chip trait setup for LiteX SoCs with a VexRiscv CPU.
+//!
+//! The peripherals of a LiteX SoC and their interrupt lines depend on how the
+//! SoC was generated, so the board tells the chip how to service each
+//! interrupt line and deferred call through `InterruptService`.
+
+use kernel;
+use kernel::common::deferred_call;
+use kernel::debug;
+use rv32i;
+
+use crate::deferred_call_tasks::DeferredCallTask;
+use crate::interrupt_controller::INTERRUPT_CONTROLLER;
+
+/// Dispatch of the interrupts and deferred calls of the SoC to the drivers.
+pub trait InterruptService {
+    /// Service interrupt line `interrupt`, and return whether it is one of
+    /// the SoC.
+    unsafe fn service_interrupt(&self, interrupt: u32) -> bool;
+
+    /// Service deferred call `task`, and return whether the SoC has the
+    /// peripheral that set it.
+    unsafe fn service_deferred_call(&self, task: DeferredCallTask) -> bool;
+}
+
+pub struct LiteXVexRiscv<I: 'static + InterruptService> {
+    userspace_kernel_boundary: rv32i::syscall::SysCall,
+    interrupt_service: &'static I,
+}
+
+impl<I: 'static + InterruptService> LiteXVexRiscv<I> {
+    pub unsafe fn new(interrupt_service: &'static I) -> LiteXVexRiscv<I> {
+        LiteXVexRiscv {
+            userspace_kernel_boundary: rv32i::syscall::SysCall::new(),
+            interrupt_service: interrupt_service,
+        }
+    }
+
+    pub fn enable_all_interrupts(&self) {
+        unsafe {
+            INTERRUPT_CONTROLLER.enable_all();
+        }
+    }
+
+    /// Configure the PMP to allow all accesses in both machine mode (the
+    /// default) and in user mode. This needs the `secure` variant of
+    /// VexRiscv, the only one with a PMP and user mode.
+    ///
+    /// This needs to be replaced with a real PMP driver. See
+    /// https://github.com/tock/tock/issues/1135
+    pub unsafe fn disable_pmp(&self) {
+        asm!("
+            // Set the first region address to 0xFFFFFFFF. When using top-of-range mode
+            // this will include the entire address space.
+            lui  t0, %hi(0xFFFFFFFF)
+            addi t0, t0, %lo(0xFFFFFFFF)
+            csrw 0x3b0, t0    // CSR=pmpaddr0
+
+            // Set the first region to use top-of-range and allow everything.
+            // This is equivalent to:
+            // R=1, W=1, X=1, A=01, L=0
+            li   t0, 0x0F
+            csrw 0x3a0, t0    // CSR=pmpcfg0
+        "
+        :
+        :
+        :
+        : "volatile");
+    }
+
+    /// Generic helper initialize function to setup all of the chip specific
+    /// operations. Different boards can call the functions that `initialize()`
+    /// calls directly if it needs to use a custom setup operation.
+    pub unsafe fn initialize(&self) {
+        self.disable_pmp();
+        INTERRUPT_CONTROLLER.disable_all();
+        rv32i::configure_trap_handler();
+    }
+}
+
+impl<I: 'static + InterruptService> kernel::Chip for LiteXVexRiscv<I> {
+    type MPU = ();
+    type UserspaceKernelBoundary = rv32i::syscall::SysCall;
+    type SysTick = ();
+
+    fn mpu(&self) -> &Self::MPU {
+        &()
+    }
+
+    fn systick(&self) -> &Self::SysTick {
+        &()
+    }
+
+    fn userspace_kernel_boundary(&self) -> &rv32i::syscall::SysCall {
+        &self.userspace_kernel_boundary
+    }
+
+    fn service_pending_interrupts(&self) {
+        unsafe {
+            loop {
+                if let Some(task) = deferred_call::DeferredCall::next_pending() {
+                    if !self.interrupt_service.service_deferred_call(task) {
+                        debug!("Unhandled deferred call");
+                    }
+                } else if let Some(interrupt) = INTERRUPT_CONTROLLER.next_pending() {
+                    if !self.interrupt_service.service_interrupt(interrupt) {
+                        debug!("Pidx {}", interrupt);
+                    }
+
+                    // Mark that we are done with this interrupt, which
+                    // unmasks it again.
+                    INTERRUPT_CONTROLLER.complete(interrupt);
+                } else {
+                    break;
+                }
+            }
+        }
+    }
+
+    fn has_pending_interrupts(&self) -> bool {
+        unsafe { INTERRUPT_CONTROLLER.has_pending() || deferred_call::has_tasks() }
+    }
+
+    fn sleep(&self) {
+        unsafe {
+            rv32i::support::wfi();
+        }
+    }
+
+    unsafe fn atomic<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce() -> R,
+    {
+        rv32i::support::atomic(f)
+    }
+}
+
+/// Trap handler for board/chip specific code.
+///
+/// For LiteX this gets called when an interrupt occurs while the chip is in
+/// kernel mode. The only interrupt in use is the machine external interrupt,
+/// raised by the interrupt lines of the SoC.
+#[export_name = "_start_trap_rust"]
+pub extern "C" fn start_trap_rust() {
+    let mut mcause: i32;
+
+    unsafe {
+        asm!("
+            // Read the mcause CSR to determine why we entered the trap handler.
+            csrr $0, 0x342    // CSR=0x342=mcause
+        "
+        : "=r"(mcause)
+        :
+        :
+        : "volatile");
+    }
+
+    // Check if the trap was from an interrupt or some other exception.
+    if mcause < 0 {
+        // If the most significant bit is set (i.e. mcause is negative) then
+        // this was an interrupt.
+        unsafe {
+            INTERRUPT_CONTROLLER.save_pending();
+        }
+    } else {
+        // Otherwise, the kernel encountered a fault...so panic!()?
+        panic!("kernel exception");
+    }
+}
+
+/// Function that gets called if an interrupt occurs while an app was running.
+/// mcause is passed in, and this function should correctly handle disabling the
+/// interrupt that fired so that it does not trigger again.
+#[export_name = "_disable_interrupt_trap_handler"]
+pub extern "C" fn disable_interrupt_trap_handler(_mcause: u32) {
+    unsafe {
+        INTERRUPT_CONTROLLER.save_pending();
+    }
+}
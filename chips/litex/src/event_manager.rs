@@ -0,0 +1,39 @@
+//! The event manager that LiteX peripherals signal their interrupts with.
+//!
+//! Each event has a bit in the status, pending and enable registers. The
+//! peripheral interrupts while any event is both pending and enabled, and a
+//! pending event is cleared by writing its bit to the pending register.
+
+use kernel::common::registers::{ReadOnly, ReadWrite};
+
+#[repr(C)]
+pub struct EventManagerRegisters {
+    /// Current state of the event sources
+    status: ReadOnly<u32>,
+    /// Latched events, write 1 to clear
+    pending: ReadWrite<u32>,
+    /// Events that raise the interrupt
+    enable: ReadWrite<u32>,
+}
+
+impl EventManagerRegisters {
+    pub fn is_pending(&self, event: u32) -> bool {
+        self.pending.get() & (1 << event) != 0
+    }
+
+    pub fn clear(&self, event: u32) {
+        self.pending.set(1 << event);
+    }
+
+    pub fn enable(&self, event: u32) {
+        self.enable.set(self.enable.get() | (1 << event));
+    }
+
+    pub fn disable(&self, event: u32) {
+        self.enable.set(self.enable.get() & !(1 << event));
+    }
+
+    pub fn disable_all(&self) {
+        self.enable.set(0);
+    }
+}
@@ -0,0 +1,92 @@
+//! Interrupt controller of the LiteX VexRiscv CPU.
+//!
+//! VexRiscv takes the interrupt lines of the SoC through two custom CSRs: a
+//! mask (0xBC0) and the pending lines (0xFC0). They raise the machine
+//! external interrupt while any line is both pending and unmasked. The trap
+//! handler masks and saves the pending lines, and the chip handles and
+//! unmasks them again from the kernel loop.
+
+use kernel::common::cells::VolatileCell;
+
+pub struct VexRiscvInterruptController {
+    saved: VolatileCell<u32>,
+}
+
+pub static mut INTERRUPT_CONTROLLER: VexRiscvInterruptController =
+    VexRiscvInterruptController::new();
+
+fn read_mask() -> u32 {
+    let mask: u32;
+    unsafe {
+        asm!("csrr $0, 0xBC0" : "=r"(mask) : : : "volatile");
+    }
+    mask
+}
+
+fn write_mask(mask: u32) {
+    unsafe {
+        asm!("csrw 0xBC0, $0" : : "r"(mask) : : "volatile");
+    }
+}
+
+fn read_pending() -> u32 {
+    let pending: u32;
+    unsafe {
+        asm!("csrr $0, 0xFC0" : "=r"(pending) : : : "volatile");
+    }
+    pending
+}
+
+impl VexRiscvInterruptController {
+    const fn new() -> VexRiscvInterruptController {
+        VexRiscvInterruptController {
+            saved: VolatileCell::new(0),
+        }
+    }
+
+    /// Unmask all interrupt lines, and enable the machine external interrupt
+    /// (mie.MEIE) that they raise.
+    pub fn enable_all(&self) {
+        write_mask(0xffff_ffff);
+        unsafe {
+            asm!("csrs 0x304, $0" : : "r"(1 << 11) : : "volatile");
+        }
+    }
+
+    pub fn disable_all(&self) {
+        write_mask(0);
+    }
+
+    /// Mask the lines that are pending and save them, from the trap handler.
+    pub fn save_pending(&self) {
+        let pending = read_pending() & read_mask();
+        write_mask(read_mask() & !pending);
+        self.saved.set(self.saved.get() | pending);
+    }
+
+    /// Get the lowest interrupt line that is pending.
+    pub fn next_pending(&self) -> Option<u32> {
+        let saved = self.saved.get();
+        if saved == 0 {
+            None
+        } else {
+            Some(saved.trailing_zeros())
+        }
+    }
+
+    /// Signal that a pending line was handled, and unmask it again.
+    pub fn complete(&self, irq: u32) {
+        unsafe {
+            // The trap handler may save other lines, so clear this one with
+            // interrupts disabled (mstatus.MIE)
+            asm!("csrc 0x300, $0" : : "r"(0x8) : : "volatile");
+            self.saved.set(self.saved.get() & !(1 << irq));
+            write_mask(read_mask() | (1 << irq));
+            asm!("csrs 0x300, $0" : : "r"(0x8) : : "volatile");
+        }
+    }
+
+    pub fn has_pending(&self) -> bool {
+        self.saved.get() != 0
+    }
+}
@@ -0,0 +1,23 @@
+//! Peripheral implementations for LiteX SoCs with a VexRiscv CPU.
+//!
+//! LiteX: <https://github.com/enjoy-digital/litex>
+//!
+//! A LiteX SoC is generated, so the addresses of the peripherals and their
+//! interrupt numbers differ from one SoC to another, and are passed in by the
+//! board. The drivers expect the CSR bus to be 32 bits wide
+//! (`--csr-data-width=32`), so that every CSR is a single word.
+
+#![feature(asm, const_fn, in_band_lifetimes)]
+#![no_std]
+#![crate_name = "litex"]
+#![crate_type = "rlib"]
+
+mod event_manager;
+
+pub mod deferred_call_tasks;
+
+pub mod chip;
+pub mod interrupt_controller;
+pub mod liteeth;
+pub mod timer;
+pub mod uart;
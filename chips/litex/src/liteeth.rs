@@ -0,0 +1,184 @@
+//! Ethernet MAC driver for LiteEth.
+//!
+//! LiteEth moves frames through slots of a buffer memory: the receive slots
+//! come first, then the transmit slots, each `SLOT_LEN` bytes long. The
+//! `available` event of the writer signals a received frame in the slot it
+//! names, and the `done` event of the reader a transmitted one. The MAC
+//! receives every frame, so frames for other stations are dropped here.
+//!
+//! Only one frame is sent at a time, from the first transmit slot.
+
+use core::cell::Cell;
+use core::ptr;
+use core::slice;
+use kernel::common::cells::{OptionalCell, TakeCell};
+use kernel::common::registers::{ReadOnly, ReadWrite};
+use kernel::common::StaticRef;
+use kernel::hil::ethernet::{self, Ethernet, RxClient, TxClient};
+use kernel::ReturnCode;
+
+use crate::event_manager::EventManagerRegisters;
+
+/// Length of each slot of the buffer memory
+pub const SLOT_LEN: usize = 2048;
+
+/// Event of the writer, raised when a frame was received
+const EVENT_AVAILABLE: u32 = 0;
+/// Event of the reader, raised when a frame was sent
+const EVENT_DONE: u32 = 0;
+
+#[repr(C)]
+pub struct LiteEthRegisters {
+    /// Slot that holds the received frame
+    rx_slot: ReadOnly<u32>,
+    /// Length of the received frame
+    rx_length: ReadOnly<u32>,
+    /// Number of frames dropped for lack of a free slot
+    rx_errors: ReadOnly<u32>,
+    /// Event manager of the writer
+    rx_ev: EventManagerRegisters,
+    /// Start sending the frame of `tx_slot`
+    tx_start: ReadWrite<u32>,
+    /// The reader can accept a frame
+    tx_ready: ReadOnly<u32>,
+    /// Number of frames queued in the reader
+    tx_level: ReadOnly<u32>,
+    /// Slot of the frame to send
+    tx_slot: ReadWrite<u32>,
+    /// Length of the frame to send
+    tx_length: ReadWrite<u32>,
+    /// Event manager of the reader
+    tx_ev: EventManagerRegisters,
+    /// Keep the preamble and the frame check sequence of received frames
+    preamble_crc: ReadOnly<u32>,
+}
+
+pub struct LiteEth<'a> {
+    registers: StaticRef<LiteEthRegisters>,
+    buffer_base: usize,
+    rx_slots: usize,
+    mac_address: [u8; 6],
+    tx_client: OptionalCell<&'a TxClient>,
+    rx_client: OptionalCell<&'a RxClient>,
+    tx_frame: TakeCell<'static, [u8]>,
+    enabled: Cell<bool>,
+}
+
+impl LiteEth<'a> {
+    /// Create the driver of the MAC whose CSRs start at `base`, and whose
+    /// buffer memory starts at `buffer_base` with `rx_slots` receive slots.
+    /// LiteEth has no MAC address of its own, so the board assigns one.
+    pub const unsafe fn new(
+        base: usize,
+        buffer_base: usize,
+        rx_slots: usize,
+        mac_address: [u8; 6],
+    ) -> LiteEth<'a> {
+        LiteEth {
+            registers: StaticRef::new(base as *const LiteEthRegisters),
+            buffer_base: buffer_base,
+            rx_slots: rx_slots,
+            mac_address: mac_address,
+            tx_client: OptionalCell::empty(),
+            rx_client: OptionalCell::empty(),
+            tx_frame: TakeCell::empty(),
+            enabled: Cell::new(false),
+        }
+    }
+
+    fn rx_slot(&self, slot: usize) -> *const u8 {
+        (self.buffer_base + slot * SLOT_LEN) as *const u8
+    }
+
+    fn tx_slot(&self, slot: usize) -> *mut u8 {
+        (self.buffer_base + (self.rx_slots + slot) * SLOT_LEN) as *mut u8
+    }
+
+    /// Whether a received frame is for this station: its own address, or a
+    /// broadcast or multicast address.
+    fn is_for_us(&self, frame: &[u8]) -> bool {
+        frame[0] & 0x01 != 0 || frame[0..6] == self.mac_address
+    }
+
+    fn receive(&self) {
+        let slot = self.registers.rx_slot.get() as usize;
+        let len = self.registers.rx_length.get() as usize;
+        if slot < self.rx_slots && len >= ethernet::HEADER_LEN && len <= SLOT_LEN {
+            let frame = unsafe { slice::from_raw_parts(self.rx_slot(slot), len) };
+            if self.is_for_us(frame) {
+                self.rx_client.map(|client| client.received_frame(frame));
+            }
+        }
+        // Clearing the event frees the slot
+        self.registers.rx_ev.clear(EVENT_AVAILABLE);
+    }
+
+    pub fn handle_interrupt(&self) {
+        while self.registers.rx_ev.is_pending(EVENT_AVAILABLE) {
+            self.receive();
+        }
+
+        if self.registers.tx_ev.is_pending(EVENT_DONE) {
+            self.registers.tx_ev.clear(EVENT_DONE);
+            self.tx_frame.take().map(|frame| {
+                self.tx_client
+                    .map(move |client| client.transmit_done(frame, ReturnCode::SUCCESS));
+            });
+        }
+    }
+}
+
+impl Ethernet<'a> for LiteEth<'a> {
+    fn set_transmit_client(&self, client: &'a TxClient) {
+        self.tx_client.set(client);
+    }
+
+    fn set_receive_client(&self, client: &'a RxClient) {
+        self.rx_client.set(client);
+    }
+
+    fn mac_address(&self) -> [u8; 6] {
+        self.mac_address
+    }
+
+    fn enable(&self) -> ReturnCode {
+        // Drop anything received before the stack was ready
+        while self.registers.rx_ev.is_pending(EVENT_AVAILABLE) {
+            self.registers.rx_ev.clear(EVENT_AVAILABLE);
+        }
+        self.registers.tx_ev.clear(EVENT_DONE);
+        self.registers.rx_ev.enable(EVENT_AVAILABLE);
+        self.registers.tx_ev.enable(EVENT_DONE);
+        self.enabled.set(true);
+        ReturnCode::SUCCESS
+    }
+
+    fn transmit(
+        &self,
+        frame: &'static mut [u8],
+        len: usize,
+    ) -> (ReturnCode, Option<&'static mut [u8]>) {
+        if !self.enabled.get() {
+            return (ReturnCode::EOFF, Some(frame));
+        }
+        if self.tx_frame.is_some() || self.registers.tx_ready.get() == 0 {
+            return (ReturnCode::EBUSY, Some(frame));
+        }
+        if len < ethernet::HEADER_LEN || len > frame.len() || len > ethernet::MAX_FRAME_LEN {
+            return (ReturnCode::ESIZE, Some(frame));
+        }
+
+        let slot = self.tx_slot(0);
+        for (i, byte) in frame[..len].iter().enumerate() {
+            unsafe {
+                ptr::write_volatile(slot.add(i), *byte);
+            }
+        }
+        self.registers.tx_slot.set(0);
+        self.registers.tx_length.set(len as u32);
+        self.tx_frame.replace(frame);
+        self.registers.tx_start.set(1);
+
+        (ReturnCode::SUCCESS, None)
+    }
+}
@@ -0,0 +1,122 @@
+//! Timer driver for the LiteX timer.
+//!
+//! The timer counts down from `load` and raises its `zero` event when it
+//! reaches zero. It also has a 64 bit `uptime` counter of the system clock,
+//! which `now()` reads. Both count the system clock, whose frequency is
+//! chosen when the SoC is generated and given as `F`.
+
+use core::marker::PhantomData;
+use kernel::common::cells::OptionalCell;
+use kernel::common::registers::{ReadOnly, ReadWrite};
+use kernel::common::StaticRef;
+use kernel::hil::time::{self, Alarm, Frequency, Time};
+
+use crate::event_manager::EventManagerRegisters;
+
+/// Event raised when the countdown reaches zero
+const EVENT_ZERO: u32 = 0;
+
+#[repr(C)]
+pub struct TimerRegisters {
+    /// Value the countdown starts from when it is enabled
+    load: ReadWrite<u32>,
+    /// Value the countdown restarts from when it reaches zero, 0 for one-shot
+    reload: ReadWrite<u32>,
+    /// Enable the countdown
+    en: ReadWrite<u32>,
+    /// Latch the countdown into `value`
+    update_value: ReadWrite<u32>,
+    /// Latched countdown
+    value: ReadOnly<u32>,
+    /// Event manager of the `zero` event
+    ev: EventManagerRegisters,
+    /// Latch the uptime counter into `uptime_cycles`
+    uptime_latch: ReadWrite<u32>,
+    /// High 32 bits of the latched uptime counter
+    uptime_cycles1: ReadOnly<u32>,
+    /// Low 32 bits of the latched uptime counter
+    uptime_cycles0: ReadOnly<u32>,
+}
+
+pub struct Timer<F: Frequency> {
+    registers: StaticRef<TimerRegisters>,
+    client: OptionalCell<&'static time::Client>,
+    alarm: OptionalCell<u32>,
+    _frequency: PhantomData<F>,
+}
+
+impl<F: Frequency> Timer<F> {
+    /// Create the driver of the timer whose CSRs start at `base`. The SoC
+    /// must be generated with the uptime counter of the timer.
+    pub const unsafe fn new(base: usize) -> Timer<F> {
+        Timer {
+            registers: StaticRef::new(base as *const TimerRegisters),
+            client: OptionalCell::empty(),
+            alarm: OptionalCell::empty(),
+            _frequency: PhantomData,
+        }
+    }
+
+    pub fn set_client(&self, client: &'static time::Client) {
+        self.client.set(client);
+    }
+
+    /// Read the full 64 bit uptime counter.
+    fn uptime(&self) -> u64 {
+        self.registers.uptime_latch.set(1);
+        (self.registers.uptime_cycles1.get() as u64) << 32
+            | self.registers.uptime_cycles0.get() as u64
+    }
+
+    pub fn handle_interrupt(&self) {
+        self.registers.ev.clear(EVENT_ZERO);
+        self.registers.ev.disable(EVENT_ZERO);
+        self.registers.en.set(0);
+        self.alarm.take();
+        self.client.map(|client| client.fired());
+    }
+}
+
+impl<F: Frequency> Time for Timer<F> {
+    type Frequency = F;
+
+    fn disable(&self) {
+        self.registers.en.set(0);
+        self.registers.ev.disable(EVENT_ZERO);
+        self.registers.ev.clear(EVENT_ZERO);
+        self.alarm.take();
+    }
+
+    fn is_armed(&self) -> bool {
+        self.alarm.is_some()
+    }
+}
+
+impl<F: Frequency> Alarm for Timer<F> {
+    fn now(&self) -> u32 {
+        self.uptime() as u32
+    }
+
+    fn set_alarm(&self, tics: u32) {
+        // The countdown runs for the ticks left until `tics`, or fires right
+        // away for a `tics` just behind `now()`
+        let delta = tics.wrapping_sub(self.now());
+        let load = if delta == 0 || delta > (1 << 31) {
+            1
+        } else {
+            delta
+        };
+
+        self.registers.en.set(0);
+        self.registers.load.set(load);
+        self.registers.reload.set(0);
+        self.registers.ev.clear(EVENT_ZERO);
+        self.registers.ev.enable(EVENT_ZERO);
+        self.alarm.set(tics);
+        self.registers.en.set(1);
+    }
+
+    fn get_alarm(&self) -> u32 {
+        self.alarm.unwrap_or(0)
+    }
+}
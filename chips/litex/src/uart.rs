@@ -0,0 +1,257 @@
+//! UART driver for the LiteX UART.
+//!
+//! The UART has a transmit and a receive FIFO, of a depth chosen when the SoC
+//! is generated, and its baud rate is fixed by the SoC as well. The `tx`
+//! event fires when the transmit FIFO stops being full, and the `rx` event
+//! when the receive FIFO stops being empty. A buffer that fits in the
+//! transmit FIFO at once never fills it, so its completion is signalled with
+//! a deferred call instead.
+
+use core::cell::Cell;
+use kernel::common::cells::{OptionalCell, TakeCell};
+use kernel::common::deferred_call::DeferredCall;
+use kernel::common::registers::{ReadOnly, ReadWrite};
+use kernel::common::StaticRef;
+use kernel::hil;
+use kernel::ReturnCode;
+
+use crate::deferred_call_tasks::DeferredCallTask;
+use crate::event_manager::EventManagerRegisters;
+
+/// Event raised when the transmit FIFO stops being full
+const EVENT_TX: u32 = 0;
+/// Event raised when the receive FIFO stops being empty
+const EVENT_RX: u32 = 1;
+
+#[repr(C)]
+pub struct UartRegisters {
+    /// Read from the receive FIFO, or write to the transmit FIFO
+    rxtx: ReadWrite<u32>,
+    /// The transmit FIFO is full
+    txfull: ReadOnly<u32>,
+    /// The receive FIFO is empty
+    rxempty: ReadOnly<u32>,
+    /// Event manager of the `tx` and `rx` events
+    ev: EventManagerRegisters,
+    /// The transmit FIFO is empty
+    txempty: ReadOnly<u32>,
+    /// The receive FIFO is full
+    rxfull: ReadOnly<u32>,
+}
+
+/// This mechanism allows us to schedule "interrupts" even if the hardware
+/// does not support them.
+static DEFERRED_CALL: DeferredCall<DeferredCallTask> =
+    unsafe { DeferredCall::new(DeferredCallTask::Uart) };
+
+pub struct Uart<'a> {
+    registers: StaticRef<UartRegisters>,
+    tx_client: OptionalCell<&'a hil::uart::TransmitClient>,
+    rx_client: OptionalCell<&'a hil::uart::ReceiveClient>,
+    tx_buffer: TakeCell<'static, [u8]>,
+    tx_len: Cell<usize>,
+    tx_index: Cell<usize>,
+    rx_buffer: TakeCell<'static, [u8]>,
+    rx_len: Cell<usize>,
+    rx_index: Cell<usize>,
+}
+
+impl Uart<'a> {
+    /// Create the driver of the UART whose CSRs start at `base`.
+    pub const unsafe fn new(base: usize) -> Uart<'a> {
+        Uart {
+            registers: StaticRef::new(base as *const UartRegisters),
+            tx_client: OptionalCell::empty(),
+            rx_client: OptionalCell::empty(),
+            tx_buffer: TakeCell::empty(),
+            tx_len: Cell::new(0),
+            tx_index: Cell::new(0),
+            rx_buffer: TakeCell::empty(),
+            rx_len: Cell::new(0),
+            rx_index: Cell::new(0),
+        }
+    }
+
+    /// Write bytes from the transmit buffer into the FIFO until either is
+    /// exhausted, and return whether the whole buffer has been written.
+    fn fill_fifo(&self) -> bool {
+        self.tx_buffer.map(|buffer| {
+            while self.tx_index.get() < self.tx_len.get() && self.registers.txfull.get() == 0 {
+                self.registers.rxtx.set(buffer[self.tx_index.get()] as u32);
+                self.tx_index.set(self.tx_index.get() + 1);
+            }
+        });
+        self.tx_index.get() == self.tx_len.get()
+    }
+
+    /// Continue the transmission, and signal the client once the whole
+    /// buffer has been written.
+    fn transmit_progress(&self) {
+        if self.tx_buffer.is_none() {
+            return;
+        }
+        if self.fill_fifo() {
+            self.registers.ev.disable(EVENT_TX);
+            self.tx_client.map(|client| {
+                self.tx_buffer.take().map(|buffer| {
+                    client.transmitted_buffer(buffer, self.tx_len.get(), ReturnCode::SUCCESS);
+                });
+            });
+        } else {
+            self.registers.ev.enable(EVENT_TX);
+        }
+    }
+
+    /// Read bytes from the FIFO into the receive buffer, and signal the
+    /// client once it is full.
+    fn drain_fifo(&self) {
+        self.rx_buffer.map(|buffer| {
+            while self.rx_index.get() < self.rx_len.get() && self.registers.rxempty.get() == 0 {
+                buffer[self.rx_index.get()] = self.registers.rxtx.get() as u8;
+                // Reading `rxtx` does not advance the FIFO, the `rx` event
+                // does
+                self.registers.ev.clear(EVENT_RX);
+                self.rx_index.set(self.rx_index.get() + 1);
+            }
+        });
+
+        if self.rx_index.get() == self.rx_len.get() {
+            self.registers.ev.disable(EVENT_RX);
+            self.rx_client.map(|client| {
+                self.rx_buffer.take().map(|buffer| {
+                    client.received_buffer(
+                        buffer,
+                        self.rx_index.get(),
+                        ReturnCode::SUCCESS,
+                        hil::uart::Error::None,
+                    );
+                });
+            });
+        }
+    }
+
+    pub fn handle_interrupt(&self) {
+        if self.registers.ev.is_pending(EVENT_TX) {
+            self.registers.ev.clear(EVENT_TX);
+            self.transmit_progress();
+        }
+        if self.registers.ev.is_pending(EVENT_RX) || self.registers.rxempty.get() == 0 {
+            self.drain_fifo();
+        }
+    }
+
+    /// The deferred call of a transmission that completed without the FIFO
+    /// filling up.
+    pub fn handle_deferred_call(&self) {
+        self.transmit_progress();
+    }
+
+    /// Write a byte, waiting for room in the FIFO. This is used for panic
+    /// messages, and must not be mixed with `transmit_buffer`.
+    pub fn send_byte(&self, data: u8) {
+        while self.registers.txfull.get() != 0 {}
+        self.registers.rxtx.set(data as u32);
+    }
+}
+
+impl hil::uart::UartData<'a> for Uart<'a> {}
+impl hil::uart::Uart<'a> for Uart<'a> {}
+
+impl hil::uart::Configure for Uart<'a> {
+    /// The frame format and the baud rate are fixed when the SoC is
+    /// generated, so this only resets the events.
+    fn configure(&self, _params: hil::uart::Parameters) -> ReturnCode {
+        self.registers.ev.disable_all();
+        self.registers.ev.clear(EVENT_TX);
+        self.registers.ev.clear(EVENT_RX);
+        ReturnCode::SUCCESS
+    }
+}
+
+impl hil::uart::Transmit<'a> for Uart<'a> {
+    fn set_transmit_client(&self, client: &'a hil::uart::TransmitClient) {
+        self.tx_client.set(client);
+    }
+
+    fn transmit_buffer(
+        &self,
+        tx_data: &'static mut [u8],
+        tx_len: usize,
+    ) -> (ReturnCode, Option<&'static mut [u8]>) {
+        if self.tx_buffer.is_some() {
+            return (ReturnCode::EBUSY, Some(tx_data));
+        }
+        if tx_len == 0 || tx_len > tx_data.len() {
+            return (ReturnCode::ESIZE, Some(tx_data));
+        }
+
+        self.tx_buffer.replace(tx_data);
+        self.tx_len.set(tx_len);
+        self.tx_index.set(0);
+
+        self.registers.ev.clear(EVENT_TX);
+        if self.fill_fifo() {
+            DEFERRED_CALL.set();
+        } else {
+            self.registers.ev.enable(EVENT_TX);
+        }
+
+        (ReturnCode::SUCCESS, None)
+    }
+
+    fn transmit_abort(&self) -> ReturnCode {
+        ReturnCode::FAIL
+    }
+
+    fn transmit_word(&self, _word: u32) -> ReturnCode {
+        ReturnCode::FAIL
+    }
+}
+
+impl hil::uart::Receive<'a> for Uart<'a> {
+    fn set_receive_client(&self, client: &'a hil::uart::ReceiveClient) {
+        self.rx_client.set(client);
+    }
+
+    fn receive_buffer(
+        &self,
+        rx_buffer: &'static mut [u8],
+        rx_len: usize,
+    ) -> (ReturnCode, Option<&'static mut [u8]>) {
+        if self.rx_buffer.is_some() {
+            return (ReturnCode::EBUSY, Some(rx_buffer));
+        }
+        if rx_len == 0 || rx_len > rx_buffer.len() {
+            return (ReturnCode::ESIZE, Some(rx_buffer));
+        }
+
+        self.rx_buffer.replace(rx_buffer);
+        self.rx_len.set(rx_len);
+        self.rx_index.set(0);
+        self.registers.ev.enable(EVENT_RX);
+
+        (ReturnCode::SUCCESS, None)
+    }
+
+    fn receive_abort(&self) -> ReturnCode {
+        self.registers.ev.disable(EVENT_RX);
+        match self.rx_buffer.take() {
+            Some(buffer) => {
+                self.rx_client.map(move |client| {
+                    client.received_buffer(
+                        buffer,
+                        self.rx_index.get(),
+                        ReturnCode::ECANCEL,
+                        hil::uart::Error::Aborted,
+                    );
+                });
+                ReturnCode::SUCCESS
+            }
+            None => ReturnCode::SUCCESS,
+        }
+    }
+
+    fn receive_word(&self) -> ReturnCode {
+        ReturnCode::FAIL
+    }
+}
@@ -1,6 +1,7 @@
 //! ADC driver for the nRF52. Uses the SAADC peripheral.
 
-use kernel::common::cells::{OptionalCell, VolatileCell};
+use core::cell::Cell;
+use kernel::common::cells::{OptionalCell, TakeCell, VolatileCell};
 use kernel::common::registers::{register_bitfields, ReadOnly, ReadWrite, WriteOnly};
 use kernel::common::StaticRef;
 use kernel::hil;
@@ -244,17 +245,55 @@ pub static mut ADC: Adc = Adc::new(SAADC_BASE);
 // Buffer to save completed sample to.
 static mut SAMPLE: [u16; 1] = [0; 1];
 
+/// The ADC samples at 16 MHz divided by SAMPLERATE.CC, which must be between
+/// 80 and 2047.
+const SAMPLERATE_CLOCK_HZ: u32 = 16_000_000;
+const SAMPLERATE_CC_MIN: u32 = 80;
+const SAMPLERATE_CC_MAX: u32 = 2047;
+
+/// The largest number of samples the ADC can store in a buffer
+const MAX_SAMPLES: usize = 0x7FFF;
+
+#[derive(Copy, Clone, PartialEq)]
+enum Mode {
+    Idle,
+    /// Taking a single sample
+    Single,
+    /// Sampling continuously into buffers
+    HighSpeed,
+    /// Waiting for the ADC to stop after `stop_sampling`
+    Stopping,
+}
+
 pub struct Adc {
     registers: StaticRef<AdcRegisters>,
     client: OptionalCell<&'static hil::adc::Client>,
+    highspeed_client: OptionalCell<&'static hil::adc::HighSpeedClient>,
+    mode: Cell<Mode>,
+    /// The buffer the ADC is sampling into
+    active_buffer: TakeCell<'static, [u16]>,
+    /// The buffer the ADC samples into once the active one is full
+    next_buffer: TakeCell<'static, [u16]>,
+    next_length: Cell<usize>,
+    /// Whether the ADC has taken the address of the active buffer, so that
+    /// the address of the next one can be given to it
+    started: Cell<bool>,
+    /// Whether the next buffer has been given to the ADC
+    next_loaded: Cell<bool>,
 }
 
 impl Adc {
     const fn new(registers: StaticRef<AdcRegisters>) -> Adc {
         Adc {
             registers: registers,
-            // state: Cell::new(State::Idle),
             client: OptionalCell::empty(),
+            highspeed_client: OptionalCell::empty(),
+            mode: Cell::new(Mode::Idle),
+            active_buffer: TakeCell::empty(),
+            next_buffer: TakeCell::empty(),
+            next_length: Cell::new(0),
+            started: Cell::new(false),
+            next_loaded: Cell::new(false),
         }
     }
 
@@ -262,29 +301,132 @@ impl Adc {
         self.client.set(client);
     }
 
+    pub fn set_highspeed_client(&self, client: &'static hil::adc::HighSpeedClient) {
+        self.highspeed_client.set(client);
+    }
+
+    /// Connects the channel to the ADC and sets up its gain and reference.
+    fn configure_channel(&self, channel: &AdcChannel) {
+        let regs = &*self.registers;
+
+        // Positive goes to the channel passed in, negative not connected.
+        regs.ch[0].pselp.write(PSEL::PSEL.val(*channel as u32));
+        regs.ch[0].pseln.write(PSEL::PSEL::NotConnected);
+
+        regs.ch[0]
+            .config
+            .write(CONFIG::GAIN::Gain1_4 + CONFIG::REFSEL::VDD1_4 + CONFIG::TACQ::us10);
+
+        // Set max resolution.
+        regs.resolution.write(RESOLUTION::VAL::bit14);
+    }
+
+    /// Enables the ADC and its interrupts, and starts it.
+    fn start(&self) {
+        let regs = &*self.registers;
+        self.started.set(false);
+        regs.enable.write(ENABLE::ENABLE::SET);
+        regs.inten
+            .write(INTEN::STARTED::SET + INTEN::END::SET + INTEN::STOPPED::SET);
+        regs.tasks_start.write(TASK::TASK::SET);
+    }
+
+    /// Gives the ADC the buffer to sample into after the current one.
+    fn load_next_buffer(&self) {
+        let regs = &*self.registers;
+        self.next_buffer.map(|buf| {
+            regs.result_ptr.set(buf.as_ptr());
+            regs.result_maxcnt
+                .write(RESULT_MAXCNT::MAXCNT.val(self.next_length.get() as u32));
+            self.next_loaded.set(true);
+        });
+    }
+
     pub fn handle_interrupt(&self) {
         let regs = &*self.registers;
 
-        // Determine what event occurred.
         if regs.events_started.is_set(EVENT::EVENT) {
             regs.events_started.write(EVENT::EVENT::CLEAR);
-            // ADC has started, now issue the sample.
-            regs.tasks_sample.write(TASK::TASK::SET);
-        } else if regs.events_end.is_set(EVENT::EVENT) {
+            self.started.set(true);
+            match self.mode.get() {
+                Mode::Single => {
+                    // ADC has started, now issue the sample.
+                    regs.tasks_sample.write(TASK::TASK::SET);
+                }
+                Mode::HighSpeed => {
+                    // The address of the active buffer has been taken, so
+                    // the next one can be given already
+                    if !self.next_loaded.get() {
+                        self.load_next_buffer();
+                    }
+                    // Sampling continues across buffers once the local
+                    // timer has been started by a first sample task
+                    regs.tasks_sample.write(TASK::TASK::SET);
+                }
+                _ => {}
+            }
+        }
+
+        if regs.events_end.is_set(EVENT::EVENT) {
             regs.events_end.write(EVENT::EVENT::CLEAR);
-            // Reading finished. Turn off the ADC.
-            regs.tasks_stop.write(TASK::TASK::SET);
-        } else if regs.events_stopped.is_set(EVENT::EVENT) {
+            match self.mode.get() {
+                Mode::Single => {
+                    // Reading finished. Turn off the ADC.
+                    regs.tasks_stop.write(TASK::TASK::SET);
+                }
+                Mode::HighSpeed => self.buffer_done(),
+                _ => {}
+            }
+        }
+
+        if regs.events_stopped.is_set(EVENT::EVENT) {
             regs.events_stopped.write(EVENT::EVENT::CLEAR);
             // ADC is stopped. Disable and return value.
             regs.enable.write(ENABLE::ENABLE::CLEAR);
 
+            match self.mode.get() {
+                Mode::Single => {
+                    self.mode.set(Mode::Idle);
+                    // Left justify to meet HIL requirements.
+                    let val = unsafe { SAMPLE[0] } << 2;
+                    self.client.map(|client| {
+                        client.sample_ready(val);
+                    });
+                }
+                Mode::Stopping => self.mode.set(Mode::Idle),
+                _ => {}
+            }
+        }
+    }
+
+    /// Switches the ADC to the next buffer, if there is one, and gives the
+    /// full one to the client.
+    fn buffer_done(&self) {
+        let regs = &*self.registers;
+        let length = regs.result_amount.read(RESULT_AMOUNT::AMOUNT) as usize;
+
+        let full = self.active_buffer.take();
+        if self.next_loaded.get() {
+            self.next_loaded.set(false);
+            self.next_buffer
+                .take()
+                .map(|buf| self.active_buffer.replace(buf));
+            self.started.set(false);
+            regs.tasks_start.write(TASK::TASK::SET);
+        }
+        // Otherwise there is nowhere to put samples, so they are dropped
+        // until the client provides a buffer
+
+        full.map(|buf| {
+            // Samples are signed, and can be slightly negative near ground.
             // Left justify to meet HIL requirements.
-            let val = unsafe { SAMPLE[0] } << 2;
-            self.client.map(|client| {
-                client.sample_ready(val);
+            for sample in buf[..length].iter_mut() {
+                *sample = ((*sample as i16).max(0) as u16) << 2;
+            }
+            self.highspeed_client.map(move |client| {
+                client.samples_ready(buf, length);
             });
-        }
+        });
     }
 }
 
@@ -294,18 +436,13 @@ impl hil::adc::Adc for Adc {
 
     fn sample(&self, channel: &Self::Channel) -> ReturnCode {
         let regs = &*self.registers;
-
-        // Positive goes to the channel passed in, negative not connected.
-        regs.ch[0].pselp.write(PSEL::PSEL.val(*channel as u32));
-        regs.ch[0].pseln.write(PSEL::PSEL::NotConnected);
+        if self.mode.get() != Mode::Idle {
+            return ReturnCode::EBUSY;
+        }
+        self.mode.set(Mode::Single);
 
         // Configure the ADC for a single read.
-        regs.ch[0]
-            .config
-            .write(CONFIG::GAIN::Gain1_4 + CONFIG::REFSEL::VDD1_4 + CONFIG::TACQ::us10);
-
-        // Set max resolution.
-        regs.resolution.write(RESOLUTION::VAL::bit14);
+        self.configure_channel(channel);
 
         // Do one measurement.
         regs.result_maxcnt.write(RESULT_MAXCNT::MAXCNT.val(1));
@@ -317,15 +454,8 @@ impl hil::adc::Adc for Adc {
         // No automatic sampling, will trigger manually.
         regs.samplerate.write(SAMPLERATE::MODE::Task);
 
-        // Enable the ADC
-        regs.enable.write(ENABLE::ENABLE::SET);
-
-        // Enable started, sample end, and stopped interrupts.
-        regs.inten
-            .write(INTEN::STARTED::SET + INTEN::END::SET + INTEN::STOPPED::SET);
-
         // Start the SAADC and wait for the started interrupt.
-        regs.tasks_start.write(TASK::TASK::SET);
+        self.start();
 
         ReturnCode::SUCCESS
     }
@@ -335,7 +465,17 @@ impl hil::adc::Adc for Adc {
     }
 
     fn stop_sampling(&self) -> ReturnCode {
-        ReturnCode::FAIL
+        match self.mode.get() {
+            Mode::HighSpeed => {
+                let regs = &*self.registers;
+                self.mode.set(Mode::Stopping);
+                self.next_loaded.set(false);
+                regs.tasks_stop.write(TASK::TASK::SET);
+                ReturnCode::SUCCESS
+            }
+            Mode::Idle | Mode::Stopping => ReturnCode::SUCCESS,
+            Mode::Single => ReturnCode::FAIL,
+        }
     }
 
     fn get_resolution_bits(&self) -> usize {
@@ -346,3 +486,108 @@ impl hil::adc::Adc for Adc {
         Some(3300)
     }
 }
+
+/// Samples continuously into buffers, with the sample rate set by the local
+/// timer of the ADC, so from 7.8 kHz up to 200 kHz.
+impl hil::adc::AdcHighSpeed for Adc {
+    fn sample_highspeed(
+        &self,
+        channel: &Self::Channel,
+        frequency: u32,
+        buffer1: &'static mut [u16],
+        length1: usize,
+        buffer2: &'static mut [u16],
+        length2: usize,
+    ) -> (
+        ReturnCode,
+        Option<&'static mut [u16]>,
+        Option<&'static mut [u16]>,
+    ) {
+        if self.mode.get() != Mode::Idle {
+            return (ReturnCode::EBUSY, Some(buffer1), Some(buffer2));
+        }
+        let cc = if frequency == 0 {
+            0
+        } else {
+            SAMPLERATE_CLOCK_HZ / frequency
+        };
+        if cc < SAMPLERATE_CC_MIN
+            || cc > SAMPLERATE_CC_MAX
+            || length1 == 0
+            || length1 > buffer1.len().min(MAX_SAMPLES)
+            || length2 == 0
+            || length2 > buffer2.len().min(MAX_SAMPLES)
+        {
+            return (ReturnCode::EINVAL, Some(buffer1), Some(buffer2));
+        }
+
+        let regs = &*self.registers;
+        self.mode.set(Mode::HighSpeed);
+        self.configure_channel(channel);
+        regs.samplerate
+            .write(SAMPLERATE::MODE::Timers + SAMPLERATE::CC.val(cc));
+
+        regs.result_ptr.set(buffer1.as_ptr());
+        regs.result_maxcnt
+            .write(RESULT_MAXCNT::MAXCNT.val(length1 as u32));
+        self.active_buffer.replace(buffer1);
+        self.next_buffer.replace(buffer2);
+        self.next_length.set(length2);
+        self.next_loaded.set(false);
+
+        self.start();
+        (ReturnCode::SUCCESS, None, None)
+    }
+
+    fn provide_buffer(
+        &self,
+        buf: &'static mut [u16],
+        length: usize,
+    ) -> (ReturnCode, Option<&'static mut [u16]>) {
+        if self.mode.get() != Mode::HighSpeed {
+            return (ReturnCode::EOFF, Some(buf));
+        }
+        if self.next_buffer.is_some() {
+            return (ReturnCode::EBUSY, Some(buf));
+        }
+        if length == 0 || length > buf.len().min(MAX_SAMPLES) {
+            return (ReturnCode::EINVAL, Some(buf));
+        }
+
+        self.next_buffer.replace(buf);
+        self.next_length.set(length);
+        if self.active_buffer.is_none() {
+            // Samples are being dropped for lack of a buffer, so start
+            // sampling into this one
+            let regs = &*self.registers;
+            self.next_buffer.take().map(|buf| {
+                regs.result_ptr.set(buf.as_ptr());
+                regs.result_maxcnt
+                    .write(RESULT_MAXCNT::MAXCNT.val(length as u32));
+                self.active_buffer.replace(buf);
+            });
+            self.started.set(false);
+            regs.tasks_start.write(TASK::TASK::SET);
+        } else if self.started.get() {
+            self.load_next_buffer();
+        }
+        (ReturnCode::SUCCESS, None)
+    }
+
+    fn retrieve_buffers(
+        &self,
+    ) -> (
+        ReturnCode,
+        Option<&'static mut [u16]>,
+        Option<&'static mut [u16]>,
+    ) {
+        if self.mode.get() != Mode::Idle {
+            return (ReturnCode::EBUSY, None, None);
+        }
+        (
+            ReturnCode::SUCCESS,
+            self.active_buffer.take(),
+            self.next_buffer.take(),
+        )
+    }
+}
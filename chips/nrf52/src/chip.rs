@@ -3,12 +3,15 @@ use crate::ble_radio;
 use crate::deferred_call_tasks::DeferredCallTask;
 use crate::i2c;
 use crate::ieee802154_radio;
+use crate::nfct;
 use crate::nvmc;
 use crate::spi;
 use crate::uart;
+use crate::usbd;
 use cortexm4::{self, nvic};
 use kernel::common::deferred_call;
 use kernel::debug;
+use kernel::interrupt_latency;
 use nrf5x::peripheral_interrupts;
 
 pub struct NRF52 {
@@ -54,6 +57,7 @@ impl kernel::Chip for NRF52 {
                         DeferredCallTask::Nvmc => nvmc::NVMC.handle_interrupt(),
                     }
                 } else if let Some(interrupt) = nvic::next_pending() {
+                    interrupt_latency::serviced(interrupt);
                     match interrupt {
                         peripheral_interrupts::ECB => nrf5x::aes::AESECB.handle_interrupt(),
                         peripheral_interrupts::GPIOTE => nrf5x::gpio::PORT.handle_interrupt(),
@@ -70,6 +74,7 @@ impl kernel::Chip for NRF52 {
                                 ),
                             }
                         }
+                        peripheral_interrupts::NFCT => nfct::NFCT.handle_interrupt(),
                         peripheral_interrupts::RNG => nrf5x::trng::TRNG.handle_interrupt(),
                         peripheral_interrupts::RTC1 => nrf5x::rtc::RTC.handle_interrupt(),
                         peripheral_interrupts::TEMP => nrf5x::temperature::TEMP.handle_interrupt(),
@@ -107,6 +112,7 @@ impl kernel::Chip for NRF52 {
                         }
                         peripheral_interrupts::SPIM2_SPIS2_SPI2 => spi::SPIM2.handle_interrupt(),
                         peripheral_interrupts::ADC => adc::ADC.handle_interrupt(),
+                        peripheral_interrupts::USBD => usbd::USBD.handle_interrupt(),
                         _ => debug!("NvicIdx not supported by Tock"),
                     }
                     let n = nvic::Nvic::new(interrupt);
@@ -1165,4 +1165,10 @@ impl kernel::hil::radio::RadioData for Radio {
         //self.enable_interrupts();
         (ReturnCode::SUCCESS, None)
     }
+
+    fn get_tx_info(&self) -> radio::TxInfo {
+        // Acknowledgements are not handled yet, so there are no retries or
+        // acknowledgement RSSI to report
+        radio::TxInfo::default()
+    }
 }
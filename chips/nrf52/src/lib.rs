@@ -12,11 +12,13 @@ mod deferred_call_tasks;
 pub mod ficr;
 pub mod i2c;
 pub mod ieee802154_radio;
+pub mod nfct;
 pub mod nvmc;
 pub mod ppi;
 pub mod pwm;
 pub mod spi;
 pub mod uart;
 pub mod uicr;
+pub mod usbd;
 
 pub use crate::crt1::init;
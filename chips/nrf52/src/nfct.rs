@@ -0,0 +1,333 @@
+//! NFC tag controller of the nRF52 (NFCT)
+//!
+//! The controller senses the field of a reader, and answers its
+//! anticollision and selection in hardware, with the default NFCID1 of the
+//! controller. Once selected, it moves frames between RAM and the reader
+//! with EasyDMA, checking and adding their CRC and parity bits, so the
+//! client only handles the frames of the protocol on top, such as ISO-DEP.
+//! The tag advertises ISO/IEC 14443-4 support, which Type 4 tags need.
+//!
+//! The reader puts the tag to sleep with a HLTA frame, which this driver
+//! handles itself.
+//!
+//! The NFC1 and NFC2 pins must be left configured for NFC in the UICR, as
+//! they are on an erased chip, and the controller needs the high frequency
+//! crystal oscillator to be running.
+
+use core::cell::Cell;
+use core::cmp;
+use kernel::common::cells::{OptionalCell, TakeCell};
+use kernel::common::registers::{register_bitfields, ReadOnly, ReadWrite, WriteOnly};
+use kernel::common::StaticRef;
+use kernel::hil::nfc;
+use kernel::ReturnCode;
+
+#[repr(C)]
+struct NfctRegisters {
+    _tasks_activate: WriteOnly<u32>,
+    tasks_disable: WriteOnly<u32>,
+    tasks_sense: WriteOnly<u32>,
+    tasks_starttx: WriteOnly<u32>,
+    _reserved0: [u32; 3],
+    tasks_enablerxdata: WriteOnly<u32>,
+    _reserved1: [u32; 1],
+    _tasks_goidle: WriteOnly<u32>,
+    tasks_gosleep: WriteOnly<u32>,
+    _reserved2: [u32; 53],
+    _events_ready: ReadWrite<u32>,
+    events_fielddetected: ReadWrite<u32>,
+    events_fieldlost: ReadWrite<u32>,
+    _events_txframestart: ReadWrite<u32>,
+    events_txframeend: ReadWrite<u32>,
+    _events_rxframestart: ReadWrite<u32>,
+    events_rxframeend: ReadWrite<u32>,
+    events_error: ReadWrite<u32>,
+    _reserved3: [u32; 2],
+    _events_rxerror: ReadWrite<u32>,
+    _events_endrx: ReadWrite<u32>,
+    _events_endtx: ReadWrite<u32>,
+    _reserved4: [u32; 1],
+    _events_autocolresstarted: ReadWrite<u32>,
+    _reserved5: [u32; 3],
+    _events_collision: ReadWrite<u32>,
+    events_selected: ReadWrite<u32>,
+    _events_started: ReadWrite<u32>,
+    _reserved6: [u32; 43],
+    shorts: ReadWrite<u32, Shorts::Register>,
+    _reserved7: [u32; 63],
+    _inten: ReadWrite<u32, Interrupt::Register>,
+    intenset: ReadWrite<u32, Interrupt::Register>,
+    intenclr: ReadWrite<u32, Interrupt::Register>,
+    _reserved8: [u32; 62],
+    errorstatus: ReadWrite<u32>,
+    _reserved9: [u32; 1],
+    framestatus_rx: ReadWrite<u32, FrameStatusRx::Register>,
+    _reserved10: [u32; 11],
+    _fieldpresent: ReadOnly<u32>,
+    _reserved11: [u32; 49],
+    _framedelaymin: ReadWrite<u32>,
+    _framedelaymax: ReadWrite<u32>,
+    framedelaymode: ReadWrite<u32, FrameDelayMode::Register>,
+    packetptr: ReadWrite<u32>,
+    maxlen: ReadWrite<u32>,
+    txd_frameconfig: ReadWrite<u32, FrameConfig::Register>,
+    txd_amount: ReadWrite<u32, Amount::Register>,
+    rxd_frameconfig: ReadWrite<u32, FrameConfig::Register>,
+    rxd_amount: ReadOnly<u32, Amount::Register>,
+    _reserved12: [u32; 26],
+    _nfcid1_last: ReadWrite<u32>,
+    _nfcid1_2nd_last: ReadWrite<u32>,
+    _nfcid1_3rd_last: ReadWrite<u32>,
+    _reserved13: [u32; 1],
+    _sensres: ReadWrite<u32>,
+    selres: ReadWrite<u32, SelRes::Register>,
+}
+
+register_bitfields![u32,
+    Shorts [
+        FIELDDETECTED_ACTIVATE 0,
+        FIELDLOST_SENSE 1
+    ],
+    Interrupt [
+        FIELDDETECTED 1,
+        FIELDLOST 2,
+        TXFRAMEEND 4,
+        RXFRAMEEND 6,
+        ERROR 7,
+        SELECTED 19
+    ],
+    FrameStatusRx [
+        CRCERROR 0,
+        PARITYSTATUS 2,
+        OVERRUN 3
+    ],
+    FrameDelayMode [
+        MODE OFFSET(0) NUMBITS(2) [
+            FreeRun = 0,
+            Window = 1,
+            ExactVal = 2,
+            WindowGrid = 3
+        ]
+    ],
+    FrameConfig [
+        PARITY 0,
+        // Only for transmission: the unused bits of the first byte are
+        // discarded, rather than the last
+        DISCARDMODE 1,
+        SOF 2,
+        CRCMODE 4
+    ],
+    Amount [
+        DATABITS OFFSET(0) NUMBITS(3) [],
+        DATABYTES OFFSET(3) NUMBITS(9) []
+    ],
+    SelRes [
+        // 1 for ISO/IEC 14443-4
+        PROTOCOL OFFSET(5) NUMBITS(2) []
+    ]
+];
+
+const NFCT_BASE: StaticRef<NfctRegisters> =
+    unsafe { StaticRef::new(0x40005000 as *const NfctRegisters) };
+
+/// The longest frame EasyDMA moves, without the CRC
+const MAX_FRAME_SIZE: usize = 257;
+
+/// The HLTA command, without its CRC
+const HLTA: [u8; 2] = [0x50, 0x00];
+
+pub static mut NFCT: Nfct = Nfct::new();
+
+pub struct Nfct {
+    registers: StaticRef<NfctRegisters>,
+    client: OptionalCell<&'static nfc::Client>,
+    rx_buffer: TakeCell<'static, [u8]>,
+    tx_buffer: TakeCell<'static, [u8]>,
+    selected: Cell<bool>,
+}
+
+impl Nfct {
+    const fn new() -> Nfct {
+        Nfct {
+            registers: NFCT_BASE,
+            client: OptionalCell::empty(),
+            rx_buffer: TakeCell::empty(),
+            tx_buffer: TakeCell::empty(),
+            selected: Cell::new(false),
+        }
+    }
+
+    /// Points EasyDMA at the reception buffer and starts receiving, unless
+    /// the tag is not selected or is transmitting, which shares the pointer.
+    fn start_rx(&self) {
+        if !self.selected.get() || self.tx_buffer.is_some() {
+            return;
+        }
+        let regs = &*self.registers;
+        self.rx_buffer.map(|buffer| {
+            regs.packetptr.set(buffer.as_ptr() as u32);
+            regs.maxlen
+                .set(cmp::min(buffer.len(), MAX_FRAME_SIZE) as u32);
+            regs.rxd_frameconfig.write(
+                FrameConfig::PARITY::SET + FrameConfig::SOF::SET + FrameConfig::CRCMODE::SET,
+            );
+            regs.tasks_enablerxdata.set(1);
+        });
+    }
+
+    fn frame_received(&self) {
+        let regs = &*self.registers;
+        let status = regs.framestatus_rx.extract();
+        regs.framestatus_rx.set(status.get());
+        let len = regs.rxd_amount.read(Amount::DATABYTES) as usize;
+
+        let result = if status.is_set(FrameStatusRx::OVERRUN) {
+            ReturnCode::ESIZE
+        } else if status.is_set(FrameStatusRx::CRCERROR)
+            || status.is_set(FrameStatusRx::PARITYSTATUS)
+        {
+            ReturnCode::FAIL
+        } else {
+            ReturnCode::SUCCESS
+        };
+
+        let halted = result == ReturnCode::SUCCESS
+            && self.rx_buffer.map_or(false, |buffer| buffer[..len] == HLTA);
+        if halted {
+            // The reader wakes the tag up and selects it again
+            self.selected.set(false);
+            regs.tasks_gosleep.set(1);
+            return;
+        }
+
+        self.rx_buffer.take().map(|buffer| {
+            self.client
+                .map(move |client| client.frame_received(buffer, len, result));
+        });
+    }
+
+    pub fn handle_interrupt(&self) {
+        let regs = &*self.registers;
+
+        if regs.events_fielddetected.get() != 0 {
+            regs.events_fielddetected.set(0);
+            self.client.map(|client| client.field_detected());
+        }
+
+        if regs.events_selected.get() != 0 {
+            regs.events_selected.set(0);
+            self.selected.set(true);
+            self.start_rx();
+            self.client.map(|client| client.selected());
+        }
+
+        if regs.events_rxframeend.get() != 0 {
+            regs.events_rxframeend.set(0);
+            self.frame_received();
+        }
+
+        if regs.events_txframeend.get() != 0 {
+            regs.events_txframeend.set(0);
+            if let Some(buffer) = self.tx_buffer.take() {
+                self.start_rx();
+                self.client
+                    .map(move |client| client.frame_transmitted(buffer, ReturnCode::SUCCESS));
+            }
+        }
+
+        if regs.events_error.get() != 0 {
+            // The frame delay timed out; the reader will send again
+            regs.events_error.set(0);
+            regs.errorstatus.set(regs.errorstatus.get());
+        }
+
+        if regs.events_fieldlost.get() != 0 {
+            regs.events_fieldlost.set(0);
+            self.selected.set(false);
+            if let Some(buffer) = self.tx_buffer.take() {
+                self.client
+                    .map(move |client| client.frame_transmitted(buffer, ReturnCode::FAIL));
+            }
+            self.client.map(|client| client.field_lost());
+        }
+    }
+}
+
+impl nfc::NfcTag for Nfct {
+    fn set_client(&self, client: &'static nfc::Client) {
+        self.client.set(client);
+    }
+
+    fn enable(&self) -> ReturnCode {
+        let regs = &*self.registers;
+        regs.selres.modify(SelRes::PROTOCOL.val(1));
+        regs.framedelaymode.write(FrameDelayMode::MODE::WindowGrid);
+        // The controller activates itself in a field, and goes back to
+        // sensing when the field is lost
+        regs.shorts
+            .write(Shorts::FIELDDETECTED_ACTIVATE::SET + Shorts::FIELDLOST_SENSE::SET);
+        regs.intenset.write(
+            Interrupt::FIELDDETECTED::SET
+                + Interrupt::FIELDLOST::SET
+                + Interrupt::SELECTED::SET
+                + Interrupt::RXFRAMEEND::SET
+                + Interrupt::TXFRAMEEND::SET
+                + Interrupt::ERROR::SET,
+        );
+        regs.tasks_sense.set(1);
+        ReturnCode::SUCCESS
+    }
+
+    fn disable(&self) -> ReturnCode {
+        let regs = &*self.registers;
+        regs.intenclr.set(0xFFFF_FFFF);
+        regs.shorts.set(0);
+        regs.tasks_disable.set(1);
+        self.selected.set(false);
+        ReturnCode::SUCCESS
+    }
+
+    fn receive(&self, buffer: &'static mut [u8]) -> (ReturnCode, Option<&'static mut [u8]>) {
+        if self.rx_buffer.is_some() {
+            return (ReturnCode::EBUSY, Some(buffer));
+        }
+        self.rx_buffer.replace(buffer);
+        self.start_rx();
+        (ReturnCode::SUCCESS, None)
+    }
+
+    fn transmit(
+        &self,
+        buffer: &'static mut [u8],
+        len: usize,
+    ) -> (ReturnCode, Option<&'static mut [u8]>) {
+        if !self.selected.get() {
+            return (ReturnCode::EOFF, Some(buffer));
+        }
+        if self.tx_buffer.is_some() {
+            return (ReturnCode::EBUSY, Some(buffer));
+        }
+        if len > buffer.len() || len > MAX_FRAME_SIZE {
+            return (ReturnCode::ESIZE, Some(buffer));
+        }
+
+        let regs = &*self.registers;
+        regs.packetptr.set(buffer.as_ptr() as u32);
+        regs.txd_amount.write(Amount::DATABYTES.val(len as u32));
+        regs.txd_frameconfig.write(
+            FrameConfig::PARITY::SET
+                + FrameConfig::DISCARDMODE::SET
+                + FrameConfig::SOF::SET
+                + FrameConfig::CRCMODE::SET,
+        );
+        self.tx_buffer.replace(buffer);
+        regs.tasks_starttx.set(1);
+        (ReturnCode::SUCCESS, None)
+    }
+
+    fn sleep(&self) -> ReturnCode {
+        self.selected.set(false);
+        self.registers.tasks_gosleep.set(1);
+        ReturnCode::SUCCESS
+    }
+}
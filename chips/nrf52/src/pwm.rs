@@ -262,6 +262,12 @@ impl hil::pwm::Pwm for Pwm {
         5333333
     }
 
+    fn get_minimum_frequency_hz(&self) -> usize {
+        // The COUNTERTOP register is 15 bits wide, and the prescaler is not
+        // used. 16000000 / 32767 = 488.3, rounded up
+        489
+    }
+
     fn get_maximum_duty_cycle(&self) -> usize {
         // We use the max frequency as the max duty cycle as well. This makes
         // calculating `dc_out` straightforward.
@@ -20,8 +20,8 @@
 //! * ✓ get_clock
 //! * ✓ set_phase
 //! * ✓ get_phase
-//! * hold_low
-//! * release_low
+//! * ✓ hold_low
+//! * ✓ release_low
 //!
 //! Author
 //! -------------------
@@ -232,6 +232,8 @@ pub struct SPIM {
     registers: StaticRef<SpimRegisters>,
     client: OptionalCell<&'static hil::spi::SpiMasterClient>,
     chip_select: OptionalCell<&'static hil::gpio::Pin>,
+    /// Keep the chip select low after transfers
+    hold_low: Cell<bool>,
     initialized: Cell<bool>,
     busy: Cell<bool>,
     tx_buf: TakeCell<'static, [u8]>,
@@ -245,6 +247,7 @@ impl SPIM {
             registers: INSTANCES[instance],
             client: OptionalCell::empty(),
             chip_select: OptionalCell::empty(),
+            hold_low: Cell::new(false),
             initialized: Cell::new(false),
             busy: Cell::new(false),
             tx_buf: TakeCell::empty(),
@@ -263,17 +266,19 @@ impl SPIM {
                 return;
             }
 
-            self.chip_select.map(|cs| cs.set());
+            if !self.hold_low.get() {
+                self.chip_select.map(|cs| cs.set());
+            }
             self.registers.events_end.write(EVENT::EVENT::CLEAR);
 
+            // The client may start the next transfer from its callback
+            self.busy.set(false);
             self.client.map(|client| match self.tx_buf.take() {
                 None => (),
                 Some(tx_buf) => {
                     client.read_write_done(tx_buf, self.rx_buf.take(), self.transfer_len.take())
                 }
             });
-
-            self.busy.set(false);
         }
 
         // Although we only configured the chip interrupt on the
@@ -464,10 +469,10 @@ impl hil::spi::SpiMaster for SPIM {
     // SAM4L, and appear to not provide much functionality. Let's not
     // bother implementing them unless needed.
     fn hold_low(&self) {
-        unimplemented!("SPI: Use `read_write_bytes()` instead.");
+        self.hold_low.set(true);
     }
 
     fn release_low(&self) {
-        unimplemented!("SPI: Use `read_write_bytes()` instead.");
+        self.hold_low.set(false);
     }
 }
@@ -125,8 +125,8 @@ register_bitfields! [u32,
 
     /// Pin select
     Psel [
-        // Pin number
-        PIN OFFSET(0) NUMBITS(5),
+        // Pin number, with the port in the top bit on the nRF52840
+        PIN OFFSET(0) NUMBITS(6),
         // Connect/Disconnect
         CONNECT OFFSET(31) NUMBITS(1)
     ],
@@ -195,19 +195,23 @@ impl<'a> Uarte<'a> {
         }
     }
 
-    /// Configure which pins the UART should use for txd, rxd, cts and rts
+    /// Configure which pins the UART should use for txd, rxd, cts and rts.
+    /// Without cts and rts, hardware flow control cannot be used.
     pub fn initialize(
         &self,
         txd: pinmux::Pinmux,
         rxd: pinmux::Pinmux,
-        cts: pinmux::Pinmux,
-        rts: pinmux::Pinmux,
+        cts: Option<pinmux::Pinmux>,
+        rts: Option<pinmux::Pinmux>,
     ) {
         let regs = &*self.registers;
         regs.pseltxd.write(Psel::PIN.val(txd.into()));
         regs.pselrxd.write(Psel::PIN.val(rxd.into()));
-        regs.pselcts.write(Psel::PIN.val(cts.into()));
-        regs.pselrts.write(Psel::PIN.val(rts.into()));
+        // Boards without flow control leave these pins disconnected
+        regs.pselcts
+            .write(cts.map_or(Psel::CONNECT::SET, |pin| Psel::PIN.val(pin.into())));
+        regs.pselrts
+            .write(rts.map_or(Psel::CONNECT::SET, |pin| Psel::PIN.val(pin.into())));
 
         self.enable_uart();
     }
@@ -0,0 +1,622 @@
+//! USB device controller of the nRF52840 (USBD)
+//!
+//! The controller handles most of the USB protocol itself, including the
+//! `SET_ADDRESS` request, and moves packets between its own buffers and the
+//! endpoint buffers in RAM with EasyDMA, which is started by software. Only
+//! one EasyDMA transfer can run at a time, and packets are at most 64 bytes,
+//! so this driver runs each transfer to completion before going on.
+//!
+//! Bulk IN endpoints are filled as soon as the host has taken their previous
+//! packet, or when the client resumes them, rather than when the host asks
+//! for data as on other controllers, but clients see the same sequence of
+//! `bulk_in` calls.
+//!
+//! The controller is powered from VBUS, so this driver is for boards that
+//! are powered through USB, such as the nRF52840 Dongle. It needs the high
+//! frequency crystal oscillator to be running.
+
+use core::cell::Cell;
+use core::ptr;
+use kernel::common::cells::{OptionalCell, VolatileCell};
+use kernel::common::registers::{register_bitfields, FieldValue, ReadOnly, ReadWrite, WriteOnly};
+use kernel::common::StaticRef;
+use kernel::hil;
+use kernel::hil::usb::{BulkInResult, BulkOutResult, CtrlInResult, CtrlOutResult, CtrlSetupResult};
+
+const NUM_ENDPOINTS: usize = 8;
+
+#[repr(C)]
+struct UsbdRegisters {
+    _reserved0: [u32; 1],
+    tasks_startepin: [WriteOnly<u32>; NUM_ENDPOINTS],
+    _tasks_startisoin: WriteOnly<u32>,
+    tasks_startepout: [WriteOnly<u32>; NUM_ENDPOINTS],
+    _tasks_startisoout: WriteOnly<u32>,
+    tasks_ep0rcvout: WriteOnly<u32>,
+    tasks_ep0status: WriteOnly<u32>,
+    tasks_ep0stall: WriteOnly<u32>,
+    _tasks_dpdmdrive: WriteOnly<u32>,
+    _tasks_dpdmnodrive: WriteOnly<u32>,
+    _reserved1: [u32; 40],
+    events_usbreset: ReadWrite<u32>,
+    _events_started: ReadWrite<u32>,
+    events_endepin: [ReadWrite<u32>; NUM_ENDPOINTS],
+    events_ep0datadone: ReadWrite<u32>,
+    _events_endisoin: ReadWrite<u32>,
+    events_endepout: [ReadWrite<u32>; NUM_ENDPOINTS],
+    _events_endisoout: ReadWrite<u32>,
+    _events_sof: ReadWrite<u32>,
+    events_usbevent: ReadWrite<u32>,
+    events_ep0setup: ReadWrite<u32>,
+    events_epdata: ReadWrite<u32>,
+    _reserved2: [u32; 39],
+    _shorts: ReadWrite<u32>,
+    _reserved3: [u32; 63],
+    inten: ReadWrite<u32, Interrupt::Register>,
+    _intenset: ReadWrite<u32, Interrupt::Register>,
+    _intenclr: ReadWrite<u32, Interrupt::Register>,
+    _reserved4: [u32; 61],
+    eventcause: ReadWrite<u32, EventCause::Register>,
+    _reserved5: [u32; 7],
+    _halted_epin: [ReadOnly<u32>; NUM_ENDPOINTS],
+    _reserved6: [u32; 1],
+    _halted_epout: [ReadOnly<u32>; NUM_ENDPOINTS],
+    _reserved7: [u32; 1],
+    _epstatus: ReadWrite<u32>,
+    epdatastatus: ReadWrite<u32>,
+    _usbaddr: ReadOnly<u32>,
+    _reserved8: [u32; 3],
+    bmrequesttype: ReadOnly<u32>,
+    brequest: ReadOnly<u32>,
+    wvaluel: ReadOnly<u32>,
+    wvalueh: ReadOnly<u32>,
+    windexl: ReadOnly<u32>,
+    windexh: ReadOnly<u32>,
+    wlengthl: ReadOnly<u32>,
+    wlengthh: ReadOnly<u32>,
+    size_epout: [ReadWrite<u32>; NUM_ENDPOINTS],
+    _size_isoout: ReadOnly<u32>,
+    _reserved9: [u32; 15],
+    enable: ReadWrite<u32, Enable::Register>,
+    usbpullup: ReadWrite<u32, UsbPullup::Register>,
+    _dpdmvalue: ReadWrite<u32>,
+    _dtoggle: ReadWrite<u32>,
+    epinen: ReadWrite<u32>,
+    epouten: ReadWrite<u32>,
+    epstall: WriteOnly<u32, EpStall::Register>,
+    _isosplit: ReadWrite<u32>,
+    _framecntr: ReadOnly<u32>,
+    _reserved10: [u32; 2],
+    _lowpower: ReadWrite<u32>,
+    _isoinconfig: ReadWrite<u32>,
+    _reserved11: [u32; 51],
+    epin: [EndpointDmaRegisters; NUM_ENDPOINTS],
+    _reserved12: [u32; 40],
+    epout: [EndpointDmaRegisters; NUM_ENDPOINTS],
+}
+
+#[repr(C)]
+struct EndpointDmaRegisters {
+    ptr: ReadWrite<u32>,
+    maxcnt: ReadWrite<u32>,
+    amount: ReadOnly<u32>,
+    _reserved: [u32; 2],
+}
+
+register_bitfields![u32,
+    Interrupt [
+        USBRESET 0,
+        EP0DATADONE 10,
+        USBEVENT 22,
+        EP0SETUP 23,
+        EPDATA 24
+    ],
+    EventCause [
+        SUSPEND 8,
+        RESUME 9,
+        READY 11
+    ],
+    Enable [
+        ENABLE 0
+    ],
+    UsbPullup [
+        CONNECT 0
+    ],
+    EpStall [
+        EP OFFSET(0) NUMBITS(3) [],
+        IO OFFSET(7) NUMBITS(1) [
+            Out = 0,
+            In = 1
+        ],
+        STALL OFFSET(8) NUMBITS(1) []
+    ]
+];
+
+const USBD_BASE: StaticRef<UsbdRegisters> =
+    unsafe { StaticRef::new(0x40027000 as *const UsbdRegisters) };
+
+/// Bit of an OUT endpoint in `EPDATASTATUS`; IN endpoints are in the low bits
+const EPDATASTATUS_OUT_SHIFT: usize = 16;
+
+/// Largest packet of the endpoints that are not isochronous
+const MAX_PACKET_SIZE: usize = 64;
+
+/// Request code of the standard `SET_ADDRESS` request
+const SET_ADDRESS: u32 = 5;
+
+#[derive(Copy, Clone, PartialEq)]
+enum EndpointKind {
+    Disabled,
+    Ctrl,
+    BulkIn,
+    BulkOut,
+}
+
+/// Stage of the transfer on the default control endpoint
+#[derive(Copy, Clone, PartialEq)]
+enum CtrlState {
+    Idle,
+    /// Sending data to the host, which ends with the packet being sent if
+    /// the flag is set
+    In(bool),
+    /// Receiving the given number of bytes from the host
+    Out(usize),
+}
+
+struct Endpoint {
+    /// The buffer in RAM that packets are moved to and from
+    buffer: Cell<*const VolatileCell<u8>>,
+    buffer_len: Cell<usize>,
+    kind: Cell<EndpointKind>,
+    /// A packet of this IN endpoint is waiting for the host to take it
+    in_busy: Cell<bool>,
+    /// The length of the packet of this OUT endpoint that the client has
+    /// not taken yet
+    out_delayed: OptionalCell<usize>,
+    /// A packet has arrived on this OUT endpoint while the client had not
+    /// taken the previous one
+    out_pending: Cell<bool>,
+}
+
+impl Endpoint {
+    const fn new() -> Endpoint {
+        Endpoint {
+            buffer: Cell::new(ptr::null()),
+            buffer_len: Cell::new(0),
+            kind: Cell::new(EndpointKind::Disabled),
+            in_busy: Cell::new(false),
+            out_delayed: OptionalCell::empty(),
+            out_pending: Cell::new(false),
+        }
+    }
+
+    fn buffer(&self) -> &[VolatileCell<u8>] {
+        if self.buffer.get().is_null() {
+            &[]
+        } else {
+            // The buffer was given by the client as a slice, which clients
+            // keep in their own state for as long as the controller is used
+            unsafe { core::slice::from_raw_parts(self.buffer.get(), self.buffer_len.get()) }
+        }
+    }
+}
+
+pub struct Usbd {
+    registers: StaticRef<UsbdRegisters>,
+    client: OptionalCell<&'static hil::usb::Client>,
+    endpoints: [Endpoint; NUM_ENDPOINTS],
+    ctrl_state: Cell<CtrlState>,
+}
+
+pub static mut USBD: Usbd = Usbd::new();
+
+impl Usbd {
+    const fn new() -> Usbd {
+        Usbd {
+            registers: USBD_BASE,
+            client: OptionalCell::empty(),
+            endpoints: [
+                Endpoint::new(),
+                Endpoint::new(),
+                Endpoint::new(),
+                Endpoint::new(),
+                Endpoint::new(),
+                Endpoint::new(),
+                Endpoint::new(),
+                Endpoint::new(),
+            ],
+            ctrl_state: Cell::new(CtrlState::Idle),
+        }
+    }
+
+    pub fn set_client(&self, client: &'static hil::usb::Client) {
+        self.client.set(client);
+    }
+
+    pub fn handle_interrupt(&self) {
+        let regs = &*self.registers;
+
+        if regs.events_usbreset.get() != 0 {
+            regs.events_usbreset.set(0);
+            self.bus_reset();
+        }
+
+        if regs.events_usbevent.get() != 0 {
+            regs.events_usbevent.set(0);
+            // Suspend and resume are not handled, so only clear them
+            regs.eventcause
+                .write(EventCause::SUSPEND::SET + EventCause::RESUME::SET);
+        }
+
+        if regs.events_ep0setup.get() != 0 {
+            regs.events_ep0setup.set(0);
+            self.ctrl_setup();
+        }
+
+        if regs.events_ep0datadone.get() != 0 {
+            regs.events_ep0datadone.set(0);
+            self.ctrl_data_done();
+        }
+
+        if regs.events_epdata.get() != 0 {
+            regs.events_epdata.set(0);
+            let status = regs.epdatastatus.get();
+            // Flags are cleared by writing 1 to them
+            regs.epdatastatus.set(status);
+
+            for endpoint in 1..NUM_ENDPOINTS {
+                if status & (1 << endpoint) != 0 {
+                    // The host has taken the packet of this IN endpoint
+                    self.endpoints[endpoint].in_busy.set(false);
+                    self.bulk_in(endpoint);
+                }
+                if status & (1 << (endpoint + EPDATASTATUS_OUT_SHIFT)) != 0 {
+                    self.bulk_out(endpoint);
+                }
+            }
+        }
+    }
+
+    fn bus_reset(&self) {
+        let regs = &*self.registers;
+        self.ctrl_state.set(CtrlState::Idle);
+        for (i, endpoint) in self.endpoints.iter().enumerate() {
+            endpoint.in_busy.set(false);
+            endpoint.out_delayed.clear();
+            endpoint.out_pending.set(false);
+            if endpoint.kind.get() == EndpointKind::BulkOut {
+                // Allow the endpoint to take a packet from the host
+                regs.size_epout[i].set(0);
+            }
+        }
+
+        self.client.map(|client| client.bus_reset());
+
+        for endpoint in 1..NUM_ENDPOINTS {
+            self.bulk_in(endpoint);
+        }
+    }
+
+    /// Runs an EasyDMA transfer to completion.
+    fn run_dma(&self, task: &WriteOnly<u32>, event: &ReadWrite<u32>) {
+        event.set(0);
+        unsafe {
+            // Errata 199: EasyDMA transfers need this workaround while they run
+            ptr::write_volatile(0x4002_7C1C as *mut u32, 0x0000_0082);
+        }
+        task.set(1);
+        while event.get() == 0 {}
+        event.set(0);
+        unsafe {
+            ptr::write_volatile(0x4002_7C1C as *mut u32, 0x0000_0000);
+        }
+    }
+
+    /// Moves a packet from the buffer of an IN endpoint to the controller.
+    fn dma_in(&self, endpoint: usize, length: usize) {
+        let regs = &*self.registers;
+        let buffer = self.endpoints[endpoint].buffer();
+        regs.epin[endpoint].ptr.set(buffer.as_ptr() as u32);
+        regs.epin[endpoint].maxcnt.set(length as u32);
+        self.run_dma(
+            &regs.tasks_startepin[endpoint],
+            &regs.events_endepin[endpoint],
+        );
+    }
+
+    /// Moves the packet that an OUT endpoint has received to its buffer, and
+    /// returns its length.
+    fn dma_out(&self, endpoint: usize) -> usize {
+        let regs = &*self.registers;
+        let buffer = self.endpoints[endpoint].buffer();
+        let length = (regs.size_epout[endpoint].get() as usize).min(buffer.len());
+        regs.epout[endpoint].ptr.set(buffer.as_ptr() as u32);
+        regs.epout[endpoint].maxcnt.set(length as u32);
+        self.run_dma(
+            &regs.tasks_startepout[endpoint],
+            &regs.events_endepout[endpoint],
+        );
+        regs.epout[endpoint].amount.get() as usize
+    }
+
+    fn ctrl_stall(&self) {
+        let regs = &*self.registers;
+        self.ctrl_state.set(CtrlState::Idle);
+        regs.tasks_ep0stall.set(1);
+    }
+
+    /// Lets the controller finish a control transfer with its status stage.
+    fn ctrl_status(&self) {
+        let regs = &*self.registers;
+        self.ctrl_state.set(CtrlState::Idle);
+        regs.tasks_ep0status.set(1);
+        self.client.map(|client| {
+            client.ctrl_status(0);
+            client.ctrl_status_complete(0);
+        });
+    }
+
+    fn ctrl_setup(&self) {
+        let regs = &*self.registers;
+        let setup = [
+            regs.bmrequesttype.get(),
+            regs.brequest.get(),
+            regs.wvaluel.get(),
+            regs.wvalueh.get(),
+            regs.windexl.get(),
+            regs.windexh.get(),
+            regs.wlengthl.get(),
+            regs.wlengthh.get(),
+        ];
+        let buffer = self.endpoints[0].buffer();
+        if buffer.len() < setup.len() {
+            self.ctrl_stall();
+            return;
+        }
+        for (byte, value) in buffer.iter().zip(setup.iter()) {
+            byte.set(*value as u8);
+        }
+
+        let result = self
+            .client
+            .map_or(CtrlSetupResult::ErrNoParse, |client| client.ctrl_setup(0));
+        match result {
+            CtrlSetupResult::Ok => {}
+            _ => {
+                self.ctrl_stall();
+                return;
+            }
+        }
+
+        let request_type = setup[0];
+        let length = (setup[6] | setup[7] << 8) as usize;
+        if request_type & 0x80 != 0 {
+            self.ctrl_in();
+        } else if length > 0 {
+            self.ctrl_state.set(CtrlState::Out(length));
+            regs.tasks_ep0rcvout.set(1);
+        } else if request_type & 0x60 == 0 && setup[1] == SET_ADDRESS {
+            // The controller has already answered the request and taken
+            // the address
+            self.ctrl_state.set(CtrlState::Idle);
+            self.client.map(|client| {
+                client.ctrl_status(0);
+                client.ctrl_status_complete(0);
+            });
+        } else {
+            self.ctrl_status();
+        }
+    }
+
+    fn ctrl_in(&self) {
+        let result = self
+            .client
+            .map_or(CtrlInResult::Error, |client| client.ctrl_in(0));
+        match result {
+            CtrlInResult::Packet(length, last) => {
+                self.ctrl_state.set(CtrlState::In(last));
+                self.dma_in(0, length);
+            }
+            // The host retries until it gives up on the request
+            CtrlInResult::Delay => self.ctrl_state.set(CtrlState::Idle),
+            CtrlInResult::Error => self.ctrl_stall(),
+        }
+    }
+
+    fn ctrl_data_done(&self) {
+        let regs = &*self.registers;
+        match self.ctrl_state.get() {
+            CtrlState::In(false) => self.ctrl_in(),
+            CtrlState::In(true) => self.ctrl_status(),
+            CtrlState::Out(remaining) => {
+                let length = self.dma_out(0);
+                let result = self.client.map_or(CtrlOutResult::Halted, |client| {
+                    client.ctrl_out(0, length as u32)
+                });
+                match result {
+                    CtrlOutResult::Ok => {
+                        let remaining = remaining.saturating_sub(length);
+                        let packet_size = self.endpoints[0].buffer_len.get().min(MAX_PACKET_SIZE);
+                        if remaining > 0 && length == packet_size {
+                            self.ctrl_state.set(CtrlState::Out(remaining));
+                            regs.tasks_ep0rcvout.set(1);
+                        } else {
+                            self.ctrl_status();
+                        }
+                    }
+                    _ => self.ctrl_stall(),
+                }
+            }
+            CtrlState::Idle => {}
+        }
+    }
+
+    /// Asks the client for a packet for an IN endpoint, if the endpoint has
+    /// no packet waiting for the host.
+    fn bulk_in(&self, endpoint: usize) {
+        let ep = &self.endpoints[endpoint];
+        if ep.kind.get() != EndpointKind::BulkIn || ep.in_busy.get() {
+            return;
+        }
+        let result = self
+            .client
+            .map_or(BulkInResult::Delay, |client| client.bulk_in(endpoint));
+        match result {
+            BulkInResult::Packet(length) => {
+                ep.in_busy.set(true);
+                self.dma_in(endpoint, length);
+            }
+            // The client resumes the endpoint once it has data
+            BulkInResult::Delay => {}
+            BulkInResult::Error => self.stall(endpoint, EpStall::IO::In),
+        }
+    }
+
+    /// Gives the packet that an OUT endpoint has received to the client.
+    fn bulk_out(&self, endpoint: usize) {
+        let ep = &self.endpoints[endpoint];
+        if ep.kind.get() != EndpointKind::BulkOut {
+            return;
+        }
+        if ep.out_delayed.is_some() {
+            // The buffer still holds a packet, so leave this one in the
+            // controller until the client resumes the endpoint
+            ep.out_pending.set(true);
+            return;
+        }
+        let length = self.dma_out(endpoint);
+        self.deliver_bulk_out(endpoint, length);
+    }
+
+    fn deliver_bulk_out(&self, endpoint: usize, length: usize) {
+        let result = self.client.map_or(BulkOutResult::Error, |client| {
+            client.bulk_out(endpoint, length as u32)
+        });
+        match result {
+            BulkOutResult::Ok => {}
+            BulkOutResult::Delay => self.endpoints[endpoint].out_delayed.set(length),
+            BulkOutResult::Error => self.stall(endpoint, EpStall::IO::Out),
+        }
+    }
+
+    fn stall(&self, endpoint: usize, direction: FieldValue<u32, EpStall::Register>) {
+        let regs = &*self.registers;
+        regs.epstall
+            .write(EpStall::EP.val(endpoint as u32) + direction + EpStall::STALL::SET);
+    }
+}
+
+/// Errata 171 and 187 of the nRF52840: the controller needs these
+/// undocumented registers set while it is being enabled.
+unsafe fn apply_enable_errata(enabling: bool) {
+    let unlock = 0x4006_EC00 as *mut u32;
+    if ptr::read_volatile(unlock) == 0 {
+        ptr::write_volatile(unlock, 0x0000_9375);
+    }
+    ptr::write_volatile(
+        0x4006_EC14 as *mut u32,
+        if enabling { 0x0000_00C0 } else { 0 },
+    );
+    ptr::write_volatile(unlock, 0x0000_9375);
+
+    ptr::write_volatile(unlock, 0x0000_9375);
+    ptr::write_volatile(
+        0x4006_ED14 as *mut u32,
+        if enabling { 0x0000_0003 } else { 0 },
+    );
+    ptr::write_volatile(unlock, 0x0000_9375);
+}
+
+impl hil::usb::UsbController for Usbd {
+    fn endpoint_set_buffer(&self, endpoint: usize, buf: &[VolatileCell<u8>]) {
+        if endpoint >= NUM_ENDPOINTS {
+            return;
+        }
+        self.endpoints[endpoint].buffer.set(buf.as_ptr());
+        self.endpoints[endpoint]
+            .buffer_len
+            .set(buf.len().min(MAX_PACKET_SIZE));
+    }
+
+    /// The controller is full speed only, so `speed` is ignored.
+    fn enable_as_device(&self, _speed: hil::usb::DeviceSpeed) {
+        let regs = &*self.registers;
+        unsafe {
+            apply_enable_errata(true);
+        }
+        regs.enable.write(Enable::ENABLE::SET);
+        while !regs.eventcause.is_set(EventCause::READY) {}
+        regs.eventcause.write(EventCause::READY::SET);
+        unsafe {
+            apply_enable_errata(false);
+        }
+
+        regs.inten.write(
+            Interrupt::USBRESET::SET
+                + Interrupt::USBEVENT::SET
+                + Interrupt::EP0SETUP::SET
+                + Interrupt::EP0DATADONE::SET
+                + Interrupt::EPDATA::SET,
+        );
+    }
+
+    fn attach(&self) {
+        let regs = &*self.registers;
+        regs.usbpullup.write(UsbPullup::CONNECT::SET);
+    }
+
+    fn detach(&self) {
+        let regs = &*self.registers;
+        regs.usbpullup.write(UsbPullup::CONNECT::CLEAR);
+    }
+
+    /// The controller handles `SET_ADDRESS` itself.
+    fn set_address(&self, _addr: u16) {}
+
+    fn enable_address(&self) {}
+
+    fn endpoint_ctrl_out_enable(&self, endpoint: usize) {
+        if endpoint == 0 {
+            self.endpoints[0].kind.set(EndpointKind::Ctrl);
+        }
+    }
+
+    fn endpoint_bulk_in_enable(&self, endpoint: usize) {
+        if endpoint == 0 || endpoint >= NUM_ENDPOINTS {
+            return;
+        }
+        let regs = &*self.registers;
+        self.endpoints[endpoint].kind.set(EndpointKind::BulkIn);
+        regs.epinen.set(regs.epinen.get() | 1 << endpoint);
+    }
+
+    fn endpoint_bulk_out_enable(&self, endpoint: usize) {
+        if endpoint == 0 || endpoint >= NUM_ENDPOINTS {
+            return;
+        }
+        let regs = &*self.registers;
+        self.endpoints[endpoint].kind.set(EndpointKind::BulkOut);
+        regs.epouten.set(regs.epouten.get() | 1 << endpoint);
+        regs.size_epout[endpoint].set(0);
+    }
+
+    fn endpoint_bulk_resume(&self, endpoint: usize) {
+        if endpoint >= NUM_ENDPOINTS {
+            return;
+        }
+        let ep = &self.endpoints[endpoint];
+        match ep.kind.get() {
+            EndpointKind::BulkIn => self.bulk_in(endpoint),
+            EndpointKind::BulkOut => {
+                if let Some(length) = ep.out_delayed.take() {
+                    self.deliver_bulk_out(endpoint, length);
+                    if ep.out_delayed.is_none() && ep.out_pending.get() {
+                        ep.out_pending.set(false);
+                        self.bulk_out(endpoint);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
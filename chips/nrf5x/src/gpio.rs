@@ -19,8 +19,16 @@ const NUM_GPIOTE: usize = 8;
 const GPIOTE_BASE: StaticRef<GpioteRegisters> =
     unsafe { StaticRef::new(0x40006000 as *const GpioteRegisters) };
 
-const GPIO_BASE: StaticRef<GpioRegisters> =
-    unsafe { StaticRef::new(0x50000000 as *const GpioRegisters) };
+/// Address of the registers of port 0. The nRF52840 has a second port, whose
+/// registers follow those of port 0.
+const GPIO_BASE_ADDRESS: usize = 0x50000000;
+const GPIO_PORT_SIZE: usize = 0x300;
+
+/// Pins are numbered across ports, so pin 32 is pin 0 of port 1 (P1.00).
+#[cfg(feature = "nrf51")]
+const NUM_PINS: usize = 32;
+#[cfg(feature = "nrf52")]
+const NUM_PINS: usize = 48;
 
 /// The nRF5x doesn't automatically provide GPIO interrupts. Instead, to receive
 /// interrupts from a GPIO line, you must allocate a GPIOTE (GPIO Task and
@@ -299,6 +307,8 @@ register_bitfields! [u32,
         /// GPIO number associated with SET\[n\], CLR\[n\] and OUT\[n\] tasks
         /// and IN\[n\] event
         PSEL OFFSET(8) NUMBITS(5) [],
+        /// Port number of the pin, on chips with more than one port
+        PORT OFFSET(13) NUMBITS(1) [],
         /// When In task mode: Operation to be performed on output
         /// when OUT\[n\] task is triggered. When In event mode: Operation
         /// on input that shall trigger IN\[n\] event
@@ -339,19 +349,34 @@ impl GPIOPin {
         GPIOPin {
             pin: pin,
             client: OptionalCell::empty(),
-            gpio_registers: GPIO_BASE,
+            gpio_registers: unsafe {
+                StaticRef::new(
+                    (GPIO_BASE_ADDRESS + (pin as usize / 32) * GPIO_PORT_SIZE)
+                        as *const GpioRegisters,
+                )
+            },
             gpiote_registers: GPIOTE_BASE,
         }
     }
 
+    /// The number of the pin within its port
+    fn port_pin(&self) -> u8 {
+        self.pin % 32
+    }
+
+    /// The bit of the pin in the registers of its port
+    fn mask(&self) -> u32 {
+        1 << self.port_pin()
+    }
+
     pub fn write_config(&self, config: FieldValue<u32, PinConfig::Register>) {
         let gpio_regs = &*self.gpio_registers;
-        gpio_regs.pin_cnf[self.pin as usize].write(config);
+        gpio_regs.pin_cnf[self.port_pin() as usize].write(config);
     }
 
     pub fn read_config(&self) -> Option<PinConfig::PULL::Value> {
         let gpio_regs = &*self.gpio_registers;
-        gpio_regs.pin_cnf[self.pin as usize].read_as_enum(PinConfig::PULL)
+        gpio_regs.pin_cnf[self.port_pin() as usize].read_as_enum(PinConfig::PULL)
     }
 }
 
@@ -377,7 +402,7 @@ impl hil::gpio::Configure for GPIOPin {
 
     fn make_output(&self) -> hil::gpio::Configuration {
         let gpio_regs = &*self.gpio_registers;
-        gpio_regs.dirset.set(1 << self.pin);
+        gpio_regs.dirset.set(self.mask());
         hil::gpio::Configuration::Output
     }
 
@@ -389,7 +414,7 @@ impl hil::gpio::Configure for GPIOPin {
     // mynewt/hw/mcu/nordic/nrf51xxx/include/mcu/nrf51_bitfields.h
     fn make_input(&self) -> hil::gpio::Configuration {
         let gpio_regs = &*self.gpio_registers;
-        gpio_regs.dirclr.set(1 << self.pin);
+        gpio_regs.dirclr.set(self.mask());
         hil::gpio::Configuration::Input
     }
 
@@ -399,7 +424,7 @@ impl hil::gpio::Configure for GPIOPin {
 
     fn configuration(&self) -> hil::gpio::Configuration {
         let gpio_regs = &*self.gpio_registers;
-        if gpio_regs.dirclr.get() & 1 << self.pin == 0 {
+        if gpio_regs.dirclr.get() & self.mask() == 0 {
             hil::gpio::Configuration::Input
         } else {
             hil::gpio::Configuration::Output
@@ -414,26 +439,26 @@ impl hil::gpio::Configure for GPIOPin {
 impl hil::gpio::Input for GPIOPin {
     fn read(&self) -> bool {
         let gpio_regs = &*self.gpio_registers;
-        gpio_regs.in_.get() & (1 << self.pin) != 0
+        gpio_regs.in_.get() & self.mask() != 0
     }
 }
 
 impl hil::gpio::Output for GPIOPin {
     fn set(&self) {
         let gpio_regs = &*self.gpio_registers;
-        gpio_regs.outset.set(1 << self.pin);
+        gpio_regs.outset.set(self.mask());
     }
 
     fn clear(&self) {
         let gpio_regs = &*self.gpio_registers;
-        gpio_regs.outclr.set(1 << self.pin);
+        gpio_regs.outclr.set(self.mask());
     }
 
     fn toggle(&self) -> bool {
         let gpio_regs = &*self.gpio_registers;
-        let result = (1 << self.pin) ^ gpio_regs.out.get();
+        let result = self.mask() ^ gpio_regs.out.get();
         gpio_regs.out.set(result);
-        result & (1 << self.pin) != 0
+        result & self.mask() != 0
     }
 }
 
@@ -445,7 +470,7 @@ impl hil::gpio::Interrupt for GPIOPin {
     }
 
     fn is_pending(&self) -> bool {
-        if let Ok(channel) = self.find_channel(self.pin) {
+        if let Ok(channel) = self.find_channel() {
             let regs = &*self.gpiote_registers;
             let ev = &regs.event_in[channel];
             ev.matches_any(EventsIn::EVENT::Ready)
@@ -462,8 +487,7 @@ impl hil::gpio::Interrupt for GPIOPin {
                 hil::gpio::InterruptEdge::FallingEdge => Config::POLARITY::HiToLo,
             };
             let regs = &*self.gpiote_registers;
-            regs.config[channel]
-                .write(Config::MODE::Event + Config::PSEL.val(self.pin as u32) + polarity);
+            regs.config[channel].write(Config::MODE::Event + self.psel() + polarity);
             regs.intenset.set(1 << channel);
         } else {
             debug!("No available GPIOTE interrupt channels");
@@ -471,10 +495,14 @@ impl hil::gpio::Interrupt for GPIOPin {
     }
 
     fn disable_interrupts(&self) {
-        if let Ok(channel) = self.find_channel(self.pin) {
+        if let Ok(channel) = self.find_channel() {
             let regs = &*self.gpiote_registers;
-            regs.config[channel]
-                .write(Config::MODE::CLEAR + Config::PSEL::CLEAR + Config::POLARITY::CLEAR);
+            regs.config[channel].write(
+                Config::MODE::CLEAR
+                    + Config::PSEL::CLEAR
+                    + Config::PORT::CLEAR
+                    + Config::POLARITY::CLEAR,
+            );
             regs.intenclr.set(1 << channel);
         }
     }
@@ -483,6 +511,11 @@ impl hil::gpio::Interrupt for GPIOPin {
 impl hil::gpio::InterruptPin for GPIOPin {}
 
 impl GPIOPin {
+    /// The pin select fields of a GPIOTE channel for this pin
+    fn psel(&self) -> FieldValue<u32, Config::Register> {
+        Config::PSEL.val(self.port_pin() as u32) + Config::PORT.val(self.pin as u32 / 32)
+    }
+
     /// Allocate a GPIOTE channel
     /// If the channel couldn't be allocated return error instead
     fn allocate_channel(&self) -> Result<usize, ()> {
@@ -497,10 +530,10 @@ impl GPIOPin {
 
     /// Return which channel is allocated to a pin,
     /// If the channel is not found return an error instead
-    fn find_channel(&self, pin: u8) -> Result<usize, ()> {
+    fn find_channel(&self) -> Result<usize, ()> {
         let regs = &*self.gpiote_registers;
         for (i, ch) in regs.config.iter().enumerate() {
-            if ch.matches_all(Config::PSEL.val(pin as u32)) {
+            if ch.matches_all(self.psel()) {
                 return Ok(i);
             }
         }
@@ -515,7 +548,7 @@ impl GPIOPin {
 }
 
 pub struct Port {
-    pins: [GPIOPin; 32],
+    pins: [GPIOPin; NUM_PINS],
 }
 
 impl Index<usize> for Port {
@@ -544,7 +577,8 @@ impl Port {
             if ev.matches_any(EventsIn::EVENT::Ready) {
                 ev.write(EventsIn::EVENT::NotReady);
                 // Get pin number for the event and `trigger` an interrupt manually on that pin
-                let pin = regs.config[i].read(Config::PSEL) as usize;
+                let config = &regs.config[i];
+                let pin = (config.read(Config::PORT) * 32 + config.read(Config::PSEL)) as usize;
                 self.pins[pin].handle_interrupt();
             }
         }
@@ -585,5 +619,37 @@ pub static mut PORT: Port = Port {
         GPIOPin::new(29),
         GPIOPin::new(30),
         GPIOPin::new(31),
+        #[cfg(feature = "nrf52")]
+        GPIOPin::new(32),
+        #[cfg(feature = "nrf52")]
+        GPIOPin::new(33),
+        #[cfg(feature = "nrf52")]
+        GPIOPin::new(34),
+        #[cfg(feature = "nrf52")]
+        GPIOPin::new(35),
+        #[cfg(feature = "nrf52")]
+        GPIOPin::new(36),
+        #[cfg(feature = "nrf52")]
+        GPIOPin::new(37),
+        #[cfg(feature = "nrf52")]
+        GPIOPin::new(38),
+        #[cfg(feature = "nrf52")]
+        GPIOPin::new(39),
+        #[cfg(feature = "nrf52")]
+        GPIOPin::new(40),
+        #[cfg(feature = "nrf52")]
+        GPIOPin::new(41),
+        #[cfg(feature = "nrf52")]
+        GPIOPin::new(42),
+        #[cfg(feature = "nrf52")]
+        GPIOPin::new(43),
+        #[cfg(feature = "nrf52")]
+        GPIOPin::new(44),
+        #[cfg(feature = "nrf52")]
+        GPIOPin::new(45),
+        #[cfg(feature = "nrf52")]
+        GPIOPin::new(46),
+        #[cfg(feature = "nrf52")]
+        GPIOPin::new(47),
     ],
 };
@@ -4,11 +4,15 @@
 //! fields that determine which pins are used by the hardware. The board
 //! configuration should create `Pinmux`s and pass them into controller drivers
 //! during initialization.
+//!
+//! Pins are numbered across ports, so on chips with two ports, like the
+//! nRF52840, pin 32 is pin 0 of port 1 (P1.00). This is also how the pin
+//! select registers of nRF52840 peripherals encode the port.
 
 use kernel::common::cells::VolatileCell;
 
 // Keep track of which pins has a `Pinmux` been created for.
-static mut USED_PINS: VolatileCell<u32> = VolatileCell::new(0);
+static mut USED_PINS: VolatileCell<u64> = VolatileCell::new(0);
 
 /// An opaque wrapper around a configurable pin.
 #[derive(Copy, Clone)]
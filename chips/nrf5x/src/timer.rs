@@ -290,7 +290,13 @@ impl TimerAlarm {
     }
 
     pub fn handle_interrupt(&self) {
-        self.clear_alarm();
+        if self.registers.shorts.is_set(Shorts::COMPARE1_CLEAR) {
+            // A repeating timer keeps running, the hardware already cleared
+            // the counter for the next interval
+            self.registers.events_compare[ALARM_COMPARE].write(Event::READY::CLEAR);
+        } else {
+            self.clear_alarm();
+        }
         self.client.map(|client| {
             client.fired();
         });
@@ -311,6 +317,29 @@ impl TimerAlarm {
         self.registers.intenset.is_set(ALARM_INTERRUPT_BIT)
     }
 
+    /// Count from 0, and interrupt when the counter reaches `interval`. If
+    /// `repeat`, the compare event also clears the counter, so that the timer
+    /// starts the next interval on its own.
+    fn start_interval(&self, interval: u32, repeat: bool) {
+        self.disable_interrupts();
+        self.registers.tasks_stop.write(Task::ENABLE::SET);
+        self.registers.tasks_clear.write(Task::ENABLE::SET);
+        self.registers.events_compare[ALARM_COMPARE].write(Event::READY::CLEAR);
+        if repeat {
+            self.registers
+                .shorts
+                .modify(Shorts::COMPARE1_CLEAR::EnableShortcut);
+        } else {
+            self.registers
+                .shorts
+                .modify(Shorts::COMPARE1_CLEAR::DisableShortcut);
+        }
+        self.registers.bitmode.write(Bitmode::BITMODE::Bit32);
+        self.registers.cc[ALARM_COMPARE].write(CC::CC.val(interval));
+        self.registers.tasks_start.write(Task::ENABLE::SET);
+        self.enable_interrupts();
+    }
+
     fn value(&self) -> u32 {
         self.registers.tasks_capture[ALARM_CAPTURE].write(Task::ENABLE::SET);
         self.registers.cc[ALARM_CAPTURE].get()
@@ -329,6 +358,20 @@ impl hil::time::Time for TimerAlarm {
     }
 }
 
+/// The compare event clears the counter of a repeating timer in hardware, so
+/// `repeat` does not drift. The counter then no longer counts up from the
+/// start, so a `TimerAlarm` can only be used as either a `Timer` or an
+/// `Alarm`.
+impl hil::time::Timer for TimerAlarm {
+    fn oneshot(&self, interval: u32) {
+        self.start_interval(interval, false);
+    }
+
+    fn repeat(&self, interval: u32) {
+        self.start_interval(interval, true);
+    }
+}
+
 impl hil::time::Alarm for TimerAlarm {
     fn now(&self) -> u32 {
         self.value()
@@ -336,6 +379,9 @@ impl hil::time::Alarm for TimerAlarm {
 
     fn set_alarm(&self, tics: u32) {
         self.disable_interrupts();
+        self.registers
+            .shorts
+            .modify(Shorts::COMPARE1_CLEAR::DisableShortcut);
         self.registers.bitmode.write(Bitmode::BITMODE::Bit32);
         self.registers.cc[ALARM_COMPARE].write(CC::CC.val(tics));
         self.registers.tasks_start.write(Task::ENABLE::SET);
@@ -0,0 +1,201 @@
+//! Chip trait setup.
+
+use kernel;
+use kernel::common::cells::VolatileCell;
+use kernel::debug;
+use kernel::hil::time::{Freq10MHz, Time};
+use kernel::record;
+use rv32i;
+use rv32i::machine_timer::MachineTimer;
+use rv32i::plic;
+
+use crate::interrupts;
+use crate::uart;
+
+/// The machine timer of the `virt` machine, whose `mtime` counts at 10 MHz.
+pub static mut MACHINETIMER: MachineTimer<Freq10MHz> = MachineTimer::new();
+
+/// Machine timer interrupt bit of mie and mip
+const MTI: u32 = 1 << 7;
+/// Machine external interrupt bit of mie and mip
+const MEI: u32 = 1 << 11;
+
+/// The interrupts that trapped and wait for the kernel loop, as bits of mie.
+/// The trap handler disables each one before it saves it, so it is only
+/// written with that interrupt disabled.
+static mut SAVED_INTERRUPTS: VolatileCell<u32> = VolatileCell::new(0);
+
+pub struct QemuRv32Virt {
+    userspace_kernel_boundary: rv32i::syscall::SysCall,
+}
+
+impl QemuRv32Virt {
+    pub unsafe fn new() -> QemuRv32Virt {
+        QemuRv32Virt {
+            userspace_kernel_boundary: rv32i::syscall::SysCall::new(),
+        }
+    }
+
+    /// Enable the machine timer interrupt and the PLIC interrupts.
+    pub fn enable_all_interrupts(&self) {
+        unsafe {
+            plic::enable_all();
+            enable_interrupts(MTI | MEI);
+        }
+    }
+
+    /// Configure the PMP to allow all accesses in both machine mode (the
+    /// default) and in user mode.
+    ///
+    /// This needs to be replaced with a real PMP driver. See
+    /// https://github.com/tock/tock/issues/1135
+    pub unsafe fn disable_pmp(&self) {
+        asm!("
+            // Set the first region address to 0xFFFFFFFF. When using top-of-range mode
+            // this will include the entire address space.
+            lui  t0, %hi(0xFFFFFFFF)
+            addi t0, t0, %lo(0xFFFFFFFF)
+            csrw 0x3b0, t0    // CSR=pmpaddr0
+
+            // Set the first region to use top-of-range and allow everything.
+            // This is equivalent to:
+            // R=1, W=1, X=1, A=01, L=0
+            li   t0, 0x0F
+            csrw 0x3a0, t0    // CSR=pmpcfg0
+        "
+        :
+        :
+        :
+        : "volatile");
+    }
+
+    /// Generic helper initialize function to setup all of the chip specific
+    /// operations. Different boards can call the functions that `initialize()`
+    /// calls directly if it needs to use a custom setup operation.
+    pub unsafe fn initialize(&self) {
+        self.disable_pmp();
+        // mtimecmp is 0 after reset, which would fire the machine timer
+        // interrupt as soon as it is enabled
+        MACHINETIMER.disable();
+        plic::disable_all();
+        plic::clear_all_pending();
+        rv32i::configure_trap_handler();
+    }
+}
+
+impl kernel::Chip for QemuRv32Virt {
+    type MPU = ();
+    type UserspaceKernelBoundary = rv32i::syscall::SysCall;
+    type SysTick = ();
+
+    fn mpu(&self) -> &Self::MPU {
+        &()
+    }
+
+    fn systick(&self) -> &Self::SysTick {
+        &()
+    }
+
+    fn userspace_kernel_boundary(&self) -> &rv32i::syscall::SysCall {
+        &self.userspace_kernel_boundary
+    }
+
+    fn service_pending_interrupts(&self) {
+        unsafe {
+            let saved = SAVED_INTERRUPTS.get();
+
+            if saved & MTI != 0 {
+                SAVED_INTERRUPTS.set(SAVED_INTERRUPTS.get() & !MTI);
+                MACHINETIMER.handle_interrupt();
+                enable_interrupts(MTI);
+            }
+
+            if saved & MEI != 0 {
+                SAVED_INTERRUPTS.set(SAVED_INTERRUPTS.get() & !MEI);
+                while let Some(interrupt) = plic::next_pending() {
+                    record::interrupt(interrupt);
+                    match interrupt {
+                        interrupts::UART0 => uart::UART0.handle_interrupt(),
+                        _ => debug!("Pidx {}", interrupt),
+                    }
+
+                    // Mark that we are done with this interrupt and the
+                    // hardware can clear it.
+                    plic::complete(interrupt);
+                }
+                enable_interrupts(MEI);
+            }
+        }
+    }
+
+    fn has_pending_interrupts(&self) -> bool {
+        unsafe { SAVED_INTERRUPTS.get() != 0 }
+    }
+
+    fn sleep(&self) {
+        unsafe {
+            rv32i::support::wfi();
+        }
+    }
+
+    unsafe fn atomic<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce() -> R,
+    {
+        rv32i::support::atomic(f)
+    }
+}
+
+unsafe fn enable_interrupts(mask: u32) {
+    asm!("csrs 0x304, $0" : : "r"(mask) : : "volatile"); // CSR=0x304=mie
+}
+
+/// Disable the interrupt that caused a trap, and save it so that the kernel
+/// loop handles it.
+unsafe fn handle_interrupt(mcause: u32) {
+    let mask = 1 << (mcause & 0x1f);
+    asm!("csrc 0x304, $0" : : "r"(mask) : : "volatile"); // CSR=0x304=mie
+    SAVED_INTERRUPTS.set(SAVED_INTERRUPTS.get() | mask);
+}
+
+/// Trap handler for board/chip specific code.
+///
+/// For the `virt` machine this gets called when an interrupt occurs while the
+/// chip is in kernel mode.
+#[export_name = "_start_trap_rust"]
+pub extern "C" fn start_trap_rust() {
+    let mut mcause: i32;
+
+    unsafe {
+        asm!("
+            // Read the mcause CSR to determine why we entered the trap handler.
+            csrr $0, 0x342    // CSR=0x342=mcause
+        "
+        : "=r"(mcause)
+        :
+        :
+        : "volatile");
+    }
+
+    // Check if the trap was from an interrupt or some other exception.
+    if mcause < 0 {
+        // If the most significant bit is set (i.e. mcause is negative) then
+        // this was an interrupt, and the lowest bits are its bit in mie.
+        unsafe {
+            handle_interrupt(mcause as u32);
+        }
+    } else {
+        // Otherwise, the kernel encountered a fault...so panic!()?
+        panic!("kernel exception");
+    }
+}
+
+/// Function that gets called if an interrupt occurs while an app was running.
+/// mcause is passed in, and this function should correctly handle disabling the
+/// interrupt that fired so that it does not trigger again.
+#[export_name = "_disable_interrupt_trap_handler"]
+pub extern "C" fn disable_interrupt_trap_handler(mcause: u32) {
+    unsafe {
+        handle_interrupt(mcause);
+    }
+}
@@ -0,0 +1,3 @@
+//! Named interrupts of the PLIC of the `virt` machine.
+
+pub const UART0: u32 = 10;
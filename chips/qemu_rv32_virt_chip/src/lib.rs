@@ -0,0 +1,16 @@
+//! Peripheral implementations for the `virt` machine of QEMU's 32 bit RISC-V
+//! system emulator (`qemu-system-riscv32 -M virt`).
+//!
+//! The machine has a CLINT, whose machine timer `rv32i::machine_timer` drives,
+//! a PLIC, a 16550 UART and a test device that stops the emulator.
+
+#![feature(asm, const_fn, in_band_lifetimes)]
+#![no_std]
+#![crate_name = "qemu_rv32_virt_chip"]
+#![crate_type = "rlib"]
+
+mod interrupts;
+
+pub mod chip;
+pub mod test_finisher;
+pub mod uart;
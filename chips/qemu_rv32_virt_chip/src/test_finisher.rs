@@ -0,0 +1,33 @@
+//! Test device of the `virt` machine, which stops the emulator.
+//!
+//! Writing to its register makes QEMU exit, with a status of 0 for a pass,
+//! and with the given code for a failure. This lets tests that run in QEMU
+//! report their result to the script that started it.
+
+use kernel::common::registers::WriteOnly;
+use kernel::common::StaticRef;
+
+#[repr(C)]
+struct TestFinisherRegisters {
+    /// A status in the low 16 bits, and a code in the high 16 bits
+    finisher: WriteOnly<u32>,
+}
+
+const TEST_FINISHER_BASE: StaticRef<TestFinisherRegisters> =
+    unsafe { StaticRef::new(0x0010_0000 as *const TestFinisherRegisters) };
+
+const FINISHER_FAIL: u32 = 0x3333;
+const FINISHER_PASS: u32 = 0x5555;
+
+/// Stop QEMU. An exit code of 0 stops it with a pass, and any other code with
+/// a failure of that code.
+pub fn exit(code: u16) -> ! {
+    let registers = TEST_FINISHER_BASE;
+    if code == 0 {
+        registers.finisher.set(FINISHER_PASS);
+    } else {
+        registers.finisher.set((code as u32) << 16 | FINISHER_FAIL);
+    }
+    // QEMU stops as soon as the register is written
+    loop {}
+}
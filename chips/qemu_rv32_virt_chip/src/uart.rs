@@ -0,0 +1,355 @@
+//! UART driver for the 16550 UART of the `virt` machine.
+//!
+//! Transmission goes through the 16 byte transmit FIFO, refilled each time
+//! it empties. Reception reads the receive FIFO from the data ready
+//! interrupt.
+
+use core::cell::Cell;
+use kernel::common::cells::{OptionalCell, TakeCell};
+use kernel::common::registers::{register_bitfields, ReadOnly, ReadWrite};
+use kernel::common::StaticRef;
+use kernel::hil;
+use kernel::ReturnCode;
+
+/// The frequency of the clock of the UART
+const UART_FREQUENCY: u32 = 3_686_400;
+
+/// The depth of the transmit and receive FIFOs
+const FIFO_DEPTH: usize = 16;
+
+#[repr(C)]
+pub struct UartRegisters {
+    /// Receive buffer or transmit holding register, or the low byte of the
+    /// divisor latch when LCR.DLAB is set
+    rbr_thr: ReadWrite<u8>,
+    /// Interrupt enable register, or the high byte of the divisor latch when
+    /// LCR.DLAB is set
+    ier: ReadWrite<u8, IER::Register>,
+    /// Interrupt identification register on read, FIFO control register on
+    /// write
+    iir_fcr: ReadWrite<u8, IIR_FCR::Register>,
+    /// Line control register
+    lcr: ReadWrite<u8, LCR::Register>,
+    /// Modem control register
+    mcr: ReadWrite<u8>,
+    /// Line status register
+    lsr: ReadOnly<u8, LSR::Register>,
+    /// Modem status register
+    msr: ReadOnly<u8>,
+    /// Scratch register
+    scr: ReadWrite<u8>,
+}
+
+register_bitfields![u8,
+    IER [
+        /// Receiver line status interrupt
+        ELSI OFFSET(2) NUMBITS(1) [],
+        /// Transmit holding register empty interrupt
+        ETBEI OFFSET(1) NUMBITS(1) [],
+        /// Received data available interrupt
+        ERBFI OFFSET(0) NUMBITS(1) []
+    ],
+    IIR_FCR [
+        /// On read, the source of the interrupt
+        IID OFFSET(1) NUMBITS(3) [
+            ModemStatus = 0,
+            TransmitEmpty = 1,
+            DataAvailable = 2,
+            LineStatus = 3,
+            CharacterTimeout = 6
+        ],
+        /// On read, no interrupt is pending
+        NO_INT OFFSET(0) NUMBITS(1) [],
+        /// On write, reset the transmit FIFO
+        TX_RESET OFFSET(2) NUMBITS(1) [],
+        /// On write, reset the receive FIFO
+        RX_RESET OFFSET(1) NUMBITS(1) [],
+        /// On write, enable the FIFOs
+        FIFO_EN OFFSET(0) NUMBITS(1) []
+    ],
+    LCR [
+        /// Divisor latch access
+        DLAB OFFSET(7) NUMBITS(1) [],
+        /// Even parity rather than odd
+        EPS OFFSET(4) NUMBITS(1) [],
+        /// Parity enable
+        PEN OFFSET(3) NUMBITS(1) [],
+        /// Two stop bits rather than one
+        STB OFFSET(2) NUMBITS(1) [],
+        /// Data bits
+        WLS OFFSET(0) NUMBITS(2) [
+            Bits5 = 0,
+            Bits6 = 1,
+            Bits7 = 2,
+            Bits8 = 3
+        ]
+    ],
+    LSR [
+        /// The transmit holding register is empty
+        THRE OFFSET(5) NUMBITS(1) [],
+        /// A break was received
+        BI OFFSET(4) NUMBITS(1) [],
+        /// A stop bit was missing
+        FE OFFSET(3) NUMBITS(1) [],
+        /// A parity bit was wrong
+        PE OFFSET(2) NUMBITS(1) [],
+        /// The receive FIFO overflowed
+        OE OFFSET(1) NUMBITS(1) [],
+        /// Received data is ready
+        DR OFFSET(0) NUMBITS(1) []
+    ]
+];
+
+const UART0_BASE: StaticRef<UartRegisters> =
+    unsafe { StaticRef::new(0x1000_0000 as *const UartRegisters) };
+
+pub static mut UART0: Uart = Uart::new(UART0_BASE);
+
+pub struct Uart<'a> {
+    registers: StaticRef<UartRegisters>,
+    tx_client: OptionalCell<&'a hil::uart::TransmitClient>,
+    rx_client: OptionalCell<&'a hil::uart::ReceiveClient>,
+    tx_buffer: TakeCell<'static, [u8]>,
+    tx_len: Cell<usize>,
+    tx_index: Cell<usize>,
+    rx_buffer: TakeCell<'static, [u8]>,
+    rx_len: Cell<usize>,
+    rx_index: Cell<usize>,
+}
+
+impl Uart<'a> {
+    const fn new(base: StaticRef<UartRegisters>) -> Uart<'a> {
+        Uart {
+            registers: base,
+            tx_client: OptionalCell::empty(),
+            rx_client: OptionalCell::empty(),
+            tx_buffer: TakeCell::empty(),
+            tx_len: Cell::new(0),
+            tx_index: Cell::new(0),
+            rx_buffer: TakeCell::empty(),
+            rx_len: Cell::new(0),
+            rx_index: Cell::new(0),
+        }
+    }
+
+    /// Write the next bytes of the transmit buffer into the FIFO, which must
+    /// be empty.
+    fn fill_fifo(&self) {
+        self.tx_buffer.map(|buffer| {
+            let mut count = 0;
+            while self.tx_index.get() < self.tx_len.get() && count < FIFO_DEPTH {
+                self.registers.rbr_thr.set(buffer[self.tx_index.get()]);
+                self.tx_index.set(self.tx_index.get() + 1);
+                count += 1;
+            }
+        });
+    }
+
+    fn transmit_progress(&self) {
+        if self.tx_index.get() < self.tx_len.get() {
+            self.fill_fifo();
+            return;
+        }
+
+        self.registers.ier.modify(IER::ETBEI::CLEAR);
+        self.tx_client.map(|client| {
+            self.tx_buffer.take().map(|buffer| {
+                client.transmitted_buffer(buffer, self.tx_len.get(), ReturnCode::SUCCESS);
+            });
+        });
+    }
+
+    /// Read bytes from the FIFO into the receive buffer, and signal the
+    /// client once it is full or an error occurs.
+    fn drain_fifo(&self) {
+        let mut error = hil::uart::Error::None;
+        self.rx_buffer.map(|buffer| {
+            while self.rx_index.get() < self.rx_len.get() {
+                let status = self.registers.lsr.extract();
+                if status.is_set(LSR::OE) {
+                    error = hil::uart::Error::OverrunError;
+                } else if status.is_set(LSR::PE) {
+                    error = hil::uart::Error::ParityError;
+                } else if status.is_set(LSR::FE) {
+                    error = hil::uart::Error::FramingError;
+                }
+                if error != hil::uart::Error::None || !status.is_set(LSR::DR) {
+                    break;
+                }
+                buffer[self.rx_index.get()] = self.registers.rbr_thr.get();
+                self.rx_index.set(self.rx_index.get() + 1);
+            }
+        });
+
+        if self.rx_index.get() == self.rx_len.get() || error != hil::uart::Error::None {
+            self.registers
+                .ier
+                .modify(IER::ERBFI::CLEAR + IER::ELSI::CLEAR);
+            let rval = if error == hil::uart::Error::None {
+                ReturnCode::SUCCESS
+            } else {
+                ReturnCode::FAIL
+            };
+            self.rx_client.map(|client| {
+                self.rx_buffer.take().map(|buffer| {
+                    client.received_buffer(buffer, self.rx_index.get(), rval, error);
+                });
+            });
+        }
+    }
+
+    pub fn handle_interrupt(&self) {
+        // Reading IIR acknowledges a transmit holding register empty
+        // interrupt
+        let iir = self.registers.iir_fcr.extract();
+        if iir.is_set(IIR_FCR::NO_INT) {
+            return;
+        }
+        match iir.read_as_enum(IIR_FCR::IID) {
+            Some(IIR_FCR::IID::Value::TransmitEmpty) => self.transmit_progress(),
+            Some(IIR_FCR::IID::Value::DataAvailable)
+            | Some(IIR_FCR::IID::Value::CharacterTimeout)
+            | Some(IIR_FCR::IID::Value::LineStatus) => self.drain_fifo(),
+            _ => {}
+        }
+    }
+
+    /// Write a byte, waiting for the transmit holding register to empty. This
+    /// is used for panic messages, and must not be mixed with
+    /// `transmit_buffer`.
+    pub fn send_byte(&self, data: u8) {
+        while !self.registers.lsr.is_set(LSR::THRE) {}
+        self.registers.rbr_thr.set(data);
+    }
+}
+
+impl hil::uart::UartData<'a> for Uart<'a> {}
+impl hil::uart::Uart<'a> for Uart<'a> {}
+
+impl hil::uart::Configure for Uart<'a> {
+    fn configure(&self, params: hil::uart::Parameters) -> ReturnCode {
+        if params.baud_rate == 0 || params.baud_rate > UART_FREQUENCY / 16 {
+            return ReturnCode::EINVAL;
+        }
+        if params.hw_flow_control {
+            return ReturnCode::ENOSUPPORT;
+        }
+
+        self.registers.ier.set(0);
+
+        let divisor = UART_FREQUENCY / (16 * params.baud_rate);
+        self.registers.lcr.write(LCR::DLAB::SET);
+        self.registers.rbr_thr.set(divisor as u8);
+        self.registers.ier.set((divisor >> 8) as u8);
+
+        let width = match params.width {
+            hil::uart::Width::Six => LCR::WLS::Bits6,
+            hil::uart::Width::Seven => LCR::WLS::Bits7,
+            hil::uart::Width::Eight => LCR::WLS::Bits8,
+        };
+        let parity = match params.parity {
+            hil::uart::Parity::None => LCR::PEN::CLEAR,
+            hil::uart::Parity::Odd => LCR::PEN::SET + LCR::EPS::CLEAR,
+            hil::uart::Parity::Even => LCR::PEN::SET + LCR::EPS::SET,
+        };
+        let stop_bits = match params.stop_bits {
+            hil::uart::StopBits::One => LCR::STB::CLEAR,
+            hil::uart::StopBits::Two => LCR::STB::SET,
+        };
+        // Clearing DLAB switches back to the data and interrupt registers
+        self.registers.lcr.write(width + parity + stop_bits);
+
+        self.registers
+            .iir_fcr
+            .write(IIR_FCR::FIFO_EN::SET + IIR_FCR::RX_RESET::SET + IIR_FCR::TX_RESET::SET);
+
+        ReturnCode::SUCCESS
+    }
+}
+
+impl hil::uart::Transmit<'a> for Uart<'a> {
+    fn set_transmit_client(&self, client: &'a hil::uart::TransmitClient) {
+        self.tx_client.set(client);
+    }
+
+    fn transmit_buffer(
+        &self,
+        tx_data: &'static mut [u8],
+        tx_len: usize,
+    ) -> (ReturnCode, Option<&'static mut [u8]>) {
+        if self.tx_buffer.is_some() {
+            return (ReturnCode::EBUSY, Some(tx_data));
+        }
+        if tx_len == 0 || tx_len > tx_data.len() {
+            return (ReturnCode::ESIZE, Some(tx_data));
+        }
+
+        self.tx_buffer.replace(tx_data);
+        self.tx_len.set(tx_len);
+        self.tx_index.set(0);
+
+        // The transmit holding register empty interrupt fires as soon as it
+        // is enabled with the FIFO empty, and starts the transmission
+        self.registers.ier.modify(IER::ETBEI::SET);
+
+        (ReturnCode::SUCCESS, None)
+    }
+
+    fn transmit_abort(&self) -> ReturnCode {
+        ReturnCode::FAIL
+    }
+
+    fn transmit_word(&self, _word: u32) -> ReturnCode {
+        ReturnCode::FAIL
+    }
+}
+
+impl hil::uart::Receive<'a> for Uart<'a> {
+    fn set_receive_client(&self, client: &'a hil::uart::ReceiveClient) {
+        self.rx_client.set(client);
+    }
+
+    fn receive_buffer(
+        &self,
+        rx_buffer: &'static mut [u8],
+        rx_len: usize,
+    ) -> (ReturnCode, Option<&'static mut [u8]>) {
+        if self.rx_buffer.is_some() {
+            return (ReturnCode::EBUSY, Some(rx_buffer));
+        }
+        if rx_len == 0 || rx_len > rx_buffer.len() {
+            return (ReturnCode::ESIZE, Some(rx_buffer));
+        }
+
+        self.rx_buffer.replace(rx_buffer);
+        self.rx_len.set(rx_len);
+        self.rx_index.set(0);
+        self.registers.ier.modify(IER::ERBFI::SET + IER::ELSI::SET);
+
+        (ReturnCode::SUCCESS, None)
+    }
+
+    fn receive_abort(&self) -> ReturnCode {
+        self.registers
+            .ier
+            .modify(IER::ERBFI::CLEAR + IER::ELSI::CLEAR);
+        match self.rx_buffer.take() {
+            Some(buffer) => {
+                self.rx_client.map(move |client| {
+                    client.received_buffer(
+                        buffer,
+                        self.rx_index.get(),
+                        ReturnCode::ECANCEL,
+                        hil::uart::Error::Aborted,
+                    );
+                });
+                ReturnCode::SUCCESS
+            }
+            None => ReturnCode::SUCCESS,
+        }
+    }
+
+    fn receive_word(&self) -> ReturnCode {
+        ReturnCode::FAIL
+    }
+}
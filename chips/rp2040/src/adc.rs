@@ -0,0 +1,269 @@
+//! ADC driver for the RP2040.
+//!
+//! The ADC is a 12 bit SAR converter clocked by the 48 MHz `clk_adc`, with
+//! four inputs on GPIO 26 to 29 and a fifth connected to the internal
+//! temperature sensor. Samples go through the result FIFO, which raises an
+//! interrupt for each of them. The pin of a channel must be deactivated
+//! (with `deactivate_to_low_power()`) before it is sampled.
+
+use core::cell::Cell;
+use kernel::common::cells::OptionalCell;
+use kernel::common::registers::{register_bitfields, ReadOnly, ReadWrite};
+use kernel::common::StaticRef;
+use kernel::hil;
+use kernel::ReturnCode;
+
+use crate::clocks;
+
+#[repr(C)]
+struct AdcRegisters {
+    /// ADC Control and Status
+    cs: ReadWrite<u32, CS::Register>,
+    /// Result of most recent ADC conversion
+    result: ReadOnly<u32>,
+    /// FIFO control and status
+    fcs: ReadWrite<u32, FCS::Register>,
+    /// Conversion result FIFO
+    fifo: ReadOnly<u32, FIFO::Register>,
+    /// Clock divider. If non-zero, CS_START_MANY will start conversions at
+    /// regular intervals rather than back-to-back.
+    div: ReadWrite<u32, DIV::Register>,
+    /// Raw Interrupts
+    intr: ReadOnly<u32, INTERRUPT::Register>,
+    /// Interrupt Enable
+    inte: ReadWrite<u32, INTERRUPT::Register>,
+    /// Interrupt Force
+    intf: ReadWrite<u32, INTERRUPT::Register>,
+    /// Interrupt status after masking & forcing
+    ints: ReadOnly<u32, INTERRUPT::Register>,
+}
+
+register_bitfields![u32,
+    CS [
+        /// Round-robin sampling, one bit per channel
+        RROBIN OFFSET(16) NUMBITS(5) [],
+        /// Select analog mux input
+        AINSEL OFFSET(12) NUMBITS(3) [],
+        /// Some past ADC conversion encountered an error
+        ERR_STICKY OFFSET(10) NUMBITS(1) [],
+        /// The most recent ADC conversion encountered an error
+        ERR OFFSET(9) NUMBITS(1) [],
+        /// The ADC is ready to start a new conversion
+        READY OFFSET(8) NUMBITS(1) [],
+        /// Continuously perform conversions whilst this bit is 1
+        START_MANY OFFSET(3) NUMBITS(1) [],
+        /// Start a single conversion
+        START_ONCE OFFSET(2) NUMBITS(1) [],
+        /// Power on temperature sensor
+        TS_EN OFFSET(1) NUMBITS(1) [],
+        /// Power on ADC and enable its clock
+        EN OFFSET(0) NUMBITS(1) []
+    ],
+    FCS [
+        /// DREQ/IRQ asserted when level >= threshold
+        THRESH OFFSET(24) NUMBITS(4) [],
+        /// The number of conversion results currently waiting in the FIFO
+        LEVEL OFFSET(16) NUMBITS(4) [],
+        /// The FIFO has overflowed
+        OVER OFFSET(11) NUMBITS(1) [],
+        /// The FIFO was read while empty
+        UNDER OFFSET(10) NUMBITS(1) [],
+        FULL OFFSET(9) NUMBITS(1) [],
+        EMPTY OFFSET(8) NUMBITS(1) [],
+        /// Assert DMA requests when FIFO contains data
+        DREQ_EN OFFSET(3) NUMBITS(1) [],
+        /// Keep the conversion error bit in bit 15 of each FIFO entry
+        ERR OFFSET(2) NUMBITS(1) [],
+        /// Right-shift results to 8 bits
+        SHIFT OFFSET(1) NUMBITS(1) [],
+        /// Write results to the FIFO
+        EN OFFSET(0) NUMBITS(1) []
+    ],
+    FIFO [
+        /// The conversion of this result encountered an error
+        ERR OFFSET(15) NUMBITS(1) [],
+        VAL OFFSET(0) NUMBITS(12) []
+    ],
+    DIV [
+        /// Integer part of clock divisor
+        INT OFFSET(8) NUMBITS(16) [],
+        /// Fractional part of clock divisor
+        FRAC OFFSET(0) NUMBITS(8) []
+    ],
+    INTERRUPT [
+        /// Triggered when the sample FIFO reaches a certain level
+        FIFO OFFSET(0) NUMBITS(1) []
+    ]
+];
+
+const ADC_BASE: StaticRef<AdcRegisters> =
+    unsafe { StaticRef::new(0x4004C000 as *const AdcRegisters) };
+
+/// The number of `clk_adc` cycles that a conversion takes
+const CYCLES_PER_SAMPLE: u32 = 96;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Channel {
+    Channel0 = 0,
+    Channel1 = 1,
+    Channel2 = 2,
+    Channel3 = 3,
+    /// The internal temperature sensor
+    Channel4 = 4,
+}
+
+pub struct Adc {
+    registers: StaticRef<AdcRegisters>,
+    client: OptionalCell<&'static hil::adc::Client>,
+    continuous: Cell<bool>,
+}
+
+pub static mut ADC: Adc = Adc::new();
+
+impl Adc {
+    const fn new() -> Adc {
+        Adc {
+            registers: ADC_BASE,
+            client: OptionalCell::empty(),
+            continuous: Cell::new(false),
+        }
+    }
+
+    pub fn set_client(&self, client: &'static hil::adc::Client) {
+        self.client.set(client);
+    }
+
+    /// Power on the ADC and wait until it is ready.
+    pub fn init(&self) {
+        self.registers.cs.write(CS::EN::SET);
+        while !self.registers.cs.is_set(CS::READY) {}
+    }
+
+    fn is_busy(&self) -> bool {
+        self.registers.inte.is_set(INTERRUPT::FIFO)
+    }
+
+    /// Select `channel`, and empty the FIFO and enable its interrupt for the
+    /// samples to come.
+    fn prepare(&self, channel: &Channel) {
+        let temperature = if *channel == Channel::Channel4 {
+            CS::TS_EN::SET
+        } else {
+            CS::TS_EN::CLEAR
+        };
+        self.registers
+            .cs
+            .write(CS::EN::SET + temperature + CS::AINSEL.val(*channel as u32));
+
+        self.registers.fcs.write(FCS::THRESH.val(1));
+        while !self.registers.fcs.is_set(FCS::EMPTY) {
+            self.registers.fifo.get();
+        }
+        self.registers
+            .fcs
+            .write(FCS::EN::SET + FCS::THRESH.val(1) + FCS::OVER::SET + FCS::UNDER::SET);
+        self.registers.inte.write(INTERRUPT::FIFO::SET);
+    }
+
+    pub fn handle_interrupt(&self) {
+        while !self.registers.fcs.is_set(FCS::EMPTY) {
+            let sample = self.registers.fifo.read(FIFO::VAL) as u16;
+            if !self.continuous.get() {
+                self.registers.inte.set(0);
+                self.registers.fcs.set(0);
+            }
+            // Left-justify the 12 bit sample, as the HIL expects
+            self.client.map(|client| client.sample_ready(sample << 4));
+            if !self.continuous.get() {
+                break;
+            }
+        }
+    }
+}
+
+impl hil::adc::Adc for Adc {
+    type Channel = Channel;
+
+    fn sample(&self, channel: &Self::Channel) -> ReturnCode {
+        if self.is_busy() {
+            return ReturnCode::EBUSY;
+        }
+        self.continuous.set(false);
+        self.prepare(channel);
+        self.registers.cs.modify(CS::START_ONCE::SET);
+        ReturnCode::SUCCESS
+    }
+
+    fn sample_continuous(&self, channel: &Self::Channel, frequency: u32) -> ReturnCode {
+        if self.is_busy() {
+            return ReturnCode::EBUSY;
+        }
+        if frequency == 0 || frequency > clocks::ADC_FREQUENCY / CYCLES_PER_SAMPLE {
+            return ReturnCode::EINVAL;
+        }
+        // A conversion starts every 1 + INT + FRAC / 256 cycles
+        let divisor = (clocks::ADC_FREQUENCY as u64 * 256 / frequency as u64) - 256;
+        if divisor >> 8 > 0xffff {
+            return ReturnCode::EINVAL;
+        }
+
+        self.continuous.set(true);
+        self.prepare(channel);
+        self.registers.div.set(divisor as u32);
+        self.registers.cs.modify(CS::START_MANY::SET);
+        ReturnCode::SUCCESS
+    }
+
+    fn stop_sampling(&self) -> ReturnCode {
+        self.registers.cs.modify(CS::START_MANY::CLEAR);
+        self.registers.inte.set(0);
+        self.registers.fcs.set(0);
+        self.continuous.set(false);
+        ReturnCode::SUCCESS
+    }
+
+    fn get_resolution_bits(&self) -> usize {
+        12
+    }
+
+    fn get_voltage_reference_mv(&self) -> Option<usize> {
+        Some(3300)
+    }
+}
+
+/// Not yet supported
+impl hil::adc::AdcHighSpeed for Adc {
+    fn sample_highspeed(
+        &self,
+        _channel: &Self::Channel,
+        _frequency: u32,
+        buffer1: &'static mut [u16],
+        _length1: usize,
+        buffer2: &'static mut [u16],
+        _length2: usize,
+    ) -> (
+        ReturnCode,
+        Option<&'static mut [u16]>,
+        Option<&'static mut [u16]>,
+    ) {
+        (ReturnCode::ENOSUPPORT, Some(buffer1), Some(buffer2))
+    }
+
+    fn provide_buffer(
+        &self,
+        buf: &'static mut [u16],
+        _length: usize,
+    ) -> (ReturnCode, Option<&'static mut [u16]>) {
+        (ReturnCode::ENOSUPPORT, Some(buf))
+    }
+
+    fn retrieve_buffers(
+        &self,
+    ) -> (
+        ReturnCode,
+        Option<&'static mut [u16]>,
+        Option<&'static mut [u16]>,
+    ) {
+        (ReturnCode::SUCCESS, None, None)
+    }
+}
@@ -0,0 +1,104 @@
+//! Chip trait setup.
+
+use cortexm0;
+use kernel::common::deferred_call;
+use kernel::Chip;
+
+use crate::adc;
+use crate::clocks;
+use crate::deferred_call_tasks::Task;
+use crate::dma;
+use crate::gpio;
+use crate::i2c;
+use crate::interrupts;
+use crate::spi;
+use crate::timer;
+use crate::uart;
+
+pub struct Rp2040 {
+    mpu: (),
+    userspace_kernel_boundary: cortexm0::syscall::SysCall,
+    systick: cortexm0::systick::SysTick,
+}
+
+impl Rp2040 {
+    pub unsafe fn new() -> Rp2040 {
+        Rp2040 {
+            mpu: (),
+            userspace_kernel_boundary: cortexm0::syscall::SysCall::new(),
+            // The SysTick calibration register of the RP2040 reads as zero
+            systick: cortexm0::systick::SysTick::new_with_calibration(clocks::SYS_FREQUENCY),
+        }
+    }
+}
+
+impl Chip for Rp2040 {
+    type MPU = ();
+    type UserspaceKernelBoundary = cortexm0::syscall::SysCall;
+    type SysTick = cortexm0::systick::SysTick;
+
+    fn service_pending_interrupts(&self) {
+        unsafe {
+            loop {
+                if let Some(task) = deferred_call::DeferredCall::next_pending() {
+                    match task {
+                        Task::Uart0 => uart::UART0.handle_deferred_call(),
+                        Task::Uart1 => uart::UART1.handle_deferred_call(),
+                    }
+                } else if let Some(interrupt) = cortexm0::nvic::next_pending() {
+                    match interrupt {
+                        interrupts::TIMER_IRQ_0 => timer::TIMER.handle_interrupt(),
+                        interrupts::DMA_IRQ_0 => dma::handle_interrupt(),
+                        interrupts::IO_IRQ_BANK0 => gpio::PORT.handle_interrupt(),
+                        interrupts::SPI0_IRQ => spi::SPI0.handle_interrupt(),
+                        interrupts::SPI1_IRQ => spi::SPI1.handle_interrupt(),
+                        interrupts::UART0_IRQ => uart::UART0.handle_interrupt(),
+                        interrupts::UART1_IRQ => uart::UART1.handle_interrupt(),
+                        interrupts::ADC_IRQ_FIFO => adc::ADC.handle_interrupt(),
+                        interrupts::I2C0_IRQ => i2c::I2C0.handle_interrupt(),
+                        interrupts::I2C1_IRQ => i2c::I2C1.handle_interrupt(),
+                        _ => {
+                            panic!("unhandled interrupt {}", interrupt);
+                        }
+                    }
+
+                    let n = cortexm0::nvic::Nvic::new(interrupt);
+                    n.clear_pending();
+                    n.enable();
+                } else {
+                    break;
+                }
+            }
+        }
+    }
+
+    fn has_pending_interrupts(&self) -> bool {
+        unsafe { cortexm0::nvic::has_pending() || deferred_call::has_tasks() }
+    }
+
+    fn mpu(&self) -> &() {
+        &self.mpu
+    }
+
+    fn systick(&self) -> &cortexm0::systick::SysTick {
+        &self.systick
+    }
+
+    fn userspace_kernel_boundary(&self) -> &cortexm0::syscall::SysCall {
+        &self.userspace_kernel_boundary
+    }
+
+    fn sleep(&self) {
+        unsafe {
+            cortexm0::scb::unset_sleepdeep();
+            cortexm0::support::wfi();
+        }
+    }
+
+    unsafe fn atomic<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce() -> R,
+    {
+        cortexm0::support::atomic(f)
+    }
+}
@@ -0,0 +1,253 @@
+//! Clock generators and PLLs.
+//!
+//! `Clocks::init()` sets up the clock tree that the rest of this crate
+//! assumes:
+//!
+//! - `clk_ref` runs from the 12 MHz crystal
+//! - `clk_sys` and `clk_peri` run from the system PLL at 125 MHz
+//! - `clk_usb` and `clk_adc` run from the USB PLL at 48 MHz
+//! - `clk_rtc` runs from the USB PLL divided down to 46875 Hz
+
+use kernel::common::registers::{register_bitfields, ReadOnly, ReadWrite};
+use kernel::common::StaticRef;
+
+use crate::resets;
+use crate::xosc;
+
+/// The frequency of `clk_sys`, in Hz.
+pub const SYS_FREQUENCY: u32 = 125_000_000;
+/// The frequency of `clk_peri`, which clocks the UARTs and SPI controllers,
+/// in Hz.
+pub const PERI_FREQUENCY: u32 = SYS_FREQUENCY;
+/// The frequency of `clk_usb`, in Hz.
+pub const USB_FREQUENCY: u32 = 48_000_000;
+/// The frequency of `clk_adc`, in Hz.
+pub const ADC_FREQUENCY: u32 = 48_000_000;
+/// The frequency of `clk_rtc`, in Hz.
+pub const RTC_FREQUENCY: u32 = 46875;
+
+#[repr(C)]
+struct ClockRegisters {
+    /// Clock control, can be changed on-the-fly (except for auxsrc)
+    ctrl: ReadWrite<u32, CLK_CTRL::Register>,
+    /// Clock divisor, can be changed on-the-fly
+    div: ReadWrite<u32, CLK_DIV::Register>,
+    /// Indicates which source is currently selected by the glitchless mux
+    /// (one-hot). Only meaningful for `clk_ref` and `clk_sys`.
+    selected: ReadOnly<u32>,
+}
+
+#[repr(C)]
+struct ClocksRegisters {
+    /// clk_gpout0 to clk_gpout3, clk_ref, clk_sys, clk_peri, clk_usb,
+    /// clk_adc and clk_rtc, in that order
+    clk: [ClockRegisters; 10],
+    /// Resus control for clk_sys
+    clk_sys_resus_ctrl: ReadWrite<u32>,
+    clk_sys_resus_status: ReadOnly<u32>,
+}
+
+register_bitfields![u32,
+    CLK_CTRL [
+        /// Starts and stops the clock generator cleanly
+        ENABLE OFFSET(11) NUMBITS(1) [],
+        /// Asynchronously kills the clock generator
+        KILL OFFSET(10) NUMBITS(1) [],
+        /// Selects the auxiliary clock source, will glitch when switching
+        AUXSRC OFFSET(5) NUMBITS(3) [],
+        /// Selects the clock source glitchlessly, can be changed on-the-fly.
+        /// Only implemented by `clk_ref` (2 bits) and `clk_sys` (1 bit).
+        SRC OFFSET(0) NUMBITS(2) []
+    ],
+    CLK_DIV [
+        /// Integer component of the divisor, 0 -> divide by 2^16
+        INT OFFSET(8) NUMBITS(24) [],
+        /// Fractional component of the divisor
+        FRAC OFFSET(0) NUMBITS(8) []
+    ]
+];
+
+#[repr(C)]
+struct PllRegisters {
+    /// Control and Status
+    cs: ReadWrite<u32, PLL_CS::Register>,
+    /// Controls the PLL power modes
+    pwr: ReadWrite<u32, PLL_PWR::Register>,
+    /// Feedback divisor
+    fbdiv_int: ReadWrite<u32>,
+    /// Controls the PLL post dividers for the primary output
+    prim: ReadWrite<u32, PLL_PRIM::Register>,
+}
+
+register_bitfields![u32,
+    PLL_CS [
+        /// PLL is locked
+        LOCK OFFSET(31) NUMBITS(1) [],
+        /// Passes the reference clock to the output instead of the divided
+        /// VCO
+        BYPASS OFFSET(8) NUMBITS(1) [],
+        /// Divides the PLL input reference clock
+        REFDIV OFFSET(0) NUMBITS(6) []
+    ],
+    PLL_PWR [
+        /// PLL VCO powerdown
+        VCOPD OFFSET(5) NUMBITS(1) [],
+        /// PLL post divider powerdown
+        POSTDIVPD OFFSET(3) NUMBITS(1) [],
+        /// PLL DSM powerdown
+        DSMPD OFFSET(2) NUMBITS(1) [],
+        /// PLL powerdown
+        PD OFFSET(0) NUMBITS(1) []
+    ],
+    PLL_PRIM [
+        /// divide by 1-7
+        POSTDIV1 OFFSET(16) NUMBITS(3) [],
+        /// divide by 1-7
+        POSTDIV2 OFFSET(12) NUMBITS(3) []
+    ]
+];
+
+const CLOCKS_BASE: StaticRef<ClocksRegisters> =
+    unsafe { StaticRef::new(0x40008000 as *const ClocksRegisters) };
+const PLL_SYS_BASE: StaticRef<PllRegisters> =
+    unsafe { StaticRef::new(0x40028000 as *const PllRegisters) };
+const PLL_USB_BASE: StaticRef<PllRegisters> =
+    unsafe { StaticRef::new(0x4002C000 as *const PllRegisters) };
+
+/// The clock generators, numbered by their position in the register block.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Clock {
+    GpOut0 = 0,
+    GpOut1 = 1,
+    GpOut2 = 2,
+    GpOut3 = 3,
+    Reference = 4,
+    System = 5,
+    Peripheral = 6,
+    Usb = 7,
+    Adc = 8,
+    Rtc = 9,
+}
+
+// Glitchless sources of clk_ref
+const REF_SRC_XOSC: u32 = 2;
+// Glitchless sources of clk_sys
+const SYS_SRC_REF: u32 = 0;
+const SYS_SRC_AUX: u32 = 1;
+// Auxiliary source of clk_sys, clk_usb, clk_adc and clk_rtc
+const SYS_AUXSRC_PLL_SYS: u32 = 0;
+const AUXSRC_PLL_USB: u32 = 0;
+// Auxiliary source of clk_peri
+const PERI_AUXSRC_CLK_SYS: u32 = 0;
+
+struct Pll {
+    registers: StaticRef<PllRegisters>,
+}
+
+impl Pll {
+    /// Configure the PLL to output `xosc / refdiv * fbdiv / postdiv1 /
+    /// postdiv2`, and wait until it is locked.
+    fn configure(&self, refdiv: u32, fbdiv: u32, postdiv1: u32, postdiv2: u32) {
+        // Power everything down before changing the dividers
+        self.registers.pwr.write(
+            PLL_PWR::VCOPD::SET + PLL_PWR::POSTDIVPD::SET + PLL_PWR::DSMPD::SET + PLL_PWR::PD::SET,
+        );
+        self.registers.fbdiv_int.set(0);
+
+        self.registers.cs.write(PLL_CS::REFDIV.val(refdiv));
+        self.registers.fbdiv_int.set(fbdiv);
+
+        // Turn on the PLL and its VCO, and wait for it to lock
+        self.registers
+            .pwr
+            .modify(PLL_PWR::PD::CLEAR + PLL_PWR::VCOPD::CLEAR);
+        while !self.registers.cs.is_set(PLL_CS::LOCK) {}
+
+        // Set up the post dividers and turn them on
+        self.registers
+            .prim
+            .write(PLL_PRIM::POSTDIV1.val(postdiv1) + PLL_PRIM::POSTDIV2.val(postdiv2));
+        self.registers.pwr.modify(PLL_PWR::POSTDIVPD::CLEAR);
+    }
+}
+
+pub struct Clocks {
+    registers: StaticRef<ClocksRegisters>,
+    pll_sys: Pll,
+    pll_usb: Pll,
+}
+
+pub static mut CLOCKS: Clocks = Clocks::new();
+
+impl Clocks {
+    const fn new() -> Clocks {
+        Clocks {
+            registers: CLOCKS_BASE,
+            pll_sys: Pll {
+                registers: PLL_SYS_BASE,
+            },
+            pll_usb: Pll {
+                registers: PLL_USB_BASE,
+            },
+        }
+    }
+
+    fn clock(&self, clock: Clock) -> &ClockRegisters {
+        &self.registers.clk[clock as usize]
+    }
+
+    /// Switch a glitchless clock (`clk_ref` or `clk_sys`) to `src` and wait
+    /// until the switch is done.
+    fn select_glitchless(&self, clock: Clock, src: u32) {
+        let clk = self.clock(clock);
+        clk.ctrl.modify(CLK_CTRL::SRC.val(src));
+        while clk.selected.get() & (1 << src) == 0 {}
+    }
+
+    /// Stop an auxiliary clock, switch its source and divisor, and start it
+    /// again.
+    fn configure_auxiliary(&self, clock: Clock, auxsrc: u32, div: u32) {
+        let clk = self.clock(clock);
+        clk.ctrl.modify(CLK_CTRL::ENABLE::CLEAR);
+        clk.ctrl.modify(CLK_CTRL::AUXSRC.val(auxsrc));
+        clk.div.write(CLK_DIV::INT.val(div));
+        clk.ctrl.modify(CLK_CTRL::ENABLE::SET);
+    }
+
+    /// Start the crystal and the PLLs, and set up the clock tree described
+    /// in the module documentation.
+    pub unsafe fn init(&self) {
+        self.registers.clk_sys_resus_ctrl.set(0);
+
+        xosc::XOSC.init();
+
+        // Run the processors and the reference clock from the ring
+        // oscillator while the PLLs are reconfigured
+        self.select_glitchless(Clock::System, SYS_SRC_REF);
+        self.select_glitchless(Clock::Reference, 0);
+
+        resets::RESETS.reset_all(&[resets::Peripheral::PllSys, resets::Peripheral::PllUsb]);
+        // 12 MHz * 125 = 1500 MHz, / 6 / 2 = 125 MHz
+        self.pll_sys.configure(1, 125, 6, 2);
+        // 12 MHz * 100 = 1200 MHz, / 5 / 5 = 48 MHz
+        self.pll_usb.configure(1, 100, 5, 5);
+
+        let clk_ref = self.clock(Clock::Reference);
+        clk_ref.div.write(CLK_DIV::INT.val(1));
+        self.select_glitchless(Clock::Reference, REF_SRC_XOSC);
+
+        // The auxiliary mux of clk_sys may only be changed while clk_sys
+        // runs from clk_ref
+        let clk_sys = self.clock(Clock::System);
+        clk_sys.div.write(CLK_DIV::INT.val(1));
+        clk_sys
+            .ctrl
+            .modify(CLK_CTRL::AUXSRC.val(SYS_AUXSRC_PLL_SYS));
+        self.select_glitchless(Clock::System, SYS_SRC_AUX);
+
+        self.configure_auxiliary(Clock::Usb, AUXSRC_PLL_USB, 1);
+        self.configure_auxiliary(Clock::Adc, AUXSRC_PLL_USB, 1);
+        self.configure_auxiliary(Clock::Rtc, AUXSRC_PLL_USB, USB_FREQUENCY / RTC_FREQUENCY);
+        self.configure_auxiliary(Clock::Peripheral, PERI_AUXSRC_CLK_SYS, 1);
+    }
+}
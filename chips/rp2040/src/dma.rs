@@ -0,0 +1,282 @@
+//! DMA driver for the RP2040.
+//!
+//! The DMA controller has 12 independent channels. Here each channel moves
+//! a buffer between memory and the data register of one peripheral, paced
+//! by the peripheral's data request (DREQ) signal, and all channels report
+//! their completion on `DMA_IRQ_0`.
+
+use core::cell::Cell;
+use core::cmp;
+use kernel::common::cells::{OptionalCell, TakeCell};
+use kernel::common::registers::{register_bitfields, ReadWrite};
+use kernel::common::StaticRef;
+
+const NUM_CHANNELS: usize = 12;
+
+#[repr(C)]
+struct DmaChannelRegisters {
+    /// DMA Channel Read Address pointer
+    read_addr: ReadWrite<u32>,
+    /// DMA Channel Write Address pointer
+    write_addr: ReadWrite<u32>,
+    /// DMA Channel Transfer Count
+    trans_count: ReadWrite<u32>,
+    /// DMA Channel Control and Status
+    ctrl_trig: ReadWrite<u32, CTRL::Register>,
+    /// Alias registers, which trigger the channel in different ways
+    _aliases: [u32; 12],
+}
+
+#[repr(C)]
+struct DmaRegisters {
+    channels: [DmaChannelRegisters; NUM_CHANNELS],
+    _reserved0: [u32; 64],
+    /// Interrupt Status (raw)
+    intr: ReadWrite<u32>,
+    /// Interrupt Enables for IRQ 0
+    inte0: ReadWrite<u32>,
+    /// Force Interrupts
+    intf0: ReadWrite<u32>,
+    /// Interrupt Status for IRQ 0, write 1 to clear
+    ints0: ReadWrite<u32>,
+    _reserved1: u32,
+    /// Interrupt Enables for IRQ 1
+    inte1: ReadWrite<u32>,
+    /// Force Interrupts for IRQ 1
+    intf1: ReadWrite<u32>,
+    /// Interrupt Status for IRQ 1
+    ints1: ReadWrite<u32>,
+    /// Pacing (X/Y) Fractional Timers
+    timer: [ReadWrite<u32>; 4],
+    /// Trigger one or more channels simultaneously
+    multi_chan_trigger: ReadWrite<u32>,
+    /// Sniffer control and data
+    _sniff: [u32; 2],
+    _reserved2: u32,
+    /// Debug RAF, WAF, TDF levels
+    fifo_levels: ReadWrite<u32>,
+    /// Abort an in-progress transfer sequence on one or more channels
+    chan_abort: ReadWrite<u32>,
+}
+
+register_bitfields![u32,
+    CTRL [
+        /// A bus error occurred on the read or write
+        AHB_ERROR OFFSET(31) NUMBITS(1) [],
+        READ_ERROR OFFSET(30) NUMBITS(1) [],
+        WRITE_ERROR OFFSET(29) NUMBITS(1) [],
+        /// This channel is actively performing transfers
+        BUSY OFFSET(24) NUMBITS(1) [],
+        /// Select a Transfer Request signal
+        TREQ_SEL OFFSET(15) NUMBITS(6) [
+            Permanent = 0x3f
+        ],
+        /// When this channel completes, it will trigger the channel
+        /// indicated by CHAIN_TO. Set to this channel's own number to disable.
+        CHAIN_TO OFFSET(11) NUMBITS(4) [],
+        /// If 1, the write address increments with each transfer
+        INCR_WRITE OFFSET(5) NUMBITS(1) [],
+        /// If 1, the read address increments with each transfer
+        INCR_READ OFFSET(4) NUMBITS(1) [],
+        /// Set the size of each bus transfer
+        DATA_SIZE OFFSET(2) NUMBITS(2) [],
+        /// Give this channel priority over low priority channels
+        HIGH_PRIORITY OFFSET(1) NUMBITS(1) [],
+        /// DMA Channel Enable
+        EN OFFSET(0) NUMBITS(1) []
+    ]
+];
+
+const DMA_BASE: StaticRef<DmaRegisters> =
+    unsafe { StaticRef::new(0x50000000 as *const DmaRegisters) };
+
+/// The peripheral function a channel is assigned to, numbered by its DREQ.
+/// `*_RX` means transfer data from peripheral to memory, `*_TX` means
+/// transfer data from memory to peripheral.
+#[allow(non_camel_case_types)]
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum DMAPeripheral {
+    SPI0_TX = 16,
+    SPI0_RX = 17,
+    SPI1_TX = 18,
+    SPI1_RX = 19,
+    UART0_TX = 20,
+    UART0_RX = 21,
+    UART1_TX = 22,
+    UART1_RX = 23,
+    I2C0_TX = 32,
+    I2C0_RX = 33,
+    I2C1_TX = 34,
+    I2C1_RX = 35,
+    ADC_RX = 36,
+}
+
+impl DMAPeripheral {
+    /// The address of the data register of the peripheral.
+    fn data_register(&self) -> u32 {
+        match *self {
+            DMAPeripheral::SPI0_TX | DMAPeripheral::SPI0_RX => 0x4003C008,
+            DMAPeripheral::SPI1_TX | DMAPeripheral::SPI1_RX => 0x40040008,
+            DMAPeripheral::UART0_TX | DMAPeripheral::UART0_RX => 0x40034000,
+            DMAPeripheral::UART1_TX | DMAPeripheral::UART1_RX => 0x40038000,
+            DMAPeripheral::I2C0_TX | DMAPeripheral::I2C0_RX => 0x40044010,
+            DMAPeripheral::I2C1_TX | DMAPeripheral::I2C1_RX => 0x40048010,
+            DMAPeripheral::ADC_RX => 0x4004C00C,
+        }
+    }
+
+    fn is_transmit(&self) -> bool {
+        match *self {
+            DMAPeripheral::SPI0_TX
+            | DMAPeripheral::SPI1_TX
+            | DMAPeripheral::UART0_TX
+            | DMAPeripheral::UART1_TX
+            | DMAPeripheral::I2C0_TX
+            | DMAPeripheral::I2C1_TX => true,
+            _ => false,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum DMAWidth {
+    Width8Bit = 0,
+    Width16Bit = 1,
+    Width32Bit = 2,
+}
+
+pub static mut DMA_CHANNELS: [DMAChannel; NUM_CHANNELS] = [
+    DMAChannel::new(0),
+    DMAChannel::new(1),
+    DMAChannel::new(2),
+    DMAChannel::new(3),
+    DMAChannel::new(4),
+    DMAChannel::new(5),
+    DMAChannel::new(6),
+    DMAChannel::new(7),
+    DMAChannel::new(8),
+    DMAChannel::new(9),
+    DMAChannel::new(10),
+    DMAChannel::new(11),
+];
+
+pub trait DMAClient {
+    fn transfer_done(&self, pid: DMAPeripheral);
+}
+
+pub struct DMAChannel {
+    channel: usize,
+    registers: StaticRef<DmaRegisters>,
+    client: OptionalCell<&'static DMAClient>,
+    width: Cell<DMAWidth>,
+    peripheral: OptionalCell<DMAPeripheral>,
+    buffer: TakeCell<'static, [u8]>,
+}
+
+impl DMAChannel {
+    const fn new(channel: usize) -> DMAChannel {
+        DMAChannel {
+            channel: channel,
+            registers: DMA_BASE,
+            client: OptionalCell::empty(),
+            width: Cell::new(DMAWidth::Width8Bit),
+            peripheral: OptionalCell::empty(),
+            buffer: TakeCell::empty(),
+        }
+    }
+
+    pub fn initialize(&self, client: &'static DMAClient, width: DMAWidth) {
+        self.client.set(client);
+        self.width.set(width);
+    }
+
+    fn channel_registers(&self) -> &DmaChannelRegisters {
+        &self.registers.channels[self.channel]
+    }
+
+    pub fn is_busy(&self) -> bool {
+        self.channel_registers().ctrl_trig.is_set(CTRL::BUSY)
+    }
+
+    fn handle_interrupt(&self) {
+        self.peripheral.map(|pid| {
+            let pid = *pid;
+            self.client.map(|client| {
+                client.transfer_done(pid);
+            });
+        });
+    }
+
+    /// Set up the channel to move `len` items of the channel's width between
+    /// `buf` and `pid`, in the direction given by `pid`, without starting it.
+    pub fn prepare_transfer(&self, pid: DMAPeripheral, buf: &'static mut [u8], len: usize) {
+        let width = self.width.get();
+        let item_size = 1 << (width as usize);
+        let len = cmp::min(len, buf.len() / item_size);
+
+        let registers = self.channel_registers();
+        let buffer_address = buf.as_ptr() as u32;
+        let (read_addr, write_addr, increment) = if pid.is_transmit() {
+            (buffer_address, pid.data_register(), CTRL::INCR_READ::SET)
+        } else {
+            (pid.data_register(), buffer_address, CTRL::INCR_WRITE::SET)
+        };
+        registers.read_addr.set(read_addr);
+        registers.write_addr.set(write_addr);
+        registers.trans_count.set(len as u32);
+        registers.ctrl_trig.write(
+            CTRL::TREQ_SEL.val(pid as u32)
+                + CTRL::CHAIN_TO.val(self.channel as u32)
+                + CTRL::DATA_SIZE.val(width as u32)
+                + increment,
+        );
+
+        self.registers
+            .inte0
+            .set(self.registers.inte0.get() | (1 << self.channel));
+        self.peripheral.set(pid);
+        // Store the buffer reference so it can be returned to the caller in
+        // `abort_transfer`
+        self.buffer.replace(buf);
+    }
+
+    pub fn start_transfer(&self) {
+        self.channel_registers().ctrl_trig.modify(CTRL::EN::SET);
+    }
+
+    pub fn do_transfer(&self, pid: DMAPeripheral, buf: &'static mut [u8], len: usize) {
+        self.prepare_transfer(pid, buf, len);
+        self.start_transfer();
+    }
+
+    /// Aborts any current transactions and returns the buffer used in the
+    /// transaction.
+    pub fn abort_transfer(&self) -> Option<&'static mut [u8]> {
+        let mask = 1 << self.channel;
+        self.registers.inte0.set(self.registers.inte0.get() & !mask);
+        self.channel_registers().ctrl_trig.modify(CTRL::EN::CLEAR);
+        self.registers.chan_abort.set(mask);
+        while self.registers.chan_abort.get() & mask != 0 {}
+        self.registers.ints0.set(mask);
+
+        self.buffer.take()
+    }
+
+    /// The number of items that remain to be transferred.
+    pub fn transfer_counter(&self) -> usize {
+        self.channel_registers().trans_count.get() as usize
+    }
+}
+
+/// `DMA_IRQ_0` interrupt: acknowledge each channel that completed, and call
+/// its client.
+pub unsafe fn handle_interrupt() {
+    let registers = DMA_BASE;
+    let pending = registers.ints0.get();
+    registers.ints0.set(pending);
+    for (i, channel) in DMA_CHANNELS.iter().enumerate() {
+        if pending & (1 << i) != 0 {
+            channel.handle_interrupt();
+        }
+    }
+}
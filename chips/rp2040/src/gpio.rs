@@ -0,0 +1,369 @@
+//! GPIO pins, driven through the SIO.
+//!
+//! Each of the 30 user pins has a pad, configured in `PADS_BANK0`, and a
+//! function select and interrupt logic, in `IO_BANK0`. A pin is a GPIO when
+//! its function is `GpioFunction::Sio`, which the `hil::gpio` implementation
+//! selects; peripherals such as the UARTs are connected with `set_function()`.
+
+use core::ops::{Index, IndexMut};
+use kernel::common::cells::OptionalCell;
+use kernel::common::registers::{register_bitfields, ReadOnly, ReadWrite};
+use kernel::common::StaticRef;
+use kernel::hil;
+
+use crate::sio::Sio;
+
+const NUM_PINS: usize = 30;
+
+#[repr(C)]
+struct GpioStatusControl {
+    /// GPIO status
+    status: ReadOnly<u32>,
+    /// GPIO control including function select and overrides
+    ctrl: ReadWrite<u32, GPIO_CTRL::Register>,
+}
+
+#[repr(C)]
+struct IoBankRegisters {
+    gpio: [GpioStatusControl; NUM_PINS],
+    /// Raw interrupts, 4 bits for each of 8 pins per register
+    intr: [ReadWrite<u32>; 4],
+    /// Interrupt enable for proc0
+    proc0_inte: [ReadWrite<u32>; 4],
+    /// Interrupt force for proc0
+    proc0_intf: [ReadWrite<u32>; 4],
+    /// Interrupt status after masking & forcing for proc0
+    proc0_ints: [ReadOnly<u32>; 4],
+}
+
+#[repr(C)]
+struct PadsRegisters {
+    /// Voltage select, per bank control
+    voltage_select: ReadWrite<u32>,
+    gpio: [ReadWrite<u32, GPIO_PAD::Register>; NUM_PINS],
+}
+
+register_bitfields![u32,
+    GPIO_CTRL [
+        /// Interrupt override
+        IRQOVER OFFSET(28) NUMBITS(2) [],
+        /// Peripheral input override
+        INOVER OFFSET(16) NUMBITS(2) [],
+        /// Output enable override
+        OEOVER OFFSET(12) NUMBITS(2) [],
+        /// Output override
+        OUTOVER OFFSET(8) NUMBITS(2) [],
+        /// Function select
+        FUNCSEL OFFSET(0) NUMBITS(5) []
+    ],
+    GPIO_PAD [
+        /// Output disable. Has priority over output enable from peripherals
+        OD OFFSET(7) NUMBITS(1) [],
+        /// Input enable
+        IE OFFSET(6) NUMBITS(1) [],
+        /// Drive strength
+        DRIVE OFFSET(4) NUMBITS(2) [
+            Drive2mA = 0,
+            Drive4mA = 1,
+            Drive8mA = 2,
+            Drive12mA = 3
+        ],
+        /// Pull up enable
+        PUE OFFSET(3) NUMBITS(1) [],
+        /// Pull down enable
+        PDE OFFSET(2) NUMBITS(1) [],
+        /// Enable schmitt trigger
+        SCHMITT OFFSET(1) NUMBITS(1) [],
+        /// Slew rate control. 1 = Fast, 0 = Slow
+        SLEWFAST OFFSET(0) NUMBITS(1) []
+    ]
+];
+
+const IO_BANK0_BASE: StaticRef<IoBankRegisters> =
+    unsafe { StaticRef::new(0x40014000 as *const IoBankRegisters) };
+const PADS_BANK0_BASE: StaticRef<PadsRegisters> =
+    unsafe { StaticRef::new(0x4001C000 as *const PadsRegisters) };
+
+// The interrupt bits of a pin within its `intr`, `inte` and `ints` nibble
+const INTR_EDGE_LOW: u32 = 1 << 2;
+const INTR_EDGE_HIGH: u32 = 1 << 3;
+
+/// The peripheral functions that a pin can be connected to. Which instance
+/// of a peripheral, and which of its signals, depends on the pin; see
+/// section 2.19.2 of the RP2040 datasheet.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum GpioFunction {
+    Spi = 1,
+    Uart = 2,
+    I2c = 3,
+    Pwm = 4,
+    Sio = 5,
+    Pio0 = 6,
+    Pio1 = 7,
+    Gpck = 8,
+    Usb = 9,
+    Null = 0x1f,
+}
+
+pub struct GpioPin {
+    pin: usize,
+    client: OptionalCell<&'static hil::gpio::Client>,
+    io_bank: StaticRef<IoBankRegisters>,
+    pads: StaticRef<PadsRegisters>,
+    sio: Sio,
+}
+
+impl GpioPin {
+    const fn new(pin: usize) -> GpioPin {
+        GpioPin {
+            pin: pin,
+            client: OptionalCell::empty(),
+            io_bank: IO_BANK0_BASE,
+            pads: PADS_BANK0_BASE,
+            sio: Sio::new(),
+        }
+    }
+
+    /// The bit of the pin in the SIO registers
+    fn mask(&self) -> u32 {
+        1 << self.pin
+    }
+
+    /// The interrupt register of the pin, and the offset of its bits in it
+    fn interrupt_position(&self) -> (usize, u32) {
+        (self.pin / 8, (self.pin % 8) as u32 * 4)
+    }
+
+    /// Connect the pin to a peripheral, or to the SIO for use as a GPIO.
+    pub fn set_function(&self, function: GpioFunction) {
+        self.pads.gpio[self.pin].modify(GPIO_PAD::OD::CLEAR + GPIO_PAD::IE::SET);
+        self.io_bank.gpio[self.pin]
+            .ctrl
+            .write(GPIO_CTRL::FUNCSEL.val(function as u32));
+    }
+
+    pub fn get_function(&self) -> u32 {
+        self.io_bank.gpio[self.pin].ctrl.read(GPIO_CTRL::FUNCSEL)
+    }
+
+    fn is_sio(&self) -> bool {
+        self.get_function() == GpioFunction::Sio as u32
+    }
+
+    fn handle_interrupt(&self) {
+        self.client.map(|client| {
+            client.fired();
+        });
+    }
+}
+
+impl hil::gpio::Configure for GpioPin {
+    fn set_floating_state(&self, mode: hil::gpio::FloatingState) {
+        let pad = &self.pads.gpio[self.pin];
+        match mode {
+            hil::gpio::FloatingState::PullUp => {
+                pad.modify(GPIO_PAD::PUE::SET + GPIO_PAD::PDE::CLEAR)
+            }
+            hil::gpio::FloatingState::PullDown => {
+                pad.modify(GPIO_PAD::PUE::CLEAR + GPIO_PAD::PDE::SET)
+            }
+            hil::gpio::FloatingState::PullNone => {
+                pad.modify(GPIO_PAD::PUE::CLEAR + GPIO_PAD::PDE::CLEAR)
+            }
+        }
+    }
+
+    fn floating_state(&self) -> hil::gpio::FloatingState {
+        let pad = &self.pads.gpio[self.pin];
+        if pad.is_set(GPIO_PAD::PUE) {
+            hil::gpio::FloatingState::PullUp
+        } else if pad.is_set(GPIO_PAD::PDE) {
+            hil::gpio::FloatingState::PullDown
+        } else {
+            hil::gpio::FloatingState::PullNone
+        }
+    }
+
+    fn make_output(&self) -> hil::gpio::Configuration {
+        if !self.is_sio() {
+            self.set_function(GpioFunction::Sio);
+        }
+        self.sio.set_gpio_oe(self.mask());
+        hil::gpio::Configuration::Output
+    }
+
+    fn disable_output(&self) -> hil::gpio::Configuration {
+        self.sio.clear_gpio_oe(self.mask());
+        self.configuration()
+    }
+
+    fn make_input(&self) -> hil::gpio::Configuration {
+        if !self.is_sio() {
+            self.set_function(GpioFunction::Sio);
+        }
+        self.pads.gpio[self.pin].modify(GPIO_PAD::IE::SET);
+        self.sio.clear_gpio_oe(self.mask());
+        hil::gpio::Configuration::Input
+    }
+
+    fn disable_input(&self) -> hil::gpio::Configuration {
+        self.pads.gpio[self.pin].modify(GPIO_PAD::IE::CLEAR);
+        self.configuration()
+    }
+
+    fn configuration(&self) -> hil::gpio::Configuration {
+        if !self.is_sio() {
+            return hil::gpio::Configuration::Function;
+        }
+        let output = self.sio.read_gpio_oe() & self.mask() != 0;
+        let input = self.pads.gpio[self.pin].is_set(GPIO_PAD::IE);
+        match (input, output) {
+            (true, true) => hil::gpio::Configuration::InputOutput,
+            (false, true) => hil::gpio::Configuration::Output,
+            (true, false) => hil::gpio::Configuration::Input,
+            (false, false) => hil::gpio::Configuration::LowPower,
+        }
+    }
+
+    /// Disconnect the pin from every function and disable its pad. This is
+    /// also how a pin is prepared for use as an ADC input.
+    fn deactivate_to_low_power(&self) {
+        self.sio.clear_gpio_oe(self.mask());
+        self.io_bank.gpio[self.pin]
+            .ctrl
+            .write(GPIO_CTRL::FUNCSEL.val(GpioFunction::Null as u32));
+        self.pads.gpio[self.pin].modify(
+            GPIO_PAD::OD::SET + GPIO_PAD::IE::CLEAR + GPIO_PAD::PUE::CLEAR + GPIO_PAD::PDE::CLEAR,
+        );
+    }
+}
+
+impl hil::gpio::Input for GpioPin {
+    fn read(&self) -> bool {
+        self.sio.read_gpio_in() & self.mask() != 0
+    }
+}
+
+impl hil::gpio::Output for GpioPin {
+    fn set(&self) {
+        self.sio.set_gpio_out(self.mask());
+    }
+
+    fn clear(&self) {
+        self.sio.clear_gpio_out(self.mask());
+    }
+
+    fn toggle(&self) -> bool {
+        self.sio.toggle_gpio_out(self.mask());
+        self.sio.read_gpio_out() & self.mask() != 0
+    }
+}
+
+impl hil::gpio::Pin for GpioPin {}
+
+impl hil::gpio::Interrupt for GpioPin {
+    fn set_client(&self, client: &'static hil::gpio::Client) {
+        self.client.set(client);
+    }
+
+    fn is_pending(&self) -> bool {
+        let (register, shift) = self.interrupt_position();
+        self.io_bank.proc0_ints[register].get() & (0xf << shift) != 0
+    }
+
+    fn enable_interrupts(&self, mode: hil::gpio::InterruptEdge) {
+        let (register, shift) = self.interrupt_position();
+        let events = match mode {
+            hil::gpio::InterruptEdge::RisingEdge => INTR_EDGE_HIGH,
+            hil::gpio::InterruptEdge::FallingEdge => INTR_EDGE_LOW,
+            hil::gpio::InterruptEdge::EitherEdge => INTR_EDGE_HIGH | INTR_EDGE_LOW,
+        };
+        // Forget edges seen before the interrupt was enabled
+        self.io_bank.intr[register].set((INTR_EDGE_HIGH | INTR_EDGE_LOW) << shift);
+        let inte = &self.io_bank.proc0_inte[register];
+        inte.set((inte.get() & !(0xf << shift)) | (events << shift));
+    }
+
+    fn disable_interrupts(&self) {
+        let (register, shift) = self.interrupt_position();
+        let inte = &self.io_bank.proc0_inte[register];
+        inte.set(inte.get() & !(0xf << shift));
+    }
+}
+
+impl hil::gpio::InterruptPin for GpioPin {}
+
+pub struct Port {
+    pins: [GpioPin; NUM_PINS],
+}
+
+impl Index<usize> for Port {
+    type Output = GpioPin;
+
+    fn index(&self, index: usize) -> &GpioPin {
+        &self.pins[index]
+    }
+}
+
+impl IndexMut<usize> for Port {
+    fn index_mut(&mut self, index: usize) -> &mut GpioPin {
+        &mut self.pins[index]
+    }
+}
+
+impl Port {
+    /// IO_IRQ_BANK0 interrupt: clear the edges that are pending for core 0,
+    /// and call the handler of each pin that has one.
+    pub fn handle_interrupt(&self) {
+        let io_bank = &*self.pins[0].io_bank;
+
+        for (register, ints) in io_bank.proc0_ints.iter().enumerate() {
+            let pending = ints.get();
+            if pending == 0 {
+                continue;
+            }
+            // Only edge events are latched, and they are write-1-to-clear
+            io_bank.intr[register].set(pending & 0xcccc_cccc);
+            for pin in 0..8 {
+                if pending & (0xf << (pin * 4)) != 0 {
+                    self.pins[register * 8 + pin].handle_interrupt();
+                }
+            }
+        }
+    }
+}
+
+pub static mut PORT: Port = Port {
+    pins: [
+        GpioPin::new(0),
+        GpioPin::new(1),
+        GpioPin::new(2),
+        GpioPin::new(3),
+        GpioPin::new(4),
+        GpioPin::new(5),
+        GpioPin::new(6),
+        GpioPin::new(7),
+        GpioPin::new(8),
+        GpioPin::new(9),
+        GpioPin::new(10),
+        GpioPin::new(11),
+        GpioPin::new(12),
+        GpioPin::new(13),
+        GpioPin::new(14),
+        GpioPin::new(15),
+        GpioPin::new(16),
+        GpioPin::new(17),
+        GpioPin::new(18),
+        GpioPin::new(19),
+        GpioPin::new(20),
+        GpioPin::new(21),
+        GpioPin::new(22),
+        GpioPin::new(23),
+        GpioPin::new(24),
+        GpioPin::new(25),
+        GpioPin::new(26),
+        GpioPin::new(27),
+        GpioPin::new(28),
+        GpioPin::new(29),
+    ],
+};
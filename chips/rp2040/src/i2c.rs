@@ -0,0 +1,377 @@
+//! I2C master driver for the Synopsys DW_apb_i2c controllers of the RP2040.
+//!
+//! A transfer queues one command per byte in the 16 entry transmit FIFO: the
+//! bytes to write, then one read command for each byte to read, with a
+//! restart between the two and a stop after the last. The controller holds
+//! the clock while the receive FIFO is full, so the FIFOs are serviced from
+//! the interrupt at whatever pace it runs. The transfer ends with the stop
+//! condition, which the controller also sends after an abort.
+
+use core::cell::Cell;
+use kernel::common::cells::{OptionalCell, TakeCell};
+use kernel::common::registers::{register_bitfields, ReadOnly, ReadWrite};
+use kernel::common::StaticRef;
+use kernel::hil;
+use kernel::hil::i2c;
+
+use crate::clocks;
+
+/// The depth of the transmit FIFO
+const TX_FIFO_DEPTH: u32 = 16;
+
+#[repr(C)]
+pub struct I2cRegisters {
+    /// I2C Control Register
+    ic_con: ReadWrite<u32, IC_CON::Register>,
+    /// I2C Target Address Register
+    ic_tar: ReadWrite<u32>,
+    /// I2C Slave Address Register
+    ic_sar: ReadWrite<u32>,
+    _reserved0: u32,
+    /// I2C Rx/Tx Data Buffer and Command Register
+    ic_data_cmd: ReadWrite<u32, IC_DATA_CMD::Register>,
+    /// Standard Speed I2C Clock SCL High Count Register
+    ic_ss_scl_hcnt: ReadWrite<u32>,
+    /// Standard Speed I2C Clock SCL Low Count Register
+    ic_ss_scl_lcnt: ReadWrite<u32>,
+    /// Fast Mode or Fast Mode Plus I2C Clock SCL High Count Register
+    ic_fs_scl_hcnt: ReadWrite<u32>,
+    /// Fast Mode or Fast Mode Plus I2C Clock SCL Low Count Register
+    ic_fs_scl_lcnt: ReadWrite<u32>,
+    _reserved1: [u32; 2],
+    /// I2C Interrupt Status Register
+    ic_intr_stat: ReadOnly<u32, INTERRUPT::Register>,
+    /// I2C Interrupt Mask Register
+    ic_intr_mask: ReadWrite<u32, INTERRUPT::Register>,
+    /// I2C Raw Interrupt Status Register
+    ic_raw_intr_stat: ReadOnly<u32, INTERRUPT::Register>,
+    /// I2C Receive FIFO Threshold Register
+    ic_rx_tl: ReadWrite<u32>,
+    /// I2C Transmit FIFO Threshold Register
+    ic_tx_tl: ReadWrite<u32>,
+    /// Clear Combined and Individual Interrupt Register
+    ic_clr_intr: ReadOnly<u32>,
+    /// Clear RX_UNDER Interrupt Register
+    ic_clr_rx_under: ReadOnly<u32>,
+    /// Clear RX_OVER Interrupt Register
+    ic_clr_rx_over: ReadOnly<u32>,
+    /// Clear TX_OVER Interrupt Register
+    ic_clr_tx_over: ReadOnly<u32>,
+    /// Clear RD_REQ Interrupt Register
+    ic_clr_rd_req: ReadOnly<u32>,
+    /// Clear TX_ABRT Interrupt Register
+    ic_clr_tx_abrt: ReadOnly<u32>,
+    /// Clear RX_DONE Interrupt Register
+    ic_clr_rx_done: ReadOnly<u32>,
+    /// Clear ACTIVITY Interrupt Register
+    ic_clr_activity: ReadOnly<u32>,
+    /// Clear STOP_DET Interrupt Register
+    ic_clr_stop_det: ReadOnly<u32>,
+    /// Clear START_DET Interrupt Register
+    ic_clr_start_det: ReadOnly<u32>,
+    /// Clear GEN_CALL Interrupt Register
+    ic_clr_gen_call: ReadOnly<u32>,
+    /// I2C Enable Register
+    ic_enable: ReadWrite<u32, IC_ENABLE::Register>,
+    /// I2C Status Register
+    ic_status: ReadOnly<u32>,
+    /// I2C Transmit FIFO Level Register
+    ic_txflr: ReadOnly<u32>,
+    /// I2C Receive FIFO Level Register
+    ic_rxflr: ReadOnly<u32>,
+    /// I2C SDA Hold Time Length Register
+    ic_sda_hold: ReadWrite<u32>,
+    /// I2C Transmit Abort Source Register
+    ic_tx_abrt_source: ReadOnly<u32, IC_TX_ABRT_SOURCE::Register>,
+    /// Generate Slave Data NACK Register
+    ic_slv_data_nack_only: ReadWrite<u32>,
+    /// DMA Control Register
+    ic_dma_cr: ReadWrite<u32>,
+    /// DMA Transmit Data Level Register
+    ic_dma_tdlr: ReadWrite<u32>,
+    /// DMA Receive Data Level Register
+    ic_dma_rdlr: ReadWrite<u32>,
+    /// I2C SDA Setup Register
+    ic_sda_setup: ReadWrite<u32>,
+    /// I2C ACK General Call Register
+    ic_ack_general_call: ReadWrite<u32>,
+    /// I2C Enable Status Register
+    ic_enable_status: ReadOnly<u32>,
+    /// I2C SS, FS or FM+ spike suppression limit
+    ic_fs_spklen: ReadWrite<u32>,
+}
+
+register_bitfields![u32,
+    IC_CON [
+        /// Hold the bus when the receive FIFO is full
+        RX_FIFO_FULL_HLD_CTRL OFFSET(9) NUMBITS(1) [],
+        /// Generate the TX_EMPTY interrupt only when the transmit FIFO is
+        /// empty and the last command has been sent
+        TX_EMPTY_CTRL OFFSET(8) NUMBITS(1) [],
+        /// Disables the slave
+        IC_SLAVE_DISABLE OFFSET(6) NUMBITS(1) [],
+        /// Allows RESTART conditions to be sent when acting as a master
+        IC_RESTART_EN OFFSET(5) NUMBITS(1) [],
+        /// Use 10 bit addressing as a master
+        IC_10BITADDR_MASTER OFFSET(4) NUMBITS(1) [],
+        /// Speed of the controller
+        SPEED OFFSET(1) NUMBITS(2) [
+            Standard = 1,
+            Fast = 2
+        ],
+        /// Enables the master
+        MASTER_MODE OFFSET(0) NUMBITS(1) []
+    ],
+    IC_DATA_CMD [
+        /// Issue a RESTART before this command
+        RESTART OFFSET(10) NUMBITS(1) [],
+        /// Issue a STOP after this command
+        STOP OFFSET(9) NUMBITS(1) [],
+        /// Read or write
+        CMD OFFSET(8) NUMBITS(1) [
+            Write = 0,
+            Read = 1
+        ],
+        /// The byte to write, or the byte read
+        DAT OFFSET(0) NUMBITS(8) []
+    ],
+    INTERRUPT [
+        RESTART_DET OFFSET(12) NUMBITS(1) [],
+        GEN_CALL OFFSET(11) NUMBITS(1) [],
+        START_DET OFFSET(10) NUMBITS(1) [],
+        STOP_DET OFFSET(9) NUMBITS(1) [],
+        ACTIVITY OFFSET(8) NUMBITS(1) [],
+        RX_DONE OFFSET(7) NUMBITS(1) [],
+        TX_ABRT OFFSET(6) NUMBITS(1) [],
+        RD_REQ OFFSET(5) NUMBITS(1) [],
+        TX_EMPTY OFFSET(4) NUMBITS(1) [],
+        TX_OVER OFFSET(3) NUMBITS(1) [],
+        RX_FULL OFFSET(2) NUMBITS(1) [],
+        RX_OVER OFFSET(1) NUMBITS(1) [],
+        RX_UNDER OFFSET(0) NUMBITS(1) []
+    ],
+    IC_ENABLE [
+        /// Abort the transfer in progress
+        ABORT OFFSET(1) NUMBITS(1) [],
+        /// Enable the controller
+        ENABLE OFFSET(0) NUMBITS(1) []
+    ],
+    IC_TX_ABRT_SOURCE [
+        /// Lost arbitration
+        ARB_LOST OFFSET(12) NUMBITS(1) [],
+        /// A data byte was not acknowledged
+        ABRT_TXDATA_NOACK OFFSET(3) NUMBITS(1) [],
+        /// The 7 bit address was not acknowledged
+        ABRT_7B_ADDR_NOACK OFFSET(0) NUMBITS(1) []
+    ]
+];
+
+const I2C0_BASE: StaticRef<I2cRegisters> =
+    unsafe { StaticRef::new(0x40044000 as *const I2cRegisters) };
+const I2C1_BASE: StaticRef<I2cRegisters> =
+    unsafe { StaticRef::new(0x40048000 as *const I2cRegisters) };
+
+pub static mut I2C0: I2c = I2c::new(I2C0_BASE);
+pub static mut I2C1: I2c = I2c::new(I2C1_BASE);
+
+pub struct I2c {
+    registers: StaticRef<I2cRegisters>,
+    client: OptionalCell<&'static hil::i2c::I2CHwMasterClient>,
+    buffer: TakeCell<'static, [u8]>,
+    write_len: Cell<usize>,
+    read_len: Cell<usize>,
+    // The number of commands written into the transmit FIFO
+    tx_position: Cell<usize>,
+    // The number of bytes read from the receive FIFO
+    rx_position: Cell<usize>,
+    error: Cell<Option<i2c::Error>>,
+}
+
+impl I2c {
+    const fn new(base: StaticRef<I2cRegisters>) -> I2c {
+        I2c {
+            registers: base,
+            client: OptionalCell::empty(),
+            buffer: TakeCell::empty(),
+            write_len: Cell::new(0),
+            read_len: Cell::new(0),
+            tx_position: Cell::new(0),
+            rx_position: Cell::new(0),
+            error: Cell::new(None),
+        }
+    }
+
+    pub fn set_client(&self, client: &'static hil::i2c::I2CHwMasterClient) {
+        self.client.set(client);
+    }
+
+    /// Set up the controller as a master at `baud_rate`, which can be up to
+    /// 400 kHz.
+    pub fn init(&self, baud_rate: u32) {
+        self.registers.ic_enable.set(0);
+        self.registers.ic_con.write(
+            IC_CON::SPEED::Fast
+                + IC_CON::MASTER_MODE::SET
+                + IC_CON::IC_SLAVE_DISABLE::SET
+                + IC_CON::IC_RESTART_EN::SET
+                + IC_CON::TX_EMPTY_CTRL::SET
+                + IC_CON::RX_FIFO_FULL_HLD_CTRL::SET,
+        );
+        self.registers.ic_tx_tl.set(0);
+        self.registers.ic_rx_tl.set(0);
+        self.registers.ic_intr_mask.set(0);
+
+        // The SCL period is split into 3/5 low and 2/5 high, which keeps both
+        // phases above their minimum in fast mode
+        let frequency = clocks::SYS_FREQUENCY;
+        let period = (frequency + baud_rate / 2) / baud_rate;
+        let lcnt = period * 3 / 5;
+        let hcnt = period - lcnt;
+        self.registers.ic_fs_scl_hcnt.set(hcnt);
+        self.registers.ic_fs_scl_lcnt.set(lcnt);
+        self.registers
+            .ic_fs_spklen
+            .set(if lcnt < 16 { 1 } else { lcnt / 16 });
+        // Hold SDA for 300ns after the falling edge of SCL
+        self.registers
+            .ic_sda_hold
+            .set(frequency / 1_000_000 * 3 / 10 + 1);
+    }
+
+    fn total_len(&self) -> usize {
+        self.write_len.get() + self.read_len.get()
+    }
+
+    /// Queue commands into the transmit FIFO until it is full or all of them
+    /// are queued.
+    fn fill_fifo(&self) {
+        let total = self.total_len();
+        self.buffer.map(|buffer| {
+            while self.tx_position.get() < total && self.registers.ic_txflr.get() < TX_FIFO_DEPTH {
+                let position = self.tx_position.get();
+                let mut command = if position < self.write_len.get() {
+                    IC_DATA_CMD::CMD::Write + IC_DATA_CMD::DAT.val(buffer[position] as u32)
+                } else if position == self.write_len.get() && position > 0 {
+                    IC_DATA_CMD::CMD::Read + IC_DATA_CMD::RESTART::SET
+                } else {
+                    IC_DATA_CMD::CMD::Read
+                };
+                if position == total - 1 {
+                    command = command + IC_DATA_CMD::STOP::SET;
+                }
+                self.registers.ic_data_cmd.write(command);
+                self.tx_position.set(position + 1);
+            }
+        });
+
+        if self.tx_position.get() == total {
+            self.registers
+                .ic_intr_mask
+                .modify(INTERRUPT::TX_EMPTY::CLEAR);
+        }
+    }
+
+    fn drain_fifo(&self) {
+        let write_len = self.write_len.get();
+        self.buffer.map(|buffer| {
+            while self.registers.ic_rxflr.get() > 0 {
+                let byte = self.registers.ic_data_cmd.read(IC_DATA_CMD::DAT) as u8;
+                let position = self.rx_position.get();
+                if position < self.read_len.get() {
+                    buffer[write_len + position] = byte;
+                    self.rx_position.set(position + 1);
+                }
+            }
+        });
+    }
+
+    pub fn handle_interrupt(&self) {
+        let status = self.registers.ic_intr_stat.extract();
+
+        if status.is_set(INTERRUPT::TX_ABRT) {
+            let source = self.registers.ic_tx_abrt_source.extract();
+            let error = if source.is_set(IC_TX_ABRT_SOURCE::ABRT_7B_ADDR_NOACK) {
+                i2c::Error::AddressNak
+            } else if source.is_set(IC_TX_ABRT_SOURCE::ARB_LOST) {
+                i2c::Error::ArbitrationLost
+            } else {
+                i2c::Error::DataNak
+            };
+            self.error.set(Some(error));
+            // Reading the clear register also releases the flushed FIFO
+            self.registers.ic_clr_tx_abrt.get();
+            self.registers
+                .ic_intr_mask
+                .modify(INTERRUPT::TX_EMPTY::CLEAR);
+        }
+
+        if status.is_set(INTERRUPT::RX_FULL) {
+            self.drain_fifo();
+        }
+
+        if status.is_set(INTERRUPT::TX_EMPTY) && self.error.get().is_none() {
+            self.fill_fifo();
+        }
+
+        if status.is_set(INTERRUPT::STOP_DET) {
+            self.registers.ic_clr_stop_det.get();
+            self.drain_fifo();
+            self.registers.ic_intr_mask.set(0);
+            self.registers.ic_enable.set(0);
+
+            let error = self.error.get().unwrap_or(i2c::Error::CommandComplete);
+            self.client.map(|client| {
+                self.buffer.take().map(|buffer| {
+                    client.command_complete(buffer, error);
+                });
+            });
+        }
+    }
+
+    fn start(&self, addr: u8, buffer: &'static mut [u8], write_len: u8, read_len: u8) {
+        // The target address can only be changed while disabled
+        self.registers.ic_enable.set(0);
+        self.registers.ic_tar.set(addr as u32);
+
+        self.buffer.replace(buffer);
+        self.write_len.set(write_len as usize);
+        self.read_len.set(read_len as usize);
+        self.tx_position.set(0);
+        self.rx_position.set(0);
+        self.error.set(None);
+
+        // Clear anything left over from the last transfer
+        self.registers.ic_clr_intr.get();
+        self.registers.ic_intr_mask.write(
+            INTERRUPT::TX_EMPTY::SET
+                + INTERRUPT::RX_FULL::SET
+                + INTERRUPT::TX_ABRT::SET
+                + INTERRUPT::STOP_DET::SET,
+        );
+        self.registers.ic_enable.write(IC_ENABLE::ENABLE::SET);
+    }
+}
+
+impl i2c::I2CMaster for I2c {
+    fn enable(&self) {
+        // The controller is enabled for each transfer, once its target
+        // address is set
+    }
+
+    fn disable(&self) {
+        self.registers.ic_intr_mask.set(0);
+        self.registers.ic_enable.set(0);
+    }
+
+    fn write_read(&self, addr: u8, data: &'static mut [u8], write_len: u8, read_len: u8) {
+        self.start(addr, data, write_len, read_len);
+    }
+
+    fn write(&self, addr: u8, data: &'static mut [u8], len: u8) {
+        self.start(addr, data, len, 0);
+    }
+
+    fn read(&self, addr: u8, buffer: &'static mut [u8], len: u8) {
+        self.start(addr, buffer, 0, len);
+    }
+}
@@ -0,0 +1,28 @@
+//! Named constants for NVIC ids
+
+pub const TIMER_IRQ_0: u32 = 0;
+pub const TIMER_IRQ_1: u32 = 1;
+pub const TIMER_IRQ_2: u32 = 2;
+pub const TIMER_IRQ_3: u32 = 3;
+pub const PWM_IRQ_WRAP: u32 = 4;
+pub const USBCTRL_IRQ: u32 = 5;
+pub const XIP_IRQ: u32 = 6;
+pub const PIO0_IRQ_0: u32 = 7;
+pub const PIO0_IRQ_1: u32 = 8;
+pub const PIO1_IRQ_0: u32 = 9;
+pub const PIO1_IRQ_1: u32 = 10;
+pub const DMA_IRQ_0: u32 = 11;
+pub const DMA_IRQ_1: u32 = 12;
+pub const IO_IRQ_BANK0: u32 = 13;
+pub const IO_IRQ_QSPI: u32 = 14;
+pub const SIO_IRQ_PROC0: u32 = 15;
+pub const SIO_IRQ_PROC1: u32 = 16;
+pub const CLOCKS_IRQ: u32 = 17;
+pub const SPI0_IRQ: u32 = 18;
+pub const SPI1_IRQ: u32 = 19;
+pub const UART0_IRQ: u32 = 20;
+pub const UART1_IRQ: u32 = 21;
+pub const ADC_IRQ_FIFO: u32 = 22;
+pub const I2C0_IRQ: u32 = 23;
+pub const I2C1_IRQ: u32 = 24;
+pub const RTC_IRQ: u32 = 25;
@@ -0,0 +1,141 @@
+//! Peripheral implementations for the Raspberry Pi RP2040 MCU.
+//!
+//! RP2040: <https://www.raspberrypi.org/documentation/rp2040/getting-started/>
+//!
+//! The RP2040 has two Cortex-M0+ cores. Tock runs on core 0 only, and
+//! `init()` returns core 1 to the boot ROM, where it waits to be launched
+//! through the SIO mailbox (see the `multicore` module).
+
+#![crate_name = "rp2040"]
+#![crate_type = "rlib"]
+#![feature(asm, const_fn, in_band_lifetimes)]
+#![no_std]
+#![allow(unused_doc_comments)]
+
+mod deferred_call_tasks;
+
+pub mod chip;
+pub mod interrupts;
+
+// Peripherals
+pub mod adc;
+pub mod clocks;
+pub mod dma;
+pub mod gpio;
+pub mod i2c;
+pub mod multicore;
+pub mod resets;
+pub mod sio;
+pub mod spi;
+pub mod timer;
+pub mod uart;
+pub mod watchdog;
+pub mod xosc;
+
+use cortexm0::{generic_isr, hard_fault_handler, svc_handler, systick_handler};
+
+unsafe extern "C" fn unhandled_interrupt() {
+    let mut interrupt_number: u32;
+
+    // IPSR[8:0] holds the currently active interrupt
+    asm!(
+    "mrs    r0, ipsr                    "
+    : "={r0}"(interrupt_number)
+    :
+    : "r0"
+    :
+    );
+
+    interrupt_number = interrupt_number & 0x1ff;
+
+    panic!("Unhandled Interrupt. ISR {} is active.", interrupt_number);
+}
+
+extern "C" {
+    // _estack is not really a function, but it makes the types work
+    // You should never actually invoke it!!
+    fn _estack();
+
+    // Defined by platform
+    fn reset_handler();
+}
+
+#[link_section = ".vectors"]
+// used Ensures that the symbol is kept until the final binary
+#[used]
+pub static BASE_VECTORS: [unsafe extern "C" fn(); 16] = [
+    _estack,
+    reset_handler,
+    unhandled_interrupt, // NMI
+    hard_fault_handler,  // Hard Fault
+    unhandled_interrupt,
+    unhandled_interrupt,
+    unhandled_interrupt,
+    unhandled_interrupt,
+    unhandled_interrupt,
+    unhandled_interrupt,
+    unhandled_interrupt,
+    svc_handler, // SVC
+    unhandled_interrupt,
+    unhandled_interrupt,
+    unhandled_interrupt, // PendSV
+    systick_handler,     // SysTick
+];
+
+// The RP2040 has 26 interrupts, the remaining 6 NVIC inputs are tied low
+#[link_section = ".irqs"]
+#[used] // Ensures that the symbol is kept until the final binary
+pub static IRQS: [unsafe extern "C" fn(); 32] = [
+    generic_isr,         // TIMER_IRQ_0 (0)
+    generic_isr,         // TIMER_IRQ_1 (1)
+    generic_isr,         // TIMER_IRQ_2 (2)
+    generic_isr,         // TIMER_IRQ_3 (3)
+    generic_isr,         // PWM_IRQ_WRAP (4)
+    generic_isr,         // USBCTRL_IRQ (5)
+    generic_isr,         // XIP_IRQ (6)
+    generic_isr,         // PIO0_IRQ_0 (7)
+    generic_isr,         // PIO0_IRQ_1 (8)
+    generic_isr,         // PIO1_IRQ_0 (9)
+    generic_isr,         // PIO1_IRQ_1 (10)
+    generic_isr,         // DMA_IRQ_0 (11)
+    generic_isr,         // DMA_IRQ_1 (12)
+    generic_isr,         // IO_IRQ_BANK0 (13)
+    generic_isr,         // IO_IRQ_QSPI (14)
+    generic_isr,         // SIO_IRQ_PROC0 (15)
+    generic_isr,         // SIO_IRQ_PROC1 (16)
+    generic_isr,         // CLOCKS_IRQ (17)
+    generic_isr,         // SPI0_IRQ (18)
+    generic_isr,         // SPI1_IRQ (19)
+    generic_isr,         // UART0_IRQ (20)
+    generic_isr,         // UART1_IRQ (21)
+    generic_isr,         // ADC_IRQ_FIFO (22)
+    generic_isr,         // I2C0_IRQ (23)
+    generic_isr,         // I2C1_IRQ (24)
+    generic_isr,         // RTC_IRQ (25)
+    unhandled_interrupt, // (26)
+    unhandled_interrupt, // (27)
+    unhandled_interrupt, // (28)
+    unhandled_interrupt, // (29)
+    unhandled_interrupt, // (30)
+    unhandled_interrupt, // (31)
+];
+
+extern "C" {
+    static mut _szero: u32;
+    static mut _ezero: u32;
+    static mut _etext: u32;
+    static mut _srelocate: u32;
+    static mut _erelocate: u32;
+}
+
+pub unsafe fn init() {
+    tock_rt0::init_data(&mut _etext, &mut _srelocate, &mut _erelocate);
+    tock_rt0::zero_bss(&mut _szero, &mut _ezero);
+
+    // A reset of core 0 alone (e.g. from a debugger or `SYSRESETREQ`) leaves
+    // core 1 running whatever it ran before, so send it back to the boot ROM.
+    multicore::reset_core1();
+
+    cortexm0::nvic::disable_all();
+    cortexm0::nvic::clear_all_pending();
+}
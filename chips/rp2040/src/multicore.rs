@@ -0,0 +1,73 @@
+//! Dual-core boot handling.
+//!
+//! Out of reset, only core 0 runs the boot ROM's path into flash. Core 1
+//! waits in the boot ROM for core 0 to send it a vector table, a stack
+//! pointer and an entry point over the SIO FIFOs. Tock itself only runs on
+//! core 0; this module keeps core 1 parked, and lets a board launch code on
+//! it.
+
+use kernel::common::registers::{register_bitfields, ReadOnly, ReadWrite};
+use kernel::common::StaticRef;
+
+use crate::sio::SIO;
+
+#[repr(C)]
+struct PsmRegisters {
+    /// Force block out of reset (i.e. power it on)
+    frce_on: ReadWrite<u32, PSM::Register>,
+    /// Force into reset (i.e. power it off)
+    frce_off: ReadWrite<u32, PSM::Register>,
+    /// Set to 1 if this peripheral should be reset when the watchdog fires
+    wdsel: ReadWrite<u32, PSM::Register>,
+    /// Indicates the peripheral's registers are ready to access
+    done: ReadOnly<u32, PSM::Register>,
+}
+
+register_bitfields![u32,
+    PSM [
+        PROC1 OFFSET(16) NUMBITS(1) [],
+        PROC0 OFFSET(15) NUMBITS(1) []
+    ]
+];
+
+const PSM_BASE: StaticRef<PsmRegisters> =
+    unsafe { StaticRef::new(0x40010000 as *const PsmRegisters) };
+
+/// Power cycle core 1, which sends it back to the boot ROM to wait for a
+/// launch sequence.
+pub unsafe fn reset_core1() {
+    let psm = PSM_BASE;
+
+    psm.frce_off.modify(PSM::PROC1::SET);
+    while !psm.frce_off.is_set(PSM::PROC1) {}
+    // Core 1 drains its RX FIFO and pushes a 0 into ours once it is back in
+    // the boot ROM, which `launch_core1()` discards
+    psm.frce_off.modify(PSM::PROC1::CLEAR);
+}
+
+/// Launch `entry` on core 1 with the given stack pointer and vector table.
+///
+/// Core 1 must be waiting in the boot ROM, which it is after `reset_core1()`.
+/// The sequence, from section 2.8.2 of the RP2040 datasheet, is echoed word by
+/// word by core 1, and restarts from the beginning if any word is not.
+pub unsafe fn launch_core1(
+    entry: unsafe extern "C" fn() -> !,
+    stack_pointer: u32,
+    vector_table: u32,
+) {
+    let sequence = [0, 0, 1, vector_table, stack_pointer, entry as u32];
+
+    let mut index = 0;
+    while index < sequence.len() {
+        let command = sequence[index];
+        if command == 0 {
+            // Core 1 may be waiting for the FIFO to be emptied before it
+            // reads the next command
+            SIO.fifo_drain();
+            asm!("sev" :::: "volatile");
+        }
+        SIO.fifo_push_blocking(command);
+        let response = SIO.fifo_pop_blocking();
+        index = if response == command { index + 1 } else { 0 };
+    }
+}
@@ -0,0 +1,131 @@
+//! Subsystem resets.
+//!
+//! Every peripheral of the RP2040 except the processors, the bus fabric and
+//! the SRAMs comes out of reset held in reset, and must be released here
+//! before its registers can be used.
+
+use kernel::common::registers::{register_bitfields, ReadOnly, ReadWrite};
+use kernel::common::StaticRef;
+
+#[repr(C)]
+struct ResetsRegisters {
+    /// Reset control. If a bit is set it means the peripheral is in reset.
+    reset: ReadWrite<u32, RESET::Register>,
+    /// Watchdog select. If a bit is set then the watchdog will reset this
+    /// peripheral when the watchdog fires.
+    wdsel: ReadWrite<u32, RESET::Register>,
+    /// Reset done. If a bit is set then a reset done signal has been
+    /// returned by the peripheral.
+    reset_done: ReadOnly<u32, RESET::Register>,
+}
+
+register_bitfields![u32,
+    RESET [
+        USBCTRL OFFSET(24) NUMBITS(1) [],
+        UART1 OFFSET(23) NUMBITS(1) [],
+        UART0 OFFSET(22) NUMBITS(1) [],
+        TIMER OFFSET(21) NUMBITS(1) [],
+        TBMAN OFFSET(20) NUMBITS(1) [],
+        SYSINFO OFFSET(19) NUMBITS(1) [],
+        SYSCFG OFFSET(18) NUMBITS(1) [],
+        SPI1 OFFSET(17) NUMBITS(1) [],
+        SPI0 OFFSET(16) NUMBITS(1) [],
+        RTC OFFSET(15) NUMBITS(1) [],
+        PWM OFFSET(14) NUMBITS(1) [],
+        PLL_USB OFFSET(13) NUMBITS(1) [],
+        PLL_SYS OFFSET(12) NUMBITS(1) [],
+        PIO1 OFFSET(11) NUMBITS(1) [],
+        PIO0 OFFSET(10) NUMBITS(1) [],
+        PADS_QSPI OFFSET(9) NUMBITS(1) [],
+        PADS_BANK0 OFFSET(8) NUMBITS(1) [],
+        JTAG OFFSET(7) NUMBITS(1) [],
+        IO_QSPI OFFSET(6) NUMBITS(1) [],
+        IO_BANK0 OFFSET(5) NUMBITS(1) [],
+        I2C1 OFFSET(4) NUMBITS(1) [],
+        I2C0 OFFSET(3) NUMBITS(1) [],
+        DMA OFFSET(2) NUMBITS(1) [],
+        BUSCTRL OFFSET(1) NUMBITS(1) [],
+        ADC OFFSET(0) NUMBITS(1) []
+    ]
+];
+
+const RESETS_BASE: StaticRef<ResetsRegisters> =
+    unsafe { StaticRef::new(0x4000C000 as *const ResetsRegisters) };
+
+/// The peripherals that can be reset, numbered by their bit in the reset
+/// registers.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Peripheral {
+    Adc = 0,
+    BusController = 1,
+    Dma = 2,
+    I2c0 = 3,
+    I2c1 = 4,
+    IOBank0 = 5,
+    IOQSpi = 6,
+    Jtag = 7,
+    PadsBank0 = 8,
+    PadsQSpi = 9,
+    Pio0 = 10,
+    Pio1 = 11,
+    PllSys = 12,
+    PllUsb = 13,
+    Pwm = 14,
+    Rtc = 15,
+    Spi0 = 16,
+    Spi1 = 17,
+    Syscfg = 18,
+    SysInfo = 19,
+    TBMan = 20,
+    Timer = 21,
+    Uart0 = 22,
+    Uart1 = 23,
+    UsbCtrl = 24,
+}
+
+impl Peripheral {
+    fn mask(&self) -> u32 {
+        1 << (*self as u32)
+    }
+}
+
+pub struct Resets {
+    registers: StaticRef<ResetsRegisters>,
+}
+
+pub static mut RESETS: Resets = Resets::new();
+
+impl Resets {
+    const fn new() -> Resets {
+        Resets {
+            registers: RESETS_BASE,
+        }
+    }
+
+    fn mask(peripherals: &[Peripheral]) -> u32 {
+        peripherals.iter().fold(0, |mask, p| mask | p.mask())
+    }
+
+    /// Hold the given peripherals in reset.
+    pub fn reset(&self, peripherals: &[Peripheral]) {
+        let mask = Resets::mask(peripherals);
+        self.registers.reset.set(self.registers.reset.get() | mask);
+    }
+
+    /// Release the given peripherals from reset, and if `wait` is set, wait
+    /// until all of them are out of reset.
+    pub fn unreset(&self, peripherals: &[Peripheral], wait: bool) {
+        let mask = Resets::mask(peripherals);
+        self.registers.reset.set(self.registers.reset.get() & !mask);
+        if wait {
+            while self.registers.reset_done.get() & mask != mask {}
+        }
+    }
+
+    /// Hold the given peripherals in reset, then release them and wait for
+    /// them to come out of reset.
+    pub fn reset_all(&self, peripherals: &[Peripheral]) {
+        self.reset(peripherals);
+        self.unreset(peripherals, true);
+    }
+}
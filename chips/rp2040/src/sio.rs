@@ -0,0 +1,143 @@
+//! Single-cycle IO block.
+//!
+//! The SIO is private to each core: `cpuid` tells the cores apart, the GPIO
+//! registers drive the pins that select the SIO function (see the `gpio`
+//! module), and the FIFOs form a mailbox between the two cores (see the
+//! `multicore` module).
+
+use kernel::common::registers::{register_bitfields, ReadOnly, ReadWrite, WriteOnly};
+use kernel::common::StaticRef;
+
+#[repr(C)]
+struct SioRegisters {
+    /// Processor core identifier, 0 for core 0 and 1 for core 1
+    cpuid: ReadOnly<u32>,
+    /// Input value for GPIO pins
+    gpio_in: ReadOnly<u32>,
+    /// Input value for QSPI pins
+    gpio_hi_in: ReadOnly<u32>,
+    _reserved0: u32,
+    /// GPIO output value
+    gpio_out: ReadWrite<u32>,
+    /// GPIO output value set
+    gpio_out_set: WriteOnly<u32>,
+    /// GPIO output value clear
+    gpio_out_clr: WriteOnly<u32>,
+    /// GPIO output value XOR
+    gpio_out_xor: WriteOnly<u32>,
+    /// GPIO output enable
+    gpio_oe: ReadWrite<u32>,
+    /// GPIO output enable set
+    gpio_oe_set: WriteOnly<u32>,
+    /// GPIO output enable clear
+    gpio_oe_clr: WriteOnly<u32>,
+    /// GPIO output enable XOR
+    gpio_oe_xor: WriteOnly<u32>,
+    /// QSPI output value and output enable registers
+    _gpio_hi: [u32; 8],
+    /// Status register for inter-core FIFOs (mailboxes)
+    fifo_st: ReadWrite<u32, FIFO_ST::Register>,
+    /// Write access to this core's TX FIFO
+    fifo_wr: WriteOnly<u32>,
+    /// Read access to this core's RX FIFO
+    fifo_rd: ReadOnly<u32>,
+}
+
+register_bitfields![u32,
+    FIFO_ST [
+        /// Sticky flag indicating the RX FIFO was read when empty
+        ROE OFFSET(3) NUMBITS(1) [],
+        /// Sticky flag indicating the TX FIFO was written when full
+        WOF OFFSET(2) NUMBITS(1) [],
+        /// Value is 1 if this core's TX FIFO is not full
+        RDY OFFSET(1) NUMBITS(1) [],
+        /// Value is 1 if this core's RX FIFO is not empty
+        VLD OFFSET(0) NUMBITS(1) []
+    ]
+];
+
+const SIO_BASE: StaticRef<SioRegisters> =
+    unsafe { StaticRef::new(0xD0000000 as *const SioRegisters) };
+
+pub struct Sio {
+    registers: StaticRef<SioRegisters>,
+}
+
+pub static mut SIO: Sio = Sio::new();
+
+impl Sio {
+    pub const fn new() -> Sio {
+        Sio {
+            registers: SIO_BASE,
+        }
+    }
+
+    /// The number of the core that runs this code.
+    pub fn get_processor(&self) -> u32 {
+        self.registers.cpuid.get()
+    }
+
+    pub fn read_gpio_in(&self) -> u32 {
+        self.registers.gpio_in.get()
+    }
+
+    pub fn read_gpio_out(&self) -> u32 {
+        self.registers.gpio_out.get()
+    }
+
+    pub fn set_gpio_out(&self, mask: u32) {
+        self.registers.gpio_out_set.set(mask);
+    }
+
+    pub fn clear_gpio_out(&self, mask: u32) {
+        self.registers.gpio_out_clr.set(mask);
+    }
+
+    pub fn toggle_gpio_out(&self, mask: u32) {
+        self.registers.gpio_out_xor.set(mask);
+    }
+
+    pub fn read_gpio_oe(&self) -> u32 {
+        self.registers.gpio_oe.get()
+    }
+
+    pub fn set_gpio_oe(&self, mask: u32) {
+        self.registers.gpio_oe_set.set(mask);
+    }
+
+    pub fn clear_gpio_oe(&self, mask: u32) {
+        self.registers.gpio_oe_clr.set(mask);
+    }
+
+    /// Whether a word from the other core is waiting in the RX FIFO.
+    pub fn fifo_valid(&self) -> bool {
+        self.registers.fifo_st.is_set(FIFO_ST::VLD)
+    }
+
+    /// Read and discard everything in the RX FIFO.
+    pub fn fifo_drain(&self) {
+        while self.fifo_valid() {
+            self.registers.fifo_rd.get();
+        }
+    }
+
+    /// Write a word to the other core, waiting for room in the TX FIFO.
+    pub fn fifo_push_blocking(&self, value: u32) {
+        while !self.registers.fifo_st.is_set(FIFO_ST::RDY) {}
+        self.registers.fifo_wr.set(value);
+        // Wake the other core if it waits for an event
+        unsafe {
+            asm!("sev" :::: "volatile");
+        }
+    }
+
+    /// Read a word from the other core, waiting until there is one.
+    pub fn fifo_pop_blocking(&self) -> u32 {
+        while !self.fifo_valid() {
+            unsafe {
+                asm!("wfe" :::: "volatile");
+            }
+        }
+        self.registers.fifo_rd.get()
+    }
+}
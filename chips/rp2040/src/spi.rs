@@ -0,0 +1,352 @@
+//! SPI master driver for the ARM PL022 controllers of the RP2040.
+//!
+//! Transfers are 8 bit Motorola frames, moved through the 8 entry FIFOs from
+//! the receive interrupt. No more bytes are written than the receive FIFO
+//! can hold, so it never overruns. The chip select is a GPIO pin, since the
+//! controller's own chip select is released between frames.
+
+use core::cell::Cell;
+use core::cmp;
+use kernel::common::cells::{OptionalCell, TakeCell};
+use kernel::common::registers::{register_bitfields, ReadOnly, ReadWrite, WriteOnly};
+use kernel::common::StaticRef;
+use kernel::hil;
+use kernel::hil::spi::{ClockPhase, ClockPolarity, SpiMasterClient};
+use kernel::ReturnCode;
+
+use crate::clocks;
+
+/// The depth of the transmit and receive FIFOs
+const FIFO_DEPTH: usize = 8;
+
+#[repr(C)]
+pub struct SpiRegisters {
+    /// Control register 0
+    cr0: ReadWrite<u32, CR0::Register>,
+    /// Control register 1
+    cr1: ReadWrite<u32, CR1::Register>,
+    /// Data register
+    dr: ReadWrite<u32>,
+    /// Status register
+    sr: ReadOnly<u32, SR::Register>,
+    /// Clock prescale register
+    cpsr: ReadWrite<u32>,
+    /// Interrupt mask set or clear register
+    imsc: ReadWrite<u32, INTERRUPT::Register>,
+    /// Raw interrupt status register
+    ris: ReadOnly<u32, INTERRUPT::Register>,
+    /// Masked interrupt status register
+    mis: ReadOnly<u32, INTERRUPT::Register>,
+    /// Interrupt clear register
+    icr: WriteOnly<u32, INTERRUPT::Register>,
+    /// DMA control register
+    dmacr: ReadWrite<u32>,
+}
+
+register_bitfields![u32,
+    CR0 [
+        /// Serial clock rate
+        SCR OFFSET(8) NUMBITS(8) [],
+        /// Clock phase, only for Motorola frames
+        SPH OFFSET(7) NUMBITS(1) [],
+        /// Clock polarity, only for Motorola frames
+        SPO OFFSET(6) NUMBITS(1) [],
+        /// Frame format
+        FRF OFFSET(4) NUMBITS(2) [
+            Motorola = 0,
+            TexasInstruments = 1,
+            NationalMicrowire = 2
+        ],
+        /// Data size select, the frame size minus one
+        DSS OFFSET(0) NUMBITS(4) [
+            Bits8 = 7
+        ]
+    ],
+    CR1 [
+        /// Slave-mode output disable
+        SOD OFFSET(3) NUMBITS(1) [],
+        /// Master or slave mode select
+        MS OFFSET(2) NUMBITS(1) [],
+        /// Synchronous serial port enable
+        SSE OFFSET(1) NUMBITS(1) [],
+        /// Loop back mode
+        LBM OFFSET(0) NUMBITS(1) []
+    ],
+    SR [
+        /// PrimeCell SSP busy flag
+        BSY OFFSET(4) NUMBITS(1) [],
+        /// Receive FIFO full
+        RFF OFFSET(3) NUMBITS(1) [],
+        /// Receive FIFO not empty
+        RNE OFFSET(2) NUMBITS(1) [],
+        /// Transmit FIFO not full
+        TNF OFFSET(1) NUMBITS(1) [],
+        /// Transmit FIFO empty
+        TFE OFFSET(0) NUMBITS(1) []
+    ],
+    INTERRUPT [
+        /// Transmit FIFO half empty or less
+        TX OFFSET(3) NUMBITS(1) [],
+        /// Receive FIFO half full or more
+        RX OFFSET(2) NUMBITS(1) [],
+        /// Receive timeout
+        RT OFFSET(1) NUMBITS(1) [],
+        /// Receive overrun
+        ROR OFFSET(0) NUMBITS(1) []
+    ]
+];
+
+const SPI0_BASE: StaticRef<SpiRegisters> =
+    unsafe { StaticRef::new(0x4003C000 as *const SpiRegisters) };
+const SPI1_BASE: StaticRef<SpiRegisters> =
+    unsafe { StaticRef::new(0x40040000 as *const SpiRegisters) };
+
+pub static mut SPI0: Spi = Spi::new(SPI0_BASE);
+pub static mut SPI1: Spi = Spi::new(SPI1_BASE);
+
+pub struct Spi {
+    registers: StaticRef<SpiRegisters>,
+    client: OptionalCell<&'static SpiMasterClient>,
+    chip_select: OptionalCell<&'static hil::gpio::Pin>,
+    hold_low: Cell<bool>,
+    busy: Cell<bool>,
+    tx_buffer: TakeCell<'static, [u8]>,
+    rx_buffer: TakeCell<'static, [u8]>,
+    transfer_len: Cell<usize>,
+    tx_position: Cell<usize>,
+    rx_position: Cell<usize>,
+}
+
+impl Spi {
+    const fn new(base: StaticRef<SpiRegisters>) -> Spi {
+        Spi {
+            registers: base,
+            client: OptionalCell::empty(),
+            chip_select: OptionalCell::empty(),
+            hold_low: Cell::new(false),
+            busy: Cell::new(false),
+            tx_buffer: TakeCell::empty(),
+            rx_buffer: TakeCell::empty(),
+            transfer_len: Cell::new(0),
+            tx_position: Cell::new(0),
+            rx_position: Cell::new(0),
+        }
+    }
+
+    /// Run `f` with the controller disabled, as the frame format must not
+    /// change while it is enabled.
+    fn with_disabled<F: FnOnce()>(&self, f: F) {
+        let enabled = self.registers.cr1.is_set(CR1::SSE);
+        self.registers.cr1.modify(CR1::SSE::CLEAR);
+        f();
+        if enabled {
+            self.registers.cr1.modify(CR1::SSE::SET);
+        }
+    }
+
+    /// Write bytes into the transmit FIFO, as long as the receive FIFO has
+    /// room for the byte that each of them clocks in.
+    fn fill_fifo(&self) {
+        self.tx_buffer.map(|buffer| {
+            while self.tx_position.get() < self.transfer_len.get()
+                && self.tx_position.get() - self.rx_position.get() < FIFO_DEPTH
+                && self.registers.sr.is_set(SR::TNF)
+            {
+                self.registers.dr.set(buffer[self.tx_position.get()] as u32);
+                self.tx_position.set(self.tx_position.get() + 1);
+            }
+        });
+    }
+
+    fn drain_fifo(&self) {
+        while self.registers.sr.is_set(SR::RNE) {
+            let byte = self.registers.dr.get() as u8;
+            let position = self.rx_position.get();
+            self.rx_buffer.map(|buffer| {
+                buffer[position] = byte;
+            });
+            self.rx_position.set(position + 1);
+        }
+    }
+
+    pub fn handle_interrupt(&self) {
+        self.registers
+            .icr
+            .write(INTERRUPT::RT::SET + INTERRUPT::ROR::SET);
+        self.drain_fifo();
+
+        if self.rx_position.get() < self.transfer_len.get() {
+            self.fill_fifo();
+            return;
+        }
+
+        self.registers.imsc.set(0);
+        if !self.hold_low.get() {
+            self.chip_select.map(|cs| cs.set());
+        }
+        self.busy.set(false);
+        self.client.map(|client| {
+            self.tx_buffer.take().map(|tx_buffer| {
+                client.read_write_done(tx_buffer, self.rx_buffer.take(), self.transfer_len.get());
+            });
+        });
+    }
+}
+
+impl hil::spi::SpiMaster for Spi {
+    type ChipSelect = &'static hil::gpio::Pin;
+
+    fn set_client(&self, client: &'static SpiMasterClient) {
+        self.client.set(client);
+    }
+
+    /// Set up 8 bit Motorola frames in master mode, at 1 MHz by default.
+    fn init(&self) {
+        self.registers.cr1.set(0);
+        self.registers
+            .cr0
+            .write(CR0::FRF::Motorola + CR0::DSS::Bits8);
+        self.set_rate(1_000_000);
+        self.registers.dmacr.set(0);
+        self.registers.imsc.set(0);
+        self.registers.cr1.write(CR1::SSE::SET);
+    }
+
+    fn is_busy(&self) -> bool {
+        self.busy.get()
+    }
+
+    fn read_write_bytes(
+        &self,
+        write_buffer: &'static mut [u8],
+        read_buffer: Option<&'static mut [u8]>,
+        len: usize,
+    ) -> ReturnCode {
+        if self.busy.get() {
+            return ReturnCode::EBUSY;
+        }
+
+        let mut transfer_len = cmp::min(len, write_buffer.len());
+        if let Some(ref buffer) = read_buffer {
+            transfer_len = cmp::min(transfer_len, buffer.len());
+        }
+        if transfer_len == 0 {
+            return ReturnCode::ESIZE;
+        }
+
+        // Throw away anything left over in the receive FIFO
+        while self.registers.sr.is_set(SR::RNE) {
+            self.registers.dr.get();
+        }
+
+        self.busy.set(true);
+        self.tx_buffer.replace(write_buffer);
+        self.rx_buffer.put(read_buffer);
+        self.transfer_len.set(transfer_len);
+        self.tx_position.set(0);
+        self.rx_position.set(0);
+
+        self.chip_select.map(|cs| cs.clear());
+        self.fill_fifo();
+        // The receive timeout reports the last bytes, that stay below the
+        // receive FIFO's trigger level
+        self.registers
+            .imsc
+            .write(INTERRUPT::RX::SET + INTERRUPT::RT::SET);
+
+        ReturnCode::SUCCESS
+    }
+
+    fn write_byte(&self, val: u8) {
+        self.read_write_byte(val);
+    }
+
+    fn read_byte(&self) -> u8 {
+        self.read_write_byte(0)
+    }
+
+    fn read_write_byte(&self, val: u8) -> u8 {
+        while !self.registers.sr.is_set(SR::TNF) {}
+        self.registers.dr.set(val as u32);
+        while !self.registers.sr.is_set(SR::RNE) {}
+        self.registers.dr.get() as u8
+    }
+
+    fn specify_chip_select(&self, cs: Self::ChipSelect) {
+        cs.make_output();
+        cs.set();
+        self.chip_select.set(cs);
+    }
+
+    /// Set the fastest rate at or below `rate`, from `clk_peri / (cpsdvsr *
+    /// (1 + scr))` with an even `cpsdvsr` between 2 and 254 and an `scr`
+    /// between 0 and 255.
+    fn set_rate(&self, rate: u32) -> u32 {
+        let frequency = clocks::PERI_FREQUENCY as u64;
+        let rate = cmp::max(rate, 1) as u64;
+
+        // Find the smallest prescale value that leaves the rest of the
+        // division to the serial clock rate
+        let mut prescale = 2;
+        while prescale < 254 && frequency >= (prescale + 2) * 256 * rate {
+            prescale += 2;
+        }
+        // Find the largest divisor that still gives a rate above `rate`
+        let mut divisor = 256;
+        while divisor > 1 && frequency / (prescale * (divisor - 1)) <= rate {
+            divisor -= 1;
+        }
+
+        self.with_disabled(|| {
+            self.registers.cpsr.set(prescale as u32);
+            self.registers.cr0.modify(CR0::SCR.val(divisor as u32 - 1));
+        });
+        self.get_rate()
+    }
+
+    fn get_rate(&self) -> u32 {
+        let prescale = self.registers.cpsr.get();
+        let divisor = self.registers.cr0.read(CR0::SCR) + 1;
+        clocks::PERI_FREQUENCY / (prescale * divisor)
+    }
+
+    fn set_clock(&self, polarity: ClockPolarity) {
+        let spo = match polarity {
+            ClockPolarity::IdleLow => CR0::SPO::CLEAR,
+            ClockPolarity::IdleHigh => CR0::SPO::SET,
+        };
+        self.with_disabled(|| self.registers.cr0.modify(spo));
+    }
+
+    fn get_clock(&self) -> ClockPolarity {
+        if self.registers.cr0.is_set(CR0::SPO) {
+            ClockPolarity::IdleHigh
+        } else {
+            ClockPolarity::IdleLow
+        }
+    }
+
+    fn set_phase(&self, phase: ClockPhase) {
+        let sph = match phase {
+            ClockPhase::SampleLeading => CR0::SPH::CLEAR,
+            ClockPhase::SampleTrailing => CR0::SPH::SET,
+        };
+        self.with_disabled(|| self.registers.cr0.modify(sph));
+    }
+
+    fn get_phase(&self) -> ClockPhase {
+        if self.registers.cr0.is_set(CR0::SPH) {
+            ClockPhase::SampleTrailing
+        } else {
+            ClockPhase::SampleLeading
+        }
+    }
+
+    fn hold_low(&self) {
+        self.hold_low.set(true);
+    }
+
+    fn release_low(&self) {
+        self.hold_low.set(false);
+    }
+}
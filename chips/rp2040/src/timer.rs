@@ -0,0 +1,129 @@
+//! Timer driver for the RP2040.
+//!
+//! The timer is a 64 bit microsecond counter, driven by the watchdog tick
+//! (see `watchdog::Watchdog::start_tick()`). The alarm uses the lower 32 bits
+//! of the counter and the first of its four alarm registers.
+
+use kernel::common::cells::OptionalCell;
+use kernel::common::registers::{register_bitfields, ReadOnly, ReadWrite, WriteOnly};
+use kernel::common::StaticRef;
+use kernel::hil::time::{self, Alarm, Frequency, Time};
+
+#[repr(C)]
+struct TimerRegisters {
+    /// Write to bits 63:32 of time, always write timelw before timehw
+    timehw: WriteOnly<u32>,
+    /// Write to bits 31:0 of time, writes do not get copied to time until
+    /// timehw is written
+    timelw: WriteOnly<u32>,
+    /// Read from bits 63:32 of time, always read timelr before timehr
+    timehr: ReadOnly<u32>,
+    /// Read from bits 31:0 of time
+    timelr: ReadOnly<u32>,
+    /// Arm alarm n, and configure the time it will fire. Once armed, the
+    /// alarm fires when the lower 32 bits of the timer match its value.
+    alarm: [ReadWrite<u32>; 4],
+    /// Indicates the armed/disarmed status of each alarm, write 1 to disarm
+    armed: ReadWrite<u32, ALARM::Register>,
+    /// Raw read from bits 63:32 of time (no side effects)
+    timerawh: ReadOnly<u32>,
+    /// Raw read from bits 31:0 of time (no side effects)
+    timerawl: ReadOnly<u32>,
+    /// Set bits high to enable pause when the debug ports are active
+    dbgpause: ReadWrite<u32>,
+    /// Set high to pause the timer
+    pause: ReadWrite<u32>,
+    /// Raw Interrupts, write 1 to clear
+    intr: ReadWrite<u32, ALARM::Register>,
+    /// Interrupt Enable
+    inte: ReadWrite<u32, ALARM::Register>,
+    /// Interrupt Force
+    intf: ReadWrite<u32, ALARM::Register>,
+    /// Interrupt status after masking & forcing
+    ints: ReadOnly<u32, ALARM::Register>,
+}
+
+register_bitfields![u32,
+    ALARM [
+        ALARM_3 OFFSET(3) NUMBITS(1) [],
+        ALARM_2 OFFSET(2) NUMBITS(1) [],
+        ALARM_1 OFFSET(1) NUMBITS(1) [],
+        ALARM_0 OFFSET(0) NUMBITS(1) []
+    ]
+];
+
+const TIMER_BASE: StaticRef<TimerRegisters> =
+    unsafe { StaticRef::new(0x40054000 as *const TimerRegisters) };
+
+pub struct Timer {
+    registers: StaticRef<TimerRegisters>,
+    client: OptionalCell<&'static time::Client>,
+}
+
+pub static mut TIMER: Timer = Timer::new();
+
+impl Timer {
+    const fn new() -> Timer {
+        Timer {
+            registers: TIMER_BASE,
+            client: OptionalCell::empty(),
+        }
+    }
+
+    pub fn set_client(&self, client: &'static time::Client) {
+        self.client.set(client);
+    }
+
+    pub fn handle_interrupt(&self) {
+        self.registers.intf.modify(ALARM::ALARM_0::CLEAR);
+        self.registers.inte.modify(ALARM::ALARM_0::CLEAR);
+        self.registers.intr.write(ALARM::ALARM_0::SET);
+        self.client.map(|client| client.fired());
+    }
+}
+
+pub struct TimerFreq(());
+
+impl Frequency for TimerFreq {
+    fn frequency() -> u32 {
+        1_000_000
+    }
+}
+
+impl Time for Timer {
+    type Frequency = TimerFreq;
+
+    fn disable(&self) {
+        self.registers.inte.modify(ALARM::ALARM_0::CLEAR);
+        self.registers.armed.write(ALARM::ALARM_0::SET);
+        self.registers.intr.write(ALARM::ALARM_0::SET);
+    }
+
+    fn is_armed(&self) -> bool {
+        self.registers.inte.is_set(ALARM::ALARM_0)
+    }
+}
+
+impl Alarm for Timer {
+    fn now(&self) -> u32 {
+        self.registers.timerawl.get()
+    }
+
+    fn set_alarm(&self, tics: u32) {
+        self.registers.intr.write(ALARM::ALARM_0::SET);
+        self.registers.inte.modify(ALARM::ALARM_0::SET);
+        self.registers.alarm[0].set(tics);
+
+        // The alarm only fires on an exact match, so one that is already in
+        // the past would not fire until the counter wraps around
+        let now = self.now();
+        if now.wrapping_sub(tics) < (1 << 31) && self.registers.armed.is_set(ALARM::ALARM_0) {
+            self.registers.armed.write(ALARM::ALARM_0::SET);
+            self.registers.intf.modify(ALARM::ALARM_0::SET);
+        }
+    }
+
+    fn get_alarm(&self) -> u32 {
+        self.registers.alarm[0].get()
+    }
+}
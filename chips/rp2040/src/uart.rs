@@ -0,0 +1,441 @@
+//! UART driver for the ARM PL011 UARTs of the RP2040.
+//!
+//! Transmission and reception go through the 32 entry FIFOs, refilled and
+//! drained from the UART interrupt. The PL011 only raises its transmit
+//! interrupt when the FIFO drains through the trigger level, which never
+//! happens for a buffer small enough to fit below it, so the completion of
+//! a buffer that fits entirely in the FIFO is signaled with a deferred call.
+
+use core::cell::Cell;
+use kernel::common::cells::{OptionalCell, TakeCell};
+use kernel::common::deferred_call::DeferredCall;
+use kernel::common::registers::{register_bitfields, ReadOnly, ReadWrite, WriteOnly};
+use kernel::common::StaticRef;
+use kernel::hil;
+use kernel::ReturnCode;
+
+use crate::clocks;
+use crate::deferred_call_tasks::Task;
+
+#[repr(C)]
+pub struct UartRegisters {
+    /// Data Register
+    dr: ReadWrite<u32, DR::Register>,
+    /// Receive Status Register/Error Clear Register
+    rsr: ReadWrite<u32>,
+    _reserved0: [u32; 4],
+    /// Flag Register
+    fr: ReadOnly<u32, FR::Register>,
+    _reserved1: u32,
+    /// IrDA Low-Power Counter Register
+    ilpr: ReadWrite<u32>,
+    /// Integer Baud Rate Register
+    ibrd: ReadWrite<u32>,
+    /// Fractional Baud Rate Register
+    fbrd: ReadWrite<u32>,
+    /// Line Control Register
+    lcr_h: ReadWrite<u32, LCR_H::Register>,
+    /// Control Register
+    cr: ReadWrite<u32, CR::Register>,
+    /// Interrupt FIFO Level Select Register
+    ifls: ReadWrite<u32, IFLS::Register>,
+    /// Interrupt Mask Set/Clear Register
+    imsc: ReadWrite<u32, INTERRUPT::Register>,
+    /// Raw Interrupt Status Register
+    ris: ReadOnly<u32, INTERRUPT::Register>,
+    /// Masked Interrupt Status Register
+    mis: ReadOnly<u32, INTERRUPT::Register>,
+    /// Interrupt Clear Register
+    icr: WriteOnly<u32, INTERRUPT::Register>,
+    /// DMA Control Register
+    dmacr: ReadWrite<u32>,
+}
+
+register_bitfields![u32,
+    DR [
+        /// Overrun error
+        OE OFFSET(11) NUMBITS(1) [],
+        /// Break error
+        BE OFFSET(10) NUMBITS(1) [],
+        /// Parity error
+        PE OFFSET(9) NUMBITS(1) [],
+        /// Framing error
+        FE OFFSET(8) NUMBITS(1) [],
+        /// Receive (read) data character, transmit (write) data character
+        DATA OFFSET(0) NUMBITS(8) []
+    ],
+    FR [
+        /// Transmit FIFO empty
+        TXFE OFFSET(7) NUMBITS(1) [],
+        /// Receive FIFO full
+        RXFF OFFSET(6) NUMBITS(1) [],
+        /// Transmit FIFO full
+        TXFF OFFSET(5) NUMBITS(1) [],
+        /// Receive FIFO empty
+        RXFE OFFSET(4) NUMBITS(1) [],
+        /// UART busy transmitting data
+        BUSY OFFSET(3) NUMBITS(1) []
+    ],
+    LCR_H [
+        /// Stick parity select
+        SPS OFFSET(7) NUMBITS(1) [],
+        /// Word length
+        WLEN OFFSET(5) NUMBITS(2) [
+            Bits5 = 0,
+            Bits6 = 1,
+            Bits7 = 2,
+            Bits8 = 3
+        ],
+        /// Enable FIFOs
+        FEN OFFSET(4) NUMBITS(1) [],
+        /// Two stop bits select
+        STP2 OFFSET(3) NUMBITS(1) [],
+        /// Even parity select
+        EPS OFFSET(2) NUMBITS(1) [],
+        /// Parity enable
+        PEN OFFSET(1) NUMBITS(1) [],
+        /// Send break
+        BRK OFFSET(0) NUMBITS(1) []
+    ],
+    CR [
+        /// CTS hardware flow control enable
+        CTSEN OFFSET(15) NUMBITS(1) [],
+        /// RTS hardware flow control enable
+        RTSEN OFFSET(14) NUMBITS(1) [],
+        /// Receive enable
+        RXE OFFSET(9) NUMBITS(1) [],
+        /// Transmit enable
+        TXE OFFSET(8) NUMBITS(1) [],
+        /// UART enable
+        UARTEN OFFSET(0) NUMBITS(1) []
+    ],
+    IFLS [
+        /// Receive interrupt FIFO level select
+        RXIFLSEL OFFSET(3) NUMBITS(3) [
+            OneEighth = 0,
+            OneQuarter = 1,
+            OneHalf = 2,
+            ThreeQuarters = 3,
+            SevenEighths = 4
+        ],
+        /// Transmit interrupt FIFO level select
+        TXIFLSEL OFFSET(0) NUMBITS(3) [
+            OneEighth = 0,
+            OneQuarter = 1,
+            OneHalf = 2,
+            ThreeQuarters = 3,
+            SevenEighths = 4
+        ]
+    ],
+    INTERRUPT [
+        /// Overrun error interrupt
+        OE OFFSET(10) NUMBITS(1) [],
+        /// Break error interrupt
+        BE OFFSET(9) NUMBITS(1) [],
+        /// Parity error interrupt
+        PE OFFSET(8) NUMBITS(1) [],
+        /// Framing error interrupt
+        FE OFFSET(7) NUMBITS(1) [],
+        /// Receive timeout interrupt
+        RT OFFSET(6) NUMBITS(1) [],
+        /// Transmit interrupt
+        TX OFFSET(5) NUMBITS(1) [],
+        /// Receive interrupt
+        RX OFFSET(4) NUMBITS(1) []
+    ]
+];
+
+const UART0_BASE: StaticRef<UartRegisters> =
+    unsafe { StaticRef::new(0x40034000 as *const UartRegisters) };
+const UART1_BASE: StaticRef<UartRegisters> =
+    unsafe { StaticRef::new(0x40038000 as *const UartRegisters) };
+
+pub static mut UART0: Uart = Uart::new(UART0_BASE, Task::Uart0);
+pub static mut UART1: Uart = Uart::new(UART1_BASE, Task::Uart1);
+
+pub struct Uart<'a> {
+    registers: StaticRef<UartRegisters>,
+    tx_client: OptionalCell<&'a hil::uart::TransmitClient>,
+    rx_client: OptionalCell<&'a hil::uart::ReceiveClient>,
+    tx_buffer: TakeCell<'static, [u8]>,
+    tx_len: Cell<usize>,
+    tx_index: Cell<usize>,
+    rx_buffer: TakeCell<'static, [u8]>,
+    rx_len: Cell<usize>,
+    rx_index: Cell<usize>,
+    deferred_call: DeferredCall<Task>,
+}
+
+impl Uart<'a> {
+    const fn new(base: StaticRef<UartRegisters>, task: Task) -> Uart<'a> {
+        Uart {
+            registers: base,
+            tx_client: OptionalCell::empty(),
+            rx_client: OptionalCell::empty(),
+            tx_buffer: TakeCell::empty(),
+            tx_len: Cell::new(0),
+            tx_index: Cell::new(0),
+            rx_buffer: TakeCell::empty(),
+            rx_len: Cell::new(0),
+            rx_index: Cell::new(0),
+            deferred_call: unsafe { DeferredCall::new(task) },
+        }
+    }
+
+    fn set_baud_rate(&self, baud_rate: u32) {
+        // The divisor is a 16.6 fixed point number: divisor = f_peri / (16 *
+        // baud_rate). Compute it with one more fractional bit to round it.
+        let divisor = 8 * clocks::PERI_FREQUENCY / baud_rate;
+        let (integer, fraction) = match divisor >> 7 {
+            0 => (1, 0),
+            i if i >= 65535 => (65535, 0),
+            i => (i, ((divisor & 0x7f) + 1) / 2),
+        };
+        self.registers.ibrd.set(integer);
+        self.registers.fbrd.set(fraction);
+    }
+
+    /// Write bytes from the transmit buffer into the FIFO until either is
+    /// exhausted, and return whether the whole buffer has been written.
+    fn fill_fifo(&self) -> bool {
+        self.tx_buffer.map(|buffer| {
+            while self.tx_index.get() < self.tx_len.get() && !self.registers.fr.is_set(FR::TXFF) {
+                self.registers
+                    .dr
+                    .write(DR::DATA.val(buffer[self.tx_index.get()] as u32));
+                self.tx_index.set(self.tx_index.get() + 1);
+            }
+        });
+        self.tx_index.get() == self.tx_len.get()
+    }
+
+    fn transmit_done(&self) {
+        self.registers.imsc.modify(INTERRUPT::TX::CLEAR);
+        self.tx_client.map(|client| {
+            self.tx_buffer.take().map(|buffer| {
+                client.transmitted_buffer(buffer, self.tx_len.get(), ReturnCode::SUCCESS);
+            });
+        });
+    }
+
+    /// Read bytes from the FIFO into the receive buffer, and signal the
+    /// client once it is full or an error occurs.
+    fn drain_fifo(&self) {
+        let mut error = hil::uart::Error::None;
+        self.rx_buffer.map(|buffer| {
+            while self.rx_index.get() < self.rx_len.get() && !self.registers.fr.is_set(FR::RXFE) {
+                let data = self.registers.dr.extract();
+                if data.is_set(DR::OE) {
+                    error = hil::uart::Error::OverrunError;
+                } else if data.is_set(DR::PE) {
+                    error = hil::uart::Error::ParityError;
+                } else if data.is_set(DR::FE) || data.is_set(DR::BE) {
+                    error = hil::uart::Error::FramingError;
+                }
+                buffer[self.rx_index.get()] = data.read(DR::DATA) as u8;
+                self.rx_index.set(self.rx_index.get() + 1);
+                if error != hil::uart::Error::None {
+                    break;
+                }
+            }
+        });
+
+        if self.rx_index.get() == self.rx_len.get() || error != hil::uart::Error::None {
+            self.registers
+                .imsc
+                .modify(INTERRUPT::RX::CLEAR + INTERRUPT::RT::CLEAR);
+            let rval = if error == hil::uart::Error::None {
+                ReturnCode::SUCCESS
+            } else {
+                ReturnCode::FAIL
+            };
+            self.rx_client.map(|client| {
+                self.rx_buffer.take().map(|buffer| {
+                    client.received_buffer(buffer, self.rx_index.get(), rval, error);
+                });
+            });
+        }
+    }
+
+    pub fn handle_interrupt(&self) {
+        let status = self.registers.mis.extract();
+
+        if status.is_set(INTERRUPT::TX) {
+            self.registers.icr.write(INTERRUPT::TX::SET);
+            if self.tx_buffer.is_some() && self.fill_fifo() {
+                self.transmit_done();
+            }
+        }
+
+        if status.is_set(INTERRUPT::RX) || status.is_set(INTERRUPT::RT) {
+            self.registers
+                .icr
+                .write(INTERRUPT::RX::SET + INTERRUPT::RT::SET);
+            self.drain_fifo();
+        }
+    }
+
+    /// Called from the deferred call of this UART, when a buffer was written
+    /// into the FIFO without waiting for an interrupt.
+    pub fn handle_deferred_call(&self) {
+        if self.tx_buffer.is_some() {
+            self.transmit_done();
+        }
+    }
+
+    /// Write a byte, waiting for room in the FIFO. This is used for panic
+    /// messages, and must not be mixed with `transmit_buffer`.
+    pub fn send_byte(&self, data: u8) {
+        while self.registers.fr.is_set(FR::TXFF) {}
+        self.registers.dr.write(DR::DATA.val(data as u32));
+    }
+
+    pub fn is_configured(&self) -> bool {
+        self.registers.cr.is_set(CR::UARTEN)
+    }
+}
+
+impl hil::uart::UartData<'a> for Uart<'a> {}
+impl hil::uart::Uart<'a> for Uart<'a> {}
+
+impl hil::uart::Configure for Uart<'a> {
+    fn configure(&self, params: hil::uart::Parameters) -> ReturnCode {
+        if params.baud_rate == 0 {
+            return ReturnCode::EINVAL;
+        }
+
+        // The line control and baud rate registers may only be changed while
+        // the UART is disabled
+        self.registers.cr.set(0);
+
+        self.set_baud_rate(params.baud_rate);
+
+        let width = match params.width {
+            hil::uart::Width::Six => LCR_H::WLEN::Bits6,
+            hil::uart::Width::Seven => LCR_H::WLEN::Bits7,
+            hil::uart::Width::Eight => LCR_H::WLEN::Bits8,
+        };
+        let parity = match params.parity {
+            hil::uart::Parity::None => LCR_H::PEN::CLEAR,
+            hil::uart::Parity::Odd => LCR_H::PEN::SET + LCR_H::EPS::CLEAR,
+            hil::uart::Parity::Even => LCR_H::PEN::SET + LCR_H::EPS::SET,
+        };
+        let stop_bits = match params.stop_bits {
+            hil::uart::StopBits::One => LCR_H::STP2::CLEAR,
+            hil::uart::StopBits::Two => LCR_H::STP2::SET,
+        };
+        // Writing LCR_H also latches the new baud rate
+        self.registers
+            .lcr_h
+            .write(width + parity + stop_bits + LCR_H::FEN::SET);
+
+        self.registers
+            .ifls
+            .write(IFLS::RXIFLSEL::OneHalf + IFLS::TXIFLSEL::OneEighth);
+
+        let flow_control = if params.hw_flow_control {
+            CR::CTSEN::SET + CR::RTSEN::SET
+        } else {
+            CR::CTSEN::CLEAR + CR::RTSEN::CLEAR
+        };
+        self.registers
+            .cr
+            .write(CR::UARTEN::SET + CR::TXE::SET + CR::RXE::SET + flow_control);
+
+        ReturnCode::SUCCESS
+    }
+}
+
+impl hil::uart::Transmit<'a> for Uart<'a> {
+    fn set_transmit_client(&self, client: &'a hil::uart::TransmitClient) {
+        self.tx_client.set(client);
+    }
+
+    fn transmit_buffer(
+        &self,
+        tx_data: &'static mut [u8],
+        tx_len: usize,
+    ) -> (ReturnCode, Option<&'static mut [u8]>) {
+        if self.tx_buffer.is_some() {
+            return (ReturnCode::EBUSY, Some(tx_data));
+        }
+        if tx_len == 0 || tx_len > tx_data.len() {
+            return (ReturnCode::ESIZE, Some(tx_data));
+        }
+
+        self.tx_buffer.replace(tx_data);
+        self.tx_len.set(tx_len);
+        self.tx_index.set(0);
+
+        if self.fill_fifo() {
+            self.deferred_call.set();
+        } else {
+            self.registers.imsc.modify(INTERRUPT::TX::SET);
+        }
+
+        (ReturnCode::SUCCESS, None)
+    }
+
+    fn transmit_abort(&self) -> ReturnCode {
+        ReturnCode::FAIL
+    }
+
+    fn transmit_word(&self, _word: u32) -> ReturnCode {
+        ReturnCode::FAIL
+    }
+}
+
+impl hil::uart::Receive<'a> for Uart<'a> {
+    fn set_receive_client(&self, client: &'a hil::uart::ReceiveClient) {
+        self.rx_client.set(client);
+    }
+
+    fn receive_buffer(
+        &self,
+        rx_buffer: &'static mut [u8],
+        rx_len: usize,
+    ) -> (ReturnCode, Option<&'static mut [u8]>) {
+        if self.rx_buffer.is_some() {
+            return (ReturnCode::EBUSY, Some(rx_buffer));
+        }
+        if rx_len == 0 || rx_len > rx_buffer.len() {
+            return (ReturnCode::ESIZE, Some(rx_buffer));
+        }
+
+        self.rx_buffer.replace(rx_buffer);
+        self.rx_len.set(rx_len);
+        self.rx_index.set(0);
+
+        // The receive timeout interrupt reports bytes that stay below the
+        // FIFO trigger level
+        self.registers
+            .imsc
+            .modify(INTERRUPT::RX::SET + INTERRUPT::RT::SET);
+
+        (ReturnCode::SUCCESS, None)
+    }
+
+    fn receive_abort(&self) -> ReturnCode {
+        self.registers
+            .imsc
+            .modify(INTERRUPT::RX::CLEAR + INTERRUPT::RT::CLEAR);
+        match self.rx_buffer.take() {
+            Some(buffer) => {
+                self.rx_client.map(move |client| {
+                    client.received_buffer(
+                        buffer,
+                        self.rx_index.get(),
+                        ReturnCode::ECANCEL,
+                        hil::uart::Error::Aborted,
+                    );
+                });
+                ReturnCode::SUCCESS
+            }
+            None => ReturnCode::SUCCESS,
+        }
+    }
+
+    fn receive_word(&self) -> ReturnCode {
+        ReturnCode::FAIL
+    }
+}
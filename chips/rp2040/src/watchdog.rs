@@ -0,0 +1,63 @@
+//! Watchdog of the RP2040.
+//!
+//! Only its tick generator is used here: it divides `clk_ref` down to the 1
+//! MHz tick that drives the timer.
+
+use kernel::common::registers::{register_bitfields, ReadOnly, ReadWrite};
+use kernel::common::StaticRef;
+
+use crate::xosc;
+
+#[repr(C)]
+struct WatchdogRegisters {
+    /// Watchdog control
+    ctrl: ReadWrite<u32>,
+    /// Load the watchdog timer
+    load: ReadWrite<u32>,
+    /// Logs the reason for the last reset
+    reason: ReadOnly<u32>,
+    /// Scratch registers, preserved through a soft reset
+    scratch: [ReadWrite<u32>; 8],
+    /// Controls the tick generator
+    tick: ReadWrite<u32, TICK::Register>,
+}
+
+register_bitfields![u32,
+    TICK [
+        /// Count down timer: the remaining number of clk_ref cycles before
+        /// the next tick
+        COUNT OFFSET(11) NUMBITS(9) [],
+        /// Is the tick generator running?
+        RUNNING OFFSET(10) NUMBITS(1) [],
+        /// Start or stop the tick generator
+        ENABLE OFFSET(9) NUMBITS(1) [],
+        /// Total number of clk_ref cycles between each tick
+        CYCLES OFFSET(0) NUMBITS(9) []
+    ]
+];
+
+const WATCHDOG_BASE: StaticRef<WatchdogRegisters> =
+    unsafe { StaticRef::new(0x40058000 as *const WatchdogRegisters) };
+
+pub struct Watchdog {
+    registers: StaticRef<WatchdogRegisters>,
+}
+
+pub static mut WATCHDOG: Watchdog = Watchdog::new();
+
+impl Watchdog {
+    const fn new() -> Watchdog {
+        Watchdog {
+            registers: WATCHDOG_BASE,
+        }
+    }
+
+    /// Start the 1 MHz tick, once `clk_ref` runs from the crystal
+    /// oscillator.
+    pub fn start_tick(&self) {
+        self.registers
+            .tick
+            .write(TICK::CYCLES.val(xosc::XOSC_FREQUENCY / 1_000_000) + TICK::ENABLE::SET);
+        while !self.registers.tick.is_set(TICK::RUNNING) {}
+    }
+}
@@ -0,0 +1,88 @@
+//! Crystal oscillator.
+//!
+//! The Pico and most RP2040 boards have a 12 MHz crystal, which is the
+//! reference for both PLLs.
+
+use kernel::common::registers::{register_bitfields, ReadWrite};
+use kernel::common::StaticRef;
+
+#[repr(C)]
+struct XoscRegisters {
+    /// Crystal Oscillator Control
+    ctrl: ReadWrite<u32, CTRL::Register>,
+    /// Crystal Oscillator Status
+    status: ReadWrite<u32, STATUS::Register>,
+    /// Crystal Oscillator pause control
+    dormant: ReadWrite<u32>,
+    /// Controls the startup delay
+    startup: ReadWrite<u32, STARTUP::Register>,
+    _reserved0: [u32; 3],
+    /// A down counter running at the xosc frequency which counts to zero and
+    /// stops
+    count: ReadWrite<u32>,
+}
+
+register_bitfields![u32,
+    CTRL [
+        /// On power-up this field is initialised to DISABLE and the chip runs
+        /// from the ROSC
+        ENABLE OFFSET(12) NUMBITS(12) [
+            Disable = 0xd1e,
+            Enable = 0xfab
+        ],
+        /// Frequency range
+        FREQ_RANGE OFFSET(0) NUMBITS(12) [
+            Range1_15MHz = 0xaa0
+        ]
+    ],
+    STATUS [
+        /// Oscillator is running and stable
+        STABLE OFFSET(31) NUMBITS(1) [],
+        /// An invalid value has been written to CTRL_ENABLE or
+        /// CTRL_FREQ_RANGE or DORMANT
+        BADWRITE OFFSET(24) NUMBITS(1) [],
+        /// Oscillator is enabled but not necessarily running and stable
+        ENABLED OFFSET(12) NUMBITS(1) []
+    ],
+    STARTUP [
+        /// Multiplies the startup_delay by 4
+        X4 OFFSET(20) NUMBITS(1) [],
+        /// In multiples of 256*xtal_period
+        DELAY OFFSET(0) NUMBITS(14) []
+    ]
+];
+
+const XOSC_BASE: StaticRef<XoscRegisters> =
+    unsafe { StaticRef::new(0x40024000 as *const XoscRegisters) };
+
+/// The frequency of the crystal, in Hz.
+pub const XOSC_FREQUENCY: u32 = 12_000_000;
+
+pub struct Xosc {
+    registers: StaticRef<XoscRegisters>,
+}
+
+pub static mut XOSC: Xosc = Xosc::new();
+
+impl Xosc {
+    const fn new() -> Xosc {
+        Xosc {
+            registers: XOSC_BASE,
+        }
+    }
+
+    /// Start the oscillator and wait until it is stable.
+    pub fn init(&self) {
+        // The startup delay is in units of 256 crystal periods, and should
+        // be at least 1ms.
+        let delay = ((XOSC_FREQUENCY / 1000) + 255) / 256;
+        self.registers.ctrl.write(CTRL::FREQ_RANGE::Range1_15MHz);
+        self.registers.startup.write(STARTUP::DELAY.val(delay));
+        self.registers.ctrl.modify(CTRL::ENABLE::Enable);
+        while !self.registers.status.is_set(STATUS::STABLE) {}
+    }
+
+    pub fn disable(&self) {
+        self.registers.ctrl.modify(CTRL::ENABLE::Disable);
+    }
+}
@@ -21,6 +21,8 @@ use crate::usbc;
 
 use cortexm4;
 use kernel::common::deferred_call;
+use kernel::interrupt_latency;
+use kernel::record;
 use kernel::Chip;
 
 pub struct Sam4l {
@@ -63,6 +65,9 @@ impl Sam4l {
         adc::ADC0.set_dma(&dma::DMA_CHANNELS[13]);
         dma::DMA_CHANNELS[13].initialize(&mut adc::ADC0, dma::DMAWidth::Width16Bit);
 
+        dac::DAC.set_dma(&dma::DMA_CHANNELS[14]);
+        dma::DMA_CHANNELS[14].initialize(&mut dac::DAC, dma::DMAWidth::Width16Bit);
+
         Sam4l {
             mpu: cortexm4::mpu::MPU::new(),
             userspace_kernel_boundary: cortexm4::syscall::SysCall::new(),
@@ -84,6 +89,8 @@ impl Chip for Sam4l {
                         Task::Flashcalw => flashcalw::FLASH_CONTROLLER.handle_interrupt(),
                     }
                 } else if let Some(interrupt) = cortexm4::nvic::next_pending() {
+                    record::interrupt(interrupt);
+                    interrupt_latency::serviced(interrupt);
                     match interrupt {
                         nvic::ASTALARM => ast::AST.handle_interrupt(),
 
@@ -174,13 +174,16 @@ impl TCR {
     }
 }
 
-fn poly_for_alg(alg: CrcAlg) -> FieldValue<u32, Mode::Register> {
+fn poly_for_alg(alg: CrcAlg) -> Option<FieldValue<u32, Mode::Register>> {
     match alg {
-        CrcAlg::Crc32 => Mode::PTYPE::Ccit8023,
-        CrcAlg::Crc32C => Mode::PTYPE::Castagnoli,
-        CrcAlg::Sam4L16 => Mode::PTYPE::Ccit16,
-        CrcAlg::Sam4L32 => Mode::PTYPE::Ccit8023,
-        CrcAlg::Sam4L32C => Mode::PTYPE::Castagnoli,
+        CrcAlg::Crc32 => Some(Mode::PTYPE::Ccit8023),
+        CrcAlg::Crc32C => Some(Mode::PTYPE::Castagnoli),
+        CrcAlg::Sam4L16 => Some(Mode::PTYPE::Ccit16),
+        CrcAlg::Sam4L32 => Some(Mode::PTYPE::Ccit8023),
+        CrcAlg::Sam4L32C => Some(Mode::PTYPE::Castagnoli),
+        // The unit only supports the three polynomials above, with a fixed
+        // initial value and bit order
+        CrcAlg::Crc8 | CrcAlg::Crc16CCITT | CrcAlg::Custom(_) => None,
     }
 }
 
@@ -191,6 +194,7 @@ fn post_process(result: u32, alg: CrcAlg) -> u32 {
         CrcAlg::Sam4L16 => result,
         CrcAlg::Sam4L32 => result,
         CrcAlg::Sam4L32C => result,
+        CrcAlg::Crc8 | CrcAlg::Crc16CCITT | CrcAlg::Custom(_) => result,
     }
 }
 
@@ -343,6 +347,11 @@ impl crc::CRC for Crccu<'a> {
     fn compute(&self, data: &[u8], alg: CrcAlg) -> ReturnCode {
         let regs: &CrccuRegisters = &*self.registers;
 
+        let poly = match poly_for_alg(alg) {
+            Some(poly) => poly,
+            None => return ReturnCode::ENOSUPPORT,
+        };
+
         self.init();
 
         if self.get_tcr().interrupt_enabled() {
@@ -386,9 +395,8 @@ impl crc::CRC for Crccu<'a> {
         self.alg.set(alg);
 
         // Configure the unit to compute a checksum
-        regs.mr.write(
-            Mode::DIVIDER.val(0) + poly_for_alg(alg) + Mode::COMPARE::CLEAR + Mode::ENABLE::Enabled,
-        );
+        regs.mr
+            .write(Mode::DIVIDER.val(0) + poly + Mode::COMPARE::CLEAR + Mode::ENABLE::Enabled);
 
         // Enable DMA channel
         regs.dmaen.write(DmaEnable::DMAEN::SET);
@@ -2,14 +2,22 @@
 //!
 //! Ensure that the `ADVREFP` pin is tied to `ADDANA`.
 //!
+//! Besides setting single values, the DACC can play audio: samples are moved
+//! to the DACC by the PDCA, and the DACC's internal trigger converts one every
+//! `CLKDIV` cycles of its clock, so playback needs no interrupts until the
+//! buffer is done.
+//!
 //! - Author: Justin Hsieh <hsiehju@umich.edu>
 //! - Date: May 26th, 2017
 
+use crate::dma;
 use crate::pm::{self, Clock, PBAClock};
 use core::cell::Cell;
+use kernel::common::cells::OptionalCell;
 use kernel::common::registers::{register_bitfields, ReadOnly, ReadWrite, WriteOnly};
 use kernel::common::StaticRef;
 use kernel::hil;
+use kernel::hil::audio::SAMPLE_LEN;
 use kernel::ReturnCode;
 
 #[repr(C)]
@@ -116,9 +124,21 @@ register_bitfields![u32,
 const DAC_BASE: StaticRef<DacRegisters> =
     unsafe { StaticRef::new(0x4003C000 as *const DacRegisters) };
 
+/// Clock divider used outside of audio playback, from 48 MHz to 500 kHz
+const DEFAULT_CLKDIV: u32 = 0x60;
+
+/// Highest sample rate, limited by the conversion time of the DACC
+const MAX_SAMPLE_RATE_HZ: u32 = 500000;
+
+/// Most samples that the PDCA can transfer at once
+const MAX_SAMPLES: usize = 0xffff;
+
 pub struct Dac {
     registers: StaticRef<DacRegisters>,
     enabled: Cell<bool>,
+    dma: OptionalCell<&'static dma::DMAChannel>,
+    audio_client: OptionalCell<&'static hil::audio::OutputClient>,
+    playing: Cell<bool>,
 }
 
 pub static mut DAC: Dac = Dac::new(DAC_BASE);
@@ -128,11 +148,42 @@ impl Dac {
         Dac {
             registers: base_address,
             enabled: Cell::new(false),
+            dma: OptionalCell::empty(),
+            audio_client: OptionalCell::empty(),
+            playing: Cell::new(false),
         }
     }
 
+    /// Sets the DMA channel used for audio playback.
+    pub fn set_dma(&self, dma: &'static dma::DMAChannel) {
+        self.dma.set(dma);
+    }
+
     // Not currently using interrupt.
     pub fn handle_interrupt(&mut self) {}
+
+    /// The clock divider that converts samples at `sample_rate_hz`. The DACC
+    /// is clocked by PBA, which runs at the system frequency.
+    fn clock_divider(&self, sample_rate_hz: u32) -> u32 {
+        if sample_rate_hz == 0 {
+            return 0;
+        }
+        pm::get_system_frequency() / sample_rate_hz
+    }
+
+    fn playback_done(&self, result: ReturnCode) {
+        self.playing.set(false);
+        self.registers.mr.modify(Mode::CLKDIV.val(DEFAULT_CLKDIV));
+        let buffer = self.dma.map_or(None, |dma| {
+            let buf = dma.abort_transfer();
+            dma.disable();
+            buf
+        });
+        buffer.map(|buffer| {
+            self.audio_client
+                .map(move |client| client.playback_done(buffer, result))
+        });
+    }
 }
 
 impl hil::dac::DacChannel for Dac {
@@ -155,7 +206,7 @@ impl hil::dac::DacChannel for Dac {
             // -enable dacc
             let mr = Mode::WORD::HalfWordTransfer
                 + Mode::STARTUP.val(0xff)
-                + Mode::CLKDIV.val(0x60)
+                + Mode::CLKDIV.val(DEFAULT_CLKDIV)
                 + Mode::TRGEN::InternalTrigger
                 + Mode::DACEN::SET;
             regs.mr.write(mr);
@@ -179,3 +230,65 @@ impl hil::dac::DacChannel for Dac {
         }
     }
 }
+
+impl dma::DMAClient for Dac {
+    fn transfer_done(&self, _pid: dma::DMAPeripheral) {
+        self.playback_done(ReturnCode::SUCCESS);
+    }
+}
+
+impl hil::audio::AudioOutput<'static> for Dac {
+    fn set_client(&self, client: &'static hil::audio::OutputClient) {
+        self.audio_client.set(client);
+    }
+
+    fn sample_rate_supported(&self, sample_rate_hz: u32) -> bool {
+        let divider = self.clock_divider(sample_rate_hz);
+        sample_rate_hz <= MAX_SAMPLE_RATE_HZ && divider > 0 && divider <= 0xffff
+    }
+
+    fn play(
+        &self,
+        buffer: &'static mut [u8],
+        samples: usize,
+        sample_rate_hz: u32,
+    ) -> (ReturnCode, Option<&'static mut [u8]>) {
+        if !self.sample_rate_supported(sample_rate_hz) {
+            return (ReturnCode::EINVAL, Some(buffer));
+        }
+        if samples == 0 || samples > buffer.len() / SAMPLE_LEN || samples > MAX_SAMPLES {
+            return (ReturnCode::ESIZE, Some(buffer));
+        }
+        if self.playing.get() {
+            return (ReturnCode::EBUSY, Some(buffer));
+        }
+        let dma = match self.dma.map(|dma| *dma) {
+            Some(dma) => dma,
+            None => return (ReturnCode::EOFF, Some(buffer)),
+        };
+
+        // Convert the samples in place to the 10-bit offset binary values of
+        // the DACC, which the PDCA moves as halfwords
+        for sample in buffer[..samples * SAMPLE_LEN].chunks_mut(SAMPLE_LEN) {
+            let value = i16::from_le_bytes([sample[0], sample[1]]) as i32 + 0x8000;
+            sample.copy_from_slice(&((value >> 6) as u16).to_le_bytes());
+        }
+
+        hil::dac::DacChannel::initialize(self);
+        self.registers
+            .mr
+            .modify(Mode::CLKDIV.val(self.clock_divider(sample_rate_hz)));
+        self.playing.set(true);
+        dma.enable();
+        dma.do_transfer(dma::DMAPeripheral::DACC_TX, buffer, samples);
+        (ReturnCode::SUCCESS, None)
+    }
+
+    fn stop(&self) -> ReturnCode {
+        if !self.playing.get() {
+            return ReturnCode::EOFF;
+        }
+        self.playback_done(ReturnCode::ECANCEL);
+        ReturnCode::SUCCESS
+    }
+}
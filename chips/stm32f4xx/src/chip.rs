@@ -2,6 +2,7 @@
 
 use cortexm4;
 use kernel::common::deferred_call;
+use kernel::interrupt_latency;
 use kernel::Chip;
 
 use crate::deferred_call_tasks::Task;
@@ -9,6 +10,7 @@ use crate::dma1;
 use crate::exti;
 use crate::nvic;
 use crate::spi;
+use crate::tamper;
 use crate::tim2;
 use crate::usart;
 
@@ -41,6 +43,7 @@ impl Chip for Stm32f4xx {
                         Task::Nop => {}
                     }
                 } else if let Some(interrupt) = cortexm4::nvic::next_pending() {
+                    interrupt_latency::serviced(interrupt);
                     match interrupt {
                         nvic::DMA1_Stream1 => dma1::Dma1Peripheral::USART3_RX
                             .get_stream()
@@ -76,6 +79,9 @@ impl Chip for Stm32f4xx {
 
                         nvic::TIM2 => tim2::TIM2.handle_interrupt(),
 
+                        nvic::PVD => tamper::TAMPER.handle_pvd_interrupt(),
+                        nvic::TAMP_STAMP => tamper::TAMPER.handle_tamper_interrupt(),
+
                         _ => {
                             panic!("unhandled interrupt {}", interrupt);
                         }
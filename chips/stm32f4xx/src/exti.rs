@@ -381,6 +381,17 @@ enum_from_primitive! {
     }
 }
 
+/// EXTI lines that are connected to other peripherals rather than to GPIO
+/// pins. Each has its own NVIC interrupt, whose handler in the peripheral must
+/// clear the pending bit with `clear_internal_pending`.
+#[derive(Copy, Clone)]
+pub enum InternalLineId {
+    /// PVD output (EXTI16)
+    Pvd,
+    /// RTC tamper and timestamp events (EXTI21)
+    TampStamp,
+}
+
 // `line_gpiopin_map` is used to call `handle_interrupt()` on the pin.
 pub struct Exti<'a> {
     registers: StaticRef<ExtiRegisters>,
@@ -610,6 +621,42 @@ impl Exti<'a> {
         }
     }
 
+    /// Unmasks the interrupt of an internal line, triggered on its rising
+    /// edge.
+    pub fn enable_internal_line(&self, lineid: InternalLineId) {
+        match lineid {
+            InternalLineId::Pvd => {
+                self.registers.rtsr.modify(RTSR::TR16::SET);
+                self.registers.imr.modify(IMR::MR16::SET);
+            }
+            InternalLineId::TampStamp => {
+                self.registers.rtsr.modify(RTSR::TR21::SET);
+                self.registers.imr.modify(IMR::MR21::SET);
+            }
+        }
+    }
+
+    pub fn disable_internal_line(&self, lineid: InternalLineId) {
+        match lineid {
+            InternalLineId::Pvd => {
+                self.registers.imr.modify(IMR::MR16::CLEAR);
+                self.registers.rtsr.modify(RTSR::TR16::CLEAR);
+            }
+            InternalLineId::TampStamp => {
+                self.registers.imr.modify(IMR::MR21::CLEAR);
+                self.registers.rtsr.modify(RTSR::TR21::CLEAR);
+            }
+        }
+    }
+
+    // Pending clear happens by writing 1
+    pub fn clear_internal_pending(&self, lineid: InternalLineId) {
+        match lineid {
+            InternalLineId::Pvd => self.registers.pr.write(PR::PR16::SET),
+            InternalLineId::TampStamp => self.registers.pr.write(PR::PR21::SET),
+        }
+    }
+
     pub fn handle_interrupt(&self) {
         let mut exti_pr: u32 = 0;
 
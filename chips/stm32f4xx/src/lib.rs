@@ -18,9 +18,11 @@ pub mod dbg;
 pub mod dma1;
 pub mod exti;
 pub mod gpio;
+pub mod pwr;
 pub mod rcc;
 pub mod spi;
 pub mod syscfg;
+pub mod tamper;
 pub mod tim2;
 pub mod usart;
 
@@ -0,0 +1,125 @@
+use kernel::common::registers::{register_bitfields, ReadWrite};
+use kernel::common::StaticRef;
+use kernel::ClockInterface;
+
+use crate::rcc;
+
+/// Power controller
+#[repr(C)]
+struct PwrRegisters {
+    /// power control register
+    cr: ReadWrite<u32, CR::Register>,
+    /// power control/status register
+    csr: ReadWrite<u32, CSR::Register>,
+}
+
+register_bitfields![u32,
+    CR [
+        /// Disable backup domain write protection
+        DBP OFFSET(8) NUMBITS(1) [],
+        /// PVD level selection
+        PLS OFFSET(5) NUMBITS(3) [],
+        /// Power voltage detector enable
+        PVDE OFFSET(4) NUMBITS(1) [],
+        /// Clear standby flag
+        CSBF OFFSET(3) NUMBITS(1) [],
+        /// Clear wakeup flag
+        CWUF OFFSET(2) NUMBITS(1) [],
+        /// Power down deepsleep
+        PDDS OFFSET(1) NUMBITS(1) [],
+        /// Low-power deepsleep
+        LPDS OFFSET(0) NUMBITS(1) []
+    ],
+    CSR [
+        /// Backup regulator ready
+        BRR OFFSET(3) NUMBITS(1) [],
+        /// PVD output
+        PVDO OFFSET(2) NUMBITS(1) [],
+        /// Standby flag
+        SBF OFFSET(1) NUMBITS(1) [],
+        /// Wakeup flag
+        WUF OFFSET(0) NUMBITS(1) []
+    ]
+];
+
+const PWR_BASE: StaticRef<PwrRegisters> =
+    unsafe { StaticRef::new(0x40007000 as *const PwrRegisters) };
+
+/// Thresholds of the power voltage detector (PVD)
+#[derive(Copy, Clone)]
+pub enum PvdLevel {
+    V2_0 = 0b000,
+    V2_1 = 0b001,
+    V2_3 = 0b010,
+    V2_5 = 0b011,
+    V2_6 = 0b100,
+    V2_7 = 0b101,
+    V2_8 = 0b110,
+    V2_9 = 0b111,
+}
+
+pub struct Pwr {
+    registers: StaticRef<PwrRegisters>,
+    clock: PwrClock,
+}
+
+pub static mut PWR: Pwr = Pwr::new();
+
+impl Pwr {
+    const fn new() -> Pwr {
+        Pwr {
+            registers: PWR_BASE,
+            clock: PwrClock(rcc::PeripheralClock::APB1(rcc::PCLK1::PWR)),
+        }
+    }
+
+    pub fn is_enabled_clock(&self) -> bool {
+        self.clock.is_enabled()
+    }
+
+    pub fn enable_clock(&self) {
+        self.clock.enable();
+    }
+
+    pub fn disable_clock(&self) {
+        self.clock.disable();
+    }
+
+    /// Allows writes to the backup domain (the RTC and its backup registers).
+    pub fn enable_backup_access(&self) {
+        self.registers.cr.modify(CR::DBP::SET);
+    }
+
+    /// Starts comparing VDD against `level`. The PVD output, which is routed
+    /// to EXTI line 16, is set while VDD is below it.
+    pub fn enable_pvd(&self, level: PvdLevel) {
+        self.registers
+            .cr
+            .modify(CR::PLS.val(level as u32) + CR::PVDE::SET);
+    }
+
+    pub fn disable_pvd(&self) {
+        self.registers.cr.modify(CR::PVDE::CLEAR);
+    }
+
+    /// Returns whether VDD is currently below the PVD threshold.
+    pub fn is_below_pvd_level(&self) -> bool {
+        self.registers.csr.is_set(CSR::PVDO)
+    }
+}
+
+struct PwrClock(rcc::PeripheralClock);
+
+impl ClockInterface for PwrClock {
+    fn is_enabled(&self) -> bool {
+        self.0.is_enabled()
+    }
+
+    fn enable(&self) {
+        self.0.enable();
+    }
+
+    fn disable(&self) {
+        self.0.disable();
+    }
+}
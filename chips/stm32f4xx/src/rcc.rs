@@ -749,6 +749,20 @@ impl Rcc {
         self.registers.apb1enr.modify(APB1ENR::SPI3EN::CLEAR)
     }
 
+    // PWR clock
+
+    fn is_enabled_pwr_clock(&self) -> bool {
+        self.registers.apb1enr.is_set(APB1ENR::PWREN)
+    }
+
+    fn enable_pwr_clock(&self) {
+        self.registers.apb1enr.modify(APB1ENR::PWREN::SET)
+    }
+
+    fn disable_pwr_clock(&self) {
+        self.registers.apb1enr.modify(APB1ENR::PWREN::CLEAR)
+    }
+
     // TIM2 clock
 
     fn is_enabled_tim2_clock(&self) -> bool {
@@ -971,6 +985,7 @@ pub enum PCLK1 {
     USART2,
     USART3,
     SPI3,
+    PWR,
 }
 
 /// Peripherals clocked by PCLK2
@@ -997,6 +1012,7 @@ impl ClockInterface for PeripheralClock {
                 PCLK1::USART2 => unsafe { RCC.is_enabled_usart2_clock() },
                 PCLK1::USART3 => unsafe { RCC.is_enabled_usart3_clock() },
                 PCLK1::SPI3 => unsafe { RCC.is_enabled_spi3_clock() },
+                PCLK1::PWR => unsafe { RCC.is_enabled_pwr_clock() },
             },
             &PeripheralClock::APB2(ref v) => match v {
                 PCLK2::SYSCFG => unsafe { RCC.is_enabled_syscfg_clock() },
@@ -1048,6 +1064,9 @@ impl ClockInterface for PeripheralClock {
                 PCLK1::SPI3 => unsafe {
                     RCC.enable_spi3_clock();
                 },
+                PCLK1::PWR => unsafe {
+                    RCC.enable_pwr_clock();
+                },
             },
             &PeripheralClock::APB2(ref v) => match v {
                 PCLK2::SYSCFG => unsafe {
@@ -1101,6 +1120,9 @@ impl ClockInterface for PeripheralClock {
                 PCLK1::SPI3 => unsafe {
                     RCC.disable_spi3_clock();
                 },
+                PCLK1::PWR => unsafe {
+                    RCC.disable_pwr_clock();
+                },
             },
             &PeripheralClock::APB2(ref v) => match v {
                 PCLK2::SYSCFG => unsafe {
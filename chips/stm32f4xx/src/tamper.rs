@@ -0,0 +1,289 @@
+//! Tamper detection, using the RTC tamper input and the power voltage
+//! detector (PVD).
+//!
+//! - Case-open events are detected on the RTC_TAMP1 input (PC13), which is
+//!   in the backup domain and keeps working from VBAT while the rest of the
+//!   chip is off. The event is latched together with the RTC time at which it
+//!   happened. Because the timestamp unit does not record the year, the
+//!   timestamp is reported as seconds since the start of the month. The
+//!   hardware also erases the RTC backup registers when a tamper event is
+//!   detected, so secrets kept there are wiped even if no software runs.
+//! - Voltage glitches are detected by the PVD, which triggers when VDD drops
+//!   below a threshold. PVD events are latched in software, so they are lost
+//!   on reset and are not timestamped.
+//!
+//! The board is responsible for selecting and enabling the RTC clock, which
+//! must be running for case-open events to be timestamped.
+
+use core::cell::Cell;
+use kernel::common::cells::OptionalCell;
+use kernel::common::registers::{register_bitfields, ReadOnly, ReadWrite};
+use kernel::common::StaticRef;
+use kernel::hil::tamper::{self, TamperEvent, TamperSource};
+use kernel::ReturnCode;
+
+use crate::exti;
+use crate::pwr;
+
+/// Real-time clock, up to the tamper and alternate function configuration
+/// register
+#[repr(C)]
+struct RtcRegisters {
+    _reserved0: [u32; 2],
+    /// control register
+    cr: ReadWrite<u32, CR::Register>,
+    /// initialization and status register
+    isr: ReadWrite<u32, ISR::Register>,
+    _reserved1: [u32; 8],
+    /// time stamp time register
+    tstr: ReadOnly<u32, TSTR::Register>,
+    /// time stamp date register
+    tsdr: ReadOnly<u32, TSDR::Register>,
+    _reserved2: [u32; 2],
+    /// tamper and alternate function configuration register
+    tafcr: ReadWrite<u32, TAFCR::Register>,
+}
+
+register_bitfields![u32,
+    CR [
+        /// Hour format (0: 24 hour, 1: AM/PM)
+        FMT OFFSET(6) NUMBITS(1) []
+    ],
+    ISR [
+        /// Tamper 2 detection flag
+        TAMP2F OFFSET(14) NUMBITS(1) [],
+        /// Tamper 1 detection flag
+        TAMP1F OFFSET(13) NUMBITS(1) [],
+        /// Timestamp overflow flag
+        TSOVF OFFSET(12) NUMBITS(1) [],
+        /// Timestamp flag
+        TSF OFFSET(11) NUMBITS(1) []
+    ],
+    TSTR [
+        /// AM/PM notation
+        PM OFFSET(22) NUMBITS(1) [],
+        /// Hour tens in BCD format
+        HT OFFSET(20) NUMBITS(2) [],
+        /// Hour units in BCD format
+        HU OFFSET(16) NUMBITS(4) [],
+        /// Minute tens in BCD format
+        MNT OFFSET(12) NUMBITS(3) [],
+        /// Minute units in BCD format
+        MNU OFFSET(8) NUMBITS(4) [],
+        /// Second tens in BCD format
+        ST OFFSET(4) NUMBITS(3) [],
+        /// Second units in BCD format
+        SU OFFSET(0) NUMBITS(4) []
+    ],
+    TSDR [
+        /// Date tens in BCD format
+        DT OFFSET(4) NUMBITS(2) [],
+        /// Date units in BCD format
+        DU OFFSET(0) NUMBITS(4) []
+    ],
+    TAFCR [
+        /// Activate timestamp on tamper detection event
+        TAMPTS OFFSET(7) NUMBITS(1) [],
+        /// Tamper interrupt enable
+        TAMPIE OFFSET(2) NUMBITS(1) [],
+        /// Active level for tamper 1 (0: rising edge, 1: falling edge)
+        TAMP1TRG OFFSET(1) NUMBITS(1) [],
+        /// Tamper 1 detection enable
+        TAMP1E OFFSET(0) NUMBITS(1) []
+    ]
+];
+
+const RTC_BASE: StaticRef<RtcRegisters> =
+    unsafe { StaticRef::new(0x40002800 as *const RtcRegisters) };
+
+pub struct Tamper<'a> {
+    rtc: StaticRef<RtcRegisters>,
+    client: OptionalCell<&'a tamper::Client>,
+    case_open_falling_edge: Cell<bool>,
+    pvd_level: Cell<pwr::PvdLevel>,
+    pvd_latched: Cell<bool>,
+}
+
+pub static mut TAMPER: Tamper<'static> = Tamper::new();
+
+impl Tamper<'a> {
+    const fn new() -> Tamper<'a> {
+        Tamper {
+            rtc: RTC_BASE,
+            client: OptionalCell::empty(),
+            case_open_falling_edge: Cell::new(false),
+            pvd_level: Cell::new(pwr::PvdLevel::V2_9),
+            pvd_latched: Cell::new(false),
+        }
+    }
+
+    /// Selects whether a case-open event is a rising (the default) or falling
+    /// edge on RTC_TAMP1. Takes effect the next time case-open detection is
+    /// enabled.
+    pub fn set_case_open_falling_edge(&self, falling_edge: bool) {
+        self.case_open_falling_edge.set(falling_edge);
+    }
+
+    /// Sets the VDD level below which a voltage glitch is detected. Takes
+    /// effect the next time voltage glitch detection is enabled.
+    pub fn set_voltage_glitch_level(&self, level: pwr::PvdLevel) {
+        self.pvd_level.set(level);
+    }
+
+    /// The RTC registers can only be written with backup domain write
+    /// protection disabled.
+    fn enable_backup_access(&self) {
+        unsafe {
+            pwr::PWR.enable_clock();
+            pwr::PWR.enable_backup_access();
+        }
+    }
+
+    /// Converts the latched timestamp to seconds since the start of the month.
+    fn timestamp(&self) -> u32 {
+        let tstr = self.rtc.tstr.extract();
+        let tsdr = self.rtc.tsdr.extract();
+        let mut hours = tstr.read(TSTR::HT) * 10 + tstr.read(TSTR::HU);
+        if self.rtc.cr.is_set(CR::FMT) {
+            hours %= 12;
+            if tstr.is_set(TSTR::PM) {
+                hours += 12;
+            }
+        }
+        let minutes = tstr.read(TSTR::MNT) * 10 + tstr.read(TSTR::MNU);
+        let seconds = tstr.read(TSTR::ST) * 10 + tstr.read(TSTR::SU);
+        let day = tsdr.read(TSDR::DT) * 10 + tsdr.read(TSDR::DU);
+        ((day.saturating_sub(1) * 24 + hours) * 60 + minutes) * 60 + seconds
+    }
+
+    fn case_open_event(&self) -> Option<TamperEvent> {
+        if !self.rtc.isr.is_set(ISR::TAMP1F) {
+            return None;
+        }
+        Some(TamperEvent {
+            source: TamperSource::CaseOpen,
+            timestamp: if self.rtc.isr.is_set(ISR::TSF) {
+                Some(self.timestamp())
+            } else {
+                None
+            },
+        })
+    }
+
+    /// Handles the TAMP_STAMP interrupt. The tamper flag is left set, and
+    /// latches the event until it is cleared.
+    pub fn handle_tamper_interrupt(&self) {
+        unsafe {
+            exti::EXTI.clear_internal_pending(exti::InternalLineId::TampStamp);
+        }
+        self.case_open_event().map(|event| {
+            self.client.map(|client| client.tamper_detected(event));
+        });
+    }
+
+    /// Handles the PVD interrupt.
+    pub fn handle_pvd_interrupt(&self) {
+        unsafe {
+            exti::EXTI.clear_internal_pending(exti::InternalLineId::Pvd);
+        }
+        if self.pvd_latched.get() {
+            return;
+        }
+        self.pvd_latched.set(true);
+        self.client.map(|client| {
+            client.tamper_detected(TamperEvent {
+                source: TamperSource::VoltageGlitch,
+                timestamp: None,
+            })
+        });
+    }
+}
+
+impl tamper::Tamper<'a> for Tamper<'a> {
+    fn set_client(&self, client: &'a tamper::Client) {
+        self.client.set(client);
+    }
+
+    fn enable(&self, source: TamperSource) -> ReturnCode {
+        match source {
+            TamperSource::CaseOpen => {
+                self.enable_backup_access();
+                unsafe {
+                    exti::EXTI.enable_internal_line(exti::InternalLineId::TampStamp);
+                }
+                let trigger = if self.case_open_falling_edge.get() {
+                    TAFCR::TAMP1TRG::SET
+                } else {
+                    TAFCR::TAMP1TRG::CLEAR
+                };
+                // The trigger must be configured before detection is enabled,
+                // or changing it may cause a spurious event
+                self.rtc.tafcr.modify(TAFCR::TAMP1E::CLEAR + trigger);
+                self.rtc
+                    .tafcr
+                    .modify(TAFCR::TAMPTS::SET + TAFCR::TAMPIE::SET + TAFCR::TAMP1E::SET);
+                ReturnCode::SUCCESS
+            }
+            TamperSource::VoltageGlitch => {
+                unsafe {
+                    pwr::PWR.enable_clock();
+                    pwr::PWR.enable_pvd(self.pvd_level.get());
+                    exti::EXTI.enable_internal_line(exti::InternalLineId::Pvd);
+                }
+                ReturnCode::SUCCESS
+            }
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    fn disable(&self, source: TamperSource) -> ReturnCode {
+        match source {
+            TamperSource::CaseOpen => {
+                self.enable_backup_access();
+                self.rtc
+                    .tafcr
+                    .modify(TAFCR::TAMP1E::CLEAR + TAFCR::TAMPIE::CLEAR);
+                unsafe {
+                    exti::EXTI.disable_internal_line(exti::InternalLineId::TampStamp);
+                }
+                ReturnCode::SUCCESS
+            }
+            TamperSource::VoltageGlitch => {
+                unsafe {
+                    exti::EXTI.disable_internal_line(exti::InternalLineId::Pvd);
+                    pwr::PWR.disable_pvd();
+                }
+                ReturnCode::SUCCESS
+            }
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    fn latched(&self, source: TamperSource) -> Option<TamperEvent> {
+        match source {
+            TamperSource::CaseOpen => self.case_open_event(),
+            TamperSource::VoltageGlitch if self.pvd_latched.get() => Some(TamperEvent {
+                source: TamperSource::VoltageGlitch,
+                timestamp: None,
+            }),
+            _ => None,
+        }
+    }
+
+    fn clear(&self, source: TamperSource) -> ReturnCode {
+        match source {
+            TamperSource::CaseOpen => {
+                self.enable_backup_access();
+                // The timestamp flags must be cleared after the tamper flag
+                self.rtc.isr.modify(ISR::TAMP1F::CLEAR);
+                self.rtc.isr.modify(ISR::TSF::CLEAR + ISR::TSOVF::CLEAR);
+                ReturnCode::SUCCESS
+            }
+            TamperSource::VoltageGlitch => {
+                self.pvd_latched.set(false);
+                ReturnCode::SUCCESS
+            }
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+}
@@ -340,7 +340,15 @@ impl Tim2<'a> {
     }
 
     pub fn handle_interrupt(&self) {
-        self.registers.sr.modify(SR::CC1IF::CLEAR);
+        if self.registers.dier.is_set(DIER::UIE) && self.registers.sr.is_set(SR::UIF) {
+            // The counter reloaded, at the end of an interval of the timer
+            self.registers.sr.modify(SR::UIF::CLEAR);
+            if self.registers.cr1.is_set(CR1::OPM) {
+                self.registers.dier.modify(DIER::UIE::CLEAR);
+            }
+        } else {
+            self.registers.sr.modify(SR::CC1IF::CLEAR);
+        }
 
         self.client.map(|client| client.fired());
     }
@@ -358,6 +366,40 @@ impl Tim2<'a> {
         self.registers.egr.write(EGR::UG::SET);
         self.registers.cr1.modify(CR1::CEN::SET);
     }
+
+    /// Count from 0 to `interval - 1`, and interrupt when the counter reloads,
+    /// once if `one_pulse`, else at the end of every interval.
+    fn start_interval(&self, interval: u32, one_pulse: bool) {
+        self.registers
+            .dier
+            .modify(DIER::CC1IE::CLEAR + DIER::UIE::CLEAR);
+        self.registers.arr.set(interval.saturating_sub(1));
+        // In one pulse mode, the counter stops when it reloads. Only reloads
+        // set UIF, and not the update below, which resets the counter.
+        let mode = if one_pulse {
+            CR1::OPM::SET
+        } else {
+            CR1::OPM::CLEAR
+        };
+        self.registers.cr1.modify(CR1::URS::SET + mode);
+        self.registers.egr.write(EGR::UG::SET);
+        self.registers.sr.modify(SR::UIF::CLEAR);
+        self.registers.dier.modify(DIER::UIE::SET);
+        self.registers.cr1.modify(CR1::CEN::SET);
+    }
+}
+
+/// The timer reloads the counter with the interval, so `repeat` does not
+/// drift. This changes the period of the counter, so TIM2 can only be used as
+/// either a `Timer` or an `Alarm`.
+impl hil::time::Timer for Tim2<'a> {
+    fn oneshot(&self, interval: u32) {
+        self.start_interval(interval, true);
+    }
+
+    fn repeat(&self, interval: u32) {
+        self.start_interval(interval, false);
+    }
 }
 
 impl hil::time::Alarm for Tim2<'a> {
@@ -382,15 +424,17 @@ impl hil::time::Time for Tim2<'a> {
         unsafe {
             atomic(|| {
                 // Disable counter
-                self.registers.dier.modify(DIER::CC1IE::CLEAR);
+                self.registers
+                    .dier
+                    .modify(DIER::CC1IE::CLEAR + DIER::UIE::CLEAR);
                 cortexm4::nvic::Nvic::new(self.irqn).clear_pending();
             });
         }
     }
 
     fn is_armed(&self) -> bool {
-        // If counter is enabled, then CC1IE is set
-        self.registers.dier.is_set(DIER::CC1IE)
+        // If counter is enabled, then CC1IE, or UIE as a timer, is set
+        self.registers.dier.is_set(DIER::CC1IE) || self.registers.dier.is_set(DIER::UIE)
     }
 }
 
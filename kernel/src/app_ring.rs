@@ -0,0 +1,152 @@
+//! Ring buffers that a driver fills in memory allowed by a process.
+//!
+//! A driver that produces data faster than a process wants to hear about it,
+//! e.g. an ADC that streams samples or a radio in sniffer mode, can write the
+//! data into a ring in a buffer the process allowed, and only schedule a
+//! callback once the ring is filling up, instead of one for each block of
+//! data. The process consumes from the ring whenever it runs, and no copy or
+//! system call is needed to hand the data over.
+//!
+//! The ring is a header of three words, in the byte order of the chip,
+//! followed by the data:
+//!
+//! ```text
+//! offset  0: head     where the kernel writes the next byte
+//! offset  4: tail     where the process reads the next byte
+//! offset  8: dropped  bytes the kernel could not write, as the ring was full
+//! offset 12: data     the rest of the buffer
+//! ```
+//!
+//! `head` and `tail` are offsets into the data, and the ring is empty when
+//! they are equal. The kernel only writes `head` and `dropped`, and the
+//! process only writes `tail`, so neither needs a lock: the process reads
+//! from `tail` up to `head`, and then sets `tail` to `head`. One byte of the
+//! data always stays free, so a ring holds up to `capacity() - 1` bytes. The
+//! kernel writes each `push()` whole or not at all, so a process that pushes
+//! records of a fixed size always finds whole records in the ring.
+//!
+//! A process sets up a ring by zeroing the header and allowing the buffer; a
+//! driver wraps it with `AppRing::new()` in its `allow()`:
+//!
+//! ```ignore
+//! 1 => self.apps.enter(appid, |app, _| match slice.map(AppRing::new) {
+//!     Some(Ok(ring)) => { app.ring = Some(ring); ReturnCode::SUCCESS }
+//!     Some(Err(_)) => ReturnCode::EINVAL,
+//!     None => { app.ring = None; ReturnCode::SUCCESS }
+//! }).unwrap_or_else(|err| err.into()),
+//! ```
+
+use core::mem;
+
+use crate::mem::{AppSlice, Shared};
+use crate::returncode::ReturnCode;
+
+const HEAD: usize = 0;
+const TAIL: usize = 1;
+const DROPPED: usize = 2;
+const HEADER_WORDS: usize = 3;
+const WORD: usize = mem::size_of::<u32>();
+
+/// A ring in memory allowed by a process, which the kernel produces into
+/// and the process consumes from.
+pub struct AppRing {
+    slice: AppSlice<Shared, u8>,
+}
+
+impl AppRing {
+    /// The smallest buffer a ring fits in: the header, and at least two
+    /// bytes of data, so that it can hold one.
+    pub const MIN_LEN: usize = HEADER_WORDS * WORD + 2;
+
+    /// Use `slice` as a ring. Returns the slice if it is shorter than
+    /// `MIN_LEN`, or `head` or `tail` are not within its data.
+    pub fn new(slice: AppSlice<Shared, u8>) -> Result<AppRing, AppSlice<Shared, u8>> {
+        if slice.len() < AppRing::MIN_LEN {
+            return Err(slice);
+        }
+        let ring = AppRing { slice: slice };
+        match ring.indices() {
+            Some(_) => Ok(ring),
+            None => Err(ring.slice),
+        }
+    }
+
+    /// Give back the buffer of the ring.
+    pub fn into_slice(self) -> AppSlice<Shared, u8> {
+        self.slice
+    }
+
+    /// The number of bytes of data in the buffer.
+    pub fn capacity(&self) -> usize {
+        self.slice.len() - HEADER_WORDS * WORD
+    }
+
+    /// The number of bytes waiting for the process, or `None` if the process
+    /// corrupted the header.
+    pub fn len(&self) -> Option<usize> {
+        let (head, tail) = self.indices()?;
+        Some((head + self.capacity() - tail) % self.capacity())
+    }
+
+    /// The number of bytes that can be pushed, or `None` if the process
+    /// corrupted the header.
+    pub fn available(&self) -> Option<usize> {
+        self.len().map(|len| self.capacity() - 1 - len)
+    }
+
+    /// The number of bytes dropped, as the ring was full, since the process
+    /// last cleared the count.
+    pub fn dropped(&self) -> u32 {
+        self.word(DROPPED)
+    }
+
+    /// Write all of `data` into the ring. Returns
+    ///
+    /// - `ENOMEM` if it does not fit, in which case none of it is written
+    ///   and its length is added to the dropped bytes.
+    /// - `EINVAL` if the process corrupted the header.
+    pub fn push(&mut self, data: &[u8]) -> ReturnCode {
+        let available = match self.available() {
+            Some(available) => available,
+            None => return ReturnCode::EINVAL,
+        };
+        if data.len() > available {
+            let dropped = self.dropped().saturating_add(data.len() as u32);
+            self.set_word(DROPPED, dropped);
+            return ReturnCode::ENOMEM;
+        }
+        let capacity = self.capacity();
+        let mut head = self.word(HEAD) as usize;
+        let start = HEADER_WORDS * WORD;
+        for (i, byte) in data.iter().enumerate() {
+            self.slice.as_mut()[start + (head + i) % capacity] = *byte;
+        }
+        head = (head + data.len()) % capacity;
+        self.set_word(HEAD, head as u32);
+        ReturnCode::SUCCESS
+    }
+
+    /// `head` and `tail`, if both are within the data.
+    fn indices(&self) -> Option<(usize, usize)> {
+        let head = self.word(HEAD) as usize;
+        let tail = self.word(TAIL) as usize;
+        if head < self.capacity() && tail < self.capacity() {
+            Some((head, tail))
+        } else {
+            None
+        }
+    }
+
+    // The buffer need not be aligned, so the header is accessed as bytes
+    fn word(&self, index: usize) -> u32 {
+        let offset = index * WORD;
+        let mut raw = [0; WORD];
+        raw.copy_from_slice(&self.slice.as_ref()[offset..offset + WORD]);
+        u32::from_ne_bytes(raw)
+    }
+
+    fn set_word(&mut self, index: usize, value: u32) {
+        let offset = index * WORD;
+        self.slice.as_mut()[offset..offset + WORD].copy_from_slice(&value.to_ne_bytes());
+    }
+}
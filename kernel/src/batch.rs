@@ -0,0 +1,64 @@
+//! Implementation of the BATCH syscall.
+
+use core::mem;
+
+use crate::process::ProcessType;
+use crate::returncode::ReturnCode;
+
+/// The most commands one batch can hold.
+const MAX_COMMANDS: usize = 8;
+
+/// The words of each command: driver, command number, two arguments, and the
+/// result, which the kernel writes.
+const COMMAND_WORDS: usize = 5;
+
+/// Handle the `batch` syscall, which makes the `count` commands at `commands`
+/// in the memory of `process` with `command`, in order.
+///
+/// The kernel writes the result of each command into its last word, and stops
+/// after the first command that fails. It returns the number of commands that
+/// it made, or
+///
+/// - `EINVAL` if there are no commands, or they are not all in the memory of
+///   the process.
+/// - `ESIZE` if there are more than `MAX_COMMANDS`.
+crate fn batch<F>(
+    process: &ProcessType,
+    commands: *mut u8,
+    count: usize,
+    mut command: F,
+) -> ReturnCode
+where
+    F: FnMut(usize, usize, usize, usize) -> ReturnCode,
+{
+    if count == 0 {
+        return ReturnCode::EINVAL;
+    }
+    if count > MAX_COMMANDS {
+        return ReturnCode::ESIZE;
+    }
+    let word = mem::size_of::<usize>();
+    let mut slice = match process.allow(commands, count * COMMAND_WORDS * word) {
+        Ok(Some(slice)) => slice,
+        _ => return ReturnCode::EINVAL,
+    };
+
+    let mut made = 0;
+    for entry in slice.chunks_mut(COMMAND_WORDS * word) {
+        let mut words = [0; COMMAND_WORDS - 1];
+        for (value, bytes) in words.iter_mut().zip(entry.chunks(word)) {
+            let mut raw = [0; mem::size_of::<usize>()];
+            raw.copy_from_slice(bytes);
+            *value = usize::from_ne_bytes(raw);
+        }
+        let result = command(words[0], words[1], words[2], words[3]);
+        let raw = (isize::from(result) as usize).to_ne_bytes();
+        entry[(COMMAND_WORDS - 1) * word..].copy_from_slice(&raw);
+        made += 1;
+        match result {
+            ReturnCode::SUCCESS | ReturnCode::SuccessWithValue { .. } => {}
+            _ => break,
+        }
+    }
+    ReturnCode::SuccessWithValue { value: made }
+}
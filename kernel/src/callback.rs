@@ -50,6 +50,71 @@ impl AppId {
             (start, end)
         })
     }
+
+    /// Returns the same region of flash as `get_editable_flash_range`, so that
+    /// capsules can read data the app stores in its flash, or an empty slice
+    /// if the app does not exist.
+    pub fn get_editable_flash(&self) -> &'static [u8] {
+        self.kernel.process_map_or(&[], self.idx, |process| process.flash_non_protected())
+    }
+
+    /// Returns the package name of the app from its TBF header, or an empty
+    /// string if the app has no name or does not exist.
+    pub fn get_process_name(&self) -> &'static str {
+        self.kernel.process_map_or("", self.idx, |process| process.get_process_name())
+    }
+}
+
+/// How a callback merges events that happen while an earlier one is still
+/// queued for the process.
+///
+/// A source of bursty events, like the edges of a bouncing button or the
+/// overruns of a fast timer, can fill the callback queue of a process faster
+/// than the process runs, and then the events after are dropped. A capsule
+/// that marks the callback of such a source as coalescing collapses the
+/// events into the callback that is queued already, so that each
+/// subscription takes at most one slot in the queue. The first argument
+/// of the merged callback then summarizes the events, and the other two are
+/// those of the last event.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Coalesce {
+    /// Queue each event as its own callback.
+    Never,
+    /// Add up the first arguments, e.g. a number of events.
+    Count,
+    /// OR together the first arguments, e.g. a set of pins.
+    Bitmap,
+}
+
+impl Coalesce {
+    /// Merge `call` into `pending`, a queued call of the same callback.
+    /// Returns `false` if the callback does not coalesce.
+    crate fn merge(
+        self,
+        pending: &mut process::FunctionCall,
+        call: &process::FunctionCall,
+    ) -> bool {
+        pending.argument0 = match self {
+            Coalesce::Never => return false,
+            Coalesce::Count => pending.argument0.saturating_add(call.argument0),
+            Coalesce::Bitmap => pending.argument0 | call.argument0,
+        };
+        pending.argument1 = call.argument1;
+        pending.argument2 = call.argument2;
+        true
+    }
+}
+
+/// Which queue of the process a callback waits in.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Priority {
+    /// Run after the callbacks queued before it.
+    Normal,
+    /// Run before the callbacks of normal priority, e.g. for events that the
+    /// process must handle with little latency. The process sets how many
+    /// of these its queue holds in its TBF header; once they do not fit, they
+    /// are queued with normal priority.
+    High,
 }
 
 /// Type for calling a callback in a process.
@@ -60,6 +125,8 @@ pub struct Callback {
     app_id: AppId,
     appdata: usize,
     fn_ptr: NonNull<*mut ()>,
+    coalesce: Coalesce,
+    priority: Priority,
 }
 
 impl Callback {
@@ -68,6 +135,31 @@ impl Callback {
             app_id: appid,
             appdata: appdata,
             fn_ptr: fn_ptr,
+            coalesce: Coalesce::Never,
+            priority: Priority::Normal,
+        }
+    }
+
+    /// The same callback, merging the events that happen while it is queued
+    /// as `coalesce` says. Capsules call this in `subscribe()` for the
+    /// subscriptions that they document as coalescing.
+    pub fn coalesced(self, coalesce: Coalesce) -> Callback {
+        Callback {
+            coalesce: coalesce,
+            ..self
+        }
+    }
+
+    /// How the callback merges events.
+    pub fn coalescing(&self) -> Coalesce {
+        self.coalesce
+    }
+
+    /// The same callback, queued with `priority`.
+    pub fn with_priority(self, priority: Priority) -> Callback {
+        Callback {
+            priority: priority,
+            ..self
         }
     }
 
@@ -75,21 +167,28 @@ impl Callback {
     ///
     /// This will queue the `Callback` for the associated process. It returns
     /// `false` if the queue for the process is full and the callback could not
-    /// be scheduled.
+    /// be scheduled. A coalescing callback that is queued already is updated
+    /// instead, which always succeeds.
     ///
     /// The arguments (`r0-r2`) are the values passed back to the process and
     /// are specific to the individual `Driver` interfaces.
     pub fn schedule(&mut self, r0: usize, r1: usize, r2: usize) -> bool {
+        let call = process::FunctionCall {
+            argument0: r0,
+            argument1: r1,
+            argument2: r2,
+            argument3: self.appdata,
+            pc: self.fn_ptr.as_ptr() as usize,
+        };
+        let coalesce = self.coalesce;
+        let priority = self.priority;
         self.app_id
             .kernel
-            .process_map_or(false, self.app_id.idx(), |process| {
-                process.enqueue_task(process::Task::FunctionCall(process::FunctionCall {
-                    argument0: r0,
-                    argument1: r1,
-                    argument2: r2,
-                    argument3: self.appdata,
-                    pc: self.fn_ptr.as_ptr() as usize,
-                }))
+            .process_map_or(false, self.app_id.idx(), |process| match coalesce {
+                Coalesce::Never => {
+                    process.enqueue_task_with_priority(process::Task::FunctionCall(call), priority)
+                }
+                _ => process.coalesce_task(call, coalesce, priority),
             })
     }
 }
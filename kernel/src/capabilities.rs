@@ -57,3 +57,11 @@ pub unsafe trait MainLoopCapability {}
 /// The `MemoryAllocationCapability` capability allows the holder to allocate
 /// memory, for example by creating grants.
 pub unsafe trait MemoryAllocationCapability {}
+
+/// The `NetworkDiagnosticsCapability` capability allows the holder to let a
+/// process observe network traffic that is not addressed to it.
+pub unsafe trait NetworkDiagnosticsCapability {}
+
+/// The `SyscallTracingCapability` capability allows the holder to observe the
+/// system calls that every process makes, and their arguments.
+pub unsafe trait SyscallTracingCapability {}
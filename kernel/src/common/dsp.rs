@@ -0,0 +1,329 @@
+//! Fixed-point signal processing for sensor and audio capsules.
+//!
+//! Samples are `i16`, as produced by most ADCs and audio interfaces, and all
+//! arithmetic is done in integers, so none of this needs a floating point
+//! unit. Intermediate results are computed in 32 bits and saturated back to
+//! 16, so that overflow clips the signal instead of wrapping it around.
+//!
+//! - `Biquad` is a second-order IIR filter section, from which low-pass,
+//!   high-pass, band-pass and notch filters are built.
+//! - `MovingAverage` averages the last samples in a window, and
+//!   `ExponentialAverage` smooths samples with a single word of state.
+//! - `fft` computes the spectrum of up to `FFT_MAX_LEN` samples in place.
+//!
+//! ```
+//! use kernel::common::dsp::{self, Biquad, BiquadCoefficients};
+//!
+//! // Second-order Butterworth low-pass filter with a cutoff at 1/10th of the
+//! // sample rate
+//! let mut filter = Biquad::new(BiquadCoefficients::from_f32(
+//!     0.0675, 0.1349, 0.0675, -1.1430, 0.4128,
+//! ));
+//! let mut samples = [1000; 32];
+//! filter.process_buffer(&mut samples);
+//! assert!((samples[31] - 1000).abs() < 10);
+//!
+//! let mut real = [0; 16];
+//! let mut imag = [0; 16];
+//! real[0] = 1600;
+//! dsp::fft(&mut real, &mut imag).unwrap();
+//! // An impulse has a flat spectrum, scaled by 1/16
+//! assert!(real.iter().all(|&x| x == 100));
+//! ```
+
+use crate::returncode::ReturnCode;
+
+/// The largest number of points `fft` can transform.
+pub const FFT_MAX_LEN: usize = 256;
+
+/// Saturates `x` to the range of an `i16`.
+pub fn saturate(x: i32) -> i16 {
+    if x > i16::max_value() as i32 {
+        i16::max_value()
+    } else if x < i16::min_value() as i32 {
+        i16::min_value()
+    } else {
+        x as i16
+    }
+}
+
+/// Multiplies two Q15 numbers (fractions in [-1, 1) scaled by 2^15),
+/// rounding the result.
+pub fn q15_mul(a: i16, b: i16) -> i16 {
+    saturate((a as i32 * b as i32 + (1 << 14)) >> 15)
+}
+
+/// Coefficients of a biquad filter in Q2.14 format (scaled by 2^14), so that
+/// coefficients from -2 to 2 can be represented. `a1` and `a2` are the
+/// feedback coefficients, with `a0` normalized to 1.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct BiquadCoefficients {
+    pub b0: i16,
+    pub b1: i16,
+    pub b2: i16,
+    pub a1: i16,
+    pub a2: i16,
+}
+
+impl BiquadCoefficients {
+    const SHIFT: u32 = 14;
+
+    /// Converts coefficients as given by filter design tools. Coefficients
+    /// outside [-2, 2) are saturated. The conversion uses floating point, so
+    /// when speed matters it should be done once at startup, or the
+    /// coefficients computed ahead of time.
+    pub fn from_f32(b0: f32, b1: f32, b2: f32, a1: f32, a2: f32) -> BiquadCoefficients {
+        let convert = |c: f32| {
+            let scaled = c * (1 << Self::SHIFT) as f32;
+            // Round half away from zero
+            let rounded = if scaled < 0.0 {
+                scaled - 0.5
+            } else {
+                scaled + 0.5
+            };
+            saturate(rounded as i32)
+        };
+        BiquadCoefficients {
+            b0: convert(b0),
+            b1: convert(b1),
+            b2: convert(b2),
+            a1: convert(a1),
+            a2: convert(a2),
+        }
+    }
+}
+
+/// A second-order IIR filter section, in direct form I. Higher-order filters
+/// are built by cascading sections.
+pub struct Biquad {
+    coefficients: BiquadCoefficients,
+    x1: i16,
+    x2: i16,
+    y1: i16,
+    y2: i16,
+}
+
+impl Biquad {
+    pub fn new(coefficients: BiquadCoefficients) -> Biquad {
+        Biquad {
+            coefficients: coefficients,
+            x1: 0,
+            x2: 0,
+            y1: 0,
+            y2: 0,
+        }
+    }
+
+    /// Clears the filter's memory of past samples.
+    pub fn reset(&mut self) {
+        self.x1 = 0;
+        self.x2 = 0;
+        self.y1 = 0;
+        self.y2 = 0;
+    }
+
+    /// Filters one sample.
+    pub fn process(&mut self, x: i16) -> i16 {
+        let c = &self.coefficients;
+        let acc =
+            c.b0 as i32 * x as i32 + c.b1 as i32 * self.x1 as i32 + c.b2 as i32 * self.x2 as i32
+                - c.a1 as i32 * self.y1 as i32
+                - c.a2 as i32 * self.y2 as i32;
+        let y =
+            saturate((acc + (1 << (BiquadCoefficients::SHIFT - 1))) >> BiquadCoefficients::SHIFT);
+        self.x2 = self.x1;
+        self.x1 = x;
+        self.y2 = self.y1;
+        self.y1 = y;
+        y
+    }
+
+    /// Filters `samples` in place.
+    pub fn process_buffer(&mut self, samples: &mut [i16]) {
+        for sample in samples.iter_mut() {
+            *sample = self.process(*sample);
+        }
+    }
+}
+
+/// The average of the last samples, over a window whose length is that of
+/// the buffer given to `new`.
+pub struct MovingAverage<'a> {
+    window: &'a mut [i16],
+    /// Index of the oldest sample in the window
+    index: usize,
+    /// Number of samples in the window, until it has filled up
+    count: usize,
+    sum: i32,
+}
+
+impl MovingAverage<'a> {
+    /// Creates an average over `window.len()` samples, which must be at most
+    /// 65536 for the sum not to overflow.
+    pub fn new(window: &'a mut [i16]) -> MovingAverage<'a> {
+        MovingAverage {
+            window: window,
+            index: 0,
+            count: 0,
+            sum: 0,
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.index = 0;
+        self.count = 0;
+        self.sum = 0;
+    }
+
+    /// Adds a sample, and returns the average of the samples in the window.
+    /// Until the window has filled up, this is the average of all samples
+    /// added so far.
+    pub fn add(&mut self, x: i16) -> i16 {
+        if self.window.is_empty() {
+            return x;
+        }
+        if self.count == self.window.len() {
+            self.sum -= self.window[self.index] as i32;
+        } else {
+            self.count += 1;
+        }
+        self.sum += x as i32;
+        self.window[self.index] = x;
+        self.index = (self.index + 1) % self.window.len();
+        self.average()
+    }
+
+    /// The average of the samples in the window, or 0 if there are none.
+    pub fn average(&self) -> i16 {
+        if self.count == 0 {
+            0
+        } else {
+            (self.sum / self.count as i32) as i16
+        }
+    }
+}
+
+/// An exponential moving average, in which each sample has a weight of
+/// 2^-`shift` and the weights of older samples decay geometrically.
+pub struct ExponentialAverage {
+    shift: u32,
+    /// The average, scaled by 2^`shift` to keep its fractional part
+    state: i32,
+}
+
+impl ExponentialAverage {
+    /// Creates an average in which samples have a weight of 2^-`shift`,
+    /// which must be at most 15.
+    pub fn new(shift: u32) -> ExponentialAverage {
+        ExponentialAverage {
+            shift: if shift > 15 { 15 } else { shift },
+            state: 0,
+        }
+    }
+
+    /// Starts the average from `x`, e.g. the first sample, rather than 0.
+    pub fn reset(&mut self, x: i16) {
+        self.state = (x as i32) << self.shift;
+    }
+
+    /// Adds a sample, and returns the new average.
+    pub fn add(&mut self, x: i16) -> i16 {
+        self.state += x as i32 - (self.state >> self.shift);
+        self.average()
+    }
+
+    pub fn average(&self) -> i16 {
+        saturate(self.state >> self.shift)
+    }
+}
+
+/// sin(2πk / `FFT_MAX_LEN`) in Q15, for k from 0 to a quarter period.
+const SINE: [i16; FFT_MAX_LEN / 4 + 1] = [
+    0, 804, 1608, 2410, 3212, 4011, 4808, 5602, 6393, 7179, 7962, 8739, 9512, 10278, 11039, 11793,
+    12539, 13279, 14010, 14732, 15446, 16151, 16846, 17530, 18204, 18868, 19519, 20159, 20787,
+    21403, 22005, 22594, 23170, 23731, 24279, 24811, 25329, 25832, 26319, 26790, 27245, 27683,
+    28105, 28510, 28898, 29268, 29621, 29956, 30273, 30571, 30852, 31113, 31356, 31580, 31785,
+    31971, 32137, 32285, 32412, 32521, 32609, 32678, 32728, 32757, 32767,
+];
+
+/// sin(2πk / `FFT_MAX_LEN`) in Q15, for k in a half period.
+fn sine(k: usize) -> i16 {
+    if k <= FFT_MAX_LEN / 4 {
+        SINE[k]
+    } else {
+        SINE[FFT_MAX_LEN / 2 - k]
+    }
+}
+
+/// cos(2πk / `FFT_MAX_LEN`) in Q15, for k in a half period.
+fn cosine(k: usize) -> i16 {
+    if k <= FFT_MAX_LEN / 4 {
+        SINE[FFT_MAX_LEN / 4 - k]
+    } else {
+        -SINE[k - FFT_MAX_LEN / 4]
+    }
+}
+
+/// Computes the discrete Fourier transform of the complex signal in `real`
+/// and `imag` in place, with a radix-2 FFT. The length must be a power of two
+/// from 2 to `FFT_MAX_LEN`, and the same for both slices.
+///
+/// Each stage of the FFT halves its values so that they cannot overflow, so
+/// the result is the transform scaled by 1/N. For a real signal, e.g. a block
+/// of sensor samples, `imag` should be all zeros; the first N/2 + 1 bins of
+/// the result then hold its spectrum, from 0 to half the sample rate.
+pub fn fft(real: &mut [i16], imag: &mut [i16]) -> Result<(), ReturnCode> {
+    let n = real.len();
+    if n < 2 || n > FFT_MAX_LEN || !n.is_power_of_two() || imag.len() != n {
+        return Err(ReturnCode::EINVAL);
+    }
+
+    // Reorder the input into bit-reversed order
+    let mut j = 0;
+    for i in 1..n {
+        let mut bit = n >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j {
+            real.swap(i, j);
+            imag.swap(i, j);
+        }
+    }
+
+    // Combine transforms of length `len / 2` into transforms of length `len`
+    let mut len = 2;
+    while len <= n {
+        let half = len / 2;
+        let step = FFT_MAX_LEN / len;
+        for start in (0..n).step_by(len) {
+            for k in 0..half {
+                // The twiddle factor e^(-2πik/len)
+                let wr = cosine(k * step) as i32;
+                let wi = -(sine(k * step) as i32);
+                let a = start + k;
+                let b = a + half;
+                let tr = (wr * real[b] as i32 - wi * imag[b] as i32 + (1 << 14)) >> 15;
+                let ti = (wr * imag[b] as i32 + wi * real[b] as i32 + (1 << 14)) >> 15;
+                let ar = real[a] as i32;
+                let ai = imag[a] as i32;
+                real[a] = saturate((ar + tr) >> 1);
+                imag[a] = saturate((ai + ti) >> 1);
+                real[b] = saturate((ar - tr) >> 1);
+                imag[b] = saturate((ai - ti) >> 1);
+            }
+        }
+        len <<= 1;
+    }
+    Ok(())
+}
+
+/// Writes the squared magnitude of each bin of a transform computed by `fft`
+/// to `power`, for as many bins as `power` holds.
+pub fn power_spectrum(real: &[i16], imag: &[i16], power: &mut [u32]) {
+    for ((p, &r), &i) in power.iter_mut().zip(real.iter()).zip(imag.iter()) {
+        *p = (r as i32 * r as i32) as u32 + (i as i32 * i as i32) as u32;
+    }
+}
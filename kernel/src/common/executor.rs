@@ -0,0 +1,238 @@
+//! Allocation-free executor for futures in the kernel and capsules.
+//!
+//! Split-phase drivers with long sequences of operations, such as the
+//! initialization and calibration of a sensor, usually keep an enum of the
+//! step they are at, and advance it in each callback. This executor lets such
+//! a sequence be a `Future` instead, which waits for each callback in turn.
+//!
+//! The executor does not allocate. Each future lives in a `Task` that the
+//! board allocates statically, and that the executor links into its list of
+//! tasks. When something wakes a task, the executor sets its deferred call,
+//! and polls the woken tasks from it, so futures always run from the main
+//! loop, never from within the callback that woke them.
+//!
+//! A `Signal` connects a future to a callback: the future waits on the
+//! signal, and the callback completes it with a value.
+//!
+//! The toolchain of the kernel does not support `async` blocks in `no_std`
+//! crates yet, so for now futures implement `Future` by hand, as `Wait` does.
+//! Once it does, a sequence can be written as straight-line code in an
+//! `async` block that awaits one signal after the other, and spawned as is.
+//!
+//! Usage
+//! -----
+//!
+//! ```ignore
+//! let executor = static_init!(Executor, Executor::new(dynamic_deferred_caller));
+//! executor.initialize_callback_handle(
+//!     dynamic_deferred_caller
+//!         .register(executor)
+//!         .expect("no deferred call slot available"),
+//! );
+//!
+//! let init_task = static_init!(Task<SensorInit>, Task::new(SensorInit::new(sensor)));
+//! executor.spawn(init_task);
+//! ```
+
+use core::cell::{Cell, UnsafeCell};
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
+
+use crate::common::cells::{MapCell, OptionalCell};
+use crate::common::dynamic_deferred_call::{
+    DeferredCallHandle, DynamicDeferredCall, DynamicDeferredCallClient,
+};
+use crate::common::list::{List, ListLink, ListNode};
+
+/// The part of a task that its wakers point to.
+struct Header {
+    /// Whether the task was woken since it was last polled
+    ready: Cell<bool>,
+    executor: OptionalCell<&'static Executor>,
+}
+
+impl Header {
+    fn wake(&self) {
+        self.ready.set(true);
+        self.executor.map(|executor| executor.schedule());
+    }
+}
+
+static WAKER_VTABLE: RawWakerVTable = RawWakerVTable::new(waker_clone, waker_wake, waker_drop);
+
+unsafe fn waker_clone(header: *const ()) -> RawWaker {
+    RawWaker::new(header, &WAKER_VTABLE)
+}
+
+unsafe fn waker_wake(header: *const ()) {
+    (*(header as *const Header)).wake();
+}
+
+unsafe fn waker_drop(_header: *const ()) {}
+
+/// A task that the executor runs, whatever the type of its future.
+trait Runnable {
+    fn header(&self) -> &Header;
+
+    fn link(&self) -> &ListLink<'static, Runnable>;
+
+    /// Poll the future of the task, if it did not finish yet.
+    fn poll(&self, cx: &mut Context);
+}
+
+impl ListNode<'static, Runnable> for Runnable {
+    fn next(&'static self) -> &'static ListLink<'static, Runnable> {
+        self.link()
+    }
+}
+
+/// A future, and what the executor needs to run it.
+pub struct Task<F: Future<Output = ()>> {
+    header: Header,
+    /// The future, until it finishes
+    future: UnsafeCell<Option<F>>,
+    next: ListLink<'static, Runnable>,
+}
+
+impl<F: Future<Output = ()>> Task<F> {
+    pub fn new(future: F) -> Task<F> {
+        Task {
+            header: Header {
+                ready: Cell::new(false),
+                executor: OptionalCell::empty(),
+            },
+            future: UnsafeCell::new(Some(future)),
+            next: ListLink::empty(),
+        }
+    }
+
+    /// Whether the future finished.
+    pub fn is_finished(&self) -> bool {
+        // The executor only changes the future while it polls it
+        unsafe { (*self.future.get()).is_none() }
+    }
+}
+
+impl<F: Future<Output = ()>> Runnable for Task<F> {
+    fn header(&self) -> &Header {
+        &self.header
+    }
+
+    fn link(&self) -> &ListLink<'static, Runnable> {
+        &self.next
+    }
+
+    fn poll(&self, cx: &mut Context) {
+        // Only the executor polls the task, one task at a time, and tasks are
+        // static, so the future never moves once it was polled.
+        let future = unsafe { &mut *self.future.get() };
+        let finished = future.as_mut().map_or(false, |future| {
+            unsafe { Pin::new_unchecked(future) }.poll(cx).is_ready()
+        });
+        if finished {
+            *future = None;
+        }
+    }
+}
+
+/// Runs tasks from a deferred call, each time they are woken.
+pub struct Executor {
+    tasks: List<'static, Runnable>,
+    deferred_caller: &'static DynamicDeferredCall,
+    deferred_call_handle: OptionalCell<DeferredCallHandle>,
+}
+
+impl Executor {
+    pub fn new(deferred_caller: &'static DynamicDeferredCall) -> Executor {
+        Executor {
+            tasks: List::new(),
+            deferred_caller: deferred_caller,
+            deferred_call_handle: OptionalCell::empty(),
+        }
+    }
+
+    pub fn initialize_callback_handle(&self, handle: DeferredCallHandle) {
+        self.deferred_call_handle.replace(handle);
+    }
+
+    /// Run `task`, starting from the next deferred call. A task can only be
+    /// spawned once.
+    pub fn spawn<F: Future<Output = ()> + 'static>(&'static self, task: &'static Task<F>) {
+        if task.header.executor.is_some() {
+            return;
+        }
+        task.header.executor.set(self);
+        self.tasks.push_tail(task);
+        task.header.wake();
+    }
+
+    fn schedule(&self) {
+        self.deferred_call_handle
+            .map(|handle| self.deferred_caller.set(*handle));
+    }
+}
+
+impl DynamicDeferredCallClient for Executor {
+    fn call(&self, _handle: DeferredCallHandle) {
+        for task in self.tasks.iter() {
+            let header = task.header();
+            if header.ready.get() {
+                header.ready.set(false);
+                let waker = unsafe {
+                    Waker::new_unchecked(RawWaker::new(
+                        header as *const Header as *const (),
+                        &WAKER_VTABLE,
+                    ))
+                };
+                task.poll(&mut Context::from_waker(&waker));
+            }
+        }
+    }
+}
+
+/// A value that a callback delivers to a future.
+pub struct Signal<T: Copy> {
+    value: Cell<Option<T>>,
+    /// Waker of the future that waits for the value
+    waker: MapCell<Waker>,
+}
+
+impl<T: Copy> Signal<T> {
+    pub fn new() -> Signal<T> {
+        Signal {
+            value: Cell::new(None),
+            waker: MapCell::empty(),
+        }
+    }
+
+    /// Deliver `value`, and wake the future that waits for it, if any.
+    pub fn signal(&self, value: T) {
+        self.value.set(Some(value));
+        self.waker.take().map(|waker| waker.wake());
+    }
+
+    /// A future that waits for the value, and takes it.
+    pub fn wait(&'a self) -> Wait<'a, T> {
+        Wait { signal: self }
+    }
+}
+
+/// Future of the value of a `Signal`.
+pub struct Wait<'a, T: Copy> {
+    signal: &'a Signal<T>,
+}
+
+impl<T: Copy> Future for Wait<'a, T> {
+    type Output = T;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<T> {
+        match self.signal.value.take() {
+            Some(value) => Poll::Ready(value),
+            None => {
+                self.signal.waker.replace(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
@@ -0,0 +1,112 @@
+//! A static buffer with an active window, for passing through driver layers.
+//!
+//! Layered drivers often pass a buffer down to a lower layer that should only
+//! see part of it: a radio driver only gives the payload to the layer above
+//! it, and a framing layer only gives the bytes between the framing to the
+//! UART. Copying the part into another buffer costs RAM and time, and passing
+//! the buffer with an offset and a length is easy to get wrong.
+//!
+//! A `LeasableBuffer` holds the whole buffer, but only gives access to its
+//! active window. Each layer narrows the window to the part the layer below
+//! it should see, with `slice`, which is relative to the current window. When
+//! the operation completes, `take` returns the whole buffer, whatever the
+//! window was, so the buffer goes back to its owner intact.
+//!
+//! ```ignore
+//! let mut buffer = LeasableBuffer::new(frame);
+//! // The lower layer sees the bytes after the header
+//! buffer.slice(HEADER_LEN..);
+//! buffer[0] = payload_byte;
+//! assert_eq!(buffer.len(), frame_len - HEADER_LEN);
+//!
+//! // Back to the whole frame
+//! let frame: &'static mut [u8] = buffer.take();
+//! ```
+
+use core::ops::{Bound, Range, RangeBounds};
+use core::ops::{Deref, DerefMut};
+
+/// A buffer, and the window of it that is active.
+pub struct LeasableBuffer<'a, T> {
+    internal: &'a mut [T],
+    active_range: Range<usize>,
+}
+
+impl<T> LeasableBuffer<'a, T> {
+    /// The whole buffer is active.
+    pub fn new(buffer: &'a mut [T]) -> LeasableBuffer<'a, T> {
+        let len = buffer.len();
+        LeasableBuffer {
+            internal: buffer,
+            active_range: 0..len,
+        }
+    }
+
+    /// Return the whole buffer, whatever the active window.
+    pub fn take(self) -> &'a mut [T] {
+        self.internal
+    }
+
+    /// Make the whole buffer active again.
+    pub fn reset(&mut self) {
+        self.active_range = 0..self.internal.len();
+    }
+
+    /// The length of the active window.
+    pub fn len(&self) -> usize {
+        self.active_range.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.active_range.start == self.active_range.end
+    }
+
+    /// The active window, as a range of the whole buffer. A layer can use it
+    /// to restore the window it gave the layer below, once the operation
+    /// completes.
+    pub fn active_range(&self) -> Range<usize> {
+        self.active_range.clone()
+    }
+
+    /// Narrow the active window to `range`, which is relative to the current
+    /// window. Parts of `range` past the end of the window are left out.
+    pub fn slice<R: RangeBounds<usize>>(&mut self, range: R) {
+        let start = match range.start_bound() {
+            Bound::Included(s) => *s,
+            Bound::Excluded(s) => *s + 1,
+            Bound::Unbounded => 0,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(e) => *e + 1,
+            Bound::Excluded(e) => *e,
+            Bound::Unbounded => self.len(),
+        };
+
+        let end = self.active_range.start + core::cmp::min(end, self.len());
+        let start = core::cmp::min(self.active_range.start + start, end);
+        self.active_range = start..end;
+    }
+
+    /// Make `range` of the whole buffer the active window, for instance to
+    /// restore a window from `active_range`. Parts of `range` past the end
+    /// of the buffer are left out.
+    pub fn set_active_range(&mut self, range: Range<usize>) {
+        let end = core::cmp::min(range.end, self.internal.len());
+        let start = core::cmp::min(range.start, end);
+        self.active_range = start..end;
+    }
+}
+
+impl<T> Deref for LeasableBuffer<'a, T> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        &self.internal[self.active_range.clone()]
+    }
+}
+
+impl<T> DerefMut for LeasableBuffer<'a, T> {
+    fn deref_mut(&mut self) -> &mut [T] {
+        &mut self.internal[self.active_range.clone()]
+    }
+}
@@ -15,10 +15,15 @@ pub mod registers {
 }
 
 pub mod deferred_call;
+pub mod dsp;
 pub mod dynamic_deferred_call;
+pub mod executor;
+pub mod leasable_buffer;
 pub mod list;
 pub mod math;
 pub mod peripherals;
+pub mod secure;
+pub mod state_machine;
 pub mod utils;
 
 mod queue;
@@ -16,6 +16,25 @@ impl<T: Copy> RingBuffer<'a, T> {
             ring: ring,
         }
     }
+
+    /// Returns how many elements the queue holds when full.
+    pub fn capacity(&self) -> usize {
+        self.ring.len() - 1
+    }
+
+    /// Returns the first element, from the front of the queue, for which `f`
+    /// returns true.
+    pub fn find_mut<F: FnMut(&T) -> bool>(&mut self, mut f: F) -> Option<&mut T> {
+        let len = self.ring.len();
+        let mut index = self.head;
+        while index != self.tail {
+            if f(&self.ring[index]) {
+                return Some(&mut self.ring[index]);
+            }
+            index = (index + 1) % len;
+        }
+        None
+    }
 }
 
 impl<T: Copy> queue::Queue<T> for RingBuffer<'a, T> {
@@ -0,0 +1,181 @@
+//! Utilities for handling secrets such as keys and authentication tags.
+//!
+//! Comparing a secret with `==` returns as soon as the first byte differs,
+//! which lets an attacker who can time the comparison guess the secret one
+//! byte at a time. Likewise, a secret that is overwritten or dropped normally
+//! may survive in memory, because the compiler is free to remove writes to
+//! memory that is never read again. The functions and types here avoid both
+//! problems, so that drivers do not need their own versions.
+//!
+//! ```
+//! use kernel::common::secure::{constant_time_eq, SecretCell};
+//!
+//! let key = SecretCell::new([0x2b; 16]);
+//! let matches = key.map(|key| constant_time_eq(key, &[0x2b; 16]));
+//! assert_eq!(matches, Some(true));
+//!
+//! // Overwrites the key in memory and leaves the cell empty
+//! key.clear();
+//! assert!(key.is_none());
+//! ```
+
+use crate::common::cells::MapCell;
+use core::ptr;
+use core::sync::atomic::{compiler_fence, Ordering};
+
+/// Compares `a` and `b` in time that depends only on their lengths, not on
+/// their contents. Slices of different lengths are never equal.
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    let mut diff = 0u8;
+    for (x, y) in a.iter().zip(b.iter()) {
+        diff |= x ^ y;
+    }
+    // The volatile read keeps the compiler from turning the loop back into
+    // one that exits early
+    unsafe { ptr::read_volatile(&diff) == 0 }
+}
+
+/// Overwrites `buf` with zeros in a way the compiler cannot optimize away.
+pub fn zeroize(buf: &mut [u8]) {
+    for byte in buf.iter_mut() {
+        unsafe {
+            ptr::write_volatile(byte, 0);
+        }
+    }
+    compiler_fence(Ordering::SeqCst);
+}
+
+/// A cell holding a secret, such as a key, that is zeroized whenever it is
+/// replaced, cleared or dropped.
+///
+/// The secret can only be accessed by reference through `map`, so that
+/// copies of it do not end up scattered around the stack.
+pub struct SecretCell<T: AsMut<[u8]>> {
+    value: MapCell<T>,
+}
+
+impl<T: AsMut<[u8]>> SecretCell<T> {
+    pub fn new(value: T) -> SecretCell<T> {
+        SecretCell {
+            value: MapCell::new(value),
+        }
+    }
+
+    pub fn empty() -> SecretCell<T> {
+        SecretCell {
+            value: MapCell::empty(),
+        }
+    }
+
+    pub fn is_some(&self) -> bool {
+        self.value.is_some()
+    }
+
+    pub fn is_none(&self) -> bool {
+        self.value.is_none()
+    }
+
+    /// Stores a new secret, zeroizing the previous one.
+    pub fn replace(&self, value: T) {
+        self.clear();
+        self.value.put(value);
+    }
+
+    /// Zeroizes the secret and leaves the cell empty.
+    pub fn clear(&self) {
+        self.value.map(|value| zeroize(value.as_mut()));
+        self.value.take();
+    }
+
+    /// Calls `closure` with a reference to the secret, if there is one.
+    pub fn map<F, R>(&self, closure: F) -> Option<R>
+    where
+        F: FnOnce(&T) -> R,
+    {
+        self.value.map(|value| closure(value))
+    }
+}
+
+impl<T: AsMut<[u8]>> Drop for SecretCell<T> {
+    fn drop(&mut self) {
+        self.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{constant_time_eq, zeroize, SecretCell};
+
+    #[test]
+    fn equal() {
+        assert!(constant_time_eq(b"", b""));
+        assert!(constant_time_eq(b"secret", b"secret"));
+        assert!(constant_time_eq(&[0xff; 32], &[0xff; 32]));
+    }
+
+    #[test]
+    fn unequal() {
+        assert!(!constant_time_eq(b"secret", b"Secret"));
+        assert!(!constant_time_eq(b"secret", b"secreT"));
+        assert!(!constant_time_eq(&[0x00; 32], &[0xff; 32]));
+        // Bits that differ in different bytes do not cancel out
+        assert!(!constant_time_eq(&[0x01, 0x00], &[0x00, 0x01]));
+    }
+
+    #[test]
+    fn different_lengths() {
+        assert!(!constant_time_eq(b"secret", b"secrets"));
+        assert!(!constant_time_eq(b"secret", b"secre"));
+        assert!(!constant_time_eq(b"", b"s"));
+    }
+
+    #[test]
+    fn zeroize_clears_buffer() {
+        let mut buf = [0xa5; 64];
+        zeroize(&mut buf);
+        assert_eq!(&buf[..], &[0; 64][..]);
+
+        let mut empty: [u8; 0] = [];
+        zeroize(&mut empty);
+    }
+
+    #[test]
+    fn secret_cell_clear() {
+        let mut key = [0x2b; 16];
+        let cell = SecretCell::new(&mut key[..]);
+        assert_eq!(cell.map(|key| key[0]), Some(0x2b));
+        cell.clear();
+        assert!(cell.is_none());
+        assert_eq!(cell.map(|key| key[0]), None);
+        drop(cell);
+        assert_eq!(key, [0; 16]);
+    }
+
+    #[test]
+    fn secret_cell_replace() {
+        let mut old = [0x2b; 16];
+        let mut new = [0x7e; 16];
+        let cell = SecretCell::empty();
+        assert!(cell.is_none());
+        cell.replace(&mut old[..]);
+        cell.replace(&mut new[..]);
+        assert_eq!(cell.map(|key| key[0]), Some(0x7e));
+        // Forget the cell, so that only `replace` can have zeroized `old`
+        core::mem::forget(cell);
+        assert_eq!(old, [0; 16]);
+        assert_eq!(new, [0x7e; 16]);
+    }
+
+    #[test]
+    fn secret_cell_drop() {
+        let mut key = [0x2b; 16];
+        {
+            let cell = SecretCell::new(&mut key[..]);
+            assert!(cell.is_some());
+        }
+        assert_eq!(key, [0; 16]);
+    }
+}
@@ -0,0 +1,163 @@
+//! States of split-phase drivers, with checked transitions.
+//!
+//! Split-phase drivers keep the step of the operation in progress in an enum,
+//! and move to the next step in each callback. A bug in these transitions,
+//! such as starting an operation while another one is in progress, is easy to
+//! make and hard to find. The `state_machine!` macro declares the enum along
+//! with the transitions that are allowed out of each state, and a
+//! `StateCell` holds the state of a driver and checks each transition:
+//!
+//! ```ignore
+//! state_machine! {
+//!     /// States of the sensor driver
+//!     enum State {
+//!         Idle => [Reset, ReadId],
+//!         Reset => [Idle],
+//!         ReadId => [Idle],
+//!     }
+//! }
+//!
+//! struct Sensor {
+//!     state: StateCell<State>,
+//! }
+//!
+//! impl Sensor {
+//!     fn reset(&self) -> ReturnCode {
+//!         // Fails, with `EBUSY`, unless the driver is idle
+//!         if let Err(error) = self.state.transition(State::Reset) {
+//!             return error.into();
+//!         }
+//!         ...
+//!     }
+//!
+//!     fn command_complete(&self) {
+//!         // Panics if the driver is not in a state that can become idle
+//!         self.state.advance(State::Idle);
+//!     }
+//! }
+//! ```
+
+use core::cell::Cell;
+use core::fmt::Debug;
+
+use crate::returncode::ReturnCode;
+
+/// The states of a driver, and the transitions allowed between them. The
+/// `state_machine!` macro implements this trait for the enum it declares.
+pub trait StateMachine: Copy + PartialEq + Debug {
+    /// Whether a driver in state `self` can move to state `to`.
+    fn can_transition(self, to: Self) -> bool;
+}
+
+/// A transition that the state machine does not allow.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct InvalidTransition<S> {
+    pub from: S,
+    pub to: S,
+}
+
+/// The usual cause of an invalid transition out of a request is that the
+/// driver is in the middle of another operation.
+impl<S> From<InvalidTransition<S>> for ReturnCode {
+    fn from(_error: InvalidTransition<S>) -> ReturnCode {
+        ReturnCode::EBUSY
+    }
+}
+
+/// The state of a driver.
+pub struct StateCell<S: StateMachine> {
+    state: Cell<S>,
+}
+
+impl<S: StateMachine> StateCell<S> {
+    pub fn new(initial: S) -> StateCell<S> {
+        StateCell {
+            state: Cell::new(initial),
+        }
+    }
+
+    pub fn get(&self) -> S {
+        self.state.get()
+    }
+
+    pub fn is(&self, state: S) -> bool {
+        self.state.get() == state
+    }
+
+    /// Move to state `to`, if the state machine allows it.
+    pub fn transition(&self, to: S) -> Result<(), InvalidTransition<S>> {
+        let from = self.state.get();
+        if from.can_transition(to) {
+            self.state.set(to);
+            Ok(())
+        } else {
+            Err(InvalidTransition { from: from, to: to })
+        }
+    }
+
+    /// Move to state `to`, for transitions that the driver makes on its own,
+    /// such as in callbacks. An invalid transition there is a bug of the
+    /// driver, so this panics.
+    pub fn advance(&self, to: S) {
+        if let Err(error) = self.transition(to) {
+            panic!(
+                "Invalid state transition from {:?} to {:?}",
+                error.from, error.to
+            );
+        }
+    }
+
+    /// Move to state `to` whatever the current state, such as to recover
+    /// from an error.
+    pub fn reset(&self, to: S) {
+        self.state.set(to);
+    }
+}
+
+/// Declares the states of a split-phase driver, and the transitions allowed
+/// out of each of them. See `kernel::common::state_machine`.
+///
+/// ```ignore
+/// state_machine! {
+///     /// States of the sensor driver
+///     pub enum State {
+///         Idle => [Reset, ReadId],
+///         Reset => [Idle],
+///         ReadId => [Idle],
+///     }
+/// }
+/// ```
+///
+/// The enum derives `Copy`, `Clone`, `Debug`, `PartialEq` and `Eq`, and
+/// implements `StateMachine`.
+#[macro_export]
+macro_rules! state_machine {
+    (
+        $(#[$attr:meta])*
+        $vis:vis enum $name:ident {
+            $(
+                $(#[$state_attr:meta])*
+                $state:ident => [$($next:ident),* $(,)?]
+            ),* $(,)?
+        }
+    ) => {
+        $(#[$attr])*
+        #[derive(Copy, Clone, Debug, PartialEq, Eq)]
+        $vis enum $name {
+            $(
+                $(#[$state_attr])*
+                $state,
+            )*
+        }
+
+        impl $crate::common::state_machine::StateMachine for $name {
+            fn can_transition(self, to: $name) -> bool {
+                match self {
+                    $(
+                        $name::$state => false $(|| to == $name::$next)*,
+                    )*
+                }
+            }
+        }
+    };
+}
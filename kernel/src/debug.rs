@@ -19,6 +19,20 @@
 //! kernel::debug::assign_console_driver(Some(hail.console), kc);
 //! ```
 //!
+//! Boards can also name their pins, so that debugging output shows the names
+//! rather than raw pin numbers:
+//!
+//! ```ignore
+//! let pin_labels = static_init!(
+//!     [kernel::debug::PinLabel; 1],
+//!     [kernel::debug::PinLabel {
+//!         pin: &cc26x2::gpio::PORT[15],
+//!         label: "DIO15/BTN-1",
+//!     }]
+//! );
+//! kernel::debug::assign_pin_labels(pin_labels);
+//! ```
+//!
 //! Example
 //! -------
 //!
@@ -39,7 +53,7 @@
 
 use core::cell::Cell;
 use core::cmp::{self, min};
-use core::fmt::{write, Arguments, Result, Write};
+use core::fmt::{self, write, Arguments, Result, Write};
 use core::panic::PanicInfo;
 use core::ptr;
 use core::slice;
@@ -69,6 +83,7 @@ pub unsafe fn panic<L: hil::led::Led, W: Write>(
     // Flush debug buffer if needed
     flush(writer);
     panic_process_info(processes, writer);
+    panic_pin_info(writer);
     panic_blink_forever(leds)
 }
 
@@ -193,6 +208,78 @@ macro_rules! debug_gpio {
     }};
 }
 
+///////////////////////////////////////////////////////////////////
+// pin label support
+
+/// A human-readable name for a pin, e.g. as printed on the board, such as
+/// `DIO7/BTN-1`.
+pub struct PinLabel {
+    pub pin: &'static hil::gpio::Pin,
+    pub label: &'static str,
+}
+
+static mut PIN_LABELS: &'static [PinLabel] = &[];
+
+/// Function used by board main.rs to name its pins, so that panic dumps and
+/// the process console print these names. Pins given to the GPIO driver
+/// should be labeled by the pins in the array passed to the driver.
+pub unsafe fn assign_pin_labels(labels: &'static [PinLabel]) {
+    PIN_LABELS = labels;
+}
+
+/// The pins the board has named.
+pub fn pin_labels() -> &'static [PinLabel] {
+    unsafe { PIN_LABELS }
+}
+
+/// Returns the name the board has given to `pin`, if any. `pin` can be any
+/// reference to the pin, e.g. as a `hil::gpio::InterruptValuePin`.
+pub fn pin_label<P: ?Sized>(pin: &P) -> Option<&'static str> {
+    let address = pin as *const P as *const u8;
+    pin_labels()
+        .iter()
+        .find(|label| label.pin as *const hil::gpio::Pin as *const u8 == address)
+        .map(|label| label.label)
+}
+
+/// Formats the configuration and level of a pin, e.g. `Output, high`.
+pub struct PinState<'a, P: hil::gpio::Pin + ?Sized>(pub &'a P);
+
+impl<P: hil::gpio::Pin + ?Sized> fmt::Display for PinState<'a, P> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result {
+        let configuration = self.0.configuration();
+        match configuration {
+            hil::gpio::Configuration::Input
+            | hil::gpio::Configuration::Output
+            | hil::gpio::Configuration::InputOutput => write!(
+                f,
+                "{:?}, {}",
+                configuration,
+                if self.0.read() { "high" } else { "low" }
+            ),
+            _ => write!(f, "{:?}", configuration),
+        }
+    }
+}
+
+/// Prints the state of the pins the board has named.
+///
+/// **NOTE:** The supplied `writer` must be synchronous.
+pub unsafe fn panic_pin_info<W: Write>(writer: &mut W) {
+    let labels = pin_labels();
+    if labels.is_empty() {
+        return;
+    }
+    let _ = writer.write_fmt(format_args!("\r\n---| Pins |---\r\n"));
+    for label in labels.iter() {
+        let _ = writer.write_fmt(format_args!(
+            "{:<20}{}\r\n",
+            label.label,
+            PinState(label.pin)
+        ));
+    }
+}
+
 ///////////////////////////////////////////////////////////////////
 // debug! and debug_verbose! support
 
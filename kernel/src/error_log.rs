@@ -0,0 +1,111 @@
+//! Log of errors that persists across reboots.
+//!
+//! Failures in the field are hard to diagnose once the device has rebooted
+//! and the debug output is gone. Capsules and chips record the errors they
+//! run into with `error_log!`, which passes them to the `ErrorLog` that the
+//! board set, if any. The capsule that implements it (such as
+//! `capsules::error_log`) keeps them in flash, so that they can be read back
+//! later through the process console or an app.
+//!
+//! ```ignore
+//! // A code that identifies the error, and a word of data about it
+//! error_log!(ERROR_SENSOR_TIMEOUT, address);
+//! // The severity is `Error` unless given
+//! error_log!(Warning, ERROR_RETRIED, attempts);
+//! ```
+//!
+//! The codes are up to each caller; the log only keeps them in order.
+
+/// How bad a logged error is.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Info = 0,
+    Warning = 1,
+    Error = 2,
+    /// The system cannot go on, such as just before a panic
+    Fatal = 3,
+}
+
+impl Severity {
+    pub fn from_u8(value: u8) -> Option<Severity> {
+        match value {
+            0 => Some(Severity::Info),
+            1 => Some(Severity::Warning),
+            2 => Some(Severity::Error),
+            3 => Some(Severity::Fatal),
+            _ => None,
+        }
+    }
+}
+
+/// An entry of the log.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Entry {
+    /// Entries are numbered in the order they were logged, across reboots
+    pub sequence: u32,
+    pub severity: Severity,
+    pub code: u32,
+    pub data: u32,
+}
+
+impl Entry {
+    /// For boards to initialize the buffers of entries they give a log.
+    pub const EMPTY: Entry = Entry {
+        sequence: 0,
+        severity: Severity::Info,
+        code: 0,
+        data: 0,
+    };
+}
+
+/// Keeps the logged errors.
+pub trait ErrorLog {
+    /// Log an error. This is called from anywhere in the kernel, so it must
+    /// not block; the entry can be written out later.
+    fn log(&self, severity: Severity, code: u32, data: u32);
+
+    /// Call `f` with each of the most recent entries, oldest first.
+    fn recent(&self, f: &mut FnMut(&Entry));
+}
+
+static mut ERROR_LOG: Option<&'static ErrorLog> = None;
+
+/// Send the errors logged with `error_log!` to `error_log`.
+pub unsafe fn set_error_log(error_log: &'static ErrorLog) {
+    ERROR_LOG = Some(error_log);
+}
+
+/// Log an error, if the board set an error log. Use `error_log!` instead.
+pub fn log(severity: Severity, code: u32, data: u32) {
+    unsafe {
+        ERROR_LOG.map(|error_log| error_log.log(severity, code, data));
+    }
+}
+
+/// Call `f` with each of the most recent entries of the error log, oldest
+/// first.
+pub fn recent(f: &mut FnMut(&Entry)) {
+    unsafe {
+        ERROR_LOG.map(|error_log| error_log.recent(f));
+    }
+}
+
+/// Log an error with a code and a word of data, and a severity of `Error`
+/// unless one is given. See `kernel::error_log`.
+#[macro_export]
+macro_rules! error_log {
+    ($code:expr, $data:expr) => {
+        $crate::error_log::log(
+            $crate::error_log::Severity::Error,
+            $code as u32,
+            $data as u32,
+        )
+    };
+    ($severity:ident, $code:expr, $data:expr) => {
+        $crate::error_log::log(
+            $crate::error_log::Severity::$severity,
+            $code as u32,
+            $data as u32,
+        )
+    };
+}
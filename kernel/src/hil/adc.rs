@@ -37,6 +37,13 @@ pub trait Adc {
     ///
     /// The returned reference voltage is in millivolts, or `None` if unknown.
     fn get_voltage_reference_mv(&self) -> Option<usize>;
+
+    /// Convert a sample, left-justified in the u16, to millivolts with the
+    /// reference voltage of the ADC, or `None` if that is unknown.
+    fn sample_to_mv(&self, sample: u16) -> Option<usize> {
+        self.get_voltage_reference_mv()
+            .map(|reference| (sample as usize * reference) >> 16)
+    }
 }
 
 /// Trait for handling callbacks from simple ADC calls.
@@ -45,6 +52,35 @@ pub trait Client {
     fn sample_ready(&self, sample: u16);
 }
 
+/// The correction applied to samples by a calibrated ADC: a raw sample `x`
+/// becomes `(x + offset) * gain / 32768`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct CalibrationValues {
+    /// The gain, where 32768 is a gain of 1
+    pub gain: u16,
+    /// The offset, in steps of the resolution of the ADC
+    pub offset: i16,
+}
+
+/// Interface for ADCs that can correct their gain and offset, so that the
+/// samples match `get_voltage_reference_mv()`.
+pub trait Calibration: Adc {
+    fn set_calibration_client(&self, client: &'static CalibrationClient);
+
+    /// Calibrate the ADC, with `calibration_done` called once the samples
+    /// taken afterwards are corrected. Returns `EBUSY` while the ADC samples.
+    fn calibrate(&self) -> ReturnCode;
+
+    /// The correction applied to samples, or `None` if the ADC is not
+    /// calibrated.
+    fn get_calibration(&self) -> Option<CalibrationValues>;
+}
+
+/// Trait for handling callbacks from ADC calibrations.
+pub trait CalibrationClient {
+    fn calibration_done(&self, result: ReturnCode);
+}
+
 // *** Interfaces for high-speed, buffered ADC sampling ***
 
 /// Interface for continuously sampling at a given frequency on a channel.
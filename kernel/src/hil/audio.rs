@@ -0,0 +1,96 @@
+//! Interfaces for audio playback and capture.
+//!
+//! Audio is exchanged as mono PCM: signed 16-bit little endian samples, two
+//! bytes per sample. A buffer is played from start to end at a fixed sample
+//! rate, after which the client is given the buffer back. Implementations may
+//! convert the samples in place to the format of their hardware, so the
+//! contents of the buffer are undefined once it has been played.
+//!
+//! Capture is continuous and double-buffered: while the client processes one
+//! full buffer, the implementation fills the other, so that no samples are
+//! lost as long as the client gives each buffer back before the other one is
+//! full.
+
+use crate::returncode::ReturnCode;
+
+/// Length of a sample, in bytes.
+pub const SAMPLE_LEN: usize = 2;
+
+pub trait OutputClient {
+    /// Called when playback of `buffer` ends. `result` is `SUCCESS` if every
+    /// sample was played and `ECANCEL` if playback was stopped.
+    fn playback_done(&self, buffer: &'static mut [u8], result: ReturnCode);
+}
+
+pub trait AudioOutput<'a> {
+    fn set_client(&self, client: &'a OutputClient);
+
+    /// Returns whether samples can be played at `sample_rate_hz`.
+    fn sample_rate_supported(&self, sample_rate_hz: u32) -> bool;
+
+    /// Plays the first `samples` samples of `buffer` at `sample_rate_hz`.
+    /// Returns `EINVAL` if the sample rate is not supported, `ESIZE` if the
+    /// buffer holds fewer samples or the implementation cannot play that
+    /// many at once, and `EBUSY` if a buffer is already being played. If
+    /// playback cannot be started, the buffer is returned along with the
+    /// error.
+    fn play(
+        &self,
+        buffer: &'static mut [u8],
+        samples: usize,
+        sample_rate_hz: u32,
+    ) -> (ReturnCode, Option<&'static mut [u8]>);
+
+    /// Stops playback. The client is then called with `ECANCEL`. Returns
+    /// `EOFF` if nothing is being played.
+    fn stop(&self) -> ReturnCode;
+}
+
+pub trait InputClient {
+    /// Called when `buffer` has been filled with `samples` samples. The buffer
+    /// should be given back with `provide_buffer` before the other buffer is
+    /// full, or capture stopped.
+    fn samples_ready(&self, buffer: &'static mut [u8], samples: usize);
+}
+
+pub trait AudioInput<'a> {
+    fn set_client(&self, client: &'a InputClient);
+
+    /// Returns whether samples can be captured at `sample_rate_hz`.
+    fn sample_rate_supported(&self, sample_rate_hz: u32) -> bool;
+
+    /// Starts capturing at `sample_rate_hz`, filling `buffer1` and then
+    /// `buffer2` with `samples` samples each, and then each buffer given back
+    /// with `provide_buffer` in turn. Returns `EINVAL` if the sample rate is
+    /// not supported, `ESIZE` if either buffer is too short or the
+    /// implementation cannot capture that many samples at once, and `EBUSY`
+    /// if capture is already running. If capture cannot be started, the
+    /// buffers are returned along with the error.
+    fn start(
+        &self,
+        sample_rate_hz: u32,
+        buffer1: &'static mut [u8],
+        buffer2: &'static mut [u8],
+        samples: usize,
+    ) -> (
+        ReturnCode,
+        Option<&'static mut [u8]>,
+        Option<&'static mut [u8]>,
+    );
+
+    /// Gives back a buffer to be filled. It must hold at least as many samples
+    /// as were given to `start`. If it cannot be used, e.g. because capture is
+    /// not running, it is returned along with the error.
+    fn provide_buffer(&self, buffer: &'static mut [u8]) -> (ReturnCode, Option<&'static mut [u8]>);
+
+    /// Stops capturing, and returns the buffers that have not been given to
+    /// the client. Samples captured into them are discarded. Returns `EOFF`
+    /// if capture is not running.
+    fn stop(
+        &self,
+    ) -> (
+        ReturnCode,
+        Option<&'static mut [u8]>,
+        Option<&'static mut [u8]>,
+    );
+}
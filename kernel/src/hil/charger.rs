@@ -0,0 +1,82 @@
+//! Interface for battery chargers.
+//!
+//! A charger takes power from an input, such as USB or a wall adapter, to
+//! power the system and charge its battery. Charging can be turned off, and
+//! the current drawn from the input limited, e.g. to what a USB host allows.
+//! Chargers report what they are doing with a `ChargerStatus`, both when it
+//! is read and when it changes.
+
+use crate::returncode::ReturnCode;
+
+/// What the charger is doing with the battery.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum ChargeState {
+    NotCharging,
+    /// Charging a deeply discharged battery with a small current
+    PreCharge,
+    FastCharge,
+    /// The battery is full
+    Done,
+}
+
+/// Where the input power comes from.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum InputSource {
+    /// There is no input, or the charger cannot tell what it is
+    Unknown,
+    UsbHost,
+    Adapter,
+    /// The charger powers the input from the battery
+    Otg,
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum ChargerFault {
+    /// The input voltage is out of range
+    Input,
+    /// The charger shut down because it is too hot
+    Thermal,
+    /// Charging took longer than the charger's safety timer allows
+    SafetyTimer,
+    /// The battery voltage is too high
+    BatteryOvervoltage,
+    /// The battery temperature is out of the range where it can be charged
+    BatteryTemperature,
+    /// The charger could not supply the input in OTG mode
+    Boost,
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct ChargerStatus {
+    pub state: ChargeState,
+    pub input: InputSource,
+    /// Whether the input is good enough to power the system
+    pub power_good: bool,
+    /// The fault the charger reports, if any
+    pub fault: Option<ChargerFault>,
+}
+
+pub trait Client {
+    /// Called with the status of the charger after `read_status`, and when
+    /// the status changes, if the charger can tell.
+    fn status(&self, status: ChargerStatus);
+
+    /// Called when `set_charging` or `set_input_current_limit` completes.
+    fn command_done(&self, result: ReturnCode);
+}
+
+pub trait Charger<'a> {
+    fn set_client(&self, client: &'a Client);
+
+    /// Starts or stops charging the battery. The system stays powered from
+    /// the input either way.
+    fn set_charging(&self, enable: bool) -> ReturnCode;
+
+    /// Limits the current drawn from the input to `limit_ma` milliamps, or to
+    /// the highest limit the charger supports below it. Returns `EINVAL` if
+    /// the charger supports no limit that low.
+    fn set_input_current_limit(&self, limit_ma: u32) -> ReturnCode;
+
+    /// Reads the status of the charger, which is passed to `Client::status`.
+    fn read_status(&self) -> ReturnCode;
+}
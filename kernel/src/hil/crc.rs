@@ -4,17 +4,30 @@ use crate::returncode::ReturnCode;
 
 /// CRC algorithms
 ///
-/// In all cases, input bytes are bit-reversed (i.e., consumed from LSB to MSB.)
+/// For `Crc32`, `Crc32C` and the `Sam4L` algorithms, input bytes are
+/// bit-reversed (i.e., consumed from LSB to MSB.)
 ///
 /// Algorithms prefixed with `Sam4L` are native to that chip and thus require
 /// no software post-processing on platforms using it.
 ///
+/// Not every implementation supports every algorithm. Implementations return
+/// `ENOSUPPORT` from `compute` for algorithms they cannot perform, in which
+/// case a software implementation can be used for any algorithm that has
+/// `params`.
+///
 #[derive(Copy, Clone)]
 pub enum CrcAlg {
     /// Polynomial 0x04C11DB7, output reversed then inverted ("CRC-32")
     Crc32,
     /// Polynomial 0x1EDC6F41, output reversed then inverted ("CRC-32C" / "Castagnoli")
     Crc32C,
+    /// Polynomial 0x07, no reflection or inversion ("CRC-8")
+    Crc8,
+    /// Polynomial 0x1021, initial value 0xFFFF, no reflection or inversion
+    /// ("CRC-16-CCITT", also known as "CRC-16/CCITT-FALSE")
+    Crc16CCITT,
+    /// Any CRC that can be described by its parameters
+    Custom(CrcParams),
 
     /// Polynomial 0x1021, no output post-processing
     Sam4L16,
@@ -24,8 +37,70 @@ pub enum CrcAlg {
     Sam4L32C,
 }
 
+/// Parameters that describe a CRC algorithm of up to 32 bits, following the
+/// widely used "Rocksoft" model.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct CrcParams {
+    /// Width of the CRC in bits, from 1 to 32
+    pub width: u8,
+    /// Generator polynomial, without the implicit highest-order term
+    pub poly: u32,
+    /// Value of the CRC register before any input is processed
+    pub init: u32,
+    /// Whether input bytes are consumed from LSB to MSB
+    pub reflect_in: bool,
+    /// Whether the CRC register is bit-reversed before the final XOR
+    pub reflect_out: bool,
+    /// Value XORed with the result
+    pub xor_out: u32,
+}
+
+impl CrcAlg {
+    /// The parameters of this algorithm, or `None` for algorithms whose output
+    /// is specific to a particular hardware unit.
+    pub fn params(&self) -> Option<CrcParams> {
+        match *self {
+            CrcAlg::Crc32 => Some(CrcParams {
+                width: 32,
+                poly: 0x04C11DB7,
+                init: 0xFFFFFFFF,
+                reflect_in: true,
+                reflect_out: true,
+                xor_out: 0xFFFFFFFF,
+            }),
+            CrcAlg::Crc32C => Some(CrcParams {
+                width: 32,
+                poly: 0x1EDC6F41,
+                init: 0xFFFFFFFF,
+                reflect_in: true,
+                reflect_out: true,
+                xor_out: 0xFFFFFFFF,
+            }),
+            CrcAlg::Crc8 => Some(CrcParams {
+                width: 8,
+                poly: 0x07,
+                init: 0x00,
+                reflect_in: false,
+                reflect_out: false,
+                xor_out: 0x00,
+            }),
+            CrcAlg::Crc16CCITT => Some(CrcParams {
+                width: 16,
+                poly: 0x1021,
+                init: 0xFFFF,
+                reflect_in: false,
+                reflect_out: false,
+                xor_out: 0x0000,
+            }),
+            CrcAlg::Custom(params) => Some(params),
+            CrcAlg::Sam4L16 | CrcAlg::Sam4L32 | CrcAlg::Sam4L32C => None,
+        }
+    }
+}
+
 pub trait CRC {
-    /// Initiate a CRC calculation
+    /// Initiate a CRC calculation. Returns `ENOSUPPORT` if the implementation
+    /// cannot perform the requested algorithm.
     fn compute(&self, data: &[u8], _: CrcAlg) -> ReturnCode;
 
     /// Disable the CRC unit until compute() is next called
@@ -0,0 +1,17 @@
+//! Interface for a counter of CPU cycles.
+//!
+//! The counter runs freely once it is enabled, so it is shared by everyone
+//! who reads it, and measurements are differences of two reads. Counters may
+//! be narrower than 64 bits and wrap around, so only the low 32 bits are
+//! exposed, and differences are taken with wrapping arithmetic.
+
+use crate::returncode::ReturnCode;
+
+pub trait CycleCounter {
+    /// Start the counter, if it is not running. Returns `ENOSUPPORT` if the
+    /// hardware has no counter.
+    fn enable(&self) -> ReturnCode;
+
+    /// The low 32 bits of the number of cycles counted.
+    fn count(&self) -> u32;
+}
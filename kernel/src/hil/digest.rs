@@ -0,0 +1,42 @@
+//! Interface for computing message digests (cryptographic hashes).
+//!
+//! Data is added to a digest in one or more calls to `add_data`, each of
+//! which completes with a call to the client's `add_data_done`. A call to
+//! `run` then writes the digest of all data added since the last `run` or
+//! `clear_data` to the provided buffer, and completes with a call to the
+//! client's `hash_done`. Only one operation may be outstanding at a time.
+
+use crate::returncode::ReturnCode;
+
+pub trait Client {
+    /// Called when the data passed to `add_data` has been added to the
+    /// digest. The data buffer is returned.
+    fn add_data_done(&self, result: ReturnCode, data: &'static mut [u8]);
+
+    /// Called when the digest has been computed. On success, the first
+    /// `digest_len()` bytes of `digest` hold the result.
+    fn hash_done(&self, result: ReturnCode, digest: &'static mut [u8]);
+}
+
+pub trait Digest<'a> {
+    fn set_client(&self, client: &'a Client);
+
+    /// Length of the digests produced, in bytes.
+    fn digest_len(&self) -> usize;
+
+    /// Adds the first `length` bytes of `data` to the digest. If the request
+    /// cannot be started, the buffer is returned along with the error.
+    fn add_data(
+        &self,
+        data: &'static mut [u8],
+        length: usize,
+    ) -> (ReturnCode, Option<&'static mut [u8]>);
+
+    /// Computes the digest of the data added so far and writes it to `digest`,
+    /// which must be at least `digest_len()` bytes long. Once the digest has
+    /// been computed, a new digest can be started with `add_data`.
+    fn run(&self, digest: &'static mut [u8]) -> (ReturnCode, Option<&'static mut [u8]>);
+
+    /// Discards the data added so far, starting a new digest.
+    fn clear_data(&self);
+}
@@ -0,0 +1,56 @@
+//! Interface for ECDSA signatures over the NIST P-256 curve.
+//!
+//! Private keys are referred to by an implementation-defined key ID, such as
+//! a key slot of a secure element, so that implementations can keep them out
+//! of reach of the kernel. Hashes, signatures and public keys are passed in a
+//! single buffer, laid out as described for each operation. All values are
+//! big-endian; a signature is `r` followed by `s`, and a public key is the
+//! uncompressed point `x` followed by `y`, without a format prefix. Only one
+//! operation may be outstanding at a time.
+
+use crate::returncode::ReturnCode;
+
+pub const HASH_LEN: usize = 32;
+pub const SIGNATURE_LEN: usize = 64;
+pub const PUBLIC_KEY_LEN: usize = 64;
+
+pub trait Client {
+    /// Called when a signature has been computed. On success, the first
+    /// `SIGNATURE_LEN` bytes of `buffer` hold the signature.
+    fn sign_done(&self, result: ReturnCode, buffer: &'static mut [u8]);
+
+    /// Called when a signature has been checked. `valid` is only meaningful
+    /// if `result` is `SUCCESS`.
+    fn verify_done(&self, result: ReturnCode, valid: bool, buffer: &'static mut [u8]);
+
+    /// Called when a public key has been retrieved. On success, the first
+    /// `PUBLIC_KEY_LEN` bytes of `buffer` hold the key.
+    fn public_key_done(&self, result: ReturnCode, buffer: &'static mut [u8]);
+}
+
+pub trait EcdsaP256<'a> {
+    fn set_client(&self, client: &'a Client);
+
+    /// Signs the hash in the first `HASH_LEN` bytes of `buffer` with the
+    /// private key `key_id`. `buffer` must be at least `SIGNATURE_LEN` bytes
+    /// long. If the request cannot be started, the buffer is returned along
+    /// with the error.
+    fn sign(
+        &self,
+        key_id: usize,
+        buffer: &'static mut [u8],
+    ) -> (ReturnCode, Option<&'static mut [u8]>);
+
+    /// Checks that the signature in `buffer`, following the hash, is a valid
+    /// signature of the hash by the public key that follows it. `buffer` must
+    /// be at least `HASH_LEN + SIGNATURE_LEN + PUBLIC_KEY_LEN` bytes long.
+    fn verify(&self, buffer: &'static mut [u8]) -> (ReturnCode, Option<&'static mut [u8]>);
+
+    /// Writes the public key matching the private key `key_id` to `buffer`,
+    /// which must be at least `PUBLIC_KEY_LEN` bytes long.
+    fn public_key(
+        &self,
+        key_id: usize,
+        buffer: &'static mut [u8],
+    ) -> (ReturnCode, Option<&'static mut [u8]>);
+}
@@ -0,0 +1,48 @@
+//! Interface for sending and receiving Ethernet frames.
+//!
+//! Frames are complete Ethernet II frames, starting with the destination MAC
+//! address and ending with the payload: the preamble and the frame check
+//! sequence are handled by the MAC.
+
+use crate::returncode::ReturnCode;
+
+/// Length of the destination, source and EtherType fields of a frame
+pub const HEADER_LEN: usize = 14;
+/// Largest payload of a frame
+pub const MTU: usize = 1500;
+/// Largest frame, excluding the frame check sequence
+pub const MAX_FRAME_LEN: usize = HEADER_LEN + MTU;
+
+/// MAC address that every station receives
+pub const BROADCAST: [u8; 6] = [0xff; 6];
+
+pub trait TxClient {
+    /// A frame passed to `transmit` was sent, or could not be.
+    fn transmit_done(&self, frame: &'static mut [u8], result: ReturnCode);
+}
+
+pub trait RxClient {
+    /// A frame addressed to this station, or to a broadcast or multicast
+    /// address, was received with a valid frame check sequence. The frame
+    /// is only valid for the duration of the call.
+    fn received_frame(&self, frame: &[u8]);
+}
+
+pub trait Ethernet<'a> {
+    fn set_transmit_client(&self, client: &'a TxClient);
+    fn set_receive_client(&self, client: &'a RxClient);
+
+    /// The MAC address of the station.
+    fn mac_address(&self) -> [u8; 6];
+
+    /// Start receiving frames.
+    fn enable(&self) -> ReturnCode;
+
+    /// Send the first `len` bytes of `frame`. On success, `frame` is returned
+    /// through `transmit_done`; otherwise it is returned right away.
+    fn transmit(
+        &self,
+        frame: &'static mut [u8],
+        len: usize,
+    ) -> (ReturnCode, Option<&'static mut [u8]>);
+}
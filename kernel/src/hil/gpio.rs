@@ -116,6 +116,24 @@ pub trait ConfigureInputOutput: Configure {
     fn is_input_output(&self) -> bool;
 }
 
+/// Enum for how an output drives its pin. An open-drain output drives the pin
+/// low and leaves it floating for high, and an open-source output the
+/// reverse, so that several devices can drive one line, as on a 1-Wire bus.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum IoMode {
+    PushPull,
+    OpenDrain,
+    OpenSource,
+}
+
+/// Configuration trait for pins whose outputs can be open-drain or
+/// open-source. A pin with an open output is also an input, which reads the
+/// level of the line rather than the level the pin drives.
+pub trait ConfigureIoMode: ConfigureInputOutput {
+    fn set_io_mode(&self, mode: IoMode);
+    fn io_mode(&self) -> IoMode;
+}
+
 pub trait Output {
     /// Set the GPIO pin high. If the pin is not an output or
     /// input/output, this call is ignored.
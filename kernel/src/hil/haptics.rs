@@ -0,0 +1,30 @@
+//! Interface for haptic (vibration) drivers.
+//!
+//! A haptic driver plays effects, such as clicks, buzzes and ramps, from a
+//! library of effects it knows. Effects are identified by their number in
+//! the library, and several can be played in a row to form a pattern.
+
+use crate::returncode::ReturnCode;
+
+pub trait Client {
+    /// Called when `play`, `stop` or `set_library` completes.
+    fn command_done(&self, result: ReturnCode);
+}
+
+pub trait Haptics<'a> {
+    fn set_client(&self, client: &'a Client);
+
+    /// The number of effects `play` accepts at once.
+    fn max_effects(&self) -> usize;
+
+    /// Plays `effects` one after the other. Returns `EINVAL` if there are no
+    /// effects or more than `max_effects`.
+    fn play(&self, effects: &[u8]) -> ReturnCode;
+
+    /// Stops the effects being played.
+    fn stop(&self) -> ReturnCode;
+
+    /// Selects the library that effects are played from. Which libraries
+    /// there are depends on the driver.
+    fn set_library(&self, library: u8) -> ReturnCode;
+}
@@ -0,0 +1,34 @@
+//! Interface for HKDF (RFC 5869) key derivation.
+//!
+//! Keys are derived from pseudorandom keys referred to by an
+//! implementation-defined key ID, such as a key slot of a secure element, so
+//! that implementations can keep them out of reach of the kernel. Only one
+//! derivation may be outstanding at a time.
+
+use crate::returncode::ReturnCode;
+
+/// Length of the derived output, which is one block of HKDF-Expand with
+/// SHA-256.
+pub const OUTPUT_LEN: usize = 32;
+
+pub trait Client {
+    /// Called when a derivation completes. On success, the first `OUTPUT_LEN`
+    /// bytes of `buffer` hold the output.
+    fn expand_done(&self, result: ReturnCode, buffer: &'static mut [u8]);
+}
+
+pub trait Hkdf<'a> {
+    fn set_client(&self, client: &'a Client);
+
+    /// Computes the first `OUTPUT_LEN` bytes of HKDF-Expand with the
+    /// pseudorandom key `key_id` and the first `info_len` bytes of `buffer`
+    /// as the info string. `buffer` must be at least `OUTPUT_LEN` bytes long.
+    /// If the request cannot be started, the buffer is returned along with
+    /// the error.
+    fn expand(
+        &self,
+        key_id: usize,
+        buffer: &'static mut [u8],
+        info_len: usize,
+    ) -> (ReturnCode, Option<&'static mut [u8]>);
+}
@@ -111,6 +111,18 @@ pub trait I2CDevice {
     fn read(&self, buffer: &'static mut [u8], len: u8);
 }
 
+/// An I2C device whose transactions can carry a deadline, for devices that
+/// are sampled periodically and share a bus with devices that are not. A bus
+/// shared by several devices serves transactions with a deadline before those
+/// without one, and the earliest deadline first.
+pub trait I2CDeviceDeadline: I2CDevice {
+    /// Give the next transaction of the device a deadline. Deadlines are times
+    /// of one clock shared by all the devices on the bus, such as the board's
+    /// alarm, and compare correctly while they are less than half its range
+    /// apart. The deadline is dropped once the transaction starts.
+    fn set_deadline(&self, deadline: u32);
+}
+
 /// Client interface for I2CDevice implementations.
 pub trait I2CClient {
     /// Called when an I2C command completed. The `error` denotes whether the command completed
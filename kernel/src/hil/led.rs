@@ -5,6 +5,25 @@
 //!
 
 use crate::hil::gpio;
+use crate::hil::pwm;
+use crate::returncode::ReturnCode;
+
+/// The brightness of an LED that is fully on.
+pub const MAX_BRIGHTNESS: usize = 255;
+
+/// The color of an LED, so that userspace can tell LEDs apart without knowing
+/// how they are laid out on the board.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LedColor {
+    Unknown = 0,
+    Red = 1,
+    Green = 2,
+    Blue = 3,
+    Yellow = 4,
+    Orange = 5,
+    White = 6,
+    Infrared = 7,
+}
 
 pub trait Led {
     fn init(&mut self);
@@ -79,3 +98,49 @@ impl Led for LedLow<'a> {
         !self.pin.read()
     }
 }
+
+/// LEDs whose brightness can be set, and not only turned on or off.
+pub trait LedBrightness {
+    /// Sets the brightness of the LED, from 0 (off) to `MAX_BRIGHTNESS`
+    /// (fully on). Returns `EINVAL` if the brightness is larger.
+    fn set_brightness(&self, brightness: usize) -> ReturnCode;
+}
+
+/// For LEDs dimmed by a PWM output.
+pub struct LedPwm<'a> {
+    pin: &'a pwm::PwmPin,
+    frequency_hz: usize,
+    /// Whether the LED is on when the output is low
+    active_low: bool,
+}
+
+impl LedPwm<'a> {
+    /// `frequency_hz` should be high enough that the LED does not visibly
+    /// flicker, e.g. 1 kHz.
+    pub fn new(pin: &'a pwm::PwmPin, frequency_hz: usize, active_low: bool) -> LedPwm<'a> {
+        LedPwm {
+            pin: pin,
+            frequency_hz: frequency_hz,
+            active_low: active_low,
+        }
+    }
+}
+
+impl LedBrightness for LedPwm<'a> {
+    fn set_brightness(&self, brightness: usize) -> ReturnCode {
+        if brightness > MAX_BRIGHTNESS {
+            return ReturnCode::EINVAL;
+        }
+        // Keep the output running when the LED is off rather than stopping
+        // it, as a stopped output may be left at the level that lights the LED
+        let max_duty_cycle = self.pin.get_maximum_duty_cycle();
+        let duty_cycle = max_duty_cycle / MAX_BRIGHTNESS * brightness
+            + max_duty_cycle % MAX_BRIGHTNESS * brightness / MAX_BRIGHTNESS;
+        if self.active_low {
+            self.pin
+                .start(self.frequency_hz, max_duty_cycle - duty_cycle)
+        } else {
+            self.pin.start(self.frequency_hz, duty_cycle)
+        }
+    }
+}
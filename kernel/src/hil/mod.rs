@@ -2,23 +2,36 @@
 
 pub mod adc;
 pub mod analog_comparator;
+pub mod audio;
 pub mod ble_advertising;
+pub mod charger;
 pub mod crc;
+pub mod cycle_counter;
 pub mod dac;
+pub mod digest;
+pub mod ecdsa;
 pub mod eic;
 pub mod entropy;
+pub mod ethernet;
 pub mod flash;
 pub mod gpio;
 pub mod gpio_async;
+pub mod haptics;
+pub mod hkdf;
 pub mod i2c;
 pub mod led;
+pub mod nfc;
 pub mod nonvolatile_storage;
+pub mod packet_radio;
+pub mod power;
+pub mod pulse_capture;
 pub mod pwm;
 pub mod radio;
 pub mod rng;
 pub mod sensors;
 pub mod spi;
 pub mod symmetric_encryption;
+pub mod tamper;
 pub mod time;
 pub mod uart;
 pub mod usb;
@@ -0,0 +1,60 @@
+//! Interface for NFC tag emulation.
+//!
+//! An NFC tag controller lets the device act as an NFC-A tag (ISO/IEC
+//! 14443-3 type A) to a reader, such as a phone. The controller senses the
+//! reader's field and answers its anticollision and selection on its own.
+//! Once the reader selects the tag, the frames it sends are passed to the
+//! client, which answers each with a frame of its own, e.g. with the ISO-DEP
+//! protocol of a Type 4 tag (see `capsules::nfc_t4t`).
+//!
+//! Frames are passed without their CRC, which the controller checks and
+//! adds.
+
+use crate::returncode::ReturnCode;
+
+pub trait Client {
+    /// Called when a reader's field appears.
+    fn field_detected(&self);
+
+    /// Called when the reader's field disappears. A transmission in progress
+    /// ends with `FAIL` first. The reception buffer is kept for the next
+    /// selection.
+    fn field_lost(&self);
+
+    /// Called when a reader selects the tag.
+    fn selected(&self);
+
+    /// Called when a frame of `len` bytes is received into `buffer`. The
+    /// result is `FAIL` if the frame had a CRC or parity error, and `ESIZE`
+    /// if it did not fit.
+    fn frame_received(&self, buffer: &'static mut [u8], len: usize, result: ReturnCode);
+
+    /// Called when the frame in `buffer` is sent.
+    fn frame_transmitted(&self, buffer: &'static mut [u8], result: ReturnCode);
+}
+
+pub trait NfcTag {
+    fn set_client(&self, client: &'static Client);
+
+    /// Starts sensing for a reader's field.
+    fn enable(&self) -> ReturnCode;
+
+    /// Stops sensing, and leaves the field if the tag is in one.
+    fn disable(&self) -> ReturnCode;
+
+    /// Receives the next frame from the reader into `buffer`, once the tag
+    /// is selected.
+    fn receive(&self, buffer: &'static mut [u8]) -> (ReturnCode, Option<&'static mut [u8]>);
+
+    /// Sends `len` bytes of `buffer` to the reader, as the answer to the
+    /// frame last received.
+    fn transmit(
+        &self,
+        buffer: &'static mut [u8],
+        len: usize,
+    ) -> (ReturnCode, Option<&'static mut [u8]>);
+
+    /// Puts the tag to sleep until the reader wakes it up again, as after a
+    /// deselection.
+    fn sleep(&self) -> ReturnCode;
+}
@@ -0,0 +1,84 @@
+//! Interface for radios that send and receive raw packets.
+//!
+//! Unlike the radios of `radio`, which send IEEE 802.15.4 frames, and of
+//! `ble_advertising`, these radios follow no standard: a packet is whatever
+//! bytes the client sends, and the client picks the data rate, the sync word
+//! and the channel, so that it can talk to devices with a proprietary
+//! protocol. The radio adds a length byte and a CRC to each packet it sends,
+//! and drops the packets it receives that fail the CRC.
+
+use crate::returncode::ReturnCode;
+
+/// The physical layer of the radio.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Config {
+    /// Bits per second
+    pub data_rate: u32,
+    /// Sent before each packet, and searched for by the receiver
+    pub sync_word: u32,
+    /// The channel, in the channel plan of the radio
+    pub channel: u8,
+}
+
+pub trait PacketRadio {
+    fn set_transmit_client(&self, client: &'static TxClient);
+    fn set_receive_client(&self, client: &'static RxClient);
+
+    /// The longest packet the radio sends and receives, without the length
+    /// and CRC that it adds.
+    fn max_packet_len(&self) -> usize;
+
+    /// Power the radio up.
+    fn start(&self) -> ReturnCode;
+
+    /// Power the radio down. Returns `EBUSY` while it transmits.
+    fn stop(&self) -> ReturnCode;
+
+    fn is_on(&self) -> bool;
+
+    /// Set the physical layer, which takes effect at once if the radio is
+    /// on. Returns
+    ///
+    /// - `ENOSUPPORT` if the radio does not support the data rate.
+    /// - `EINVAL` if the channel is not in its channel plan.
+    /// - `EBUSY` while the radio transmits.
+    fn configure(&self, config: Config) -> ReturnCode;
+
+    fn get_config(&self) -> Config;
+
+    /// Send the first `len` bytes of `buf`. The buffer is returned to the
+    /// transmit client once the packet is sent, or at once on an error:
+    ///
+    /// - `EOFF` if the radio is off.
+    /// - `EBUSY` if it is sending another packet.
+    /// - `ESIZE` if `len` is longer than `max_packet_len()` or `buf`.
+    fn transmit(
+        &self,
+        buf: &'static mut [u8],
+        len: usize,
+    ) -> (ReturnCode, Option<&'static mut [u8]>);
+
+    /// Receive the next packet into `buf`, which is returned to the receive
+    /// client with it. Packets that arrive while the radio has no buffer are
+    /// dropped. Returns the buffer on an error:
+    ///
+    /// - `EOFF` if the radio is off.
+    /// - `EBUSY` if it has a buffer already.
+    /// - `ESIZE` if `buf` is shorter than `max_packet_len()`.
+    fn receive(&self, buf: &'static mut [u8]) -> (ReturnCode, Option<&'static mut [u8]>);
+
+    /// Take back the buffer given to `receive()`, if no packet has been
+    /// received into it yet.
+    fn receive_cancel(&self) -> Option<&'static mut [u8]>;
+}
+
+pub trait TxClient {
+    /// The packet in `buf` was sent, or failed to be.
+    fn transmit_done(&self, buf: &'static mut [u8], result: ReturnCode);
+}
+
+pub trait RxClient {
+    /// A packet of `len` bytes was received into `buf`, with a signal
+    /// strength of `rssi` dBm.
+    fn packet_received(&self, buf: &'static mut [u8], len: usize, rssi: i8, result: ReturnCode);
+}
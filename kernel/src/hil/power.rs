@@ -0,0 +1,25 @@
+//! Interface for subsystems that can lower the power they draw.
+//!
+//! When energy is scarce, e.g. on a device powered only by a solar cell, or
+//! the chip runs hot, whatever watches the supply or the temperature tells
+//! the subsystems that can trade function for power, such as a radio that
+//! can listen less often or a sensor that can be sampled less frequently, how
+//! much power they may draw.
+
+/// How much power a consumer may draw, from least to most.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd)]
+pub enum PowerLevel {
+    /// Only what keeps the system running. Loads that can be turned off
+    /// should be.
+    Minimal,
+    /// Loads should run at a reduced duty cycle.
+    Reduced,
+    /// There is no limit.
+    Full,
+}
+
+pub trait PowerConsumer {
+    /// Limits the power the consumer draws to `level`, until it is called
+    /// again.
+    fn set_power_level(&self, level: PowerLevel);
+}
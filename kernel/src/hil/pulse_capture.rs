@@ -0,0 +1,40 @@
+//! Interface for timestamping the edges of a digital input.
+//!
+//! A capture timer records the time of each edge of the input in hardware,
+//! so the timestamps are exact even though clients hear of them later, from
+//! the bottom half of the interrupt. Only the most recent edge is held, so
+//! edges that come faster than the kernel services them are lost. Pulse
+//! widths and periods are differences of timestamps, which wrap around after
+//! `max_timestamp()`, so they are taken with wrapping arithmetic and must be
+//! shorter than a full wrap.
+
+use crate::returncode::ReturnCode;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Edge {
+    Rising,
+    Falling,
+    Both,
+}
+
+pub trait Client {
+    /// An edge was captured at `timestamp`.
+    fn edge_captured(&self, timestamp: u32);
+}
+
+pub trait PulseCapture<'a> {
+    fn set_client(&self, client: &'a Client);
+
+    /// Start capturing `edge` edges, or switch to them if capturing already.
+    fn start(&self, edge: Edge) -> ReturnCode;
+
+    /// Stop capturing edges.
+    fn stop(&self) -> ReturnCode;
+
+    /// The frequency the timestamps count at, in Hz.
+    fn frequency(&self) -> u32;
+
+    /// The largest timestamp, after which they wrap around to 0. This is one
+    /// less than a power of two.
+    fn max_timestamp(&self) -> u32;
+}
@@ -25,6 +25,10 @@ pub trait Pwm {
     /// The frequency will be specified in Hertz.
     fn get_maximum_frequency_hz(&self) -> usize;
 
+    /// Return the minimum PWM frequency supported by the PWM implementation.
+    /// The frequency will be specified in Hertz.
+    fn get_minimum_frequency_hz(&self) -> usize;
+
     /// Return an opaque number that represents a 100% duty cycle. This value
     /// will be hardware specific, and essentially represents the precision
     /// of the underlying PWM hardware.
@@ -55,6 +59,10 @@ pub trait PwmPin {
     /// Same as the `get_maximum_frequency_hz` function in the `Pwm` trait.
     fn get_maximum_frequency_hz(&self) -> usize;
 
+    /// Return the minimum PWM frequency supported by the PWM implementation.
+    /// Same as the `get_minimum_frequency_hz` function in the `Pwm` trait.
+    fn get_minimum_frequency_hz(&self) -> usize;
+
     /// Return an opaque number that represents a 100% duty cycle. This value
     /// Same as the `get_maximum_duty_cycle` function in the `Pwm` trait.
     fn get_maximum_duty_cycle(&self) -> usize;
@@ -8,6 +8,18 @@
 //! config_commit. Please see the relevant TRD for more details.
 
 use crate::returncode::ReturnCode;
+
+/// Link-layer details of a completed transmission. Fields are `None` if the
+/// radio does not report them.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct TxInfo {
+    /// Number of times the frame was retransmitted because it was not
+    /// acknowledged
+    pub retries: Option<u8>,
+    /// RSSI of the received acknowledgement, in dBm
+    pub ack_rssi: Option<i8>,
+}
+
 pub trait TxClient {
     fn send_done(&self, buf: &'static mut [u8], acked: bool, result: ReturnCode);
 }
@@ -110,4 +122,8 @@ pub trait RadioData {
         spi_buf: &'static mut [u8],
         frame_len: usize,
     ) -> (ReturnCode, Option<&'static mut [u8]>);
+
+    /// Returns the details of the most recently completed transmission. This
+    /// is only meaningful when called from the `send_done` callback.
+    fn get_tx_info(&self) -> TxInfo;
 }
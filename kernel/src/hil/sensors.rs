@@ -17,6 +17,21 @@ pub trait TemperatureClient {
     fn callback(&self, value: usize);
 }
 
+/// A basic interface for a sensor of a voltage, such as the supply voltage of
+/// a chip, or that of its battery.
+pub trait VoltageDriver {
+    fn set_client(&self, client: &'static VoltageClient);
+    fn read_voltage(&self) -> ReturnCode;
+}
+
+/// Client for receiving voltage readings.
+pub trait VoltageClient {
+    /// Called when a voltage reading has completed.
+    ///
+    /// - `value`: the most recently read voltage in millivolts.
+    fn callback(&self, value: usize);
+}
+
 /// A basic interface for a humidity sensor
 pub trait HumidityDriver {
     fn set_client(&self, client: &'static HumidityClient);
@@ -151,6 +151,47 @@ pub trait SpiMasterDevice {
     fn get_rate(&self) -> u32;
 }
 
+/// One segment of a chained transfer, such as the command, the address or
+/// the payload of a request to a flash chip.
+pub struct Segment {
+    /// The bytes to write, which every segment must have
+    pub write: Option<&'static mut [u8]>,
+    /// Where to put the bytes read, if they are wanted
+    pub read: Option<&'static mut [u8]>,
+    /// The length of the segment, at most that of its buffers
+    pub len: usize,
+    /// Keep the chip select asserted after the segment, so that the next
+    /// segment is part of the same transaction. Ignored for the last segment,
+    /// after which the chip select is always released.
+    pub hold_chip_select: bool,
+}
+
+pub trait SpiMasterChainClient {
+    /// Called when a chained transfer finishes, with its segments and their
+    /// buffers. On an error, the segments from the one that failed onwards
+    /// were not transferred, and the buffers of the one that failed may be
+    /// missing if the SPI master did not return them.
+    fn transfer_chain_done(&self, segments: &'static mut [Segment], result: ReturnCode);
+}
+
+/// Transfers made of several segments, each with its own buffers, so that a
+/// driver can send a command, an address and a payload without copying them
+/// into one buffer. The segments are transferred back to back, before any
+/// other transfer on the bus.
+pub trait SpiMasterDeviceChain: SpiMasterDevice {
+    fn set_chain_client(&self, client: &'static SpiMasterChainClient);
+
+    /// Transfer `segments` in order. Returns the segments on an error:
+    ///
+    /// - `EINVAL` if there are no segments, or a segment has no write buffer.
+    /// - `ESIZE` if a segment is longer than one of its buffers.
+    /// - `EBUSY` if a transfer of the device is pending already.
+    fn transfer_chain(
+        &self,
+        segments: &'static mut [Segment],
+    ) -> (ReturnCode, Option<&'static mut [Segment]>);
+}
+
 pub trait SpiSlaveClient {
     /// This is called whenever the slave is selected by the master
     fn chip_selected(&self);
@@ -0,0 +1,58 @@
+//! Interface for tamper detection.
+//!
+//! A tamper detector watches for physical attacks on the device, such as its
+//! case being opened or its supply voltage being glitched to make the CPU
+//! skip instructions. When a detector triggers, the chip latches the event,
+//! often in a battery-backed domain so that events that happen while the
+//! device is off are not lost, and records when it happened if it can. The
+//! client is called for each event, and a latched event stays latched (and
+//! the detector does not trigger again) until it is cleared with `clear`.
+//! Events latched before a client was set, e.g. while the device was off, can
+//! be read with `latched`.
+
+use crate::returncode::ReturnCode;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum TamperSource {
+    /// A tamper switch or mesh, e.g. one that opens with the case
+    CaseOpen,
+    /// The supply voltage dropped below a safe level
+    VoltageGlitch,
+    /// The clock ran outside its expected frequency range
+    ClockGlitch,
+    /// The die temperature left its expected range
+    Temperature,
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct TamperEvent {
+    pub source: TamperSource,
+    /// When the event happened, in seconds as counted by the chip's
+    /// backup-domain clock, or `None` if the chip does not record when events
+    /// from this source happen. The epoch is chip-specific.
+    pub timestamp: Option<u32>,
+}
+
+pub trait Client {
+    /// Called when a tamper event is detected.
+    fn tamper_detected(&self, event: TamperEvent);
+}
+
+pub trait Tamper<'a> {
+    fn set_client(&self, client: &'a Client);
+
+    /// Starts detecting events from `source`. Returns `ENOSUPPORT` if the chip
+    /// cannot detect them.
+    fn enable(&self, source: TamperSource) -> ReturnCode;
+
+    /// Stops detecting events from `source`. Events that are already latched
+    /// stay latched.
+    fn disable(&self, source: TamperSource) -> ReturnCode;
+
+    /// Returns the latched event from `source`, if there is one.
+    fn latched(&self, source: TamperSource) -> Option<TamperEvent>;
+
+    /// Clears the latched event from `source`, so that a new event can be
+    /// detected.
+    fn clear(&self, source: TamperSource) -> ReturnCode;
+}
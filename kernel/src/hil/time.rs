@@ -1,5 +1,7 @@
 //! Hardware agnostic interfaces for counter-like resources.
 
+use core::marker::PhantomData;
+
 pub trait Time {
     type Frequency: Frequency;
 
@@ -16,6 +18,130 @@ pub trait Time {
 /// convert native cycles to real-time values.
 pub trait Frequency {
     fn frequency() -> u32;
+
+    /// Converts `ms` milliseconds to clock tics, rounded up so that a delay
+    /// never ends early.
+    fn ms_to_tics(ms: u32) -> u32 {
+        to_tics(ms, Self::frequency(), 1000)
+    }
+
+    /// Converts `us` microseconds to clock tics, rounded up so that a delay
+    /// never ends early.
+    fn us_to_tics(us: u32) -> u32 {
+        to_tics(us, Self::frequency(), 1000000)
+    }
+
+    /// Converts `tics` clock tics to milliseconds, rounded down.
+    fn tics_to_ms(tics: u32) -> u32 {
+        saturate(tics as u64 * 1000 / Self::frequency() as u64)
+    }
+
+    /// Converts `tics` clock tics to microseconds, rounded down.
+    fn tics_to_us(tics: u32) -> u32 {
+        saturate(tics as u64 * 1000000 / Self::frequency() as u64)
+    }
+}
+
+/// `duration` in units of `1 / units_per_second` seconds, in tics of a clock
+/// at `frequency`. The product is computed on 64 bits, as it overflows 32
+/// bits for fast clocks and durations of more than a fraction of a second.
+/// Durations too long for 32 bits of tics, e.g. of more than about 268 s at
+/// 16 MHz, saturate to `u32::max_value()` tics rather than wrap around to a
+/// short duration that would end early.
+fn to_tics(duration: u32, frequency: u32, units_per_second: u64) -> u32 {
+    saturate((duration as u64 * frequency as u64 + units_per_second - 1) / units_per_second)
+}
+
+fn saturate(value: u64) -> u32 {
+    if value > u32::max_value() as u64 {
+        u32::max_value()
+    } else {
+        value as u32
+    }
+}
+
+/// A number of tics of a clock at frequency `F`.
+///
+/// Tics of clocks at different frequencies are different types, so a
+/// duration computed for one clock can't be given to another without
+/// converting it.
+///
+/// ```
+/// use kernel::hil::time::{Freq16KHz, Freq32KHz, Tics};
+///
+/// let timeout = Tics::<Freq32KHz>::from_ms(10);
+/// assert_eq!(timeout.tics(), 328);
+/// let timeout: Tics<Freq16KHz> = timeout.convert();
+/// assert_eq!(timeout.tics(), 161);
+/// assert_eq!(timeout.to_ms(), 10);
+/// ```
+#[derive(Debug)]
+pub struct Tics<F: Frequency> {
+    tics: u32,
+    _frequency: PhantomData<F>,
+}
+
+impl<F: Frequency> Clone for Tics<F> {
+    fn clone(&self) -> Tics<F> {
+        *self
+    }
+}
+
+impl<F: Frequency> Copy for Tics<F> {}
+
+impl<F: Frequency> Tics<F> {
+    pub fn new(tics: u32) -> Tics<F> {
+        Tics {
+            tics: tics,
+            _frequency: PhantomData,
+        }
+    }
+
+    /// `ms` milliseconds, rounded up to a whole tic.
+    pub fn from_ms(ms: u32) -> Tics<F> {
+        Tics::new(F::ms_to_tics(ms))
+    }
+
+    /// `us` microseconds, rounded up to a whole tic.
+    pub fn from_us(us: u32) -> Tics<F> {
+        Tics::new(F::us_to_tics(us))
+    }
+
+    /// The number of tics, e.g. to add to the `now()` of an alarm.
+    pub fn tics(&self) -> u32 {
+        self.tics
+    }
+
+    /// In milliseconds, rounded down.
+    pub fn to_ms(&self) -> u32 {
+        F::tics_to_ms(self.tics)
+    }
+
+    /// In microseconds, rounded down.
+    pub fn to_us(&self) -> u32 {
+        F::tics_to_us(self.tics)
+    }
+
+    /// The same duration in tics of a clock at frequency `G`, rounded up.
+    pub fn convert<G: Frequency>(&self) -> Tics<G> {
+        Tics::new(to_tics(self.tics, G::frequency(), F::frequency() as u64))
+    }
+}
+
+/// Returns whether `dt` tics have passed since `reference`, at time `now`.
+///
+/// Clocks wrap around, so comparing `now` with `reference + dt` directly gets
+/// the wrong answer whenever one of them wrapped and not the other. This
+/// compares the time elapsed since `reference` instead, which is correct as
+/// long as `now` is less than a whole period of the clock past `reference`.
+pub fn expired(now: u32, reference: u32, dt: u32) -> bool {
+    now.wrapping_sub(reference) >= dt
+}
+
+/// Returns how many tics are left, at time `now`, until `dt` tics have passed
+/// since `reference`, or 0 if they already have.
+pub fn remaining(now: u32, reference: u32, dt: u32) -> u32 {
+    dt.saturating_sub(now.wrapping_sub(reference))
 }
 
 /// 16MHz `Frequency`
@@ -27,6 +153,24 @@ impl Frequency for Freq16MHz {
     }
 }
 
+/// 10MHz `Frequency`
+#[derive(Debug)]
+pub struct Freq10MHz;
+impl Frequency for Freq10MHz {
+    fn frequency() -> u32 {
+        10000000
+    }
+}
+
+/// 1MHz `Frequency`
+#[derive(Debug)]
+pub struct Freq1MHz;
+impl Frequency for Freq1MHz {
+    fn frequency() -> u32 {
+        1000000
+    }
+}
+
 /// 32KHz `Frequency`
 #[derive(Debug)]
 pub struct Freq32KHz;
@@ -65,6 +209,14 @@ pub trait Alarm: Time {
     /// Returns the current time in hardware clock units.
     fn now(&self) -> u32;
 
+    /// Returns the current time as a 64-bit count of tics, if the hardware
+    /// counts on more than 32 bits. Its low 32 bits are the same as
+    /// [`now`](#tymethod.now). Alarms whose clock is only 32 bits (or fewer)
+    /// wide return `None`, the default.
+    fn now64(&self) -> Option<u64> {
+        None
+    }
+
     /// Sets a one-shot alarm fire when the clock reaches `tics`.
     ///
     /// [`Client#fired`](trait.Client.html#tymethod.fired) is signaled
@@ -92,9 +244,97 @@ pub trait Client {
 
 /// The `Timer` trait models a timer that can notify when a particular interval
 /// has elapsed.
+///
+/// Timers signal their [`Client`](trait.Client.html) each time the interval
+/// elapses. Implementations of `repeat` should use the auto-reload of the
+/// hardware, which restarts the interval at the tic it elapsed. Re-arming the
+/// timer from the interrupt handler instead adds the latency of the handler to
+/// every period, so the timer drifts further behind with each one.
 pub trait Timer: Time {
     /// Sets a one-shot timer to fire in `interval` clock-tics.
     fn oneshot(&self, interval: u32);
-    /// Sets repeating timer to fire every `interval` clock-tics.
+    /// Sets repeating timer to fire every `interval` clock-tics, until it is
+    /// disabled.
     fn repeat(&self, interval: u32);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{expired, remaining, Freq16MHz, Freq1KHz, Freq1MHz, Freq32KHz, Frequency, Tics};
+
+    #[test]
+    fn ms_to_tics_rounds_up() {
+        // 10 ms are 327.68 tics at 32768 Hz
+        assert_eq!(Freq32KHz::ms_to_tics(10), 328);
+        assert_eq!(Freq32KHz::us_to_tics(1), 1);
+        assert_eq!(Freq1MHz::us_to_tics(1000), 1000);
+        assert_eq!(Freq32KHz::ms_to_tics(0), 0);
+    }
+
+    #[test]
+    fn tics_to_ms_rounds_down() {
+        assert_eq!(Freq32KHz::tics_to_ms(328), 10);
+        assert_eq!(Freq32KHz::tics_to_ms(32767), 999);
+        assert_eq!(Freq32KHz::tics_to_us(1), 30);
+        assert_eq!(Freq1MHz::tics_to_us(u32::max_value()), u32::max_value());
+    }
+
+    #[test]
+    fn to_tics_saturates() {
+        // The longest duration that fits in 32 bits of tics at 16 MHz is
+        // 268435455 us, just under 268.5 s
+        assert_eq!(Freq16MHz::ms_to_tics(268435), 4294960000);
+        assert_eq!(Freq16MHz::ms_to_tics(268436), u32::max_value());
+        assert_eq!(Freq16MHz::us_to_tics(268435455), 4294967280);
+        assert_eq!(Freq16MHz::us_to_tics(268435456), u32::max_value());
+        assert_eq!(Freq16MHz::ms_to_tics(u32::max_value()), u32::max_value());
+    }
+
+    #[test]
+    fn tics_to_us_saturates() {
+        // More microseconds than fit in 32 bits
+        assert_eq!(Freq1KHz::tics_to_us(u32::max_value()), u32::max_value());
+        assert_eq!(Freq1KHz::tics_to_us(4294967), 4294967000);
+        assert_eq!(Freq1KHz::tics_to_us(4294968), u32::max_value());
+    }
+
+    #[test]
+    fn tics_convert() {
+        let timeout = Tics::<Freq32KHz>::from_ms(1000);
+        assert_eq!(timeout.tics(), 32768);
+        assert_eq!(timeout.convert::<Freq1MHz>().tics(), 1000000);
+        assert_eq!(Tics::<Freq16MHz>::from_ms(300000).tics(), u32::max_value());
+    }
+
+    #[test]
+    fn expired_without_wraparound() {
+        assert!(!expired(100, 100, 10));
+        assert!(!expired(109, 100, 10));
+        assert!(expired(110, 100, 10));
+        assert!(expired(200, 100, 10));
+        assert!(expired(100, 100, 0));
+    }
+
+    #[test]
+    fn expired_across_wraparound() {
+        let reference = u32::max_value() - 5;
+        // The deadline, reference + 10, wraps around to 4
+        assert!(!expired(u32::max_value(), reference, 10));
+        assert!(!expired(3, reference, 10));
+        assert!(expired(4, reference, 10));
+        assert!(expired(100, reference, 10));
+        // A deadline that wraps around to exactly 0
+        assert!(!expired(u32::max_value(), u32::max_value() - 1, 2));
+        assert!(expired(0, u32::max_value() - 1, 2));
+    }
+
+    #[test]
+    fn remaining_across_wraparound() {
+        let reference = u32::max_value() - 5;
+        assert_eq!(remaining(reference, reference, 10), 10);
+        assert_eq!(remaining(u32::max_value(), reference, 10), 5);
+        assert_eq!(remaining(3, reference, 10), 1);
+        assert_eq!(remaining(4, reference, 10), 0);
+        assert_eq!(remaining(100, reference, 10), 0);
+    }
+}
@@ -61,6 +61,7 @@ pub enum Error {
 pub trait Uart<'a>: Configure + Transmit<'a> + Receive<'a> {}
 pub trait UartData<'a>: Transmit<'a> + Receive<'a> {}
 pub trait UartAdvanced<'a>: Configure + Transmit<'a> + ReceiveAdvanced<'a> {}
+pub trait UartDataAdvanced<'a>: Transmit<'a> + ReceiveAdvanced<'a> {}
 pub trait Client: ReceiveClient + TransmitClient {}
 
 /// Trait for configuring a UART.
@@ -108,6 +109,24 @@ pub trait Transmit<'a> {
         tx_len: usize,
     ) -> (ReturnCode, Option<&'static mut [u8]>);
 
+    /// Queue a buffer to be transmitted as soon as the buffer being
+    /// transmitted is done, so that the line does not go idle between
+    /// them. The queued buffer starts before the `transmitted_buffer`
+    /// callback of the one before it, and has a callback of its own. If no
+    /// `transmit_buffer` is outstanding, this is the same as
+    /// `transmit_buffer`. If the `ReturnCode` is not SUCCESS, the buffer is
+    /// returned in the `Option`. Other valid `ReturnCode` values are:
+    ///  - EBUSY: a buffer is queued already, or a word is being transmitted.
+    ///  - ESIZE : `tx_len` is larger than the passed slice.
+    ///  - ENOSUPPORT: the UART cannot queue buffers, which is the default.
+    fn transmit_continue(
+        &self,
+        tx_buffer: &'static mut [u8],
+        _tx_len: usize,
+    ) -> (ReturnCode, Option<&'static mut [u8]>) {
+        (ReturnCode::ENOSUPPORT, Some(tx_buffer))
+    }
+
     /// Transmit a single word of data asynchronously. The word length is
     /// determined by the UART configuration: it can be 6, 7, 8, or 9 bits long.
     /// If the `ReturnCode` is SUCCESS, on completion,
@@ -0,0 +1,146 @@
+//! Latency of the bottom halves of interrupts.
+//!
+//! The kernel does not handle an interrupt when it is taken: the top half
+//! only disables it and wakes the kernel, which services it later from
+//! `Chip::service_pending_interrupts`, after the system call or interrupt it
+//! is busy with. This module measures how long an interrupt waits for its
+//! bottom half. The top half of the architecture calls `entered()` with the
+//! number of the interrupt, the chip calls `serviced()` with it before it
+//! runs the handler, and the time between the two is kept, in microseconds,
+//! as the worst case of each interrupt that is watched.
+//!
+//! A board watches the interrupts it cares about, and gives the module a
+//! clock to take the time from:
+//!
+//! ```ignore
+//! let uart0 = static_init!(IrqLatency, IrqLatency::new(5, "irq.uart0.latency_us"));
+//! kernel::interrupt_latency::set_clock(&cc26x2::rtc::RTC);
+//! kernel::interrupt_latency::watch(uart0);
+//! ```
+//!
+//! The worst cases are gauges registered with `kernel::metrics`, so they are
+//! exported with the other metrics. Latencies longer than the clock takes to
+//! wrap around are wrong, and latencies shorter than a tick of it count as
+//! 0, so the clock should be the fastest one that is always running.
+
+use core::cell::Cell;
+
+use crate::common::list::{List, ListLink, ListNode};
+use crate::hil::time::{self, Frequency};
+use crate::metrics::{self, Metric};
+
+/// A free running counter that interrupts are timestamped with.
+pub trait Clock {
+    /// The current value of the counter.
+    fn now(&self) -> u32;
+
+    /// The rate the counter counts at, in Hz.
+    fn frequency(&self) -> u32;
+}
+
+impl<A: time::Alarm> Clock for A {
+    fn now(&self) -> u32 {
+        time::Alarm::now(self)
+    }
+
+    fn frequency(&self) -> u32 {
+        A::Frequency::frequency()
+    }
+}
+
+/// The latency of an interrupt that is watched.
+pub struct IrqLatency {
+    irq: u32,
+    /// When the interrupt was taken, if it has not been serviced since
+    entered: Cell<Option<u32>>,
+    /// The worst latency so far, in microseconds
+    worst: Metric,
+    next: ListLink<'static, IrqLatency>,
+}
+
+impl ListNode<'static, IrqLatency> for IrqLatency {
+    fn next(&'static self) -> &'static ListLink<'static, IrqLatency> {
+        &self.next
+    }
+}
+
+impl IrqLatency {
+    /// Watch interrupt `irq`, with its worst latency in the metric `name`.
+    pub const fn new(irq: u32, name: &'static str) -> IrqLatency {
+        IrqLatency {
+            irq: irq,
+            entered: Cell::new(None),
+            worst: Metric::gauge(name),
+            next: ListLink::empty(),
+        }
+    }
+
+    pub fn irq(&self) -> u32 {
+        self.irq
+    }
+
+    /// The worst latency of the interrupt so far, in microseconds.
+    pub fn worst(&self) -> u32 {
+        self.worst.get()
+    }
+
+    /// Forget the worst latency, e.g. once it has been reported.
+    pub fn reset(&self) {
+        self.worst.set(0);
+    }
+}
+
+static mut CLOCK: Option<&'static Clock> = None;
+
+static mut WATCHED: List<'static, IrqLatency> = List::new();
+
+/// Timestamp interrupts with `clock`. Until a board sets a clock, nothing is
+/// measured.
+pub fn set_clock(clock: &'static Clock) {
+    unsafe {
+        CLOCK = Some(clock);
+    }
+}
+
+/// Measure the latency of `latency.irq()`, and register its metric. An
+/// interrupt must be watched only once.
+pub fn watch(latency: &'static IrqLatency) {
+    metrics::register(&latency.worst);
+    unsafe {
+        WATCHED.push_tail(latency);
+    }
+}
+
+fn find(irq: u32) -> Option<&'static IrqLatency> {
+    unsafe { WATCHED.iter().find(|latency| latency.irq == irq) }
+}
+
+/// Interrupt `irq` was taken. This is called from the top half, which
+/// disables the interrupt, so it is not taken again before it is serviced.
+pub fn entered(irq: u32) {
+    if let Some(clock) = unsafe { CLOCK } {
+        if let Some(latency) = find(irq) {
+            latency.entered.set(Some(clock.now()));
+        }
+    }
+}
+
+/// The kernel is about to service interrupt `irq`.
+pub fn serviced(irq: u32) {
+    if let Some(clock) = unsafe { CLOCK } {
+        if let Some(latency) = find(irq) {
+            if let Some(entered) = latency.entered.take() {
+                let ticks = clock.now().wrapping_sub(entered);
+                let us = ticks as u64 * 1_000_000 / clock.frequency().max(1) as u64;
+                let us = if us > u32::max_value() as u64 {
+                    u32::max_value()
+                } else {
+                    us as u32
+                };
+                if us > latency.worst.get() {
+                    latency.worst.set(us);
+                }
+            }
+        }
+    }
+}
@@ -101,6 +101,29 @@ impl KernelInfo {
         })
     }
 
+    /// Returns how many callbacks the queues of the app hold, of normal and
+    /// of high priority, as the app set in its TBF header.
+    pub fn app_callback_queue_depths(
+        &self,
+        app: AppId,
+        _capability: &ProcessManagementCapability,
+    ) -> (usize, usize) {
+        self.kernel
+            .process_map_or((0, 0), app.idx(), |process| process.callback_queue_depths())
+    }
+
+    /// Returns the most callbacks that were queued for the app at once. An
+    /// app whose queues fill up drops callbacks, and should ask for deeper
+    /// queues.
+    pub fn number_app_max_queued_callbacks(
+        &self,
+        app: AppId,
+        _capability: &ProcessManagementCapability,
+    ) -> usize {
+        self.kernel
+            .process_map_or(0, app.idx(), |process| process.debug_max_queued_callbacks())
+    }
+
     /// Returns the number of time this app has been restarted.
     pub fn number_app_restarts(
         &self,
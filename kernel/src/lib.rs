@@ -10,6 +10,7 @@
 #![feature(panic_info_message)]
 #![feature(in_band_lifetimes, crate_visibility_modifier)]
 #![feature(associated_type_defaults)]
+#![feature(futures_api)]
 #![warn(unreachable_pub)]
 #![no_std]
 
@@ -19,11 +20,20 @@ pub mod common;
 pub mod component;
 #[macro_use]
 pub mod debug;
+#[macro_use]
+pub mod error_log;
 pub mod hil;
+pub mod interrupt_latency;
 pub mod introspection;
 pub mod ipc;
+pub mod metrics;
+pub mod process_info;
+pub mod profiler;
+pub mod record;
 pub mod syscall;
 
+mod app_ring;
+mod batch;
 mod callback;
 mod driver;
 mod grant;
@@ -35,13 +45,15 @@ mod returncode;
 mod sched;
 mod tbfheader;
 
-pub use crate::callback::{AppId, Callback};
+pub use crate::app_ring::AppRing;
+pub use crate::callback::{AppId, Callback, Coalesce, Priority};
 pub use crate::driver::Driver;
 pub use crate::grant::Grant;
-pub use crate::mem::{AppPtr, AppSlice, Private, Shared};
+pub use crate::mem::{AppPtr, AppSlice, LentAppSlice, Private, Shared};
 pub use crate::platform::systick::SysTick;
 pub use crate::platform::{mpu, Chip, Platform};
 pub use crate::platform::{ClockInterface, NoClockControl, NO_CLOCK_CONTROL};
+pub use crate::process::{ProcessEvent, ProcessStateObserver};
 pub use crate::returncode::ReturnCode;
 pub use crate::sched::Kernel;
 
@@ -128,3 +128,60 @@ impl<L, T> AsMut<[T]> for AppSlice<L, T> {
         unsafe { slice::from_raw_parts_mut(self.ptr.ptr.as_mut(), self.len) }
     }
 }
+
+/// An `AppSlice` whose buffer is lent to a HIL that takes buffers as
+/// `&'static mut [T]`, so that a driver can hand the memory of a process to
+/// the hardware, e.g. for DMA, without copying it.
+///
+/// The `LentAppSlice` keeps the `AppSlice`, so the memory stays allowed to
+/// the driver while the HIL has it, and the driver gets the `AppSlice` back
+/// with `reclaim()` once the HIL returns the buffer.
+pub struct LentAppSlice<T> {
+    slice: AppSlice<Shared, T>,
+}
+
+impl<T> AppSlice<Shared, T> {
+    /// Lend the buffer, to pass it to a HIL.
+    ///
+    /// # Safety
+    ///
+    /// The returned buffer is neither `'static` nor exclusive. It is memory
+    /// of the process, which the process can write whenever it runs, and
+    /// which it can also `allow` to other drivers, so other `AppSlice`s may
+    /// cover the same memory. The caller must:
+    ///
+    /// - only lend it to a HIL that uses its contents as bytes that can
+    ///   change at any time, e.g. as the source of a DMA transfer, and never
+    ///   makes a reference into it that outlives the transfer;
+    /// - not access the memory through any other `AppSlice` while it is lent;
+    /// - get the buffer back from the HIL and pass it to `reclaim()` before
+    ///   the process is torn down. If the process faults or restarts while
+    ///   the buffer is lent, the caller must stop the transfer, e.g. by
+    ///   aborting it when a `ProcessStateObserver` sees the process fault,
+    ///   as the memory then belongs to the next run of the process;
+    /// - not use the buffer after passing it to `reclaim()`.
+    pub unsafe fn lend(mut self) -> (LentAppSlice<T>, &'static mut [T]) {
+        let buffer = slice::from_raw_parts_mut(self.ptr.ptr.as_mut(), self.len);
+        (LentAppSlice { slice: self }, buffer)
+    }
+}
+
+impl<T> LentAppSlice<T> {
+    /// Number of elements in the lent buffer.
+    pub fn len(&self) -> usize {
+        self.slice.len()
+    }
+
+    /// Take the `AppSlice` back, given the buffer the HIL returned. If
+    /// `buffer` is not the one that was lent, it is returned with the loan.
+    pub fn reclaim(
+        self,
+        buffer: &'static mut [T],
+    ) -> Result<AppSlice<Shared, T>, (LentAppSlice<T>, &'static mut [T])> {
+        if buffer.as_ptr() == self.slice.ptr() && buffer.len() == self.slice.len() {
+            Ok(self.slice)
+        } else {
+            Err((self, buffer))
+        }
+    }
+}
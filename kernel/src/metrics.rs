@@ -0,0 +1,106 @@
+//! Named counters and gauges, for watching devices in the field.
+//!
+//! The kernel and capsules keep `Metric`s for the things worth watching on a
+//! deployed device: how many packets were dropped, how many times a sensor
+//! did not answer, how deep a queue is. Each metric that is registered with
+//! `register` is part of the registry that exporters (such as
+//! `capsules::net::metrics_export`) read and publish.
+//!
+//! ```ignore
+//! struct Radio {
+//!     dropped: Metric,
+//! }
+//!
+//! let radio = static_init!(Radio, Radio { dropped: Metric::counter("radio.dropped") });
+//! kernel::metrics::register(&radio.dropped);
+//! ...
+//! self.dropped.incr();
+//! ```
+//!
+//! Metrics that are not registered still count; they are only not exported.
+
+use core::cell::Cell;
+
+use crate::common::list::{List, ListIterator, ListLink, ListNode};
+
+/// What the value of a metric means.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Kind {
+    /// A count of events since boot, which only goes up
+    Counter,
+    /// A level that goes up and down
+    Gauge,
+}
+
+pub struct Metric {
+    name: &'static str,
+    kind: Kind,
+    value: Cell<u32>,
+    next: ListLink<'static, Metric>,
+}
+
+impl ListNode<'static, Metric> for Metric {
+    fn next(&'static self) -> &'static ListLink<'static, Metric> {
+        &self.next
+    }
+}
+
+impl Metric {
+    pub const fn counter(name: &'static str) -> Metric {
+        Metric::new(name, Kind::Counter)
+    }
+
+    pub const fn gauge(name: &'static str) -> Metric {
+        Metric::new(name, Kind::Gauge)
+    }
+
+    const fn new(name: &'static str, kind: Kind) -> Metric {
+        Metric {
+            name: name,
+            kind: kind,
+            value: Cell::new(0),
+            next: ListLink::empty(),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    pub fn kind(&self) -> Kind {
+        self.kind
+    }
+
+    pub fn get(&self) -> u32 {
+        self.value.get()
+    }
+
+    /// Count one event. Counters saturate rather than wrap around.
+    pub fn incr(&self) {
+        self.add(1);
+    }
+
+    pub fn add(&self, amount: u32) {
+        self.value.set(self.value.get().saturating_add(amount));
+    }
+
+    /// Set the level of a gauge.
+    pub fn set(&self, value: u32) {
+        self.value.set(value);
+    }
+}
+
+static mut METRICS: List<'static, Metric> = List::new();
+
+/// Add `metric` to the metrics that are exported. A metric must be
+/// registered only once.
+pub fn register(metric: &'static Metric) {
+    unsafe {
+        METRICS.push_tail(metric);
+    }
+}
+
+/// The registered metrics, in the order they were registered.
+pub fn iter() -> ListIterator<'static, Metric> {
+    unsafe { METRICS.iter() }
+}
@@ -5,13 +5,14 @@ use core::fmt::Write;
 use core::ptr::write_volatile;
 use core::{mem, ptr, slice, str};
 
-use crate::callback::AppId;
+use crate::callback::{AppId, Coalesce, Priority};
 use crate::capabilities::ProcessManagementCapability;
 use crate::common::cells::MapCell;
 use crate::common::{Queue, RingBuffer};
 use crate::mem::{AppSlice, Shared};
 use crate::platform::mpu::{self, MPU};
 use crate::platform::Chip;
+use crate::process_info;
 use crate::returncode::ReturnCode;
 use crate::sched::Kernel;
 use crate::syscall::{self, Syscall, UserspaceKernelBoundary};
@@ -62,6 +63,14 @@ pub fn load_processes<C: Chip>(
                 }
             } else {
                 procs[i] = process;
+                process.map(|process| {
+                    process_info::process_loaded(i, process);
+                    kernel.process_event(
+                        process.appid(),
+                        process.get_process_name(),
+                        ProcessEvent::Created,
+                    );
+                });
             }
 
             apps_in_flash_ptr = apps_in_flash_ptr.add(flash_offset);
@@ -85,6 +94,16 @@ pub trait ProcessType {
     /// this is passed to the capsule that tried to schedule the `Task`.
     fn enqueue_task(&self, task: Task) -> bool;
 
+    /// Queue a `Task` as `enqueue_task()` does, ahead of the `Task`s of
+    /// normal priority if `priority` is high and the process has room for
+    /// it in its queue of high priority tasks.
+    fn enqueue_task_with_priority(&self, task: Task, priority: Priority) -> bool;
+
+    /// Merge `call` into a call of the same callback that is queued
+    /// already, as `coalesce` says, or else queue it with `priority`. Calls
+    /// are of the same callback if they have the same function and userdata.
+    fn coalesce_task(&self, call: FunctionCall, coalesce: Coalesce, priority: Priority) -> bool;
+
     /// Remove the scheduled operation from the front of the queue and return it
     /// to be handled by the scheduler.
     ///
@@ -176,6 +195,10 @@ pub trait ProcessType {
     /// and cannot be edited by the process.
     fn flash_non_protected_start(&self) -> *const u8;
 
+    /// The flash of the process that isn't protected by the kernel, from
+    /// `flash_non_protected_start` to `flash_end`.
+    fn flash_non_protected(&self) -> &'static [u8];
+
     // mpu
 
     /// Configure the MPU to use the process's allocated regions.
@@ -224,6 +247,13 @@ pub trait ProcessType {
     /// Returns how many callbacks for this process have been dropped.
     fn debug_dropped_callback_count(&self) -> usize;
 
+    /// Returns how many callbacks the queues of this process hold, of normal
+    /// and of high priority.
+    fn callback_queue_depths(&self) -> (usize, usize);
+
+    /// Returns the most callbacks that were queued for this process at once.
+    fn debug_max_queued_callbacks(&self) -> usize;
+
     /// Returns how many times this process has been restarted.
     fn debug_restart_count(&self) -> usize;
 
@@ -293,6 +323,33 @@ pub enum State {
     Unstarted,
 }
 
+/// A change in the life of a process, as a `ProcessStateObserver` sees it.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum ProcessEvent {
+    /// The kernel loaded the process.
+    Created,
+    /// The process has work to do, such as a callback, and is ready to be
+    /// scheduled.
+    Running,
+    /// The process called `yield`.
+    Yielded,
+    /// The kernel stopped the process.
+    Stopped,
+    /// The kernel resumed the process after stopping it.
+    Resumed,
+    /// The process caused a fault.
+    Faulted,
+    /// The kernel reset the process after a fault, and will start it again.
+    Restarted,
+}
+
+/// Observer of the changes in the state of processes, such as a debug capsule
+/// that monitors the system.
+pub trait ProcessStateObserver {
+    /// The process `appid`, named `name`, went through `event`.
+    fn process_event(&self, appid: AppId, name: &'static str, event: ProcessEvent);
+}
+
 /// The reaction the kernel should take when an app encounters a fault.
 ///
 /// When an exception occurs during an app's execution (a common example is an
@@ -342,6 +399,30 @@ pub struct FunctionCall {
     pub pc: usize,
 }
 
+/// The number of callbacks the queue of a process holds, if its TBF header
+/// does not say.
+const DEFAULT_CALLBACK_QUEUE_DEPTH: usize = 9;
+
+/// Merge `call` into the first call of the same callback in `tasks`, if
+/// there is one and `coalesce` allows.
+fn merge_queued_call(
+    tasks: &mut RingBuffer<Task>,
+    call: &FunctionCall,
+    coalesce: Coalesce,
+) -> bool {
+    tasks
+        .find_mut(|task| match task {
+            Task::FunctionCall(pending) => {
+                pending.pc == call.pc && pending.argument3 == call.argument3
+            }
+            Task::IPC(_) => false,
+        })
+        .map_or(false, |task| match task {
+            Task::FunctionCall(pending) => coalesce.merge(pending, call),
+            Task::IPC(_) => false,
+        })
+}
+
 /// State for helping with debugging apps.
 ///
 /// These pointers and counters are not strictly required for kernel operation,
@@ -368,6 +449,10 @@ struct ProcessDebug {
     /// long.
     dropped_callback_count: usize,
 
+    /// The most callbacks that were queued at once, to tell how close the
+    /// process came to dropping callbacks.
+    max_queued_callbacks: usize,
+
     /// How many times this process has entered into a fault condition and the
     /// kernel has restarted it.
     restart_count: usize,
@@ -466,6 +551,10 @@ pub struct Process<'a, C: 'static + Chip> {
     /// process.
     tasks: MapCell<RingBuffer<'a, Task>>,
 
+    /// The callbacks of high priority, which run before those in `tasks`.
+    /// Empty if the process did not ask for room for them.
+    priority_tasks: MapCell<RingBuffer<'a, Task>>,
+
     /// Name of the app.
     process_name: &'static str,
 
@@ -479,19 +568,33 @@ impl<C: Chip> ProcessType for Process<'a, C> {
     }
 
     fn enqueue_task(&self, task: Task) -> bool {
+        self.enqueue_task_with_priority(task, Priority::Normal)
+    }
+
+    fn enqueue_task_with_priority(&self, task: Task, priority: Priority) -> bool {
         // If this app is in the `Fault` state then we shouldn't schedule
         // any work for it.
         if self.state.get() == State::Fault {
             return false;
         }
 
-        self.kernel.increment_work();
-
-        let ret = self.tasks.map_or(false, |tasks| tasks.enqueue(task));
-
-        // Make a note that we lost this callback if the enqueue function
-        // fails.
-        if ret == false {
+        // High priority tasks that do not fit in their own queue wait with
+        // the others.
+        let ret = (priority == Priority::High
+            && self
+                .priority_tasks
+                .map_or(false, |tasks| tasks.enqueue(task)))
+            || self.tasks.map_or(false, |tasks| tasks.enqueue(task));
+
+        if ret {
+            self.kernel.increment_work();
+            let queued = self.queued_task_count();
+            self.debug.map(|debug| {
+                debug.max_queued_callbacks = max(debug.max_queued_callbacks, queued);
+            });
+        } else {
+            // Make a note that we lost this callback if the enqueue function
+            // fails.
             self.debug.map(|debug| {
                 debug.dropped_callback_count += 1;
             });
@@ -500,35 +603,54 @@ impl<C: Chip> ProcessType for Process<'a, C> {
         ret
     }
 
+    fn coalesce_task(&self, call: FunctionCall, coalesce: Coalesce, priority: Priority) -> bool {
+        if self.state.get() == State::Fault {
+            return false;
+        }
+
+        let merged = self
+            .priority_tasks
+            .map_or(false, |tasks| merge_queued_call(tasks, &call, coalesce))
+            || self
+                .tasks
+                .map_or(false, |tasks| merge_queued_call(tasks, &call, coalesce));
+
+        merged || self.enqueue_task_with_priority(Task::FunctionCall(call), priority)
+    }
+
     fn get_state(&self) -> State {
         self.state.get()
     }
 
     fn set_yielded_state(&self) {
         if self.state.get() == State::Running {
-            self.state.set(State::Yielded);
+            self.set_state(State::Yielded);
             self.kernel.decrement_work();
+            self.notify(ProcessEvent::Yielded);
         }
     }
 
     fn stop(&self) {
         match self.state.get() {
-            State::Running => self.state.set(State::StoppedRunning),
-            State::Yielded => self.state.set(State::StoppedYielded),
-            _ => {} // Do nothing
+            State::Running => self.set_state(State::StoppedRunning),
+            State::Yielded => self.set_state(State::StoppedYielded),
+            _ => return, // Do nothing
         }
+        self.notify(ProcessEvent::Stopped);
     }
 
     fn resume(&self) {
         match self.state.get() {
-            State::StoppedRunning => self.state.set(State::Running),
-            State::StoppedYielded => self.state.set(State::Yielded),
-            _ => {} // Do nothing
+            State::StoppedRunning => self.set_state(State::Running),
+            State::StoppedYielded => self.set_state(State::Yielded),
+            _ => return, // Do nothing
         }
+        self.notify(ProcessEvent::Resumed);
     }
 
     fn set_fault_state(&self) {
-        self.state.set(State::Fault);
+        self.set_state(State::Fault);
+        self.notify(ProcessEvent::Faulted);
 
         match self.fault_response {
             FaultResponse::Panic => {
@@ -536,17 +658,9 @@ impl<C: Chip> ProcessType for Process<'a, C> {
                 panic!("Process {} had a fault", self.process_name);
             }
             FaultResponse::Restart => {
-                // Remove the tasks that were scheduled for the app from the
-                // amount of work queue.
-                let tasks_len = self.tasks.map_or(0, |tasks| tasks.len());
-                for _ in 0..tasks_len {
-                    self.kernel.decrement_work();
-                }
-
-                // And remove those tasks
-                self.tasks.map(|tasks| {
-                    tasks.empty();
-                });
+                // Remove the tasks that were scheduled for the app, and the
+                // work they account for.
+                self.remove_queued_tasks();
 
                 // Update debug information
                 self.debug.map(|debug| {
@@ -557,6 +671,7 @@ impl<C: Chip> ProcessType for Process<'a, C> {
                     debug.syscall_count = 0;
                     debug.last_syscall = None;
                     debug.dropped_callback_count = 0;
+                    debug.max_queued_callbacks = 0;
                 });
 
                 // We are going to start this process over again, so need
@@ -566,7 +681,7 @@ impl<C: Chip> ProcessType for Process<'a, C> {
                     app_flash_address.offset(self.header.get_init_function_offset() as isize)
                         as usize
                 };
-                self.state.set(State::Unstarted);
+                self.set_state(State::Unstarted);
 
                 // Need to reset the grant region.
                 unsafe {
@@ -594,6 +709,7 @@ impl<C: Chip> ProcessType for Process<'a, C> {
                 });
 
                 self.kernel.increment_work();
+                self.notify(ProcessEvent::Restarted);
             }
             FaultResponse::Stop => {
                 // This looks a lot like restart, except we just leave the app
@@ -602,17 +718,9 @@ impl<C: Chip> ProcessType for Process<'a, C> {
                 // clearing all of the grant regions will cause capsules to drop
                 // this app as well.
 
-                // Remove the tasks that were scheduled for the app from the
-                // amount of work queue.
-                let tasks_len = self.tasks.map_or(0, |tasks| tasks.len());
-                for _ in 0..tasks_len {
-                    self.kernel.decrement_work();
-                }
-
-                // And remove those tasks
-                self.tasks.map(|tasks| {
-                    tasks.empty();
-                });
+                // Remove the tasks that were scheduled for the app, and the
+                // work they account for.
+                self.remove_queued_tasks();
 
                 // Clear any grant regions this app has setup with any capsules.
                 unsafe {
@@ -620,18 +728,19 @@ impl<C: Chip> ProcessType for Process<'a, C> {
                 }
 
                 // Mark the app as stopped so the scheduler won't try to run it.
-                self.state.set(State::StoppedFaulted);
+                self.set_state(State::StoppedFaulted);
             }
         }
     }
 
     fn dequeue_task(&self) -> Option<Task> {
-        self.tasks.map_or(None, |tasks| {
-            tasks.dequeue().map(|cb| {
+        self.priority_tasks
+            .map_or(None, |tasks| tasks.dequeue())
+            .or_else(|| self.tasks.map_or(None, |tasks| tasks.dequeue()))
+            .map(|cb| {
                 self.kernel.decrement_work();
                 cb
             })
-        })
     }
 
     fn mem_start(&self) -> *const u8 {
@@ -654,6 +763,10 @@ impl<C: Chip> ProcessType for Process<'a, C> {
         unsafe { self.flash.as_ptr().add(self.flash.len()) }
     }
 
+    fn flash_non_protected(&self) -> &'static [u8] {
+        &self.flash[self.header.get_protected_size() as usize..]
+    }
+
     fn kernel_memory_break(&self) -> *const u8 {
         self.kernel_memory_break.get()
     }
@@ -843,7 +956,8 @@ impl<C: Chip> ProcessType for Process<'a, C> {
 
                 // Move this process to the "running" state so the scheduler
                 // will schedule it.
-                self.state.set(State::Running);
+                self.set_state(State::Running);
+                self.notify(ProcessEvent::Running);
 
                 // Update helpful debugging metadata.
                 self.current_stack_pointer.set(stack_bottom as *mut u8);
@@ -905,6 +1019,17 @@ impl<C: Chip> ProcessType for Process<'a, C> {
         self.debug.map_or(0, |debug| debug.dropped_callback_count)
     }
 
+    fn callback_queue_depths(&self) -> (usize, usize) {
+        (
+            self.tasks.map_or(0, |tasks| tasks.capacity()),
+            self.priority_tasks.map_or(0, |tasks| tasks.capacity()),
+        )
+    }
+
+    fn debug_max_queued_callbacks(&self) -> usize {
+        self.debug.map_or(0, |debug| debug.max_queued_callbacks)
+    }
+
     fn debug_restart_count(&self) -> usize {
         self.debug.map_or(0, |debug| debug.restart_count)
     }
@@ -972,7 +1097,9 @@ impl<C: Chip> ProcessType for Process<'a, C> {
         }
 
         // application statistics
-        let events_queued = self.tasks.map_or(0, |tasks| tasks.len());
+        let events_queued = self.queued_task_count();
+        let (queue_depth, priority_queue_depth) = self.callback_queue_depths();
+        let max_events_queued = self.debug.map_or(0, |debug| debug.max_queued_callbacks);
         let syscall_count = self.debug.map_or(0, |debug| debug.syscall_count);
         let last_syscall = self.debug.map(|debug| debug.last_syscall);
         let dropped_callback_count = self.debug.map_or(0, |debug| debug.dropped_callback_count);
@@ -982,12 +1109,16 @@ impl<C: Chip> ProcessType for Process<'a, C> {
             "\
              App: {}   -   [{:?}]\
              \r\n Events Queued: {}   Syscall Count: {}   Dropped Callback Count: {}\
+             \n Queue Depth: {} + {} high priority   Max Events Queued: {}\
              \n Restart Count: {}\n",
             self.process_name,
             self.state.get(),
             events_queued,
             syscall_count,
             dropped_callback_count,
+            queue_depth,
+            priority_queue_depth,
+            max_events_queued,
             restart_count,
         ));
 
@@ -1062,6 +1193,22 @@ impl<C: Chip> ProcessType for Process<'a, C> {
 }
 
 impl<C: 'static + Chip> Process<'a, C> {
+    /// The number of tasks queued for the process, of either priority.
+    fn queued_task_count(&self) -> usize {
+        self.tasks.map_or(0, |tasks| tasks.len())
+            + self.priority_tasks.map_or(0, |tasks| tasks.len())
+    }
+
+    /// Remove all the tasks queued for the process, and the work they
+    /// account for.
+    fn remove_queued_tasks(&self) {
+        for _ in 0..self.queued_task_count() {
+            self.kernel.decrement_work();
+        }
+        self.tasks.map(|tasks| tasks.empty());
+        self.priority_tasks.map(|tasks| tasks.empty());
+    }
+
     crate unsafe fn create(
         kernel: &'static Kernel,
         chip: &'static C,
@@ -1109,10 +1256,20 @@ impl<C: 'static + Chip> Process<'a, C> {
             let grant_ptrs_num = kernel.get_grant_count_and_finalize();
             let grant_ptrs_offset = grant_ptrs_num * grant_ptr_size;
 
-            // Allocate memory for callback ring buffer.
+            // Allocate memory for the callback ring buffers, as deep as the
+            // app asks for. A ring buffer holds one task less than its
+            // length, and the normal one must at least hold the init task.
+            let (queue_depth, priority_queue_depth) = tbf_header
+                .get_callback_queue_depths()
+                .unwrap_or((DEFAULT_CALLBACK_QUEUE_DEPTH, 0));
             let callback_size = mem::size_of::<Task>();
-            let callback_len = 10;
-            let callbacks_offset = callback_len * callback_size;
+            let callback_len = max(queue_depth, 1) + 1;
+            let priority_callback_len = if priority_queue_depth > 0 {
+                priority_queue_depth + 1
+            } else {
+                0
+            };
+            let callbacks_offset = (callback_len + priority_callback_len) * callback_size;
 
             // Make room to store this process's metadata.
             let process_struct_offset = mem::size_of::<Process<C>>();
@@ -1175,10 +1332,19 @@ impl<C: 'static + Chip> Process<'a, C> {
             // for the callbacks.
             kernel_memory_break = kernel_memory_break.offset(-(callbacks_offset as isize));
 
-            // Set up ring buffer.
+            // Set up ring buffers.
             let callback_buf =
                 slice::from_raw_parts_mut(kernel_memory_break as *mut Task, callback_len);
             let tasks = RingBuffer::new(callback_buf);
+            let priority_tasks = if priority_callback_len > 0 {
+                let priority_callback_buf = slice::from_raw_parts_mut(
+                    (kernel_memory_break as *mut Task).add(callback_len),
+                    priority_callback_len,
+                );
+                MapCell::new(RingBuffer::new(priority_callback_buf))
+            } else {
+                MapCell::empty()
+            };
 
             // Last thing is the process struct.
             kernel_memory_break = kernel_memory_break.offset(-(process_struct_offset as isize));
@@ -1223,6 +1389,7 @@ impl<C: 'static + Chip> Process<'a, C> {
                 Cell::new(None),
             ];
             process.tasks = MapCell::new(tasks);
+            process.priority_tasks = priority_tasks;
             process.process_name = process_name;
 
             process.debug = MapCell::new(ProcessDebug {
@@ -1232,6 +1399,7 @@ impl<C: 'static + Chip> Process<'a, C> {
                 syscall_count: 0,
                 last_syscall: None,
                 dropped_callback_count: 0,
+                max_queued_callbacks: 0,
                 restart_count: 0,
                 timeslice_expiration_count: 0,
             });
@@ -1284,6 +1452,19 @@ impl<C: 'static + Chip> Process<'a, C> {
         self.current_stack_pointer.get() as *const usize
     }
 
+    /// Move the process to `state`, and record it in the process info table
+    /// for debuggers.
+    fn set_state(&self, state: State) {
+        self.state.set(state);
+        process_info::process_state_changed(self.app_idx, state);
+    }
+
+    /// Tell the process state observer, if there is one, about `event`.
+    fn notify(&self, event: ProcessEvent) {
+        self.kernel
+            .process_event(self.appid(), self.process_name, event);
+    }
+
     /// Checks if the buffer represented by the passed in base pointer and size
     /// are within the memory bounds currently exposed to the processes (i.e.
     /// ending at `app_break`. If this method returns true, the buffer
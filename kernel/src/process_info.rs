@@ -0,0 +1,231 @@
+//! Table of the loaded processes in RAM, for debuggers.
+//!
+//! Debuggers attached over SWD or JTAG see the memory of the chip, but not
+//! which processes the kernel loaded, or where. The kernel keeps a table that
+//! describes each process, at the well known symbol `TOCK_PROCESS_INFO`, so
+//! that debugger scripts can list the processes and load the symbols of an
+//! application at the address it runs from. `tools/gdb/tock.py` does this for
+//! GDB.
+//!
+//! The table is optional, as it costs RAM. A board enables it by giving the
+//! kernel an entry for each of its process slots, before it loads processes:
+//!
+//! ```ignore
+//! static mut PROCESS_INFO: [ProcessInfoEntry; NUM_PROCS] = [ProcessInfoEntry::EMPTY; NUM_PROCS];
+//!
+//! kernel::process_info::set_entries(&mut PROCESS_INFO, &process_mgmt_cap);
+//! ```
+//!
+//! The kernel then fills in the entry of each process as it loads it, and
+//! updates its state whenever it changes.
+//!
+//! Load map
+//! --------
+//!
+//! Without a debugger, a board can print the same addresses on its console
+//! once processes are loaded, one line per process:
+//!
+//! ```text
+//! tock-process index=0 name=blink flash=0x00030000-0x00031000 text=0x00030048 ram=0x20004000-0x20006000
+//! ```
+//!
+//! `tools/process_symbols.py` reads these lines from a console log and prints
+//! the GDB commands that load the symbols of each application.
+//!
+//! Consistency
+//! -----------
+//!
+//! The debugger may halt the chip while the kernel is updating the table. So
+//! that it can tell, the kernel increments `generation` before and after each
+//! update: the table is only consistent if `generation` is even, and did not
+//! change while the debugger read the entries.
+
+use core::ptr;
+use core::sync::atomic::{compiler_fence, Ordering};
+
+use crate::capabilities::ProcessManagementCapability;
+use crate::common::cells::VolatileCell;
+use crate::process::{ProcessType, State};
+
+/// Identifies the table in memory, "TOCK" in ASCII.
+pub const MAGIC: u32 = 0x544f_434b;
+
+/// Version of the layout of the table, incremented on any change to it.
+pub const VERSION: u32 = 1;
+
+/// The header of the table.
+#[repr(C)]
+pub struct ProcessInfoBlock {
+    magic: u32,
+    version: u32,
+    /// Odd while the kernel updates the table.
+    generation: VolatileCell<u32>,
+    /// Size of an entry, in bytes.
+    entry_size: u32,
+    /// Number of entries, one per process slot of the board.
+    entry_count: u32,
+    entries: *mut ProcessInfoEntry,
+}
+
+/// The description of a process. The fields are the addresses and the state
+/// of the process, and are all the size of a pointer.
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct ProcessInfoEntry {
+    /// Process name, which is not null terminated.
+    name: *const u8,
+    name_len: usize,
+    /// State of the process, `STATE_EMPTY` if the slot holds no process.
+    state: usize,
+    /// The whole process in flash, including its TBF header.
+    flash_start: usize,
+    flash_end: usize,
+    /// Start of the application code, after the TBF header and the protected
+    /// region. The text of the application's ELF is loaded here.
+    text_start: usize,
+    /// Process memory, including the grant region.
+    ram_start: usize,
+    ram_end: usize,
+}
+
+/// State of an entry whose slot holds no process.
+pub const STATE_EMPTY: usize = 0;
+
+impl ProcessInfoEntry {
+    pub const EMPTY: ProcessInfoEntry = ProcessInfoEntry {
+        name: ptr::null(),
+        name_len: 0,
+        state: STATE_EMPTY,
+        flash_start: 0,
+        flash_end: 0,
+        text_start: 0,
+        ram_start: 0,
+        ram_end: 0,
+    };
+}
+
+/// The table that debuggers look for.
+#[no_mangle]
+pub static mut TOCK_PROCESS_INFO: ProcessInfoBlock = ProcessInfoBlock {
+    magic: MAGIC,
+    version: VERSION,
+    generation: VolatileCell::new(0),
+    entry_size: 0,
+    entry_count: 0,
+    entries: ptr::null_mut(),
+};
+
+/// The value of the `state` field of an entry for each process state. These
+/// are part of the layout of the table, so they must not change.
+fn state_code(state: State) -> usize {
+    match state {
+        State::Running => 1,
+        State::Yielded => 2,
+        State::StoppedRunning => 3,
+        State::StoppedYielded => 4,
+        State::StoppedFaulted => 5,
+        State::Fault => 6,
+        State::Unstarted => 7,
+    }
+}
+
+/// Give the kernel the entries of the table, one per process slot. This must
+/// be called before processes are loaded, so that they all get an entry.
+pub fn set_entries(
+    entries: &'static mut [ProcessInfoEntry],
+    _capability: &ProcessManagementCapability,
+) {
+    update(|block| {
+        for entry in entries.iter_mut() {
+            *entry = ProcessInfoEntry::EMPTY;
+        }
+        block.entry_size = core::mem::size_of::<ProcessInfoEntry>() as u32;
+        block.entry_count = entries.len() as u32;
+        block.entries = entries.as_mut_ptr();
+    });
+}
+
+/// Print the load map of `processes` on the debug console, one line per
+/// loaded process. The format of the lines is part of the interface with host
+/// tools, so fields may only be added to their end.
+pub fn print_load_map(
+    processes: &[Option<&'static ProcessType>],
+    _capability: &ProcessManagementCapability,
+) {
+    for (index, process) in processes.iter().enumerate() {
+        process.map(|process| {
+            debug!(
+                "tock-process index={} name={} flash={:#010x}-{:#010x} text={:#010x} ram={:#010x}-{:#010x}",
+                index,
+                process.get_process_name(),
+                process.flash_start() as usize,
+                process.flash_end() as usize,
+                process.flash_non_protected_start() as usize,
+                process.mem_start() as usize,
+                process.mem_end() as usize
+            );
+        });
+    }
+}
+
+/// Run `f` on the table, with the generation odd while it does.
+fn update<F: FnOnce(&mut ProcessInfoBlock)>(f: F) {
+    unsafe {
+        let block = &mut TOCK_PROCESS_INFO;
+        block.generation.set(block.generation.get().wrapping_add(1));
+        compiler_fence(Ordering::SeqCst);
+        f(block);
+        compiler_fence(Ordering::SeqCst);
+        block.generation.set(block.generation.get().wrapping_add(1));
+    }
+}
+
+/// Run `f` on the entry at `index`, if the board gave the kernel a table.
+fn update_entry<F: FnOnce(&mut ProcessInfoEntry)>(index: usize, f: F) {
+    unsafe {
+        if index >= TOCK_PROCESS_INFO.entry_count as usize {
+            return;
+        }
+    }
+    update(|block| unsafe {
+        let entry = block.entries.add(index);
+        let mut value = ptr::read_volatile(entry);
+        f(&mut value);
+        ptr::write_volatile(entry, value);
+    });
+}
+
+/// Describe the process that the kernel loaded into slot `index`.
+crate fn process_loaded(index: usize, process: &ProcessType) {
+    update_entry(index, |entry| {
+        let name = process.get_process_name();
+        entry.name = name.as_ptr();
+        entry.name_len = name.len();
+        entry.state = state_code(process.get_state());
+        entry.flash_start = process.flash_start() as usize;
+        entry.flash_end = process.flash_end() as usize;
+        entry.text_start = process.flash_non_protected_start() as usize;
+        entry.ram_start = process.mem_start() as usize;
+        entry.ram_end = process.mem_end() as usize;
+    });
+}
+
+/// Record that the process in slot `index` is now in `state`.
+crate fn process_state_changed(index: usize, state: State) {
+    update_entry(index, |entry| entry.state = state_code(state));
+}
+
+/// The slot of the process whose flash holds `address`. This may be called
+/// from an interrupt, so it finds none while the table is being updated.
+crate fn process_containing(address: usize) -> Option<usize> {
+    unsafe {
+        let block = &TOCK_PROCESS_INFO;
+        if block.generation.get() % 2 == 1 {
+            return None;
+        }
+        (0..block.entry_count as usize).find(|&index| {
+            let entry = ptr::read_volatile(block.entries.add(index));
+            entry.state != STATE_EMPTY && address >= entry.flash_start && address < entry.flash_end
+        })
+    }
+}
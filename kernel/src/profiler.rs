@@ -0,0 +1,252 @@
+//! Sampling profiler of the kernel and processes.
+//!
+//! A periodic timer interrupt, that a chip provides (such as
+//! `cc26x2::sampler`), passes the program counter it interrupted to
+//! `record()`. The profiler keeps the most recent samples in a trace, and
+//! counts them by region: the kernel, each process, and other code, like a
+//! process running from RAM. Samples in the kernel are also counted in
+//! buckets of its text, so that the hot spots can be found by looking up
+//! the addresses of the busiest buckets in the kernel ELF. The process
+//! console prints them with `profile`.
+//!
+//! The profiler is optional, as it costs RAM. A board enables it by giving it
+//! buffers, and the range of the kernel text:
+//!
+//! ```ignore
+//! static mut TRACE: [usize; 64] = [0; 64];
+//! static mut PROCESS_SAMPLES: [u32; NUM_PROCS] = [0; NUM_PROCS];
+//! static mut KERNEL_SAMPLES: [u32; 256] = [0; 256];
+//!
+//! kernel::profiler::enable(
+//!     &mut TRACE,
+//!     &mut PROCESS_SAMPLES,
+//!     &mut KERNEL_SAMPLES,
+//!     &_stext as *const u8 as usize,
+//!     &_etext as *const u8 as usize,
+//!     &process_mgmt_cap,
+//! );
+//! ```
+//!
+//! Samples of processes are attributed using the table of
+//! `kernel::process_info`, so the board needs to enable it as well, or they
+//! count as other code. Code that runs with interrupts disabled is never
+//! sampled.
+
+use core::ptr;
+
+use crate::capabilities::ProcessManagementCapability;
+use crate::process_info;
+
+/// Where a sample was taken.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Region {
+    Kernel,
+    /// The process in this slot
+    Process(usize),
+    /// Neither the kernel nor the flash of a process
+    Other,
+}
+
+struct Profiler {
+    /// The most recent samples, with bit 0, which is always clear in a
+    /// program counter, set for samples of processes
+    trace: *mut usize,
+    trace_len: usize,
+    /// Where the next sample goes in the trace
+    next: usize,
+    samples: u32,
+    kernel: u32,
+    other: u32,
+    processes: *mut u32,
+    processes_len: usize,
+    buckets: *mut u32,
+    buckets_len: usize,
+    bucket_size: usize,
+    text_start: usize,
+    text_end: usize,
+}
+
+static mut PROFILER: Profiler = Profiler {
+    trace: ptr::null_mut(),
+    trace_len: 0,
+    next: 0,
+    samples: 0,
+    kernel: 0,
+    other: 0,
+    processes: ptr::null_mut(),
+    processes_len: 0,
+    buckets: ptr::null_mut(),
+    buckets_len: 0,
+    bucket_size: 1,
+    text_start: 0,
+    text_end: 0,
+};
+
+/// Give the profiler its buffers: `trace` for the most recent samples,
+/// `processes` for the count of each process slot, and `kernel_buckets` for
+/// the counts of the kernel text from `text_start` to `text_end`, split into
+/// as many buckets of equal size.
+pub fn enable(
+    trace: &'static mut [usize],
+    processes: &'static mut [u32],
+    kernel_buckets: &'static mut [u32],
+    text_start: usize,
+    text_end: usize,
+    _capability: &ProcessManagementCapability,
+) {
+    unsafe {
+        let profiler = &mut PROFILER;
+        profiler.trace_len = trace.len();
+        profiler.trace = trace.as_mut_ptr();
+        profiler.processes_len = processes.len();
+        profiler.processes = processes.as_mut_ptr();
+        profiler.buckets_len = kernel_buckets.len();
+        profiler.buckets = kernel_buckets.as_mut_ptr();
+        let text_len = text_end.saturating_sub(text_start);
+        let buckets = kernel_buckets.len().max(1);
+        // Keep buckets aligned to instructions
+        let bucket_size = (text_len + buckets - 1) / buckets;
+        profiler.bucket_size = ((bucket_size + 3) & !3).max(4);
+        profiler.text_start = text_start;
+        profiler.text_end = text_end;
+    }
+    reset();
+}
+
+/// Whether the board enabled the profiler.
+pub fn enabled() -> bool {
+    unsafe { PROFILER.trace_len > 0 }
+}
+
+/// Record a sample of `pc`, which a process was running if `in_process`.
+/// This is called from the interrupt of the sampling timer, with interrupts
+/// disabled.
+pub fn record(pc: usize, in_process: bool) {
+    unsafe {
+        let profiler = &mut PROFILER;
+        if profiler.trace_len == 0 {
+            return;
+        }
+        ptr::write_volatile(
+            profiler.trace.add(profiler.next),
+            (pc & !1) | in_process as usize,
+        );
+        profiler.next = (profiler.next + 1) % profiler.trace_len;
+        profiler.samples = profiler.samples.wrapping_add(1);
+
+        let region = if in_process {
+            match process_info::process_containing(pc) {
+                Some(index) if index < profiler.processes_len => Region::Process(index),
+                _ => Region::Other,
+            }
+        } else {
+            Region::Kernel
+        };
+        match region {
+            Region::Kernel => {
+                profiler.kernel = profiler.kernel.wrapping_add(1);
+                if pc >= profiler.text_start && pc < profiler.text_end {
+                    let bucket = (pc - profiler.text_start) / profiler.bucket_size;
+                    increment(profiler.buckets.add(bucket));
+                }
+            }
+            Region::Process(index) => increment(profiler.processes.add(index)),
+            Region::Other => profiler.other = profiler.other.wrapping_add(1),
+        }
+    }
+}
+
+unsafe fn increment(count: *mut u32) {
+    ptr::write_volatile(count, ptr::read_volatile(count).wrapping_add(1));
+}
+
+/// Forget all samples. A sample taken meanwhile may be counted partially.
+pub fn reset() {
+    unsafe {
+        let profiler = &mut PROFILER;
+        for i in 0..profiler.trace_len {
+            ptr::write_volatile(profiler.trace.add(i), 0);
+        }
+        for i in 0..profiler.processes_len {
+            ptr::write_volatile(profiler.processes.add(i), 0);
+        }
+        for i in 0..profiler.buckets_len {
+            ptr::write_volatile(profiler.buckets.add(i), 0);
+        }
+        profiler.next = 0;
+        profiler.samples = 0;
+        profiler.kernel = 0;
+        profiler.other = 0;
+    }
+}
+
+/// The number of samples since the profiler was reset.
+pub fn samples() -> u32 {
+    unsafe { ptr::read_volatile(&PROFILER.samples) }
+}
+
+/// Call `f` with the number of samples of the kernel, of other code, and of
+/// each process slot that has samples.
+pub fn regions(f: &mut FnMut(Region, u32)) {
+    unsafe {
+        let profiler = &PROFILER;
+        f(Region::Kernel, ptr::read_volatile(&profiler.kernel));
+        for i in 0..profiler.processes_len {
+            let count = ptr::read_volatile(profiler.processes.add(i));
+            if count > 0 {
+                f(Region::Process(i), count);
+            }
+        }
+        f(Region::Other, ptr::read_volatile(&profiler.other));
+    }
+}
+
+/// Call `f` with the start and end address and the number of samples of the
+/// `n` busiest buckets of the kernel text, busiest first.
+pub fn hot_spots(n: usize, f: &mut FnMut(usize, usize, u32)) {
+    unsafe {
+        let profiler = &PROFILER;
+        let count = |i: usize| ptr::read_volatile(profiler.buckets.add(i));
+        // The previous bucket printed, which the next one must rank after
+        let mut previous: Option<(u32, usize)> = None;
+        for _ in 0..n {
+            let mut best: Option<(u32, usize)> = None;
+            for i in 0..profiler.buckets_len {
+                let candidate = (count(i), i);
+                // Rank by count, then by address
+                let after_previous = previous.map_or(true, |(c, j)| {
+                    candidate.0 < c || (candidate.0 == c && i > j)
+                });
+                let better = best.map_or(true, |(c, j)| {
+                    candidate.0 > c || (candidate.0 == c && i < j)
+                });
+                if candidate.0 > 0 && after_previous && better {
+                    best = Some(candidate);
+                }
+            }
+            match best {
+                Some((samples, i)) => {
+                    let start = profiler.text_start + i * profiler.bucket_size;
+                    f(start, start + profiler.bucket_size, samples);
+                    previous = best;
+                }
+                None => break,
+            }
+        }
+    }
+}
+
+/// Call `f` with the program counter of each of the most recent samples,
+/// and whether a process was running, oldest first.
+pub fn recent(f: &mut FnMut(usize, bool)) {
+    unsafe {
+        let profiler = &PROFILER;
+        for i in 0..profiler.trace_len {
+            let sample =
+                ptr::read_volatile(profiler.trace.add((profiler.next + i) % profiler.trace_len));
+            if sample != 0 {
+                f(sample & !1, sample & 1 != 0);
+            }
+        }
+    }
+}
@@ -0,0 +1,341 @@
+//! Record of interrupts and system calls, for replaying them.
+//!
+//! Concurrency bugs in virtualizers often depend on the order in which
+//! interrupts arrive and processes make system calls, which makes them hard
+//! to reproduce. In record mode, the kernel logs that order, with the inputs
+//! of each system call, into a buffer. A replay harness can then feed the
+//! same sequence to the code under test, as many times as needed.
+//!
+//! Recording is optional. A board enables it by giving the kernel a
+//! `Recorder` with a buffer of entries:
+//!
+//! ```ignore
+//! static mut RECORD: [RecordEntry; 256] = [RecordEntry::EMPTY; 256];
+//!
+//! let recorder = static_init!(Recorder, Recorder::new(&mut RECORD));
+//! kernel::record::set_recorder(recorder, &syscall_tracing_cap);
+//! ```
+//!
+//! The kernel then records every system call, and chips record each
+//! interrupt they service with `record::interrupt`. Once the buffer is full,
+//! the newest entries replace the oldest ones. Each entry has a sequence
+//! number, so that the order survives the buffer wrapping around.
+//!
+//! The entries have a fixed layout, so the buffer can be read out of RAM by a
+//! debugger, or copied to flash by the board. `Replay` reads the events back
+//! in order from the entries, and `replay` drives a `ReplayTarget` with them.
+
+use core::cell::Cell;
+
+use crate::capabilities::SyscallTracingCapability;
+use crate::common::cells::TakeCell;
+use crate::syscall::Syscall;
+
+/// `kind` of an entry that holds no event.
+const KIND_EMPTY: usize = 0;
+/// `kind` of an interrupt entry.
+const KIND_INTERRUPT: usize = 1;
+/// `kind` of a system call entry is this plus the number of the system call.
+const KIND_SYSCALL: usize = 0x10;
+
+/// An entry of the record.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct RecordEntry {
+    /// What the entry holds
+    kind: usize,
+    /// Position of the event in the record
+    sequence: usize,
+    /// The interrupt number, or the index of the process
+    source: usize,
+    /// The arguments of the system call
+    args: [usize; 4],
+    /// What the system call returned to the process
+    result: isize,
+    /// Whether the system call returned
+    has_result: bool,
+}
+
+impl RecordEntry {
+    pub const EMPTY: RecordEntry = RecordEntry {
+        kind: KIND_EMPTY,
+        sequence: 0,
+        source: 0,
+        args: [0; 4],
+        result: 0,
+        has_result: false,
+    };
+
+    fn new(kind: usize, source: usize, args: [usize; 4]) -> RecordEntry {
+        RecordEntry {
+            kind: kind,
+            source: source,
+            args: args,
+            ..RecordEntry::EMPTY
+        }
+    }
+
+    fn from_syscall(process: usize, syscall: Syscall, result: Option<isize>) -> RecordEntry {
+        let mut entry = match syscall {
+            Syscall::YIELD => RecordEntry::new(KIND_SYSCALL, process, [0; 4]),
+            Syscall::SUBSCRIBE {
+                driver_number,
+                subdriver_number,
+                callback_ptr,
+                appdata,
+            } => RecordEntry::new(
+                KIND_SYSCALL + 1,
+                process,
+                [
+                    driver_number,
+                    subdriver_number,
+                    callback_ptr as usize,
+                    appdata,
+                ],
+            ),
+            Syscall::COMMAND {
+                driver_number,
+                subdriver_number,
+                arg0,
+                arg1,
+            } => RecordEntry::new(
+                KIND_SYSCALL + 2,
+                process,
+                [driver_number, subdriver_number, arg0, arg1],
+            ),
+            Syscall::ALLOW {
+                driver_number,
+                subdriver_number,
+                allow_address,
+                allow_size,
+            } => RecordEntry::new(
+                KIND_SYSCALL + 3,
+                process,
+                [
+                    driver_number,
+                    subdriver_number,
+                    allow_address as usize,
+                    allow_size,
+                ],
+            ),
+            Syscall::MEMOP { operand, arg0 } => {
+                RecordEntry::new(KIND_SYSCALL + 4, process, [operand, arg0, 0, 0])
+            }
+            Syscall::BATCH { commands, count } => {
+                RecordEntry::new(KIND_SYSCALL + 5, process, [commands as usize, count, 0, 0])
+            }
+        };
+        entry.has_result = result.is_some();
+        entry.result = result.unwrap_or(0);
+        entry
+    }
+
+    /// The event that the entry holds, if any.
+    pub fn event(&self) -> Option<Event> {
+        let args = self.args;
+        let syscall = match self.kind {
+            KIND_INTERRUPT => {
+                return Some(Event::Interrupt {
+                    interrupt: self.source as u32,
+                })
+            }
+            k if k == KIND_SYSCALL => Syscall::YIELD,
+            k if k == KIND_SYSCALL + 1 => Syscall::SUBSCRIBE {
+                driver_number: args[0],
+                subdriver_number: args[1],
+                callback_ptr: args[2] as *mut (),
+                appdata: args[3],
+            },
+            k if k == KIND_SYSCALL + 2 => Syscall::COMMAND {
+                driver_number: args[0],
+                subdriver_number: args[1],
+                arg0: args[2],
+                arg1: args[3],
+            },
+            k if k == KIND_SYSCALL + 3 => Syscall::ALLOW {
+                driver_number: args[0],
+                subdriver_number: args[1],
+                allow_address: args[2] as *mut u8,
+                allow_size: args[3],
+            },
+            k if k == KIND_SYSCALL + 4 => Syscall::MEMOP {
+                operand: args[0],
+                arg0: args[1],
+            },
+            k if k == KIND_SYSCALL + 5 => Syscall::BATCH {
+                commands: args[0] as *mut u8,
+                count: args[1],
+            },
+            _ => return None,
+        };
+        Some(Event::Syscall {
+            process: self.source,
+            syscall: syscall,
+            result: if self.has_result {
+                Some(self.result)
+            } else {
+                None
+            },
+        })
+    }
+}
+
+/// A recorded event.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Event {
+    /// The chip serviced `interrupt`.
+    Interrupt { interrupt: u32 },
+    /// The process in slot `process` made `syscall`, which returned `result`
+    /// (`None` for a yield).
+    Syscall {
+        process: usize,
+        syscall: Syscall,
+        result: Option<isize>,
+    },
+}
+
+/// Keeps the record in a buffer of entries.
+pub struct Recorder {
+    entries: TakeCell<'static, [RecordEntry]>,
+    /// Entry that the next event goes into
+    next: Cell<usize>,
+    sequence: Cell<usize>,
+}
+
+impl Recorder {
+    pub fn new(entries: &'static mut [RecordEntry]) -> Recorder {
+        for entry in entries.iter_mut() {
+            *entry = RecordEntry::EMPTY;
+        }
+        Recorder {
+            entries: TakeCell::new(entries),
+            next: Cell::new(0),
+            sequence: Cell::new(0),
+        }
+    }
+
+    fn push(&self, mut entry: RecordEntry) {
+        self.entries.map(|entries| {
+            if entries.is_empty() {
+                return;
+            }
+            entry.sequence = self.sequence.get();
+            self.sequence.set(self.sequence.get().wrapping_add(1));
+            entries[self.next.get()] = entry;
+            self.next.set((self.next.get() + 1) % entries.len());
+        });
+    }
+
+    /// Run `f` on the entries, for instance to copy them to flash.
+    pub fn map_entries<F: FnOnce(&[RecordEntry])>(&self, f: F) {
+        self.entries.map(|entries| f(entries));
+    }
+
+    /// Forget the recorded events.
+    pub fn clear(&self) {
+        self.entries.map(|entries| {
+            for entry in entries.iter_mut() {
+                *entry = RecordEntry::EMPTY;
+            }
+        });
+        self.next.set(0);
+    }
+}
+
+static mut RECORDER: Option<&'static Recorder> = None;
+
+/// Start recording into `recorder`. Recording system calls observes every
+/// process, so this needs the same capability as tracing them.
+pub fn set_recorder(recorder: &'static Recorder, _capability: &SyscallTracingCapability) {
+    unsafe {
+        RECORDER = Some(recorder);
+    }
+}
+
+fn with_recorder<F: FnOnce(&Recorder)>(f: F) {
+    unsafe {
+        RECORDER.map(f);
+    }
+}
+
+/// Record that the chip is servicing `interrupt`. Chips call this before
+/// they dispatch each interrupt, and it does nothing unless recording.
+pub fn interrupt(interrupt: u32) {
+    with_recorder(|recorder| {
+        recorder.push(RecordEntry::new(KIND_INTERRUPT, interrupt as usize, [0; 4]))
+    });
+}
+
+/// Record a system call that the kernel handled.
+crate fn syscall(process: usize, syscall: Syscall, result: Option<isize>) {
+    with_recorder(|recorder| recorder.push(RecordEntry::from_syscall(process, syscall, result)));
+}
+
+/// Reads the events of a record back, oldest first.
+pub struct Replay<'a> {
+    entries: &'a [RecordEntry],
+    /// Sequence number of the next event
+    sequence: usize,
+    remaining: usize,
+}
+
+impl Replay<'a> {
+    pub fn new(entries: &'a [RecordEntry]) -> Replay<'a> {
+        let recorded = entries.iter().filter(|entry| entry.kind != KIND_EMPTY);
+        let first = recorded.clone().map(|entry| entry.sequence).min();
+        Replay {
+            entries: entries,
+            sequence: first.unwrap_or(0),
+            remaining: recorded.count(),
+        }
+    }
+}
+
+impl Iterator for Replay<'a> {
+    type Item = Event;
+
+    fn next(&mut self) -> Option<Event> {
+        while self.remaining > 0 {
+            self.remaining -= 1;
+            let sequence = self.sequence;
+            self.sequence = self.sequence.wrapping_add(1);
+            if let Some(entry) = self
+                .entries
+                .iter()
+                .find(|entry| entry.kind != KIND_EMPTY && entry.sequence == sequence)
+            {
+                return entry.event();
+            }
+        }
+        None
+    }
+}
+
+/// The code that a record is replayed against, such as a virtualizer with
+/// emulated hardware and processes below and above it.
+pub trait ReplayTarget {
+    /// Deliver `interrupt`, as the chip would service it.
+    fn interrupt(&self, interrupt: u32);
+
+    /// Make `syscall` on behalf of the process in slot `process`. `result` is
+    /// what it returned when it was recorded, to check the replay against.
+    fn syscall(&self, process: usize, syscall: Syscall, result: Option<isize>);
+}
+
+/// Replay the events of `entries` against `target`, in the order they were
+/// recorded. Returns the number of events replayed.
+pub fn replay(entries: &[RecordEntry], target: &ReplayTarget) -> usize {
+    let mut count = 0;
+    for event in Replay::new(entries) {
+        match event {
+            Event::Interrupt { interrupt } => target.interrupt(interrupt),
+            Event::Syscall {
+                process,
+                syscall,
+                result,
+            } => target.syscall(process, syscall, result),
+        }
+        count += 1;
+    }
+    count
+}
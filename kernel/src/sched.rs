@@ -3,19 +3,23 @@
 use core::cell::Cell;
 use core::ptr::NonNull;
 
-use crate::callback::Callback;
+use crate::batch;
+use crate::callback::{AppId, Callback};
 use crate::capabilities;
-use crate::common::cells::NumericCellExt;
+use crate::common::cells::{NumericCellExt, OptionalCell};
 use crate::common::dynamic_deferred_call::DynamicDeferredCall;
 use crate::grant::Grant;
+use crate::hil::watchdog::Watchdog;
 use crate::ipc;
 use crate::memop;
+use crate::metrics::{self, Metric};
 use crate::platform::mpu::MPU;
 use crate::platform::systick::SysTick;
 use crate::platform::{Chip, Platform};
 use crate::process::{self, Task};
+use crate::record;
 use crate::returncode::ReturnCode;
-use crate::syscall::{ContextSwitchReason, Syscall};
+use crate::syscall::{ContextSwitchReason, Syscall, SyscallTracer};
 
 /// The time a process is permitted to run before being pre-empted
 const KERNEL_TICK_DURATION_US: u32 = 10000;
@@ -38,6 +42,18 @@ pub struct Kernel {
     /// created and the data structures for grants have already been
     /// established.
     grants_finalized: Cell<bool>,
+    /// Observer of the system calls that processes make, if any.
+    syscall_tracer: OptionalCell<&'static SyscallTracer>,
+    /// Observer of the changes in the state of processes, if any.
+    process_observer: OptionalCell<&'static process::ProcessStateObserver>,
+    /// Watchdog that the main loop services, if any.
+    watchdog: OptionalCell<&'static Watchdog>,
+    /// Period of the watchdog, in milliseconds.
+    watchdog_period: Cell<usize>,
+    /// System calls that processes made.
+    syscalls: Metric,
+    /// Times that processes faulted.
+    process_faults: Metric,
 }
 
 impl Kernel {
@@ -47,9 +63,60 @@ impl Kernel {
             processes: processes,
             grant_counter: Cell::new(0),
             grants_finalized: Cell::new(false),
+            syscall_tracer: OptionalCell::empty(),
+            process_observer: OptionalCell::empty(),
+            watchdog: OptionalCell::empty(),
+            watchdog_period: Cell::new(0),
+            syscalls: Metric::counter("kernel.syscalls"),
+            process_faults: Metric::counter("kernel.process_faults"),
         }
     }
 
+    /// Export the metrics that the kernel keeps. See `kernel::metrics`.
+    pub fn register_metrics(&'static self) {
+        metrics::register(&self.syscalls);
+        metrics::register(&self.process_faults);
+    }
+
+    /// Have `tracer` observe every system call that processes make, once the
+    /// kernel has handled it.
+    pub fn set_syscall_tracer(
+        &self,
+        tracer: &'static SyscallTracer,
+        _capability: &capabilities::SyscallTracingCapability,
+    ) {
+        self.syscall_tracer.set(tracer);
+    }
+
+    /// Have `observer` observe processes as they are loaded, run, yield,
+    /// stop, fault and restart.
+    pub fn set_process_state_observer(
+        &self,
+        observer: &'static process::ProcessStateObserver,
+        _capability: &capabilities::ProcessManagementCapability,
+    ) {
+        self.process_observer.set(observer);
+    }
+
+    /// Have the main loop start `watchdog` with a period of `period_ms`, and
+    /// service it on every pass, so that the chip resets if the kernel hangs.
+    /// The watchdog is stopped while the chip sleeps.
+    pub fn set_watchdog(
+        &self,
+        watchdog: &'static Watchdog,
+        period_ms: usize,
+        _capability: &capabilities::MainLoopCapability,
+    ) {
+        self.watchdog.set(watchdog);
+        self.watchdog_period.set(period_ms);
+    }
+
+    /// The number of times processes have faulted since boot, for capsules
+    /// that judge whether the system is healthy.
+    pub fn process_fault_count(&self) -> u32 {
+        self.process_faults.get()
+    }
+
     /// Something was scheduled for a process, so there is more work to do.
     crate fn increment_work(&self) {
         self.work.increment();
@@ -81,6 +148,22 @@ impl Kernel {
         self.processes[process_index].map_or(default, |process| closure(process))
     }
 
+    /// Tell the syscall tracer, if there is one, about a system call that the
+    /// process `appid` made, and record it if recording.
+    fn trace_syscall(&self, appid: AppId, syscall: Syscall, result: Option<ReturnCode>) {
+        self.syscalls.incr();
+        record::syscall(appid.idx(), syscall, result.map(isize::from));
+        self.syscall_tracer
+            .map(|tracer| tracer.syscall(appid, syscall, result));
+    }
+
+    /// Tell the process state observer, if there is one, that the process
+    /// `appid` went through `event`.
+    crate fn process_event(&self, appid: AppId, name: &'static str, event: process::ProcessEvent) {
+        self.process_observer
+            .map(|observer| observer.process_event(appid, name, event));
+    }
+
     /// Run a closure on every valid process. This will iterate the array of
     /// processes and call the closure on every process that exists.
     crate fn process_each<F>(&self, closure: F)
@@ -208,8 +291,11 @@ impl Kernel {
         ipc: Option<&ipc::IPC>,
         _capability: &capabilities::MainLoopCapability,
     ) {
+        self.watchdog
+            .map(|watchdog| watchdog.start(self.watchdog_period.get()));
         loop {
             unsafe {
+                self.watchdog.map(|watchdog| watchdog.tickle());
                 chip.service_pending_interrupts();
                 DynamicDeferredCall::call_global_instance_while(|| !chip.has_pending_interrupts());
 
@@ -229,7 +315,10 @@ impl Kernel {
                         && !DynamicDeferredCall::global_instance_calls_pending().unwrap_or(false)
                         && self.processes_blocked()
                     {
+                        self.watchdog.map(|watchdog| watchdog.stop());
                         chip.sleep();
+                        self.watchdog
+                            .map(|watchdog| watchdog.start(self.watchdog_period.get()));
                     }
                 });
             };
@@ -276,6 +365,7 @@ impl Kernel {
                     match context_switch_reason {
                         Some(ContextSwitchReason::Fault) => {
                             // Let process deal with it as appropriate.
+                            self.process_faults.incr();
                             process.set_fault_state();
                         }
                         Some(ContextSwitchReason::SyscallFired { syscall }) => {
@@ -284,9 +374,43 @@ impl Kernel {
                                 Syscall::MEMOP { operand, arg0 } => {
                                     let res = memop::memop(process, operand, arg0);
                                     process.set_syscall_return_value(res.into());
+                                    self.trace_syscall(appid, syscall, Some(res));
+                                }
+                                Syscall::BATCH { commands, count } => {
+                                    // Each command is traced on its own, as
+                                    // if the process had made it
+                                    let res = batch::batch(
+                                        process,
+                                        commands,
+                                        count,
+                                        |driver_number, subdriver_number, arg0, arg1| {
+                                            let res =
+                                                platform.with_driver(driver_number, |driver| {
+                                                    match driver {
+                                                        Some(d) => d.command(
+                                                            subdriver_number,
+                                                            arg0,
+                                                            arg1,
+                                                            appid,
+                                                        ),
+                                                        None => ReturnCode::ENODEVICE,
+                                                    }
+                                                });
+                                            let command = Syscall::COMMAND {
+                                                driver_number: driver_number,
+                                                subdriver_number: subdriver_number,
+                                                arg0: arg0,
+                                                arg1: arg1,
+                                            };
+                                            self.trace_syscall(appid, command, Some(res));
+                                            res
+                                        },
+                                    );
+                                    process.set_syscall_return_value(res.into());
                                 }
                                 Syscall::YIELD => {
                                     process.set_yielded_state();
+                                    self.trace_syscall(appid, syscall, None);
 
                                     // There might be already enqueued callbacks
                                     continue;
@@ -312,6 +436,7 @@ impl Kernel {
                                             },
                                         );
                                     process.set_syscall_return_value(res.into());
+                                    self.trace_syscall(appid, syscall, Some(res));
                                 }
                                 Syscall::COMMAND {
                                     driver_number,
@@ -330,6 +455,7 @@ impl Kernel {
                                             },
                                         );
                                     process.set_syscall_return_value(res.into());
+                                    self.trace_syscall(appid, syscall, Some(res));
                                 }
                                 Syscall::ALLOW {
                                     driver_number,
@@ -351,6 +477,7 @@ impl Kernel {
                                         }
                                     });
                                     process.set_syscall_return_value(res.into());
+                                    self.trace_syscall(appid, syscall, Some(res));
                                 }
                             }
                         }
@@ -366,6 +493,7 @@ impl Kernel {
                             // Something went wrong when switching to this
                             // process. Indicate this by putting it in a fault
                             // state.
+                            self.process_faults.incr();
                             process.set_fault_state();
                         }
                     }
@@ -2,7 +2,9 @@
 
 use core::fmt::Write;
 
+use crate::callback::AppId;
 use crate::process;
+use crate::returncode::ReturnCode;
 
 /// The syscall number assignments.
 #[derive(Copy, Clone, Debug, PartialEq)]
@@ -47,6 +49,20 @@ pub enum Syscall {
     ///
     /// SVC_NUM = 4
     MEMOP { operand: usize, arg0: usize },
+
+    /// Instruct drivers to perform a list of operations, in one call.
+    ///
+    /// SVC_NUM = 5
+    BATCH { commands: *mut u8, count: usize },
+}
+
+/// Observer of the system calls that processes make. The kernel calls it once
+/// it has handled each system call, if it was set with
+/// `Kernel::set_syscall_tracer`.
+pub trait SyscallTracer {
+    /// The process `appid` made `syscall`, which returned `result`. A `yield`
+    /// does not return a value, so its result is `None`.
+    fn syscall(&self, appid: AppId, syscall: Syscall, result: Option<ReturnCode>);
 }
 
 /// Why the process stopped executing and execution returned to the kernel.
@@ -198,6 +214,10 @@ pub fn arguments_to_syscall(
             operand: r0,
             arg0: r1,
         }),
+        5 => Some(Syscall::BATCH {
+            commands: r0 as *mut u8,
+            count: r1,
+        }),
         _ => None,
     }
 }
@@ -29,6 +29,7 @@ crate enum TbfHeaderTypes {
     TbfHeaderWriteableFlashRegions = 2,
     TbfHeaderPackageName = 3,
     Unused = 5,
+    TbfHeaderCallbackQueue = 6,
 }
 
 /// The TLV header (T and L).
@@ -62,6 +63,14 @@ crate struct TbfHeaderV2WriteableFlashRegion {
     writeable_flash_region_size: u32,
 }
 
+/// The depths of the callback queues of the process.
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+crate struct TbfHeaderV2CallbackQueue {
+    depth: u16,
+    priority_depth: u16,
+}
+
 /// Single header that can contain all parts of a v2 header.
 #[derive(Clone, Copy, Debug)]
 crate struct TbfHeaderV2 {
@@ -69,6 +78,7 @@ crate struct TbfHeaderV2 {
     main: Option<&'static TbfHeaderV2Main>,
     package_name: Option<&'static str>,
     writeable_regions: Option<&'static [TbfHeaderV2WriteableFlashRegion]>,
+    callback_queue: Option<&'static TbfHeaderV2CallbackQueue>,
 }
 
 /// Type that represents the fields of the Tock Binary Format header.
@@ -152,6 +162,17 @@ impl TbfHeader {
         }
     }
 
+    /// Get the number of callbacks the queue of the app holds, and the number
+    /// its queue of high priority callbacks holds, if it set them.
+    crate fn get_callback_queue_depths(&self) -> Option<(usize, usize)> {
+        match *self {
+            TbfHeader::TbfHeaderV2(hd) => hd
+                .callback_queue
+                .map(|q| (q.depth as usize, q.priority_depth as usize)),
+            _ => None,
+        }
+    }
+
     /// Get the number of flash regions this app has specified in its header.
     crate fn number_writeable_flash_regions(&self) -> usize {
         match *self {
@@ -244,6 +265,7 @@ crate unsafe fn parse_and_validate_tbf_header(address: *const u8) -> Option<TbfH
                 let mut main_pointer: Option<&TbfHeaderV2Main> = None;
                 let mut wfr_pointer: Option<&'static [TbfHeaderV2WriteableFlashRegion]> = None;
                 let mut app_name_str = "";
+                let mut callback_queue_pointer: Option<&TbfHeaderV2CallbackQueue> = None;
 
                 // Loop through the header looking for known options.
                 while remaining_length > mem::size_of::<TbfHeaderTlv>() {
@@ -252,9 +274,11 @@ crate unsafe fn parse_and_validate_tbf_header(address: *const u8) -> Option<TbfH
                     remaining_length -= mem::size_of::<TbfHeaderTlv>();
                     offset += mem::size_of::<TbfHeaderTlv>() as isize;
 
-                    // Only parse known TLV blocks. There is no type 0.
-                    if (tbf_tlv_header.tipe as u16) < TbfHeaderTypes::Unused as u16
-                        && (tbf_tlv_header.tipe as u16) > 0
+                    // Only parse known TLV blocks. There is no type 0, and
+                    // the callback queue is the only type after `Unused`.
+                    let tipe = tbf_tlv_header.tipe as u16;
+                    if (tipe > 0 && tipe < TbfHeaderTypes::Unused as u16)
+                        || tipe == TbfHeaderTypes::TbfHeaderCallbackQueue as u16
                     {
                         // This lets us skip unknown header types.
 
@@ -302,6 +326,18 @@ crate unsafe fn parse_and_validate_tbf_header(address: *const u8) -> Option<TbfH
                                         });
                                 }
                             }
+                            TbfHeaderTypes::TbfHeaderCallbackQueue =>
+                            /* Callback Queue */
+                            {
+                                if remaining_length >= mem::size_of::<TbfHeaderV2CallbackQueue>()
+                                    && tbf_tlv_header.length as usize
+                                        == mem::size_of::<TbfHeaderV2CallbackQueue>()
+                                {
+                                    let tbf_callback_queue = &*(address.offset(offset)
+                                        as *const TbfHeaderV2CallbackQueue);
+                                    callback_queue_pointer = Some(tbf_callback_queue);
+                                }
+                            }
                             TbfHeaderTypes::Unused => {}
                         }
                     }
@@ -317,6 +353,7 @@ crate unsafe fn parse_and_validate_tbf_header(address: *const u8) -> Option<TbfH
                     main: main_pointer,
                     package_name: Some(app_name_str),
                     writeable_regions: wfr_pointer,
+                    callback_queue: callback_queue_pointer,
                 };
 
                 Some(TbfHeader::TbfHeaderV2(tbf_header))
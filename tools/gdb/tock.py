@@ -0,0 +1,174 @@
+# GDB commands for debugging Tock processes.
+#
+# The kernel describes the processes it loaded in the table at the symbol
+# `TOCK_PROCESS_INFO`, if the board enables it (see `kernel::process_info`).
+# These commands read that table.
+#
+# usage, from GDB attached to the kernel ELF:
+#   (gdb) source tools/gdb/tock.py
+#   (gdb) tock-processes
+#   (gdb) tock-app-symbols <process name> <application ELF>
+#
+# `tock-app-symbols` loads the symbols of an application at the address it
+# runs from, so that breakpoints can be set in it by name. The ELF must be the
+# one that the TBF of the process was made from.
+
+import struct
+
+import gdb
+
+MAGIC = 0x544F434B
+VERSION = 1
+
+STATES = {
+    0: "Empty",
+    1: "Running",
+    2: "Yielded",
+    3: "StoppedRunning",
+    4: "StoppedYielded",
+    5: "StoppedFaulted",
+    6: "Fault",
+    7: "Unstarted",
+}
+
+# Fields of an entry, in order, each the size of a pointer
+ENTRY_FIELDS = [
+    "name",
+    "name_len",
+    "state",
+    "flash_start",
+    "flash_end",
+    "text_start",
+    "ram_start",
+    "ram_end",
+]
+
+
+def read_u32(inferior, address):
+    return struct.unpack("<I", inferior.read_memory(address, 4).tobytes())[0]
+
+
+def read_processes():
+    """Read the entries of the table, retrying while the kernel updates it."""
+    inferior = gdb.selected_inferior()
+    block = int(gdb.parse_and_eval("&TOCK_PROCESS_INFO"))
+    ptr_size = gdb.parse_and_eval("TOCK_PROCESS_INFO.entries").type.sizeof
+    ptr_fmt = "<I" if ptr_size == 4 else "<Q"
+
+    if read_u32(inferior, block) != MAGIC:
+        raise gdb.GdbError("TOCK_PROCESS_INFO has the wrong magic number")
+    if read_u32(inferior, block + 4) != VERSION:
+        raise gdb.GdbError("TOCK_PROCESS_INFO has an unknown version")
+
+    for _ in range(10):
+        generation = read_u32(inferior, block + 8)
+        entry_size = read_u32(inferior, block + 12)
+        entry_count = read_u32(inferior, block + 16)
+        entries = struct.unpack(
+            ptr_fmt, inferior.read_memory(block + 20 + (-20 % ptr_size), ptr_size).tobytes()
+        )[0]
+
+        processes = []
+        for index in range(entry_count):
+            raw = inferior.read_memory(entries + index * entry_size, entry_size).tobytes()
+            values = [
+                struct.unpack_from(ptr_fmt, raw, i * ptr_size)[0]
+                for i in range(len(ENTRY_FIELDS))
+            ]
+            entry = dict(zip(ENTRY_FIELDS, values))
+            entry["index"] = index
+            if entry["state"] != 0:
+                entry["name"] = (
+                    inferior.read_memory(entry["name"], entry["name_len"]).tobytes().decode()
+                )
+            processes.append(entry)
+
+        if generation % 2 == 0 and read_u32(inferior, block + 8) == generation:
+            if entry_count == 0:
+                raise gdb.GdbError("The board does not enable the process info table")
+            return processes
+
+    raise gdb.GdbError("The kernel is updating the process info table, try again")
+
+
+def elf_load_address(path):
+    """The lowest virtual address of a loadable segment of an ELF."""
+    with open(path, "rb") as elf:
+        data = elf.read()
+    if data[:4] != b"\x7fELF":
+        raise gdb.GdbError("%s is not an ELF file" % path)
+    if data[4] == 1:
+        phoff, = struct.unpack_from("<I", data, 28)
+        phentsize, phnum = struct.unpack_from("<HH", data, 42)
+        segment_fmt, vaddr_offset = "<I", 8
+    else:
+        phoff, = struct.unpack_from("<Q", data, 32)
+        phentsize, phnum = struct.unpack_from("<HH", data, 54)
+        segment_fmt, vaddr_offset = "<Q", 16
+    addresses = []
+    for i in range(phnum):
+        offset = phoff + i * phentsize
+        p_type, = struct.unpack_from("<I", data, offset)
+        if p_type == 1:  # PT_LOAD
+            addresses.append(struct.unpack_from(segment_fmt, data, offset + vaddr_offset)[0])
+    if not addresses:
+        raise gdb.GdbError("%s has no loadable segment" % path)
+    return min(addresses)
+
+
+class TockProcesses(gdb.Command):
+    """List the processes that the kernel loaded."""
+
+    def __init__(self):
+        super(TockProcesses, self).__init__("tock-processes", gdb.COMMAND_DATA)
+
+    def invoke(self, argument, from_tty):
+        print(
+            "%-3s %-20s %-15s %-23s %-10s %s"
+            % ("#", "Name", "State", "Flash", "Text", "RAM")
+        )
+        for p in read_processes():
+            if p["state"] == 0:
+                continue
+            print(
+                "%-3d %-20s %-15s %#010x-%#010x %#010x %#010x-%#010x"
+                % (
+                    p["index"],
+                    p["name"],
+                    STATES.get(p["state"], "Unknown"),
+                    p["flash_start"],
+                    p["flash_end"],
+                    p["text_start"],
+                    p["ram_start"],
+                    p["ram_end"],
+                )
+            )
+
+
+class TockAppSymbols(gdb.Command):
+    """Load the symbols of an application at the address of its process.
+
+    tock-app-symbols <process name> <application ELF>"""
+
+    def __init__(self):
+        super(TockAppSymbols, self).__init__(
+            "tock-app-symbols", gdb.COMMAND_FILES, gdb.COMPLETE_FILENAME
+        )
+
+    def invoke(self, argument, from_tty):
+        args = gdb.string_to_argv(argument)
+        if len(args) != 2:
+            raise gdb.GdbError("usage: tock-app-symbols <process name> <application ELF>")
+        name, path = args
+
+        for p in read_processes():
+            if p["state"] != 0 and p["name"] == name:
+                # The TBF holds the binary of the ELF right after its header
+                offset = p["text_start"] - elf_load_address(path)
+                gdb.execute("add-symbol-file %s -o %#x" % (path, offset), from_tty)
+                return
+        raise gdb.GdbError("No process is named %s" % name)
+
+
+TockProcesses()
+TockAppSymbols()
@@ -0,0 +1,88 @@
+#!/usr/bin/env python3
+# Print the GDB commands that load the symbols of Tock applications.
+#
+# Boards that call `kernel::process_info::print_load_map` print a line for
+# each process they load:
+#
+#   tock-process index=0 name=blink flash=0x00030000-0x00031000 text=0x00030048 ram=0x20004000-0x20006000
+#
+# This reads those lines from a console log, and for each process that one of
+# the given application ELFs is named after, prints the `add-symbol-file`
+# command that loads the symbols of the ELF at the address the process runs
+# from. The ELFs must be the ones that the TBFs of the processes were made
+# from.
+#
+# usage:
+#   tools/process_symbols.py <console log> <application ELF>... > symbols.gdb
+#   (gdb) source symbols.gdb
+#
+# An ELF is matched to the process with the name of its file, without the
+# extension, e.g. `blink.elf` or `blink` for the process `blink`.
+
+import os
+import re
+import struct
+import sys
+
+LINE = re.compile(
+    r"tock-process index=(\d+) name=(\S*) flash=0x([0-9a-f]+)-0x([0-9a-f]+)"
+    r" text=0x([0-9a-f]+) ram=0x([0-9a-f]+)-0x([0-9a-f]+)"
+)
+
+
+def elf_load_address(path):
+    """The lowest virtual address of a loadable segment of an ELF."""
+    with open(path, "rb") as elf:
+        data = elf.read()
+    if data[:4] != b"\x7fELF":
+        sys.exit("%s is not an ELF file" % path)
+    if data[4] == 1:
+        phoff, = struct.unpack_from("<I", data, 28)
+        phentsize, phnum = struct.unpack_from("<HH", data, 42)
+        segment_fmt, vaddr_offset = "<I", 8
+    else:
+        phoff, = struct.unpack_from("<Q", data, 32)
+        phentsize, phnum = struct.unpack_from("<HH", data, 54)
+        segment_fmt, vaddr_offset = "<Q", 16
+    addresses = []
+    for i in range(phnum):
+        offset = phoff + i * phentsize
+        p_type, = struct.unpack_from("<I", data, offset)
+        if p_type == 1:  # PT_LOAD
+            addresses.append(struct.unpack_from(segment_fmt, data, offset + vaddr_offset)[0])
+    if not addresses:
+        sys.exit("%s has no loadable segment" % path)
+    return min(addresses)
+
+
+def read_load_map(path):
+    """The text address of each process, by name. As a log may hold several
+    boots, the last line of a process wins."""
+    text_starts = {}
+    with open(path, errors="replace") as log:
+        for line in log:
+            match = LINE.search(line)
+            if match:
+                text_starts[match.group(2)] = int(match.group(5), 16)
+    return text_starts
+
+
+def main():
+    if len(sys.argv) < 3:
+        sys.exit("usage: %s <console log> <application ELF>..." % sys.argv[0])
+    text_starts = read_load_map(sys.argv[1])
+    if not text_starts:
+        sys.exit("%s has no tock-process lines" % sys.argv[1])
+
+    for path in sys.argv[2:]:
+        name = os.path.splitext(os.path.basename(path))[0]
+        if name not in text_starts:
+            sys.stderr.write("no process is named %s, skipping %s\n" % (name, path))
+            continue
+        # The TBF holds the binary of the ELF right after its header
+        offset = text_starts[name] - elf_load_address(path)
+        print("add-symbol-file %s -o %#x" % (os.path.abspath(path), offset))
+
+
+if __name__ == "__main__":
+    main()